@@ -0,0 +1,70 @@
+// Memory-mapped against buffered reads for the scans that take either
+//
+//     cargo bench --features mmap --bench file_access [size_mib]
+//
+// Each scan runs once to warm the page cache, then the best of five runs is reported, so the
+// numbers compare the read paths rather than the disk.
+
+use std::{
+    env,
+    fs,
+    path::Path,
+    time::{Duration, Instant}
+};
+
+use mercy::{CarveOptions, FileAccess, carve_file, mercy_entropy_profile, mercy_hash_file, set_file_access};
+
+const RUNS: usize = 5;
+
+// One scan of the file, its result discarded
+type Scan = fn(&Path);
+
+const SCANS: &[(&str, Scan)] = &[
+    ("sha2_256", |path| { mercy_hash_file(path, "sha2_256").expect("hash"); }),
+    ("xxhash64", |path| { mercy_hash_file(path, "xxhash64").expect("hash"); }),
+    ("entropy_profile", |path| { mercy_entropy_profile(path, 4096, 1024).expect("profile"); }),
+    ("carve", |path| { carve_file(path, &CarveOptions::default()).expect("carve"); })
+];
+
+fn main() {
+    // `cargo bench` passes "--bench" ahead of any arguments of ours
+    let size_mib = env::args().skip(1).find_map(|arg| arg.parse::<usize>().ok()).unwrap_or(256);
+    let path = env::temp_dir().join(format!("mercy-bench-{}.bin", std::process::id()));
+    write_fixture(&path, size_mib << 20);
+
+    println!("{} MiB fixture, best of {} runs", size_mib, RUNS);
+    println!("{:<16} {:>12} {:>12} {:>8}", "scan", "buffered", "mapped", "speedup");
+
+    for (name, scan) in SCANS {
+        let buffered = best(&path, FileAccess::Buffered, *scan);
+        let mapped = best(&path, FileAccess::Mapped, *scan);
+        let throughput = |time: Duration| format!("{:.0} MiB/s", size_mib as f64 / time.as_secs_f64());
+        println!("{:<16} {:>12} {:>12} {:>7.2}x", name, throughput(buffered), throughput(mapped), buffered.as_secs_f64() / mapped.as_secs_f64());
+    }
+
+    set_file_access(FileAccess::Buffered);
+    let _ = fs::remove_file(&path);
+}
+
+fn best(path: &Path, access: FileAccess, scan: Scan) -> Duration {
+    set_file_access(access);
+    scan(path);
+
+    (0..RUNS).map(|_| {
+        let start = Instant::now();
+        scan(path);
+        start.elapsed()
+    }).min().unwrap_or_default()
+}
+
+// Pseudo-random bytes, so entropy and carving do their full work
+fn write_fixture(path: &Path, size: usize) {
+    let mut state = 0x9e37_79b9_u32;
+    let bytes: Vec<u8> = (0..size).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    }).collect();
+    fs::write(path, bytes).expect("fixture");
+}
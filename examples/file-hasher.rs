@@ -0,0 +1,45 @@
+/*
+    Hashes files in the layout of sha256sum, in constant memory whatever their size
+
+    cargo run --example file-hasher -- [--algorithm sha2_512] <file>...
+*/
+
+use std::{env, process};
+
+use mercy::mercy_hash_file;
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut algorithm = "sha2_256".to_string();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--algorithm") {
+        if index + 1 >= args.len() {
+            usage();
+        }
+        algorithm = args.remove(index + 1);
+        args.remove(index);
+    }
+    if args.is_empty() {
+        usage();
+    }
+
+    let mut failed = false;
+    for path in &args {
+        match mercy_hash_file(path, &algorithm) {
+            Ok(digest) => println!("{}  {}", digest, path),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: file-hasher [--algorithm md5|sha1|sha2_256|sha2_512|sha3_256|sha3_512|ssdeep] <file>...");
+    process::exit(2)
+}
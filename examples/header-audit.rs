@@ -0,0 +1,41 @@
+/*
+    Audits the security headers of a page and prints the findings
+
+    cargo run --example header-audit -- https://example.com/
+*/
+
+use std::{env, process};
+
+use mercy::{audit_security_headers, Severity};
+
+#[tokio::main]
+async fn main() {
+    let Some(url) = env::args().nth(1) else {
+        eprintln!("usage: header-audit <url>");
+        process::exit(2);
+    };
+
+    let response = match reqwest::get(&url).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Unable to fetch {}: {}", url, e);
+            process::exit(1);
+        }
+    };
+
+    let final_url = response.url().to_string();
+    let headers: Vec<(String, String)> = response.headers().iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+        .collect();
+
+    let findings = audit_security_headers(&final_url, &headers);
+    println!("{} ({}): {} finding(s)", final_url, response.status(), findings.len());
+    for finding in &findings {
+        println!();
+        print!("{}", finding);
+    }
+
+    if findings.iter().any(|finding| finding.severity >= Severity::Medium) {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,41 @@
+/*
+    Hex dump of a file, or of a slice of it, in the layout of xxd
+
+    cargo run --example hexdump-viewer -- <file> [offset] [length]
+*/
+
+use std::{env, process};
+
+use mercy::{mercy_hex_range, mercy_hex_result};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |index: usize| args.get(index).map(|value| parse_number(value).unwrap_or_else(|| usage()));
+
+    let dump = match (args.first(), number(1), number(2)) {
+        (None, _, _) => usage(),
+        (Some(path), None, None) => mercy_hex_result("hex_dump", path),
+        (Some(path), offset, length) => mercy_hex_range(path, offset.unwrap_or(0), length.unwrap_or(256))
+    };
+
+    match dump {
+        Ok(dump) => print!("{}", dump),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Decimal or 0x-prefixed hexadecimal
+fn parse_number(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok()
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: hexdump-viewer <file> [offset] [length]");
+    process::exit(2)
+}
@@ -0,0 +1,42 @@
+/*
+    Triage of one indicator from the command line
+
+    cargo run --example ioc-triage -- "hxxps://evil[.]example/login" [--json] [--server 1.1.1.1] [--timeout 10]
+*/
+
+use std::{env, process, time::Duration};
+
+use mercy::{dns_server_addr, mercy_triage, TriageOptions};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut indicator = None;
+    let mut json = false;
+    let mut options = TriageOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--server" => options.server = Some(args.next().as_deref().map(dns_server_addr).unwrap_or_else(|| usage()).unwrap_or_else(|e| fail(&e))),
+            "--timeout" => options.step_timeout = Duration::from_secs(args.next().and_then(|secs| secs.parse().ok()).unwrap_or_else(|| usage())),
+            _ if indicator.is_none() => indicator = Some(arg),
+            _ => usage()
+        }
+    }
+
+    let report = mercy_triage(&indicator.unwrap_or_else(|| usage()), &options).unwrap_or_else(|e| fail(&e));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+    } else {
+        print!("{}", report);
+    }
+}
+
+fn usage() -> ! {
+    fail("usage: ioc-triage <indicator> [--json] [--server addr] [--timeout secs]")
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2)
+}
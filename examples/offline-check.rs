@@ -0,0 +1,291 @@
+/*
+    Runs the network code paths against the local mock services, no network needed
+
+    cargo run --example offline-check --features testing
+
+    Every check prints PASS or FAIL with what it saw; the exit status is 1 if any failed.
+*/
+
+use std::{
+    net::{IpAddr, UdpSocket},
+    process,
+    time::{Duration, Instant}
+};
+
+use mercy::{
+    mercy_dns_lookup, mercy_external_ip, mercy_extra, mercy_extra_with, mercy_http_headers, mercy_json, mercy_malicious, mercy_malicious_batch, mercy_malicious_with, mercy_port_check, mercy_port_scan, mercy_reverse_dns, mercy_security_headers, mercy_telnet_check, mercy_triage, mercy_whois, reverse_lookup, service_endpoints, set_service_endpoints, DnsLookup,
+    DnsLookupOptions, DnsLookupStatus, DnsQuery, DnsRdata, DnsType, HttpHeadersOptions, MercyConfig, MercyJson, MockHttp, MockRoute, MockServices, MockTcp, PortOptions, ServiceEndpoints, PortState, TriageOptions, TriageStatus, MOCK_CRTSH_DOMAIN, MOCK_EXTERNAL_IP, MOCK_INQUEST_EMPTY, MOCK_INQUEST_MALICIOUS, MOCK_MALICIOUS_DOMAIN, MOCK_MALICIOUS_HASH, MOCK_PWNED_PASSWORD
+};
+
+fn main() {
+    let mocks = MockServices::start().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let timeout = Duration::from_secs(2);
+    let mut failed = 0;
+    let mut check = |name: &str, result: Result<String, String>| match result {
+        Ok(seen) => println!("PASS {:<22} {}", name, seen),
+        Err(e) => {
+            println!("FAIL {:<22} {}", name, e);
+            failed += 1;
+        }
+    };
+
+    check("inquest/malicious", expect(mercy_malicious("status", MOCK_MALICIOUS_DOMAIN), "Malicious"));
+    check("inquest/unseen", expect(mercy_malicious("status", "clean.example"), "No classification available"));
+    let requests = mocks.http.requests();
+    check("inquest/requests", if requests.len() == 2 { Ok(format!("{} requests served", requests.len())) } else { Err(format!("requests {:?}", requests)) });
+
+    check("external_ip", mercy_external_ip(false).and_then(|address| {
+        if address.to_string() == MOCK_EXTERNAL_IP { Ok(address.to_string()) } else { Err(format!("answered {}", address)) }
+    }));
+
+    check("whois", expect(mercy_extra("whois", MOCK_MALICIOUS_DOMAIN), "Creation Date: 2024-02-28T00:00:00Z"));
+
+    // The JSON envelope reads back with serde, the record's registrar and creation date parsed out
+    check("json/whois", serde_json::from_str::<MercyJson>(&mercy_json("extra", "whois", MOCK_MALICIOUS_DOMAIN)).map_err(|e| e.to_string()).and_then(|envelope| {
+        match (envelope.output["registrar"].as_str(), envelope.output["created"].as_str(), &envelope.error) {
+            (Some(registrar), Some(created), None) => Ok(format!("{}, created {}", registrar, created)),
+            _ => Err(format!("{:?}", envelope))
+        }
+    }));
+    check("json/error", serde_json::from_str::<MercyJson>(&mercy_json("extra", "whois", " ")).map_err(|e| e.to_string()).and_then(|envelope| {
+        match (&envelope.output, &envelope.error) {
+            (serde_json::Value::Null, Some(error)) => Ok(error["message"].to_string()),
+            _ => Err(format!("{:?}", envelope))
+        }
+    }));
+
+    // A thin registry record refers to the registrar, and ARIN to the RIR managing an address
+    let endpoints = service_endpoints();
+    let registrar = MockTcp::whois("Domain Name: evil.example\r\nRegistrar: Example Registrar, Inc.\r\nRegistrant Country: XX\r\n");
+    let referred = registrar.and_then(|registrar| {
+        let registry = MockTcp::whois(&format!("Domain Name: EVIL.EXAMPLE\r\nRegistrar WHOIS Server: {}\r\n", registrar.addr()))?;
+        set_service_endpoints(Some(ServiceEndpoints { whois: Some(registry.addr().to_string()), ..endpoints.clone() }));
+        let domain = mercy_whois(MOCK_MALICIOUS_DOMAIN);
+        let rir = MockTcp::whois(&format!("NetRange: 192.0.2.0 - 192.0.2.255\r\nReferralServer: whois://{}\r\n", registrar.addr()))?;
+        set_service_endpoints(Some(ServiceEndpoints { whois: Some(rir.addr().to_string()), ..endpoints.clone() }));
+        let address = mercy_whois("192.0.2.10");
+        Ok((domain?, address?))
+    });
+    set_service_endpoints(Some(endpoints));
+    check("whois/referral", referred.and_then(|(domain, address)| {
+        if domain.record.contains("Registrant Country") && domain.servers.len() == 2 && address.servers.len() == 2 {
+            Ok(format!("{} -> {}", domain.servers[0], domain.servers[1]))
+        } else {
+            Err(format!("{:?} {:?}", domain, address))
+        }
+    }));
+
+    // Announced, private and unannounced addresses in one bulk connection, and a single lookup
+    check("asn_bulk", match mercy_extra("asn_bulk", "1.1.1.1, 10.0.0.1, 8.8.8.8, 100.128.0.1, 198.18.255.1, not-an-ip") {
+        output if output.contains("1.1.1.1 | AS13335 | 1.1.1.0/24 | AU | apnic | CLOUDFLARENET, US") && output.contains("10.0.0.1 | - | Private address \u{2014} no ASN")
+            && output.contains("100.128.0.1 | - | not announced") && output.contains("198.18.255.1 | - | Reserved address") && output.contains("Skipped (not an IP address): not-an-ip") => Ok("2 announced, 1 not announced, 1 private, 1 reserved".to_string()),
+        output => Err(output)
+    });
+    check("asn_lookup", expect(mercy_extra("asn_lookup", "8.8.8.8"), "8.8.8.8 | AS15169 | 8.8.8.0/24 | US | arin | GOOGLE, US"));
+    let previous = service_endpoints();
+    set_service_endpoints(Some(ServiceEndpoints { asn_whois: "127.0.0.1:9".to_string(), ..previous.clone() }));
+    check("asn_lookup/offline", expect(mercy_extra_with(&MercyConfig::builder().retries(0).build().unwrap_or_default(), "asn_lookup", "8.8.8.8"), "ASN lookup of 8.8.8.8 failed: Unable to connect to WHOIS server 127.0.0.1:9"));
+    check("asn_lookup/private", expect(mercy_extra("asn_lookup", "192.168.1.20"), "Private address \u{2014} no ASN"));
+    set_service_endpoints(Some(previous));
+
+    check("dns/a", DnsQuery::new(MOCK_MALICIOUS_DOMAIN, DnsType::A).send(mocks.dns.addr(), timeout).and_then(|response| {
+        match response.answers.first().map(|record| &record.rdata) {
+            Some(DnsRdata::A(addr)) if addr.to_string() == "192.0.2.10" => Ok(addr.to_string()),
+            other => Err(format!("unexpected answer {:?}", other))
+        }
+    }));
+    check("dns/nxdomain", DnsQuery::new("missing.example", DnsType::A).send(mocks.dns.addr(), timeout)
+        .and_then(|response| if response.rcode == 3 { Ok(response.rcode_name()) } else { Err(response.rcode_name()) }));
+    check("dns/ptr", reverse_lookup(IpAddr::from([192, 0, 2, 10]), mocks.dns.addr(), timeout)
+        .and_then(|name| name.filter(|name| name == MOCK_MALICIOUS_DOMAIN).ok_or_else(|| "no PTR answer".to_string())));
+
+    let lookup = DnsLookupOptions { server: Some(mocks.dns.addr()), timeout };
+    let status = |wanted: DnsLookupStatus, result: Result<DnsLookup, String>| result.and_then(|lookup| {
+        if lookup.status == wanted { Ok(lookup.to_string()) } else { Err(format!("{:?}: {}", lookup.status, lookup)) }
+    });
+    check("dns_lookup", status(DnsLookupStatus::Ok, mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &lookup)));
+    check("dns_lookup/nxdomain", status(DnsLookupStatus::Nxdomain, mercy_dns_lookup("missing.example", &lookup)));
+    check("reverse_dns", status(DnsLookupStatus::Ok, Ok(mercy_reverse_dns(IpAddr::from([192, 0, 2, 10]), &lookup))));
+    check("reverse_dns/none", status(DnsLookupStatus::NoRecords, Ok(mercy_reverse_dns(IpAddr::from([192, 0, 2, 11]), &lookup))));
+
+    // A socket nobody reads from stands in for an unresponsive server
+    let silent = UdpSocket::bind("127.0.0.1:0").and_then(|socket| socket.local_addr().map(|addr| (socket, addr)));
+    check("dns_lookup/timeout", silent.map_err(|e| e.to_string()).and_then(|(_socket, addr)| {
+        status(DnsLookupStatus::Timeout, mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &DnsLookupOptions { server: Some(addr), timeout: Duration::from_millis(500) }))
+    }));
+
+    check("banner", mercy_telnet_check(&mocks.banner.addr().to_string()).and_then(|report| {
+        if report.banner.starts_with("SSH-2.0-") { Ok(report.banner.trim().to_string()) } else { Err(format!("banner {:?}", report.banner)) }
+    }));
+
+    let banner = mocks.banner.addr();
+    check("port_check", mercy_port_check(&banner.to_string(), &PortOptions::default()).and_then(|check| {
+        if check.state == PortState::Open { Ok(check.to_string()) } else { Err(check.to_string()) }
+    }));
+    check("port_scan", mercy_port_scan(&banner.ip().to_string(), banner.port().saturating_sub(2).max(1), banner.port(), &PortOptions::default())
+        .and_then(|report| if report.open.contains(&banner.port()) { Ok(format!("{} open of {}", report.open.len(), report.scanned)) } else { Err(report.to_string()) }));
+
+    let options = TriageOptions { step_timeout: Duration::from_secs(5), server: Some(mocks.dns.addr()), ..Default::default() };
+    check("triage/domain", mercy_triage(MOCK_MALICIOUS_DOMAIN, &options).and_then(|report| {
+        let ok = |name: &str| report.steps.iter().any(|step| step.name == name && step.status == TriageStatus::Ok);
+        if ok("dns") && ok("whois") && ok("reputation") && report.findings.iter().any(|finding| finding.id == "triage.reputation") {
+            Ok(format!("{} steps, {} findings", report.steps.len(), report.findings.len()))
+        } else {
+            Err(report.to_string())
+        }
+    }));
+    check("triage/url", mercy_triage(&mocks.http.url("/login"), &options).and_then(|report| {
+        match report.steps.iter().find(|step| step.name == "headers") {
+            Some(step) if step.status == TriageStatus::Ok => Ok(step.summary.clone()),
+            _ => Err(report.to_string())
+        }
+    }));
+
+    check("hash_status", expect(mercy_malicious("hash_status", MOCK_MALICIOUS_HASH), "Malicious, file type DOC, first seen 2024-03-01T10:00:00"));
+    check("hash_status/unseen", expect(mercy_malicious("hash_status", "098f6bcd4621d373cade4e832627b4f6"), "No records found"));
+    let before = mocks.http.requests().len();
+    check("hash_status/invalid", expect(mercy_malicious("hash_status", "abc123"), "Invalid hash").and_then(|wanted| {
+        if mocks.http.requests().len() == before { Ok(wanted) } else { Err("a request was made for an invalid hash".to_string()) }
+    }));
+
+    check("http_headers", mercy_http_headers(&mocks.http.url("/login"), &HttpHeadersOptions::default()).and_then(|response| {
+        if response.status == 200 && response.headers.iter().any(|header| header.name == "content-type") { Ok(response.status_line) } else { Err(response.to_string()) }
+    }));
+    check("security_headers", mercy_security_headers(&mocks.http.url("/"), &HttpHeadersOptions::default()).and_then(|report| {
+        if report.headers.iter().all(|header| header.status == "missing") { Ok(format!("{} missing", report.findings.len())) } else { Err(report.to_string()) }
+    }));
+    check("http_headers/not-http", match mercy_http_headers(&format!("http://{}/", banner), &HttpHeadersOptions::default()) {
+        Ok(response) => Err(format!("banner service answered as HTTP: {}", response)),
+        Err(e) => Ok(e)
+    });
+
+    // The mock HTTP server stands in for a proxy: a proxied request names the whole URL
+    let proxied = MercyConfig::builder().proxy(&mocks.http.url("")).build();
+    check("config/proxy", proxied.and_then(|config| {
+        mercy_extra_with(&config, "http_headers", "http://proxied.example/page");
+        let requests = mocks.http.requests();
+        if requests.iter().any(|request| request.contains("http://proxied.example/page")) { Ok(format!("{} requests served", requests.len())) } else { Err(format!("requests {:?}", requests)) }
+    }));
+
+    // Every lookup waits 100 ms, so a batch of 100 only finishes quickly if they overlap
+    let slow = MockHttp::start(vec![
+        MockRoute::new("/api/dfi/search/ioc/domain?keyword=", 200, MOCK_INQUEST_EMPTY).delay(Duration::from_millis(100)),
+        MockRoute::new(&format!("/api/dfi/search/ioc/domain?keyword={}", MOCK_MALICIOUS_DOMAIN), 200, MOCK_INQUEST_MALICIOUS).delay(Duration::from_millis(100))
+    ]);
+    check("batch/concurrent", slow.and_then(|slow| {
+        let previous = service_endpoints();
+        set_service_endpoints(Some(ServiceEndpoints { inquest: slow.url(""), ..previous.clone() }));
+        let started = Instant::now();
+        mercy_malicious("status", "single.example");
+        let single = started.elapsed();
+
+        let domains: Vec<String> = (0..100).map(|n| if n % 10 == 7 { MOCK_MALICIOUS_DOMAIN.to_string() } else { format!("host{}.example", n) }).collect();
+        let inputs: Vec<&str> = domains.iter().map(String::as_str).collect();
+        let started = Instant::now();
+        let outputs = mercy_malicious_batch("status", &inputs);
+        let batch = started.elapsed();
+        set_service_endpoints(Some(previous));
+
+        let ordered = outputs.len() == 100 && outputs.iter().enumerate().all(|(n, output)| output.starts_with(if n % 10 == 7 { "Malicious" } else { "No classification" }));
+        match (ordered, batch < single * 25) {
+            (true, true) => Ok(format!("100 lookups in {:?}, one in {:?}", batch, single)),
+            (false, _) => Err(format!("outputs out of order: {:?}", outputs)),
+            (true, false) => Err(format!("100 lookups took {:?}, one {:?}", batch, single))
+        }
+    }));
+
+    // Two rate limits, the first with a Retry-After of a second, then the answer
+    let limited = MockHttp::start(vec![
+        MockRoute::new("/api/dfi/search/ioc/domain", 429, "").header("Retry-After", "1").times(1),
+        MockRoute::new("/api/dfi/search/ioc/domain", 429, "").times(1),
+        MockRoute::new("/api/dfi/search/ioc/domain", 200, MOCK_INQUEST_MALICIOUS)
+    ]);
+    let quick = MercyConfig::builder().retry_backoff(Duration::from_millis(20)).build();
+    check("retry/429", limited.and_then(|limited| {
+        let config = quick.clone()?;
+        let previous = service_endpoints();
+        set_service_endpoints(Some(ServiceEndpoints { inquest: limited.url(""), ..previous.clone() }));
+        let started = Instant::now();
+        let output = mercy_malicious_with(&config, "status", MOCK_MALICIOUS_DOMAIN);
+        let elapsed = started.elapsed();
+        set_service_endpoints(Some(previous));
+
+        match (expect(output, "Malicious"), limited.requests().len()) {
+            (Ok(wanted), 3) if elapsed >= Duration::from_secs(1) => Ok(format!("{} after 3 requests in {:?}", wanted, elapsed)),
+            (Ok(_), count) => Err(format!("{} requests in {:?}, expected 3 taking at least the Retry-After", count, elapsed)),
+            (Err(e), _) => Err(e)
+        }
+    }));
+
+    // Five server errors outlast the default three retries
+    let failing = MockHttp::start(vec![
+        MockRoute::new("/api/dfi/search/", 500, "").times(5),
+        MockRoute::new("/api/dfi/search/", 200, MOCK_INQUEST_EMPTY)
+    ]);
+    check("retry/500", failing.and_then(|failing| {
+        let config = quick.clone()?;
+        let previous = service_endpoints();
+        set_service_endpoints(Some(ServiceEndpoints { inquest: failing.url(""), ..previous.clone() }));
+        let output = mercy_malicious_with(&config, "hash_status", MOCK_MALICIOUS_HASH);
+        set_service_endpoints(Some(previous));
+
+        match (expect(output, "gave up after 4 attempt(s), last status 500 Internal Server Error"), failing.requests().len()) {
+            (Ok(wanted), 4) => Ok(wanted),
+            (Ok(_), count) => Err(format!("{} requests instead of 4", count)),
+            (Err(e), _) => Err(e)
+        }
+    }));
+
+    // Only the first five hex digits of the SHA-1 may reach the range API
+    check("hibp_check", expect(mercy_extra("hibp_check", MOCK_PWNED_PASSWORD), "seen 2254650 times").and_then(|wanted| {
+        let requests = mocks.http.requests();
+        let ranges: Vec<&String> = requests.iter().filter(|request| request.contains("/range/")).collect();
+        match ranges.iter().all(|request| request.starts_with("GET /range/CBFDA ")) && !requests.iter().any(|request| request.contains("C6008F9")) {
+            true if !ranges.is_empty() => Ok(wanted),
+            _ => Err(format!("requests {:?}", requests))
+        }
+    }));
+    check("hibp_check/unseen", expect(mercy_extra("hibp_check", "q7#Vx!2mR9$kLp@4Zt&w"), "Not found"));
+    let previous = service_endpoints();
+    set_service_endpoints(Some(ServiceEndpoints { pwned_passwords: "http://127.0.0.1:9".to_string(), ..previous.clone() }));
+    check("hibp_check/offline", expect(mercy_extra("hibp_check", MOCK_PWNED_PASSWORD), "(offline?)"));
+    set_service_endpoints(Some(previous));
+
+    // Names split, unwrapped, lowercased and deduplicated; the email address, the apex and the other domain left out
+    let wanted = "api.corp.example\ndev.corp.example\nmail.corp.example\nvpn.corp.example\nwww.corp.example";
+    check("subdomains", match mercy_extra("subdomains", MOCK_CRTSH_DOMAIN) {
+        found if found == wanted => Ok(found.replace('\n', " ")),
+        found => Err(format!("expected {:?}, got {:?}", wanted, found))
+    });
+    check("subdomains/none", expect(mercy_extra("subdomains", "quiet.example"), "No certificates found for quiet.example"));
+    let previous = service_endpoints();
+    let overloaded = MockHttp::start(vec![MockRoute::new("/", 503, "")]).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    set_service_endpoints(Some(ServiceEndpoints { crtsh: overloaded.url(""), ..previous.clone() }));
+    check("subdomains/retry", expect(mercy_extra("subdomains", MOCK_CRTSH_DOMAIN), "gave up after 2 attempt(s), last status 503 Service Unavailable").and_then(|wanted| match overloaded.requests().len() {
+        2 => Ok(wanted),
+        count => Err(format!("{} requests instead of 2", count))
+    }));
+    set_service_endpoints(Some(ServiceEndpoints { crtsh: "http://127.0.0.1:9".to_string(), ..previous.clone() }));
+    check("subdomains/offline", expect(mercy_extra("subdomains", MOCK_CRTSH_DOMAIN), "(offline?)"));
+    set_service_endpoints(Some(previous));
+
+    drop(mocks);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+// The output when it contains `wanted`
+fn expect(output: String, wanted: &str) -> Result<String, String> {
+    if output.contains(wanted) {
+        Ok(wanted.to_string())
+    } else {
+        Err(format!("expected {:?} in {:?}", wanted, output))
+    }
+}
@@ -0,0 +1,458 @@
+/*
+    ANSI escape sequences in captured terminal output
+
+    Sequences are recognised in both their 7-bit (ESC [) and 8-bit (U+009B) forms: CSI
+    control functions, OSC commands ended by BEL or ST, DCS/SOS/PM/APC strings ended by ST,
+    and two-character escapes. An escape cut off by the end of the input runs to the end
+    and is marked truncated; an ESC, CAN or SUB inside a string aborts it the way terminals
+    do. Every step advances at least one character, so hostile input cannot stall the scan.
+
+    Stripping removes the sequences and leaves everything else, including plain control
+    characters such as CR and backspace. Inspection describes each sequence and rates the
+    ones that do more than colour text: clipboard access (OSC 52), window title changes and
+    reports, requests that make the terminal type a reply, file transfers and pass-through
+    to an outer terminal, and text hidden by concealment or screen clearing.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::finding::Severity;
+
+// Longest escaped form kept for one sequence in a report
+const MAX_RAW: usize = 160;
+
+const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// One escape sequence found in text
+#[derive(Debug, Clone, Serialize)]
+pub struct AnsiSequence {
+    /// Char offset of the sequence's first character
+    pub offset: usize,
+    /// Length in chars
+    pub length: usize,
+    /// "csi", "osc", "dcs", "sos", "pm", "apc", "esc" or "c1"
+    pub kind: String,
+    /// The sequence with control characters written as "\x1b" (long ones cut short)
+    pub raw: String,
+    pub description: String,
+    /// Set for sequences that can do harm
+    pub severity: Option<Severity>,
+    pub risk: Option<String>,
+    /// Cut off by the end of the input
+    pub truncated: bool
+}
+
+/// Escape sequences found in text, with the text stripped of them
+#[derive(Debug, Clone, Serialize)]
+pub struct AnsiReport {
+    pub characters: usize,
+    pub sequences: Vec<AnsiSequence>,
+    /// Sequences rated medium or above
+    pub dangerous: usize,
+    pub stripped: String
+}
+
+impl fmt::Display for AnsiReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} escape sequence(s) in {} characters, {} dangerous", self.sequences.len(), self.characters, self.dangerous)?;
+
+        for sequence in &self.sequences {
+            write!(f, "  {:>6} {:<4} {} - {}", sequence.offset, sequence.kind, sequence.raw, sequence.description)?;
+            if sequence.truncated {
+                write!(f, " (truncated)")?;
+            }
+            if let (Some(severity), Some(risk)) = (sequence.severity, &sequence.risk) {
+                write!(f, " [{}: {}]", severity, risk)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The text with every escape sequence removed (7-bit and 8-bit forms, including truncated ones)
+pub fn mercy_strip_ansi(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut stripped = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for sequence in scan(&chars) {
+        stripped.extend(&chars[last..sequence.start]);
+        last = sequence.end;
+    }
+
+    stripped.extend(&chars[last..]);
+    stripped
+}
+
+/// Lists and describes every escape sequence in the text, rating the dangerous ones
+pub fn mercy_ansi_inspect(text: &str) -> AnsiReport {
+    let chars: Vec<char> = text.chars().collect();
+    let sequences: Vec<AnsiSequence> = scan(&chars).into_iter().map(|sequence| describe(&chars, &sequence)).collect();
+
+    AnsiReport {
+        characters: chars.len(),
+        dangerous: sequences.iter().filter(|sequence| sequence.severity >= Some(Severity::Medium)).count(),
+        sequences,
+        stripped: mercy_strip_ansi(text)
+    }
+}
+
+/* Scanning */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Csi,
+    Osc,
+    Dcs,
+    Sos,
+    Pm,
+    Apc,
+    Esc,
+    C1
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Csi => "csi",
+            Kind::Osc => "osc",
+            Kind::Dcs => "dcs",
+            Kind::Sos => "sos",
+            Kind::Pm => "pm",
+            Kind::Apc => "apc",
+            Kind::Esc => "esc",
+            Kind::C1 => "c1"
+        }
+    }
+}
+
+// A sequence's span, and where its body (after the introducer, before any terminator) lies
+struct Span {
+    kind: Kind,
+    start: usize,
+    end: usize,
+    body: (usize, usize),
+    truncated: bool
+}
+
+fn scan(chars: &[char]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut index = 0;
+
+    while index < chars.len() {
+        match sequence_at(chars, index) {
+            Some(span) => {
+                index = span.end.max(index + 1);
+                spans.push(span);
+            },
+            None => index += 1
+        }
+    }
+
+    spans
+}
+
+fn sequence_at(chars: &[char], start: usize) -> Option<Span> {
+    let (kind, body) = match chars[start] {
+        '\x1b' => match chars.get(start + 1) {
+            Some('[') => (Kind::Csi, start + 2),
+            Some(']') => (Kind::Osc, start + 2),
+            Some('P') => (Kind::Dcs, start + 2),
+            Some('X') => (Kind::Sos, start + 2),
+            Some('^') => (Kind::Pm, start + 2),
+            Some('_') => (Kind::Apc, start + 2),
+            _ => (Kind::Esc, start + 1)
+        },
+        '\u{9b}' => (Kind::Csi, start + 1),
+        '\u{9d}' => (Kind::Osc, start + 1),
+        '\u{90}' => (Kind::Dcs, start + 1),
+        '\u{98}' => (Kind::Sos, start + 1),
+        '\u{9e}' => (Kind::Pm, start + 1),
+        '\u{9f}' => (Kind::Apc, start + 1),
+        '\u{80}'..='\u{9f}' => (Kind::C1, start + 1),
+        _ => return None
+    };
+
+    let span = |end: usize, body_end: usize, truncated: bool| Span { kind, start, end, body: (body, body_end), truncated };
+
+    Some(match kind {
+        Kind::C1 => span(body, body, false),
+        Kind::Esc => {
+            // Intermediates (0x20-0x2F) then a final character (0x30-0x7E)
+            let finish = (body..chars.len()).find(|index| !('\x20'..='\x2f').contains(&chars[*index]));
+            match finish {
+                Some(index) if ('\x30'..='\x7e').contains(&chars[index]) => span(index + 1, index + 1, false),
+                Some(index) => span(index, index, false),
+                None => span(chars.len(), chars.len(), true)
+            }
+        },
+        Kind::Csi => {
+            // Parameters (0x30-0x3F), intermediates (0x20-0x2F), then a final character (0x40-0x7E)
+            let mut index = body;
+            while index < chars.len() && ('\x30'..='\x3f').contains(&chars[index]) {
+                index += 1;
+            }
+            while index < chars.len() && ('\x20'..='\x2f').contains(&chars[index]) {
+                index += 1;
+            }
+            match chars.get(index) {
+                Some(c) if ('\x40'..='\x7e').contains(c) => span(index + 1, index, false),
+                // Malformed: the sequence ends before the character that does not belong
+                Some(_) => span(index, index, false),
+                None => span(chars.len(), chars.len(), true)
+            }
+        },
+        _ => {
+            // Strings end at ST (ESC \ or U+009C), or BEL for OSC; another ESC, CAN or SUB aborts them
+            let mut index = body;
+            loop {
+                match chars.get(index) {
+                    None => break span(chars.len(), chars.len(), true),
+                    Some('\x07') if kind == Kind::Osc => break span(index + 1, index, false),
+                    Some('\u{9c}') => break span(index + 1, index, false),
+                    Some('\x1b') if chars.get(index + 1) == Some(&'\\') => break span(index + 2, index, false),
+                    Some('\x1b') => break span(index, index, false),
+                    Some('\x18') | Some('\x1a') => break span(index + 1, index, false),
+                    Some(_) => index += 1
+                }
+            }
+        }
+    })
+}
+
+/* Descriptions */
+
+fn describe(chars: &[char], span: &Span) -> AnsiSequence {
+    let body: String = chars[span.body.0..span.body.1].iter().collect();
+    let (description, rating) = match span.kind {
+        Kind::Csi => describe_csi(&body, chars.get(span.body.1).filter(|_| !span.truncated && span.end > span.body.1).copied()),
+        Kind::Osc => describe_osc(&body),
+        Kind::Dcs => describe_dcs(&body),
+        Kind::Sos => ("start of string (ignored by most terminals)".to_string(), Some((Severity::Low, "text inside it is hidden from view"))),
+        Kind::Pm => ("privacy message (ignored by most terminals)".to_string(), Some((Severity::Low, "text inside it is hidden from view"))),
+        Kind::Apc => describe_apc(&body),
+        Kind::Esc => describe_esc(&chars[span.start + 1..span.end]),
+        Kind::C1 => (format!("C1 control U+{:04X}", chars[span.start] as u32), None)
+    };
+
+    let raw: String = chars[span.start..span.end].iter().map(|c| visible(*c)).collect();
+    let raw = if raw.chars().count() > MAX_RAW { format!("{}...", raw.chars().take(MAX_RAW).collect::<String>()) } else { raw };
+
+    AnsiSequence {
+        offset: span.start,
+        length: span.end - span.start,
+        kind: span.kind.as_str().to_string(),
+        raw,
+        description,
+        severity: rating.map(|(severity, _)| severity),
+        risk: rating.map(|(_, risk)| risk.to_string()),
+        truncated: span.truncated
+    }
+}
+
+type Rating = Option<(Severity, &'static str)>;
+
+fn describe_csi(body: &str, last: Option<char>) -> (String, Rating) {
+    let Some(last) = last else {
+        return (format!("incomplete control sequence \"{}\"", body), None);
+    };
+
+    let split = body.find(|c: char| ('\x20'..='\x2f').contains(&c)).unwrap_or(body.len());
+    let (parameters, intermediates) = body.split_at(split);
+    let private = parameters.starts_with(['?', '>', '<', '=']);
+    let numbers: Vec<u32> = parameters.trim_start_matches(['?', '>', '<', '=']).split([';', ':']).map(|number| number.parse().unwrap_or(0)).collect();
+    let first = numbers.first().copied().unwrap_or(0);
+    let count = first.max(1);
+
+    let text = |name: &str| (name.to_string(), None);
+    match (last, intermediates, private) {
+        ('m', "", false) => describe_sgr(&numbers, parameters.is_empty()),
+        ('A', "", false) => (format!("cursor up {}", count), Some((Severity::Low, "moves back over earlier output, which can be overwritten"))),
+        ('B', "", false) => text(&format!("cursor down {}", count)),
+        ('C', "", false) => text(&format!("cursor forward {}", count)),
+        ('D', "", false) => text(&format!("cursor back {}", count)),
+        ('E', "", false) => text(&format!("cursor to start of line {} down", count)),
+        ('F', "", false) => (format!("cursor to start of line {} up", count), Some((Severity::Low, "moves back over earlier output, which can be overwritten"))),
+        ('G', "", false) => text(&format!("cursor to column {}", count)),
+        ('H' | 'f', "", false) => (format!("cursor position {}", parameters.replace(';', ",")), Some((Severity::Low, "can move back over earlier output"))),
+        ('J', "", false) => match first {
+            2 | 3 => ("erase display".to_string(), Some((Severity::Low, "clears earlier output from view"))),
+            _ => text("erase part of the display")
+        },
+        ('K', "", false) => text("erase in line"),
+        ('S', "", false) => text(&format!("scroll up {}", count)),
+        ('T', "", false) => text(&format!("scroll down {}", count)),
+        ('s', "", false) => text("save cursor"),
+        ('u', "", false) => text("restore cursor"),
+        ('@' | 'P' | 'X' | 'L' | 'M', "", false) => text("insert or delete characters or lines"),
+        ('r', "", false) => text("set scrolling region"),
+        ('n', "", false) if first == 5 || first == 6 => (format!("device status report {}", first), Some((Severity::Medium, "makes the terminal type a reply into the input"))),
+        ('c', "", _) => (format!("device attributes request {}", parameters), Some((Severity::Low, "makes the terminal type a reply into the input"))),
+        ('t', "", false) => match first {
+            20 | 21 => ("report window title".to_string(), Some((Severity::High, "types the window title into the input, which a title change can set to a command"))),
+            11 | 13 | 14 | 18 | 19 => ("report window state".to_string(), Some((Severity::Medium, "makes the terminal type a reply into the input"))),
+            22 | 23 => text("save or restore window title"),
+            _ => ("window manipulation".to_string(), Some((Severity::Medium, "moves, resizes or iconifies the terminal window")))
+        },
+        ('h' | 'l', "", true) => {
+            let setting = if last == 'h' { "set" } else { "reset" };
+            match first {
+                25 => text(&format!("cursor visibility {}", setting)),
+                47 | 1047 | 1049 => (format!("alternate screen {}", setting), Some((Severity::Low, "switches screens, hiding output from view"))),
+                2004 if last == 'l' => ("bracketed paste off".to_string(), Some((Severity::Medium, "pasted text can run as typed commands"))),
+                1000..=1006 | 1015 => text(&format!("mouse reporting {}", setting)),
+                _ => text(&format!("private mode {} {}", parameters, setting))
+            }
+        },
+        ('h' | 'l', "", false) => text("mode set or reset"),
+        ('q', " ", false) => text("cursor style"),
+        ('p', "!", false) => text("soft terminal reset"),
+        _ => text(&format!("control function '{}'", last))
+    }
+}
+
+fn describe_sgr(numbers: &[u32], reset: bool) -> (String, Rating) {
+    if reset {
+        return ("SGR reset".to_string(), None);
+    }
+
+    let mut parts = Vec::new();
+    let mut rating = None;
+    let (mut foreground, mut background) = (None, None);
+    let mut index = 0;
+
+    while index < numbers.len() {
+        let number = numbers[index];
+        match number {
+            0 => parts.push("reset".to_string()),
+            1 => parts.push("bold".to_string()),
+            2 => parts.push("dim".to_string()),
+            3 => parts.push("italic".to_string()),
+            4 => parts.push("underline".to_string()),
+            5 | 6 => parts.push("blink".to_string()),
+            7 => parts.push("reverse".to_string()),
+            8 => {
+                parts.push("conceal".to_string());
+                rating = Some((Severity::Medium, "conceals the text that follows"));
+            },
+            9 => parts.push("strikethrough".to_string()),
+            30..=37 | 90..=97 => {
+                foreground = Some(number % 10);
+                parts.push(format!("foreground {}{}", if number >= 90 { "bright " } else { "" }, COLORS[(number % 10) as usize]));
+            },
+            40..=47 | 100..=107 => {
+                background = Some(number % 10);
+                parts.push(format!("background {}{}", if number >= 100 { "bright " } else { "" }, COLORS[(number % 10) as usize]));
+            },
+            38 | 48 => {
+                let layer = if number == 38 { "foreground" } else { "background" };
+                match numbers.get(index + 1) {
+                    Some(5) => {
+                        parts.push(format!("{} colour {}", layer, numbers.get(index + 2).copied().unwrap_or(0)));
+                        index += 2;
+                    },
+                    Some(2) => {
+                        let rgb: Vec<String> = numbers.iter().skip(index + 2).take(3).map(|value| value.to_string()).collect();
+                        parts.push(format!("{} rgb({})", layer, rgb.join(",")));
+                        index += 4;
+                    },
+                    _ => parts.push(format!("{} colour", layer))
+                }
+            },
+            39 => parts.push("default foreground".to_string()),
+            49 => parts.push("default background".to_string()),
+            _ => parts.push(format!("attribute {}", number))
+        }
+        index += 1;
+    }
+
+    if foreground.is_some() && foreground == background {
+        rating = Some((Severity::Medium, "foreground matches background, hiding the text"));
+    }
+
+    (format!("SGR {}", parts.join(", ")), rating)
+}
+
+fn describe_osc(body: &str) -> (String, Rating) {
+    let (command, data) = body.split_once(';').unwrap_or((body, ""));
+    let preview = |text: &str| text.chars().take(60).map(visible).collect::<String>();
+
+    match command {
+        "0" | "1" | "2" => (format!("set window title \"{}\"", preview(data)), Some((Severity::Medium, "changes the window title, which a title report can type into the input"))),
+        "52" => {
+            let (_, payload) = data.split_once(';').unwrap_or(("", data));
+            if payload == "?" {
+                ("clipboard query".to_string(), Some((Severity::High, "makes the terminal type the clipboard contents into the input")))
+            } else {
+                let decoded = base64::decode(payload).map(|bytes| String::from_utf8_lossy(&bytes).to_string()).unwrap_or_default();
+                (format!("clipboard write \"{}\"", preview(&decoded)), Some((Severity::High, "replaces the clipboard, which the user may paste into a shell")))
+            }
+        },
+        "8" => {
+            let target = data.split_once(';').map_or("", |(_, uri)| uri);
+            if target.is_empty() {
+                ("hyperlink end".to_string(), None)
+            } else {
+                (format!("hyperlink to \"{}\"", preview(target)), Some((Severity::Low, "the link target can differ from the visible text")))
+            }
+        },
+        "4" | "10" | "11" | "12" | "17" | "19" | "104" | "110" | "111" | "112" if data.contains('?') => ("colour query".to_string(), Some((Severity::Medium, "makes the terminal type a reply into the input"))),
+        "4" | "10" | "11" | "12" | "17" | "19" | "104" | "110" | "111" | "112" => ("set colour".to_string(), None),
+        "7" => (format!("working directory \"{}\"", preview(data)), None),
+        "9" | "777" => (format!("desktop notification \"{}\"", preview(data)), Some((Severity::Low, "shows text outside the terminal"))),
+        "50" => ("font change or query".to_string(), Some((Severity::Medium, "a query makes the terminal type a reply into the input"))),
+        "1337" if data.starts_with("File=") => ("iTerm2 file transfer".to_string(), Some((Severity::High, "writes a file to disk or displays content from it"))),
+        "1337" => ("iTerm2 command".to_string(), Some((Severity::Medium, "proprietary terminal command"))),
+        _ => (format!("operating system command {}", preview(command)), None)
+    }
+}
+
+fn describe_dcs(body: &str) -> (String, Rating) {
+    if body.starts_with("tmux;") {
+        ("tmux pass-through".to_string(), Some((Severity::High, "sends sequences past tmux to the outer terminal")))
+    } else if body.starts_with("$q") || body.starts_with("+q") {
+        ("status or capability request".to_string(), Some((Severity::Medium, "makes the terminal type a reply into the input")))
+    } else if body.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';').starts_with('q') {
+        ("sixel graphics".to_string(), Some((Severity::Low, "draws an image, which can cover text")))
+    } else {
+        ("device control string".to_string(), Some((Severity::Low, "device-specific command")))
+    }
+}
+
+fn describe_apc(body: &str) -> (String, Rating) {
+    if body.starts_with('G') {
+        ("kitty graphics".to_string(), Some((Severity::Low, "draws an image, which can cover text")))
+    } else {
+        ("application program command".to_string(), Some((Severity::Low, "text inside it is hidden from view")))
+    }
+}
+
+fn describe_esc(sequence: &[char]) -> (String, Rating) {
+    let text = |name: &str| (name.to_string(), None);
+    match sequence {
+        [] => text("lone ESC"),
+        ['c'] => ("full reset".to_string(), Some((Severity::Low, "clears earlier output from view"))),
+        ['7'] => text("save cursor"),
+        ['8'] => text("restore cursor"),
+        ['M'] => text("reverse line feed"),
+        ['D'] => text("line feed"),
+        ['E'] => text("next line"),
+        ['=' | '>'] => text("keypad mode"),
+        ['Z'] => ("identify terminal".to_string(), Some((Severity::Low, "makes the terminal type a reply into the input"))),
+        ['#', '8'] => ("screen alignment test".to_string(), Some((Severity::Low, "fills the screen, hiding earlier output"))),
+        ['(' | ')' | '*' | '+', _] => text("character set designation"),
+        _ => text("escape sequence")
+    }
+}
+
+// Control characters as \x escapes, everything else as it is
+fn visible(c: char) -> String {
+    match c {
+        '\0'..='\x1f' | '\x7f'..='\u{9f}' => format!("\\x{:02x}", c as u32),
+        _ => c.to_string()
+    }
+}
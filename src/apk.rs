@@ -0,0 +1,641 @@
+/*
+    Android APK quick triage
+
+    Decodes the binary (AXML) AndroidManifest.xml for identity, SDK levels, permissions, exported
+    components and the debuggable/allowBackup flags, then summarizes the signing certificates
+    (META-INF PKCS#7 blocks, or the v2/v3 APK Signing Block) and the dex files. A manifest that
+    stops parsing part way still yields whatever was read before the damage.
+*/
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    finding::{Finding, Severity},
+    paths::path_to_string,
+    registry::{le16, le32, le64},
+    x509::{CertificateInfo, parse_certificate, pkcs7_certificates},
+    zip::ZipArchive
+};
+
+// Largest manifest or signature entry decompressed
+const MAX_ENTRY: usize = 16 * 1024 * 1024;
+
+// Guards against deeply nested or looping element streams in hostile manifests
+const MAX_DEPTH: usize = 64;
+
+// Runtime ("dangerous" protection level) permissions
+const DANGEROUS_PERMISSIONS: [&str; 40] = [
+    "READ_CALENDAR", "WRITE_CALENDAR", "CAMERA", "READ_CONTACTS", "WRITE_CONTACTS", "GET_ACCOUNTS",
+    "ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION", "ACCESS_BACKGROUND_LOCATION", "ACCESS_MEDIA_LOCATION",
+    "RECORD_AUDIO", "READ_PHONE_STATE", "READ_PHONE_NUMBERS", "CALL_PHONE", "ANSWER_PHONE_CALLS",
+    "READ_CALL_LOG", "WRITE_CALL_LOG", "ADD_VOICEMAIL", "USE_SIP", "PROCESS_OUTGOING_CALLS", "ACCEPT_HANDOVER",
+    "BODY_SENSORS", "BODY_SENSORS_BACKGROUND", "ACTIVITY_RECOGNITION", "SEND_SMS", "RECEIVE_SMS", "READ_SMS",
+    "RECEIVE_WAP_PUSH", "RECEIVE_MMS", "READ_EXTERNAL_STORAGE", "WRITE_EXTERNAL_STORAGE", "READ_MEDIA_IMAGES",
+    "READ_MEDIA_VIDEO", "READ_MEDIA_AUDIO", "READ_MEDIA_VISUAL_USER_SELECTED", "POST_NOTIFICATIONS",
+    "NEARBY_WIFI_DEVICES", "BLUETOOTH_SCAN", "BLUETOOTH_CONNECT", "BLUETOOTH_ADVERTISE"
+];
+
+/// A permission requested with uses-permission
+#[derive(Debug, Clone, Serialize)]
+pub struct ApkPermission {
+    pub name: String,
+    pub dangerous: bool
+}
+
+/// An activity, activity-alias, service, receiver or provider reachable from other apps
+#[derive(Debug, Clone, Serialize)]
+pub struct ApkComponent {
+    pub kind: String,
+    pub name: String,
+    /// False when exported only implicitly (intent filter without android:exported)
+    pub explicit: bool,
+    /// Permission a caller must hold, if any
+    pub permission: Option<String>
+}
+
+/// A dex file in the archive root
+#[derive(Debug, Clone, Serialize)]
+pub struct ApkDex {
+    pub name: String,
+    pub size: u64
+}
+
+/// APK triage results
+#[derive(Debug, Clone, Serialize)]
+pub struct ApkReport {
+    pub path: String,
+    pub package: Option<String>,
+    pub version_code: Option<String>,
+    pub version_name: Option<String>,
+    pub min_sdk: Option<String>,
+    pub target_sdk: Option<String>,
+    pub permissions: Vec<ApkPermission>,
+    /// Custom permissions the app defines
+    pub defined_permissions: Vec<String>,
+    pub debuggable: bool,
+    /// android:allowBackup, which defaults to true when absent
+    pub allow_backup: bool,
+    pub exported_components: Vec<ApkComponent>,
+    pub signature_schemes: Vec<String>,
+    pub certificates: Vec<CertificateInfo>,
+    pub dex_files: Vec<ApkDex>,
+    /// Parts of the APK that could not be read
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for ApkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+
+        writeln!(f, "APK: {}", self.path)?;
+        writeln!(f, "Package: {}", unknown(&self.package))?;
+        writeln!(f, "Version: {} ({})", unknown(&self.version_name), unknown(&self.version_code))?;
+        writeln!(f, "SDK: min {}, target {}", unknown(&self.min_sdk), unknown(&self.target_sdk))?;
+        writeln!(f, "Debuggable: {}", self.debuggable)?;
+        writeln!(f, "Allow backup: {}", self.allow_backup)?;
+
+        writeln!(f, "Permissions ({}):", self.permissions.len())?;
+        for permission in &self.permissions {
+            writeln!(f, "  {}{}", permission.name, if permission.dangerous { "  [dangerous]" } else { "" })?;
+        }
+
+        writeln!(f, "Exported components ({}):", self.exported_components.len())?;
+        for component in &self.exported_components {
+            write!(f, "  {} {}", component.kind, component.name)?;
+            if !component.explicit {
+                write!(f, "  [implicit]")?;
+            }
+            if let Some(permission) = &component.permission {
+                write!(f, "  [requires {}]", permission)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Signature schemes: {}", if self.signature_schemes.is_empty() { "none found".to_string() } else { self.signature_schemes.join(", ") })?;
+        for certificate in &self.certificates {
+            writeln!(f, "Certificate: {} (SHA-256 {})", certificate.subject, certificate.sha256)?;
+        }
+
+        for dex in &self.dex_files {
+            writeln!(f, "Dex: {} ({} bytes)", dex.name, dex.size)?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ApkReport {
+    /// Debug builds, backups, unprotected exported components, debug certificates and dangerous
+    /// permissions as findings
+    pub fn findings(&self) -> Vec<Finding> {
+        let target = self.package.clone().unwrap_or_else(|| self.path.clone());
+        let mut findings = Vec::new();
+
+        if self.debuggable {
+            findings.push(Finding::new(
+                "apk.debuggable",
+                "Application is debuggable",
+                Severity::High,
+                &target,
+                "android:debuggable=\"true\" on <application>",
+                "Ship release builds without android:debuggable; a debuggable app can be attached to and its data read"
+            ));
+        }
+
+        if self.allow_backup {
+            findings.push(Finding::new(
+                "apk.allow-backup",
+                "Application data can be backed up",
+                Severity::Low,
+                &target,
+                "android:allowBackup is true or not set",
+                "Set android:allowBackup=\"false\" or restrict backups with data extraction rules"
+            ));
+        }
+
+        let open: Vec<String> = self.exported_components.iter()
+            .filter(|component| component.permission.is_none())
+            .map(|component| format!("{} {}", component.kind, component.name))
+            .collect();
+        if !open.is_empty() {
+            findings.push(Finding::new(
+                "apk.exported-components",
+                "Exported components without a permission",
+                Severity::Medium,
+                &target,
+                &open.join("; "),
+                "Set android:exported=\"false\" or protect the component with a signature-level permission"
+            ));
+        }
+
+        if self.certificates.iter().any(|certificate| certificate.subject.contains("CN=Android Debug")) {
+            findings.push(Finding::new(
+                "apk.debug-certificate",
+                "Signed with an Android debug certificate",
+                Severity::Medium,
+                &target,
+                "Signing certificate subject contains CN=Android Debug",
+                "Sign release builds with a dedicated release key"
+            ));
+        }
+
+        let dangerous: Vec<&str> = self.permissions.iter().filter(|permission| permission.dangerous).map(|permission| permission.name.as_str()).collect();
+        if !dangerous.is_empty() {
+            findings.push(Finding::new(
+                "apk.dangerous-permissions",
+                "Dangerous permissions requested",
+                Severity::Info,
+                &target,
+                &dangerous.join(", "),
+                "Confirm each runtime permission is needed by the app's features"
+            ));
+        }
+
+        findings
+    }
+}
+
+/// Triages an APK: manifest, signing certificates and dex files
+pub fn mercy_apk<P: AsRef<Path>>(path: P) -> Result<ApkReport, String> {
+    let path = path.as_ref();
+    let archive = ZipArchive::open(Path::new(path))?;
+
+    let mut report = ApkReport {
+        path: path_to_string(path),
+        package: None,
+        version_code: None,
+        version_name: None,
+        min_sdk: None,
+        target_sdk: None,
+        permissions: Vec::new(),
+        defined_permissions: Vec::new(),
+        debuggable: false,
+        allow_backup: true,
+        exported_components: Vec::new(),
+        signature_schemes: Vec::new(),
+        certificates: Vec::new(),
+        dex_files: Vec::new(),
+        notes: Vec::new()
+    };
+
+    match archive.read("AndroidManifest.xml", MAX_ENTRY) {
+        Ok(Some(manifest)) => {
+            let (root, problem) = parse_axml(&manifest);
+            if let Some(problem) = problem {
+                report.notes.push(format!("AndroidManifest.xml is malformed ({}); results are partial", problem));
+            }
+            match root {
+                Some(root) => apply_manifest(&root, &mut report),
+                None => report.notes.push("AndroidManifest.xml has no root element".to_string())
+            }
+        }
+        Ok(None) => report.notes.push("AndroidManifest.xml is missing".to_string()),
+        Err(e) => report.notes.push(format!("Unable to read AndroidManifest.xml: {}", e))
+    }
+
+    signatures(&archive, &mut report);
+
+    report.dex_files = archive.entries().iter()
+        .filter(|entry| !entry.name.contains('/') && entry.name.starts_with("classes") && entry.name.ends_with(".dex"))
+        .map(|entry| ApkDex { name: entry.name.clone(), size: entry.size })
+        .collect();
+
+    Ok(report)
+}
+
+/* Manifest */
+
+// A decoded element: name, attributes (name to rendered value) and children
+#[derive(Debug, Default)]
+struct XmlElement {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<XmlElement>
+}
+
+impl XmlElement {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        self.attribute(name).map(|value| value == "true")
+    }
+
+    fn children_named<'e>(&'e self, name: &'e str) -> impl Iterator<Item = &'e XmlElement> + 'e {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn apply_manifest(root: &XmlElement, report: &mut ApkReport) {
+    report.package = root.attribute("package").map(str::to_string);
+    report.version_code = root.attribute("versionCode").map(str::to_string);
+    report.version_name = root.attribute("versionName").map(str::to_string);
+
+    if let Some(sdk) = root.children_named("uses-sdk").next() {
+        report.min_sdk = sdk.attribute("minSdkVersion").map(str::to_string);
+        report.target_sdk = sdk.attribute("targetSdkVersion").map(str::to_string);
+    }
+
+    for permission in root.children.iter().filter(|child| child.name == "uses-permission" || child.name == "uses-permission-sdk-23") {
+        if let Some(name) = permission.attribute("name") {
+            let short = name.strip_prefix("android.permission.").unwrap_or("");
+            report.permissions.push(ApkPermission { name: name.to_string(), dangerous: DANGEROUS_PERMISSIONS.contains(&short) });
+        }
+    }
+
+    report.defined_permissions = root.children_named("permission").filter_map(|permission| permission.attribute("name").map(str::to_string)).collect();
+
+    let application = match root.children_named("application").next() {
+        Some(application) => application,
+        None => return
+    };
+
+    report.debuggable = application.flag("debuggable").unwrap_or(false);
+    report.allow_backup = application.flag("allowBackup").unwrap_or(true);
+
+    // Providers were exported by default before API 17
+    let target_sdk = report.target_sdk.as_deref().and_then(|sdk| sdk.parse::<u32>().ok()).unwrap_or(1);
+    let package = report.package.clone().unwrap_or_default();
+
+    for component in &application.children {
+        if !matches!(component.name.as_str(), "activity" | "activity-alias" | "service" | "receiver" | "provider") {
+            continue;
+        }
+
+        let explicit = component.flag("exported");
+        let exported = match explicit {
+            Some(exported) => exported,
+            None => component.children_named("intent-filter").next().is_some() || (component.name == "provider" && target_sdk < 17)
+        };
+
+        if !exported {
+            continue;
+        }
+
+        let name = component.attribute("name").unwrap_or("");
+        let name = if name.starts_with('.') { format!("{}{}", package, name) } else { name.to_string() };
+
+        // Providers can also be guarded by separate read/write permissions
+        let permission = component.attribute("permission")
+            .or_else(|| component.attribute("readPermission"))
+            .map(str::to_string);
+
+        report.exported_components.push(ApkComponent { kind: component.name.clone(), name, explicit: explicit.is_some(), permission });
+    }
+}
+
+// Framework attribute resource IDs, used when the string pool names are missing or obfuscated
+fn framework_attribute(id: u32) -> Option<&'static str> {
+    Some(match id {
+        0x0101_0003 => "name",
+        0x0101_0006 => "permission",
+        0x0101_0008 => "readPermission",
+        0x0101_000F => "debuggable",
+        0x0101_0010 => "exported",
+        0x0101_020C => "minSdkVersion",
+        0x0101_021B => "versionCode",
+        0x0101_021C => "versionName",
+        0x0101_0270 => "targetSdkVersion",
+        0x0101_0280 => "allowBackup",
+        _ => return None
+    })
+}
+
+/* AXML */
+
+// Parses a binary XML document into its element tree; the second value describes where
+// parsing stopped early, in which case the tree holds everything read before that point
+fn parse_axml(data: &[u8]) -> (Option<XmlElement>, Option<String>) {
+    let mut strings: Vec<String> = Vec::new();
+    let mut resource_ids: Vec<u32> = Vec::new();
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut root = None;
+    let mut problem = None;
+
+    if le16(data, 0) != Some(0x0003) {
+        return (None, Some("missing binary XML header".to_string()));
+    }
+
+    let mut offset = le16(data, 2).unwrap_or(8) as usize;
+
+    while offset + 8 <= data.len() {
+        let kind = le16(data, offset).unwrap_or(0);
+        let header_size = le16(data, offset + 2).unwrap_or(0) as usize;
+        let size = le32(data, offset + 4).unwrap_or(0) as usize;
+
+        let chunk = match data.get(offset..offset.saturating_add(size)) {
+            Some(chunk) if size >= 8 && header_size >= 8 && header_size <= size => chunk,
+            _ => {
+                problem = Some(format!("invalid chunk at offset {}", offset));
+                break;
+            }
+        };
+
+        match kind {
+            0x0001 => match string_pool(chunk) {
+                Some(pool) => strings = pool,
+                None => {
+                    problem = Some("unreadable string pool".to_string());
+                    break;
+                }
+            },
+            0x0180 => resource_ids = chunk[header_size..].chunks_exact(4).map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]])).collect(),
+            0x0102 => {
+                if stack.len() >= MAX_DEPTH {
+                    problem = Some("elements nested too deeply".to_string());
+                    break;
+                }
+                match start_element(chunk, header_size, &strings, &resource_ids) {
+                    Some(element) => stack.push(element),
+                    None => {
+                        problem = Some(format!("unreadable element at offset {}", offset));
+                        break;
+                    }
+                }
+            }
+            0x0103 => {
+                if let Some(element) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => root = root.or(Some(element))
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        offset += size;
+    }
+
+    // Close whatever a truncated document left open
+    while let Some(element) = stack.pop() {
+        problem = problem.or_else(|| Some("document ends inside an element".to_string()));
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => root = root.or(Some(element))
+        }
+    }
+
+    (root, problem)
+}
+
+fn string_pool(chunk: &[u8]) -> Option<Vec<String>> {
+    let header_size = le16(chunk, 2)? as usize;
+    let count = le32(chunk, 8)? as usize;
+    let utf8 = le32(chunk, 16)? & 0x100 != 0;
+    let strings_start = le32(chunk, 20)? as usize;
+
+    if count > chunk.len() / 4 {
+        return None;
+    }
+
+    let mut strings = Vec::with_capacity(count);
+    for index in 0..count {
+        let offset = strings_start + le32(chunk, header_size + index * 4)? as usize;
+        strings.push(if utf8 { pool_utf8(chunk, offset) } else { pool_utf16(chunk, offset) }.unwrap_or_default());
+    }
+
+    Some(strings)
+}
+
+// UTF-8 entries: character count then byte count, each one or two bytes long
+fn pool_utf8(chunk: &[u8], offset: usize) -> Option<String> {
+    let (_, offset) = utf8_length(chunk, offset)?;
+    let (length, offset) = utf8_length(chunk, offset)?;
+    chunk.get(offset..offset + length).map(|bytes| String::from_utf8_lossy(bytes).to_string())
+}
+
+fn utf8_length(chunk: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let first = *chunk.get(offset)? as usize;
+    if first & 0x80 != 0 {
+        Some((((first & 0x7F) << 8) | *chunk.get(offset + 1)? as usize, offset + 2))
+    } else {
+        Some((first, offset + 1))
+    }
+}
+
+// UTF-16 entries: unit count (one or two u16s) then the units
+fn pool_utf16(chunk: &[u8], offset: usize) -> Option<String> {
+    let first = le16(chunk, offset)? as usize;
+    let (length, start) = if first & 0x8000 != 0 {
+        (((first & 0x7FFF) << 16) | le16(chunk, offset + 2)? as usize, offset + 4)
+    } else {
+        (first, offset + 2)
+    };
+
+    let bytes = chunk.get(start..start + length * 2)?;
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn start_element(chunk: &[u8], header_size: usize, strings: &[String], resource_ids: &[u32]) -> Option<XmlElement> {
+    let body = chunk.get(header_size..)?;
+    let name = strings.get(le32(body, 4)? as usize)?.clone();
+    let attribute_start = le16(body, 8)? as usize;
+    let attribute_size = le16(body, 10)? as usize;
+    let attribute_count = le16(body, 12)? as usize;
+
+    let mut element = XmlElement { name, ..XmlElement::default() };
+
+    for index in 0..attribute_count {
+        let attribute = body.get(attribute_start + index * attribute_size.max(20)..)?;
+        let name_index = le32(attribute, 4)? as usize;
+        let raw_value = le32(attribute, 8)?;
+        let data_type = *attribute.get(15)?;
+        let data = le32(attribute, 16)?;
+
+        // Prefer the framework ID so renamed attributes still resolve
+        let name = resource_ids.get(name_index)
+            .and_then(|id| framework_attribute(*id))
+            .map(str::to_string)
+            .or_else(|| strings.get(name_index).cloned())
+            .unwrap_or_default();
+
+        let value = match data_type {
+            0x03 => strings.get(data as usize).or_else(|| strings.get(raw_value as usize)).cloned().unwrap_or_default(),
+            0x01 => format!("@0x{:08x}", data),
+            0x10 => (data as i32).to_string(),
+            0x11 => format!("0x{:x}", data),
+            0x12 => (data != 0).to_string(),
+            _ => strings.get(raw_value as usize).cloned().unwrap_or_else(|| format!("0x{:x}", data))
+        };
+
+        element.attributes.insert(name, value);
+    }
+
+    Some(element)
+}
+
+/* Signatures */
+
+fn signatures(archive: &ZipArchive, report: &mut ApkReport) {
+    // v1 (JAR signing): PKCS#7 blocks next to the signature files
+    for entry in archive.entries() {
+        let upper = entry.name.to_ascii_uppercase();
+        if !upper.starts_with("META-INF/") || !(upper.ends_with(".RSA") || upper.ends_with(".DSA") || upper.ends_with(".EC")) {
+            continue;
+        }
+
+        if !report.signature_schemes.iter().any(|scheme| scheme == "v1") {
+            report.signature_schemes.push("v1".to_string());
+        }
+
+        match archive.read_entry(entry, MAX_ENTRY).and_then(|block| {
+            pkcs7_certificates(&block)?.into_iter().map(parse_certificate).collect::<Result<Vec<_>, _>>()
+        }) {
+            Ok(certificates) => add_certificates(report, certificates),
+            Err(e) => report.notes.push(format!("Unable to read {}: {}", entry.name, e))
+        }
+    }
+
+    signing_block(archive, report);
+}
+
+fn add_certificates(report: &mut ApkReport, certificates: Vec<CertificateInfo>) {
+    for certificate in certificates {
+        if !report.certificates.contains(&certificate) {
+            report.certificates.push(certificate);
+        }
+    }
+}
+
+// APK Signing Block (v2/v3): sits directly before the central directory, framed by its size
+// and the "APK Sig Block 42" magic
+fn signing_block(archive: &ZipArchive, report: &mut ApkReport) {
+    let data = archive.bytes();
+    let directory = archive.central_directory();
+
+    if directory < 32 || data.get(directory - 16..directory) != Some(b"APK Sig Block 42".as_slice()) {
+        return;
+    }
+
+    let size = le64(data, directory - 24).unwrap_or(0) as usize;
+    let start = match directory.checked_sub(size + 8) {
+        Some(start) => start,
+        None => {
+            report.notes.push("APK Signing Block size is invalid".to_string());
+            return;
+        }
+    };
+
+    let mut position = start + 8;
+    while position + 12 <= directory - 24 {
+        let length = le64(data, position).unwrap_or(0) as usize;
+        let id = le32(data, position + 8).unwrap_or(0);
+        let value = match data.get(position + 12..(position + 8).saturating_add(length)) {
+            Some(value) if length >= 4 => value,
+            _ => break
+        };
+
+        let scheme = match id {
+            0x7109_871A => Some("v2"),
+            0xF053_68C0 => Some("v3"),
+            0x1B93_AD61 => Some("v3.1"),
+            _ => None
+        };
+
+        if let Some(scheme) = scheme {
+            report.signature_schemes.push(scheme.to_string());
+            match scheme_certificates(value) {
+                Some(certificates) => add_certificates(report, certificates),
+                None => report.notes.push(format!("Unable to parse the {} signature block", scheme))
+            }
+        }
+
+        position += 8 + length;
+    }
+}
+
+// Length-prefixed sequence of u32-prefixed items
+fn prefixed(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut items = Vec::new();
+    let mut position = 0;
+
+    while position < data.len() {
+        let length = le32(data, position)? as usize;
+        items.push(data.get(position + 4..position + 4 + length)?);
+        position += 4 + length;
+    }
+
+    Some(items)
+}
+
+// signers -> signer -> signed data -> (digests, certificates). v3 signers and signed data carry
+// unprefixed SDK bounds after the leading items, so only those are read.
+fn scheme_certificates(value: &[u8]) -> Option<Vec<CertificateInfo>> {
+    let mut certificates = Vec::new();
+
+    for signers in prefixed(value)? {
+        for signer in prefixed(signers)? {
+            let signed_data = leading_items(signer, 1)?[0];
+            let sections = leading_items(signed_data, 2)?;
+
+            for der in prefixed(sections[1])? {
+                certificates.push(parse_certificate(der).ok()?);
+            }
+        }
+    }
+
+    Some(certificates)
+}
+
+// The first `count` u32-prefixed items, ignoring whatever follows them
+fn leading_items(data: &[u8], count: usize) -> Option<Vec<&[u8]>> {
+    let mut items = Vec::new();
+    let mut position = 0;
+
+    for _ in 0..count {
+        let length = le32(data, position)? as usize;
+        items.push(data.get(position + 4..position + 4 + length)?);
+        position += 4 + length;
+    }
+
+    Some(items)
+}
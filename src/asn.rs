@@ -0,0 +1,581 @@
+/*
+    Origin ASN, AS name and announced prefix of IP addresses
+
+    Online lookups use Team Cymru's DNS interface, so no API key is needed: a TXT query for
+    the reversed address under origin.asn.cymru.com (origin6 for IPv6) returns
+    "ASN | prefix | country | registry | allocated", and one under AS<n>.asn.cymru.com the AS
+    name. Bulk lookups run on a bounded pool of threads, reuse an answer for every later
+    address inside the same announced prefix, and ask for each AS name once.
+
+    Where DNS is filtered but port 43 is not, Team Cymru's WHOIS service answers the same in
+    its verbose format, "AS | IP | BGP Prefix | CC | Registry | Allocated | AS Name", AS
+    name included. Any number of addresses go in one connection between "begin" and "end"
+    lines; the answer starts with a header line (and a "Bulk mode" line) and pads its columns
+    with spaces, and rows are matched to the addresses asked by their IP column.
+
+    Offline lookups use a CAIDA/RouteViews pfx2as file ("prefix<TAB>length<TAB>asn", where a
+    multi-origin prefix lists "asn_asn" and an AS set "asn,asn") loaded into a binary trie
+    per address family, so a lookup is one longest-prefix walk. pfx2as files carry no AS
+    names. Private, loopback, documentation and other bogon addresses are answered locally in
+    both modes.
+*/
+
+use std::{
+    fmt,
+    collections::{BTreeMap, BTreeSet},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    sync::{Mutex, atomic::{AtomicUsize, Ordering}},
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    bogon::{bogon_description, bogon_range},
+    cidr::{Cidr, PrefixTrie},
+    config,
+    dns::{DnsQuery, DnsRdata, DnsType, default_dns_server, dns_server_addr, reverse_name},
+    endpoints::service_endpoints,
+    stats::{self, Counter},
+    text::{read_text_lines, TextLines},
+    trailing_options,
+    whois::whois_query
+};
+
+/// Options for `mercy_asn_lookup`
+#[derive(Debug, Clone)]
+pub struct AsnOptions {
+    /// DNS server (default: the system resolver's first nameserver)
+    pub server: Option<SocketAddr>,
+    pub timeout: Duration,
+    /// Also look up the name of each AS
+    pub names: bool,
+    /// Maximum number of queries in flight
+    pub concurrency: usize
+}
+
+impl Default for AsnOptions {
+    fn default() -> Self {
+        AsnOptions {
+            server: None,
+            timeout: config::read_timeout(Duration::from_secs(3)),
+            names: true,
+            concurrency: 16
+        }
+    }
+}
+
+/// Routing origin of one address
+#[derive(Debug, Clone, Serialize)]
+pub struct AsnInfo {
+    pub ip: IpAddr,
+    /// First origin AS of the announced prefix
+    pub asn: Option<u32>,
+    /// Every origin AS (more than one for multi-origin prefixes)
+    pub origins: Vec<u32>,
+    pub name: Option<String>,
+    /// Most specific announced prefix covering the address
+    pub prefix: Option<String>,
+    pub country: Option<String>,
+    pub registry: Option<String>,
+    pub allocated: Option<String>,
+    /// "cymru", "cymru-whois", "pfx2as" or "special" (answered locally for bogon addresses)
+    pub source: String,
+    pub note: Option<String>
+}
+
+impl AsnInfo {
+    fn new(ip: IpAddr, source: &str) -> AsnInfo {
+        AsnInfo { ip, asn: None, origins: Vec::new(), name: None, prefix: None, country: None, registry: None, allocated: None, source: source.to_string(), note: None }
+    }
+}
+
+impl fmt::Display for AsnInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(_) = self.asn else {
+            return write!(f, "{} | - | {}", self.ip, self.note.as_deref().unwrap_or("no origin found"));
+        };
+
+        let origins: Vec<String> = self.origins.iter().map(|asn| format!("AS{}", asn)).collect();
+        write!(f, "{} | {} | {}", self.ip, origins.join(" "), self.prefix.as_deref().unwrap_or("-"))?;
+        if let Some(country) = &self.country {
+            write!(f, " | {}", country)?;
+        }
+        if let Some(registry) = &self.registry {
+            write!(f, " | {}", registry)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " | {}", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ASN lookups for a set of addresses
+#[derive(Debug, Clone, Serialize)]
+pub struct AsnReport {
+    pub results: Vec<AsnInfo>,
+    /// DNS queries or WHOIS connections made (0 offline)
+    pub queries: usize,
+    /// Inputs that are not IP addresses
+    pub skipped: Vec<String>
+}
+
+impl fmt::Display for AsnReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{}", result)?;
+        }
+
+        for skipped in &self.skipped {
+            writeln!(f, "Skipped (not an IP address): {}", skipped)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A prefix-to-ASN table (CAIDA/RouteViews pfx2as) for offline lookups
+#[derive(Debug, Clone, Default)]
+pub struct Pfx2As {
+    // Values index the origin lists
+    trie: PrefixTrie,
+    origins: Vec<Vec<u32>>
+}
+
+impl Pfx2As {
+    /// Reads "prefix<TAB>length<TAB>asn" lines (or "prefix/length asn"); lines that do not parse are skipped
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Pfx2As, String> {
+        let path = path.as_ref();
+        let lines = TextLines::open(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let mut table = Pfx2As::default();
+
+        for line in lines {
+            let line = line.map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+            table.insert_line(&line);
+        }
+
+        if table.origins.is_empty() {
+            return Err(format!("No prefixes found in {}", path.display()));
+        }
+
+        Ok(table)
+    }
+
+    /// Adds one announced prefix with its origin ASes
+    pub fn insert(&mut self, prefix: Cidr, origins: Vec<u32>) {
+        let value = self.origins.len() as u32;
+        self.origins.push(origins);
+        self.trie.insert(&prefix, value);
+    }
+
+    /// Number of prefixes loaded
+    pub fn len(&self) -> usize {
+        self.origins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.origins.is_empty()
+    }
+
+    /// Most specific prefix covering the address and its origin ASes
+    pub fn lookup(&self, ip: IpAddr) -> Option<(Cidr, &[u32])> {
+        let (length, value) = self.trie.longest(ip)?;
+        let prefix = Cidr::new(ip, length).ok()?;
+
+        Some((prefix, self.origins.get(value as usize)?.as_slice()))
+    }
+
+    fn insert_line(&mut self, line: &str) {
+        let mut fields = line.split_whitespace();
+        let (Some(first), Some(second)) = (fields.next(), fields.next()) else { return };
+
+        let (prefix, origins) = if first.contains('/') {
+            (first.parse::<Cidr>(), second)
+        } else {
+            let Some(origins) = fields.next() else { return };
+            (format!("{}/{}", first, second).parse::<Cidr>(), origins)
+        };
+
+        let origins: Vec<u32> = origins.split(|c: char| !c.is_ascii_digit()).filter_map(|asn| asn.parse().ok()).collect();
+        if let (Ok(prefix), false) = (prefix, origins.is_empty()) {
+            self.insert(prefix, origins);
+        }
+    }
+}
+
+/// Origin ASN, AS name and announced prefix of each address from Team Cymru's DNS service
+pub fn mercy_asn_lookup(ips: &[IpAddr], options: &AsnOptions) -> AsnReport {
+    let server = options.server.unwrap_or_else(default_dns_server);
+    let queries = AtomicUsize::new(0);
+
+    // Distinct public addresses in order, so neighbours inside one prefix follow each other
+    let mut results: Vec<AsnInfo> = ips.iter().map(|ip| special(*ip).unwrap_or_else(|| AsnInfo::new(*ip, "cymru"))).collect();
+    let pending: BTreeSet<IpAddr> = results.iter().filter(|result| result.source == "cymru").map(|result| result.ip).collect();
+
+    let cache: Mutex<Vec<(Cidr, AsnInfo)>> = Mutex::new(Vec::new());
+    let answers: Mutex<BTreeMap<IpAddr, AsnInfo>> = Mutex::new(BTreeMap::new());
+    let work = Mutex::new(pending.iter());
+
+    pool(options.concurrency, pending.len(), || while let Some(ip) = work.lock().ok().and_then(|mut work| work.next().copied()) {
+        let cached = cache.lock().ok().and_then(|cache| cache.iter().find(|(prefix, _)| prefix.contains(ip)).map(|(_, info)| info.clone()));
+        let info = match cached {
+            Some(info) => {
+                stats::count(Counter::CacheHits, 1);
+                AsnInfo { ip, ..info }
+            },
+            None => {
+                stats::count(Counter::CacheMisses, 1);
+                queries.fetch_add(1, Ordering::Relaxed);
+                let info = origin(ip, server, options.timeout);
+                if let (Some(prefix), Ok(mut cache)) = (info.prefix.as_ref().and_then(|prefix| prefix.parse::<Cidr>().ok()), cache.lock()) {
+                    cache.push((prefix, info.clone()));
+                }
+                info
+            }
+        };
+
+        if let Ok(mut answers) = answers.lock() {
+            answers.insert(ip, info);
+        }
+    });
+
+    let answers = answers.into_inner().unwrap_or_default();
+    let mut names: BTreeMap<u32, Option<String>> = BTreeMap::new();
+
+    if options.names {
+        let asns: BTreeSet<u32> = answers.values().filter_map(|info| info.asn).collect();
+        let found = Mutex::new(BTreeMap::new());
+        let work = Mutex::new(asns.iter());
+
+        pool(options.concurrency, asns.len(), || while let Some(asn) = work.lock().ok().and_then(|mut work| work.next().copied()) {
+            queries.fetch_add(1, Ordering::Relaxed);
+            let name = as_name(asn, server, options.timeout);
+            if let Ok(mut found) = found.lock() {
+                found.insert(asn, name);
+            }
+        });
+
+        names = found.into_inner().unwrap_or_default();
+    }
+
+    for result in results.iter_mut().filter(|result| result.source == "cymru") {
+        if let Some(info) = answers.get(&result.ip) {
+            *result = info.clone();
+            result.name = info.asn.and_then(|asn| names.get(&asn).cloned().flatten());
+        }
+    }
+
+    AsnReport { results, queries: queries.into_inner(), skipped: Vec::new() }
+}
+
+/// Origin ASN and announced prefix of each address from a pfx2as table, without any network access
+pub fn mercy_asn_offline(ips: &[IpAddr], table: &Pfx2As) -> AsnReport {
+    let results = ips.iter().map(|ip| special(*ip).unwrap_or_else(|| {
+        let mut info = AsnInfo::new(*ip, "pfx2as");
+        match table.lookup(*ip) {
+            Some((prefix, origins)) => {
+                info.asn = origins.first().copied();
+                info.origins = origins.to_vec();
+                info.prefix = Some(prefix.to_string());
+            },
+            None => info.note = Some("not announced in the table".to_string())
+        }
+        info
+    })).collect();
+
+    AsnReport { results, queries: 0, skipped: Vec::new() }
+}
+
+/// Origin ASN, AS name, BGP prefix and registry of each address from Team Cymru's WHOIS service, all of them in one connection
+pub fn mercy_asn_whois(ips: &[IpAddr]) -> Result<AsnReport, String> {
+    let mut results: Vec<AsnInfo> = ips.iter().map(|ip| reserved(*ip).unwrap_or_else(|| AsnInfo::new(*ip, "cymru-whois"))).collect();
+    let pending: BTreeSet<IpAddr> = results.iter().filter(|result| result.source == "cymru-whois").map(|result| result.ip).collect();
+    if pending.is_empty() {
+        return Ok(AsnReport { results, queries: 0, skipped: Vec::new() });
+    }
+
+    // "-v" asks for the verbose columns of one address; bulk mode takes "verbose" on a line of its own
+    let query = match pending.len() {
+        1 => format!("-v {}", pending.iter().next().map(IpAddr::to_string).unwrap_or_default()),
+        _ => format!("begin\nverbose\n{}\nend", pending.iter().map(IpAddr::to_string).collect::<Vec<String>>().join("\n"))
+    };
+    let server = service_endpoints().asn_whois;
+    let response = whois_query(&server, &query).map_err(|e| match pending.len() {
+        1 => format!("ASN lookup of {} failed: {}", pending.iter().next().map(IpAddr::to_string).unwrap_or_default(), e),
+        count => format!("ASN lookup of {} addresses failed: {}", count, e)
+    })?;
+
+    let rows = cymru_whois_rows(&response);
+    for result in results.iter_mut().filter(|result| result.source == "cymru-whois") {
+        match rows.get(&result.ip) {
+            Some(row) => *result = row.clone(),
+            None => result.note = Some("not in Team Cymru's answer".to_string())
+        }
+    }
+
+    Ok(AsnReport { results, queries: 1, skipped: Vec::new() })
+}
+
+/// The rows of a Team Cymru verbose WHOIS answer by address; the header, "Bulk mode" and error lines are skipped
+pub(crate) fn cymru_whois_rows(response: &str) -> BTreeMap<IpAddr, AsnInfo> {
+    let mut rows: BTreeMap<IpAddr, AsnInfo> = BTreeMap::new();
+
+    for line in response.lines() {
+        let columns: Vec<String> = line.splitn(7, '|').map(|column| column.trim().to_string()).collect();
+        let Some(ip) = columns.get(1).and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+            continue;
+        };
+        let column = |index: usize| columns.get(index).filter(|value| !value.is_empty() && !value.eq_ignore_ascii_case("NA")).cloned();
+
+        let info = rows.entry(ip).or_insert_with(|| AsnInfo::new(ip, "cymru-whois"));
+        // A multi-origin prefix is one row per origin AS
+        match columns[0].parse::<u32>() {
+            Ok(asn) if !info.origins.contains(&asn) => info.origins.push(asn),
+            Ok(_) => {},
+            Err(_) if info.origins.is_empty() => info.note = Some("not announced".to_string()),
+            Err(_) => {}
+        }
+        info.asn = info.origins.first().copied();
+        if info.asn.is_some() {
+            info.note = None;
+            info.prefix = info.prefix.take().or_else(|| column(2));
+            info.country = info.country.take().or_else(|| column(3));
+            info.registry = info.registry.take().or_else(|| column(4));
+            info.allocated = info.allocated.take().or_else(|| column(5));
+            info.name = info.name.take().or_else(|| column(6));
+        }
+    }
+
+    rows
+}
+
+// Private and reserved addresses are answered without asking anyone
+fn reserved(ip: IpAddr) -> Option<AsnInfo> {
+    let (category, description) = bogon_range(ip)?;
+    let mut info = AsnInfo::new(ip, "special");
+    info.note = Some(match category {
+        "private" => "Private address \u{2014} no ASN".to_string(),
+        _ => format!("Reserved address \u{2014} no ASN ({})", description)
+    });
+    Some(info)
+}
+
+// Runs `work` on up to `concurrency` threads, no more than there are items
+fn pool<F: Fn() + Sync>(concurrency: usize, items: usize, work: F) {
+    let workers = concurrency.max(1).min(items);
+    let work = &work;
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(work);
+        }
+    });
+}
+
+// Bogons never appear in the global routing table
+fn special(ip: IpAddr) -> Option<AsnInfo> {
+    let description = bogon_description(ip)?;
+    let mut info = AsnInfo::new(ip, "special");
+    info.note = Some(format!("{} address, not routed publicly", description));
+    Some(info)
+}
+
+// origin.asn.cymru.com answer for one address; the most specific prefix wins when several are returned
+fn origin(ip: IpAddr, server: SocketAddr, timeout: Duration) -> AsnInfo {
+    let reversed = reverse_name(ip);
+    let name = match ip {
+        IpAddr::V4(_) => format!("{}.origin.asn.cymru.com", reversed.trim_end_matches(".in-addr.arpa")),
+        IpAddr::V6(_) => format!("{}.origin6.asn.cymru.com", reversed.trim_end_matches(".ip6.arpa"))
+    };
+    let mut info = AsnInfo::new(ip, "cymru");
+
+    let response = match DnsQuery::new(&name, DnsType::Txt).send(server, timeout) {
+        Ok(response) => response,
+        Err(e) => {
+            info.note = Some(format!("lookup failed: {}", e));
+            return info;
+        }
+    };
+
+    let best = response.answers.iter()
+        .filter_map(|record| match &record.rdata {
+            DnsRdata::Txt(parts) => Some(fields(&parts.concat())),
+            _ => None
+        })
+        .filter(|fields| fields.len() >= 2)
+        .max_by_key(|fields| fields[1].parse::<Cidr>().map(|prefix| prefix.prefix()).unwrap_or(0));
+
+    match best {
+        Some(fields) => {
+            info.origins = fields[0].split_whitespace().filter_map(|asn| asn.parse().ok()).collect();
+            info.asn = info.origins.first().copied();
+            info.prefix = Some(fields[1].clone());
+            info.country = fields.get(2).filter(|value| !value.is_empty()).cloned();
+            info.registry = fields.get(3).filter(|value| !value.is_empty()).cloned();
+            info.allocated = fields.get(4).filter(|value| !value.is_empty()).cloned();
+        },
+        None if response.rcode == 0 || response.rcode == 3 => info.note = Some("not announced".to_string()),
+        None => info.note = Some(format!("lookup failed: {}", response.rcode_name()))
+    }
+
+    info
+}
+
+// Name of an AS from AS<n>.asn.cymru.com ("asn | country | registry | allocated | name")
+fn as_name(asn: u32, server: SocketAddr, timeout: Duration) -> Option<String> {
+    let response = DnsQuery::new(&format!("AS{}.asn.cymru.com", asn), DnsType::Txt).send(server, timeout).ok()?;
+
+    response.answers.iter().find_map(|record| match &record.rdata {
+        DnsRdata::Txt(parts) => fields(&parts.concat()).get(4).filter(|name| !name.is_empty()).cloned(),
+        _ => None
+    })
+}
+
+fn fields(text: &str) -> Vec<String> {
+    text.split('|').map(|field| field.trim().to_string()).collect()
+}
+
+// Parses "ip[,ip...]" or the path of a file with one address per line, then
+// "[pfx2as=routeviews.pfx2as] [server=1.1.1.1] [timeout=3] [names=false] [concurrency=16]"
+pub(crate) fn asn_from_args(input: &str) -> Result<AsnReport, String> {
+    let (targets, pairs) = trailing_options(input, &["pfx2as", "server", "timeout", "names", "concurrency"]);
+    let mut options = AsnOptions::default();
+    let mut table = None;
+
+    for (key, value) in pairs {
+        match key {
+            "pfx2as" => table = Some(Pfx2As::load(value)?),
+            "server" => options.server = Some(dns_server_addr(value)?),
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            "names" => options.names = value != "false",
+            _ => options.concurrency = value.parse().map_err(|_| format!("Invalid concurrency '{}'", value))?
+        }
+    }
+
+    let targets = targets.trim();
+    if targets.is_empty() {
+        return Err("No IP address specified".to_string());
+    }
+
+    let entries: Vec<String> = if targets.parse::<IpAddr>().is_err() && !targets.contains(',') && Path::new(targets).is_file() {
+        read_text_lines(targets).map_err(|e| format!("Unable to read {}: {}", targets, e))?
+            .into_iter()
+            .map(|line| line.split('#').next().unwrap_or("").trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    } else {
+        targets.split(',').map(|target| target.trim().to_string()).filter(|target| !target.is_empty()).collect()
+    };
+
+    let mut ips = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in entries {
+        match entry.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => ips.push(ip),
+            Err(_) => skipped.push(entry)
+        }
+    }
+
+    let mut report = match &table {
+        Some(table) => mercy_asn_offline(&ips, table),
+        None => mercy_asn_lookup(&ips, &options)
+    };
+    report.skipped = skipped;
+
+    Ok(report)
+}
+
+// Parses one IP address for `asn_lookup`
+pub(crate) fn asn_lookup_from_args(input: &str) -> Result<AsnInfo, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("No IP address specified".to_string());
+    }
+    let ip = input.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().map_err(|_| format!("Invalid IP address '{}'", input))?;
+
+    let report = mercy_asn_whois(&[ip])?;
+    report.results.into_iter().next().ok_or_else(|| format!("No answer for {}", ip))
+}
+
+// Parses "ip,ip,..." for `asn_bulk`; entries that are not addresses are reported as skipped
+pub(crate) fn asn_bulk_from_args(input: &str) -> Result<AsnReport, String> {
+    let mut ips = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in input.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        match entry.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            Ok(ip) => ips.push(ip),
+            Err(_) => skipped.push(entry.to_string())
+        }
+    }
+    if ips.is_empty() {
+        return Err(match skipped.is_empty() {
+            true => "No IP address specified".to_string(),
+            false => format!("No IP address among '{}'", skipped.join(", "))
+        });
+    }
+
+    let mut report = mercy_asn_whois(&ips)?;
+    report.skipped = skipped;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bulk answer as whois.cymru.com sends it: banner, header, padded columns, a multi-origin
+    // prefix as two rows, an unannounced address and the error line for a query it could not read
+    const CYMRU_BULK: &str = "Bulk mode; whois.cymru.com [2024-03-06 10:12:44 +0000]\r\n\
+AS      | IP                   | BGP Prefix          | CC | Registry | Allocated  | AS Name\r\n\
+13335   | 1.1.1.1              | 1.1.1.0/24          | AU | apnic    | 2011-08-11 | CLOUDFLARENET, US\r\n\
+15169   | 2001:4860:4860::8888 | 2001:4860::/32      | US | arin     | 2005-03-14 | GOOGLE, US\r\n\
+64500   | 192.0.2.77           | 192.0.2.0/24        | ZZ | ripencc  |            | EXAMPLE | PIPE NET, ZZ\r\n\
+64501   | 192.0.2.77           | 192.0.2.0/24        | ZZ | ripencc  |            | SECOND ORIGIN, ZZ\r\n\
+NA      | 100.128.0.1          | NA                  |    |          |            | NA\r\n\
+Error: no ASN or IP match on line 7.\r\n";
+
+    fn row<'a>(rows: &'a BTreeMap<IpAddr, AsnInfo>, ip: &str) -> &'a AsnInfo {
+        rows.get(&ip.parse().expect("address")).unwrap_or_else(|| panic!("no row for {}", ip))
+    }
+
+    #[test]
+    fn cymru_rows_parse_past_the_header_and_padding() {
+        let rows = cymru_whois_rows(CYMRU_BULK);
+        assert_eq!(rows.len(), 4);
+
+        let cloudflare = row(&rows, "1.1.1.1");
+        assert_eq!((cloudflare.asn, cloudflare.origins.as_slice()), (Some(13335), &[13335][..]));
+        assert_eq!(cloudflare.prefix.as_deref(), Some("1.1.1.0/24"));
+        assert_eq!((cloudflare.country.as_deref(), cloudflare.registry.as_deref(), cloudflare.allocated.as_deref()), (Some("AU"), Some("apnic"), Some("2011-08-11")));
+        assert_eq!(cloudflare.name.as_deref(), Some("CLOUDFLARENET, US"));
+        assert_eq!(cloudflare.source, "cymru-whois");
+        assert_eq!(cloudflare.to_string(), "1.1.1.1 | AS13335 | 1.1.1.0/24 | AU | apnic | CLOUDFLARENET, US");
+
+        assert_eq!(row(&rows, "2001:4860:4860::8888").prefix.as_deref(), Some("2001:4860::/32"));
+
+        // Both origins kept, the first one's details; a "|" inside the AS name stays in the name
+        let shared = row(&rows, "192.0.2.77");
+        assert_eq!((shared.asn, shared.origins.as_slice()), (Some(64500), &[64500, 64501][..]));
+        assert_eq!(shared.name.as_deref(), Some("EXAMPLE | PIPE NET, ZZ"));
+        assert_eq!(shared.allocated, None);
+
+        let unannounced = row(&rows, "100.128.0.1");
+        assert_eq!((unannounced.asn, unannounced.prefix.as_deref(), unannounced.name.as_deref()), (None, None, None));
+        assert_eq!(unannounced.note.as_deref(), Some("not announced"));
+    }
+
+    #[test]
+    fn cymru_rows_of_nothing_useful() {
+        assert!(cymru_whois_rows("").is_empty());
+        assert!(cymru_whois_rows("Bulk mode; whois.cymru.com [2024-03-06 10:12:44 +0000]\nError: no ASN or IP match on line 2.\n").is_empty());
+        assert!(cymru_whois_rows("AS | IP | BGP Prefix\n13335 | not-an-ip | 1.1.1.0/24\n|||\n").is_empty());
+
+        // A short row still gives what it has
+        let rows = cymru_whois_rows("13335 | 1.0.0.1");
+        assert_eq!(row(&rows, "1.0.0.1").asn, Some(13335));
+        assert_eq!(row(&rows, "1.0.0.1").prefix, None);
+    }
+}
@@ -0,0 +1,224 @@
+/*
+    Service banners read from a TCP port
+
+    Many services say who they are as soon as the handshake completes (SSH, FTP, SMTP, POP3,
+    IMAP, MySQL, VNC); others only answer once spoken to. The automatic probe therefore sends
+    nothing to the ports of services that talk first, "HEAD / HTTP/1.0" to the HTTP ports and
+    a bare CRLF everywhere else, which is enough to make most line-based services print a
+    greeting or an error that names them.
+
+    The first read waits for the full timeout; once data arrives, reading stops after a short
+    quiet period or at MAX_BANNER bytes, so a chatty service costs little more than its
+    greeting. The banner is returned as text with every byte outside printable ASCII written
+    as \xNN (and "\" itself as \x5c, so the escaping can be undone), keeping line breaks, so
+    a binary protocol's answer cannot move the cursor or change colours in a terminal.
+
+    A port that refuses the connection, one that accepts it but stays silent, one that resets
+    it and one that closes it without a word fail with different messages, since each says
+    something different about what is listening.
+*/
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{checks::resolve_target, config, trailing_options};
+
+// Banners are cut at this many bytes
+const MAX_BANNER: usize = 4096;
+
+// Quiet period after the first data after which the banner is considered complete
+const BANNER_IDLE: Duration = Duration::from_millis(500);
+
+// Services that greet the client before it sends anything
+const TALKS_FIRST: &[u16] = &[21, 22, 25, 110, 143, 587, 3306, 5900];
+
+// Plain-HTTP ports, asked with a HEAD request
+const HTTP_PORTS: &[u16] = &[80, 8000, 8008, 8080];
+
+/// What is sent after connecting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BannerProbe {
+    /// Chosen from the port: none, http or crlf
+    Auto,
+    /// Nothing; only what the service volunteers is read
+    None,
+    /// A bare CRLF
+    Crlf,
+    /// "HEAD / HTTP/1.0" and a blank line
+    Http
+}
+
+impl BannerProbe {
+    fn parse(name: &str) -> Result<BannerProbe, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BannerProbe::Auto),
+            "none" => Ok(BannerProbe::None),
+            "crlf" => Ok(BannerProbe::Crlf),
+            "http" => Ok(BannerProbe::Http),
+            _ => Err(format!("Invalid probe '{}' (expected auto, none, crlf or http)", name))
+        }
+    }
+
+    // The probe used on `port`, with Auto resolved
+    fn for_port(self, port: u16) -> BannerProbe {
+        match self {
+            BannerProbe::Auto if TALKS_FIRST.contains(&port) => BannerProbe::None,
+            BannerProbe::Auto if HTTP_PORTS.contains(&port) => BannerProbe::Http,
+            BannerProbe::Auto => BannerProbe::Crlf,
+            probe => probe
+        }
+    }
+
+    fn bytes(self) -> &'static [u8] {
+        match self {
+            BannerProbe::Crlf => b"\r\n",
+            BannerProbe::Http => b"HEAD / HTTP/1.0\r\n\r\n",
+            _ => b""
+        }
+    }
+}
+
+impl fmt::Display for BannerProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BannerProbe::Auto => write!(f, "auto"),
+            BannerProbe::None => write!(f, "none"),
+            BannerProbe::Crlf => write!(f, "crlf"),
+            BannerProbe::Http => write!(f, "http")
+        }
+    }
+}
+
+/// Options for `mercy_banner_grab`
+#[derive(Debug, Clone)]
+pub struct BannerOptions {
+    /// For the connect and for the first data to arrive
+    pub timeout: Duration,
+    pub probe: BannerProbe
+}
+
+impl Default for BannerOptions {
+    fn default() -> Self {
+        BannerOptions { timeout: Duration::from_secs(5), probe: BannerProbe::Auto }
+    }
+}
+
+/// What a service sent after the connection opened
+#[derive(Debug, Clone, Serialize)]
+pub struct BannerGrab {
+    pub address: SocketAddr,
+    /// The probe actually sent
+    pub probe: BannerProbe,
+    /// Bytes received
+    pub bytes: usize,
+    /// Whether reading stopped at the size limit rather than at a pause or the end of the stream
+    pub truncated: bool,
+    /// What was received, printable ASCII as is, line breaks kept and every other byte as \xNN
+    pub banner: String
+}
+
+impl fmt::Display for BannerGrab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Banner from {} ({} bytes{}, probe {}):", self.address, self.bytes, if self.truncated { ", truncated" } else { "" }, self.probe)?;
+        write!(f, "{}", self.banner.trim_end_matches('\n'))
+    }
+}
+
+/// Connects to "host:port", sends the probe and reads what the service answers
+pub fn mercy_banner_grab(target: &str, options: &BannerOptions) -> Result<BannerGrab, String> {
+    let address = resolve_target(target, 0)?;
+    if address.port() == 0 {
+        return Err(format!("Expected \"host:port\", got '{}'", target.trim()));
+    }
+
+    let connect_timeout = config::connect_timeout(options.timeout);
+    let mut stream = TcpStream::connect_timeout(&address, connect_timeout).map_err(|e| match e.kind() {
+        io::ErrorKind::ConnectionRefused => format!("Connection refused by {} (nothing listening)", address),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => format!("No answer from {} within {} s (filtered?)", address, connect_timeout.as_secs_f64()),
+        _ => format!("Unable to connect to {}: {}", address, e)
+    })?;
+
+    let timeout = config::read_timeout(options.timeout);
+    let probe = options.probe.for_port(address.port());
+    stream.set_read_timeout(Some(timeout)).map_err(|e| format!("Unable to set read timeout: {}", e))?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| format!("Unable to set write timeout: {}", e))?;
+    if probe != BannerProbe::None {
+        stream.write_all(probe.bytes()).map_err(|e| match e.kind() {
+            io::ErrorKind::ConnectionReset | io::ErrorKind::BrokenPipe => format!("{} reset the connection before the probe was sent", address),
+            _ => format!("Write failed: {}", e)
+        })?;
+    }
+
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let mut truncated = false;
+    loop {
+        match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => {
+                received.extend_from_slice(&buffer[..read]);
+                if received.len() >= MAX_BANNER {
+                    received.truncate(MAX_BANNER);
+                    truncated = true;
+                    break;
+                }
+                stream.set_read_timeout(Some(BANNER_IDLE)).map_err(|e| format!("Unable to set read timeout: {}", e))?;
+            },
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) if !received.is_empty() => break,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return Err(format!("Connected to {} but nothing was sent within {} s (probe {})", address, timeout.as_secs_f64(), probe));
+            },
+            Err(e) if matches!(e.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted) => {
+                return Err(format!("{} reset the connection without sending anything", address));
+            },
+            Err(e) => return Err(format!("Read failed: {}", e))
+        }
+    }
+
+    if received.is_empty() {
+        return Err(format!("{} closed the connection without sending anything (probe {})", address, probe));
+    }
+
+    Ok(BannerGrab { address, probe, bytes: received.len(), truncated, banner: escape_banner(&received) })
+}
+
+/// Printable ASCII as is, CRLF and LF as a line break, tab kept, "\" and every other byte as \xNN
+pub fn escape_banner(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\r' if bytes.get(index + 1) == Some(&b'\n') => {
+                text.push('\n');
+                index += 1;
+            },
+            b'\n' => text.push('\n'),
+            b'\t' => text.push('\t'),
+            byte @ 0x20..=0x7E if byte != b'\\' => text.push(byte as char),
+            byte => text.push_str(&format!("\\x{:02x}", byte))
+        }
+        index += 1;
+    }
+    text
+}
+
+// Parses "host:port [timeout=5] [probe=auto]"
+pub(crate) fn banner_grab_from_args(input: &str) -> Result<BannerGrab, String> {
+    let (target, pairs) = trailing_options(input, &["timeout", "probe"]);
+    let mut options = BannerOptions::default();
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.timeout = Duration::from_secs_f64(value.parse().ok().filter(|secs: &f64| *secs > 0.0).ok_or_else(|| format!("Invalid timeout '{}'", value))?),
+            _ => options.probe = BannerProbe::parse(value)?
+        }
+    }
+    mercy_banner_grab(&target, &options)
+}
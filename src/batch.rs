@@ -0,0 +1,159 @@
+/*
+    Batch forms of the dispatchers
+
+    `mercy_hash_batch` and `mercy_decode_batch` resolve the call once and run the digest or
+    decoder over every input, spreading a large batch across the CPU's threads.
+    `mercy_malicious_batch` keeps up to `BATCH_CONCURRENCY` lookups in flight (or the
+    concurrency of the current `MercyConfig`), each worker taking the next input as soon as
+    its lookup finishes. Either way there is one output per input, in input order, and an
+    input that fails gets its own error text without stopping the rest.
+
+    The `_to` forms take a `BatchOutput`: the outputs are also streamed to an `OutputSink` as
+    NDJSON records, one per input, and a summary is posted to a webhook once the batch
+    finishes, as the streaming and notifying calls of the string dispatchers do with "out="
+    and "notify=". The sink is opened before the first input is touched, so a collector that
+    is not there fails the batch at once rather than after all the work.
+*/
+
+use std::{
+    future::Future,
+    io::Write,
+    panic,
+    sync::{Arc, atomic::{AtomicUsize, Ordering}},
+    thread
+};
+
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+use crate::{
+    config,
+    notify::{NotifyFormat, notify_finished},
+    sink::{OutputSink, SinkOptions, SinkStats, SinkTarget}
+};
+
+/// Lookups a network batch runs at once unless the `MercyConfig` says otherwise
+pub const BATCH_CONCURRENCY: usize = 8;
+
+// Smallest batch worth spreading across threads
+const PARALLEL_THRESHOLD: usize = 256;
+
+/// Where a `_to` batch function sends its outputs besides returning them, and who hears when it finishes
+#[derive(Debug, Clone, Default)]
+pub struct BatchOutput {
+    /// Each output also sent as an NDJSON record, {"index": 0, "output": "..."} or {"index": 1, "error": "..."}
+    pub sink: Option<SinkTarget>,
+    pub sink_options: SinkOptions,
+    /// "true" for the webhook set with `set_notify_target`, or a webhook URL, posted a summary once the batch finishes
+    pub notify: Option<String>,
+    /// The notification body, the target's own format when None
+    pub notify_format: Option<NotifyFormat>
+}
+
+/// A finished `_to` batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport {
+    pub call: String,
+    /// One per input, in order, each the result or that input's error text
+    pub outputs: Vec<String>,
+    /// Inputs whose output is an error
+    pub failed: usize,
+    /// What the sink was handed, when there was one
+    pub sink: Option<SinkStats>
+}
+
+// Opens the sink, runs the batch, streams its outputs (Err holding an input's error text) and
+// posts the summary; a sink that cannot be opened or written to fails the batch
+pub(crate) fn deliver(call: &str, output: &BatchOutput, run: impl FnOnce() -> Vec<Result<String, String>>) -> Result<BatchReport, String> {
+    let result = output.sink.clone().map(|target| OutputSink::open(target, &output.sink_options)).transpose()
+        .and_then(|mut sink| {
+            let outputs = run();
+            let sink = match sink.as_mut() {
+                Some(sink) => Some(stream(sink, &outputs)?),
+                None => None
+            };
+
+            let failed = outputs.iter().filter(|output| output.is_err()).count();
+            let outputs = outputs.into_iter().map(|output| output.unwrap_or_else(|e| e)).collect();
+            Ok(BatchReport { call: call.to_string(), outputs, failed, sink })
+        });
+
+    if let Some(notify) = output.notify.as_deref().filter(|notify| *notify != "false") {
+        notify_finished(&format!("{} batch", call), notify, output.notify_format, &result);
+    }
+    result
+}
+
+// One record per output, flushed, giving the sink's counts
+fn stream(sink: &mut OutputSink, outputs: &[Result<String, String>]) -> Result<SinkStats, String> {
+    let failed = |sink: &OutputSink, e: std::io::Error| format!("Unable to write to {}: {}", sink.stats().target, e);
+
+    for (index, output) in outputs.iter().enumerate() {
+        let record = match output {
+            Ok(output) => serde_json::json!({ "index": index, "output": output }),
+            Err(e) => serde_json::json!({ "index": index, "error": e })
+        };
+        if let Err(e) = writeln!(sink, "{}", record) {
+            return Err(failed(sink, e));
+        }
+    }
+    if let Err(e) = sink.flush() {
+        return Err(failed(sink, e));
+    }
+
+    Ok(sink.stats().clone())
+}
+
+// `map` over every input, in order, on the CPU's threads when the batch is large
+pub(crate) fn map_parallel<R: Send>(inputs: &[&str], map: impl Fn(&str) -> R + Sync) -> Vec<R> {
+    let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+    if inputs.len() < PARALLEL_THRESHOLD || threads == 1 {
+        return inputs.iter().map(|input| map(input)).collect();
+    }
+
+    let map = &map;
+    thread::scope(|scope| {
+        let parts: Vec<_> = inputs.chunks(inputs.len().div_ceil(threads))
+            .map(|part| scope.spawn(move || part.iter().map(|input| map(input)).collect::<Vec<R>>()))
+            .collect();
+        parts.into_iter()
+            .flat_map(|part| part.join().unwrap_or_else(|payload| panic::resume_unwind(payload)))
+            .collect()
+    })
+}
+
+// Runs `job` for every index below `count` with at most the configured number in flight; a slot
+// is None only if the worker running it panicked
+pub(crate) async fn run_concurrent<T, F, Fut>(count: usize, job: F) -> Vec<Option<T>>
+where
+    T: Send + 'static,
+    F: Fn(usize) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send
+{
+    let workers = config::mercy_config().concurrency().unwrap_or(BATCH_CONCURRENCY).min(count);
+    let job = Arc::new(job);
+    let next = Arc::new(AtomicUsize::new(0));
+    let mut running = JoinSet::new();
+
+    for _ in 0..workers {
+        let (job, next) = (Arc::clone(&job), Arc::clone(&next));
+        running.spawn(config::propagate(async move {
+            let mut done = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= count {
+                    break done;
+                }
+                done.push((index, job(index).await));
+            }
+        }));
+    }
+
+    let mut slots: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    while let Some(finished) = running.join_next().await {
+        for (index, output) in finished.unwrap_or_default() {
+            slots[index] = Some(output);
+        }
+    }
+    slots
+}
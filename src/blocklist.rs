@@ -0,0 +1,277 @@
+/*
+    Offline reputation from local blocklist files
+
+    For networks without internet access, domains and IP addresses are checked against
+    blocklists kept on disk instead of InQuest. Two formats are read, and may be mixed:
+
+        evil.example                    one domain, IP address or CIDR block per line
+        0.0.0.0 evil.example ads.test   hosts file: an address, then the names it blocks
+
+    Comments start with "#" (also after an entry) or a leading "!" or ";", names are compared
+    without case, and a listed domain also lists every name under it, so "evil.example"
+    matches "login.evil.example" (a leading "*." is dropped to the same effect). Lines that
+    are neither are skipped and counted.
+
+    Lists of a million lines are loaded once into a hash map of names and a prefix trie of
+    blocks, so each check is a handful of lookups whatever the size of the list; `Blocklist`
+    is the loaded handle for checking many indicators. The string dispatcher reads the files
+    named by MERCY_BLOCKLIST (or "list="), and keeps them loaded until one of them changes.
+*/
+
+use std::{
+    collections::HashMap,
+    env,
+    fmt,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime
+};
+
+use serde::Serialize;
+
+use crate::{
+    cidr::{Cidr, PrefixTrie},
+    stats::{self, Counter},
+    text::TextLines,
+    trailing_options
+};
+
+/// Environment variable naming the blocklist files `status_offline` reads, separated like PATH (":" on Unix, ";" on Windows)
+pub const BLOCKLIST_ENV: &str = "MERCY_BLOCKLIST";
+
+// Names hosts files map to themselves, not blocked domains
+const HOSTS_NAMES: &[&str] = &["localhost", "localhost.localdomain", "local", "broadcasthost", "ip6-localhost", "ip6-loopback", "ip6-localnet", "ip6-mcastprefix", "ip6-allnodes", "ip6-allrouters", "ip6-allhosts", "0.0.0.0"];
+
+// Blocklist files with their modification times, and the list loaded from them
+type LoadedList = (Vec<(PathBuf, Option<SystemTime>)>, Arc<Blocklist>);
+
+// Last files loaded by the string dispatcher
+static LOADED: Mutex<Option<LoadedList>> = Mutex::new(None);
+
+/// Domains and IP blocks loaded from blocklist files, each remembering the file it came from
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    // Values index `sources`
+    domains: HashMap<String, u32>,
+    // Values index `blocks`
+    trie: PrefixTrie,
+    blocks: Vec<(Cidr, u32)>,
+    sources: Vec<String>,
+    skipped: usize
+}
+
+/// Whether an indicator is on a blocklist
+#[derive(Debug, Clone, Serialize)]
+pub struct BlocklistMatch {
+    pub indicator: String,
+    pub listed: bool,
+    /// The file that lists it
+    pub source: Option<String>,
+    /// The entry that matched: the indicator itself, a parent domain or a block
+    pub entry: Option<String>
+}
+
+impl fmt::Display for BlocklistMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) if self.listed => write!(f, "Listed ({})", source),
+            _ => write!(f, "Not listed")
+        }
+    }
+}
+
+impl Blocklist {
+    pub fn new() -> Blocklist {
+        Blocklist::default()
+    }
+
+    /// Parses blocklist text (see the module notes), recording `source` as where its entries came from
+    pub fn parse(text: &str, source: &str) -> Blocklist {
+        let mut list = Blocklist::new();
+        let index = list.add_source(source);
+        for line in text.lines() {
+            list.add_line(line, index);
+        }
+        list
+    }
+
+    /// Reads a blocklist file line by line; its path is the source of its entries
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Blocklist, String> {
+        let path = path.as_ref();
+        let unreadable = |e: std::io::Error| format!("Unable to read blocklist {}: {}", path.display(), e);
+        let mut list = Blocklist::new();
+        let index = list.add_source(&path.display().to_string());
+
+        for line in TextLines::open(path).map_err(unreadable)? {
+            list.add_line(&line.map_err(unreadable)?, index);
+        }
+        Ok(list)
+    }
+
+    /// Reads several blocklist files into one list
+    pub fn load_all<P: AsRef<Path>>(paths: &[P]) -> Result<Blocklist, String> {
+        let mut list = Blocklist::new();
+        for path in paths {
+            list.extend(Blocklist::load(path)?);
+        }
+        Ok(list)
+    }
+
+    /// Adds the entries of another list; an entry already present keeps its first source
+    pub fn extend(&mut self, other: Blocklist) {
+        let offset = self.sources.len() as u32;
+        self.sources.extend(other.sources);
+        self.skipped += other.skipped;
+
+        for (domain, source) in other.domains {
+            self.domains.entry(domain).or_insert(source + offset);
+        }
+        for (block, source) in other.blocks {
+            self.add_block(block, source + offset);
+        }
+    }
+
+    /// Checks a domain (and its parent domains) or an IP address
+    pub fn check(&self, indicator: &str) -> BlocklistMatch {
+        let indicator = indicator.trim();
+        let query = indicator.trim_start_matches('[').trim_end_matches(']').trim_end_matches('.').to_lowercase();
+        let found = match query.parse::<IpAddr>() {
+            Ok(addr) => self.trie.longest(addr).map(|(_, value)| {
+                let (block, source) = self.blocks[value as usize];
+                (if block.size() == 1 { block.network().to_string() } else { block.to_string() }, source)
+            }),
+            Err(_) => {
+                let mut name = query.as_str();
+                loop {
+                    if let Some(source) = self.domains.get(name) {
+                        break Some((name.to_string(), *source));
+                    }
+                    match name.split_once('.') {
+                        Some((_, parent)) if !parent.is_empty() => name = parent,
+                        _ => break None
+                    }
+                }
+            }
+        };
+
+        BlocklistMatch {
+            indicator: indicator.to_string(),
+            listed: found.is_some(),
+            source: found.as_ref().map(|(_, source)| self.sources[*source as usize].clone()),
+            entry: found.map(|(entry, _)| entry)
+        }
+    }
+
+    /// Distinct domains and blocks listed
+    pub fn len(&self) -> usize {
+        self.domains.len() + self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The files (or sources given to `parse`) the entries came from
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Lines that were neither a comment nor an entry
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn add_source(&mut self, source: &str) -> u32 {
+        self.sources.push(source.to_string());
+        (self.sources.len() - 1) as u32
+    }
+
+    fn add_block(&mut self, block: Cidr, source: u32) {
+        let value = self.blocks.len() as u32;
+        if let Some(previous) = self.trie.insert(&block, value) {
+            self.trie.insert(&block, previous);
+            return;
+        }
+        self.blocks.push((block, source));
+    }
+
+    fn add_line(&mut self, line: &str, source: u32) {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() || line.starts_with('!') || line.starts_with(';') {
+            return;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            // Hosts file: the names after the address are the blocked ones
+            [address, names @ ..] if !names.is_empty() && address.parse::<IpAddr>().is_ok() => {
+                for name in names.iter().filter(|name| !HOSTS_NAMES.iter().any(|local| name.eq_ignore_ascii_case(local))) {
+                    self.add_entry(name, source);
+                }
+            }
+            [entry, ..] => self.add_entry(entry, source),
+            [] => {}
+        }
+    }
+
+    fn add_entry(&mut self, entry: &str, source: u32) {
+        match (entry.parse::<Cidr>(), domain_entry(entry)) {
+            (Ok(block), _) => self.add_block(block, source),
+            (_, Some(domain)) => {
+                self.domains.entry(domain).or_insert(source);
+            }
+            _ => self.skipped += 1
+        }
+    }
+}
+
+// A listed name made comparable: lowercase, without a trailing dot or a leading "*." or "."
+fn domain_entry(entry: &str) -> Option<String> {
+    let domain = entry.trim_end_matches('.').trim_start_matches("*.").trim_start_matches('.').to_lowercase();
+    let valid = !domain.is_empty() && domain.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '.' | '_')) && !domain.contains("..");
+    valid.then_some(domain)
+}
+
+// The list in the files, reusing the last one loaded while none of them has changed
+pub(crate) fn load_cached(paths: &[PathBuf]) -> Result<Arc<Blocklist>, String> {
+    let stamps: Vec<(PathBuf, Option<SystemTime>)> = paths.iter()
+        .map(|path| (path.clone(), std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()))
+        .collect();
+    let mut loaded = LOADED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some((cached, list)) = loaded.as_ref() {
+        if *cached == stamps && stamps.iter().all(|(_, modified)| modified.is_some()) {
+            stats::count(Counter::CacheHits, 1);
+            return Ok(Arc::clone(list));
+        }
+    }
+
+    stats::count(Counter::CacheMisses, 1);
+    let list = Arc::new(Blocklist::load_all(paths)?);
+    *loaded = Some((stamps, Arc::clone(&list)));
+    Ok(list)
+}
+
+// Parses "indicator [list=path]", the files coming from MERCY_BLOCKLIST when no list is given
+pub(crate) fn status_offline_from_args(input: &str) -> Result<BlocklistMatch, String> {
+    let (indicator, pairs) = trailing_options(input, &["list"]);
+    let paths: Vec<PathBuf> = match pairs.last() {
+        Some((_, path)) => vec![PathBuf::from(path)],
+        None => env::var_os(BLOCKLIST_ENV)
+            .map(|value| env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()).collect())
+            .unwrap_or_default()
+    };
+
+    if paths.is_empty() {
+        return Err(format!("No blocklist: set {} to the list files, or give \"list=path\"", BLOCKLIST_ENV));
+    }
+    check_with_paths(&indicator, &paths)
+}
+
+pub(crate) fn check_with_paths(indicator: &str, paths: &[PathBuf]) -> Result<BlocklistMatch, String> {
+    if indicator.trim().is_empty() {
+        return Err("No domain or IP address given".to_string());
+    }
+    Ok(load_cached(paths)?.check(indicator))
+}
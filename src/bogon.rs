@@ -0,0 +1,175 @@
+/*
+    Bogon detection for addresses and prefixes
+
+    Bogons are addresses that should never appear as a source or destination on the public
+    Internet: special-use ranges from the IANA registries (private, loopback, link-local,
+    documentation, benchmarking, multicast...), reserved space, and for IPv6 everything
+    outside 2000::/3, which IANA has not allocated. Since IANA handed out the last IPv4 /8s
+    in 2011 there is no unallocated IPv4 space at that level; "fullbogons" (space the RIRs
+    have not yet assigned) change daily, so a list such as Team Cymru's fullbogons can be
+    loaded on top of the embedded table.
+
+    A prefix is a bogon when a bogon range contains it, and partially one when it contains
+    bogon ranges without being inside one.
+*/
+
+use std::{
+    fmt,
+    net::IpAddr,
+    sync::OnceLock
+};
+
+use serde::Serialize;
+
+use crate::{
+    cidr::{Cidr, PrefixTrie},
+    prefixset::PrefixSet,
+    trailing_options
+};
+
+// Range, category, description
+const BOGONS: &[(&str, &str, &str)] = &[
+    ("0.0.0.0/8", "reserved", "\"this network\" (RFC 791)"),
+    ("10.0.0.0/8", "private", "private-use (RFC 1918)"),
+    ("100.64.0.0/10", "private", "shared address space / CGNAT (RFC 6598)"),
+    ("127.0.0.0/8", "loopback", "loopback (RFC 1122)"),
+    ("169.254.0.0/16", "link-local", "link-local (RFC 3927)"),
+    ("172.16.0.0/12", "private", "private-use (RFC 1918)"),
+    ("192.0.0.0/24", "special-use", "IETF protocol assignments (RFC 6890)"),
+    ("192.0.2.0/24", "documentation", "TEST-NET-1 documentation (RFC 5737)"),
+    ("192.88.99.0/24", "deprecated", "6to4 relay anycast (RFC 7526)"),
+    ("192.168.0.0/16", "private", "private-use (RFC 1918)"),
+    ("198.18.0.0/15", "benchmarking", "network benchmarking (RFC 2544)"),
+    ("198.51.100.0/24", "documentation", "TEST-NET-2 documentation (RFC 5737)"),
+    ("203.0.113.0/24", "documentation", "TEST-NET-3 documentation (RFC 5737)"),
+    ("224.0.0.0/4", "multicast", "multicast (RFC 5771)"),
+    ("240.0.0.0/4", "reserved", "reserved for future use (RFC 1112)"),
+    ("255.255.255.255/32", "reserved", "limited broadcast (RFC 919)"),
+    ("::/3", "unallocated", "outside global unicast 2000::/3 (unallocated by IANA)"),
+    ("::/128", "reserved", "unspecified address (RFC 4291)"),
+    ("::1/128", "loopback", "loopback (RFC 4291)"),
+    ("::ffff:0:0/96", "special-use", "IPv4-mapped addresses (RFC 4291)"),
+    ("64:ff9b:1::/48", "private", "local-use IPv4/IPv6 translation (RFC 8215)"),
+    ("100::/64", "special-use", "discard-only (RFC 6666)"),
+    ("2001:2::/48", "benchmarking", "benchmarking (RFC 5180)"),
+    ("2001:10::/28", "deprecated", "ORCHID (RFC 4843)"),
+    ("2001:db8::/32", "documentation", "documentation (RFC 3849)"),
+    ("3ffe::/16", "deprecated", "6bone, returned to IANA (RFC 3701)"),
+    ("3fff::/20", "documentation", "documentation (RFC 9637)"),
+    ("4000::/2", "unallocated", "outside global unicast 2000::/3 (unallocated by IANA)"),
+    ("8000::/1", "unallocated", "outside global unicast 2000::/3 (unallocated by IANA)"),
+    ("fc00::/7", "private", "unique local (RFC 4193)"),
+    ("fe80::/10", "link-local", "link-local (RFC 4291)"),
+    ("fec0::/10", "deprecated", "site-local (RFC 3879)"),
+    ("ff00::/8", "multicast", "multicast (RFC 4291)")
+];
+
+/// A bogon range that matched
+#[derive(Debug, Clone, Serialize)]
+pub struct BogonMatch {
+    pub prefix: String,
+    /// "private", "loopback", "link-local", "documentation", "benchmarking", "multicast", "reserved",
+    /// "deprecated", "special-use", "unallocated", or "list" for a loaded list
+    pub category: String,
+    pub description: String,
+    /// "contains" when the range contains the input, "inside" when it lies inside an input prefix
+    pub relation: String
+}
+
+/// Whether an address or prefix is bogon space
+#[derive(Debug, Clone, Serialize)]
+pub struct BogonReport {
+    pub input: String,
+    /// All of the input is bogon space
+    pub bogon: bool,
+    /// Part of the input prefix is bogon space
+    pub partial: bool,
+    /// Ranges containing the input (most specific first), then ranges inside it
+    pub matches: Vec<BogonMatch>
+}
+
+impl fmt::Display for BogonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = if self.bogon { "bogon" } else if self.partial { "partially bogon" } else { "not a bogon" };
+        writeln!(f, "{}: {}", self.input, verdict)?;
+
+        for found in &self.matches {
+            let relation = if found.relation == "contains" { "in" } else { "contains" };
+            writeln!(f, "  {} {} ({}: {})", relation, found.prefix, found.category, found.description)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Embedded ranges with their category and description, in a trie whose values index them
+type BogonTable = (PrefixTrie, Vec<(Cidr, &'static str, &'static str)>);
+
+fn table() -> &'static BogonTable {
+    static TABLE: OnceLock<BogonTable> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let ranges: Vec<(Cidr, &'static str, &'static str)> = BOGONS.iter()
+            .filter_map(|(range, category, description)| Some((range.parse().ok()?, *category, *description)))
+            .collect();
+        let mut trie = PrefixTrie::default();
+        for (index, (range, _, _)) in ranges.iter().enumerate() {
+            trie.insert(range, index as u32);
+        }
+        (trie, ranges)
+    })
+}
+
+/// Description of the most specific embedded bogon range containing the address
+pub(crate) fn bogon_description(addr: IpAddr) -> Option<&'static str> {
+    bogon_range(addr).map(|(_, description)| description)
+}
+
+/// Category and description of the most specific embedded bogon range containing the address
+pub(crate) fn bogon_range(addr: IpAddr) -> Option<(&'static str, &'static str)> {
+    let (trie, ranges) = table();
+    trie.longest(addr).map(|(_, index)| (ranges[index as usize].1, ranges[index as usize].2))
+}
+
+/// Checks an address or prefix against the embedded bogon table and, if given, a loaded list (e.g. fullbogons)
+pub fn mercy_bogon_check(input: &str, list: Option<&PrefixSet>) -> Result<BogonReport, String> {
+    let prefix: Cidr = input.trim().parse()?;
+    let (trie, ranges) = table();
+    let mut containing = Vec::new();
+    let mut inside = Vec::new();
+
+    let embedded = |index: u32, relation: &str| {
+        let (range, category, description) = ranges[index as usize];
+        BogonMatch { prefix: range.to_string(), category: category.to_string(), description: description.to_string(), relation: relation.to_string() }
+    };
+    let listed = |range: Cidr, relation: &str| BogonMatch { prefix: range.to_string(), category: "list".to_string(), description: "loaded bogon list".to_string(), relation: relation.to_string() };
+
+    containing.extend(trie.covering(&prefix).into_iter().rev().map(|(_, index)| embedded(index, "contains")));
+    containing.extend(list.into_iter().flat_map(|list| list.covering(&prefix)).rev().map(|range| listed(range, "contains")));
+
+    let mut nested: Vec<u32> = trie.within(&prefix).into_iter().filter(|index| ranges[*index as usize].0 != prefix).collect();
+    nested.sort_unstable();
+    inside.extend(nested.into_iter().map(|index| embedded(index, "inside")));
+    inside.extend(list.into_iter().flat_map(|list| list.within(&prefix)).filter(|range| *range != prefix).map(|range| listed(range, "inside")));
+
+    let bogon = !containing.is_empty();
+    let partial = !bogon && !inside.is_empty();
+    containing.extend(inside);
+
+    Ok(BogonReport { input: input.trim().to_string(), bogon, partial, matches: containing })
+}
+
+// Parses "ip_or_prefix [list=fullbogons-ipv4.txt[,fullbogons-ipv6.txt]]"
+pub(crate) fn bogon_check_from_args(input: &str) -> Result<BogonReport, String> {
+    let (target, pairs) = trailing_options(input, &["list"]);
+    let mut list: Option<PrefixSet> = None;
+
+    for (_, value) in pairs {
+        for path in value.split(',').filter(|path| !path.is_empty()) {
+            let loaded = PrefixSet::load(path)?;
+            list.get_or_insert_with(PrefixSet::new).extend(loaded.iter().copied());
+        }
+    }
+
+    mercy_bogon_check(&target, list.as_ref())
+}
@@ -0,0 +1,330 @@
+/*
+    Canary tokens and the log lines that show them firing
+
+    Three kinds of artifact carry a canary ID:
+
+        dns      <id>.canary.example.com, a hostname whose lookup reaches your nameserver
+        url      https://canary.example.com/t/<id>, a link whose fetch reaches your web server
+        aws_key  an access key ID "AKIA<id>" with a matching secret, planted where stolen
+                 credentials get tried; it is not a real key and cannot authenticate
+
+    An ID is random characters of the lowercase base32 alphabet (a-z, 2-7) from the OS
+    CSPRNG, 20 of them (100 bits, so collisions stay out of reach across any number of
+    deployments) for DNS and URL tokens and 12 for the 16-character AWS key ID, followed by
+    4 check characters from a SHA-256 of the random part. The check lets `parse_trigger`
+    tell a canary in a log line from any other string of the right shape, without keeping a
+    list of issued IDs; it is not a secret, and anyone who knows the scheme can forge one.
+
+    The IDs and the AWS secret are drawn through `MercyRng::secret`: "seed=42" makes them
+    repeatable for a test, and is refused unless "insecure_seeded_secrets=true" says the
+    predictable token is wanted, since anyone with the seed can forge or recognize it.
+
+    The DNS and URL tokens need the domain you control, set once with `set_canary_domain`
+    or per call with "domain=". `parse_trigger` reads one log line (a BIND, dnsmasq or
+    unbound query log line, a web access log line, or a CloudTrail JSON record) and gives
+    the ID that fired, the kind, the first IP address in the line as the source and the
+    line's timestamp in UTC.
+*/
+
+use std::{
+    fmt,
+    net::IpAddr,
+    sync::RwLock,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    logtime::{LogTimeOptions, detect_timestamp, parse_any_timestamp},
+    rng::MercyRng,
+    timefmt::{civil_to_unix, unix_to_rfc3339},
+    trailing_options
+};
+
+const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+// Random characters of DNS/URL and AWS key IDs, and check characters after them
+const LONG_ID: usize = 20;
+const KEY_ID: usize = 12;
+const CHECK: usize = 4;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+static CANARY_DOMAIN: RwLock<Option<String>> = RwLock::new(None);
+
+/// Kind of canary artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryKind {
+    Dns,
+    Url,
+    AwsKey
+}
+
+impl CanaryKind {
+    /// "dns", "url" or "aws_key"
+    pub fn parse(name: &str) -> Result<CanaryKind, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "dns" => Ok(CanaryKind::Dns),
+            "url" => Ok(CanaryKind::Url),
+            "aws_key" | "aws" => Ok(CanaryKind::AwsKey),
+            _ => Err(format!("Unknown canary kind '{}' (expected dns, url or aws_key)", name))
+        }
+    }
+}
+
+impl fmt::Display for CanaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CanaryKind::Dns => "dns",
+            CanaryKind::Url => "url",
+            CanaryKind::AwsKey => "aws_key"
+        })
+    }
+}
+
+/// A generated canary artifact
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryToken {
+    pub kind: CanaryKind,
+    /// The ID to record against the place the artifact is planted
+    pub id: String,
+    /// Hostname, URL or access key ID
+    pub token: String,
+    /// Secret access key of an AWS key
+    pub secret: Option<String>,
+    pub created: String
+}
+
+impl fmt::Display for CanaryToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} canary {} (created {})", self.kind, self.id, self.created)?;
+        match (&self.kind, &self.secret) {
+            (CanaryKind::AwsKey, Some(secret)) => {
+                writeln!(f, "aws_access_key_id = {}", self.token)?;
+                writeln!(f, "aws_secret_access_key = {}", secret)
+            },
+            _ => writeln!(f, "{}", self.token)
+        }
+    }
+}
+
+/// Options for `mercy_canary_generate_with`
+#[derive(Debug, Clone, Default)]
+pub struct CanaryOptions {
+    /// Domain of DNS and URL canaries, the one set with `set_canary_domain` when None
+    pub domain: Option<String>,
+    /// Seed for the ID and secret, refused unless `insecure_seeded_secrets` is set
+    pub seed: Option<u64>,
+    /// Allows a seed, for tests that need the same canary every run
+    pub insecure_seeded_secrets: bool
+}
+
+/// A canary ID found in a log line
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryTrigger {
+    pub id: String,
+    pub kind: CanaryKind,
+    /// First IP address in the line: the querying resolver, the web client or the API caller
+    pub source: Option<String>,
+    /// The line's timestamp in UTC
+    pub time: Option<String>,
+    /// Hostname, URL path or access key ID the ID was found in
+    pub token: String
+}
+
+impl fmt::Display for CanaryTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} canary {} fired from {} at {} ({})", self.kind, self.id, self.source.as_deref().unwrap_or("an unknown source"),
+            self.time.as_deref().unwrap_or("an unknown time"), self.token)
+    }
+}
+
+/// Sets the domain DNS and URL canaries are generated under (None clears it)
+pub fn set_canary_domain(domain: Option<&str>) -> Result<(), String> {
+    let domain = domain.map(valid_domain).transpose()?;
+    match CANARY_DOMAIN.write() {
+        Ok(mut current) => *current = domain,
+        Err(poisoned) => *poisoned.into_inner() = domain
+    }
+    Ok(())
+}
+
+/// The domain set with `set_canary_domain`
+pub fn canary_domain() -> Option<String> {
+    match CANARY_DOMAIN.read() {
+        Ok(current) => current.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    }
+}
+
+/// A new canary of `kind`; DNS and URL canaries go under `domain`, or the one set with `set_canary_domain`
+pub fn mercy_canary_generate(kind: CanaryKind, domain: Option<&str>) -> Result<CanaryToken, String> {
+    mercy_canary_generate_with(kind, &CanaryOptions { domain: domain.map(str::to_string), ..CanaryOptions::default() })
+}
+
+/// `mercy_canary_generate` with a domain, and a seed where the caller accepts a predictable canary
+pub fn mercy_canary_generate_with(kind: CanaryKind, options: &CanaryOptions) -> Result<CanaryToken, String> {
+    let mut rng = MercyRng::secret(options.seed, options.insecure_seeded_secrets)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs() as i64).unwrap_or(0);
+    let created = unix_to_rfc3339(now, 0);
+
+    let domain = || -> Result<String, String> {
+        match options.domain.as_deref() {
+            Some(domain) => valid_domain(domain),
+            None => canary_domain().ok_or_else(|| "No canary domain: pass domain= or call set_canary_domain".to_string())
+        }
+    };
+
+    let token = match kind {
+        CanaryKind::Dns => {
+            let id = new_id(&mut rng, LONG_ID);
+            CanaryToken { kind, token: format!("{}.{}", id, domain()?), id, secret: None, created }
+        },
+        CanaryKind::Url => {
+            let id = new_id(&mut rng, LONG_ID);
+            CanaryToken { kind, token: format!("https://{}/t/{}", domain()?, id), id, secret: None, created }
+        },
+        CanaryKind::AwsKey => {
+            let id = new_id(&mut rng, KEY_ID);
+            // Secret keys are 40 characters of base64 without padding
+            let secret: String = (0..40).map(|_| b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"[rng.below(64)] as char).collect();
+            CanaryToken { kind, token: format!("AKIA{}", id.to_ascii_uppercase()), id, secret: Some(secret), created }
+        }
+    };
+
+    Ok(token)
+}
+
+/// The canary that fired in a DNS query, web access or CloudTrail log line
+pub fn mercy_canary_parse_trigger(line: &str) -> Result<CanaryTrigger, String> {
+    let line = line.trim();
+    let record: Option<Value> = serde_json::from_str(line).ok().filter(Value::is_object);
+
+    let (id, kind, token) = find_id(line).ok_or_else(|| "No canary ID in the line".to_string())?;
+
+    let (source, time) = match &record {
+        Some(record) => (
+            ["sourceIPAddress", "source_ip", "client_ip", "remote_addr", "src_ip"].iter().find_map(|key| record[*key].as_str()).map(str::to_string),
+            ["eventTime", "timestamp", "time", "@timestamp"].iter().find_map(|key| record[*key].as_str())
+                .and_then(|time| parse_any_timestamp(time).ok()).map(|timestamp| unix_to_rfc3339(timestamp.secs, 0))
+        ),
+        None => (first_address(line), line_time(line))
+    };
+
+    Ok(CanaryTrigger { id, kind, source, time, token })
+}
+
+// Random characters followed by their check characters
+fn new_id(rng: &mut MercyRng, length: usize) -> String {
+    let body: String = (0..length).map(|_| ALPHABET[rng.below(32)] as char).collect();
+    let check = check_chars(&body);
+    body + &check
+}
+
+// 20 bits of SHA-256 over the lowercase random part, as 4 base32 characters
+fn check_chars(body: &str) -> String {
+    let digest = Sha256::digest(format!("mercy-canary:{}", body).as_bytes());
+    let bits = (u32::from(digest[0]) << 12) | (u32::from(digest[1]) << 4) | (u32::from(digest[2]) >> 4);
+    (0..CHECK).rev().map(|index| ALPHABET[((bits >> (index * 5)) & 31) as usize] as char).collect()
+}
+
+fn valid_id(candidate: &str) -> bool {
+    let candidate = candidate.to_ascii_lowercase();
+    candidate.len() > CHECK
+        && candidate.bytes().all(|byte| ALPHABET.contains(&byte))
+        && check_chars(&candidate[..candidate.len() - CHECK]) == candidate[candidate.len() - CHECK..]
+}
+
+// The first canary ID in the line, its kind and the word it was found in
+fn find_id(line: &str) -> Option<(String, CanaryKind, String)> {
+    let words = line.split(|c: char| !(c.is_ascii_alphanumeric() || "-._/:".contains(c))).filter(|word| !word.is_empty());
+
+    for word in words {
+        // AWS access key IDs stand alone
+        if let Some(key) = word.strip_prefix("AKIA").filter(|key| key.len() == KEY_ID + CHECK) {
+            if valid_id(key) {
+                return Some((key.to_ascii_lowercase(), CanaryKind::AwsKey, word.to_string()));
+            }
+        }
+
+        // A hostname label, or a URL path segment after "/t/"
+        let pieces = word.split(['.', '/', ':']).collect::<Vec<_>>();
+        for (index, piece) in pieces.iter().enumerate() {
+            if piece.len() != LONG_ID + CHECK || !valid_id(piece) {
+                continue;
+            }
+
+            let kind = if index > 0 && pieces[index - 1] == "t" && word.contains('/') { CanaryKind::Url } else { CanaryKind::Dns };
+            return Some((piece.to_ascii_lowercase(), kind, word.trim_end_matches('.').to_string()));
+        }
+    }
+
+    None
+}
+
+// The first IP address: a word on its own, or before "#port" (BIND) or ":port" (IPv4)
+fn first_address(line: &str) -> Option<String> {
+    line.split(|c: char| c.is_whitespace() || "[]()\",;".contains(c)).find_map(|word| {
+        let word = word.split('#').next().unwrap_or(word);
+        let candidate = match word.rsplit_once(':') {
+            Some((address, port)) if address.contains('.') && port.chars().all(|c| c.is_ascii_digit()) => address,
+            _ => word
+        };
+        candidate.parse::<IpAddr>().ok().map(|address| address.to_string())
+    })
+}
+
+// The line's timestamp: any format the log parsers know, or BIND's "15-Oct-2024 10:22:01.123"
+fn line_time(line: &str) -> Option<String> {
+    if let Some(detected) = detect_timestamp(line, &LogTimeOptions::default()) {
+        return Some(unix_to_rfc3339(detected.timestamp.secs, 0));
+    }
+
+    let mut fields = line.split_whitespace();
+    let (date, time) = (fields.next()?, fields.next()?);
+    let mut date_parts = date.split('-');
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    let month_name = date_parts.next()?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|month| *month == month_name)? as u32 + 1;
+    let year: i64 = date_parts.next()?.parse().ok()?;
+
+    let clock: Vec<i64> = time.split('.').next()?.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    if clock.len() != 3 || clock[0] > 23 || clock[1] > 59 || clock[2] > 60 {
+        return None;
+    }
+
+    civil_to_unix(year, month, day, clock[0] * 3600 + clock[1] * 60 + clock[2]).map(|secs| unix_to_rfc3339(secs, 0))
+}
+
+// Lowercased domain of LDH labels
+fn valid_domain(domain: &str) -> Result<String, String> {
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let valid = domain.len() <= 200 && domain.contains('.') && domain.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63 && !label.starts_with('-') && !label.ends_with('-')
+            && label.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+    });
+
+    if valid {
+        Ok(domain)
+    } else {
+        Err(format!("Invalid canary domain '{}'", domain))
+    }
+}
+
+// Parses "dns|url|aws_key [domain=canary.example.com] [seed=42 insecure_seeded_secrets=true]"
+pub(crate) fn generate_from_args(input: &str) -> Result<CanaryToken, String> {
+    let (kind, pairs) = trailing_options(input, &["domain", "seed", "insecure_seeded_secrets"]);
+    let mut options = CanaryOptions::default();
+    for (key, value) in pairs {
+        match key {
+            "domain" => options.domain = Some(value.to_string()),
+            "seed" => options.seed = Some(value.parse::<u64>().map_err(|_| format!("Invalid seed '{}'", value))?),
+            _ => options.insecure_seeded_secrets = value == "true"
+        }
+    }
+    mercy_canary_generate_with(CanaryKind::parse(&kind)?, &options)
+}
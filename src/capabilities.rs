@@ -0,0 +1,918 @@
+/*
+    Which functions can work in this environment
+
+    Deployments differ: containers without raw sockets or /proc, builds without the `image`
+    feature, hosts without git or a resolver configuration. `mercy_capabilities` answers
+    "will this call work here, and if not why?" from cheap local probes only: opening (and
+    immediately closing) ICMP sockets, checking for files and directories, scanning PATH and
+    reading the compiled features and process-wide settings. Nothing is sent on the network,
+    so calls marked `network` are reported usable as far as the host goes; whether traffic
+    actually gets out is not probed.
+
+    Each dispatcher call is listed under its own name, `_json` variants included, so a menu
+    can be filtered directly against the report.
+
+    The same table is the registry of calls: `mercy_methods` describes what each call of a
+    dispatcher takes, `mercy_validate` checks a call and its input without running it, and
+    the dispatchers refuse a call the table does not list, so the three cannot disagree.
+*/
+
+use std::{
+    env,
+    fmt,
+    fs,
+    future::Future,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    pin::Pin
+};
+
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::{
+    all_hashes,
+    asn,
+    banner,
+    base64_decode,
+    base64_detect,
+    blocklist,
+    bogon,
+    canary,
+    carve,
+    charset::{self, Charset},
+    cipher,
+    cluster,
+    cmd_unix_decode,
+    cmd_unix_encode,
+    cmd_windows_decode,
+    cmd_windows_encode,
+    collect_file_hex,
+    config,
+    crc32_hash,
+    decoded_text,
+    defang,
+    dns::{self, default_dns_server},
+    domaincat,
+    encode_bytes,
+    entropy,
+    entropy_bytes,
+    epoch_to_utc,
+    error::MercyError,
+    evtx,
+    filetime_to_utc,
+    fuzz,
+    gitsecrets,
+    gzip_hex_bytes,
+    hashing,
+    hexdiff,
+    hexdump_bytes,
+    hsts,
+    html_attr_decode,
+    html_attr_encode,
+    html_entity_encode,
+    http::{HttpIdentity, http_identity},
+    httpheaders,
+    icmp::{self, IcmpAccess},
+    internal_ip,
+    ipcalc,
+    js_string_decode,
+    js_string_encode,
+    json_result,
+    jwt_claims,
+    listen,
+    logmerge,
+    logtime,
+    mac,
+    malicious_domain_report,
+    malicious_domain_save,
+    malicious_hash_report,
+    manifest,
+    md5_hash,
+    mercy_ansi_inspect,
+    mercy_apk,
+    mercy_caesar_solve,
+    mercy_canary_parse_trigger,
+    mercy_cidr_range,
+    mercy_csp_audit,
+    mercy_detect_encoding,
+    mercy_email_headers,
+    mercy_entropy_file,
+    mercy_exif,
+    mercy_file_type,
+    mercy_freq_analysis,
+    mercy_ftp_check,
+    mercy_hash_identify,
+    mercy_homograph_check,
+    mercy_is_private,
+    mercy_mac_lookup,
+    mercy_mobileconfig,
+    mercy_oci,
+    mercy_password_strength,
+    mercy_pcap_summary,
+    mercy_pe_info,
+    mercy_plist,
+    mercy_prefetch,
+    mercy_rdp_check,
+    mercy_sbom,
+    mercy_shimcache,
+    mercy_smb_check,
+    mercy_stats,
+    mercy_strip_ansi,
+    mercy_sweep,
+    mercy_telnet_check,
+    mercy_traceroute,
+    mercy_unicode_inspect,
+    mercy_unicode_inspect_file,
+    mercy_url_normalize,
+    mercy_url_parse,
+    mercy_uuid_inspect,
+    mercy_whois,
+    mercy_zip_list,
+    minidump,
+    morse_decode,
+    morse_encode,
+    nato_decode,
+    nato_encode,
+    notify,
+    oci,
+    osguess,
+    parse_jwt,
+    password,
+    ping_from_args,
+    portscan,
+    prefixset,
+    publicip,
+    punycode_decode,
+    punycode_encode,
+    qr_decode,
+    qr_encode,
+    redact::{self, redaction_policy},
+    refang,
+    report_for,
+    report_json,
+    report_text,
+    reset_mercy_stats,
+    result_for,
+    rot13_decode,
+    safedisplay,
+    sbom,
+    secretscan,
+    selftest,
+    sha1_hash,
+    sha2_256_hash,
+    sha2_512_hash,
+    sha3_hash,
+    sinkhole::{self, SinkholeList, sinkhole_list},
+    sql_string_decode,
+    sql_string_encode,
+    snapshot,
+    sri,
+    ssdeep_hash,
+    stego_check,
+    strings,
+    subdomains,
+    SweepOptions,
+    system_info,
+    text_result,
+    timeline,
+    tlscert,
+    TracerouteOptions,
+    triage,
+    utc_to_epoch,
+    uuid,
+    xor,
+    xxhash64_hash,
+    zonefile
+};
+
+/// What a call takes as its input; options such as "timeout=5" may follow any of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputKind {
+    /// Nothing; the input is ignored
+    None,
+    /// Any text, or a form of it the call documents ("13,text", "host,start-end")
+    Text,
+    /// The path of an existing file or directory
+    FilePath,
+    /// Comma-separated paths of existing files
+    FilePaths,
+    /// "host:port", "[v6]:port" for IPv6
+    HostPort,
+    /// A domain name or IP address, with ":port" where the call has a default port
+    Domain,
+    /// An http or https URL
+    Url
+}
+
+impl fmt::Display for InputKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputKind::None => write!(f, "none"),
+            InputKind::Text => write!(f, "text"),
+            InputKind::FilePath => write!(f, "file path"),
+            InputKind::FilePaths => write!(f, "file paths"),
+            InputKind::HostPort => write!(f, "host:port"),
+            InputKind::Domain => write!(f, "domain"),
+            InputKind::Url => write!(f, "url")
+        }
+    }
+}
+
+// What a call depends on beyond its input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Need {
+    // Outbound connections (not probed)
+    Network,
+    // A DNS server to query directly
+    Resolver,
+    // ICMP echo, with a TCP connect fallback
+    Ping,
+    // Raw or error-queue ICMP on a Unix platform
+    Traceroute,
+    // Raw sockets for remote fingerprinting, observations otherwise
+    Fingerprint,
+    // ICMP sweeps, TCP sweeps otherwise
+    Sweep,
+    // /proc on Linux for system counters
+    Proc,
+    // A writable temp directory for saved API responses
+    Temp,
+    // The git executable
+    Git,
+    // Built with the `image` feature
+    Image
+}
+
+// A `mercy_malicious` lookup, given the call name and the input
+type Lookup = fn(String, String) -> Pin<Box<dyn Future<Output = Result<String, MercyError>> + Send>>;
+
+// The code behind a call, in the form its dispatcher runs; a `_json` variant shares its call's
+// handler, which tells the two apart by the call name it is given
+#[derive(Clone, Copy)]
+pub(crate) enum Handler {
+    // `mercy_decode`: the input
+    Decode(fn(&str) -> Result<String, MercyError>),
+    // `mercy_encode`, `mercy_hash`, `mercy_hex` and the `mercy_decode` calls with a `_json`
+    // variant: the call name and the input
+    Method(fn(&str, &str) -> Result<String, MercyError>),
+    // `mercy_hash` of the input text itself, which batches can run without the error plumbing
+    Digest(fn(&str) -> String),
+    // `mercy_malicious`: the call name and the input, awaited
+    Lookup(Lookup),
+    // The string dispatchers (`mercy_extra`, `mercy_forensics`...): the call name and the input,
+    // failures written into the output
+    Report(fn(&str, &str) -> String),
+    // `mercy_hmac` and `mercy_hash_compare`, which take two inputs and read the digest from the call name
+    ByName
+}
+
+// Dispatcher, call, whether a `_json` variant exists, input, dependencies, description, handler
+type Call = (&'static str, &'static str, bool, InputKind, &'static [Need], &'static str, Handler);
+
+const CALLS: &[Call] = &[
+    ("mercy_decode", "base64", false, InputKind::Text, &[], "Standard base64, padded or not", Handler::Decode(base64_decode)),
+    ("mercy_decode", "base64_url", false, InputKind::Text, &[], "URL-safe base64, padded or not", Handler::Decode(|text| decoded_text("base64_url", text))),
+    ("mercy_decode", "base64_detect", false, InputKind::Text, &[], "Which base64 alphabet and padding the text uses, and its decoded size", Handler::Decode(|text| base64_detect(text).map(|variant| variant.to_string()).map_err(|e| MercyError::invalid_encoding("base64_detect", e)))),
+    ("mercy_decode", "rot13", false, InputKind::Text, &[], "ROT13", Handler::Decode(|text| Ok(rot13_decode(text.to_string())))),
+    ("mercy_decode", "rot", false, InputKind::Text, &[], "Caesar shift of \"13,text\" undone", Handler::Decode(|text| cipher::rot_decode_from_args(text).map_err(|e| MercyError::invalid_encoding("rot", e)))),
+    ("mercy_decode", "rot47", false, InputKind::Text, &[], "ROT47 of the printable ASCII range", Handler::Decode(|text| Ok(cipher::rot47(text)))),
+    ("mercy_decode", "rot_brute", false, InputKind::Text, &[], "All 25 Caesar decodings, the most English-like marked", Handler::Decode(|text| cipher::rot_brute(text).map_err(|e| MercyError::invalid_encoding("rot_brute", e)))),
+    ("mercy_decode", "morse", false, InputKind::Text, &[], "Morse code", Handler::Decode(|text| Ok(morse_decode(text)))),
+    ("mercy_decode", "nato", false, InputKind::Text, &[], "NATO phonetic alphabet", Handler::Decode(|text| Ok(nato_decode(text)))),
+    ("mercy_decode", "hex", false, InputKind::Text, &[], "Hex in either case", Handler::Decode(|text| decoded_text("hex", text))),
+    ("mercy_decode", "base32", false, InputKind::Text, &[], "RFC 4648 base32", Handler::Decode(|text| decoded_text("base32", text))),
+    ("mercy_decode", "base58", false, InputKind::Text, &[], "Bitcoin-alphabet base58", Handler::Decode(|text| decoded_text("base58", text))),
+    ("mercy_decode", "base85", false, InputKind::Text, &[], "Adobe ASCII85", Handler::Decode(|text| decoded_text("base85", text))),
+    ("mercy_decode", "z85", false, InputKind::Text, &[], "Z85", Handler::Decode(|text| decoded_text("z85", text))),
+    ("mercy_decode", "url", false, InputKind::Text, &[], "Percent-encoding, one layer", Handler::Decode(|text| decoded_text("url", text))),
+    ("mercy_decode", "url_form", false, InputKind::Text, &[], "Form data: percent-encoding with \"+\" as a space", Handler::Decode(|text| decoded_text("url_form", text))),
+    ("mercy_decode", "html_entity", false, InputKind::Text, &[], "Named, decimal and hex HTML character references", Handler::Decode(|text| Ok(html_attr_decode(text)))),
+    ("mercy_decode", "html_attr", false, InputKind::Text, &[], "HTML attribute escapes", Handler::Decode(|text| Ok(html_attr_decode(text)))),
+    ("mercy_decode", "js_string", false, InputKind::Text, &[], "Escapes of a JavaScript string literal", Handler::Decode(|text| Ok(js_string_decode(text)))),
+    ("mercy_decode", "sql_string", false, InputKind::Text, &[], "Doubled quotes of a SQL string literal", Handler::Decode(|text| Ok(sql_string_decode(text)))),
+    ("mercy_decode", "cmd_unix", false, InputKind::Text, &[], "One POSIX shell word", Handler::Decode(|text| Ok(cmd_unix_decode(text)))),
+    ("mercy_decode", "cmd_windows", false, InputKind::Text, &[], "One cmd.exe argument", Handler::Decode(|text| Ok(cmd_windows_decode(text)))),
+    ("mercy_decode", "jwt", false, InputKind::Text, &[], "Header and payload of a JWT, unverified", Handler::Decode(|text| parse_jwt(text).map(|jwt| jwt.to_string()).map_err(|e| MercyError::invalid_encoding("jwt", e)))),
+    ("mercy_decode", "jwt_claims", false, InputKind::Text, &[], "Registered claims of a JWT, dates as UTC", Handler::Decode(|text| jwt_claims(text).map(|claims| claims.to_string()).map_err(|e| MercyError::invalid_encoding("jwt", e)))),
+    ("mercy_decode", "xor", false, InputKind::Text, &[], "\"payload,key\" in hex XORed with the repeated key", Handler::Decode(|text| decoded_text("xor", text))),
+    ("mercy_decode", "xor_brute", true, InputKind::Text, &[], "The five single-byte XOR keys giving the most English-like text", Handler::Method(|call, text| xor::xor_brute_from_args(text)
+        .map_err(|e| MercyError::invalid_encoding("xor_brute", e))
+        .and_then(|ranking| result_for(call, Ok(ranking))))),
+    ("mercy_decode", "punycode", false, InputKind::Text, &[], "\"xn--\" labels of a domain to Unicode", Handler::Decode(|text| punycode_decode(text.trim()).map_err(|e| MercyError::invalid_encoding("punycode", e)))),
+    ("mercy_decode", "utf16le", false, InputKind::Text, &[], "UTF-16LE hex or file transcoded to UTF-8", Handler::Decode(|text| charset::transcode_from_args(text, Charset::Utf16Le).map_err(|e| MercyError::invalid_encoding("utf16le", e)))),
+    ("mercy_decode", "utf16be", false, InputKind::Text, &[], "UTF-16BE hex or file transcoded to UTF-8", Handler::Decode(|text| charset::transcode_from_args(text, Charset::Utf16Be).map_err(|e| MercyError::invalid_encoding("utf16be", e)))),
+    ("mercy_decode", "latin1", false, InputKind::Text, &[], "Latin-1 hex or file transcoded to UTF-8", Handler::Decode(|text| charset::transcode_from_args(text, Charset::Latin1).map_err(|e| MercyError::invalid_encoding("latin1", e)))),
+    ("mercy_decode", "windows1252", false, InputKind::Text, &[], "Windows-1252 hex or file transcoded to UTF-8", Handler::Decode(|text| charset::transcode_from_args(text, Charset::Windows1252).map_err(|e| MercyError::invalid_encoding("windows1252", e)))),
+    ("mercy_encode", "base64", false, InputKind::Text, &[], "Standard padded base64", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "base64_url", false, InputKind::Text, &[], "Unpadded URL-safe base64", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "morse", false, InputKind::Text, &[], "Morse code", Handler::Method(|_, text| Ok(morse_encode(text)))),
+    ("mercy_encode", "nato", false, InputKind::Text, &[], "NATO phonetic alphabet", Handler::Method(|_, text| Ok(nato_encode(text)))),
+    ("mercy_encode", "rot", false, InputKind::Text, &[], "Caesar shift of \"13,text\"", Handler::Method(|_, text| cipher::rot_encode_from_args(text).map_err(|e| MercyError::invalid_encoding("rot", e)))),
+    ("mercy_encode", "rot47", false, InputKind::Text, &[], "ROT47 of the printable ASCII range", Handler::Method(|_, text| Ok(cipher::rot47(text)))),
+    ("mercy_encode", "hex", false, InputKind::Text, &[], "Lowercase hex", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "base32", false, InputKind::Text, &[], "Padded RFC 4648 base32", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "base58", false, InputKind::Text, &[], "Bitcoin-alphabet base58", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "base85", false, InputKind::Text, &[], "Adobe ASCII85", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "z85", false, InputKind::Text, &[], "Z85 of text a multiple of 4 bytes long", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "url", false, InputKind::Text, &[], "Percent-encoding of everything but unreserved characters", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "url_form", false, InputKind::Text, &[], "Form data: percent-encoding with spaces as \"+\"", Handler::Method(|call, text| encode_bytes(call, text.as_bytes()))),
+    ("mercy_encode", "html_entity", false, InputKind::Text, &[], "The five reserved HTML characters escaped", Handler::Method(|_, text| Ok(html_entity_encode(text)))),
+    ("mercy_encode", "html_attr", false, InputKind::Text, &[], "Escapes for an HTML attribute value", Handler::Method(|_, text| Ok(html_attr_encode(text)))),
+    ("mercy_encode", "js_string", false, InputKind::Text, &[], "Escapes for a JavaScript string literal", Handler::Method(|_, text| Ok(js_string_encode(text)))),
+    ("mercy_encode", "sql_string", false, InputKind::Text, &[], "Escapes for a SQL string literal", Handler::Method(|_, text| Ok(sql_string_encode(text)))),
+    ("mercy_encode", "cmd_unix", false, InputKind::Text, &[], "One POSIX shell word", Handler::Method(|_, text| Ok(cmd_unix_encode(text)))),
+    ("mercy_encode", "cmd_windows", false, InputKind::Text, &[], "One cmd.exe argument", Handler::Method(|_, text| Ok(cmd_windows_encode(text)))),
+    ("mercy_encode", "punycode", false, InputKind::Text, &[], "Non-ASCII labels of a domain as \"xn--\"", Handler::Method(|_, text| punycode_encode(text.trim()).map_err(|e| MercyError::invalid_encoding("punycode", e)))),
+    ("mercy_encode", "qr", false, InputKind::Text, &[Need::Image], "QR code PNG of the text", Handler::Method(|_, text| qr_encode(text))),
+    ("mercy_hash", "sha2_256", false, InputKind::Text, &[], "SHA-256 of the text", Handler::Digest(sha2_256_hash)),
+    ("mercy_hash", "md5", false, InputKind::Text, &[], "MD5 of the text", Handler::Digest(md5_hash)),
+    ("mercy_hash", "sha1", false, InputKind::Text, &[], "SHA-1 of the text", Handler::Digest(sha1_hash)),
+    ("mercy_hash", "sha2_512", false, InputKind::Text, &[], "SHA-512 of the text", Handler::Digest(sha2_512_hash)),
+    ("mercy_hash", "sha3_256", false, InputKind::Text, &[], "SHA3-256 of the text", Handler::Digest(|text| sha3_hash(text, 32))),
+    ("mercy_hash", "sha3_512", false, InputKind::Text, &[], "SHA3-512 of the text", Handler::Digest(|text| sha3_hash(text, 64))),
+    ("mercy_hash", "ssdeep", false, InputKind::Text, &[], "ssdeep fuzzy hash of the text", Handler::Digest(|text| ssdeep_hash(text.as_bytes()))),
+    ("mercy_hash", "fuzzy", false, InputKind::Text, &[], "ssdeep fuzzy hash of the text", Handler::Digest(|text| ssdeep_hash(text.as_bytes()))),
+    ("mercy_hash", "crc32", false, InputKind::Text, &[], "CRC-32 (IEEE) of the text", Handler::Digest(crc32_hash)),
+    ("mercy_hash", "xxhash64", false, InputKind::Text, &[], "xxHash64 of the text", Handler::Digest(xxhash64_hash)),
+    ("mercy_hash", "xxhash64_seeded", false, InputKind::Text, &[], "xxHash64 of \"seed=42 text\"", Handler::Method(|_, input| text_result(hashing::xxhash64_seeded_from_args(input)))),
+    ("mercy_hash", "all", false, InputKind::Text, &[], "Every digest of the text, one per line", Handler::Method(|_, input| Ok(all_hashes(input)))),
+    ("mercy_hash", "sha2_256_file", false, InputKind::FilePath, &[], "SHA-256 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "md5_file", false, InputKind::FilePath, &[], "MD5 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha1_file", false, InputKind::FilePath, &[], "SHA-1 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha2_512_file", false, InputKind::FilePath, &[], "SHA-512 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha3_256_file", false, InputKind::FilePath, &[], "SHA3-256 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha3_512_file", false, InputKind::FilePath, &[], "SHA3-512 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "ssdeep_file", false, InputKind::FilePath, &[], "ssdeep fuzzy hash of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "fuzzy_file", false, InputKind::FilePath, &[], "ssdeep fuzzy hash of a file", Handler::Method(|_, input| text_result(hashing::hash_file_from_args("ssdeep_file", input)))),
+    ("mercy_hash", "crc32_file", false, InputKind::FilePath, &[], "CRC-32 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "xxhash64_file", false, InputKind::FilePath, &[], "xxHash64 of a file", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha2_256_gz", false, InputKind::FilePath, &[], "SHA-256 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "md5_gz", false, InputKind::FilePath, &[], "MD5 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha1_gz", false, InputKind::FilePath, &[], "SHA-1 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha2_512_gz", false, InputKind::FilePath, &[], "SHA-512 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha3_256_gz", false, InputKind::FilePath, &[], "SHA3-256 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "sha3_512_gz", false, InputKind::FilePath, &[], "SHA3-512 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "crc32_gz", false, InputKind::FilePath, &[], "CRC-32 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "xxhash64_gz", false, InputKind::FilePath, &[], "xxHash64 of a gzip file's contents", Handler::Method(|call, input| text_result(hashing::hash_file_from_args(call, input)))),
+    ("mercy_hash", "verify_manifest", true, InputKind::FilePath, &[], "Every file of a checksum manifest checked", Handler::Method(|call, input| result_for(call, manifest::verify_manifest_from_args(input)))),
+    ("mercy_hash_compare", "fuzzy", false, InputKind::Text, &[], "Similarity of two ssdeep digests, 0 to 100", Handler::ByName),
+    ("mercy_hash_compare", "ssdeep", false, InputKind::Text, &[], "Similarity of two ssdeep digests, 0 to 100", Handler::ByName),
+    ("mercy_hash_compare", "fuzzy_file", false, InputKind::FilePath, &[], "ssdeep similarity of two files, 0 to 100", Handler::ByName),
+    ("mercy_hash_compare", "ssdeep_file", false, InputKind::FilePath, &[], "ssdeep similarity of two files, 0 to 100", Handler::ByName),
+    ("mercy_hmac", "hmac_sha256", false, InputKind::Text, &[], "HMAC-SHA256 under a text key", Handler::ByName),
+    ("mercy_hmac", "hmac_sha256_hexkey", false, InputKind::Text, &[], "HMAC-SHA256 under a hex key", Handler::ByName),
+    ("mercy_hmac", "hmac_sha512", false, InputKind::Text, &[], "HMAC-SHA512 under a text key", Handler::ByName),
+    ("mercy_hmac", "hmac_sha512_hexkey", false, InputKind::Text, &[], "HMAC-SHA512 under a hex key", Handler::ByName),
+    ("mercy_hmac", "hmac_sha1", false, InputKind::Text, &[], "HMAC-SHA1 under a text key", Handler::ByName),
+    ("mercy_hmac", "hmac_sha1_hexkey", false, InputKind::Text, &[], "HMAC-SHA1 under a hex key", Handler::ByName),
+    ("mercy_hmac", "hmac_md5", false, InputKind::Text, &[], "HMAC-MD5 under a text key", Handler::ByName),
+    ("mercy_hmac", "hmac_md5_hexkey", false, InputKind::Text, &[], "HMAC-MD5 under a hex key", Handler::ByName),
+    ("mercy_hex", "hex_dump", false, InputKind::FilePath, &[], "xxd-style hex dump of a file or a slice of it", Handler::Method(|_, file| collect_file_hex(file))),
+    ("mercy_hex", "hex_dump_gz", false, InputKind::FilePath, &[], "Hex dump of a gzip file's contents", Handler::Method(|_, file| gzip_hex_bytes(file).map(|(bytes, offset)| hexdump_bytes(&bytes, offset)))),
+    ("mercy_hex", "hex_diff", true, InputKind::FilePaths, &[], "Byte regions that differ between \"old,new\" files", Handler::Method(|call, file| result_for(call, hexdiff::hex_diff_from_args(file)))),
+    ("mercy_malicious", "status", true, InputKind::Domain, &[Need::Network], "InQuest classification of a domain", Handler::Lookup(|call, domain| Box::pin(async move {
+        let report = malicious_domain_report(&domain).await;
+        if call == "status_json" { json_result(report) } else { text_result(report.map(|report| report.status().to_string())) }
+    }))),
+    ("mercy_malicious", "status_save", false, InputKind::Domain, &[Need::Network, Need::Temp], "InQuest classification, keeping the raw response in a file", Handler::Lookup(|_, domain| Box::pin(async move { text_result(malicious_domain_save(&domain).await) }))),
+    ("mercy_malicious", "hash_status", true, InputKind::Text, &[Need::Network], "What InQuest knows of a file hash", Handler::Lookup(|call, hash| Box::pin(async move { result_for(&call, malicious_hash_report(&hash).await) }))),
+    ("mercy_malicious", "status_offline", true, InputKind::Domain, &[], "Domain or IP checked against local blocklist files", Handler::Lookup(|call, input| Box::pin(async move {
+        // A large list takes a moment to load the first time, so it is read off the runtime's worker threads
+        let check = tokio::task::spawn_blocking(move || blocklist::status_offline_from_args(&input)).await
+            .unwrap_or_else(|e| Err(format!("Blocklist check task failed: {}", e)));
+        result_for(&call, check)
+    }))),
+    ("mercy_malicious", "sinkhole_check", true, InputKind::Domain, &[Need::Resolver], "Whether a domain resolves or delegates to a sinkhole", Handler::Lookup(|call, input| Box::pin(async move {
+        // DNS queries block, so the check runs off the runtime's worker threads
+        let check = tokio::task::spawn_blocking(config::propagate_fn(move || sinkhole::sinkhole_check_from_args(&input))).await
+            .unwrap_or_else(|e| Err(format!("Sinkhole check task failed: {}", e)));
+        result_for(&call, check)
+    }))),
+    ("mercy_forensics", "timeline", false, InputKind::FilePath, &[], "MACB timeline of a directory tree", Handler::Report(|_, input| report_text(notify::notified("timeline", input, timeline::timeline_from_args)))),
+    ("mercy_forensics", "prefetch", true, InputKind::FilePath, &[], "Run count, run times and files of a Windows prefetch file", Handler::Report(|call, input| report_for(call, mercy_prefetch(input)))),
+    ("mercy_forensics", "shimcache", true, InputKind::FilePath, &[], "AppCompatCache entries of an exported SYSTEM hive", Handler::Report(|call, input| report_for(call, mercy_shimcache(input)))),
+    ("mercy_forensics", "evtx", false, InputKind::FilePath, &[], "Windows event log records as NDJSON", Handler::Report(|_, input| report_text(notify::notified("evtx", input, evtx::evtx_from_args)))),
+    ("mercy_forensics", "carve", true, InputKind::FilePath, &[], "PE, PNG, JPEG, ZIP and PDF objects embedded in a blob", Handler::Report(|call, input| report_for(call, notify::notified("carve", input, carve::carve_from_args)))),
+    ("mercy_forensics", "minidump", false, InputKind::FilePath, &[], "Modules, threads and strings of a process minidump", Handler::Report(|_, input| report_text(notify::notified("minidump", input, minidump::minidump_from_args)))),
+    ("mercy_forensics", "apk", true, InputKind::FilePath, &[], "Identity, permissions, components and signers of an APK", Handler::Report(|call, input| report_for(call, mercy_apk(input)))),
+    ("mercy_forensics", "oci", false, InputKind::FilePath, &[], "Config, layers and history of a container image tarball", Handler::Report(|_, input| report_json(mercy_oci(input)))),
+    ("mercy_forensics", "oci_scan", false, InputKind::FilePath, &[], "Secrets and indicators in a container image's layers", Handler::Report(|_, input| report_json(notify::notified("oci_scan", input, oci::oci_scan_from_args)))),
+    ("mercy_forensics", "secrets_git", false, InputKind::FilePath, &[Need::Git], "Secrets added anywhere in a git repository's history", Handler::Report(|_, input| report_text(notify::notified("secrets_git", input, gitsecrets::secrets_git_from_args)))),
+    ("mercy_forensics", "entropy_profile", false, InputKind::FilePath, &[], "Sliding-window entropy of a file", Handler::Report(|_, input| report_json(entropy::entropy_profile_from_args(input)))),
+    ("mercy_parse", "plist", false, InputKind::FilePath, &[], "XML or binary property list as JSON", Handler::Report(|_, input| report_json(mercy_plist(input)))),
+    ("mercy_parse", "mobileconfig", true, InputKind::FilePath, &[], "Payloads, certificates and signer of a configuration profile", Handler::Report(|call, input| report_for(call, mercy_mobileconfig(input)))),
+    ("mercy_parse", "sbom", false, InputKind::FilePath, &[], "Components of a CycloneDX or SPDX SBOM", Handler::Report(|_, input| report_json(mercy_sbom(input)))),
+    ("mercy_parse", "sbom_check", false, InputKind::FilePath, &[Need::Network], "Components of an SBOM affected by OSV advisories", Handler::Report(|_, input| report_json(sbom::sbom_check_from_args(input)))),
+    ("mercy_parse", "qr", true, InputKind::FilePath, &[Need::Image], "Payloads of the QR codes in a PNG or JPEG", Handler::Report(qr_decode)),
+    ("mercy_parse", "zonefile", true, InputKind::FilePath, &[], "Records of a BIND zone file", Handler::Report(|call, input| report_for(call, zonefile::zonefile_from_args(input)))),
+    ("mercy_parse", "zone_audit", true, InputKind::FilePath, &[], "Risky records and missing policies of a zone file", Handler::Report(|call, input| report_for(call, zonefile::zone_audit_from_args(input)))),
+    ("mercy_solve", "substitution", false, InputKind::Text, &[], "Best monoalphabetic substitution key and plaintext", Handler::Report(|_, input| report_json(cipher::substitution_from_args(input)))),
+    ("mercy_generate", "fuzz_strings", false, InputKind::Text, &[], "Classic problem strings as NDJSON", Handler::Report(|_, input| report_text(fuzz::fuzz_strings_from_args(input)))),
+    ("mercy_generate", "fuzz_bytes", false, InputKind::Text, &[], "Seeded byte-level mutations of the input as NDJSON", Handler::Report(|_, input| report_text(fuzz::fuzz_bytes_from_args(input)))),
+    ("mercy_canary", "generate", true, InputKind::Text, &[], "A new DNS, URL or AWS key canary", Handler::Report(|call, input| report_for(call, canary::generate_from_args(input)))),
+    ("mercy_canary", "parse_trigger", true, InputKind::Text, &[], "The canary that fired in a log line", Handler::Report(|call, input| report_for(call, mercy_canary_parse_trigger(input)))),
+    ("mercy_extra", "internal_ip", false, InputKind::None, &[Need::Network], "Address of the interface the default route leaves from", Handler::Report(|_, _| internal_ip())),
+    ("mercy_extra", "external_ip", false, InputKind::Text, &[Need::Network], "Public IPv4 address from IP echo services", Handler::Report(|_, input| report_text(publicip::external_ip_from_args(input, false)))),
+    ("mercy_extra", "external_ipv6", false, InputKind::Text, &[Need::Network], "Public IPv6 address from IP echo services", Handler::Report(|_, input| report_text(publicip::external_ip_from_args(input, true)))),
+    ("mercy_extra", "system_info", false, InputKind::Text, &[Need::Proc], "A host metric such as \"memory\" or \"uptime\", or \"all\"", Handler::Report(|_, input| system_info(input))),
+    ("mercy_extra", "defang", false, InputKind::Text, &[], "URLs, domains, IPs and emails made safe to share", Handler::Report(|_, input| defang(input))),
+    ("mercy_extra", "refang", false, InputKind::Text, &[], "Defanged indicators made live again", Handler::Report(|_, input| refang(input))),
+    ("mercy_extra", "whois", true, InputKind::Domain, &[Need::Network], "WHOIS record of a domain or IP, following referrals", Handler::Report(|call, input| report_for(call, mercy_whois(input)))),
+    ("mercy_extra", "traceroute", true, InputKind::Domain, &[Need::Network, Need::Traceroute], "Network path to a host", Handler::Report(|call, input| report_for(call, mercy_traceroute(input, &TracerouteOptions::default())))),
+    ("mercy_extra", "ping", true, InputKind::Domain, &[Need::Network, Need::Ping], "ICMP echo statistics of a host", Handler::Report(|call, input| report_for(call, ping_from_args(input)))),
+    ("mercy_extra", "smb_check", true, InputKind::Domain, &[Need::Network], "SMB dialect, SMB1 acceptance and signing", Handler::Report(|call, input| report_for(call, mercy_smb_check(input)))),
+    ("mercy_extra", "rdp_check", true, InputKind::Domain, &[Need::Network], "RDP, TLS and NLA support", Handler::Report(|call, input| report_for(call, mercy_rdp_check(input)))),
+    ("mercy_extra", "ftp_check", true, InputKind::Domain, &[Need::Network], "FTP banner, anonymous login and features", Handler::Report(|call, input| report_for(call, mercy_ftp_check(input)))),
+    ("mercy_extra", "telnet_check", true, InputKind::Domain, &[Need::Network], "Telnet login banner", Handler::Report(|call, input| report_for(call, mercy_telnet_check(input)))),
+    ("mercy_extra", "os_guess", true, InputKind::Text, &[Need::Network, Need::Fingerprint], "Coarse OS family from TTL and window size", Handler::Report(|call, input| report_for(call, osguess::os_guess_from_args(input)))),
+    ("mercy_extra", "freq_analysis", false, InputKind::Text, &[], "Letter frequencies and index of coincidence of a ciphertext", Handler::Report(|_, input| report_json(mercy_freq_analysis(input)))),
+    ("mercy_extra", "caesar_solve", false, InputKind::Text, &[], "Most English-like Caesar shift of a ciphertext", Handler::Report(|_, input| report_json(mercy_caesar_solve(input)))),
+    ("mercy_extra", "entropy", false, InputKind::Text, &[], "Shannon entropy of the text in bits per byte", Handler::Report(|_, input| format!("{:.2}", entropy_bytes(input.as_bytes())))),
+    ("mercy_extra", "entropy_file", false, InputKind::FilePath, &[], "Shannon entropy of a file in bits per byte", Handler::Report(|_, input| report_text(mercy_entropy_file(input.trim()).map(|entropy| format!("{:.2}", entropy))))),
+    ("mercy_extra", "entropy_sections", true, InputKind::FilePath, &[], "Entropy of each block of a file, high ones marked", Handler::Report(|call, input| report_for(call, entropy::entropy_sections_from_args(input)))),
+    ("mercy_extra", "strings", true, InputKind::FilePath, &[], "ASCII and UTF-16LE strings of a file", Handler::Report(|call, input| report_for(call, strings::strings_from_args(input)))),
+    ("mercy_extra", "file_type", true, InputKind::FilePath, &[], "What a file is from its magic bytes", Handler::Report(|call, input| report_for(call, mercy_file_type(input.trim())))),
+    ("mercy_extra", "detect_encoding", true, InputKind::FilePath, &[], "Likely text encoding of a file", Handler::Report(|call, input| report_for(call, mercy_detect_encoding(input.trim())))),
+    ("mercy_extra", "exif", true, InputKind::FilePath, &[], "EXIF tags, GPS position and appended data of an image", Handler::Report(|call, input| report_for(call, mercy_exif(input)))),
+    ("mercy_extra", "zip_list", true, InputKind::FilePath, &[], "Entries of a ZIP archive", Handler::Report(|call, input| report_for(call, mercy_zip_list(input.trim())))),
+    ("mercy_extra", "stego_check", false, InputKind::FilePath, &[Need::Image], "Signs of data hidden in a PNG or JPEG", Handler::Report(|_, input| stego_check(input))),
+    ("mercy_extra", "unicode_inspect", true, InputKind::Text, &[], "Non-ASCII, invisible and bidi characters of the text", Handler::Report(|call, input| report_for(call, Ok(mercy_unicode_inspect(input))))),
+    ("mercy_extra", "unicode_inspect_file", true, InputKind::FilePath, &[], "Non-ASCII, invisible and bidi characters of a source file", Handler::Report(|call, input| report_for(call, mercy_unicode_inspect_file(input)))),
+    ("mercy_extra", "safe_display", true, InputKind::Text, &[], "Untrusted text on one line, safe for terminals", Handler::Report(|call, input| report_for(call, safedisplay::safe_display_from_args(input)))),
+    ("mercy_extra", "safe_display_html", false, InputKind::Text, &[], "Untrusted text on one line, safe for HTML", Handler::Report(|_, input| report_text(safedisplay::safe_display_from_args(input).map(|display| display.html)))),
+    ("mercy_extra", "url_parse", true, InputKind::Text, &[], "Parts of a URL as a browser reads it", Handler::Report(|call, input| report_for(call, mercy_url_parse(input)))),
+    ("mercy_extra", "url_normalize", false, InputKind::Text, &[], "A URL normalized for comparison", Handler::Report(|_, input| report_text(mercy_url_normalize(input)))),
+    ("mercy_extra", "homograph_check", true, InputKind::Text, &[], "Mixed scripts and lookalike letters in a domain", Handler::Report(|call, input| report_for(call, mercy_homograph_check(input)))),
+    ("mercy_extra", "strip_ansi", false, InputKind::Text, &[], "The text without ANSI escape sequences", Handler::Report(|_, input| mercy_strip_ansi(input))),
+    ("mercy_extra", "ansi_inspect", true, InputKind::Text, &[], "Escape sequences in the text, dangerous ones rated", Handler::Report(|call, input| report_for(call, Ok(mercy_ansi_inspect(input))))),
+    ("mercy_extra", "epoch_to_utc", true, InputKind::Text, &[], "A Unix epoch value as UTC", Handler::Report(|call, input| report_for(call, epoch_to_utc(input)))),
+    ("mercy_extra", "utc_to_epoch", true, InputKind::Text, &[], "An ISO 8601 time as Unix seconds", Handler::Report(|call, input| match call {
+        "utc_to_epoch_json" => report_json(utc_to_epoch(input)),
+        _ => report_text(utc_to_epoch(input).map(|conversion| conversion.epoch_seconds()))
+    })),
+    ("mercy_extra", "filetime_to_utc", true, InputKind::Text, &[], "A Windows FILETIME as UTC", Handler::Report(|call, input| report_for(call, filetime_to_utc(input)))),
+    ("mercy_extra", "normalize_timestamps", false, InputKind::FilePath, &[], "Each line of a log prefixed with its time in UTC", Handler::Report(|_, input| report_text(notify::notified("normalize_timestamps", input, logtime::normalize_timestamps_from_args)))),
+    ("mercy_extra", "logs_merge", false, InputKind::FilePaths, &[], "Log files merged into one chronological timeline", Handler::Report(|_, input| report_text(notify::notified("logs_merge", input, logmerge::logs_merge_from_args)))),
+    ("mercy_extra", "cluster", false, InputKind::FilePath, &[], "Duplicate and similar files under a directory", Handler::Report(|_, input| report_json(notify::notified("cluster", input, cluster::cluster_from_args)))),
+    ("mercy_extra", "asn", true, InputKind::Text, &[Need::Resolver], "Origin ASN of IPs from Team Cymru's DNS service", Handler::Report(|call, input| report_for(call, notify::notified("asn", input, asn::asn_from_args)))),
+    ("mercy_extra", "asn_lookup", true, InputKind::Text, &[Need::Network], "ASN of an IP from Team Cymru's WHOIS service", Handler::Report(|call, input| report_for(call, asn::asn_lookup_from_args(input)))),
+    ("mercy_extra", "asn_bulk", true, InputKind::Text, &[Need::Network], "ASNs of many IPs in one Team Cymru WHOIS query", Handler::Report(|call, input| report_for(call, asn::asn_bulk_from_args(input)))),
+    ("mercy_extra", "bogon_check", true, InputKind::Text, &[], "Whether an IP or prefix is bogon space", Handler::Report(|call, input| report_for(call, bogon::bogon_check_from_args(input)))),
+    ("mercy_extra", "prefix_contains", true, InputKind::Text, &[], "Which of a set of prefixes contains each IP", Handler::Report(|call, input| report_for(call, prefixset::prefix_contains_from_args(input)))),
+    ("mercy_extra", "dns_lookup", true, InputKind::Domain, &[Need::Resolver], "A and AAAA records of a domain", Handler::Report(|call, input| report_for(call, dns::dns_lookup_from_args(input)))),
+    ("mercy_extra", "reverse_dns", true, InputKind::Domain, &[Need::Resolver], "PTR host name of an IP", Handler::Report(|call, input| report_for(call, dns::reverse_dns_from_args(input)))),
+    ("mercy_extra", "port_check", true, InputKind::HostPort, &[Need::Network], "Whether a TCP port is open, closed or filtered", Handler::Report(|call, input| report_for(call, portscan::port_check_from_args(input)))),
+    ("mercy_extra", "port_scan", true, InputKind::Text, &[Need::Network], "Open TCP ports of \"host,start-end\"", Handler::Report(|call, input| report_for(call, portscan::port_scan_from_args(input)))),
+    ("mercy_extra", "banner_grab", true, InputKind::HostPort, &[Need::Network], "What a TCP service sends first", Handler::Report(|call, input| report_for(call, banner::banner_grab_from_args(input)))),
+    ("mercy_extra", "ip_in_cidr", true, InputKind::Text, &[], "Whether \"address,cidr\" has the address inside", Handler::Report(|call, input| report_for(call, ipcalc::ip_in_cidr_from_args(input)))),
+    ("mercy_extra", "cidr_range", true, InputKind::Text, &[], "Netmask, bounds and size of a CIDR block", Handler::Report(|call, input| report_for(call, mercy_cidr_range(input)))),
+    ("mercy_extra", "is_private", true, InputKind::Text, &[], "Whether an IP is private, loopback or link-local", Handler::Report(|call, input| report_for(call, mercy_is_private(input)))),
+    ("mercy_extra", "redact", false, InputKind::Text, &[], "Internal addresses, hostnames and usernames replaced", Handler::Report(|_, input| redact::redact_from_args(input))),
+    ("mercy_extra", "stats", false, InputKind::Text, &[], "Operation counters, \"reset\" to zero them", Handler::Report(|_, input| match input.trim() {
+        "reset" => report_json(Ok(reset_mercy_stats())),
+        _ => report_json(Ok(mercy_stats()))
+    })),
+    ("mercy_extra", "domain_category", true, InputKind::Domain, &[], "Rough category of a domain from the words in its name", Handler::Report(|call, input| report_for(call, domaincat::domain_category_from_args(input)))),
+    ("mercy_extra", "notify", true, InputKind::Text, &[Need::Network], "A payload POSTed to a webhook", Handler::Report(|call, input| report_for(call, notify::notify_from_args(input)))),
+    ("mercy_extra", "listen", false, InputKind::Text, &[Need::Network], "A fake SSH, HTTP or echo service, logging each connection", Handler::Report(|_, input| report_text(listen::listen_from_args(input)))),
+    ("mercy_extra", "sri", true, InputKind::Text, &[Need::Network], "Subresource Integrity value of a URL or file", Handler::Report(|call, input| match call {
+        "sri_json" => report_json(sri::sri_from_args(input).map(|(hashes, _)| hashes)),
+        _ => report_text(sri::sri_from_args(input).map(|(hashes, algorithm)| match algorithm {
+            Some(algorithm) => hashes.get(algorithm).to_string(),
+            None => hashes.to_string()
+        }))
+    })),
+    ("mercy_extra", "sri_audit", true, InputKind::Text, &[Need::Network], "Script and stylesheet tags of a page without integrity", Handler::Report(|call, input| report_for(call, sri::sri_audit_from_args(input)))),
+    ("mercy_extra", "csp_audit", true, InputKind::Text, &[], "Weaknesses of a Content-Security-Policy", Handler::Report(|call, input| report_for(call, mercy_csp_audit(input)))),
+    ("mercy_extra", "http_headers", true, InputKind::Url, &[Need::Network], "Redirect chain, status and response headers of a URL", Handler::Report(|call, input| report_for(call, httpheaders::http_headers_from_args(input)))),
+    ("mercy_extra", "security_headers", true, InputKind::Url, &[Need::Network], "Security headers of a URL graded", Handler::Report(|call, input| report_for(call, httpheaders::security_headers_from_args(input)))),
+    ("mercy_extra", "hsts_check", true, InputKind::Domain, &[Need::Network], "HSTS preload eligibility of a domain", Handler::Report(|call, input| report_for(call, hsts::hsts_check_from_args(input)))),
+    ("mercy_extra", "tls_cert", true, InputKind::Domain, &[Need::Network], "Leaf certificate of a TLS service", Handler::Report(|call, input| report_for(call, tlscert::tls_cert_from_args(input)))),
+    ("mercy_extra", "triage", true, InputKind::Text, &[Need::Network, Need::Resolver], "One-shot triage of a domain, IP, URL, email or hash", Handler::Report(|call, input| report_for(call, triage::triage_from_args(input)))),
+    ("mercy_extra", "snapshot_info", true, InputKind::FilePath, &[], "Schema version and sections of a snapshot file", Handler::Report(|call, input| report_for(call, snapshot::snapshot_info_from_args(input)))),
+    ("mercy_extra", "password_strength", true, InputKind::Text, &[], "Offline strength estimate of a password", Handler::Report(|call, input| report_for(call, Ok(mercy_password_strength(input))))),
+    ("mercy_extra", "hibp_check", true, InputKind::Text, &[Need::Network], "Breach count of a password by k-anonymity", Handler::Report(|call, input| report_for(call, password::hibp_check_from_args(input)))),
+    ("mercy_extra", "hash_identify", true, InputKind::Text, &[], "Candidate algorithms of a digest with hashcat modes", Handler::Report(|call, input| report_for(call, mercy_hash_identify(input)))),
+    ("mercy_extra", "email_headers", true, InputKind::Text, &[], "Phishing read of a raw email header block", Handler::Report(|call, input| report_for(call, mercy_email_headers(input)))),
+    ("mercy_extra", "secret_scan", true, InputKind::Text, &[], "Exposed credentials in a file or the text, masked", Handler::Report(|call, input| report_for(call, secretscan::secret_scan_from_args(input)))),
+    ("mercy_extra", "pe_info", true, InputKind::FilePath, &[], "Headers, sections and imports of a Windows executable", Handler::Report(|call, input| report_for(call, mercy_pe_info(input)))),
+    ("mercy_extra", "pcap_summary", true, InputKind::FilePath, &[], "Packets, hosts and protocols of a pcap or pcapng capture", Handler::Report(|call, input| report_for(call, mercy_pcap_summary(input)))),
+    ("mercy_extra", "mac_lookup", true, InputKind::Text, &[], "Vendor of a MAC address", Handler::Report(|call, input| report_for(call, mercy_mac_lookup(input)))),
+    ("mercy_extra", "mac_lookup_file", true, InputKind::Text, &[], "Vendor of \"mac,csv_path\" from an IEEE registry file", Handler::Report(|call, input| report_for(call, mac::mac_lookup_file_from_args(input)))),
+    ("mercy_extra", "uuid_v4", false, InputKind::Text, &[], "A random version 4 UUID", Handler::Report(|_, input| report_text(uuid::uuid_v4_from_args(input)))),
+    ("mercy_extra", "uuid_v5", false, InputKind::Text, &[], "Name-based version 5 UUID of \"namespace,name\"", Handler::Report(|_, input| report_text(uuid::uuid_v5_from_args(input)))),
+    ("mercy_extra", "uuid_inspect", true, InputKind::Text, &[], "Version, variant and embedded time of a UUID", Handler::Report(|call, input| report_for(call, mercy_uuid_inspect(input)))),
+    ("mercy_extra", "subdomains", true, InputKind::Domain, &[Need::Network], "Hostnames under a domain from certificate transparency", Handler::Report(|call, input| report_for(call, subdomains::subdomains_from_args(input)))),
+    ("mercy_extra", "methods", true, InputKind::Text, &[], "The calls of a dispatcher and what each takes", Handler::Report(|call, input| match call {
+        "methods_json" => report_json(methods_from_args(input)),
+        _ => report_text(methods_from_args(input).map(|methods| methods.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n")))
+    })),
+    ("mercy_extra", "capabilities", true, InputKind::None, &[], "Which calls can work in this environment", Handler::Report(|call, _| report_for(call, Ok(mercy_capabilities())))),
+    ("mercy_extra", "selftest", true, InputKind::Text, &[Need::Network], "Checks that the toolbox works here", Handler::Report(|call, input| report_for(call, selftest::selftest_from_args(input)))),
+    ("mercy_discover", "sweep", true, InputKind::Text, &[Need::Network, Need::Sweep], "Live hosts of a CIDR block", Handler::Report(|call, input| report_for(call, mercy_sweep(input, &SweepOptions::default(), |_| {}))))
+];
+
+/// Whether a call can be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CapabilityStatus {
+    Available,
+    /// Works, with a fallback or reduced results (see the reason)
+    Degraded,
+    Unavailable
+}
+
+impl fmt::Display for CapabilityStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapabilityStatus::Available => write!(f, "available"),
+            CapabilityStatus::Degraded => write!(f, "degraded"),
+            CapabilityStatus::Unavailable => write!(f, "unavailable")
+        }
+    }
+}
+
+/// One dispatcher call and whether it can work here
+#[derive(Debug, Clone, Serialize)]
+pub struct Capability {
+    /// Dispatcher function, e.g. "mercy_extra"
+    pub dispatcher: String,
+    /// Call name passed to the dispatcher
+    pub call: String,
+    pub status: CapabilityStatus,
+    /// Needs outbound connections, which are not probed
+    pub network: bool,
+    /// Why the call is degraded or unavailable
+    pub reason: Option<String>
+}
+
+/// One call of a dispatcher, as `mercy_methods` lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodInfo {
+    /// Call name passed to the dispatcher
+    pub name: String,
+    /// One line on what the call gives
+    pub description: String,
+    pub input: InputKind,
+    /// Sends or receives on the network, DNS queries included
+    pub network: bool
+}
+
+impl fmt::Display for MethodInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} - {} (input: {}{})", self.name, self.description, self.input, if self.network { ", network" } else { "" })
+    }
+}
+
+/// What the probes found about the environment
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityEnvironment {
+    /// Target OS and architecture
+    pub platform: String,
+    /// Optional crate features compiled in
+    pub features: Vec<String>,
+    /// "raw", "datagram" or "unavailable: <reason>"
+    pub icmp_v4: String,
+    pub icmp_v6: String,
+    pub proc_fs: bool,
+    pub resolv_conf: bool,
+    /// Server used when a call names none
+    pub dns_server: String,
+    pub temp_writable: bool,
+    /// Path of the git executable found on PATH
+    pub git: Option<String>,
+    /// A default HTTP identity was set with `set_http_identity`
+    pub http_identity: bool,
+    /// A sinkhole list was set with `set_sinkhole_list`
+    pub custom_sinkhole_list: bool,
+    /// Dispatcher results are redacted (`set_redaction`)
+    pub redaction: bool
+}
+
+/// Usable calls and the environment they were judged against
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityReport {
+    pub environment: CapabilityEnvironment,
+    pub capabilities: Vec<Capability>
+}
+
+impl CapabilityReport {
+    /// Status of a call, None when the dispatcher has no such call
+    pub fn status(&self, dispatcher: &str, call: &str) -> Option<CapabilityStatus> {
+        self.capabilities.iter().find(|capability| capability.dispatcher == dispatcher && capability.call == call).map(|capability| capability.status)
+    }
+
+    /// Whether a call is available, possibly degraded
+    pub fn usable(&self, dispatcher: &str, call: &str) -> bool {
+        matches!(self.status(dispatcher, call), Some(CapabilityStatus::Available | CapabilityStatus::Degraded))
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let environment = &self.environment;
+        let features = if environment.features.is_empty() { "none".to_string() } else { environment.features.join(", ") };
+
+        writeln!(f, "Platform: {} (features: {})", environment.platform, features)?;
+        writeln!(f, "ICMP: IPv4 {}, IPv6 {}", environment.icmp_v4, environment.icmp_v6)?;
+        writeln!(f, "DNS server: {}{}", environment.dns_server, if environment.resolv_conf { "" } else { " (no /etc/resolv.conf)" })?;
+        writeln!(f, "/proc: {}, temp directory writable: {}, git: {}", yes_no(environment.proc_fs), yes_no(environment.temp_writable), environment.git.as_deref().unwrap_or("not found"))?;
+
+        let limited: Vec<&Capability> = self.capabilities.iter().filter(|capability| capability.status != CapabilityStatus::Available).collect();
+        writeln!(f, "{} of {} calls available without limits", self.capabilities.len() - limited.len(), self.capabilities.len())?;
+
+        for capability in limited {
+            writeln!(f, "  {} {}: {} ({})", capability.dispatcher, capability.call, capability.status, capability.reason.as_deref().unwrap_or(""))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Probes the environment and reports which dispatcher calls can work here; sends nothing on the network
+pub fn mercy_capabilities() -> CapabilityReport {
+    let icmp_v4 = icmp::icmp_access(false);
+    let icmp_v6 = icmp::icmp_access(true);
+    let environment = probe_environment(&icmp_v4, &icmp_v6);
+
+    let mut capabilities = Vec::new();
+    for (dispatcher, call, json, _, needs, ..) in CALLS {
+        let (status, reason) = assess(needs, &environment, &icmp_v4);
+        let network = networked(needs);
+        let capability = Capability { dispatcher: dispatcher.to_string(), call: call.to_string(), status, network, reason };
+
+        if *json {
+            capabilities.push(Capability { call: format!("{}_json", call), ..capability.clone() });
+        }
+        capabilities.push(capability);
+    }
+    capabilities.sort_by(|a, b| a.dispatcher.cmp(&b.dispatcher).then(a.call.cmp(&b.call)));
+
+    CapabilityReport { environment, capabilities }
+}
+
+/// The calls of a dispatcher ("extra" or "mercy_extra", "decode", "hash"...) in the order of its documentation, each `_json` variant after its call; calls needing a feature not compiled in are left out, and an unknown dispatcher has none
+pub fn mercy_methods(module: &str) -> Vec<MethodInfo> {
+    let dispatcher = dispatcher_name(module);
+    let mut methods = Vec::new();
+
+    for (name, call, json, input, needs, description, _) in CALLS {
+        if *name != dispatcher || !compiled(needs) {
+            continue;
+        }
+
+        let method = MethodInfo { name: call.to_string(), description: description.to_string(), input: *input, network: networked(needs) };
+        if *json {
+            methods.push(method.clone());
+            methods.push(MethodInfo { name: format!("{}_json", call), description: format!("{} as JSON", description), ..method });
+        } else {
+            methods.push(method);
+        }
+    }
+
+    methods
+}
+
+/// Checks, without running it, that `call` is a call of the dispatcher `module` and that `input` looks like what it takes: an existing file, "host:port", a domain name or IP address, or an http(s) URL before any "key=value" options; text is always accepted
+pub fn mercy_validate(module: &str, call: &str, input: &str) -> Result<(), MercyError> {
+    let dispatcher = dispatcher_name(module);
+    let checked = match method(&dispatcher, call) {
+        Some((.., input_kind, needs, _, _)) if compiled(needs) => check_input(*input_kind, input),
+        _ => Err(MercyError::unsupported(&dispatcher, call))
+    };
+
+    checked.map_err(|e| e.with_context(&dispatcher, call, input, false))
+}
+
+// The `methods` call: the calls of the dispatcher named by the input
+pub(crate) fn methods_from_args(input: &str) -> Result<Vec<MethodInfo>, String> {
+    let methods = mercy_methods(input.trim());
+    match methods.is_empty() {
+        true => Err(format!("Unknown dispatcher '{}' (try decode, encode, hash, hex, malicious or extra)", input.trim())),
+        false => Ok(methods)
+    }
+}
+
+// "mercy_decode" for "decode" or "mercy_decode"
+pub(crate) fn dispatcher_name(module: &str) -> String {
+    match module.strip_prefix("mercy_") {
+        Some(_) => module.to_string(),
+        None => format!("mercy_{}", module)
+    }
+}
+
+// The handler of the dispatcher's call or of the call whose `_json` variant it names; none for a
+// call the table does not list or whose feature is not compiled in
+pub(crate) fn handler(dispatcher: &str, call: &str) -> Option<Handler> {
+    match method(dispatcher, call) {
+        Some((.., needs, _, handler)) if compiled(needs) => Some(*handler),
+        _ => None
+    }
+}
+
+// Whether the dispatcher's call has a `_json` variant
+pub(crate) fn has_json_variant(dispatcher: &str, call: &str) -> bool {
+    CALLS.iter().any(|(name, listed, json, ..)| *name == dispatcher && *listed == call && *json)
+}
+
+// The table entry of a call, or of the call whose `_json` variant it is
+fn method(dispatcher: &str, call: &str) -> Option<&'static Call> {
+    CALLS.iter().find(|(name, listed, json, ..)| {
+        *name == dispatcher && (*listed == call || (*json && call.strip_suffix("_json") == Some(listed)))
+    })
+}
+
+fn networked(needs: &[Need]) -> bool {
+    needs.contains(&Need::Network) || needs.contains(&Need::Resolver)
+}
+
+// Whether the features a call needs are compiled in
+fn compiled(needs: &[Need]) -> bool {
+    !needs.contains(&Need::Image) || cfg!(feature = "image")
+}
+
+// Whether the input, options after it set aside, looks like what the call takes
+fn check_input(kind: InputKind, input: &str) -> Result<(), MercyError> {
+    let argument = without_options(input);
+
+    match kind {
+        InputKind::None | InputKind::Text => Ok(()),
+        InputKind::FilePath => existing_path(&argument),
+        InputKind::FilePaths => argument.split(',').try_for_each(|path| existing_path(path.trim())),
+        InputKind::HostPort if host_port(&argument).is_some() => Ok(()),
+        InputKind::HostPort => Err(MercyError::invalid_input(format!("Expected host:port, got '{}'", argument))),
+        InputKind::Domain if valid_host(&argument) || host_port(&argument).is_some() => Ok(()),
+        InputKind::Domain => Err(MercyError::invalid_input(format!("Expected a domain name or IP address, got '{}'", argument))),
+        InputKind::Url => match Url::parse(&argument) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            Ok(url) => Err(MercyError::invalid_input(format!("Expected an http or https URL, got a {} URL", url.scheme()))),
+            Err(e) => Err(MercyError::invalid_input(format!("Invalid URL '{}': {}", argument, e)))
+        }
+    }
+}
+
+// The input without the "key=value" tokens that end it
+fn without_options(input: &str) -> String {
+    let mut tokens: Vec<&str> = input.trim().split(' ').collect();
+    let is_option = |token: &str| token.split_once('=').is_some_and(|(key, _)| !key.is_empty() && key.bytes().all(|b| b.is_ascii_lowercase() || b == b'_'));
+
+    while tokens.len() > 1 && tokens.last().is_some_and(|token| is_option(token)) {
+        tokens.pop();
+    }
+    tokens.join(" ").trim().to_string()
+}
+
+fn existing_path(path: &str) -> Result<(), MercyError> {
+    if path.is_empty() {
+        return Err(MercyError::invalid_input("Expected a file path, got nothing".to_string()));
+    }
+    fs::metadata(path).map(|_| ()).map_err(|e| MercyError::io(path, e))
+}
+
+// The host and nonzero port of "host:port" or "[v6]:port"
+fn host_port(target: &str) -> Option<(&str, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    let port = port.parse::<u16>().ok().filter(|port| *port != 0)?;
+    valid_host(host).then_some((host, port))
+}
+
+// An IP address, or a name of 1 to 63 character labels (letters in any script, digits, '-' and '_', no hyphen at either end)
+fn valid_host(host: &str) -> bool {
+    if host.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+
+    let name = host.strip_suffix('.').unwrap_or(host);
+    !name.is_empty() && name.len() <= 253 && name.split('.').all(|label| {
+        (1..=63).contains(&label.chars().count())
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+fn probe_environment(icmp_v4: &IcmpAccess, icmp_v6: &IcmpAccess) -> CapabilityEnvironment {
+    let mut features = Vec::new();
+    if cfg!(feature = "git") {
+        features.push("git".to_string());
+    }
+    if cfg!(feature = "image") {
+        features.push("image".to_string());
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap".to_string());
+    }
+
+    CapabilityEnvironment {
+        platform: format!("{}-{}", env::consts::OS, env::consts::ARCH),
+        features,
+        icmp_v4: access_name(icmp_v4),
+        icmp_v6: access_name(icmp_v6),
+        proc_fs: Path::new("/proc/self").exists(),
+        resolv_conf: Path::new("/etc/resolv.conf").is_file(),
+        dns_server: default_dns_server().to_string(),
+        temp_writable: temp_writable(),
+        git: find_executable("git").map(|path| path.display().to_string()),
+        http_identity: http_identity() != HttpIdentity::default(),
+        custom_sinkhole_list: sinkhole_list() != SinkholeList::builtin(),
+        redaction: redaction_policy().is_some()
+    }
+}
+
+// The weakest verdict over a call's dependencies, with the reason for it
+fn assess(needs: &[Need], environment: &CapabilityEnvironment, icmp_v4: &IcmpAccess) -> (CapabilityStatus, Option<String>) {
+    let mut status = CapabilityStatus::Available;
+    let mut reasons = Vec::new();
+
+    for need in needs {
+        let (verdict, reason) = match need {
+            Need::Network => (CapabilityStatus::Available, None),
+            Need::Resolver if environment.resolv_conf => (CapabilityStatus::Available, None),
+            Need::Resolver => (CapabilityStatus::Degraded, Some(format!("no /etc/resolv.conf, queries go to {} unless a server is given", environment.dns_server))),
+            Need::Ping => match icmp_v4 {
+                IcmpAccess::Unavailable(reason) => (CapabilityStatus::Degraded, Some(format!("ICMP sockets unavailable ({}), falls back to TCP connect time", reason))),
+                _ => (CapabilityStatus::Available, None)
+            },
+            Need::Traceroute => match icmp_v4 {
+                _ if !cfg!(unix) => (CapabilityStatus::Unavailable, Some("unsupported platform".to_string())),
+                IcmpAccess::Raw => (CapabilityStatus::Available, None),
+                _ if cfg!(target_os = "linux") => (CapabilityStatus::Degraded, Some("no raw sockets: UDP probes only (TCP probes need root or CAP_NET_RAW)".to_string())),
+                _ => (CapabilityStatus::Unavailable, Some("raw ICMP sockets required (root)".to_string()))
+            },
+            Need::Fingerprint => match icmp_v4 {
+                IcmpAccess::Raw => (CapabilityStatus::Available, None),
+                _ => (CapabilityStatus::Degraded, Some("no raw sockets: only from supplied observations (\"ttl=N window=N\")".to_string()))
+            },
+            Need::Sweep => match icmp_v4 {
+                IcmpAccess::Unavailable(reason) => (CapabilityStatus::Degraded, Some(format!("ICMP sockets unavailable ({}), TCP method only", reason))),
+                _ => (CapabilityStatus::Available, None)
+            },
+            Need::Proc if cfg!(target_os = "linux") && !environment.proc_fs => (CapabilityStatus::Unavailable, Some("/proc is not mounted".to_string())),
+            Need::Proc => (CapabilityStatus::Available, None),
+            Need::Temp if environment.temp_writable => (CapabilityStatus::Available, None),
+            Need::Temp => (CapabilityStatus::Unavailable, Some(format!("{} is missing or not writable", env::temp_dir().display()))),
+            Need::Git if cfg!(feature = "git") || environment.git.is_some() => (CapabilityStatus::Available, None),
+            Need::Git => (CapabilityStatus::Unavailable, Some("git executable not found on PATH and built without the `git` feature".to_string())),
+            Need::Image if cfg!(feature = "image") => (CapabilityStatus::Available, None),
+            Need::Image => (CapabilityStatus::Unavailable, Some("built without the `image` feature".to_string()))
+        };
+
+        status = worse(status, verdict);
+        reasons.extend(reason);
+    }
+
+    (status, if reasons.is_empty() { None } else { Some(reasons.join("; ")) })
+}
+
+fn worse(a: CapabilityStatus, b: CapabilityStatus) -> CapabilityStatus {
+    let rank = |status: CapabilityStatus| match status {
+        CapabilityStatus::Available => 0,
+        CapabilityStatus::Degraded => 1,
+        CapabilityStatus::Unavailable => 2
+    };
+
+    if rank(b) > rank(a) { b } else { a }
+}
+
+fn access_name(access: &IcmpAccess) -> String {
+    match access {
+        IcmpAccess::Raw => "raw".to_string(),
+        IcmpAccess::Datagram => "datagram".to_string(),
+        IcmpAccess::Unavailable(reason) => format!("unavailable: {}", reason)
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value { "yes" } else { "no" }
+}
+
+// Asks the OS whether this process may create files in the temp directory, without creating one
+#[cfg(unix)]
+fn temp_writable() -> bool {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let directory = env::temp_dir();
+    let Ok(name) = CString::new(directory.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    // access only reads the NUL-terminated name
+    directory.is_dir() && unsafe { libc::access(name.as_ptr(), libc::W_OK) } == 0
+}
+
+#[cfg(not(unix))]
+fn temp_writable() -> bool {
+    std::fs::metadata(env::temp_dir()).map(|metadata| metadata.is_dir() && !metadata.permissions().readonly()).unwrap_or(false)
+}
+
+// First file named `name` (or `name.exe`) in a PATH directory
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+
+    env::split_paths(&path).find_map(|directory| {
+        [name.to_string(), format!("{}.exe", name)].into_iter().map(|file| directory.join(file)).find(|candidate| candidate.is_file())
+    })
+}
@@ -0,0 +1,692 @@
+/*
+    File carving from binary blobs (memory dumps, firmware images, disk images)
+
+    The input is scanned in fixed-size blocks for known headers, and each candidate is sized by
+    walking its own structure (PE section table, PNG chunks, JPEG segments, ZIP central directory,
+    PDF trailers) with positioned reads, so multi-gigabyte inputs never sit in memory (or are
+    memory-mapped, see `filemap`). Every
+    candidate is reported, including ones nested inside or overlapping another object.
+*/
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{filemap::ScanFile, paths::path_to_string, registry::{le16, le32}, trailing_options};
+
+const SCAN_BLOCK: usize = 1024 * 1024;
+
+// Longest header signature minus one, carried between blocks so no header is split
+const SCAN_OVERLAP: usize = 7;
+
+/// Object types the carver recognises
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CarveType {
+    Pe,
+    Png,
+    Jpeg,
+    Zip,
+    Pdf
+}
+
+impl CarveType {
+    pub const ALL: [CarveType; 5] = [CarveType::Pe, CarveType::Png, CarveType::Jpeg, CarveType::Zip, CarveType::Pdf];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CarveType::Pe => "pe",
+            CarveType::Png => "png",
+            CarveType::Jpeg => "jpeg",
+            CarveType::Zip => "zip",
+            CarveType::Pdf => "pdf"
+        }
+    }
+
+    /// File extension used for extracted objects
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CarveType::Pe => "bin",
+            CarveType::Jpeg => "jpg",
+            other => other.as_str()
+        }
+    }
+}
+
+impl fmt::Display for CarveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CarveType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CarveType, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "pe" | "mz" | "exe" | "dll" => Ok(CarveType::Pe),
+            "png" => Ok(CarveType::Png),
+            "jpeg" | "jpg" => Ok(CarveType::Jpeg),
+            "zip" => Ok(CarveType::Zip),
+            "pdf" => Ok(CarveType::Pdf),
+            other => Err(format!("Unknown carve type '{}'", other))
+        }
+    }
+}
+
+/// Carving parameters and runaway guards
+#[derive(Debug, Clone)]
+pub struct CarveOptions {
+    pub types: Vec<CarveType>,
+    /// Extract objects into this directory instead of only listing them
+    pub output: Option<PathBuf>,
+    /// Stop after this many objects
+    pub max_objects: usize,
+    /// Objects are cut at this length and marked incomplete
+    pub max_object_size: u64,
+    /// Stop once this many bytes have been extracted
+    pub max_extracted: u64
+}
+
+impl Default for CarveOptions {
+    fn default() -> CarveOptions {
+        CarveOptions {
+            types: CarveType::ALL.to_vec(),
+            output: None,
+            max_objects: 1000,
+            max_object_size: 256 * 1024 * 1024,
+            max_extracted: 4 * 1024 * 1024 * 1024
+        }
+    }
+}
+
+/// An object found in the input
+#[derive(Debug, Clone, Serialize)]
+pub struct CarvedObject {
+    pub index: usize,
+    pub offset: u64,
+    pub kind: CarveType,
+    pub length: u64,
+    /// False when the structure ran past the input, the size guard, or could not be closed
+    pub complete: bool,
+    pub sha256: String,
+    /// Index of the smallest object this one lies entirely within
+    pub contained_in: Option<usize>,
+    /// Indexes of objects that partially overlap this one
+    pub overlaps: Vec<usize>,
+    pub detail: Option<String>,
+    pub extracted: Option<String>
+}
+
+/// Result of a carving run
+#[derive(Debug, Clone, Serialize)]
+pub struct CarveReport {
+    pub path: String,
+    pub size: u64,
+    pub objects: Vec<CarvedObject>,
+    /// Set when a guard stopped the run early
+    pub limit_reached: Option<String>
+}
+
+impl fmt::Display for CarveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Carved {} ({} bytes)", self.path, self.size)?;
+
+        for object in &self.objects {
+            write!(f, "#{:<4} 0x{:010x}  {:<4}  {:>10}  {}", object.index, object.offset, object.kind, object.length, object.sha256)?;
+            if !object.complete {
+                write!(f, "  [incomplete]")?;
+            }
+            if let Some(parent) = object.contained_in {
+                write!(f, "  [inside #{}]", parent)?;
+            }
+            if !object.overlaps.is_empty() {
+                let list: Vec<String> = object.overlaps.iter().map(|index| format!("#{}", index)).collect();
+                write!(f, "  [overlaps {}]", list.join(", "))?;
+            }
+            if let Some(detail) = &object.detail {
+                write!(f, "  ({})", detail)?;
+            }
+            if let Some(extracted) = &object.extracted {
+                write!(f, "  -> {}", extracted)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "{} objects", self.objects.len())?;
+        if let Some(limit) = &self.limit_reached {
+            writeln!(f, "Stopped early: {}", limit)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the objects of the given comma separated types ("all" or "" for every type) found in a file
+pub fn mercy_carve<P: AsRef<Path>>(path: P, types: &str) -> Result<CarveReport, String> {
+    let path = path.as_ref();
+    let options = CarveOptions { types: parse_types(types)?, ..CarveOptions::default() };
+    carve_file(Path::new(path), &options)
+}
+
+/// Scans a file for embedded objects, extracting them when `options.output` is set
+pub fn carve_file<P: AsRef<Path>>(path: P, options: &CarveOptions) -> Result<CarveReport, String> {
+    let path = path.as_ref();
+    let file = ScanFile::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let size = file.size();
+    let source = Source { file, size };
+
+    if let Some(output) = &options.output {
+        fs::create_dir_all(output).map_err(|e| format!("Unable to create {}: {}", output.display(), e))?;
+    }
+
+    let mut report = CarveReport { path: path_to_string(path), size, objects: Vec::new(), limit_reached: None };
+
+    // Local headers of members already accounted for by a reconciled ZIP
+    let mut zip_members = HashSet::new();
+    let mut extracted_total = 0u64;
+    let mut base = 0u64;
+
+    'scan: while base < size {
+        let block = source.file.read_at(base, SCAN_BLOCK + SCAN_OVERLAP).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let filled = block.len();
+
+        for index in 0..filled.min(SCAN_BLOCK) {
+            let offset = base + index as u64;
+            let kind = match signature(&block[index..filled]) {
+                Some(kind) if options.types.contains(&kind) => kind,
+                _ => continue
+            };
+
+            if kind == CarveType::Zip && zip_members.contains(&offset) {
+                continue;
+            }
+
+            let limit = offset.saturating_add(options.max_object_size).min(size);
+            let extent = match kind {
+                CarveType::Pe => pe_extent(&source, offset),
+                CarveType::Png => png_extent(&source, offset, limit),
+                CarveType::Jpeg => jpeg_extent(&source, offset, limit),
+                CarveType::Zip => zip_extent(&source, offset, limit, &mut zip_members),
+                CarveType::Pdf => pdf_extent(&source, offset, limit)
+            };
+
+            let mut extent = match extent {
+                Some(extent) => extent,
+                None => continue
+            };
+
+            // Clip to the input and the per-object guard
+            if extent.length > limit - offset {
+                extent.length = limit - offset;
+                extent.complete = false;
+            }
+
+            if report.objects.len() >= options.max_objects {
+                report.limit_reached = Some(format!("object limit of {} reached at offset 0x{:x}", options.max_objects, offset));
+                break 'scan;
+            }
+
+            if options.output.is_some() && extracted_total + extent.length > options.max_extracted {
+                report.limit_reached = Some(format!("extraction limit of {} bytes reached at offset 0x{:x}", options.max_extracted, offset));
+                break 'scan;
+            }
+
+            let index = report.objects.len();
+            let destination = options.output.as_ref().map(|output| output.join(format!("{:012x}.{}", offset, kind.extension())));
+            let sha256 = source.copy_range(offset, extent.length, destination.as_deref())?;
+
+            if destination.is_some() {
+                extracted_total += extent.length;
+            }
+
+            report.objects.push(CarvedObject {
+                index,
+                offset,
+                kind,
+                length: extent.length,
+                complete: extent.complete,
+                sha256,
+                contained_in: None,
+                overlaps: Vec::new(),
+                detail: extent.detail,
+                extracted: destination.map(|destination| path_to_string(&destination))
+            });
+        }
+
+        base += SCAN_BLOCK as u64;
+    }
+
+    relate(&mut report.objects);
+    Ok(report)
+}
+
+fn parse_types(types: &str) -> Result<Vec<CarveType>, String> {
+    let types = types.trim();
+    if types.is_empty() || types.eq_ignore_ascii_case("all") {
+        return Ok(CarveType::ALL.to_vec());
+    }
+
+    types.split(',').filter(|kind| !kind.trim().is_empty()).map(CarveType::from_str).collect()
+}
+
+fn signature(bytes: &[u8]) -> Option<CarveType> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(CarveType::Png)
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        Some(CarveType::Jpeg)
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        Some(CarveType::Zip)
+    } else if bytes.starts_with(b"%PDF-") {
+        Some(CarveType::Pdf)
+    } else if bytes.starts_with(b"MZ") {
+        Some(CarveType::Pe)
+    } else {
+        None
+    }
+}
+
+// Records nesting (smallest enclosing object) and partial overlaps; objects are in offset order
+fn relate(objects: &mut [CarvedObject]) {
+    let ranges: Vec<(u64, u64)> = objects.iter().map(|object| (object.offset, object.offset + object.length)).collect();
+
+    for (index, object) in objects.iter_mut().enumerate() {
+        let (start, end) = ranges[index];
+
+        for (other, &(other_start, other_end)) in ranges.iter().enumerate() {
+            if other == index || other_end <= start || other_start >= end {
+                continue;
+            }
+
+            if other_start <= start && end <= other_end {
+                let smaller = object.contained_in.map(|current| other_end - other_start < ranges[current].1 - ranges[current].0).unwrap_or(true);
+                if smaller {
+                    object.contained_in = Some(other);
+                }
+            } else if !(start <= other_start && other_end <= end) {
+                object.overlaps.push(other);
+            }
+        }
+    }
+}
+
+/* Structure walkers */
+
+// Size of a candidate as far as its structure could be followed
+struct Extent {
+    length: u64,
+    complete: bool,
+    detail: Option<String>
+}
+
+impl Extent {
+    fn complete(length: u64) -> Option<Extent> {
+        Some(Extent { length, complete: true, detail: None })
+    }
+
+    fn partial(length: u64, detail: &str) -> Option<Extent> {
+        Some(Extent { length, complete: false, detail: Some(detail.to_string()) })
+    }
+}
+
+// Positioned reads over the input
+struct Source {
+    file: ScanFile,
+    size: u64
+}
+
+impl Source {
+    // Exactly `length` bytes at `offset`, or None
+    fn read(&self, offset: u64, length: usize) -> Option<Cow<'_, [u8]>> {
+        if offset.checked_add(length as u64)? > self.size {
+            return None;
+        }
+
+        self.file.read_at(offset, length).ok().filter(|bytes| bytes.len() == length)
+    }
+
+    // First offset in [from, limit) where `matches` accepts the bytes starting there
+    fn find<F: Fn(&[u8]) -> bool>(&self, from: u64, limit: u64, width: usize, matches: F) -> Option<u64> {
+        let mut base = from;
+
+        while base < limit {
+            let length = ((limit - base) as usize).min(SCAN_BLOCK) + width - 1;
+            let length = length.min((self.size - base) as usize);
+            let block = self.read(base, length)?;
+
+            for index in 0..block.len().saturating_sub(width - 1) {
+                if base + (index as u64) >= limit {
+                    return None;
+                }
+                if matches(&block[index..]) {
+                    return Some(base + index as u64);
+                }
+            }
+
+            base += SCAN_BLOCK as u64;
+        }
+
+        None
+    }
+
+    // Hashes the range and, when given a destination, writes it out at the same time
+    fn copy_range(&self, offset: u64, length: u64, destination: Option<&Path>) -> Result<String, String> {
+        let mut output = match destination {
+            Some(path) => Some(File::create(path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?),
+            None => None
+        };
+
+        let mut hash = Sha256::new();
+        let mut done = 0u64;
+
+        while done < length {
+            let chunk = ((length - done) as usize).min(SCAN_BLOCK);
+            let data = self.read(offset + done, chunk).ok_or("Input shrank while carving")?;
+            hash.update(&data);
+
+            if let Some(output) = output.as_mut() {
+                output.write_all(&data).map_err(|e| format!("Unable to write carved object: {}", e))?;
+            }
+
+            done += chunk as u64;
+        }
+
+        Ok(format!("{:x}", hash.finalize()))
+    }
+}
+
+fn be16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// PE: end of the furthest section's raw data, or of the Authenticode certificate table after it.
+// Overlays appended past both are not part of the image and are not counted.
+fn pe_extent(source: &Source, offset: u64) -> Option<Extent> {
+    let dos = source.read(offset, 0x40)?;
+    let nt_offset = le32(&dos, 0x3C)? as u64;
+    if !(0x40..=0x1000).contains(&nt_offset) {
+        return None;
+    }
+
+    let nt = source.read(offset + nt_offset, 24)?;
+    if &nt[..4] != b"PE\0\0" {
+        return None;
+    }
+
+    let sections = le16(&nt, 6)? as u64;
+    let optional_size = le16(&nt, 20)? as usize;
+    if sections == 0 || sections > 96 || optional_size < 96 {
+        return None;
+    }
+
+    let optional = source.read(offset + nt_offset + 24, optional_size)?;
+    let directories = match le16(&optional, 0)? {
+        0x10B => 96,
+        0x20B => 112,
+        _ => return None
+    };
+
+    let mut end = le32(&optional, 60)? as u64;
+    let table = offset + nt_offset + 24 + optional_size as u64;
+
+    let detail = match source.read(table, sections as usize * 40) {
+        Some(headers) => {
+            for section in headers.chunks_exact(40) {
+                let raw_size = le32(section, 16).unwrap_or(0) as u64;
+                let raw_pointer = le32(section, 20).unwrap_or(0) as u64;
+                if raw_size > 0 {
+                    end = end.max(raw_pointer + raw_size);
+                }
+            }
+
+            // The security directory holds a file offset rather than an RVA
+            if let (Some(cert_offset), Some(cert_size)) = (le32(&optional, directories + 32), le32(&optional, directories + 36)) {
+                if cert_offset > 0 && cert_size > 0 {
+                    end = end.max(cert_offset as u64 + cert_size as u64);
+                }
+            }
+
+            let machine = match le16(&nt, 4)? {
+                0x014C => "x86",
+                0x8664 => "x64",
+                0xAA64 => "arm64",
+                0x01C4 => "arm",
+                _ => "other machine"
+            };
+            let dll = le16(&nt, 22)? & 0x2000 != 0;
+            format!("{} {}, {} sections", machine, if dll { "DLL" } else { "executable" }, sections)
+        }
+        None => return Extent::partial(source.size - offset, "section table truncated")
+    };
+
+    Some(Extent { length: end, complete: offset + end <= source.size, detail: Some(detail) })
+}
+
+// PNG: IHDR first, then length/type/data/CRC chunks up to IEND
+fn png_extent(source: &Source, offset: u64, limit: u64) -> Option<Extent> {
+    let ihdr = source.read(offset + 8, 16)?;
+    let (width, height) = (be32(&ihdr, 8)?, be32(&ihdr, 12)?);
+    if &ihdr[4..8] != b"IHDR" || width == 0 || height == 0 {
+        return None;
+    }
+
+    let size = format!("{}x{}", width, height);
+    let mut position = offset + 8;
+
+    while position < limit {
+        let header = match source.read(position, 8) {
+            Some(header) => header,
+            None => break
+        };
+
+        let kind = &header[4..8];
+        if !kind.iter().all(u8::is_ascii_alphabetic) {
+            return Extent::partial(position - offset, &format!("{}, invalid chunk before IEND", size));
+        }
+
+        position += 12 + be32(&header, 0)? as u64;
+        if kind == b"IEND" {
+            return Some(Extent { length: position - offset, complete: true, detail: Some(size) });
+        }
+    }
+
+    Extent::partial(limit - offset, &format!("{}, truncated before IEND", size))
+}
+
+// JPEG: marker segments up to start of scan, then entropy-coded data until the next real marker,
+// repeated (progressive images have several scans) until EOI
+fn jpeg_extent(source: &Source, offset: u64, limit: u64) -> Option<Extent> {
+    let mut position = offset + 2;
+    let mut segments = 0usize;
+
+    while position < limit {
+        let marker = match source.read(position, 4) {
+            Some(marker) => marker,
+            None => break
+        };
+
+        // A bad marker straight after SOI means this was never a JPEG
+        let corrupt = |detail: &str| if segments == 0 { None } else { Extent::partial(position - offset, detail) };
+
+        if marker[0] != 0xFF {
+            return corrupt("corrupt marker");
+        }
+
+        match marker[1] {
+            // Fill bytes
+            0xFF => position += 1,
+            0xD9 => return Extent::complete(position + 2 - offset),
+            0x01 | 0xD0..=0xD7 => position += 2,
+            0xDA => {
+                position += 2 + be16(&marker, 2)? as u64;
+
+                // Stuffed 0xFF00 and restart markers belong to the scan
+                match source.find(position, limit, 2, |bytes| bytes[0] == 0xFF && bytes[1] != 0x00 && !(0xD0..=0xD7).contains(&bytes[1])) {
+                    Some(next) => position = next,
+                    None => break
+                }
+            }
+            0xC0..=0xFE => {
+                let length = be16(&marker, 2)? as u64;
+                if length < 2 {
+                    return corrupt("corrupt segment length");
+                }
+                position += 2 + length;
+            }
+            _ => return corrupt("corrupt marker")
+        }
+
+        segments += 1;
+    }
+
+    if segments == 0 { None } else { Extent::partial(limit - offset, "truncated before EOI") }
+}
+
+// ZIP: the archive ends with an end of central directory record whose directory size and offset
+// lead back to this local header. Members are walked by their local headers until one hides its
+// size behind a data descriptor; after that the EOCD is searched for.
+fn zip_extent(source: &Source, offset: u64, limit: u64, members: &mut HashSet<u64>) -> Option<Extent> {
+    let first = source.read(offset, 30)?;
+    let name_length = le16(&first, 26)?;
+    if name_length == 0 || le16(&first, 8)? > 99 {
+        return None;
+    }
+
+    let mut position = offset;
+    let mut walked = 0usize;
+
+    while let Some(header) = source.read(position, 30) {
+        if &header[..4] != b"PK\x03\x04" {
+            break;
+        }
+
+        let flags = le16(&header, 6)?;
+        let compressed = le32(&header, 18)?;
+        if flags & 0x0008 != 0 || compressed == 0xFFFF_FFFF {
+            break;
+        }
+
+        position += 30 + le16(&header, 26)? as u64 + le16(&header, 28)? as u64 + compressed as u64;
+        walked += 1;
+    }
+
+    let mut search = position;
+    while let Some(eocd) = source.find(search, limit, 4, |bytes| bytes.starts_with(b"PK\x05\x06")) {
+        let record = match source.read(eocd, 22) {
+            Some(record) => record,
+            None => break
+        };
+
+        let entries = le16(&record, 10)? as usize;
+        let directory_size = le32(&record, 12)? as u64;
+        let directory_offset = le32(&record, 16)? as u64;
+        let start = eocd.checked_sub(directory_size).and_then(|end| end.checked_sub(directory_offset));
+
+        match start {
+            Some(start) if start == offset => {
+                zip_central_members(source, offset, directory_offset, entries, members);
+                let comment = le16(&record, 20)? as u64;
+                let detail = format!("{} entries", entries);
+                return Some(Extent { length: eocd + 22 + comment - offset, complete: true, detail: Some(detail) });
+            }
+            // A directory for an archive starting after this header; ours has none
+            Some(start) if start > offset => break,
+            _ => search = eocd + 4
+        }
+    }
+
+    if walked == 0 {
+        return None;
+    }
+
+    Extent::partial(position - offset, "no matching central directory")
+}
+
+fn zip_central_members(source: &Source, archive: u64, directory: u64, entries: usize, members: &mut HashSet<u64>) {
+    let mut position = archive + directory;
+
+    for _ in 0..entries.min(65_535) {
+        let header = match source.read(position, 46) {
+            Some(header) if &header[..4] == b"PK\x01\x02" => header,
+            _ => break
+        };
+
+        if let Some(local) = le32(&header, 42) {
+            members.insert(archive + local as u64);
+        }
+
+        let variable = le16(&header, 28).unwrap_or(0) as u64 + le16(&header, 30).unwrap_or(0) as u64 + le16(&header, 32).unwrap_or(0) as u64;
+        position += 46 + variable;
+    }
+}
+
+// PDF: incremental updates append further %%EOF markers, so the last one before the next
+// %PDF- header (or the size guard) closes the document
+fn pdf_extent(source: &Source, offset: u64, limit: u64) -> Option<Extent> {
+    let version = source.read(offset + 5, 3)?;
+    if !version[0].is_ascii_digit() || version[1] != b'.' {
+        return None;
+    }
+
+    let mut position = offset + 5;
+    let mut end = None;
+
+    while let Some(found) = source.find(position, limit, 5, |bytes| bytes.starts_with(b"%%EOF") || bytes.starts_with(b"%PDF-")) {
+        if *source.read(found, 5)? == *b"%PDF-" {
+            break;
+        }
+
+        // Keep the line ending that follows the marker
+        let mut close = found + 5;
+        match source.read(close, 2).as_deref() {
+            Some(b"\r\n") => close += 2,
+            Some([b'\n', _]) | Some([b'\r', _]) => close += 1,
+            _ => {}
+        }
+
+        end = Some(close);
+        position = found + 5;
+    }
+
+    let detail = format!("PDF {}", String::from_utf8_lossy(&version));
+    match end {
+        Some(end) => Some(Extent { length: end - offset, complete: true, detail: Some(detail) }),
+        None => Extent::partial(limit - offset, "no %%EOF marker")
+    }
+}
+
+// Parses "path [types=png,zip] [out=dir] [max_objects=N] [max_size=bytes]"
+pub(crate) fn carve_from_args(input: &str) -> Result<CarveReport, String> {
+    let (path, pairs) = trailing_options(input, &["types", "out", "max_objects", "max_size", "max_extracted"]);
+    let mut options = CarveOptions::default();
+
+    for (key, value) in pairs {
+        let number = || value.parse::<u64>().map_err(|_| format!("Invalid {} '{}'", key, value));
+        match key {
+            "types" => options.types = parse_types(value)?,
+            "out" => options.output = Some(PathBuf::from(value)),
+            "max_objects" => options.max_objects = number()? as usize,
+            "max_size" => options.max_object_size = number()?,
+            _ => options.max_extracted = number()?
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for carve".to_string());
+    }
+
+    carve_file(Path::new(&path), &options)
+}
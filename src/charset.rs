@@ -0,0 +1,320 @@
+/*
+    Character encoding detection and transcoding to UTF-8
+
+    Detection looks at the start of a file: a BOM settles it, then a zero byte in every other
+    position says UTF-16 without one, then NUL and other control bytes say binary. What is left
+    is UTF-8 when it validates, and otherwise a single-byte encoding: Windows-1252 when bytes
+    0x80 to 0x9F appear (curly quotes, dashes and the euro sign there are far more common than
+    the C1 controls Latin-1 puts in that range) and Latin-1 when they do not. The offset of the
+    first invalid UTF-8 sequence is kept, since it is usually where a file was pasted together
+    or mangled. A sequence cut short by the end of the sample is not counted against UTF-8.
+
+    Transcoding never fails on the text itself: lone surrogates, a trailing odd byte and bytes
+    Windows-1252 leaves undefined become U+FFFD, and the output ends with a warning line
+    counting them, so a damaged string is shown as far as it goes instead of refused.
+*/
+
+use std::{
+    fmt,
+    fs::File,
+    io::Read,
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::{hex_decode, text::{TextEncoding, sniff_encoding}};
+
+// Bytes read from the start of a file to detect its encoding
+const SAMPLE: u64 = 64 * 1024;
+
+// Files larger than this are refused by the transcoding decoders
+const MAX_TRANSCODE: u64 = 16 * 1024 * 1024;
+
+// Share of control bytes (other than tab, line breaks, form feed and escape) beyond which text is binary
+const MAX_CONTROL_SHARE: f64 = 0.1;
+
+// Windows-1252 characters for bytes 0x80 to 0x9F; None where the code page leaves a byte undefined
+const WINDOWS_1252: [Option<char>; 32] = [
+    Some('\u{20AC}'), None, Some('\u{201A}'), Some('\u{0192}'), Some('\u{201E}'), Some('\u{2026}'), Some('\u{2020}'), Some('\u{2021}'),
+    Some('\u{02C6}'), Some('\u{2030}'), Some('\u{0160}'), Some('\u{2039}'), Some('\u{0152}'), None, Some('\u{017D}'), None,
+    None, Some('\u{2018}'), Some('\u{2019}'), Some('\u{201C}'), Some('\u{201D}'), Some('\u{2022}'), Some('\u{2013}'), Some('\u{2014}'),
+    Some('\u{02DC}'), Some('\u{2122}'), Some('\u{0161}'), Some('\u{203A}'), Some('\u{0153}'), None, Some('\u{017E}'), Some('\u{0178}')
+];
+
+/// An encoding `mercy_detect_encoding` can report and `transcode` can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Charset {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    #[serde(rename = "latin-1")]
+    Latin1,
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+    /// Not text in any of the others
+    #[serde(rename = "binary")]
+    Binary
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Charset::Utf8 => write!(f, "UTF-8"),
+            Charset::Utf16Le => write!(f, "UTF-16LE"),
+            Charset::Utf16Be => write!(f, "UTF-16BE"),
+            Charset::Latin1 => write!(f, "Latin-1"),
+            Charset::Windows1252 => write!(f, "Windows-1252"),
+            Charset::Binary => write!(f, "binary")
+        }
+    }
+}
+
+/// How sure the detection is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncodingConfidence {
+    /// A BOM, non-ASCII text that validates as UTF-8, or NUL bytes in what is not UTF-16
+    High,
+    /// A pattern that fits, such as UTF-16 zero bytes without a BOM or bytes that are not UTF-8
+    Medium,
+    /// Nothing to tell encodings apart, such as plain ASCII or an empty file
+    Low
+}
+
+impl fmt::Display for EncodingConfidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingConfidence::High => write!(f, "high"),
+            EncodingConfidence::Medium => write!(f, "medium"),
+            EncodingConfidence::Low => write!(f, "low")
+        }
+    }
+}
+
+/// The likely encoding of a file
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodingReport {
+    pub path: String,
+    pub size: u64,
+    /// Bytes examined from the start of the file
+    pub sampled: usize,
+    pub encoding: Charset,
+    pub confidence: EncodingConfidence,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+    /// Offset of the first byte that is not valid UTF-8, when one ruled UTF-8 out
+    pub invalid_utf8_offset: Option<usize>,
+    /// What decided it ("UTF-16LE byte order mark", "37 multi-byte sequences, all valid")
+    pub reason: String
+}
+
+impl fmt::Display for EncodingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} ({} confidence)", self.path, self.encoding, self.confidence)?;
+        if let Some(offset) = self.invalid_utf8_offset {
+            writeln!(f, "Not UTF-8: invalid sequence at offset {} (0x{:x})", offset, offset)?;
+        }
+        write!(f, "{} of {} bytes examined: {}", self.sampled, self.size, self.reason)
+    }
+}
+
+/// Detects the encoding of the file at `path` from its first 64 KiB
+pub fn mercy_detect_encoding<P: AsRef<Path>>(path: P) -> Result<EncodingReport, String> {
+    let path = path.as_ref();
+    let unreadable = |e: std::io::Error| format!("Unable to read {}: {}", path.display(), e);
+
+    let file = File::open(path).map_err(unreadable)?;
+    let size = file.metadata().map_err(unreadable)?.len();
+    let mut sample = Vec::with_capacity(SAMPLE.min(size) as usize);
+    file.take(SAMPLE).read_to_end(&mut sample).map_err(unreadable)?;
+
+    let (encoding, confidence, invalid_utf8_offset, reason) = detect(&sample, (sample.len() as u64) < size);
+    Ok(EncodingReport {
+        path: path.display().to_string(),
+        size,
+        sampled: sample.len(),
+        encoding,
+        confidence,
+        bom: sniff_encoding(&sample).1 > 0,
+        invalid_utf8_offset,
+        reason
+    })
+}
+
+// The encoding of a sample, with how sure, where UTF-8 broke and why; `cut` says the sample stops before the end of the file
+fn detect(sample: &[u8], cut: bool) -> (Charset, EncodingConfidence, Option<usize>, String) {
+    let (encoding, bom) = sniff_encoding(sample);
+    match (encoding, bom) {
+        (TextEncoding::Utf8, 3) => return (Charset::Utf8, EncodingConfidence::High, None, "UTF-8 byte order mark".to_string()),
+        (TextEncoding::Utf16Le, 2) => return (Charset::Utf16Le, EncodingConfidence::High, None, "UTF-16LE byte order mark".to_string()),
+        (TextEncoding::Utf16Be, 2) => return (Charset::Utf16Be, EncodingConfidence::High, None, "UTF-16BE byte order mark".to_string()),
+        (TextEncoding::Utf16Le, _) => return (Charset::Utf16Le, EncodingConfidence::Medium, None, "zero byte after most ASCII characters, no byte order mark".to_string()),
+        (TextEncoding::Utf16Be, _) => return (Charset::Utf16Be, EncodingConfidence::Medium, None, "zero byte before most ASCII characters, no byte order mark".to_string()),
+        _ => {}
+    }
+
+    if sample.is_empty() {
+        return (Charset::Utf8, EncodingConfidence::Low, None, "empty".to_string());
+    }
+
+    if let Some(offset) = sample.iter().position(|b| *b == 0) {
+        return (Charset::Binary, EncodingConfidence::High, None, format!("NUL byte at offset {}", offset));
+    }
+
+    let controls = sample.iter().filter(|b| b.is_ascii_control() && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B)).count();
+    if controls as f64 > sample.len() as f64 * MAX_CONTROL_SHARE {
+        return (Charset::Binary, EncodingConfidence::Medium, None, format!("{} control bytes", controls));
+    }
+
+    let invalid = match std::str::from_utf8(sample) {
+        Ok(_) => None,
+        Err(e) if cut && e.error_len().is_none() => None,
+        Err(e) => Some(e.valid_up_to())
+    };
+
+    let Some(offset) = invalid else {
+        let multibyte = sample.iter().filter(|b| **b >= 0xC0).count();
+        return match multibyte {
+            0 => (Charset::Utf8, EncodingConfidence::Low, None, "ASCII only, the same in UTF-8, Latin-1 and Windows-1252".to_string()),
+            count => (Charset::Utf8, EncodingConfidence::High, None, format!("{} multi-byte sequences, all valid", count))
+        };
+    };
+
+    let high = sample.iter().filter(|b| **b >= 0x80).count();
+    let c1: Vec<u8> = sample.iter().copied().filter(|b| (0x80..=0x9F).contains(b)).collect();
+    match c1.iter().find(|b| WINDOWS_1252[(**b - 0x80) as usize].is_none()) {
+        _ if c1.is_empty() => (Charset::Latin1, EncodingConfidence::Medium, Some(offset), format!("{} bytes above 0x7F, none of them 0x80 to 0x9F", high)),
+        None => (Charset::Windows1252, EncodingConfidence::Medium, Some(offset), format!("{} bytes above 0x7F, {} of them 0x80 to 0x9F", high, c1.len())),
+        Some(undefined) => (Charset::Latin1, EncodingConfidence::Low, Some(offset), format!("{} bytes above 0x7F; 0x{:02x} is undefined in Windows-1252", high, undefined))
+    }
+}
+
+/// Text transcoded to UTF-8, with what had to be replaced along the way
+#[derive(Debug, Clone, Default)]
+pub struct Transcoded {
+    pub text: String,
+    /// UTF-16 surrogates without their other half
+    pub lone_surrogates: usize,
+    /// Whether UTF-16 input ended on an odd byte
+    pub truncated: bool,
+    /// Bytes with no character in the encoding (0x81, 0x8D, 0x8F, 0x90 and 0x9D in Windows-1252)
+    pub undefined: usize,
+    /// Invalid UTF-8 sequences
+    pub invalid: usize
+}
+
+impl Transcoded {
+    /// The text followed by a warning line when anything was replaced with U+FFFD
+    pub fn with_warning(self) -> String {
+        let mut problems = Vec::new();
+        if self.lone_surrogates > 0 {
+            problems.push(format!("{} lone surrogate(s)", self.lone_surrogates));
+        }
+        if self.truncated {
+            problems.push("a truncated trailing byte".to_string());
+        }
+        if self.undefined > 0 {
+            problems.push(format!("{} undefined byte(s)", self.undefined));
+        }
+        if self.invalid > 0 {
+            problems.push(format!("{} invalid sequence(s)", self.invalid));
+        }
+        match problems.is_empty() {
+            true => self.text,
+            false => format!("{}\n[warning: {} replaced with U+FFFD]", self.text, problems.join(", "))
+        }
+    }
+}
+
+/// Transcodes bytes in `charset` to UTF-8; never fails, replacing what cannot be read with U+FFFD (binary is read as UTF-8)
+pub fn transcode(bytes: &[u8], charset: Charset) -> Transcoded {
+    match charset {
+        Charset::Utf16Le | Charset::Utf16Be => {
+            let units = bytes.chunks_exact(2).map(|pair| match charset {
+                Charset::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                _ => u16::from_le_bytes([pair[0], pair[1]])
+            });
+            let mut transcoded = Transcoded::default();
+            for decoded in char::decode_utf16(units) {
+                transcoded.text.push(decoded.unwrap_or_else(|_| {
+                    transcoded.lone_surrogates += 1;
+                    char::REPLACEMENT_CHARACTER
+                }));
+            }
+            if bytes.len() % 2 == 1 {
+                transcoded.text.push(char::REPLACEMENT_CHARACTER);
+                transcoded.truncated = true;
+            }
+            transcoded
+        },
+        Charset::Latin1 => Transcoded { text: bytes.iter().map(|b| *b as char).collect(), ..Transcoded::default() },
+        Charset::Windows1252 => {
+            let mut transcoded = Transcoded::default();
+            for byte in bytes {
+                transcoded.text.push(match byte {
+                    0x80..=0x9F => WINDOWS_1252[(byte - 0x80) as usize].unwrap_or_else(|| {
+                        transcoded.undefined += 1;
+                        char::REPLACEMENT_CHARACTER
+                    }),
+                    _ => *byte as char
+                });
+            }
+            transcoded
+        },
+        Charset::Utf8 | Charset::Binary => {
+            let invalid = if std::str::from_utf8(bytes).is_ok() { 0 } else { invalid_sequences(bytes) };
+            Transcoded { text: String::from_utf8_lossy(bytes).into_owned(), invalid, ..Transcoded::default() }
+        }
+    }
+}
+
+// Invalid UTF-8 sequences in bytes, each of which `from_utf8_lossy` replaces with one U+FFFD
+fn invalid_sequences(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    let mut rest = bytes;
+    while let Err(e) = std::str::from_utf8(rest) {
+        count += 1;
+        match e.error_len() {
+            Some(length) => rest = &rest[e.valid_up_to() + length..],
+            None => break
+        }
+    }
+    count
+}
+
+// The bytes of a `mercy_decode` transcoding input: a path to an existing file, or hex
+pub(crate) fn transcode_input(input: &str) -> Result<Vec<u8>, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("No hex or file path to transcode".to_string());
+    }
+
+    let path = Path::new(trimmed);
+    if path.is_file() {
+        let unreadable = |e: std::io::Error| format!("Unable to read {}: {}", path.display(), e);
+        let file = File::open(path).map_err(unreadable)?;
+        if file.metadata().map_err(unreadable)?.len() > MAX_TRANSCODE {
+            return Err(format!("{} is larger than the {} byte limit", path.display(), MAX_TRANSCODE));
+        }
+        let mut bytes = Vec::new();
+        file.take(MAX_TRANSCODE).read_to_end(&mut bytes).map_err(unreadable)?;
+        return Ok(bytes);
+    }
+
+    hex_decode(trimmed).map_err(|e| format!("Expected hex or the path of a file: {}", e))
+}
+
+// A `mercy_decode` transcoding call: hex or a file in `charset` as UTF-8, a BOM of that encoding dropped
+pub(crate) fn transcode_from_args(input: &str, charset: Charset) -> Result<String, String> {
+    let bytes = transcode_input(input)?;
+    let body = match charset {
+        Charset::Utf16Le if bytes.starts_with(&[0xFF, 0xFE]) => &bytes[2..],
+        Charset::Utf16Be if bytes.starts_with(&[0xFE, 0xFF]) => &bytes[2..],
+        _ => &bytes[..]
+    };
+    Ok(transcode(body, charset).with_warning())
+}
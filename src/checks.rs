@@ -0,0 +1,929 @@
+/*
+    Single-handshake exposure checks for network services (SMB, RDP, FTP, Telnet)
+
+    Each check opens one connection per probe, sends a read-only protocol greeting, and parses the
+    reply defensively: honeypots and middleboxes answer with garbage, so every length is bounds
+    checked and unexpected frames are reported rather than trusted.
+*/
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    config,
+    finding::{Finding, Severity}
+};
+
+// Default timeout for connecting and for each read
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Upper bound on any single frame we are willing to buffer
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Splits "host" or "host:port" (including "[v6]:port") and resolves it
+pub(crate) fn resolve_target(input: &str, default_port: u16) -> Result<SocketAddr, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("No target specified".to_string());
+    }
+
+    if let Ok(addr) = input.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+
+    if let Ok(ip) = input.trim_start_matches('[').trim_end_matches(']').parse::<std::net::IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+
+    let (host, port) = match input.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => {
+            (host, port.parse::<u16>().map_err(|_| format!("Invalid port in '{}'", input))?)
+        }
+        _ => (input, default_port)
+    };
+
+    (host, port).to_socket_addrs()
+        .map_err(|e| format!("Unable to resolve {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Unable to resolve {}", host))
+}
+
+// Connects with the check timeout applied to connect (unless a `MercyConfig` sets a connect timeout), reads and writes
+pub(crate) fn connect(addr: SocketAddr, timeout: Duration) -> Result<TcpStream, String> {
+    let stream = TcpStream::connect_timeout(&addr, config::connect_timeout(timeout)).map_err(|e| format!("Unable to connect to {}: {}", addr, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| format!("Unable to set read timeout: {}", e))?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| format!("Unable to set write timeout: {}", e))?;
+    Ok(stream)
+}
+
+// Reads exactly `len` bytes, mapping timeouts and resets to readable messages
+fn read_exact(stream: &mut TcpStream, len: usize) -> Result<Vec<u8>, String> {
+    let mut buffer = vec![0u8; len];
+    stream.read_exact(&mut buffer).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => "Connection closed by the server".to_string(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => "Timed out waiting for a response".to_string(),
+        std::io::ErrorKind::ConnectionReset => "Connection reset by the server".to_string(),
+        _ => format!("Read failed: {}", e)
+    })?;
+    Ok(buffer)
+}
+
+fn le16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn le32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/* SMB */
+
+/// Outcome of an SMB negotiate check
+#[derive(Debug, Clone, Serialize)]
+pub struct SmbCheck {
+    pub target: String,
+    pub reachable: bool,
+    /// Highest SMB2/3 dialect the server selected (e.g. "3.1.1")
+    pub dialect: Option<String>,
+    /// Whether an SMB1-only negotiate was accepted
+    pub smb1_accepted: Option<bool>,
+    pub signing_enabled: Option<bool>,
+    pub signing_required: Option<bool>,
+    pub server_guid: Option<String>,
+    /// Problems met while parsing unexpected responses
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for SmbCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "SMB check for {}", self.target)?;
+        writeln!(f, "Reachable: {}", self.reachable)?;
+        writeln!(f, "Dialect: {}", self.dialect.as_deref().unwrap_or("unknown"))?;
+        writeln!(f, "SMB1 accepted: {}", describe(self.smb1_accepted))?;
+        writeln!(f, "Signing enabled: {}", describe(self.signing_enabled))?;
+        writeln!(f, "Signing required: {}", describe(self.signing_required))?;
+
+        if let Some(guid) = &self.server_guid {
+            writeln!(f, "Server GUID: {}", guid)?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn describe(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "unknown"
+    }
+}
+
+impl SmbCheck {
+    /// SMB1 acceptance and unenforced signing as findings
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if self.smb1_accepted == Some(true) {
+            findings.push(Finding::new(
+                "smb.smb1-enabled",
+                "SMBv1 is enabled",
+                Severity::High,
+                &self.target,
+                "Server accepted an SMB1-only negotiate (NT LM 0.12)",
+                "Disable SMBv1; it is deprecated and exposed to EternalBlue-class vulnerabilities"
+            ));
+        }
+
+        if self.signing_required == Some(false) {
+            findings.push(Finding::new(
+                "smb.signing-not-required",
+                "SMB signing is not required",
+                Severity::Medium,
+                &self.target,
+                &format!("Negotiated dialect {} with signing enabled={} required=false",
+                    self.dialect.as_deref().unwrap_or("unknown"),
+                    describe(self.signing_enabled)),
+                "Require SMB signing to prevent NTLM relay and tampering"
+            ));
+        }
+
+        findings
+    }
+}
+
+/// Negotiates SMB2/3 and separately tries an SMB1-only negotiate against port 445
+pub fn mercy_smb_check(target: &str) -> Result<SmbCheck, String> {
+    let addr = resolve_target(target, 445)?;
+
+    // The SMB2 negotiate decides reachability; failure to connect is an error, not a finding
+    let mut stream = connect(addr, CHECK_TIMEOUT)?;
+    let mut check = SmbCheck {
+        target: addr.to_string(),
+        reachable: true,
+        dialect: None,
+        smb1_accepted: None,
+        signing_enabled: None,
+        signing_required: None,
+        server_guid: None,
+        notes: Vec::new()
+    };
+
+    match smb_exchange(&mut stream, &smb2_negotiate_request()).and_then(|reply| parse_smb2_negotiate(&reply)) {
+        Ok(negotiated) => {
+            check.dialect = Some(negotiated.dialect);
+            check.signing_enabled = Some(negotiated.security_mode & 0x01 != 0);
+            check.signing_required = Some(negotiated.security_mode & 0x02 != 0);
+            check.server_guid = Some(negotiated.guid);
+        }
+        Err(e) => check.notes.push(format!("SMB2 negotiate: {}", e))
+    }
+
+    // SMB1 needs a fresh connection; servers with SMB1 disabled simply reset it
+    match connect(addr, CHECK_TIMEOUT).and_then(|mut s| smb_exchange(&mut s, &smb1_negotiate_request())) {
+        Ok(reply) => match parse_smb1_negotiate(&reply) {
+            Ok((accepted, security_mode)) => {
+                check.smb1_accepted = Some(accepted);
+
+                // Only an SMB1-only server leaves signing to be read from the SMB1 reply
+                if accepted && check.signing_enabled.is_none() {
+                    check.signing_enabled = Some(security_mode & 0x04 != 0);
+                    check.signing_required = Some(security_mode & 0x08 != 0);
+                }
+            }
+            Err(e) => check.notes.push(format!("SMB1 negotiate: {}", e))
+        },
+        Err(_) => check.smb1_accepted = Some(false)
+    }
+
+    Ok(check)
+}
+
+// Sends a NetBIOS-framed request and returns the framed reply payload
+fn smb_exchange(stream: &mut TcpStream, request: &[u8]) -> Result<Vec<u8>, String> {
+    let mut framed = vec![0u8];
+    framed.extend_from_slice(&(request.len() as u32).to_be_bytes()[1..]);
+    framed.extend_from_slice(request);
+    stream.write_all(&framed).map_err(|e| format!("Write failed: {}", e))?;
+
+    let header = read_exact(stream, 4)?;
+    if header[0] != 0x00 {
+        return Err(format!("Unexpected NetBIOS message type 0x{:02x}", header[0]));
+    }
+
+    let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    if length > MAX_FRAME {
+        return Err(format!("Oversized response frame ({} bytes)", length));
+    }
+
+    read_exact(stream, length)
+}
+
+fn smb2_negotiate_request() -> Vec<u8> {
+    let dialects: [u16; 5] = [0x0202, 0x0210, 0x0300, 0x0302, 0x0311];
+
+    let mut packet = Vec::new();
+    packet.extend_from_slice(b"\xFESMB");
+    packet.extend_from_slice(&64u16.to_le_bytes());
+    packet.extend_from_slice(&[0u8; 2]);
+    packet.extend_from_slice(&[0u8; 4]);
+    packet.extend_from_slice(&0u16.to_le_bytes());
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    packet.extend_from_slice(&[0u8; 4 + 4 + 8 + 4 + 4 + 8 + 16]);
+
+    // Negotiate body; contexts are required when offering 3.1.1
+    let body_start = packet.len();
+    packet.extend_from_slice(&36u16.to_le_bytes());
+    packet.extend_from_slice(&(dialects.len() as u16).to_le_bytes());
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    packet.extend_from_slice(&0u16.to_le_bytes());
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(b"mercy-smb-check!");
+    let context_offset_at = packet.len();
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&2u16.to_le_bytes());
+    packet.extend_from_slice(&0u16.to_le_bytes());
+
+    for dialect in dialects {
+        packet.extend_from_slice(&dialect.to_le_bytes());
+    }
+
+    debug_assert_eq!(body_start, 64);
+    while packet.len() % 8 != 0 {
+        packet.push(0);
+    }
+
+    let context_offset = packet.len() as u32;
+    packet[context_offset_at..context_offset_at + 4].copy_from_slice(&context_offset.to_le_bytes());
+
+    // Pre-authentication integrity: SHA-512 with a 32-byte salt
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    packet.extend_from_slice(&38u16.to_le_bytes());
+    packet.extend_from_slice(&[0u8; 4]);
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    packet.extend_from_slice(&32u16.to_le_bytes());
+    packet.extend_from_slice(&1u16.to_le_bytes());
+    packet.extend_from_slice(&[0x5A; 32]);
+
+    while packet.len() % 8 != 0 {
+        packet.push(0);
+    }
+
+    // Encryption capabilities: AES-128-GCM, AES-128-CCM
+    packet.extend_from_slice(&2u16.to_le_bytes());
+    packet.extend_from_slice(&6u16.to_le_bytes());
+    packet.extend_from_slice(&[0u8; 4]);
+    packet.extend_from_slice(&2u16.to_le_bytes());
+    packet.extend_from_slice(&2u16.to_le_bytes());
+    packet.extend_from_slice(&1u16.to_le_bytes());
+
+    packet
+}
+
+struct Smb2Negotiated {
+    dialect: String,
+    security_mode: u16,
+    guid: String
+}
+
+fn parse_smb2_negotiate(reply: &[u8]) -> Result<Smb2Negotiated, String> {
+    if reply.starts_with(b"\xFFSMB") {
+        return Err("Server answered with SMB1 only".to_string());
+    }
+
+    if !reply.starts_with(b"\xFESMB") || reply.len() < 64 + 24 {
+        return Err("Response is not an SMB2 negotiate reply".to_string());
+    }
+
+    let status = le32(reply, 8).unwrap_or(u32::MAX);
+    if status != 0 {
+        return Err(format!("Negotiate failed with NTSTATUS 0x{:08x}", status));
+    }
+
+    let body = &reply[64..];
+    let security_mode = le16(body, 2).ok_or("Truncated negotiate reply")?;
+    let dialect = le16(body, 4).ok_or("Truncated negotiate reply")?;
+    let guid = body.get(8..24).ok_or("Truncated negotiate reply")?;
+
+    let dialect = match dialect {
+        0x0202 => "2.0.2".to_string(),
+        0x0210 => "2.1".to_string(),
+        0x0300 => "3.0".to_string(),
+        0x0302 => "3.0.2".to_string(),
+        0x0311 => "3.1.1".to_string(),
+        0x02FF => "2.??? (wildcard)".to_string(),
+        other => format!("unknown (0x{:04x})", other)
+    };
+
+    Ok(Smb2Negotiated {
+        dialect,
+        security_mode,
+        guid: guid.iter().map(|b| format!("{:02x}", b)).collect()
+    })
+}
+
+fn smb1_negotiate_request() -> Vec<u8> {
+    let mut packet = Vec::new();
+    packet.extend_from_slice(b"\xFFSMB");
+    packet.push(0x72);
+    packet.extend_from_slice(&[0u8; 4]);
+    packet.push(0x18);
+    packet.extend_from_slice(&0xC853u16.to_le_bytes());
+    packet.extend_from_slice(&[0u8; 12]);
+    packet.extend_from_slice(&0xFFFFu16.to_le_bytes());
+    packet.extend_from_slice(&0xFEFFu16.to_le_bytes());
+    packet.extend_from_slice(&0u16.to_le_bytes());
+    packet.extend_from_slice(&0u16.to_le_bytes());
+
+    let dialect = b"\x02NT LM 0.12\x00";
+    packet.push(0);
+    packet.extend_from_slice(&(dialect.len() as u16).to_le_bytes());
+    packet.extend_from_slice(dialect);
+
+    packet
+}
+
+// Returns (dialect accepted, SMB1 security mode byte)
+fn parse_smb1_negotiate(reply: &[u8]) -> Result<(bool, u8), String> {
+    if reply.starts_with(b"\xFESMB") {
+        // Some servers answer an SMB1 negotiate with SMB2 even when SMB1 is off
+        return Ok((false, 0));
+    }
+
+    if !reply.starts_with(b"\xFFSMB") || reply.len() < 35 {
+        return Err("Response is not an SMB1 negotiate reply".to_string());
+    }
+
+    let status = le32(reply, 5).unwrap_or(u32::MAX);
+    if status != 0 {
+        return Ok((false, 0));
+    }
+
+    let dialect_index = le16(reply, 33).ok_or("Truncated SMB1 reply")?;
+    let security_mode = reply.get(35).copied().unwrap_or(0);
+
+    Ok((dialect_index != 0xFFFF, security_mode))
+}
+
+/* RDP */
+
+/// Outcome of an RDP X.224 negotiation check
+#[derive(Debug, Clone, Serialize)]
+pub struct RdpCheck {
+    pub target: String,
+    pub answered: bool,
+    /// Standard RDP security (no TLS) accepted
+    pub standard_rdp: Option<bool>,
+    pub tls: Option<bool>,
+    /// CredSSP / Network Level Authentication
+    pub nla: Option<bool>,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for RdpCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "RDP check for {}", self.target)?;
+        writeln!(f, "Answered: {}", self.answered)?;
+        writeln!(f, "Standard RDP security: {}", describe(self.standard_rdp))?;
+        writeln!(f, "TLS: {}", describe(self.tls))?;
+        writeln!(f, "NLA (CredSSP): {}", describe(self.nla))?;
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RdpCheck {
+    /// Missing NLA enforcement and standard RDP security as findings
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if self.standard_rdp == Some(true) {
+            findings.push(Finding::new(
+                "rdp.standard-security",
+                "RDP accepts standard RDP security",
+                Severity::Medium,
+                &self.target,
+                "Server selected standard RDP security when TLS and NLA were not requested",
+                "Require TLS or NLA; standard RDP security is vulnerable to man-in-the-middle attacks"
+            ));
+        }
+
+        if self.answered && self.nla != Some(true) {
+            findings.push(Finding::new(
+                "rdp.nla-unavailable",
+                "RDP does not offer Network Level Authentication",
+                Severity::Medium,
+                &self.target,
+                &format!("NLA (CredSSP): {}", describe(self.nla)),
+                "Enable and require NLA so clients authenticate before a session is created"
+            ));
+        } else if self.nla == Some(true) && (self.standard_rdp == Some(true) || self.tls == Some(true)) {
+            findings.push(Finding::new(
+                "rdp.nla-not-required",
+                "RDP does not require Network Level Authentication",
+                Severity::Low,
+                &self.target,
+                "Server offers NLA but also accepts connections without it",
+                "Require NLA for all connections"
+            ));
+        }
+
+        findings
+    }
+}
+
+// RDP_NEG_REQ requestedProtocols flags
+const PROTOCOL_RDP: u32 = 0x0;
+const PROTOCOL_SSL: u32 = 0x1;
+const PROTOCOL_HYBRID: u32 = 0x2;
+
+// What the server said to one connection request
+enum RdpNegotiation {
+    Selected(u32),
+    Failure(u32),
+    // Pre-RDP 5.2 servers send a bare connection confirm: standard security only
+    Legacy
+}
+
+/// Sends X.224 connection requests for each security protocol on port 3389 and records which are accepted
+pub fn mercy_rdp_check(target: &str) -> Result<RdpCheck, String> {
+    let addr = resolve_target(target, 3389)?;
+
+    let mut check = RdpCheck {
+        target: addr.to_string(),
+        answered: false,
+        standard_rdp: None,
+        tls: None,
+        nla: None,
+        notes: Vec::new()
+    };
+
+    let probes = [(PROTOCOL_RDP, "standard"), (PROTOCOL_SSL, "tls"), (PROTOCOL_SSL | PROTOCOL_HYBRID, "nla")];
+
+    for (index, (requested, label)) in probes.iter().enumerate() {
+        let result = connect(addr, CHECK_TIMEOUT).and_then(|mut stream| rdp_negotiate(&mut stream, *requested));
+
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            // Without any answer to the first probe the host is simply not speaking RDP to us
+            Err(e) if index == 0 => return Err(e),
+            Err(e) => {
+                check.notes.push(format!("{} probe: {}", label, e));
+                continue;
+            }
+        };
+
+        check.answered = true;
+
+        match (label, outcome) {
+            (&"standard", RdpNegotiation::Legacy) => check.standard_rdp = Some(true),
+            (_, RdpNegotiation::Legacy) => {
+                check.tls = Some(false);
+                check.nla = Some(false);
+                check.notes.push("Server does not support security negotiation (legacy RDP)".to_string());
+                break;
+            }
+            (&"standard", RdpNegotiation::Selected(_)) => check.standard_rdp = Some(true),
+            (&"standard", RdpNegotiation::Failure(code)) => {
+                check.standard_rdp = Some(false);
+                check.notes.push(format!("Standard security refused: {}", failure_reason(code)));
+            }
+            (&"tls", RdpNegotiation::Selected(selected)) => check.tls = Some(selected & PROTOCOL_SSL != 0),
+            (&"tls", RdpNegotiation::Failure(code)) => {
+                check.tls = Some(false);
+                check.notes.push(format!("TLS refused: {}", failure_reason(code)));
+            }
+            (_, RdpNegotiation::Selected(selected)) => {
+                check.nla = Some(selected & PROTOCOL_HYBRID != 0);
+                if selected & PROTOCOL_SSL != 0 {
+                    check.tls = Some(true);
+                }
+            }
+            (_, RdpNegotiation::Failure(code)) => {
+                check.nla = Some(false);
+                check.notes.push(format!("NLA refused: {}", failure_reason(code)));
+            }
+        }
+    }
+
+    Ok(check)
+}
+
+fn failure_reason(code: u32) -> String {
+    match code {
+        1 => "SSL required by server".to_string(),
+        2 => "SSL not allowed by server".to_string(),
+        3 => "SSL certificate not on server".to_string(),
+        4 => "inconsistent flags".to_string(),
+        5 => "NLA (hybrid) required by server".to_string(),
+        6 => "SSL with user authentication required by server".to_string(),
+        other => format!("failure code {}", other)
+    }
+}
+
+fn rdp_negotiate(stream: &mut TcpStream, requested: u32) -> Result<RdpNegotiation, String> {
+    let cookie = b"Cookie: mstshash=mercy\r\n";
+
+    let mut x224 = vec![0u8, 0xE0, 0, 0, 0, 0, 0];
+    x224.extend_from_slice(cookie);
+    x224.extend_from_slice(&[0x01, 0x00]);
+    x224.extend_from_slice(&8u16.to_le_bytes());
+    x224.extend_from_slice(&requested.to_le_bytes());
+    x224[0] = (x224.len() - 1) as u8;
+
+    let mut packet = vec![0x03, 0x00];
+    packet.extend_from_slice(&((x224.len() + 4) as u16).to_be_bytes());
+    packet.extend_from_slice(&x224);
+    stream.write_all(&packet).map_err(|e| format!("Write failed: {}", e))?;
+
+    let tpkt = read_exact(stream, 4)?;
+    if tpkt[0] != 0x03 {
+        return Err(format!("Response is not TPKT (first byte 0x{:02x})", tpkt[0]));
+    }
+
+    let length = u16::from_be_bytes([tpkt[2], tpkt[3]]) as usize;
+    if !(7..=MAX_FRAME).contains(&length) {
+        return Err(format!("Implausible TPKT length {}", length));
+    }
+
+    let body = read_exact(stream, length - 4)?;
+    let indicator = body[0] as usize;
+
+    if body.get(1).map(|code| code & 0xF0) != Some(0xD0) {
+        return Err(format!("Expected X.224 connection confirm, got 0x{:02x}", body.get(1).copied().unwrap_or(0)));
+    }
+
+    // Negotiation data, when present, follows the 7-byte X.224 header
+    let negotiation = match body.get(7..(indicator + 1).min(body.len())) {
+        Some(data) if data.len() >= 8 => data,
+        _ => return Ok(RdpNegotiation::Legacy)
+    };
+
+    let value = le32(negotiation, 4).ok_or("Truncated negotiation response")?;
+
+    match negotiation[0] {
+        0x02 => Ok(RdpNegotiation::Selected(value)),
+        0x03 => Ok(RdpNegotiation::Failure(value)),
+        other => Err(format!("Unknown negotiation response type 0x{:02x}", other))
+    }
+}
+
+/* FTP */
+
+/// Outcome of an FTP banner and anonymous login check
+#[derive(Debug, Clone, Serialize)]
+pub struct FtpCheck {
+    pub target: String,
+    pub banner: String,
+    pub anonymous_login: bool,
+    /// Features listed by FEAT after login (empty when unsupported)
+    pub features: Vec<String>,
+    /// Whether the server offers to upgrade the session with AUTH TLS
+    pub tls_supported: bool,
+    /// Suggested severity for reporting
+    pub severity: Severity,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for FtpCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "FTP check for {}", self.target)?;
+        writeln!(f, "Banner: {}", self.banner)?;
+        writeln!(f, "Anonymous login: {}", if self.anonymous_login { "allowed" } else { "refused" })?;
+        writeln!(f, "AUTH TLS: {}", if self.tls_supported { "offered" } else { "not offered" })?;
+
+        if !self.features.is_empty() {
+            writeln!(f, "Features: {}", self.features.join(", "))?;
+        }
+
+        writeln!(f, "Severity: {}", self.severity)?;
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FtpCheck {
+    /// Anonymous access and cleartext logins as findings
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        if self.anonymous_login {
+            findings.push(Finding::new(
+                "ftp.anonymous-login",
+                "FTP allows anonymous login",
+                Severity::High,
+                &self.target,
+                &format!("USER anonymous / PASS guest@ accepted; banner: {}", self.banner),
+                "Disable anonymous FTP unless the server intentionally publishes public files"
+            ));
+        }
+
+        if !self.tls_supported {
+            findings.push(Finding::new(
+                "ftp.cleartext",
+                "FTP does not offer TLS",
+                Severity::Medium,
+                &self.target,
+                &format!("FEAT did not list AUTH TLS; banner: {}", self.banner),
+                "Offer and require FTPS (AUTH TLS) or replace FTP with SFTP"
+            ));
+        }
+
+        findings
+    }
+}
+
+// Longest reply line we keep before giving up on a misbehaving server
+const MAX_LINE: usize = 4096;
+
+// Maximum lines in a single multi-line FTP reply
+const MAX_REPLY_LINES: usize = 256;
+
+/// Grabs the FTP banner on port 21, tries the anonymous convention (USER anonymous / PASS guest@) and lists FEAT
+pub fn mercy_ftp_check(target: &str) -> Result<FtpCheck, String> {
+    let addr = resolve_target(target, 21)?;
+    let mut stream = connect(addr, CHECK_TIMEOUT)?;
+
+    let (code, banner) = ftp_reply(&mut stream)?;
+    if code != 220 {
+        return Err(format!("Unexpected FTP greeting {}: {}", code, banner.join(" ")));
+    }
+
+    let mut check = FtpCheck {
+        target: addr.to_string(),
+        banner: banner.iter().map(|line| line.get(4..).unwrap_or("").trim()).collect::<Vec<_>>().join(" "),
+        anonymous_login: false,
+        features: Vec::new(),
+        tls_supported: false,
+        severity: Severity::Low,
+        notes: Vec::new()
+    };
+
+    let (code, _) = ftp_command(&mut stream, "USER anonymous")?;
+    let code = match code {
+        331 => ftp_command(&mut stream, "PASS guest@")?.0,
+        other => other
+    };
+    check.anonymous_login = code == 230;
+
+    // FEAT is valid before and after login; ask once, now that the session state is settled
+    match ftp_command(&mut stream, "FEAT") {
+        Ok((211, lines)) => {
+            check.features = lines.iter()
+                .skip(1)
+                .take(lines.len().saturating_sub(2))
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect();
+        }
+        Ok((code, _)) => check.notes.push(format!("FEAT not supported ({})", code)),
+        Err(e) => check.notes.push(format!("FEAT: {}", e))
+    }
+
+    check.tls_supported = check.features.iter().any(|feature| feature.to_ascii_uppercase().starts_with("AUTH") && feature.to_ascii_uppercase().contains("TLS"));
+
+    // Best effort; the server may already have hung up
+    let _ = stream.write_all(b"QUIT\r\n");
+
+    check.severity = if check.anonymous_login {
+        check.notes.push("Anonymous login accepted".to_string());
+        Severity::High
+    } else if !check.tls_supported {
+        check.notes.push("Credentials would be sent in cleartext".to_string());
+        Severity::Medium
+    } else {
+        Severity::Low
+    };
+
+    Ok(check)
+}
+
+fn ftp_command(stream: &mut TcpStream, command: &str) -> Result<(u16, Vec<String>), String> {
+    stream.write_all(format!("{}\r\n", command).as_bytes()).map_err(|e| format!("Write failed: {}", e))?;
+    ftp_reply(stream)
+}
+
+// Reads a (possibly multi-line "123-" ... "123 ") reply, returning the code and raw lines
+fn ftp_reply(stream: &mut TcpStream) -> Result<(u16, Vec<String>), String> {
+    let first = read_line(stream)?;
+    let code: u16 = first.get(..3).and_then(|c| c.parse().ok()).ok_or_else(|| format!("Malformed FTP reply: {}", first))?;
+    let mut lines = vec![first.clone()];
+
+    if first.as_bytes().get(3) == Some(&b'-') {
+        let terminator = format!("{} ", code);
+        loop {
+            if lines.len() >= MAX_REPLY_LINES {
+                return Err("FTP reply has too many lines".to_string());
+            }
+
+            let line = read_line(stream)?;
+            let done = line.starts_with(&terminator) || line == code.to_string();
+            lines.push(line);
+            if done {
+                break;
+            }
+        }
+    }
+
+    Ok((code, lines))
+}
+
+// Reads one CRLF (or LF) terminated line byte by byte so no data is buffered past it
+fn read_line(stream: &mut TcpStream) -> Result<String, String> {
+    let mut line = Vec::new();
+
+    loop {
+        let byte = read_exact(stream, 1)?[0];
+        if byte == b'\n' {
+            break;
+        }
+
+        if line.len() >= MAX_LINE {
+            return Err("Reply line too long".to_string());
+        }
+        line.push(byte);
+    }
+
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    Ok(String::from_utf8_lossy(&line).to_string())
+}
+
+/* Telnet */
+
+/// Outcome of a Telnet banner check
+#[derive(Debug, Clone, Serialize)]
+pub struct TelnetCheck {
+    pub target: String,
+    pub banner: String,
+    /// Option codes the server asked about (all refused)
+    pub options_refused: Vec<u8>,
+    /// Whether a login or password prompt was seen
+    pub login_prompt: bool,
+    /// Suggested severity for reporting
+    pub severity: Severity
+}
+
+impl fmt::Display for TelnetCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Telnet check for {}", self.target)?;
+        writeln!(f, "Login prompt: {}", if self.login_prompt { "yes" } else { "no" })?;
+        writeln!(f, "Options refused: {:?}", self.options_refused)?;
+        writeln!(f, "Severity: {}", self.severity)?;
+        writeln!(f, "Banner:")?;
+        writeln!(f, "{}", self.banner)
+    }
+}
+
+impl TelnetCheck {
+    /// The exposed Telnet service as a finding
+    pub fn findings(&self) -> Vec<Finding> {
+        let first_line = self.banner.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+
+        vec![Finding::new(
+            "telnet.exposed",
+            "Telnet service exposed",
+            self.severity,
+            &self.target,
+            &format!("Telnet answered (login prompt: {}); banner: {}", if self.login_prompt { "yes" } else { "no" }, first_line),
+            "Disable Telnet and use SSH; Telnet sends credentials in cleartext"
+        )]
+    }
+}
+
+// Telnet command bytes
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+
+// Stop collecting once the banner is this large
+const MAX_BANNER: usize = 4096;
+
+// Quiet period after which the banner is considered complete
+const BANNER_IDLE: Duration = Duration::from_secs(2);
+
+/// Connects to port 23, refuses every option the server proposes, and captures the login banner
+pub fn mercy_telnet_check(target: &str) -> Result<TelnetCheck, String> {
+    let addr = resolve_target(target, 23)?;
+    let mut stream = connect(addr, CHECK_TIMEOUT)?;
+    stream.set_read_timeout(Some(BANNER_IDLE)).map_err(|e| format!("Unable to set read timeout: {}", e))?;
+
+    let deadline = std::time::Instant::now() + CHECK_TIMEOUT;
+    let mut text = Vec::new();
+    let mut refused = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let mut state = TelnetState::Data;
+
+    while text.len() < MAX_BANNER && std::time::Instant::now() < deadline {
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(format!("Read failed: {}", e))
+        };
+
+        let mut replies = Vec::new();
+        for &byte in &buffer[..read] {
+            state = match (state, byte) {
+                (TelnetState::Data, IAC) => TelnetState::Iac,
+                (TelnetState::Data, byte) => {
+                    text.push(byte);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, IAC) => {
+                    text.push(IAC);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, command @ (DO | DONT | WILL | WONT)) => TelnetState::Option(command),
+                (TelnetState::Iac, SB) => TelnetState::Sub,
+                (TelnetState::Iac, _) => TelnetState::Data,
+                (TelnetState::Option(command), option) => {
+                    // Refuse everything: DO -> WONT, WILL -> DONT; acknowledgements need no reply
+                    let reply = match command {
+                        DO => Some(WONT),
+                        WILL => Some(DONT),
+                        _ => None
+                    };
+                    if let Some(reply) = reply {
+                        replies.extend_from_slice(&[IAC, reply, option]);
+                        if !refused.contains(&option) {
+                            refused.push(option);
+                        }
+                    }
+                    TelnetState::Data
+                }
+                (TelnetState::Sub, IAC) => TelnetState::SubIac,
+                (TelnetState::Sub, _) => TelnetState::Sub,
+                (TelnetState::SubIac, SE) => TelnetState::Data,
+                (TelnetState::SubIac, _) => TelnetState::Sub
+            };
+        }
+
+        if !replies.is_empty() {
+            stream.write_all(&replies).map_err(|e| format!("Write failed: {}", e))?;
+        }
+
+        if has_prompt(&text) {
+            break;
+        }
+    }
+
+    if text.is_empty() && refused.is_empty() {
+        return Err(format!("No Telnet data received from {}", addr));
+    }
+
+    let banner: String = String::from_utf8_lossy(&text)
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .collect();
+
+    let login_prompt = has_prompt(&text);
+
+    Ok(TelnetCheck {
+        target: addr.to_string(),
+        banner: banner.trim().to_string(),
+        options_refused: refused,
+        login_prompt,
+        // Any reachable Telnet service exposes credentials in cleartext
+        severity: if login_prompt { Severity::High } else { Severity::Medium }
+    })
+}
+
+#[derive(Clone, Copy)]
+enum TelnetState {
+    Data,
+    Iac,
+    Option(u8),
+    Sub,
+    SubIac
+}
+
+fn has_prompt(text: &[u8]) -> bool {
+    let tail = String::from_utf8_lossy(&text[text.len().saturating_sub(64)..]).to_ascii_lowercase();
+    let tail = tail.trim_end();
+    ["login:", "username:", "user:", "password:", "user name:"].iter().any(|prompt| tail.ends_with(prompt))
+}
@@ -0,0 +1,168 @@
+/*
+    Non-cryptographic checksums for fingerprinting and deduplicating artifacts
+
+    CRC-32 is the IEEE 802.3 polynomial (reflected 0xEDB88320) used by zlib, gzip, PNG and ZIP,
+    so its values match `crc32` tools and `cksum -a crc32b` (plain `cksum` is the POSIX CRC, a
+    different checksum). xxHash64 follows the reference implementation and is several times
+    faster than any digest; neither resists deliberate collisions, so use them to group files,
+    not to prove two files are the same.
+
+    Both are streaming: feed data in pieces of any size and the result is the same as for the
+    whole.
+*/
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+// Remainder of each byte value, for one table lookup per byte
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+// xxHash64 consumes input in stripes of four 8-byte lanes
+const STRIPE: usize = 32;
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ CRC32_POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE) over `data`, continuing from `crc`; callers start at 0xFFFFFFFF and invert the result
+pub(crate) fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC-32 (IEEE, as zlib computes it) of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0xFFFF_FFFF, data) ^ 0xFFFF_FFFF
+}
+
+/// xxHash64 of `data` with `seed` (0 is the usual default)
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    let mut hasher = XxHash64::new(seed);
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Streaming xxHash64
+#[derive(Debug, Clone)]
+pub(crate) struct XxHash64 {
+    seed: u64,
+    lanes: [u64; 4],
+    buffer: [u8; STRIPE],
+    buffered: usize,
+    total: u64
+}
+
+impl XxHash64 {
+    pub(crate) fn new(seed: u64) -> XxHash64 {
+        XxHash64 {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1)
+            ],
+            buffer: [0; STRIPE],
+            buffered: 0,
+            total: 0
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.total += data.len() as u64;
+
+        // Top up a partial stripe left by the previous call first
+        if self.buffered > 0 {
+            let take = (STRIPE - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+
+            if self.buffered < STRIPE {
+                return;
+            }
+            let stripe = self.buffer;
+            self.stripe(&stripe);
+            self.buffered = 0;
+        }
+
+        let mut stripes = data.chunks_exact(STRIPE);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        let mut hash = if self.total >= STRIPE as u64 {
+            let [v1, v2, v3, v4] = self.lanes;
+            let mut hash = v1.rotate_left(1).wrapping_add(v2.rotate_left(7)).wrapping_add(v3.rotate_left(12)).wrapping_add(v4.rotate_left(18));
+            for lane in self.lanes {
+                hash = (hash ^ round(0, lane)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+            }
+            hash
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+        hash = hash.wrapping_add(self.total);
+
+        let mut tail = &self.buffer[..self.buffered];
+        while tail.len() >= 8 {
+            hash ^= round(0, read_u64(tail));
+            hash = hash.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+            tail = &tail[8..];
+        }
+        if tail.len() >= 4 {
+            hash ^= (u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64).wrapping_mul(PRIME64_1);
+            hash = hash.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+            tail = &tail[4..];
+        }
+        for byte in tail {
+            hash ^= (*byte as u64).wrapping_mul(PRIME64_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        // Avalanche
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^ (hash >> 32)
+    }
+
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = round(*lane, read_u64(word));
+        }
+    }
+}
+
+fn round(accumulator: u64, input: u64) -> u64 {
+    accumulator.wrapping_add(input.wrapping_mul(PRIME64_2)).rotate_left(31).wrapping_mul(PRIME64_1)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+}
@@ -0,0 +1,232 @@
+/*
+    CIDR block parsing and address arithmetic shared by the network features
+*/
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr
+};
+
+/// An IPv4 or IPv6 network in CIDR notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8
+}
+
+impl Cidr {
+    /// Builds a network, masking off any host bits of `addr`
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Cidr, String> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            return Err(format!("Prefix length /{} is out of range for {}", prefix, addr));
+        }
+
+        Ok(Cidr { network: mask(addr, prefix), prefix })
+    }
+
+    /// Network (first) address
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Prefix length in bits
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Width of the address family in bits (32 or 128)
+    pub fn bits(&self) -> u8 {
+        if self.network.is_ipv4() { 32 } else { 128 }
+    }
+
+    /// Last address in the block (the broadcast address for IPv4)
+    pub fn last(&self) -> IpAddr {
+        let host_bits = (self.bits() - self.prefix) as u32;
+        let host_mask = if host_bits == 128 { u128::MAX } else { (1u128 << host_bits) - 1 };
+        from_u128(to_u128(self.network) | host_mask, self.network.is_ipv4())
+    }
+
+    /// Total number of addresses in the block (saturating for a whole IPv6 /0)
+    pub fn size(&self) -> u128 {
+        let host_bits = (self.bits() - self.prefix) as u32;
+        1u128.checked_shl(host_bits).unwrap_or(u128::MAX)
+    }
+
+    /// Whether `addr` falls inside the block (always false across address families)
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        addr.is_ipv4() == self.network.is_ipv4() && mask(addr, self.prefix) == self.network
+    }
+
+    /// Whether `other` is entirely inside this block
+    pub fn contains_cidr(&self, other: &Cidr) -> bool {
+        other.prefix >= self.prefix && self.contains(other.network)
+    }
+
+    /// Addresses that can be assigned to hosts; IPv4 blocks larger than /31 skip network and broadcast
+    pub fn hosts(&self) -> impl Iterator<Item = IpAddr> {
+        let v4 = self.network.is_ipv4();
+        let mut start = to_u128(self.network);
+        let mut end = to_u128(self.last());
+
+        if v4 && self.prefix < 31 {
+            start += 1;
+            end -= 1;
+        }
+
+        (start..=end).map(move |value| from_u128(value, v4))
+    }
+
+    /// Number of addresses yielded by `hosts`
+    pub fn host_count(&self) -> u128 {
+        if self.network.is_ipv4() && self.prefix < 31 { self.size() - 2 } else { self.size() }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parses "10.0.0.0/8", "2001:db8::/32" or a bare address (treated as a single-host block)
+    fn from_str(input: &str) -> Result<Cidr, String> {
+        let input = input.trim();
+
+        let (addr, prefix) = match input.split_once('/') {
+            Some((addr, prefix)) => {
+                let prefix: u8 = prefix.trim().parse().map_err(|_| format!("Invalid prefix length in '{}'", input))?;
+                (addr.trim(), Some(prefix))
+            }
+            None => (input, None)
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| format!("Invalid IP address in '{}'", input))?;
+        let prefix = prefix.unwrap_or(if addr.is_ipv4() { 32 } else { 128 });
+
+        Cidr::new(addr, prefix)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+/// Numeric value of an address (IPv4 occupies the low 32 bits)
+pub(crate) fn to_u128(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(v4) => u32::from(v4) as u128,
+        IpAddr::V6(v6) => u128::from(v6)
+    }
+}
+
+pub(crate) fn from_u128(value: u128, v4: bool) -> IpAddr {
+    if v4 {
+        IpAddr::V4(Ipv4Addr::from(value as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(value))
+    }
+}
+
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    let host_bits = (bits - prefix.min(bits)) as u32;
+    let value = to_u128(addr);
+    let masked = if host_bits >= 128 { 0 } else { value & !((1u128 << host_bits) - 1) };
+    from_u128(masked, addr.is_ipv4())
+}
+
+// Trie node: children for bit 0 and 1 (0 when absent, the root is never a child) and the value stored there
+type TrieNode = ([u32; 2], Option<u32>);
+
+/// Binary trie over the bits of IPv4 and IPv6 blocks, each stored block carrying a value
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PrefixTrie {
+    v4: Vec<TrieNode>,
+    v6: Vec<TrieNode>
+}
+
+impl PrefixTrie {
+    /// Stores `value` for the block, returning the value it replaces
+    pub(crate) fn insert(&mut self, cidr: &Cidr, value: u32) -> Option<u32> {
+        let (bits, width) = (to_u128(cidr.network), cidr.bits());
+        let nodes = if cidr.network.is_ipv4() { &mut self.v4 } else { &mut self.v6 };
+        if nodes.is_empty() {
+            nodes.push(([0, 0], None));
+        }
+
+        let mut node = 0;
+        for depth in 0..cidr.prefix {
+            let bit = bit_at(bits, width, depth);
+            if nodes[node].0[bit] == 0 {
+                nodes.push(([0, 0], None));
+                let child = (nodes.len() - 1) as u32;
+                nodes[node].0[bit] = child;
+            }
+            node = nodes[node].0[bit] as usize;
+        }
+
+        nodes[node].1.replace(value)
+    }
+
+    /// Stored blocks containing `cidr` (itself included), least specific first, as prefix length and value
+    pub(crate) fn covering(&self, cidr: &Cidr) -> Vec<(u8, u32)> {
+        let (bits, width) = (to_u128(cidr.network), cidr.bits());
+        let nodes = self.nodes(cidr);
+        let mut found = Vec::new();
+        let Some(root) = nodes.first() else { return found };
+
+        found.extend(root.1.map(|value| (0, value)));
+        let mut node = 0;
+        for depth in 0..cidr.prefix {
+            match nodes[node].0[bit_at(bits, width, depth)] {
+                0 => break,
+                child => node = child as usize
+            }
+            found.extend(nodes[node].1.map(|value| (depth + 1, value)));
+        }
+
+        found
+    }
+
+    /// Most specific stored block containing the address
+    pub(crate) fn longest(&self, addr: IpAddr) -> Option<(u8, u32)> {
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        self.covering(&Cidr { network: addr, prefix: bits }).pop()
+    }
+
+    /// Values of the stored blocks inside `cidr` (itself included)
+    pub(crate) fn within(&self, cidr: &Cidr) -> Vec<u32> {
+        let (bits, width) = (to_u128(cidr.network), cidr.bits());
+        let nodes = self.nodes(cidr);
+        let mut found = Vec::new();
+        if nodes.is_empty() {
+            return found;
+        }
+
+        let mut node = 0;
+        for depth in 0..cidr.prefix {
+            match nodes[node].0[bit_at(bits, width, depth)] {
+                0 => return found,
+                child => node = child as usize
+            }
+        }
+
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            found.extend(nodes[node].1);
+            stack.extend(nodes[node].0.iter().filter(|child| **child != 0).map(|child| *child as usize));
+        }
+
+        found
+    }
+
+    fn nodes(&self, cidr: &Cidr) -> &[TrieNode] {
+        if cidr.network.is_ipv4() { &self.v4 } else { &self.v6 }
+    }
+}
+
+// Bit `depth` of an address counted from the most significant end
+fn bit_at(bits: u128, width: u8, depth: u8) -> usize {
+    ((bits >> (width - 1 - depth)) & 1) as usize
+}
@@ -0,0 +1,540 @@
+/*
+    Classical cipher analysis
+
+    Letter and digraph frequencies, the index of coincidence and a chi-squared distance from
+    English are the numbers that separate plaintext and transpositions (English statistics),
+    monoalphabetic substitutions (English IC, wrong letters) and polyalphabetic or random
+    text (flat IC). The same chi-squared score picks the shift of a Caesar cipher, discounted by
+    how many of the text's letter pairs are common English bigrams: a couple of short words have
+    too few letters for their counts alone to tell "Hello World" from "Ebiil Tloia".
+
+    ROT-N rotates letters only, keeping case and passing everything else through; ROT47 rotates
+    the 94 printable ASCII characters from '!' to '~' and is its own inverse.
+
+    General substitutions are solved by hill climbing over keys, scored by how likely their
+    plaintext's quadgrams are in English, with random restarts to escape local maxima.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+
+use crate::{
+    rng::{MercyRng, random_seed},
+    trailing_options
+};
+
+// English letter frequencies, A to Z
+const ENGLISH: [f64; 26] = [
+    0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, 0.06094, 0.06966, 0.00153, 0.00772, 0.04025, 0.02406,
+    0.06749, 0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056, 0.02758, 0.00978, 0.02360, 0.00150, 0.01974, 0.00074
+];
+
+// Share of spaces in English prose
+const ENGLISH_SPACE: f64 = 0.17;
+
+// The commonest letter pairs of English prose
+const COMMON_BIGRAMS: [&[u8; 2]; 20] = [
+    b"TH", b"HE", b"IN", b"ER", b"AN", b"RE", b"ND", b"ON", b"EN", b"AT", b"OU", b"ED", b"HA", b"TO", b"OR", b"IT", b"IS", b"HI", b"ES", b"NG"
+];
+
+// Part of the English similarity that comes from common bigrams rather than letter frequencies
+const BIGRAM_WEIGHT: f64 = 0.25;
+
+// Index of coincidence of English text
+const ENGLISH_IC: f64 = 0.0667;
+
+// Index of coincidence of uniformly random letters (1/26)
+const RANDOM_IC: f64 = 0.0385;
+
+// Digraphs listed in a frequency report
+const TOP_DIGRAPHS: usize = 26;
+
+/// Count and share of one letter or digraph
+#[derive(Debug, Clone, Serialize)]
+pub struct Frequency {
+    pub count: u64,
+    pub frequency: f64
+}
+
+/// A digraph and how often it occurs
+#[derive(Debug, Clone, Serialize)]
+pub struct Digraph {
+    pub digraph: String,
+    pub count: u64,
+    pub frequency: f64
+}
+
+/// Frequency statistics of the letters in a text
+#[derive(Debug, Clone, Serialize)]
+pub struct FrequencyReport {
+    pub letters_total: u64,
+    /// Every letter A to Z, case folded
+    pub letters: BTreeMap<char, Frequency>,
+    /// Other non-space characters as they appear
+    pub other_characters: BTreeMap<char, u64>,
+    /// Most common pairs of consecutive letters, ignoring anything between them
+    pub digraphs: Vec<Digraph>,
+    pub index_of_coincidence: f64,
+    pub english_ic: f64,
+    pub random_ic: f64,
+    /// Chi-squared distance of the letter counts from English (lower is more English)
+    pub chi_squared: f64,
+    /// What the statistics suggest the text is
+    pub assessment: String
+}
+
+/// A candidate Caesar shift and its score
+#[derive(Debug, Clone, Serialize)]
+pub struct CaesarCandidate {
+    /// Shift applied when encrypting (ciphertext = plaintext + shift)
+    pub shift: u8,
+    pub chi_squared: f64
+}
+
+/// The most English-like Caesar decryption
+#[derive(Debug, Clone, Serialize)]
+pub struct CaesarSolution {
+    pub shift: u8,
+    pub plaintext: String,
+    pub chi_squared: f64,
+    /// The next best shifts, to judge how clear the winner is
+    pub runners_up: Vec<CaesarCandidate>
+}
+
+/// Letter, digraph and coincidence statistics of `text`
+pub fn mercy_freq_analysis(text: &str) -> Result<FrequencyReport, String> {
+    let counts = letter_counts(text);
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return Err("No letters to analyse".to_string());
+    }
+
+    let letters = (0..26u8)
+        .map(|index| ((b'A' + index) as char, Frequency { count: counts[index as usize], frequency: round(counts[index as usize] as f64 / total as f64) }))
+        .collect();
+
+    let mut other_characters = BTreeMap::new();
+    for c in text.chars().filter(|c| !c.is_ascii_alphabetic() && !c.is_whitespace()) {
+        *other_characters.entry(c).or_insert(0) += 1;
+    }
+
+    let sequence: Vec<u8> = text.bytes().filter(u8::is_ascii_alphabetic).map(|byte| byte.to_ascii_uppercase()).collect();
+    let mut pairs: HashMap<[u8; 2], u64> = HashMap::new();
+    for pair in sequence.windows(2) {
+        *pairs.entry([pair[0], pair[1]]).or_insert(0) += 1;
+    }
+    let pair_total = sequence.len().saturating_sub(1).max(1) as f64;
+    let mut digraphs: Vec<Digraph> = pairs.into_iter()
+        .map(|(pair, count)| Digraph { digraph: String::from_utf8_lossy(&pair).to_string(), count, frequency: round(count as f64 / pair_total) })
+        .collect();
+    digraphs.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.digraph.cmp(&b.digraph)));
+    digraphs.truncate(TOP_DIGRAPHS);
+
+    let ic = index_of_coincidence(&counts);
+    let chi = chi_squared(&counts);
+
+    Ok(FrequencyReport {
+        letters_total: total,
+        letters,
+        other_characters,
+        digraphs,
+        index_of_coincidence: round(ic),
+        english_ic: ENGLISH_IC,
+        random_ic: RANDOM_IC,
+        chi_squared: round(chi),
+        assessment: assessment(total, ic, chi).to_string()
+    })
+}
+
+/// Decrypts a Caesar cipher with whichever of the 26 shifts reads most like English: the lowest
+/// chi-squared once discounted by the plaintext's share of common English bigrams
+pub fn mercy_caesar_solve(text: &str) -> Result<CaesarSolution, String> {
+    let counts = letter_counts(text);
+    if counts.iter().sum::<u64>() == 0 {
+        return Err("No letters to analyse".to_string());
+    }
+
+    // Undoing a shift of s moves the count of ciphertext letter i + s to plaintext letter i
+    let mut candidates: Vec<(f64, CaesarCandidate)> = (0..26u8)
+        .map(|shift| {
+            let mut shifted = [0u64; 26];
+            for (index, count) in shifted.iter_mut().enumerate() {
+                *count = counts[(index + shift as usize) % 26];
+            }
+            let chi_squared = chi_squared(&shifted);
+            let bigrams = common_bigram_share(shift_text(text, 26 - shift).as_bytes());
+            (chi_squared * (1.0 - bigrams), CaesarCandidate { shift, chi_squared })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut candidates: Vec<CaesarCandidate> = candidates.into_iter().map(|(_, candidate)| candidate).collect();
+
+    let best = candidates.remove(0);
+    candidates.truncate(3);
+
+    Ok(CaesarSolution {
+        shift: best.shift,
+        plaintext: shift_text(text, 26 - best.shift),
+        chi_squared: round(best.chi_squared),
+        runners_up: candidates.into_iter().map(|candidate| CaesarCandidate { shift: candidate.shift, chi_squared: round(candidate.chi_squared) }).collect()
+    })
+}
+
+// Rotates letters forward by `shift`, keeping case and everything else
+pub(crate) fn shift_text(text: &str, shift: u8) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => ((c as u8 - b'a' + shift) % 26 + b'a') as char,
+            'A'..='Z' => ((c as u8 - b'A' + shift) % 26 + b'A') as char,
+            _ => c
+        })
+        .collect()
+}
+
+/// ROT47 of `text`: each character from '!' to '~' rotated by 47 within that range, others unchanged
+pub(crate) fn rot47(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '!'..='~' => ((c as u8 - b'!' + 47) % 94 + b'!') as char,
+            _ => c
+        })
+        .collect()
+}
+
+// Parses "13,text" into the shift (1 to 25) and the text
+fn rot_args(input: &str) -> Result<(u8, &str), String> {
+    let (shift, text) = input.split_once(',').ok_or_else(|| format!("Expected \"shift,text\" (shift 1 to 25), got '{}'", input))?;
+    match shift.trim().parse::<u8>() {
+        Ok(shift @ 1..=25) => Ok((shift, text)),
+        _ => Err(format!("Shift must be 1 to 25, got '{}'", shift.trim()))
+    }
+}
+
+/// Rotates the letters of "13,text" forward by the shift
+pub(crate) fn rot_encode_from_args(input: &str) -> Result<String, String> {
+    let (shift, text) = rot_args(input)?;
+    Ok(shift_text(text, shift))
+}
+
+/// Undoes a forward rotation of "13,text"
+pub(crate) fn rot_decode_from_args(input: &str) -> Result<String, String> {
+    let (shift, text) = rot_args(input)?;
+    Ok(shift_text(text, 26 - shift))
+}
+
+/// Every decoding of a Caesar-shifted text, one line per shift 1 to 25 as "rot3: text", with the
+/// most English-like one (by `english_similarity`) marked; line breaks in the text are shown as "\n"
+pub(crate) fn rot_brute(text: &str) -> Result<String, String> {
+    if !text.chars().any(|c| c.is_ascii_alphabetic()) {
+        return Err("No letters to rotate".to_string());
+    }
+
+    let candidates: Vec<(u8, String)> = (1..26u8).map(|shift| (shift, shift_text(text, 26 - shift))).collect();
+    let best = candidates.iter()
+        .map(|(shift, candidate)| (*shift, english_similarity(candidate.as_bytes())))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(shift, _)| shift);
+
+    Ok(candidates.iter()
+        .map(|(shift, candidate)| {
+            let line = candidate.replace('\r', "\\r").replace('\n', "\\n");
+            format!("rot{}: {}{}", shift, line, if Some(*shift) == best { "  <- most English-like" } else { "" })
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn letter_counts(text: &str) -> [u64; 26] {
+    let mut counts = [0u64; 26];
+    for byte in text.bytes().filter(u8::is_ascii_alphabetic) {
+        counts[(byte.to_ascii_uppercase() - b'A') as usize] += 1;
+    }
+    counts
+}
+
+// Chance that two letters drawn without replacement are the same
+fn index_of_coincidence(counts: &[u64; 26]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total < 2 {
+        return 0.0;
+    }
+    counts.iter().map(|count| (count * count.saturating_sub(1)) as f64).sum::<f64>() / (total * (total - 1)) as f64
+}
+
+fn chi_squared(counts: &[u64; 26]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    counts.iter().zip(ENGLISH)
+        .map(|(count, share)| {
+            let expected = share * total as f64;
+            (*count as f64 - expected).powi(2) / expected
+        })
+        .sum()
+}
+
+/// How closely the letters and spaces of `bytes` follow English, from 0 to 1 (Bhattacharyya
+/// coefficient against English letter frequencies with one character in six a space, a quarter of
+/// it traded for the share of common English bigrams); every other byte dilutes the score, so
+/// binary noise and punctuation runs stay near 0
+pub(crate) fn english_similarity(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0u64; 27];
+    for byte in bytes {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' => counts[(byte.to_ascii_uppercase() - b'A') as usize] += 1,
+            b' ' => counts[26] += 1,
+            _ => {}
+        }
+    }
+
+    let total = bytes.len() as f64;
+    let expected = ENGLISH.iter().map(|share| share * (1.0 - ENGLISH_SPACE)).chain([ENGLISH_SPACE]);
+    let letters: f64 = counts.iter().zip(expected).map(|(count, share)| (*count as f64 / total * share).sqrt()).sum();
+    letters * (1.0 - BIGRAM_WEIGHT) + common_bigram_share(bytes) * BIGRAM_WEIGHT
+}
+
+// Share of the adjacent letter pairs of `bytes` that are among the commonest English bigrams
+fn common_bigram_share(bytes: &[u8]) -> f64 {
+    let pairs: Vec<[u8; 2]> = bytes.windows(2)
+        .filter(|pair| pair.iter().all(u8::is_ascii_alphabetic))
+        .map(|pair| [pair[0].to_ascii_uppercase(), pair[1].to_ascii_uppercase()])
+        .collect();
+    if pairs.is_empty() {
+        return 0.0;
+    }
+    pairs.iter().filter(|pair| COMMON_BIGRAMS.contains(pair)).count() as f64 / pairs.len() as f64
+}
+
+// Rough reading of the statistics; short texts are too noisy to say much
+fn assessment(total: u64, ic: f64, chi: f64) -> &'static str {
+    // Halfway between the English and random coincidence rates
+    let threshold = (ENGLISH_IC + RANDOM_IC) / 2.0;
+    // Chi-squared grows with length, so compare it per letter
+    let english_letters = chi / (total as f64) < 0.5;
+
+    if total < 50 {
+        "too short to judge"
+    } else if ic < threshold {
+        "flat letter distribution: polyalphabetic cipher (Vigenere and similar) or random data"
+    } else if english_letters {
+        "English letter distribution: plaintext or a transposition cipher"
+    } else {
+        "English coincidence rate with the wrong letters: monoalphabetic substitution (Caesar, Atbash, keyword)"
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// Limits and seed for the substitution solver
+#[derive(Debug, Clone)]
+pub struct SubstitutionOptions {
+    /// Most candidate keys scored across all restarts
+    pub iterations: u64,
+    /// Wall-clock limit
+    pub time_limit: Duration,
+    /// Seed for the key shuffles; a fixed seed (with an iteration budget reached before the time limit) makes a run repeatable
+    pub seed: Option<u64>
+}
+
+impl Default for SubstitutionOptions {
+    fn default() -> SubstitutionOptions {
+        SubstitutionOptions { iterations: 2_000_000, time_limit: Duration::from_secs(10), seed: None }
+    }
+}
+
+/// The best monoalphabetic substitution key found
+#[derive(Debug, Clone, Serialize)]
+pub struct SubstitutionSolution {
+    /// Ciphertext letter for each plaintext letter A to Z
+    pub key: String,
+    /// Plaintext letter for each ciphertext letter that occurs
+    pub mapping: BTreeMap<char, char>,
+    pub plaintext: String,
+    /// Mean log10 quadgram probability of the plaintext (closer to zero is more English)
+    pub score: f64,
+    pub iterations: u64,
+    pub restarts: u64,
+    pub seed: u64,
+    /// Whether the search stopped on its budget rather than settling on an answer
+    pub budget_exhausted: bool
+}
+
+// English quadgrams grouped by log10 probability, one group per line ("-2.5 ETHETHIS")
+const QUADGRAMS: &str = include_str!("quadgrams.txt");
+
+// Climbs stop after this many swaps in a row that fail to improve the key
+const PATIENCE: u32 = 2_000;
+
+// The search settles once the best key has been reached from this many random starts
+const AGREEING_RESTARTS: u32 = 3;
+
+/// Solves a monoalphabetic substitution cipher by hill climbing with random restarts, scored by quadgrams
+pub fn mercy_substitution_solve(ciphertext: &str, options: &SubstitutionOptions) -> Result<SubstitutionSolution, String> {
+    let letters: Vec<u8> = ciphertext.bytes().filter(u8::is_ascii_alphabetic).map(|byte| byte.to_ascii_uppercase() - b'A').collect();
+    if letters.len() < 4 {
+        return Err("Not enough letters to score (at least 4 are needed)".to_string());
+    }
+
+    let table = quadgram_table();
+    let seed = options.seed.unwrap_or_else(random_seed);
+    let mut rng = MercyRng::seeded(seed);
+    let started = Instant::now();
+
+    // key[c] is the plaintext letter for ciphertext letter c
+    let mut best_key = frequency_key(&letters);
+    let mut best_score = score(&letters, &best_key, table);
+    let mut iterations = 0u64;
+    let mut restarts = 0u64;
+    let mut agreeing = 0u32;
+
+    let budget_left = |iterations: u64| iterations < options.iterations && started.elapsed() < options.time_limit;
+
+    // The first climb starts from the frequency guess, later ones from shuffles
+    let mut key = best_key;
+    while budget_left(iterations) && agreeing < AGREEING_RESTARTS {
+        let mut current = score(&letters, &key, table);
+        let mut failures = 0;
+
+        while failures < PATIENCE && budget_left(iterations) {
+            let a = rng.below(26);
+            let b = (a + 1 + rng.below(25)) % 26;
+            key.swap(a, b);
+            iterations += 1;
+
+            let candidate = score(&letters, &key, table);
+            if candidate > current {
+                current = candidate;
+                failures = 0;
+            } else {
+                key.swap(a, b);
+                failures += 1;
+            }
+        }
+
+        // Letters absent from the ciphertext swap freely, so keys are compared by score
+        if current == best_score {
+            agreeing += 1;
+        } else if current > best_score {
+            best_key = key;
+            best_score = current;
+            agreeing = 1;
+        }
+
+        restarts += 1;
+        for index in (1..26).rev() {
+            key.swap(index, rng.below(index + 1));
+        }
+    }
+
+    let mut encrypt = [b'?'; 26];
+    for (cipher, plain) in best_key.iter().enumerate() {
+        encrypt[*plain as usize] = b'A' + cipher as u8;
+    }
+
+    let mut mapping = BTreeMap::new();
+    for cipher in &letters {
+        mapping.insert((b'A' + cipher) as char, (b'A' + best_key[*cipher as usize]) as char);
+    }
+
+    Ok(SubstitutionSolution {
+        key: String::from_utf8_lossy(&encrypt).to_string(),
+        mapping,
+        plaintext: apply_key(ciphertext, &best_key),
+        score: round(best_score / (letters.len() - 3) as f64),
+        iterations,
+        restarts,
+        seed,
+        budget_exhausted: agreeing < AGREEING_RESTARTS
+    })
+}
+
+// log10 probability of every quadgram, indexed by its four letters in base 26. Quadgrams too
+// rare to be listed get half the probability of the rarest listed ones
+fn quadgram_table() -> &'static [f32] {
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let buckets: Vec<(f32, &str)> = QUADGRAMS.lines()
+            .filter_map(|line| {
+                let (probability, quadgrams) = line.split_once(' ')?;
+                Some((probability.parse().ok()?, quadgrams.trim()))
+            })
+            .collect();
+
+        let floor = buckets.iter().map(|(probability, _)| *probability).fold(0.0, f32::min) - 0.3;
+        let mut table = vec![floor; 26 * 26 * 26 * 26];
+        for (probability, quadgrams) in buckets {
+            for quadgram in quadgrams.as_bytes().chunks_exact(4) {
+                if let Some(index) = quadgram.iter().try_fold(0usize, |acc, byte| byte.is_ascii_uppercase().then(|| acc * 26 + (byte - b'A') as usize)) {
+                    table[index] = probability;
+                }
+            }
+        }
+        table
+    })
+}
+
+fn score(letters: &[u8], key: &[u8; 26], table: &[f32]) -> f64 {
+    letters.windows(4)
+        .map(|quad| {
+            let index = quad.iter().fold(0usize, |acc, letter| acc * 26 + key[*letter as usize] as usize);
+            table[index] as f64
+        })
+        .sum()
+}
+
+// Ciphertext letters ranked by frequency mapped onto English letters ranked the same way
+fn frequency_key(letters: &[u8]) -> [u8; 26] {
+    let mut counts = [0u64; 26];
+    for letter in letters {
+        counts[*letter as usize] += 1;
+    }
+
+    let mut cipher_order: Vec<u8> = (0..26).collect();
+    cipher_order.sort_by(|a, b| counts[*b as usize].cmp(&counts[*a as usize]).then(a.cmp(b)));
+    let mut english_order: Vec<u8> = (0..26).collect();
+    english_order.sort_by(|a, b| ENGLISH[*b as usize].total_cmp(&ENGLISH[*a as usize]));
+
+    let mut key = [0u8; 26];
+    for (cipher, plain) in cipher_order.iter().zip(english_order) {
+        key[*cipher as usize] = plain;
+    }
+    key
+}
+
+// Deciphers letters with the key, keeping case and everything else
+fn apply_key(text: &str, key: &[u8; 26]) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' => (key[(c as u8 - b'a') as usize] + b'a') as char,
+            'A'..='Z' => (key[(c as u8 - b'A') as usize] + b'A') as char,
+            _ => c
+        })
+        .collect()
+}
+
+// Parses "ciphertext [iterations=2000000] [seconds=10] [seed=42]"; options trail the text
+pub(crate) fn substitution_from_args(input: &str) -> Result<SubstitutionSolution, String> {
+    let (ciphertext, pairs) = trailing_options(input, &["iterations", "seconds", "seed"]);
+    let mut options = SubstitutionOptions::default();
+
+    for (key, value) in pairs {
+        let number = value.parse::<u64>().map_err(|_| format!("Invalid {} '{}'", key, value))?;
+        match key {
+            "iterations" => options.iterations = number,
+            "seconds" => options.time_limit = Duration::from_secs(number),
+            _ => options.seed = Some(number)
+        }
+    }
+
+    mercy_substitution_solve(&ciphertext, &options)
+}
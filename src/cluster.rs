@@ -0,0 +1,404 @@
+/*
+    Duplicate and similarity clustering of a sample set
+
+    Files are first grouped by SHA-256, so exact copies collapse to one entry. The distinct
+    contents are then linked by fuzzy similarity and clusters are the connected groups
+    (single linkage), each reported with a representative: the member linked to the most
+    others.
+
+    - ssdeep: digests scoring at or above the threshold (0-100) are linked. Rather than
+      comparing every pair, digests are indexed by (block size, 7-character run) and only
+      those sharing an entry are compared; ssdeep scores any other pair 0, so nothing is
+      missed.
+    - histogram: cosine similarity of byte histograms, as a percentage. Cruder, but works on
+      files too small for a useful ssdeep digest; every pair is compared.
+
+    Both are quadratic in the worst case, so directories with more files than the guard
+    allows are refused unless forced.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::Path
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    paths::{path_to_string, path_with_flag},
+    ssdeep::{SsdeepDigest, compare_digests, ssdeep_hash},
+    stats::{self, Counter},
+    trailing_options,
+    walk::{EntryKind, WalkEntry, WalkOptions, walk_dir}
+};
+
+// Errors kept in the report; the rest are only counted
+const ERROR_SAMPLES: usize = 50;
+
+// Largest file read
+const MAX_FILE: u64 = 1024 * 1024 * 1024;
+
+/// How distinct files are compared by `mercy_cluster`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterMethod {
+    /// ssdeep digest similarity (default threshold 50)
+    Ssdeep,
+    /// Cosine similarity of byte histograms (default threshold 95)
+    Histogram
+}
+
+impl ClusterMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ClusterMethod::Ssdeep => "ssdeep",
+            ClusterMethod::Histogram => "histogram"
+        }
+    }
+}
+
+/// Options for `mercy_cluster`
+#[derive(Debug, Clone)]
+pub struct ClusterOptions {
+    /// Lowest similarity (0-100) that links two files; None uses the method's default
+    pub threshold: Option<u32>,
+    /// Refuse directories with more files than this...
+    pub max_files: usize,
+    /// ...unless forced
+    pub force: bool
+}
+
+impl Default for ClusterOptions {
+    fn default() -> Self {
+        ClusterOptions {
+            threshold: None,
+            max_files: 5_000,
+            force: false
+        }
+    }
+}
+
+/// One file in a cluster
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterMember {
+    pub path: String,
+    /// The name was not valid UTF-8 and `path` is percent-encoded (see `path_from_string`)
+    pub path_encoded: bool,
+    pub size: u64,
+    pub sha256: String,
+    /// ssdeep digest (ssdeep method only)
+    pub ssdeep: Option<String>,
+    /// Highest similarity to another member (100 for exact copies); None for a lone file
+    pub best_match: Option<u32>
+}
+
+/// Files linked by identical contents or similarity
+#[derive(Debug, Clone, Serialize)]
+pub struct Cluster {
+    pub representative: String,
+    pub size: usize,
+    /// Distinct contents among the members
+    pub distinct: usize,
+    pub members: Vec<ClusterMember>
+}
+
+/// Clusters of a directory of samples, largest first
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterReport {
+    pub directory: String,
+    pub method: String,
+    pub threshold: u32,
+    pub files: usize,
+    /// Files with distinct contents
+    pub distinct: usize,
+    /// Number of clusters, lone files included
+    pub families: usize,
+    /// Similarity comparisons made
+    pub comparisons: u64,
+    pub clusters: Vec<Cluster>,
+    pub errors: Vec<String>
+}
+
+impl fmt::Display for ClusterReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} files in {}: {} distinct, {} families ({} at threshold {})", self.files, self.directory, self.distinct, self.families, self.method, self.threshold)?;
+
+        for cluster in self.clusters.iter().filter(|cluster| cluster.size > 1) {
+            writeln!(f, "  {} files ({} distinct), representative {}", cluster.size, cluster.distinct, cluster.representative)?;
+        }
+
+        for error in &self.errors {
+            writeln!(f, "Error: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Files sharing one content, with what the method compares
+struct Content {
+    sha256: String,
+    // Path, whether it is percent-encoded, size
+    files: Vec<(String, bool, u64)>,
+    ssdeep: Option<String>,
+    digest: Option<SsdeepDigest>,
+    histogram: Option<[u64; 256]>
+}
+
+/// Groups the files under `dir` by exact hash, then by similarity
+pub fn mercy_cluster<P: AsRef<Path>>(dir: P, method: ClusterMethod, options: &ClusterOptions) -> Result<ClusterReport, String> {
+    let dir = dir.as_ref();
+    let threshold = options.threshold.unwrap_or(match method {
+        ClusterMethod::Ssdeep => 50,
+        ClusterMethod::Histogram => 95
+    });
+    if threshold > 100 {
+        return Err(format!("Invalid threshold {} (expected 0-100)", threshold));
+    }
+
+    let mut errors = Vec::new();
+    let paths = list_files(dir, &mut errors)?;
+    if paths.len() > options.max_files && !options.force {
+        return Err(format!("{} files in {} exceeds the limit of {} (pairwise comparison); use force=true to cluster anyway", paths.len(), dir.display(), options.max_files));
+    }
+
+    let mut contents: Vec<Content> = Vec::new();
+    let mut by_hash: HashMap<String, usize> = HashMap::new();
+
+    for walked in &paths {
+        let path = &walked.path;
+        let data = match walked.read_capped(MAX_FILE) {
+            Ok(read) if read.truncated => {
+                errors.push(format!("{} is larger than the {} byte limit", path.display(), MAX_FILE));
+                continue;
+            },
+            Ok(read) => read.data,
+            Err(e) => {
+                errors.push(e);
+                continue;
+            }
+        };
+
+        stats::count(Counter::BytesHashed, data.len() as u64);
+        let sha256 = format!("{:x}", Sha256::digest(&data));
+        let (name, encoded) = path_with_flag(path);
+        let file = (name, encoded, data.len() as u64);
+
+        if let Some(index) = by_hash.get(&sha256) {
+            contents[*index].files.push(file);
+            continue;
+        }
+
+        let (ssdeep, digest, histogram) = match method {
+            ClusterMethod::Ssdeep => {
+                let ssdeep = ssdeep_hash(&data);
+                let digest = SsdeepDigest::parse(&ssdeep)?;
+                (Some(ssdeep), Some(digest), None)
+            },
+            ClusterMethod::Histogram => {
+                let mut histogram = [0u64; 256];
+                for byte in &data {
+                    histogram[*byte as usize] += 1;
+                }
+                (None, None, Some(histogram))
+            }
+        };
+
+        by_hash.insert(sha256.clone(), contents.len());
+        contents.push(Content { sha256, files: vec![file], ssdeep, digest, histogram });
+    }
+
+    let (links, comparisons) = match method {
+        ClusterMethod::Ssdeep => ssdeep_links(&contents, threshold),
+        ClusterMethod::Histogram => histogram_links(&contents, threshold)
+    };
+
+    // Connected groups of linked contents
+    let mut parent: Vec<usize> = (0..contents.len()).collect();
+    for (a, b, _) in &links {
+        let (root_a, root_b) = (find(&mut parent, *a), find(&mut parent, *b));
+        parent[root_a.max(root_b)] = root_a.min(root_b);
+    }
+
+    let mut best = vec![None; contents.len()];
+    let mut degree = vec![0usize; contents.len()];
+    for (a, b, score) in &links {
+        for index in [*a, *b] {
+            best[index] = best[index].max(Some(*score));
+            degree[index] += 1;
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..contents.len() {
+        let root = find(&mut parent, index);
+        groups.entry(root).or_default().push(index);
+    }
+
+    let mut clusters: Vec<Cluster> = groups.into_values().map(|group| {
+        // Most links wins, exact copies counting as links
+        let representative = *group.iter()
+            .max_by_key(|index| (degree[**index] + contents[**index].files.len() - 1, std::cmp::Reverse(&contents[**index].files[0].0)))
+            .unwrap_or(&group[0]);
+
+        let mut members: Vec<ClusterMember> = group.iter().flat_map(|index| {
+            let content = &contents[*index];
+            let best_match = if content.files.len() > 1 { Some(100) } else { best[*index] };
+            content.files.iter().map(move |(path, path_encoded, size)| ClusterMember {
+                path: path.clone(),
+                path_encoded: *path_encoded,
+                size: *size,
+                sha256: content.sha256.clone(),
+                ssdeep: content.ssdeep.clone(),
+                best_match
+            })
+        }).collect();
+        members.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Cluster {
+            representative: contents[representative].files[0].0.clone(),
+            size: members.len(),
+            distinct: group.len(),
+            members
+        }
+    }).collect();
+
+    clusters.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.representative.cmp(&b.representative)));
+
+    let error_count = errors.len();
+    errors.truncate(ERROR_SAMPLES);
+    if error_count > ERROR_SAMPLES {
+        errors.push(format!("... {} more errors", error_count - ERROR_SAMPLES));
+    }
+
+    Ok(ClusterReport {
+        directory: path_to_string(dir),
+        method: method.as_str().to_string(),
+        threshold,
+        files: contents.iter().map(|content| content.files.len()).sum(),
+        distinct: contents.len(),
+        families: clusters.len(),
+        comparisons,
+        clusters,
+        errors
+    })
+}
+
+// Pairs scoring at or above the threshold, comparing only digests that share a (block size, 7-gram) entry
+fn ssdeep_links(contents: &[Content], threshold: u32) -> (Vec<(usize, usize, u32)>, u64) {
+    let digests: Vec<&SsdeepDigest> = contents.iter().filter_map(|content| content.digest.as_ref()).collect();
+    let mut index: HashMap<(u32, u64), Vec<usize>> = HashMap::new();
+    let mut links = Vec::new();
+    let mut comparisons = 0;
+
+    for (current, digest) in digests.iter().enumerate() {
+        let mut candidates = HashSet::new();
+        for gram in digest.grams() {
+            let entry = index.entry(gram).or_default();
+            candidates.extend(entry.iter().copied());
+            entry.push(current);
+        }
+
+        for candidate in candidates {
+            comparisons += 1;
+            let score = compare_digests(digests[candidate], digest);
+            if score >= threshold.max(1) {
+                links.push((candidate, current, score));
+            }
+        }
+    }
+
+    (links, comparisons)
+}
+
+// Pairs whose byte histograms have a cosine similarity at or above the threshold percentage
+fn histogram_links(contents: &[Content], threshold: u32) -> (Vec<(usize, usize, u32)>, u64) {
+    let vectors: Vec<(&[u64; 256], f64)> = contents.iter()
+        .filter_map(|content| content.histogram.as_ref())
+        .map(|histogram| (histogram, histogram.iter().map(|count| (*count as f64).powi(2)).sum::<f64>().sqrt()))
+        .collect();
+    let mut links = Vec::new();
+    let mut comparisons = 0;
+
+    for a in 0..vectors.len() {
+        for b in a + 1..vectors.len() {
+            comparisons += 1;
+            let ((first, first_norm), (second, second_norm)) = (vectors[a], vectors[b]);
+            if first_norm == 0.0 || second_norm == 0.0 {
+                continue;
+            }
+
+            let dot: f64 = first.iter().zip(second.iter()).map(|(x, y)| *x as f64 * *y as f64).sum();
+            let score = (dot / (first_norm * second_norm) * 100.0).floor() as u32;
+            if score >= threshold {
+                links.push((a, b, score));
+            }
+        }
+    }
+
+    (links, comparisons)
+}
+
+fn find(parent: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parent[root] != root {
+        root = parent[root];
+    }
+
+    // Point the walked chain straight at the root
+    let mut current = index;
+    while parent[current] != root {
+        let next = parent[current];
+        parent[current] = root;
+        current = next;
+    }
+
+    root
+}
+
+// Regular files under `dir`, recursively, in path order; links and special files are left alone
+fn list_files(dir: &Path, errors: &mut Vec<String>) -> Result<Vec<WalkEntry>, String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()));
+    }
+
+    let mut files = Vec::new();
+
+    for walked in walk_dir(dir, &WalkOptions::default())? {
+        match walked {
+            Ok(walked) if walked.kind == EntryKind::File => files.push(walked),
+            Ok(_) => {},
+            Err(e) => errors.push(e.to_string())
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+// Parses "dir [method=ssdeep|histogram] [threshold=50] [max_files=5000] [force=true]"
+pub(crate) fn cluster_from_args(input: &str) -> Result<ClusterReport, String> {
+    let (dir, pairs) = trailing_options(input, &["method", "threshold", "max_files", "force"]);
+    let mut method = ClusterMethod::Ssdeep;
+    let mut options = ClusterOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "method" => method = match value {
+                "ssdeep" => ClusterMethod::Ssdeep,
+                "histogram" => ClusterMethod::Histogram,
+                _ => return Err(format!("Unknown cluster method '{}' (expected ssdeep or histogram)", value))
+            },
+            "threshold" => options.threshold = Some(value.parse().map_err(|_| format!("Invalid threshold '{}'", value))?),
+            "max_files" => options.max_files = value.parse().map_err(|_| format!("Invalid max_files '{}'", value))?,
+            _ => options.force = value == "true"
+        }
+    }
+
+    if dir.is_empty() {
+        return Err("No directory specified for cluster".to_string());
+    }
+
+    mercy_cluster(Path::new(&dir), method, &options)
+}
@@ -0,0 +1,94 @@
+/*
+    Morse code and the NATO phonetic alphabet
+
+    Both encode letters one symbol at a time, with "/" between words. Decoding is forgiving:
+    any run of whitespace separates symbols, typographic dots and dashes are accepted, case is
+    ignored, and anything unrecognised comes back as "?" rather than failing the message.
+*/
+
+const MORSE: &[(char, &str)] = &[
+    ('A', ".-"), ('B', "-..."), ('C', "-.-."), ('D', "-.."), ('E', "."), ('F', "..-."), ('G', "--."),
+    ('H', "...."), ('I', ".."), ('J', ".---"), ('K', "-.-"), ('L', ".-.."), ('M', "--"), ('N', "-."),
+    ('O', "---"), ('P', ".--."), ('Q', "--.-"), ('R', ".-."), ('S', "..."), ('T', "-"), ('U', "..-"),
+    ('V', "...-"), ('W', ".--"), ('X', "-..-"), ('Y', "-.--"), ('Z', "--.."),
+    ('0', "-----"), ('1', ".----"), ('2', "..---"), ('3', "...--"), ('4', "....-"), ('5', "....."),
+    ('6', "-...."), ('7', "--..."), ('8', "---.."), ('9', "----."),
+    ('.', ".-.-.-"), (',', "--..--"), ('?', "..--.."), ('\'', ".----."), ('!', "-.-.--"), ('/', "-..-."),
+    ('(', "-.--."), (')', "-.--.-"), ('&', ".-..."), (':', "---..."), (';', "-.-.-."), ('=', "-...-"),
+    ('+', ".-.-."), ('-', "-....-"), ('_', "..--.-"), ('"', ".-..-."), ('$', "...-..-"), ('@', ".--.-.")
+];
+
+const NATO: &[(char, &str)] = &[
+    ('A', "Alfa"), ('B', "Bravo"), ('C', "Charlie"), ('D', "Delta"), ('E', "Echo"), ('F', "Foxtrot"),
+    ('G', "Golf"), ('H', "Hotel"), ('I', "India"), ('J', "Juliett"), ('K', "Kilo"), ('L', "Lima"),
+    ('M', "Mike"), ('N', "November"), ('O', "Oscar"), ('P', "Papa"), ('Q', "Quebec"), ('R', "Romeo"),
+    ('S', "Sierra"), ('T', "Tango"), ('U', "Uniform"), ('V', "Victor"), ('W', "Whiskey"), ('X', "X-ray"),
+    ('Y', "Yankee"), ('Z', "Zulu"),
+    ('0', "Zero"), ('1', "One"), ('2', "Two"), ('3', "Three"), ('4', "Four"), ('5', "Five"),
+    ('6', "Six"), ('7', "Seven"), ('8', "Eight"), ('9', "Nine"),
+    // "/" separates words, so it is spelled out
+    ('/', "Slash")
+];
+
+// Other spellings seen in the wild (ICAO digits, common misspellings)
+const NATO_VARIANTS: &[(char, &str)] = &[
+    ('A', "alpha"), ('J', "juliet"), ('X', "xray"), ('9', "niner"), ('3', "tree"), ('5', "fife")
+];
+
+/// Morse code for `text`: symbols separated by spaces, words by " / ", "?" for characters with no code
+pub fn morse_encode(text: &str) -> String {
+    encode_words(text, |c| MORSE.iter().find(|(letter, _)| *letter == c.to_ascii_uppercase()).map(|(_, code)| code.to_string()).unwrap_or_else(|| "?".to_string()))
+}
+
+/// Text for Morse code, accepting "·" and "−" style variants; unknown sequences become "?"
+pub fn morse_decode(code: &str) -> String {
+    decode_words(code, |symbol| {
+        let normalized: String = symbol.chars()
+            .map(|c| match c {
+                '·' | '•' | '∙' | '⋅' => '.',
+                '−' | '–' | '—' | '_' => '-',
+                other => other
+            })
+            .collect();
+        MORSE.iter().find(|(_, code)| *code == normalized).map(|(letter, _)| *letter).unwrap_or('?')
+    })
+}
+
+/// NATO phonetic words for `text`; characters without a word are kept as they are
+pub fn nato_encode(text: &str) -> String {
+    encode_words(text, |c| NATO.iter().find(|(letter, _)| *letter == c.to_ascii_uppercase()).map(|(_, word)| word.to_string()).unwrap_or_else(|| c.to_string()))
+}
+
+/// Text for NATO phonetic words, in any case; single characters pass through and unknown words become "?"
+pub fn nato_decode(words: &str) -> String {
+    decode_words(words, |word| {
+        let lower = word.to_ascii_lowercase();
+        NATO.iter().chain(NATO_VARIANTS)
+            .find(|(_, spelling)| spelling.to_ascii_lowercase() == lower)
+            .map(|(letter, _)| *letter)
+            .unwrap_or_else(|| {
+                let mut chars = word.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if !c.is_alphanumeric() => c,
+                    _ => '?'
+                }
+            })
+    })
+}
+
+// Each character's symbol joined by spaces, words joined by " / "
+fn encode_words<F: Fn(char) -> String>(text: &str, symbol: F) -> String {
+    text.split_whitespace()
+        .map(|word| word.chars().map(&symbol).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+// Words split on "/", symbols on any whitespace
+fn decode_words<F: Fn(&str) -> char>(code: &str, letter: F) -> String {
+    code.split('/')
+        .map(|word| word.split_whitespace().map(&letter).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,365 @@
+/*
+    Content-Security-Policy parsing and evaluation
+
+    A header value may hold several policies separated by commas, and a response may carry
+    several headers; every policy applies on its own, so each is parsed and evaluated
+    separately. Input lines may start with the header name, which tells an enforced policy
+    from a Content-Security-Policy-Report-Only one. Evaluation follows what browsers do with a
+    policy: script rules fall back to default-src, 'unsafe-inline' is ignored next to a nonce
+    or hash, and host allowlists are ignored under 'strict-dynamic'. Allowlisted hosts that
+    serve JSONP, AngularJS or anyone's uploads come from an embedded list.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::finding::{Finding, Severity};
+
+const BYPASS_HOSTS: &str = include_str!("csp_bypass_hosts.txt");
+
+const ENFORCE_HEADER: &str = "Content-Security-Policy";
+const REPORT_ONLY_HEADER: &str = "Content-Security-Policy-Report-Only";
+
+// Directives whose values are source lists
+const SOURCE_DIRECTIVES: &[&str] = &[
+    "default-src", "script-src", "script-src-elem", "script-src-attr", "style-src", "style-src-elem",
+    "style-src-attr", "img-src", "font-src", "connect-src", "media-src", "object-src", "frame-src",
+    "child-src", "worker-src", "manifest-src", "prefetch-src", "base-uri", "form-action",
+    "frame-ancestors", "navigate-to"
+];
+
+// Directives with other kinds of values
+const OTHER_DIRECTIVES: &[&str] = &[
+    "sandbox", "report-uri", "report-to", "upgrade-insecure-requests", "block-all-mixed-content",
+    "require-trusted-types-for", "trusted-types", "require-sri-for", "plugin-types", "webrtc"
+];
+
+const KEYWORDS: &[&str] = &[
+    "'self'", "'none'", "'unsafe-inline'", "'unsafe-eval'", "'strict-dynamic'", "'unsafe-hashes'",
+    "'report-sample'", "'wasm-unsafe-eval'", "'unsafe-allow-redirects'", "'inline-speculation-rules'"
+];
+
+/// A directive and its values as written
+#[derive(Debug, Clone, Serialize)]
+pub struct CspDirective {
+    pub name: String,
+    pub values: Vec<String>
+}
+
+impl fmt::Display for CspDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for value in &self.values {
+            write!(f, " {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// One policy with its findings
+#[derive(Debug, Clone, Serialize)]
+pub struct CspPolicy {
+    /// Content-Security-Policy or Content-Security-Policy-Report-Only
+    pub header: String,
+    pub report_only: bool,
+    /// Directives in order, later duplicates (which browsers ignore) left out
+    pub directives: Vec<CspDirective>,
+    /// The policy re-serialized: directive names and keywords lowercased, duplicates removed
+    pub normalized: String,
+    pub findings: Vec<Finding>
+}
+
+impl CspPolicy {
+    pub fn directive(&self, name: &str) -> Option<&CspDirective> {
+        self.directives.iter().find(|directive| directive.name == name)
+    }
+}
+
+/// Every policy of a Content-Security-Policy input
+#[derive(Debug, Clone, Serialize)]
+pub struct CspAudit {
+    pub policies: Vec<CspPolicy>
+}
+
+impl CspAudit {
+    pub fn findings(&self) -> Vec<Finding> {
+        self.policies.iter().flat_map(|policy| policy.findings.iter().cloned()).collect()
+    }
+}
+
+impl fmt::Display for CspAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, policy) in self.policies.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "Policy {} ({}): {} issue(s)", index + 1, policy.header, policy.findings.len())?;
+            writeln!(f, "  {}", policy.normalized)?;
+
+            for finding in &policy.findings {
+                writeln!(f)?;
+                write!(f, "{}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses and evaluates each policy in `input`: policies separated by commas or newlines, a line
+/// optionally starting with "Content-Security-Policy:" or "Content-Security-Policy-Report-Only:"
+pub fn mercy_csp_audit(input: &str) -> Result<CspAudit, String> {
+    let mut values = Vec::new();
+    for line in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (header, value) = match line.split_once(':') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case(REPORT_ONLY_HEADER) => (REPORT_ONLY_HEADER, value),
+            Some((name, value)) if name.trim().eq_ignore_ascii_case(ENFORCE_HEADER) => (ENFORCE_HEADER, value),
+            _ => (ENFORCE_HEADER, line)
+        };
+        values.extend(value.split(',').map(str::trim).filter(|policy| !policy.is_empty()).map(|policy| (header, policy)));
+    }
+
+    if values.is_empty() {
+        return Err("No Content-Security-Policy given".to_string());
+    }
+
+    let enforced = values.iter().any(|(header, _)| *header == ENFORCE_HEADER);
+    let policies = values.iter()
+        .enumerate()
+        .map(|(index, (header, value))| evaluate(&format!("policy {}", index + 1), header, value, enforced))
+        .collect();
+
+    Ok(CspAudit { policies })
+}
+
+fn evaluate(target: &str, header: &str, value: &str, enforced: bool) -> CspPolicy {
+    let mut findings = Vec::new();
+    let mut finding = |id: &str, title: &str, severity: Severity, evidence: &str, recommendation: &str| {
+        findings.push(Finding::new(id, title, severity, target, evidence, recommendation));
+    };
+
+    // Syntax
+    let mut directives: Vec<CspDirective> = Vec::new();
+    for text in value.split(';').map(str::trim).filter(|text| !text.is_empty()) {
+        let mut tokens = text.split_ascii_whitespace();
+        let name = tokens.next().unwrap_or("").to_ascii_lowercase();
+        let values: Vec<String> = tokens.map(str::to_string).collect();
+
+        if directives.iter().any(|directive| directive.name == name) {
+            finding("csp.duplicate-directive", "Directive repeated", Severity::Low, text, "Browsers only use the first occurrence; merge the sources into one directive");
+            continue;
+        }
+        if !SOURCE_DIRECTIVES.contains(&name.as_str()) && !OTHER_DIRECTIVES.contains(&name.as_str()) {
+            finding("csp.unknown-directive", "Unknown directive", Severity::Low, text, "Browsers ignore unknown directives; check the spelling");
+        } else if SOURCE_DIRECTIVES.contains(&name.as_str()) {
+            for source in &values {
+                if let Some(problem) = source_problem(source) {
+                    finding("csp.invalid-source", "Invalid source expression", Severity::Low, &format!("{} ({})", text, problem), "Quote keywords ('self', 'none') and write hosts as [scheme://]host[:port][/path]");
+                }
+            }
+            if values.len() > 1 && values.iter().any(|source| source.eq_ignore_ascii_case("'none'")) {
+                finding("csp.none-with-sources", "'none' combined with other sources", Severity::Low, text, "'none' has no effect next to other sources; use it alone or drop it");
+            }
+        } else if matches!(name.as_str(), "upgrade-insecure-requests" | "block-all-mixed-content") && !values.is_empty() {
+            finding("csp.invalid-source", "Directive takes no value", Severity::Low, text, "Remove the values");
+        }
+
+        directives.push(CspDirective { name, values });
+    }
+
+    let sources = |name: &str| -> Option<Vec<String>> {
+        directives.iter().find(|directive| directive.name == name).map(|directive| directive.values.iter().map(|value| value.to_ascii_lowercase()).collect())
+    };
+    let shown = |name: &str| directives.iter().find(|directive| directive.name == name).map(|directive| directive.to_string()).unwrap_or_else(|| format!("no {}", name));
+
+    // Scripts: script-src, falling back to default-src, and the element/attribute variants that override it
+    let main_script = ["script-src", "default-src"].into_iter().find(|name| sources(name).is_some());
+    if main_script.is_none() {
+        finding("csp.script-unrestricted", "Policy does not restrict scripts", Severity::High, "no script-src and no default-src", "Add script-src with nonces or hashes and 'strict-dynamic', or at least default-src 'self'");
+    }
+
+    for name in main_script.into_iter().chain(["script-src-elem", "script-src-attr"].into_iter().filter(|name| sources(name).is_some())) {
+        let list = sources(name).unwrap_or_default();
+        let evidence = shown(name);
+        let nonce_or_hash = list.iter().any(|source| source.starts_with("'nonce-") || source.starts_with("'sha"));
+        let strict_dynamic = list.iter().any(|source| source == "'strict-dynamic'");
+
+        if list.iter().any(|source| source == "'unsafe-inline'") && !nonce_or_hash {
+            finding("csp.unsafe-inline", "Inline scripts allowed", Severity::High, &evidence, "Remove 'unsafe-inline' and allow inline scripts by nonce or hash");
+        }
+        if list.iter().any(|source| source == "'unsafe-eval'") {
+            finding("csp.unsafe-eval", "eval() allowed", Severity::Medium, &evidence, "Remove 'unsafe-eval'; replace eval, new Function and string timers in the page's scripts");
+        }
+        // Under 'strict-dynamic' browsers ignore host and scheme sources
+        if strict_dynamic {
+            continue;
+        }
+
+        let broad: Vec<&str> = list.iter().map(String::as_str).filter(|source| matches!(*source, "*" | "http:" | "https:")).collect();
+        if !broad.is_empty() {
+            finding("csp.wildcard-source", "Scripts allowed from any host", Severity::High, &evidence, &format!("Remove {} and list the script hosts, or use nonces with 'strict-dynamic'", broad.join(" ")));
+        }
+        if list.iter().any(|source| source == "data:") {
+            finding("csp.data-source", "Scripts allowed from data: URLs", Severity::High, &evidence, "Remove data: from the script sources");
+        }
+        let insecure: Vec<&str> = list.iter().map(String::as_str).filter(|source| source.starts_with("http://")).collect();
+        if !insecure.is_empty() {
+            finding("csp.insecure-source", "Scripts allowed over plain HTTP", Severity::Medium, &evidence, &format!("Load {} over HTTPS", insecure.join(" ")));
+        }
+        for (host, reason) in bypass_hosts(&list) {
+            finding("csp.bypassable-host", "Allowlisted host can be used to bypass the policy", Severity::High, &evidence, &format!("{} {}; use nonces or hashes with 'strict-dynamic' instead of allowlisting it", host, reason));
+        }
+    }
+
+    match sources("default-src") {
+        None => finding("csp.default-src-missing", "No default-src", Severity::Low, "no default-src", "Add default-src 'self' (or 'none') so resource types without their own directive are restricted"),
+        Some(list) if main_script != Some("default-src") => {
+            let broad: Vec<&str> = list.iter().map(String::as_str).filter(|source| matches!(*source, "*" | "http:" | "https:" | "data:")).collect();
+            if !broad.is_empty() {
+                finding("csp.default-src-broad", "default-src allows any host", Severity::Medium, &shown("default-src"), &format!("Replace {} with 'self' and the hosts the site needs", broad.join(" ")));
+            }
+        },
+        Some(_) => {}
+    }
+
+    // Plugins fall back to default-src; only 'none' keeps them out
+    let object = sources("object-src").or_else(|| sources("default-src"));
+    if object.as_deref() != Some(&["'none'".to_string()]) {
+        let evidence = if sources("object-src").is_some() { shown("object-src") } else { format!("no object-src; {}", shown("default-src")) };
+        finding("csp.object-src", "Plugins not blocked", Severity::Medium, &evidence, "Add object-src 'none'");
+    }
+
+    // base-uri has no fallback
+    match sources("base-uri") {
+        None => finding("csp.base-uri-missing", "No base-uri", Severity::Medium, "no base-uri", "Add base-uri 'none' (or 'self') so an injected <base> cannot redirect relative script URLs"),
+        Some(list) if list.iter().any(|source| matches!(source.as_str(), "*" | "http:" | "https:" | "data:")) => {
+            finding("csp.base-uri-broad", "base-uri allows any host", Severity::Medium, &shown("base-uri"), "Use base-uri 'none' or 'self'");
+        },
+        Some(_) => {}
+    }
+
+    for directive in directives.iter().filter(|directive| !matches!(directive.name.as_str(), "default-src" | "script-src" | "script-src-elem" | "script-src-attr" | "object-src" | "base-uri")) {
+        if !SOURCE_DIRECTIVES.contains(&directive.name.as_str()) {
+            continue;
+        }
+        if directive.values.iter().any(|source| source == "*") {
+            finding("csp.wildcard-source", "Directive allows any host", Severity::Low, &directive.to_string(), "List the hosts needed instead of *");
+        }
+        if matches!(directive.name.as_str(), "frame-src" | "child-src" | "worker-src") && directive.values.iter().any(|source| source.eq_ignore_ascii_case("data:")) {
+            finding("csp.data-source", "Frames or workers allowed from data: URLs", Severity::Medium, &directive.to_string(), "Remove data: from the directive");
+        }
+    }
+
+    let report_only = header == REPORT_ONLY_HEADER;
+    if report_only && !enforced {
+        finding("csp.report-only", "Policy is only reported, not enforced", Severity::Medium, &format!("{} with no Content-Security-Policy header", REPORT_ONLY_HEADER), "Once the reports are clean, send the policy as Content-Security-Policy");
+    }
+
+    let normalized = directives.iter()
+        .map(|directive| {
+            let mut values: Vec<String> = Vec::new();
+            for value in &directive.values {
+                let value = if value.starts_with('\'') && !value.starts_with("'nonce-") && !value.starts_with("'sha") { value.to_ascii_lowercase() } else { value.clone() };
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+            CspDirective { name: directive.name.clone(), values }.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("; ");
+
+    CspPolicy {
+        header: header.to_string(),
+        report_only,
+        directives,
+        normalized,
+        findings
+    }
+}
+
+// What is wrong with a source expression, if anything
+fn source_problem(source: &str) -> Option<String> {
+    let lowered = source.to_ascii_lowercase();
+
+    if lowered.starts_with('\'') {
+        if KEYWORDS.contains(&lowered.as_str()) {
+            return None;
+        }
+        let digest = lowered.strip_prefix("'nonce-")
+            .or_else(|| lowered.strip_prefix("'sha256-"))
+            .or_else(|| lowered.strip_prefix("'sha384-"))
+            .or_else(|| lowered.strip_prefix("'sha512-"));
+        return match digest.and_then(|digest| digest.strip_suffix('\'')) {
+            Some(digest) if !digest.is_empty() && digest.chars().all(|c| c.is_ascii_alphanumeric() || "+/=-_".contains(c)) => None,
+            Some(_) => Some(format!("{} is not valid base64", source)),
+            None => Some(format!("{} is not a keyword, nonce or hash", source))
+        };
+    }
+    if KEYWORDS.iter().any(|keyword| keyword.trim_matches('\'') == lowered) {
+        return Some(format!("{} must be quoted as '{}', unquoted it is a host name", source, lowered));
+    }
+    if lowered == "*" {
+        return None;
+    }
+
+    // scheme: alone, or [scheme://]host[:port][/path]
+    let (scheme, rest) = match lowered.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None if lowered.ends_with(':') => return if valid_scheme(&lowered[..lowered.len() - 1]) { None } else { Some(format!("{} is not a valid scheme", source)) },
+        None => (None, lowered.as_str())
+    };
+    if scheme.is_some_and(|scheme| !valid_scheme(scheme)) {
+        return Some(format!("{} has an invalid scheme", source));
+    }
+
+    let authority = rest.split('/').next().unwrap_or("");
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (authority, None)
+    };
+    if port.is_some_and(|port| port != "*" && (port.is_empty() || !port.chars().all(|c| c.is_ascii_digit()))) {
+        return Some(format!("{} has an invalid port", source));
+    }
+
+    let labels = host.strip_prefix("*.").unwrap_or(host);
+    let valid_host = host == "*" || (!labels.is_empty() && labels.split('.').all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')));
+    if !valid_host {
+        return Some(format!("{} is not a valid host source", source));
+    }
+    None
+}
+
+fn valid_scheme(scheme: &str) -> bool {
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic()) && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+}
+
+// Hosts of a script source list found on the bypass list, with the reason
+fn bypass_hosts(sources: &[String]) -> Vec<(String, String)> {
+    let listed: Vec<(&str, &str)> = BYPASS_HOSTS.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+
+    let mut found = Vec::new();
+    for source in sources {
+        let rest = source.split_once("://").map(|(_, rest)| rest).unwrap_or(source);
+        let host = rest.split(['/', ':']).next().unwrap_or("");
+        if host.is_empty() || host == "*" || source.starts_with('\'') {
+            continue;
+        }
+
+        // Either side may be a wildcard: *.googleapis.com allows ajax.googleapis.com
+        if let Some((entry, reason)) = listed.iter().find(|(entry, _)| covers(entry, host) || covers(host, entry)) {
+            if !found.iter().any(|(seen, _)| seen == host) {
+                let reason = if covers(entry, host) { reason.to_string() } else { format!("includes {}, which {}", entry, reason) };
+                found.push((host.to_string(), reason));
+            }
+        }
+    }
+    found
+}
+
+fn covers(pattern: &str, host: &str) -> bool {
+    pattern == host || pattern.strip_prefix("*.").is_some_and(|suffix| host.ends_with(&format!(".{}", suffix)))
+}
@@ -0,0 +1,167 @@
+/*
+    DEFLATE compression (RFC 1951) and gzip framing (RFC 1952)
+
+    The counterpart of inflate for data mercy writes itself, such as compressed snapshots.
+    Matches are found with a hash chain over three-byte prefixes in a 32 KiB window and coded
+    with the fixed Huffman tables, which compresses JSON and text several times over without
+    building per-block trees.
+*/
+
+use crate::{
+    checksum::crc32_update,
+    inflate::{DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA}
+};
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+// Candidates tried per position; longer chains find little more in typical input
+const MAX_CHAIN: usize = 64;
+
+const HASH_BITS: u32 = 15;
+
+/// Compresses `input` into a single fixed Huffman DEFLATE block
+pub(crate) fn deflate(input: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // BFINAL, then BTYPE 01 (fixed codes)
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW];
+
+    let mut position = 0;
+    while position < input.len() {
+        let (length, distance) = longest_match(input, position, &head, &previous);
+
+        if length >= MIN_MATCH {
+            bits.length(length);
+            bits.distance(distance);
+            for offset in 0..length {
+                insert(input, position + offset, &mut head, &mut previous);
+            }
+            position += length;
+        } else {
+            bits.literal(input[position] as u16);
+            insert(input, position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+
+    bits.literal(256);
+    bits.finish()
+}
+
+/// Wraps `input` in a gzip member: header without a name or timestamp, DEFLATE data, CRC-32 and size
+pub(crate) fn gzip(input: &[u8]) -> Vec<u8> {
+    // Magic, CM=8, no flags, MTIME=0, XFL=0, OS=255 (unknown)
+    let mut output = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+    output.extend(deflate(input));
+    output.extend((crc32_update(0xFFFF_FFFF, input) ^ 0xFFFF_FFFF).to_le_bytes());
+    output.extend((input.len() as u32).to_le_bytes());
+    output
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+// Makes `position` the newest entry of its hash chain
+fn insert(input: &[u8], position: usize, head: &mut [usize], previous: &mut [usize]) {
+    if position + MIN_MATCH <= input.len() {
+        let hash = hash(&input[position..position + MIN_MATCH]);
+        previous[position % WINDOW] = head[hash];
+        head[hash] = position;
+    }
+}
+
+// Longest earlier occurrence of the bytes at `position` within the window: (length, distance)
+fn longest_match(input: &[u8], position: usize, head: &[usize], previous: &[usize]) -> (usize, usize) {
+    if position + MIN_MATCH > input.len() {
+        return (0, 0);
+    }
+
+    let limit = (input.len() - position).min(MAX_MATCH);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(&input[position..position + MIN_MATCH])];
+
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || candidate >= position || position - candidate > WINDOW {
+            break;
+        }
+
+        let length = input[candidate..].iter().zip(&input[position..position + limit]).take_while(|(a, b)| a == b).count();
+        if length > best.0 {
+            best = (length, position - candidate);
+            if length == limit {
+                break;
+            }
+        }
+
+        let next = previous[candidate % WINDOW];
+        // An older entry overwritten in the ring buffer would point forward; stop there
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+
+    best
+}
+
+// Bits are packed from the least significant end; Huffman codes go most significant bit first
+#[derive(Default)]
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u64,
+    count: u32
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    // Fixed literal/length code: 0-143 8 bits, 144-255 9 bits, 256-279 7 bits, 280-287 8 bits
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8)
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let index = LENGTH_BASE.iter().rposition(|base| *base as usize <= length).unwrap_or(0);
+        self.literal(257 + index as u16);
+        self.write((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+    }
+
+    // Fixed distance codes are plain five-bit numbers
+    fn distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASE.iter().rposition(|base| *base as usize <= distance).unwrap_or(0);
+        self.code(index as u32, 5);
+        self.write((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.buffer as u8);
+        }
+        self.output
+    }
+}
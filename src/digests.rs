@@ -0,0 +1,204 @@
+/*
+    SHA-1 and SHA-3 digests
+
+    The sha2 crate covers SHA-256 and SHA-512; SHA-1 (FIPS 180-4), still the key of many
+    threat intelligence feeds, and SHA3-256/SHA3-512 (FIPS 202) are implemented here. Both
+    hash streaming input in constant memory: `update` as often as needed, then `finalize`.
+*/
+
+// Keccak-f[1600] round constants
+const KECCAK_ROUNDS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008
+];
+
+// Rotation of each lane visited by the combined rho and pi steps, in visiting order
+const KECCAK_ROTATIONS: [u32; 24] = [1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44];
+const KECCAK_LANES: [usize; 24] = [10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1];
+
+const SHA1_INITIAL: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// Streaming SHA-1
+#[derive(Debug, Clone)]
+pub(crate) struct Sha1 {
+    state: [u32; 5],
+    // Bytes of an incomplete 64-byte block
+    pending: Vec<u8>,
+    length: u64
+}
+
+impl Sha1 {
+    pub(crate) fn new() -> Sha1 {
+        Sha1 { state: SHA1_INITIAL, pending: Vec::with_capacity(64), length: 0 }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if !self.pending.is_empty() {
+            let take = (64 - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.pending.len() < 64 {
+                return;
+            }
+
+            let block = std::mem::take(&mut self.pending);
+            sha1_compress(&mut self.state, &block);
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            sha1_compress(&mut self.state, block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    pub(crate) fn finalize(mut self) -> [u8; 20] {
+        let bits = self.length.wrapping_mul(8);
+
+        // 0x80, zeros up to 56 bytes into the block, then the big-endian length in bits
+        let mut padding = vec![0x80u8];
+        padding.resize(1 + (55usize.wrapping_sub(self.pending.len()) % 64), 0);
+        padding.extend_from_slice(&bits.to_be_bytes());
+        self.update(&padding);
+
+        let mut digest = [0u8; 20];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+/// Streaming SHA3-256 or SHA3-512
+#[derive(Debug, Clone)]
+pub(crate) struct Sha3 {
+    lanes: [u64; 25],
+    // Bytes absorbed per permutation: 200 minus twice the digest length
+    rate: usize,
+    output: usize,
+    pending: Vec<u8>
+}
+
+impl Sha3 {
+    /// A hasher producing `output` bytes (32 for SHA3-256, 64 for SHA3-512)
+    pub(crate) fn new(output: usize) -> Sha3 {
+        let rate = 200 - 2 * output;
+        Sha3 { lanes: [0; 25], rate, output, pending: Vec::with_capacity(rate) }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        if !self.pending.is_empty() {
+            let take = (self.rate - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.pending.len() < self.rate {
+                return;
+            }
+
+            let block = std::mem::take(&mut self.pending);
+            self.absorb(&block);
+        }
+
+        let mut blocks = data.chunks_exact(self.rate);
+        for block in &mut blocks {
+            self.absorb(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    pub(crate) fn finalize(mut self) -> Vec<u8> {
+        // SHA-3 domain bits 01, then pad10*1 up to the end of the block
+        let mut block = std::mem::take(&mut self.pending);
+        let end = block.len();
+        block.resize(self.rate, 0);
+        block[end] ^= 0x06;
+        block[self.rate - 1] ^= 0x80;
+        self.absorb(&block);
+
+        // Both digest lengths fit in one block of output
+        self.lanes.iter().flat_map(|lane| lane.to_le_bytes()).take(self.output).collect()
+    }
+
+    fn absorb(&mut self, block: &[u8]) {
+        for (lane, bytes) in self.lanes.iter_mut().zip(block.chunks_exact(8)) {
+            *lane ^= u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
+        }
+        keccak_f(&mut self.lanes);
+    }
+}
+
+// One 64-byte block
+fn sha1_compress(state: &mut [u32; 5], block: &[u8]) {
+    let mut schedule = [0u32; 80];
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for index in 16..80 {
+        schedule[index] = (schedule[index - 3] ^ schedule[index - 8] ^ schedule[index - 14] ^ schedule[index - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+    for (index, word) in schedule.iter().enumerate() {
+        let (mixed, constant) = match index / 20 {
+            0 => ((b & c) | (!b & d), 0x5a827999),
+            1 => (b ^ c ^ d, 0x6ed9eba1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6)
+        };
+
+        let next = a.rotate_left(5).wrapping_add(mixed).wrapping_add(e).wrapping_add(constant).wrapping_add(*word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = next;
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+// The Keccak-f[1600] permutation; lane (x, y) is lanes[x + 5 * y]
+fn keccak_f(lanes: &mut [u64; 25]) {
+    for constant in KECCAK_ROUNDS {
+        // Theta: each column's parity folded into its neighbours
+        let mut parity = [0u64; 5];
+        for (x, column) in parity.iter_mut().enumerate() {
+            *column = lanes[x] ^ lanes[x + 5] ^ lanes[x + 10] ^ lanes[x + 15] ^ lanes[x + 20];
+        }
+        for x in 0..5 {
+            let mix = parity[(x + 4) % 5] ^ parity[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                lanes[x + 5 * y] ^= mix;
+            }
+        }
+
+        // Rho and pi: every lane but (0, 0) rotated and moved
+        let mut carried = lanes[1];
+        for (lane, rotation) in KECCAK_LANES.iter().zip(KECCAK_ROTATIONS) {
+            let next = lanes[*lane];
+            lanes[*lane] = carried.rotate_left(rotation);
+            carried = next;
+        }
+
+        // Chi: the only non-linear step, along each row
+        for y in 0..5 {
+            let row = [lanes[5 * y], lanes[5 * y + 1], lanes[5 * y + 2], lanes[5 * y + 3], lanes[5 * y + 4]];
+            for x in 0..5 {
+                lanes[x + 5 * y] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        // Iota
+        lanes[0] ^= constant;
+    }
+}
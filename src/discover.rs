@@ -0,0 +1,231 @@
+/*
+    Local subnet host discovery
+
+    Sweeps a CIDR block with TCP connects to a short port list (works unprivileged; a refused
+    connection still proves the host is up) or ICMP echo when the process may open ICMP sockets.
+    Work is spread over a bounded number of threads and results stream back to the caller.
+*/
+
+use std::{
+    fmt,
+    io,
+    net::{IpAddr, SocketAddr, TcpStream},
+    sync::{mpsc, Mutex},
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+
+use crate::{
+    capabilities::{self, Handler},
+    cidr::Cidr,
+    dns::{default_dns_server, reverse_lookup},
+    icmp::{self, IcmpAccess},
+    ping::echo_once
+};
+
+/// Sweeps larger than this many hosts need `force` (a /20 is allowed, a /16 is not)
+pub const MAX_SWEEP_HOSTS: u128 = 4096;
+
+/// Probe used to decide whether a host is alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SweepMethod {
+    /// TCP connect to each port in turn until one answers
+    Tcp(Vec<u16>),
+    /// ICMP echo (needs raw or unprivileged ICMP sockets)
+    Icmp
+}
+
+/// Options for `mercy_sweep`
+#[derive(Debug, Clone)]
+pub struct SweepOptions {
+    pub method: SweepMethod,
+    /// Per-probe timeout
+    pub timeout: Duration,
+    /// Maximum number of hosts probed at once
+    pub concurrency: usize,
+    /// Allow sweeps above `MAX_SWEEP_HOSTS`
+    pub force: bool,
+    /// Look up PTR names for hosts that answered
+    pub resolve_names: bool
+}
+
+impl Default for SweepOptions {
+    fn default() -> Self {
+        SweepOptions {
+            method: SweepMethod::Tcp(vec![22, 80, 443, 445, 3389]),
+            timeout: Duration::from_millis(800),
+            concurrency: 64,
+            force: false,
+            resolve_names: false
+        }
+    }
+}
+
+/// A host that answered, with the evidence
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepHost {
+    pub address: IpAddr,
+    pub evidence: String,
+    pub port: Option<u16>,
+    pub rtt_ms: f64,
+    pub hostname: Option<String>
+}
+
+/// Sweep summary
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepReport {
+    pub range: String,
+    pub method: String,
+    pub scanned: u128,
+    pub alive: Vec<SweepHost>,
+    pub elapsed_ms: f64
+}
+
+impl fmt::Display for SweepReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sweep of {} ({})", self.range, self.method)?;
+
+        for host in &self.alive {
+            write!(f, "{}  {}  {:.1} ms", host.address, host.evidence, host.rtt_ms)?;
+            if let Some(name) = &host.hostname {
+                write!(f, "  {}", name)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "{} of {} hosts alive ({:.1} s)", self.alive.len(), self.scanned, self.elapsed_ms / 1000.0)
+    }
+}
+
+/// Discovery operations
+///
+/// `sweep` / `sweep_json` - Probes every host in a CIDR block with TCP connects (input "10.0.0.0/24")
+pub fn mercy_discover(mercy_call: &str, mercy_range: &str) -> String {
+    match capabilities::handler("mercy_discover", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_range),
+        _ => "Unable to run the discovery method requested".to_string()
+    }
+}
+
+/// Sweeps `range`, calling `on_alive` as each live host is found (from the calling thread)
+pub fn mercy_sweep<F: FnMut(&SweepHost)>(range: &str, options: &SweepOptions, mut on_alive: F) -> Result<SweepReport, String> {
+    let cidr: Cidr = range.parse()?;
+    let total = cidr.host_count();
+
+    if total > MAX_SWEEP_HOSTS && !options.force {
+        return Err(format!("Refusing to sweep {} ({} hosts); the limit is {} unless force is set", cidr, total, MAX_SWEEP_HOSTS));
+    }
+
+    if let SweepMethod::Tcp(ports) = &options.method {
+        if ports.is_empty() {
+            return Err("TCP sweep needs at least one port".to_string());
+        }
+    }
+
+    let v6 = cidr.network().is_ipv6();
+    let icmp_raw = match options.method {
+        SweepMethod::Icmp => match icmp::icmp_access(v6) {
+            IcmpAccess::Raw => true,
+            IcmpAccess::Datagram => false,
+            IcmpAccess::Unavailable(reason) => return Err(format!("ICMP sweep unavailable ({}); use the TCP method", reason))
+        },
+        SweepMethod::Tcp(_) => false
+    };
+
+    let method_label = match &options.method {
+        SweepMethod::Tcp(ports) => format!("tcp connect to {:?}", ports),
+        SweepMethod::Icmp => "icmp echo".to_string()
+    };
+
+    let start = Instant::now();
+    let work = Mutex::new(cidr.hosts());
+    let workers = options.concurrency.max(1).min(total.min(usize::MAX as u128) as usize).max(1);
+    let (sender, receiver) = mpsc::channel::<SweepHost>();
+    let mut alive = Vec::new();
+
+    std::thread::scope(|scope| {
+        for worker in 0..workers {
+            let sender = sender.clone();
+            let work = &work;
+
+            scope.spawn(move || {
+                // Each worker gets its own ICMP socket and identifier
+                let icmp_socket = match options.method {
+                    SweepMethod::Icmp => icmp::open_icmp(v6, icmp_raw).ok(),
+                    SweepMethod::Tcp(_) => None
+                };
+                let ident = ((std::process::id() as usize + worker) & 0xFFFF) as u16;
+                let mut seq: u16 = 0;
+
+                loop {
+                    let next = work.lock().map(|mut hosts| hosts.next()).unwrap_or(None);
+                    let address = match next {
+                        Some(address) => address,
+                        None => break
+                    };
+
+                    let found = match &options.method {
+                        SweepMethod::Tcp(ports) => tcp_alive(address, ports, options.timeout),
+                        SweepMethod::Icmp => icmp_socket.as_ref().and_then(|socket| {
+                            seq = seq.wrapping_add(1);
+                            echo_once(socket, icmp_raw, address, ident, seq, options.timeout).ok().flatten()
+                                .map(|(rtt, _)| SweepHost { address, evidence: "echo reply".to_string(), port: None, rtt_ms: rtt, hostname: None })
+                        })
+                    };
+
+                    if let Some(host) = found {
+                        if sender.send(host).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        let dns_server = default_dns_server();
+        for mut host in receiver {
+            if options.resolve_names {
+                host.hostname = reverse_lookup(host.address, dns_server, Duration::from_secs(1)).ok().flatten();
+            }
+
+            on_alive(&host);
+            alive.push(host);
+        }
+    });
+
+    alive.sort_by_key(|host| crate::cidr::to_u128(host.address));
+
+    Ok(SweepReport {
+        range: cidr.to_string(),
+        method: method_label,
+        scanned: total,
+        alive,
+        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0
+    })
+}
+
+// Tries each port; an accepted or refused connection means the host answered
+fn tcp_alive(address: IpAddr, ports: &[u16], timeout: Duration) -> Option<SweepHost> {
+    for port in ports {
+        let start = Instant::now();
+
+        let evidence = match TcpStream::connect_timeout(&SocketAddr::new(address, *port), timeout) {
+            Ok(_) => format!("tcp/{} open", port),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => format!("tcp/{} refused (RST)", port),
+            Err(_) => continue
+        };
+
+        return Some(SweepHost {
+            address,
+            evidence,
+            port: Some(*port),
+            rtt_ms: start.elapsed().as_secs_f64() * 1000.0,
+            hostname: None
+        });
+    }
+
+    None
+}
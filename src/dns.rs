@@ -0,0 +1,895 @@
+/*
+    Minimal DNS packet builder/parser
+
+    Used by checks that need raw packets rather than the system resolver (open-resolver probes,
+    CAA/ANY lookups, tunneling experiments). Parsing is defensive: every read is bounds checked and
+    compression pointers may only jump backwards, a bounded number of times.
+*/
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+
+use crate::{
+    config,
+    rng,
+    stats::{self, Counter},
+    text::read_text_lines,
+    trailing_options
+};
+
+// Ends the error of a query that got no answer in time, which lookups report apart from other failures
+const TIMED_OUT: &str = "timed out";
+
+// Limits applied while decoding names from untrusted packets
+const MAX_POINTER_JUMPS: usize = 32;
+const MAX_NAME_LEN: usize = 255;
+
+// EDNS UDP payload size advertised by outgoing queries
+const EDNS_UDP_SIZE: u16 = 1232;
+
+/// DNS record/query types understood by the parser (anything else is kept as `Other`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Opt,
+    Any,
+    Caa,
+    Other(u16)
+}
+
+impl DnsType {
+    /// Numeric type code used on the wire
+    pub fn code(self) -> u16 {
+        match self {
+            DnsType::A => 1,
+            DnsType::Ns => 2,
+            DnsType::Cname => 5,
+            DnsType::Soa => 6,
+            DnsType::Ptr => 12,
+            DnsType::Mx => 15,
+            DnsType::Txt => 16,
+            DnsType::Aaaa => 28,
+            DnsType::Srv => 33,
+            DnsType::Opt => 41,
+            DnsType::Any => 255,
+            DnsType::Caa => 257,
+            DnsType::Other(code) => code
+        }
+    }
+
+    /// Maps a wire type code back to a `DnsType`
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            1 => DnsType::A,
+            2 => DnsType::Ns,
+            5 => DnsType::Cname,
+            6 => DnsType::Soa,
+            12 => DnsType::Ptr,
+            15 => DnsType::Mx,
+            16 => DnsType::Txt,
+            28 => DnsType::Aaaa,
+            33 => DnsType::Srv,
+            41 => DnsType::Opt,
+            255 => DnsType::Any,
+            257 => DnsType::Caa,
+            other => DnsType::Other(other)
+        }
+    }
+
+    /// Parses a mnemonic such as "AAAA" or "caa" (also accepts "TYPE65")
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.trim().to_ascii_uppercase();
+
+        let parsed = match upper.as_str() {
+            "A" => DnsType::A,
+            "NS" => DnsType::Ns,
+            "CNAME" => DnsType::Cname,
+            "SOA" => DnsType::Soa,
+            "PTR" => DnsType::Ptr,
+            "MX" => DnsType::Mx,
+            "TXT" => DnsType::Txt,
+            "AAAA" => DnsType::Aaaa,
+            "SRV" => DnsType::Srv,
+            "OPT" => DnsType::Opt,
+            "ANY" => DnsType::Any,
+            "CAA" => DnsType::Caa,
+            _ => DnsType::from_code(upper.strip_prefix("TYPE")?.parse().ok()?)
+        };
+
+        Some(parsed)
+    }
+}
+
+impl fmt::Display for DnsType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsType::A => write!(f, "A"),
+            DnsType::Ns => write!(f, "NS"),
+            DnsType::Cname => write!(f, "CNAME"),
+            DnsType::Soa => write!(f, "SOA"),
+            DnsType::Ptr => write!(f, "PTR"),
+            DnsType::Mx => write!(f, "MX"),
+            DnsType::Txt => write!(f, "TXT"),
+            DnsType::Aaaa => write!(f, "AAAA"),
+            DnsType::Srv => write!(f, "SRV"),
+            DnsType::Opt => write!(f, "OPT"),
+            DnsType::Any => write!(f, "ANY"),
+            DnsType::Caa => write!(f, "CAA"),
+            DnsType::Other(code) => write!(f, "TYPE{}", code)
+        }
+    }
+}
+
+/// A single-question DNS query
+#[derive(Debug, Clone)]
+pub struct DnsQuery {
+    pub id: u16,
+    pub name: String,
+    pub qtype: DnsType,
+    pub recursion_desired: bool,
+    pub edns: bool
+}
+
+impl DnsQuery {
+    /// Creates a recursive IN-class query with a random id and an EDNS OPT record
+    pub fn new(name: &str, qtype: DnsType) -> Self {
+        DnsQuery {
+            id: random_id(),
+            name: name.trim().trim_end_matches('.').to_string(),
+            qtype,
+            recursion_desired: true,
+            edns: true
+        }
+    }
+
+    /// Encodes the query into wire format
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut packet = Vec::with_capacity(64);
+        packet.extend_from_slice(&self.id.to_be_bytes());
+        packet.extend_from_slice(&(if self.recursion_desired { 0x0100u16 } else { 0 }).to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&0u16.to_be_bytes());
+        packet.extend_from_slice(&(self.edns as u16).to_be_bytes());
+
+        encode_name(&self.name, &mut packet)?;
+        packet.extend_from_slice(&self.qtype.code().to_be_bytes());
+        packet.extend_from_slice(&1u16.to_be_bytes());
+
+        if self.edns {
+            // Root name, OPT type, UDP payload size as class, zero extended rcode/flags, no options
+            packet.push(0);
+            packet.extend_from_slice(&DnsType::Opt.code().to_be_bytes());
+            packet.extend_from_slice(&EDNS_UDP_SIZE.to_be_bytes());
+            packet.extend_from_slice(&0u32.to_be_bytes());
+            packet.extend_from_slice(&0u16.to_be_bytes());
+        }
+
+        Ok(packet)
+    }
+
+    /// Sends the query over UDP (retrying over TCP when the answer is truncated)
+    pub fn send(&self, server: SocketAddr, timeout: Duration) -> Result<DnsResponse, String> {
+        let packet = self.to_bytes()?;
+        stats::count(Counter::DnsQueries, 1);
+
+        let bind_addr = if server.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(bind_addr).map_err(|e| format!("Unable to bind UDP socket: {}", e))?;
+        socket.connect(server).map_err(|e| format!("Unable to connect to {}: {}", server, e))?;
+        socket.send(&packet).map_err(|e| format!("Unable to send DNS query: {}", e))?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [0u8; 65535];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                stats::count(Counter::DnsTimeouts, 1);
+                return Err(format!("DNS query to {} {}", server, TIMED_OUT));
+            }
+            socket.set_read_timeout(Some(remaining)).map_err(|e| format!("Unable to set socket timeout: {}", e))?;
+
+            let received = socket.recv(&mut buffer).map_err(|e| match e.kind() {
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => {
+                    stats::count(Counter::DnsTimeouts, 1);
+                    format!("DNS query to {} {}", server, TIMED_OUT)
+                },
+                _ => format!("Unable to receive DNS response: {}", e)
+            })?;
+
+            // Ignore stray datagrams that do not belong to this query
+            if received < 2 || u16::from_be_bytes([buffer[0], buffer[1]]) != self.id {
+                continue;
+            }
+
+            let response = DnsResponse::parse(&buffer[..received])?;
+            if response.truncated {
+                return self.send_tcp(server, timeout);
+            }
+
+            return Ok(response);
+        }
+    }
+
+    /// Sends the query over TCP with a two-byte length prefix
+    pub fn send_tcp(&self, server: SocketAddr, timeout: Duration) -> Result<DnsResponse, String> {
+        let packet = self.to_bytes()?;
+        stats::count(Counter::DnsQueries, 1);
+
+        let mut stream = TcpStream::connect_timeout(&server, timeout)
+            .map_err(|e| format!("Unable to connect to {} over TCP: {}", server, e))?;
+        stream.set_read_timeout(Some(timeout)).map_err(|e| format!("Unable to set socket timeout: {}", e))?;
+        stream.set_write_timeout(Some(timeout)).map_err(|e| format!("Unable to set socket timeout: {}", e))?;
+
+        let mut framed = (packet.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&packet);
+        stream.write_all(&framed).map_err(|e| format!("Unable to send DNS query: {}", e))?;
+
+        let mut length = [0u8; 2];
+        stream.read_exact(&mut length).map_err(|e| format!("Unable to read DNS response: {}", e))?;
+
+        let mut body = vec![0u8; u16::from_be_bytes(length) as usize];
+        stream.read_exact(&mut body).map_err(|e| format!("Unable to read DNS response: {}", e))?;
+
+        DnsResponse::parse(&body)
+    }
+}
+
+/// EDNS information carried by an OPT pseudo-record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsInfo {
+    pub udp_payload_size: u16,
+    pub extended_rcode: u8,
+    pub version: u8,
+    pub dnssec_ok: bool
+}
+
+/// The question section of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: DnsType,
+    pub class: u16
+}
+
+/// Decoded record data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRdata {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Name(String),
+    Mx { preference: u16, exchange: String },
+    Txt(Vec<String>),
+    Soa { mname: String, rname: String, serial: u32, refresh: u32, retry: u32, expire: u32, minimum: u32 },
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Caa { flags: u8, tag: String, value: String },
+    Raw(Vec<u8>)
+}
+
+impl fmt::Display for DnsRdata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsRdata::A(addr) => write!(f, "{}", addr),
+            DnsRdata::Aaaa(addr) => write!(f, "{}", addr),
+            DnsRdata::Name(name) => write!(f, "{}", name),
+            DnsRdata::Mx { preference, exchange } => write!(f, "{} {}", preference, exchange),
+            DnsRdata::Txt(parts) => {
+                let quoted: Vec<String> = parts.iter().map(|p| format!("{:?}", p)).collect();
+                write!(f, "{}", quoted.join(" "))
+            }
+            DnsRdata::Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                write!(f, "{} {} {} {} {} {} {}", mname, rname, serial, refresh, retry, expire, minimum)
+            }
+            DnsRdata::Srv { priority, weight, port, target } => write!(f, "{} {} {} {}", priority, weight, port, target),
+            DnsRdata::Caa { flags, tag, value } => write!(f, "{} {} {:?}", flags, tag, value),
+            DnsRdata::Raw(bytes) => {
+                // RFC 3597 generic rendering
+                let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                write!(f, "\\# {} {}", bytes.len(), hex)
+            }
+        }
+    }
+}
+
+/// A resource record from any section of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: DnsType,
+    pub class: u16,
+    pub ttl: u32,
+    pub rdata: DnsRdata
+}
+
+impl fmt::Display for DnsRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.name, self.ttl, self.rtype, self.rdata)
+    }
+}
+
+/// A parsed DNS response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsResponse {
+    pub id: u16,
+    pub opcode: u8,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
+    pub rcode: u16,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+    pub authorities: Vec<DnsRecord>,
+    pub additionals: Vec<DnsRecord>,
+    pub edns: Option<EdnsInfo>
+}
+
+impl DnsResponse {
+    /// Parses a wire-format message; malformed input returns an error instead of panicking
+    pub fn parse(bytes: &[u8]) -> Result<DnsResponse, String> {
+        let mut reader = PacketReader { packet: bytes, pos: 0 };
+
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let qdcount = reader.u16()?;
+        let ancount = reader.u16()?;
+        let nscount = reader.u16()?;
+        let arcount = reader.u16()?;
+
+        let mut questions = Vec::new();
+        for _ in 0..qdcount {
+            let name = reader.name()?;
+            let qtype = DnsType::from_code(reader.u16()?);
+            let class = reader.u16()?;
+            questions.push(DnsQuestion { name, qtype, class });
+        }
+
+        let answers = reader.records(ancount)?;
+        let authorities = reader.records(nscount)?;
+        let mut additionals = reader.records(arcount)?;
+
+        let mut rcode = flags & 0x000F;
+        let mut edns = None;
+
+        // The OPT pseudo-record is not a real answer; lift it into `edns`
+        if let Some(index) = additionals.iter().position(|r| r.rtype == DnsType::Opt) {
+            let opt = additionals.remove(index);
+            let extended_rcode = (opt.ttl >> 24) as u8;
+            rcode |= (extended_rcode as u16) << 4;
+
+            edns = Some(EdnsInfo {
+                udp_payload_size: opt.class,
+                extended_rcode,
+                version: ((opt.ttl >> 16) & 0xFF) as u8,
+                dnssec_ok: opt.ttl & 0x8000 != 0
+            });
+        }
+
+        Ok(DnsResponse {
+            id,
+            opcode: ((flags >> 11) & 0x0F) as u8,
+            authoritative: flags & 0x0400 != 0,
+            truncated: flags & 0x0200 != 0,
+            recursion_desired: flags & 0x0100 != 0,
+            recursion_available: flags & 0x0080 != 0,
+            rcode,
+            questions,
+            answers,
+            authorities,
+            additionals,
+            edns
+        })
+    }
+
+    /// Mnemonic for the response code (e.g. "NOERROR", "NXDOMAIN")
+    pub fn rcode_name(&self) -> String {
+        rcode_name(self.rcode)
+    }
+}
+
+impl fmt::Display for DnsResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Status: {} (id {})", self.rcode_name(), self.id)?;
+        writeln!(f, "Flags: aa={} tc={} rd={} ra={}", self.authoritative, self.truncated, self.recursion_desired, self.recursion_available)?;
+
+        match &self.edns {
+            Some(edns) => writeln!(f, "EDNS: version {}, udp size {}, do={}", edns.version, edns.udp_payload_size, edns.dnssec_ok)?,
+            None => writeln!(f, "EDNS: not present")?
+        }
+
+        for (label, section) in [("Answer", &self.answers), ("Authority", &self.authorities), ("Additional", &self.additionals)] {
+            for record in section {
+                writeln!(f, "{}: {}", label, record)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Bounds-checked cursor over a packet
+struct PacketReader<'a> {
+    packet: &'a [u8],
+    pos: usize
+}
+
+impl PacketReader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.packet.len())
+            .ok_or_else(|| format!("Truncated DNS packet at offset {}", self.pos))?;
+        let slice = &self.packet[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // Reads a possibly compressed name starting at the cursor
+    fn name(&mut self) -> Result<String, String> {
+        let (name, next) = read_name(self.packet, self.pos)?;
+        self.pos = next;
+        Ok(name)
+    }
+
+    fn records(&mut self, count: u16) -> Result<Vec<DnsRecord>, String> {
+        let mut records = Vec::new();
+
+        for _ in 0..count {
+            let name = self.name()?;
+            let rtype = DnsType::from_code(self.u16()?);
+            let class = self.u16()?;
+            let ttl = self.u32()?;
+            let rdlength = self.u16()? as usize;
+
+            let rdata_start = self.pos;
+            self.take(rdlength)?;
+            let rdata = parse_rdata(self.packet, rdata_start, rdlength, rtype)?;
+
+            records.push(DnsRecord { name, rtype, class, ttl, rdata });
+        }
+
+        Ok(records)
+    }
+}
+
+// Decodes a name at `start`, returning it and the offset just past it in the original stream
+fn read_name(packet: &[u8], start: usize) -> Result<(String, usize), String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut resume_at = None;
+    let mut jumps = 0;
+    let mut total_len = 0;
+
+    loop {
+        let len = *packet.get(pos).ok_or_else(|| format!("Truncated name at offset {}", pos))?;
+
+        match len & 0xC0 {
+            0x00 => {
+                if len == 0 {
+                    pos += 1;
+                    break;
+                }
+
+                let label = packet.get(pos + 1..pos + 1 + len as usize)
+                    .ok_or_else(|| format!("Truncated label at offset {}", pos))?;
+
+                total_len += len as usize + 1;
+                if total_len > MAX_NAME_LEN {
+                    return Err(format!("Name exceeds {} bytes at offset {}", MAX_NAME_LEN, start));
+                }
+
+                labels.push(escape_label(label));
+                pos += 1 + len as usize;
+            }
+            0xC0 => {
+                let low = *packet.get(pos + 1).ok_or_else(|| format!("Truncated compression pointer at offset {}", pos))?;
+                let target = (((len & 0x3F) as usize) << 8) | low as usize;
+
+                // Pointers must go strictly backwards, which rules out loops; the jump cap bounds work
+                if target >= pos {
+                    return Err(format!("Forward or looping compression pointer at offset {}", pos));
+                }
+
+                jumps += 1;
+                if jumps > MAX_POINTER_JUMPS {
+                    return Err(format!("Too many compression pointers in name at offset {}", start));
+                }
+
+                if resume_at.is_none() {
+                    resume_at = Some(pos + 2);
+                }
+                pos = target;
+            }
+            _ => return Err(format!("Unsupported label type 0x{:02x} at offset {}", len, pos))
+        }
+    }
+
+    let name = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+    Ok((name, resume_at.unwrap_or(pos)))
+}
+
+// Renders label bytes, escaping anything that isn't a printable hostname character
+fn escape_label(label: &[u8]) -> String {
+    let mut out = String::with_capacity(label.len());
+
+    for byte in label {
+        match byte {
+            b'.' | b'\\' => {
+                out.push('\\');
+                out.push(*byte as char);
+            }
+            0x21..=0x7E => out.push(*byte as char),
+            _ => out.push_str(&format!("\\{:03}", byte))
+        }
+    }
+
+    out
+}
+
+fn parse_rdata(packet: &[u8], start: usize, len: usize, rtype: DnsType) -> Result<DnsRdata, String> {
+    let data = &packet[start..start + len];
+    let mut reader = PacketReader { packet, pos: start };
+
+    let rdata = match rtype {
+        DnsType::A if len == 4 => DnsRdata::A(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+        DnsType::Aaaa if len == 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(data);
+            DnsRdata::Aaaa(Ipv6Addr::from(octets))
+        }
+        DnsType::Ns | DnsType::Cname | DnsType::Ptr => DnsRdata::Name(reader.name()?),
+        DnsType::Mx => {
+            let preference = reader.u16()?;
+            DnsRdata::Mx { preference, exchange: reader.name()? }
+        }
+        DnsType::Txt => {
+            let mut parts = Vec::new();
+            let mut offset = 0;
+
+            while offset < len {
+                let part_len = data[offset] as usize;
+                let part = data.get(offset + 1..offset + 1 + part_len)
+                    .ok_or_else(|| format!("Truncated TXT string at offset {}", start + offset))?;
+                parts.push(String::from_utf8_lossy(part).into_owned());
+                offset += 1 + part_len;
+            }
+
+            DnsRdata::Txt(parts)
+        }
+        DnsType::Soa => DnsRdata::Soa {
+            mname: reader.name()?,
+            rname: reader.name()?,
+            serial: reader.u32()?,
+            refresh: reader.u32()?,
+            retry: reader.u32()?,
+            expire: reader.u32()?,
+            minimum: reader.u32()?
+        },
+        DnsType::Srv => DnsRdata::Srv {
+            priority: reader.u16()?,
+            weight: reader.u16()?,
+            port: reader.u16()?,
+            target: reader.name()?
+        },
+        DnsType::Caa if len >= 2 => {
+            let tag_len = data[1] as usize;
+            let tag = data.get(2..2 + tag_len).ok_or_else(|| format!("Truncated CAA tag at offset {}", start))?;
+
+            DnsRdata::Caa {
+                flags: data[0],
+                tag: String::from_utf8_lossy(tag).into_owned(),
+                value: String::from_utf8_lossy(&data[2 + tag_len..]).into_owned()
+            }
+        }
+        _ => DnsRdata::Raw(data.to_vec())
+    };
+
+    // Names inside rdata must not run past the record boundary
+    if reader.pos > start + len {
+        return Err(format!("Record data overruns its length at offset {}", start));
+    }
+
+    Ok(rdata)
+}
+
+// Encodes a dotted name as length-prefixed labels
+pub(crate) fn encode_name(name: &str, packet: &mut Vec<u8>) -> Result<(), String> {
+    let mut total = 1;
+
+    if !name.is_empty() && name != "." {
+        for label in name.trim_end_matches('.').split('.') {
+            if label.is_empty() {
+                return Err(format!("Empty label in name '{}'", name));
+            }
+
+            if label.len() > 63 {
+                return Err(format!("Label '{}' exceeds 63 bytes", label));
+            }
+
+            total += label.len() + 1;
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+    }
+
+    if total > MAX_NAME_LEN {
+        return Err(format!("Name '{}' exceeds {} bytes", name, MAX_NAME_LEN));
+    }
+
+    packet.push(0);
+    Ok(())
+}
+
+// Query ids come from the OS CSPRNG so they cannot be predicted by a spoofer
+fn random_id() -> u16 {
+    rng::random_seed() as u16
+}
+
+/// First nameserver from /etc/resolv.conf, falling back to 8.8.8.8
+pub fn default_dns_server() -> SocketAddr {
+    let configured = read_text_lines("/etc/resolv.conf").ok().and_then(|lines| {
+        lines.iter().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            if parts.next() != Some("nameserver") {
+                return None;
+            }
+
+            // Strip IPv6 zone ids such as fe80::1%eth0
+            let addr = parts.next()?.split('%').next()?;
+            addr.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53))
+        })
+    });
+
+    configured.unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53))
+}
+
+/// Resolves a "host", "host:port" or "ip" string into a DNS server address (port 53 by default)
+pub fn dns_server_addr(server: &str) -> Result<SocketAddr, String> {
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+
+    let with_port = if server.contains(':') { server.to_string() } else { format!("{}:53", server) };
+    with_port.to_socket_addrs()
+        .map_err(|e| format!("Unable to resolve DNS server '{}': {}", server, e))?
+        .next()
+        .ok_or_else(|| format!("Unable to resolve DNS server '{}'", server))
+}
+
+/// Builds the in-addr.arpa / ip6.arpa name used for PTR lookups
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6.octets().iter().rev()
+                .flat_map(|b| [format!("{:x}", b & 0x0F), format!("{:x}", b >> 4)])
+                .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+/// Looks up the PTR record for an address, returning `None` when no name is published
+pub fn reverse_lookup(ip: IpAddr, server: SocketAddr, timeout: Duration) -> Result<Option<String>, String> {
+    let response = DnsQuery::new(&reverse_name(ip), DnsType::Ptr).send(server, timeout)?;
+
+    if response.rcode != 0 && response.rcode != 3 {
+        return Err(format!("PTR lookup for {} failed: {}", ip, response.rcode_name()));
+    }
+
+    Ok(response.answers.iter().find_map(|record| match &record.rdata {
+        DnsRdata::Name(name) if record.rtype == DnsType::Ptr => Some(name.clone()),
+        _ => None
+    }))
+}
+
+// Resolves a host name or literal address, preferring IPv4 like most network tools
+pub(crate) fn resolve_host(host: &str) -> Result<IpAddr, String> {
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err("No host specified".to_string());
+    }
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(ip);
+    }
+
+    let addrs: Vec<SocketAddr> = (host, 0).to_socket_addrs()
+        .map_err(|e| format!("Unable to resolve {}: {}", host, e))?
+        .collect();
+
+    addrs.iter().find(|a| a.is_ipv4()).or_else(|| addrs.first())
+        .map(|a| a.ip())
+        .ok_or_else(|| format!("Unable to resolve {}", host))
+}
+
+/// How a lookup ended, for callers that branch on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DnsLookupStatus {
+    Ok,
+    /// The name does not exist
+    Nxdomain,
+    /// The name exists but has no records of the types asked for
+    NoRecords,
+    /// The server did not answer in time
+    Timeout,
+    /// Any other failure (SERVFAIL, REFUSED, a network error)
+    Failed
+}
+
+/// Options for `mercy_dns_lookup` and `mercy_reverse_dns`
+#[derive(Debug, Clone)]
+pub struct DnsLookupOptions {
+    /// DNS server (default: the system resolver's first nameserver)
+    pub server: Option<SocketAddr>,
+    /// Per query
+    pub timeout: Duration
+}
+
+impl Default for DnsLookupOptions {
+    fn default() -> Self {
+        DnsLookupOptions { server: None, timeout: config::read_timeout(Duration::from_secs(3)) }
+    }
+}
+
+/// Addresses of a name, or the name of an address
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsLookup {
+    pub query: String,
+    pub server: SocketAddr,
+    pub status: DnsLookupStatus,
+    /// Addresses for a forward lookup, the host name for a reverse one
+    pub answers: Vec<String>,
+    /// Why there are no answers
+    pub message: Option<String>
+}
+
+impl fmt::Display for DnsLookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}", message),
+            None => write!(f, "{}", self.answers.join("\n"))
+        }
+    }
+}
+
+/// A and AAAA records of a name (CNAMEs followed by the server), one lookup status for both
+pub fn mercy_dns_lookup(name: &str, options: &DnsLookupOptions) -> Result<DnsLookup, String> {
+    let name = name.trim().trim_end_matches('.').to_string();
+    if name.is_empty() {
+        return Err("No domain specified".to_string());
+    }
+    let server = options.server.unwrap_or_else(default_dns_server);
+
+    let mut answers = Vec::new();
+    let mut outcomes = Vec::new();
+    for qtype in [DnsType::A, DnsType::Aaaa] {
+        match DnsQuery::new(&name, qtype).send(server, options.timeout) {
+            Ok(response) => {
+                answers.extend(response.answers.iter().filter(|record| record.rtype == qtype).map(|record| record.rdata.to_string()));
+                outcomes.push(Ok(response.rcode));
+            },
+            Err(e) => outcomes.push(Err(e))
+        }
+    }
+
+    let (status, message) = if !answers.is_empty() {
+        (DnsLookupStatus::Ok, None)
+    } else if outcomes.contains(&Ok(3)) {
+        (DnsLookupStatus::Nxdomain, Some(format!("NXDOMAIN: {} does not exist", name)))
+    } else if let Some(Err(e)) = outcomes.iter().find(|outcome| outcome.is_err()) {
+        lookup_failure(e, server, options.timeout)
+    } else if let Some(Ok(rcode)) = outcomes.iter().find(|outcome| !matches!(outcome, Ok(0))) {
+        (DnsLookupStatus::Failed, Some(format!("{}: {} answered the lookup of {} with an error", rcode_name(*rcode), server, name)))
+    } else {
+        (DnsLookupStatus::NoRecords, Some(format!("No records: {} exists but has no A or AAAA records", name)))
+    };
+
+    Ok(DnsLookup { query: name, server, status, answers, message })
+}
+
+/// The PTR host name of an address
+pub fn mercy_reverse_dns(ip: IpAddr, options: &DnsLookupOptions) -> DnsLookup {
+    let server = options.server.unwrap_or_else(default_dns_server);
+    let query = ip.to_string();
+
+    let (status, answers, message) = match DnsQuery::new(&reverse_name(ip), DnsType::Ptr).send(server, options.timeout) {
+        Ok(response) => {
+            let names: Vec<String> = response.answers.iter()
+                .filter_map(|record| match &record.rdata {
+                    DnsRdata::Name(name) if record.rtype == DnsType::Ptr => Some(name.trim_end_matches('.').to_string()),
+                    _ => None
+                })
+                .collect();
+
+            match response.rcode {
+                _ if !names.is_empty() => (DnsLookupStatus::Ok, names, None),
+                // Most resolvers answer NXDOMAIN for an address without a PTR record
+                0 | 3 => (DnsLookupStatus::NoRecords, names, Some(format!("No PTR record: {} has no reverse DNS name", ip))),
+                rcode => (DnsLookupStatus::Failed, names, Some(format!("{}: {} answered the PTR lookup of {} with an error", rcode_name(rcode), server, ip)))
+            }
+        },
+        Err(e) => {
+            let (status, message) = lookup_failure(&e, server, options.timeout);
+            (status, Vec::new(), message)
+        }
+    };
+
+    DnsLookup { query, server, status, answers, message }
+}
+
+fn lookup_failure(error: &str, server: SocketAddr, timeout: Duration) -> (DnsLookupStatus, Option<String>) {
+    if error.ends_with(TIMED_OUT) {
+        (DnsLookupStatus::Timeout, Some(format!("Timeout: no answer from {} within {} s", server, timeout.as_secs_f64())))
+    } else {
+        (DnsLookupStatus::Failed, Some(format!("Lookup failed: {}", error)))
+    }
+}
+
+fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        16 => "BADVERS".to_string(),
+        other => format!("RCODE{}", other)
+    }
+}
+
+// Parses "domain-or-ip [server=1.1.1.1] [timeout=3]"
+fn lookup_options_from_args(input: &str) -> Result<(String, DnsLookupOptions), String> {
+    let (target, pairs) = trailing_options(input, &["server", "timeout"]);
+    let mut options = DnsLookupOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "server" => options.server = Some(dns_server_addr(value)?),
+            _ => options.timeout = Duration::from_secs_f64(value.parse().ok().filter(|secs: &f64| *secs > 0.0).ok_or_else(|| format!("Invalid timeout '{}'", value))?)
+        }
+    }
+
+    Ok((target, options))
+}
+
+pub(crate) fn dns_lookup_from_args(input: &str) -> Result<DnsLookup, String> {
+    let (name, options) = lookup_options_from_args(input)?;
+    mercy_dns_lookup(&name, &options)
+}
+
+pub(crate) fn reverse_dns_from_args(input: &str) -> Result<DnsLookup, String> {
+    let (ip, options) = lookup_options_from_args(input)?;
+    let ip: IpAddr = ip.trim().trim_start_matches('[').trim_end_matches(']').parse().map_err(|_| format!("Invalid IP address '{}'", ip.trim()))?;
+    Ok(mercy_reverse_dns(ip, &options))
+}
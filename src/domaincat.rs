@@ -0,0 +1,295 @@
+/*
+    Offline domain categorization from wordlists
+
+    Phishing and abuse domains are usually built from a handful of telling words
+    ("paypal-secure-login-verify.com"), so a rough category falls out of the name itself.
+    Each label left of the public suffix is split on hyphens and digits, and each run of
+    letters is segmented into dictionary words: of all ways to cut the run, the one leaving
+    the fewest letters outside any word wins, then the one with the fewest pieces, so
+    "bitcoinwallet" reads "bitcoin wallet" rather than "bit coin wall et". Words may carry a
+    plural "s". Tokens are then matched against category wordlists.
+
+    Words of three letters or fewer only count when their run split into words completely,
+    so "sol" does not make "solution" about crypto. The public suffix is found with a short
+    list of generic second levels (co.uk, com.au...) rather than the full Public Suffix List.
+*/
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::{
+    text::read_text_file,
+    trailing_options
+};
+
+const DOMAIN_WORDS: &str = include_str!("domain_words.txt");
+
+// Wordlist category whose words only help segmentation
+const COMMON: &str = "common";
+
+// Second-level labels that, under a two-letter country code, are part of the suffix
+const GENERIC_SECOND_LEVELS: &[&str] = &["ac", "co", "com", "edu", "go", "gob", "gov", "mil", "ne", "net", "nic", "or", "org"];
+
+// Longest word tried while segmenting
+const MAX_WORD: usize = 24;
+
+/// Category wordlists: lines of "category word word ..." ("common" words only help segmentation)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CategoryWordlist {
+    pub categories: BTreeMap<String, BTreeSet<String>>
+}
+
+impl CategoryWordlist {
+    /// The wordlists built into the crate (finance, shipping, auth, adult, gaming, crypto)
+    pub fn builtin() -> CategoryWordlist {
+        // The embedded file is known to parse
+        CategoryWordlist::parse(DOMAIN_WORDS).unwrap_or_default()
+    }
+
+    /// Parses "category word word ..." lines; a category may span several lines and "#" starts a comment
+    pub fn parse(text: &str) -> Result<CategoryWordlist, String> {
+        let mut wordlist = CategoryWordlist::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let category = fields.next().unwrap_or("").to_lowercase();
+            let words: Vec<String> = fields.map(|word| word.to_lowercase()).collect();
+
+            if words.is_empty() {
+                return Err(format!("Wordlist line {}: expected \"category word word ...\", got '{}'", number + 1, line));
+            }
+            if let Some(word) = words.iter().find(|word| !word.chars().all(|c| c.is_ascii_lowercase())) {
+                return Err(format!("Wordlist line {}: '{}' is not a word of letters a-z", number + 1, word));
+            }
+
+            wordlist.categories.entry(category).or_default().extend(words);
+        }
+
+        Ok(wordlist)
+    }
+
+    /// Reads a wordlist file (see `parse`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<CategoryWordlist, String> {
+        let path = path.as_ref();
+        let text = read_text_file(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        CategoryWordlist::parse(&text)
+    }
+
+    /// Adds the categories and words of another wordlist
+    pub fn extend(&mut self, other: CategoryWordlist) {
+        for (category, words) in other.categories {
+            self.categories.entry(category).or_default().extend(words);
+        }
+    }
+
+    // Every word of every list, for segmentation
+    fn dictionary(&self) -> BTreeSet<&str> {
+        self.categories.values().flatten().map(String::as_str).collect()
+    }
+}
+
+/// A category and the tokens of the domain that triggered it
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryMatch {
+    pub category: String,
+    pub tokens: Vec<String>
+}
+
+/// Words found in a domain name and the categories they point to
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainCategoryReport {
+    pub domain: String,
+    /// The label left of the public suffix
+    pub sld: String,
+    pub suffix: String,
+    /// Every token of the labels left of the suffix ("www" aside), in order
+    pub tokens: Vec<String>,
+    /// Tokens that are not dictionary words
+    pub unknown: Vec<String>,
+    /// Most tokens first
+    pub categories: Vec<CategoryMatch>
+}
+
+impl fmt::Display for DomainCategoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} (SLD {}, suffix {})", self.domain, self.sld, self.suffix)?;
+        writeln!(f, "Tokens: {}", self.tokens.join(" "))?;
+
+        if self.categories.is_empty() {
+            writeln!(f, "Categories: none")?;
+        }
+        for category in &self.categories {
+            writeln!(f, "  {}: {}", category.category, category.tokens.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+// One piece of a segmented run of letters
+struct Piece {
+    text: String,
+    known: bool
+}
+
+// Labels of the public suffix: "co.uk" style names under a country code count as two, anything else as one
+pub(crate) fn suffix_labels(labels: &[&str]) -> usize {
+    if labels.len() > 2 && labels[labels.len() - 1].len() == 2 && GENERIC_SECOND_LEVELS.contains(&labels[labels.len() - 2]) { 2 } else { 1 }
+}
+
+/// Categories a domain name suggests, from the words it is made of
+pub fn mercy_domain_category(domain: &str, wordlist: &CategoryWordlist) -> Result<DomainCategoryReport, String> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    let domain = domain.split("://").last().unwrap_or("").split(['/', ':']).next().unwrap_or("").to_string();
+    let labels: Vec<&str> = domain.split('.').collect();
+
+    if labels.len() < 2 || labels.iter().any(|label| label.is_empty()) {
+        return Err(format!("Invalid domain '{}'", domain));
+    }
+
+    let suffix_labels = suffix_labels(&labels);
+    let (names, suffix) = labels.split_at(labels.len() - suffix_labels);
+    let dictionary = wordlist.dictionary();
+
+    let mut tokens = Vec::new();
+    let mut unknown = Vec::new();
+    let mut matched: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for label in names.iter().filter(|label| **label != "www") {
+        for run in runs(label) {
+            // Digit runs are kept as tokens but never categorized
+            if run.starts_with(|c: char| c.is_ascii_digit()) {
+                tokens.push(run.to_string());
+                continue;
+            }
+
+            let pieces = segment(run, &dictionary);
+            let complete = pieces.iter().all(|piece| piece.known);
+
+            for piece in pieces {
+                if !piece.known {
+                    unknown.push(piece.text.clone());
+                } else if piece.text.len() > 3 || complete {
+                    for (category, words) in &wordlist.categories {
+                        if category != COMMON && lookup(words, &piece.text) && !matched.get(category.as_str()).is_some_and(|tokens| tokens.contains(&piece.text)) {
+                            matched.entry(category).or_default().push(piece.text.clone());
+                        }
+                    }
+                }
+                tokens.push(piece.text);
+            }
+        }
+    }
+
+    let mut categories: Vec<CategoryMatch> = matched.into_iter()
+        .map(|(category, tokens)| CategoryMatch { category: category.to_string(), tokens })
+        .collect();
+    categories.sort_by(|a, b| b.tokens.len().cmp(&a.tokens.len()).then_with(|| a.category.cmp(&b.category)));
+
+    Ok(DomainCategoryReport {
+        sld: names.last().unwrap_or(&"").to_string(),
+        suffix: suffix.join("."),
+        domain,
+        tokens,
+        unknown,
+        categories
+    })
+}
+
+// Runs of letters and runs of digits in a label, in order (hyphens and anything else separate them)
+fn runs(label: &str) -> Vec<&str> {
+    let mut runs = Vec::new();
+    let mut start = None;
+
+    for (index, c) in label.char_indices().chain([(label.len(), '-')]) {
+        let class = if c.is_ascii_lowercase() { Some(false) } else if c.is_ascii_digit() { Some(true) } else { None };
+
+        if let Some((from, digits)) = start {
+            if class != Some(digits) {
+                runs.push(&label[from..index]);
+                start = None;
+            }
+        }
+        if start.is_none() {
+            start = class.map(|digits| (index, digits));
+        }
+    }
+
+    runs
+}
+
+// A word in the set, or its plural
+fn lookup<S: std::borrow::Borrow<str> + Ord>(words: &BTreeSet<S>, word: &str) -> bool {
+    words.contains(word) || word.strip_suffix('s').is_some_and(|singular| singular.len() > 1 && words.contains(singular))
+}
+
+// Cuts a run of letters into words, leaving the fewest letters unmatched, then using the fewest pieces
+fn segment(run: &str, dictionary: &BTreeSet<&str>) -> Vec<Piece> {
+    let n = run.len();
+    // best[i]: (unmatched letters, pieces, start of the last piece, whether it is a word) for run[..i]
+    let mut best: Vec<Option<(usize, usize, usize, bool)>> = vec![None; n + 1];
+    best[0] = Some((0, 0, 0, false));
+
+    for end in 1..=n {
+        for start in end.saturating_sub(MAX_WORD)..end {
+            let Some((unmatched, pieces, _, _)) = best[start] else { continue };
+            let word = &run[start..end];
+            let known = word.len() > 1 && lookup(dictionary, word);
+            let candidate = if known { (unmatched, pieces + 1) } else { (unmatched + word.len(), pieces + 1) };
+
+            if best[end].is_none_or(|(u, p, _, _)| candidate < (u, p)) {
+                best[end] = Some((candidate.0, candidate.1, start, known));
+            }
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut end = n;
+    while end > 0 {
+        let Some((_, _, start, known)) = best[end] else { break };
+        let text = run[start..end].to_string();
+
+        // Adjacent unmatched pieces read as one
+        match pieces.last_mut() {
+            Some(Piece { text: last, known: false }) if !known => last.insert_str(0, &text),
+            _ => pieces.push(Piece { text, known })
+        }
+        end = start;
+    }
+
+    pieces.reverse();
+    pieces
+}
+
+// Parses "domain [words=extra.txt[,more.txt]] [replace=true]"
+pub(crate) fn domain_category_from_args(input: &str) -> Result<DomainCategoryReport, String> {
+    let (domain, pairs) = trailing_options(input, &["words", "replace"]);
+    let mut extra = Vec::new();
+    let mut replace = false;
+
+    for (key, value) in pairs {
+        match key {
+            "words" => for path in value.split(',').filter(|path| !path.is_empty()) {
+                extra.push(CategoryWordlist::load(path)?);
+            },
+            _ => replace = value == "true"
+        }
+    }
+
+    let mut wordlist = if replace { CategoryWordlist::default() } else { CategoryWordlist::builtin() };
+    for list in extra {
+        wordlist.extend(list);
+    }
+
+    mercy_domain_category(&domain, &wordlist)
+}
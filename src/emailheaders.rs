@@ -0,0 +1,538 @@
+/*
+    Email header analysis for phishing triage
+
+    Takes the header block of a message as text (anything after the first blank line, the
+    body, is ignored) and gives a quick read of where it came from and whether it is who it
+    claims to be:
+
+    - every header in order, folded lines unfolded and duplicates all kept
+    - the From, Sender, Reply-To and Return-Path addresses, with RFC 2047 encoded display
+      names decoded, flagging a Reply-To or Return-Path in another organization's domain
+      than From, and a display name showing an address other than the real one
+    - the Received chain from the origin to the recipient, each hop's sending host and
+      address, receiving host, protocol, time and the delay since the hop before; the
+      originating IP is the sending address of the earliest hop that is not private,
+      loopback or otherwise reserved (documentation ranges count, as in test data), or
+      X-Originating-IP when no hop has one
+    - SPF, DKIM, DMARC and the other results of each Authentication-Results header, and
+      Received-SPF, flagging failures
+
+    Domains are compared by organization (the label left of the public suffix, with the
+    "co.uk" style suffixes of domaincat.rs), so mail.example.com and example.com match. The
+    headers are only read: nothing is looked up, so the results of a receiving server that
+    cannot be trusted are reported as they stand.
+*/
+
+use std::{
+    fmt,
+    net::IpAddr
+};
+
+use serde::Serialize;
+
+use crate::{
+    bogon::bogon_range,
+    domaincat::suffix_labels,
+    logtime::{LogTimeOptions, detect_timestamp},
+    timefmt::{parse_utc, unix_to_rfc3339}
+};
+
+// Received hops whose time runs backwards by more than this (seconds) are flagged
+const CLOCK_SKEW: i64 = 300;
+
+// Results of an authentication method that are flagged
+const FAILED_RESULTS: [&str; 5] = ["fail", "softfail", "permerror", "temperror", "neutral"];
+
+/// One header, unfolded
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailHeader {
+    pub name: String,
+    pub value: String
+}
+
+/// An address from From, Sender, Reply-To or Return-Path
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailAddress {
+    /// The header it came from
+    pub header: String,
+    pub display_name: Option<String>,
+    pub address: String,
+    /// Lowercase domain of the address
+    pub domain: String
+}
+
+impl fmt::Display for EmailAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.display_name {
+            Some(name) => write!(f, "{}: \"{}\" <{}>", self.header, name, self.address),
+            None => write!(f, "{}: <{}>", self.header, self.address)
+        }
+    }
+}
+
+/// One Received header, numbered from the origin
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedHop {
+    /// 1 is the earliest hop
+    pub hop: usize,
+    /// Name the sender gave (HELO) and the reverse DNS name the receiver found, as written
+    pub from: Option<String>,
+    /// Sending address the receiver recorded
+    pub ip: Option<String>,
+    /// Receiving host
+    pub by: Option<String>,
+    /// Protocol ("ESMTPS", "SMTP", "HTTP"...)
+    pub with: Option<String>,
+    /// RFC 3339 UTC
+    pub timestamp: Option<String>,
+    /// Seconds since the hop before, negative when the clocks disagree
+    pub delay: Option<i64>,
+    pub raw: String
+}
+
+impl fmt::Display for ReceivedHop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let from = self.from.as_deref().unwrap_or("?");
+        write!(f, "{}. from {}", self.hop, from)?;
+        if let Some(ip) = self.ip.as_deref().filter(|ip| !from.contains(ip)) {
+            write!(f, " [{}]", ip)?;
+        }
+        write!(f, " by {}", self.by.as_deref().unwrap_or("?"))?;
+        if let Some(with) = &self.with {
+            write!(f, " with {}", with)?;
+        }
+        if let Some(timestamp) = &self.timestamp {
+            write!(f, " at {}", timestamp)?;
+        }
+        if let Some(delay) = self.delay {
+            write!(f, " ({:+} s)", delay)?;
+        }
+        Ok(())
+    }
+}
+
+/// One result of an Authentication-Results or Received-SPF header
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthResult {
+    /// "spf", "dkim", "dmarc", "arc"...
+    pub method: String,
+    /// "pass", "fail", "softfail", "none"...
+    pub result: String,
+    /// The properties and comments after the result ("smtp.mailfrom=example.com")
+    pub details: String,
+    /// Server that recorded it (the authserv-id)
+    pub server: Option<String>
+}
+
+/// The header analysis of a message
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailHeaderReport {
+    pub subject: Option<String>,
+    pub date: Option<String>,
+    pub message_id: Option<String>,
+    pub addresses: Vec<EmailAddress>,
+    /// From the origin to the recipient
+    pub received: Vec<ReceivedHop>,
+    pub originating_ip: Option<String>,
+    pub authentication: Vec<AuthResult>,
+    /// What looks wrong, one line each
+    pub flags: Vec<String>,
+    /// Every header in order
+    pub headers: Vec<EmailHeader>,
+    /// Lines that were neither a header nor a continuation
+    pub skipped_lines: usize
+}
+
+impl fmt::Display for EmailHeaderReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Message")?;
+        writeln!(f, "  Subject: {}", self.subject.as_deref().unwrap_or("(none)"))?;
+        writeln!(f, "  Date: {}", self.date.as_deref().unwrap_or("(none)"))?;
+        writeln!(f, "  Message-ID: {}", self.message_id.as_deref().unwrap_or("(none)"))?;
+
+        writeln!(f, "\nAddresses")?;
+        if self.addresses.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for address in &self.addresses {
+            writeln!(f, "  {}", address)?;
+        }
+
+        writeln!(f, "\nReceived chain (origin first)")?;
+        if self.received.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for hop in &self.received {
+            writeln!(f, "  {}", hop)?;
+        }
+        writeln!(f, "  Originating IP: {}", self.originating_ip.as_deref().unwrap_or("unknown"))?;
+
+        writeln!(f, "\nAuthentication")?;
+        if self.authentication.is_empty() {
+            writeln!(f, "  (no results recorded)")?;
+        }
+        for result in &self.authentication {
+            writeln!(f, "  {}={} {}", result.method, result.result, result.details)?;
+        }
+
+        write!(f, "\nFlags")?;
+        if self.flags.is_empty() {
+            write!(f, "\n  (none)")?;
+        }
+        for flag in &self.flags {
+            write!(f, "\n  - {}", flag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a raw header block (folded lines unfolded, duplicate headers kept) and reports its addresses, Received chain and authentication results with what looks wrong
+pub fn mercy_email_headers(raw: &str) -> Result<EmailHeaderReport, String> {
+    let (headers, skipped_lines) = unfold_headers(raw);
+    if headers.is_empty() {
+        return Err("No email headers found (expected \"Name: value\" lines)".to_string());
+    }
+
+    let values = |name: &'static str| headers.iter().filter(move |header| header.name.eq_ignore_ascii_case(name)).map(|header| header.value.as_str());
+    let first = |name: &'static str| values(name).next().map(|value| decode_words(value).trim().to_string());
+    let mut flags = Vec::new();
+
+    // Addresses
+    let mut addresses = Vec::new();
+    for name in ["From", "Sender", "Reply-To", "Return-Path"] {
+        for value in values(name) {
+            addresses.extend(parse_address_list(value).into_iter().map(|(display_name, address)| EmailAddress {
+                header: name.to_string(),
+                domain: address.rsplit_once('@').map(|(_, domain)| domain.trim_end_matches('.').to_lowercase()).unwrap_or_default(),
+                display_name,
+                address
+            }));
+        }
+    }
+    let from_headers = values("From").count();
+    let from: Vec<&EmailAddress> = addresses.iter().filter(|address| address.header == "From").collect();
+    match (from_headers, from.len()) {
+        (0, _) => flags.push("No From header".to_string()),
+        (1, 0) => flags.push("From has no address".to_string()),
+        (1, 1) => {},
+        (1, count) => flags.push(format!("From lists {} addresses", count)),
+        (count, _) => flags.push(format!("{} From headers; clients differ in which they show", count))
+    }
+    if let Some(sender) = from.first() {
+        let sender_organization = organization(&sender.domain);
+        for other in addresses.iter().filter(|address| matches!(address.header.as_str(), "Reply-To" | "Return-Path")) {
+            if !other.domain.is_empty() && organization(&other.domain) != sender_organization {
+                flags.push(format!("{} domain {} differs from From domain {}", other.header, other.domain, sender.domain));
+            }
+        }
+    }
+    for address in &addresses {
+        let shown = address.display_name.as_deref().and_then(|name| parse_address_list(name).into_iter().find(|(_, shown)| shown.contains('@')).map(|(_, shown)| shown));
+        if let Some(shown) = shown.filter(|shown| !shown.eq_ignore_ascii_case(&address.address)) {
+            flags.push(format!("{} display name shows {} but the address is {}", address.header, shown, address.address));
+        }
+    }
+
+    // Received chain, the last header being the earliest hop
+    let mut received: Vec<ReceivedHop> = values("Received").collect::<Vec<_>>().into_iter().rev().enumerate()
+        .map(|(index, value)| parse_received(index + 1, value))
+        .collect();
+    let mut previous: Option<i64> = None;
+    for hop in &mut received {
+        let secs = hop.timestamp.as_deref().and_then(|time| parse_utc(time).ok());
+        if let (Some(secs), Some(before)) = (secs, previous) {
+            hop.delay = Some(secs - before);
+            if secs - before < -CLOCK_SKEW {
+                flags.push(format!("Received hop {} is {} s earlier than the hop before it; a forged or misdated header", hop.hop, before - secs));
+            }
+        }
+        previous = secs.or(previous);
+    }
+    let originating_ip = received.iter()
+        .filter_map(|hop| hop.ip.as_deref())
+        .find(|ip| ip.parse::<IpAddr>().map(|ip| matches!(bogon_range(ip), None | Some(("documentation", _)))).unwrap_or(false))
+        .map(str::to_string)
+        .or_else(|| first("X-Originating-IP").map(|ip| ip.trim_matches(|c| c == '[' || c == ']').to_string()));
+    if received.is_empty() {
+        flags.push("No Received headers; the message was not relayed or they were removed".to_string());
+    }
+
+    // Authentication results
+    let mut authentication = Vec::new();
+    for value in values("Authentication-Results") {
+        authentication.extend(parse_authentication_results(value));
+    }
+    for value in values("Received-SPF") {
+        let result = value.split_whitespace().next().unwrap_or("").to_lowercase();
+        let details = value.trim()[result.len()..].trim().trim_end_matches(';').to_string();
+        authentication.push(AuthResult { method: "spf".to_string(), result, details, server: None });
+    }
+    if authentication.is_empty() {
+        flags.push("No Authentication-Results or Received-SPF; SPF, DKIM and DMARC were not recorded".to_string());
+    }
+    // One flag per method and result, though Authentication-Results and Received-SPF may both record it
+    let mut failed: Vec<(&str, &str)> = Vec::new();
+    for result in &authentication {
+        if FAILED_RESULTS.contains(&result.result.as_str()) && !failed.contains(&(result.method.as_str(), result.result.as_str())) {
+            failed.push((result.method.as_str(), result.result.as_str()));
+            flags.push(format!("{} {}{}", result.method.to_uppercase(), result.result, result.server.as_deref().map(|server| format!(" at {}", server)).unwrap_or_default()));
+        }
+    }
+    if !authentication.is_empty() && !authentication.iter().any(|result| result.method == "dmarc") {
+        flags.push("No DMARC result".to_string());
+    }
+
+    Ok(EmailHeaderReport {
+        subject: first("Subject"),
+        date: first("Date"),
+        message_id: first("Message-ID"),
+        addresses,
+        received,
+        originating_ip,
+        authentication,
+        flags,
+        headers,
+        skipped_lines
+    })
+}
+
+// Headers up to the first blank line, each continuation line (starting with a space or tab)
+// joined to the one before with a single space; a leading mbox "From " line is skipped
+fn unfold_headers(raw: &str) -> (Vec<EmailHeader>, usize) {
+    let mut headers: Vec<EmailHeader> = Vec::new();
+    let mut skipped = 0;
+
+    for (index, line) in raw.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            if headers.is_empty() {
+                continue;
+            }
+            break;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            match headers.last_mut() {
+                Some(header) => {
+                    header.value.push(' ');
+                    header.value.push_str(line.trim());
+                },
+                None => skipped += 1
+            }
+            continue;
+        }
+
+        match line.split_once(':') {
+            Some((name, value)) if !name.is_empty() && name.chars().all(|c| c.is_ascii_graphic()) => {
+                headers.push(EmailHeader { name: name.to_string(), value: value.trim().to_string() });
+            },
+            _ if index == 0 && line.starts_with("From ") => {},
+            _ => skipped += 1
+        }
+    }
+
+    for header in &mut headers {
+        header.value = header.value.trim().to_string();
+    }
+    (headers, skipped)
+}
+
+// "Name <a@b>, c@d, \"Last, First\" <e@f>" as (display name, address) pairs
+fn parse_address_list(value: &str) -> Vec<(Option<String>, String)> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let (mut quoted, mut angle, mut comment) = (false, false, 0);
+
+    for c in value.chars() {
+        match c {
+            '"' if !angle && comment == 0 => quoted = !quoted,
+            '<' if !quoted && comment == 0 => angle = true,
+            '>' if !quoted && comment == 0 => angle = false,
+            '(' if !quoted && !angle => comment += 1,
+            ')' if !quoted && !angle && comment > 0 => comment -= 1,
+            ',' if !quoted && !angle && comment == 0 => {
+                entries.push(std::mem::take(&mut current));
+                continue;
+            },
+            _ => {}
+        }
+        current.push(c);
+    }
+    entries.push(current);
+
+    entries.iter().filter_map(|entry| {
+        let entry = entry.trim();
+        let (name, address) = match (entry.rfind('<'), entry.rfind('>')) {
+            (Some(open), Some(close)) if open < close => (entry[..open].trim(), entry[open + 1..close].trim()),
+            _ => {
+                // A bare address, perhaps with a "(Name)" comment after it
+                let (address, comment) = entry.split_once('(').unwrap_or((entry, ""));
+                (comment.trim_end_matches(')').trim(), address.trim())
+            }
+        };
+        let name = decode_words(name.trim_matches('"').trim());
+        (!address.is_empty()).then(|| ((!name.is_empty()).then_some(name), address.to_string()))
+    }).collect()
+}
+
+// RFC 2047 encoded words ("=?UTF-8?B?...?=", "=?iso-8859-1?Q?...?=") decoded; whitespace between two of them dropped
+fn decode_words(text: &str) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    let mut after_word = false;
+
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..].split_once("?=").and_then(|(word, tail)| {
+            let mut parts = word.splitn(3, '?');
+            let (charset, encoding, data) = (parts.next()?, parts.next()?, parts.next()?);
+            let bytes = match encoding.to_ascii_uppercase().as_str() {
+                "B" => base64::decode(data).ok()?,
+                "Q" => quoted_printable(data),
+                _ => return None
+            };
+            let text = match charset.to_ascii_lowercase().as_str() {
+                "iso-8859-1" | "latin1" | "windows-1252" => bytes.iter().map(|byte| *byte as char).collect(),
+                _ => String::from_utf8_lossy(&bytes).to_string()
+            };
+            Some((text, tail))
+        });
+
+        match decoded {
+            Some((text, tail)) => {
+                let between = &rest[..start];
+                if !(after_word && between.trim().is_empty()) {
+                    output.push_str(between);
+                }
+                output.push_str(&text);
+                rest = tail;
+                after_word = true;
+            },
+            None => {
+                output.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+// The Q encoding: "_" is a space, "=XX" a byte
+fn quoted_printable(data: &str) -> Vec<u8> {
+    let bytes = data.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'_' => output.push(b' '),
+            b'=' => match data.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    output.push(byte);
+                    index += 2;
+                },
+                None => output.push(b'=')
+            },
+            byte => output.push(byte)
+        }
+        index += 1;
+    }
+    output
+}
+
+// "from helo (rdns [ip]) by host (comment) with ESMTPS id x for <y>; date"
+fn parse_received(hop: usize, value: &str) -> ReceivedHop {
+    let (clauses, date) = match value.rsplit_once(';') {
+        Some((clauses, date)) => (clauses, Some(date.trim())),
+        None => (value, None)
+    };
+
+    // Clause keyword -> its text, split at keywords outside comments
+    let mut parts: Vec<(String, String)> = Vec::new();
+    let mut depth = 0;
+    for word in clauses.split_whitespace() {
+        let keyword = word.to_ascii_lowercase();
+        if depth == 0 && matches!(keyword.as_str(), "from" | "by" | "via" | "with" | "id" | "for") {
+            parts.push((keyword, String::new()));
+        } else if let Some((_, text)) = parts.last_mut() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(word);
+        }
+        depth += word.matches('(').count() as i32 - word.matches(')').count() as i32;
+        depth = depth.max(0);
+    }
+    let clause = |name: &str| parts.iter().find(|(keyword, _)| keyword == name).map(|(_, text)| text.as_str()).filter(|text| !text.is_empty());
+    let first_word = |name: &str| clause(name).and_then(|text| text.split_whitespace().next()).map(|word| word.trim_matches(|c| c == '(' || c == ')').to_string());
+
+    let ip = clause("from").and_then(received_ip);
+    let timestamp = date.and_then(|date| detect_timestamp(date, &LogTimeOptions::default()))
+        .map(|detected| unix_to_rfc3339(detected.timestamp.secs, detected.timestamp.nanos));
+
+    ReceivedHop {
+        hop,
+        from: clause("from").map(str::to_string),
+        ip,
+        by: first_word("by"),
+        with: first_word("with"),
+        timestamp,
+        delay: None,
+        raw: value.to_string()
+    }
+}
+
+// The sending address in a from clause: the receiver records it in the comment after the
+// sender's name ("from helo (rdns [203.0.113.5])"), so the comment is searched before the
+// name, which is only what the sender claimed; bracketed ("[IPv6:2001:db8::1]") before bare
+fn received_ip(from: &str) -> Option<String> {
+    let comment = from.split_once('(').map(|(_, rest)| rest.split(')').next().unwrap_or(rest)).unwrap_or("");
+    [comment, from].into_iter().find_map(|text| {
+        let bracketed = text.split('[').skip(1).filter_map(|part| part.split(']').next());
+        let words = text.split(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '=' | ','));
+        bracketed.chain(words)
+            .map(|candidate| candidate.trim_start_matches("IPv6:").trim_start_matches("ipv6:"))
+            .find_map(|candidate| candidate.parse::<IpAddr>().ok())
+    }).map(|ip| ip.to_string())
+}
+
+// "mx.example; spf=pass smtp.mailfrom=a.example; dkim=fail (bad signature) header.d=b.example"
+fn parse_authentication_results(value: &str) -> Vec<AuthResult> {
+    let mut parts: Vec<&str> = value.split(';').collect();
+    // The authserv-id comes first, unless a sender left it out
+    let server = match parts.first() {
+        Some(first) if !first.contains('=') => {
+            let server = first.split_whitespace().next().map(str::to_string);
+            parts.remove(0);
+            server
+        },
+        _ => None
+    };
+    let mut results = Vec::new();
+
+    for part in parts {
+        let part = part.trim();
+        let Some((method, rest)) = part.split_once('=') else { continue };
+        let method = method.trim().to_lowercase();
+        if method.is_empty() || method.contains(char::is_whitespace) || method == "none" {
+            continue;
+        }
+        let rest = rest.trim();
+        let result: String = rest.chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+        results.push(AuthResult {
+            method,
+            result: result.to_lowercase(),
+            details: rest[result.len()..].trim().to_string(),
+            server: server.clone()
+        });
+    }
+    results
+}
+
+// The organization a domain belongs to: the label left of its public suffix, with the suffix
+fn organization(domain: &str) -> String {
+    let labels: Vec<&str> = domain.split('.').filter(|label| !label.is_empty()).collect();
+    let keep = (suffix_labels(&labels) + 1).min(labels.len());
+    labels[labels.len() - keep..].join(".")
+}
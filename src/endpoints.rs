@@ -0,0 +1,62 @@
+/*
+    Addresses of the fixed third-party services some lookups talk to
+
+    InQuest Labs for domain reputation by default, for WHOIS the registry of each query (see
+    whois.rs), public IP echo services for the external address (see publicip.rs), the Have I
+    Been Pwned range API for breached passwords (see password.rs), crt.sh for subdomains
+    from certificate transparency (see subdomains.rs) and Team Cymru's WHOIS service for
+    ASN lookups over port 43 (see asn.rs). Pointing them elsewhere reaches a
+    private mirror or, with the `testing` feature, the local mock services so those code paths
+    run offline.
+*/
+
+use std::sync::RwLock;
+
+static ENDPOINTS: RwLock<Option<ServiceEndpoints>> = RwLock::new(None);
+
+/// Where the InQuest, WHOIS, external IP, breached password, subdomain and ASN WHOIS lookups connect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceEndpoints {
+    /// Base URL the DFI search path is appended to
+    pub inquest: String,
+    /// "host:port" of a WHOIS server asked first instead of the query's registry
+    pub whois: Option<String>,
+    /// URL of an IP echo service asked instead of the public ones
+    pub ip_echo: Option<String>,
+    /// Base URL of the Pwned Passwords API, "/range/<prefix>" is appended
+    pub pwned_passwords: String,
+    /// Base URL of the crt.sh certificate search, "/?q=...&output=json" is appended
+    pub crtsh: String,
+    /// "host" or "host:port" of Team Cymru's IP to ASN WHOIS service
+    pub asn_whois: String
+}
+
+impl Default for ServiceEndpoints {
+    fn default() -> Self {
+        ServiceEndpoints {
+            inquest: "https://labs.inquest.net".to_string(),
+            whois: None,
+            ip_echo: None,
+            pwned_passwords: "https://api.pwnedpasswords.com".to_string(),
+            crtsh: "https://crt.sh".to_string(),
+            asn_whois: "whois.cymru.com".to_string()
+        }
+    }
+}
+
+/// Replaces the service endpoints for every later lookup (None restores the defaults)
+pub fn set_service_endpoints(endpoints: Option<ServiceEndpoints>) {
+    match ENDPOINTS.write() {
+        Ok(mut current) => *current = endpoints,
+        Err(poisoned) => *poisoned.into_inner() = endpoints
+    }
+}
+
+/// The endpoints set with `set_service_endpoints`, or the defaults
+pub fn service_endpoints() -> ServiceEndpoints {
+    let current = match ENDPOINTS.read() {
+        Ok(current) => current.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    };
+    current.unwrap_or_default()
+}
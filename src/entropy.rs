@@ -0,0 +1,330 @@
+/*
+    Shannon entropy of strings and files, whole, in fixed blocks, or over sliding windows
+
+    The file is read once, front to back. A ring buffer holds the current window and a byte
+    histogram is updated as bytes enter and leave it, so overlapping windows never re-read the
+    disk and memory is bounded by the window size. The highest-entropy windows (packed code,
+    compressed or encrypted data, key material) are reported with a short hex preview.
+
+    Whole-file and per-block entropy stream the file in chunks the same way; the last block
+    of a file that is not a multiple of the block size is reported at its real length.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::{filemap::ScanFile, paths::path_to_string, trailing_options};
+
+// Largest window accepted, which is also the most memory the ring buffer takes
+const MAX_WINDOW: usize = 64 * 1024 * 1024;
+
+// Regions listed in the summary, and the bytes previewed for each
+const TOP_REGIONS: usize = 10;
+const PREVIEW_BYTES: usize = 16;
+
+// Bytes taken from the file per read
+const READ_CHUNK: usize = 1024 * 1024;
+
+// Blocks at or above this entropy (bits per byte) are marked as likely compressed or encrypted
+pub(crate) const HIGH_ENTROPY: f64 = 7.2;
+
+/// Entropy of the window starting at `offset`, in bits per byte (0 to 8)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EntropyPoint {
+    pub offset: u64,
+    pub entropy: f64
+}
+
+/// One of the highest-entropy windows
+#[derive(Debug, Clone, Serialize)]
+pub struct EntropyRegion {
+    pub offset: u64,
+    pub length: u64,
+    pub entropy: f64,
+    /// The first bytes of the window as hex
+    pub preview: String
+}
+
+/// Sliding-window entropy of a file with summary statistics
+#[derive(Debug, Clone, Serialize)]
+pub struct EntropyProfile {
+    pub path: String,
+    pub size: u64,
+    pub window: usize,
+    pub step: usize,
+    /// Entropy of the file as a whole
+    pub file_entropy: f64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    /// Highest-entropy windows, highest first, none overlapping another
+    pub top_regions: Vec<EntropyRegion>,
+    pub points: Vec<EntropyPoint>
+}
+
+/// Entropy of one fixed-size block of a file
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EntropySection {
+    pub offset: u64,
+    pub length: u64,
+    pub entropy: f64
+}
+
+/// Per-block entropy of a file, in file order
+#[derive(Debug, Clone, Serialize)]
+pub struct EntropySections {
+    pub path: String,
+    pub size: u64,
+    pub block: usize,
+    pub file_entropy: f64,
+    /// Offset of the first block at 7.2 bits per byte or more, where packed or encrypted data likely starts
+    pub first_high: Option<u64>,
+    pub sections: Vec<EntropySection>
+}
+
+impl fmt::Display for EntropySections {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} bytes, {:.2} bits per byte, {}-byte blocks", self.path, self.size, self.file_entropy, self.block)?;
+        for section in &self.sections {
+            let marker = if section.entropy >= HIGH_ENTROPY { "  high" } else { "" };
+            writeln!(f, "0x{:08x}  {:.2}{}", section.offset, section.entropy, marker)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EntropyProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// Computes the entropy of every `window`-byte window of `path`, starting every `step` bytes
+pub fn mercy_entropy_profile<P: AsRef<Path>>(path: P, window: usize, step: usize) -> Result<EntropyProfile, String> {
+    let path = path.as_ref();
+    let file = ScanFile::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let size = file.size();
+
+    if window == 0 || step == 0 {
+        return Err("Window and step must both be at least 1 byte".to_string());
+    }
+    if step > window {
+        return Err(format!("Step ({}) must not be larger than the window ({})", step, window));
+    }
+    if window > MAX_WINDOW {
+        return Err(format!("Window ({}) is larger than the {} byte limit", window, MAX_WINDOW));
+    }
+    if window as u64 > size {
+        return Err(format!("Window ({}) is larger than {} ({} bytes)", window, path.display(), size));
+    }
+
+    let mut ring = vec![0u8; window];
+    let mut histogram = [0u64; 256];
+    let mut totals = [0u64; 256];
+    let mut points = Vec::new();
+
+    let mut position: u64 = 0;
+
+    loop {
+        let chunk = file.read_at(position, READ_CHUNK).map_err(|e| format!("Unable to read {} at offset {}: {}", path.display(), position, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        for byte in chunk.iter() {
+            let slot = (position % window as u64) as usize;
+            if position >= window as u64 {
+                histogram[ring[slot] as usize] -= 1;
+            }
+            ring[slot] = *byte;
+            histogram[*byte as usize] += 1;
+            totals[*byte as usize] += 1;
+            position += 1;
+
+            // A window ends here; keep it when its start falls on a step
+            if let Some(start) = position.checked_sub(window as u64) {
+                if start % step as u64 == 0 {
+                    points.push(EntropyPoint { offset: start, entropy: shannon(&histogram, window as u64) });
+                }
+            }
+        }
+    }
+
+    // The file can shrink between the size check and the read
+    if points.is_empty() {
+        return Err(format!("{} is shorter than the window ({} bytes)", path.display(), window));
+    }
+
+    let count = points.len() as f64;
+    let mean = points.iter().map(|point| point.entropy).sum::<f64>() / count;
+    let variance = points.iter().map(|point| (point.entropy - mean).powi(2)).sum::<f64>() / count;
+
+    let top_regions = top_regions(&file, &points, window)?;
+
+    Ok(EntropyProfile {
+        path: path_to_string(path),
+        size,
+        window,
+        step,
+        file_entropy: round(shannon(&totals, position)),
+        min: round(points.iter().map(|point| point.entropy).fold(f64::MAX, f64::min)),
+        max: round(points.iter().map(|point| point.entropy).fold(0.0, f64::max)),
+        mean: round(mean),
+        std_dev: round(variance.sqrt()),
+        top_regions,
+        points: points.into_iter().map(|point| EntropyPoint { offset: point.offset, entropy: round(point.entropy) }).collect()
+    })
+}
+
+/// Shannon entropy of `bytes` in bits per byte (0 for no bytes)
+pub fn entropy_bytes(bytes: &[u8]) -> f64 {
+    let mut histogram = [0u64; 256];
+    for byte in bytes {
+        histogram[*byte as usize] += 1;
+    }
+    shannon(&histogram, bytes.len() as u64)
+}
+
+/// Shannon entropy of the whole file in bits per byte, read in chunks (0 for an empty file)
+pub fn mercy_entropy_file<P: AsRef<Path>>(path: P) -> Result<f64, String> {
+    let (totals, size) = blocks(path.as_ref(), READ_CHUNK, |_, _, _| {})?;
+    Ok(shannon(&totals, size))
+}
+
+/// Entropy of every `block`-byte block of the file, read in chunks
+pub fn mercy_entropy_sections<P: AsRef<Path>>(path: P, block: usize) -> Result<EntropySections, String> {
+    let path = path.as_ref();
+    if block == 0 || block > MAX_WINDOW {
+        return Err(format!("Block size must be between 1 and {} bytes", MAX_WINDOW));
+    }
+
+    let mut sections = Vec::new();
+    let (totals, size) = blocks(path, block, |offset, histogram, length| {
+        sections.push(EntropySection { offset, length, entropy: round(shannon(histogram, length)) });
+    })?;
+
+    Ok(EntropySections {
+        path: path_to_string(path),
+        size,
+        block,
+        file_entropy: round(shannon(&totals, size)),
+        first_high: sections.iter().find(|section| section.entropy >= HIGH_ENTROPY).map(|section| section.offset),
+        sections
+    })
+}
+
+// Streams the file in `block`-byte blocks, handing each block's offset, histogram and length
+// to `on_block`, and returns the histogram and length of the whole file
+fn blocks<F: FnMut(u64, &[u64; 256], u64)>(path: &Path, block: usize, mut on_block: F) -> Result<([u64; 256], u64), String> {
+    let file = ScanFile::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut totals = [0u64; 256];
+    let mut offset: u64 = 0;
+
+    loop {
+        let chunk = file.read_at(offset, block).map_err(|e| format!("Unable to read {} at offset {}: {}", path.display(), offset, e))?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        let mut histogram = [0u64; 256];
+        for byte in chunk.iter() {
+            histogram[*byte as usize] += 1;
+        }
+        on_block(offset, &histogram, chunk.len() as u64);
+
+        for (total, count) in totals.iter_mut().zip(histogram) {
+            *total += count;
+        }
+        offset += chunk.len() as u64;
+    }
+
+    Ok((totals, offset))
+}
+
+/// Shannon entropy in bits per byte of a histogram over `total` bytes
+pub(crate) fn shannon(histogram: &[u64; 256], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    let total = total as f64;
+    histogram.iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+// Four decimal places is plenty for plotting and keeps the JSON small
+pub(crate) fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+// The highest windows, skipping any that overlap one already taken, with their first bytes
+fn top_regions(file: &ScanFile, points: &[EntropyPoint], window: usize) -> Result<Vec<EntropyRegion>, String> {
+    let mut order: Vec<&EntropyPoint> = points.iter().collect();
+    order.sort_by(|a, b| b.entropy.total_cmp(&a.entropy).then(a.offset.cmp(&b.offset)));
+
+    let mut regions: Vec<EntropyRegion> = Vec::new();
+    for point in order {
+        if regions.len() == TOP_REGIONS {
+            break;
+        }
+        if regions.iter().any(|region| point.offset < region.offset + window as u64 && region.offset < point.offset + window as u64) {
+            continue;
+        }
+
+        let preview = file.read_at(point.offset, PREVIEW_BYTES.min(window))
+            .map_err(|e| format!("Unable to read the region at offset {}: {}", point.offset, e))?;
+
+        regions.push(EntropyRegion {
+            offset: point.offset,
+            length: window as u64,
+            entropy: round(point.entropy),
+            preview: preview.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+        });
+    }
+
+    Ok(regions)
+}
+
+// Parses "path [window=4096] [step=1024]"; options trail the path
+pub(crate) fn entropy_profile_from_args(input: &str) -> Result<EntropyProfile, String> {
+    let (path, pairs) = trailing_options(input, &["window", "step"]);
+    let mut window = 4096;
+    let mut step = 1024;
+
+    for (key, value) in pairs {
+        let number = value.parse::<usize>().map_err(|_| format!("Invalid {} '{}'", key, value))?;
+        match key {
+            "window" => window = number,
+            _ => step = number
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for entropy_profile".to_string());
+    }
+
+    mercy_entropy_profile(&path, window, step)
+}
+
+// Parses "path [block=256]"
+pub(crate) fn entropy_sections_from_args(input: &str) -> Result<EntropySections, String> {
+    let (path, pairs) = trailing_options(input, &["block"]);
+    let mut block = 256;
+
+    for (key, value) in pairs {
+        block = value.parse::<usize>().map_err(|_| format!("Invalid {} '{}'", key, value))?;
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for entropy_sections".to_string());
+    }
+
+    mercy_entropy_sections(&path, block)
+}
@@ -0,0 +1,233 @@
+/*
+    Errors of the fallible dispatchers
+
+    `mercy_decode_result`, `mercy_encode_result`, `mercy_hash_result` and `mercy_hex_result`
+    return these instead of putting the error text in the output string. Their messages are
+    the ones the string dispatchers return, so switching between the two forms changes only
+    how a failure is told apart from a result.
+
+    Every variant has a string code and a number for wrappers that turn failures into exit
+    statuses or structured output (`MercyError::CODES` lists them). A code keeps its meaning
+    for the whole 1.x series: codes may be added for new variants, but none is renumbered,
+    renamed or reused. Failures reported as text by the code underneath are sorted into
+    timeout, network, quota, I/O and input errors by the same rules as the error counters.
+
+    The context names the call that failed and its input. Inputs that are the caller's own
+    content (text being hashed or encoded) are withheld and only their length is given;
+    other inputs are shortened and pass through the redaction policy, if one is set.
+*/
+
+use std::{error::Error, fmt, io};
+
+use serde::Serialize;
+
+use crate::{
+    redact,
+    stats::{self, ErrorKind}
+};
+
+// Longest input kept in a context, in characters
+const CONTEXT_INPUT: usize = 200;
+
+/// Why a fallible dispatcher call failed
+#[derive(Debug)]
+pub enum MercyError {
+    /// The input is not valid in the encoding being decoded
+    InvalidEncoding {
+        encoding: String,
+        message: String,
+        context: ErrorContext
+    },
+    /// Options or arguments the call cannot use
+    InvalidInput {
+        message: String,
+        context: ErrorContext
+    },
+    /// The dispatcher has no call by this name (or it needs a feature not compiled in)
+    UnsupportedMethod {
+        dispatcher: String,
+        method: String,
+        context: ErrorContext
+    },
+    /// A file could not be found, opened or read (`path` when it is known)
+    Io {
+        path: Option<String>,
+        source: io::Error,
+        context: ErrorContext
+    },
+    Timeout {
+        message: String,
+        context: ErrorContext
+    },
+    /// A connection could not be made or broke off
+    Network {
+        message: String,
+        context: ErrorContext
+    },
+    /// A remote service refused the request for its rate limit or quota
+    ProviderQuota {
+        message: String,
+        context: ErrorContext
+    },
+    /// Any other failure of a call, with its message
+    Failed {
+        message: String,
+        context: ErrorContext
+    }
+}
+
+/// The call a `MercyError` came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ErrorContext {
+    /// "dispatcher/call", e.g. "mercy_hash/sha2_256_file"
+    pub operation: Option<String>,
+    /// The call's input, shortened and redacted, or a note that it was withheld
+    pub input: Option<String>
+}
+
+impl MercyError {
+    /// Every code and its number, in numeric order; neither changes meaning within a major version
+    pub const CODES: [(&'static str, u8); 8] = [
+        ("E_FAILED", 1),
+        ("E_INVALID_INPUT", 2),
+        ("E_INVALID_ENCODING", 3),
+        ("E_UNSUPPORTED_METHOD", 4),
+        ("E_IO", 5),
+        ("E_TIMEOUT", 6),
+        ("E_NETWORK", 7),
+        ("E_PROVIDER_QUOTA", 8)
+    ];
+
+    pub(crate) fn invalid_encoding(encoding: &str, message: String) -> MercyError {
+        MercyError::InvalidEncoding { encoding: encoding.to_string(), message, context: ErrorContext::default() }
+    }
+
+    pub(crate) fn invalid_input(message: String) -> MercyError {
+        MercyError::InvalidInput { message, context: ErrorContext::default() }
+    }
+
+    pub(crate) fn unsupported(dispatcher: &str, method: &str) -> MercyError {
+        MercyError::UnsupportedMethod { dispatcher: dispatcher.to_string(), method: method.to_string(), context: ErrorContext::default() }
+    }
+
+    pub(crate) fn io(path: &str, source: io::Error) -> MercyError {
+        MercyError::Io { path: Some(path.to_string()), source, context: ErrorContext::default() }
+    }
+
+    /// Stable string code such as "E_TIMEOUT"
+    pub fn code(&self) -> &'static str {
+        MercyError::CODES[self.code_index()].0
+    }
+
+    /// Stable number of the code, from 1; usable as a process exit status
+    pub fn numeric_code(&self) -> u8 {
+        MercyError::CODES[self.code_index()].1
+    }
+
+    pub fn context(&self) -> &ErrorContext {
+        match self {
+            MercyError::InvalidEncoding { context, .. }
+            | MercyError::InvalidInput { context, .. }
+            | MercyError::UnsupportedMethod { context, .. }
+            | MercyError::Io { context, .. }
+            | MercyError::Timeout { context, .. }
+            | MercyError::Network { context, .. }
+            | MercyError::ProviderQuota { context, .. }
+            | MercyError::Failed { context, .. } => context
+        }
+    }
+
+    /// {"code", "numeric_code", "message", "context": {"operation", "input"}}
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "code": self.code(),
+            "numeric_code": self.numeric_code(),
+            "message": self.to_string(),
+            "context": self.context()
+        })
+    }
+
+    // Records the call the error came from; `sensitive` withholds the input itself
+    pub(crate) fn with_context(mut self, dispatcher: &str, call: &str, input: &str, sensitive: bool) -> MercyError {
+        let input = if sensitive {
+            format!("<{} bytes withheld>", input.len())
+        } else if input.chars().count() > CONTEXT_INPUT {
+            format!("{}...", input.chars().take(CONTEXT_INPUT).collect::<String>())
+        } else {
+            input.to_string()
+        };
+
+        *self.context_mut() = ErrorContext {
+            operation: Some(format!("{}/{}", dispatcher, call)),
+            input: Some(redact::redact_enabled(input))
+        };
+        self
+    }
+
+    // The position of the variant's code in CODES
+    fn code_index(&self) -> usize {
+        match self {
+            MercyError::Failed { .. } => 0,
+            MercyError::InvalidInput { .. } => 1,
+            MercyError::InvalidEncoding { .. } => 2,
+            MercyError::UnsupportedMethod { .. } => 3,
+            MercyError::Io { .. } => 4,
+            MercyError::Timeout { .. } => 5,
+            MercyError::Network { .. } => 6,
+            MercyError::ProviderQuota { .. } => 7
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut ErrorContext {
+        match self {
+            MercyError::InvalidEncoding { context, .. }
+            | MercyError::InvalidInput { context, .. }
+            | MercyError::UnsupportedMethod { context, .. }
+            | MercyError::Io { context, .. }
+            | MercyError::Timeout { context, .. }
+            | MercyError::Network { context, .. }
+            | MercyError::ProviderQuota { context, .. }
+            | MercyError::Failed { context, .. } => context
+        }
+    }
+}
+
+impl fmt::Display for MercyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MercyError::UnsupportedMethod { dispatcher, method, .. } => write!(f, "{} does not support '{}'", dispatcher, method),
+            MercyError::Io { path: Some(path), source, .. } => write!(f, "Unable to read {}: {}", path, source),
+            MercyError::Io { path: None, source, .. } => write!(f, "{}", source),
+            MercyError::InvalidEncoding { message, .. }
+            | MercyError::InvalidInput { message, .. }
+            | MercyError::Timeout { message, .. }
+            | MercyError::Network { message, .. }
+            | MercyError::ProviderQuota { message, .. }
+            | MercyError::Failed { message, .. } => write!(f, "{}", message)
+        }
+    }
+}
+
+impl Error for MercyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MercyError::Io { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
+
+impl From<String> for MercyError {
+    /// Sorts a failure message into a variant by what it describes
+    fn from(message: String) -> Self {
+        let context = ErrorContext::default();
+        match stats::classify_error(&message) {
+            ErrorKind::Timeout => MercyError::Timeout { message, context },
+            ErrorKind::Quota => MercyError::ProviderQuota { message, context },
+            ErrorKind::Network => MercyError::Network { message, context },
+            ErrorKind::Io => MercyError::Io { path: None, source: io::Error::other(message), context },
+            ErrorKind::Input => MercyError::InvalidInput { message, context },
+            ErrorKind::Other => MercyError::Failed { message, context }
+        }
+    }
+}
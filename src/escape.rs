@@ -0,0 +1,392 @@
+/*
+    Context-specific escaping for web and shell test payloads
+
+    Each encoder escapes for exactly one context, and the contexts differ in which characters
+    matter and how they are written, so none of them is a substitute for another:
+
+    - html_entity: HTML text or a quoted attribute value: only the five characters with a
+      meaning there ("&", "<", ">", '"' and "'") become character references.
+    - html_attr: an HTML attribute value, quoted or not. Every ASCII character other than
+      letters, digits and ",.-_" becomes a hex character reference, so no quote style,
+      whitespace or "=" can end the value; non-ASCII text is left for the page's UTF-8.
+    - js_string: the inside of a JavaScript string literal in either quote style, including
+      one inside a <script> block. Quotes and backslashes are backslash-escaped, controls get
+      their short escapes, and "<", ">", "&", "/" and U+2028/U+2029 are hex-escaped so the
+      literal cannot close the script element or break a line.
+    - url: one URL component (path segment or query value). Every UTF-8 byte other than
+      the RFC 3986 unreserved characters is percent-encoded; url_form also writes spaces as
+      "+", as HTML forms do.
+    - sql_string: the inside of a standard SQL single-quoted literal, where a quote is
+      written twice. Backslashes are not special in standard SQL; MySQL without
+      NO_BACKSLASH_ESCAPES also needs them doubled.
+    - cmd_unix: one complete POSIX shell word, single-quoted unless it is made only of
+      characters the shell never interprets.
+    - cmd_windows: one complete argument on a cmd.exe command line: quoted by the
+      CommandLineToArgvW rules, then with cmd.exe metacharacters caret-escaped. In a batch
+      file "%" must be written "%%" instead.
+
+    The decoders reverse their encoder and also accept the other forms of the same context
+    (decimal and named references, \u{...} escapes, double-quoted shell words), leaving
+    anything malformed as it is, except the URL decoders, which reject a "%" not followed by
+    two hex digits since a truncated escape usually means the value was cut.
+*/
+
+// Characters POSIX shells never interpret, so a word made only of them needs no quoting
+const UNIX_SAFE: &str = "@%+=:,./-_";
+
+// Characters cmd.exe interprets on a command line
+const CMD_METACHARACTERS: &str = "()%!^\"<>&|";
+
+/// Escapes the five HTML reserved characters: `&amp;`, `&lt;`, `&gt;`, `&quot;` and `&#39;`
+pub fn html_entity_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => encoded.push_str("&amp;"),
+            '<' => encoded.push_str("&lt;"),
+            '>' => encoded.push_str("&gt;"),
+            '"' => encoded.push_str("&quot;"),
+            '\'' => encoded.push_str("&#39;"),
+            _ => encoded.push(c)
+        }
+    }
+
+    encoded
+}
+
+/// Escapes text for an HTML attribute value: every ASCII character but letters, digits and ",.-_" as `&#xHH;`
+pub fn html_attr_encode(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | ',' | '.' | '-' | '_' => c.to_string(),
+            _ if c.is_ascii() => format!("&#x{:02X};", c as u32),
+            _ => c.to_string()
+        })
+        .collect()
+}
+
+/// Resolves hex, decimal and the common named character references in an attribute value
+pub fn html_attr_decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let reference = rest.find(';').filter(|end| *end <= 12).and_then(|end| {
+            let name = &rest[1..end];
+            let value = match name.strip_prefix('#') {
+                Some(number) => match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => number.parse::<u32>().ok().and_then(char::from_u32)
+                },
+                None => match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    _ => None
+                }
+            };
+            value.map(|value| (value, end + 1))
+        });
+
+        match reference {
+            Some((value, length)) => {
+                decoded.push(value);
+                rest = &rest[length..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Percent-encodes every UTF-8 byte of the text except the unreserved characters (letters, digits and "-._~"); `form` writes spaces as "+"
+pub fn url_encode(text: &str, form: bool) -> String {
+    url_encode_bytes(text.as_bytes(), form)
+}
+
+/// `url_encode` of any bytes, UTF-8 or not
+pub fn url_encode_bytes(bytes: &[u8], form: bool) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+
+    for &byte in bytes {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b' ' if form => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+
+    encoded
+}
+
+/// Resolves `%XX` escapes (either case) into bytes, and with `form` "+" into a space; a "%" without two hex digits after it is an error
+pub fn url_decode(text: &str, form: bool) -> Result<String, String> {
+    url_decode_bytes(text, form).map(|decoded| String::from_utf8_lossy(&decoded).into_owned())
+}
+
+/// `url_decode` keeping the bytes as they were escaped, UTF-8 or not
+pub fn url_decode_bytes(text: &str, form: bool) -> Result<Vec<u8>, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' => {
+                let escape = bytes.get(index + 1..index + 3)
+                    .and_then(|digits| std::str::from_utf8(digits).ok())
+                    .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+                match escape {
+                    Some(byte) => decoded.push(byte),
+                    None => {
+                        let shown: String = text[index..].chars().take(3).collect();
+                        return Err(format!("Invalid percent escape '{}' at byte {}", shown, index));
+                    }
+                }
+                index += 3;
+            },
+            b'+' if form => {
+                decoded.push(b' ');
+                index += 1;
+            },
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Escapes text for the inside of a JavaScript string literal (either quote style, safe inside `<script>`)
+pub fn js_string_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' => encoded.push_str("\\\\"),
+            '\'' => encoded.push_str("\\'"),
+            '"' => encoded.push_str("\\\""),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            '\u{8}' => encoded.push_str("\\b"),
+            '\u{c}' => encoded.push_str("\\f"),
+            '<' | '>' | '&' | '/' | '`' | '\0'..='\u{1f}' | '\u{7f}' => encoded.push_str(&format!("\\x{:02X}", c as u32)),
+            '\u{2028}' | '\u{2029}' => encoded.push_str(&format!("\\u{:04X}", c as u32)),
+            _ => encoded.push(c)
+        }
+    }
+
+    encoded
+}
+
+/// Resolves the escapes of a JavaScript string literal: short forms, `\xHH`, `\uHHHH` (with surrogate pairs) and `\u{...}`
+pub fn js_string_decode(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut decoded = String::with_capacity(text.len());
+    let mut index = 0;
+
+    // Hex digits at `start`, exactly `count` of them
+    let hex = |start: usize, count: usize| -> Option<u32> {
+        let digits: String = chars.get(start..start + count)?.iter().collect();
+        u32::from_str_radix(&digits, 16).ok().filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()))
+    };
+
+    while index < chars.len() {
+        if chars[index] != '\\' || index + 1 == chars.len() {
+            decoded.push(chars[index]);
+            index += 1;
+            continue;
+        }
+
+        let escape = chars[index + 1];
+        let (value, length) = match escape {
+            'n' => (Some('\n'), 2),
+            'r' => (Some('\r'), 2),
+            't' => (Some('\t'), 2),
+            'b' => (Some('\u{8}'), 2),
+            'f' => (Some('\u{c}'), 2),
+            'v' => (Some('\u{b}'), 2),
+            '0' if !chars.get(index + 2).is_some_and(char::is_ascii_digit) => (Some('\0'), 2),
+            'x' => (hex(index + 2, 2).and_then(char::from_u32), 4),
+            'u' if chars.get(index + 2) == Some(&'{') => {
+                match chars[index + 3..].iter().position(|c| *c == '}') {
+                    Some(end) => (hex(index + 3, end).and_then(char::from_u32), end + 4),
+                    None => (None, 0)
+                }
+            },
+            'u' => match hex(index + 2, 4) {
+                Some(high @ 0xD800..=0xDBFF) if chars.get(index + 6) == Some(&'\\') && chars.get(index + 7) == Some(&'u') => {
+                    match hex(index + 8, 4) {
+                        Some(low @ 0xDC00..=0xDFFF) => (char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)), 12),
+                        _ => (None, 0)
+                    }
+                },
+                Some(unit) => (char::from_u32(unit), 6),
+                None => (None, 0)
+            },
+            // A backslash before a line break continues the line
+            '\n' => {
+                index += 2;
+                continue;
+            },
+            other => (Some(other), 2)
+        };
+
+        match value {
+            Some(value) => {
+                decoded.push(value);
+                index += length;
+            },
+            None => {
+                decoded.push('\\');
+                index += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+/// Escapes text for the inside of a standard SQL single-quoted literal by doubling each quote
+pub fn sql_string_encode(text: &str) -> String {
+    text.replace('\'', "''")
+}
+
+/// Undoes quote doubling in the inside of a SQL string literal
+pub fn sql_string_decode(text: &str) -> String {
+    text.replace("''", "'")
+}
+
+/// Quotes text as one POSIX shell word: unchanged when every character is safe, otherwise single-quoted
+pub fn cmd_unix_encode(text: &str) -> String {
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || UNIX_SAFE.contains(c)) {
+        return text.to_string();
+    }
+
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// The value of a POSIX shell word: single quotes, double quotes (with `\` before `$`, `` ` ``, `"`, `\` and newline) and bare backslashes
+pub fn cmd_unix_decode(word: &str) -> String {
+    let mut decoded = String::with_capacity(word.len());
+    let mut chars = word.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => decoded.extend(chars.by_ref().take_while(|c| *c != '\'')),
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.peek() {
+                            Some('\n') => {
+                                chars.next();
+                            },
+                            Some('$' | '`' | '"' | '\\') => decoded.push(chars.next().unwrap_or('\\')),
+                            _ => decoded.push('\\')
+                        },
+                        _ => decoded.push(c)
+                    }
+                }
+            },
+            '\\' => match chars.next() {
+                Some('\n') | None => {},
+                Some(escaped) => decoded.push(escaped)
+            },
+            _ => decoded.push(c)
+        }
+    }
+
+    decoded
+}
+
+/// Quotes text as one argument on a cmd.exe command line: CommandLineToArgvW quoting, then cmd.exe metacharacters escaped with `^`
+pub fn cmd_windows_encode(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+
+    for c in text.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are doubled, and the quote itself escaped
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            },
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+
+    // Backslashes before the closing quote are doubled too
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+
+    quoted.chars()
+        .map(|c| if CMD_METACHARACTERS.contains(c) { format!("^{}", c) } else { c.to_string() })
+        .collect()
+}
+
+/// The value of one cmd.exe argument: `^` escapes removed, then CommandLineToArgvW quoting resolved
+pub fn cmd_windows_decode(argument: &str) -> String {
+    let mut unescaped = String::with_capacity(argument.len());
+    let mut chars = argument.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c)
+        }
+    }
+
+    let mut decoded = String::with_capacity(unescaped.len());
+    let mut backslashes = 0;
+    let mut quoted = false;
+    let mut chars = unescaped.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                decoded.push_str(&"\\".repeat(backslashes / 2));
+                if backslashes % 2 == 1 {
+                    decoded.push('"');
+                } else if quoted && chars.peek() == Some(&'"') {
+                    // "" inside quotes is a literal quote
+                    decoded.push('"');
+                    chars.next();
+                } else {
+                    quoted = !quoted;
+                }
+                backslashes = 0;
+            },
+            _ => {
+                decoded.push_str(&"\\".repeat(backslashes));
+                decoded.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+
+    decoded.push_str(&"\\".repeat(backslashes));
+    decoded
+}
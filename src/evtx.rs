@@ -0,0 +1,719 @@
+/*
+    Windows Event Log (.evtx) record extraction
+
+    Walks the 64 KiB chunks of an evtx file, validates each chunk's checksums, and renders the
+    Binary XML of every record through its (cached) template into a flat record: EventID,
+    timestamp, channel, provider and the EventData/UserData fields as key/value pairs.
+    Filters run during the walk so irrelevant records are never materialized, and chunks or
+    records that fail to parse are skipped and counted rather than aborting the file.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    rc::Rc
+};
+
+use serde::Serialize;
+
+use crate::{
+    checksum::crc32_update,
+    registry::{le16, le32, le64, utf16},
+    sink::{SinkArgs, finish_sink},
+    timefmt::{filetime_to_rfc3339, filetime_to_unix, parse_utc},
+    trailing_options
+};
+
+const FILE_HEADER_SIZE: u64 = 4096;
+const CHUNK_SIZE: usize = 65_536;
+const CHUNK_HEADER_SIZE: usize = 512;
+
+// Nesting limit for elements and embedded Binary XML in hostile files
+const MAX_DEPTH: usize = 64;
+
+/// A flattened event record
+#[derive(Debug, Clone, Serialize)]
+pub struct EvtxRecord {
+    pub record_id: u64,
+    /// System/TimeCreated, falling back to the record header write time
+    pub timestamp: String,
+    pub event_id: Option<u32>,
+    pub provider: Option<String>,
+    pub channel: Option<String>,
+    pub computer: Option<String>,
+    pub level: Option<u8>,
+    /// EventData "Data" elements by Name, or UserData leaves by element path
+    pub fields: BTreeMap<String, String>
+}
+
+/// Filters applied while parsing
+#[derive(Debug, Clone, Default)]
+pub struct EvtxOptions {
+    /// Keep only these event IDs (empty keeps all)
+    pub event_ids: Vec<u32>,
+    /// Keep records written at or after this Unix time
+    pub start: Option<i64>,
+    /// Keep records written at or before this Unix time
+    pub end: Option<i64>
+}
+
+/// Counts from an evtx run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EvtxSummary {
+    pub chunks: u64,
+    /// Chunks with a bad signature or checksum
+    pub chunks_skipped: u64,
+    pub records_emitted: u64,
+    pub records_filtered: u64,
+    /// Records whose header or Binary XML could not be parsed
+    pub records_skipped: u64
+}
+
+impl fmt::Display for EvtxSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} records emitted, {} filtered, {} corrupt records skipped; {} chunks ({} skipped)",
+            self.records_emitted, self.records_filtered, self.records_skipped, self.chunks, self.chunks_skipped
+        )
+    }
+}
+
+/// Parses `path`, calling `on_record` for each record that passes the filters
+pub fn read_evtx<P: AsRef<Path>, F: FnMut(EvtxRecord) -> Result<(), String>>(path: P, options: &EvtxOptions, mut on_record: F) -> Result<EvtxSummary, String> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header).map_err(|_| "Not an evtx file: too short".to_string())?;
+    if &header != b"ElfFile\0" {
+        return Err("Not an evtx file: missing ElfFile signature".to_string());
+    }
+
+    let mut summary = EvtxSummary::default();
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+
+    // Walk by file size rather than the header's chunk count, which is stale in dirty logs
+    file.seek(SeekFrom::Start(FILE_HEADER_SIZE)).map_err(|e| e.to_string())?;
+    loop {
+        match read_full(&mut file, &mut chunk) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => return Err(format!("Unable to read {}: {}", path.display(), e))
+        }
+
+        summary.chunks += 1;
+        if !chunk_valid(&chunk) {
+            summary.chunks_skipped += 1;
+            continue;
+        }
+
+        let mut parser = ChunkParser { data: &chunk, templates: HashMap::new() };
+        let free_offset = (le32(&chunk, 48).unwrap_or(0) as usize).min(CHUNK_SIZE);
+        let mut offset = CHUNK_HEADER_SIZE;
+
+        while offset + 28 <= free_offset {
+            if le32(&chunk, offset) != Some(0x0000_2A2A) {
+                break;
+            }
+
+            let size = le32(&chunk, offset + 4).unwrap_or(0) as usize;
+            if size < 28 || offset + size > free_offset {
+                summary.records_skipped += 1;
+                break;
+            }
+
+            let record_id = le64(&chunk, offset + 8).unwrap_or(0);
+            let written = le64(&chunk, offset + 16).unwrap_or(0);
+            let (written_secs, _) = filetime_to_unix(written);
+            let record_start = offset;
+            offset += size;
+
+            if options.start.map(|start| written_secs < start).unwrap_or(false) || options.end.map(|end| written_secs > end).unwrap_or(false) {
+                summary.records_filtered += 1;
+                continue;
+            }
+
+            let content = match parser.fragment(record_start + 24, record_start + size - 4, 0) {
+                Ok(content) => content,
+                Err(_) => {
+                    summary.records_skipped += 1;
+                    continue;
+                }
+            };
+
+            let record = flatten(record_id, written, &content);
+
+            if !options.event_ids.is_empty() && !record.event_id.map(|id| options.event_ids.contains(&id)).unwrap_or(false) {
+                summary.records_filtered += 1;
+                continue;
+            }
+
+            on_record(record)?;
+            summary.records_emitted += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Parses `path` and writes matching records to `writer` as NDJSON
+pub fn write_evtx_ndjson<P: AsRef<Path>, W: Write>(path: P, options: &EvtxOptions, writer: &mut W) -> Result<EvtxSummary, String> {
+    let path = path.as_ref();
+    let summary = read_evtx(path, options, |record| {
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| format!("Unable to write record: {}", e))
+    })?;
+
+    writer.flush().map_err(|e| format!("Unable to write record: {}", e))?;
+    Ok(summary)
+}
+
+// Fills `buffer` completely; false at a clean end of file (a partial trailing chunk is ignored)
+fn read_full(file: &mut File, buffer: &mut [u8]) -> std::io::Result<bool> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => return Ok(false),
+            read => filled += read
+        }
+    }
+    Ok(true)
+}
+
+// Signature, header CRC32 (bytes 0..120 and 128..512) and records CRC32 (512..free space)
+fn chunk_valid(chunk: &[u8]) -> bool {
+    if &chunk[..8] != b"ElfChnk\0" {
+        return false;
+    }
+
+    let header_crc = crc32_update(crc32_update(0xFFFF_FFFF, &chunk[..120]), &chunk[128..CHUNK_HEADER_SIZE]) ^ 0xFFFF_FFFF;
+    if Some(header_crc) != le32(chunk, 124) {
+        return false;
+    }
+
+    let free_offset = le32(chunk, 48).unwrap_or(0) as usize;
+    if !(CHUNK_HEADER_SIZE..=CHUNK_SIZE).contains(&free_offset) {
+        return false;
+    }
+
+    let records_crc = crc32_update(0xFFFF_FFFF, &chunk[CHUNK_HEADER_SIZE..free_offset]) ^ 0xFFFF_FFFF;
+    Some(records_crc) == le32(chunk, 52)
+}
+
+/* Binary XML */
+
+// Content of an element: text, child elements, or (inside templates) substitution slots
+#[derive(Debug, Clone)]
+enum Node {
+    Element(Element),
+    Text(String),
+    Substitution { index: usize, optional: bool }
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, Vec<Node>)>,
+    children: Vec<Node>
+}
+
+// A substitution value: rendered text, nested Binary XML, or NULL
+enum Value {
+    Null,
+    Text(String),
+    Xml(Vec<Node>)
+}
+
+struct ChunkParser<'a> {
+    data: &'a [u8],
+    // Parsed template definitions keyed by their chunk offset
+    templates: HashMap<usize, Rc<Vec<Node>>>
+}
+
+// Reading position within the chunk; name and template offsets are chunk-relative
+struct Cursor {
+    pos: usize,
+    end: usize
+}
+
+impl<'a> ChunkParser<'a> {
+    fn fragment(&mut self, start: usize, end: usize, depth: usize) -> Result<Vec<Node>, String> {
+        let mut cursor = Cursor { pos: start, end };
+        self.nodes(&mut cursor, depth, false)
+    }
+
+    fn byte(&self, cursor: &mut Cursor) -> Result<u8, String> {
+        if cursor.pos >= cursor.end {
+            return Err("Binary XML runs past its record".to_string());
+        }
+        let value = *self.data.get(cursor.pos).ok_or("Binary XML runs past the chunk")?;
+        cursor.pos += 1;
+        Ok(value)
+    }
+
+    fn u16(&self, cursor: &mut Cursor) -> Result<u16, String> {
+        let value = le16(self.data, cursor.pos).ok_or("Binary XML runs past the chunk")?;
+        cursor.pos += 2;
+        Ok(value)
+    }
+
+    fn u32(&self, cursor: &mut Cursor) -> Result<u32, String> {
+        let value = le32(self.data, cursor.pos).ok_or("Binary XML runs past the chunk")?;
+        cursor.pos += 4;
+        Ok(value)
+    }
+
+    fn peek(&self, cursor: &Cursor) -> Option<u8> {
+        if cursor.pos < cursor.end { self.data.get(cursor.pos).copied() } else { None }
+    }
+
+    // Reads a sequence of nodes until EOF, an end-element token, or the end of the data.
+    // `in_element` makes the end-element token terminate the sequence.
+    fn nodes(&mut self, cursor: &mut Cursor, depth: usize, in_element: bool) -> Result<Vec<Node>, String> {
+        if depth > MAX_DEPTH {
+            return Err("Binary XML nesting is too deep".to_string());
+        }
+
+        let mut nodes = Vec::new();
+
+        while let Some(token) = self.peek(cursor) {
+            match token & !0x40 {
+                0x00 => {
+                    cursor.pos += 1;
+                    break;
+                }
+                0x04 if in_element => {
+                    cursor.pos += 1;
+                    break;
+                }
+                0x01 => nodes.push(Node::Element(self.element(cursor, depth + 1)?)),
+                0x0C => {
+                    cursor.pos += 1;
+                    nodes.extend(self.template_instance(cursor, depth + 1)?);
+                }
+                0x0F => cursor.pos += 4,
+                0x0A | 0x0B => self.processing_instruction(cursor)?,
+                _ => nodes.push(self.content(cursor)?)
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    // Value, substitution, character/entity reference or CDATA
+    fn content(&mut self, cursor: &mut Cursor) -> Result<Node, String> {
+        let token = self.byte(cursor)?;
+
+        match token & !0x40 {
+            0x05 => {
+                let value_type = self.byte(cursor)?;
+                if value_type != 0x01 {
+                    return Err(format!("Unsupported inline value type 0x{:02x}", value_type));
+                }
+                let count = self.u16(cursor)? as usize;
+                let text = self.utf16_at(cursor.pos, count)?;
+                cursor.pos += count * 2;
+                Ok(Node::Text(text))
+            }
+            0x07 => {
+                let count = self.u16(cursor)? as usize;
+                let text = self.utf16_at(cursor.pos, count)?;
+                cursor.pos += count * 2;
+                Ok(Node::Text(text))
+            }
+            0x08 => {
+                let code = self.u16(cursor)? as u32;
+                Ok(Node::Text(char::from_u32(code).unwrap_or('\u{FFFD}').to_string()))
+            }
+            0x09 => {
+                let name = self.name(cursor)?;
+                let text = match name.as_str() {
+                    "amp" => "&",
+                    "lt" => "<",
+                    "gt" => ">",
+                    "quot" => "\"",
+                    "apos" => "'",
+                    _ => ""
+                };
+                Ok(Node::Text(text.to_string()))
+            }
+            0x0D | 0x0E => {
+                let index = self.u16(cursor)? as usize;
+                self.byte(cursor)?;
+                Ok(Node::Substitution { index, optional: token & !0x40 == 0x0E })
+            }
+            other => Err(format!("Unexpected Binary XML token 0x{:02x}", other))
+        }
+    }
+
+    fn element(&mut self, cursor: &mut Cursor, depth: usize) -> Result<Element, String> {
+        let token = self.byte(cursor)?;
+        self.u16(cursor)?;
+        self.u32(cursor)?;
+        let name = self.name(cursor)?;
+
+        let mut attributes = Vec::new();
+        if token & 0x40 != 0 {
+            self.u32(cursor)?;
+
+            while let Some(next) = self.peek(cursor) {
+                if next & !0x40 != 0x06 {
+                    break;
+                }
+                cursor.pos += 1;
+                let attribute = self.name(cursor)?;
+
+                // The value is every content token up to the next attribute or the element close
+                let mut value = Vec::new();
+                while let Some(next) = self.peek(cursor) {
+                    if matches!(next & !0x40, 0x05 | 0x07 | 0x08 | 0x09 | 0x0D | 0x0E) {
+                        value.push(self.content(cursor)?);
+                    } else {
+                        break;
+                    }
+                }
+
+                attributes.push((attribute, value));
+            }
+        }
+
+        let children = match self.byte(cursor)? {
+            0x02 => self.nodes(cursor, depth, true)?,
+            0x03 => Vec::new(),
+            other => return Err(format!("Expected element close, found 0x{:02x}", other))
+        };
+
+        Ok(Element { name, attributes, children })
+    }
+
+    // Names live at chunk offsets; when the offset is the current position the name is inline and skipped
+    fn name(&self, cursor: &mut Cursor) -> Result<String, String> {
+        let offset = self.u32(cursor)? as usize;
+        let count = le16(self.data, offset + 6).ok_or("Element name runs past the chunk")? as usize;
+        let name = self.utf16_at(offset + 8, count)?;
+
+        if offset == cursor.pos {
+            cursor.pos += 8 + count * 2 + 2;
+        }
+
+        Ok(name)
+    }
+
+    fn utf16_at(&self, offset: usize, count: usize) -> Result<String, String> {
+        let bytes = self.data.get(offset..offset + count * 2).ok_or("String runs past the chunk")?;
+        Ok(utf16(bytes))
+    }
+
+    fn processing_instruction(&self, cursor: &mut Cursor) -> Result<(), String> {
+        match self.byte(cursor)? {
+            0x0A => {
+                self.name(cursor)?;
+            }
+            _ => {
+                let count = self.u16(cursor)? as usize;
+                cursor.pos += count * 2;
+            }
+        }
+        Ok(())
+    }
+
+    fn template_instance(&mut self, cursor: &mut Cursor, depth: usize) -> Result<Vec<Node>, String> {
+        self.byte(cursor)?;
+        self.u32(cursor)?;
+        let definition = self.u32(cursor)? as usize;
+
+        let template = match self.templates.get(&definition) {
+            Some(template) => {
+                if definition == cursor.pos {
+                    let size = le32(self.data, definition + 20).ok_or("Truncated template definition")? as usize;
+                    cursor.pos += 24 + size;
+                }
+                template.clone()
+            }
+            None => {
+                let size = le32(self.data, definition + 20).ok_or("Truncated template definition")? as usize;
+                let start = definition + 24;
+                let nodes = self.fragment(start, (start + size).min(self.data.len()), depth + 1)?;
+
+                if definition == cursor.pos {
+                    cursor.pos = start + size;
+                }
+
+                let template = Rc::new(nodes);
+                self.templates.insert(definition, template.clone());
+                template
+            }
+        };
+
+        // Substitution descriptors (size, type) followed by the packed values
+        let count = self.u32(cursor)? as usize;
+        if count > 4096 {
+            return Err("Implausible substitution count".to_string());
+        }
+
+        let mut descriptors = Vec::with_capacity(count);
+        for _ in 0..count {
+            let size = self.u16(cursor)? as usize;
+            let value_type = self.byte(cursor)?;
+            self.byte(cursor)?;
+            descriptors.push((size, value_type));
+        }
+
+        let mut values = Vec::with_capacity(count);
+        for (size, value_type) in descriptors {
+            let start = cursor.pos;
+            let bytes = self.data.get(start..start + size).ok_or("Substitution value runs past the chunk")?;
+
+            values.push(match value_type {
+                0x00 => Value::Null,
+                0x21 => Value::Xml(self.fragment(start, start + size, depth + 1)?),
+                _ => Value::Text(render_value(value_type, bytes))
+            });
+            cursor.pos += size;
+        }
+
+        Ok(template.iter().filter_map(|node| substitute(node, &values)).collect())
+    }
+}
+
+// Replaces substitution slots with their values; optional slots with NULL values disappear
+fn substitute(node: &Node, values: &[Value]) -> Option<Node> {
+    match node {
+        Node::Text(text) => Some(Node::Text(text.clone())),
+        Node::Substitution { index, optional } => match values.get(*index) {
+            Some(Value::Text(text)) => Some(Node::Text(text.clone())),
+            Some(Value::Xml(nodes)) => Some(Node::Element(Element { name: String::new(), attributes: Vec::new(), children: nodes.clone() })),
+            _ if *optional => None,
+            _ => Some(Node::Text(String::new()))
+        },
+        Node::Element(element) => Some(Node::Element(Element {
+            name: element.name.clone(),
+            attributes: element.attributes.iter()
+                .map(|(name, value)| (name.clone(), value.iter().filter_map(|node| substitute(node, values)).collect()))
+                .collect(),
+            children: element.children.iter().filter_map(|node| substitute(node, values)).collect()
+        }))
+    }
+}
+
+fn render_value(value_type: u8, bytes: &[u8]) -> String {
+    // Arrays: UTF-16 strings are NUL separated, fixed-size items are packed
+    if value_type & 0x80 != 0 {
+        let base = value_type & 0x7F;
+        if base == 0x01 {
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            return units.split(|unit| *unit == 0).filter(|part| !part.is_empty()).map(String::from_utf16_lossy).collect::<Vec<_>>().join(",");
+        }
+
+        let width = match base {
+            0x03 | 0x04 => 1,
+            0x05 | 0x06 => 2,
+            0x07 | 0x08 | 0x0B | 0x0D | 0x14 => 4,
+            0x09 | 0x0A | 0x0C | 0x11 | 0x15 => 8,
+            0x0F | 0x12 => 16,
+            _ => return hex(bytes)
+        };
+        return bytes.chunks_exact(width).map(|item| render_value(base, item)).collect::<Vec<_>>().join(",");
+    }
+
+    let int = |width: usize| -> u64 {
+        let mut buffer = [0u8; 8];
+        let take = width.min(bytes.len());
+        buffer[..take].copy_from_slice(&bytes[..take]);
+        u64::from_le_bytes(buffer)
+    };
+
+    match value_type {
+        0x01 => utf16(bytes),
+        0x02 => bytes.iter().take_while(|byte| **byte != 0).map(|byte| *byte as char).collect(),
+        0x03 => (int(1) as u8 as i8).to_string(),
+        0x04 => int(1).to_string(),
+        0x05 => (int(2) as u16 as i16).to_string(),
+        0x06 => int(2).to_string(),
+        0x07 => (int(4) as u32 as i32).to_string(),
+        0x08 => int(4).to_string(),
+        0x09 => (int(8) as i64).to_string(),
+        0x0A => int(8).to_string(),
+        0x0B => f32::from_bits(int(4) as u32).to_string(),
+        0x0C => f64::from_bits(int(8)).to_string(),
+        0x0D => (int(4) != 0).to_string(),
+        0x0F if bytes.len() >= 16 => format!(
+            "{{{:08X}-{:04X}-{:04X}-{}-{}}}",
+            le32(bytes, 0).unwrap_or(0),
+            le16(bytes, 4).unwrap_or(0),
+            le16(bytes, 6).unwrap_or(0),
+            hex(&bytes[8..10]),
+            hex(&bytes[10..16])
+        ),
+        0x10 => format!("0x{:x}", int(bytes.len().min(8))),
+        0x11 => filetime_to_rfc3339(int(8)).unwrap_or_default(),
+        0x12 if bytes.len() >= 16 => {
+            let field = |index: usize| le16(bytes, index * 2).unwrap_or(0);
+            format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", field(0), field(1), field(3), field(4), field(5), field(6), field(7))
+        }
+        0x13 => render_sid(bytes),
+        0x14 => format!("0x{:08x}", int(4)),
+        0x15 => format!("0x{:016x}", int(8)),
+        _ => hex(bytes)
+    }
+}
+
+fn render_sid(bytes: &[u8]) -> String {
+    if bytes.len() < 8 {
+        return hex(bytes);
+    }
+
+    let authority = bytes[2..8].iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+    let mut sid = format!("S-{}-{}", bytes[0], authority);
+
+    for index in 0..bytes[1] as usize {
+        match le32(bytes, 8 + index * 4) {
+            Some(part) => sid.push_str(&format!("-{}", part)),
+            None => break
+        }
+    }
+
+    sid
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/* Flattening */
+
+fn text_of(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(value) => text.push_str(value),
+            Node::Element(element) if element.name.is_empty() => text.push_str(&text_of(&element.children)),
+            _ => {}
+        }
+    }
+    text
+}
+
+// Child elements, looking through the unnamed wrappers left by Binary XML substitutions
+fn elements(nodes: &[Node]) -> Vec<&Element> {
+    let mut found = Vec::new();
+    for node in nodes {
+        if let Node::Element(element) = node {
+            if element.name.is_empty() {
+                found.extend(elements(&element.children));
+            } else {
+                found.push(element);
+            }
+        }
+    }
+    found
+}
+
+fn child<'e>(element: &'e Element, name: &str) -> Option<&'e Element> {
+    elements(&element.children).into_iter().find(|child| child.name == name)
+}
+
+fn attribute(element: &Element, name: &str) -> Option<String> {
+    element.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| text_of(value))
+}
+
+fn flatten(record_id: u64, written: u64, content: &[Node]) -> EvtxRecord {
+    let mut record = EvtxRecord {
+        record_id,
+        timestamp: filetime_to_rfc3339(written).unwrap_or_default(),
+        event_id: None,
+        provider: None,
+        channel: None,
+        computer: None,
+        level: None,
+        fields: BTreeMap::new()
+    };
+
+    let event = match elements(content).into_iter().find(|element| element.name == "Event") {
+        Some(event) => event,
+        None => return record
+    };
+
+    if let Some(system) = child(event, "System") {
+        record.provider = child(system, "Provider").and_then(|provider| attribute(provider, "Name"));
+        record.event_id = child(system, "EventID").and_then(|id| text_of(&id.children).trim().parse().ok());
+        record.level = child(system, "Level").and_then(|level| text_of(&level.children).trim().parse().ok());
+        record.channel = child(system, "Channel").map(|channel| text_of(&channel.children)).filter(|text| !text.is_empty());
+        record.computer = child(system, "Computer").map(|computer| text_of(&computer.children)).filter(|text| !text.is_empty());
+
+        if let Some(created) = child(system, "TimeCreated").and_then(|time| attribute(time, "SystemTime")).filter(|time| !time.is_empty()) {
+            record.timestamp = created;
+        }
+    }
+
+    if let Some(data) = child(event, "EventData") {
+        for (index, item) in elements(&data.children).into_iter().enumerate() {
+            let key = attribute(item, "Name").filter(|name| !name.is_empty()).unwrap_or_else(|| format!("{}_{}", item.name, index));
+            record.fields.insert(key, text_of(&item.children));
+        }
+    }
+
+    if let Some(user_data) = child(event, "UserData") {
+        flatten_leaves(&user_data.children, "", &mut record.fields);
+    }
+
+    record
+}
+
+fn flatten_leaves(nodes: &[Node], prefix: &str, fields: &mut BTreeMap<String, String>) {
+    for element in elements(nodes) {
+        let key = if prefix.is_empty() { element.name.clone() } else { format!("{}.{}", prefix, element.name) };
+        let children = elements(&element.children);
+
+        if children.is_empty() {
+            fields.insert(key, text_of(&element.children));
+        } else {
+            flatten_leaves(&element.children, &key, fields);
+        }
+    }
+}
+
+// Parses "path [ids=4624,4625] [start=...] [end=...] [out=file] [backpressure=block] [retries=5]" and returns NDJSON ending in a summary line
+pub(crate) fn evtx_from_args(input: &str) -> Result<String, String> {
+    let (path, pairs) = trailing_options(input, &["ids", "start", "end", "out", "backpressure", "retries"]);
+    let mut options = EvtxOptions::default();
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        match key {
+            "ids" => {
+                options.event_ids = value.split(',')
+                    .filter(|id| !id.is_empty())
+                    .map(|id| id.trim().parse::<u32>().map_err(|_| format!("Invalid event ID '{}'", id)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "start" => options.start = Some(parse_utc(value)?),
+            _ => options.end = Some(parse_utc(value)?)
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for evtx".to_string());
+    }
+
+    if let Some(mut output) = sink.open()? {
+        let summary = write_evtx_ndjson(Path::new(&path), &options, &mut output)?;
+        return finish_sink(output, &summary);
+    }
+
+    let mut output = Vec::new();
+    let summary = write_evtx_ndjson(Path::new(&path), &options, &mut output)?;
+
+    // Close the stream with the counts so skipped chunks and records are not silently lost
+    let line = serde_json::json!({ "summary": summary });
+    output.extend_from_slice(line.to_string().as_bytes());
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
@@ -0,0 +1,680 @@
+/*
+    EXIF and container metadata of JPEG, PNG and TIFF images
+
+    EXIF is a TIFF structure wherever it lives: a JPEG APP1 segment, a PNG eXIf chunk or the
+    file itself. Its IFD0, Exif and GPS directories are walked with every offset bounds-checked
+    and each directory visited once, so a corrupt or hostile block ends up as a warning and the
+    rest of the file is still reported. PNG text chunks (tEXt, zTXt, iTXt), JPEG comments and
+    XMP packets are collected alongside. For JPEG and PNG the container is walked to its end
+    marker, and anything stored after it is reported with its size and SHA-256.
+*/
+
+use std::{collections::{BTreeMap, HashSet}, fmt, fs, path::Path};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{checksum::crc32_update, inflate::inflate, paths::path_to_string};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Largest file read, and the most a compressed PNG text chunk may expand to
+const MAX_FILE: u64 = 1024 * 1024 * 1024;
+const MAX_TEXT: usize = 1024 * 1024;
+
+// Entries read from one IFD; real images use a few dozen
+const MAX_ENTRIES: usize = 1000;
+
+// UNDEFINED values up to this length are shown as hex, longer ones by size
+const INLINE_BYTES: usize = 32;
+
+/// GPS position of an image, in decimal degrees
+#[derive(Debug, Clone, Serialize)]
+pub struct GpsPosition {
+    /// Positive north
+    pub latitude: f64,
+    /// Positive east
+    pub longitude: f64,
+    /// Metres, negative below sea level
+    pub altitude: Option<f64>,
+    /// UTC time of the fix (RFC 3339)
+    pub timestamp: Option<String>
+}
+
+/// Bytes stored after an image's end marker
+#[derive(Debug, Clone, Serialize)]
+pub struct TrailingData {
+    pub offset: u64,
+    pub size: u64,
+    pub sha256: String
+}
+
+/// Camera, time, location and text metadata of an image, with any data appended after it
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageMetadata {
+    pub path: String,
+    /// "jpeg", "png" or "tiff"
+    pub format: String,
+    pub size: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub software: Option<String>,
+    /// When the file was last changed (EXIF DateTime, or PNG tIME), as RFC 3339 with the offset when recorded
+    pub modified: Option<String>,
+    /// When the picture was taken (DateTimeOriginal)
+    pub taken: Option<String>,
+    /// When the picture was digitized (DateTimeDigitized)
+    pub digitized: Option<String>,
+    pub gps: Option<GpsPosition>,
+    /// Every EXIF tag read, keyed "IFD0.Make", "Exif.FNumber", "GPS.GPSLatitude" (unknown tags by hex ID)
+    pub tags: BTreeMap<String, String>,
+    /// PNG text chunks by keyword, JPEG comments and XMP packets
+    pub text: BTreeMap<String, String>,
+    /// Not checked for TIFF, whose data can sit anywhere in the file
+    pub trailing_data: Option<TrailingData>,
+    /// Problems skipped over while reading
+    pub warnings: Vec<String>
+}
+
+impl fmt::Display for ImageMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}, {} bytes", self.path, self.format.to_uppercase(), self.size)?;
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            write!(f, ", {}x{}", width, height)?;
+        }
+        writeln!(f, ")")?;
+
+        for (label, value) in [("Make", &self.make), ("Model", &self.model), ("Software", &self.software), ("Modified", &self.modified), ("Taken", &self.taken), ("Digitized", &self.digitized)] {
+            if let Some(value) = value {
+                writeln!(f, "{}: {}", label, value)?;
+            }
+        }
+
+        if let Some(gps) = &self.gps {
+            write!(f, "GPS: {}, {}", gps.latitude, gps.longitude)?;
+            if let Some(altitude) = gps.altitude {
+                write!(f, ", {} m", altitude)?;
+            }
+            if let Some(timestamp) = &gps.timestamp {
+                write!(f, " at {}", timestamp)?;
+            }
+            writeln!(f)?;
+        }
+
+        match &self.trailing_data {
+            Some(trailing) => writeln!(f, "Trailing data: {} bytes at offset {} (sha256 {})", trailing.size, trailing.offset, trailing.sha256)?,
+            None if self.format != "tiff" => writeln!(f, "Trailing data: none")?,
+            None => {}
+        }
+
+        if !self.tags.is_empty() {
+            writeln!(f, "\nTags:")?;
+            for (name, value) in &self.tags {
+                writeln!(f, "  {}: {}", name, value)?;
+            }
+        }
+
+        if !self.text.is_empty() {
+            writeln!(f, "\nText:")?;
+            for (keyword, value) in &self.text {
+                writeln!(f, "  {}: {}", keyword, value)?;
+            }
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f, "\nWarnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "  {}", warning)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the metadata of a JPEG, PNG or TIFF file, chosen by its signature
+pub fn mercy_exif<P: AsRef<Path>>(path: P) -> Result<ImageMetadata, String> {
+    let path = path.as_ref();
+    let size = fs::metadata(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?.len();
+    if size > MAX_FILE {
+        return Err(format!("{} is larger than the {} byte limit", path.display(), MAX_FILE));
+    }
+    let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    let format = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "jpeg"
+    } else if data.starts_with(&PNG_SIGNATURE) {
+        "png"
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        "tiff"
+    } else {
+        return Err(format!("{} is not a JPEG, PNG or TIFF image", path.display()));
+    };
+
+    let mut metadata = empty_metadata(&path_to_string(path), format, data.len());
+
+    let mut exif = Exif::default();
+    let end = match format {
+        "jpeg" => read_jpeg(&data, &mut metadata, &mut exif),
+        "png" => read_png(&data, &mut metadata, &mut exif),
+        _ => {
+            exif.read(&data, &mut metadata.warnings);
+            None
+        }
+    };
+
+    exif.apply(&mut metadata);
+
+    if let Some(end) = end {
+        if end < data.len() {
+            metadata.trailing_data = Some(TrailingData {
+                offset: end as u64,
+                size: (data.len() - end) as u64,
+                sha256: format!("{:x}", Sha256::digest(&data[end..]))
+            });
+        }
+    }
+
+    Ok(metadata)
+}
+
+/* Containers */
+
+/// Where a JPEG or PNG image ends, or None when its end marker is missing or it is neither
+#[cfg(feature = "image")]
+pub(crate) fn image_end(data: &[u8]) -> Option<usize> {
+    let mut metadata = empty_metadata("", "", data.len());
+    let mut exif = Exif::default();
+
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        read_jpeg(data, &mut metadata, &mut exif)
+    } else if data.starts_with(&PNG_SIGNATURE) {
+        read_png(data, &mut metadata, &mut exif)
+    } else {
+        None
+    }
+}
+
+fn empty_metadata(path: &str, format: &str, size: usize) -> ImageMetadata {
+    ImageMetadata {
+        path: path.to_string(),
+        format: format.to_string(),
+        size: size as u64,
+        width: None,
+        height: None,
+        make: None,
+        model: None,
+        software: None,
+        modified: None,
+        taken: None,
+        digitized: None,
+        gps: None,
+        tags: BTreeMap::new(),
+        text: BTreeMap::new(),
+        trailing_data: None,
+        warnings: Vec::new()
+    }
+}
+
+// Segments up to each scan, the entropy-coded data to the next real marker, and so on to EOI;
+// returns where the image ends
+fn read_jpeg(data: &[u8], metadata: &mut ImageMetadata, exif: &mut Exif) -> Option<usize> {
+    let mut position = 2;
+
+    while position + 2 <= data.len() {
+        if data[position] != 0xFF {
+            metadata.warnings.push(format!("Corrupt JPEG marker at offset {}", position));
+            return None;
+        }
+
+        let marker = data[position + 1];
+        match marker {
+            0xFF => {
+                position += 1;
+                continue;
+            },
+            0xD9 => return Some(position + 2),
+            0x01 | 0xD0..=0xD7 => {
+                position += 2;
+                continue;
+            },
+            _ => {}
+        }
+
+        let length = data.get(position + 2..position + 4).map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize);
+        let Some(body) = data.get(position + 4..position + 2 + length).filter(|_| length >= 2) else {
+            metadata.warnings.push(format!("JPEG segment 0x{:02X} at offset {} runs past the end of the file", marker, position));
+            return None;
+        };
+
+        match marker {
+            0xE1 if body.starts_with(b"Exif\0\0") => exif.read(&body[6..], &mut metadata.warnings),
+            0xE1 if body.starts_with(b"http://ns.adobe.com/xap/1.0/\0") => {
+                add_text(&mut metadata.text, "XMP", &String::from_utf8_lossy(&body[29..]));
+            },
+            0xFE => add_text(&mut metadata.text, "Comment", &String::from_utf8_lossy(body)),
+            0xC0..=0xCF if marker != 0xC4 && marker != 0xC8 && marker != 0xCC && body.len() >= 5 => {
+                metadata.height = Some(u16::from_be_bytes([body[1], body[2]]) as u32);
+                metadata.width = Some(u16::from_be_bytes([body[3], body[4]]) as u32);
+            },
+            _ => {}
+        }
+        position += 2 + length;
+
+        // Stuffed 0xFF00 and restart markers belong to the scan
+        if marker == 0xDA {
+            match (position..data.len().saturating_sub(1)).find(|i| data[*i] == 0xFF && data[i + 1] != 0x00 && !(0xD0..=0xD7).contains(&data[i + 1])) {
+                Some(next) => position = next,
+                None => break
+            }
+        }
+    }
+
+    metadata.warnings.push("JPEG has no end of image marker (truncated)".to_string());
+    None
+}
+
+// Chunks up to IEND, collecting text and EXIF; returns where the image ends
+fn read_png(data: &[u8], metadata: &mut ImageMetadata, exif: &mut Exif) -> Option<usize> {
+    let mut position = PNG_SIGNATURE.len();
+
+    while position + 12 <= data.len() {
+        let length = u32::from_be_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]]) as usize;
+        let kind = &data[position + 4..position + 8];
+        let Some(body) = data.get(position + 8..position + 8 + length) else {
+            metadata.warnings.push(format!("PNG chunk {} at offset {} runs past the end of the file", String::from_utf8_lossy(kind), position));
+            return None;
+        };
+        let Some(stored) = data.get(position + 8 + length..position + 12 + length) else {
+            metadata.warnings.push(format!("PNG chunk {} at offset {} has no CRC", String::from_utf8_lossy(kind), position));
+            return None;
+        };
+        let name = String::from_utf8_lossy(kind).to_string();
+
+        if crc32_update(crc32_update(0xFFFF_FFFF, kind), body) ^ 0xFFFF_FFFF != u32::from_be_bytes([stored[0], stored[1], stored[2], stored[3]]) {
+            metadata.warnings.push(format!("PNG chunk {} at offset {} has a bad CRC", name, position));
+        }
+
+        match kind {
+            b"IHDR" if body.len() >= 8 => {
+                metadata.width = Some(u32::from_be_bytes([body[0], body[1], body[2], body[3]]));
+                metadata.height = Some(u32::from_be_bytes([body[4], body[5], body[6], body[7]]));
+            },
+            b"eXIf" => exif.read(body, &mut metadata.warnings),
+            b"tIME" if body.len() == 7 => {
+                metadata.modified = Some(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", u16::from_be_bytes([body[0], body[1]]), body[2], body[3], body[4], body[5], body[6]));
+            },
+            b"tEXt" | b"zTXt" | b"iTXt" => match png_text(kind, body) {
+                Ok((keyword, text)) => add_text(&mut metadata.text, &keyword, &text),
+                Err(e) => metadata.warnings.push(format!("PNG chunk {} at offset {}: {}", name, position, e))
+            },
+            _ => {}
+        }
+
+        position += 12 + length;
+        if kind == b"IEND" {
+            return Some(position);
+        }
+    }
+
+    metadata.warnings.push("PNG has no IEND chunk (truncated)".to_string());
+    None
+}
+
+// Keyword and text of a tEXt (Latin-1), zTXt (compressed Latin-1) or iTXt (UTF-8, optionally compressed) chunk
+fn png_text(kind: &[u8], body: &[u8]) -> Result<(String, String), String> {
+    let split = body.iter().position(|byte| *byte == 0).ok_or("no keyword terminator")?;
+    let keyword: String = body[..split].iter().map(|byte| *byte as char).collect();
+    let rest = &body[split + 1..];
+
+    let text = match kind {
+        b"tEXt" => rest.iter().map(|byte| *byte as char).collect(),
+        b"zTXt" => zlib(rest.get(1..).ok_or("missing compression method")?)?.iter().map(|byte| *byte as char).collect(),
+        _ => {
+            let (compressed, rest) = (rest.first().copied().ok_or("missing compression flag")?, rest.get(2..).ok_or("missing compression method")?);
+            let language_end = rest.iter().position(|byte| *byte == 0).ok_or("no language terminator")?;
+            let rest = &rest[language_end + 1..];
+            let translated_end = rest.iter().position(|byte| *byte == 0).ok_or("no translated keyword terminator")?;
+            let text = &rest[translated_end + 1..];
+            if compressed == 1 {
+                String::from_utf8_lossy(&zlib(text)?).to_string()
+            } else {
+                String::from_utf8_lossy(text).to_string()
+            }
+        }
+    };
+
+    Ok((keyword, text))
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2 || data[0] & 0x0F != 8 {
+        return Err("text is not zlib compressed".to_string());
+    }
+    inflate(&data[2..], MAX_TEXT)
+}
+
+// Repeated keywords (several comments, say) are kept on separate lines
+fn add_text(text: &mut BTreeMap<String, String>, keyword: &str, value: &str) {
+    let value = value.trim_end_matches('\0');
+    text.entry(keyword.to_string())
+        .and_modify(|existing| {
+            existing.push('\n');
+            existing.push_str(value);
+        })
+        .or_insert_with(|| value.to_string());
+}
+
+/* EXIF */
+
+#[derive(Debug, Clone)]
+enum TagValue {
+    Text(String),
+    Numbers(Vec<f64>),
+    Bytes(Vec<u8>)
+}
+
+impl TagValue {
+    fn text(&self) -> Option<&str> {
+        match self {
+            TagValue::Text(text) => Some(text),
+            _ => None
+        }
+    }
+
+    fn numbers(&self) -> Option<&[f64]> {
+        match self {
+            TagValue::Numbers(numbers) => Some(numbers),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for TagValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagValue::Text(text) => f.write_str(text),
+            TagValue::Numbers(numbers) => f.write_str(&numbers.iter().map(|number| {
+                if number.fract() == 0.0 { format!("{}", number) } else { format!("{}", (number * 1e6).round() / 1e6) }
+            }).collect::<Vec<_>>().join(", ")),
+            TagValue::Bytes(bytes) if bytes.len() <= INLINE_BYTES => f.write_str(&bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+            TagValue::Bytes(bytes) => write!(f, "<{} bytes>", bytes.len())
+        }
+    }
+}
+
+// Tags of the three directories, merged across every EXIF block in the file
+#[derive(Default)]
+struct Exif {
+    primary: BTreeMap<u16, TagValue>,
+    exif: BTreeMap<u16, TagValue>,
+    gps: BTreeMap<u16, TagValue>
+}
+
+// A TIFF structure: its bytes and byte order
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool
+}
+
+impl Tiff<'_> {
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = [*self.data.get(offset)?, *self.data.get(offset + 1)?];
+        Some(if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    // The entries of the IFD at `offset` and the offset of the next IFD
+    fn directory(&self, offset: usize, name: &str, warnings: &mut Vec<String>) -> (Vec<(u16, TagValue)>, usize) {
+        let Some(count) = self.u16(offset) else {
+            warnings.push(format!("{} directory at offset {} is outside the EXIF block", name, offset));
+            return (Vec::new(), 0);
+        };
+        if count as usize > MAX_ENTRIES {
+            warnings.push(format!("{} directory claims {} entries; skipped", name, count));
+            return (Vec::new(), 0);
+        }
+
+        let mut entries = Vec::new();
+        for index in 0..count as usize {
+            let entry = offset + 2 + index * 12;
+            let (Some(tag), Some(kind), Some(values)) = (self.u16(entry), self.u16(entry + 2), self.u32(entry + 4)) else {
+                warnings.push(format!("{} directory is cut short after {} of {} entries", name, index, count));
+                return (entries, 0);
+            };
+
+            match self.value(entry, kind, values as usize) {
+                Some(TagValue::Bytes(bytes)) if name == "Exif" => entries.push((tag, self.exif_text(tag, bytes))),
+                Some(value) => entries.push((tag, value)),
+                None => warnings.push(format!("{} tag 0x{:04X} has an unreadable value; skipped", name, tag))
+            }
+        }
+
+        (entries, self.u32(offset + 2 + count as usize * 12).unwrap_or(0) as usize)
+    }
+
+    // Version tags hold ASCII digits, and UserComment text behind an 8-byte character set prefix
+    fn exif_text(&self, tag: u16, bytes: Vec<u8>) -> TagValue {
+        match tag {
+            0x9000 | 0xA000 if bytes.iter().all(u8::is_ascii_digit) => TagValue::Text(String::from_utf8_lossy(&bytes).to_string()),
+            0x9286 if bytes.len() >= 8 => {
+                let text = &bytes[8..];
+                let decoded = match &bytes[..8] {
+                    b"UNICODE\0" => {
+                        let units: Vec<u16> = text.chunks_exact(2)
+                            .map(|pair| if self.big_endian { u16::from_be_bytes([pair[0], pair[1]]) } else { u16::from_le_bytes([pair[0], pair[1]]) })
+                            .collect();
+                        String::from_utf16_lossy(&units)
+                    },
+                    _ => String::from_utf8_lossy(text).to_string()
+                };
+                TagValue::Text(decoded.trim_end_matches(['\0', ' ']).to_string())
+            },
+            _ => TagValue::Bytes(bytes)
+        }
+    }
+
+    fn value(&self, entry: usize, kind: u16, count: usize) -> Option<TagValue> {
+        let width: usize = match kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None
+        };
+        let length = width.checked_mul(count)?;
+        let start = if length <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+        let bytes = self.data.get(start..start.checked_add(length)?)?;
+
+        Some(match kind {
+            2 => TagValue::Text(String::from_utf8_lossy(bytes).trim_end_matches('\0').trim().to_string()),
+            1 | 7 => TagValue::Bytes(bytes.to_vec()),
+            _ => TagValue::Numbers((0..count).filter_map(|index| {
+                let at = start + index * width;
+                match kind {
+                    3 => self.u16(at).map(|value| value as f64),
+                    8 => self.u16(at).map(|value| value as i16 as f64),
+                    4 => self.u32(at).map(|value| value as f64),
+                    9 => self.u32(at).map(|value| value as i32 as f64),
+                    11 => self.u32(at).map(|value| f32::from_bits(value) as f64),
+                    5 => match (self.u32(at)?, self.u32(at + 4)?) {
+                        (_, 0) => None,
+                        (numerator, denominator) => Some(numerator as f64 / denominator as f64)
+                    },
+                    10 => match (self.u32(at)? as i32, self.u32(at + 4)? as i32) {
+                        (_, 0) => None,
+                        (numerator, denominator) => Some(numerator as f64 / denominator as f64)
+                    },
+                    6 => self.data.get(at).map(|value| *value as i8 as f64),
+                    _ => {
+                        let high = self.u32(at)? as u64;
+                        let low = self.u32(at + 4)? as u64;
+                        Some(f64::from_bits(if self.big_endian { (high << 32) | low } else { (low << 32) | high }))
+                    }
+                }
+            }).collect())
+        })
+    }
+}
+
+impl Exif {
+    // Walks the IFD0 chain with its Exif and GPS sub-directories, each offset once
+    fn read(&mut self, data: &[u8], warnings: &mut Vec<String>) {
+        let big_endian = match data.get(..4) {
+            Some(b"II*\0") => false,
+            Some(b"MM\0*") => true,
+            _ => {
+                warnings.push("EXIF block has no TIFF header; skipped".to_string());
+                return;
+            }
+        };
+        let tiff = Tiff { data, big_endian };
+
+        let mut visited = HashSet::new();
+        let mut next = tiff.u32(4).unwrap_or(0) as usize;
+        let mut first = true;
+        while next != 0 && visited.insert(next) {
+            let (entries, following) = tiff.directory(next, if first { "IFD0" } else { "IFD1" }, warnings);
+            for (tag, value) in entries {
+                let pointer = value.numbers().and_then(|numbers| numbers.first()).map(|offset| *offset as usize);
+                match (tag, pointer) {
+                    (0x8769, Some(offset)) | (0x8825, Some(offset)) => {
+                        if !visited.insert(offset) {
+                            warnings.push(format!("EXIF directory at offset {} is referenced twice; skipped", offset));
+                            continue;
+                        }
+                        let (name, target) = if tag == 0x8769 { ("Exif", &mut self.exif) } else { ("GPS", &mut self.gps) };
+                        let (entries, _) = tiff.directory(offset, name, warnings);
+                        target.extend(entries);
+                    },
+                    // Only IFD0 describes the image; IFD1 is the thumbnail
+                    _ if first => {
+                        self.primary.insert(tag, value);
+                    },
+                    _ => {}
+                }
+            }
+
+            first = false;
+            next = following;
+        }
+    }
+
+    fn apply(self, metadata: &mut ImageMetadata) {
+        let text = |tags: &BTreeMap<u16, TagValue>, tag: u16| tags.get(&tag).and_then(TagValue::text).filter(|text| !text.is_empty()).map(str::to_string);
+        let number = |tags: &BTreeMap<u16, TagValue>, tag: u16| tags.get(&tag).and_then(TagValue::numbers).and_then(|numbers| numbers.first().copied());
+
+        metadata.make = text(&self.primary, 0x010F);
+        metadata.model = text(&self.primary, 0x0110);
+        metadata.software = text(&self.primary, 0x0131);
+        if metadata.width.is_none() {
+            metadata.width = number(&self.primary, 0x0100).or_else(|| number(&self.exif, 0xA002)).map(|width| width as u32);
+            metadata.height = number(&self.primary, 0x0101).or_else(|| number(&self.exif, 0xA003)).map(|height| height as u32);
+        }
+
+        if let Some(modified) = text(&self.primary, 0x0132).and_then(|date| exif_date(&date, text(&self.exif, 0x9010).as_deref())) {
+            metadata.modified = Some(modified);
+        }
+        metadata.taken = text(&self.exif, 0x9003).and_then(|date| exif_date(&date, text(&self.exif, 0x9011).as_deref()));
+        metadata.digitized = text(&self.exif, 0x9004).and_then(|date| exif_date(&date, text(&self.exif, 0x9012).as_deref()));
+
+        metadata.gps = self.position(&mut metadata.warnings);
+
+        for (group, tags, names) in [("IFD0", &self.primary, IFD0_TAGS), ("Exif", &self.exif, EXIF_TAGS), ("GPS", &self.gps, GPS_TAGS)] {
+            for (tag, value) in tags {
+                let name = names.iter().find(|(id, _)| id == tag).map(|(_, name)| name.to_string()).unwrap_or_else(|| format!("0x{:04X}", tag));
+                metadata.tags.insert(format!("{}.{}", group, name), value.to_string());
+            }
+        }
+    }
+
+    // Degrees, minutes and seconds with their hemisphere references, as signed decimal degrees
+    fn position(&self, warnings: &mut Vec<String>) -> Option<GpsPosition> {
+        let coordinate = |value: u16, reference: u16, negative: &str| -> Option<f64> {
+            let parts = self.gps.get(&value)?.numbers()?;
+            let degrees = parts.first()? + parts.get(1).unwrap_or(&0.0) / 60.0 + parts.get(2).unwrap_or(&0.0) / 3600.0;
+            let sign = if self.gps.get(&reference).and_then(TagValue::text).is_some_and(|text| text.eq_ignore_ascii_case(negative)) { -1.0 } else { 1.0 };
+            Some((sign * degrees * 1e7).round() / 1e7)
+        };
+
+        let (latitude, longitude) = (coordinate(2, 1, "S"), coordinate(4, 3, "W"));
+        let (Some(latitude), Some(longitude)) = (latitude, longitude) else {
+            if latitude.is_some() || longitude.is_some() {
+                warnings.push("GPS position has only one coordinate; skipped".to_string());
+            }
+            return None;
+        };
+        if latitude.abs() > 90.0 || longitude.abs() > 180.0 || !latitude.is_finite() || !longitude.is_finite() {
+            warnings.push(format!("GPS position {}, {} is out of range; skipped", latitude, longitude));
+            return None;
+        }
+
+        let below_sea = matches!(self.gps.get(&5), Some(TagValue::Bytes(bytes)) if bytes.first() == Some(&1));
+        let altitude = self.gps.get(&6).and_then(TagValue::numbers).and_then(|numbers| numbers.first().copied())
+            .map(|altitude| if below_sea { -altitude } else { altitude });
+
+        let timestamp = match (self.gps.get(&0x1D).and_then(TagValue::text), self.gps.get(&7).and_then(TagValue::numbers)) {
+            (Some(date), Some([hours, minutes, seconds])) => {
+                let date = date.replace(':', "-");
+                (date.len() == 10).then(|| format!("{}T{:02}:{:02}:{:02}Z", date, *hours as u32, *minutes as u32, *seconds as u32))
+            },
+            _ => None
+        };
+
+        Some(GpsPosition { latitude, longitude, altitude, timestamp })
+    }
+}
+
+// "2024:03:01 12:00:00" as "2024-03-01T12:00:00", with the recorded offset when there is one;
+// blank or malformed dates (often all spaces or zeros) give nothing
+fn exif_date(date: &str, offset: Option<&str>) -> Option<String> {
+    let bytes = date.as_bytes();
+    let shaped = bytes.len() >= 19 && [4, 7].iter().all(|i| bytes[*i] == b':') && bytes[10] == b' ' && [13, 16].iter().all(|i| bytes[*i] == b':');
+    let digits = [0..4, 5..7, 8..10, 11..13, 14..16, 17..19].iter().all(|range| bytes[range.clone()].iter().all(u8::is_ascii_digit));
+    if !shaped || !digits || date.starts_with("0000") {
+        return None;
+    }
+
+    let offset = offset.filter(|offset| offset.len() == 6 && (offset.starts_with('+') || offset.starts_with('-'))).unwrap_or("");
+    Some(format!("{}-{}-{}T{}{}", &date[0..4], &date[5..7], &date[8..10], &date[11..19], offset))
+}
+
+const IFD0_TAGS: &[(u16, &str)] = &[
+    (0x0100, "ImageWidth"), (0x0101, "ImageLength"), (0x0102, "BitsPerSample"), (0x0103, "Compression"),
+    (0x0106, "PhotometricInterpretation"), (0x010E, "ImageDescription"), (0x010F, "Make"), (0x0110, "Model"),
+    (0x0111, "StripOffsets"), (0x0112, "Orientation"), (0x0115, "SamplesPerPixel"), (0x0116, "RowsPerStrip"),
+    (0x0117, "StripByteCounts"), (0x011A, "XResolution"), (0x011B, "YResolution"), (0x011C, "PlanarConfiguration"),
+    (0x0128, "ResolutionUnit"), (0x0131, "Software"), (0x0132, "DateTime"), (0x013B, "Artist"),
+    (0x013C, "HostComputer"), (0x0213, "YCbCrPositioning"), (0x8298, "Copyright"), (0xA430, "CameraOwnerName"),
+    (0xA431, "BodySerialNumber"), (0xC4A5, "PrintImageMatching")
+];
+
+const EXIF_TAGS: &[(u16, &str)] = &[
+    (0x829A, "ExposureTime"), (0x829D, "FNumber"), (0x8822, "ExposureProgram"), (0x8827, "ISOSpeedRatings"),
+    (0x8830, "SensitivityType"), (0x9000, "ExifVersion"), (0x9003, "DateTimeOriginal"), (0x9004, "DateTimeDigitized"),
+    (0x9010, "OffsetTime"), (0x9011, "OffsetTimeOriginal"), (0x9012, "OffsetTimeDigitized"), (0x9101, "ComponentsConfiguration"),
+    (0x9201, "ShutterSpeedValue"), (0x9202, "ApertureValue"), (0x9203, "BrightnessValue"), (0x9204, "ExposureBiasValue"),
+    (0x9205, "MaxApertureValue"), (0x9207, "MeteringMode"), (0x9208, "LightSource"), (0x9209, "Flash"),
+    (0x920A, "FocalLength"), (0x9214, "SubjectArea"), (0x927C, "MakerNote"), (0x9286, "UserComment"),
+    (0x9290, "SubSecTime"), (0x9291, "SubSecTimeOriginal"), (0x9292, "SubSecTimeDigitized"), (0xA000, "FlashpixVersion"),
+    (0xA001, "ColorSpace"), (0xA002, "PixelXDimension"), (0xA003, "PixelYDimension"), (0xA005, "InteroperabilityIFD"),
+    (0xA217, "SensingMethod"), (0xA301, "SceneType"), (0xA401, "CustomRendered"), (0xA402, "ExposureMode"),
+    (0xA403, "WhiteBalance"), (0xA404, "DigitalZoomRatio"), (0xA405, "FocalLengthIn35mmFilm"), (0xA406, "SceneCaptureType"),
+    (0xA420, "ImageUniqueID"), (0xA430, "CameraOwnerName"), (0xA431, "BodySerialNumber"), (0xA432, "LensSpecification"),
+    (0xA433, "LensMake"), (0xA434, "LensModel"), (0xA435, "LensSerialNumber")
+];
+
+const GPS_TAGS: &[(u16, &str)] = &[
+    (0x00, "GPSVersionID"), (0x01, "GPSLatitudeRef"), (0x02, "GPSLatitude"), (0x03, "GPSLongitudeRef"),
+    (0x04, "GPSLongitude"), (0x05, "GPSAltitudeRef"), (0x06, "GPSAltitude"), (0x07, "GPSTimeStamp"),
+    (0x08, "GPSSatellites"), (0x09, "GPSStatus"), (0x0A, "GPSMeasureMode"), (0x0B, "GPSDOP"),
+    (0x0C, "GPSSpeedRef"), (0x0D, "GPSSpeed"), (0x0E, "GPSTrackRef"), (0x0F, "GPSTrack"),
+    (0x10, "GPSImgDirectionRef"), (0x11, "GPSImgDirection"), (0x12, "GPSMapDatum"), (0x1B, "GPSProcessingMethod"),
+    (0x1D, "GPSDateStamp"), (0x1F, "GPSHPositioningError")
+];
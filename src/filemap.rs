@@ -0,0 +1,209 @@
+/*
+    Memory-mapped input for read-only scans
+
+    Carving, entropy profiles and file hashes read their input through `ScanFile`. With the
+    `mmap` feature and `set_file_access(FileAccess::Mapped)`, the file is mapped rather than
+    read: repeated scans of the same multi-gigabyte image (carve, then profile, then hash) are
+    served from the page cache with no read calls or copies into scan buffers. Results are
+    identical either way.
+
+    The mapping itself is memmap2's. It falls back to buffered reads whenever it cannot be
+    used: the feature is off, the file is empty or larger than the address space (32-bit
+    targets), or the map call fails (some network and FUSE file systems).
+
+    Caveat: a mapping follows the file as it changes. If another process truncates a mapped
+    file, touching the pages past the new end raises SIGBUS on Unix (an access violation on
+    Windows) and ends the process, where a buffered read would only come up short. Map
+    evidence nothing else is writing to, such as a read-only image copy.
+*/
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering}
+};
+
+use log::warn;
+
+static MAPPED: AtomicBool = AtomicBool::new(false);
+
+/// How read-only scans access their input files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileAccess {
+    /// Positioned buffered reads
+    #[default]
+    Buffered,
+    /// Memory-mapped when the `mmap` feature is compiled in and the file can be mapped, buffered otherwise
+    Mapped
+}
+
+/// Sets how carving, entropy profiles and file hashes read their input
+pub fn set_file_access(access: FileAccess) {
+    MAPPED.store(access == FileAccess::Mapped, Ordering::Relaxed);
+}
+
+/// The access mode set with `set_file_access` (buffered until then)
+pub fn file_access() -> FileAccess {
+    if MAPPED.load(Ordering::Relaxed) { FileAccess::Mapped } else { FileAccess::Buffered }
+}
+
+/// An input file opened for scanning, mapped when requested and possible
+pub(crate) struct ScanFile {
+    file: File,
+    size: u64,
+    map: Option<Mapping>
+}
+
+impl ScanFile {
+    pub fn open(path: &Path) -> io::Result<ScanFile> {
+        ScanFile::open_with(path, file_access())
+    }
+
+    /// `open` with the access given rather than the one set with `set_file_access`
+    pub fn open_with(path: &Path, access: FileAccess) -> io::Result<ScanFile> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let map = match access {
+            FileAccess::Mapped => Mapping::new(&file, size),
+            FileAccess::Buffered => None
+        };
+        if map.is_none() && size > 0 && cfg!(feature = "mmap") && access == FileAccess::Mapped {
+            warn!("Unable to map {}, falling back to buffered reads", path.display());
+        }
+
+        Ok(ScanFile { file, size, map })
+    }
+
+    /// Length when opened
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Up to `length` bytes from `offset`, fewer at the end of the file
+    pub fn read_at(&self, offset: u64, length: usize) -> io::Result<Cow<'_, [u8]>> {
+        if let Some(map) = &self.map {
+            return Ok(Cow::Borrowed(range(map.bytes(), offset, length)));
+        }
+
+        let mut buffer = vec![0u8; length];
+        let filled = self.read_into(offset, &mut buffer)?;
+        buffer.truncate(filled);
+        Ok(Cow::Owned(buffer))
+    }
+
+    /// Fills `buffer` from `offset`, returning how much was read (less only at the end of the file)
+    pub fn read_into(&self, offset: u64, buffer: &mut [u8]) -> io::Result<usize> {
+        if let Some(map) = &self.map {
+            let bytes = range(map.bytes(), offset, buffer.len());
+            buffer[..bytes.len()].copy_from_slice(bytes);
+            return Ok(bytes.len());
+        }
+
+        // &File reads and seeks, so shared borrows of the scan file can be held across reads
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match file.read(&mut buffer[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(filled)
+    }
+}
+
+// Up to `length` mapped bytes from `offset`
+fn range(bytes: &[u8], offset: u64, length: usize) -> &[u8] {
+    let start = offset.min(bytes.len() as u64) as usize;
+    &bytes[start..start.saturating_add(length).min(bytes.len())]
+}
+
+#[cfg(feature = "mmap")]
+struct Mapping(memmap2::Mmap);
+
+#[cfg(feature = "mmap")]
+impl Mapping {
+    fn new(file: &File, size: u64) -> Option<Mapping> {
+        // Nothing to map in an empty file, and no room for one larger than the address space
+        let length = usize::try_from(size).ok().filter(|length| *length > 0)?;
+
+        // Read-only and private; the caveat above is why mapping is unsafe at all
+        let map = unsafe { memmap2::MmapOptions::new().len(length).map(file) };
+        map.ok().map(Mapping)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Without the feature nothing is mapped and every read is buffered
+#[cfg(not(feature = "mmap"))]
+struct Mapping;
+
+#[cfg(not(feature = "mmap"))]
+impl Mapping {
+    fn new(_file: &File, _size: u64) -> Option<Mapping> {
+        None
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    fn fixture(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("mercy-filemap-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn mapped_reads_match_buffered_reads() {
+        let bytes: Vec<u8> = (0..70_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let path = fixture("reads", &bytes);
+        let mapped = ScanFile::open_with(&path, FileAccess::Mapped).unwrap();
+        let buffered = ScanFile::open_with(&path, FileAccess::Buffered).unwrap();
+
+        // With the feature the file really is mapped rather than quietly read
+        assert_eq!(mapped.map.is_some(), cfg!(feature = "mmap"));
+        assert!(buffered.map.is_none());
+        assert_eq!(mapped.size(), buffered.size());
+
+        // Page boundaries, the last bytes, and ranges running past the end or starting beyond it
+        for (offset, length) in [(0, 16), (4095, 2), (65_536, 4096), (69_990, 100), (70_000, 5), (80_000, 5)] {
+            let expected = &bytes[(offset as usize).min(bytes.len())..(offset as usize + length).min(bytes.len())];
+            assert_eq!(mapped.read_at(offset, length).unwrap().as_ref(), expected, "read_at({}, {})", offset, length);
+            assert_eq!(buffered.read_at(offset, length).unwrap().as_ref(), expected, "read_at({}, {})", offset, length);
+
+            let (mut a, mut b) = (vec![0u8; length], vec![0u8; length]);
+            assert_eq!(mapped.read_into(offset, &mut a).unwrap(), buffered.read_into(offset, &mut b).unwrap());
+            assert_eq!(a, b);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn empty_files_are_read_not_mapped() {
+        let path = fixture("empty", b"");
+        let file = ScanFile::open_with(&path, FileAccess::Mapped).unwrap();
+
+        assert!(file.map.is_none());
+        assert!(file.read_at(0, 16).unwrap().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
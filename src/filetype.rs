@@ -0,0 +1,235 @@
+/*
+    File type identification from magic bytes
+
+    Only the start of the file is read and matched against the signatures of common
+    executables, archives, documents and images, so the answer does not depend on the file
+    name. ZIP archives are opened to tell the formats built on them (DOCX, XLSX, PPTX,
+    OpenDocument, JAR, APK) from a plain ZIP, as long as the archive is small enough to read
+    whole. A file matching no signature is text when its start is printable ASCII or UTF-8,
+    and unknown otherwise.
+*/
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Read,
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::zip::ZipArchive;
+
+// Bytes read from the start of the file; enough for the PE header of most executables
+const HEAD: usize = 4096;
+
+// PDF readers accept the header anywhere in the first KiB
+const PDF_WINDOW: usize = 1024;
+
+// Largest ZIP opened to look at its entries
+const MAX_ZIP: u64 = 64 * 1024 * 1024;
+
+// Signatures at offset 0, most specific first
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x7FELF", "ELF executable"),
+    (b"\xFE\xED\xFA\xCE", "Mach-O executable (32-bit)"),
+    (b"\xCE\xFA\xED\xFE", "Mach-O executable (32-bit)"),
+    (b"\xFE\xED\xFA\xCF", "Mach-O executable (64-bit)"),
+    (b"\xCF\xFA\xED\xFE", "Mach-O executable (64-bit)"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"PK\x05\x06", "ZIP archive (empty)"),
+    (b"\x1F\x8B", "GZIP compressed data"),
+    (b"Rar!\x1A\x07\x01\x00", "RAR archive (v5)"),
+    (b"Rar!\x1A\x07\x00", "RAR archive"),
+    (b"7z\xBC\xAF\x27\x1C", "7-Zip archive"),
+    (b"\x89PNG\r\n\x1A\n", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"MZ", "DOS executable")
+];
+
+/// What a file's magic bytes say it is
+#[derive(Debug, Clone, Serialize)]
+pub struct FileType {
+    pub path: String,
+    pub size: u64,
+    /// Short description ("PE executable", "DOCX document", "unknown")
+    pub label: String,
+    /// Where the signature matched
+    pub offset: Option<usize>,
+    /// The matched bytes, printable ones as text and the rest as \xNN
+    pub signature: Option<String>,
+    /// What else was looked at to decide ("PE\0\0 at offset 0x80", "contains word/document.xml")
+    pub detail: Option<String>,
+    /// First 8 bytes in hex
+    pub first_bytes: String
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label)?;
+        match (&self.signature, self.offset) {
+            (Some(signature), Some(offset)) => write!(f, " ({} at offset {}", signature, offset)?,
+            _ if self.label == "unknown" => return write!(f, " (first 8 bytes: {})", self.first_bytes),
+            _ => match &self.detail {
+                Some(detail) => return write!(f, " ({})", detail),
+                None => return Ok(())
+            }
+        }
+        match &self.detail {
+            Some(detail) => write!(f, "; {})", detail),
+            None => write!(f, ")")
+        }
+    }
+}
+
+/// Identifies the file at `path` from its first bytes (and, for a ZIP, its entry names)
+pub fn mercy_file_type<P: AsRef<Path>>(path: P) -> Result<FileType, String> {
+    let path = path.as_ref();
+    let unreadable = |e: std::io::Error| format!("Unable to read {}: {}", path.display(), e);
+
+    let size = fs::metadata(path).map_err(unreadable)?.len();
+    let mut head = Vec::with_capacity(HEAD);
+    File::open(path).and_then(|file| file.take(HEAD as u64).read_to_end(&mut head)).map_err(unreadable)?;
+
+    let mut identified = identify(&head);
+    if identified.label == "ZIP archive" && size <= MAX_ZIP {
+        if let Ok(archive) = ZipArchive::open(path) {
+            if let Some((label, detail)) = zip_format(&archive) {
+                identified.label = label.to_string();
+                identified.detail = Some(detail);
+            }
+        }
+    }
+
+    Ok(FileType {
+        path: path.display().to_string(),
+        size,
+        label: identified.label,
+        offset: identified.offset,
+        signature: identified.signature,
+        detail: identified.detail,
+        first_bytes: head.iter().take(8).map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+    })
+}
+
+// What the start of a file matched
+struct Identified {
+    label: String,
+    offset: Option<usize>,
+    signature: Option<String>,
+    detail: Option<String>
+}
+
+fn identify(head: &[u8]) -> Identified {
+    let found = |label: &str, offset: usize, signature: &[u8], detail: Option<String>| Identified {
+        label: label.to_string(),
+        offset: Some(offset),
+        signature: Some(escape(signature)),
+        detail
+    };
+
+    if head.is_empty() {
+        return Identified { label: "empty".to_string(), offset: None, signature: None, detail: None };
+    }
+
+    // Fat Mach-O binaries and Java classes share CAFEBABE; a fat header counts a few
+    // architectures where a class file has its version (45 and up)
+    if head.starts_with(b"\xCA\xFE\xBA\xBE") {
+        let next = head.get(4..8).map(|word| u32::from_be_bytes([word[0], word[1], word[2], word[3]]));
+        let label = match next {
+            Some(count) if count < 45 => "Mach-O universal binary",
+            _ => "Java class file"
+        };
+        return found(label, 0, &head[..4], None);
+    }
+
+    if let Some((magic, label)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        // The DOS stub of a Windows executable points to the PE header at 0x3C
+        if *magic == b"MZ" {
+            let pe = head.get(0x3C..0x40).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]) as usize);
+            if let Some(pe) = pe.filter(|pe| head.get(*pe..*pe + 4) == Some(b"PE\0\0")) {
+                return found("PE executable", 0, magic, Some(format!("PE header at offset {:#x}", pe)));
+            }
+        }
+        return found(label, 0, magic, None);
+    }
+
+    let window = &head[..head.len().min(PDF_WINDOW)];
+    if let Some(offset) = window.windows(5).position(|bytes| bytes == b"%PDF-") {
+        let version: String = head[offset + 5..].iter().take(3).take_while(|byte| byte.is_ascii_digit() || **byte == b'.').map(|byte| *byte as char).collect();
+        let detail = (!version.is_empty()).then(|| format!("version {}", version));
+        return found("PDF document", offset, b"%PDF-", detail);
+    }
+
+    match text_kind(head) {
+        Some(label) => Identified { label: label.to_string(), offset: None, signature: None, detail: None },
+        None => Identified { label: "unknown".to_string(), offset: None, signature: None, detail: None }
+    }
+}
+
+// ASCII or UTF-8 text when every character is printable or common whitespace; a character
+// cut off at the end of the read is allowed
+fn text_kind(head: &[u8]) -> Option<&'static str> {
+    let (bom, body) = match head.strip_prefix(b"\xEF\xBB\xBF") {
+        Some(body) => (true, body),
+        None => (false, head)
+    };
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&body[..e.valid_up_to()]).ok()?,
+        Err(_) => return None
+    };
+
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0C')) {
+        return None;
+    }
+    Some(match (bom, text.is_ascii()) {
+        (true, _) => "UTF-8 text (with BOM)",
+        (false, true) => "ASCII text",
+        (false, false) => "UTF-8 text"
+    })
+}
+
+// The ZIP based format an archive's entries point to, and the entry that gave it away
+fn zip_format(archive: &ZipArchive) -> Option<(&'static str, String)> {
+    let has = |name: &str| archive.entries().iter().any(|entry| entry.name == name);
+    let contains = |name: &str| format!("contains {}", name);
+
+    if has("[Content_Types].xml") {
+        for (name, label) in [("word/document.xml", "DOCX document"), ("xl/workbook.xml", "XLSX spreadsheet"), ("ppt/presentation.xml", "PPTX presentation")] {
+            if has(name) {
+                return Some((label, contains(name)));
+            }
+        }
+        return Some(("Office Open XML document", contains("[Content_Types].xml")));
+    }
+    if has("AndroidManifest.xml") && has("classes.dex") {
+        return Some(("APK Android package", contains("AndroidManifest.xml")));
+    }
+    if has("META-INF/MANIFEST.MF") {
+        return Some(("JAR archive", contains("META-INF/MANIFEST.MF")));
+    }
+    if has("mimetype") {
+        let mimetype = archive.read("mimetype", 128).ok().flatten()?;
+        let mimetype = String::from_utf8_lossy(&mimetype).trim().to_string();
+        let label = match mimetype.as_str() {
+            "application/vnd.oasis.opendocument.text" => "ODT document",
+            "application/vnd.oasis.opendocument.spreadsheet" => "ODS spreadsheet",
+            "application/vnd.oasis.opendocument.presentation" => "ODP presentation",
+            "application/epub+zip" => "EPUB book",
+            _ => return None
+        };
+        return Some((label, format!("mimetype {}", mimetype)));
+    }
+    None
+}
+
+// Printable ASCII as is, anything else as \xNN
+fn escape(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| match byte {
+        0x21..=0x7E if *byte != b'\\' => (*byte as char).to_string(),
+        _ => format!("\\x{:02x}", byte)
+    }).collect()
+}
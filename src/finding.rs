@@ -0,0 +1,161 @@
+/*
+    Common finding type shared by the audit-style checks, with Markdown and CSV rendering
+
+    The JSON form is the stable schema: field names and the lowercase severity strings below do
+    not change between releases.
+*/
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Severity of a finding, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical
+}
+
+impl Severity {
+    /// All severities, most severe first (the order reports are grouped in)
+    pub const DESCENDING: [Severity; 5] = [Severity::Critical, Severity::High, Severity::Medium, Severity::Low, Severity::Info];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical"
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single issue raised by an audit or exposure check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Stable identifier such as "smb.smb1-enabled"
+    pub id: String,
+    pub title: String,
+    pub severity: Severity,
+    /// Host, URL or domain the finding applies to
+    pub target: String,
+    pub evidence: String,
+    pub recommendation: String
+}
+
+impl Finding {
+    pub fn new(id: &str, title: &str, severity: Severity, target: &str, evidence: &str, recommendation: &str) -> Finding {
+        Finding {
+            id: id.to_string(),
+            title: title.to_string(),
+            severity,
+            target: target.to_string(),
+            evidence: evidence.to_string(),
+            recommendation: recommendation.to_string()
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "[{}] {} ({})", self.severity, self.title, self.id)?;
+        writeln!(f, "Target: {}", self.target)?;
+        writeln!(f, "Evidence: {}", self.evidence)?;
+        writeln!(f, "Recommendation: {}", self.recommendation)
+    }
+}
+
+/// Renders findings as Markdown, grouped by severity (most severe first)
+pub fn findings_markdown(findings: &[Finding]) -> String {
+    let mut output = String::from("# Findings\n\n");
+
+    if findings.is_empty() {
+        output.push_str("No findings.\n");
+        return output;
+    }
+
+    output.push_str("| Severity | Count |\n| -------- | ----- |\n");
+    for severity in Severity::DESCENDING {
+        let count = findings.iter().filter(|finding| finding.severity == severity).count();
+        if count > 0 {
+            output.push_str(&format!("| {} | {} |\n", severity, count));
+        }
+    }
+
+    for severity in Severity::DESCENDING {
+        let group: Vec<&Finding> = findings.iter().filter(|finding| finding.severity == severity).collect();
+        if group.is_empty() {
+            continue;
+        }
+
+        output.push_str(&format!("\n## {}\n", capitalize(severity.as_str())));
+
+        for finding in group {
+            output.push_str(&format!("\n### {} (`{}`)\n\n", markdown_escape(&finding.title), finding.id));
+            output.push_str(&format!("- **Target:** {}\n", markdown_escape(&finding.target)));
+            output.push_str(&format!("- **Evidence:** {}\n", markdown_escape(&finding.evidence)));
+            output.push_str(&format!("- **Recommendation:** {}\n", markdown_escape(&finding.recommendation)));
+        }
+    }
+
+    output
+}
+
+/// Renders findings as RFC 4180 CSV with a header row, sorted by severity (most severe first)
+pub fn findings_csv(findings: &[Finding]) -> String {
+    let mut output = String::from("id,title,severity,target,evidence,recommendation\r\n");
+
+    for severity in Severity::DESCENDING {
+        for finding in findings.iter().filter(|finding| finding.severity == severity) {
+            let fields = [
+                finding.id.as_str(),
+                finding.title.as_str(),
+                finding.severity.as_str(),
+                finding.target.as_str(),
+                finding.evidence.as_str(),
+                finding.recommendation.as_str()
+            ];
+
+            output.push_str(&fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+            output.push_str("\r\n");
+        }
+    }
+
+    output
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Keeps evidence from breaking list items or injecting markup
+fn markdown_escape(value: &str) -> String {
+    value.replace('\r', "")
+        .replace('\n', " ")
+        .replace('|', "\\|")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
@@ -0,0 +1,422 @@
+/*
+    Fuzz payload generators for quick robustness testing
+
+    Strings come from a fixed catalogue of classic problem inputs (long runs, format
+    specifiers, embedded NULs, Unicode edge cases, SQL/XSS/path traversal probes and numbers
+    outside common integer and float ranges), either all of them or a seeded sample. Byte
+    mutations are derived from a seed input: bit flips, byte swaps and replacements,
+    truncations, duplicated slices and integer boundary values written over the input as if
+    it held a length field. Every record names the case or mutation that produced it, and the
+    seed is reported, so a failure can be traced back and the same run repeated.
+*/
+
+use std::fs;
+
+use serde::Serialize;
+
+use crate::{
+    rng::{MercyRng, random_seed},
+    trailing_options
+};
+
+// Categories of the string catalogue, in output order
+const STRING_CATEGORIES: &[&str] = &["empty", "long", "format", "null", "unicode", "sql", "xss", "path", "number"];
+
+// Mutations generated when no count is given
+const DEFAULT_MUTATIONS: usize = 100;
+
+// Most records one run may produce, and the largest seed input
+const MAX_COUNT: usize = 100_000;
+const MAX_INPUT: usize = 16 * 1024 * 1024;
+
+// Values around the edges of 8, 16 and 32-bit length fields
+const BOUNDARIES: &[(u32, usize)] = &[
+    (0, 1), (1, 1), (0x7F, 1), (0x80, 1), (0xFF, 1),
+    (0, 2), (0x7FFF, 2), (0x8000, 2), (0xFFFF, 2),
+    (0, 4), (0x7FFF_FFFF, 4), (0x8000_0000, 4), (0xFFFF_FFFF, 4)
+];
+
+/// Options shared by the fuzz generators
+#[derive(Debug, Clone, Default)]
+pub struct FuzzOptions {
+    /// Records to produce; every catalogue string, or 100 mutations, when None
+    pub count: Option<usize>,
+    /// Seed for sampling and mutation; a fixed seed makes a run repeatable (None draws one from the OS)
+    pub seed: Option<u64>,
+    /// String categories to draw from (all when empty)
+    pub categories: Vec<String>
+}
+
+/// One problem string
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzString {
+    pub index: usize,
+    /// "long", "format", "null", "unicode", "sql", "xss", "path", "number" or "empty"
+    pub category: String,
+    /// What the string exercises ("format specifier %n", "65536 x 'A'")
+    pub label: String,
+    /// The string itself; NUL and control characters are escaped by JSON
+    pub payload: String,
+    /// Length in bytes
+    pub length: usize
+}
+
+/// One mutation of the seed input
+#[derive(Debug, Clone, Serialize)]
+pub struct FuzzMutation {
+    pub index: usize,
+    /// "bit_flip", "byte_swap", "byte_set", "truncate", "duplicate" or "boundary"
+    pub mutation: String,
+    /// Where and how it was applied ("offset 3 bit 5", "u32 BE 0x80000000 at offset 4")
+    pub detail: String,
+    /// Length in bytes
+    pub length: usize,
+    /// The mutated bytes as hex
+    pub hex: String
+}
+
+/// Classic problem strings, all of them in catalogue order or a seeded sample of `count`, with the seed used
+pub fn mercy_fuzz_strings(options: &FuzzOptions) -> Result<(Vec<FuzzString>, u64), String> {
+    for category in &options.categories {
+        if !STRING_CATEGORIES.contains(&category.as_str()) {
+            return Err(format!("Unknown fuzz string category '{}' ({})", category, STRING_CATEGORIES.join(", ")));
+        }
+    }
+
+    let mut cases: Vec<(&str, String, String)> = catalogue().into_iter()
+        .filter(|(category, _, _)| options.categories.is_empty() || options.categories.iter().any(|wanted| wanted == category))
+        .collect();
+
+    let seed = options.seed.unwrap_or_else(random_seed);
+    if let Some(count) = options.count.filter(|count| *count < cases.len()) {
+        // Partial Fisher-Yates: the first `count` cases become the sample, kept in catalogue order
+        let mut rng = MercyRng::seeded(seed);
+        let mut order: Vec<usize> = (0..cases.len()).collect();
+        for index in 0..count {
+            let pick = index + rng.below(order.len() - index);
+            order.swap(index, pick);
+        }
+
+        let mut chosen = order[..count].to_vec();
+        chosen.sort_unstable();
+        cases = chosen.into_iter().map(|index| cases[index].clone()).collect();
+    }
+
+    let strings = cases.into_iter()
+        .enumerate()
+        .map(|(index, (category, label, payload))| FuzzString { index, category: category.to_string(), label, length: payload.len(), payload })
+        .collect();
+
+    Ok((strings, seed))
+}
+
+/// `count` seeded mutations of `input`, each applied to a fresh copy, with the seed used
+pub fn mercy_fuzz_bytes(input: &[u8], options: &FuzzOptions) -> Result<(Vec<FuzzMutation>, u64), String> {
+    if input.is_empty() {
+        return Err("The seed input for fuzz_bytes is empty".to_string());
+    }
+    if input.len() > MAX_INPUT {
+        return Err(format!("The seed input is larger than the {} byte limit", MAX_INPUT));
+    }
+
+    let count = options.count.unwrap_or(DEFAULT_MUTATIONS);
+    if count > MAX_COUNT {
+        return Err(format!("At most {} records can be generated", MAX_COUNT));
+    }
+
+    let seed = options.seed.unwrap_or_else(random_seed);
+    let mut rng = MercyRng::seeded(seed);
+    let mutations = (0..count)
+        .map(|index| {
+            let (mutation, detail, bytes) = mutate(input, &mut rng);
+            FuzzMutation { index, mutation: mutation.to_string(), detail, length: bytes.len(), hex: hex(&bytes) }
+        })
+        .collect();
+
+    Ok((mutations, seed))
+}
+
+/* Strings */
+
+fn catalogue() -> Vec<(&'static str, String, String)> {
+    let mut cases = Vec::new();
+    let mut add = |category: &'static str, label: &str, payload: String| cases.push((category, label.to_string(), payload));
+
+    add("empty", "empty string", String::new());
+    add("empty", "single space", " ".to_string());
+    add("empty", "whitespace only", " \t\r\n".to_string());
+    add("empty", "CRLF", "\r\n".to_string());
+
+    for length in [256, 1024, 4096, 65536] {
+        add("long", &format!("{} x 'A'", length), "A".repeat(length));
+    }
+    add("long", "1024 x '%s'", "%s".repeat(1024));
+    add("long", "4096 x '/'", "/".repeat(4096));
+    add("long", "1024 nested '('", "(".repeat(1024));
+    add("long", "1024 nested '['", "[".repeat(1024));
+    add("long", "1024 x 'é' (2-byte UTF-8)", "é".repeat(1024));
+    add("long", "10000 line feeds", "\n".repeat(10000));
+
+    for (label, payload) in [
+        ("format specifier %s", "%s%s%s%s%s%s%s%s%s%s"),
+        ("format specifier %x", "%x%x%x%x%x%x%x%x"),
+        ("format specifier %n", "%n%n%n%n"),
+        ("format specifier %p", "%p%p%p%p"),
+        ("positional format specifier", "%1$s%2$n"),
+        ("wide format specifier", "%99999999999s"),
+        ("brace format", "{0}{1}{}{:?}"),
+        ("template expression", "{{7*7}}${7*7}<%= 7*7 %>#{7*7}"),
+        ("log4j lookup", "${jndi:ldap://127.0.0.1/a}"),
+        ("python format attribute", "{0.__class__}")
+    ] {
+        add("format", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("NUL byte", "\0"),
+        ("embedded NUL", "abc\0def"),
+        ("trailing NUL", "abc\0"),
+        ("NUL before extension", "file.txt\0.png"),
+        ("URL-encoded NUL", "%00"),
+        ("escaped NUL", "\\0\\x00\\u0000"),
+        ("C0 controls", "\u{1}\u{2}\u{3}\u{7}\u{8}\u{1b}\u{7f}")
+    ] {
+        add("null", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("byte order mark", "\u{feff}abc"),
+        ("right-to-left override", "abc\u{202e}fed"),
+        ("zero width joiner and space", "a\u{200d}b\u{200b}c"),
+        ("combining mark stack", "Z\u{0351}\u{0344}\u{036b}\u{0310}\u{0312}\u{035b}\u{0324}\u{0347}\u{0353}\u{0348}"),
+        ("noncharacter U+FFFF", "\u{ffff}"),
+        ("replacement character", "\u{fffd}"),
+        ("astral plane emoji", "\u{1f4a9}"),
+        ("emoji ZWJ sequence", "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}"),
+        ("fullwidth ASCII", "\u{ff41}\u{ff44}\u{ff4d}\u{ff49}\u{ff4e}"),
+        ("Cyrillic homoglyph", "p\u{0430}ypal"),
+        ("case mapping expansion", "\u{df}\u{130}\u{149}\u{1f0}"),
+        ("normalization expansion", "\u{fdfa}\u{fdfa}\u{fdfa}"),
+        ("line and paragraph separators", "a\u{2028}b\u{2029}c"),
+        ("private use", "\u{e000}\u{f8ff}"),
+        ("tag characters", "\u{e0041}\u{e0042}\u{e007f}"),
+        ("right-to-left text", "\u{5e9}\u{5dc}\u{5d5}\u{5dd} \u{645}\u{631}\u{62d}\u{628}\u{627}")
+    ] {
+        add("unicode", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("single quote", "'"),
+        ("double quote", "\""),
+        ("tautology", "' OR '1'='1"),
+        ("tautology with comment", "' OR 1=1-- -"),
+        ("double-quoted tautology", "\" OR \"\"=\""),
+        ("stacked query", "1; SELECT 1--"),
+        ("union probe", "' UNION SELECT NULL--"),
+        ("time-based probe", "1' AND SLEEP(5)-- -"),
+        ("comment terminator", "admin'/*"),
+        ("backslash escape", "\\'")
+    ] {
+        add("sql", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("script tag", "<script>alert(1)</script>"),
+        ("attribute breakout", "\"><img src=x onerror=alert(1)>"),
+        ("single-quoted attribute breakout", "'><svg onload=alert(1)>"),
+        ("javascript URL", "javascript:alert(1)"),
+        ("event handler", "\" autofocus onfocus=alert(1) x=\""),
+        ("script context breakout", "</script><script>alert(1)</script>"),
+        ("template literal", "${alert(1)}"),
+        ("mixed-case tag", "<ScRiPt>alert(1)</sCrIpT>"),
+        ("data URL", "data:text/html,<script>alert(1)</script>")
+    ] {
+        add("xss", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("Unix traversal", "../../../../../../etc/passwd"),
+        ("Windows traversal", "..\\..\\..\\..\\windows\\win.ini"),
+        ("URL-encoded traversal", "%2e%2e%2f%2e%2e%2f%2e%2e%2fetc%2fpasswd"),
+        ("double URL-encoded traversal", "%252e%252e%252fetc%252fpasswd"),
+        ("overlong UTF-8 traversal", "%c0%ae%c0%ae/%c0%ae%c0%ae/etc/passwd"),
+        ("filter bypass traversal", "....//....//....//etc/passwd"),
+        ("absolute path", "/etc/passwd"),
+        ("file URL", "file:///etc/passwd"),
+        ("UNC path", "\\\\127.0.0.1\\c$\\windows\\win.ini"),
+        ("Windows device name", "CON"),
+        ("NUL-truncated extension", "../../etc/passwd\0.png")
+    ] {
+        add("path", label, payload.to_string());
+    }
+
+    for (label, payload) in [
+        ("zero", "0"),
+        ("negative zero", "-0"),
+        ("minus one", "-1"),
+        ("i32 max", "2147483647"),
+        ("i32 max + 1", "2147483648"),
+        ("i32 min - 1", "-2147483649"),
+        ("u32 max + 1", "4294967296"),
+        ("i64 max", "9223372036854775807"),
+        ("i64 max + 1", "9223372036854775808"),
+        ("i64 min - 1", "-9223372036854775809"),
+        ("u64 max + 1", "18446744073709551616"),
+        ("f64 overflow", "1e309"),
+        ("f64 max", "1.7976931348623157e308"),
+        ("f64 denormal", "4.9e-324"),
+        ("NaN", "NaN"),
+        ("infinity", "-Infinity"),
+        ("hex literal", "0x7FFFFFFF"),
+        ("leading zeros", "000000000000000000000001"),
+        ("100-digit number", &"9".repeat(100))
+    ] {
+        add("number", label, payload.to_string());
+    }
+
+    cases
+}
+
+/* Bytes */
+
+// Applies one randomly chosen mutation to a copy of the input
+fn mutate(input: &[u8], rng: &mut MercyRng) -> (&'static str, String, Vec<u8>) {
+    let mut bytes = input.to_vec();
+    let length = bytes.len();
+
+    match rng.below(6) {
+        0 => {
+            let (offset, bit) = (rng.below(length), rng.below(8));
+            bytes[offset] ^= 1 << bit;
+            ("bit_flip", format!("offset {} bit {}", offset, bit), bytes)
+        },
+        1 if length > 1 => {
+            let first = rng.below(length);
+            let second = (first + 1 + rng.below(length - 1)) % length;
+            bytes.swap(first, second);
+            ("byte_swap", format!("offsets {} and {}", first, second), bytes)
+        },
+        2 => {
+            let offset = rng.below(length);
+            let value = [0x00, 0x7F, 0x80, 0xFF, rng.below(256) as u8][rng.below(5)];
+            bytes[offset] = value;
+            ("byte_set", format!("offset {} to 0x{:02X}", offset, value), bytes)
+        },
+        3 => {
+            let keep = rng.below(length);
+            bytes.truncate(keep);
+            ("truncate", format!("to {} of {} bytes", keep, length), bytes)
+        },
+        4 => {
+            let start = rng.below(length);
+            let size = 1 + rng.below((length - start).min(256));
+            let times = 1 + rng.below(16);
+            let slice = bytes[start..start + size].to_vec();
+            for _ in 0..times {
+                bytes.splice(start + size..start + size, slice.iter().copied());
+            }
+            ("duplicate", format!("{} byte(s) at offset {} repeated {} more time(s)", size, start, times), bytes)
+        },
+        _ => {
+            let candidates: Vec<&(u32, usize)> = BOUNDARIES.iter().filter(|(_, width)| *width <= length).collect();
+            let (value, width) = *candidates[rng.below(candidates.len())];
+            let offset = rng.below(length - width + 1);
+            let big_endian = width > 1 && rng.below(2) == 1;
+            let encoded = value.to_le_bytes();
+
+            for index in 0..width {
+                bytes[offset + index] = if big_endian { encoded[width - 1 - index] } else { encoded[index] };
+            }
+
+            let order = match (width, big_endian) {
+                (1, _) => "",
+                (_, true) => " BE",
+                _ => " LE"
+            };
+            ("boundary", format!("u{}{} 0x{:X} at offset {}", width * 8, order, value, offset), bytes)
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = text.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("Hex seed input has an odd number of digits".to_string());
+    }
+
+    digits.chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| "Hex seed input is not ASCII".to_string())?;
+            u8::from_str_radix(pair, 16).map_err(|_| format!("Invalid hex byte '{}'", pair))
+        })
+        .collect()
+}
+
+/* Options */
+
+// Records as NDJSON, ending with a {"summary": ...} line holding the seed that reproduces the run
+fn ndjson<T: Serialize>(generator: &str, records: &[T], seed: u64) -> Result<String, String> {
+    let mut output = String::new();
+    for record in records {
+        output.push_str(&serde_json::to_string(record).map_err(|e| e.to_string())?);
+        output.push('\n');
+    }
+
+    let summary = serde_json::json!({ "summary": { "generator": generator, "records": records.len(), "seed": seed } });
+    output.push_str(&summary.to_string());
+    Ok(output)
+}
+
+fn parse_options(pairs: &[(&str, &str)]) -> Result<FuzzOptions, String> {
+    let mut options = FuzzOptions::default();
+
+    for (key, value) in pairs {
+        match *key {
+            "categories" => options.categories = value.split(',').filter(|category| !category.is_empty()).map(str::to_string).collect(),
+            "count" => {
+                let count = value.parse::<usize>().map_err(|_| format!("Invalid count '{}'", value))?;
+                if count > MAX_COUNT {
+                    return Err(format!("At most {} records can be generated", MAX_COUNT));
+                }
+                options.count = Some(count);
+            },
+            "seed" => options.seed = Some(value.parse::<u64>().map_err(|_| format!("Invalid seed '{}'", value))?),
+            _ => {}
+        }
+    }
+
+    Ok(options)
+}
+
+// Parses "[count=N] [seed=S] [categories=sql,xss]" and returns NDJSON
+pub(crate) fn fuzz_strings_from_args(input: &str) -> Result<String, String> {
+    let (rest, pairs) = trailing_options(input, &["count", "seed", "categories"]);
+    if !rest.is_empty() {
+        return Err(format!("Unexpected fuzz_strings argument '{}' (count=, seed= and categories= are accepted)", rest));
+    }
+
+    let (strings, seed) = mercy_fuzz_strings(&parse_options(&pairs)?)?;
+    ndjson("fuzz_strings", &strings, seed)
+}
+
+// Parses "seed input [count=N] [seed=S] [hex=true] [file=true]" and returns NDJSON; the input is
+// taken as text, as hex digits, or as the path of a file to read
+pub(crate) fn fuzz_bytes_from_args(input: &str) -> Result<String, String> {
+    let (text, pairs) = trailing_options(input, &["count", "seed", "hex", "file"]);
+    let flag = |name: &str| pairs.iter().any(|(key, value)| *key == name && *value == "true");
+
+    let data = if flag("file") {
+        fs::read(&text).map_err(|e| format!("Unable to read {}: {}", text, e))?
+    } else if flag("hex") {
+        parse_hex(&text)?
+    } else {
+        text.into_bytes()
+    };
+
+    let (mutations, seed) = mercy_fuzz_bytes(&data, &parse_options(&pairs)?)?;
+    ndjson("fuzz_bytes", &mutations, seed)
+}
@@ -0,0 +1,387 @@
+/*
+    Hash identification: which algorithms could have produced a digest string
+
+    A bare hex digest only says how many bits it has, so every algorithm of that size is a
+    candidate, the common ones first (32 hex digits are far more often MD5 or NTLM than MD4).
+    Well-known constants such as the LM and NTLM hashes of an empty password are named
+    outright. Crypt-style strings carry their scheme as a prefix ("$2b$" bcrypt, "$6$"
+    sha512crypt, "$argon2id$"), as do LDAP password values ("{SSHA}") and Django's
+    "pbkdf2_sha256$", so those are matched on the prefix and checked for the expected shape.
+
+    Lines copied from dumps are taken apart first: pwdump lines ("user:RID:LM:NT:::"),
+    NetNTLMv1 and v2 responses as Responder writes them, "LM:NT" pairs, "user:hash" (the
+    hash then identified on its own) and "hash:salt". Base64 strings that decode to a digest
+    length are reported as encoded digests.
+
+    Candidates carry the hashcat mode where hashcat has one, so the next step is one copy
+    away. Nothing here can prove what a digest is; the order is only a likelihood.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{radix::base64_standard_decode, ssdeep::ssdeep_compare};
+
+// The LM hash of an empty password, which is also what LM looks like when it is disabled
+const EMPTY_LM: &str = "aad3b435b51404eeaad3b435b51404ee";
+
+// The NTLM hash of an empty password
+const EMPTY_NTLM: &str = "31d6cfe0d16ae931b73c59d7e0c089c0";
+
+// Digests of the empty string
+const EMPTY_DIGESTS: &[(&str, &str)] = &[
+    ("d41d8cd98f00b204e9800998ecf8427e", "MD5"),
+    ("da39a3ee5e6b4b0d3255bfef95601890afd80709", "SHA-1"),
+    ("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", "SHA-256")
+];
+
+// An algorithm with its hashcat mode and why it fits
+type Known = (&'static str, Option<u32>, &'static str);
+
+// Algorithms by hex length, most likely first
+const HEX_DIGESTS: &[(usize, &[Known])] = &[
+    (8, &[
+        ("CRC-32", Some(11500), "32-bit checksum, the usual 8-hex-digit value"),
+        ("Adler-32", None, "32-bit checksum of zlib streams"),
+        ("FNV-1a (32-bit)", None, "32-bit non-cryptographic hash")
+    ]),
+    (16, &[
+        ("MySQL 3.23 (OLD_PASSWORD)", Some(200), "64-bit password hash of old MySQL servers"),
+        ("xxHash64", None, "64-bit non-cryptographic hash"),
+        ("CRC-64", None, "64-bit checksum"),
+        ("LM (one half)", Some(3000), "each 7-character half of an LM hash is 64 bits")
+    ]),
+    (32, &[
+        ("MD5", Some(0), "128 bits, the most common digest of this length"),
+        ("NTLM", Some(1000), "128-bit MD4 of the UTF-16LE password, as Windows stores it"),
+        ("MD4", Some(900), "128 bits, rare outside NTLM"),
+        ("LM", Some(3000), "two 64-bit DES halves from pre-Vista Windows"),
+        ("RIPEMD-128", None, "128 bits, rarely seen")
+    ]),
+    (40, &[
+        ("SHA-1", Some(100), "160 bits, the most common digest of this length"),
+        ("MySQL 4.1+ without the leading '*'", Some(300), "SHA-1 of SHA-1 of the password"),
+        ("RIPEMD-160", Some(6000), "160 bits, common in Bitcoin tooling")
+    ]),
+    (48, &[
+        ("Tiger-192", None, "192 bits"),
+        ("HAVAL-192", None, "192 bits, rarely seen")
+    ]),
+    (56, &[
+        ("SHA-224", Some(1300), "224 bits"),
+        ("SHA3-224", Some(17300), "224 bits")
+    ]),
+    (64, &[
+        ("SHA-256", Some(1400), "256 bits, the most common digest of this length"),
+        ("SHA3-256", Some(17400), "256 bits"),
+        ("Keccak-256", Some(17800), "256 bits, the Ethereum variant of SHA-3"),
+        ("BLAKE2s-256", None, "256 bits"),
+        ("GOST R 34.11-94", Some(6900), "256 bits, rarely seen")
+    ]),
+    (96, &[
+        ("SHA-384", Some(10800), "384 bits"),
+        ("SHA3-384", Some(17500), "384 bits")
+    ]),
+    (128, &[
+        ("SHA-512", Some(1700), "512 bits, the most common digest of this length"),
+        ("SHA3-512", Some(17600), "512 bits"),
+        ("BLAKE2b-512", Some(600), "512 bits"),
+        ("Whirlpool", Some(6100), "512 bits, rarely seen")
+    ])
+];
+
+// Crypt-style and scheme prefixes: (prefix, algorithm, hashcat mode, justification)
+const PREFIXES: &[(&str, &str, Option<u32>, &str)] = &[
+    ("$2a$", "bcrypt", Some(3200), "\"$2a$\" prefix"),
+    ("$2b$", "bcrypt", Some(3200), "\"$2b$\" prefix"),
+    ("$2x$", "bcrypt", Some(3200), "\"$2x$\" prefix"),
+    ("$2y$", "bcrypt", Some(3200), "\"$2y$\" prefix (PHP)"),
+    ("$argon2id$", "Argon2id", None, "\"$argon2id$\" prefix"),
+    ("$argon2i$", "Argon2i", None, "\"$argon2i$\" prefix"),
+    ("$argon2d$", "Argon2d", None, "\"$argon2d$\" prefix"),
+    ("$1$", "md5crypt", Some(500), "\"$1$\" prefix of MD5-based Unix crypt"),
+    ("$apr1$", "Apache MD5 (apr1)", Some(1600), "\"$apr1$\" prefix of .htpasswd files"),
+    ("$5$", "sha256crypt", Some(7400), "\"$5$\" prefix of SHA-256 Unix crypt"),
+    ("$6$", "sha512crypt", Some(1800), "\"$6$\" prefix of SHA-512 Unix crypt, the Linux shadow default before yescrypt"),
+    ("$y$", "yescrypt", None, "\"$y$\" prefix, the current Debian and Fedora shadow default"),
+    ("$7$", "scrypt (crypt)", None, "\"$7$\" prefix"),
+    ("$sha1$", "sha1crypt", Some(15100), "\"$sha1$\" prefix of NetBSD crypt"),
+    ("$P$", "phpass", Some(400), "\"$P$\" prefix of WordPress and phpBB"),
+    ("$H$", "phpass", Some(400), "\"$H$\" prefix of phpBB"),
+    ("$8$", "Cisco type 8 (PBKDF2-SHA256)", Some(9200), "\"$8$\" prefix of Cisco IOS"),
+    ("$9$", "Cisco type 9 (scrypt)", Some(9300), "\"$9$\" prefix of Cisco IOS"),
+    ("$DCC2$", "Domain Cached Credentials 2 (mscash2)", Some(2100), "\"$DCC2$\" prefix"),
+    ("$krb5tgs$23$", "Kerberos 5 TGS-REP etype 23 (Kerberoasting)", Some(13100), "\"$krb5tgs$23$\" prefix"),
+    ("$krb5tgs$17$", "Kerberos 5 TGS-REP etype 17 (AES128)", Some(19600), "\"$krb5tgs$17$\" prefix"),
+    ("$krb5tgs$18$", "Kerberos 5 TGS-REP etype 18 (AES256)", Some(19700), "\"$krb5tgs$18$\" prefix"),
+    ("$krb5asrep$23$", "Kerberos 5 AS-REP etype 23 (AS-REP roasting)", Some(18200), "\"$krb5asrep$23$\" prefix"),
+    ("SCRYPT:", "scrypt", Some(8900), "\"SCRYPT:\" prefix of hashcat's scrypt format"),
+    ("pbkdf2_sha256$", "Django PBKDF2-SHA256", Some(10000), "\"pbkdf2_sha256$\" prefix of Django"),
+    ("pbkdf2_sha1$", "Django PBKDF2-SHA1", None, "\"pbkdf2_sha1$\" prefix of Django"),
+    ("argon2$argon2", "Django Argon2", None, "\"argon2$\" prefix of Django"),
+    ("bcrypt_sha256$", "Django bcrypt-SHA256", None, "\"bcrypt_sha256$\" prefix of Django"),
+    ("$pbkdf2-sha256$", "PBKDF2-SHA256 (passlib)", Some(20300), "\"$pbkdf2-sha256$\" prefix of passlib"),
+    ("$pbkdf2-sha512$", "PBKDF2-SHA512 (passlib)", Some(20200), "\"$pbkdf2-sha512$\" prefix of passlib")
+];
+
+// LDAP password schemes: (scheme, algorithm, hashcat mode, bytes of the digest without a salt)
+const LDAP_SCHEMES: &[(&str, &str, Option<u32>, usize)] = &[
+    ("{SSHA}", "Salted SHA-1 (LDAP SSHA)", Some(111), 20),
+    ("{SHA}", "SHA-1 (LDAP, base64)", Some(101), 20),
+    ("{SSHA256}", "Salted SHA-256 (LDAP SSHA256)", Some(1411), 32),
+    ("{SHA256}", "SHA-256 (LDAP, base64)", None, 32),
+    ("{SSHA512}", "Salted SHA-512 (LDAP SSHA512)", Some(1711), 64),
+    ("{SHA512}", "SHA-512 (LDAP, base64)", None, 64),
+    ("{SMD5}", "Salted MD5 (LDAP SMD5)", None, 16),
+    ("{MD5}", "MD5 (LDAP, base64)", None, 16)
+];
+
+/// One algorithm a digest could come from
+#[derive(Debug, Clone, Serialize)]
+pub struct HashCandidate {
+    pub algorithm: String,
+    /// The hashcat -m mode, when hashcat has one
+    pub hashcat_mode: Option<u32>,
+    /// Why it is a candidate
+    pub reason: String
+}
+
+/// Candidate algorithms for a digest string, most likely first
+#[derive(Debug, Clone, Serialize)]
+pub struct HashIdentification {
+    pub input: String,
+    /// The hash itself when the input was a line around it ("user:hash", pwdump)
+    pub hash: String,
+    /// The account name of a "user:hash", pwdump or NetNTLM line
+    pub username: Option<String>,
+    pub recognized: bool,
+    pub candidates: Vec<HashCandidate>
+}
+
+impl fmt::Display for HashIdentification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.recognized {
+            return write!(f, "'{}' is not a recognized hash format", self.input);
+        }
+
+        if let Some(username) = &self.username {
+            writeln!(f, "User: {}", username)?;
+        }
+        for (index, candidate) in self.candidates.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}. {}", index + 1, candidate.algorithm)?;
+            if let Some(mode) = candidate.hashcat_mode {
+                write!(f, " (hashcat -m {})", mode)?;
+            }
+            write!(f, ": {}", candidate.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Guesses the algorithm of a digest from its length, alphabet and prefix, most likely first
+pub fn mercy_hash_identify(input: &str) -> Result<HashIdentification, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("No hash given".to_string());
+    }
+
+    let (hash, username, candidates) = match line_candidates(input) {
+        Some(found) => found,
+        None => (input.to_string(), None, hash_candidates(input))
+    };
+
+    Ok(HashIdentification {
+        input: input.to_string(),
+        hash,
+        username,
+        recognized: !candidates.is_empty(),
+        candidates
+    })
+}
+
+fn candidate(algorithm: &str, hashcat_mode: Option<u32>, reason: &str) -> HashCandidate {
+    HashCandidate { algorithm: algorithm.to_string(), hashcat_mode, reason: reason.to_string() }
+}
+
+fn is_hex(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_hex_of(text: &str, len: usize) -> bool {
+    text.len() == len && is_hex(text)
+}
+
+// Lines that wrap a hash: NetNTLM responses, pwdump, "LM:NT", "hash:salt" and "user:hash"
+fn line_candidates(input: &str) -> Option<(String, Option<String>, Vec<HashCandidate>)> {
+    // Prefixed formats and ssdeep digests have colons of their own
+    if input.starts_with('$') || input.starts_with('{') || !hash_candidates(input).is_empty() {
+        return None;
+    }
+
+    let fields: Vec<&str> = input.split(':').collect();
+    if fields.len() < 2 {
+        return None;
+    }
+    let user = Some(fields[0].to_string()).filter(|user| !user.is_empty());
+
+    // Responder and hashcat write "user::domain:challenge:NTProofStr:blob" for NetNTLMv2
+    if fields.len() == 6 && fields[1].is_empty() && is_hex_of(fields[3], 16) && is_hex_of(fields[4], 32) && is_hex(fields[5]) {
+        return Some((input.to_string(), user, vec![candidate("NetNTLMv2", Some(5600), "user::domain:challenge:NTProofStr:blob, a captured challenge response")]));
+    }
+    // and "user::domain:LM response:NT response:challenge" for NetNTLMv1
+    if fields.len() == 6 && fields[1].is_empty() && is_hex_of(fields[3], 48) && is_hex_of(fields[4], 48) && is_hex_of(fields[5], 16) {
+        return Some((input.to_string(), user, vec![candidate("NetNTLMv1", Some(5500), "user::domain:LM:NT:challenge, a captured challenge response")]));
+    }
+
+    // pwdump and secretsdump: "user:RID:LM:NT:::"
+    if fields.len() >= 4 && fields[1].chars().all(|c| c.is_ascii_digit()) && !fields[1].is_empty() && is_hex_of(fields[2], 32) && is_hex_of(fields[3], 32) {
+        let nt = fields[3].to_ascii_lowercase();
+        let mut candidates = vec![candidate("NTLM", Some(1000), &nt_reason(&nt, "the NT hash of a pwdump line (user:RID:LM:NT)"))];
+        match fields[2].eq_ignore_ascii_case(EMPTY_LM) {
+            true => candidates.push(candidate("LM (disabled)", None, "the LM field holds the empty-password constant, so no LM hash is stored")),
+            false => candidates.push(candidate("LM", Some(3000), "the LM hash of the same password, crackable in 7-character halves"))
+        }
+        return Some((fields[3].to_string(), user, candidates));
+    }
+
+    if fields.len() != 2 {
+        return None;
+    }
+    let (first, second) = (fields[0], fields[1]);
+
+    // "LM:NT" as some dumps print it
+    if is_hex_of(first, 32) && is_hex_of(second, 32) {
+        let nt = second.to_ascii_lowercase();
+        let lm = match first.eq_ignore_ascii_case(EMPTY_LM) {
+            true => candidate("LM (disabled)", None, "the first half is the empty-password LM constant"),
+            false => candidate("LM", Some(3000), "the first half of an LM:NT pair")
+        };
+        return Some((second.to_string(), None, vec![candidate("NTLM", Some(1000), &nt_reason(&nt, "the second half of an LM:NT pair")), lm]));
+    }
+
+    // "hash:salt" when the first part is a digest on its own
+    if is_hex(first) && HEX_DIGESTS.iter().any(|(len, _)| *len == first.len() && *len >= 32) {
+        let reason = format!("hash:salt with a {}-bit digest", first.len() * 4);
+        let candidates = hex_candidates(first).into_iter()
+            .filter(|found| !hex_only(&found.algorithm))
+            .map(|found| candidate(&format!("salted {}", found.algorithm), None, &reason))
+            .collect();
+        return Some((input.to_string(), None, candidates));
+    }
+
+    // "user:hash": the hash is identified on its own
+    let candidates = hash_candidates(second);
+    (!candidates.is_empty()).then(|| (second.to_string(), user, candidates))
+}
+
+// The NTLM reason, naming the empty password when it is that constant
+fn nt_reason(nt: &str, reason: &str) -> String {
+    match nt == EMPTY_NTLM {
+        true => format!("{}; this is the NTLM hash of an empty password", reason),
+        false => reason.to_string()
+    }
+}
+
+// Candidates for a hash on its own
+fn hash_candidates(hash: &str) -> Vec<HashCandidate> {
+    if let Some((prefix, algorithm, mode, reason)) = PREFIXES.iter().find(|(prefix, ..)| hash.starts_with(prefix)) {
+        let reason = match prefix.starts_with("$2") && !is_bcrypt(hash) {
+            true => format!("{}, but not the usual $2b$NN$ and 53 characters", reason),
+            false => reason.to_string()
+        };
+        return vec![candidate(algorithm, *mode, &reason)];
+    }
+
+    let upper = hash.to_ascii_uppercase();
+    if let Some((scheme, algorithm, mode, digest_len)) = LDAP_SCHEMES.iter().find(|(scheme, ..)| upper.starts_with(scheme)) {
+        let reason = match base64_standard_decode(&hash[scheme.len()..]) {
+            Ok(bytes) if bytes.len() == *digest_len => format!("{} scheme with a {}-byte digest", scheme, digest_len),
+            Ok(bytes) if bytes.len() > *digest_len && scheme.starts_with("{S") => format!("{} scheme: {}-byte digest and {}-byte salt", scheme, digest_len, bytes.len() - digest_len),
+            _ => format!("{} scheme, but the value is not the expected base64", scheme)
+        };
+        return vec![candidate(algorithm, *mode, &reason)];
+    }
+    if let Some(rest) = upper.strip_prefix("{CRYPT}") {
+        return hash_candidates(&hash[hash.len() - rest.len()..]).into_iter()
+            .map(|found| candidate(&found.algorithm, found.hashcat_mode, &format!("{{CRYPT}} scheme around a {}", found.reason)))
+            .collect();
+    }
+
+    // MySQL 4.1 and later: "*" and SHA-1(SHA-1(password)) in upper-case hex
+    if hash.strip_prefix('*').is_some_and(|digest| is_hex_of(digest, 40)) {
+        return vec![candidate("MySQL 4.1+", Some(300), "\"*\" followed by 40 hex digits, as mysql.user stores passwords")];
+    }
+
+    if is_ssdeep(hash) {
+        return vec![candidate("ssdeep", None, "blocksize:hash:hash fuzzy digest")];
+    }
+
+    if is_hex(hash) {
+        return hex_candidates(hash);
+    }
+
+    base64_candidates(hash)
+}
+
+// The length table, with well-known constants named first
+fn hex_candidates(hash: &str) -> Vec<HashCandidate> {
+    let lower = hash.to_ascii_lowercase();
+    let mut candidates: Vec<HashCandidate> = HEX_DIGESTS.iter()
+        .find(|(len, _)| *len == hash.len())
+        .map(|(_, algorithms)| algorithms.iter().map(|(algorithm, mode, reason)| candidate(algorithm, *mode, reason)).collect())
+        .unwrap_or_default();
+
+    let known = match lower.as_str() {
+        EMPTY_NTLM => Some(("NTLM", "the NTLM hash of an empty password")),
+        EMPTY_LM => Some(("LM", "the LM hash of an empty password, stored when LM is disabled")),
+        _ => EMPTY_DIGESTS.iter().find(|(digest, _)| *digest == lower).map(|(_, algorithm)| (*algorithm, "the digest of empty input"))
+    };
+    if let Some((algorithm, reason)) = known {
+        if let Some(index) = candidates.iter().position(|found| found.algorithm == algorithm) {
+            let mut found = candidates.remove(index);
+            found.reason = reason.to_string();
+            candidates.insert(0, found);
+        }
+    }
+
+    candidates
+}
+
+// Base64 that decodes to a digest length
+fn base64_candidates(hash: &str) -> Vec<HashCandidate> {
+    let looks_base64 = hash.len() >= 20 && hash.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='));
+    let bytes = match looks_base64.then(|| base64_standard_decode(hash)) {
+        Some(Ok(bytes)) => bytes,
+        _ => return Vec::new()
+    };
+
+    HEX_DIGESTS.iter()
+        .find(|(len, _)| *len == bytes.len() * 2 && *len >= 32)
+        .map(|(_, algorithms)| algorithms.iter()
+            .filter(|(algorithm, _, _)| !hex_only(algorithm))
+            .map(|(algorithm, _, _)| candidate(&format!("{} (base64)", algorithm), None, &format!("base64 of {} bytes, a {}-bit digest", bytes.len(), bytes.len() * 8)))
+            .collect())
+        .unwrap_or_default()
+}
+
+// Password hashes that are only ever stored as bare hex, never salted or base64-encoded
+fn hex_only(algorithm: &str) -> bool {
+    algorithm.starts_with("NTLM") || algorithm.starts_with("LM") || algorithm.starts_with("MySQL")
+}
+
+// "$2b$" and a two-digit cost, then 22 characters of salt and 31 of hash in bcrypt's base64
+fn is_bcrypt(hash: &str) -> bool {
+    let parts: Vec<&str> = hash.split('$').collect();
+    parts.len() == 4 && parts[2].len() == 2 && parts[2].chars().all(|c| c.is_ascii_digit())
+        && parts[3].len() == 53 && parts[3].chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '/')
+}
+
+fn is_ssdeep(hash: &str) -> bool {
+    hash.split(':').next().is_some_and(|block| !block.is_empty() && block.chars().all(|c| c.is_ascii_digit()))
+        && hash.matches(':').count() == 2
+        && ssdeep_compare(hash, hash).is_ok()
+}
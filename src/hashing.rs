@@ -0,0 +1,666 @@
+/*
+    Hashing of files and of data arriving over time
+
+    Files are read in fixed-size chunks (or memory-mapped, see `filemap`) and fed to the digest
+    (SHA-1, SHA-256, SHA-512, SHA3-256, SHA3-512 or MD5) or checksum (CRC-32 or xxHash64, see
+    `checksum`), so a multi-gigabyte image hashes in
+    constant memory. ssdeep picks its block size from the
+    total length and so needs the whole file at once. The `_gz` forms hash what a gzip file
+    decompresses to, inflating it chunk by chunk into the same digests, so a multi-gigabyte
+    capture is never held in memory; ssdeep has no such form.
+
+    `MercyHasher` hashes a stream piece by piece, split anywhere, with any algorithm of
+    `mercy_hash` but ssdeep (its block size depends on the total length), and implements
+    `io::Write` so a reader can be copied straight into it. SHA-256 and MD5 hashers can also
+    save their state to a string and resume from it in another process, so a capture arriving
+    over hours survives a restart. The digest crates keep their state private, so those two
+    are implemented here on plain state words (checked against the crates).
+
+    The saved state is "mercy-hasher/1/<algorithm>/<bytes hashed>/<state words>/<pending
+    bytes>" in hex. Resuming checks the version and the algorithm, so a state is never
+    continued by the wrong code.
+
+    HMAC (RFC 2104) over SHA-256, SHA-512, SHA-1 or MD5 backs `mercy_hmac` and signs outgoing
+    webhook notifications so receivers can check who sent them.
+*/
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path
+};
+
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{
+    checksum::{XxHash64, crc32_update, xxhash64},
+    digests::{Sha1, Sha3},
+    error::MercyError,
+    filemap::ScanFile,
+    inflate::gzip_stream,
+    radix::hex_encode,
+    ssdeep::ssdeep_hash,
+    stats::{self, Counter},
+    trailing_options
+};
+
+// Read size for streaming digests
+const CHUNK: usize = 64 * 1024;
+
+// Saved state format: tag and version
+const STATE_TAG: &str = "mercy-hasher";
+const STATE_VERSION: u32 = 1;
+
+const SHA256_INITIAL: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+
+const SHA256_ROUNDS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+];
+
+const MD5_INITIAL: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+// floor(|sin(i + 1)| * 2^32)
+const MD5_ROUNDS: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391
+];
+
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// Algorithms `MercyHasher` can hash incrementally
+pub const INCREMENTAL_HASH_ALGORITHMS: &[&str] = &["sha2_256", "md5", "sha1", "sha2_512", "sha3_256", "sha3_512", "crc32", "xxhash64"];
+
+/// Algorithms whose `MercyHasher` state can be saved and resumed
+pub const RESUMABLE_HASH_ALGORITHMS: &[&str] = &["sha2_256", "md5"];
+
+/// Algorithms accepted by `mercy_hash_file`
+pub const FILE_HASH_ALGORITHMS: &[&str] = &["sha2_256", "md5", "sha1", "sha2_512", "sha3_256", "sha3_512", "ssdeep", "crc32", "xxhash64"];
+
+/// Lowercase hex digest (or ssdeep signature) of a file's contents
+pub fn mercy_hash_file<P: AsRef<Path>>(path: P, algorithm: &str) -> Result<String, String> {
+    hash_file(path.as_ref(), algorithm, 0)
+}
+
+// `seed` applies to xxhash64 only
+fn hash_file(path: &Path, algorithm: &str, seed: u64) -> Result<String, String> {
+    if !FILE_HASH_ALGORITHMS.contains(&algorithm) {
+        return Err(format!("Unknown hash algorithm '{}' ({})", algorithm, FILE_HASH_ALGORITHMS.join(", ")));
+    }
+
+    let file = ScanFile::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let read_error = |e: std::io::Error| format!("Unable to read {}: {}", path.display(), e);
+
+    if algorithm == "ssdeep" {
+        let length = usize::try_from(file.size()).map_err(|_| format!("{} is too large to hash with ssdeep", path.display()))?;
+        let data = file.read_at(0, length).map_err(read_error)?;
+        stats::count(Counter::BytesHashed, data.len() as u64);
+        return Ok(ssdeep_hash(&data));
+    }
+
+    let mut digest = FileDigest::new(algorithm, seed);
+    let mut offset = 0u64;
+
+    loop {
+        let chunk = file.read_at(offset, CHUNK).map_err(read_error)?;
+        if chunk.is_empty() {
+            break;
+        }
+
+        offset += chunk.len() as u64;
+        stats::count(Counter::BytesHashed, chunk.len() as u64);
+        digest.update(&chunk);
+    }
+
+    Ok(digest.finish())
+}
+
+/// Algorithms the `_gz` hash calls accept: all of `FILE_HASH_ALGORITHMS` but ssdeep, which needs the whole input at once
+pub const GZIP_HASH_ALGORITHMS: &[&str] = &["sha2_256", "md5", "sha1", "sha2_512", "sha3_256", "sha3_512", "crc32", "xxhash64"];
+
+/// Hex digest of the decompressed contents of a gzip file, streamed so the data is never held in memory
+pub fn mercy_hash_gzip_file<P: AsRef<Path>>(path: P, algorithm: &str) -> Result<String, String> {
+    hash_gzip_file(path.as_ref(), algorithm, 0)
+}
+
+fn hash_gzip_file(path: &Path, algorithm: &str, seed: u64) -> Result<String, String> {
+    if !GZIP_HASH_ALGORITHMS.contains(&algorithm) {
+        return Err(format!("Unknown hash algorithm '{}' for gzip data ({})", algorithm, GZIP_HASH_ALGORITHMS.join(", ")));
+    }
+
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut stream = gzip_stream(BufReader::with_capacity(CHUNK, file)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut digest = FileDigest::new(algorithm, seed);
+    let mut chunk = vec![0; CHUNK];
+
+    loop {
+        let count = stream.read(&mut chunk).map_err(|e| format!("Unable to decompress {}: {}", path.display(), e))?;
+        if count == 0 {
+            break;
+        }
+
+        stats::count(Counter::BytesHashed, count as u64);
+        digest.update(&chunk[..count]);
+    }
+
+    Ok(digest.finish())
+}
+
+// The streaming digests behind `mercy_hash_file`
+#[derive(Clone)]
+enum FileDigest {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Md5(md5::Context),
+    Sha1(Sha1),
+    Sha3(Sha3),
+    Crc32(u32),
+    XxHash64(XxHash64)
+}
+
+impl FileDigest {
+    // One of FILE_HASH_ALGORITHMS other than ssdeep
+    fn new(algorithm: &str, seed: u64) -> FileDigest {
+        match algorithm {
+            "crc32" => FileDigest::Crc32(0xFFFF_FFFF),
+            "xxhash64" => FileDigest::XxHash64(XxHash64::new(seed)),
+            "sha2_256" => FileDigest::Sha256(Sha256::new()),
+            "sha2_512" => FileDigest::Sha512(Sha512::new()),
+            "sha1" => FileDigest::Sha1(Sha1::new()),
+            "sha3_256" => FileDigest::Sha3(Sha3::new(32)),
+            "sha3_512" => FileDigest::Sha3(Sha3::new(64)),
+            _ => FileDigest::Md5(md5::Context::new())
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            FileDigest::Sha256(digest) => digest.update(data),
+            FileDigest::Sha512(digest) => digest.update(data),
+            FileDigest::Md5(digest) => digest.consume(data),
+            FileDigest::Sha1(digest) => digest.update(data),
+            FileDigest::Sha3(digest) => digest.update(data),
+            FileDigest::Crc32(crc) => *crc = crc32_update(*crc, data),
+            FileDigest::XxHash64(digest) => digest.update(data)
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            FileDigest::Sha256(digest) => format!("{:x}", digest.finalize()),
+            FileDigest::Sha512(digest) => format!("{:x}", digest.finalize()),
+            FileDigest::Md5(digest) => format!("{:x}", digest.compute()),
+            FileDigest::Sha1(digest) => hex_encode(&digest.finalize()),
+            FileDigest::Sha3(digest) => hex_encode(&digest.finalize()),
+            FileDigest::Crc32(crc) => format!("{:08x}", crc ^ 0xFFFF_FFFF),
+            FileDigest::XxHash64(digest) => format!("{:016x}", digest.finish())
+        }
+    }
+}
+
+// Hex digits in a digest of the algorithm; ssdeep signatures are not hex
+pub(crate) fn hex_digest_len(algorithm: &str) -> Option<usize> {
+    match algorithm {
+        "md5" => Some(32),
+        "sha1" => Some(40),
+        "sha2_256" | "sha3_256" => Some(64),
+        "sha2_512" | "sha3_512" => Some(128),
+        "crc32" => Some(8),
+        "xxhash64" | "xxhash64_seeded" => Some(16),
+        _ => None
+    }
+}
+
+// Parses "path" for the `<algorithm>_file` and `<algorithm>_gz` hash calls, "path [seed=N]" for xxhash64_file and xxhash64_gz
+pub(crate) fn hash_file_from_args(call: &str, input: &str) -> Result<String, String> {
+    let (algorithm, gzip) = match call.strip_suffix("_gz") {
+        Some(algorithm) => (algorithm, true),
+        None => (call.strip_suffix("_file").unwrap_or(call), false)
+    };
+
+    let (path, seed) = match algorithm {
+        "xxhash64" => {
+            let (path, pairs) = trailing_options(input, &["seed"]);
+            let seed = match pairs.first() {
+                Some((_, value)) => parse_seed(value)?,
+                None => 0
+            };
+            (path, seed)
+        },
+        _ => (input.trim().to_string(), 0)
+    };
+
+    match gzip {
+        true => hash_gzip_file(Path::new(&path), algorithm, seed),
+        false => hash_file(Path::new(&path), algorithm, seed)
+    }
+}
+
+// Parses "seed=N <text>" for the xxhash64_seeded call
+pub(crate) fn xxhash64_seeded_from_args(input: &str) -> Result<String, String> {
+    let (seed, text) = input.strip_prefix("seed=")
+        .map(|rest| rest.split_once(' ').unwrap_or((rest, "")))
+        .ok_or_else(|| format!("Expected \"seed=N text\", got '{}'", input))?;
+
+    stats::count(Counter::BytesHashed, text.len() as u64);
+    Ok(format!("{:016x}", xxhash64(text.as_bytes(), parse_seed(seed)?)))
+}
+
+// Decimal or 0x-prefixed hex
+fn parse_seed(value: &str) -> Result<u64, String> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse()
+    }.map_err(|_| format!("Invalid seed '{}' (a decimal or 0x-prefixed hex 64-bit number)", value))
+}
+
+/// Digest under an HMAC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HmacDigest {
+    Sha256,
+    Sha512,
+    Sha1,
+    Md5
+}
+
+impl HmacDigest {
+    pub(crate) fn parse(name: &str) -> Option<HmacDigest> {
+        match name {
+            "sha256" => Some(HmacDigest::Sha256),
+            "sha512" => Some(HmacDigest::Sha512),
+            "sha1" => Some(HmacDigest::Sha1),
+            "md5" => Some(HmacDigest::Md5),
+            _ => None
+        }
+    }
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HmacDigest::Sha256 => Sha256::digest(data).to_vec(),
+            HmacDigest::Sha512 => Sha512::digest(data).to_vec(),
+            HmacDigest::Sha1 => {
+                let mut hasher = Sha1::new();
+                hasher.update(data);
+                hasher.finalize().to_vec()
+            },
+            HmacDigest::Md5 => md5::compute(data).0.to_vec()
+        }
+    }
+
+    // Input block size, which the key is padded to
+    fn block(&self) -> usize {
+        match self {
+            HmacDigest::Sha512 => 128,
+            _ => 64
+        }
+    }
+}
+
+/// HMAC of `message` under `key` (RFC 2104)
+pub(crate) fn hmac(digest: HmacDigest, key: &[u8], message: &[u8]) -> Vec<u8> {
+    // Keys longer than a block are hashed first; shorter ones are zero-padded
+    let mut block = vec![0u8; digest.block()];
+    if key.len() > block.len() {
+        let hashed = digest.digest(key);
+        block[..hashed.len()].copy_from_slice(&hashed);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    inner.extend_from_slice(message);
+    let mut outer: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend(digest.digest(&inner));
+    digest.digest(&outer)
+}
+
+/// Whether two byte strings are equal, taking the same time wherever they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Sha256,
+    Md5,
+    // Fed to its digest crate or checksum, whose state cannot be saved
+    Streamed(&'static str)
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Result<Algorithm, MercyError> {
+        match name {
+            "sha2_256" => Ok(Algorithm::Sha256),
+            "md5" => Ok(Algorithm::Md5),
+            "ssdeep" => Err(MercyError::invalid_input("ssdeep cannot be computed incrementally: its block size depends on the total length".to_string())),
+            _ => INCREMENTAL_HASH_ALGORITHMS.iter().find(|algorithm| **algorithm == name).map(|algorithm| Algorithm::Streamed(algorithm)).ok_or_else(|| MercyError::unsupported("MercyHasher", name))
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha2_256",
+            Algorithm::Md5 => "md5",
+            Algorithm::Streamed(name) => name
+        }
+    }
+
+    fn words(&self) -> usize {
+        match self {
+            Algorithm::Sha256 => 8,
+            Algorithm::Md5 => 4,
+            Algorithm::Streamed(_) => 0
+        }
+    }
+}
+
+/// Incremental hash giving the digest `mercy_hash` gives for the whole input; SHA-256 and MD5
+/// states can also be saved and resumed in another process
+#[derive(Clone)]
+pub struct MercyHasher {
+    algorithm: Algorithm,
+    // Chaining state (MD5 uses the first four words)
+    state: [u32; 8],
+    // Bytes of an incomplete 64-byte block
+    pending: Vec<u8>,
+    length: u64,
+    // The digest of any other algorithm
+    streamed: Option<FileDigest>
+}
+
+impl fmt::Debug for MercyHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MercyHasher").field("algorithm", &self.algorithm.name()).field("length", &self.length).finish_non_exhaustive()
+    }
+}
+
+impl MercyHasher {
+    /// A hasher for one of `INCREMENTAL_HASH_ALGORITHMS`
+    pub fn new(algorithm: &str) -> Result<MercyHasher, MercyError> {
+        let algorithm = Algorithm::parse(algorithm)?;
+        let mut state = [0u32; 8];
+        let mut streamed = None;
+        match algorithm {
+            Algorithm::Sha256 => state = SHA256_INITIAL,
+            Algorithm::Md5 => state[..4].copy_from_slice(&MD5_INITIAL),
+            Algorithm::Streamed(name) => streamed = Some(FileDigest::new(name, 0))
+        }
+
+        Ok(MercyHasher { algorithm, state, pending: Vec::with_capacity(64), length: 0, streamed })
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        self.algorithm.name()
+    }
+
+    /// Bytes hashed so far
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        stats::count(Counter::BytesHashed, data.len() as u64);
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if let Some(digest) = self.streamed.as_mut() {
+            digest.update(data);
+            return;
+        }
+
+        if !self.pending.is_empty() {
+            let take = (64 - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+
+            if self.pending.len() < 64 {
+                return;
+            }
+
+            let block = std::mem::take(&mut self.pending);
+            self.compress(&block);
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    /// Lowercase hex digest of everything hashed so far; the hasher can carry on afterwards
+    pub fn finalize(&self) -> String {
+        if let Some(digest) = &self.streamed {
+            return digest.clone().finish();
+        }
+
+        let mut last = self.clone();
+        let bits = self.length.wrapping_mul(8);
+
+        // 0x80, zeros up to 56 bytes into the block, then the length in bits
+        let mut padding = vec![0x80u8];
+        padding.resize(1 + (55usize.wrapping_sub(self.pending.len()) % 64), 0);
+        padding.extend_from_slice(&match self.algorithm {
+            Algorithm::Md5 => bits.to_le_bytes(),
+            _ => bits.to_be_bytes()
+        });
+        last.absorb(&padding);
+
+        match self.algorithm {
+            Algorithm::Md5 => last.state[..4].iter().flat_map(|word| word.to_le_bytes()).map(|byte| format!("{:02x}", byte)).collect(),
+            _ => last.state.iter().map(|word| format!("{:08x}", word)).collect()
+        }
+    }
+
+    /// Whether `serialize_state` can save this hasher (one of `RESUMABLE_HASH_ALGORITHMS`)
+    pub fn resumable(&self) -> bool {
+        self.streamed.is_none()
+    }
+
+    /// The state as a versioned string for `resume`
+    pub fn serialize_state(&self) -> Result<String, MercyError> {
+        if !self.resumable() {
+            return Err(MercyError::invalid_input(format!("A {} hasher cannot be saved; only {} states resume", self.algorithm.name(), RESUMABLE_HASH_ALGORITHMS.join(" and "))));
+        }
+
+        let words: String = self.state[..self.algorithm.words()].iter().map(|word| format!("{:08x}", word)).collect();
+        let pending: String = self.pending.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(format!("{}/{}/{}/{}/{}/{}", STATE_TAG, STATE_VERSION, self.algorithm.name(), self.length, words, pending))
+    }
+
+    /// Continues from a `serialize_state` string, refusing one of another algorithm or format version
+    pub fn resume(algorithm: &str, state: &str) -> Result<MercyHasher, MercyError> {
+        let mut hasher = MercyHasher::new(algorithm)?;
+        if !hasher.resumable() {
+            return Err(MercyError::invalid_input(format!("A {} hasher cannot be resumed; only {} states resume", algorithm, RESUMABLE_HASH_ALGORITHMS.join(" and "))));
+        }
+        let fields: Vec<&str> = state.trim().split('/').collect();
+
+        if fields.len() != 6 || fields[0] != STATE_TAG {
+            return Err(MercyError::invalid_input("Not a saved hasher state".to_string()));
+        }
+        if fields[1] != STATE_VERSION.to_string() {
+            return Err(MercyError::invalid_input(format!("Hasher state version {} is not supported (expected {})", fields[1], STATE_VERSION)));
+        }
+        if fields[2] != hasher.algorithm.name() {
+            return Err(MercyError::invalid_input(format!("Hasher state is for {}, not {}", fields[2], hasher.algorithm.name())));
+        }
+
+        let length: u64 = fields[3].parse().map_err(|_| MercyError::invalid_input(format!("Invalid length '{}' in hasher state", fields[3])))?;
+        let words = hex_bytes(fields[4]).filter(|bytes| bytes.len() == hasher.algorithm.words() * 4).ok_or_else(|| MercyError::invalid_input("Invalid state words in hasher state".to_string()))?;
+        let pending = hex_bytes(fields[5]).filter(|bytes| bytes.len() < 64).ok_or_else(|| MercyError::invalid_input("Invalid pending bytes in hasher state".to_string()))?;
+        if length % 64 != pending.len() as u64 {
+            return Err(MercyError::invalid_input("Hasher state length does not match its pending bytes".to_string()));
+        }
+
+        for (word, bytes) in hasher.state.iter_mut().zip(words.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        hasher.pending = pending;
+        hasher.length = length;
+        Ok(hasher)
+    }
+
+    // Feeds padding without counting it as hashed data
+    fn absorb(&mut self, data: &[u8]) {
+        let mut data_with_pending = std::mem::take(&mut self.pending);
+        data_with_pending.extend_from_slice(data);
+        for block in data_with_pending.chunks_exact(64) {
+            self.compress(block);
+        }
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        match self.algorithm {
+            Algorithm::Md5 => {
+                let mut state = [self.state[0], self.state[1], self.state[2], self.state[3]];
+                md5_compress(&mut state, block);
+                self.state[..4].copy_from_slice(&state);
+            },
+            _ => sha256_compress(&mut self.state, block)
+        }
+    }
+}
+
+// Lets `io::copy` feed a reader straight in
+impl Write for MercyHasher {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.update(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len()).step_by(2).map(|index| text.get(index..index + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok())).collect()
+}
+
+// One 64-byte block (FIPS 180-4)
+fn sha256_compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut schedule = [0u32; 64];
+    for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    for index in 16..64 {
+        let s0 = schedule[index - 15].rotate_right(7) ^ schedule[index - 15].rotate_right(18) ^ (schedule[index - 15] >> 3);
+        let s1 = schedule[index - 2].rotate_right(17) ^ schedule[index - 2].rotate_right(19) ^ (schedule[index - 2] >> 10);
+        schedule[index] = schedule[index - 16].wrapping_add(s0).wrapping_add(schedule[index - 7]).wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for index in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let choice = (e & f) ^ (!e & g);
+        let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(SHA256_ROUNDS[index]).wrapping_add(schedule[index]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let majority = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(majority);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+// One 64-byte block (RFC 1321)
+fn md5_compress(state: &mut [u32; 4], block: &[u8]) {
+    let mut message = [0u32; 16];
+    for (word, bytes) in message.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+    for index in 0..64 {
+        let (mixed, word) = match index / 16 {
+            0 => ((b & c) | (!b & d), index),
+            1 => ((d & b) | (!d & c), (5 * index + 1) % 16),
+            2 => (b ^ c ^ d, (3 * index + 5) % 16),
+            _ => (c ^ (b | !d), (7 * index) % 16)
+        };
+
+        let shift = MD5_SHIFTS[(index / 16) * 4 + index % 4];
+        let rotated = a.wrapping_add(mixed).wrapping_add(MD5_ROUNDS[index]).wrapping_add(message[word]).rotate_left(shift);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 4231 section 4: key, data, then HMAC-SHA-256 and HMAC-SHA-512
+    fn rfc4231_cases() -> Vec<(Vec<u8>, Vec<u8>, &'static str, &'static str)> {
+        let long_key = vec![0xaa; 131];
+        vec![
+            (vec![0x0b; 20], b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854"),
+            (b"Jefe".to_vec(), b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"),
+            (vec![0xaa; 20], vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+                "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb"),
+            ((1..=25).collect(), vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+                "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3dba91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd"),
+            // Test case 5 only publishes the first 128 bits
+            (vec![0x0c; 20], b"Test With Truncation".to_vec(),
+                "a3b6167473100ee06e0c796c2955552b",
+                "415fad6271580a531d4179bc891d87a6"),
+            (long_key.clone(), b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598"),
+            (long_key, b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.".to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+                "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58")
+        ]
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        for (case, (key, data, sha256, sha512)) in rfc4231_cases().into_iter().enumerate() {
+            assert!(hex_encode(&hmac(HmacDigest::Sha256, &key, &data)).starts_with(sha256), "test case {}", case + 1);
+            assert!(hex_encode(&hmac(HmacDigest::Sha512, &key, &data)).starts_with(sha512), "test case {}", case + 1);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+/*
+    Security response header evaluation
+
+    Works on headers already collected from a response so that it can be used offline (pasted
+    headers, proxy logs) as well as by the HTTP fetching functions.
+*/
+
+use crate::finding::{Finding, Severity};
+
+// Minimum HSTS max-age considered adequate (180 days)
+const HSTS_MIN_MAX_AGE: u64 = 15_552_000;
+
+/// Evaluates Strict-Transport-Security, Content-Security-Policy, X-Frame-Options,
+/// X-Content-Type-Options and Referrer-Policy, returning a finding per missing or weak header
+pub fn audit_security_headers(target: &str, headers: &[(String, String)]) -> Vec<Finding> {
+    let lookup = |name: &str| {
+        headers.iter()
+            .filter(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+            .collect::<Vec<&str>>()
+    };
+
+    let mut findings = Vec::new();
+
+    // HSTS
+    match lookup("strict-transport-security").first() {
+        None => findings.push(Finding::new(
+            "headers.hsts-missing",
+            "Strict-Transport-Security header missing",
+            Severity::Medium,
+            target,
+            "No Strict-Transport-Security header in the response",
+            "Send Strict-Transport-Security: max-age=31536000; includeSubDomains over HTTPS"
+        )),
+        Some(value) => {
+            let max_age = value.split(';')
+                .filter_map(|directive| directive.trim().split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("max-age"))
+                .and_then(|(_, age)| age.trim().trim_matches('"').parse::<u64>().ok());
+
+            match max_age {
+                Some(age) if age >= HSTS_MIN_MAX_AGE => {}
+                Some(age) => findings.push(Finding::new(
+                    "headers.hsts-weak",
+                    "Strict-Transport-Security max-age is short",
+                    Severity::Low,
+                    target,
+                    &format!("Strict-Transport-Security: {} (max-age {} is below {})", value, age, HSTS_MIN_MAX_AGE),
+                    "Raise max-age to at least 15552000 seconds (180 days), ideally 31536000"
+                )),
+                None => findings.push(Finding::new(
+                    "headers.hsts-invalid",
+                    "Strict-Transport-Security header has no valid max-age",
+                    Severity::Medium,
+                    target,
+                    &format!("Strict-Transport-Security: {}", value),
+                    "Include a numeric max-age directive; browsers ignore the header without one"
+                ))
+            }
+        }
+    }
+
+    // CSP
+    let csp = lookup("content-security-policy");
+    match csp.first() {
+        None => findings.push(Finding::new(
+            "headers.csp-missing",
+            "Content-Security-Policy header missing",
+            Severity::Medium,
+            target,
+            "No Content-Security-Policy header in the response",
+            "Deploy a Content-Security-Policy restricting script-src, object-src and base-uri"
+        )),
+        Some(value) => {
+            let lowered = value.to_ascii_lowercase();
+            let mut weaknesses = Vec::new();
+
+            if lowered.contains("'unsafe-inline'") {
+                weaknesses.push("'unsafe-inline'");
+            }
+            if lowered.contains("'unsafe-eval'") {
+                weaknesses.push("'unsafe-eval'");
+            }
+            if lowered.split(';').any(|directive| {
+                let mut parts = directive.split_whitespace();
+                matches!(parts.next(), Some("default-src") | Some("script-src")) && parts.any(|source| source == "*")
+            }) {
+                weaknesses.push("wildcard script source");
+            }
+
+            if !weaknesses.is_empty() {
+                findings.push(Finding::new(
+                    "headers.csp-weak",
+                    "Content-Security-Policy allows unsafe script sources",
+                    Severity::Low,
+                    target,
+                    &format!("Content-Security-Policy contains {}: {}", weaknesses.join(", "), value),
+                    "Remove unsafe-inline/unsafe-eval and wildcards; use nonces or hashes for inline scripts"
+                ));
+            }
+        }
+    }
+
+    // Clickjacking protection may come from X-Frame-Options or CSP frame-ancestors
+    let frame_ancestors = csp.iter().any(|value| value.to_ascii_lowercase().contains("frame-ancestors"));
+    match lookup("x-frame-options").first() {
+        None if frame_ancestors => {}
+        None => findings.push(Finding::new(
+            "headers.xfo-missing",
+            "X-Frame-Options header missing",
+            Severity::Low,
+            target,
+            "No X-Frame-Options header and no CSP frame-ancestors directive",
+            "Send X-Frame-Options: DENY (or SAMEORIGIN) or a CSP frame-ancestors directive"
+        )),
+        Some(value) if value.eq_ignore_ascii_case("deny") || value.eq_ignore_ascii_case("sameorigin") => {}
+        Some(value) => findings.push(Finding::new(
+            "headers.xfo-weak",
+            "X-Frame-Options value is not enforced by browsers",
+            Severity::Low,
+            target,
+            &format!("X-Frame-Options: {}", value),
+            "Use DENY or SAMEORIGIN; ALLOW-FROM is obsolete, use CSP frame-ancestors instead"
+        ))
+    }
+
+    match lookup("x-content-type-options").first() {
+        None => findings.push(Finding::new(
+            "headers.xcto-missing",
+            "X-Content-Type-Options header missing",
+            Severity::Low,
+            target,
+            "No X-Content-Type-Options header in the response",
+            "Send X-Content-Type-Options: nosniff"
+        )),
+        Some(value) if value.eq_ignore_ascii_case("nosniff") => {}
+        Some(value) => findings.push(Finding::new(
+            "headers.xcto-weak",
+            "X-Content-Type-Options has an unexpected value",
+            Severity::Low,
+            target,
+            &format!("X-Content-Type-Options: {}", value),
+            "The only valid value is nosniff"
+        ))
+    }
+
+    // The last listed policy is the one browsers apply
+    let referrer = lookup("referrer-policy");
+    let effective = referrer.iter()
+        .flat_map(|value| value.split(','))
+        .map(|policy| policy.trim().to_ascii_lowercase())
+        .rfind(|policy| !policy.is_empty());
+
+    match effective.as_deref() {
+        None => findings.push(Finding::new(
+            "headers.referrer-policy-missing",
+            "Referrer-Policy header missing",
+            Severity::Info,
+            target,
+            "No Referrer-Policy header; browsers default to strict-origin-when-cross-origin",
+            "Send Referrer-Policy: strict-origin-when-cross-origin or no-referrer"
+        )),
+        Some(policy @ ("unsafe-url" | "no-referrer-when-downgrade")) => findings.push(Finding::new(
+            "headers.referrer-policy-weak",
+            "Referrer-Policy leaks full URLs",
+            Severity::Low,
+            target,
+            &format!("Referrer-Policy: {}", policy),
+            "Use strict-origin-when-cross-origin or no-referrer"
+        )),
+        Some(_) => {}
+    }
+
+    findings
+}
@@ -0,0 +1,299 @@
+/*
+    Byte-by-byte comparison of two files
+
+    Both files are read in step through buffered readers, so firmware images of hundreds of
+    megabytes are compared without holding either in memory. Differing bytes close enough to
+    share their context are grouped into one region, shown as the offset where the region
+    starts, the old bytes and the new bytes:
+
+        00001a28: 00 11 22 33 44 -> 00 11 aa 33 44  (1 byte differs)
+
+    with "context=" equal bytes kept on each side (4 by default). A region keeps at most
+    `HEXDIFF_REGION_BYTES` bytes of each file, and only the first "max_regions=" regions are
+    listed; differing bytes past those are still counted. When one file is longer the bytes
+    only it has are reported once, with where they start, so a file that is a prefix of the
+    other is a single line.
+*/
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read}
+};
+
+use serde::Serialize;
+
+use crate::{
+    hexdump::HEXDUMP_WIDTH,
+    stats::{self, Counter},
+    trailing_options
+};
+
+/// Most bytes of each file a region keeps for display
+pub const HEXDIFF_REGION_BYTES: usize = 256;
+
+/// Options for `mercy_hex_diff`
+#[derive(Debug, Clone)]
+pub struct HexDiffOptions {
+    /// Equal bytes shown on each side of a difference
+    pub context: usize,
+    /// Regions listed before the rest are only counted
+    pub max_regions: usize
+}
+
+impl Default for HexDiffOptions {
+    fn default() -> Self {
+        HexDiffOptions {
+            context: 4,
+            max_regions: 100
+        }
+    }
+}
+
+/// A run of differing bytes with its context
+#[derive(Debug, Clone, Serialize)]
+pub struct HexDiffRegion {
+    /// Where the region, context included, starts
+    pub offset: u64,
+    /// Bytes in the region, context included
+    pub length: u64,
+    /// Bytes in it that differ
+    pub differing: u64,
+    /// The first file's bytes as lowercase hex, at most `HEXDIFF_REGION_BYTES` of them
+    pub old: String,
+    /// The second file's bytes
+    pub new: String,
+    /// Whether the region is longer than the bytes kept
+    pub truncated: bool
+}
+
+/// Bytes only the longer file has
+#[derive(Debug, Clone, Serialize)]
+pub struct HexDiffTail {
+    /// The longer file
+    pub file: String,
+    pub offset: u64,
+    pub length: u64,
+    /// Its first bytes as lowercase hex, at most one dump line
+    pub first_bytes: String
+}
+
+/// How two files differ
+#[derive(Debug, Clone, Serialize)]
+pub struct HexDiff {
+    pub old_file: String,
+    pub new_file: String,
+    pub old_length: u64,
+    pub new_length: u64,
+    /// Differing bytes in the length both files have
+    pub differing_bytes: u64,
+    /// Regions in all, listed or not
+    pub region_count: u64,
+    /// The first `max_regions` regions
+    pub regions: Vec<HexDiffRegion>,
+    pub tail: Option<HexDiffTail>
+}
+
+impl HexDiff {
+    pub fn identical(&self) -> bool {
+        self.differing_bytes == 0 && self.tail.is_none()
+    }
+}
+
+impl fmt::Display for HexDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.identical() {
+            return write!(f, "Files are identical ({} bytes)", self.old_length);
+        }
+
+        for region in &self.regions {
+            let more = if region.truncated { " ..." } else { "" };
+            writeln!(f, "{:08x}: {}{} -> {}{}  ({} {} {})", region.offset, spaced(&region.old), more, spaced(&region.new), more,
+                region.differing, if region.differing == 1 { "byte" } else { "bytes" }, if region.differing == 1 { "differs" } else { "differ" })?;
+        }
+        if self.region_count > self.regions.len() as u64 {
+            writeln!(f, "... {} more regions", self.region_count - self.regions.len() as u64)?;
+        }
+        if let Some(tail) = &self.tail {
+            let more = if tail.length > HEXDUMP_WIDTH as u64 { " ..." } else { "" };
+            writeln!(f, "{:08x}: {} more bytes only in {}: {}{}", tail.offset, tail.length, tail.file, spaced(&tail.first_bytes), more)?;
+        }
+
+        match self.differing_bytes {
+            0 => write!(f, "No bytes differ in the {} both files have", self.old_length.min(self.new_length))?,
+            1 => write!(f, "1 byte differs")?,
+            bytes => write!(f, "{} bytes differ in {} {}", bytes, self.region_count, if self.region_count == 1 { "region" } else { "regions" })?
+        }
+        match &self.tail {
+            Some(tail) if self.differing_bytes == 0 => {
+                let shorter = if tail.file == self.old_file { &self.new_file } else { &self.old_file };
+                write!(f, "; lengths differ ({} vs {} bytes), {} is a prefix of {}", self.old_length, self.new_length, shorter, tail.file)
+            },
+            Some(_) => write!(f, "; lengths differ ({} vs {} bytes)", self.old_length, self.new_length),
+            None => write!(f, "; same length ({} bytes)", self.old_length)
+        }
+    }
+}
+
+// Region being built while the files are read
+struct OpenRegion {
+    offset: u64,
+    length: u64,
+    differing: u64,
+    // Equal bytes since the last difference
+    since_difference: u64,
+    bytes: Vec<(u8, u8)>
+}
+
+/// Compares `old_file` with `new_file` byte by byte, reading both as streams, and returns the regions that differ
+pub fn mercy_hex_diff(old_file: &str, new_file: &str, options: &HexDiffOptions) -> Result<HexDiff, String> {
+    let open = |path: &str| File::open(path).map(|file| BufReader::with_capacity(64 * 1024, file)).map_err(|e| format!("Unable to open {}: {}", path, e));
+    let mut old = open(old_file)?;
+    let mut new = open(new_file)?;
+    let failed = |path: &str, e: io::Error| format!("Unable to read {}: {}", path, e);
+
+    let context = options.context as u64;
+    let mut diff = HexDiff {
+        old_file: old_file.to_string(),
+        new_file: new_file.to_string(),
+        old_length: 0,
+        new_length: 0,
+        differing_bytes: 0,
+        region_count: 0,
+        regions: Vec::new(),
+        tail: None
+    };
+    // The last `context` bytes read, the leading context of a region opened at the next one
+    let mut recent: VecDeque<(u8, u8)> = VecDeque::with_capacity(options.context);
+    let mut open_region: Option<OpenRegion> = None;
+    let mut offset: u64 = 0;
+
+    loop {
+        let old_bytes = old.fill_buf().map_err(|e| failed(old_file, e))?;
+        let new_bytes = new.fill_buf().map_err(|e| failed(new_file, e))?;
+        let count = old_bytes.len().min(new_bytes.len());
+        if count == 0 {
+            break;
+        }
+
+        for (&a, &b) in old_bytes[..count].iter().zip(&new_bytes[..count]) {
+            let differs = a != b;
+            match open_region.as_mut() {
+                Some(region) => {
+                    region.length += 1;
+                    if region.bytes.len() < HEXDIFF_REGION_BYTES {
+                        region.bytes.push((a, b));
+                    }
+                    match differs {
+                        true => {
+                            region.differing += 1;
+                            region.since_difference = 0;
+                        },
+                        false => region.since_difference += 1
+                    }
+                },
+                None if differs => {
+                    let leading = recent.len() as u64;
+                    let mut bytes: Vec<(u8, u8)> = recent.iter().copied().collect();
+                    bytes.push((a, b));
+                    open_region = Some(OpenRegion { offset: offset - leading, length: leading + 1, differing: 1, since_difference: 0, bytes });
+                },
+                None => {}
+            }
+            if options.context > 0 {
+                if recent.len() == options.context {
+                    recent.pop_front();
+                }
+                recent.push_back((a, b));
+            }
+
+            // Once the equal run is too long to share context with a later difference, the region
+            // ends `context` bytes after its last difference; the last `context` bytes of the run
+            // are then the leading context of the next region
+            if open_region.as_ref().is_some_and(|region| region.since_difference > 2 * context) {
+                if let Some(region) = open_region.take() {
+                    let unused = region.since_difference - context;
+                    close_region(&mut diff, region, unused, options);
+                }
+            }
+            offset += 1;
+        }
+
+        old.consume(count);
+        new.consume(count);
+    }
+
+    if let Some(region) = open_region.take() {
+        let unused = region.since_difference.saturating_sub(context);
+        close_region(&mut diff, region, unused, options);
+    }
+
+    // Whatever is left of the longer file
+    let (longer, longer_file) = match old.fill_buf().map_err(|e| failed(old_file, e))?.is_empty() {
+        true => (&mut new as &mut BufReader<File>, new_file),
+        false => (&mut old as &mut BufReader<File>, old_file)
+    };
+    let mut first = Vec::with_capacity(HEXDUMP_WIDTH);
+    longer.by_ref().take(HEXDUMP_WIDTH as u64).read_to_end(&mut first).map_err(|e| failed(longer_file, e))?;
+    let rest = io::copy(longer, &mut io::sink()).map_err(|e| failed(longer_file, e))?;
+    let tail_length = first.len() as u64 + rest;
+
+    diff.old_length = offset;
+    diff.new_length = offset;
+    if tail_length > 0 {
+        match longer_file == old_file {
+            true => diff.old_length += tail_length,
+            false => diff.new_length += tail_length
+        }
+        diff.tail = Some(HexDiffTail { file: longer_file.to_string(), offset, length: tail_length, first_bytes: hex(first.iter()) });
+    }
+
+    stats::count(Counter::BytesHexDumped, diff.old_length + diff.new_length);
+    Ok(diff)
+}
+
+// Drops the `unused` equal bytes at the end of a finished region and records it
+fn close_region(diff: &mut HexDiff, region: OpenRegion, unused: u64, options: &HexDiffOptions) {
+    diff.differing_bytes += region.differing;
+    diff.region_count += 1;
+    if diff.regions.len() >= options.max_regions {
+        return;
+    }
+
+    let length = region.length - unused;
+    let kept = &region.bytes[..region.bytes.len().min(length as usize)];
+    diff.regions.push(HexDiffRegion {
+        offset: region.offset,
+        length,
+        differing: region.differing,
+        old: hex(kept.iter().map(|(a, _)| a)),
+        new: hex(kept.iter().map(|(_, b)| b)),
+        truncated: (kept.len() as u64) < length
+    });
+}
+
+fn hex<'a>(bytes: impl Iterator<Item = &'a u8>) -> String {
+    bytes.map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// "0011aa" as "00 11 aa"
+fn spaced(hex: &str) -> String {
+    hex.as_bytes().chunks(2).map(|pair| String::from_utf8_lossy(pair)).collect::<Vec<_>>().join(" ")
+}
+
+// Parses "old,new [context=4] [max_regions=100]"
+pub(crate) fn hex_diff_from_args(input: &str) -> Result<HexDiff, String> {
+    let (files, pairs) = trailing_options(input, &["context", "max_regions"]);
+    let mut options = HexDiffOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "context" => options.context = value.parse().map_err(|_| format!("Invalid context '{}'", value))?,
+            _ => options.max_regions = value.parse().map_err(|_| format!("Invalid max_regions '{}'", value))?
+        }
+    }
+
+    let (old_file, new_file) = files.split_once(',').ok_or_else(|| format!("Expected \"old_file,new_file\", got '{}'", files))?;
+    mercy_hex_diff(old_file.trim(), new_file.trim(), &options)
+}
@@ -0,0 +1,63 @@
+/*
+    xxd-style hex dumps
+
+    Each line is the offset as 8 hex digits, up to 16 bytes in lowercase hex in groups of
+    two, and the same bytes as ASCII with anything outside the printable range shown as '.',
+    the layout `xxd` prints, so a dump can be diffed against `xxd file`. Dumps are returned
+    as strings; nothing is written to stdout. The same lines are also available as rows for
+    JSON output.
+*/
+
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+/// Bytes shown on each line of a dump
+pub const HEXDUMP_WIDTH: usize = 16;
+
+// Characters taken by the hex column of a full line: 8 groups of 4 digits and the spaces between them
+const HEX_COLUMN: usize = HEXDUMP_WIDTH * 2 + HEXDUMP_WIDTH / 2 - 1;
+
+/// xxd-style dump of `bytes`, offsets counted from `start`, each line ending in a newline (empty input gives an empty dump)
+pub fn hexdump_bytes(bytes: &[u8], start: u64) -> String {
+    let lines = bytes.len().div_ceil(HEXDUMP_WIDTH);
+    let mut output = String::with_capacity(lines * (10 + HEX_COLUMN + 2 + HEXDUMP_WIDTH + 1));
+
+    for (index, chunk) in bytes.chunks(HEXDUMP_WIDTH).enumerate() {
+        let mut hex = String::with_capacity(HEX_COLUMN);
+        for (position, byte) in chunk.iter().enumerate() {
+            if position > 0 && position % 2 == 0 {
+                hex.push(' ');
+            }
+            let _ = write!(hex, "{:02x}", byte);
+        }
+
+        let offset = start + (index * HEXDUMP_WIDTH) as u64;
+        let _ = writeln!(output, "{:08x}: {:<width$}  {}", offset, hex, ascii_column(chunk), width = HEX_COLUMN);
+    }
+
+    output
+}
+
+/// One line of a dump
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HexRow {
+    pub offset: u64,
+    /// Up to 16 bytes as lowercase hex, without spaces
+    pub bytes: String,
+    /// The same bytes as ASCII, unprintable ones as '.'
+    pub ascii: String
+}
+
+/// The lines of `hexdump_bytes` as rows, offsets counted from `start`
+pub fn hexdump_rows(bytes: &[u8], start: u64) -> Vec<HexRow> {
+    bytes.chunks(HEXDUMP_WIDTH).enumerate().map(|(index, chunk)| HexRow {
+        offset: start + (index * HEXDUMP_WIDTH) as u64,
+        bytes: chunk.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        ascii: ascii_column(chunk)
+    }).collect()
+}
+
+fn ascii_column(chunk: &[u8]) -> String {
+    chunk.iter().map(|byte| if (0x20..=0x7e).contains(byte) { *byte as char } else { '.' }).collect()
+}
@@ -0,0 +1,187 @@
+/*
+    Homograph checks of domain names
+
+    A look-alike domain swaps letters for ones from another script that render the same:
+    "pаypal.com" with a Cyrillic а mixes two scripts in one label, and "аррӏе.com" is written
+    entirely in Cyrillic letters that each pass for Latin. Each label is decoded from punycode
+    if needed and checked for both: letters from more than one script (outside the mixes real
+    text uses, as `mercy_unicode_inspect` judges them) and letters that look like a Latin
+    letter or digit. The look-alikes are replaced to give the name the label passes for, so
+    the report can say which brand is imitated.
+
+    The table covers the Cyrillic, Greek, Armenian and Latin letters most used in look-alike
+    domains and the fullwidth forms. A label in a single non-Latin script that is not made
+    only of look-alikes ("пример", "δοκιμή") is ordinary IDN text and is not flagged.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    punycode::{punycode_decode, punycode_encode},
+    unicode::{codepoint, letter_scripts, mixes_scripts, name, script_of_letter}
+};
+
+// Characters that render like a lowercase Latin letter, and what they pass for; letters that
+// only resemble a capital or a small capital (Cyrillic т, н, к) are left out, or ordinary
+// Russian words would read as Latin
+const CONFUSABLES: &[(char, char)] = &[
+    // Cyrillic
+    ('\u{0430}', 'a'), ('\u{0435}', 'e'), ('\u{043E}', 'o'), ('\u{0440}', 'p'), ('\u{0441}', 'c'),
+    ('\u{0443}', 'y'), ('\u{0445}', 'x'), ('\u{0455}', 's'), ('\u{0456}', 'i'), ('\u{0458}', 'j'),
+    ('\u{04BB}', 'h'), ('\u{04CF}', 'l'), ('\u{0501}', 'd'), ('\u{051B}', 'q'), ('\u{051D}', 'w'),
+    ('\u{04AF}', 'y'),
+    // Greek
+    ('\u{03B1}', 'a'), ('\u{03BF}', 'o'), ('\u{03C1}', 'p'), ('\u{03BD}', 'v'), ('\u{03B9}', 'i'),
+    ('\u{03BA}', 'k'), ('\u{03C5}', 'u'), ('\u{03C7}', 'x'), ('\u{03F2}', 'c'), ('\u{03F3}', 'j'),
+    // Armenian
+    ('\u{0585}', 'o'), ('\u{057D}', 'u'), ('\u{0570}', 'h'), ('\u{0581}', 'g'), ('\u{0566}', 'q'),
+    ('\u{0561}', 'w'), ('\u{0578}', 'n'),
+    // Latin letters outside ASCII
+    ('\u{0131}', 'i'), ('\u{0269}', 'i'), ('\u{0251}', 'a'), ('\u{0261}', 'g'), ('\u{0237}', 'j'),
+    ('\u{01C0}', 'l'), ('\u{2113}', 'l'), ('\u{0280}', 'r'), ('\u{1D0F}', 'o'), ('\u{1D04}', 'c'),
+    ('\u{1D20}', 'v'), ('\u{1D21}', 'w'), ('\u{1D22}', 'z')
+];
+
+/// A character that renders like an ASCII one
+#[derive(Debug, Clone, Serialize)]
+pub struct Confusable {
+    pub character: char,
+    /// "U+0430"
+    pub codepoint: String,
+    pub name: Option<String>,
+    pub script: String,
+    pub looks_like: char
+}
+
+/// One label of a domain checked for look-alike characters
+#[derive(Debug, Clone, Serialize)]
+pub struct LabelCheck {
+    /// The label as it reads (punycode decoded)
+    pub label: String,
+    /// Scripts of its letters, Common characters (digits, hyphens) left out
+    pub scripts: Vec<String>,
+    /// Letters from more than one script outside the combinations real text uses
+    pub mixed_script: bool,
+    /// Every letter is a look-alike of a Latin letter, from a script that is not Latin
+    pub whole_script_confusable: bool,
+    pub confusables: Vec<Confusable>,
+    /// The label with each look-alike replaced by what it passes for ("apple")
+    pub looks_like: String,
+    pub suspicious: bool,
+    /// Why the label is suspicious, empty when it is not
+    pub reasons: Vec<String>
+}
+
+/// A domain checked label by label for homograph tricks
+#[derive(Debug, Clone, Serialize)]
+pub struct HomographReport {
+    pub domain: String,
+    /// The domain as it reads (punycode decoded)
+    pub unicode: String,
+    /// The domain in punycode, as DNS and logs carry it
+    pub ascii: String,
+    /// The ASCII name the domain passes for, when it has look-alikes
+    pub looks_like: Option<String>,
+    pub suspicious: bool,
+    pub labels: Vec<LabelCheck>
+}
+
+impl fmt::Display for HomographReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.unicode)?;
+        if self.ascii != self.unicode {
+            write!(f, " ({})", self.ascii)?;
+        }
+        if !self.suspicious {
+            return write!(f, ": no look-alike characters or mixed scripts");
+        }
+
+        write!(f, ": SUSPICIOUS")?;
+        if let Some(looks_like) = &self.looks_like {
+            write!(f, ", passes for {}", looks_like)?;
+        }
+        for label in self.labels.iter().filter(|label| label.suspicious) {
+            write!(f, "\n{}: {}", label.label, label.reasons.join("; "))?;
+            for confusable in &label.confusables {
+                write!(f, "\n  {} {} ({}) looks like '{}'", confusable.codepoint, confusable.name.as_deref().unwrap_or("unnamed"), confusable.script, confusable.looks_like)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks each label of a domain (Unicode or punycode) for mixed scripts and characters that pass for Latin letters
+pub fn mercy_homograph_check(domain: &str) -> Result<HomographReport, String> {
+    let domain = domain.trim().trim_end_matches('.');
+    if domain.is_empty() {
+        return Err("No domain given".to_string());
+    }
+
+    let unicode = punycode_decode(domain)?;
+    let ascii = punycode_encode(&unicode)?;
+    let labels: Vec<LabelCheck> = unicode.split('.').map(check_label).collect();
+
+    let suspicious = labels.iter().any(|label| label.suspicious);
+    let looks_like = labels.iter().any(|label| !label.confusables.is_empty())
+        .then(|| labels.iter().map(|label| label.looks_like.as_str()).collect::<Vec<_>>().join("."));
+
+    Ok(HomographReport { domain: domain.to_string(), unicode, ascii, looks_like, suspicious, labels })
+}
+
+fn check_label(label: &str) -> LabelCheck {
+    let chars: Vec<char> = label.chars().flat_map(char::to_lowercase).collect();
+    let counts = letter_scripts(&chars);
+    let mixed_script = mixes_scripts(&counts);
+
+    let confusables: Vec<Confusable> = chars.iter()
+        .filter_map(|c| look_alike(*c).map(|looks_like| Confusable {
+            character: *c,
+            codepoint: codepoint(*c),
+            name: name(*c),
+            script: script_of_letter(*c).to_string(),
+            looks_like
+        }))
+        .collect();
+    let looks_like: String = chars.iter().map(|c| look_alike(*c).unwrap_or(*c)).collect();
+
+    let letters = chars.iter().filter(|c| c.is_alphabetic()).count();
+    let whole_script_confusable = counts.len() == 1 && !counts.contains_key("Latin") && letters > 0 && confusables.len() == letters;
+    let latin_look_alikes: Vec<&Confusable> = confusables.iter().filter(|confusable| confusable.script == "Latin" || confusable.script == "Common").collect();
+
+    let mut reasons = Vec::new();
+    if mixed_script {
+        reasons.push(format!("mixes {} letters", counts.keys().copied().collect::<Vec<_>>().join(" and ")));
+    }
+    if whole_script_confusable {
+        let script = counts.keys().next().copied().unwrap_or_default();
+        reasons.push(format!("written entirely in {} letters that look like Latin ones", script));
+    }
+    if !latin_look_alikes.is_empty() && !mixed_script {
+        reasons.push(format!("has {} non-ASCII look-alike{} of ASCII", latin_look_alikes.len(), if latin_look_alikes.len() == 1 { "" } else { "s" }));
+    }
+    if !reasons.is_empty() && looks_like != chars.iter().collect::<String>() {
+        reasons.push(format!("reads as \"{}\"", looks_like));
+    }
+
+    LabelCheck {
+        label: label.to_string(),
+        scripts: counts.keys().map(|script| script.to_string()).collect(),
+        mixed_script,
+        whole_script_confusable,
+        confusables,
+        looks_like,
+        suspicious: !reasons.is_empty(),
+        reasons
+    }
+}
+
+// The ASCII character `c` passes for, if it is a look-alike (fullwidth forms included)
+fn look_alike(c: char) -> Option<char> {
+    match c as u32 {
+        0xFF10..=0xFF19 => char::from_u32(c as u32 - 0xFF10 + '0' as u32),
+        0xFF41..=0xFF5A => char::from_u32(c as u32 - 0xFF41 + 'a' as u32),
+        _ => CONFUSABLES.iter().find(|(confusable, _)| *confusable == c).map(|(_, ascii)| *ascii)
+    }
+}
@@ -0,0 +1,143 @@
+/*
+    Host details for `system_info` beyond what sys-info reports directly
+
+    Memory comes from sys-info in KiB and is given in bytes. Uptime is read from /proc/uptime
+    on Linux and worked out from the kernel's boot time on the BSDs and macOS. Disk usage is
+    that of the root filesystem from statvfs, counting only the space an unprivileged user
+    may still fill as free, as `df` does. The user is the effective user's login name, or the
+    environment's name for it where there is no account database.
+
+    Each lookup fails with a reason instead of panicking, so a platform or container that
+    lacks one metric still reports the others.
+*/
+
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use crate::timefmt::unix_to_rfc3339;
+
+/// Physical memory in bytes
+pub(crate) struct Memory {
+    pub total: u64,
+    pub free: u64,
+    /// Free plus what the kernel can reclaim from caches
+    pub available: u64
+}
+
+/// Size of the root filesystem in bytes
+pub(crate) struct Disk {
+    pub total: u64,
+    pub used: u64,
+    /// Space an unprivileged user may still use (blocks reserved for root are neither used nor free)
+    pub free: u64
+}
+
+impl Disk {
+    /// Used share of the space users can have, as `df` gives it
+    pub fn percent_used(&self) -> f64 {
+        match self.used + self.free {
+            0 => 0.0,
+            usable => self.used as f64 * 100.0 / usable as f64
+        }
+    }
+}
+
+pub(crate) fn memory() -> Result<Memory, String> {
+    let info = sys_info::mem_info().map_err(|e| e.to_string())?;
+    Ok(Memory { total: info.total * 1024, free: info.free * 1024, available: info.avail * 1024 })
+}
+
+// Seconds since boot
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn uptime() -> Result<u64, String> {
+    let text = std::fs::read_to_string("/proc/uptime").map_err(|e| format!("Unable to read /proc/uptime: {}", e))?;
+    text.split_whitespace().next()
+        .and_then(|seconds| seconds.parse::<f64>().ok())
+        .map(|seconds| seconds as u64)
+        .ok_or_else(|| format!("Unreadable /proc/uptime: {:?}", text.trim()))
+}
+
+#[cfg(any(target_vendor = "apple", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd"))]
+pub(crate) fn uptime() -> Result<u64, String> {
+    let boot = sys_info::boottime().map_err(|e| e.to_string())?;
+    Ok(now().saturating_sub(boot.tv_sec as u64))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_vendor = "apple", target_os = "freebsd", target_os = "openbsd", target_os = "netbsd")))]
+pub(crate) fn uptime() -> Result<u64, String> {
+    Err("Uptime is not available on this platform".to_string())
+}
+
+// RFC 3339 UTC time of the boot `uptime` seconds ago
+pub(crate) fn boot_time(uptime: u64) -> String {
+    unix_to_rfc3339(now().saturating_sub(uptime) as i64, 0)
+}
+
+#[cfg(unix)]
+pub(crate) fn root_disk() -> Result<Disk, String> {
+    // statvfs fills the structure it is given and keeps no pointer to it
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c"/".as_ptr(), &mut stats) } != 0 {
+        return Err(format!("Unable to read the root filesystem: {}", std::io::Error::last_os_error()));
+    }
+
+    // The counters are 32-bit on some platforms and 64-bit on others
+    fn widen(count: impl Into<u64>) -> u64 {
+        count.into()
+    }
+
+    let block = widen(stats.f_frsize);
+    let blocks = widen(stats.f_blocks);
+    Ok(Disk { total: blocks * block, used: blocks.saturating_sub(widen(stats.f_bfree)) * block, free: widen(stats.f_bavail) * block })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn root_disk() -> Result<Disk, String> {
+    Err("Disk usage is not available on this platform".to_string())
+}
+
+// The effective user's login name; a uid with no account falls back to $USER, then to "uid N"
+#[cfg(unix)]
+pub(crate) fn current_user() -> Result<String, String> {
+    let uid = unsafe { libc::geteuid() };
+    let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut found = std::ptr::null_mut();
+
+    // getpwuid_r writes the entry's strings into `buffer`, which outlives the read of pw_name
+    let status = unsafe { libc::getpwuid_r(uid, &mut entry, buffer.as_mut_ptr(), buffer.len(), &mut found) };
+    if status == 0 && !found.is_null() && !entry.pw_name.is_null() {
+        let name = unsafe { std::ffi::CStr::from_ptr(entry.pw_name) }.to_string_lossy().into_owned();
+        if !name.is_empty() {
+            return Ok(name);
+        }
+    }
+
+    Ok(environment_user().unwrap_or_else(|| format!("uid {}", uid)))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn current_user() -> Result<String, String> {
+    environment_user().ok_or_else(|| "Neither USERNAME nor USER is set".to_string())
+}
+
+fn environment_user() -> Option<String> {
+    ["USER", "LOGNAME", "USERNAME"].iter().find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+/// Bytes as GiB with two decimals ("15.52 GiB")
+pub(crate) fn gib(bytes: u64) -> String {
+    format!("{:.2} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+/// Seconds as days, hours and minutes ("3 days, 4 hours, 1 minute")
+pub(crate) fn days_hours_minutes(seconds: u64) -> String {
+    let count = |value: u64, unit: &str| format!("{} {}{}", value, unit, if value == 1 { "" } else { "s" });
+    format!("{}, {}, {}", count(seconds / 86_400, "day"), count(seconds % 86_400 / 3600, "hour"), count(seconds % 3600 / 60, "minute"))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,272 @@
+/*
+    HSTS preload eligibility
+
+    Checks a domain against the hstspreload.org submission rules that can be seen from
+    outside: a valid certificate on https://domain/, a Strict-Transport-Security header on
+    that response (even when it is a redirect) with max-age of at least a year,
+    includeSubDomains and preload, plain HTTP redirecting to HTTPS on the same host before
+    going anywhere else, and the domain being the registrable one. Redirects are followed by
+    hand so the whole chain can be shown as evidence, each request retried on rate limits,
+    server errors and timeouts like other lookups (see net.rs). Whether every subdomain
+    serves HTTPS, the remaining rule, is not checked.
+*/
+
+use std::{
+    fmt,
+    time::Duration
+};
+
+use reqwest::{Url, redirect::Policy};
+use serde::Serialize;
+
+use crate::{
+    config,
+    domaincat::suffix_labels,
+    finding::{Finding, Severity},
+    http::{HttpIdentity, IDENTITY_KEYS, http_client_builder, identity_option},
+    net::{self, KEEP_LAST},
+    runtime,
+    trailing_options
+};
+
+/// Shortest max-age the preload list accepts (one year)
+pub const PRELOAD_MIN_MAX_AGE: u64 = 31_536_000;
+
+/// Options for `mercy_hsts_check`
+#[derive(Debug, Clone)]
+pub struct HstsOptions {
+    /// Per request
+    pub timeout: Duration,
+    /// Redirects followed from http:// before giving up
+    pub max_redirects: usize,
+    pub identity: HttpIdentity
+}
+
+impl Default for HstsOptions {
+    fn default() -> Self {
+        HstsOptions {
+            timeout: config::read_timeout(Duration::from_secs(10)),
+            max_redirects: 10,
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// One response of a redirect chain
+#[derive(Debug, Clone, Serialize)]
+pub struct HstsHop {
+    pub url: String,
+    pub status: u16,
+    /// Where a redirect points, resolved against `url`
+    pub location: Option<String>,
+    pub hsts: Option<String>
+}
+
+/// One preload rule and whether the domain meets it
+#[derive(Debug, Clone, Serialize)]
+pub struct HstsCriterion {
+    pub name: String,
+    pub passed: bool,
+    pub evidence: String
+}
+
+/// Preload eligibility of a domain, criterion by criterion
+#[derive(Debug, Clone, Serialize)]
+pub struct HstsReport {
+    pub domain: String,
+    /// The Strict-Transport-Security header of https://domain/
+    pub header: Option<String>,
+    pub max_age: Option<u64>,
+    pub include_subdomains: bool,
+    pub preload: bool,
+    /// The response of https://domain/, not followed
+    pub https: HstsHop,
+    /// Responses from http://domain/, following redirects
+    pub http: Vec<HstsHop>,
+    pub criteria: Vec<HstsCriterion>,
+    pub eligible: bool,
+    /// The overall result
+    pub findings: Vec<Finding>
+}
+
+impl fmt::Display for HstsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "HSTS preload check for {}: {}", self.domain, if self.eligible { "eligible" } else { "not eligible" })?;
+        writeln!(f, "Header: {}", self.header.as_deref().unwrap_or("(none)"))?;
+        writeln!(f, "HTTP redirects: {}", chain(&self.http))?;
+
+        for criterion in &self.criteria {
+            writeln!(f, "[{}] {}: {}", if criterion.passed { "PASS" } else { "FAIL" }, criterion.name, criterion.evidence)?;
+        }
+        Ok(())
+    }
+}
+
+// "http://a/ (301) -> https://a/ (200)"
+fn chain(hops: &[HstsHop]) -> String {
+    if hops.is_empty() {
+        return "(no response)".to_string();
+    }
+    hops.iter().map(|hop| format!("{} ({})", hop.url, hop.status)).collect::<Vec<String>>().join(" -> ")
+}
+
+/// Fetches https://domain/ and http://domain/ and checks the domain against the HSTS preload rules
+pub fn mercy_hsts_check(domain: &str, options: &HstsOptions) -> Result<HstsReport, String> {
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.split("://").last().unwrap_or("").split(['/', ':']).next().unwrap_or("").to_string();
+    if domain.is_empty() || !domain.contains('.') {
+        return Err(format!("Invalid domain '{}'", domain));
+    }
+
+    let client = http_client_builder(&options.identity)?
+        .redirect(Policy::none())
+        .timeout(options.timeout)
+        .build()
+        .map_err(|e| format!("Unable to build HTTP client: {}", e))?;
+
+    runtime::block_on(async {
+        // A name that does not resolve fails both fetches; say so once
+        let _ = tokio::time::timeout(options.timeout, tokio::net::lookup_host((domain.as_str(), 443)))
+            .await
+            .map_err(|_| format!("Resolving {} timed out", domain))?
+            .map_err(|e| format!("{} does not resolve: {}", domain, e))?;
+
+        let https = fetch(&client, &format!("https://{}/", domain)).await
+            .map_err(|e| format!("HTTPS request to {} failed (an invalid certificate or nothing listening on port 443): {}", domain, e))?;
+        let http = follow(&client, &format!("http://{}/", domain), options.max_redirects).await?;
+
+        Ok(evaluate(&domain, https, http))
+    })
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<HstsHop, String> {
+    let response = net::send_with(client.get(url), &KEEP_LAST).await?.response;
+    let location = response.headers().get(reqwest::header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|location| Url::parse(url).ok()?.join(location).ok())
+        .map(|location| location.to_string());
+    // Browsers only honour the first header
+    let hsts = response.headers().get(reqwest::header::STRICT_TRANSPORT_SECURITY)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string());
+
+    Ok(HstsHop { url: url.to_string(), status: response.status().as_u16(), location, hsts })
+}
+
+// The redirect chain from `url`; nothing listening gives an empty chain
+async fn follow(client: &reqwest::Client, url: &str, max_redirects: usize) -> Result<Vec<HstsHop>, String> {
+    let mut hops: Vec<HstsHop> = Vec::new();
+    let mut next = Some(url.to_string());
+
+    while let Some(url) = next.take() {
+        if hops.iter().any(|hop| hop.url == url) {
+            return Err(format!("Redirect loop: {} -> {}", chain(&hops), url));
+        }
+        if hops.len() > max_redirects {
+            return Err(format!("More than {} redirects: {}", max_redirects, chain(&hops)));
+        }
+
+        let hop = match fetch(client, &url).await {
+            Ok(hop) => hop,
+            Err(_) if hops.is_empty() => return Ok(hops),
+            Err(e) => return Err(format!("Following redirects from {} failed at {}: {}", hops[0].url, url, e))
+        };
+        if (300..400).contains(&hop.status) {
+            next = hop.location.clone();
+        }
+        hops.push(hop);
+    }
+
+    Ok(hops)
+}
+
+fn evaluate(domain: &str, https: HstsHop, http: Vec<HstsHop>) -> HstsReport {
+    let header = https.hsts.clone();
+    let directives: Vec<(String, Option<String>)> = header.as_deref().unwrap_or("")
+        .split(';')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), Some(value.trim().trim_matches('"').to_string())),
+            None => (directive.to_ascii_lowercase(), None)
+        })
+        .collect();
+    let max_age = directives.iter().find(|(name, _)| name == "max-age").and_then(|(_, value)| value.as_deref()?.parse::<u64>().ok());
+    let include_subdomains = directives.iter().any(|(name, _)| name == "includesubdomains");
+    let preload = directives.iter().any(|(name, _)| name == "preload");
+
+    let mut criteria = Vec::new();
+    let mut criterion = |name: &str, passed: bool, evidence: String| criteria.push(HstsCriterion { name: name.to_string(), passed, evidence });
+    let shown = header.as_deref().map(|header| format!("Strict-Transport-Security: {}", header)).unwrap_or_else(|| "no Strict-Transport-Security header".to_string());
+
+    criterion("https", true, format!("https://{}/ answered {} with a valid certificate", domain, https.status));
+    criterion("header", header.is_some(), format!("{} on https://{}/ ({})", shown, domain, https.status));
+    criterion("max-age", max_age.is_some_and(|age| age >= PRELOAD_MIN_MAX_AGE), match max_age {
+        Some(age) => format!("max-age={} (at least {} needed)", age, PRELOAD_MIN_MAX_AGE),
+        None => format!("no valid max-age ({})", shown)
+    });
+    criterion("includeSubDomains", include_subdomains, shown.clone());
+    criterion("preload", preload, shown.clone());
+
+    // The first redirect must stay on the host and switch to HTTPS, so the header is seen before going elsewhere
+    let first = http.first().and_then(|hop| hop.location.as_deref()).and_then(|location| Url::parse(location).ok());
+    let (redirect_ok, redirect_evidence) = match (http.first(), first) {
+        (None, _) => (true, format!("nothing listening on http://{}/", domain)),
+        (Some(_), Some(location)) if location.scheme() == "https" && location.host_str() == Some(domain) => (true, chain(&http)),
+        (Some(_), Some(location)) => (false, format!("first redirect goes to {} instead of https://{}/: {}", location, domain, chain(&http))),
+        (Some(hop), None) => (false, format!("http://{}/ answers {} without redirecting to HTTPS", domain, hop.status))
+    };
+    criterion("http-redirect", redirect_ok, redirect_evidence);
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let base = labels[labels.len().saturating_sub(suffix_labels(&labels) + 1)..].join(".");
+    criterion("base-domain", base == domain, if base == domain {
+        format!("{} is a registrable domain", domain)
+    } else {
+        format!("{} is a subdomain; only {} can be submitted", domain, base)
+    });
+
+    let eligible = criteria.iter().all(|criterion| criterion.passed);
+    let failed: Vec<&str> = criteria.iter().filter(|criterion| !criterion.passed).map(|criterion| criterion.name.as_str()).collect();
+    let finding = if eligible {
+        Finding::new("hsts.preload-eligible", "Domain meets the HSTS preload requirements", Severity::Info, domain, &shown, "Submit the domain at https://hstspreload.org once every subdomain serves HTTPS")
+    } else {
+        Finding::new(
+            "hsts.preload-ineligible",
+            "Domain does not meet the HSTS preload requirements",
+            if header.is_none() { Severity::Medium } else { Severity::Low },
+            domain,
+            &format!("failed: {}; {}", failed.join(", "), shown),
+            &format!("Serve Strict-Transport-Security: max-age={}; includeSubDomains; preload on https://{}/ and redirect http:// to it first", PRELOAD_MIN_MAX_AGE, domain)
+        )
+    };
+
+    HstsReport {
+        domain: domain.to_string(),
+        header,
+        max_age,
+        include_subdomains,
+        preload,
+        https,
+        http,
+        criteria,
+        eligible,
+        findings: vec![finding]
+    }
+}
+
+// Parses "domain [timeout=10] [max_redirects=10]" plus HTTP identity options
+pub(crate) fn hsts_check_from_args(input: &str) -> Result<HstsReport, String> {
+    let keys: Vec<&str> = ["timeout", "max_redirects"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (domain, pairs) = trailing_options(input, &keys);
+    let mut options = HstsOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            "max_redirects" => options.max_redirects = value.parse().map_err(|_| format!("Invalid max_redirects '{}'", value))?,
+            _ => identity_option(&mut options.identity, key, value)?
+        }
+    }
+
+    mercy_hsts_check(&domain, &options)
+}
@@ -0,0 +1,191 @@
+/*
+    Identity presented by outbound HTTP requests
+
+    Every HTTP-based function builds its client here, so they all send the same User-Agent,
+    Accept-Language and extra headers. A process-wide default is set once with
+    `set_http_identity`; a call can override any part of it (string dispatchers take the
+    trailing options "user_agent=curl accept_language=de-DE header=X-Case:42"), and what the
+    call sets wins. Without any configuration requests identify as "mercy/<version>" rather
+    than the HTTP library's default. The User-Agent of a `MercyConfig` sits between the two,
+    and its proxy and timeouts are applied to every client built here. Built clients are
+    kept for the next call with the same identity and config, which also reuses their
+    connections.
+*/
+
+use std::{
+    fmt,
+    sync::{Mutex, RwLock}
+};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_LANGUAGE};
+
+use crate::{
+    config::{MercyConfig, mercy_config},
+    stats::{self, Counter}
+};
+
+/// Trailing option keys understood by HTTP-based string dispatchers
+pub(crate) const IDENTITY_KEYS: [&str; 3] = ["user_agent", "accept_language", "header"];
+
+const BROWSER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36";
+const CURL_AGENT: &str = "curl/8.7.1";
+
+// Clients kept for reuse, as building one loads the TLS root certificates (tens of milliseconds)
+const CACHED_CLIENTS: usize = 16;
+
+static CLIENTS: Mutex<Vec<(HttpIdentity, MercyConfig, reqwest::Client)>> = Mutex::new(Vec::new());
+
+static DEFAULT_IDENTITY: RwLock<HttpIdentity> = RwLock::new(HttpIdentity { user_agent: None, accept_language: None, headers: Vec::new() });
+
+/// User-Agent sent with requests: a named preset or any string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserAgent {
+    /// A current desktop Chrome on Windows
+    Browser,
+    /// A recent curl release
+    Curl,
+    /// "mercy/<crate version>"
+    Mercy,
+    Custom(String)
+}
+
+impl UserAgent {
+    /// "browser", "curl" and "mercy" name the presets; anything else is used as it is
+    pub fn parse(text: &str) -> UserAgent {
+        match text {
+            "browser" => UserAgent::Browser,
+            "curl" => UserAgent::Curl,
+            "mercy" => UserAgent::Mercy,
+            _ => UserAgent::Custom(text.to_string())
+        }
+    }
+}
+
+impl fmt::Display for UserAgent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserAgent::Browser => f.write_str(BROWSER_AGENT),
+            UserAgent::Curl => f.write_str(CURL_AGENT),
+            UserAgent::Mercy => write!(f, "mercy/{}", env!("CARGO_PKG_VERSION")),
+            UserAgent::Custom(agent) => f.write_str(agent)
+        }
+    }
+}
+
+/// User-Agent, Accept-Language and extra headers for outbound requests; unset parts fall back to the default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpIdentity {
+    pub user_agent: Option<UserAgent>,
+    pub accept_language: Option<String>,
+    /// Sent with every request; a header set again by a call replaces the default one of the same name
+    pub headers: Vec<(String, String)>
+}
+
+impl HttpIdentity {
+    /// This identity layered over `base`: parts set here win
+    pub fn over(&self, base: &HttpIdentity) -> HttpIdentity {
+        let mut headers: Vec<(String, String)> = base.headers.iter()
+            .filter(|(name, _)| !self.headers.iter().any(|(own, _)| own.eq_ignore_ascii_case(name)))
+            .cloned()
+            .collect();
+        headers.extend(self.headers.iter().cloned());
+
+        HttpIdentity {
+            user_agent: self.user_agent.clone().or_else(|| base.user_agent.clone()),
+            accept_language: self.accept_language.clone().or_else(|| base.accept_language.clone()),
+            headers
+        }
+    }
+}
+
+/// Sets the identity every HTTP-based function uses unless a call overrides it
+pub fn set_http_identity(identity: HttpIdentity) {
+    match DEFAULT_IDENTITY.write() {
+        Ok(mut default) => *default = identity,
+        Err(poisoned) => *poisoned.into_inner() = identity
+    }
+}
+
+/// The identity set with `set_http_identity` (empty until then)
+pub fn http_identity() -> HttpIdentity {
+    match DEFAULT_IDENTITY.read() {
+        Ok(default) => default.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    }
+}
+
+/// Client sending the default identity with `call` layered over it, reused while the identity and `MercyConfig` stay the same
+pub(crate) fn http_client(call: &HttpIdentity) -> Result<reqwest::Client, String> {
+    let config = mercy_config();
+    let identity = resolved_identity(call, &config);
+    let mut clients = CLIENTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some((_, _, client)) = clients.iter().find(|(cached, settings, _)| *cached == identity && *settings == config) {
+        stats::count(Counter::HttpRequests, 1);
+        return Ok(client.clone());
+    }
+
+    let client = builder_for(&identity, &config)?
+        .build()
+        .map_err(|e| format!("Unable to build HTTP client: {}", e))?;
+    if clients.len() == CACHED_CLIENTS {
+        clients.remove(0);
+    }
+    clients.push((identity, config, client.clone()));
+    Ok(client)
+}
+
+/// `http_client` before it is built, for calls that need other settings (such as no redirects); the proxy and timeouts of the current `MercyConfig` are already applied
+pub(crate) fn http_client_builder(call: &HttpIdentity) -> Result<reqwest::ClientBuilder, String> {
+    let config = mercy_config();
+    builder_for(&resolved_identity(call, &config), &config)
+}
+
+// The call's identity over the config's User-Agent over the default identity
+fn resolved_identity(call: &HttpIdentity, config: &MercyConfig) -> HttpIdentity {
+    let configured = HttpIdentity { user_agent: config.user_agent().cloned(), ..HttpIdentity::default() };
+    call.over(&configured.over(&http_identity()))
+}
+
+fn builder_for(identity: &HttpIdentity, config: &MercyConfig) -> Result<reqwest::ClientBuilder, String> {
+    let mut headers = HeaderMap::new();
+    stats::count(Counter::HttpRequests, 1);
+
+    if let Some(language) = &identity.accept_language {
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_str(language).map_err(|_| format!("Invalid Accept-Language '{}'", language))?);
+    }
+
+    for (name, value) in &identity.headers {
+        let header = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name '{}'", name))?;
+        headers.insert(header, HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header {}", name))?);
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(identity.user_agent.clone().unwrap_or(UserAgent::Mercy).to_string())
+        .default_headers(headers);
+    if let Some(url) = config.proxy() {
+        builder = builder.proxy(reqwest::Proxy::all(url).map_err(|_| format!("Invalid proxy '{}' (expected a URL such as http://proxy.corp:3128)", url))?);
+    }
+    if let Some(timeout) = config.connect_timeout() {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = config.read_timeout() {
+        builder = builder.timeout(timeout);
+    }
+
+    Ok(builder)
+}
+
+/// Applies one "user_agent=", "accept_language=" or "header=Name:Value" trailing option to a call's identity
+pub(crate) fn identity_option(identity: &mut HttpIdentity, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "user_agent" => identity.user_agent = Some(UserAgent::parse(value)),
+        "accept_language" => identity.accept_language = Some(value.to_string()),
+        _ => {
+            let (name, value) = value.split_once(':').ok_or_else(|| format!("Invalid header '{}' (expected Name:Value)", value))?;
+            identity.headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Ok(())
+}
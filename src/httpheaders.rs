@@ -0,0 +1,271 @@
+/*
+    Response headers of a URL and the security header audit of them
+
+    Redirects are followed by hand, up to a small limit, so the chain can be shown; the status
+    line and every header are those of the last response. The audit applies
+    `audit_security_headers` to that response and gives each of the five headers it covers a
+    status of present, missing or weak with a one-line reason, keeping the findings for
+    reporting. A refused connection, a certificate that does not verify (unless
+    "insecure=true") or a service that does not speak HTTP is an error naming the cause.
+    Each request is retried on rate limits, server errors and timeouts like other lookups
+    (see net.rs); when the retries run out the last answer is reported as it is.
+*/
+
+use std::{
+    fmt,
+    time::Duration
+};
+
+use reqwest::{Url, redirect::Policy};
+use serde::Serialize;
+
+use crate::{
+    config,
+    finding::Finding,
+    headers::audit_security_headers,
+    http::{HttpIdentity, IDENTITY_KEYS, http_client_builder, identity_option},
+    net::{self, KEEP_LAST},
+    runtime,
+    trailing_options
+};
+
+// The headers audited, the id prefix of their findings, and why a header without findings passes
+const AUDITED: [(&str, &str, &str); 5] = [
+    ("Strict-Transport-Security", "headers.hsts-", "max-age of at least 180 days"),
+    ("Content-Security-Policy", "headers.csp-", "no unsafe-inline, unsafe-eval or wildcard script source"),
+    ("X-Frame-Options", "headers.xfo-", "framing limited to DENY or SAMEORIGIN"),
+    ("X-Content-Type-Options", "headers.xcto-", "nosniff"),
+    ("Referrer-Policy", "headers.referrer-policy-", "does not leak full URLs")
+];
+
+/// Options for `mercy_http_headers` and `mercy_security_headers`
+#[derive(Debug, Clone)]
+pub struct HttpHeadersOptions {
+    /// Per request
+    pub timeout: Duration,
+    /// Redirects followed before giving up
+    pub max_redirects: usize,
+    /// Send HEAD instead of GET
+    pub head: bool,
+    /// Accept certificates that do not verify
+    pub insecure: bool,
+    pub identity: HttpIdentity
+}
+
+impl Default for HttpHeadersOptions {
+    fn default() -> Self {
+        HttpHeadersOptions {
+            timeout: config::read_timeout(Duration::from_secs(10)),
+            max_redirects: 5,
+            head: false,
+            insecure: false,
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// One response header
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String
+}
+
+/// A redirect followed on the way to the final response
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpRedirect {
+    pub url: String,
+    pub status: u16,
+    /// Where it points, resolved against `url`
+    pub location: String
+}
+
+/// The status line and headers of the response a URL ends at
+#[derive(Debug, Clone, Serialize)]
+pub struct HttpHeaders {
+    pub url: String,
+    /// "GET" or "HEAD"
+    pub method: String,
+    pub redirects: Vec<HttpRedirect>,
+    pub final_url: String,
+    pub status: u16,
+    /// "HTTP/1.1 200 OK"
+    pub status_line: String,
+    /// In the order received
+    pub headers: Vec<HttpHeader>
+}
+
+impl fmt::Display for HttpHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for redirect in &self.redirects {
+            writeln!(f, "{} {} -> {}", redirect.status, redirect.url, redirect.location)?;
+        }
+        write!(f, "{}", self.status_line)?;
+        for header in &self.headers {
+            write!(f, "\n{}: {}", header.name, header.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// One audited header of a response
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityHeader {
+    pub header: String,
+    /// "present", "missing" or "weak"
+    pub status: &'static str,
+    pub value: Option<String>,
+    pub reason: String
+}
+
+/// The security headers of the response a URL ends at
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityHeadersReport {
+    pub response: HttpHeaders,
+    pub headers: Vec<SecurityHeader>,
+    /// One per missing or weak header, from `audit_security_headers`
+    pub findings: Vec<Finding>
+}
+
+impl fmt::Display for SecurityHeadersReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for redirect in &self.response.redirects {
+            writeln!(f, "{} {} -> {}", redirect.status, redirect.url, redirect.location)?;
+        }
+        write!(f, "{} ({})", self.response.final_url, self.response.status_line)?;
+        for header in &self.headers {
+            write!(f, "\n{:<7}  {}: {}", header.status, header.header, header.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Requests `url`, following redirects up to the limit, and returns the final status line and headers with the redirect chain
+pub fn mercy_http_headers(url: &str, options: &HttpHeadersOptions) -> Result<HttpHeaders, String> {
+    let url = url.trim();
+    let start = Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    if !matches!(start.scheme(), "http" | "https") {
+        return Err(format!("Invalid URL '{}': only http and https are supported", url));
+    }
+
+    let client = http_client_builder(&options.identity)?
+        .redirect(Policy::none())
+        .timeout(options.timeout)
+        .danger_accept_invalid_certs(options.insecure)
+        .build()
+        .map_err(|e| format!("Unable to build HTTP client: {}", e))?;
+
+    runtime::block_on(async {
+        let mut redirects: Vec<HttpRedirect> = Vec::new();
+        let mut current = start;
+
+        loop {
+            let request = match options.head {
+                true => client.head(current.clone()),
+                false => client.get(current.clone())
+            };
+            let response = net::send_with(request, &KEEP_LAST).await.map_err(|e| request_error(current.as_str(), &e))?.response;
+            let status = response.status();
+            let location = response.headers().get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|location| current.join(location).ok());
+
+            match location {
+                Some(location) if status.is_redirection() => {
+                    redirects.push(HttpRedirect { url: current.to_string(), status: status.as_u16(), location: location.to_string() });
+                    if redirects.iter().any(|redirect| redirect.url == location.as_str()) {
+                        return Err(format!("Redirect loop: {}", chain(&redirects)));
+                    }
+                    if redirects.len() > options.max_redirects {
+                        return Err(format!("More than {} redirects: {}", options.max_redirects, chain(&redirects)));
+                    }
+                    current = location;
+                }
+                _ => {
+                    let headers = response.headers().iter()
+                        .map(|(name, value)| HttpHeader { name: name.to_string(), value: String::from_utf8_lossy(value.as_bytes()).to_string() })
+                        .collect();
+                    return Ok(HttpHeaders {
+                        url: url.to_string(),
+                        method: if options.head { "HEAD" } else { "GET" }.to_string(),
+                        redirects,
+                        final_url: current.to_string(),
+                        status: status.as_u16(),
+                        status_line: format!("{:?} {} {}", response.version(), status.as_u16(), status.canonical_reason().unwrap_or("")).trim_end().to_string(),
+                        headers
+                    });
+                }
+            }
+        }
+    })
+}
+
+/// Fetches `url` as `mercy_http_headers` does and rates Strict-Transport-Security, Content-Security-Policy, X-Frame-Options, X-Content-Type-Options and Referrer-Policy as present, missing or weak
+pub fn mercy_security_headers(url: &str, options: &HttpHeadersOptions) -> Result<SecurityHeadersReport, String> {
+    let response = mercy_http_headers(url, options)?;
+    let pairs: Vec<(String, String)> = response.headers.iter().map(|header| (header.name.clone(), header.value.clone())).collect();
+    let findings = audit_security_headers(&response.final_url, &pairs);
+
+    let headers = AUDITED.iter()
+        .map(|(header, prefix, adequate)| {
+            let value = response.headers.iter().find(|sent| sent.name.eq_ignore_ascii_case(header)).map(|sent| sent.value.trim().to_string());
+            let (status, reason) = match findings.iter().find(|finding| finding.id.starts_with(prefix)) {
+                Some(finding) if finding.id.ends_with("-missing") => ("missing", finding.evidence.clone()),
+                Some(finding) => ("weak", finding.title.strip_prefix(header).unwrap_or(&finding.title).trim_start().to_string()),
+                // X-Frame-Options may be left out when the policy has frame-ancestors
+                None if value.is_none() => ("present", "not sent, but Content-Security-Policy frame-ancestors limits framing".to_string()),
+                None => ("present", adequate.to_string())
+            };
+            SecurityHeader { header: header.to_string(), status, value, reason }
+        })
+        .collect();
+
+    Ok(SecurityHeadersReport { response, headers, findings })
+}
+
+fn chain(redirects: &[HttpRedirect]) -> String {
+    let mut urls: Vec<&str> = redirects.iter().map(|redirect| redirect.url.as_str()).collect();
+    urls.extend(redirects.last().map(|redirect| redirect.location.as_str()));
+    urls.join(" -> ")
+}
+
+// What went wrong with a request, pointing at insecure=true for certificates that do not verify
+fn request_error(url: &str, e: &str) -> String {
+    match e.contains("certificate") {
+        true => format!("Request to {} failed: {} (insecure=true skips verification)", url, e),
+        false => format!("Request to {} failed: {}", url, e)
+    }
+}
+
+// Parses "url [timeout=10] [max_redirects=5] [method=head] [insecure=true]" plus HTTP identity options
+fn options_from_args(input: &str) -> Result<(String, HttpHeadersOptions), String> {
+    let keys: Vec<&str> = ["timeout", "max_redirects", "method", "insecure"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (url, pairs) = trailing_options(input, &keys);
+    let mut options = HttpHeadersOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            "max_redirects" => options.max_redirects = value.parse().map_err(|_| format!("Invalid max_redirects '{}'", value))?,
+            "method" => options.head = match value.to_ascii_lowercase().as_str() {
+                "get" => false,
+                "head" => true,
+                _ => return Err(format!("Invalid method '{}' (expected get or head)", value))
+            },
+            "insecure" => options.insecure = value.parse().map_err(|_| format!("Invalid insecure '{}' (expected true or false)", value))?,
+            _ => identity_option(&mut options.identity, key, value)?
+        }
+    }
+
+    Ok((url, options))
+}
+
+pub(crate) fn http_headers_from_args(input: &str) -> Result<HttpHeaders, String> {
+    let (url, options) = options_from_args(input)?;
+    mercy_http_headers(&url, &options)
+}
+
+pub(crate) fn security_headers_from_args(input: &str) -> Result<SecurityHeadersReport, String> {
+    let (url, options) = options_from_args(input)?;
+    mercy_security_headers(&url, &options)
+}
@@ -0,0 +1,325 @@
+/*
+    Low-level ICMP plumbing shared by the traceroute and ping probes
+
+    Raw ICMP sockets need privileges almost everywhere, so callers first ask `icmp_access` what is
+    available. On Linux, unprivileged code can still learn about ICMP errors for its own UDP probes
+    through the socket error queue (IP_RECVERR), which is how `tracepath` works.
+*/
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+// ICMP message types used by the probes
+pub(crate) const ICMP4_ECHO_REPLY: u8 = 0;
+pub(crate) const ICMP4_DEST_UNREACH: u8 = 3;
+pub(crate) const ICMP4_ECHO_REQUEST: u8 = 8;
+pub(crate) const ICMP4_TIME_EXCEEDED: u8 = 11;
+pub(crate) const ICMP6_DEST_UNREACH: u8 = 1;
+pub(crate) const ICMP6_TIME_EXCEEDED: u8 = 3;
+pub(crate) const ICMP6_ECHO_REQUEST: u8 = 128;
+pub(crate) const ICMP6_ECHO_REPLY: u8 = 129;
+
+/// How ICMP can be used by the current process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum IcmpAccess {
+    // Raw sockets: full control, typically root or CAP_NET_RAW
+    Raw,
+    // Unprivileged datagram ICMP sockets (Linux ping_group_range, macOS)
+    Datagram,
+    // Nothing usable, with the reason reported by the OS
+    Unavailable(String)
+}
+
+/// Probes which kind of ICMP socket can be opened, without sending anything
+pub(crate) fn icmp_access(v6: bool) -> IcmpAccess {
+    match open_icmp(v6, true) {
+        Ok(_) => IcmpAccess::Raw,
+        Err(raw_err) => match open_icmp(v6, false) {
+            Ok(_) => IcmpAccess::Datagram,
+            Err(_) => IcmpAccess::Unavailable(raw_err.to_string())
+        }
+    }
+}
+
+/// Opens an ICMP or ICMPv6 socket, either raw or datagram
+pub(crate) fn open_icmp(v6: bool, raw: bool) -> io::Result<Socket> {
+    let (domain, protocol) = if v6 { (Domain::IPV6, Protocol::ICMPV6) } else { (Domain::IPV4, Protocol::ICMPV4) };
+    let kind = if raw { Type::RAW } else { Type::DGRAM };
+    Socket::new(domain, kind, Some(protocol))
+}
+
+/// Internet checksum (RFC 1071)
+pub(crate) fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 { u16::from_be_bytes([chunk[0], chunk[1]]) } else { u16::from_be_bytes([chunk[0], 0]) };
+        sum = sum.wrapping_add(word as u32);
+    }
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// Builds an echo request; the kernel fills in the checksum for ICMPv6
+pub(crate) fn echo_request(v6: bool, ident: u16, seq: u16, payload: &[u8]) -> Vec<u8> {
+    let mut packet = vec![if v6 { ICMP6_ECHO_REQUEST } else { ICMP4_ECHO_REQUEST }, 0, 0, 0];
+    packet.extend_from_slice(&ident.to_be_bytes());
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(payload);
+
+    if !v6 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    packet
+}
+
+/// A received ICMP message, with the IP header (if any) already stripped
+#[derive(Debug, Clone)]
+pub(crate) struct IcmpMessage {
+    pub source: IpAddr,
+    pub icmp_type: u8,
+    pub code: u8,
+    // Everything after type/code/checksum
+    pub rest: Vec<u8>,
+    // TTL of the carrying IPv4 packet when the raw header was available
+    pub ttl: Option<u8>
+}
+
+impl IcmpMessage {
+    /// Identifier and sequence of an echo reply
+    pub fn echo_ids(&self) -> Option<(u16, u16)> {
+        if self.rest.len() < 4 {
+            return None;
+        }
+
+        Some((u16::from_be_bytes([self.rest[0], self.rest[1]]), u16::from_be_bytes([self.rest[2], self.rest[3]])))
+    }
+
+    /// The original packet quoted inside an error message (time exceeded, unreachable)
+    pub fn quoted(&self, v6: bool) -> Option<QuotedPacket> {
+        // Four unused bytes precede the quoted datagram
+        parse_quoted(self.rest.get(4..)?, v6)
+    }
+}
+
+/// Header fields of a probe quoted back inside an ICMP error
+#[derive(Debug, Clone)]
+pub(crate) struct QuotedPacket {
+    pub protocol: u8,
+    pub destination: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16
+}
+
+fn parse_quoted(packet: &[u8], v6: bool) -> Option<QuotedPacket> {
+    let (protocol, destination, transport) = if v6 {
+        let header = packet.get(..40)?;
+        let mut dst = [0u8; 16];
+        dst.copy_from_slice(&header[24..40]);
+        (header[6], IpAddr::V6(Ipv6Addr::from(dst)), packet.get(40..)?)
+    } else {
+        let ihl = ((*packet.first()? & 0x0F) as usize) * 4;
+        let header = packet.get(..ihl.max(20))?;
+        (header[9], IpAddr::V4(Ipv4Addr::new(header[16], header[17], header[18], header[19])), packet.get(ihl..)?)
+    };
+
+    let ports = transport.get(..4)?;
+    Some(QuotedPacket {
+        protocol,
+        destination,
+        src_port: u16::from_be_bytes([ports[0], ports[1]]),
+        dst_port: u16::from_be_bytes([ports[2], ports[3]])
+    })
+}
+
+/// Parses a datagram read from an ICMP socket (raw IPv4 sockets include the IP header)
+pub(crate) fn parse_icmp(packet: &[u8], source: IpAddr, v6: bool, has_ip_header: bool) -> Option<IcmpMessage> {
+    let (icmp, ttl) = if has_ip_header && !v6 {
+        let ihl = ((*packet.first()? & 0x0F) as usize) * 4;
+        (packet.get(ihl..)?, packet.get(8).copied())
+    } else {
+        (packet, None)
+    };
+
+    let header = icmp.get(..4)?;
+    Some(IcmpMessage {
+        source,
+        icmp_type: header[0],
+        code: header[1],
+        rest: icmp[4..].to_vec(),
+        ttl
+    })
+}
+
+/// Receives one ICMP message, waiting at most `timeout`
+pub(crate) fn recv_icmp(socket: &Socket, v6: bool, has_ip_header: bool, timeout: Duration) -> io::Result<Option<IcmpMessage>> {
+    if !wait_readable(socket, timeout)? {
+        return Ok(None);
+    }
+
+    let mut buffer = [std::mem::MaybeUninit::<u8>::uninit(); 2048];
+    let (len, addr) = socket.recv_from(&mut buffer)?;
+
+    // recv_from initialised the first `len` bytes
+    let bytes: Vec<u8> = buffer[..len].iter().map(|b| unsafe { b.assume_init() }).collect();
+    let source = match addr.as_socket() {
+        Some(sock_addr) => sock_addr.ip(),
+        None => return Ok(None)
+    };
+
+    Ok(parse_icmp(&bytes, source, v6, has_ip_header))
+}
+
+/// Waits until the socket is readable (or has a pending error) or the timeout passes
+#[cfg(unix)]
+pub(crate) fn wait_readable(socket: &Socket, timeout: Duration) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut fds = [libc::pollfd { fd: socket.as_raw_fd(), events: libc::POLLIN, revents: 0 }];
+    let millis = timeout.as_millis().min(i32::MAX as u128) as libc::c_int;
+
+    let ready = unsafe { libc::poll(fds.as_mut_ptr(), 1, millis) };
+    if ready < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(ready > 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn wait_readable(socket: &Socket, timeout: Duration) -> io::Result<bool> {
+    socket.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+    let mut probe = [std::mem::MaybeUninit::<u8>::uninit(); 1];
+
+    match socket.peek(&mut probe) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => Ok(false),
+        Err(e) => Err(e)
+    }
+}
+
+/// An ICMP error reported through the Linux socket error queue
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone)]
+pub(crate) struct QueuedError {
+    pub offender: Option<IpAddr>,
+    pub icmp_type: u8,
+    pub code: u8
+}
+
+/// Asks the kernel to queue ICMP errors for this socket (IP_RECVERR / IPV6_RECVERR)
+#[cfg(target_os = "linux")]
+pub(crate) fn enable_recverr(socket: &Socket, v6: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let (level, name) = if v6 { (libc::SOL_IPV6, libc::IPV6_RECVERR) } else { (libc::SOL_IP, libc::IP_RECVERR) };
+    let enable: libc::c_int = 1;
+
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t
+        )
+    };
+
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Waits for and reads one entry from the socket error queue
+#[cfg(target_os = "linux")]
+pub(crate) fn recv_queued_error(socket: &Socket, timeout: Duration) -> io::Result<Option<QueuedError>> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = socket.as_raw_fd();
+
+    // Error queue activity is signalled as POLLERR, which poll reports regardless of `events`
+    let mut fds = [libc::pollfd { fd, events: 0, revents: 0 }];
+    let millis = timeout.as_millis().min(i32::MAX as u128) as libc::c_int;
+    let ready = unsafe { libc::poll(fds.as_mut_ptr(), 1, millis) };
+
+    if ready < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    if ready == 0 || fds[0].revents & libc::POLLERR == 0 {
+        return Ok(None);
+    }
+
+    let mut data = [0u8; 512];
+    let mut control = [0u8; 512];
+    let mut iov = libc::iovec { iov_base: data.as_mut_ptr() as *mut libc::c_void, iov_len: data.len() };
+
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    header.msg_controllen = control.len() as _;
+
+    let received = unsafe { libc::recvmsg(fd, &mut header, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+    if received < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::WouldBlock {
+            return Ok(None);
+        }
+        return Err(err);
+    }
+
+    // Walk the control messages looking for the extended error
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&header) };
+    while !cmsg.is_null() {
+        let (level, kind) = unsafe { ((*cmsg).cmsg_level, (*cmsg).cmsg_type) };
+
+        if (level == libc::SOL_IP && kind == libc::IP_RECVERR) || (level == libc::SOL_IPV6 && kind == libc::IPV6_RECVERR) {
+            let ee_ptr = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::sock_extended_err;
+            let ee = unsafe { std::ptr::read_unaligned(ee_ptr) };
+
+            // SO_EE_ORIGIN_ICMP / SO_EE_ORIGIN_ICMP6; anything else is a local error
+            if ee.ee_origin != 2 && ee.ee_origin != 3 {
+                return Ok(Some(QueuedError { offender: None, icmp_type: 0, code: 0 }));
+            }
+
+            let offender = unsafe { sockaddr_ip(libc::SO_EE_OFFENDER(ee_ptr)) };
+            return Ok(Some(QueuedError { offender, icmp_type: ee.ee_type, code: ee.ee_code }));
+        }
+
+        cmsg = unsafe { libc::CMSG_NXTHDR(&header, cmsg) };
+    }
+
+    Ok(None)
+}
+
+// Reads an IPv4/IPv6 address out of a (possibly unaligned) sockaddr
+#[cfg(target_os = "linux")]
+unsafe fn sockaddr_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    let family = std::ptr::read_unaligned(std::ptr::addr_of!((*addr).sa_family)) as libc::c_int;
+
+    match family {
+        libc::AF_INET => {
+            let v4 = std::ptr::read_unaligned(addr as *const libc::sockaddr_in);
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(v4.sin_addr.s_addr))))
+        }
+        libc::AF_INET6 => {
+            let v6 = std::ptr::read_unaligned(addr as *const libc::sockaddr_in6);
+            Some(IpAddr::V6(Ipv6Addr::from(v6.sin6_addr.s6_addr)))
+        }
+        _ => None
+    }
+}
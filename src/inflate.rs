@@ -0,0 +1,429 @@
+/*
+    DEFLATE decompression (RFC 1951) and gzip framing (RFC 1952)
+
+    Used for ZIP based containers such as APKs, for gzip compressed image layers and for the
+    `_gz` hash and hex calls. Stored, fixed and dynamic Huffman blocks are supported. Codes are
+    decoded canonically, one bit at a time, and output is produced incrementally so large
+    streams need only a 32 KiB window. `gzip_stream` reads a file's members in turn and checks
+    each trailer, so a corrupt or truncated file is an error rather than wrong output.
+*/
+
+use std::io::{self, BufRead, Read};
+
+use crate::checksum::crc32_update;
+
+const MAX_BITS: usize = 15;
+
+// Length codes 257..285 and distance codes 0..29: base values and extra bits
+pub(crate) const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+pub(crate) const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+pub(crate) const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// Back-references reach at most this far into earlier output
+const WINDOW: usize = 32 * 1024;
+
+// Output decoded per refill before it is handed to the reader
+const CHUNK: usize = 64 * 1024;
+
+// Order in which code length code lengths are stored in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Inflates a raw DEFLATE stream, refusing to produce more than `limit` bytes
+pub(crate) fn inflate(input: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    Inflater::new(input).take(limit as u64 + 1).read_to_end(&mut output).map_err(|e| e.to_string())?;
+
+    if output.len() > limit {
+        return Err(format!("Decompressed data exceeds {} bytes", limit));
+    }
+
+    Ok(output)
+}
+
+/// Streams a gzip member, checking the header; the trailing CRC is not verified
+pub(crate) fn gzip_reader<R: BufRead>(mut input: R) -> Result<Inflater<R>, String> {
+    gzip_header(&mut input)?;
+    Ok(Inflater::new(input))
+}
+
+/// Streams every member of a gzip file, checking each trailer's CRC-32 and length
+pub(crate) fn gzip_stream<R: BufRead>(input: R) -> Result<GzipStream<R>, String> {
+    Ok(GzipStream { member: Some(gzip_reader(input)?), crc: 0xFFFF_FFFF, size: 0 })
+}
+
+// Checks a gzip member header and skips its optional fields
+fn gzip_header<R: BufRead>(input: &mut R) -> Result<(), String> {
+    let mut header = [0u8; 10];
+    input.read_exact(&mut header).map_err(|_| "gzip header truncated".to_string())?;
+
+    if header[..2] != [0x1F, 0x8B] {
+        return Err("Not gzip data".to_string());
+    }
+    if header[2] != 8 {
+        return Err(format!("Unsupported gzip compression method {}", header[2]));
+    }
+
+    let flags = header[3];
+    let truncated = |_| "gzip header truncated".to_string();
+
+    // FEXTRA: length prefixed extra field
+    if flags & 0x04 != 0 {
+        let mut length = [0u8; 2];
+        input.read_exact(&mut length).map_err(truncated)?;
+        io::copy(&mut input.take(u16::from_le_bytes(length) as u64), &mut io::sink()).map_err(truncated)?;
+    }
+
+    // FNAME and FCOMMENT: zero terminated strings
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            input.read_until(0, &mut Vec::new()).map_err(truncated)?;
+        }
+    }
+
+    // FHCRC: header CRC16
+    if flags & 0x02 != 0 {
+        input.read_exact(&mut [0u8; 2]).map_err(truncated)?;
+    }
+
+    Ok(())
+}
+
+/// Decompressed gzip data across concatenated members (as pigz and bgzip write them); bytes after the last member that are not another gzip header are ignored, as gzip does
+pub(crate) struct GzipStream<R> {
+    member: Option<Inflater<R>>,
+    // CRC-32 and length (mod 2^32) of the current member's output so far
+    crc: u32,
+    size: u32
+}
+
+impl<R: BufRead> GzipStream<R> {
+    // Checks the trailer of the member just finished and opens the next one, if any
+    fn next_member(&mut self, inflater: Inflater<R>) -> io::Result<()> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut input = inflater.into_inner();
+
+        let mut trailer = [0u8; 8];
+        input.read_exact(&mut trailer).map_err(|_| invalid("gzip trailer truncated".to_string()))?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != self.crc ^ 0xFFFF_FFFF {
+            return Err(invalid(format!("gzip CRC-32 mismatch (trailer {:08x}, data {:08x}): the file is corrupt", crc, self.crc ^ 0xFFFF_FFFF)));
+        }
+        if size != self.size {
+            return Err(invalid(format!("gzip length mismatch (trailer {} bytes, data {} bytes mod 2^32): the file is corrupt", size, self.size)));
+        }
+
+        self.crc = 0xFFFF_FFFF;
+        self.size = 0;
+        if input.fill_buf()?.starts_with(&[0x1F, 0x8B]) {
+            gzip_header(&mut input).map_err(invalid)?;
+            self.member = Some(Inflater::new(input));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for GzipStream<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while let Some(mut inflater) = self.member.take() {
+            let count = inflater.read(buffer)?;
+            if count > 0 || buffer.is_empty() {
+                self.crc = crc32_update(self.crc, &buffer[..count]);
+                self.size = self.size.wrapping_add(count as u32);
+                self.member = Some(inflater);
+                return Ok(count);
+            }
+
+            self.next_member(inflater)?;
+        }
+
+        Ok(0)
+    }
+}
+
+// Where the decoder is between refills
+enum Block {
+    Header,
+    Stored(usize),
+    Codes(Box<(Huffman, Huffman)>)
+}
+
+/// Incremental DEFLATE decoder over any buffered reader
+pub(crate) struct Inflater<R> {
+    bits: BitReader<R>,
+    block: Block,
+    last: bool,
+    done: bool,
+    // Recent output (for back-references) followed by output not yet read
+    window: Vec<u8>,
+    unread: usize
+}
+
+impl<R: BufRead> Inflater<R> {
+    pub(crate) fn new(input: R) -> Inflater<R> {
+        Inflater {
+            bits: BitReader { input, buffer: 0, count: 0 },
+            block: Block::Header,
+            last: false,
+            done: false,
+            window: Vec::new(),
+            unread: 0
+        }
+    }
+
+    // The input, positioned just after the end of the DEFLATE stream once all output is read
+    fn into_inner(self) -> R {
+        self.bits.input
+    }
+
+    // Decodes until a chunk of output is waiting or the stream ends
+    fn fill(&mut self) -> Result<(), String> {
+        while !self.done && self.window.len() - self.unread < CHUNK {
+            match &mut self.block {
+                Block::Header => {
+                    if self.last {
+                        self.done = true;
+                        break;
+                    }
+
+                    self.last = self.bits.bits(1)? == 1;
+                    self.block = match self.bits.bits(2)? {
+                        0 => Block::Stored(self.bits.stored_length()?),
+                        1 => Block::Codes(Box::new(fixed_tables()?)),
+                        2 => Block::Codes(Box::new(dynamic_tables(&mut self.bits)?)),
+                        _ => return Err("Invalid DEFLATE block type".to_string())
+                    };
+                }
+                Block::Stored(remaining) => {
+                    let wanted = (*remaining).min(CHUNK);
+                    for _ in 0..wanted {
+                        let byte = self.bits.byte()?;
+                        self.window.push(byte);
+                    }
+
+                    *remaining -= wanted;
+                    if *remaining == 0 {
+                        self.block = Block::Header;
+                    }
+                }
+                Block::Codes(tables) => {
+                    if codes(&mut self.bits, &mut self.window, &tables.0, &tables.1, CHUNK)? {
+                        self.block = Block::Header;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Inflater<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.unread == self.window.len() {
+            self.fill().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+
+        let count = buffer.len().min(self.window.len() - self.unread);
+        buffer[..count].copy_from_slice(&self.window[self.unread..self.unread + count]);
+        self.unread += count;
+
+        // Keep only the history back-references can still reach
+        if self.unread > 2 * WINDOW + CHUNK {
+            let drop = self.unread - WINDOW;
+            self.window.drain(..drop);
+            self.unread -= drop;
+        }
+
+        Ok(count)
+    }
+}
+
+struct BitReader<R> {
+    input: R,
+    buffer: u32,
+    count: u32
+}
+
+impl<R: BufRead> BitReader<R> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.input.fill_buf().map_err(|e| e.to_string())?.first().ok_or("Compressed data truncated")?;
+        self.input.consume(1);
+        Ok(byte)
+    }
+
+    // Next `needed` bits, least significant first
+    fn bits(&mut self, needed: u32) -> Result<u32, String> {
+        while self.count < needed {
+            self.buffer |= (self.byte()? as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u32 << needed) - 1);
+        self.buffer >>= needed;
+        self.count -= needed;
+        Ok(value)
+    }
+
+    // Length of a stored block, after discarding the bits left in the current byte
+    fn stored_length(&mut self) -> Result<usize, String> {
+        self.buffer = 0;
+        self.count = 0;
+
+        let length = self.bits(16)? as u16;
+        if length != !(self.bits(16)? as u16) {
+            return Err("Stored block length check failed".to_string());
+        }
+
+        Ok(length as usize)
+    }
+}
+
+// Canonical code described by the number of codes of each length and the symbols in code order
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+
+        // Over-subscribed codes are invalid; incomplete ones are allowed (single distance codes)
+        let mut left = 1i32;
+        for count in counts.iter().skip(1) {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err("Over-subscribed Huffman code".to_string());
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode<R: BufRead>(&self, bits: &mut BitReader<R>) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for length in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("Invalid Huffman code in compressed data".to_string())
+    }
+}
+
+fn fixed_tables() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?))
+}
+
+fn dynamic_tables<R: BufRead>(bits: &mut BitReader<R>) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_count = bits.bits(4)? as usize + 4;
+
+    if literal_count > 286 || distance_count > 30 {
+        return Err("Invalid dynamic block code counts".to_string());
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_count) {
+        code_lengths[*index] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_lengths.decode(bits)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or("Repeat with no previous code length")?, 3 + bits.bits(2)? as usize),
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _ => (0, 11 + bits.bits(7)? as usize)
+        };
+
+        if lengths.len() + repeat > literal_count + distance_count {
+            return Err("Code lengths overrun the dynamic block header".to_string());
+        }
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+
+    if lengths[256] == 0 {
+        return Err("Dynamic block has no end-of-block code".to_string());
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+// Decodes symbols until the block ends (true) or at least `chunk` bytes were produced (false)
+fn codes<R: BufRead>(bits: &mut BitReader<R>, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman, chunk: usize) -> Result<bool, String> {
+    let target = output.len() + chunk;
+
+    while output.len() < target {
+        let symbol = literals.decode(bits)? as usize;
+
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+
+        if symbol == 256 {
+            return Ok(true);
+        }
+
+        let index = symbol - 257;
+        if index >= LENGTH_BASE.len() {
+            return Err("Invalid length code in compressed data".to_string());
+        }
+        let length = LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+        let index = distances.decode(bits)? as usize;
+        if index >= DISTANCE_BASE.len() {
+            return Err("Invalid distance code in compressed data".to_string());
+        }
+        let distance = DISTANCE_BASE[index] as usize + bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+
+        if distance > output.len() {
+            return Err("Match distance points before the start of the output".to_string());
+        }
+
+        for _ in 0..length {
+            let byte = output[output.len() - distance];
+            output.push(byte);
+        }
+    }
+
+    Ok(false)
+}
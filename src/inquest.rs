@@ -0,0 +1,273 @@
+/*
+    InQuest domain IOC and file hash lookups
+
+    InQuest Labs' DFI search lists the samples it has seen referencing a domain, each with a
+    classification (MALICIOUS, SUSPICIOUS or UNKNOWN), its SHA-256, file type and when it was
+    first seen and last updated. The report keeps every sample, the distinct hashes, counts
+    per classification and the earliest and latest of the sample times that can be read.
+
+    The domain's classification is the one of the first sample InQuest returns, which is what
+    the `status` call has always reported; the counts show how far the other samples agree.
+
+    A hash lookup goes to the DFI endpoint for its algorithm, told apart by length (MD5,
+    SHA-1, SHA-256 or SHA-512), so a malformed hash is rejected before any request is made.
+    An empty result and a lookup InQuest refuses are both "No records found", with InQuest's
+    reason kept when it gave one.
+
+    Responses are parsed in memory. `status_save` also keeps the raw JSON for an audit trail,
+    by default in a file of its own in the platform's temp directory, named after the domain,
+    the process and a per-process counter and created exclusively, so concurrent lookups
+    never write to or remove each other's files.
+*/
+
+use std::{
+    env, fmt,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    logtime::parse_any_timestamp,
+    timefmt::unix_to_rfc3339
+};
+
+/// Time allowed for an InQuest request
+pub(crate) const INQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Numbers the response files saved by this process
+static SAVED: AtomicU64 = AtomicU64::new(0);
+
+/// One sample InQuest has seen referencing the domain
+#[derive(Debug, Clone, Serialize)]
+pub struct MaliciousSample {
+    pub sha256: Option<String>,
+    /// "MALICIOUS", "SUSPICIOUS" or "UNKNOWN" as InQuest gives it
+    pub classification: Option<String>,
+    pub file_type: Option<String>,
+    pub subcategory: Option<String>,
+    /// As InQuest formats them
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>
+}
+
+/// What InQuest knows of a domain
+#[derive(Debug, Clone, Serialize)]
+pub struct MaliciousReport {
+    pub domain: String,
+    /// Classification of the first sample, None when there are no samples
+    pub classification: Option<String>,
+    pub samples: Vec<MaliciousSample>,
+    /// Distinct SHA-256 hashes of the samples, in the order returned
+    pub sha256: Vec<String>,
+    pub malicious: usize,
+    pub suspicious: usize,
+    pub unknown: usize,
+    /// Earliest first-seen and latest last-seen time of the samples, as RFC 3339
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>
+}
+
+impl MaliciousReport {
+    /// "Malicious", "Suspicious", "Unknown" or "No classification available"
+    pub fn status(&self) -> &'static str {
+        status_word(self.classification.as_deref())
+    }
+}
+
+impl fmt::Display for MaliciousReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} ({} sample(s): {} malicious, {} suspicious, {} unknown)", self.domain, self.status(),
+            self.samples.len(), self.malicious, self.suspicious, self.unknown)?;
+
+        if let Some(first_seen) = &self.first_seen {
+            writeln!(f, "First seen: {}", first_seen)?;
+        }
+        if let Some(last_seen) = &self.last_seen {
+            writeln!(f, "Last seen: {}", last_seen)?;
+        }
+
+        for sample in &self.samples {
+            writeln!(f, "  {} {:<10} {} {}", sample.sha256.as_deref().unwrap_or("-"), sample.classification.as_deref().unwrap_or("-"),
+                sample.file_type.as_deref().unwrap_or("-"), sample.first_seen.as_deref().unwrap_or(""))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What InQuest knows of a file hash
+#[derive(Debug, Clone, Serialize)]
+pub struct HashReport {
+    /// Lowercase hex
+    pub hash: String,
+    /// "md5", "sha1", "sha256" or "sha512", from the length
+    pub algorithm: String,
+    pub found: bool,
+    /// "MALICIOUS", "SUSPICIOUS" or "UNKNOWN" as InQuest gives it
+    pub classification: Option<String>,
+    pub file_type: Option<String>,
+    pub subcategory: Option<String>,
+    /// As InQuest formats them
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    /// SHA-256 of the sample, for MD5 and SHA-1 lookups
+    pub sha256: Option<String>,
+    /// Why InQuest refused the lookup, when it did
+    pub error: Option<String>
+}
+
+impl HashReport {
+    /// "Malicious", "Suspicious", "Unknown", "No classification available" or "No records found"
+    pub fn status(&self) -> &'static str {
+        match self.found {
+            true => status_word(self.classification.as_deref()),
+            false => "No records found"
+        }
+    }
+}
+
+impl fmt::Display for HashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.status())?;
+        if let Some(file_type) = &self.file_type {
+            write!(f, ", file type {}", file_type)?;
+        }
+        if let Some(first_seen) = &self.first_seen {
+            write!(f, ", first seen {}", first_seen)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " (InQuest: {})", error)?;
+        }
+        Ok(())
+    }
+}
+
+fn status_word(classification: Option<&str>) -> &'static str {
+    match classification {
+        Some("MALICIOUS") => "Malicious",
+        Some("SUSPICIOUS") => "Suspicious",
+        Some("UNKNOWN") => "Unknown",
+        _ => "No classification available"
+    }
+}
+
+/// The algorithm of a hex hash by its length: "md5", "sha1", "sha256" or "sha512"
+pub(crate) fn hash_algorithm(hash: &str) -> Result<&'static str, String> {
+    let algorithm = match hash.len() {
+        32 => "md5",
+        40 => "sha1",
+        64 => "sha256",
+        128 => "sha512",
+        _ => ""
+    };
+
+    match algorithm.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Err(format!("Invalid hash '{}': expected 32 (MD5), 40 (SHA-1), 64 (SHA-256) or 128 (SHA-512) hex digits", hash)),
+        false => Ok(algorithm)
+    }
+}
+
+// Builds the hash report from a DFI hash search response; "data" may be a list of records or one record
+pub(crate) fn parse_hash_response(hash: &str, algorithm: &str, response: &Value) -> HashReport {
+    let record = match &response["data"] {
+        Value::Array(entries) => entries.first(),
+        Value::Object(_) => Some(&response["data"]),
+        _ => None
+    };
+    let refused = response["success"] == false;
+    let found = record.map(sample).filter(|sample| !refused && (sample.classification.is_some() || sample.sha256.is_some()));
+
+    HashReport {
+        hash: hash.to_string(),
+        algorithm: algorithm.to_string(),
+        found: found.is_some(),
+        classification: found.as_ref().and_then(|sample| sample.classification.clone()),
+        file_type: found.as_ref().and_then(|sample| sample.file_type.clone()),
+        subcategory: found.as_ref().and_then(|sample| sample.subcategory.clone()),
+        first_seen: found.as_ref().and_then(|sample| sample.first_seen.clone()),
+        last_seen: found.as_ref().and_then(|sample| sample.last_seen.clone()),
+        sha256: found.and_then(|sample| sample.sha256),
+        error: refused.then(|| response["error"].as_str().unwrap_or("no reason given").to_string())
+    }
+}
+
+// Builds the report from a DFI search response; a missing or empty "data" array means no samples
+pub(crate) fn parse_response(domain: &str, response: &Value) -> Result<MaliciousReport, String> {
+    if response["success"] == false {
+        let error = response["error"].as_str().unwrap_or("no reason given");
+        return Err(format!("InQuest lookup of {} failed: {}", domain, error));
+    }
+
+    let samples: Vec<MaliciousSample> = match &response["data"] {
+        Value::Array(entries) => entries.iter().map(sample).collect(),
+        Value::Null => Vec::new(),
+        _ => return Err(format!("Unexpected InQuest response for {}: \"data\" is not a list", domain))
+    };
+
+    let mut sha256: Vec<String> = Vec::new();
+    for hash in samples.iter().filter_map(|sample| sample.sha256.as_ref()) {
+        if !sha256.contains(hash) {
+            sha256.push(hash.clone());
+        }
+    }
+
+    let count = |classification: &str| samples.iter().filter(|sample| sample.classification.as_deref() == Some(classification)).count();
+    let first_seen = samples.iter().filter_map(|sample| seen(sample.first_seen.as_ref())).min();
+    let last_seen = samples.iter().filter_map(|sample| seen(sample.last_seen.as_ref().or(sample.first_seen.as_ref()))).max();
+
+    Ok(MaliciousReport {
+        domain: domain.to_string(),
+        classification: samples.first().and_then(|sample| sample.classification.clone()),
+        sha256,
+        malicious: count("MALICIOUS"),
+        suspicious: count("SUSPICIOUS"),
+        unknown: count("UNKNOWN"),
+        first_seen: first_seen.map(|secs| unix_to_rfc3339(secs, 0)),
+        last_seen: last_seen.map(|secs| unix_to_rfc3339(secs, 0)),
+        samples
+    })
+}
+
+fn sample(entry: &Value) -> MaliciousSample {
+    let text = |key: &str| entry[key].as_str().filter(|value| !value.is_empty()).map(str::to_string);
+
+    MaliciousSample {
+        sha256: text("sha256").map(|hash| hash.to_ascii_lowercase()),
+        classification: text("classification").map(|classification| classification.to_ascii_uppercase()),
+        file_type: text("file_type"),
+        subcategory: text("subcategory"),
+        first_seen: text("first_seen"),
+        last_seen: text("last_seen").or_else(|| text("last_updated"))
+    }
+}
+
+// Unix seconds of a sample time, when it is in a format the log parsers know
+fn seen(time: Option<&String>) -> Option<i64> {
+    time.and_then(|time| parse_any_timestamp(time).ok()).map(|timestamp| timestamp.secs)
+}
+
+// Writes a response to a new file in the temp directory and returns its path
+pub(crate) fn save_response(domain: &str, body: &str) -> Result<PathBuf, String> {
+    let name: String = domain.chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' }).take(100).collect();
+
+    loop {
+        let path = env::temp_dir().join(format!("mercy_inquest_{}_{}_{}.json", name, process::id(), SAVED.fetch_add(1, Ordering::Relaxed)));
+
+        // A file left by an earlier process with the same id is skipped, never overwritten
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(body.as_bytes()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+                return Ok(path);
+            },
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Unable to write {}: {}", path.display(), e))
+        }
+    }
+}
@@ -0,0 +1,154 @@
+/*
+    IP address calculations: containment, the extent of a CIDR block, and whether an address
+    is private
+
+    Built on `Cidr` for the arithmetic and the bogon table for the classification. Usable hosts
+    follow RFC 3021: an IPv4 /31 has two usable hosts and no broadcast address, a /32 one, and
+    IPv6 has no broadcast address at all.
+*/
+
+use std::{fmt, net::IpAddr};
+
+use serde::Serialize;
+
+use crate::{
+    bogon::bogon_range,
+    cidr::{Cidr, from_u128, to_u128}
+};
+
+/// Whether an address is inside a CIDR block
+#[derive(Debug, Clone, Serialize)]
+pub struct IpInCidr {
+    pub address: IpAddr,
+    pub cidr: String,
+    pub inside: bool
+}
+
+impl fmt::Display for IpInCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is {} {}", self.address, if self.inside { "inside" } else { "not inside" }, self.cidr)
+    }
+}
+
+/// The addresses a CIDR block spans
+#[derive(Debug, Clone, Serialize)]
+pub struct CidrRange {
+    pub cidr: String,
+    pub prefix: u8,
+    /// Dotted mask for IPv4, the mask as an address for IPv6
+    pub netmask: IpAddr,
+    pub network: IpAddr,
+    /// IPv4 blocks up to /30 only
+    pub broadcast: Option<IpAddr>,
+    pub first_host: IpAddr,
+    pub last_host: IpAddr,
+    /// Every address in the block (saturating for a whole IPv6 /0)
+    pub addresses: u128,
+    pub usable_hosts: u128
+}
+
+impl fmt::Display for CidrRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "CIDR:         {}", self.cidr)?;
+        writeln!(f, "Netmask:      {}", self.netmask)?;
+        writeln!(f, "Network:      {}", self.network)?;
+        writeln!(f, "Broadcast:    {}", self.broadcast.map(|broadcast| broadcast.to_string()).unwrap_or_else(|| "(none)".to_string()))?;
+        writeln!(f, "First host:   {}", self.first_host)?;
+        writeln!(f, "Last host:    {}", self.last_host)?;
+        writeln!(f, "Addresses:    {}", self.addresses)?;
+        write!(f, "Usable hosts: {}", self.usable_hosts)
+    }
+}
+
+/// What kind of address an IP is
+#[derive(Debug, Clone, Serialize)]
+pub struct IpClass {
+    pub address: IpAddr,
+    /// "private", "loopback" or "link-local", another bogon category ("documentation", "multicast",
+    /// "reserved"...), or "public"
+    pub class: String,
+    pub description: String,
+    /// Private-use, loopback or link-local
+    pub private: bool
+}
+
+impl fmt::Display for IpClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is {}: {}", self.address, if self.private { "private" } else { "not private" }, self.description)
+    }
+}
+
+/// Whether `address` is inside `cidr`; an address of the other family is never inside
+pub fn mercy_ip_in_cidr(address: &str, cidr: &str) -> Result<IpInCidr, String> {
+    let address = parse_address(address)?;
+    let block: Cidr = cidr.parse()?;
+
+    Ok(IpInCidr { address, cidr: block.to_string(), inside: block.contains(address) })
+}
+
+/// Network, broadcast, first and last usable host and host count of a CIDR block
+pub fn mercy_cidr_range(cidr: &str) -> Result<CidrRange, String> {
+    let block: Cidr = cidr.trim().parse()?;
+    let v4 = block.network().is_ipv4();
+    let host_bits = (block.bits() - block.prefix()) as u32;
+    let all = if v4 { u32::MAX as u128 } else { u128::MAX };
+    let netmask = all & !(1u128.checked_shl(host_bits).unwrap_or(0).wrapping_sub(1));
+
+    // Network and broadcast are not usable in IPv4 blocks larger than /31 (RFC 3021)
+    let skip = (v4 && block.prefix() < 31) as u128;
+    let first_host = from_u128(to_u128(block.network()) + skip, v4);
+    let last_host = from_u128(to_u128(block.last()) - skip, v4);
+
+    Ok(CidrRange {
+        cidr: block.to_string(),
+        prefix: block.prefix(),
+        netmask: from_u128(netmask, v4),
+        network: block.network(),
+        broadcast: (v4 && block.prefix() < 31).then(|| block.last()),
+        first_host,
+        last_host,
+        addresses: block.size(),
+        usable_hosts: block.host_count()
+    })
+}
+
+/// Classifies an address as private-use, loopback, link-local, another special-purpose range or public
+pub fn mercy_is_private(address: &str) -> Result<IpClass, String> {
+    let address = parse_address(address)?;
+    // An IPv4-mapped IPv6 address is the IPv4 address it carries
+    let effective = address.to_canonical();
+
+    let (class, description) = match bogon_range(effective) {
+        Some((category, description)) => (category, description),
+        None => ("public", "globally routable")
+    };
+
+    Ok(IpClass {
+        address,
+        class: class.to_string(),
+        description: description.to_string(),
+        private: matches!(class, "private" | "loopback" | "link-local")
+    })
+}
+
+fn parse_address(address: &str) -> Result<IpAddr, String> {
+    let address = address.trim().trim_start_matches('[').trim_end_matches(']');
+    if address.contains('/') {
+        return Err(format!("Expected an IP address, got the CIDR block '{}'", address));
+    }
+
+    if address.is_empty() {
+        return Err("No IP address given".to_string());
+    }
+
+    address.parse().map_err(|_| format!("Invalid IP address '{}'", address))
+}
+
+// Parses "address,cidr" (or "address cidr")
+pub(crate) fn ip_in_cidr_from_args(input: &str) -> Result<IpInCidr, String> {
+    let (address, cidr) = input.trim().split_once(',')
+        .or_else(|| input.trim().split_once(char::is_whitespace))
+        .ok_or_else(|| format!("Expected \"address,cidr\", got '{}'", input.trim()))?;
+
+    mercy_ip_in_cidr(address, cidr)
+}
@@ -0,0 +1,219 @@
+/*
+    Machine-readable JSON for every dispatcher
+
+    `mercy_json` runs a call of any dispatcher and wraps the result in one envelope: the
+    module, method and input, the output, and the error, exactly one of the last two null.
+    The envelope is built with serde and never formatted by hand, and nothing in it panics:
+    a failure of the call lands in "error" as `MercyError::to_json` gives it.
+
+    Calls with a `_json` variant are run as that variant, so the output is the typed report
+    itself; NDJSON streams become an array of records and plain text stays a string. The
+    calls that only ever returned text are given structure here: `hex_dump` and `hex_dump_gz`
+    become rows of offset, hex and ASCII, and `system_info` keyed fields.
+*/
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sys_info::{cpu_num, cpu_speed, hostname, os_release, proc_total};
+
+use crate::{
+    capabilities::{dispatcher_name, has_json_variant},
+    error::MercyError,
+    file_hash_call,
+    file_hex_bytes,
+    gzip_hex_bytes,
+    hexdump::hexdump_rows,
+    hostinfo,
+    internal_ip_addr,
+    redact,
+    runtime,
+    stats
+};
+
+// system_info fields: the name the text form takes and the keys of the JSON form
+const SYSTEM_FIELDS: &[(&str, &[&str])] = &[
+    ("hostname", &["hostname"]),
+    ("cpu_cores", &["cpu_cores"]),
+    ("cpu_speed", &["cpu_speed_mhz"]),
+    ("os_release", &["os_release"]),
+    ("proc", &["processes"]),
+    ("memory", &["memory_total_bytes", "memory_available_bytes", "memory_free_bytes"]),
+    ("uptime", &["uptime_seconds", "boot_time"]),
+    ("disk", &["disk_total_bytes", "disk_used_bytes", "disk_free_bytes"]),
+    ("user", &["user"])
+];
+
+/// The envelope of a `mercy_json` answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MercyJson {
+    /// The dispatcher, as "mercy_decode"
+    pub module: String,
+    pub method: String,
+    /// The input as given, withheld ("<12 bytes withheld>") when it may be a secret
+    pub input: String,
+    /// The result: a report as its JSON, NDJSON as an array of records, text as a string; null on failure
+    pub output: Value,
+    /// `MercyError::to_json` of the failure ({code, numeric_code, message, context}); null on success
+    pub error: Option<Value>
+}
+
+/// Runs `call` of the dispatcher `module` ("decode" or "mercy_decode", "hex", "extra"...) and returns the result as a JSON envelope
+pub fn mercy_json(module: &str, call: &str, input: &str) -> String {
+    let dispatcher = dispatcher_name(module);
+
+    let result = run(&dispatcher, call, input).map_err(|e| e.with_context(&dispatcher, call, input, sensitive(&dispatcher, call)));
+
+    let (output, error) = match result {
+        Ok(output) => (output, None),
+        Err(e) => (Value::Null, Some(e.to_json()))
+    };
+    let envelope = MercyJson {
+        input: if sensitive(&dispatcher, call) { format!("<{} bytes withheld>", input.len()) } else { redact::redact_enabled(input.to_string()) },
+        module: dispatcher,
+        method: call.to_string(),
+        output,
+        error
+    };
+
+    serde_json::to_string_pretty(&envelope).unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string())
+}
+
+fn run(dispatcher: &str, call: &str, input: &str) -> Result<Value, MercyError> {
+    let call_json = match has_json_variant(dispatcher, call) {
+        true => format!("{}_json", call),
+        false => call.to_string()
+    };
+
+    match dispatcher {
+        // Decoded text stays text even when it happens to be JSON; only a call with a JSON variant is a report
+        "mercy_decode" if has_json_variant(dispatcher, call) => crate::mercy_decode_result(&call_json, input).map(output_value),
+        "mercy_decode" => crate::mercy_decode_result(call, input).map(Value::String),
+        "mercy_encode" => crate::mercy_encode_result(call, input).map(Value::String),
+        "mercy_hash" => crate::mercy_hash_result(&call_json, input).map(output_value),
+        "mercy_hex" if call == "hex_diff" => crate::mercy_hex_result(&call_json, input).map(output_value),
+        "mercy_hex" => hex_rows(call, input),
+        "mercy_malicious" => runtime::block_on(crate::mercy_malicious_async(&call_json, input)).map(output_value),
+        "mercy_extra" => match call {
+            "system_info" => system_info(input),
+            "internal_ip" => internal_ip_addr().map(|ip| Value::String(ip.to_string())).map_err(|e| failed(format!("Unable to find the internal IP address: {}", e))),
+            _ => dispatched(dispatcher, call, crate::mercy_extra(&call_json, input), "Unable to provide the information you requested")
+        },
+        "mercy_discover" => dispatched(dispatcher, call, crate::mercy_discover(&call_json, input), "Unable to run the discovery method requested"),
+        "mercy_forensics" => dispatched(dispatcher, call, crate::mercy_forensics(&call_json, input), "Unable to run the forensic method requested"),
+        "mercy_parse" => dispatched(dispatcher, call, crate::mercy_parse(&call_json, input), "Unable to parse the format requested"),
+        "mercy_solve" => dispatched(dispatcher, call, crate::mercy_solve(&call_json, input), "Unable to solve the cipher requested"),
+        "mercy_generate" => dispatched(dispatcher, call, crate::mercy_generate(&call_json, input), "Unable to generate the payloads requested"),
+        "mercy_canary" => dispatched(dispatcher, call, crate::mercy_canary(&call_json, input), "Unable to handle the canary request"),
+        _ => Err(MercyError::unsupported("mercy_json", dispatcher))
+    }
+}
+
+// Tokens and hashed text are treated as secrets, as the dispatchers' own errors treat them
+fn sensitive(dispatcher: &str, call: &str) -> bool {
+    match dispatcher {
+        "mercy_decode" => call.starts_with("jwt"),
+        "mercy_extra" => matches!(call.trim_end_matches("_json"), "password_strength" | "hibp_check"),
+        "mercy_hash" => !file_hash_call(call) && !call.starts_with("verify_manifest"),
+        _ => false
+    }
+}
+
+// A string dispatcher's output: its unknown-call message and a lone {"error": ...} are failures
+fn dispatched(dispatcher: &str, call: &str, output: String, unknown: &str) -> Result<Value, MercyError> {
+    if output == unknown {
+        return Err(MercyError::unsupported(dispatcher, call));
+    }
+
+    match output_value(output) {
+        Value::Object(object) if object.len() == 1 && object.get("error").is_some_and(Value::is_string) => {
+            let message = object.get("error").and_then(Value::as_str).unwrap_or_default();
+            Err(MercyError::from(message.to_string()))
+        },
+        value => Ok(value)
+    }
+}
+
+// JSON documents as they are, NDJSON as an array of its records, anything else as a string
+fn output_value(output: String) -> Value {
+    if let Ok(value @ (Value::Object(_) | Value::Array(_))) = serde_json::from_str::<Value>(&output) {
+        return value;
+    }
+
+    let records: Option<Vec<Value>> = output.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str::<Value>(line).ok().filter(Value::is_object))
+        .collect();
+    match records {
+        Some(records) if records.len() > 1 => Value::Array(records),
+        _ => Value::String(output)
+    }
+}
+
+// `hex_dump` and `hex_dump_gz` as [{offset, bytes, ascii}] rows, with the same "offset=" and "length=" options
+fn hex_rows(call: &str, input: &str) -> Result<Value, MercyError> {
+    let bytes = match call {
+        "hex_dump" => file_hex_bytes(input),
+        "hex_dump_gz" => gzip_hex_bytes(input),
+        _ => return Err(MercyError::unsupported("mercy_hex", call))
+    };
+
+    let (bytes, offset) = bytes.inspect_err(|e| stats::count_error(&e.to_string()))?;
+    serde_json::to_value(hexdump_rows(&bytes, offset)).map_err(|e| failed(e.to_string()))
+}
+
+// `system_info` as keyed fields ("all" or one field name); a field the system does not give is null
+fn system_info(input: &str) -> Result<Value, MercyError> {
+    let wanted = input.trim();
+    let fields: Vec<&(&str, &[&str])> = SYSTEM_FIELDS.iter().filter(|(name, _)| wanted == "all" || wanted.is_empty() || *name == wanted).collect();
+    if fields.is_empty() {
+        return Err(failed(format!("Unknown system_info field '{}' (expected hostname, cpu_cores, cpu_speed, os_release, proc, memory, uptime, disk, user or all)", wanted)));
+    }
+
+    let asked = fields.len();
+    let mut info = Map::new();
+    let mut errors = Vec::new();
+    for (name, keys) in fields {
+        let values = system_field(name).unwrap_or_else(|e| {
+            errors.push(format!("{}: {}", name, e));
+            vec![Value::Null; keys.len()]
+        });
+        for (key, value) in keys.iter().zip(values) {
+            info.insert(key.to_string(), value);
+        }
+    }
+
+    if errors.len() == asked {
+        return Err(failed(format!("Unable to gather system information ({})", errors.join("; "))));
+    }
+    Ok(Value::Object(info))
+}
+
+// The values of one field, in the order of its keys in SYSTEM_FIELDS
+fn system_field(name: &str) -> Result<Vec<Value>, String> {
+    let text = |e: sys_info::Error| e.to_string();
+    Ok(match name {
+        "hostname" => vec![Value::String(redact::redact_enabled(hostname().map_err(text)?))],
+        "cpu_cores" => vec![cpu_num().map_err(text)?.into()],
+        "cpu_speed" => vec![cpu_speed().map_err(text)?.into()],
+        "os_release" => vec![os_release().map_err(text)?.into()],
+        "proc" => vec![proc_total().map_err(text)?.into()],
+        "memory" => {
+            let memory = hostinfo::memory()?;
+            vec![memory.total.into(), memory.available.into(), memory.free.into()]
+        },
+        "uptime" => {
+            let uptime = hostinfo::uptime()?;
+            vec![uptime.into(), hostinfo::boot_time(uptime).into()]
+        },
+        "disk" => {
+            let disk = hostinfo::root_disk()?;
+            vec![disk.total.into(), disk.used.into(), disk.free.into()]
+        },
+        _ => vec![redact::redact_enabled(hostinfo::current_user()?).into()]
+    })
+}
+
+fn failed(message: String) -> MercyError {
+    stats::count_error(&message);
+    MercyError::from(message)
+}
@@ -0,0 +1,143 @@
+/*
+    JSON Web Token inspection (RFC 7519)
+
+    A compact JWT is three base64url segments (RFC 4648 section 5, padding usually dropped)
+    joined by dots: a JSON header, a JSON claims set and the signature. The header and claims
+    are decoded for reading only; the signature is measured but never verified, so nothing
+    here says whether a token is genuine.
+*/
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::timefmt::unix_to_rfc3339;
+
+/// The decoded parts of a compact JWT
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwt {
+    pub header: Value,
+    pub payload: Value,
+    /// Length of the decoded signature (0 for an unsecured "alg": "none" token)
+    pub signature_bytes: usize
+}
+
+impl fmt::Display for Jwt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pretty = |value: &Value| serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string());
+        writeln!(f, "{}", pretty(&self.header))?;
+        writeln!(f)?;
+        writeln!(f, "{}", pretty(&self.payload))?;
+        writeln!(f)?;
+        match self.signature_bytes {
+            0 => write!(f, "Signature: none (unsecured token)"),
+            bytes => write!(f, "Signature: {} bytes (not verified)", bytes)
+        }
+    }
+}
+
+/// The registered claims of a JWT, with the NumericDate ones as UTC times
+#[derive(Debug, Clone, Serialize)]
+pub struct JwtClaims {
+    /// Signing algorithm from the header
+    pub algorithm: Option<String>,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    /// A single audience is a list of one
+    pub audience: Vec<String>,
+    pub expires: Option<String>,
+    pub not_before: Option<String>,
+    pub issued_at: Option<String>,
+    pub jwt_id: Option<String>,
+    /// Whether "exp" has passed, by this host's clock
+    pub expired: Option<bool>
+}
+
+impl fmt::Display for JwtClaims {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        writeln!(f, "alg: {}", show(&self.algorithm))?;
+        writeln!(f, "iss: {}", show(&self.issuer))?;
+        writeln!(f, "sub: {}", show(&self.subject))?;
+        writeln!(f, "aud: {}", if self.audience.is_empty() { "-".to_string() } else { self.audience.join(", ") })?;
+        write!(f, "exp: {}", show(&self.expires))?;
+        if let Some(expired) = self.expired {
+            write!(f, " ({})", if expired { "expired" } else { "not expired" })?;
+        }
+        writeln!(f)?;
+        writeln!(f, "nbf: {}", show(&self.not_before))?;
+        writeln!(f, "iat: {}", show(&self.issued_at))?;
+        write!(f, "jti: {}", show(&self.jwt_id))
+    }
+}
+
+/// Decodes the header and claims of a compact JWT ("Bearer " prefix allowed) without verifying it
+pub fn parse_jwt(token: &str) -> Result<Jwt, String> {
+    let token = token.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+
+    let segments: Vec<&str> = token.split('.').collect();
+    if segments.len() != 3 {
+        return Err(format!("A compact JWT has three dot-separated segments (header.payload.signature), found {}", segments.len()));
+    }
+
+    let header = json_segment("header", segments[0])?;
+    let payload = json_segment("payload", segments[1])?;
+    let signature = base64url_decode(segments[2]).map_err(|e| format!("Invalid base64url in the signature: {}", e))?;
+
+    Ok(Jwt { header, payload, signature_bytes: signature.len() })
+}
+
+/// The registered claims (iss, sub, aud, exp, nbf, iat, jti) and algorithm of a compact JWT
+pub fn jwt_claims(token: &str) -> Result<JwtClaims, String> {
+    let jwt = parse_jwt(token)?;
+    let text = |value: Option<&Value>| value.map(|value| value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()));
+    let claim = |name: &str| jwt.payload.get(name);
+
+    let audience = match claim("aud") {
+        Some(Value::Array(audiences)) => audiences.iter().map(|audience| text(Some(audience)).unwrap_or_default()).collect(),
+        Some(audience) => vec![text(Some(audience)).unwrap_or_default()],
+        None => Vec::new()
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0);
+
+    Ok(JwtClaims {
+        algorithm: text(jwt.header.get("alg")),
+        issuer: text(claim("iss")),
+        subject: text(claim("sub")),
+        audience,
+        expires: claim("exp").map(numeric_date),
+        not_before: claim("nbf").map(numeric_date),
+        issued_at: claim("iat").map(numeric_date),
+        jwt_id: text(claim("jti")),
+        expired: claim("exp").and_then(Value::as_f64).map(|exp| (exp as i64) <= now)
+    })
+}
+
+// Header and payload must be JSON objects
+fn json_segment(name: &str, segment: &str) -> Result<Value, String> {
+    let bytes = base64url_decode(segment).map_err(|e| format!("Invalid base64url in the {}: {}", name, e))?;
+    match serde_json::from_slice(&bytes) {
+        Ok(Value::Object(object)) => Ok(Value::Object(object)),
+        Ok(_) => Err(format!("The {} is JSON but not an object", name)),
+        Err(e) => Err(format!("The {} is not JSON ({})", name, e))
+    }
+}
+
+// base64url, with or without "=" padding
+fn base64url_decode(segment: &str) -> Result<Vec<u8>, String> {
+    base64::decode_config(segment.trim_end_matches('='), base64::URL_SAFE_NO_PAD).map_err(|e| e.to_string())
+}
+
+// NumericDate (seconds since the epoch, possibly fractional) as UTC; other values as they are
+fn numeric_date(value: &Value) -> String {
+    match value.as_f64() {
+        Some(secs) if secs.is_finite() && secs.abs() < 1e15 => unix_to_rfc3339(secs.floor() as i64, 0),
+        _ => value.to_string()
+    }
+}
@@ -5,12 +5,27 @@
 //! | Function                | More Info                              |
 //! | ----------------------- | -------------------------------------- |
 //! | `mercy_source`          | Learn more about the crate             |
-//! | `mercy_decode`          | Supports: base64, rot13                |
-//! | `mercy_encode`          | Supports: base64                       |
-//! | `mercy_hash`            | Supports: sha2_256, md5                |
+//! | `mercy_decode`          | Supports: base64, rot13, morse, nato   |
+//! | `mercy_encode`          | Supports: base64, morse, nato, qr      |
+//! | `mercy_hash`            | Supports: sha1/2/3, md5, crc32, xxhash |
+//! | `mercy_hash_verify`     | Check a digest against a checksum      |
+//! | `mercy_hash_compare`    | Fuzzy hash similarity from 0 to 100    |
+//! | `mercy_hmac`            | Supports: hmac_sha256/sha512/sha1/md5  |
 //! | `mercy_hex`             | Dump hexadecimal values of a file      |
 //! | `mercy_malicious`       | Malware detection or malicious intent  |
 //! | `mercy_extra`           | Information about various data points  |
+//! | `mercy_discover`        | Host discovery sweeps across a CIDR    |
+//! | `mercy_forensics`       | Forensic triage of files and artifacts |
+//! | `mercy_parse`           | Supports: plist, mobileconfig, sbom    |
+//! | `mercy_solve`           | Supports: substitution                 |
+//! | `mercy_generate`        | Supports: fuzz_strings, fuzz_bytes     |
+//! | `mercy_canary`          | Canary tokens and their triggers       |
+//! | `mercy_json`            | Any of the above as a JSON envelope    |
+//! 
+//! Nothing is written to stdout or stderr: results come back from the functions, and diagnostics
+//! go through the `log` facade under the module's path as target (`mercy::net`, `mercy::whois`...):
+//! debug for request URLs, warn for retries and fallbacks, error for failed lookups. Install a
+//! logger such as env_logger or tracing-log to see them; without one they cost nothing.
 //! 
 
 /*
@@ -19,20 +34,19 @@
     License: BSD 2-Clause
 */
 
-use std::{io::Write, net::TcpStream, str::from_utf8};
+use std::fmt;
+use serde::Serialize;
 use serde_json::Value;
 
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     fs::{self, File},
-    io::Read,
-    net::UdpSocket
+    io::{self, Read, Seek, SeekFrom},
+    net::{IpAddr, UdpSocket},
+    sync::Arc
 };
 
-use base64;
-use md5;
-use sha2::{Sha256, Digest};
-use hexdump::hexdump;
+use sha2::{Sha256, Sha512, Digest};
 
 use sys_info::{
     hostname,
@@ -42,127 +56,1752 @@ use sys_info::{
     proc_total
 };
 
+use capabilities::Handler;
+use stats::Counter;
+
+mod ansi;
+mod apk;
+mod asn;
+mod banner;
+mod batch;
+mod blocklist;
+mod bogon;
+mod canary;
+mod capabilities;
+mod carve;
+mod charset;
+mod checks;
+mod checksum;
+mod cidr;
+mod cipher;
+mod cluster;
+mod codes;
+mod config;
+mod csp;
+mod deflate;
+mod digests;
+mod discover;
+mod dns;
+mod domaincat;
+mod emailheaders;
+mod endpoints;
+mod entropy;
+mod error;
+mod escape;
+mod evtx;
+mod exif;
+mod filemap;
+mod filetype;
+mod finding;
+mod fuzz;
+mod gitsecrets;
+mod hashid;
+mod hashing;
+mod headers;
+mod hexdiff;
+mod hexdump;
+mod homograph;
+mod hostinfo;
+mod hsts;
+mod http;
+mod httpheaders;
+mod icmp;
+mod inflate;
+mod inquest;
+mod ipcalc;
+mod jsonmode;
+mod jwt;
+mod listen;
+mod logmerge;
+mod logtime;
+mod mac;
+mod manifest;
+mod metrics;
+mod minidump;
+mod net;
+mod notify;
+mod oci;
+mod osguess;
+mod pacer;
+mod password;
+mod paths;
+mod pcap;
+mod pe;
+mod ping;
+mod plist;
+mod portscan;
+mod prefetch;
+mod prefixset;
+mod publicip;
+mod punycode;
+#[cfg(feature = "image")]
+mod qr;
+mod radix;
+#[cfg(feature = "image")]
+mod raster;
+mod redact;
+mod registry;
+mod rng;
+mod runtime;
+mod safedisplay;
+mod sbom;
+mod secrets;
+mod secretscan;
+mod selftest;
+mod shimcache;
+mod sink;
+mod sinkhole;
+mod snapshot;
+mod sri;
+mod ssdeep;
+mod stats;
+#[cfg(feature = "image")]
+mod stego;
+mod strings;
+mod subdomains;
+mod tar;
+#[cfg(feature = "testing")]
+mod testing;
+mod text;
+mod timeconv;
+mod timefmt;
+mod timeline;
+mod tlscert;
+mod traceroute;
+mod triage;
+mod unicode;
+mod urlinfo;
+mod uuid;
+mod version;
+mod walk;
+mod whois;
+mod x509;
+mod xor;
+mod xpress;
+mod zip;
+mod ziplist;
+mod zonefile;
+
+pub use ansi::{
+    AnsiReport,
+    AnsiSequence,
+    mercy_ansi_inspect,
+    mercy_strip_ansi
+};
+
+pub use apk::{
+    ApkComponent,
+    ApkDex,
+    ApkPermission,
+    ApkReport,
+    mercy_apk
+};
+
+pub use asn::{
+    AsnInfo,
+    AsnOptions,
+    AsnReport,
+    Pfx2As,
+    mercy_asn_lookup,
+    mercy_asn_offline,
+    mercy_asn_whois
+};
+
+pub use banner::{
+    BannerGrab,
+    BannerOptions,
+    BannerProbe,
+    escape_banner,
+    mercy_banner_grab
+};
+
+pub use batch::{
+    BATCH_CONCURRENCY,
+    BatchOutput,
+    BatchReport
+};
+
+pub use blocklist::{
+    BLOCKLIST_ENV,
+    Blocklist,
+    BlocklistMatch
+};
+
+pub use bogon::{
+    BogonMatch,
+    BogonReport,
+    mercy_bogon_check
+};
+
+pub use canary::{
+    CanaryKind,
+    CanaryOptions,
+    CanaryToken,
+    CanaryTrigger,
+    canary_domain,
+    mercy_canary_generate,
+    mercy_canary_generate_with,
+    mercy_canary_parse_trigger,
+    set_canary_domain
+};
+
+pub use capabilities::{
+    Capability,
+    CapabilityEnvironment,
+    CapabilityReport,
+    CapabilityStatus,
+    InputKind,
+    MethodInfo,
+    mercy_capabilities,
+    mercy_methods,
+    mercy_validate
+};
+
+pub use carve::{
+    CarveOptions,
+    CarveReport,
+    CarveType,
+    CarvedObject,
+    carve_file,
+    mercy_carve
+};
+
+pub use charset::{
+    Charset,
+    EncodingConfidence,
+    EncodingReport,
+    Transcoded,
+    mercy_detect_encoding,
+    transcode
+};
+
+pub use checks::{
+    FtpCheck,
+    RdpCheck,
+    SmbCheck,
+    TelnetCheck,
+    mercy_ftp_check,
+    mercy_rdp_check,
+    mercy_smb_check,
+    mercy_telnet_check
+};
+
+pub use checksum::{
+    crc32,
+    xxhash64
+};
+
+pub use cidr::Cidr;
+
+pub use cipher::{
+    CaesarCandidate,
+    CaesarSolution,
+    Digraph,
+    Frequency,
+    FrequencyReport,
+    SubstitutionOptions,
+    SubstitutionSolution,
+    mercy_caesar_solve,
+    mercy_freq_analysis,
+    mercy_substitution_solve
+};
+
+pub use cluster::{
+    Cluster,
+    ClusterMember,
+    ClusterMethod,
+    ClusterOptions,
+    ClusterReport,
+    mercy_cluster
+};
+
+pub use codes::{
+    morse_decode,
+    morse_encode,
+    nato_decode,
+    nato_encode
+};
+
+pub use config::{
+    API_TOKEN_SERVICES,
+    MercyConfig,
+    MercyConfigBuilder,
+    mercy_config,
+    set_mercy_config,
+    with_mercy_config,
+    with_mercy_config_async
+};
+
+pub use csp::{
+    CspAudit,
+    CspDirective,
+    CspPolicy,
+    mercy_csp_audit
+};
+
+pub use discover::{
+    MAX_SWEEP_HOSTS,
+    SweepHost,
+    SweepMethod,
+    SweepOptions,
+    SweepReport,
+    mercy_discover,
+    mercy_sweep
+};
+
+pub use escape::{
+    cmd_unix_decode,
+    cmd_unix_encode,
+    cmd_windows_decode,
+    cmd_windows_encode,
+    html_attr_decode,
+    html_attr_encode,
+    html_entity_encode,
+    js_string_decode,
+    js_string_encode,
+    sql_string_decode,
+    sql_string_encode,
+    url_decode,
+    url_decode_bytes,
+    url_encode,
+    url_encode_bytes
+};
+
+pub use dns::{
+    DnsLookup,
+    DnsLookupOptions,
+    DnsLookupStatus,
+    DnsQuery,
+    DnsQuestion,
+    DnsRdata,
+    DnsRecord,
+    DnsResponse,
+    DnsType,
+    EdnsInfo,
+    default_dns_server,
+    dns_server_addr,
+    mercy_dns_lookup,
+    mercy_reverse_dns,
+    reverse_lookup,
+    reverse_name
+};
+
+pub use domaincat::{
+    CategoryMatch,
+    CategoryWordlist,
+    DomainCategoryReport,
+    mercy_domain_category
+};
+
+pub use emailheaders::{
+    AuthResult,
+    EmailAddress,
+    EmailHeader,
+    EmailHeaderReport,
+    ReceivedHop,
+    mercy_email_headers
+};
+
+pub use endpoints::{
+    ServiceEndpoints,
+    service_endpoints,
+    set_service_endpoints
+};
+
+pub use entropy::{
+    EntropyPoint,
+    EntropyProfile,
+    EntropyRegion,
+    EntropySection,
+    EntropySections,
+    entropy_bytes,
+    mercy_entropy_file,
+    mercy_entropy_profile,
+    mercy_entropy_sections
+};
+
+pub use error::{
+    ErrorContext,
+    MercyError
+};
+
+pub use evtx::{
+    EvtxOptions,
+    EvtxRecord,
+    EvtxSummary,
+    read_evtx,
+    write_evtx_ndjson
+};
+
+pub use exif::{
+    GpsPosition,
+    ImageMetadata,
+    TrailingData,
+    mercy_exif
+};
+
+pub use filemap::{
+    FileAccess,
+    file_access,
+    set_file_access
+};
+
+pub use filetype::{
+    FileType,
+    mercy_file_type
+};
+pub use finding::{
+    Finding,
+    Severity,
+    findings_csv,
+    findings_markdown
+};
+
+pub use fuzz::{
+    FuzzMutation,
+    FuzzOptions,
+    FuzzString,
+    mercy_fuzz_bytes,
+    mercy_fuzz_strings
+};
+
+pub use gitsecrets::{
+    GitSecret,
+    GitSecretOptions,
+    GitSecretSummary,
+    mercy_secrets_git,
+    read_git_secrets
+};
+
+pub use hashid::{
+    HashCandidate,
+    HashIdentification,
+    mercy_hash_identify
+};
+
+pub use hashing::{
+    FILE_HASH_ALGORITHMS,
+    GZIP_HASH_ALGORITHMS,
+    INCREMENTAL_HASH_ALGORITHMS,
+    MercyHasher,
+    RESUMABLE_HASH_ALGORITHMS,
+    mercy_hash_file,
+    mercy_hash_gzip_file
+};
+
+pub use headers::audit_security_headers;
+
+pub use hexdiff::{
+    HEXDIFF_REGION_BYTES,
+    HexDiff,
+    HexDiffOptions,
+    HexDiffRegion,
+    HexDiffTail,
+    mercy_hex_diff
+};
+
+pub use hexdump::{
+    HEXDUMP_WIDTH,
+    HexRow,
+    hexdump_bytes,
+    hexdump_rows
+};
+
+pub use homograph::{
+    Confusable,
+    HomographReport,
+    LabelCheck,
+    mercy_homograph_check
+};
+
+pub use hsts::{
+    HstsCriterion,
+    HstsHop,
+    HstsOptions,
+    HstsReport,
+    PRELOAD_MIN_MAX_AGE,
+    mercy_hsts_check
+};
+
+pub use http::{
+    HttpIdentity,
+    UserAgent,
+    http_identity,
+    set_http_identity
+};
+
+pub use httpheaders::{
+    HttpHeader,
+    HttpHeaders,
+    HttpHeadersOptions,
+    HttpRedirect,
+    SecurityHeader,
+    SecurityHeadersReport,
+    mercy_http_headers,
+    mercy_security_headers
+};
+
+pub use inquest::{
+    HashReport,
+    MaliciousReport,
+    MaliciousSample
+};
+
+pub use ipcalc::{
+    CidrRange,
+    IpClass,
+    IpInCidr,
+    mercy_cidr_range,
+    mercy_ip_in_cidr,
+    mercy_is_private
+};
+
+pub use jsonmode::{
+    MercyJson,
+    mercy_json
+};
+
+pub use jwt::{
+    Jwt,
+    JwtClaims,
+    jwt_claims,
+    parse_jwt
+};
+
+pub use listen::{
+    ConnectionRecord,
+    ListenHandle,
+    ListenOptions,
+    ListenSummary,
+    Persona,
+    mercy_listen,
+    ndjson_logger
+};
+
+pub use logmerge::{
+    MergeOptions,
+    MergeSource,
+    MergeSummary,
+    mercy_logs_merge
+};
+
+pub use logtime::{
+    DetectedTimestamp,
+    LogTimeOptions,
+    NormalizeSummary,
+    detect_timestamp,
+    normalize_timestamps,
+    normalize_timestamps_file,
+    parse_any_timestamp
+};
+
+pub use mac::{
+    MacLookup,
+    mercy_mac_lookup,
+    mercy_mac_lookup_file
+};
+
+pub use manifest::{
+    ManifestEntry,
+    ManifestReport,
+    ManifestStatus,
+    mercy_verify_manifest
+};
+
+pub use metrics::{
+    CheckResult,
+    mercy_metrics_render,
+    mercy_metrics_render_at
+};
+
+pub use minidump::{
+    Minidump,
+    MinidumpException,
+    MinidumpInfo,
+    MinidumpModule,
+    MinidumpOptions,
+    MinidumpRegion,
+    MinidumpString,
+    MinidumpSummary,
+    MinidumpSystem,
+    read_minidump,
+    write_minidump_ndjson
+};
+
+pub use net::{
+    DEFAULT_RETRIES,
+    DEFAULT_RETRY_BACKOFF,
+    MAX_RETRY_AFTER
+};
+
+pub use notify::{
+    NotifyAuth,
+    NotifyFormat,
+    NotifyOptions,
+    NotifyPayload,
+    NotifyReport,
+    NotifyTarget,
+    last_notification,
+    mercy_notify,
+    notify_target,
+    set_notify_target,
+    verify_notify_signature
+};
+
+pub use oci::{
+    OciEnv,
+    OciHistory,
+    OciLayer,
+    OciLayerScan,
+    OciMatch,
+    OciReport,
+    OciScanOptions,
+    OciScanReport,
+    mercy_oci,
+    mercy_oci_scan
+};
+
+pub use osguess::{
+    OsGuess,
+    mercy_os_guess,
+    os_guess_from
+};
+
+pub use pacer::MercyPacer;
+
+pub use password::{
+    PasswordClasses,
+    PasswordPattern,
+    PasswordStrength,
+    PwnedPassword,
+    mercy_hibp_check,
+    mercy_password_strength
+};
+
+pub use paths::{
+    path_from_string,
+    path_is_utf8,
+    path_to_string
+};
+
+pub use pcap::{
+    PcapPort,
+    PcapProtocols,
+    PcapSummary,
+    PcapTalker,
+    mercy_pcap_summary
+};
+
+pub use pe::{
+    PeInfo,
+    PeOverlay,
+    PeSection,
+    mercy_pe_info
+};
+
+pub use ping::{
+    PingMethod,
+    PingProbe,
+    PingReport,
+    mercy_ping,
+    mercy_ping_async
+};
+
+pub use plist::{
+    MobileconfigReport,
+    PlistValue,
+    ProfilePayload,
+    mercy_mobileconfig,
+    mercy_plist,
+    parse_plist
+};
+
+pub use portscan::{
+    PortCheck,
+    PortOptions,
+    PortScanReport,
+    PortState,
+    mercy_port_check,
+    mercy_port_scan
+};
+
+pub use prefetch::{
+    PrefetchInfo,
+    PrefetchVolume,
+    mercy_prefetch
+};
+
+pub use prefixset::{
+    PrefixContainsReport,
+    PrefixMembership,
+    PrefixSet,
+    mercy_prefix_contains
+};
+
+pub use publicip::mercy_external_ip;
+
+pub use punycode::{
+    punycode_decode,
+    punycode_encode
+};
+
+#[cfg(feature = "image")]
+pub use qr::{
+    QrCode,
+    QrDecodeReport,
+    QrEncodeOptions,
+    QrEncodeReport,
+    QrErrorCorrection,
+    QrSegment,
+    mercy_qr_decode,
+    mercy_qr_encode
+};
+
+pub use radix::{
+    Base64Variant,
+    ascii85_decode,
+    ascii85_encode,
+    base32_decode,
+    base32_encode,
+    base58_decode,
+    base58_encode,
+    base64_detect,
+    base64url_decode,
+    base64url_encode,
+    hex_decode,
+    hex_encode,
+    z85_decode,
+    z85_encode
+};
+
+pub use redact::{
+    RedactedValue,
+    RedactionPolicy,
+    mercy_redact_output,
+    redaction_key,
+    redaction_policy,
+    reset_redaction_key,
+    set_redaction
+};
+
+pub use rng::MercyRng;
+
+pub use safedisplay::{
+    DisplayKind,
+    SafeDisplay,
+    mercy_safe_display
+};
+
+pub use sbom::{
+    SbomCheckReport,
+    SbomComponent,
+    SbomReport,
+    SbomVulnerability,
+    mercy_sbom,
+    mercy_sbom_check
+};
+
+pub use secretscan::{
+    SecretFinding,
+    SecretScan,
+    mercy_secret_scan_file,
+    mercy_secret_scan_text
+};
+
+pub use selftest::{
+    SelftestCheck,
+    SelftestOptions,
+    SelftestReport,
+    SelftestStatus,
+    mercy_selftest
+};
+
+pub use shimcache::{
+    ShimcacheEntry,
+    ShimcacheReport,
+    mercy_shimcache
+};
+
+pub use sink::{
+    Backpressure,
+    OutputSink,
+    SinkOptions,
+    SinkStats,
+    SinkTarget
+};
+
+pub use sinkhole::{
+    SinkholeEvidence,
+    SinkholeList,
+    SinkholeOptions,
+    SinkholePattern,
+    SinkholeRange,
+    SinkholeReport,
+    mercy_sinkhole_check,
+    set_sinkhole_list,
+    sinkhole_list
+};
+
+pub use snapshot::{
+    SNAPSHOT_SCHEMA_VERSION,
+    Snapshot,
+    SnapshotOptions,
+    SnapshotSection,
+    load_snapshot,
+    save_snapshot
+};
+
+pub use sri::{
+    SriAlgorithm,
+    SriAudit,
+    SriHashes,
+    SriOptions,
+    SriTag,
+    mercy_sri,
+    mercy_sri_audit
+};
+
+pub use ssdeep::{
+    ssdeep_compare,
+    ssdeep_hash
+};
+
+pub use stats::{
+    MercyStats,
+    mercy_stats,
+    reset_mercy_stats
+};
+
+#[cfg(feature = "image")]
+pub use stego::{
+    AppendedData,
+    BitPlane,
+    ChunkAnomaly,
+    StegoReport,
+    StegoSignal,
+    mercy_stego_check,
+    mercy_stego_extract
+};
+
+#[cfg(feature = "testing")]
+pub use testing::{
+    MOCK_BANNER,
+    MOCK_CRTSH,
+    MOCK_CRTSH_DOMAIN,
+    MOCK_CYMRU,
+    MOCK_EXTERNAL_IP,
+    MOCK_INQUEST_EMPTY,
+    MOCK_INQUEST_HASH,
+    MOCK_INQUEST_MALICIOUS,
+    MOCK_MALICIOUS_DOMAIN,
+    MOCK_MALICIOUS_HASH,
+    MOCK_PWNED_PASSWORD,
+    MOCK_PWNED_RANGE,
+    MOCK_WHOIS,
+    MockDns,
+    MockHttp,
+    MockRequest,
+    MockRoute,
+    MockServices,
+    MockTcp
+};
+
+pub use strings::{
+    ExtractedString,
+    StringEncoding,
+    StringsOptions,
+    StringsReport,
+    mercy_strings
+};
+
+pub use subdomains::{
+    Subdomains,
+    mercy_subdomains
+};
+
+pub use text::{
+    TextEncoding,
+    TextLines,
+    decode_text,
+    detect_text_encoding,
+    normalize_newlines,
+    read_text_file,
+    read_text_lines
+};
+
+pub use timeconv::{
+    TimestampConversion,
+    epoch_to_utc,
+    filetime_to_utc,
+    utc_to_epoch
+};
+
+pub use timeline::{
+    TimelineEntry,
+    TimelineFormat,
+    TimelineOptions,
+    TimelineSummary,
+    Timestamp,
+    write_timeline
+};
+
+pub use tlscert::{
+    TlsCertOptions,
+    TlsCertificate,
+    mercy_tls_cert
+};
+
+pub use traceroute::{
+    TraceHop,
+    TraceMethod,
+    TracerouteOptions,
+    TracerouteReport,
+    mercy_traceroute
+};
+
+pub use triage::{
+    Indicator,
+    IndicatorKind,
+    TriageOptions,
+    TriageReport,
+    TriageStatus,
+    TriageStep,
+    mercy_triage
+};
+
+pub use unicode::{
+    UnicodeCharacter,
+    UnicodeFinding,
+    UnicodeReport,
+    mercy_unicode_inspect,
+    mercy_unicode_inspect_file
+};
+
+pub use urlinfo::{
+    QueryParam,
+    UrlParts,
+    UrlWarning,
+    mercy_url_normalize,
+    mercy_url_parse
+};
+
+pub use uuid::{
+    UuidInfo,
+    mercy_uuid_inspect,
+    mercy_uuid_v4,
+    mercy_uuid_v4_with,
+    mercy_uuid_v5
+};
+
+pub use whois::{
+    WhoisResponse,
+    mercy_whois
+};
+
+pub use x509::CertificateInfo;
+
+pub use xor::{
+    XorCandidate,
+    XorRanking,
+    xor_brute,
+    xor_bytes
+};
+
+pub use ziplist::{
+    ZipListEntry,
+    ZipListing,
+    mercy_zip_list
+};
+
+pub use zonefile::{
+    ZoneAudit,
+    ZoneAuditOptions,
+    ZoneFile,
+    ZoneRecord,
+    mercy_zone_audit,
+    mercy_zonefile
+};
+
 /// Learn more about the crate
 pub fn mercy_source() -> String {
     const VERSION: &str = "1.2.17";
     const AUTHOR: &str = "Catherine Framework (https://github.com/CatherineFramework)";
-    return format!("Author: {}\nVersion: {}\nDocumentation: https://docs.rs/crate/mercy/latest", AUTHOR, VERSION);
+    format!("Author: {}\nVersion: {}\nDocumentation: https://docs.rs/crate/mercy/latest", AUTHOR, VERSION)
 }
 
 /* Public decoding methods provided by Mercy */
 
-/// Supports: base64, rot13
-pub fn mercy_decode(mercy_call: &str, mercy_string: &str) -> String {
-    match mercy_call {
-        "base64" => base64_decode(mercy_string.to_string()),
-        "rot13" => rot13_decode(mercy_string.to_string()),
-         _ => unknown_msg("Unable to decode message")
-    }
+/// Supports: base64, base64_url, base64_detect, rot13, rot, rot47, rot_brute, morse, nato, hex, base32, base58, base85, z85, url, url_form, html_entity, html_attr, js_string, sql_string, cmd_unix, cmd_windows, jwt, jwt_claims, xor, xor_brute, xor_brute_json, punycode, utf16le, utf16be, latin1, windows1252
+/// 
+/// `base64` / `base64_url` - The standard alphabet and the URL-safe one ("-" and "_" for "+" and "/"), padded or not; whitespace, such as MIME's line breaks every 76 characters, is ignored, and a character of the other alphabet is an error saying so
+/// 
+/// `base64_detect` - Which base64 form the text is in: the alphabet (standard, URL-safe, or either when it has none of "+/-_"), padded, unpadded or none needed, the line length when wrapped, and the decoded size; text that does not decode in the form found is an error
+/// 
+/// `rot` - Caesar shift of any size, input "13,text" (shift 1 to 25, then the text): letters are rotated back by the shift keeping their case, everything else passes through unchanged
+/// 
+/// `rot47` - ROT47: the printable ASCII characters '!' to '~' rotated by 47 (its own inverse, common in obfuscated JavaScript); spaces and other characters unchanged
+/// 
+/// `rot_brute` - All 25 Caesar decodings of the text, one line each as "rot3: text" (the shift `rot` would take), the most English-like by letter frequencies marked "<- most English-like"; line breaks in the text are shown as "\n"
+/// 
+/// `hex` - Either case, with an optional "0x" prefix and whitespace between bytes
+/// 
+/// `base32` - RFC 4648, either case, padded or not
+/// 
+/// `base58` / `base85` / `z85` - Bitcoin-alphabet base58 (leading '1's are zero bytes), Adobe ASCII85 (the "<~" and "~>" delimiters optional, "z" for four zero bytes) and Z85; decoded bytes that are not valid UTF-8 come back as "hex:" and their lowercase hex
+/// 
+/// `url` / `url_form` - `%XX` escapes in either case, multi-byte UTF-8 included; `url_form` also reads "+" as a space (form data); a truncated escape such as "%2" is an error. One layer is removed per call, so "%2541" gives "%41"
+/// 
+/// `html_entity` - Named (`&amp;`, `&lt;`, `&gt;`, `&quot;`, `&apos;`, `&nbsp;`), decimal (`&#65;`) and hex (`&#x41;`) character references; anything else is left as it is
+/// 
+/// `jwt` - Header and payload of a compact JWT (base64url, padded or not; a "Bearer " prefix is ignored) as pretty-printed JSON separated by a blank line, then the signature length in bytes; the signature is not verified
+/// 
+/// `jwt_claims` - The registered claims of a JWT one per line (alg, iss, sub, aud, exp with whether it has passed, nbf, iat, jti), dates as UTC
+/// 
+/// `xor` - "<hex payload>,<hex key>" XORed with the key repeated over the payload (input "1b37373331363f,58" or a multi-byte key "...,deadbeef")
+/// 
+/// `punycode` - Each "xn--" label of a domain decoded to Unicode ("xn--80ak6aa92e.com" gives "аррӏе.com"), other labels unchanged; a label that is not valid punycode is an error
+/// 
+/// `xor_brute` - The five single-byte keys whose output of a hex payload looks most like English text, best first, one line each as "key 0x58 score 0.93: <plaintext>" (the score is printable share times English letter similarity, 0 to 1; control characters in the plaintext are escaped)
+/// 
+/// `xor_brute_json` - The same five as a JSON array of key, score, printable share and plaintext
+/// 
+/// `utf16le` / `utf16be` / `latin1` / `windows1252` - Hex, or the path of a file, in that encoding transcoded to UTF-8 for display (a UTF-16 byte order mark dropped); lone surrogates, a truncated trailing byte and bytes Windows-1252 leaves undefined become U+FFFD, and a last line "[warning: 1 lone surrogate(s) replaced with U+FFFD]" counts them
+/// 
+/// Other bytes that are not valid UTF-8 are replaced with U+FFFD; malformed input returns an error message
+/// 
+/// The context-specific forms reverse the matching `mercy_encode` method (see `mercy_encode`)
+pub fn mercy_decode(mercy_call: &str, mercy_string: &str) -> String {
+    result_text(mercy_call, mercy_decode_result(mercy_call, mercy_string), "Unable to decode message")
+}
+
+/// `mercy_decode` with failures as a `MercyError` rather than text in the output; never panics
+/// 
+/// Decoded bytes that are not valid UTF-8 are replaced with U+FFFD (or given as "hex:..." for base58, base85 and z85), as in `mercy_decode`; use `hex_decode`, `base32_decode`, `base58_decode`, `ascii85_decode` or `z85_decode` for the raw bytes
+pub fn mercy_decode_result(mercy_call: &str, mercy_string: &str) -> Result<String, MercyError> {
+    let output = run_decode(capabilities::handler("mercy_decode", mercy_call), mercy_call, mercy_string);
+
+    finish_result(output.map_err(|e| e.with_context("mercy_decode", mercy_call, mercy_string, decode_sensitive(mercy_call))))
+}
+
+/// `mercy_decode` over many inputs: one output per input, in order, each the decoded text or that input's error; the call is resolved once and a large batch is decoded on every CPU thread
+pub fn mercy_decode_batch(mercy_call: &str, inputs: &[&str]) -> Vec<String> {
+    decode_batch(mercy_call, inputs).into_iter().map(|output| output.unwrap_or_else(|e| e)).collect()
+}
+
+/// `mercy_decode_batch` with each output also streamed to `output.sink` and a summary posted to `output.notify` when it finishes; fails only when the sink cannot be opened or written to
+pub fn mercy_decode_batch_to(mercy_call: &str, inputs: &[&str], output: &BatchOutput) -> Result<BatchReport, String> {
+    batch::deliver(mercy_call, output, || decode_batch(mercy_call, inputs))
+}
+
+// The decoded texts of a batch, failures as their error text
+fn decode_batch(mercy_call: &str, inputs: &[&str]) -> Vec<Result<String, String>> {
+    let handler = capabilities::handler("mercy_decode", mercy_call);
+    if !matches!(handler, Some(Handler::Decode(_) | Handler::Method(_))) {
+        return vec![Err(unknown_msg("Unable to decode message")); inputs.len()];
+    }
+    let sensitive = decode_sensitive(mercy_call);
+
+    batch::map_parallel(inputs, |input| {
+        let output = finish_result(run_decode(handler, mercy_call, input).map_err(|e| e.with_context("mercy_decode", mercy_call, input, sensitive)));
+        let failed = output.is_err();
+        let text = result_text(mercy_call, output, "Unable to decode message");
+        if failed { Err(text) } else { Ok(text) }
+    })
+}
+
+/// The raw bytes of a decode, untouched by any UTF-8 conversion, for binary payloads such as shellcode
+/// 
+/// Supports the byte encodings of `mercy_decode`: base64, base64_url, hex, base32, base58, base85, z85, url, url_form and xor; `mercy_decode` gives the same bytes as text
+pub fn mercy_decode_bytes(mercy_call: &str, mercy_string: &str) -> Result<Vec<u8>, MercyError> {
+    decode_bytes(mercy_call, mercy_string)
+        .map_err(|e| counted_error(e.with_context("mercy_decode_bytes", mercy_call, mercy_string, false)))
+}
+
+/// Encodes any bytes, UTF-8 or not, so `mercy_decode_bytes` gives them back unchanged
+/// 
+/// Supports base64, base64_url, hex, base32, base58, base85, z85 (whose input must be a multiple of 4 bytes), url and url_form; a failure is returned as its message, as from `mercy_encode`
+pub fn mercy_encode_bytes(mercy_call: &str, data: &[u8]) -> String {
+    let output = encode_bytes(mercy_call, data).map_err(|e| counted_error(e.with_context("mercy_encode", mercy_call, "", true)));
+    result_text(mercy_call, output.map(redact::redact_enabled), "Unable to encode message")
+}
+
+// The byte decoders behind `mercy_decode_bytes` and the byte calls of `mercy_decode`
+fn decode_bytes(mercy_call: &str, text: &str) -> Result<Vec<u8>, MercyError> {
+    let decoded = match mercy_call {
+        "base64" => radix::base64_standard_decode(text),
+        "base64_url" => base64url_decode(text),
+        "hex" => hex_decode(text),
+        "base32" => base32_decode(text),
+        "base58" => base58_decode(text),
+        "base85" => ascii85_decode(text),
+        "z85" => z85_decode(text),
+        "url" => url_decode_bytes(text, false),
+        "url_form" => url_decode_bytes(text, true),
+        "xor" => xor::xor_from_args(text),
+        _ => return Err(MercyError::unsupported("mercy_decode_bytes", mercy_call))
+    };
+    decoded.map_err(|e| MercyError::invalid_encoding(mercy_call, e))
+}
+
+// Decoded bytes as `mercy_decode` returns them: invalid UTF-8 replaced with U+FFFD, or for base58, base85 and z85 given as "hex:..."
+fn decoded_text(mercy_call: &str, text: &str) -> Result<String, MercyError> {
+    let bytes = decode_bytes(mercy_call, text)?;
+    Ok(match mercy_call {
+        "base58" | "base85" | "z85" => radix::utf8_or_hex(bytes),
+        _ => String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+// The byte encoders behind `mercy_encode_bytes` and the byte calls of `mercy_encode`
+fn encode_bytes(mercy_call: &str, data: &[u8]) -> Result<String, MercyError> {
+    match mercy_call {
+        "base64" => Ok(base64::encode(data)),
+        "base64_url" => Ok(base64url_encode(data)),
+        "hex" => Ok(hex_encode(data)),
+        "base32" => Ok(base32_encode(data)),
+        "base58" => Ok(base58_encode(data)),
+        "base85" => Ok(ascii85_encode(data)),
+        "z85" => z85_encode(data).map_err(|e| MercyError::invalid_encoding("z85", e)),
+        "url" => Ok(url_encode_bytes(data, false)),
+        "url_form" => Ok(url_encode_bytes(data, true)),
+        _ => Err(MercyError::unsupported("mercy_encode_bytes", mercy_call))
+    }
+}
+
+// A `mercy_decode` handler run on one input; a call with a JSON variant is given its name
+fn run_decode(handler: Option<Handler>, mercy_call: &str, mercy_string: &str) -> Result<String, MercyError> {
+    match handler {
+        Some(Handler::Decode(decode)) => decode(mercy_string),
+        Some(Handler::Method(method)) => method(mercy_call, mercy_string),
+        _ => Err(MercyError::unsupported("mercy_decode", mercy_call))
+    }
+}
+
+// A token is a credential, so it stays out of error reports
+fn decode_sensitive(mercy_call: &str) -> bool {
+    mercy_call.starts_with("jwt")
+}
+
+/* Public encoding methods provided by Mercy */
+
+/// Supports: base64, base64_url, rot, rot47, morse, nato, hex, base32, base58, base85, z85, qr, url, url_form, html_entity, html_attr, js_string, sql_string, cmd_unix, cmd_windows, punycode
+/// 
+/// `base64_url` - URL-safe base64 ("-" and "_" for "+" and "/") of the text's UTF-8 bytes, unpadded as in tokens and JWTs
+/// 
+/// `rot` / `rot47` - Caesar shift of "13,text" (letters rotated forward by 1 to 25, case kept) and ROT47 of the printable ASCII range; `mercy_decode` undoes both
+/// 
+/// `hex` / `base32` - Lowercase hex and standard padded base32 of the text's UTF-8 bytes
+/// 
+/// `base58` / `base85` / `z85` - Bitcoin-alphabet base58, Adobe ASCII85 between "<~" and "~>", and Z85 of the text's UTF-8 bytes; Z85 needs a length that is a multiple of 4 bytes
+/// 
+/// `url` / `url_form` - Every UTF-8 byte but letters, digits and "-._~" as uppercase `%XX`; `url_form` writes spaces as "+"
+/// 
+/// `html_entity` - The five reserved characters as `&amp;`, `&lt;`, `&gt;`, `&quot;` and `&#39;`, for HTML text and quoted attribute values
+/// 
+/// `html_attr` - Escapes for an HTML attribute value (quoted or not): every ASCII character except letters, digits and ",.-_" as `&#xHH;`
+/// 
+/// `js_string` - Escapes for the inside of a JavaScript string literal in either quote style, safe inside `<script>` (`<`, `>`, `&`, `/` and line separators hex-escaped)
+/// 
+/// `sql_string` - Escapes for the inside of a standard SQL single-quoted literal by doubling quotes (backslashes untouched)
+/// 
+/// `cmd_unix` - One POSIX shell word, single-quoted unless made only of safe characters
+/// 
+/// `cmd_windows` - One cmd.exe argument: CommandLineToArgvW quoting with cmd.exe metacharacters escaped by `^`
+/// 
+/// `punycode` - Each non-ASCII label of a domain, lowercased, in its "xn--" form ("аррӏе.com" gives "xn--80ak6aa92e.com"), ASCII labels unchanged; a label longer than 63 characters encoded is an error
+/// 
+/// `qr` - Writes the text as a QR code PNG (requires the `image` feature); trailing options "out=code.png ec=M scale=8 border=4"
+pub fn mercy_encode(mercy_call: &str, mercy_string: &str) -> String {
+    result_text(mercy_call, mercy_encode_result(mercy_call, mercy_string), "Unable to encode message")
+}
+
+/// `mercy_encode` with failures as a `MercyError` rather than text in the output; never panics
+pub fn mercy_encode_result(mercy_call: &str, mercy_string: &str) -> Result<String, MercyError> {
+    let output = match capabilities::handler("mercy_encode", mercy_call) {
+        Some(Handler::Method(encode)) => encode(mercy_call, mercy_string),
+        _ => Err(MercyError::unsupported("mercy_encode", mercy_call))
+    };
+
+    // The text being encoded is the caller's own content
+    finish_result(output.map_err(|e| e.with_context("mercy_encode", mercy_call, mercy_string, true)))
+}
+
+/* Public hashing methods provided by Mercy */
+
+/// Supports: sha1, sha2_256, sha2_512, sha3_256, sha3_512, md5, ssdeep (also as fuzzy), and the non-cryptographic crc32 (IEEE, as zlib) and xxhash64 (seed 0) for fast deduplication
+/// 
+/// `xxhash64_seeded` - xxHash64 with a seed, input "seed=42 text" (the seed decimal or 0x-prefixed hex)
+/// 
+/// `all` - Every digest above, one per line as "algorithm: digest"
+/// 
+/// `fuzzy` - The ssdeep context-triggered piecewise hash: the block size doubles until the data splits into at most 64 chunks, so digests of inputs of different sizes stay comparable (see `mercy_hash_compare`)
+/// 
+/// `sha1_file` / `sha2_256_file` / `sha2_512_file` / `sha3_256_file` / `sha3_512_file` / `md5_file` / `ssdeep_file` / `fuzzy_file` / `crc32_file` / `xxhash64_file` - Hash of the file at the given path, read in chunks so any size hashes in constant memory (see `mercy_hash_file`); `xxhash64_file` takes the trailing option "seed=42"
+/// 
+/// `sha1_gz` / `sha2_256_gz` / `sha2_512_gz` / `sha3_256_gz` / `sha3_512_gz` / `md5_gz` / `crc32_gz` / `xxhash64_gz` - Hash of what the gzip file at the given path decompresses to, inflated as it is hashed so a multi-gigabyte capture is never held in memory (see `mercy_hash_gzip_file`); every member of a concatenated file is hashed and each trailer's CRC checked, so a corrupt or truncated file is an error; `xxhash64_gz` takes "seed=42" too
+/// 
+/// `verify_manifest` / `verify_manifest_json` - Checks every file listed in a sha256sum/sha512sum/sha1sum/md5sum or BSD-style checksum manifest; trailing option "base=dir" (default: the manifest's directory)
+pub fn mercy_hash(mercy_call: &str, mercy_string: &str) -> String {
+    result_text(mercy_call, mercy_hash_result(mercy_call, mercy_string), "Unable to hash message")
+}
+
+/// `mercy_hash` with failures as a `MercyError` rather than text in the output; never panics
+pub fn mercy_hash_result(mercy_call: &str, mercy_string: &str) -> Result<String, MercyError> {
+    let output = match capabilities::handler("mercy_hash", mercy_call) {
+        Some(Handler::Digest(digest)) => return finish_result(Ok(digest(mercy_string))),
+        Some(Handler::Method(hash)) => hash(mercy_call, mercy_string),
+        _ => Err(MercyError::unsupported("mercy_hash", mercy_call))
+    };
+
+    // Text being hashed may be a password; file paths and manifests are not withheld
+    let sensitive = !file_hash_call(mercy_call) && !mercy_call.starts_with("verify_manifest");
+    finish_result(output.map_err(|e| e.with_context("mercy_hash", mercy_call, mercy_string, sensitive)))
+}
+
+/// `mercy_hash` over many inputs: one output per input, in order, each the digest or that input's error; the call is resolved once and a large batch is hashed on every CPU thread
+pub fn mercy_hash_batch(mercy_call: &str, inputs: &[&str]) -> Vec<String> {
+    hash_batch(mercy_call, inputs).into_iter().map(|output| output.unwrap_or_else(|e| e)).collect()
+}
+
+/// `mercy_hash_batch` with each output also streamed to `output.sink` and a summary posted to `output.notify` when it finishes; fails only when the sink cannot be opened or written to
+pub fn mercy_hash_batch_to(mercy_call: &str, inputs: &[&str], output: &BatchOutput) -> Result<BatchReport, String> {
+    batch::deliver(mercy_call, output, || hash_batch(mercy_call, inputs))
+}
+
+// The digests of a batch, failures as their error text
+fn hash_batch(mercy_call: &str, inputs: &[&str]) -> Vec<Result<String, String>> {
+    match capabilities::handler("mercy_hash", mercy_call) {
+        Some(Handler::Digest(digest)) => batch::map_parallel(inputs, |input| Ok(redact::redact_enabled(digest(input)))),
+        _ => batch::map_parallel(inputs, |input| {
+            mercy_hash_result(mercy_call, input).map_err(|e| result_text(mercy_call, Err(e), "Unable to hash message"))
+        })
+    }
+}
+
+/// Checks the digest `mercy_hash` computes for the call against a published checksum: "match", or "mismatch (got <digest>)"
+/// 
+/// Supports the hex digests: sha1, sha2_256, sha2_512, sha3_256, sha3_512, md5, crc32, xxhash64, xxhash64_seeded and their `_file` and `_gz` forms
+/// 
+/// `expected` is the hex digest in either case, or a whole line pasted from a sha256sum-style ("digest  name") or BSD-style ("SHA256 (name) = digest") SUMS file; a `_file` or `_gz` call given an empty path hashes the file the line names. An expected value with non-hex characters or the wrong length for the algorithm is an error. To check every file a SHA256SUMS file lists, use `verify_manifest` (see `mercy_hash`)
+pub fn mercy_hash_verify(mercy_call: &str, path_or_string: &str, expected: &str) -> String {
+    result_text(mercy_call, mercy_hash_verify_result(mercy_call, path_or_string, expected), "Unable to verify hash")
+}
+
+/// `mercy_hash_verify` with failures as a `MercyError` rather than text in the output; never panics
+pub fn mercy_hash_verify_result(mercy_call: &str, path_or_string: &str, expected: &str) -> Result<String, MercyError> {
+    let file_call = file_hash_call(mercy_call);
+    let (digest, listed_file) = match expected_hex_digest(mercy_call, expected) {
+        Ok(expected) => expected,
+        Err(e) => return finish_result(Err(e.with_context("mercy_hash", mercy_call, path_or_string, !file_call)))
+    };
+
+    let input = match listed_file {
+        Some(file) if file_call && path_or_string.trim().is_empty() => file,
+        _ => path_or_string.to_string()
+    };
+    let actual = mercy_hash_result(mercy_call, &input)?;
+
+    if actual.eq_ignore_ascii_case(&digest) {
+        Ok("match".to_string())
+    } else {
+        Ok(format!("mismatch (got {})", actual))
+    }
+}
+
+/// Similarity of two fuzzy hashes from 0 (unrelated) to 100 (identical), as `ssdeep -d` scores it: "87"
+/// 
+/// Supports `fuzzy` / `ssdeep`, comparing two digests `mercy_hash` gave, and `fuzzy_file` / `ssdeep_file`, hashing the two files at the given paths first. Only digests whose block sizes are equal or a factor of two apart can score above 0; a malformed digest is an error
+pub fn mercy_hash_compare(mercy_call: &str, a: &str, b: &str) -> String {
+    result_text(mercy_call, mercy_hash_compare_result(mercy_call, a, b).map(|score| score.to_string()), "Unable to compare hashes")
+}
+
+/// `mercy_hash_compare` with the score as a number and failures as a `MercyError`; never panics
+pub fn mercy_hash_compare_result(mercy_call: &str, a: &str, b: &str) -> Result<u32, MercyError> {
+    let digests = match mercy_call {
+        "fuzzy" | "ssdeep" => Ok((a.trim().to_string(), b.trim().to_string())),
+        "fuzzy_file" | "ssdeep_file" => hashing::hash_file_from_args("ssdeep_file", a)
+            .and_then(|a| Ok((a, hashing::hash_file_from_args("ssdeep_file", b)?)))
+            .map_err(MercyError::invalid_input),
+        _ => Err(MercyError::unsupported("mercy_hash_compare", mercy_call))
+    };
+
+    let score = digests.and_then(|(a, b)| ssdeep_compare(&a, &b).map_err(MercyError::invalid_input));
+    score.map_err(|e| counted_error(e.with_context("mercy_hash_compare", mercy_call, &format!("{} {}", a, b), false)))
+}
+
+// The expected digest of a hash call, checked against the algorithm's length, and the file a SUMS line names
+fn expected_hex_digest(mercy_call: &str, expected: &str) -> Result<(String, Option<String>), MercyError> {
+    let algorithm = mercy_call.strip_suffix("_file").or_else(|| mercy_call.strip_suffix("_gz")).unwrap_or(mercy_call);
+    let digits = hashing::hex_digest_len(algorithm).ok_or_else(|| MercyError::unsupported("mercy_hash", mercy_call))?;
+
+    let (digest, file) = manifest::expected_digest(expected);
+    if let Some(bad) = digest.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(MercyError::invalid_input(format!("Expected digest '{}' has the non-hex character '{}'", digest, bad)));
+    }
+    if digest.len() != digits {
+        return Err(MercyError::invalid_input(format!("Expected a {}-digit {} digest, got {} digits", digits, algorithm, digest.len())));
+    }
+
+    Ok((digest, file))
+}
+
+// Hash calls that take a file path rather than the text to hash
+pub(crate) fn file_hash_call(mercy_call: &str) -> bool {
+    mercy_call.ends_with("_file") || mercy_call.ends_with("_gz")
+}
+
+/* Public HMAC methods provided by Mercy */
+
+/// Keyed digests (RFC 2104) of a message, as lowercase hex
+/// 
+/// `hmac_sha256` / `hmac_sha512` / `hmac_sha1` / `hmac_md5` - HMAC of the message under the key taken as UTF-8 text
+/// 
+/// `hmac_sha256_hexkey` / `hmac_sha512_hexkey` / `hmac_sha1_hexkey` / `hmac_md5_hexkey` - The same with the key given in hex, as signing secrets often are
+pub fn mercy_hmac(mercy_call: &str, mercy_key: &str, mercy_message: &str) -> String {
+    result_text(mercy_call, mercy_hmac_result(mercy_call, mercy_key, mercy_message), "Unable to compute HMAC")
+}
+
+/// `mercy_hmac` with failures as a `MercyError` rather than text in the output; never panics
+pub fn mercy_hmac_result(mercy_call: &str, mercy_key: &str, mercy_message: &str) -> Result<String, MercyError> {
+    let output = hmac_bytes(mercy_call, mercy_key, mercy_message).map(|digest| hex_encode(&digest));
+
+    // The message may be as secret as the key, which never appears in errors
+    finish_result(output.map_err(|e| e.with_context("mercy_hmac", mercy_call, mercy_message, true)))
+}
+
+/// Whether `expected_hex` (either case) is the HMAC `mercy_hmac` computes for the call, key and message; compared in constant time
+pub fn mercy_hmac_verify(mercy_call: &str, mercy_key: &str, mercy_message: &str, expected_hex: &str) -> bool {
+    match (hmac_bytes(mercy_call, mercy_key, mercy_message), hex_decode(expected_hex.trim())) {
+        (Ok(digest), Ok(expected)) => hashing::constant_time_eq(&digest, &expected),
+        _ => false
+    }
+}
+
+// "hmac_<digest>" or "hmac_<digest>_hexkey"
+fn hmac_bytes(mercy_call: &str, mercy_key: &str, mercy_message: &str) -> Result<Vec<u8>, MercyError> {
+    let (name, hex_key) = match mercy_call.strip_suffix("_hexkey") {
+        Some(name) => (name, true),
+        None => (mercy_call, false)
+    };
+    let digest = name.strip_prefix("hmac_").and_then(hashing::HmacDigest::parse).ok_or_else(|| MercyError::unsupported("mercy_hmac", mercy_call))?;
+
+    let key = if hex_key {
+        hex_decode(mercy_key).map_err(|e| MercyError::invalid_encoding("hex", format!("HMAC key: {}", e)))?
+    } else {
+        mercy_key.as_bytes().to_vec()
+    };
+
+    stats::count(Counter::BytesHashed, mercy_message.len() as u64);
+    Ok(hashing::hmac(digest, &key, mercy_message.as_bytes()))
+}
+
+/* Public hexadecimal methods provided by Mercy */
+
+/// Dump hexadecimal values of a file
+/// 
+/// `hex_dump` - Hex dump of a file in the layout of `xxd` (offset, 16 bytes in groups of two, ASCII with unprintable bytes as '.'), returned rather than printed; trailing options "offset=0x1000 length=256" dump only that slice (offset alone runs to the end of the file)
+/// 
+/// `hex_dump_gz` - The same for what a gzip file decompresses to, with the offset and length counted in decompressed bytes; the data is inflated as it is read, up to the end of the range, so a slice deep into a multi-gigabyte capture needs only the slice in memory. Without "length=" at most 16 MiB is dumped (more is an error); corruption or truncation before the end of the range is an error
+/// 
+/// `hex_diff` / `hex_diff_json` - Byte-by-byte comparison of two files given as "old,new": each region that differs as its offset, the old bytes and the new bytes, with "context=4" equal bytes on each side, then a summary of the differing bytes and whether the lengths differ (bytes only the longer file has are one line, so a prefix of the other file is reported as such); both files are streamed, so images of hundreds of megabytes work; identical files give "Files are identical (N bytes)"; trailing options "context=4 max_regions=100"
+pub fn mercy_hex(mercy_call: &str, mercy_file: &str) -> String {
+    result_text(mercy_call, mercy_hex_result(mercy_call, mercy_file), "Unable to provide hexadecimal dump for file specified")
+}
+
+/// Hex dump of exactly `length` bytes of a file from `offset` (the dump's offsets count from there); an error when the range is not inside the file
+pub fn mercy_hex_range(mercy_file: &str, offset: u64, length: u64) -> Result<String, MercyError> {
+    let input = format!("{} offset={} length={}", mercy_file, offset, length);
+    finish_result(collect_range_hex(mercy_file, offset, length).map_err(|e| e.with_context("mercy_hex", "hex_dump", &input, false)))
+}
+
+/// `mercy_hex` with failures as a `MercyError` rather than text in the output; never panics
+pub fn mercy_hex_result(mercy_call: &str, mercy_file: &str) -> Result<String, MercyError> {
+    let output = match capabilities::handler("mercy_hex", mercy_call) {
+        Some(Handler::Method(dump)) => dump(mercy_call, mercy_file),
+        _ => Err(MercyError::unsupported("mercy_hex", mercy_call))
+    };
+
+    finish_result(output.map_err(|e| e.with_context("mercy_hex", mercy_call, mercy_file, false)))
+}
+
+/* Public malware and malicious detection */
+
+/// Malware detection or malicious intent
+/// 
+/// Network calls run on a runtime shared by the synchronous API, so this also works from inside a Tokio runtime, though it blocks the calling thread; async code should await `mercy_malicious_async` instead
+/// 
+/// `status` - Returns a status of 'malicious', 'unknown', or 'suspicious' from the InQuest API; trailing options "user_agent=curl accept_language=en-US header=Name:Value" override the HTTP identity set with `set_http_identity`
+/// 
+/// `status_json` - What the InQuest API knows of the domain: its classification, the samples referencing it (SHA-256, classification, file type, first and last seen), the distinct hashes, counts per classification and the earliest and latest sighting; same options as `status`
+/// 
+/// `status_save` - The `status` lookup that also keeps InQuest's raw JSON response, in the file given by "out=review.json" or a new file in the platform's temp directory, returning the status and the path; same options as `status`
+/// 
+/// `hash_status` / `hash_status_json` - What InQuest knows of a file hash, the MD5, SHA-1, SHA-256 or SHA-512 endpoint chosen by length: the classification with the file type and first-seen date when present ("Malicious, file type DOC, first seen 2024-03-01T10:00:00"), or "No records found" when InQuest has none or refuses the lookup; a malformed hash is an error before any request; trailing options "timeout=30" plus the HTTP identity options
+/// 
+/// `status_offline` / `status_offline_json` - "Listed (<source file>)" or "Not listed" for a domain or IP address checked against local blocklist files instead of InQuest, for air-gapped networks: the files named by the MERCY_BLOCKLIST environment variable (separated like PATH), or "list=blocklist.txt"; lines are domains, addresses or CIDR blocks, or hosts file entries ("0.0.0.0 evil.example"), "#" starts a comment, names compare without case and a listed domain lists its subdomains; the files are loaded once and reloaded when they change (see `Blocklist` for checking many indicators)
+/// 
+/// `sinkhole_check` / `sinkhole_check_json` - Whether the domain looks sinkholed: its A/AAAA records against known sinkhole and blocklist ranges, its CNAMEs and nameservers against sinkhole name patterns, and with "fetch=true" its web page against seizure and sinkhole banners, with the evidence matched; trailing options "fetch=true list=extra.txt replace=true server=1.1.1.1 timeout=5" plus the HTTP identity options ("list=" adds "ip <cidr> label", "ns <pattern> label" and "phrase <text>" lines to the list in use, "replace=true" uses them alone)
+pub fn mercy_malicious(mercy_call: &str, mercy_domain: &str) -> String {
+    result_text(mercy_call, runtime::block_on(mercy_malicious_async(mercy_call, mercy_domain)), "Unable to classify domain")
+}
+
+/// `mercy_malicious` for async callers: the same calls, awaited on the caller's runtime, with failures as a `MercyError`
+pub async fn mercy_malicious_async(mercy_call: &str, mercy_domain: &str) -> Result<String, MercyError> {
+    let output = match capabilities::handler("mercy_malicious", mercy_call) {
+        Some(Handler::Lookup(lookup)) => lookup(mercy_call.to_string(), mercy_domain.to_string()).await,
+        _ => Err(MercyError::unsupported("mercy_malicious", mercy_call))
+    };
+
+    finish_result(output.map_err(|e| e.with_context("mercy_malicious", mercy_call, mercy_domain, false)))
+}
+
+/// `mercy_malicious` with `config` in place of the default `MercyConfig`: its proxy, timeouts, User-Agent, InQuest token, retries and rate limits
+pub fn mercy_malicious_with(config: &MercyConfig, mercy_call: &str, mercy_domain: &str) -> String {
+    with_mercy_config(config, || mercy_malicious(mercy_call, mercy_domain))
+}
+
+/// `mercy_malicious_async` with `config` in place of the default `MercyConfig`
+pub async fn mercy_malicious_with_async(config: &MercyConfig, mercy_call: &str, mercy_domain: &str) -> Result<String, MercyError> {
+    with_mercy_config_async(config, mercy_malicious_async(mercy_call, mercy_domain)).await
+}
+
+/// `mercy_malicious` over many domains (or hashes, for `hash_status`): up to `BATCH_CONCURRENCY` lookups run at once, or the concurrency of the current `MercyConfig`; one output per input, in order, each the result or that input's error
+pub fn mercy_malicious_batch(mercy_call: &str, mercy_domains: &[&str]) -> Vec<String> {
+    runtime::block_on(mercy_malicious_batch_async(mercy_call, mercy_domains)).into_iter()
+        .map(|output| result_text(mercy_call, output, "Unable to classify domain"))
+        .collect()
+}
+
+/// `mercy_malicious_batch` with each output also streamed to `output.sink` and a summary posted to `output.notify` when it finishes; fails only when the sink cannot be opened or written to
+pub fn mercy_malicious_batch_to(mercy_call: &str, mercy_domains: &[&str], output: &BatchOutput) -> Result<BatchReport, String> {
+    batch::deliver(mercy_call, output, || {
+        runtime::block_on(mercy_malicious_batch_async(mercy_call, mercy_domains)).into_iter()
+            .map(|output| output.map_err(|e| result_text(mercy_call, Err(e), "Unable to classify domain")))
+            .collect()
+    })
+}
+
+/// `mercy_malicious_batch` for async callers, with each failure as a `MercyError`
+pub async fn mercy_malicious_batch_async(mercy_call: &str, mercy_domains: &[&str]) -> Vec<Result<String, MercyError>> {
+    let call = mercy_call.to_string();
+    let domains: Arc<Vec<String>> = Arc::new(mercy_domains.iter().map(|domain| domain.to_string()).collect());
+    let inputs = Arc::clone(&domains);
+
+    let outputs = batch::run_concurrent(domains.len(), move |index| {
+        let (call, inputs) = (call.clone(), Arc::clone(&inputs));
+        async move { mercy_malicious_async(&call, &inputs[index]).await }
+    }).await;
+
+    outputs.into_iter().zip(domains.iter())
+        .map(|(output, domain)| output.unwrap_or_else(|| finish_result(Err(MercyError::from("Lookup task failed".to_string()).with_context("mercy_malicious", mercy_call, domain, false)))))
+        .collect()
+}
+
+/// `status_offline` against one blocklist file: "Listed (<source file>)" or "Not listed" for a domain (or any domain under a listed one) or IP address
+pub fn mercy_malicious_with_list(mercy_domain: &str, list_path: &str) -> String {
+    let output = text_result(blocklist::check_with_paths(mercy_domain, &[PathBuf::from(list_path)]));
+    result_text("status_offline", finish_result(output.map_err(|e| e.with_context("mercy_malicious", "status_offline", mercy_domain, false))), "Unable to classify domain")
+}
+
+/* Public forensic methods provided by Mercy */
+
+/// Forensic triage of files and directories
+/// 
+/// The streaming calls (`timeline`, `evtx`, `minidump`, `secrets_git`) take "out=" to send their records to a file or named pipe, "unix:/run/collector.sock" or "tcp:host:port" instead of returning them, and then return the summary with the sink's counts; "backpressure=drop" drops records the reader has no room for (default: block) and "retries=5" bounds reconnection (see `OutputSink`); the `_to` batch functions send one record per input to the sink of their `BatchOutput`
+/// 
+/// `timeline`, `evtx`, `carve`, `minidump`, `oci_scan` and `secrets_git` also take "notify=true" or "notify=https://..." to post a summary to a webhook when they finish (see `notify` in `mercy_extra`)
+/// 
+/// `timeline` - MACB timeline of a directory tree as a body file; trailing options "format=ndjson start=2024-01-01 end=2024-01-02T12:00:00 follow=true sorted=false out=timeline.ndjson"
+/// 
+/// `prefetch` / `prefetch_json` - Executable, run count, last run times and referenced files of a .pf file (XP through Windows 11)
+/// 
+/// `shimcache` / `shimcache_json` - AppCompatCache entries from an exported SYSTEM hive
+/// 
+/// `evtx` - Windows event log records as NDJSON, ending with a {"summary": ...} line of counts; trailing options "ids=4624,4625 start=2024-01-01 end=2024-01-02T12:00:00 out=unix:/run/collector.sock"
+/// 
+/// `carve` / `carve_json` - PE, PNG, JPEG, ZIP and PDF objects embedded in a blob; trailing options "types=png,zip out=carved max_objects=1000 max_size=268435456 max_extracted=4294967296"
+/// 
+/// `minidump` - Modules, threads, system details and memory strings (with owning module) of a process minidump as NDJSON; trailing options "min=6 max=100000 strings=false out=tcp:127.0.0.1:9000"
+/// 
+/// `apk` / `apk_json` - Package identity, SDK levels, permissions (dangerous ones flagged), exported components, debuggable/allowBackup, signing certificates and dex files of an APK
+/// 
+/// `oci` - JSON summary of a `docker save` or OCI layout tarball: config (entrypoint, cmd, env with secrets flagged), layer digests and sizes, and build history
+/// 
+/// `oci_scan` - JSON results of streaming layer files through the secret patterns and an optional indicator list, noting whiteouts; trailing options "layer=2 iocs=indicators.txt max_file_size=10485760"
+/// 
+/// `secrets_git` - Secrets added anywhere in a git repository's history as NDJSON (rule, redacted secret, commit, author, date, path, line), each reported once at the commit that introduced it and ending with a {"summary": ...} line; trailing options "since=2024-01-01 range=v1.0..HEAD all=true out=secrets.ndjson" (read through libgit2 with the `git` feature, falling back to `git log` for what it cannot read, such as "since=2 weeks ago")
+/// 
+/// `entropy_profile` - Shannon entropy of sliding windows over a file as JSON {offset, entropy} points, with summary statistics and the top regions previewed in hex; trailing options "window=4096 step=1024"
+pub fn mercy_forensics(mercy_call: &str, mercy_path: &str) -> String {
+    let output = match capabilities::handler("mercy_forensics", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_path),
+        _ => unknown_msg("Unable to run the forensic method requested")
+    };
+
+    redact::redact_enabled(output)
+}
+
+/* Public parsing methods provided by Mercy */
+
+/// Parse structured file formats
+/// 
+/// `plist` - XML or binary property list as JSON (dates as RFC 3339, data as base64)
+/// 
+/// `mobileconfig` / `mobileconfig_json` - Configuration profile summary: payload types, installed certificates with fingerprints, VPN, proxy and restriction settings, and the signing certificates of signed profiles
+/// 
+/// `sbom` - Components (name, version, purl, licenses) of a CycloneDX JSON or SPDX JSON SBOM as JSON
+/// 
+/// `sbom_check` - Components of an SBOM affected by OSV advisories, with fixed versions, as JSON; trailing options "osv=/path/to/osv-dump" (default https://api.osv.dev) and the HTTP identity options of `mercy_malicious`
+/// 
+/// `qr` / `qr_json` - Payloads of every QR code in a PNG or JPEG image, with version, error correction level, mask, segment modes and byte encodings (requires the `image` feature)
+/// 
+/// `zonefile` / `zonefile_json` - Records of a BIND zone file with absolute lowercase names, TTLs in seconds and classes filled in ($ORIGIN, $TTL, `@`, blank owners, relative names, parentheses and TTL units handled), with skipped lines as warnings; trailing option "origin=example.com" for files without $ORIGIN (otherwise guessed from names like db.example.com)
+/// 
+/// `zone_audit` / `zone_audit_json` - Findings for a zone file: internal and unroutable addresses in a public zone, wildcard records, CNAMEs to names not defined in the zone, missing SPF and DMARC records, a DMARC policy of p=none, TTLs spread wider than the ratio and TTLs of 0 (zones under .local, .internal, .corp and similar, or with "internal=true", skip the address and mail checks); trailing options "origin=example.com internal=true resolve=true server=1.1.1.1 timeout=3 ttl_ratio=1000" ("resolve=true" also flags CNAMEs to external names that return NXDOMAIN)
+pub fn mercy_parse(mercy_call: &str, mercy_path: &str) -> String {
+    let output = match capabilities::handler("mercy_parse", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_path),
+        _ => unknown_msg("Unable to parse the format requested")
+    };
+
+    redact::redact_enabled(output)
 }
 
-/* Public encoding methods provided by Mercy */
-
-/// Supports: base64
-pub fn mercy_encode(mercy_call: &str, mercy_string: &str) -> String {
-    match mercy_call {
-        "base64" => base64_encode(mercy_string.to_string()),
-         _ => unknown_msg("Unable to encode message")
-    }
-}
+/* Public cipher solving methods provided by Mercy */
 
-/* Public hashing methods provided by Mercy */
+/// Solve classical ciphers
+/// 
+/// `substitution` - JSON of the best monoalphabetic substitution key found by quadgram-scored hill climbing, with the plaintext (case and punctuation kept) and its score; trailing options "iterations=2000000 seconds=10 seed=42"
+pub fn mercy_solve(mercy_call: &str, mercy_ciphertext: &str) -> String {
+    let output = match capabilities::handler("mercy_solve", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_ciphertext),
+        _ => unknown_msg("Unable to solve the cipher requested")
+    };
 
-/// Supports: sha2_256, md5
-pub fn mercy_hash(mercy_call: &str, mercy_string: &str) -> String {
-    match mercy_call {
-        "sha2_256" => sha2_256_hash(mercy_string.to_string()),
-        "md5" => md5_hash(mercy_string.to_string()),
-        _ => unknown_msg("Unable to hash message")
-    }
+    redact::redact_enabled(output)
 }
 
-/* Public hexadecimal methods provided by Mercy */
+/* Public payload generation methods provided by Mercy */
 
-/// Dump hexadecimal values of a file
+/// Generate test payloads
 /// 
-/// `hex_dump` - Dumps hexadecimal data of a file
-pub fn mercy_hex(mercy_call: &str, mercy_file: &str) -> String {
-    match mercy_call {
-        "hex_dump" => collect_file_hex(mercy_file),
-        _ => unknown_msg("Unable to provide hexadecimal dump for file specified")
-    }
+/// `fuzz_strings` - NDJSON of classic problem strings (long runs, format specifiers, NULs, Unicode edge cases, SQL/XSS/path traversal probes, out-of-range numbers), each labeled, ending with a {"summary": ...} line holding the seed; trailing options "count=20 seed=42 categories=sql,xss"
+/// 
+/// `fuzz_bytes` - NDJSON of seeded byte-level mutations of the input (bit flips, byte swaps, truncations, duplicated slices, 8/16/32-bit boundary values), each labeled with the mutation applied; trailing options "count=100 seed=42 hex=true file=true"
+pub fn mercy_generate(mercy_call: &str, mercy_spec: &str) -> String {
+    let output = match capabilities::handler("mercy_generate", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_spec),
+        _ => unknown_msg("Unable to generate the payloads requested")
+    };
+
+    redact::redact_enabled(output)
 }
 
-/* Public malware and malicious detection */
+/* Public canary token methods provided by Mercy */
 
-/// Malware detection or malicious intent
+/// Canary tokens and the log lines that show them firing
 /// 
-/// `status` - Returns a status of 'malicious', 'unknown', or 'suspicious' from the InQuest API
-pub fn mercy_malicious(mercy_call: &str, mercy_domain: &str) -> String {
-    match mercy_call {
-        "status" => malicious_domain_status(mercy_domain),
-        _ => unknown_msg("Unable to classify domain")
-    }
+/// `generate` / `generate_json` - A new canary with a random, checksummed ID: "dns" (a hostname under the canary domain), "url" (https://domain/t/id) or "aws_key" (an AKIA access key ID and secret that cannot authenticate); trailing option "domain=canary.example.com", else the domain set with `set_canary_domain`; "seed=42" repeats a canary exactly and is refused without "insecure_seeded_secrets=true", as a seeded canary is predictable
+/// 
+/// `parse_trigger` / `parse_trigger_json` - The canary ID, kind, source address and UTC time in a BIND, dnsmasq or unbound query log line, a web access log line or a CloudTrail JSON record
+pub fn mercy_canary(mercy_call: &str, mercy_input: &str) -> String {
+    let output = match capabilities::handler("mercy_canary", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_input),
+        _ => unknown_msg("Unable to handle the canary request")
+    };
+
+    redact::redact_enabled(output)
 }
 
 /* Public extra methods provided by Mercy */
 
 /// Information about various data points
 /// ### Methods
-/// `internal_ip` - Returns the host (internal) ip address of the system
+/// `internal_ip` - Returns the host (internal) ip address of the system, or an error message when it has no route out
+/// 
+/// `external_ip` / `external_ipv6` - The public IPv4 or IPv6 address the host's traffic leaves from, asked of two IP echo services over HTTPS (ipify, then icanhazip) with a 5 second timeout each; "Unable to determine external IP (offline?)" with the reasons when both fail; trailing options "user_agent=curl accept_language=en-US header=Name:Value" set the HTTP identity
+/// 
+/// `system_info` - Returns numerous data points associated with the host system: "hostname", "cpu_cores", "cpu_speed", "os_release", "proc", "memory" (total, available and free in GiB), "uptime" (days, hours and minutes, with the boot time), "disk" (the root filesystem's usage), "user" (the effective user), or "all"; a metric the platform does not give reads "unavailable"
+/// 
+/// `defang` - Defangs URLs, domains, IP addresses and emails: "https://evil.com/a" becomes "hxxps://evil[.]com/a", "user@evil.com" becomes "user[@]evil[.]com" (http, https and ftp schemes become hxxp, hxxps and fxp; already defanged parts are left alone)
+/// 
+/// `refang` - Turns defanged indicators back into live ones: hxxp/hxxps/fxp schemes, "[.]", "(.)", "[dot]", "[@]", "[at]", "[:]" and "[://]" in any case
+/// 
+/// `whois` / `whois_json` - WHOIS record of a domain or IP address: domains from their TLD's registry (IANA for TLDs not in the built-in table), following the "Registrar WHOIS Server:" referral of thin registries such as .com to the registrar's record; IP addresses from ARIN, following its referral to RIPE, APNIC, LACNIC or AFRINIC; the JSON form also gives the registrar and creation date when the record names them
+/// 
+/// `traceroute` / `traceroute_json` - Traces the network path to a host (see `mercy_traceroute` for options)
+/// 
+/// `ping` / `ping_json` - ICMP echo statistics for "host count=4 timeout=2" (see `mercy_ping`)
+/// 
+/// `smb_check` / `smb_check_json` - SMB negotiate on port 445: dialect, SMB1 acceptance and signing
+/// 
+/// `rdp_check` / `rdp_check_json` - X.224 negotiation on port 3389: standard RDP, TLS and NLA support
+/// 
+/// `ftp_check` / `ftp_check_json` - FTP banner, anonymous login attempt and FEAT list on port 21
+/// 
+/// `telnet_check` / `telnet_check_json` - Telnet login banner on port 23 (all options refused)
+/// 
+/// `os_guess` / `os_guess_json` - Coarse OS family from "ttl=117 window=64240", or observed from one connection to "host:port"
+/// 
+/// `freq_analysis` - JSON letter and digraph frequencies, index of coincidence, chi-squared distance from English and a rough assessment of the cipher type
+/// 
+/// `entropy` - Shannon entropy of the text's UTF-8 bytes in bits per byte, to two places ("0.00" for empty text)
+/// 
+/// `entropy_file` - Shannon entropy of a file in bits per byte, read in chunks ("7.92"; an empty file gives "0.00")
+/// 
+/// `entropy_sections` / `entropy_sections_json` - Entropy of each fixed-size block of a file, read in chunks, with blocks of 7.2 bits per byte or more marked and the offset of the first one; trailing option "block=256"
+/// 
+/// `strings` / `strings_json` - Printable ASCII runs and UTF-16LE strings of at least 4 characters in a file of any size, one per line in offset order, found whole when they cross a read boundary; trailing options "min=6 encoding=ascii|utf16le|all offsets=true" ("offsets=true" prefixes each line with its offset in hex)
+/// 
+/// `caesar_solve` - JSON of the most English-like Caesar shift of the text, its plaintext and the runner-up shifts
+/// 
+/// `file_type` / `file_type_json` - What the file at a path is from its magic bytes, as a short label with the matched signature and its offset ("PE executable (MZ at offset 0; PE header at offset 0x80)"): PE, ELF, Mach-O, Java class, PDF, ZIP, GZIP, RAR, 7z, PNG, JPEG and GIF, ZIP archives opened to tell DOCX, XLSX, PPTX, OpenDocument, EPUB, JAR and APK apart, then ASCII or UTF-8 text; anything else is "unknown" with its first 8 bytes in hex
+/// 
+/// `detect_encoding` / `detect_encoding_json` - The likely encoding of the file at a path from its first 64 KiB: UTF-8, UTF-16LE or UTF-16BE (from a byte order mark, or zero bytes in every other position without one), Latin-1, Windows-1252 (bytes 0x80 to 0x9F present) or binary (NUL or control bytes), with a confidence of high, medium or low, what decided it, and the offset of the first invalid sequence when UTF-8 was ruled out; `mercy_decode` transcodes the last four to UTF-8
+/// 
+/// `exif` / `exif_json` - Camera make/model, software, timestamps, GPS position in decimal degrees and all EXIF tags of a JPEG, PNG or TIFF, with PNG text chunks, comments, and any data appended after the image (size and SHA-256)
+/// 
+/// `zip_list` / `zip_list_json` - The entries of the ZIP archive at a path (CRC-32, compressed and uncompressed size, compression method, name), read from the central directory alone so nothing is extracted and a large archive is never loaded; password protected entries are listed and marked "encrypted", and a corrupt or truncated archive is an error naming the problem
+/// 
+/// `stego_check` - Heuristic JSON scores for data appended to a PNG or JPEG, unusual PNG chunks and a random LSB plane; "path out=blob.bin" also writes the appended data to a file (requires the `image` feature)
+/// 
+/// `unicode_inspect` / `unicode_inspect_json` - Every non-ASCII codepoint of the text with its name, category and script, zero-width, bidi control and mixed-script findings at char offsets, and a copy with flagged characters escaped ("\u{202E}")
+/// 
+/// `unicode_inspect_file` / `unicode_inspect_file_json` - The same for a source file, with the line and column of each finding
+/// 
+/// `safe_display` / `safe_display_html` / `safe_display_json` - An untrusted domain, URL or string on one line safe for terminals and reports: bidi controls, invisible and control characters escaped ("\u{202E}", "\n"), a non-ASCII value followed by its ASCII form (punycode for domain labels and URL hosts, percent-encoding for the rest of a URL, "\u{...}" escapes for text), and values longer than the limit cut in the middle with an ellipsis and the SHA-256 of the whole input; `safe_display_html` is the same line HTML-escaped; trailing option "max=120"
+/// 
+/// `url_parse` / `url_parse_json` - A URL as a browser reads it (WHATWG rules): scheme, user name, host (with punycode labels decoded alongside), port (the scheme's default when none is given), path, each query parameter decoded on its own line, fragment and the normalized form, with warnings for a user name before '@' that hides the real host ("https://google.com@evil.com"), a bare IP address host (and the hex, octal or single-number form it was written in) and a punycode host; a malformed URL is an error
+/// 
+/// `url_normalize` - The URL normalized for comparison: scheme and host lowercased, default port dropped, "." and ".." segments resolved, tracking parameters (utm_*, fbclid, gclid, msclkid and the like) removed and the other query parameters sorted
+/// 
+/// `homograph_check` / `homograph_check_json` - Each label of a domain (Unicode or "xn--" punycode) checked for letters from mixed scripts and for characters that pass for Latin letters (Cyrillic, Greek, Armenian and non-ASCII Latin look-alikes, fullwidth forms), with each suspicious character's codepoint, name and script and the name the domain passes for ("аррӏе.com" reads as "apple.com"); invalid punycode is an error
+/// 
+/// `strip_ansi` - The text with every ANSI escape sequence removed: CSI, OSC (ended by BEL or ST), DCS/SOS/PM/APC strings and two-character escapes, in 7-bit and 8-bit forms, including ones cut off at the end
 /// 
-/// `system_info` - Returns numerous data points associated with the host system
+/// `ansi_inspect` / `ansi_inspect_json` - Every escape sequence in the text at its char offset, described, with dangerous ones rated (clipboard access via OSC 52, title changes and reports, requests that make the terminal type a reply, hidden text)
 /// 
-/// `defang` - Returns a defanged url and/or ip address
+/// `epoch_to_utc` / `epoch_to_utc_json` - A Unix epoch value as RFC 3339 UTC, in seconds, milliseconds, microseconds or nanoseconds told apart by magnitude (below 1e11 is seconds, below 1e14 milliseconds, and so on), fractions allowed ("1700000000123" gives "2023-11-14T22:13:20.123Z")
 /// 
-/// `whois` - Returns WHOIS lookup information
+/// `utc_to_epoch` / `utc_to_epoch_json` - ISO 8601 / RFC 3339 text (a zoneless time is UTC, offsets such as "+02:00" are applied) as Unix seconds, with a fraction when the time has one
+/// 
+/// `filetime_to_utc` / `filetime_to_utc_json` - A Windows FILETIME (100 ns intervals since 1601-01-01, as in registry key times and MFT records), decimal or "0x" hex, as RFC 3339 UTC
+/// 
+/// `normalize_timestamps` - Each line of a log prefixed with its timestamp in UTC ISO-8601 and a tab ("-" when none is found), detecting syslog, ISO-8601, ctime, RFC 2822, Apache CLF, Windows and epoch formats, ending with a {"summary": ...} line of formats and unparsed lines; trailing options "zone=+02:00 year=2024 position=16 after=marker out=normalized.log backpressure=block retries=5" (zone and year are assumed where the line has none; "out=" takes the same targets as the streaming forensics calls)
+/// 
+/// `logs_merge` - Comma-separated log files merged into one chronological timeline of "UTC timestamp<TAB>source<TAB>line" with a k-way merge (out-of-order inputs are chunk-sorted on disk first), lines without a timestamp taking the previous line's time, ending with a {"summary": ...} line; trailing options "errors=untimed.log out=merged.log backpressure=block retries=5 zone=+02:00 year=2024 chunk_lines=200000" ("out=" takes the same targets as the streaming forensics calls)
+/// 
+/// `cluster` - JSON clusters of the files under a directory: exact duplicates by SHA-256, then families linked by ssdeep similarity (candidates indexed by block size and shared 7-grams) or byte histogram cosine similarity, each with a representative and members; trailing options "method=histogram threshold=50 max_files=5000 force=true"
+/// 
+/// `asn` / `asn_json` - Origin ASN, AS name, announced prefix, country and registry of comma-separated IPs (or a file of them, one per line) from Team Cymru's DNS service, on a pool of concurrent queries that reuses answers within a prefix; private and other special-purpose addresses are answered locally; trailing options "pfx2as=routeviews.pfx2as server=1.1.1.1 timeout=3 names=false concurrency=16" ("pfx2as=" looks up offline in a prefix-to-ASN file)
+/// 
+/// `asn_lookup` / `asn_lookup_json` - ASN, AS name, BGP prefix, country and registry of one IP address from Team Cymru's WHOIS service (whois.cymru.com, port 43), for networks that filter the DNS the `asn` call uses; private and reserved addresses are answered locally as "Private address — no ASN" without a connection
+/// 
+/// `asn_bulk` / `asn_bulk_json` - The same for comma-separated IPs, all sent in one connection in Team Cymru's bulk format; entries that are not addresses are listed as skipped
+/// 
+/// `bogon_check` / `bogon_check_json` - Whether an IP or prefix is bogon space (private, loopback, link-local, documentation, benchmarking, multicast, reserved and deprecated ranges, and IPv6 outside 2000::/3) from an embedded table, listing the ranges containing it or, for a prefix, inside it; trailing option "list=fullbogons-ipv4.txt,fullbogons-ipv6.txt" adds prefix lists such as Team Cymru's fullbogons
+/// 
+/// `prefix_contains` / `prefix_contains_json` - Whether each comma-separated IP or prefix lies inside a set of prefixes (IPv4 and IPv6), with the most specific one containing it; trailing option "prefixes=scope.txt" (one prefix per line, kept loaded until the file changes) or "prefixes=10.0.0.0/8,2001:db8::/32"
+/// 
+/// `dns_lookup` / `dns_lookup_json` - Every A and AAAA record of a domain, one address per line; a failed lookup answers "NXDOMAIN: ...", "No records: ...", "Timeout: ..." or, for other server errors, the response code, so callers can tell them apart (the JSON has a `status` of ok, nxdomain, no-records, timeout or failed); trailing options "server=1.1.1.1 timeout=3"
+/// 
+/// `reverse_dns` / `reverse_dns_json` - The PTR host name of an IPv4 or IPv6 address, or "No PTR record: ...", "Timeout: ..." or the response code of a failed lookup (JSON `status` as for `dns_lookup`); trailing options "server=1.1.1.1 timeout=3"
+/// 
+/// `port_check` / `port_check_json` - Whether a TCP connect to "host:port" (input "10.0.0.5:443") succeeds: open, closed (refused) or filtered (no answer within the timeout), with the handshake time; trailing option "timeout=2"
+/// 
+/// `port_scan` / `port_scan_json` - TCP connect scan of "host,start-end" (input "10.0.0.5,1-1024", or "host,port" for one), a bounded number of attempts at a time, listing the open ports one per line followed by the scanned, open, closed and filtered counts; trailing options "timeout=2 concurrency=128"
+/// 
+/// `banner_grab` / `banner_grab_json` - What a TCP service sends after the connection opens (input "10.0.0.5:22"), bytes outside printable ASCII written as \xNN; the probe sent first is nothing for services that talk first such as SSH, "HEAD / HTTP/1.0" on port 80 and 8080 and a CRLF elsewhere, and refused, silent, reset and closed connections fail with different messages; trailing options "timeout=5 probe=auto" (auto, none, crlf or http)
+/// 
+/// `ip_in_cidr` / `ip_in_cidr_json` - Whether "address,cidr" (input "192.168.1.55,192.168.1.0/24") has the address inside the block, for IPv4 and IPv6; an address of the other family is never inside
+/// 
+/// `cidr_range` / `cidr_range_json` - Netmask, network and broadcast address, first and last usable host, address count and usable host count of a CIDR block; a /31 has two usable hosts and a /32 one, neither with a broadcast address (RFC 3021), and IPv6 blocks have none
+/// 
+/// `is_private` / `is_private_json` - Whether an IP is private-use (RFC 1918, CGNAT, IPv6 unique local), loopback or link-local, and otherwise which special-purpose range it falls in or that it is public; IPv4-mapped IPv6 addresses are classified by the IPv4 address they carry
+/// 
+/// `redact` - The text with RFC 1918 and IPv6 unique-local/link-local addresses, the local hostname, usernames from profile paths (C:\\Users\\name, /home/name) and sensitive strings replaced by placeholders (internal-ip-1, host-1, user-1, sensitive-1) that stay the same for a value across calls; trailing options "sensitive=acme,contoso ips=false host=false users=false" (`set_redaction` applies a policy to every result instead)
+/// 
+/// `stats` - JSON snapshot of the operation counters (DNS queries and timeouts, HTTP requests, WHOIS lookups, cache hits and misses, failed calls by kind, bytes hashed and hex dumped); "reset" also zeroes them
+/// 
+/// `capabilities` / `capabilities_json` - Which dispatcher calls can work in this environment and why the others are degraded or unavailable (missing ICMP/raw sockets, /proc, /etc/resolv.conf, a writable temp directory, git, features not compiled), from local probes only; nothing is sent on the network
+/// 
+/// `methods` / `methods_json` - The calls of the dispatcher named by the input ("extra", "hash", "decode"...), each with a one-line description, the input it takes (none, text, file path, file paths, host:port, domain or url) and whether it uses the network (see `mercy_methods`; `mercy_validate` checks a call and its input without running it)
+/// 
+/// `selftest` / `selftest_json` - Checks that the toolbox works here through the real code paths: codec round trips, hash and checksum known-answer vectors, HMAC vectors, digest verification, multi-member gzip streams, hex dump and strings of embedded fixtures, embedded data tables, blocklist matching, CIDR arithmetic, URL parsing, IDN punycode and homograph checks, timestamp conversions, JSON envelopes, snapshot migration, artifact directory writes, optional data files, a DNS query and an HTTP request, each pass/warn/fail/skip with timing; trailing options "network=false dns=example.com server=1.1.1.1 url=https://example.com/ dir=/tmp timeout=5 databases=pfx2as.txt,scope.txt"
+/// 
+/// `domain_category` / `domain_category_json` - Rough offline category of a domain from the words in its name: labels split on hyphens and digits and segmented into dictionary words, matched against embedded finance, shipping, auth, adult, gaming and crypto wordlists, with the tokens behind each category; trailing options "words=extra.txt,more.txt replace=true" (files of "category word word ..." lines extend the lists, or replace them)
+/// 
+/// `notify` / `notify_json` - POSTs "webhook_url payload" (a JSON payload is sent as the summary, other text as {"text": ...}) as a generic JSON document or Slack blocks, retrying timeouts, 429 and 5xx answers; trailing options "format=slack bearer=token hmac=secret timeout=10 retries=2 title=Scan_finished" plus the HTTP identity options ("hmac=" signs with X-Mercy-Timestamp and X-Mercy-Signature headers; underscores in the title become spaces). The batch calls `asn`, `cluster`, `logs_merge`, `normalize_timestamps` and the forensics calls `timeline`, `evtx`, `carve`, `minidump`, `oci_scan` and `secrets_git` take "notify=true" (the target set with `set_notify_target`) or "notify=https://..." and "notify_format=slack" to post a summary of their result when they finish; the result is returned unchanged whether or not the notification got through (see `last_notification`); `mercy_decode_batch_to`, `mercy_hash_batch_to` and `mercy_malicious_batch_to` take the same target in their `BatchOutput`
+/// 
+/// `listen` - Serves a fake SSH, HTTP or echo service on "port persona" (input "8022 ssh") for a while and returns an NDJSON line per connection (source, timing, bytes sent and a hex dump of the bytes received), ending with a {"summary": ...} line; trailing options "seconds=30 bind=127.0.0.1 max_read=65536 max_connections=64 timeout=10 out=connections.ndjson backpressure=block retries=5" ("out=" takes the same targets as the streaming forensics calls; `mercy_listen` runs one in the background until stopped)
+/// 
+/// `sri` / `sri_json` - Subresource Integrity value of a resource at a URL (downloaded up to a size cap) or in a local file, "sha384-<base64>" by default; `sri_json` gives the sha256, sha384 and sha512 values with the size; trailing options "algorithm=sha256|sha384|sha512|all max_bytes=5242880 timeout=10" plus the HTTP identity options
+/// 
+/// `sri_audit` / `sri_audit_json` - The script and stylesheet tags of an HTML page (a URL, or a file with "base=" for its relative links) resolved against the page or its <base href>, with findings for third-party ones lacking an integrity attribute or a crossorigin attribute; with "fetch=true" each third-party resource is downloaded to give the value it should carry and to check the one it has; trailing options "fetch=true base=https://example.com/ max_bytes=5242880 timeout=10" plus the HTTP identity options
+/// 
+/// `csp_audit` / `csp_audit_json` - Each Content-Security-Policy in the input (policies separated by commas or newlines, a line optionally starting with "Content-Security-Policy:" or "Content-Security-Policy-Report-Only:") parsed into directives and re-serialized, with findings naming the offending directive: syntax errors, 'unsafe-inline'/'unsafe-eval' and wildcard, scheme-only or data: script sources, missing object-src, base-uri or default-src, allowlisted hosts known to allow bypasses (JSONP, AngularJS, user content) from an embedded list, and report-only deployment
+/// 
+/// `http_headers` / `http_headers_json` - GETs a URL (or HEADs it with "method=head"), following up to 5 redirects, and returns the redirect chain, the status line and every response header; a refused connection, a certificate that does not verify or a service that does not speak HTTP is an error; trailing options "timeout=10 max_redirects=5 method=head insecure=true" plus the HTTP identity options
+/// 
+/// `security_headers` / `security_headers_json` - The same request, with Strict-Transport-Security, Content-Security-Policy, X-Frame-Options, X-Content-Type-Options and Referrer-Policy of the final response each marked present, missing or weak with a one-line reason (the JSON form carries the findings); same options
+/// 
+/// `hsts_check` / `hsts_check_json` - HSTS preload eligibility of a domain by the hstspreload.org rules: a valid certificate, a Strict-Transport-Security header on https://domain/ with max-age of at least 31536000, includeSubDomains and preload, http:// redirecting to HTTPS on the same host first, and a registrable domain; each criterion pass/fail with the header and redirect chain as evidence, and the overall result as a finding; trailing options "timeout=10 max_redirects=10" plus the HTTP identity options
+/// 
+/// `tls_cert` / `tls_cert_json` - The leaf certificate of a TLS service ("host" or "host:port", port 443 by default): subject CN, alternative names, issuer, validity with days until expiry, serial and SHA-256 fingerprint; expired, not yet valid, self-signed and wrongly named certificates are still reported, with a warning; trailing options "timeout=5 sni=name"
+/// 
+/// `triage` / `triage_json` - One-shot triage of an indicator (refanged first): a domain or email address gets DNS records, WHOIS age, InQuest reputation and the sinkhole check; an IP address its special-purpose range, origin ASN and country, and reverse DNS; a URL a breakdown, its page's security headers and its host's reputation; a hash its likely algorithm. Steps run side by side with their own timeout, and one that fails or times out is reported without sinking the rest; trailing options "timeout=15 server=1.1.1.1" plus the HTTP identity options
+/// 
+/// `snapshot_info` / `snapshot_info_json` - Schema version, creation and update times, writing mercy version and sections of a snapshot file (the versioned state container of the change-detection features), noting a migration from an older layout; a corrupted file gives the reason; trailing options "migrate=true compress=true backup=true" rewrite an older file in the current layout, optionally gzipped and after a timestamped backup
+/// 
+/// `password_strength` / `password_strength_json` - Offline strength estimate of a password (the whole input, spaces included): length, character classes, common words from a built-in list (also with look-alike digits and symbols), keyboard walks, sequences, years and repeated characters, an entropy estimate in bits and a verdict of weak (under 40 bits or 8 characters), fair or strong (65 bits and up) with the reasons for it; nothing is sent anywhere
+/// 
+/// `hibp_check` / `hibp_check_json` - How often a password appears in Have I Been Pwned breach corpora, by the k-anonymity range API: the password is hashed with SHA-1 locally and only the first five hex digits are sent, so neither the password nor its full hash leaves the host; "Not found..." when it is not listed, and an error naming the cause ("offline?") when the API cannot be reached; trailing options "timeout=10" plus the HTTP identity options
+/// 
+/// `hash_identify` / `hash_identify_json` - Candidate algorithms for a digest from a dump, most likely first, each with a short reason and its hashcat mode: hex digests by length (32 hex digits MD5, NTLM, MD4, LM; 40 SHA-1; 64 SHA-256; 128 SHA-512 and so on), crypt and scheme prefixes ($2b$ bcrypt, $6$ sha512crypt, $argon2id$, {SSHA}, pbkdf2_sha256$), base64 digests, and pwdump, NetNTLM, "LM:NT", "user:hash" and "hash:salt" lines; anything else is "not a recognized hash format"
+/// 
+/// `email_headers` / `email_headers_json` - Phishing read of a raw email header block given as the input text (not a file; a body after the first blank line is ignored): folded headers unfolded and duplicates kept, the From, Sender, Reply-To and Return-Path addresses with mismatched domains and display names showing another address flagged, the Received chain from origin to recipient with each hop's sending address, time and delay and the originating IP (the earliest public one), and the SPF, DKIM and DMARC results of Authentication-Results and Received-SPF with failures flagged; sectioned plain text, or everything as JSON
+/// 
+/// `secret_scan` / `secret_scan_json` - Exposed credentials in a file (input a path) or in the input text itself: AWS access key IDs and secret access keys, GitHub, GitLab, Slack, Stripe, npm and Google API tokens, private key PEM headers, JWTs, and password=/api_key=/token= style assignments, each with its rule, line, column and an excerpt showing only the first and last four characters; placeholders ("changeme", "example", "${VAR}") and low-entropy assigned values are skipped
+/// 
+/// `pe_info` / `pe_info_json` - Headers of a Windows executable or DLL given by path: machine (x86, x64, ARM64, ...), PE32 or PE32+, link time as a UTC date, subsystem, entry point, each section's name, virtual and raw size, permissions and entropy, the DLL names of the import table, and overlay data past the last section with its size; packer tells (writable and executable sections, high entropy, overlay) are noted, and a truncated or corrupted header gives an error naming it ("Not a PE file: missing MZ", "Truncated optional header")
+/// 
+/// `pcap_summary` / `pcap_summary_json` - Summary of a packet capture given by path, classic pcap (either byte order, micro- or nanosecond timestamps) or pcapng: packet and byte counts, first and last packet time and the duration, TCP/UDP/ICMP/other counts, and the top 10 source addresses and destination ports of Ethernet, raw IP and Linux cooked frames carrying IPv4 or IPv6; the file is streamed, and records with truncated headers are counted as malformed and skipped
+/// 
+/// `mac_lookup` / `mac_lookup_json` - Vendor of a MAC address ("AA:BB:CC:DD:EE:FF", "aa-bb-cc-dd-ee-ff" or "aabb.ccdd.eeff") from an embedded table of common vendors ("Unknown OUI" otherwise), with whether it is unicast, multicast or broadcast and globally unique or locally administered (randomized phones and laptops, most virtual NICs); a malformed address is an error naming what is wrong
+/// 
+/// `mac_lookup_file` / `mac_lookup_file_json` - The same from the full IEEE registry CSV, input "mac,csv_path" (oui.csv, mam.csv or oas.csv; the longest matching assignment wins)
+/// 
+/// `uuid_v4` - A random version 4 UUID from the OS CSPRNG; input "seed=42" gives the same UUID every time, for tests and reproduced runs (see `mercy_uuid_v4_with`)
+/// 
+/// `uuid_v5` - The name-based version 5 UUID (SHA-1, RFC 4122) of input "namespace,name": the namespace a UUID or "dns", "url", "oid" or "x500", the name everything after the first comma exactly as given
+/// 
+/// `uuid_inspect` / `uuid_inspect_json` - Version and variant of a UUID (hyphens optional, braces, "urn:uuid:" and uppercase accepted), and for time-based ones the embedded time in UTC (v1, v6, v7) with the clock sequence and node, usually the MAC address of the host that made it, and its vendor (v1, v6); an invalid UUID is an error naming what is wrong
+/// 
+/// `subdomains` / `subdomains_json` - Hostnames under a domain from certificate-transparency logs (crt.sh), one per line, sorted and each once: multi-name entries split, wildcard prefixes dropped, email addresses and other domains left out; "No certificates found for <domain>" when there are none. A failed request (no answer, 429 or 5xx) is tried once more; trailing options "timeout=30" plus the HTTP identity options
 pub fn mercy_extra(mercy_call: &str, mercy_choose: &str) -> String {
-    match mercy_call {
-        "internal_ip" => internal_ip(),
-        "system_info" => system_info(mercy_choose),
-        "defang" => defang(mercy_choose),
-        "whois" => whois_lookup(mercy_choose),
+    let output = match capabilities::handler("mercy_extra", mercy_call) {
+        Some(Handler::Report(run)) => run(mercy_call, mercy_choose),
         _ => unknown_msg("Unable to provide the information you requested")
-    }
+    };
+
+    redact::redact_enabled(output)
+}
+
+/// `mercy_extra` with `config` in place of the default `MercyConfig`: its proxy, timeouts and User-Agent apply to the lookups the call makes (`whois`, `external_ip`, `http_headers`, `triage` and the rest)
+pub fn mercy_extra_with(config: &MercyConfig, mercy_call: &str, mercy_choose: &str) -> String {
+    with_mercy_config(config, || mercy_extra(mercy_call, mercy_choose))
 }
 
 /* Decoding methods */
 
 // Base64 decode
-fn base64_decode(encoded_msg: String) -> String {
-    // Converts into bytes
-    let bytes = base64::decode(encoded_msg.to_string()).expect("Unable to decode provided string");
-    
-    // Converts into a more readable format
-    let final_out = String::from_utf8_lossy(&bytes);
-
-    return final_out.to_string();
+fn base64_decode(encoded_msg: &str) -> Result<String, MercyError> {
+    decoded_text("base64", encoded_msg)
 }
 
 // rot13 decode
 fn rot13_decode(encoded_msg: String) -> String {
-    let mut result_str = String::from("");
-    
-    // Iterates over encoded_msg
-    for x in encoded_msg.chars() {
-        let charcode = x as u32;
-        
-        if x.is_lowercase() {
-            // Checks if character in string is lowercase
-            let check_text = 'a' as u32;
-            let rot_final = ((charcode - check_text + 13) % 26) + check_text;
-            result_str.push(char::from_u32(rot_final).unwrap());
-        } else if x.is_uppercase() {
-            // Checks if character in string is uppercse
-            let check_text = 'A' as u32;
-            let rot_final = ((charcode - check_text + 13) % 26) + check_text;
-            result_str.push(char::from_u32(rot_final).unwrap());
-        } else {
-            result_str.push(x);
-        }
-    }
-    
-    return result_str.to_string();
+    cipher::shift_text(&encoded_msg, 13)
 }
 
 /* Encoding methods */
@@ -170,55 +1809,206 @@ fn rot13_decode(encoded_msg: String) -> String {
 // Base64 encode
 fn base64_encode(plaintext_msg: String) -> String {
     // Converts into bytes
-    let encoded_msg = base64::encode(plaintext_msg.as_bytes());
-    return encoded_msg.to_string();
+    base64::encode(plaintext_msg.as_bytes())
 }
 
 /* Hashing methods */
 
 // SHA256 hash
-fn sha2_256_hash(plaintext_msg: String) -> String {
+fn sha2_256_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
     let mut run_hash = Sha256::new();
     run_hash.update(plaintext_msg.as_bytes());
 
     let hash = run_hash.finalize();
-    return format!("{:x}", hash);
+    format!("{:x}", hash)
 }
 
 // MD5 hash
-fn md5_hash(plaintext_msg: String) -> String {
+fn md5_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
     let hash = md5::compute(plaintext_msg.as_bytes());
-    return format!("{:x}", hash);
+    format!("{:x}", hash)
+}
+
+// SHA-1 hash
+fn sha1_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
+    let mut run_hash = digests::Sha1::new();
+    run_hash.update(plaintext_msg.as_bytes());
+    hex_encode(&run_hash.finalize())
+}
+
+// SHA-512 hash
+fn sha2_512_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
+    let mut run_hash = Sha512::new();
+    run_hash.update(plaintext_msg.as_bytes());
+
+    let hash = run_hash.finalize();
+    format!("{:x}", hash)
+}
+
+// SHA3-256 or SHA3-512 hash, by digest length in bytes
+fn sha3_hash(plaintext_msg: &str, output: usize) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
+    let mut run_hash = digests::Sha3::new(output);
+    run_hash.update(plaintext_msg.as_bytes());
+    hex_encode(&run_hash.finalize())
+}
+
+// CRC-32 (IEEE) checksum
+fn crc32_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
+    format!("{:08x}", crc32(plaintext_msg.as_bytes()))
+}
+
+// xxHash64 with seed 0
+fn xxhash64_hash(plaintext_msg: &str) -> String {
+    stats::count(Counter::BytesHashed, plaintext_msg.len() as u64);
+    format!("{:016x}", xxhash64(plaintext_msg.as_bytes(), 0))
+}
+
+// Every digest of the message, one "algorithm: digest" line each
+fn all_hashes(plaintext_msg: &str) -> String {
+    let digests = [
+        ("md5", md5_hash(plaintext_msg)),
+        ("sha1", sha1_hash(plaintext_msg)),
+        ("sha2_256", sha2_256_hash(plaintext_msg)),
+        ("sha2_512", sha2_512_hash(plaintext_msg)),
+        ("sha3_256", sha3_hash(plaintext_msg, 32)),
+        ("sha3_512", sha3_hash(plaintext_msg, 64)),
+        ("ssdeep", ssdeep_hash(plaintext_msg.as_bytes())),
+        ("crc32", crc32_hash(plaintext_msg)),
+        ("xxhash64", xxhash64_hash(plaintext_msg))
+    ];
+
+    digests.iter().map(|(algorithm, digest)| format!("{}: {}", algorithm, digest)).collect::<Vec<_>>().join("\n")
 }
 
 /* Hexadecimal manipulation */
 
 // Converts file/bytes to a readable vector
-fn byte_to_vec(filename: &str) -> Vec<u8> {
-    let mut file = File::open(&filename).expect("Unable to locate file");
-    let file_metadata = fs::metadata(&filename).expect("Unable to read file metadata");
-    let mut buffer = vec![0; file_metadata.len() as usize];
+fn byte_to_vec<P: AsRef<Path>>(filename: P) -> io::Result<Vec<u8>> {
+    fs::read(filename)
+}
 
-    // Writes buffer data to the hex file 
-    for i in 0..buffer.len() {
-        file.read(&mut buffer).expect("Buffer overflow detected. Stopping operation...");
+// Exactly `length` bytes of a file from `offset`, or an error when they are not all inside it
+fn byte_range_to_vec(filename: &str, offset: u64, length: u64) -> Result<Vec<u8>, MercyError> {
+    let mut file = File::open(filename).map_err(|e| MercyError::io(filename, e))?;
+    let size = file.metadata().map_err(|e| MercyError::io(filename, e))?.len();
 
-        if i == buffer.len() {
-            println!("Buffer limit exceeded");
-            break;
+    if offset > size {
+        return Err(MercyError::invalid_input(format!("Offset {} is past the end of {} ({} bytes)", offset, filename, size)));
+    }
+    if length > size - offset {
+        return Err(MercyError::invalid_input(format!("Range of {} bytes from offset {} runs past the end of {} ({} bytes)", length, offset, filename, size)));
+    }
+
+    let mut buffer = vec![0; length as usize];
+    file.seek(SeekFrom::Start(offset)).map_err(|e| MercyError::io(filename, e))?;
+    file.read_exact(&mut buffer).map_err(|e| MercyError::io(filename, e))?;
+    Ok(buffer)
+}
+
+// "path [offset=0x1000] [length=256]": the whole file, or the range when either option is given
+fn collect_file_hex(input: &str) -> Result<String, MercyError> {
+    file_hex_bytes(input).map(|(bytes, offset)| hexdump_bytes(&bytes, offset))
+}
+
+// The bytes `collect_file_hex` dumps and the offset they start at
+fn file_hex_bytes(input: &str) -> Result<(Vec<u8>, u64), MercyError> {
+    let (convert_file, offset, length) = hex_options(input)?;
+    if offset.is_none() && length.is_none() {
+        return whole_file_hex_bytes(&convert_file).map(|bytes| (bytes, 0));
+    }
+
+    // Without a length the range runs to the end of the file
+    let offset = offset.unwrap_or(0);
+    let length = match length {
+        Some(length) => length,
+        None => fs::metadata(&convert_file).map_err(|e| MercyError::io(&convert_file, e))?.len().saturating_sub(offset)
+    };
+
+    range_hex_bytes(&convert_file, offset, length).map(|bytes| (bytes, offset))
+}
+
+// Most decompressed bytes `hex_dump_gz` dumps without a length
+const GZIP_HEX_LIMIT: u64 = 16 * 1024 * 1024;
+
+// "path [offset=0x1000] [length=256]" for gzip data: the range is of the decompressed bytes, reached by inflating past the offset
+fn gzip_hex_bytes(input: &str) -> Result<(Vec<u8>, u64), MercyError> {
+    let (convert_file, offset, length) = hex_options(input)?;
+    let offset = offset.unwrap_or(0);
+
+    let file = File::open(&convert_file).map_err(|e| MercyError::io(&convert_file, e))?;
+    let mut stream = inflate::gzip_stream(io::BufReader::new(file)).map_err(|e| MercyError::from(format!("{}: {}", convert_file, e)))?;
+    let corrupt = |e: io::Error| MercyError::from(format!("Unable to decompress {}: {}", convert_file, e));
+
+    let skipped = io::copy(&mut (&mut stream).take(offset), &mut io::sink()).map_err(corrupt)?;
+    if skipped < offset {
+        return Err(MercyError::invalid_input(format!("Offset {} is past the end of the decompressed {} ({} bytes)", offset, convert_file, skipped)));
+    }
+
+    // Without a length the rest is dumped, unless there is too much of it to return as text
+    let mut bytes = Vec::new();
+    (&mut stream).take(length.unwrap_or(GZIP_HEX_LIMIT + 1)).read_to_end(&mut bytes).map_err(corrupt)?;
+    match length {
+        Some(length) if (bytes.len() as u64) < length => {
+            return Err(MercyError::invalid_input(format!("Range of {} bytes from offset {} runs past the end of the decompressed {} ({} bytes)", length, offset, convert_file, offset + bytes.len() as u64)));
+        },
+        None if bytes.len() as u64 > GZIP_HEX_LIMIT => {
+            return Err(MercyError::invalid_input(format!("{} decompresses to more than {} bytes from offset {}; give \"length=\" to dump part of it", convert_file, GZIP_HEX_LIMIT, offset)));
+        },
+        _ => {}
+    }
+
+    stats::count(Counter::BytesHexDumped, bytes.len() as u64);
+    Ok((bytes, offset))
+}
+
+// The path and the "offset=" and "length=" options of a hex dump
+fn hex_options(input: &str) -> Result<(String, Option<u64>, Option<u64>), MercyError> {
+    let (convert_file, pairs) = trailing_options(input, &["offset", "length"]);
+
+    let mut offset = None;
+    let mut length = None;
+    for (key, value) in pairs {
+        let number = parse_byte_count(value).ok_or_else(|| MercyError::invalid_input(format!("Invalid {} '{}' (expected a byte count, decimal or 0x hex)", key, value)))?;
+        match key {
+            "offset" => offset = Some(number),
+            _ => length = Some(number)
         }
     }
 
-    buffer
+    Ok((convert_file, offset, length))
+}
+
+fn collect_range_hex(convert_file: &str, offset: u64, length: u64) -> Result<String, MercyError> {
+    range_hex_bytes(convert_file, offset, length).map(|bytes| hexdump_bytes(&bytes, offset))
+}
+
+fn range_hex_bytes(convert_file: &str, offset: u64, length: u64) -> Result<Vec<u8>, MercyError> {
+    let bytes = byte_range_to_vec(convert_file, offset, length)?;
+    stats::count(Counter::BytesHexDumped, bytes.len() as u64);
+    Ok(bytes)
 }
 
-fn collect_file_hex(convert_file: &str) -> String {
+fn whole_file_hex_bytes(convert_file: &str) -> Result<Vec<u8>, MercyError> {
     // convert_file requires an absolute path to work 100% of the time
     if Path::new(convert_file).exists() {
-        // Dumps hex data to stdout
-        return format!("{:#?}", hexdump(&byte_to_vec(convert_file)));
+        let bytes = byte_to_vec(convert_file).map_err(|e| MercyError::io(convert_file, e))?;
+        stats::count(Counter::BytesHexDumped, bytes.len() as u64);
+        Ok(bytes)
     } else {
-        return format!("Unable to locate the file specified");
+        Err(MercyError::from("Unable to locate the file specified".to_string()))
+    }
+}
+
+fn parse_byte_count(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
     }
 }
 
@@ -226,100 +2016,331 @@ fn collect_file_hex(convert_file: &str) -> String {
 
 // Quick method for collecting the internal ip address of the local system
 fn internal_ip() -> String {
-    let socket = UdpSocket::bind("0.0.0.0:0").expect("Unable to bind UDP socket");
-    socket.connect("8.8.8.8:80").expect("Unable to connect to address");
-    let addr = socket.local_addr().expect("Unable to return the socket address");
-    return addr.ip().to_string();
+    match internal_ip_addr() {
+        Ok(ip) => ip.to_string(),
+        Err(e) => format!("Unable to find the internal IP address: {}", e)
+    }
 }
 
-// System information based on matching parameter
-fn system_info(data: &str) -> String {
+// The address of the interface outbound traffic leaves from; connecting a UDP socket sends nothing
+fn internal_ip_addr() -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect("8.8.8.8:80")?;
+    Ok(socket.local_addr()?.ip())
+}
 
-    let all_system_info = format!("\nHostname: {}\nNumber of CPU cores: {}\nCPU Fan Speed: {} MHz\nOperating System Release Version: {}\nNumber of Processes: {}\n", hostname().unwrap(), cpu_num().unwrap(), cpu_speed().unwrap(), os_release().unwrap(), proc_total().unwrap());
+// System information based on matching parameter; a metric the platform does not give reads "unavailable"
+fn system_info(data: &str) -> String {
+    const FIELDS: [&str; 9] = ["hostname", "cpu_cores", "cpu_speed", "os_release", "proc", "memory", "uptime", "disk", "user"];
 
     match data {
-        "hostname" => return format!("Hostname: {}", hostname().unwrap()),
-        "cpu_cores" => return format!("Number of CPU cores: {}", cpu_num().unwrap()),
-        "cpu_speed" => return format!("CPU Fan Speed: {} MHz", cpu_speed().unwrap()),
-        "os_release" => return format!("Operating System Release Version: {}", os_release().unwrap()),
-        "proc" => return format!("Number of Processes: {}", proc_total().unwrap()),
-        "all" => return format!("{}", all_system_info),
-        _ => return format!("Unable to gather system information")
+        "all" => format!("\n{}\n", FIELDS.iter().map(|field| system_info_line(field)).collect::<Vec<_>>().join("\n")),
+        field if FIELDS.contains(&field) => system_info_line(field),
+        _ => "Unable to gather system information".to_string()
+    }
+}
+
+fn system_info_line(field: &str) -> String {
+    fn available<T, E>(value: Result<T, E>, show: impl Fn(T) -> String) -> String {
+        value.map(show).unwrap_or_else(|_| "unavailable".to_string())
+    }
+
+    match field {
+        "hostname" => format!("Hostname: {}", available(hostname(), |name| name)),
+        "cpu_cores" => format!("Number of CPU cores: {}", available(cpu_num(), |cores| cores.to_string())),
+        "cpu_speed" => format!("CPU Fan Speed: {}", available(cpu_speed(), |speed| format!("{} MHz", speed))),
+        "os_release" => format!("Operating System Release Version: {}", available(os_release(), |release| release)),
+        "proc" => format!("Number of Processes: {}", available(proc_total(), |count| count.to_string())),
+        "memory" => format!("Memory: {}", available(hostinfo::memory(), |memory| {
+            format!("{} total, {} available, {} free", hostinfo::gib(memory.total), hostinfo::gib(memory.available), hostinfo::gib(memory.free))
+        })),
+        "uptime" => format!("System Uptime: {}", available(hostinfo::uptime(), |uptime| {
+            format!("{} (booted {})", hostinfo::days_hours_minutes(uptime), hostinfo::boot_time(uptime))
+        })),
+        "disk" => format!("Disk Usage (/): {}", available(hostinfo::root_disk(), |disk| {
+            format!("{} used of {}, {} free ({:.0}%)", hostinfo::gib(disk.used), hostinfo::gib(disk.total), hostinfo::gib(disk.free), disk.percent_used())
+        })),
+        _ => format!("Current User: {}", available(hostinfo::current_user(), |user| user))
     }
 }
 
-// Basic defang for URLs and IP addresses (or any string with a '.')
+// Defangs URLs, domains, IP addresses and emails: http(s)/ftp schemes become hxxp(s)/fxp, '.' becomes "[.]" and '@' becomes "[@]"
 fn defang(ip_or_url: &str) -> String {
-    return ip_or_url.replace(".", "[.]")
+    let schemes = [("https://", "hxxps://"), ("http://", "hxxp://"), ("ftp://", "fxp://")];
+    let text = schemes.iter().fold(ip_or_url.to_string(), |text, (live, defanged)| replace_ignore_case(&text, live, defanged));
+
+    // Already bracketed characters are left alone, so defanging twice changes nothing
+    let chars: Vec<char> = text.chars().collect();
+    let mut defanged = String::with_capacity(text.len() + 8);
+    for (index, c) in chars.iter().enumerate() {
+        let bracketed = index > 0 && chars[index - 1] == '[' && chars.get(index + 1) == Some(&']');
+        match c {
+            '.' | '@' if !bracketed => {
+                defanged.push('[');
+                defanged.push(*c);
+                defanged.push(']');
+            },
+            _ => defanged.push(*c)
+        }
+    }
+
+    defanged
+}
+
+// Turns defanged indicators back into live ones: hxxp(s)/fxp schemes, and bracketed or spelled-out dots, at signs and colons
+fn refang(defanged: &str) -> String {
+    let tokens = [
+        ("[://]", "://"), ("[:]", ":"), ("[/]", "/"),
+        ("[.]", "."), ("(.)", "."), ("{.}", "."), ("[dot]", "."), ("(dot)", "."), ("{dot}", "."),
+        ("[@]", "@"), ("(@)", "@"), ("{@}", "@"), ("[at]", "@"), ("(at)", "@"), ("{at}", "@"),
+        ("hxxps://", "https://"), ("hxxp://", "http://"), ("hxtps://", "https://"), ("hxtp://", "http://"), ("fxp://", "ftp://")
+    ];
+
+    tokens.iter().fold(defanged.to_string(), |text, (defanged, live)| replace_ignore_case(&text, defanged, live))
+}
+
+// Replaces every ASCII case-insensitive occurrence of `from`
+fn replace_ignore_case(text: &str, from: &str, to: &str) -> String {
+    // ASCII lowercasing keeps byte offsets, so matches in the copy index the original
+    let lowered = text.to_ascii_lowercase();
+    let from = from.to_ascii_lowercase();
+    let mut replaced = String::with_capacity(text.len());
+    let mut position = 0;
+
+    while let Some(found) = lowered[position..].find(&from) {
+        replaced.push_str(&text[position..position + found]);
+        replaced.push_str(to);
+        position += found + from.len();
+    }
+
+    replaced.push_str(&text[position..]);
+    replaced
 }
 
-// WHOIS lookup for domain information
-fn whois_lookup(url: &str) -> String {
-    let whois_server = "whois.verisign-grs.com";
-    let whois_port = 43;
+// Renders a typed report (or its error) as human-readable text
+fn report_text<T: fmt::Display>(result: Result<T, String>) -> String {
+    match result {
+        Ok(report) => report.to_string(),
+        Err(e) => {
+            stats::count_error(&e);
+            e
+        }
+    }
+}
+
+// Renders a typed report as JSON, with errors as {"error": ...}
+fn report_json<T: Serialize>(result: Result<T, String>) -> String {
+    match result {
+        Ok(report) => serde_json::to_string_pretty(&report).unwrap_or_else(|e| e.to_string()),
+        Err(e) => {
+            stats::count_error(&e);
+            serde_json::json!({ "error": e }).to_string()
+        }
+    }
+}
+
+// A typed report as text for the fallible dispatchers
+fn text_result<T: fmt::Display>(result: Result<T, String>) -> Result<String, MercyError> {
+    result.map(|report| report.to_string()).map_err(MercyError::from)
+}
+
+// A typed report as JSON for the fallible dispatchers
+fn json_result<T: Serialize>(result: Result<T, String>) -> Result<String, MercyError> {
+    let report = result.map_err(MercyError::from)?;
+    serde_json::to_string_pretty(&report).map_err(|e| MercyError::from(e.to_string()))
+}
+
+// A report as text, or as JSON for the `_json` variant of the call
+fn report_for<T: fmt::Display + Serialize>(mercy_call: &str, result: Result<T, String>) -> String {
+    match mercy_call.ends_with("_json") {
+        true => report_json(result),
+        false => report_text(result)
+    }
+}
+
+// `report_for` for the fallible dispatchers
+fn result_for<T: fmt::Display + Serialize>(mercy_call: &str, result: Result<T, String>) -> Result<String, MercyError> {
+    match mercy_call.ends_with("_json") {
+        true => json_result(result),
+        false => text_result(result)
+    }
+}
+
+// The calls of the `image` feature; the registry leaves them out of builds without it, so the
+// stand-ins below are never run
+#[cfg(feature = "image")]
+fn qr_encode(text: &str) -> Result<String, MercyError> {
+    text_result(qr::qr_encode_from_args(text))
+}
+
+#[cfg(not(feature = "image"))]
+fn qr_encode(_: &str) -> Result<String, MercyError> {
+    Err(MercyError::unsupported("mercy_encode", "qr"))
+}
+
+#[cfg(feature = "image")]
+fn qr_decode(mercy_call: &str, path: &str) -> String {
+    report_for(mercy_call, mercy_qr_decode(path))
+}
+
+#[cfg(not(feature = "image"))]
+fn qr_decode(_: &str, _: &str) -> String {
+    unknown_msg("Unable to parse the format requested")
+}
+
+#[cfg(feature = "image")]
+fn stego_check(input: &str) -> String {
+    report_json(stego::stego_check_from_args(input))
+}
+
+#[cfg(not(feature = "image"))]
+fn stego_check(_: &str) -> String {
+    unknown_msg("Unable to provide the information you requested")
+}
+
+// Counts a failed call (unknown calls are not errors of the toolbox) and redacts a result
+fn finish_result(result: Result<String, MercyError>) -> Result<String, MercyError> {
+    result.map(redact::redact_enabled).map_err(counted_error)
+}
+
+// Counts a failure in the error counters; an unknown call is not one
+fn counted_error(e: MercyError) -> MercyError {
+    if !matches!(e, MercyError::UnsupportedMethod { .. }) {
+        stats::count_error(&e.to_string());
+    }
+    e
+}
+
+// The string dispatchers' form of a fallible call: an unknown call gets the dispatcher's message, a failure its text ({"error": ...} for a _json call)
+fn result_text(mercy_call: &str, result: Result<String, MercyError>, unknown: &str) -> String {
+    match result {
+        Ok(output) => output,
+        Err(MercyError::UnsupportedMethod { .. }) => unknown_msg(unknown),
+        Err(e) if mercy_call.ends_with("_json") => redact::redact_enabled(serde_json::json!({ "error": e.to_string() }).to_string()),
+        Err(e) => redact::redact_enabled(e.to_string())
+    }
+}
+
+// Ping driven by the "host count=N timeout=S" string form
+fn ping_from_args(args: &str) -> Result<PingReport, String> {
+    let (host, count, timeout) = ping::parse_ping_args(args)?;
+    mercy_ping(&host, count, timeout)
+}
 
-    let mut stream = TcpStream::connect((whois_server, whois_port)).unwrap();
-    stream.write_all(format!("{}\r\n", url).as_bytes()).unwrap();
+// Splits "a path with spaces key=value key=value" into the path and the recognised trailing options (in input order)
+pub(crate) fn trailing_options<'a>(input: &'a str, keys: &[&str]) -> (String, Vec<(&'a str, &'a str)>) {
+    let mut tokens: Vec<&str> = input.trim().split(' ').collect();
+    let mut options = Vec::new();
 
-    let mut whois_response = Vec::new();
-    stream.read_to_end(&mut whois_response).unwrap();
+    while let Some((key, value)) = tokens.last().and_then(|token| token.split_once('=')) {
+        if !keys.contains(&key) {
+            break;
+        }
+        options.insert(0, (key, value));
+        tokens.pop();
+    }
 
-    let res_to_str = from_utf8(&whois_response).unwrap();
-    return res_to_str.to_string();
+    (tokens.join(" ").trim().to_string(), options)
 }
 
 fn unknown_msg(custom_msg: &str) -> String {
-    return format!("{}", custom_msg);
+    custom_msg.to_string()
 }
 
 /* Malicious Detection */
 
-// Handles the actual JSON response from the url request
-#[tokio::main]
-async fn malicious_domain_status(domain: &str) -> String {
-    url_request(domain).await.unwrap();
-
-    // Saves a local JSON file for parsing
-    let json_file: &str = "/tmp/mercy_domain_review.json";
-    
-    let json_parse = {
-        // Load the JSON file and convert to an easier to read format
-        let json_convert = std::fs::read_to_string(&json_file).expect("Unable to locate file");
-        serde_json::from_str::<Value>(&json_convert).unwrap()
-    };
+// Fetches and parses what InQuest knows of a domain; "domain user_agent=curl" style options set the HTTP identity
+async fn malicious_domain_report(input: &str) -> Result<MaliciousReport, String> {
+    let (domain, pairs) = trailing_options(input, &http::IDENTITY_KEYS);
+    let mut identity = HttpIdentity::default();
+    for (key, value) in pairs {
+        http::identity_option(&mut identity, key, value)?;
+    }
 
-    // Deletes temporary JSON file
-    fs::remove_file("/tmp/mercy_domain_review.json").unwrap();
+    let body = url_request(&domain, &identity).await.map_err(|e| format!("InQuest lookup of {} failed: {}", domain, e))?;
+    inquest_response(&domain, &body)
+}
 
-    if &json_parse["data"][0]["classification"] == "MALICIOUS" {
-        return "Malicious".to_string();
-    } else if &json_parse["data"][0]["classification"] == "UNKNOWN" {
-        return "Unknown".to_string();
-    } else if &json_parse["data"][0]["classification"] == "SUSPICIOUS" {
-        return "Suspicious".to_string();
-    } else {
-        return "No classification available".to_string();
+// Looks up a file hash: "hash [timeout=30]" plus identity options; the hash is checked before any request
+async fn malicious_hash_report(input: &str) -> Result<HashReport, String> {
+    let keys: Vec<&str> = ["timeout"].into_iter().chain(http::IDENTITY_KEYS).collect();
+    let (hash, pairs) = trailing_options(input, &keys);
+    let mut identity = HttpIdentity::default();
+    let mut timeout = config::read_timeout(inquest::INQUEST_TIMEOUT);
+    for (key, value) in pairs {
+        match key {
+            "timeout" => timeout = std::time::Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => http::identity_option(&mut identity, key, value)?
+        }
+    }
+
+    let hash = hash.to_ascii_lowercase();
+    let algorithm = inquest::hash_algorithm(&hash)?;
+    let url = format!("{}/api/dfi/search/hash/{}?hash={}", service_endpoints().inquest.trim_end_matches('/'), algorithm, hash);
+    let body = net::send(inquest_request(http::http_client(&identity)?.get(url)).timeout(timeout))
+        .await
+        .map_err(|e| format!("InQuest lookup of {} failed: {}", hash, e))?
+        .text()
+        .await
+        .map_err(|e| format!("InQuest lookup of {} failed: {}", hash, e))?;
+
+    let response = serde_json::from_str::<Value>(&body).map_err(|e| format!("Unreadable InQuest response for {}: {}", hash, e))?;
+    Ok(inquest::parse_hash_response(&hash, algorithm, &response))
+}
+
+// The same lookup keeping the raw response: "domain out=review.json" or a new file in the temp directory
+async fn malicious_domain_save(input: &str) -> Result<String, String> {
+    let keys: Vec<&str> = ["out"].into_iter().chain(http::IDENTITY_KEYS).collect();
+    let (domain, pairs) = trailing_options(input, &keys);
+    let mut identity = HttpIdentity::default();
+    let mut out = None;
+    for (key, value) in pairs {
+        match key {
+            "out" => out = Some(PathBuf::from(value)),
+            _ => http::identity_option(&mut identity, key, value)?
+        }
     }
+
+    let body = url_request(&domain, &identity).await.map_err(|e| format!("InQuest lookup of {} failed: {}", domain, e))?;
+
+    let path = match out {
+        Some(path) => {
+            fs::write(&path, &body).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+            path
+        },
+        None => inquest::save_response(&domain, &body)?
+    };
+
+    let report = inquest_response(&domain, &body)?;
+    Ok(format!("{} (response saved to {})", report.status(), path.display()))
+}
+
+fn inquest_response(domain: &str, body: &str) -> Result<MaliciousReport, String> {
+    let response = serde_json::from_str::<Value>(body).map_err(|e| format!("Unreadable InQuest response for {}: {}", domain, e))?;
+    inquest::parse_response(domain, &response)
 }
 
-// Makes an async url request to the InQuest API for domain IOC info
-async fn url_request(url: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-    
-    // Creates temp file for JSON data
-    let mut file = File::create("/tmp/mercy_domain_review.json").expect("Failed to create file");
+// Makes an async url request to the InQuest API for domain IOC info, retried and paced (see net.rs)
+async fn url_request(url: &str, identity: &HttpIdentity) -> Result<String, String> {
+    let client = http::http_client(identity)?;
 
     // Constructs API request via InQuest
-    let form_url = format!("https://labs.inquest.net/api/dfi/search/ioc/domain?keyword={}", url);
+    let form_url = format!("{}/api/dfi/search/ioc/domain?keyword={}", service_endpoints().inquest.trim_end_matches('/'), url);
 
     // Data from API request
-    let body = client.get(form_url).send()
+    let body = net::send(inquest_request(client.get(form_url)).timeout(config::read_timeout(inquest::INQUEST_TIMEOUT)))
         .await?
         .text()
-        .await?;
-
-    // Writes JSON data to the temp file
-    file.write_all(body.as_bytes()).expect("Failed to write to file");
+        .await
+        .map_err(|e| e.to_string())?;
 
     Ok(body)
+}
+
+// Adds the InQuest API token of the current config, if there is one
+fn inquest_request(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match mercy_config().api_token("inquest") {
+        Some(token) => request.header(reqwest::header::AUTHORIZATION, format!("Basic {}", token)),
+        None => request
+    }
 }
\ No newline at end of file
@@ -0,0 +1,592 @@
+/*
+    Fake network services for deception and for testing scanners
+
+    `mercy_listen` binds a TCP port and answers every connection with a canned persona: an
+    SSH server that sends its version banner, an HTTP server with a fixed status, headers
+    and body, or an echo service. Each connection is logged once it closes, with the source
+    address, timing and a hex dump of what the client sent, through a callback (or NDJSON
+    lines with `ndjson_logger`).
+
+    Connections are served on their own threads, at most `max_connections` at once; a
+    connection over that limit is closed straight away and logged as refused. A client may
+    send at most `max_read` bytes and has `read_timeout` to send them, after which the
+    connection is closed, so a silent or endless client cannot hold a thread. `stop()` on
+    the returned handle (or dropping it) closes the listener and joins every thread.
+*/
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+        Mutex
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+
+use serde::Serialize;
+
+use crate::{
+    hexdump::hexdump_bytes,
+    sink::{SinkArgs, finish_sink},
+    timefmt::unix_to_rfc3339,
+    trailing_options
+};
+
+// How often the accept loop and blocked reads look at the stop flag
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Protocol a fake service speaks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Persona {
+    /// Sends `banner` (an SSH identification string) on connect, then only listens
+    Ssh { banner: String },
+    /// Answers the first request with `status`, `headers` and `body`
+    Http { status: u16, headers: Vec<(String, String)>, body: String },
+    /// Sends back everything it receives
+    Echo
+}
+
+impl Persona {
+    /// An Ubuntu OpenSSH banner
+    pub fn ssh() -> Persona {
+        Persona::Ssh { banner: "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6".to_string() }
+    }
+
+    /// The Apache default page
+    pub fn http() -> Persona {
+        Persona::Http {
+            status: 200,
+            headers: vec![
+                ("Server".to_string(), "Apache/2.4.52 (Ubuntu)".to_string()),
+                ("Content-Type".to_string(), "text/html; charset=UTF-8".to_string())
+            ],
+            body: "<html><body><h1>It works!</h1></body></html>\n".to_string()
+        }
+    }
+
+    /// "ssh", "http" or "echo", with their defaults
+    pub fn parse(name: &str) -> Result<Persona, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "ssh" => Ok(Persona::ssh()),
+            "http" => Ok(Persona::http()),
+            "echo" => Ok(Persona::Echo),
+            _ => Err(format!("Unknown persona '{}' (ssh, http or echo)", name))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Persona::Ssh { .. } => "ssh",
+            Persona::Http { .. } => "http",
+            Persona::Echo => "echo"
+        }
+    }
+}
+
+/// Options for `mercy_listen`
+#[derive(Debug, Clone)]
+pub struct ListenOptions {
+    /// Address to bind; loopback unless the service should be reachable from other hosts
+    pub bind: IpAddr,
+    /// Bytes read from a client before its connection is closed
+    pub max_read: usize,
+    /// Time a client has to send its data, from the moment it connects
+    pub read_timeout: Duration,
+    /// Connections served at once; later ones are closed and logged as refused
+    pub max_connections: usize
+}
+
+impl Default for ListenOptions {
+    fn default() -> Self {
+        ListenOptions {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            max_read: 64 * 1024,
+            read_timeout: Duration::from_secs(10),
+            max_connections: 64
+        }
+    }
+}
+
+/// One connection to a fake service, logged when it closes
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionRecord {
+    pub source: String,
+    pub port: u16,
+    pub persona: String,
+    /// When the client connected (RFC 3339, UTC)
+    pub started: String,
+    pub duration_ms: f64,
+    pub bytes_received: usize,
+    /// Hex dump of the bytes received, one line per 16 bytes
+    pub received_hex: String,
+    /// The read limit was reached
+    pub truncated: bool,
+    pub bytes_sent: usize,
+    /// Closed without being served because `max_connections` were open
+    pub refused: bool,
+    /// How the connection ended when it was not closed by the client or a limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+impl fmt::Display for ConnectionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> :{} {} received {} bytes, sent {} bytes in {:.1} ms", self.started, self.source, self.port, self.persona, self.bytes_received, self.bytes_sent, self.duration_ms)?;
+        if self.refused {
+            write!(f, " (refused)")?;
+        }
+        if self.truncated {
+            write!(f, " (read limit reached)")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " ({})", error)?;
+        }
+        if !self.received_hex.is_empty() {
+            write!(f, "\n{}", self.received_hex)?;
+        }
+        Ok(())
+    }
+}
+
+/// Totals of a fake service, returned by `ListenHandle::stop`
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenSummary {
+    pub address: String,
+    pub persona: String,
+    pub connections: u64,
+    pub refused: u64,
+    pub bytes_received: u64,
+    pub duration_ms: f64
+}
+
+impl fmt::Display for ListenSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {} connections ({} refused), {} bytes received in {:.1} s", self.persona, self.address, self.connections, self.refused, self.bytes_received, self.duration_ms / 1000.0)
+    }
+}
+
+type Logger = Arc<dyn Fn(&ConnectionRecord) + Send + Sync>;
+
+// State shared by the accept loop and the connection threads
+struct Service {
+    persona: Persona,
+    options: ListenOptions,
+    port: u16,
+    stop: AtomicBool,
+    connections: AtomicU64,
+    refused: AtomicU64,
+    bytes_received: AtomicU64,
+    active: AtomicUsize,
+    log: Logger
+}
+
+/// A running fake service; stops when `stop` is called or the handle is dropped
+pub struct ListenHandle {
+    address: SocketAddr,
+    service: Arc<Service>,
+    started: Instant,
+    acceptor: Option<JoinHandle<()>>
+}
+
+impl fmt::Debug for ListenHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListenHandle").field("address", &self.address).field("persona", &self.service.persona.name()).finish()
+    }
+}
+
+impl ListenHandle {
+    /// The bound address (useful with port 0)
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Connections being served right now
+    pub fn active(&self) -> usize {
+        self.service.active.load(Ordering::SeqCst)
+    }
+
+    /// Closes the listener, waits for every connection thread to finish and returns the totals
+    pub fn stop(mut self) -> ListenSummary {
+        self.shutdown();
+
+        let service = &self.service;
+        ListenSummary {
+            address: self.address.to_string(),
+            persona: service.persona.name().to_string(),
+            connections: service.connections.load(Ordering::SeqCst),
+            refused: service.refused.load(Ordering::SeqCst),
+            bytes_received: service.bytes_received.load(Ordering::SeqCst),
+            duration_ms: self.started.elapsed().as_secs_f64() * 1000.0
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.service.stop.store(true, Ordering::SeqCst);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+impl Drop for ListenHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Serves `persona` on `port` (0 picks a free one), calling `on_connection` from the connection's thread as each one closes
+pub fn mercy_listen<F>(port: u16, persona: Persona, options: &ListenOptions, on_connection: F) -> Result<ListenHandle, String>
+where
+    F: Fn(&ConnectionRecord) + Send + Sync + 'static
+{
+    if options.max_connections == 0 {
+        return Err("Invalid max_connections 0 (expected at least 1)".to_string());
+    }
+
+    let listener = TcpListener::bind((options.bind, port)).map_err(|e| format!("Unable to listen on {}:{}: {}", options.bind, port, e))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    // A nonblocking accept lets the loop notice the stop flag without a wake-up connection
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+    let service = Arc::new(Service {
+        persona,
+        options: options.clone(),
+        port: address.port(),
+        stop: AtomicBool::new(false),
+        connections: AtomicU64::new(0),
+        refused: AtomicU64::new(0),
+        bytes_received: AtomicU64::new(0),
+        active: AtomicUsize::new(0),
+        log: Arc::new(on_connection)
+    });
+
+    let acceptor = {
+        let service = Arc::clone(&service);
+        thread::Builder::new()
+            .name(format!("mercy-listen-{}", address.port()))
+            .spawn(move || accept_loop(listener, service))
+            .map_err(|e| format!("Unable to start the listener thread: {}", e))?
+    };
+
+    Ok(ListenHandle { address, service, started: Instant::now(), acceptor: Some(acceptor) })
+}
+
+/// A logger for `mercy_listen` writing each record as one JSON line; the caller keeps the writer to flush or read it
+pub fn ndjson_logger<W: Write + Send + 'static>(writer: Arc<Mutex<W>>) -> impl Fn(&ConnectionRecord) + Send + Sync + 'static {
+    move |record| {
+        let line = serde_json::to_string(record).unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }).to_string());
+        let mut writer = match writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner()
+        };
+        let _ = writeln!(writer, "{}", line);
+    }
+}
+
+fn accept_loop(listener: TcpListener, service: Arc<Service>) {
+    let mut workers: Vec<JoinHandle<()>> = Vec::new();
+
+    while !service.stop.load(Ordering::SeqCst) {
+        let (stream, peer) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            },
+            // A connection reset before it was accepted, or out of descriptors; keep serving
+            Err(_) => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+
+        workers.retain(|worker| !worker.is_finished());
+        service.connections.fetch_add(1, Ordering::SeqCst);
+
+        if workers.len() >= service.options.max_connections {
+            service.refused.fetch_add(1, Ordering::SeqCst);
+            let _ = stream.shutdown(Shutdown::Both);
+            let mut record = new_record(&service, peer);
+            record.refused = true;
+            (service.log)(&record);
+            continue;
+        }
+
+        let worker = {
+            let service = Arc::clone(&service);
+            thread::Builder::new()
+                .name(format!("mercy-listen-{}-conn", service.port))
+                .spawn(move || {
+                    service.active.fetch_add(1, Ordering::SeqCst);
+                    let record = serve(&service, stream, peer);
+                    service.active.fetch_sub(1, Ordering::SeqCst);
+                    (service.log)(&record);
+                })
+        };
+
+        match worker {
+            Ok(worker) => workers.push(worker),
+            Err(_) => {
+                service.refused.fetch_add(1, Ordering::SeqCst);
+                let mut record = new_record(&service, peer);
+                record.refused = true;
+                record.error = Some("Unable to start a connection thread".to_string());
+                (service.log)(&record);
+            }
+        }
+    }
+
+    // The listener closes here; the connection threads see the flag within one poll interval
+    drop(listener);
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+fn new_record(service: &Service, peer: SocketAddr) -> ConnectionRecord {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    ConnectionRecord {
+        source: peer.to_string(),
+        port: service.port,
+        persona: service.persona.name().to_string(),
+        started: unix_to_rfc3339(now.as_secs() as i64, now.subsec_millis() * 1_000_000),
+        duration_ms: 0.0,
+        bytes_received: 0,
+        received_hex: String::new(),
+        truncated: false,
+        bytes_sent: 0,
+        refused: false,
+        error: None
+    }
+}
+
+// Why a connection ended, other than the client closing it
+enum Ending {
+    Closed,
+    LimitReached,
+    TimedOut,
+    Stopped,
+    Failed(io::Error)
+}
+
+// Reads the client's bytes, bounded by the read limit, the deadline and the stop flag
+struct Reader<'a> {
+    service: &'a Service,
+    stream: &'a TcpStream,
+    deadline: Instant,
+    received: Vec<u8>
+}
+
+impl Reader<'_> {
+    // Reads once into `received`, returning the number of new bytes
+    fn read_some(&mut self) -> Result<usize, Ending> {
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            let remaining = self.service.options.max_read.saturating_sub(self.received.len());
+            if remaining == 0 {
+                return Err(Ending::LimitReached);
+            }
+            if self.service.stop.load(Ordering::SeqCst) {
+                return Err(Ending::Stopped);
+            }
+            if Instant::now() >= self.deadline {
+                return Err(Ending::TimedOut);
+            }
+
+            let want = remaining.min(chunk.len());
+            match (&*self.stream).read(&mut chunk[..want]) {
+                Ok(0) => return Err(Ending::Closed),
+                Ok(read) => {
+                    self.received.extend_from_slice(&chunk[..read]);
+                    return Ok(read);
+                },
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(Ending::Failed(e))
+            }
+        }
+    }
+}
+
+fn serve(service: &Service, stream: TcpStream, peer: SocketAddr) -> ConnectionRecord {
+    let start = Instant::now();
+    let mut record = new_record(service, peer);
+    let mut reader = Reader { service, stream: &stream, deadline: start + service.options.read_timeout, received: Vec::new() };
+
+    // Accepted sockets may inherit the listener's nonblocking mode; reads wake up to check the stop flag instead
+    let setup = stream.set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(POLL_INTERVAL)))
+        .and_then(|_| stream.set_write_timeout(Some(service.options.read_timeout.max(POLL_INTERVAL))));
+
+    let ending = match setup {
+        Ok(()) => converse(&service.persona, &mut reader, &mut record.bytes_sent),
+        Err(e) => Ending::Failed(e)
+    };
+    let _ = stream.shutdown(Shutdown::Both);
+
+    match ending {
+        Ending::Closed => {},
+        Ending::LimitReached => record.truncated = true,
+        Ending::TimedOut => record.error = Some("read timeout".to_string()),
+        Ending::Stopped => record.error = Some("listener stopped".to_string()),
+        Ending::Failed(e) => record.error = Some(e.to_string())
+    }
+
+    service.bytes_received.fetch_add(reader.received.len() as u64, Ordering::SeqCst);
+    record.bytes_received = reader.received.len();
+    if !reader.received.is_empty() {
+        record.received_hex = hexdump_bytes(&reader.received, 0).trim_end().to_string();
+    }
+    record.duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    record
+}
+
+// Plays the persona until the connection ends
+fn converse(persona: &Persona, reader: &mut Reader<'_>, sent: &mut usize) -> Ending {
+    let stream = reader.stream;
+    let mut send = |bytes: &[u8]| -> Result<(), Ending> {
+        (&*stream).write_all(bytes).map_err(Ending::Failed)?;
+        *sent += bytes.len();
+        Ok(())
+    };
+
+    let result = match persona {
+        Persona::Ssh { banner } => send(format!("{}\r\n", banner).as_bytes()).and_then(|_| drain(reader)),
+        Persona::Http { status, headers, body } => match read_request(reader) {
+            Ok(()) => send(&http_response(*status, headers, body)),
+            // A request cut off by the read limit is still answered
+            Err(Ending::LimitReached) => send(&http_response(*status, headers, body)).and(Err(Ending::LimitReached)),
+            Err(ending) => Err(ending)
+        },
+        Persona::Echo => loop {
+            let read = match reader.read_some() {
+                Ok(read) => read,
+                Err(ending) => break Err(ending)
+            };
+            let start = reader.received.len() - read;
+            if let Err(ending) = send(&reader.received[start..]) {
+                break Err(ending);
+            }
+        }
+    };
+
+    match result {
+        Ok(()) => Ending::Closed,
+        Err(ending) => ending
+    }
+}
+
+// Reads everything the client sends until the connection ends
+fn drain(reader: &mut Reader<'_>) -> Result<(), Ending> {
+    loop {
+        reader.read_some()?;
+    }
+}
+
+// Reads an HTTP request head and as much of its body as Content-Length announces
+fn read_request(reader: &mut Reader<'_>) -> Result<(), Ending> {
+    loop {
+        if let Some(end) = find(&reader.received, b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&reader.received[..end]).to_ascii_lowercase();
+            let length = head.lines()
+                .filter_map(|line| line.strip_prefix("content-length:"))
+                .find_map(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+
+            while reader.received.len() < end + 4 + length {
+                reader.read_some()?;
+            }
+            return Ok(());
+        }
+        reader.read_some()?;
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn http_response(status: u16, headers: &[(String, String)], body: &str) -> Vec<u8> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown"
+    };
+
+    let mut response = format!("HTTP/1.1 {} {}\r\n", status, reason);
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body));
+    response.into_bytes()
+}
+
+// Parses "port persona [seconds=30] [bind=127.0.0.1] [max_read=65536] [max_connections=64] [timeout=10] [out=file] [backpressure=block] [retries=5]"; serves for the given time, then returns the connection log
+pub(crate) fn listen_from_args(input: &str) -> Result<String, String> {
+    let (args, pairs) = trailing_options(input, &["seconds", "bind", "max_read", "max_connections", "timeout", "out", "backpressure", "retries"]);
+    let mut options = ListenOptions::default();
+    let mut seconds = 30;
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        let number = || value.parse::<u64>().map_err(|_| format!("Invalid {} '{}'", key, value));
+        match key {
+            "seconds" => seconds = number()?,
+            "bind" => options.bind = value.parse().map_err(|_| format!("Invalid bind address '{}'", value))?,
+            "max_read" => options.max_read = number()? as usize,
+            "max_connections" => options.max_connections = number()? as usize,
+            _ => options.read_timeout = Duration::from_secs(number()?)
+        }
+    }
+
+    let mut words = args.split_whitespace();
+    let port = match words.next() {
+        Some(port) => port.parse::<u16>().map_err(|_| format!("Invalid port '{}'", port))?,
+        None => return Err("No port specified for listen (\"8022 ssh\")".to_string())
+    };
+    let persona = Persona::parse(words.next().unwrap_or("echo"))?;
+
+    if seconds == 0 || seconds > 3600 {
+        return Err(format!("Invalid seconds {} (expected 1 to 3600)", seconds));
+    }
+
+    if let Some(output) = sink.open()? {
+        let (summary, output) = serve_for(port, persona, &options, seconds, output)?;
+        return finish_sink(output, &summary);
+    }
+
+    let (summary, mut output) = serve_for(port, persona, &options, seconds, Vec::new())?;
+    let line = serde_json::json!({ "summary": summary });
+    output.extend_from_slice(line.to_string().as_bytes());
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+// Serves for `seconds`, logging NDJSON to `output` as connections close
+fn serve_for<W: Write + Send + 'static>(port: u16, persona: Persona, options: &ListenOptions, seconds: u64, output: W) -> Result<(ListenSummary, W), String> {
+    let output = Arc::new(Mutex::new(output));
+    let handle = mercy_listen(port, persona, options, ndjson_logger(Arc::clone(&output)))?;
+    thread::sleep(Duration::from_secs(seconds));
+    let summary = handle.stop();
+
+    // Every thread holding the logger has been joined by now
+    let output = Arc::try_unwrap(output).map_err(|_| "The connection log is still in use".to_string())?;
+    Ok((summary, output.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())))
+}
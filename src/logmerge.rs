@@ -0,0 +1,393 @@
+/*
+    Merging log files into one timeline
+
+    Each input is read twice at most: once to count its lines and check that its timestamps
+    never go backwards, then again while merging. Inputs in order are merged straight from
+    disk; an input out of order is first cut into chunks that are sorted in memory and
+    spilled to temporary files. A k-way merge over a heap holding one line per input or
+    chunk then writes the combined timeline, so memory stays bounded by the chunk size
+    whatever the size of the inputs.
+
+    Timestamps are found with the same detection as `normalize_timestamps`. A line without
+    one (a stack trace, a wrapped message) takes the time of the line before it, so it stays
+    with its entry; lines ahead of a file's first timestamp sort to the start with "-".
+    Given an errors file, lines without a timestamp are written there instead. Lines with
+    equal times keep their input order, earlier inputs first.
+*/
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering}
+};
+
+use serde::Serialize;
+
+use crate::{
+    logtime::{LogTimeOptions, detect_timestamp, parse_zone_offset},
+    paths::path_to_string,
+    sink::{SinkArgs, finish_sink},
+    text::TextLines,
+    timeline::Timestamp,
+    trailing_options
+};
+
+// Distinguishes spill files of concurrent merges in one process
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Options for `mercy_logs_merge`
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// How timestamps are found and read in every input
+    pub time: LogTimeOptions,
+    /// Lines without a timestamp go to this file ("source:line<TAB>text") instead of inheriting the previous time
+    pub errors: Option<PathBuf>,
+    /// Lines sorted in memory at a time for an input that is out of order
+    pub chunk_lines: usize,
+    /// Directory for sorted chunks (default: the system temporary directory)
+    pub temp_dir: Option<PathBuf>
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            time: LogTimeOptions::default(),
+            errors: None,
+            chunk_lines: 200_000,
+            temp_dir: None
+        }
+    }
+}
+
+/// Counts for one merged input
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeSource {
+    pub path: String,
+    pub lines: u64,
+    pub timestamped: u64,
+    /// Lines that took the time of the line before them
+    pub inherited: u64,
+    /// Lines written to the errors file, or placed at the start for lack of an earlier time
+    pub untimed: u64,
+    /// Whether the timestamps never went backwards; if not, the input was chunk-sorted
+    pub sorted: bool,
+    /// Sorted chunks spilled for an out-of-order input
+    pub chunks: u64
+}
+
+/// Counts from a merge
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MergeSummary {
+    pub sources: Vec<MergeSource>,
+    pub written: u64,
+    /// Lines written to the errors file
+    pub errors: u64
+}
+
+impl fmt::Display for MergeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Merged {} lines from {} files ({} to the errors file)", self.written, self.sources.len(), self.errors)?;
+
+        for source in &self.sources {
+            write!(f, "  {}: {} lines, {} timestamped, {} inherited, {} untimed", source.path, source.lines, source.timestamped, source.inherited, source.untimed)?;
+            if !source.sorted {
+                write!(f, ", out of order ({} sorted chunks)", source.chunks)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Merges log files into one chronological stream of "UTC timestamp<TAB>source path<TAB>original line"
+pub fn mercy_logs_merge<P: AsRef<Path>, W: Write>(paths: &[P], options: &MergeOptions, writer: &mut W) -> Result<MergeSummary, String> {
+    if paths.is_empty() {
+        return Err("No log files to merge".to_string());
+    }
+
+    let mut errors = match &options.errors {
+        Some(path) => Some(BufWriter::new(File::create(path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?)),
+        None => None
+    };
+
+    let mut summary = MergeSummary::default();
+    let mut spills = Spills { files: Vec::new() };
+    let mut runs: Vec<Run> = Vec::new();
+
+    for (source, path) in paths.iter().enumerate() {
+        let path = path.as_ref();
+        let mut stats = check_source(path, options)?;
+
+        if stats.sorted {
+            runs.push(Run::Log(LogLines::open(path, source, options)?));
+        } else {
+            for chunk in spill_chunks(path, source, options, &mut spills, &mut errors, &mut summary)? {
+                runs.push(Run::Spill(BufReader::new(File::open(&chunk).map_err(|e| format!("Unable to reopen sorted chunk: {}", e))?)));
+                stats.chunks += 1;
+            }
+        }
+
+        summary.sources.push(stats);
+    }
+
+    let labels: Vec<String> = paths.iter().map(path_to_string).collect();
+    let mut heap = BinaryHeap::new();
+
+    for (index, run) in runs.iter_mut().enumerate() {
+        if let Some(record) = next_record(run, &labels, &mut errors, &mut summary)? {
+            heap.push(Reverse((record, index)));
+        }
+    }
+
+    while let Some(Reverse((record, index))) = heap.pop() {
+        let time = record.time.map(|time| time.to_string()).unwrap_or_else(|| "-".to_string());
+        writeln!(writer, "{}\t{}\t{}", time, labels[record.source], record.line).map_err(|e| format!("Unable to write merged log: {}", e))?;
+        summary.written += 1;
+
+        if let Some(record) = next_record(&mut runs[index], &labels, &mut errors, &mut summary)? {
+            heap.push(Reverse((record, index)));
+        }
+    }
+
+    writer.flush().map_err(|e| format!("Unable to write merged log: {}", e))?;
+    if let Some(errors) = errors.as_mut() {
+        errors.flush().map_err(|e| format!("Unable to write errors file: {}", e))?;
+    }
+
+    Ok(summary)
+}
+
+/* Inputs */
+
+// One line with its place in the merge; ordered by time, then input, then position in the input
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Record {
+    time: Option<Timestamp>,
+    source: usize,
+    sequence: u64,
+    line: String
+}
+
+enum Timing {
+    Found(Timestamp),
+    Inherited(Timestamp),
+    // No timestamp on the line, with the line number for the errors file
+    Missing(u64)
+}
+
+// Lines of one input, decoded from UTF-16 if need be, with their timestamps, inheriting or reporting missing ones
+struct LogLines {
+    lines: TextLines<File>,
+    source: usize,
+    time: LogTimeOptions,
+    inherit: bool,
+    previous: Option<Timestamp>,
+    sequence: u64
+}
+
+impl LogLines {
+    fn open(path: &Path, source: usize, options: &MergeOptions) -> Result<LogLines, String> {
+        let lines = TextLines::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+
+        Ok(LogLines {
+            lines,
+            source,
+            time: options.time.clone(),
+            inherit: options.errors.is_none(),
+            previous: None,
+            sequence: 0
+        })
+    }
+
+    fn next_line(&mut self) -> Result<Option<(Timing, Record)>, String> {
+        let line = match self.lines.next() {
+            Some(line) => line.map_err(|e| format!("Unable to read log: {}", e))?,
+            None => return Ok(None)
+        };
+        self.sequence += 1;
+
+        let timing = match (detect_timestamp(&line, &self.time), self.previous) {
+            (Some(detected), _) => {
+                self.previous = Some(detected.timestamp);
+                Timing::Found(detected.timestamp)
+            },
+            (None, Some(previous)) if self.inherit => Timing::Inherited(previous),
+            (None, _) => Timing::Missing(self.sequence)
+        };
+
+        let time = match timing {
+            Timing::Found(time) | Timing::Inherited(time) => Some(time),
+            Timing::Missing(_) => None
+        };
+
+        Ok(Some((timing, Record { time, source: self.source, sequence: self.sequence, line })))
+    }
+}
+
+// Counts an input's lines and checks that its timestamps never go backwards
+fn check_source(path: &Path, options: &MergeOptions) -> Result<MergeSource, String> {
+    let mut lines = LogLines::open(path, 0, options)?;
+    let mut stats = MergeSource { path: path_to_string(path), sorted: true, ..MergeSource::default() };
+    let mut latest = None;
+
+    while let Some((timing, _)) = lines.next_line()? {
+        stats.lines += 1;
+        match timing {
+            Timing::Found(time) => {
+                stats.timestamped += 1;
+                if latest.is_some_and(|latest| time < latest) {
+                    stats.sorted = false;
+                }
+                latest = Some(time);
+            },
+            Timing::Inherited(_) => stats.inherited += 1,
+            Timing::Missing(_) => stats.untimed += 1
+        }
+    }
+
+    Ok(stats)
+}
+
+/* Sorted chunks */
+
+// Spill files, removed when the merge finishes or fails
+struct Spills {
+    files: Vec<PathBuf>
+}
+
+impl Drop for Spills {
+    fn drop(&mut self) {
+        for file in &self.files {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+// Sorts an out-of-order input in chunks, each written as "secs<TAB>nanos<TAB>source<TAB>sequence<TAB>line"
+fn spill_chunks(path: &Path, source: usize, options: &MergeOptions, spills: &mut Spills, errors: &mut Option<BufWriter<File>>, summary: &mut MergeSummary) -> Result<Vec<PathBuf>, String> {
+    let directory = options.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+    let label = path_to_string(path);
+    let mut lines = LogLines::open(path, source, options)?;
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut finished = false;
+
+    while !finished {
+        match lines.next_line()? {
+            Some((Timing::Missing(number), record)) if errors.is_some() => write_error(errors, &label, number, &record.line, summary)?,
+            Some((_, record)) => chunk.push(record),
+            None => finished = true
+        }
+
+        if chunk.len() >= options.chunk_lines.max(1) || (finished && !chunk.is_empty()) {
+            chunk.sort();
+
+            let spill = directory.join(format!("mercy-merge-{}-{}.tmp", process::id(), SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)));
+            spills.files.push(spill.clone());
+            let file = File::create(&spill).map_err(|e| format!("Unable to create sorted chunk {}: {}", spill.display(), e))?;
+            let mut writer = BufWriter::new(file);
+
+            for record in chunk.drain(..) {
+                let (secs, nanos) = record.time.map(|time| (time.secs.to_string(), time.nanos)).unwrap_or_else(|| ("-".to_string(), 0));
+                writeln!(writer, "{}\t{}\t{}\t{}\t{}", secs, nanos, record.source, record.sequence, record.line).map_err(|e| format!("Unable to write sorted chunk: {}", e))?;
+            }
+
+            writer.flush().map_err(|e| format!("Unable to write sorted chunk: {}", e))?;
+            chunks.push(spill);
+        }
+    }
+
+    Ok(chunks)
+}
+
+/* Merging */
+
+enum Run {
+    Log(LogLines),
+    // A sorted chunk of an out-of-order input
+    Spill(BufReader<File>)
+}
+
+// The next line of a run, writing lines without a timestamp to the errors file when there is one
+fn next_record(run: &mut Run, labels: &[String], errors: &mut Option<BufWriter<File>>, summary: &mut MergeSummary) -> Result<Option<Record>, String> {
+    match run {
+        Run::Log(lines) => loop {
+            match lines.next_line()? {
+                Some((Timing::Missing(number), record)) if errors.is_some() => write_error(errors, &labels[lines.source], number, &record.line, summary)?,
+                Some((_, record)) => return Ok(Some(record)),
+                None => return Ok(None)
+            }
+        },
+        Run::Spill(reader) => {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(|e| format!("Unable to read sorted chunk: {}", e))? == 0 {
+                return Ok(None);
+            }
+
+            let mut fields = line.trim_end_matches('\n').splitn(5, '\t');
+            let invalid = || "Corrupt sorted chunk".to_string();
+            let secs = fields.next().ok_or_else(invalid)?;
+            let nanos: u32 = fields.next().and_then(|nanos| nanos.parse().ok()).ok_or_else(invalid)?;
+            let source: usize = fields.next().and_then(|source| source.parse().ok()).ok_or_else(invalid)?;
+            let sequence: u64 = fields.next().and_then(|sequence| sequence.parse().ok()).ok_or_else(invalid)?;
+            let time = match secs {
+                "-" => None,
+                _ => Some(Timestamp { secs: secs.parse().map_err(|_| invalid())?, nanos })
+            };
+
+            Ok(Some(Record { time, source, sequence, line: fields.next().unwrap_or("").to_string() }))
+        }
+    }
+}
+
+fn write_error(errors: &mut Option<BufWriter<File>>, label: &str, number: u64, line: &str, summary: &mut MergeSummary) -> Result<(), String> {
+    if let Some(errors) = errors.as_mut() {
+        writeln!(errors, "{}:{}\t{}", label, number, line).map_err(|e| format!("Unable to write errors file: {}", e))?;
+        summary.errors += 1;
+    }
+    Ok(())
+}
+
+// Parses "a.log,b.log [errors=untimed.log] [out=merged.log] [backpressure=block] [retries=5] [zone=+02:00] [year=2024] [chunk_lines=200000]"
+pub(crate) fn logs_merge_from_args(input: &str) -> Result<String, String> {
+    let (paths, pairs) = trailing_options(input, &["errors", "out", "backpressure", "retries", "zone", "year", "chunk_lines"]);
+    let mut options = MergeOptions::default();
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        match key {
+            "errors" => options.errors = Some(PathBuf::from(value)),
+            "zone" => options.time.zone_offset = parse_zone_offset(value)?,
+            "year" => options.time.year = Some(value.parse().map_err(|_| format!("Invalid year '{}'", value))?),
+            _ => options.chunk_lines = value.parse().map_err(|_| format!("Invalid chunk_lines '{}'", value))?
+        }
+    }
+
+    let paths: Vec<&str> = paths.split(',').map(str::trim).filter(|path| !path.is_empty()).collect();
+
+    match sink.open()? {
+        Some(mut output) => {
+            let summary = mercy_logs_merge(&paths, &options, &mut output)?;
+            finish_sink(output, &summary)
+        },
+        None => {
+            let mut output = Vec::new();
+            let summary = mercy_logs_merge(&paths, &options, &mut output)?;
+
+            // Close the stream with the counts so out-of-order inputs and untimed lines are visible
+            let line = serde_json::json!({ "summary": summary });
+            output.extend_from_slice(line.to_string().as_bytes());
+            Ok(String::from_utf8_lossy(&output).to_string())
+        }
+    }
+}
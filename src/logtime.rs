@@ -0,0 +1,563 @@
+/*
+    Log timestamp detection and normalization
+
+    Recognises the timestamp formats found in the logs gathered during an incident and
+    converts them to UTC:
+
+    - iso8601: "2024-01-05T14:03:22.123+02:00" (also with a space, a comma before the
+      fraction or "/" between date parts); iso8601_local when there is no zone
+    - syslog: "Jan  5 14:03:22" (RFC 3164, no year or zone) and syslog_rfc5424:
+      "<34>1 2024-01-05T14:03:22Z"
+    - ctime: "Fri Jan  5 14:03:22 2024", as in Apache error logs
+    - rfc2822: "Fri, 05 Jan 2024 14:03:22 +0000"
+    - clf: "[05/Jan/2024:14:03:22 -0700]", Apache/NGINX access logs
+    - windows: "1/5/2024 2:03:22 PM", Event Viewer exports (month first unless the first
+      number cannot be a month)
+    - epoch_seconds, epoch_millis, epoch_micros, epoch_nanos: 10, 13, 16 or 19 digits within
+      1990-2100, seconds optionally with a fraction
+
+    Timestamps without a zone are read in an assumed zone offset (UTC by default). Syslog
+    lines carry no year: the assumed year is the current one, stepping back a year for dates
+    that would otherwise be more than a day in the future, so December lines read in January
+    land in the right year. No timezone database is used, so zone names other than UTC, GMT
+    and Z are not understood.
+
+    A line's timestamp is looked for at its start, then after each of the first few "["
+    characters (which finds access log timestamps after the client address), unless a byte
+    position or a literal marker to look after is given.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use serde::Serialize;
+
+use crate::{
+    paths::path_to_string,
+    sink::{SinkArgs, finish_sink},
+    text::TextLines,
+    timefmt::{civil_to_unix, unix_year},
+    timeline::Timestamp,
+    trailing_options
+};
+
+// Number of unparsed lines kept in the summary; the rest are only counted
+const UNPARSED_SAMPLES: usize = 20;
+
+// How far into a line "[" characters are tried as the start of a timestamp
+const BRACKET_SEARCH: usize = 256;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// How timestamps are found and read
+#[derive(Debug, Clone, Default)]
+pub struct LogTimeOptions {
+    /// Offset from UTC in seconds assumed for timestamps without a zone (east positive)
+    pub zone_offset: i32,
+    /// Year assumed for syslog timestamps; None uses the current year (see the module notes)
+    pub year: Option<i64>,
+    /// Byte offset in each line where the timestamp starts
+    pub position: Option<usize>,
+    /// The timestamp follows the first occurrence of this text in each line
+    pub after: Option<String>
+}
+
+/// A timestamp found in a line
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectedTimestamp {
+    pub timestamp: Timestamp,
+    /// Format name, e.g. "syslog" or "clf" (see the module notes)
+    pub format: &'static str,
+    /// Byte range of the timestamp in the line
+    pub start: usize,
+    pub end: usize
+}
+
+/// Counts from a normalization run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NormalizeSummary {
+    pub path: String,
+    pub lines: u64,
+    pub normalized: u64,
+    pub unparsed: u64,
+    /// Lines per detected format
+    pub formats: BTreeMap<String, u64>,
+    /// The first few lines without a recognisable timestamp, as "line N: text"
+    pub unparsed_samples: Vec<String>
+}
+
+impl fmt::Display for NormalizeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Normalized {} of {} lines in {} ({} unparsed)", self.normalized, self.lines, self.path, self.unparsed)?;
+
+        for (format, count) in &self.formats {
+            writeln!(f, "  {:<16} {}", format, count)?;
+        }
+
+        for sample in &self.unparsed_samples {
+            writeln!(f, "Unparsed: {}", sample)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a timestamp in any supported format (zoneless ones as UTC, syslog in the current year)
+pub fn parse_any_timestamp(text: &str) -> Result<Timestamp, String> {
+    let trimmed = text.trim();
+
+    match detect_at(trimmed.as_bytes(), 0, &LogTimeOptions::default()) {
+        Some(detected) if detected.end == trimmed.len() => Ok(detected.timestamp),
+        _ => Err(format!("Unrecognised timestamp '{}'", trimmed))
+    }
+}
+
+/// Finds the timestamp of a log line: at the position or marker in `options`, else at the start or after a "["
+pub fn detect_timestamp(line: &str, options: &LogTimeOptions) -> Option<DetectedTimestamp> {
+    let bytes = line.as_bytes();
+
+    if let Some(position) = options.position {
+        return detect_at(bytes, position, options);
+    }
+
+    if let Some(marker) = options.after.as_deref() {
+        let start = line.find(marker)? + marker.len();
+        let start = start + bytes[start..].iter().take_while(|b| **b == b' ').count();
+        return detect_at(bytes, start, options);
+    }
+
+    let start = bytes.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    if let Some(detected) = detect_at(bytes, start, options) {
+        return Some(detected);
+    }
+
+    bytes.iter()
+        .take(BRACKET_SEARCH)
+        .enumerate()
+        .filter(|(index, b)| **b == b'[' && *index > start)
+        .find_map(|(index, _)| detect_at(bytes, index, options))
+}
+
+/// Rewrites each line as "UTC ISO-8601 timestamp<TAB>original line" ("-" when none is found), streaming;
+/// a UTF-16 log (BOM or not) is transcoded and a UTF-8 BOM dropped
+pub fn normalize_timestamps<R: Read, W: Write>(reader: R, options: &LogTimeOptions, writer: &mut W) -> Result<NormalizeSummary, String> {
+    let mut summary = NormalizeSummary::default();
+
+    for line in TextLines::new(reader) {
+        let line = line.map_err(|e| format!("Unable to read log: {}", e))?;
+        let line = line.as_str();
+        summary.lines += 1;
+
+        let normalized = match detect_timestamp(line, options) {
+            Some(detected) => {
+                summary.normalized += 1;
+                *summary.formats.entry(detected.format.to_string()).or_insert(0) += 1;
+                detected.timestamp.to_string()
+            },
+            None => {
+                summary.unparsed += 1;
+                if summary.unparsed_samples.len() < UNPARSED_SAMPLES {
+                    summary.unparsed_samples.push(format!("line {}: {}", summary.lines, line.chars().take(200).collect::<String>()));
+                }
+                "-".to_string()
+            }
+        };
+
+        writeln!(writer, "{}\t{}", normalized, line).map_err(|e| format!("Unable to write normalized log: {}", e))?;
+    }
+
+    writer.flush().map_err(|e| format!("Unable to write normalized log: {}", e))?;
+    Ok(summary)
+}
+
+/// Normalizes the log at `path` into `writer` (see `normalize_timestamps`)
+pub fn normalize_timestamps_file<P: AsRef<Path>, W: Write>(path: P, options: &LogTimeOptions, writer: &mut W) -> Result<NormalizeSummary, String> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut summary = normalize_timestamps(file, options, writer)?;
+    summary.path = path_to_string(path);
+    Ok(summary)
+}
+
+/// Parses a zone offset: "UTC", "Z", "+02:00", "-0700" or "+5"; returns seconds east of UTC
+pub(crate) fn parse_zone_offset(text: &str) -> Result<i32, String> {
+    let mut cursor = Cursor { bytes: text.trim().as_bytes(), at: 0 };
+
+    match cursor.zone() {
+        Some(offset) if cursor.at == cursor.bytes.len() => Ok(offset),
+        _ => Err(format!("Invalid zone '{}' (expected UTC or an offset such as +02:00)", text))
+    }
+}
+
+/* Formats */
+
+fn detect_at(bytes: &[u8], start: usize, options: &LogTimeOptions) -> Option<DetectedTimestamp> {
+    if start >= bytes.len() {
+        return None;
+    }
+
+    let mut cursor = Cursor { bytes, at: start };
+    let bracketed = cursor.eat(b'[');
+    let begin = cursor.at;
+
+    // RFC 5424 syslog: "<PRI>VERSION " ahead of an ISO timestamp
+    let rfc5424 = {
+        let mut prefix = cursor.clone();
+        (prefix.eat(b'<') && prefix.number(1, 3).is_some() && prefix.eat(b'>') && prefix.number(1, 2).is_some() && prefix.eat(b' ')).then_some(prefix)
+    };
+
+    let (secs, nanos, format, end) = match rfc5424 {
+        Some(mut prefix) => {
+            let (secs, nanos, _) = iso(&mut prefix, options)?;
+            (secs, nanos, "syslog_rfc5424", prefix.at)
+        },
+        None => [epoch, iso, clf, windows, rfc2822, ctime, syslog].iter().find_map(|parser| {
+            let mut attempt = cursor.clone();
+            let (secs, nanos, format) = parser(&mut attempt, options)?;
+            attempt.boundary().then_some((secs, nanos, format, attempt.at))
+        })?
+    };
+
+    let mut close = Cursor { bytes, at: end };
+    let end = if bracketed && close.eat(b']') { close.at } else { end };
+
+    Some(DetectedTimestamp {
+        timestamp: Timestamp { secs, nanos },
+        format,
+        start: if bracketed { begin - 1 } else { begin },
+        end
+    })
+}
+
+type Parsed = Option<(i64, u32, &'static str)>;
+
+// 10, 13, 16 or 19 digits (seconds to nanoseconds) within 1990-2100; seconds may carry a fraction
+fn epoch(cursor: &mut Cursor, _options: &LogTimeOptions) -> Parsed {
+    let digits = cursor.bytes[cursor.at..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let value: i64 = std::str::from_utf8(&cursor.bytes[cursor.at..cursor.at + digits]).ok()?.parse().ok()?;
+
+    let (secs, nanos, format) = match digits {
+        10 => (value, 0, "epoch_seconds"),
+        13 => (value / 1_000, (value % 1_000) as u32 * 1_000_000, "epoch_millis"),
+        16 => (value / 1_000_000, (value % 1_000_000) as u32 * 1_000, "epoch_micros"),
+        19 => (value / 1_000_000_000, (value % 1_000_000_000) as u32, "epoch_nanos"),
+        _ => return None
+    };
+
+    if !(631_152_000..4_102_444_800).contains(&secs) {
+        return None;
+    }
+
+    cursor.at += digits;
+    let nanos = if digits == 10 && cursor.peek() == Some(b'.') { cursor.fraction() } else { nanos };
+    Some((secs, nanos, format))
+}
+
+// 2024-01-05T14:03:22.123+02:00, 2024/01/05 14:03:22,123, or a bare date at the end of the text
+fn iso(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    let year = cursor.number(4, 4)?;
+    let separator = cursor.peek().filter(|b| *b == b'-' || *b == b'/')?;
+    cursor.at += 1;
+    let month = cursor.number(1, 2)? as u32;
+    cursor.eat(separator).then_some(())?;
+    let day = cursor.number(1, 2)? as u32;
+
+    if cursor.at == cursor.bytes.len() {
+        return Some((civil_to_unix(year, month, day, 0)? - options.zone_offset as i64, 0, "iso8601_local"));
+    }
+
+    if !(cursor.eat(b'T') || cursor.eat(b't') || cursor.eat(b' ')) {
+        return None;
+    }
+
+    let (second_of_day, nanos) = cursor.time()?;
+    let zone = cursor.trailing_zone();
+    let secs = civil_to_unix(year, month, day, second_of_day)? - zone.unwrap_or(options.zone_offset) as i64;
+    Some((secs, nanos, if zone.is_some() { "iso8601" } else { "iso8601_local" }))
+}
+
+// 05/Jan/2024:14:03:22 -0700
+fn clf(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    let day = cursor.number(1, 2)? as u32;
+    cursor.eat(b'/').then_some(())?;
+    let month = cursor.month()?;
+    cursor.eat(b'/').then_some(())?;
+    let year = cursor.number(4, 4)?;
+    cursor.eat(b':').then_some(())?;
+    let (second_of_day, nanos) = cursor.time()?;
+    let zone = cursor.trailing_zone().unwrap_or(options.zone_offset);
+
+    Some((civil_to_unix(year, month, day, second_of_day)? - zone as i64, nanos, "clf"))
+}
+
+// 1/5/2024 2:03:22 PM (month first unless the first number cannot be a month)
+fn windows(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    let first = cursor.number(1, 2)? as u32;
+    cursor.eat(b'/').then_some(())?;
+    let second = cursor.number(1, 2)? as u32;
+    cursor.eat(b'/').then_some(())?;
+    let year = cursor.number(4, 4)?;
+    cursor.eat(b' ').then_some(())?;
+    let (mut second_of_day, nanos) = cursor.time()?;
+
+    let mut meridiem = cursor.clone();
+    meridiem.spaces();
+    let hour = second_of_day / 3600;
+    if meridiem.word("am") && hour <= 12 {
+        second_of_day -= if hour == 12 { 12 * 3600 } else { 0 };
+        *cursor = meridiem;
+    } else if meridiem.word("pm") && hour <= 12 {
+        second_of_day += if hour == 12 { 0 } else { 12 * 3600 };
+        *cursor = meridiem;
+    }
+
+    let (month, day) = if first > 12 { (second, first) } else { (first, second) };
+    Some((civil_to_unix(year, month, day, second_of_day)? - options.zone_offset as i64, nanos, "windows"))
+}
+
+// [Fri, ]05 Jan 2024 14:03:22 +0000
+fn rfc2822(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    let mut weekday = cursor.clone();
+    if weekday.weekday() && weekday.eat(b',') {
+        weekday.spaces();
+        *cursor = weekday;
+    }
+
+    let day = cursor.number(1, 2)? as u32;
+    (cursor.spaces() > 0).then_some(())?;
+    let month = cursor.month()?;
+    (cursor.spaces() > 0).then_some(())?;
+    let year = cursor.number(4, 4)?;
+    (cursor.spaces() > 0).then_some(())?;
+    let (second_of_day, nanos) = cursor.time()?;
+    let zone = cursor.trailing_zone().unwrap_or(options.zone_offset);
+
+    Some((civil_to_unix(year, month, day, second_of_day)? - zone as i64, nanos, "rfc2822"))
+}
+
+// Fri Jan  5 14:03:22[.123456] 2024
+fn ctime(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    cursor.weekday().then_some(())?;
+    (cursor.spaces() > 0).then_some(())?;
+    let month = cursor.month()?;
+    (cursor.spaces() > 0).then_some(())?;
+    let day = cursor.number(1, 2)? as u32;
+    (cursor.spaces() > 0).then_some(())?;
+    let (second_of_day, nanos) = cursor.time()?;
+    (cursor.spaces() > 0).then_some(())?;
+    let year = cursor.number(4, 4)?;
+
+    Some((civil_to_unix(year, month, day, second_of_day)? - options.zone_offset as i64, nanos, "ctime"))
+}
+
+// Jan  5 14:03:22 (RFC 3164), optionally with a year before the time
+fn syslog(cursor: &mut Cursor, options: &LogTimeOptions) -> Parsed {
+    let month = cursor.month()?;
+    (cursor.spaces() > 0).then_some(())?;
+    let day = cursor.number(1, 2)? as u32;
+    (cursor.spaces() > 0).then_some(())?;
+
+    let mut with_year = cursor.clone();
+    let year = match with_year.number(4, 4) {
+        Some(year) if with_year.spaces() > 0 => {
+            *cursor = with_year;
+            Some(year)
+        },
+        _ => None
+    };
+
+    let (second_of_day, nanos) = cursor.time()?;
+    let offset = options.zone_offset as i64;
+
+    let secs = match year.or(options.year) {
+        Some(year) => civil_to_unix(year, month, day, second_of_day)? - offset,
+        None => {
+            // No year anywhere: this year, unless that puts the line more than a day ahead
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+            let this_year = unix_year(now);
+            match civil_to_unix(this_year, month, day, second_of_day) {
+                Some(secs) if secs - offset <= now + 86_400 => secs - offset,
+                _ => civil_to_unix(this_year - 1, month, day, second_of_day)? - offset
+            }
+        }
+    };
+
+    Some((secs, nanos, "syslog"))
+}
+
+/* Cursor */
+
+#[derive(Clone)]
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    at: usize
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.at).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.peek() == Some(byte);
+        if matched {
+            self.at += 1;
+        }
+        matched
+    }
+
+    fn spaces(&mut self) -> usize {
+        let count = self.bytes[self.at..].iter().take_while(|b| **b == b' ').count();
+        self.at += count;
+        count
+    }
+
+    // Between `min` and `max` digits (stopping at `max`)
+    fn number(&mut self, min: usize, max: usize) -> Option<i64> {
+        let count = self.bytes[self.at..].iter().take(max).take_while(|b| b.is_ascii_digit()).count();
+        if count < min {
+            return None;
+        }
+
+        let value = self.bytes[self.at..self.at + count].iter().fold(0, |value, b| value * 10 + (b - b'0') as i64);
+        self.at += count;
+        Some(value)
+    }
+
+    // A case-insensitive word, not followed by another letter
+    fn word(&mut self, word: &str) -> bool {
+        let end = self.at + word.len();
+        let matched = self.bytes.get(self.at..end).is_some_and(|text| text.eq_ignore_ascii_case(word.as_bytes()))
+            && !self.bytes.get(end).is_some_and(u8::is_ascii_alphabetic);
+        if matched {
+            self.at = end;
+        }
+        matched
+    }
+
+    // Three-letter month name (1-12)
+    fn month(&mut self) -> Option<u32> {
+        let index = MONTHS.iter().position(|month| self.word(month))?;
+        Some(index as u32 + 1)
+    }
+
+    fn weekday(&mut self) -> bool {
+        WEEKDAYS.iter().any(|weekday| self.word(weekday))
+    }
+
+    // H:MM[:SS][.fraction] as seconds into the day and nanoseconds
+    fn time(&mut self) -> Option<(i64, u32)> {
+        let hour = self.number(1, 2)?;
+        self.eat(b':').then_some(())?;
+        let minute = self.number(2, 2)?;
+        let second = if self.eat(b':') { self.number(2, 2)? } else { 0 };
+
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let nanos = if matches!(self.peek(), Some(b'.' | b',')) { self.fraction() } else { 0 };
+        Some((hour * 3600 + minute * 60 + second, nanos))
+    }
+
+    // "." or "," and up to nine significant digits; a separator without digits is left alone
+    fn fraction(&mut self) -> u32 {
+        let digits = self.bytes[self.at + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return 0;
+        }
+
+        let nanos = self.bytes[self.at + 1..self.at + 1 + digits.min(9)].iter().fold(0, |value, b| value * 10 + (b - b'0') as u32);
+        self.at += 1 + digits;
+        nanos * 10u32.pow(9 - digits.min(9) as u32)
+    }
+
+    // Z, UTC, GMT, +HH:MM, +HHMM or +HH, in seconds east of UTC
+    fn zone(&mut self) -> Option<i32> {
+        if self.word("z") || self.word("utc") || self.word("gmt") {
+            return Some(0);
+        }
+
+        let sign = match self.peek()? {
+            b'+' => 1,
+            b'-' => -1,
+            _ => return None
+        };
+
+        let mut offset = self.clone();
+        offset.at += 1;
+        let hours = offset.number(1, 2)?;
+        let minutes = if offset.eat(b':') { offset.number(2, 2)? } else { offset.number(2, 2).unwrap_or(0) };
+        if hours > 14 || minutes > 59 {
+            return None;
+        }
+
+        *self = offset;
+        Some(sign * (hours * 3600 + minutes * 60) as i32)
+    }
+
+    // A zone right after a time, or after one space
+    fn trailing_zone(&mut self) -> Option<i32> {
+        let mut spaced = self.clone();
+        if spaced.spaces() > 1 {
+            return None;
+        }
+
+        let zone = spaced.zone()?;
+        *self = spaced;
+        Some(zone)
+    }
+
+    // The timestamp must not run into a following digit or letter
+    fn boundary(&self) -> bool {
+        !self.peek().is_some_and(|b| b.is_ascii_alphanumeric())
+    }
+}
+
+// Parses "path [zone=+02:00] [year=2024] [position=16] [after=marker] [out=file] [backpressure=block] [retries=5]"; options trail the path
+pub(crate) fn normalize_timestamps_from_args(input: &str) -> Result<String, String> {
+    let (path, pairs) = trailing_options(input, &["zone", "year", "position", "after", "out", "backpressure", "retries"]);
+    let mut options = LogTimeOptions::default();
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        match key {
+            "zone" => options.zone_offset = parse_zone_offset(value)?,
+            "year" => options.year = Some(value.parse().map_err(|_| format!("Invalid year '{}'", value))?),
+            "position" => options.position = Some(value.parse().map_err(|_| format!("Invalid position '{}'", value))?),
+            _ => options.after = Some(value.to_string())
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for normalize_timestamps".to_string());
+    }
+
+    match sink.open()? {
+        Some(mut output) => {
+            let summary = normalize_timestamps_file(Path::new(&path), &options, &mut output)?;
+            finish_sink(output, &summary)
+        },
+        None => {
+            let mut output = Vec::new();
+            let summary = normalize_timestamps_file(Path::new(&path), &options, &mut output)?;
+
+            // Close the stream with the counts so unparsed lines are not silently lost
+            let line = serde_json::json!({ "summary": summary });
+            output.extend_from_slice(line.to_string().as_bytes());
+            Ok(String::from_utf8_lossy(&output).to_string())
+        }
+    }
+}
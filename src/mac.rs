@@ -0,0 +1,185 @@
+/*
+    MAC address vendor (OUI) lookup
+
+    An address is accepted as "AA:BB:CC:DD:EE:FF" (single-digit groups as macOS prints them are
+    padded), "aa-bb-cc-dd-ee-ff", Cisco's "aabb.ccdd.eeff" or twelve bare hex digits, and shown
+    normalized to the colon form. Its first three bytes are looked up in an embedded table of
+    common vendors, or in the full IEEE registry CSV (oui.csv, mam.csv or oas.csv from
+    standards-oui.ieee.org) when one is given; the CSV also holds 28- and 36-bit assignments,
+    so the longest matching one wins.
+
+    The two low bits of the first byte say whether the address is multicast and whether it is
+    locally administered. Randomized addresses of phones and laptops, and most virtual NICs,
+    are locally administered, so their first bytes name no vendor.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::text::TextLines;
+
+const OUI_TABLE: &str = include_str!("oui.txt");
+
+const UNKNOWN_OUI: &str = "Unknown OUI";
+
+/// Vendor and address type of a MAC address
+#[derive(Debug, Clone, Serialize)]
+pub struct MacLookup {
+    /// Normalized as "AA:BB:CC:DD:EE:FF"
+    pub mac: String,
+    /// The assignment that matched ("AA:BB:CC", or longer from a registry CSV), else the first three bytes
+    pub oui: String,
+    /// The vendor, or "Unknown OUI"
+    pub vendor: String,
+    pub known: bool,
+    /// "built-in table" or the CSV path
+    pub source: String,
+    pub multicast: bool,
+    pub broadcast: bool,
+    pub locally_administered: bool
+}
+
+impl fmt::Display for MacLookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "MAC: {}", self.mac)?;
+        writeln!(f, "Vendor: {} (OUI {}, {})", self.vendor, self.oui, self.source)?;
+
+        match (self.broadcast, self.multicast, self.locally_administered) {
+            (true, _, _) => write!(f, "Address: broadcast"),
+            (false, true, local) => write!(f, "Address: multicast (a group address), {} administered", if local { "locally" } else { "globally" }),
+            (false, false, true) => write!(f, "Address: unicast, locally administered (randomized or set by software; the OUI does not name the maker)"),
+            (false, false, false) => write!(f, "Address: unicast, globally unique (assigned by the vendor)")
+        }
+    }
+}
+
+/// Looks up the vendor of a MAC address in the built-in OUI table
+pub fn mercy_mac_lookup(mac: &str) -> Result<MacLookup, String> {
+    let bytes = parse_mac(mac)?;
+    let digits = hex_digits(&bytes);
+    let vendor = OUI_TABLE.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(' '))
+        .find(|(oui, _)| digits.starts_with(oui))
+        .map(|(oui, vendor)| (oui.to_string(), vendor.to_string()));
+
+    Ok(lookup(bytes, vendor, "built-in table".to_string()))
+}
+
+/// Looks up the vendor of a MAC address in an IEEE registry CSV ("Registry,Assignment,Organization Name,...")
+pub fn mercy_mac_lookup_file<P: AsRef<Path>>(mac: &str, csv: P) -> Result<MacLookup, String> {
+    let csv = csv.as_ref();
+    let bytes = parse_mac(mac)?;
+    let digits = hex_digits(&bytes);
+    let failed = |e: std::io::Error| format!("Unable to read {}: {}", csv.display(), e);
+
+    let mut assignments = 0usize;
+    let mut best: Option<(String, String)> = None;
+    for line in TextLines::open(csv).map_err(failed)? {
+        let line = line.map_err(failed)?;
+        let fields = csv_fields(&line);
+        let (assignment, vendor) = match (fields.get(1), fields.get(2)) {
+            (Some(assignment), Some(vendor)) if matches!(assignment.len(), 6 | 7 | 9) && assignment.chars().all(|c| c.is_ascii_hexdigit()) => (assignment.to_ascii_uppercase(), vendor.trim()),
+            _ => continue
+        };
+
+        assignments += 1;
+        if digits.starts_with(&assignment) && best.as_ref().is_none_or(|(oui, _)| assignment.len() > oui.len()) {
+            best = Some((assignment, vendor.to_string()));
+        }
+    }
+
+    if assignments == 0 {
+        return Err(format!("No OUI assignments in {}; expected the IEEE registry CSV (Registry,Assignment,Organization Name,Organization Address)", csv.display()));
+    }
+    Ok(lookup(bytes, best, csv.display().to_string()))
+}
+
+fn lookup(bytes: [u8; 6], vendor: Option<(String, String)>, source: String) -> MacLookup {
+    let known = vendor.is_some();
+    let (oui, vendor) = vendor.unwrap_or_else(|| (hex_digits(&bytes)[..6].to_string(), UNKNOWN_OUI.to_string()));
+
+    MacLookup {
+        mac: bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(":"),
+        // "AABBCC" as "AA:BB:CC", and the 28-bit "AABBCCD" as "AA:BB:CC:D"
+        oui: oui.as_bytes().chunks(2).map(|pair| String::from_utf8_lossy(pair)).collect::<Vec<_>>().join(":"),
+        vendor,
+        known,
+        source,
+        multicast: bytes[0] & 0x01 != 0,
+        broadcast: bytes == [0xFF; 6],
+        locally_administered: bytes[0] & 0x02 != 0
+    }
+}
+
+// Accepts "AA:BB:CC:DD:EE:FF", "aa-bb-cc-dd-ee-ff", "aabb.ccdd.eeff" and "aabbccddeeff"
+fn parse_mac(input: &str) -> Result<[u8; 6], String> {
+    let mac = input.trim();
+    let invalid = |reason: String| format!("Invalid MAC address '{}': {}", mac, reason);
+
+    let separators: Vec<char> = mac.chars().filter(|c| matches!(c, ':' | '-' | '.')).collect();
+    let digits: String = match separators.first() {
+        None => mac.to_string(),
+        Some(separator) if separators.iter().any(|other| other != separator) => return Err(invalid("mixed separators".to_string())),
+        Some('.') => {
+            let groups: Vec<&str> = mac.split('.').collect();
+            if groups.len() != 3 || groups.iter().any(|group| group.len() != 4) {
+                return Err(invalid("expected three groups of four hex digits (aabb.ccdd.eeff)".to_string()));
+            }
+            groups.concat()
+        },
+        Some(separator) => {
+            let groups: Vec<&str> = mac.split(*separator).collect();
+            if groups.len() != 6 || groups.iter().any(|group| group.is_empty() || group.len() > 2) {
+                return Err(invalid(format!("expected six groups of two hex digits (AA{}BB{}CC{}DD{}EE{}FF)", separator, separator, separator, separator, separator)));
+            }
+            groups.iter().map(|group| format!("{:0>2}", group)).collect()
+        }
+    };
+
+    if let Some(bad) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(invalid(format!("'{}' is not a hex digit", bad)));
+    }
+    if digits.len() != 12 {
+        return Err(invalid(format!("expected 12 hex digits, got {}", digits.len())));
+    }
+
+    let mut bytes = [0u8; 6];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).map_err(|e| invalid(e.to_string()))?;
+    }
+    Ok(bytes)
+}
+
+fn hex_digits(bytes: &[u8; 6]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+// Fields of one CSV line; quoted fields may hold commas and "" for a quote
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c)
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+// Parses "<mac>,<csv_path>"
+pub(crate) fn mac_lookup_file_from_args(input: &str) -> Result<MacLookup, String> {
+    let (mac, csv) = input.split_once(',').ok_or_else(|| format!("Expected \"mac,csv_path\", got '{}'", input))?;
+    mercy_mac_lookup_file(mac, csv.trim())
+}
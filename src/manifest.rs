@@ -0,0 +1,349 @@
+/*
+    Checksum manifest verification
+
+    Reads the checksum lists projects publish beside their downloads and checks every file
+    they name:
+
+    - GNU coreutils (sha256sum, sha512sum, sha1sum, md5sum): "digest  name", or "digest *name"
+      for binary mode.
+      A line starting with "\" has its name escaped ("\\", "\n", "\r").
+    - BSD and `shasum --tag`: "SHA256 (name) = digest", also SHA1, SHA512, SHA3-256 and
+      SHA3-512.
+
+    A PGP clear-signed manifest has its armour and signature block skipped and dash-escaped
+    lines restored; the signature itself is not checked, so verify it with gpg first when it
+    matters. Names are taken relative to the base directory. Lines that are neither format,
+    and digests of algorithms this crate does not compute (such as BLAKE2), are reported
+    rather than silently passed.
+*/
+
+use std::{
+    fmt,
+    fs,
+    path::{Path, PathBuf}
+};
+
+use serde::Serialize;
+
+use crate::{
+    hashing::{FILE_HASH_ALGORITHMS, mercy_hash_file},
+    paths::path_to_string,
+    text::read_text_file,
+    trailing_options
+};
+
+// Largest manifest read
+const MAX_MANIFEST: u64 = 16 * 1024 * 1024;
+
+/// Result for one manifest entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManifestStatus {
+    Ok,
+    Mismatch,
+    Missing,
+    /// The digest's algorithm is not one this crate computes
+    Unsupported,
+    /// The file exists but could not be read
+    Error
+}
+
+impl fmt::Display for ManifestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestStatus::Ok => write!(f, "OK"),
+            ManifestStatus::Mismatch => write!(f, "MISMATCH"),
+            ManifestStatus::Missing => write!(f, "MISSING"),
+            ManifestStatus::Unsupported => write!(f, "UNSUPPORTED"),
+            ManifestStatus::Error => write!(f, "ERROR")
+        }
+    }
+}
+
+/// One file listed in a manifest and what verifying it found
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    /// Manifest line number (1-based)
+    pub line: usize,
+    /// Name as listed
+    pub file: String,
+    /// One of `FILE_HASH_ALGORITHMS` ("sha2_256", "sha1", ...), or the manifest's own name for an unsupported algorithm
+    pub algorithm: String,
+    pub expected: String,
+    pub actual: Option<String>,
+    pub status: ManifestStatus,
+    /// Why the file could not be read (Error only)
+    pub detail: Option<String>
+}
+
+/// Verification of every entry of a checksum manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestReport {
+    pub manifest: String,
+    pub base_dir: String,
+    /// Every entry verified and at least one listed
+    pub verified: bool,
+    /// The manifest is PGP clear-signed (the signature was not checked)
+    pub signed: bool,
+    pub ok: usize,
+    pub mismatched: usize,
+    pub missing: usize,
+    pub unsupported: usize,
+    pub errors: usize,
+    pub entries: Vec<ManifestEntry>,
+    /// Line numbers that are neither GNU nor BSD checksum lines
+    pub unparsed_lines: Vec<usize>
+}
+
+impl fmt::Display for ManifestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            // A name with a newline must not read as two entries
+            let file = entry.file.escape_debug();
+            match &entry.detail {
+                Some(detail) => writeln!(f, "{} {} ({})", entry.status, file, detail)?,
+                None => writeln!(f, "{} {}", entry.status, file)?
+            }
+        }
+
+        if !self.unparsed_lines.is_empty() {
+            let lines: Vec<String> = self.unparsed_lines.iter().map(usize::to_string).collect();
+            writeln!(f, "Unparsed lines: {}", lines.join(", "))?;
+        }
+        if self.signed {
+            writeln!(f, "Manifest is PGP signed; the signature was not checked")?;
+        }
+
+        writeln!(f, "{}: {} ok, {} mismatched, {} missing, {} unsupported, {} errors",
+            if self.verified { "PASS" } else { "FAIL" }, self.ok, self.mismatched, self.missing, self.unsupported, self.errors)
+    }
+}
+
+/// Verifies each file listed in a sha256sum/md5sum or BSD-style manifest, with names relative to `base_dir`
+pub fn mercy_verify_manifest<P: AsRef<Path>, Q: AsRef<Path>>(manifest_path: P, base_dir: Q) -> Result<ManifestReport, String> {
+    let (manifest_path, base_dir) = (manifest_path.as_ref(), base_dir.as_ref());
+    let size = fs::metadata(manifest_path).map_err(|e| format!("Unable to read {}: {}", manifest_path.display(), e))?.len();
+    if size > MAX_MANIFEST {
+        return Err(format!("{} is larger than the {} byte limit", manifest_path.display(), MAX_MANIFEST));
+    }
+
+    // Without its BOM, in UTF-8 whatever it was saved as, with \n line ends
+    let text = read_text_file(manifest_path).map_err(|e| format!("Unable to read {}: {}", manifest_path.display(), e))?;
+
+    let (lines, signed) = unwrap_clearsigned(&text);
+    let mut report = ManifestReport {
+        manifest: path_to_string(manifest_path),
+        base_dir: path_to_string(base_dir),
+        verified: false,
+        signed,
+        ok: 0,
+        mismatched: 0,
+        missing: 0,
+        unsupported: 0,
+        errors: 0,
+        entries: Vec::new(),
+        unparsed_lines: Vec::new()
+    };
+
+    for (number, line) in lines {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (algorithm, expected, file) = match parse_line(&line) {
+            Some(parsed) => parsed,
+            None => {
+                report.unparsed_lines.push(number);
+                continue;
+            }
+        };
+
+        let entry = verify_entry(number, base_dir, algorithm, &expected, file);
+        match entry.status {
+            ManifestStatus::Ok => report.ok += 1,
+            ManifestStatus::Mismatch => report.mismatched += 1,
+            ManifestStatus::Missing => report.missing += 1,
+            ManifestStatus::Unsupported => report.unsupported += 1,
+            ManifestStatus::Error => report.errors += 1
+        }
+        report.entries.push(entry);
+    }
+
+    report.verified = !report.entries.is_empty() && report.ok == report.entries.len();
+    Ok(report)
+}
+
+fn verify_entry(line: usize, base_dir: &Path, algorithm: String, expected: &str, file: String) -> ManifestEntry {
+    let mut entry = ManifestEntry { line, file, algorithm, expected: expected.to_ascii_lowercase(), actual: None, status: ManifestStatus::Unsupported, detail: None };
+    if entry.algorithm == "ssdeep" || !FILE_HASH_ALGORITHMS.contains(&entry.algorithm.as_str()) {
+        return entry;
+    }
+
+    let path: PathBuf = base_dir.join(&entry.file);
+    match fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() => {},
+        // Opening a FIFO or device to hash it could block forever
+        Ok(_) => {
+            entry.status = ManifestStatus::Error;
+            entry.detail = Some("not a regular file".to_string());
+            return entry;
+        },
+        Err(_) => {
+            entry.status = ManifestStatus::Missing;
+            return entry;
+        }
+    }
+
+    match mercy_hash_file(&path, &entry.algorithm) {
+        Ok(actual) => {
+            entry.status = if actual == entry.expected { ManifestStatus::Ok } else { ManifestStatus::Mismatch };
+            entry.actual = Some(actual);
+        },
+        Err(e) => {
+            entry.status = ManifestStatus::Error;
+            entry.detail = Some(e);
+        }
+    }
+
+    entry
+}
+
+// The numbered lines carrying checksums, and whether a PGP clear-signature was removed
+fn unwrap_clearsigned(text: &str) -> (Vec<(usize, String)>, bool) {
+    let numbered = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+    if !text.lines().any(|line| line.trim_end() == "-----BEGIN PGP SIGNED MESSAGE-----") {
+        return (numbered.map(|(number, line)| (number, line.to_string())).collect(), false);
+    }
+
+    // Armour header, then "Hash:" headers up to a blank line, then the dash-escaped message
+    let mut lines = Vec::new();
+    let mut stage = 0;
+    for (number, line) in numbered {
+        let trimmed = line.trim_end();
+        match stage {
+            0 if trimmed == "-----BEGIN PGP SIGNED MESSAGE-----" => stage = 1,
+            1 if trimmed.is_empty() => stage = 2,
+            2 if trimmed == "-----BEGIN PGP SIGNATURE-----" => stage = 3,
+            2 => lines.push((number, line.strip_prefix("- ").unwrap_or(line).to_string())),
+            3 if trimmed == "-----END PGP SIGNATURE-----" => stage = 0,
+            _ => {}
+        }
+    }
+
+    (lines, true)
+}
+
+// (algorithm, digest, file name) of a GNU or BSD checksum line
+fn parse_line(line: &str) -> Option<(String, String, String)> {
+    parse_bsd(line).or_else(|| parse_gnu(line))
+}
+
+// "SHA256 (name) = digest"; the name may itself contain ") = "
+fn parse_bsd(line: &str) -> Option<(String, String, String)> {
+    let (tag, rest) = line.split_once(" (")?;
+    let (file, digest) = rest.rsplit_once(") = ")?;
+    if tag.is_empty() || !tag.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-') || !is_hex(digest.trim()) {
+        return None;
+    }
+
+    let algorithm = match tag.to_ascii_uppercase().as_str() {
+        "SHA256" | "SHA2-256" => "sha2_256".to_string(),
+        "SHA512" | "SHA2-512" => "sha2_512".to_string(),
+        "SHA1" => "sha1".to_string(),
+        "SHA3-256" => "sha3_256".to_string(),
+        "SHA3-512" => "sha3_512".to_string(),
+        "MD5" => "md5".to_string(),
+        other => other.to_string()
+    };
+
+    Some((algorithm, digest.trim().to_string(), file.to_string()))
+}
+
+// "digest  name" or "digest *name"; a leading "\" means the name is escaped
+fn parse_gnu(line: &str) -> Option<(String, String, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line)
+    };
+
+    let (digest, rest) = line.split_once(' ')?;
+    let file = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+    if !is_hex(digest) || file.is_empty() {
+        return None;
+    }
+
+    // The length is all GNU output says about the algorithm
+    let algorithm = match digest.len() {
+        64 => "sha2_256",
+        32 => "md5",
+        40 => "sha1",
+        128 => "sha2_512",
+        _ => return None
+    };
+
+    let file = if escaped { unescape(file) } else { file.to_string() };
+    Some((algorithm.to_string(), digest.to_string(), file))
+}
+
+// The digest of an expected value given bare, as a GNU "digest  name" line or as a BSD line, and the
+// file the line names
+pub(crate) fn expected_digest(expected: &str) -> (String, Option<String>) {
+    let line = expected.trim();
+    if let Some((_, digest, file)) = parse_bsd(line) {
+        return (digest, Some(file));
+    }
+
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(rest) => (true, rest),
+        None => (false, line)
+    };
+    match line.split_once(char::is_whitespace) {
+        Some((digest, rest)) => {
+            let file = rest.trim_start();
+            let file = file.strip_prefix('*').unwrap_or(file);
+            let file = if escaped { unescape(file) } else { file.to_string() };
+            (digest.to_string(), (!file.is_empty()).then_some(file))
+        },
+        None => (line.to_string(), None)
+    }
+}
+
+fn is_hex(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn unescape(name: &str) -> String {
+    let mut output = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('r') => output.push('\r'),
+            Some(other) => output.push(other),
+            None => output.push('\\')
+        }
+    }
+
+    output
+}
+
+// Parses "manifest [base=dir]"; names are relative to the manifest's directory by default
+pub(crate) fn verify_manifest_from_args(input: &str) -> Result<ManifestReport, String> {
+    let (manifest, pairs) = trailing_options(input, &["base"]);
+    if manifest.is_empty() {
+        return Err("No manifest specified for verify_manifest".to_string());
+    }
+
+    let manifest = PathBuf::from(manifest);
+    let base_dir = match pairs.iter().find(|(key, _)| *key == "base") {
+        Some((_, value)) => PathBuf::from(value),
+        None => manifest.parent().map(Path::to_path_buf).unwrap_or_default()
+    };
+
+    mercy_verify_manifest(&manifest, &base_dir)
+}
@@ -0,0 +1,226 @@
+/*
+    Prometheus text exposition of check results
+
+    `mercy_metrics_render` turns the results of the service, reachability, certificate and
+    audit checks into the text format Prometheus scrapes (version 0.0.4): every metric
+    family is a gauge with one HELP and one TYPE line, followed by all of its samples, and
+    label values are escaped (backslash, double quote and newline) so banners, subjects and
+    hostnames can be used as they are. Serving the text over HTTP is left to the caller.
+
+    Families, each labelled with the target and check:
+
+        mercy_check_up               1 when the service answered, 0 when it did not
+        mercy_check_weakness         1 per insecure setting seen (SMB1, unsigned SMB, RDP
+                                     without NLA, anonymous or cleartext FTP, telnet login)
+        mercy_ping_loss_ratio        share of echo requests lost, 0 to 1
+        mercy_ping_rtt_seconds       average round-trip time
+        mercy_sweep_hosts_scanned    addresses probed by a sweep
+        mercy_sweep_hosts_alive      addresses that answered
+        mercy_cert_expiry_seconds    seconds until notAfter (negative once expired)
+        mercy_domain_sinkholed       1 when the domain looks sinkholed
+        mercy_findings               audit findings per severity
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    checks::{FtpCheck, RdpCheck, SmbCheck, TelnetCheck},
+    discover::SweepReport,
+    finding::{Finding, Severity},
+    ping::PingReport,
+    sinkhole::SinkholeReport,
+    timefmt::parse_utc,
+    x509::CertificateInfo
+};
+
+// Name and HELP text of each family, in the order they are written
+const FAMILIES: [(&str, &str); 9] = [
+    ("mercy_check_up", "Whether the checked service answered (1) or not (0)."),
+    ("mercy_check_weakness", "Insecure setting seen by a check (1 present, 0 absent)."),
+    ("mercy_ping_loss_ratio", "Share of ICMP echo requests without a reply."),
+    ("mercy_ping_rtt_seconds", "Average round-trip time of answered echo requests."),
+    ("mercy_sweep_hosts_scanned", "Addresses probed by a host sweep."),
+    ("mercy_sweep_hosts_alive", "Addresses that answered a host sweep."),
+    ("mercy_cert_expiry_seconds", "Seconds until the certificate expires, negative once it has."),
+    ("mercy_domain_sinkholed", "Whether the domain looks sinkholed (1) or not (0)."),
+    ("mercy_findings", "Audit findings by severity.")
+];
+
+const UP: usize = 0;
+const WEAKNESS: usize = 1;
+const LOSS: usize = 2;
+const RTT: usize = 3;
+const SCANNED: usize = 4;
+const ALIVE: usize = 5;
+const EXPIRY: usize = 6;
+const SINKHOLED: usize = 7;
+const FINDINGS: usize = 8;
+
+/// A check result to expose as metrics
+#[derive(Debug, Clone)]
+pub enum CheckResult {
+    Smb(SmbCheck),
+    Rdp(RdpCheck),
+    Ftp(FtpCheck),
+    Telnet(TelnetCheck),
+    Ping(PingReport),
+    Sweep(SweepReport),
+    /// A certificate served by or collected from `target`
+    Certificate {
+        target: String,
+        certificate: CertificateInfo
+    },
+    Sinkhole(SinkholeReport),
+    /// Findings of an audit such as `audit_security_headers`, counted per severity
+    Audit {
+        target: String,
+        check: String,
+        findings: Vec<Finding>
+    }
+}
+
+// One line of a family: label pairs and value
+struct Sample {
+    family: usize,
+    labels: Vec<(&'static str, String)>,
+    value: f64
+}
+
+/// The results in Prometheus text exposition format, certificate expiry counted from now
+pub fn mercy_metrics_render(results: &[CheckResult]) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs() as i64).unwrap_or(0);
+    mercy_metrics_render_at(results, now)
+}
+
+/// The same with certificate expiry counted from `now` (Unix seconds)
+pub fn mercy_metrics_render_at(results: &[CheckResult], now: i64) -> String {
+    let mut samples: Vec<Sample> = results.iter().flat_map(|result| samples(result, now)).collect();
+
+    // Every sample of a family has to follow its HELP and TYPE lines
+    samples.sort_by_key(|sample| sample.family);
+
+    let mut output = String::new();
+    let mut current = None;
+
+    for sample in samples {
+        if current != Some(sample.family) {
+            let (name, help) = FAMILIES[sample.family];
+            output.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n", name, escape_help(help), name));
+            current = Some(sample.family);
+        }
+
+        let labels: Vec<String> = sample.labels.iter().map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value))).collect();
+        output.push_str(&format!("{}{{{}}} {}\n", FAMILIES[sample.family].0, labels.join(","), format_value(sample.value)));
+    }
+
+    output
+}
+
+fn samples(result: &CheckResult, now: i64) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    let check_labels = |target: &str, check: &str| vec![("target", target.to_string()), ("check", check.to_string())];
+    let weakness = |samples: &mut Vec<Sample>, target: &str, check: &str, name: &str, present: bool| {
+        let mut labels = check_labels(target, check);
+        labels.push(("weakness", name.to_string()));
+        samples.push(Sample { family: WEAKNESS, labels, value: flag(present) });
+    };
+
+    match result {
+        CheckResult::Smb(check) => {
+            samples.push(Sample { family: UP, labels: check_labels(&check.target, "smb"), value: flag(check.reachable) });
+            if let Some(accepted) = check.smb1_accepted {
+                weakness(&mut samples, &check.target, "smb", "smb1_accepted", accepted);
+            }
+            if let Some(required) = check.signing_required {
+                weakness(&mut samples, &check.target, "smb", "signing_not_required", !required);
+            }
+        },
+        CheckResult::Rdp(check) => {
+            samples.push(Sample { family: UP, labels: check_labels(&check.target, "rdp"), value: flag(check.answered) });
+            if let Some(standard) = check.standard_rdp {
+                weakness(&mut samples, &check.target, "rdp", "standard_rdp_security", standard);
+            }
+            if let Some(nla) = check.nla {
+                weakness(&mut samples, &check.target, "rdp", "nla_not_supported", !nla);
+            }
+        },
+        CheckResult::Ftp(check) => {
+            samples.push(Sample { family: UP, labels: check_labels(&check.target, "ftp"), value: 1.0 });
+            weakness(&mut samples, &check.target, "ftp", "anonymous_login", check.anonymous_login);
+            weakness(&mut samples, &check.target, "ftp", "no_tls", !check.tls_supported);
+        },
+        CheckResult::Telnet(check) => {
+            samples.push(Sample { family: UP, labels: check_labels(&check.target, "telnet"), value: 1.0 });
+            weakness(&mut samples, &check.target, "telnet", "cleartext_login", check.login_prompt);
+        },
+        CheckResult::Ping(report) => {
+            samples.push(Sample { family: UP, labels: check_labels(&report.target, "ping"), value: flag(report.received > 0) });
+            samples.push(Sample { family: LOSS, labels: check_labels(&report.target, "ping"), value: report.loss_percent / 100.0 });
+            if let Some(average) = report.avg_ms {
+                samples.push(Sample { family: RTT, labels: check_labels(&report.target, "ping"), value: average / 1000.0 });
+            }
+        },
+        CheckResult::Sweep(report) => {
+            let labels = || vec![("target", report.range.clone()), ("check", "sweep".to_string()), ("method", report.method.clone())];
+            samples.push(Sample { family: SCANNED, labels: labels(), value: report.scanned as f64 });
+            samples.push(Sample { family: ALIVE, labels: labels(), value: report.alive.len() as f64 });
+        },
+        CheckResult::Certificate { target, certificate } => {
+            // A certificate without a readable notAfter has no expiry to report
+            if let Some(not_after) = certificate.not_after.as_deref().and_then(|time| parse_utc(time).ok()) {
+                let mut labels = check_labels(target, "certificate");
+                labels.push(("subject", certificate.subject.clone()));
+                labels.push(("sha256", certificate.sha256.clone()));
+                samples.push(Sample { family: EXPIRY, labels, value: (not_after - now) as f64 });
+            }
+        },
+        CheckResult::Sinkhole(report) => {
+            samples.push(Sample { family: SINKHOLED, labels: check_labels(&report.domain, "sinkhole"), value: flag(report.sinkholed) });
+        },
+        CheckResult::Audit { target, check, findings } => {
+            // Every severity is written, so a fixed issue drops to 0 rather than disappearing
+            for severity in Severity::DESCENDING {
+                let mut labels = check_labels(target, check);
+                labels.push(("severity", severity.to_string()));
+                let count = findings.iter().filter(|finding| finding.severity == severity).count();
+                samples.push(Sample { family: FINDINGS, labels, value: count as f64 });
+            }
+        }
+    }
+
+    samples
+}
+
+fn flag(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+// Label values escape backslash, double quote and line feed
+fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+// HELP text escapes only backslash and line feed
+fn escape_help(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+// Go-style float text: "NaN", "+Inf" and "-Inf" for the special values
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf".to_string() } else { "-Inf".to_string() }
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,616 @@
+/*
+    Windows minidump (.dmp) parsing
+
+    Reads the stream directory of a single-process minidump: system information, process and
+    exception details, the module list and the captured memory ranges. Printable ASCII and UTF-16
+    strings are extracted from the memory ranges and attributed to the module whose image covers
+    their address. Streams that point outside a truncated dump are noted and skipped.
+*/
+
+use std::{
+    fmt,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::{
+    paths::path_to_string,
+    registry::{le16, le32, le64},
+    sink::{SinkArgs, finish_sink},
+    timefmt::unix_to_rfc3339,
+    trailing_options
+};
+
+const READ_BLOCK: usize = 1024 * 1024;
+
+// Longest string emitted in one piece; longer runs continue in a new string
+const MAX_STRING: usize = 4096;
+
+/// Dump-level information from the header and the system, misc and exception streams
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpInfo {
+    pub path: String,
+    pub timestamp: String,
+    pub flags: String,
+    pub streams: Vec<String>,
+    pub system: Option<MinidumpSystem>,
+    pub process_id: Option<u32>,
+    pub process_created: Option<String>,
+    pub exception: Option<MinidumpException>,
+    pub thread_count: Option<u32>,
+    pub module_count: usize,
+    pub region_count: usize,
+    pub memory_bytes: u64,
+    /// Streams or ranges that could not be read, usually because the dump is truncated
+    pub notes: Vec<String>
+}
+
+/// Operating system and processor of the dumped machine
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpSystem {
+    pub architecture: String,
+    pub processors: u8,
+    pub product_type: String,
+    pub version: String,
+    pub service_pack: Option<String>
+}
+
+/// The exception that triggered the dump, if any
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpException {
+    pub thread_id: u32,
+    pub code: String,
+    pub address: String
+}
+
+/// A module loaded in the dumped process
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpModule {
+    pub base: String,
+    pub size: u32,
+    pub path: String,
+    pub version: Option<String>,
+    /// PE link timestamp
+    pub timestamp: String,
+    #[serde(skip)]
+    base_address: u64
+}
+
+/// A captured memory range
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpRegion {
+    pub start: String,
+    pub size: u64,
+    /// Owning module, when the range lies within a loaded image
+    pub module: Option<String>,
+    #[serde(skip)]
+    start_address: u64,
+    #[serde(skip)]
+    file_offset: u64
+}
+
+/// A string found in captured memory
+#[derive(Debug, Clone, Serialize)]
+pub struct MinidumpString {
+    pub address: String,
+    pub encoding: &'static str,
+    pub module: Option<String>,
+    pub value: String
+}
+
+/// A parsed dump: information, modules and memory ranges
+#[derive(Debug, Clone, Serialize)]
+pub struct Minidump {
+    pub info: MinidumpInfo,
+    pub modules: Vec<MinidumpModule>,
+    pub regions: Vec<MinidumpRegion>
+}
+
+impl fmt::Display for Minidump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = &self.info;
+        writeln!(f, "Minidump: {} (written {})", info.path, info.timestamp)?;
+
+        if let Some(system) = &info.system {
+            writeln!(f, "System: Windows {} {} ({}, {} processors)", system.version, system.product_type, system.architecture, system.processors)?;
+        }
+        if let Some(pid) = info.process_id {
+            writeln!(f, "Process: {} (created {})", pid, info.process_created.as_deref().unwrap_or("unknown"))?;
+        }
+        if let Some(exception) = &info.exception {
+            writeln!(f, "Exception: {} at {} in thread {}", exception.code, exception.address, exception.thread_id)?;
+        }
+
+        writeln!(f, "Threads: {}", info.thread_count.map(|count| count.to_string()).unwrap_or_else(|| "unknown".to_string()))?;
+        writeln!(f, "Memory: {} ranges, {} bytes", info.region_count, info.memory_bytes)?;
+        writeln!(f, "Modules ({}):", self.modules.len())?;
+        for module in &self.modules {
+            writeln!(f, "  {}  {:>10}  {}  {}", module.base, module.size, module.version.as_deref().unwrap_or("-"), module.path)?;
+        }
+
+        for note in &info.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// String extraction settings
+#[derive(Debug, Clone)]
+pub struct MinidumpOptions {
+    /// Extract strings from captured memory
+    pub strings: bool,
+    /// Shortest run of printable characters reported
+    pub min_length: usize,
+    /// Stop after this many strings
+    pub max_strings: usize
+}
+
+impl Default for MinidumpOptions {
+    fn default() -> MinidumpOptions {
+        MinidumpOptions { strings: true, min_length: 6, max_strings: 1_000_000 }
+    }
+}
+
+/// Counts from an NDJSON run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MinidumpSummary {
+    pub modules: usize,
+    pub regions: usize,
+    pub strings: u64,
+    /// True when `max_strings` stopped extraction
+    pub strings_truncated: bool
+}
+
+// One NDJSON line, tagged with its record type
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Line<'a> {
+    Dump(&'a MinidumpInfo),
+    Module(&'a MinidumpModule),
+    String(&'a MinidumpString),
+    Summary(&'a MinidumpSummary)
+}
+
+/// Parses the stream directory of a minidump
+pub fn read_minidump<P: AsRef<Path>>(path: P) -> Result<Minidump, String> {
+    let path = path.as_ref();
+    let mut file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let header = read_at(&mut file, 0, 32).ok_or("Not a minidump: too short")?;
+    if &header[..4] != b"MDMP" {
+        return Err("Not a minidump: missing MDMP signature".to_string());
+    }
+
+    let stream_count = le32(&header, 8).unwrap_or(0) as usize;
+    let directory = le32(&header, 12).unwrap_or(0) as u64;
+
+    let mut info = MinidumpInfo {
+        path: path_to_string(path),
+        timestamp: unix_to_rfc3339(le32(&header, 20).unwrap_or(0) as i64, 0),
+        flags: format!("0x{:x}", le64(&header, 24).unwrap_or(0)),
+        streams: Vec::new(),
+        system: None,
+        process_id: None,
+        process_created: None,
+        exception: None,
+        thread_count: None,
+        module_count: 0,
+        region_count: 0,
+        memory_bytes: 0,
+        notes: Vec::new()
+    };
+
+    let mut modules = Vec::new();
+    let mut regions = Vec::new();
+
+    for index in 0..stream_count.min(1024) {
+        let entry = match read_at(&mut file, directory + index as u64 * 12, 12) {
+            Some(entry) => entry,
+            None => {
+                info.notes.push(format!("stream directory truncated after {} of {} entries", index, stream_count));
+                break;
+            }
+        };
+
+        let kind = le32(&entry, 0).unwrap_or(0);
+        let length = le32(&entry, 4).unwrap_or(0) as usize;
+        let rva = le32(&entry, 8).unwrap_or(0) as u64;
+
+        if kind == 0 {
+            continue;
+        }
+
+        let name = stream_name(kind);
+        info.streams.push(name.clone());
+
+        // Check against the file size first so a corrupt length never drives the allocation
+        let data = match Some(rva + length as u64).filter(|end| *end <= size).and_then(|_| read_at(&mut file, rva, length)) {
+            Some(data) => data,
+            None => {
+                info.notes.push(format!("{} stream lies beyond the end of the dump", name));
+                continue;
+            }
+        };
+
+        let parsed = match kind {
+            3 => le32(&data, 0).map(|count| info.thread_count = Some(count)).ok_or("empty"),
+            4 => module_list(&mut file, &data, &mut modules),
+            5 => memory_list(&data, &mut regions),
+            6 => exception(&data).map(|exception| info.exception = Some(exception)).ok_or("truncated"),
+            7 => system_info(&mut file, &data).map(|system| info.system = Some(system)).ok_or("truncated"),
+            9 => memory64_list(&data, &mut regions),
+            15 => {
+                misc_info(&data, &mut info);
+                Ok(())
+            }
+            _ => Ok(())
+        };
+
+        if let Err(problem) = parsed {
+            info.notes.push(format!("{} stream is {}", name, problem));
+        }
+    }
+
+    // Clip ranges whose bytes were not all written before the dump was cut short
+    for region in regions.iter_mut() {
+        if region.file_offset.saturating_add(region.size) > size {
+            info.notes.push(format!("memory at {} is truncated ({} of {} bytes present)", region.start, size.saturating_sub(region.file_offset), region.size));
+            region.size = size.saturating_sub(region.file_offset);
+        }
+    }
+
+    modules.sort_by_key(|module: &MinidumpModule| module.base_address);
+    regions.sort_by_key(|region: &MinidumpRegion| region.start_address);
+
+    for region in regions.iter_mut() {
+        region.module = owner(&modules, region.start_address).map(str::to_string);
+    }
+
+    info.module_count = modules.len();
+    info.region_count = regions.len();
+    info.memory_bytes = regions.iter().map(|region| region.size).sum();
+
+    Ok(Minidump { info, modules, regions })
+}
+
+/// Writes the dump as NDJSON: a "dump" line, one "module" line per module, one "string" line per
+/// extracted string and a closing "summary" line
+pub fn write_minidump_ndjson<P: AsRef<Path>, W: Write>(path: P, options: &MinidumpOptions, writer: &mut W) -> Result<MinidumpSummary, String> {
+    let path = path.as_ref();
+    let dump = read_minidump(path)?;
+    let mut summary = MinidumpSummary { modules: dump.modules.len(), regions: dump.regions.len(), ..MinidumpSummary::default() };
+
+    let mut emit = |line: Line| -> Result<(), String> {
+        let json = serde_json::to_string(&line).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", json).map_err(|e| format!("Unable to write record: {}", e))
+    };
+
+    emit(Line::Dump(&dump.info))?;
+    for module in &dump.modules {
+        emit(Line::Module(module))?;
+    }
+
+    if options.strings {
+        let mut file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+
+        for region in &dump.regions {
+            let complete = region_strings(&mut file, region, &dump.modules, options.min_length.max(1), &mut |string| {
+                if summary.strings >= options.max_strings as u64 {
+                    return Ok(false);
+                }
+                summary.strings += 1;
+                emit(Line::String(&string)).map(|_| true)
+            })?;
+
+            if !complete {
+                summary.strings_truncated = true;
+                break;
+            }
+        }
+    }
+
+    emit(Line::Summary(&summary))?;
+    writer.flush().map_err(|e| format!("Unable to write record: {}", e))?;
+    Ok(summary)
+}
+
+fn read_at(file: &mut File, offset: u64, length: usize) -> Option<Vec<u8>> {
+    let mut buffer = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut buffer).ok()?;
+    Some(buffer)
+}
+
+fn stream_name(kind: u32) -> String {
+    let name = match kind {
+        3 => "ThreadList",
+        4 => "ModuleList",
+        5 => "MemoryList",
+        6 => "Exception",
+        7 => "SystemInfo",
+        8 => "ThreadExList",
+        9 => "Memory64List",
+        10 => "CommentA",
+        11 => "CommentW",
+        12 => "HandleData",
+        13 => "FunctionTable",
+        14 => "UnloadedModuleList",
+        15 => "MiscInfo",
+        16 => "MemoryInfoList",
+        17 => "ThreadInfoList",
+        18 => "HandleOperationList",
+        19 => "Token",
+        21 => "SystemMemoryInfo",
+        22 => "ProcessVmCounters",
+        23 => "IptTrace",
+        24 => "ThreadNames",
+        other => return format!("Stream{}", other)
+    };
+    name.to_string()
+}
+
+// MINIDUMP_STRING: byte length then UTF-16 text
+fn minidump_string(file: &mut File, rva: u64) -> Option<String> {
+    let length = le32(&read_at(file, rva, 4)?, 0)? as usize;
+    if length > 64 * 1024 {
+        return None;
+    }
+
+    let raw = read_at(file, rva + 4, length)?;
+    let units: Vec<u16> = raw.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+fn module_list(file: &mut File, data: &[u8], modules: &mut Vec<MinidumpModule>) -> Result<(), &'static str> {
+    let count = le32(data, 0).ok_or("empty")? as usize;
+
+    for index in 0..count {
+        let entry = data.get(4 + index * 108..4 + (index + 1) * 108).ok_or("truncated")?;
+        let base = le64(entry, 0).unwrap_or(0);
+
+        // VS_FIXEDFILEINFO, present when its signature is
+        let version = match (le32(entry, 24), le32(entry, 32), le32(entry, 36)) {
+            (Some(0xFEEF_04BD), Some(high), Some(low)) => Some(format!("{}.{}.{}.{}", high >> 16, high & 0xFFFF, low >> 16, low & 0xFFFF)),
+            _ => None
+        };
+
+        modules.push(MinidumpModule {
+            base: format!("0x{:016x}", base),
+            size: le32(entry, 8).unwrap_or(0),
+            path: minidump_string(file, le32(entry, 20).unwrap_or(0) as u64).unwrap_or_default(),
+            version,
+            timestamp: unix_to_rfc3339(le32(entry, 16).unwrap_or(0) as i64, 0),
+            base_address: base
+        });
+    }
+
+    Ok(())
+}
+
+// MINIDUMP_MEMORY_DESCRIPTOR entries, each with its own RVA
+fn memory_list(data: &[u8], regions: &mut Vec<MinidumpRegion>) -> Result<(), &'static str> {
+    let count = le32(data, 0).ok_or("empty")? as usize;
+
+    for index in 0..count {
+        let entry = data.get(4 + index * 16..4 + (index + 1) * 16).ok_or("truncated")?;
+        let start = le64(entry, 0).unwrap_or(0);
+        regions.push(region(start, le32(entry, 8).unwrap_or(0) as u64, le32(entry, 12).unwrap_or(0) as u64));
+    }
+
+    Ok(())
+}
+
+// Full-memory dumps: descriptors carry only sizes, the data runs contiguously from one base RVA
+fn memory64_list(data: &[u8], regions: &mut Vec<MinidumpRegion>) -> Result<(), &'static str> {
+    let count = le64(data, 0).ok_or("empty")? as usize;
+    let mut offset = le64(data, 8).ok_or("truncated")?;
+
+    for index in 0..count {
+        let entry = data.get(16 + index * 16..16 + (index + 1) * 16).ok_or("truncated")?;
+        let size = le64(entry, 8).unwrap_or(0);
+        regions.push(region(le64(entry, 0).unwrap_or(0), size, offset));
+        offset = offset.saturating_add(size);
+    }
+
+    Ok(())
+}
+
+fn region(start: u64, size: u64, file_offset: u64) -> MinidumpRegion {
+    MinidumpRegion { start: format!("0x{:016x}", start), size, module: None, start_address: start, file_offset }
+}
+
+fn exception(data: &[u8]) -> Option<MinidumpException> {
+    Some(MinidumpException {
+        thread_id: le32(data, 0)?,
+        code: format!("0x{:08x}", le32(data, 8)?),
+        address: format!("0x{:016x}", le64(data, 24)?)
+    })
+}
+
+fn system_info(file: &mut File, data: &[u8]) -> Option<MinidumpSystem> {
+    let architecture = match le16(data, 0)? {
+        0 => "x86",
+        5 => "arm",
+        6 => "ia64",
+        9 => "x64",
+        12 => "arm64",
+        _ => "unknown"
+    };
+
+    let product_type = match *data.get(7)? {
+        1 => "workstation",
+        2 => "domain controller",
+        3 => "server",
+        _ => "unknown"
+    };
+
+    let service_pack = match le32(data, 24)? {
+        0 => None,
+        rva => minidump_string(file, rva as u64).filter(|text| !text.is_empty())
+    };
+
+    Some(MinidumpSystem {
+        architecture: architecture.to_string(),
+        processors: *data.get(6)?,
+        product_type: product_type.to_string(),
+        version: format!("{}.{}.{}", le32(data, 8)?, le32(data, 12)?, le32(data, 16)?),
+        service_pack
+    })
+}
+
+// MINIDUMP_MISC_INFO: Flags1 says which of the process fields are valid
+fn misc_info(data: &[u8], info: &mut MinidumpInfo) {
+    let flags = le32(data, 4).unwrap_or(0);
+
+    if flags & 0x1 != 0 {
+        info.process_id = le32(data, 8);
+    }
+    if flags & 0x2 != 0 {
+        info.process_created = le32(data, 12).map(|created| unix_to_rfc3339(created as i64, 0));
+    }
+}
+
+// Base name of the module whose image contains `address` (modules sorted by base)
+fn owner(modules: &[MinidumpModule], address: u64) -> Option<&str> {
+    let index = modules.partition_point(|module| module.base_address <= address).checked_sub(1)?;
+    let module = &modules[index];
+
+    if address < module.base_address + module.size as u64 {
+        Some(module.path.rsplit(['\\', '/']).next().unwrap_or(&module.path))
+    } else {
+        None
+    }
+}
+
+// Runs of printable characters, as ASCII bytes or as UTF-16LE code units, built across reads
+struct Run {
+    encoding: &'static str,
+    start: u64,
+    text: String
+}
+
+impl Run {
+    fn new(encoding: &'static str) -> Run {
+        Run { encoding, start: 0, text: String::new() }
+    }
+
+    fn push(&mut self, address: u64, character: char) {
+        if self.text.is_empty() {
+            self.start = address;
+        }
+        self.text.push(character);
+    }
+
+    // Hands the run to `emit` when it is long enough; false once `emit` wants no more
+    fn finish<F: FnMut(MinidumpString) -> Result<bool, String>>(&mut self, modules: &[MinidumpModule], min_length: usize, emit: &mut F) -> Result<bool, String> {
+        let text = std::mem::take(&mut self.text);
+        if text.len() < min_length {
+            return Ok(true);
+        }
+
+        emit(MinidumpString {
+            address: format!("0x{:016x}", self.start),
+            encoding: self.encoding,
+            module: owner(modules, self.start).map(str::to_string),
+            value: text
+        })
+    }
+}
+
+fn printable(byte: u8) -> bool {
+    (0x20..0x7F).contains(&byte) || byte == b'\t'
+}
+
+// Streams one memory range through the ASCII and UTF-16 scanners; false when `emit` stopped early
+fn region_strings<F: FnMut(MinidumpString) -> Result<bool, String>>(
+    file: &mut File,
+    region: &MinidumpRegion,
+    modules: &[MinidumpModule],
+    min_length: usize,
+    emit: &mut F
+) -> Result<bool, String> {
+    let mut ascii = Run::new("ascii");
+    let mut wide = Run::new("utf16");
+    let mut done = 0u64;
+
+    // READ_BLOCK is even, so a UTF-16 pair never straddles two reads
+    while done < region.size {
+        let chunk = ((region.size - done) as usize).min(READ_BLOCK);
+        let data = read_at(file, region.file_offset + done, chunk).ok_or("Dump shrank while reading memory")?;
+
+        for (index, byte) in data.iter().enumerate() {
+            let address = region.start_address + done + index as u64;
+
+            if printable(*byte) && ascii.text.len() < MAX_STRING {
+                ascii.push(address, *byte as char);
+            } else {
+                let keep = ascii.finish(modules, min_length, emit)?;
+                if !keep {
+                    return Ok(false);
+                }
+                if printable(*byte) {
+                    ascii.push(address, *byte as char);
+                }
+            }
+
+            // UTF-16LE: a printable byte at an even address followed by a zero byte
+            if index % 2 == 0 {
+                let next = data.get(index + 1).copied().unwrap_or(0xFF);
+                if printable(*byte) && next == 0 && wide.text.len() < MAX_STRING {
+                    wide.push(address, *byte as char);
+                } else {
+                    let keep = wide.finish(modules, min_length, emit)?;
+                    if !keep {
+                        return Ok(false);
+                    }
+                    if printable(*byte) && next == 0 {
+                        wide.push(address, *byte as char);
+                    }
+                }
+            }
+        }
+
+        done += chunk as u64;
+    }
+
+    Ok(ascii.finish(modules, min_length, emit)? && wide.finish(modules, min_length, emit)?)
+}
+
+// Parses "path [min=6] [max=100000] [strings=false] [out=file] [backpressure=block] [retries=5]" and returns NDJSON
+pub(crate) fn minidump_from_args(input: &str) -> Result<String, String> {
+    let (path, pairs) = trailing_options(input, &["min", "max", "strings", "out", "backpressure", "retries"]);
+    let mut options = MinidumpOptions::default();
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        let number = || value.parse::<usize>().map_err(|_| format!("Invalid {} '{}'", key, value));
+        match key {
+            "min" => options.min_length = number()?,
+            "max" => options.max_strings = number()?,
+            _ => options.strings = value != "false"
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for minidump".to_string());
+    }
+
+    if let Some(mut output) = sink.open()? {
+        let summary = write_minidump_ndjson(Path::new(&path), &options, &mut output)?;
+        return finish_sink(output, &summary);
+    }
+
+    let mut output = Vec::new();
+    write_minidump_ndjson(Path::new(&path), &options, &mut output)?;
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
@@ -0,0 +1,333 @@
+/*
+    Retries and per-host pacing for lookups of online services
+
+    Every HTTP request of an online lookup (InQuest, OSV, crt.sh, Pwned Passwords, the IP echo
+    services, the header, HSTS and SRI audits, webhooks) and every WHOIS query goes through
+    here. A rate limit (HTTP 429),
+    a server error (500, 502, 503, 504), a request timeout (408) or a connection that fails or
+    times out is tried again, up to `DEFAULT_RETRIES` more times or the retries of the current
+    `MercyConfig`. The wait before a retry starts at the configured backoff and doubles each
+    time, plus a random extra of up to half of it so parallel lookups that failed together do
+    not come back together. A Retry-After header, in seconds or as an HTTP date, replaces the
+    backoff; one asking for more than `MAX_RETRY_AFTER` ends the lookup at once instead of
+    stalling a batch for minutes. Any other answer, 404 included, goes back to the caller, and
+    so does the last answer when the retries run out for callers that report the status
+    itself (the header audits) rather than an error.
+
+    Each host also has a token bucket (see pacer.rs) shared by every lookup in the process,
+    from a built-in limit for the services known to throttle (labs.inquest.net) or a limit of
+    the config, so a batch of lookups never sends more than that many requests a second to
+    it. Retries take a token like any other request.
+
+    Each attempt is logged at debug level with its URL, each retry at warn and a lookup that
+    gives up at error (see `log`).
+*/
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use log::{debug, error, warn};
+use reqwest::{RequestBuilder, Response, StatusCode, header::RETRY_AFTER};
+
+use crate::{
+    config::mercy_config,
+    logtime::parse_any_timestamp,
+    pacer::MercyPacer,
+    rng::MercyRng
+};
+
+/// Attempts after the first one, unless the `MercyConfig` sets its own
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Wait before the first retry, unless the `MercyConfig` sets its own
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest Retry-After honored; a service asking for more ends the lookup
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// Longest backoff between two attempts, however many retries are allowed
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// Requests per second to services that throttle, unless the config sets a limit for the host
+const DEFAULT_RATE_LIMITS: &[(&str, u32)] = &[("labs.inquest.net", 2), ("api.osv.dev", 4)];
+
+// Token buckets by host and rate; a host is paced again from scratch when its rate changes
+static PACERS: Mutex<Vec<(String, u32, Arc<MercyPacer>)>> = Mutex::new(Vec::new());
+
+/// Why one attempt of a non-HTTP lookup failed
+pub(crate) enum Failure {
+    /// Worth another attempt: the service could not be reached or did not answer in time
+    Transient(String),
+    /// Would fail the same way again
+    Permanent(String)
+}
+
+/// How `send_with` treats one request; the default retries as the config says and logs the URL
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SendOptions {
+    /// Attempts after the first, instead of the config's or `DEFAULT_RETRIES`
+    pub retries: Option<u32>,
+    /// Named in logs and errors instead of the URL, for URLs that hold a secret
+    pub label: Option<String>,
+    /// Returns the last answer once the retries run out instead of an error, for callers that report the status itself
+    pub keep_last: bool
+}
+
+/// Default retries, giving back the last answer rather than an error when they run out
+pub(crate) const KEEP_LAST: SendOptions = SendOptions { retries: None, label: None, keep_last: true };
+
+/// A response and the attempts it took
+pub(crate) struct Sent {
+    pub response: Response,
+    pub attempts: u32
+}
+
+/// Sends `request`, trying again on rate limits, server errors and timeouts; the error after the last attempt names its status
+pub(crate) async fn send(request: RequestBuilder) -> Result<Response, String> {
+    send_with(request, &SendOptions::default()).await.map(|sent| sent.response)
+}
+
+/// `send` with the retries, naming and exhaustion behavior of `options`
+pub(crate) async fn send_with(request: RequestBuilder, options: &SendOptions) -> Result<Sent, String> {
+    let built = request.try_clone().and_then(|request| request.build().ok());
+    let method = built.as_ref().map(|built| built.method().to_string()).unwrap_or_else(|| "GET".to_string());
+    let timeout = built.as_ref().and_then(|built| built.timeout().copied());
+    let host = built.as_ref().and_then(|built| built.url().host_str()).map(str::to_ascii_lowercase).unwrap_or_default();
+    let target = match (&options.label, &built) {
+        (Some(label), _) => label.clone(),
+        (None, Some(built)) => built.url().to_string(),
+        (None, None) => host.clone()
+    };
+    let (config_retries, backoff) = retry_settings();
+    let retries = options.retries.unwrap_or(config_retries);
+    let mut rng = MercyRng::os();
+    let mut last = String::new();
+
+    for attempt in 0..=retries {
+        let Some(attempt_request) = request.try_clone() else {
+            return match request.send().await {
+                Ok(response) => Ok(Sent { response, attempts: 1 }),
+                Err(e) => Err(transport_error(e, timeout, options))
+            };
+        };
+        if let Some(pacer) = pacer(&host) {
+            pacer.acquire_async().await;
+        }
+        debug!("{} {} (attempt {} of {})", method, target, attempt + 1, retries + 1);
+
+        let wait = match attempt_request.send().await {
+            Ok(response) if retryable(response.status()) => {
+                last = format!("last status {}", status_text(response.status()));
+                let wait = retry_after(&response);
+                if options.keep_last && (attempt == retries || wait.is_some_and(|wait| wait > MAX_RETRY_AFTER)) {
+                    return Ok(Sent { response, attempts: attempt + 1 });
+                }
+                match wait {
+                    Some(wait) if wait > MAX_RETRY_AFTER => {
+                        let message = format!("{} asked to retry after {} s ({}, attempt {})", host, wait.as_secs(), status_text(response.status()), attempt + 1);
+                        error!("{}", message);
+                        return Err(message);
+                    },
+                    Some(wait) => wait,
+                    None => backoff_delay(backoff, attempt, &mut rng)
+                }
+            },
+            Ok(response) => return Ok(Sent { response, attempts: attempt + 1 }),
+            Err(e) if e.is_timeout() || e.is_connect() => {
+                last = transport_error(e, timeout, options);
+                backoff_delay(backoff, attempt, &mut rng)
+            },
+            Err(e) => {
+                let message = transport_error(e, timeout, options);
+                error!("{} {} failed: {}", method, target, message);
+                return Err(message);
+            }
+        };
+
+        if attempt < retries {
+            warn!("{}: {}, retrying in {} ms", target, last, wait.as_millis());
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    error!("{}: gave up after {} attempt(s), {}", target, retries + 1, last);
+    Err(format!("gave up after {} attempt(s), {}", retries + 1, last))
+}
+
+// What a failed request ran into, by its innermost cause and without the URL
+fn transport_error(e: reqwest::Error, timeout: Option<Duration>, options: &SendOptions) -> String {
+    if e.is_timeout() {
+        return match timeout {
+            Some(timeout) => format!("no answer within {} s", timeout.as_secs_f64()),
+            None => "no answer in time".to_string()
+        };
+    }
+
+    let e = match options.label {
+        Some(_) => e.without_url(),
+        None => e
+    };
+    let mut cause = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(inner) = source {
+        cause = inner.to_string();
+        source = inner.source();
+    }
+
+    match e.is_connect() {
+        true => format!("unable to connect (offline?): {}", cause),
+        false => cause
+    }
+}
+
+/// Runs a blocking lookup of `host` (such as a WHOIS query), paced and tried again while it fails transiently
+pub(crate) fn retry_blocking<T>(host: &str, mut attempt: impl FnMut() -> Result<T, Failure>) -> Result<T, String> {
+    let (retries, backoff) = retry_settings();
+    let pacer = pacer(&host.to_ascii_lowercase());
+    let mut rng = MercyRng::os();
+    let mut last = String::new();
+
+    for count in 0..=retries {
+        if let Some(pacer) = &pacer {
+            pacer.acquire();
+        }
+
+        match attempt() {
+            Ok(value) => return Ok(value),
+            Err(Failure::Permanent(e)) => {
+                error!("{}", e);
+                return Err(e);
+            },
+            Err(Failure::Transient(e)) => last = e
+        }
+
+        if count < retries {
+            let wait = backoff_delay(backoff, count, &mut rng);
+            warn!("{}, retrying in {} ms", last, wait.as_millis());
+            thread::sleep(wait);
+        }
+    }
+
+    error!("{} (gave up after {} attempt(s))", last, retries + 1);
+    Err(match retries {
+        0 => last,
+        _ => format!("{} (gave up after {} attempts)", last, retries + 1)
+    })
+}
+
+fn retry_settings() -> (u32, Duration) {
+    let config = mercy_config();
+    (config.retries().unwrap_or(DEFAULT_RETRIES), config.retry_backoff().unwrap_or(DEFAULT_RETRY_BACKOFF))
+}
+
+// The shared bucket of `host` at its configured or built-in rate, None when it is not limited
+fn pacer(host: &str) -> Option<Arc<MercyPacer>> {
+    let rate = mercy_config().rate_limit(host)
+        .or_else(|| DEFAULT_RATE_LIMITS.iter().find(|(limited, _)| *limited == host).map(|(_, rate)| *rate))?;
+    let mut pacers = PACERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some((_, _, pacer)) = pacers.iter().find(|(paced, paced_rate, _)| paced == host && *paced_rate == rate) {
+        return Some(Arc::clone(pacer));
+    }
+
+    let pacer = Arc::new(MercyPacer::new(rate as f64, rate).ok()?);
+    pacers.retain(|(paced, _, _)| paced != host);
+    pacers.push((host.to_string(), rate, Arc::clone(&pacer)));
+    Some(pacer)
+}
+
+fn retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+// "429 Too Many Requests"
+fn status_text(status: StatusCode) -> String {
+    match status.canonical_reason() {
+        Some(reason) => format!("{} {}", status.as_u16(), reason),
+        None => status.as_u16().to_string()
+    }
+}
+
+// `backoff` doubled `attempt` times, capped, plus up to half of it again at random
+fn backoff_delay(backoff: Duration, attempt: u32, rng: &mut MercyRng) -> Duration {
+    let delay = backoff.saturating_mul(1 << attempt.min(16)).min(MAX_BACKOFF);
+    let fraction = rng.next_u64() as f64 / u64::MAX as f64;
+    delay + delay.mul_f64(fraction / 2.0)
+}
+
+// The wait a Retry-After header asks for: seconds, or an HTTP date (a date already past is no wait)
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = parse_any_timestamp(value).ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(Duration::from_secs(at.secs.saturating_sub(now).max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::config::{MercyConfig, with_mercy_config};
+
+    fn quick(retries: u32) -> MercyConfig {
+        MercyConfig::builder().retries(retries).retry_backoff(Duration::from_millis(1)).build().unwrap()
+    }
+
+    #[test]
+    fn retry_blocking_recovers_from_transient_failures() {
+        let calls = Cell::new(0);
+        let result = with_mercy_config(&quick(3), || retry_blocking("whois.example", || {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                1 | 2 => Err(Failure::Transient("Unable to connect to whois.example".to_string())),
+                _ => Ok("record")
+            }
+        }));
+
+        assert_eq!(result, Ok("record"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_blocking_gives_up_with_the_last_failure() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = with_mercy_config(&quick(2), || retry_blocking("whois.example", || {
+            calls.set(calls.get() + 1);
+            Err(Failure::Transient(format!("attempt {} timed out", calls.get())))
+        }));
+
+        assert_eq!(result, Err("attempt 3 timed out (gave up after 3 attempts)".to_string()));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn retry_blocking_stops_at_a_permanent_failure() {
+        let calls = Cell::new(0);
+        let result: Result<(), String> = with_mercy_config(&quick(3), || retry_blocking("whois.example", || {
+            calls.set(calls.get() + 1);
+            Err(Failure::Permanent("No WHOIS server for .invalid".to_string()))
+        }));
+
+        assert_eq!(result, Err("No WHOIS server for .invalid".to_string()));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_a_cap() {
+        let mut rng = MercyRng::seeded(7);
+        for attempt in 0..6 {
+            let base = Duration::from_millis(100) * (1 << attempt);
+            let delay = backoff_delay(Duration::from_millis(100), attempt, &mut rng);
+            assert!(delay >= base && delay <= base + base / 2, "attempt {}: {:?}", attempt, delay);
+        }
+        assert!(backoff_delay(Duration::from_secs(10), 10, &mut rng) <= MAX_BACKOFF + MAX_BACKOFF / 2);
+    }
+}
@@ -0,0 +1,390 @@
+/*
+    Webhook notifications for finished analyses
+
+    `mercy_notify` POSTs a payload to a webhook, either as Slack blocks or as a generic JSON
+    document, through the shared HTTP client. Sends that fail to connect, time out or get a
+    429 or 5xx answer are retried by the shared net layer (see net.rs) with a doubling delay
+    and any Retry-After honoured, up to the options' retries; other 4xx answers are final. Receivers can check the sender from an
+    "Authorization: Bearer" token or from an HMAC-SHA256 signature:
+
+        X-Mercy-Timestamp: 1718000000
+        X-Mercy-Signature: sha256=<hex HMAC of "1718000000.<body>" under the shared secret>
+
+    (`verify_notify_signature` does the receiving side). The batch calls of the string
+    dispatchers take "notify=true" to post a summary to the target set with
+    `set_notify_target`, or "notify=https://..." for a one-off URL, once they finish, and
+    the `_to` batch functions take the same in their `BatchOutput`. A
+    failed notification never changes the call's result: it is kept for
+    `last_notification` instead.
+
+    Webhook URLs usually carry their secret in the path, so reports and errors name only
+    the scheme and host.
+*/
+
+use std::{
+    fmt,
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use log::error;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    config,
+    hashing::{HmacDigest, constant_time_eq, hmac},
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net::{self, SendOptions},
+    radix::hex_encode,
+    redact,
+    runtime,
+    timefmt::unix_to_rfc3339,
+    trailing_options
+};
+
+// Slack rejects section text longer than 3000 characters
+const SLACK_SECTION_LIMIT: usize = 2900;
+
+static NOTIFY_TARGET: RwLock<Option<NotifyTarget>> = RwLock::new(None);
+static LAST_NOTIFICATION: RwLock<Option<Result<NotifyReport, String>>> = RwLock::new(None);
+
+/// Body layout of a notification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyFormat {
+    /// {"source": "mercy", "title", "sent_at", "summary"}
+    #[default]
+    Json,
+    /// Incoming-webhook message: fallback text and blocks with the summary in a code block
+    Slack
+}
+
+impl NotifyFormat {
+    /// "json" or "slack"
+    pub fn parse(text: &str) -> Result<NotifyFormat, String> {
+        match text {
+            "json" => Ok(NotifyFormat::Json),
+            "slack" => Ok(NotifyFormat::Slack),
+            _ => Err(format!("Invalid notification format '{}' (expected json or slack)", text))
+        }
+    }
+
+    /// The request body for `payload` in this layout
+    pub fn render(&self, payload: &NotifyPayload) -> Value {
+        match self {
+            NotifyFormat::Json => serde_json::json!({
+                "source": "mercy",
+                "version": env!("CARGO_PKG_VERSION"),
+                "title": payload.title,
+                "sent_at": now_rfc3339(),
+                "summary": payload.summary
+            }),
+            NotifyFormat::Slack => {
+                let mut summary = serde_json::to_string_pretty(&payload.summary).unwrap_or_default();
+                if summary.len() > SLACK_SECTION_LIMIT {
+                    let cut = (0..=SLACK_SECTION_LIMIT).rev().find(|index| summary.is_char_boundary(*index)).unwrap_or(0);
+                    summary.truncate(cut);
+                    summary.push_str("\n...");
+                }
+
+                serde_json::json!({
+                    "text": payload.title,
+                    "blocks": [
+                        { "type": "section", "text": { "type": "mrkdwn", "text": format!("*{}*", payload.title) } },
+                        { "type": "section", "text": { "type": "mrkdwn", "text": format!("```{}```", summary) } }
+                    ]
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for NotifyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyFormat::Json => f.write_str("json"),
+            NotifyFormat::Slack => f.write_str("slack")
+        }
+    }
+}
+
+/// How the receiver can tell the notification came from us
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum NotifyAuth {
+    #[default]
+    None,
+    /// "Authorization: Bearer <token>"
+    Bearer(String),
+    /// X-Mercy-Timestamp and X-Mercy-Signature headers signed with this shared secret
+    Hmac(String)
+}
+
+/// Delivery settings of a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyOptions {
+    pub format: NotifyFormat,
+    pub auth: NotifyAuth,
+    /// Per attempt
+    pub timeout: Duration,
+    /// Further attempts after a failed one
+    pub retries: u32,
+    pub identity: HttpIdentity
+}
+
+impl Default for NotifyOptions {
+    fn default() -> Self {
+        NotifyOptions {
+            format: NotifyFormat::Json,
+            auth: NotifyAuth::None,
+            timeout: config::read_timeout(Duration::from_secs(10)),
+            retries: 2,
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// Webhook the batch calls post to with "notify=true"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyTarget {
+    pub url: String,
+    pub options: NotifyOptions
+}
+
+/// What a notification says
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NotifyPayload {
+    pub title: String,
+    pub summary: Value
+}
+
+/// Outcome of a delivered notification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotifyReport {
+    /// Scheme and host of the webhook
+    pub target: String,
+    pub format: NotifyFormat,
+    /// HTTP status of the answer that accepted it
+    pub status: u16,
+    pub attempts: u32
+}
+
+impl fmt::Display for NotifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Notified {} ({} payload): HTTP {} after {} attempt(s)", self.target, self.format, self.status, self.attempts)
+    }
+}
+
+/// Sets (or with `None` clears) the webhook used by "notify=true"
+pub fn set_notify_target(target: Option<NotifyTarget>) {
+    match NOTIFY_TARGET.write() {
+        Ok(mut current) => *current = target,
+        Err(poisoned) => *poisoned.into_inner() = target
+    }
+}
+
+/// The webhook set with `set_notify_target`
+pub fn notify_target() -> Option<NotifyTarget> {
+    match NOTIFY_TARGET.read() {
+        Ok(current) => current.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    }
+}
+
+/// Outcome of the latest notification a batch call sent, if any
+pub fn last_notification() -> Option<Result<NotifyReport, String>> {
+    match LAST_NOTIFICATION.read() {
+        Ok(last) => last.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    }
+}
+
+/// POSTs `payload` to `webhook_url`, retrying transient failures
+pub fn mercy_notify(webhook_url: &str, payload: &NotifyPayload, options: &NotifyOptions) -> Result<NotifyReport, String> {
+    let target = url_origin(webhook_url)?;
+    let body = redact::redact_enabled(options.format.render(payload).to_string());
+    runtime::block_on(send(webhook_url, &target, &body, options))
+}
+
+/// Whether `signature` ("sha256=<hex>") is the HMAC a sender holding `secret` puts on `body` sent at `timestamp`
+pub fn verify_notify_signature(secret: &str, timestamp: &str, body: &str, signature: &str) -> bool {
+    let expected = format!("sha256={}", sign(secret, timestamp, body));
+
+    // Compared in constant time so a forger learns nothing from how long a rejection takes
+    constant_time_eq(expected.as_bytes(), signature.as_bytes())
+}
+
+async fn send(url: &str, target: &str, body: &str, options: &NotifyOptions) -> Result<NotifyReport, String> {
+    let client = http_client(&options.identity)?;
+    let mut request = client.post(url)
+        .timeout(options.timeout)
+        .header("Content-Type", "application/json")
+        .body(body.to_string());
+
+    match &options.auth {
+        NotifyAuth::None => {},
+        NotifyAuth::Bearer(token) => request = request.bearer_auth(token),
+        NotifyAuth::Hmac(secret) => {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0).to_string();
+            request = request
+                .header("X-Mercy-Signature", format!("sha256={}", sign(secret, &timestamp, body)))
+                .header("X-Mercy-Timestamp", timestamp);
+        }
+    }
+
+    // Only the origin is logged or reported; the rest of the URL is usually the secret
+    let send = SendOptions { retries: Some(options.retries), label: Some(target.to_string()), keep_last: false };
+    let sent = net::send_with(request, &send).await
+        .map_err(|e| format!("Unable to send the notification to {}: {}", target, e))?;
+    let status = sent.response.status();
+    if !status.is_success() {
+        error!("Webhook at {} refused the notification: HTTP {}", target, status.as_u16());
+        return Err(format!("Webhook at {} refused the notification: HTTP {}", target, status.as_u16()));
+    }
+
+    Ok(NotifyReport { target: target.to_string(), format: options.format, status: status.as_u16(), attempts: sent.attempts })
+}
+
+// Hex HMAC-SHA256 of "<timestamp>.<body>"
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    hex_encode(&hmac(HmacDigest::Sha256, secret.as_bytes(), format!("{}.{}", timestamp, body).as_bytes()))
+}
+
+// "https://hooks.slack.com" from "https://hooks.slack.com/services/T000/B000/XXXX"
+fn url_origin(url: &str) -> Result<String, String> {
+    let (scheme, rest) = url.split_once("://").ok_or_else(|| "Invalid webhook URL (expected http:// or https://)".to_string())?;
+    if scheme != "http" && scheme != "https" {
+        return Err(format!("Invalid webhook URL scheme '{}' (expected http or https)", scheme));
+    }
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once('@').map(|(_, host)| host).unwrap_or(host);
+    if host.is_empty() {
+        return Err("Invalid webhook URL (no host)".to_string());
+    }
+
+    Ok(format!("{}://{}", scheme, host))
+}
+
+fn now_rfc3339() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    unix_to_rfc3339(now.as_secs() as i64, 0)
+}
+
+fn record(outcome: Result<NotifyReport, String>) {
+    match LAST_NOTIFICATION.write() {
+        Ok(mut last) => *last = Some(outcome),
+        Err(poisoned) => *poisoned.into_inner() = Some(outcome)
+    }
+}
+
+// Removes "notify=" and "notify_format=" from the trailing options, leaving the rest for the call
+fn split_notify_options(input: &str) -> (String, Option<String>, Option<String>) {
+    let mut tokens: Vec<&str> = input.trim().split(' ').collect();
+    let mut kept = Vec::new();
+    let (mut notify, mut format) = (None, None);
+
+    while let Some((key, value)) = tokens.last().and_then(|token| token.split_once('=')) {
+        match key {
+            "notify" => notify = Some(value.to_string()),
+            "notify_format" => format = Some(value.to_string()),
+            _ => kept.insert(0, tokens[tokens.len() - 1])
+        }
+        tokens.pop();
+    }
+
+    tokens.extend(kept);
+    (tokens.join(" "), notify, format)
+}
+
+// Short form of a call's result: scalars as they are, lists as their lengths, and streamed
+// text as its closing {"summary": ...} line or its line count
+fn summarize(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::from(items.len()),
+        Value::Object(fields) => Value::Object(fields.into_iter().map(|(key, field)| (key, summarize(field))).collect()),
+        Value::String(text) if text.contains('\n') => summarize_text(&text),
+        other => other
+    }
+}
+
+fn summarize_text(text: &str) -> Value {
+    // A sink's {"summary", "sink"} document, or NDJSON ending with a summary line
+    if let Ok(Value::Object(document)) = serde_json::from_str::<Value>(text) {
+        return summarize(Value::Object(document));
+    }
+
+    let last = text.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or("");
+    match serde_json::from_str::<Value>(last) {
+        Ok(Value::Object(line)) if line.contains_key("summary") => summarize(Value::Object(line)),
+        _ => serde_json::json!({ "lines": text.lines().count() })
+    }
+}
+
+// Runs a batch call, then posts a summary of its result when "notify=" asks for one
+pub(crate) fn notified<T: Serialize>(call: &str, input: &str, run: impl FnOnce(&str) -> Result<T, String>) -> Result<T, String> {
+    let (input, notify, format) = split_notify_options(input);
+    let result = run(&input);
+
+    if let Some(notify) = notify.filter(|notify| notify != "false") {
+        match format.as_deref().map(NotifyFormat::parse).transpose() {
+            Ok(format) => notify_finished(call, &notify, format, &result),
+            Err(e) => record(Err(e))
+        }
+    }
+
+    result
+}
+
+// Posts a summary of a finished call's result to "true" (the target set with `set_notify_target`) or a URL, keeping the outcome for `last_notification`
+pub(crate) fn notify_finished<T: Serialize>(call: &str, notify: &str, format: Option<NotifyFormat>, result: &Result<T, String>) {
+    let (title, summary) = match result {
+        Ok(report) => (format!("mercy {} completed", call), summarize(serde_json::to_value(report).unwrap_or(Value::Null))),
+        Err(e) => (format!("mercy {} failed", call), serde_json::json!({ "error": e }))
+    };
+    record(post_summary(notify, format, &NotifyPayload { title, summary }));
+}
+
+fn post_summary(notify: &str, format: Option<NotifyFormat>, payload: &NotifyPayload) -> Result<NotifyReport, String> {
+    let configured = notify_target();
+    let (url, mut options) = match (notify, configured) {
+        ("true", Some(target)) => (target.url, target.options),
+        ("true", None) => return Err("No notification target set (use set_notify_target or notify=<url>)".to_string()),
+        (url, target) => (url.to_string(), target.map(|target| target.options).unwrap_or_default())
+    };
+
+    if let Some(format) = format {
+        options.format = format;
+    }
+
+    mercy_notify(&url, payload, &options)
+}
+
+// Parses "webhook_url payload [format=slack] [bearer=token] [hmac=secret] [timeout=10] [retries=2] [title=...]" plus HTTP identity options; a JSON payload is sent as the summary, other text as {"text": ...}
+pub(crate) fn notify_from_args(input: &str) -> Result<NotifyReport, String> {
+    let keys: Vec<&str> = ["format", "bearer", "hmac", "timeout", "retries", "title"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (rest, pairs) = trailing_options(input, &keys);
+    let (url, text) = rest.split_once(' ').unwrap_or((&rest, ""));
+    let mut options = NotifyOptions::default();
+    let mut title = "mercy notification".to_string();
+
+    for (key, value) in pairs {
+        match key {
+            "format" => options.format = NotifyFormat::parse(value)?,
+            "bearer" => options.auth = NotifyAuth::Bearer(value.to_string()),
+            "hmac" => options.auth = NotifyAuth::Hmac(value.to_string()),
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            "retries" => options.retries = value.parse().map_err(|_| format!("Invalid retries '{}'", value))?,
+            "title" => title = value.replace('_', " "),
+            _ => identity_option(&mut options.identity, key, value)?
+        }
+    }
+
+    if url.is_empty() {
+        return Err("No webhook URL specified".to_string());
+    }
+
+    let text = text.trim();
+    let summary = serde_json::from_str(text).unwrap_or_else(|_| serde_json::json!({ "text": text }));
+    mercy_notify(url, &NotifyPayload { title, summary }, &options)
+}
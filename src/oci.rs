@@ -0,0 +1,790 @@
+/*
+    Container image inspection for `docker save` archives and OCI image layouts
+
+    The outer tarball is indexed by seeking past entries, the image configuration and manifests
+    are read from it, and layers are streamed (decompressing gzip on the fly) when their files
+    are scanned, so nothing is unpacked to disk. Overlay whiteouts (.wh.<name> and the opaque
+    .wh..wh..opq marker) are tracked so content deleted by a later layer is still reported as
+    present in the image.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Take},
+    path::Path
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    finding::{Finding, Severity},
+    inflate::{gzip_reader, Inflater},
+    paths::path_to_string,
+    secrets::{excerpt, find_secrets, redact, redact_matches, secret_env},
+    tar::{TarEntry, TarReader, TarSource},
+    text::read_text_lines,
+    trailing_options
+};
+
+// Manifests, indexes and configs are small; anything bigger is not one
+const MAX_JSON: u64 = 16 * 1024 * 1024;
+
+// Files larger than this are hashed but not searched
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+// Leading bytes checked for NUL to decide a file is binary
+const BINARY_SNIFF: usize = 8192;
+
+/// An environment variable from the image configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct OciEnv {
+    pub name: String,
+    /// Redacted when the variable looks like a secret
+    pub value: String,
+    /// Secret pattern the variable matched
+    pub secret: Option<String>
+}
+
+/// A filesystem layer of the image, bottom first
+#[derive(Debug, Clone, Serialize)]
+pub struct OciLayer {
+    pub index: usize,
+    /// Location of the layer inside the archive
+    pub path: String,
+    /// Digest of the layer blob as stored (OCI layouts and newer docker archives)
+    pub digest: Option<String>,
+    /// Digest of the uncompressed layer, from the image configuration
+    pub diff_id: Option<String>,
+    pub size: u64,
+    pub media_type: Option<String>,
+    /// History command that produced the layer
+    pub created_by: Option<String>
+}
+
+/// A build history step; empty layers are steps that only changed metadata
+#[derive(Debug, Clone, Serialize)]
+pub struct OciHistory {
+    pub created: Option<String>,
+    /// Command with any secrets redacted
+    pub created_by: Option<String>,
+    pub empty_layer: bool,
+    pub comment: Option<String>,
+    /// Secret patterns found in the command
+    pub secrets: Vec<String>
+}
+
+/// Configuration, layers and history of a saved image
+#[derive(Debug, Clone, Serialize)]
+pub struct OciReport {
+    pub path: String,
+    /// "docker-archive" (manifest.json) or "oci-layout" (index.json)
+    pub format: String,
+    pub repo_tags: Vec<String>,
+    pub config_digest: Option<String>,
+    pub architecture: Option<String>,
+    pub os: Option<String>,
+    pub created: Option<String>,
+    pub user: Option<String>,
+    pub working_dir: Option<String>,
+    pub entrypoint: Vec<String>,
+    pub cmd: Vec<String>,
+    pub env: Vec<OciEnv>,
+    pub exposed_ports: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    pub layers: Vec<OciLayer>,
+    pub history: Vec<OciHistory>,
+    /// Parts of the archive that could not be read
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for OciReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl OciReport {
+    /// Secrets baked into the image configuration or build history, and images that run as root
+    pub fn findings(&self) -> Vec<Finding> {
+        let target = self.repo_tags.first().cloned().unwrap_or_else(|| self.path.clone());
+        let mut findings = Vec::new();
+
+        let variables: Vec<String> = self.env.iter()
+            .filter_map(|env| env.secret.as_ref().map(|rule| format!("{}={} ({})", env.name, env.value, rule)))
+            .collect();
+        if !variables.is_empty() {
+            findings.push(Finding::new(
+                "oci.secret-env",
+                "Secrets in image environment variables",
+                Severity::High,
+                &target,
+                &variables.join("; "),
+                "Pass secrets at runtime (secret mounts or orchestrator secrets) instead of ENV; anyone who can pull the image can read them"
+            ));
+        }
+
+        let steps: Vec<String> = self.history.iter()
+            .filter(|step| !step.secrets.is_empty())
+            .map(|step| format!("{} ({})", step.created_by.clone().unwrap_or_default(), step.secrets.join(", ")))
+            .collect();
+        if !steps.is_empty() {
+            findings.push(Finding::new(
+                "oci.secret-in-history",
+                "Secrets in image build history",
+                Severity::High,
+                &target,
+                &steps.join("; "),
+                "Use build secrets (RUN --mount=type=secret) rather than ARG or inline values, and rotate the exposed credentials"
+            ));
+        }
+
+        if matches!(self.user.as_deref(), None | Some("") | Some("root") | Some("0") | Some("0:0")) {
+            findings.push(Finding::new(
+                "oci.root-user",
+                "Image runs as root",
+                Severity::Low,
+                &target,
+                &format!("User is {}", self.user.as_deref().filter(|user| !user.is_empty()).unwrap_or("not set")),
+                "Set a non-root USER in the image"
+            ));
+        }
+
+        findings
+    }
+}
+
+/// Options for scanning layer contents
+#[derive(Debug, Clone)]
+pub struct OciScanOptions {
+    /// Layer index to scan; all layers when None
+    pub layer: Option<usize>,
+    /// Indicators matched against file contents and paths; 64 hex digit values match file SHA-256
+    pub iocs: Vec<String>,
+    /// Files larger than this are hashed but their contents not searched
+    pub max_file_size: u64
+}
+
+impl Default for OciScanOptions {
+    fn default() -> Self {
+        OciScanOptions { layer: None, iocs: Vec::new(), max_file_size: DEFAULT_MAX_FILE_SIZE }
+    }
+}
+
+/// What a scan found in one layer
+#[derive(Debug, Clone, Serialize)]
+pub struct OciLayerScan {
+    pub index: usize,
+    pub digest: Option<String>,
+    pub files: u64,
+    pub bytes: u64,
+    /// Files hashed but not searched (binary or over the size limit)
+    pub files_skipped: u64,
+    /// Paths deleted from lower layers by whiteout entries
+    pub whiteouts: Vec<String>,
+    /// Directories whose lower-layer contents are hidden by an opaque whiteout
+    pub opaque_dirs: Vec<String>
+}
+
+/// A secret or indicator found in a layer file
+#[derive(Debug, Clone, Serialize)]
+pub struct OciMatch {
+    pub layer: usize,
+    pub path: String,
+    /// "secret" or "ioc"
+    pub kind: String,
+    /// Secret pattern, or how the indicator matched (content, path, sha256)
+    pub rule: String,
+    pub line: Option<usize>,
+    /// Matched text with secrets redacted
+    pub excerpt: String,
+    /// Later layer whose whiteout deletes the file (it is still recoverable from this layer)
+    pub removed_in_layer: Option<usize>
+}
+
+/// Results of scanning layer contents
+#[derive(Debug, Clone, Serialize)]
+pub struct OciScanReport {
+    pub path: String,
+    pub layers: Vec<OciLayerScan>,
+    pub matches: Vec<OciMatch>,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for OciScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl OciScanReport {
+    /// One finding per file with secrets, and one per indicator match
+    pub fn findings(&self) -> Vec<Finding> {
+        self.matches.iter()
+            .map(|found| {
+                let location = format!("layer {} {}{}", found.layer, found.path, found.line.map(|line| format!(":{}", line)).unwrap_or_default());
+                let removed = found.removed_in_layer.map(|layer| format!(" (deleted by layer {} but still in the image)", layer)).unwrap_or_default();
+
+                if found.kind == "secret" {
+                    Finding::new(
+                        "oci.secret-in-layer",
+                        "Secret in image layer",
+                        Severity::High,
+                        &self.path,
+                        &format!("{}: {} {}{}", location, found.rule, found.excerpt, removed),
+                        "Remove the secret from the build context and rebuild; deleting it in a later layer does not remove it from the image"
+                    )
+                } else {
+                    Finding::new(
+                        "oci.ioc-match",
+                        "Indicator of compromise in image layer",
+                        Severity::High,
+                        &self.path,
+                        &format!("{}: {} match {}{}", location, found.rule, found.excerpt, removed),
+                        "Investigate where the file came from and whether the image or its base is compromised"
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads the configuration, layers and history of a `docker save` or OCI layout tarball
+pub fn mercy_oci<P: AsRef<Path>>(path: P) -> Result<OciReport, String> {
+    let path = path.as_ref();
+    let image = Image::open(Path::new(path))?;
+    let config = image.config.as_ref();
+    let settings = config.and_then(|config| config.get("config"));
+    let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+    let strings = |value: Option<&Value>| -> Vec<String> {
+        value.and_then(Value::as_array).map(|items| items.iter().filter_map(|item| item.as_str().map(str::to_string)).collect()).unwrap_or_default()
+    };
+
+    let env = strings(settings.and_then(|settings| settings.get("Env")))
+        .into_iter()
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((&pair, ""));
+            let secret = secret_env(name, value);
+            OciEnv {
+                name: name.to_string(),
+                value: if secret.is_some() { redact(value) } else { value.to_string() },
+                secret: secret.map(str::to_string)
+            }
+        })
+        .collect();
+
+    let history: Vec<OciHistory> = config.and_then(|config| config.get("history")).and_then(Value::as_array).unwrap_or(&Vec::new())
+        .iter()
+        .map(|step| {
+            let command = text(step.get("created_by"));
+            let matches = command.as_deref().map(find_secrets).unwrap_or_default();
+            OciHistory {
+                created: text(step.get("created")),
+                created_by: command.map(|command| redact_matches(&command, &matches)),
+                empty_layer: step.get("empty_layer").and_then(Value::as_bool).unwrap_or(false),
+                comment: text(step.get("comment")),
+                secrets: matches.iter().map(|found| found.rule.to_string()).collect()
+            }
+        })
+        .collect();
+
+    // Steps that were not empty produced the layers, in order
+    let mut producing = history.iter().filter(|step| !step.empty_layer);
+    let layers = image.layers.iter()
+        .map(|layer| OciLayer { created_by: producing.next().and_then(|step| step.created_by.clone()), ..layer.clone() })
+        .collect();
+
+    let labels = settings.and_then(|settings| settings.get("Labels")).and_then(Value::as_object)
+        .map(|labels| labels.iter().map(|(key, value)| (key.clone(), value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()))).collect())
+        .unwrap_or_default();
+
+    Ok(OciReport {
+        path: path_to_string(path),
+        format: image.format.to_string(),
+        repo_tags: image.repo_tags.clone(),
+        config_digest: image.config_digest.clone(),
+        architecture: text(config.and_then(|config| config.get("architecture"))),
+        os: text(config.and_then(|config| config.get("os"))),
+        created: text(config.and_then(|config| config.get("created"))),
+        user: text(settings.and_then(|settings| settings.get("User"))),
+        working_dir: text(settings.and_then(|settings| settings.get("WorkingDir"))),
+        entrypoint: strings(settings.and_then(|settings| settings.get("Entrypoint"))),
+        cmd: strings(settings.and_then(|settings| settings.get("Cmd"))),
+        env,
+        exposed_ports: settings.and_then(|settings| settings.get("ExposedPorts")).and_then(Value::as_object)
+            .map(|ports| ports.keys().cloned().collect())
+            .unwrap_or_default(),
+        labels,
+        layers,
+        history,
+        notes: image.notes
+    })
+}
+
+/// Streams the files of one or all layers, searching them for secrets and indicators
+pub fn mercy_oci_scan<P: AsRef<Path>>(path: P, options: &OciScanOptions) -> Result<OciScanReport, String> {
+    let path = path.as_ref();
+    let image = Image::open(Path::new(path))?;
+    let mut notes = image.notes.clone();
+
+    if let Some(layer) = options.layer {
+        if layer >= image.layers.len() {
+            return Err(format!("Layer {} does not exist (the image has {} layers)", layer, image.layers.len()));
+        }
+    }
+
+    let iocs: Vec<String> = options.iocs.iter().map(|ioc| ioc.trim().to_lowercase()).filter(|ioc| !ioc.is_empty()).collect();
+    let hashes: HashSet<&str> = iocs.iter().filter(|ioc| ioc.len() == 64 && ioc.bytes().all(|byte| byte.is_ascii_hexdigit())).map(String::as_str).collect();
+    let mut scans = Vec::new();
+    let mut matches = Vec::new();
+
+    for layer in &image.layers {
+        let selected = options.layer.map(|chosen| chosen == layer.index).unwrap_or(true);
+
+        // Layers above the chosen one are walked for their whiteouts only
+        if !selected && options.layer.map(|chosen| layer.index < chosen).unwrap_or(false) {
+            continue;
+        }
+
+        let mut scan = OciLayerScan {
+            index: layer.index,
+            digest: layer.digest.clone().or_else(|| layer.diff_id.clone()),
+            files: 0,
+            bytes: 0,
+            files_skipped: 0,
+            whiteouts: Vec::new(),
+            opaque_dirs: Vec::new()
+        };
+
+        let mut tar = match image.layer_reader(layer) {
+            Ok(tar) => tar,
+            Err(e) => {
+                notes.push(format!("Layer {}: {}", layer.index, e));
+                continue;
+            }
+        };
+
+        loop {
+            let entry = match tar.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    notes.push(format!("Layer {}: {}", layer.index, e));
+                    break;
+                }
+            };
+
+            let path = normalize(&entry.path);
+            let (parent, name) = path.rsplit_once('/').unwrap_or(("", &path));
+
+            if name == ".wh..wh..opq" {
+                scan.opaque_dirs.push(parent.to_string());
+                continue;
+            }
+            if let Some(deleted) = name.strip_prefix(".wh.") {
+                scan.whiteouts.push(if parent.is_empty() { deleted.to_string() } else { format!("{}/{}", parent, deleted) });
+                continue;
+            }
+
+            if !selected || !entry.is_file() {
+                continue;
+            }
+
+            scan.files += 1;
+            scan.bytes += entry.size;
+
+            let lowered = path.to_lowercase();
+            for ioc in iocs.iter().filter(|ioc| !hashes.contains(ioc.as_str()) && lowered.contains(ioc.as_str())) {
+                matches.push(layer_match(layer.index, &path, "ioc", "path", None, ioc.clone()));
+            }
+
+            match scan_file(&mut tar, &entry, &path, layer.index, &iocs, &hashes, options.max_file_size) {
+                Ok((found, searched)) => {
+                    matches.extend(found);
+                    if !searched {
+                        scan.files_skipped += 1;
+                    }
+                }
+                Err(e) => {
+                    notes.push(format!("Layer {} {}: {}", layer.index, path, e));
+                    break;
+                }
+            }
+        }
+
+        if selected || !scan.whiteouts.is_empty() || !scan.opaque_dirs.is_empty() {
+            scans.push((selected, scan));
+        }
+    }
+
+    // A whiteout or opaque directory in a later layer deletes the file from the merged view
+    for found in &mut matches {
+        found.removed_in_layer = scans.iter()
+            .map(|(_, scan)| scan)
+            .filter(|scan| scan.index > found.layer)
+            .find(|scan| {
+                scan.whiteouts.iter().any(|deleted| found.path == *deleted || found.path.starts_with(&format!("{}/", deleted)))
+                    || scan.opaque_dirs.iter().any(|dir| found.path.starts_with(&format!("{}/", dir)))
+            })
+            .map(|scan| scan.index);
+    }
+
+    Ok(OciScanReport {
+        path: path_to_string(path),
+        layers: scans.into_iter().filter(|(selected, _)| *selected).map(|(_, scan)| scan).collect(),
+        matches,
+        notes
+    })
+}
+
+/// Scan driven by "image.tar layer=2 iocs=indicators.txt max_file_size=10485760"
+pub(crate) fn oci_scan_from_args(input: &str) -> Result<OciScanReport, String> {
+    let (path, pairs) = trailing_options(input, &["layer", "iocs", "max_file_size"]);
+    let mut options = OciScanOptions::default();
+
+    for (key, value) in pairs {
+        let number = || value.parse::<u64>().map_err(|_| format!("Invalid {} '{}'", key, value));
+        match key {
+            "layer" => options.layer = Some(number()? as usize),
+            "iocs" => options.iocs = read_text_lines(value).map_err(|e| format!("Unable to read {}: {}", value, e))?,
+            _ => options.max_file_size = number()?
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No image archive specified".to_string());
+    }
+
+    mercy_oci_scan(&path, &options)
+}
+
+// Hashes a layer file and searches its text; returns the matches and whether it was searched
+fn scan_file<R: TarSource>(
+    tar: &mut TarReader<R>,
+    entry: &TarEntry,
+    path: &str,
+    layer: usize,
+    iocs: &[String],
+    hashes: &HashSet<&str>,
+    max_file_size: u64
+) -> Result<(Vec<OciMatch>, bool), String> {
+    let mut data = tar.data();
+    let mut hasher = Sha256::new();
+    let mut content = Vec::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let searchable = entry.size <= max_file_size;
+
+    loop {
+        let count = data.read(&mut buffer).map_err(|e| e.to_string())?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+        if searchable {
+            content.extend_from_slice(&buffer[..count]);
+        }
+
+        // Without hash indicators there is no reason to read past the searchable part
+        if !searchable && hashes.is_empty() {
+            break;
+        }
+    }
+
+    let mut matches = Vec::new();
+    if !hashes.is_empty() {
+        let digest = format!("{:x}", hasher.finalize());
+        if hashes.contains(digest.as_str()) {
+            matches.push(layer_match(layer, path, "ioc", "sha256", None, digest));
+        }
+    }
+
+    if !searchable || content[..content.len().min(BINARY_SNIFF)].contains(&0) {
+        return Ok((matches, false));
+    }
+
+    let text = String::from_utf8_lossy(&content);
+    for (number, line) in text.lines().enumerate() {
+        let found = find_secrets(line);
+        for secret in &found {
+            matches.push(layer_match(layer, path, "secret", secret.rule, Some(number + 1), excerpt(line, secret)));
+        }
+
+        if !iocs.is_empty() {
+            let lowered = line.to_lowercase();
+            for ioc in iocs.iter().filter(|ioc| !hashes.contains(ioc.as_str()) && lowered.contains(ioc.as_str())) {
+                let excerpt: String = redact_matches(line, &found).trim().chars().take(200).collect();
+                matches.push(layer_match(layer, path, "ioc", "content", Some(number + 1), format!("{} in {}", ioc, excerpt)));
+            }
+        }
+    }
+
+    Ok((matches, true))
+}
+
+fn layer_match(layer: usize, path: &str, kind: &str, rule: &str, line: Option<usize>, excerpt: String) -> OciMatch {
+    OciMatch { layer, path: path_to_string(path), kind: kind.to_string(), rule: rule.to_string(), line, excerpt, removed_in_layer: None }
+}
+
+// Archive paths without "./" or leading slashes, with ".." resolved
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part)
+        }
+    }
+    parts.join("/")
+}
+
+// A layer blob opened for streaming, decompressed if needed
+enum LayerStream {
+    Plain(BufReader<Take<File>>),
+    Gzip(Inflater<BufReader<Take<File>>>)
+}
+
+impl Read for LayerStream {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            LayerStream::Plain(reader) => reader.read(buffer),
+            LayerStream::Gzip(reader) => reader.read(buffer)
+        }
+    }
+}
+
+impl TarSource for LayerStream {}
+
+// The parts of the archive the reports are built from
+struct Image {
+    path: String,
+    format: &'static str,
+    entries: HashMap<String, TarEntry>,
+    repo_tags: Vec<String>,
+    config_digest: Option<String>,
+    config: Option<Value>,
+    layers: Vec<OciLayer>,
+    notes: Vec<String>
+}
+
+impl Image {
+    fn open(path: &Path) -> Result<Image, String> {
+        let file = File::open(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        let mut tar = TarReader::new(file);
+        let mut entries = HashMap::new();
+
+        while let Some(entry) = tar.next_entry()? {
+            entries.insert(normalize(&entry.path), entry);
+        }
+
+        let mut image = Image {
+            path: path_to_string(path),
+            format: "docker-archive",
+            entries,
+            repo_tags: Vec::new(),
+            config_digest: None,
+            config: None,
+            layers: Vec::new(),
+            notes: Vec::new()
+        };
+
+        if image.entries.contains_key("manifest.json") {
+            image.docker_manifest()?;
+        } else if image.entries.contains_key("index.json") {
+            image.format = "oci-layout";
+            image.oci_index()?;
+        } else {
+            return Err("Not an image archive: no manifest.json or index.json".to_string());
+        }
+
+        Ok(image)
+    }
+
+    // manifest.json: [{"Config": ..., "RepoTags": [...], "Layers": [...]}]
+    fn docker_manifest(&mut self) -> Result<(), String> {
+        let manifest = self.json("manifest.json")?;
+        let images = manifest.as_array().ok_or("manifest.json is not an array")?;
+        let first = images.first().ok_or("manifest.json lists no images")?;
+
+        if images.len() > 1 {
+            self.notes.push(format!("Archive holds {} images; reporting the first", images.len()));
+        }
+
+        self.repo_tags = first.get("RepoTags").and_then(Value::as_array)
+            .map(|tags| tags.iter().filter_map(|tag| tag.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+
+        if let Some(config) = first.get("Config").and_then(Value::as_str) {
+            self.config_digest = blob_digest(config);
+            match self.json(config) {
+                Ok(value) => self.config = Some(value),
+                Err(e) => self.notes.push(e)
+            }
+        }
+
+        // Layer sources in newer archives carry the blob descriptors
+        let sources = first.get("LayerSources").and_then(Value::as_object);
+        let layers: Vec<String> = first.get("Layers").and_then(Value::as_array)
+            .map(|layers| layers.iter().filter_map(|layer| layer.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+
+        for (index, layer) in layers.iter().enumerate() {
+            let digest = blob_digest(layer);
+            let media_type = digest.as_ref()
+                .and_then(|digest| sources.and_then(|sources| sources.get(digest)))
+                .and_then(|source| source.get("mediaType"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            self.push_layer(index, layer, digest, media_type);
+        }
+
+        Ok(())
+    }
+
+    // index.json -> (nested indexes) -> image manifest -> config and layer blobs
+    fn oci_index(&mut self) -> Result<(), String> {
+        let index = self.json("index.json")?;
+        let mut manifest = index;
+
+        for _ in 0..8 {
+            let descriptors = match manifest.get("manifests").and_then(Value::as_array) {
+                Some(descriptors) => descriptors.clone(),
+                None => break
+            };
+
+            // Attestation manifests are listed with an unknown platform
+            let descriptor = descriptors.iter()
+                .find(|descriptor| descriptor.pointer("/platform/os").and_then(Value::as_str) != Some("unknown"))
+                .or_else(|| descriptors.first())
+                .ok_or("OCI index lists no manifests")?;
+
+            if let Some(annotations) = descriptor.get("annotations").and_then(Value::as_object) {
+                for key in ["io.containerd.image.name", "org.opencontainers.image.ref.name"] {
+                    if let Some(name) = annotations.get(key).and_then(Value::as_str) {
+                        if !self.repo_tags.iter().any(|tag| tag == name) {
+                            self.repo_tags.push(name.to_string());
+                        }
+                    }
+                }
+            }
+
+            let digest = descriptor.get("digest").and_then(Value::as_str).ok_or("OCI descriptor has no digest")?;
+            manifest = self.json(&digest_path(digest)?)?;
+        }
+
+        let layers = manifest.get("layers").and_then(Value::as_array).ok_or("OCI image manifest lists no layers")?.clone();
+
+        if let Some(digest) = manifest.pointer("/config/digest").and_then(Value::as_str) {
+            self.config_digest = Some(digest.to_string());
+            match digest_path(digest).and_then(|path| self.json(&path)) {
+                Ok(value) => self.config = Some(value),
+                Err(e) => self.notes.push(e)
+            }
+        }
+
+        for (index, layer) in layers.iter().enumerate() {
+            let digest = layer.get("digest").and_then(Value::as_str).ok_or_else(|| format!("Layer {} descriptor has no digest", index))?;
+            let media_type = layer.get("mediaType").and_then(Value::as_str).map(str::to_string);
+            self.push_layer(index, &digest_path(digest)?, Some(digest.to_string()), media_type);
+        }
+
+        Ok(())
+    }
+
+    fn push_layer(&mut self, index: usize, path: &str, digest: Option<String>, media_type: Option<String>) {
+        let size = match self.resolve(path) {
+            Some(entry) => entry.size,
+            None => {
+                self.notes.push(format!("Layer {} ({}) is missing from the archive", index, path));
+                0
+            }
+        };
+
+        let diff_id = self.config.as_ref()
+            .and_then(|config| config.pointer(&format!("/rootfs/diff_ids/{}", index)))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        self.layers.push(OciLayer { index, path: path_to_string(path), digest, diff_id, size, media_type, created_by: None });
+    }
+
+    // Follows the symlinks and hard links docker uses to share identical layers
+    fn resolve(&self, path: &str) -> Option<&TarEntry> {
+        let mut path = normalize(path);
+
+        for _ in 0..8 {
+            let entry = self.entries.get(&path)?;
+            match entry.kind {
+                b'2' => {
+                    let parent = path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+                    path = if entry.link.starts_with('/') { normalize(&entry.link) } else { normalize(&format!("{}/{}", parent, entry.link)) };
+                }
+                b'1' => path = normalize(&entry.link),
+                _ => return Some(entry)
+            }
+        }
+
+        None
+    }
+
+    fn open_entry(&self, path: &str) -> Result<(File, &TarEntry), String> {
+        let entry = self.resolve(path).ok_or_else(|| format!("{} is missing from the archive", path))?;
+        let mut file = File::open(&self.path).map_err(|e| format!("Unable to read {}: {}", self.path, e))?;
+        file.seek(SeekFrom::Start(entry.data_offset)).map_err(|e| e.to_string())?;
+        Ok((file, entry))
+    }
+
+    fn json(&self, path: &str) -> Result<Value, String> {
+        let (file, entry) = self.open_entry(path)?;
+        if entry.size > MAX_JSON {
+            return Err(format!("{} is larger than {} bytes", path, MAX_JSON));
+        }
+
+        let mut data = Vec::new();
+        file.take(entry.size).read_to_end(&mut data).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        serde_json::from_slice(&data).map_err(|e| format!("{} is not valid JSON: {}", path, e))
+    }
+
+    fn layer_reader(&self, layer: &OciLayer) -> Result<TarReader<LayerStream>, String> {
+        let (file, entry) = self.open_entry(&layer.path)?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file.take(entry.size));
+        let magic = reader.fill_buf().map_err(|e| e.to_string())?;
+
+        let stream = if magic.starts_with(&[0x1F, 0x8B]) {
+            LayerStream::Gzip(gzip_reader(reader)?)
+        } else if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            return Err("zstd compressed layers are not supported".to_string());
+        } else {
+            LayerStream::Plain(reader)
+        };
+
+        Ok(TarReader::new(stream))
+    }
+}
+
+// "blobs/sha256/<hex>" for a digest, refusing anything that could escape the blob directory
+fn digest_path(digest: &str) -> Result<String, String> {
+    match digest.split_once(':') {
+        Some((algorithm, hex)) if !algorithm.is_empty() && !hex.is_empty() && hex.bytes().all(|byte| byte.is_ascii_alphanumeric()) => {
+            Ok(format!("blobs/{}/{}", algorithm, hex))
+        }
+        _ => Err(format!("Invalid digest '{}'", digest))
+    }
+}
+
+// Digest implied by a blob path ("blobs/sha256/<hex>" or "<hex>.json")
+fn blob_digest(path: &str) -> Option<String> {
+    let path = normalize(path);
+    if let Some(rest) = path.strip_prefix("blobs/") {
+        let (algorithm, hex) = rest.split_once('/')?;
+        return Some(format!("{}:{}", algorithm, hex));
+    }
+
+    let hex = path.strip_suffix(".json")?;
+    (hex.len() == 64 && hex.bytes().all(|byte| byte.is_ascii_hexdigit())).then(|| format!("sha256:{}", hex))
+}
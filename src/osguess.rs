@@ -0,0 +1,253 @@
+/*
+    Coarse OS family guess from the IP TTL and TCP window size
+
+    Operating systems start packets at a few well known TTLs (64, 128, 255) which routers then
+    decrement, so the observed TTL rounded up to the next common value hints at the sender's
+    family. It is a heuristic: the report always carries the observations and reasoning.
+*/
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    checks::resolve_target,
+    icmp::{self, IcmpAccess},
+    ping::echo_once
+};
+
+// Initial TTLs used by common stacks, ascending
+const INITIAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+
+const OBSERVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The observations, inference and reasoning behind an OS family guess
+#[derive(Debug, Clone, Serialize)]
+pub struct OsGuess {
+    pub target: Option<String>,
+    /// How the observations were made ("supplied", "tcp syn-ack (raw socket)", "icmp echo reply")
+    pub source: String,
+    pub observed_ttl: u8,
+    pub initial_ttl: u8,
+    /// Estimated router hops between the target and us
+    pub hops: u8,
+    pub window: Option<u16>,
+    pub family: String,
+    /// "low", "medium" or "high"
+    pub confidence: String,
+    pub reasoning: Vec<String>
+}
+
+impl fmt::Display for OsGuess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(target) = &self.target {
+            writeln!(f, "OS guess for {}", target)?;
+        }
+
+        writeln!(f, "Guess: {} (confidence: {})", self.family, self.confidence)?;
+        writeln!(f, "Observed via: {}", self.source)?;
+        writeln!(f, "Observed TTL: {}", self.observed_ttl)?;
+        writeln!(f, "Inferred initial TTL: {}", self.initial_ttl)?;
+        writeln!(f, "Estimated hops: {}", self.hops)?;
+
+        if let Some(window) = self.window {
+            writeln!(f, "TCP window: {}", window)?;
+        }
+
+        for reason in &self.reasoning {
+            writeln!(f, "- {}", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Guesses the OS family from an observed TTL and, optionally, the SYN-ACK TCP window size
+pub fn os_guess_from(ttl: u8, window: Option<u16>) -> OsGuess {
+    let initial_ttl = INITIAL_TTLS.iter().copied().find(|initial| *initial >= ttl).unwrap_or(255);
+    let hops = initial_ttl - ttl;
+
+    let family = match initial_ttl {
+        32 => "Legacy Windows (95/98/NT) or embedded device",
+        64 => "Linux / Unix (Linux, BSD, macOS, Android)",
+        128 => "Windows",
+        _ => "Network device (Cisco IOS, Solaris/AIX or similar)"
+    };
+
+    let mut reasoning = vec![format!(
+        "Observed TTL {} rounds up to the common initial TTL {} ({} hops away); initial TTL {} is typical of {}",
+        ttl, initial_ttl, hops, initial_ttl, family
+    )];
+
+    // TTL alone is the baseline; a window size that agrees raises confidence, one that disagrees lowers it
+    let mut score: i8 = if hops <= 20 { 1 } else { 0 };
+
+    if hops > 20 {
+        reasoning.push(format!("{} hops is unusually far; the TTL may have started at a non-default value", hops));
+    }
+
+    if let Some(window) = window {
+        match window_family(window) {
+            Some((hint, hint_ttl)) if hint_ttl == initial_ttl => {
+                score += 1;
+                reasoning.push(format!("TCP window {} is typical of {}, which agrees with the TTL", window, hint));
+            }
+            Some((hint, _)) => {
+                score -= 1;
+                reasoning.push(format!("TCP window {} is typical of {}, which contradicts the TTL", window, hint));
+            }
+            None => reasoning.push(format!("TCP window {} is not a distinctive default", window))
+        }
+    }
+
+    let confidence = match score {
+        s if s >= 2 => "high",
+        1 => "medium",
+        _ => "low"
+    };
+
+    OsGuess {
+        target: None,
+        source: "supplied".to_string(),
+        observed_ttl: ttl,
+        initial_ttl,
+        hops,
+        window,
+        family: family.to_string(),
+        confidence: confidence.to_string(),
+        reasoning
+    }
+}
+
+// Default SYN-ACK windows of common stacks, with the initial TTL that family uses
+fn window_family(window: u16) -> Option<(&'static str, u8)> {
+    match window {
+        5720 | 5840 | 14600 | 26883 | 28960 | 29200 | 42340 | 43440 | 65160 => Some(("Linux", 64)),
+        8192 | 64240 => Some(("Windows", 128)),
+        4128 => Some(("Cisco IOS", 255)),
+        16384 | 65535 => Some(("BSD / macOS", 64)),
+        _ => None
+    }
+}
+
+/// Makes one TCP connection to `target` ("host:port", port 80 by default) and guesses the OS from
+/// the SYN-ACK TTL and window (raw sockets, IPv4, Linux) or otherwise from an ICMP echo reply TTL
+pub fn mercy_os_guess(target: &str) -> Result<OsGuess, String> {
+    let addr = resolve_target(target, 80)?;
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some((ttl, window)) = observe_syn_ack(addr)? {
+            let mut guess = os_guess_from(ttl, Some(window));
+            guess.target = Some(addr.to_string());
+            guess.source = "tcp syn-ack (raw socket)".to_string();
+            return Ok(guess);
+        }
+    }
+
+    match observe_echo_ttl(addr.ip())? {
+        Some(ttl) => {
+            let mut guess = os_guess_from(ttl, None);
+            guess.target = Some(addr.to_string());
+            guess.source = "icmp echo reply".to_string();
+            guess.reasoning.push("No TCP window available (raw TCP capture not possible here)".to_string());
+            Ok(guess)
+        }
+        None => Err(format!("Unable to observe a TTL for {}; supply observations instead (\"ttl=117 window=64240\")", addr))
+    }
+}
+
+// Captures the SYN-ACK of a normal connect on a raw TCP socket; None when raw sockets are unavailable
+#[cfg(target_os = "linux")]
+fn observe_syn_ack(addr: SocketAddr) -> Result<Option<(u8, u16)>, String> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => return Ok(None)
+    };
+
+    let sniffer = match Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)) {
+        Ok(socket) => socket,
+        Err(_) => return Ok(None)
+    };
+
+    let stream = std::net::TcpStream::connect_timeout(&addr, OBSERVE_TIMEOUT).map_err(|e| format!("Unable to connect to {}: {}", addr, e))?;
+    let local_port = stream.local_addr().map_err(|e| e.to_string())?.port();
+    let deadline = std::time::Instant::now() + OBSERVE_TIMEOUT;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() || !icmp::wait_readable(&sniffer, remaining).map_err(|e| e.to_string())? {
+            return Ok(None);
+        }
+
+        let mut buffer = [std::mem::MaybeUninit::<u8>::uninit(); 1500];
+        let len = sniffer.recv(&mut buffer).map_err(|e| format!("Raw receive failed: {}", e))?;
+        // recv initialised the first `len` bytes
+        let packet: Vec<u8> = buffer[..len].iter().map(|b| unsafe { b.assume_init() }).collect();
+
+        if let Some((ttl, window)) = parse_syn_ack(&packet, ip, addr.port(), local_port) {
+            return Ok(Some((ttl, window)));
+        }
+    }
+}
+
+// Returns (TTL, window) when `packet` is the SYN-ACK from ip:port to our local port
+#[cfg(target_os = "linux")]
+fn parse_syn_ack(packet: &[u8], ip: std::net::Ipv4Addr, port: u16, local_port: u16) -> Option<(u8, u16)> {
+    let header_len = ((*packet.first()? & 0x0F) as usize) * 4;
+    if *packet.first()? >> 4 != 4 || header_len < 20 || packet.get(12..16)? != ip.octets() {
+        return None;
+    }
+
+    let tcp = packet.get(header_len..header_len + 20)?;
+    let src_port = u16::from_be_bytes([tcp[0], tcp[1]]);
+    let dst_port = u16::from_be_bytes([tcp[2], tcp[3]]);
+
+    if src_port != port || dst_port != local_port || tcp[13] & 0x12 != 0x12 {
+        return None;
+    }
+
+    Some((packet[8], u16::from_be_bytes([tcp[14], tcp[15]])))
+}
+
+// Reads the reply TTL of one ICMP echo; only raw IPv4 sockets expose it
+fn observe_echo_ttl(address: IpAddr) -> Result<Option<u8>, String> {
+    if address.is_ipv6() || icmp::icmp_access(false) != IcmpAccess::Raw {
+        return Ok(None);
+    }
+
+    let socket = icmp::open_icmp(false, true).map_err(|e| format!("Unable to open ICMP socket: {}", e))?;
+    let ident = (std::process::id() & 0xFFFF) as u16;
+
+    Ok(echo_once(&socket, true, address, ident, 1, OBSERVE_TIMEOUT)?.and_then(|(_, ttl)| ttl))
+}
+
+// Parses "ttl=117 window=64240" for the string dispatcher; anything else is a target
+pub(crate) fn os_guess_from_args(input: &str) -> Result<OsGuess, String> {
+    if !input.contains("ttl=") {
+        return mercy_os_guess(input);
+    }
+
+    let mut ttl = None;
+    let mut window = None;
+
+    for part in input.split_whitespace() {
+        match part.split_once('=') {
+            Some(("ttl", value)) => ttl = Some(value.parse::<u8>().map_err(|_| format!("Invalid TTL '{}'", value))?),
+            Some(("window", value)) => window = Some(value.parse::<u16>().map_err(|_| format!("Invalid window '{}'", value))?),
+            _ => return Err(format!("Unknown os_guess option '{}'", part))
+        }
+    }
+
+    match ttl {
+        Some(0) => Err("TTL must be at least 1".to_string()),
+        Some(ttl) => Ok(os_guess_from(ttl, window)),
+        None => Err("No TTL specified".to_string())
+    }
+}
@@ -0,0 +1,280 @@
+/*
+    Client-side pacing for login testing and API probing
+
+    `MercyPacer` is a token bucket: it holds up to `burst` tokens, gains `rate` tokens per
+    second, and every operation takes one. A lockout policy of 5 attempts per 15 minutes is
+    kept with `MercyPacer::new(5.0 / 900.0, 1)`; a burst above 1 lets a quiet pacer run that
+    many operations back to back before settling at the rate.
+
+    Blocking and async acquires reserve their token before they wait, so threads and tasks
+    sharing one pacer (it is Sync; put it in an Arc) are served in the order they asked and
+    never run more operations than the bucket allows between them. Jitter adds a random
+    extra delay of up to the given length to each blocking or async acquire, so the timing
+    of requests is not perfectly regular; it only ever slows operations down.
+
+    Every time-dependent method has an `_at` form taking the current instant, which makes
+    the arithmetic testable with a simulated clock.
+*/
+
+use std::{
+    fmt,
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant}
+};
+
+use crate::rng::MercyRng;
+
+/// Token bucket pacing operations to `rate` per second with bursts of up to `burst`
+pub struct MercyPacer {
+    rate: f64,
+    burst: f64,
+    jitter: Duration,
+    state: Mutex<Bucket>
+}
+
+struct Bucket {
+    // Negative while acquires wait for tokens they have reserved
+    tokens: f64,
+    updated: Instant,
+    rng: MercyRng
+}
+
+impl Bucket {
+    // Adds the tokens earned since the last update, up to the burst
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        if now > self.updated {
+            self.tokens = (self.tokens + (now - self.updated).as_secs_f64() * rate).min(burst);
+            self.updated = now;
+        }
+    }
+}
+
+impl fmt::Debug for MercyPacer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MercyPacer").field("rate", &self.rate).field("burst", &self.burst).field("jitter", &self.jitter).finish()
+    }
+}
+
+impl MercyPacer {
+    /// A pacer allowing `rate` operations per second and `burst` at once, starting full
+    pub fn new(rate: f64, burst: u32) -> Result<MercyPacer, String> {
+        MercyPacer::new_at(rate, burst, Instant::now())
+    }
+
+    /// The same, created at `now`
+    pub fn new_at(rate: f64, burst: u32, now: Instant) -> Result<MercyPacer, String> {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("Invalid rate {} (expected operations per second above 0)", rate));
+        }
+        if burst == 0 {
+            return Err("Invalid burst 0 (expected at least 1)".to_string());
+        }
+
+        Ok(MercyPacer {
+            rate,
+            burst: burst as f64,
+            jitter: Duration::ZERO,
+            state: Mutex::new(Bucket { tokens: burst as f64, updated: now, rng: MercyRng::os() })
+        })
+    }
+
+    /// Adds a random delay of up to `jitter` to every blocking and async acquire; `seed` makes the delays repeatable
+    pub fn with_jitter(mut self, jitter: Duration, seed: Option<u64>) -> MercyPacer {
+        self.jitter = jitter;
+        self.bucket().rng = MercyRng::from_seed(seed);
+        self
+    }
+
+    /// Operations per second
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst as u32
+    }
+
+    /// Waits until an operation may run
+    pub fn acquire(&self) {
+        let wait = self.reserve_at(Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+
+    /// Waits until an operation may run, without blocking the async runtime's thread
+    pub async fn acquire_async(&self) {
+        let wait = self.reserve_at(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available now, never waiting and never adding jitter
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    pub fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut bucket = self.bucket();
+        bucket.refill(now, self.rate, self.burst);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Takes a token at `now` and returns how long to wait (jitter included) before using it
+    pub fn reserve_at(&self, now: Instant) -> Duration {
+        let mut bucket = self.bucket();
+        bucket.refill(now, self.rate, self.burst);
+        bucket.tokens -= 1.0;
+
+        // Another thread may have refilled at a later instant than ours
+        let behind = bucket.updated.saturating_duration_since(now);
+        let mut wait = behind + self.wait_for(bucket.tokens);
+
+        if !self.jitter.is_zero() {
+            let fraction = bucket.rng.next_u64() as f64 / u64::MAX as f64;
+            wait += self.jitter.mul_f64(fraction);
+        }
+
+        wait
+    }
+
+    /// How long from now each of the next `count` operations would have to wait, without taking any tokens or adding jitter
+    pub fn schedule(&self, count: usize) -> Vec<Duration> {
+        self.schedule_at(count, Instant::now())
+    }
+
+    pub fn schedule_at(&self, count: usize, now: Instant) -> Vec<Duration> {
+        let (mut tokens, behind) = {
+            let bucket = self.bucket();
+            let earned = now.saturating_duration_since(bucket.updated).as_secs_f64() * self.rate;
+            ((bucket.tokens + earned).min(self.burst), bucket.updated.saturating_duration_since(now))
+        };
+
+        (0..count).map(|_| {
+            tokens -= 1.0;
+            behind + self.wait_for(tokens)
+        }).collect()
+    }
+
+    // Time until a balance of `tokens` (after taking one) climbs back to 0
+    fn wait_for(&self, tokens: f64) -> Duration {
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.rate)
+        }
+    }
+
+    // The bucket, recovered if a thread panicked while holding it (its numbers are always consistent)
+    fn bucket(&self) -> MutexGuard<'_, Bucket> {
+        match self.state.lock() {
+            Ok(bucket) => bucket,
+            Err(poisoned) => poisoned.into_inner()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::Arc};
+
+    use super::*;
+
+    // Whole microseconds, which absorbs the rounding of the float arithmetic
+    fn micros(duration: Duration) -> u128 {
+        (duration.as_nanos() + 500) / 1000
+    }
+
+    #[test]
+    fn a_simulated_clock_sees_the_rate_and_the_burst() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let pacer = MercyPacer::new_at(10.0, 3, start).expect("pacer");
+
+        // The burst runs at once, then a token comes every 100 ms
+        assert!((0..3).all(|_| pacer.try_acquire_at(start)));
+        assert!(!pacer.try_acquire_at(start));
+        assert!(!pacer.try_acquire_at(at(99)));
+        assert!(pacer.try_acquire_at(at(100)));
+        assert!(!pacer.try_acquire_at(at(150)));
+        assert!(pacer.try_acquire_at(at(200)));
+
+        // A long quiet spell refills only up to the burst
+        assert!((0..3).all(|_| pacer.try_acquire_at(at(10_000))));
+        assert!(!pacer.try_acquire_at(at(10_000)));
+
+        // Reservations queue up behind each other
+        assert_eq!(micros(pacer.reserve_at(at(10_000))), 100_000);
+        assert_eq!(micros(pacer.reserve_at(at(10_000))), 200_000);
+        assert_eq!(micros(pacer.reserve_at(at(10_050))), 250_000);
+    }
+
+    #[test]
+    fn schedules_take_no_tokens() {
+        let start = Instant::now();
+        let pacer = MercyPacer::new_at(4.0, 2, start).expect("pacer");
+
+        let schedule: Vec<u128> = pacer.schedule_at(5, start).into_iter().map(micros).collect();
+        assert_eq!(schedule, [0, 0, 250_000, 500_000, 750_000]);
+        assert_eq!(pacer.schedule_at(5, start).len(), 5);
+
+        // Half a second on, the two tokens are back and nothing was taken meanwhile
+        assert!(pacer.try_acquire_at(start + Duration::from_millis(500)));
+        let schedule: Vec<u128> = pacer.schedule_at(3, start + Duration::from_millis(500)).into_iter().map(micros).collect();
+        assert_eq!(schedule, [0, 250_000, 500_000]);
+    }
+
+    #[test]
+    fn jitter_only_ever_adds_delay() {
+        let start = Instant::now();
+        let jitter = Duration::from_millis(30);
+        let pacer = MercyPacer::new_at(1000.0, 1, start).expect("pacer").with_jitter(jitter, Some(7));
+        let repeat = MercyPacer::new_at(1000.0, 1, start).expect("pacer").with_jitter(jitter, Some(7));
+
+        for n in 0..50u32 {
+            let wait = pacer.reserve_at(start);
+            let paced = Duration::from_millis(u64::from(n));
+            assert!(micros(wait) >= micros(paced) && wait <= paced + jitter, "{}: {:?}", n, wait);
+            assert_eq!(wait, repeat.reserve_at(start));
+        }
+    }
+
+    #[test]
+    fn threads_sharing_a_pacer_each_get_their_own_slot() {
+        let start = Instant::now();
+        let pacer = Arc::new(MercyPacer::new_at(100.0, 1, start).expect("pacer"));
+
+        let waits: Vec<u128> = (0..8).map(|_| {
+            let pacer = Arc::clone(&pacer);
+            thread::spawn(move || (0..25).map(|_| micros(pacer.reserve_at(start))).collect::<Vec<_>>())
+        }).collect::<Vec<_>>().into_iter().flat_map(|worker| worker.join().expect("worker")).collect();
+
+        // 200 reservations at one instant: no two share a slot and none is skipped
+        let slots: BTreeSet<u128> = waits.iter().copied().collect();
+        assert_eq!(slots.len(), 200);
+        assert!(slots.iter().enumerate().all(|(n, wait)| *wait == n as u128 * 10_000), "{:?}", slots);
+    }
+
+    #[test]
+    fn blocking_acquires_from_threads_keep_to_the_rate() {
+        let pacer = Arc::new(MercyPacer::new(200.0, 1).expect("pacer"));
+        let started = Instant::now();
+
+        let workers: Vec<_> = (0..4).map(|_| {
+            let pacer = Arc::clone(&pacer);
+            thread::spawn(move || (0..10).for_each(|_| pacer.acquire()))
+        }).collect();
+        workers.into_iter().for_each(|worker| worker.join().expect("worker"));
+
+        // 40 operations at 200 a second with a burst of 1 need 39 intervals of 5 ms
+        assert!(started.elapsed() >= Duration::from_millis(195), "{:?}", started.elapsed());
+    }
+}
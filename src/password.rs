@@ -0,0 +1,380 @@
+/*
+    Password strength and breach checks for password audits
+
+    `password_strength` works offline. It counts the character classes used, then looks for
+    what cracking rules try first: words from a short built-in list of common passwords and
+    their parts (also spelled with "@" for "a", "0" for "o" and the like), keyboard walks
+    ("qwerty", "1qaz"), alphabetic and numeric sequences, years from 1900 to 2099 and runs of
+    one character. Each character outside a pattern is worth log2 of the size of the
+    character classes in use; each pattern is worth only the bits needed to pick it from its
+    kind. The sum is the entropy estimate, and the verdict follows from it: weak below 40
+    bits or under 8 characters, fair below 65 bits, strong otherwise.
+
+    `hibp_check` asks the Have I Been Pwned range API with k-anonymity: the password is hashed
+    with SHA-1 here, only the first five hex digits of the hash are sent, and the suffixes
+    returned for that prefix are compared locally. Neither the password nor its full hash
+    leaves the host. Responses are requested with padding, so their size does not hint at
+    the prefix either. Rate limits and failed requests are retried (see net.rs).
+*/
+
+use std::{
+    fmt,
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    config,
+    digests::Sha1,
+    endpoints::service_endpoints,
+    http::{HttpIdentity, IDENTITY_KEYS, http_client_builder, identity_option},
+    net,
+    radix::hex_encode,
+    runtime,
+    trailing_options
+};
+
+// Common passwords and the words they are built from, lowercase
+const COMMON_WORDS: &[&str] = &[
+    "password", "passwd", "pass", "admin", "administrator", "root", "toor", "login", "welcome", "letmein",
+    "qwerty", "secret", "master", "dragon", "monkey", "shadow", "sunshine", "princess", "football", "baseball",
+    "soccer", "hockey", "iloveyou", "love", "trustno1", "superman", "batman", "starwars", "whatever", "freedom",
+    "hello", "charlie", "michael", "jordan", "jennifer", "thomas", "robert", "daniel", "andrew", "jessica",
+    "ashley", "summer", "winter", "spring", "autumn", "flower", "cookie", "cheese", "pepper", "ginger",
+    "orange", "banana", "chocolate", "computer", "internet", "server", "system", "access", "changeme", "default",
+    "guest", "user", "test", "demo", "temp", "office", "company", "service", "support", "backup",
+    "mustang", "ferrari", "corvette", "harley", "matrix", "killer", "hunter", "ranger", "tigger", "buster",
+    "ninja", "pokemon", "minecraft", "abc", "azerty", "zxcvbn", "asdf", "secure", "private", "shopping",
+    "angel", "lovely", "family", "friend", "forever", "heaven", "magic", "silver", "golden", "diamond",
+    "purple", "yellow", "player", "gamer", "rocky", "lucky", "happy", "money", "power", "blessed",
+    "january", "february", "march", "april", "june", "july", "august", "september", "october", "november",
+    "december", "monday", "friday", "sunday", "weekend", "apple", "google", "microsoft", "windows", "linux"
+];
+
+// Keys in keyboard order, rows and columns, for walks in either direction
+const KEYBOARD_RUNS: &[&str] = &[
+    "`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./", "~!@#$%^&*()_+",
+    "1qaz", "2wsx", "3edc", "4rfv", "5tgb", "6yhn", "7ujm", "8ik,", "9ol.", "0p;/",
+    "qaz", "wsx", "edc", "rfv", "tgb", "yhn", "ujm", "azertyuiop", "qwertzuiop"
+];
+
+// Alphabetic and numeric orders for sequences in either direction
+const SEQUENCES: &[&str] = &["abcdefghijklmnopqrstuvwxyz", "0123456789"];
+
+// Shortest keyboard walk and sequence reported
+const MIN_WALK: usize = 4;
+const MIN_SEQUENCE: usize = 3;
+
+// Per request; the answer is a few kilobytes
+const HIBP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Hex digits of the SHA-1 sent to the range API
+const HIBP_PREFIX: usize = 5;
+
+/// Characters of each class in a password
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PasswordClasses {
+    pub lowercase: usize,
+    pub uppercase: usize,
+    pub digits: usize,
+    /// ASCII punctuation and space
+    pub symbols: usize,
+    /// Anything outside ASCII
+    pub other: usize
+}
+
+impl PasswordClasses {
+    // Characters an attacker has to try for each position
+    fn pool(&self) -> f64 {
+        [(self.lowercase, 26), (self.uppercase, 26), (self.digits, 10), (self.symbols, 33), (self.other, 100)].iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(_, size)| *size as f64)
+            .sum()
+    }
+
+    fn used(&self) -> usize {
+        [self.lowercase, self.uppercase, self.digits, self.symbols, self.other].iter().filter(|count| **count > 0).count()
+    }
+}
+
+impl fmt::Display for PasswordClasses {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [(self.lowercase, "lowercase", ""), (self.uppercase, "uppercase", ""), (self.digits, "digit", "s"), (self.symbols, "symbol", "s"), (self.other, "non-ASCII", "")].iter()
+            .filter(|(count, _, _)| *count > 0)
+            .map(|(count, name, plural)| format!("{} {}{}", count, name, if *count == 1 { "" } else { plural }))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// A guessable part of a password
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordPattern {
+    /// "common word", "keyboard walk", "sequence", "year" or "repeat"
+    pub kind: &'static str,
+    /// Character offset
+    pub offset: usize,
+    pub length: usize,
+    pub text: String,
+    /// Bits the pattern is worth in the estimate
+    pub bits: f64
+}
+
+/// Offline strength estimate of a password
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordStrength {
+    /// Characters
+    pub length: usize,
+    pub classes: PasswordClasses,
+    pub entropy_bits: f64,
+    pub patterns: Vec<PasswordPattern>,
+    /// "weak", "fair" or "strong"
+    pub verdict: &'static str,
+    pub reasons: Vec<String>
+}
+
+impl fmt::Display for PasswordStrength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Verdict: {}", self.verdict)?;
+        writeln!(f, "Entropy: about {:.0} bits", self.entropy_bits)?;
+        match self.length {
+            0 => write!(f, "Length: 0")?,
+            length => write!(f, "Length: {} ({})", length, self.classes)?
+        }
+        for pattern in &self.patterns {
+            write!(f, "\nPattern: {} \"{}\" at {}", pattern.kind, pattern.text, pattern.offset)?;
+        }
+        for reason in &self.reasons {
+            write!(f, "\nReason: {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// What Have I Been Pwned knows of a password
+#[derive(Debug, Clone, Serialize)]
+pub struct PwnedPassword {
+    /// The hex digits of the SHA-1 that were sent
+    pub prefix: String,
+    /// Times the password appears in breach corpora, 0 when it does not
+    pub count: u64,
+    pub breached: bool,
+    /// Suffixes returned for the prefix, padding included
+    pub candidates: usize
+}
+
+impl fmt::Display for PwnedPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.breached {
+            true => write!(f, "Breached: seen {} times in Have I Been Pwned (prefix {} sent)", self.count, self.prefix),
+            false => write!(f, "Not found in Have I Been Pwned (prefix {} sent, {} candidates compared)", self.prefix, self.candidates)
+        }
+    }
+}
+
+/// Estimates the strength of `password` from its length, character classes and guessable patterns, without any network access
+pub fn mercy_password_strength(password: &str) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().collect();
+    let mut classes = PasswordClasses::default();
+    for c in &chars {
+        match c {
+            'a'..='z' => classes.lowercase += 1,
+            'A'..='Z' => classes.uppercase += 1,
+            '0'..='9' => classes.digits += 1,
+            c if c.is_ascii() => classes.symbols += 1,
+            _ => classes.other += 1
+        }
+    }
+    let pool = classes.pool();
+
+    // Longest candidates first, each kept only where no earlier one already covers it
+    let mut candidates = pattern_candidates(&chars, pool);
+    candidates.sort_by(|a, b| b.length.cmp(&a.length).then(a.offset.cmp(&b.offset)));
+    let mut covered = vec![false; chars.len()];
+    let mut patterns: Vec<PasswordPattern> = Vec::new();
+    for candidate in candidates {
+        let span = candidate.offset..candidate.offset + candidate.length;
+        if covered[span.clone()].iter().any(|taken| *taken) {
+            continue;
+        }
+        covered[span].iter_mut().for_each(|taken| *taken = true);
+        patterns.push(candidate);
+    }
+    patterns.sort_by_key(|pattern| pattern.offset);
+
+    let free = covered.iter().filter(|taken| !**taken).count() as f64;
+    let bits = free * pool.max(1.0).log2() + patterns.iter().map(|pattern| pattern.bits).sum::<f64>();
+    let entropy_bits = (bits * 10.0).round() / 10.0;
+
+    let verdict = match chars.len() {
+        length if length < 8 || entropy_bits < 40.0 => "weak",
+        _ if entropy_bits < 65.0 => "fair",
+        _ => "strong"
+    };
+
+    let mut reasons = Vec::new();
+    match chars.len() {
+        0 => reasons.push("empty".to_string()),
+        length if length < 8 => reasons.push(format!("only {} characters, fewer than 8", length)),
+        length if length < 12 => reasons.push(format!("{} characters; 12 or more resist offline cracking better", length)),
+        length => reasons.push(format!("{} characters", length))
+    }
+    match classes.used() {
+        0 => {},
+        1 => reasons.push(format!("one character class ({})", classes)),
+        used => reasons.push(format!("{} character classes", used))
+    }
+    for pattern in &patterns {
+        reasons.push(match pattern.kind {
+            "common word" => format!("contains the common password word \"{}\"", pattern.text),
+            "repeat" => format!("repeats \"{}\" {} times", pattern.text.chars().next().unwrap_or(' '), pattern.length),
+            kind => format!("contains the {} \"{}\"", kind, pattern.text)
+        });
+    }
+    if patterns.is_empty() && !chars.is_empty() {
+        reasons.push("no common words, keyboard walks, sequences, years or repeats".to_string());
+    }
+    reasons.push(format!("about {:.0} bits of entropy (weak below 40, strong from 65)", entropy_bits));
+
+    PasswordStrength { length: chars.len(), classes, entropy_bits, patterns, verdict, reasons }
+}
+
+/// Looks `password` up in Have I Been Pwned by k-anonymity: only the first five hex digits of its SHA-1 are sent, and the count for the rest of the hash is found locally
+pub fn mercy_hibp_check(password: &str, timeout: Duration, identity: &HttpIdentity) -> Result<PwnedPassword, String> {
+    let mut hasher = Sha1::new();
+    hasher.update(password.as_bytes());
+    let hash = hex_encode(&hasher.finalize()).to_ascii_uppercase();
+    let (prefix, suffix) = hash.split_at(HIBP_PREFIX);
+
+    let url = format!("{}/range/{}", service_endpoints().pwned_passwords.trim_end_matches('/'), prefix);
+    let client = http_client_builder(identity)?
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("Unable to build HTTP client: {}", e))?;
+
+    let body = runtime::block_on(async {
+        let response = net::send(client.get(&url).header("Add-Padding", "true")).await.map_err(|e| format!("Have I Been Pwned lookup failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Have I Been Pwned answered HTTP {}", response.status().as_u16()));
+        }
+        response.text().await.map_err(|e| format!("Have I Been Pwned lookup failed: {}", e))
+    })?;
+
+    // "SUFFIX:COUNT" lines; padding lines have a count of 0
+    let mut candidates = 0;
+    let mut count = 0;
+    for line in body.lines() {
+        let Some((returned, times)) = line.trim().split_once(':') else { continue };
+        candidates += 1;
+        if returned.eq_ignore_ascii_case(suffix) {
+            count = times.trim().parse().map_err(|_| format!("Have I Been Pwned sent an unreadable count '{}'", times.trim()))?;
+        }
+    }
+
+    Ok(PwnedPassword { prefix: prefix.to_string(), count, breached: count > 0, candidates })
+}
+
+// Every guessable part, overlapping; the caller keeps the longest
+fn pattern_candidates(chars: &[char], pool: f64) -> Vec<PasswordPattern> {
+    let lower: Vec<char> = chars.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let unleet: Vec<char> = lower.iter().map(|c| match c {
+        '@' | '4' => 'a',
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '$' | '5' => 's',
+        '7' => 't',
+        c => *c
+    }).collect();
+    let text = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let pattern = |kind, start: usize, end: usize, bits: f64| PasswordPattern { kind, offset: start, length: end - start, text: text(start, end), bits: (bits * 10.0).round() / 10.0 };
+    let mut found = Vec::new();
+
+    // Common words, as typed or with look-alike digits and symbols; the list's size, one bit for
+    // capitals and one for substitutions
+    let word_bits = (COMMON_WORDS.len() as f64).log2();
+    for word in COMMON_WORDS.iter().filter(|word| word.len() >= 3) {
+        let word: Vec<char> = word.chars().collect();
+        for start in 0..chars.len().saturating_sub(word.len() - 1) {
+            let end = start + word.len();
+            let plain = lower[start..end] == word[..];
+            if plain || unleet[start..end] == word[..] {
+                let capitals = chars[start..end].iter().any(char::is_ascii_uppercase);
+                found.push(pattern("common word", start, end, word_bits + capitals as u8 as f64 + !plain as u8 as f64));
+            }
+        }
+    }
+
+    // Keyboard walks and sequences: the run to start in, the start key and the direction
+    for (kind, runs, shortest) in [("keyboard walk", KEYBOARD_RUNS, MIN_WALK), ("sequence", SEQUENCES, MIN_SEQUENCE)] {
+        let keys: usize = runs.iter().map(|run| run.len()).sum();
+        let mut start = 0;
+        while start < lower.len() {
+            let mut end = start + 1;
+            while end < lower.len() && runs.iter().any(|run| adjacent(run, lower[end - 1], lower[end], lower.get(end.wrapping_sub(2)).filter(|_| end - start >= 2).copied())) {
+                end += 1;
+            }
+            if end - start >= shortest {
+                found.push(pattern(kind, start, end, (keys as f64 * 2.0).log2() + ((end - start) as f64).log2()));
+            }
+            start = if end - start >= shortest { end } else { start + 1 };
+        }
+    }
+
+    // Years 1900 to 2099 standing alone as four digits
+    for start in 0..chars.len().saturating_sub(3) {
+        let digits: String = chars[start..start + 4].iter().collect();
+        let alone = (start == 0 || !chars[start - 1].is_ascii_digit()) && chars.get(start + 4).is_none_or(|c| !c.is_ascii_digit());
+        if alone && matches!(digits.parse::<u32>(), Ok(1900..=2099)) && digits.chars().all(|c| c.is_ascii_digit()) {
+            found.push(pattern("year", start, start + 4, 200f64.log2()));
+        }
+    }
+
+    // Runs of one character, three or more long: the character and the run's length
+    let mut start = 0;
+    while start < chars.len() {
+        let end = start + chars[start..].iter().take_while(|c| **c == chars[start]).count();
+        if end - start >= 3 {
+            found.push(pattern("repeat", start, end, pool.max(1.0).log2() + ((end - start) as f64).log2()));
+        }
+        start = end;
+    }
+
+    found
+}
+
+// Whether `next` follows `current` in `run` in the direction `previous` set, if any
+fn adjacent(run: &str, current: char, next: char, previous: Option<char>) -> bool {
+    let keys: Vec<char> = run.chars().collect();
+    let position = |key: char| keys.iter().position(|k| *k == key);
+    let (Some(at), Some(to)) = (position(current), position(next)) else { return false };
+    let step = to as isize - at as isize;
+    if step.abs() != 1 {
+        return false;
+    }
+    match previous.and_then(position) {
+        Some(before) => at as isize - before as isize == step,
+        None => true
+    }
+}
+
+// Parses "password [timeout=10]" plus HTTP identity options
+pub(crate) fn hibp_check_from_args(input: &str) -> Result<PwnedPassword, String> {
+    let keys: Vec<&str> = ["timeout"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (password, pairs) = trailing_options(input, &keys);
+    let mut timeout = config::read_timeout(HIBP_TIMEOUT);
+    let mut identity = HttpIdentity::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => identity_option(&mut identity, key, value)?
+        }
+    }
+
+    if password.is_empty() {
+        return Err("No password given".to_string());
+    }
+    mercy_hibp_check(&password, timeout, &identity)
+}
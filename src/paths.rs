@@ -0,0 +1,183 @@
+/*
+    File names that are not plain UTF-8, and Windows long paths
+
+    Unix file names are arbitrary bytes and Windows ones arbitrary UTF-16, so a name from a
+    hostile directory may not be valid Unicode at all. Results carry names as strings: valid
+    names unchanged, others with every byte that is not part of valid UTF-8 (and every "%")
+    written as %XX, and a flag saying so. `path_from_string` reverses the encoding, so a flagged
+    name can be opened again exactly.
+
+    Rust's own file functions already add the `\\?\` prefix on Windows when a path exceeds
+    MAX_PATH; `native_path` does the same for paths handed to other programs (git), which get
+    the path as given.
+*/
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf}
+};
+
+/// Whether a path converts to a string without loss
+pub fn path_is_utf8<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().to_str().is_some()
+}
+
+/// A path as a string: unchanged when it is valid UTF-8, otherwise percent-encoded (see `path_from_string`)
+pub fn path_to_string<P: AsRef<Path>>(path: P) -> String {
+    let path = path.as_ref();
+    match path.to_str() {
+        Some(text) => text.to_string(),
+        None => encode_invalid(path)
+    }
+}
+
+/// The path named by a `path_to_string` result; `encoded` is the flag reported beside it
+pub fn path_from_string(text: &str, encoded: bool) -> PathBuf {
+    if !encoded {
+        return PathBuf::from(text);
+    }
+
+    decode(text)
+}
+
+/// A path and whether it had to be percent-encoded
+pub(crate) fn path_with_flag(path: &Path) -> (String, bool) {
+    (path_to_string(path), !path_is_utf8(path))
+}
+
+/// The path in the form to pass to other programs: on Windows, paths longer than MAX_PATH get the `\\?\` prefix
+pub(crate) fn native_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        const MAX_PATH: usize = 260;
+
+        let text = path.as_os_str();
+        if text.len() >= MAX_PATH && !path.starts_with(r"\\?\") {
+            if let Ok(absolute) = std::path::absolute(path) {
+                let mut prefixed = std::ffi::OsString::from(r"\\?\");
+                match absolute.to_str().and_then(|absolute| absolute.strip_prefix(r"\\")) {
+                    // \\server\share becomes \\?\UNC\server\share
+                    Some(unc) => prefixed.push(format!(r"UNC\{}", unc)),
+                    None => prefixed.push(absolute.as_os_str())
+                }
+                return Cow::Owned(PathBuf::from(prefixed));
+            }
+        }
+    }
+
+    Cow::Borrowed(path)
+}
+
+#[cfg(unix)]
+fn encode_invalid(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+    percent_encode(path.as_os_str().as_bytes())
+}
+
+// Valid UTF-8 runs are kept, other bytes and "%" become %XX
+#[cfg(unix)]
+fn percent_encode(mut bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len() * 2);
+
+    while !bytes.is_empty() {
+        let (valid, rest) = match std::str::from_utf8(bytes) {
+            Ok(text) => (text, &[][..]),
+            Err(e) => {
+                // valid_up_to marks a UTF-8 boundary
+                let (head, tail) = bytes.split_at(e.valid_up_to());
+                (std::str::from_utf8(head).unwrap_or(""), tail)
+            }
+        };
+
+        encoded.push_str(&valid.replace('%', "%25"));
+        if let Some((byte, tail)) = rest.split_first() {
+            encoded.push_str(&format!("%{:02X}", byte));
+            bytes = tail;
+        } else {
+            bytes = rest;
+        }
+    }
+
+    encoded
+}
+
+#[cfg(unix)]
+fn decode(text: &str) -> PathBuf {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+    PathBuf::from(OsString::from_vec(percent_decode(text)))
+}
+
+#[cfg(unix)]
+fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%').then(|| bytes.get(index + 1..index + 3)).flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            },
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+// Unpaired surrogates become %uXXXX, "%" becomes %25
+#[cfg(windows)]
+fn encode_invalid(path: &Path) -> String {
+    use std::os::windows::ffi::OsStrExt;
+
+    let units: Vec<u16> = path.as_os_str().encode_wide().collect();
+    char::decode_utf16(units.iter().copied())
+        .map(|unit| match unit {
+            Ok('%') => "%25".to_string(),
+            Ok(c) => c.to_string(),
+            Err(e) => format!("%u{:04X}", e.unpaired_surrogate())
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn decode(text: &str) -> PathBuf {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+
+    let mut units = Vec::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let surrogate = rest.strip_prefix("%u").and_then(|hex| hex.get(..4)).and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        if let Some(unit) = surrogate {
+            units.push(unit);
+            rest = &rest[6..];
+        } else if rest.starts_with("%25") {
+            units.push(b'%' as u16);
+            rest = &rest[3..];
+        } else {
+            let mut buffer = [0u16; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buffer));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    PathBuf::from(OsString::from_wide(&units))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn encode_invalid(path: &Path) -> String {
+    path.to_string_lossy().replace('%', "%25")
+}
+
+#[cfg(not(any(unix, windows)))]
+fn decode(text: &str) -> PathBuf {
+    PathBuf::from(text.replace("%25", "%"))
+}
@@ -0,0 +1,504 @@
+/*
+    Packet capture summary: counts, duration, top talkers, top ports and protocols
+
+    Reads classic libpcap files (magic a1b2c3d4 for microsecond and a1b23c4d for nanosecond
+    timestamps, in either byte order) and pcapng files (section header, interface description,
+    enhanced, simple and obsolete packet blocks, with each interface's link type and timestamp
+    resolution). The file is streamed a record at a time, so a capture of any size costs the
+    memory of its largest packet.
+
+    Frames are taken apart as far as the transport header: Ethernet (VLAN tags skipped), raw
+    IP, Linux cooked captures (v1 and v2) and BSD loopback, then IPv4 or IPv6 (walking the
+    IPv6 extension headers) to TCP, UDP or ICMP. Destination ports come from the first
+    fragment only. Frames of other link types and non-IP frames such as ARP count as "other".
+
+    A record whose headers are cut short is counted as malformed and skipped. A record header
+    claiming an impossible length means the file is damaged from there on, so reading stops
+    with that record counted as malformed and the summary covers what came before.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::{paths::path_to_string, timefmt::unix_to_rfc3339};
+
+// Entries in each top-N list
+const TOP: usize = 10;
+
+// Largest record or block accepted; anything bigger means the file is damaged
+const MAX_RECORD: usize = 16 * 1024 * 1024;
+
+// pcapng block types
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const OBSOLETE_PACKET: u32 = 2;
+const SIMPLE_PACKET: u32 = 3;
+const ENHANCED_PACKET: u32 = 6;
+
+// pcapng byte-order magic as read in little-endian order
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+/// Packets per transport protocol
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PcapProtocols {
+    pub tcp: u64,
+    pub udp: u64,
+    /// ICMP and ICMPv6
+    pub icmp: u64,
+    /// Other IP protocols and non-IP frames
+    pub other: u64
+}
+
+/// A source address and what it sent
+#[derive(Debug, Clone, Serialize)]
+pub struct PcapTalker {
+    pub address: IpAddr,
+    pub packets: u64,
+    pub bytes: u64
+}
+
+/// A destination port and how many packets went to it
+#[derive(Debug, Clone, Serialize)]
+pub struct PcapPort {
+    pub port: u16,
+    /// "tcp" or "udp"
+    pub protocol: String,
+    pub packets: u64
+}
+
+/// What a capture file holds
+#[derive(Debug, Clone, Serialize)]
+pub struct PcapSummary {
+    pub path: String,
+    /// "pcap" or "pcapng"
+    pub format: String,
+    /// Link type of the first interface ("Ethernet", "Raw IP", ...)
+    pub link_type: String,
+    pub packets: u64,
+    /// Original length of every packet, including what the capture cut off
+    pub bytes: u64,
+    pub first_packet: Option<String>,
+    pub last_packet: Option<String>,
+    pub duration_secs: f64,
+    pub protocols: PcapProtocols,
+    /// Most packets first
+    pub top_talkers: Vec<PcapTalker>,
+    /// Most packets first
+    pub top_ports: Vec<PcapPort>,
+    /// Records skipped because their headers were cut short or damaged
+    pub malformed: u64
+}
+
+impl fmt::Display for PcapSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Capture: {} ({}, {})", self.path, self.format, self.link_type)?;
+        writeln!(f, "Packets: {} ({} bytes)", self.packets, self.bytes)?;
+        if let (Some(first), Some(last)) = (&self.first_packet, &self.last_packet) {
+            writeln!(f, "Time: {} to {} ({:.3} s)", first, last, self.duration_secs)?;
+        }
+        writeln!(f, "Protocols: TCP {}, UDP {}, ICMP {}, other {}", self.protocols.tcp, self.protocols.udp, self.protocols.icmp, self.protocols.other)?;
+        if self.malformed > 0 {
+            writeln!(f, "Malformed records skipped: {}", self.malformed)?;
+        }
+
+        if !self.top_talkers.is_empty() {
+            write!(f, "\nTop talkers:")?;
+            for talker in &self.top_talkers {
+                write!(f, "\n  {} ({} packets, {} bytes)", talker.address, talker.packets, talker.bytes)?;
+            }
+            writeln!(f)?;
+        }
+        if !self.top_ports.is_empty() {
+            write!(f, "\nTop destination ports:")?;
+            for port in &self.top_ports {
+                write!(f, "\n  {}/{} ({} packets)", port.port, port.protocol, port.packets)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The transport a frame carries
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Transport {
+    Tcp,
+    Udp,
+    Icmp,
+    Other
+}
+
+// What the summary needs from one frame
+struct Dissected {
+    source: Option<IpAddr>,
+    transport: Transport,
+    destination_port: Option<u16>
+}
+
+// Running totals while the file streams past
+#[derive(Default)]
+struct Counters {
+    packets: u64,
+    bytes: u64,
+    first: Option<(i64, u32)>,
+    last: Option<(i64, u32)>,
+    protocols: PcapProtocols,
+    talkers: HashMap<IpAddr, (u64, u64)>,
+    ports: HashMap<(u16, Transport), u64>,
+    malformed: u64
+}
+
+impl Counters {
+    fn record(&mut self, link_type: u32, time: Option<(i64, u32)>, original_length: u32, data: &[u8]) {
+        let dissected = match dissect(link_type, data) {
+            Some(dissected) => dissected,
+            None => {
+                self.malformed += 1;
+                return;
+            }
+        };
+
+        self.packets += 1;
+        self.bytes += original_length as u64;
+        if let Some(time) = time {
+            self.first = Some(self.first.map_or(time, |first| first.min(time)));
+            self.last = Some(self.last.map_or(time, |last| last.max(time)));
+        }
+
+        match dissected.transport {
+            Transport::Tcp => self.protocols.tcp += 1,
+            Transport::Udp => self.protocols.udp += 1,
+            Transport::Icmp => self.protocols.icmp += 1,
+            Transport::Other => self.protocols.other += 1
+        }
+        if let Some(source) = dissected.source {
+            let talker = self.talkers.entry(source).or_default();
+            talker.0 += 1;
+            talker.1 += original_length as u64;
+        }
+        if let Some(port) = dissected.destination_port {
+            *self.ports.entry((port, dissected.transport)).or_default() += 1;
+        }
+    }
+
+    fn summary(self, path: String, format: &str, link_type: Option<u32>) -> PcapSummary {
+        let mut talkers: Vec<PcapTalker> = self.talkers.into_iter().map(|(address, (packets, bytes))| PcapTalker { address, packets, bytes }).collect();
+        talkers.sort_by(|a, b| b.packets.cmp(&a.packets).then(b.bytes.cmp(&a.bytes)).then(a.address.cmp(&b.address)));
+        talkers.truncate(TOP);
+
+        let mut ports: Vec<PcapPort> = self.ports.into_iter()
+            .map(|((port, transport), packets)| PcapPort { port, protocol: if transport == Transport::Tcp { "tcp" } else { "udp" }.to_string(), packets })
+            .collect();
+        ports.sort_by(|a, b| b.packets.cmp(&a.packets).then(a.port.cmp(&b.port)).then(a.protocol.cmp(&b.protocol)));
+        ports.truncate(TOP);
+
+        let duration_secs = match (self.first, self.last) {
+            (Some(first), Some(last)) => (last.0 - first.0) as f64 + (last.1 as f64 - first.1 as f64) / 1e9,
+            _ => 0.0
+        };
+
+        PcapSummary {
+            path,
+            format: format.to_string(),
+            link_type: link_type.map_or_else(|| "none".to_string(), link_type_name),
+            packets: self.packets,
+            bytes: self.bytes,
+            first_packet: self.first.map(|(secs, nanos)| unix_to_rfc3339(secs, nanos)),
+            last_packet: self.last.map(|(secs, nanos)| unix_to_rfc3339(secs, nanos)),
+            duration_secs,
+            protocols: self.protocols,
+            top_talkers: talkers,
+            top_ports: ports,
+            malformed: self.malformed
+        }
+    }
+}
+
+/// Packet count, duration, top talkers and ports and the protocol mix of a pcap or pcapng file
+pub fn mercy_pcap_summary<P: AsRef<Path>>(path: P) -> Result<PcapSummary, String> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    let read_error = |e: io::Error| format!("Unable to read {}: {}", path.display(), e);
+
+    let mut magic = [0u8; 4];
+    if !read_full(&mut reader, &mut magic).map_err(read_error)? {
+        return Err(format!("{} is empty", path.display()));
+    }
+
+    let mut counters = Counters::default();
+    let (format, link_type) = match u32::from_le_bytes(magic) {
+        0xA1B2_C3D4 | 0xD4C3_B2A1 | 0xA1B2_3C4D | 0x4D3C_B2A1 => ("pcap", read_pcap(&mut reader, magic, &mut counters).map_err(read_error)?),
+        SECTION_HEADER => ("pcapng", read_pcapng(&mut reader, &mut counters).map_err(read_error)?),
+        _ => return Err(format!("{} is not a pcap or pcapng capture (magic {:02x}{:02x}{:02x}{:02x})", path.display(), magic[0], magic[1], magic[2], magic[3]))
+    };
+
+    Ok(counters.summary(path_to_string(path), format, link_type))
+}
+
+// Classic libpcap: a 24-byte file header, then a 16-byte header before each packet
+fn read_pcap<R: Read>(reader: &mut R, magic: [u8; 4], counters: &mut Counters) -> io::Result<Option<u32>> {
+    let big = matches!(u32::from_le_bytes(magic), 0xD4C3_B2A1 | 0x4D3C_B2A1);
+    let nanosecond = matches!(u32::from_le_bytes(magic), 0xA1B2_3C4D | 0x4D3C_B2A1);
+
+    let mut header = [0u8; 20];
+    if !read_full(reader, &mut header)? {
+        counters.malformed += 1;
+        return Ok(None);
+    }
+    let link_type = u32_at(&header, 16, big) & 0x0FFF_FFFF;
+
+    let mut record = [0u8; 16];
+    let mut data = Vec::new();
+    loop {
+        if !read_full(reader, &mut record)? {
+            return Ok(Some(link_type));
+        }
+        let (secs, fraction) = (u32_at(&record, 0, big), u32_at(&record, 4, big));
+        let (captured, original) = (u32_at(&record, 8, big) as usize, u32_at(&record, 12, big));
+        if captured > MAX_RECORD {
+            counters.malformed += 1;
+            return Ok(Some(link_type));
+        }
+
+        data.resize(captured, 0);
+        if !read_full(reader, &mut data)? {
+            counters.malformed += 1;
+            return Ok(Some(link_type));
+        }
+        let nanos = if nanosecond { fraction } else { fraction.saturating_mul(1000) };
+        counters.record(link_type, Some((secs as i64, nanos.min(999_999_999))), original, &data);
+    }
+}
+
+// pcapng: blocks of type, length, body and the length again; the section header sets the byte order
+fn read_pcapng<R: Read>(reader: &mut R, counters: &mut Counters) -> io::Result<Option<u32>> {
+    // (link type, timestamp units per second) of each interface of the current section
+    let mut interfaces: Vec<(u32, u64)> = Vec::new();
+    let mut first_link_type = None;
+    let mut big = false;
+    let mut block_type = SECTION_HEADER;
+    let mut body = Vec::new();
+
+    loop {
+        let mut lengths = [0u8; 4];
+        if !read_full(reader, &mut lengths)? {
+            counters.malformed += 1;
+            return Ok(first_link_type);
+        }
+
+        if block_type == SECTION_HEADER {
+            // The byte order is only known once the magic after the length is read
+            let mut order = [0u8; 4];
+            if !read_full(reader, &mut order)? {
+                counters.malformed += 1;
+                return Ok(first_link_type);
+            }
+            big = u32::from_le_bytes(order) != BYTE_ORDER_MAGIC;
+            if big && u32::from_be_bytes(order) != BYTE_ORDER_MAGIC {
+                counters.malformed += 1;
+                return Ok(first_link_type);
+            }
+            interfaces.clear();
+        }
+
+        let length = u32_at(&lengths, 0, big) as usize;
+        let consumed = if block_type == SECTION_HEADER { 12 } else { 8 };
+        if length < consumed + 4 || !length.is_multiple_of(4) || length > MAX_RECORD {
+            counters.malformed += 1;
+            return Ok(first_link_type);
+        }
+        body.resize(length - consumed, 0);
+        if !read_full(reader, &mut body)? {
+            counters.malformed += 1;
+            return Ok(first_link_type);
+        }
+        let body = &body[..body.len() - 4];
+
+        match block_type {
+            INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                let link_type = u16_at(body, 0, big) as u32;
+                first_link_type.get_or_insert(link_type);
+                interfaces.push((link_type, timestamp_units(&body[8..], big)));
+            },
+            ENHANCED_PACKET | OBSOLETE_PACKET if body.len() >= 20 => {
+                let interface = match block_type {
+                    ENHANCED_PACKET => u32_at(body, 0, big) as usize,
+                    _ => u16_at(body, 0, big) as usize
+                };
+                let ticks = ((u32_at(body, 4, big) as u64) << 32) | u32_at(body, 8, big) as u64;
+                let (captured, original) = (u32_at(body, 12, big) as usize, u32_at(body, 16, big));
+                match (interfaces.get(interface), body.get(20..20 + captured)) {
+                    (Some(&(link_type, units)), Some(data)) => counters.record(link_type, Some(split_ticks(ticks, units)), original, data),
+                    _ => counters.malformed += 1
+                }
+            },
+            SIMPLE_PACKET if body.len() >= 4 => match interfaces.first() {
+                // No timestamp: the packet counts but leaves the capture's time span alone
+                Some(&(link_type, _)) => {
+                    let original = u32_at(body, 0, big);
+                    counters.record(link_type, None, original, &body[4..body.len().min(4 + original as usize)]);
+                },
+                None => counters.malformed += 1
+            },
+            INTERFACE_DESCRIPTION | ENHANCED_PACKET | OBSOLETE_PACKET | SIMPLE_PACKET => counters.malformed += 1,
+            _ => {}
+        }
+
+        let mut next = [0u8; 4];
+        if !read_full(reader, &mut next)? {
+            return Ok(first_link_type);
+        }
+        block_type = u32_at(&next, 0, big);
+    }
+}
+
+// Timestamp units per second from an interface's if_tsresol option (microseconds without one)
+fn timestamp_units(mut options: &[u8], big: bool) -> u64 {
+    while options.len() >= 4 {
+        let (code, length) = (u16_at(options, 0, big), u16_at(options, 2, big) as usize);
+        if code == 0 {
+            break;
+        }
+        if code == 9 && length >= 1 && options.len() > 4 {
+            let resolution = options[4];
+            let exponent = (resolution & 0x7F) as u32;
+            return match resolution & 0x80 {
+                0 => 10u64.checked_pow(exponent),
+                _ => 2u64.checked_pow(exponent)
+            }.unwrap_or(1_000_000).max(1);
+        }
+        options = options.get(4 + length.div_ceil(4) * 4..).unwrap_or_default();
+    }
+    1_000_000
+}
+
+// Seconds and nanoseconds of a timestamp counted in `units` per second
+fn split_ticks(ticks: u64, units: u64) -> (i64, u32) {
+    let nanos = (ticks % units) as u128 * 1_000_000_000 / units as u128;
+    ((ticks / units) as i64, nanos as u32)
+}
+
+// The network and transport headers of a frame; None when they are cut short
+fn dissect(link_type: u32, data: &[u8]) -> Option<Dissected> {
+    let other = || Some(Dissected { source: None, transport: Transport::Other, destination_port: None });
+
+    let (ethertype, payload) = match link_type {
+        // Ethernet, with 802.1Q and 802.1ad tags skipped
+        1 => {
+            let mut offset = 12;
+            let mut ethertype = u16_at(data.get(..14)?, 12, true);
+            while matches!(ethertype, 0x8100 | 0x88A8 | 0x9100) {
+                offset += 4;
+                ethertype = u16_at(data.get(..offset + 2)?, offset, true);
+            }
+            (Some(ethertype), &data[offset + 2..])
+        },
+        // Raw IP
+        101 | 12 | 14 | 228 | 229 => (None, data),
+        // BSD loopback: a 4-byte address family in the capturing host's byte order
+        0 | 108 => (None, data.get(4..)?),
+        // Linux cooked capture v1 and v2
+        113 => (Some(u16_at(data.get(..16)?, 14, true)), &data[16..]),
+        276 => (Some(u16_at(data.get(..20)?, 0, true)), &data[20..]),
+        _ => return other()
+    };
+
+    match (ethertype, payload.first().map(|byte| byte >> 4)) {
+        (Some(0x0800) | None, Some(4)) => ipv4(payload),
+        (Some(0x86DD) | None, Some(6)) => ipv6(payload),
+        (Some(0x0800 | 0x86DD) | None, _) => None,
+        _ => other()
+    }
+}
+
+fn ipv4(packet: &[u8]) -> Option<Dissected> {
+    let header_length = ((*packet.first()? & 0x0F) as usize) * 4;
+    if header_length < 20 || packet.len() < header_length {
+        return None;
+    }
+
+    let source = IpAddr::V4(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]));
+    let first_fragment = u16_at(packet, 6, true) & 0x1FFF == 0;
+    Some(transport(source, packet[9], first_fragment.then(|| &packet[header_length..])))
+}
+
+fn ipv6(packet: &[u8]) -> Option<Dissected> {
+    if packet.len() < 40 {
+        return None;
+    }
+
+    let mut address = [0u8; 16];
+    address.copy_from_slice(&packet[8..24]);
+    let source = IpAddr::V6(Ipv6Addr::from(address));
+
+    // Hop-by-hop, routing, fragment and destination options headers precede the transport
+    let (mut next, mut offset, mut first_fragment) = (packet[6], 40, true);
+    while matches!(next, 0 | 43 | 44 | 60) {
+        let header = packet.get(offset..offset + 8)?;
+        if next == 44 {
+            first_fragment &= u16_at(header, 2, true) & 0xFFF8 == 0;
+            offset += 8;
+        } else {
+            offset += (header[1] as usize + 1) * 8;
+        }
+        next = header[0];
+    }
+
+    Some(transport(source, next, first_fragment.then(|| packet.get(offset..)).flatten()))
+}
+
+// The transport protocol and, for the first fragment of TCP and UDP, the destination port
+fn transport(source: IpAddr, protocol: u8, segment: Option<&[u8]>) -> Dissected {
+    let transport = match protocol {
+        6 => Transport::Tcp,
+        17 => Transport::Udp,
+        1 | 58 => Transport::Icmp,
+        _ => Transport::Other
+    };
+    let destination_port = match transport {
+        Transport::Tcp | Transport::Udp => segment.and_then(|segment| segment.get(..4)).map(|ports| u16_at(ports, 2, true)),
+        _ => None
+    };
+    Dissected { source: Some(source), transport, destination_port }
+}
+
+fn link_type_name(link_type: u32) -> String {
+    match link_type {
+        0 => "BSD loopback".to_string(),
+        1 => "Ethernet".to_string(),
+        12 | 14 | 101 => "Raw IP".to_string(),
+        108 => "OpenBSD loopback".to_string(),
+        113 => "Linux cooked capture".to_string(),
+        228 => "Raw IPv4".to_string(),
+        229 => "Raw IPv6".to_string(),
+        276 => "Linux cooked capture v2".to_string(),
+        other => format!("link type {} (not decoded)", other)
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize, big: bool) -> u16 {
+    let raw = [bytes[offset], bytes[offset + 1]];
+    if big { u16::from_be_bytes(raw) } else { u16::from_le_bytes(raw) }
+}
+
+fn u32_at(bytes: &[u8], offset: usize, big: bool) -> u32 {
+    let raw = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+    if big { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) }
+}
+
+// Fills `buffer`; false when the stream ends first (a partial read counts as ending)
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e)
+    }
+}
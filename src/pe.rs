@@ -0,0 +1,385 @@
+/*
+    PE (Windows executable and DLL) header parsing for malware triage
+
+    Reads the DOS header, the PE signature, the COFF file header, the optional header (PE32 or
+    PE32+) and the section table, then the import directory for the DLL names. Each section's
+    raw data is streamed once for its entropy, and whatever follows the last section (less an
+    Authenticode certificate table at the very end) is reported as overlay, a common place for
+    installers and packers to keep a payload. Only the headers and the import names are held in
+    memory.
+
+    Every field is read through bounds-checked accessors, so a truncated or corrupted file ends
+    in an error naming the structure that does not fit ("Truncated optional header") rather than
+    a panic. Damage past the headers (a section running off the end of the file, an import
+    directory pointing nowhere) is kept as a note and the rest is still reported.
+*/
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path
+};
+
+use serde::Serialize;
+
+use crate::{
+    entropy::{HIGH_ENTROPY, round, shannon},
+    paths::path_to_string,
+    registry::{le16, le32, le64},
+    timefmt::unix_to_rfc3339
+};
+
+// The Windows loader refuses images with more sections than this
+const MAX_SECTIONS: usize = 96;
+
+// Import descriptors and DLL name bytes read before giving up on a damaged directory
+const MAX_IMPORTS: usize = 4096;
+const MAX_NAME: usize = 256;
+
+// Bytes taken from the file per read while hashing section entropy
+const READ_CHUNK: usize = 64 * 1024;
+
+/// One entry of the section table
+#[derive(Debug, Clone, Serialize)]
+pub struct PeSection {
+    /// Up to 8 characters, bytes outside printable ASCII as "\xNN"
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    /// File offset of the raw data
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    /// Of the raw data present in the file, in bits per byte
+    pub entropy: f64,
+    /// "r", "w" and "x" from the characteristics, "-" where absent
+    pub permissions: String
+}
+
+/// Bytes past the end of the image
+#[derive(Debug, Clone, Serialize)]
+pub struct PeOverlay {
+    pub offset: u64,
+    pub size: u64,
+    pub entropy: f64
+}
+
+/// Parsed headers of a PE file
+#[derive(Debug, Clone, Serialize)]
+pub struct PeInfo {
+    pub path: String,
+    pub file_size: u64,
+    /// "x86", "x64", "ARM64" and so on, or the raw value
+    pub machine: String,
+    /// "PE32" or "PE32+"
+    pub format: String,
+    pub dll: bool,
+    /// Link time as a UTC date; none when the field is zero
+    pub compiled: Option<String>,
+    /// The raw TimeDateStamp, also a build hash in reproducible builds
+    pub timestamp: u32,
+    pub subsystem: String,
+    pub entry_point: u32,
+    pub image_base: u64,
+    pub section_count: usize,
+    pub sections: Vec<PeSection>,
+    /// DLL names of the import directory, in table order
+    pub imports: Vec<String>,
+    /// Whether an Authenticode certificate table is present (not whether it verifies)
+    pub signed: bool,
+    pub overlay: Option<PeOverlay>,
+    /// Packer tells and damage found past the headers
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for PeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PE: {} ({} bytes)", self.path, self.file_size)?;
+        writeln!(f, "Machine: {} ({}, {})", self.machine, self.format, if self.dll { "DLL" } else { "executable" })?;
+        match &self.compiled {
+            Some(compiled) => writeln!(f, "Compiled: {} (0x{:08x})", compiled, self.timestamp)?,
+            None => writeln!(f, "Compiled: not recorded")?
+        }
+        writeln!(f, "Subsystem: {}", self.subsystem)?;
+        writeln!(f, "Entry point: 0x{:x} (image base 0x{:x})", self.entry_point, self.image_base)?;
+        writeln!(f, "Signed: {}", if self.signed { "certificate table present" } else { "no" })?;
+
+        writeln!(f, "Sections ({}):", self.section_count)?;
+        for section in &self.sections {
+            writeln!(f, "  {:<10} {}  virtual 0x{:08x} ({} bytes)  raw 0x{:08x} ({} bytes)  entropy {:.2}", section.name, section.permissions,
+                section.virtual_address, section.virtual_size, section.raw_offset, section.raw_size, section.entropy)?;
+        }
+
+        writeln!(f, "Imports ({}):", self.imports.len())?;
+        for dll in &self.imports {
+            writeln!(f, "  {}", dll)?;
+        }
+
+        if let Some(overlay) = &self.overlay {
+            writeln!(f, "Overlay: {} bytes at 0x{:x} (entropy {:.2})", overlay.size, overlay.offset, overlay.entropy)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the headers, sections, imports and overlay of a PE file
+pub fn mercy_pe_info<P: AsRef<Path>>(path: P) -> Result<PeInfo, String> {
+    let path = path.as_ref();
+    let failed = |e: io::Error| format!("Unable to read {}: {}", path.display(), e);
+    let mut file = File::open(path).map_err(failed)?;
+    let file_size = file.metadata().map_err(failed)?.len();
+
+    let dos = read_at(&mut file, 0, 64).map_err(failed)?;
+    if dos.get(..2) != Some(b"MZ") {
+        return Err("Not a PE file: missing MZ".to_string());
+    }
+    let nt_offset = le32(&dos, 0x3C).ok_or("Truncated DOS header")? as u64;
+    if nt_offset + 4 > file_size {
+        return Err(format!("Not a PE file: PE header offset 0x{:x} is past the end of the file", nt_offset));
+    }
+
+    let nt = read_at(&mut file, nt_offset, 24).map_err(failed)?;
+    if nt.get(..4) != Some(b"PE\0\0") {
+        return Err(format!("Not a PE file: missing PE signature at 0x{:x}", nt_offset));
+    }
+    let machine = le16(&nt, 4).ok_or("Truncated COFF file header")?;
+    let section_count = le16(&nt, 6).ok_or("Truncated COFF file header")? as usize;
+    let timestamp = le32(&nt, 8).ok_or("Truncated COFF file header")?;
+    let optional_size = le16(&nt, 20).ok_or("Truncated COFF file header")? as usize;
+    let characteristics = le16(&nt, 22).ok_or("Truncated COFF file header")?;
+
+    if section_count > MAX_SECTIONS {
+        return Err(format!("Corrupted COFF file header: {} sections (the loader allows {})", section_count, MAX_SECTIONS));
+    }
+
+    let optional_offset = nt_offset + 24;
+    let optional = read_at(&mut file, optional_offset, optional_size).map_err(failed)?;
+    if optional.len() < optional_size {
+        return Err("Truncated optional header".to_string());
+    }
+    let magic = le16(&optional, 0).ok_or("Truncated optional header")?;
+    let (format, image_base, directories) = match magic {
+        0x10B => ("PE32", le32(&optional, 28).map(u64::from), 96),
+        0x20B => ("PE32+", le64(&optional, 24), 112),
+        other => return Err(format!("Not a PE image: optional header magic 0x{:x} (0x10b or 0x20b expected)", other))
+    };
+    let image_base = image_base.ok_or("Truncated optional header")?;
+    let entry_point = le32(&optional, 16).ok_or("Truncated optional header")?;
+    let subsystem = le16(&optional, 68).ok_or("Truncated optional header")?;
+    let size_of_headers = le32(&optional, 60).ok_or("Truncated optional header")?;
+    let directory_count = le32(&optional, directories - 4).ok_or("Truncated optional header")? as usize;
+    // (RVA, size) of a data directory, or none when absent or cut off
+    let directory = |index: usize| match index < directory_count {
+        true => le32(&optional, directories + index * 8).zip(le32(&optional, directories + index * 8 + 4)).filter(|(address, size)| *address > 0 && *size > 0),
+        false => None
+    };
+
+    let table = read_at(&mut file, optional_offset + optional_size as u64, section_count * 40).map_err(failed)?;
+    if table.len() < section_count * 40 {
+        return Err(format!("Truncated section table ({} sections declared, {} present)", section_count, table.len() / 40));
+    }
+
+    let mut notes = Vec::new();
+    let mut sections = Vec::with_capacity(section_count);
+    let mut image_end = size_of_headers as u64;
+
+    for entry in table.chunks_exact(40) {
+        let field = |offset: usize| le32(entry, offset).unwrap_or(0);
+        let flags = field(36);
+        let mut section = PeSection {
+            name: printable(entry[..8].split(|byte| *byte == 0).next().unwrap_or(&[])),
+            virtual_address: field(12),
+            virtual_size: field(8),
+            raw_offset: field(20),
+            raw_size: field(16),
+            entropy: 0.0,
+            permissions: [(0x4000_0000, 'r'), (0x8000_0000, 'w'), (0x2000_0000, 'x')].iter()
+                .map(|(bit, letter)| if flags & bit != 0 { *letter } else { '-' })
+                .collect()
+        };
+
+        if section.raw_size > 0 {
+            let end = section.raw_offset as u64 + section.raw_size as u64;
+            if end > file_size {
+                notes.push(format!("Section {} raw data runs {} bytes past the end of the file", section.name, end - file_size));
+            }
+            image_end = image_end.max(end.min(file_size));
+            section.entropy = entropy_at(&mut file, section.raw_offset as u64, section.raw_size as u64).map_err(failed)?;
+        }
+
+        if section.permissions == "rwx" {
+            notes.push(format!("Section {} is writable and executable", section.name));
+        }
+        if section.entropy >= HIGH_ENTROPY {
+            notes.push(format!("Section {} has entropy {:.2}; packed or encrypted", section.name, section.entropy));
+        }
+        if section.raw_size == 0 && section.virtual_size > 0 && section.permissions.contains('x') {
+            notes.push(format!("Executable section {} has no data on disk; unpacked at run time", section.name));
+        }
+        sections.push(section);
+    }
+
+    // The security directory holds a file offset rather than an RVA, and its table sits past the sections
+    let certificate = directory(4);
+    let mut overlay_end = file_size;
+    if let Some((offset, size)) = certificate {
+        if offset as u64 >= image_end && offset as u64 + size as u64 == file_size {
+            overlay_end = offset as u64;
+        }
+    }
+    let overlay = match overlay_end > image_end {
+        true => {
+            let size = overlay_end - image_end;
+            notes.push(format!("{} bytes of overlay after the last section", size));
+            Some(PeOverlay { offset: image_end, size, entropy: entropy_at(&mut file, image_end, size).map_err(failed)? })
+        },
+        false => None
+    };
+
+    let imports = match directory(1) {
+        Some((address, _)) => imports(&mut file, &sections, address, &mut notes).map_err(failed)?,
+        None => Vec::new()
+    };
+
+    Ok(PeInfo {
+        path: path_to_string(path),
+        file_size,
+        machine: machine_name(machine),
+        format: format.to_string(),
+        dll: characteristics & 0x2000 != 0,
+        compiled: (timestamp != 0).then(|| unix_to_rfc3339(timestamp as i64, 0)),
+        timestamp,
+        subsystem: subsystem_name(subsystem),
+        entry_point,
+        image_base,
+        section_count,
+        sections,
+        imports,
+        signed: certificate.is_some(),
+        overlay,
+        notes
+    })
+}
+
+// Reads up to `length` bytes at `offset`; fewer at the end of the file
+fn read_at(file: &mut File, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(length.min(READ_CHUNK));
+    file.seek(SeekFrom::Start(offset))?;
+    file.by_ref().take(length as u64).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// Entropy of the bytes present in the file from `offset`, streamed in chunks
+fn entropy_at(file: &mut File, offset: u64, length: u64) -> io::Result<f64> {
+    let mut histogram = [0u64; 256];
+    let mut total = 0u64;
+    let mut buffer = vec![0u8; READ_CHUNK];
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = file.by_ref().take(length);
+
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        for byte in &buffer[..count] {
+            histogram[*byte as usize] += 1;
+        }
+        total += count as u64;
+    }
+
+    Ok(round(shannon(&histogram, total)))
+}
+
+// File offset of an RVA: inside a section's raw data, or in the headers before the first section
+fn rva_to_offset(sections: &[PeSection], rva: u32) -> Option<u64> {
+    for section in sections {
+        let span = section.virtual_size.max(section.raw_size);
+        if rva >= section.virtual_address && rva - section.virtual_address < span {
+            let into = rva - section.virtual_address;
+            return (into < section.raw_size).then(|| section.raw_offset as u64 + into as u64);
+        }
+    }
+    sections.iter().map(|section| section.virtual_address).min().filter(|first| rva < *first).map(|_| rva as u64)
+}
+
+// DLL names of the import descriptors, which end at an all-zero one
+fn imports(file: &mut File, sections: &[PeSection], address: u32, notes: &mut Vec<String>) -> io::Result<Vec<String>> {
+    let mut dlls = Vec::new();
+
+    for index in 0..MAX_IMPORTS {
+        let descriptor = match address.checked_add(index as u32 * 20).and_then(|rva| rva_to_offset(sections, rva)) {
+            Some(offset) => read_at(file, offset, 20)?,
+            None => {
+                notes.push(format!("Import descriptor {} at RVA 0x{:x} is outside every section", index, address as u64 + index as u64 * 20));
+                break;
+            }
+        };
+        if descriptor.len() < 20 {
+            notes.push("Import directory runs past the end of the file".to_string());
+            break;
+        }
+        if descriptor.iter().all(|byte| *byte == 0) {
+            return Ok(dlls);
+        }
+
+        let name_rva = le32(&descriptor, 12).unwrap_or(0);
+        let name = match rva_to_offset(sections, name_rva) {
+            Some(offset) => read_at(file, offset, MAX_NAME)?,
+            None => {
+                notes.push(format!("Import {} names RVA 0x{:x}, outside every section", index, name_rva));
+                continue;
+            }
+        };
+        dlls.push(printable(name.split(|byte| *byte == 0).next().unwrap_or(&[])));
+    }
+
+    if dlls.len() == MAX_IMPORTS {
+        notes.push(format!("Import directory has no terminating entry in its first {} descriptors", MAX_IMPORTS));
+    }
+    Ok(dlls)
+}
+
+// Names are attacker-controlled bytes; anything outside printable ASCII is escaped
+fn printable(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| match byte {
+        0x20..=0x7E => (*byte as char).to_string(),
+        _ => format!("\\x{:02x}", byte)
+    }).collect()
+}
+
+fn machine_name(machine: u16) -> String {
+    match machine {
+        0x014C => "x86",
+        0x8664 => "x64",
+        0xAA64 => "ARM64",
+        0xA641 => "ARM64EC",
+        0x01C0 => "ARM",
+        0x01C4 => "ARM Thumb-2",
+        0x0200 => "IA-64",
+        0x5064 => "RISC-V 64",
+        other => return format!("unknown (0x{:04x})", other)
+    }.to_string()
+}
+
+fn subsystem_name(subsystem: u16) -> String {
+    match subsystem {
+        1 => "Native",
+        2 => "Windows GUI",
+        3 => "Windows console",
+        5 => "OS/2 console",
+        7 => "POSIX console",
+        9 => "Windows CE GUI",
+        10 => "EFI application",
+        11 => "EFI boot service driver",
+        12 => "EFI runtime driver",
+        13 => "EFI ROM",
+        14 => "Xbox",
+        16 => "Windows boot application",
+        other => return format!("unknown ({})", other)
+    }.to_string()
+}
@@ -0,0 +1,281 @@
+/*
+    ICMP echo (ping) with statistics
+
+    Uses raw ICMP sockets when privileged, unprivileged datagram ICMP sockets where the OS allows
+    them, and otherwise falls back to timing TCP connects. The fallback is always labelled in the
+    report because a TCP handshake time is not an ICMP round trip.
+*/
+
+use std::{
+    fmt,
+    io,
+    net::{IpAddr, SocketAddr, TcpStream},
+    time::{Duration, Instant}
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    dns::resolve_host,
+    icmp::{self, IcmpAccess}
+};
+
+// Delay between consecutive probes, as in the classic ping utility
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Ports tried, in order, by the TCP fallback
+const FALLBACK_PORTS: [u16; 2] = [80, 443];
+
+/// How the probes were sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PingMethod {
+    IcmpRaw,
+    IcmpDatagram,
+    TcpConnect
+}
+
+impl fmt::Display for PingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingMethod::IcmpRaw => write!(f, "ICMP echo (raw socket)"),
+            PingMethod::IcmpDatagram => write!(f, "ICMP echo (unprivileged socket)"),
+            PingMethod::TcpConnect => write!(f, "TCP connect time (FALLBACK: ICMP unavailable, not an ICMP round trip)")
+        }
+    }
+}
+
+/// A single probe result
+#[derive(Debug, Clone, Serialize)]
+pub struct PingProbe {
+    pub seq: u16,
+    pub rtt_ms: Option<f64>,
+    /// TTL of the reply when the raw IP header was visible
+    pub ttl: Option<u8>,
+    /// TCP fallback only: the port that answered
+    pub port: Option<u16>
+}
+
+/// Ping results and summary statistics
+#[derive(Debug, Clone, Serialize)]
+pub struct PingReport {
+    pub target: String,
+    pub address: IpAddr,
+    pub method: PingMethod,
+    pub transmitted: u32,
+    pub received: u32,
+    pub loss_percent: f64,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub stddev_ms: Option<f64>,
+    pub probes: Vec<PingProbe>
+}
+
+impl fmt::Display for PingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PING {} ({}) via {}", self.target, self.address, self.method)?;
+
+        for probe in &self.probes {
+            match probe.rtt_ms {
+                Some(rtt) => {
+                    write!(f, "reply from {}: seq={} time={:.3} ms", self.address, probe.seq, rtt)?;
+                    if let Some(ttl) = probe.ttl {
+                        write!(f, " ttl={}", ttl)?;
+                    }
+                    if let Some(port) = probe.port {
+                        write!(f, " port={}", port)?;
+                    }
+                    writeln!(f)?;
+                }
+                None => writeln!(f, "request timeout: seq={}", probe.seq)?
+            }
+        }
+
+        writeln!(f, "--- {} ping statistics ---", self.target)?;
+        writeln!(f, "{} transmitted, {} received, {:.1}% packet loss", self.transmitted, self.received, self.loss_percent)?;
+
+        if let (Some(min), Some(avg), Some(max), Some(stddev)) = (self.min_ms, self.avg_ms, self.max_ms, self.stddev_ms) {
+            writeln!(f, "rtt min/avg/max/stddev = {:.3}/{:.3}/{:.3}/{:.3} ms", min, avg, max, stddev)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends `count` echo requests to `host`, waiting up to `timeout` for each reply
+pub fn mercy_ping(host: &str, count: u32, timeout: Duration) -> Result<PingReport, String> {
+    if count == 0 {
+        return Err("Ping count must be at least 1".to_string());
+    }
+
+    let address = resolve_host(host)?;
+    let v6 = address.is_ipv6();
+
+    let (method, probes) = match icmp::icmp_access(v6) {
+        IcmpAccess::Raw => (PingMethod::IcmpRaw, icmp_ping(address, count, timeout, true)?),
+        IcmpAccess::Datagram => (PingMethod::IcmpDatagram, icmp_ping(address, count, timeout, false)?),
+        IcmpAccess::Unavailable(reason) => {
+            warn!("ICMP unavailable ({}), timing TCP connects to {} instead", reason, address);
+            (PingMethod::TcpConnect, tcp_ping(address, count, timeout))
+        }
+    };
+
+    Ok(summarize(host, address, method, probes))
+}
+
+/// Async wrapper around `mercy_ping` that keeps blocking socket work off the runtime threads
+pub async fn mercy_ping_async(host: &str, count: u32, timeout: Duration) -> Result<PingReport, String> {
+    let host = host.to_string();
+    tokio::task::spawn_blocking(move || mercy_ping(&host, count, timeout))
+        .await
+        .map_err(|e| format!("Ping task failed: {}", e))?
+}
+
+fn icmp_ping(address: IpAddr, count: u32, timeout: Duration, raw: bool) -> Result<Vec<PingProbe>, String> {
+    let socket = icmp::open_icmp(address.is_ipv6(), raw).map_err(|e| format!("Unable to open ICMP socket: {}", e))?;
+    let ident = (std::process::id() & 0xFFFF) as u16;
+
+    let mut probes = Vec::new();
+    for index in 0..count {
+        if index > 0 {
+            std::thread::sleep(PROBE_INTERVAL);
+        }
+
+        let seq = (index + 1) as u16;
+        let mut probe = PingProbe { seq, rtt_ms: None, ttl: None, port: None };
+
+        if let Some((rtt, ttl)) = echo_once(&socket, raw, address, ident, seq, timeout)? {
+            probe.rtt_ms = Some(rtt);
+            probe.ttl = ttl;
+        }
+
+        probes.push(probe);
+    }
+
+    Ok(probes)
+}
+
+// Sends one echo request and waits for the matching reply, returning (rtt ms, reply TTL)
+pub(crate) fn echo_once(socket: &socket2::Socket, raw: bool, address: IpAddr, ident: u16, seq: u16, timeout: Duration) -> Result<Option<(f64, Option<u8>)>, String> {
+    let v6 = address.is_ipv6();
+    let target: socket2::SockAddr = SocketAddr::new(address, 0).into();
+
+    // Datagram sockets have their identifier rewritten by the kernel, so only raw sockets check it
+    let (reply_type, has_ip_header) = if v6 { (icmp::ICMP6_ECHO_REPLY, false) } else { (icmp::ICMP4_ECHO_REPLY, raw) };
+    let packet = icmp::echo_request(v6, ident, seq, b"mercy-ping-payload-0123456789");
+
+    let start = Instant::now();
+    socket.send_to(&packet, &target).map_err(|e| format!("Unable to send echo request: {}", e))?;
+    let deadline = start + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+
+        let message = match icmp::recv_icmp(socket, v6, has_ip_header, remaining).map_err(|e| format!("Unable to read echo reply: {}", e))? {
+            Some(message) => message,
+            None => return Ok(None)
+        };
+
+        let matches = message.icmp_type == reply_type
+            && message.source == address
+            && message.echo_ids().map(|(id, s)| s == seq && (!raw || id == ident)).unwrap_or(false);
+
+        if matches {
+            return Ok(Some((start.elapsed().as_secs_f64() * 1000.0, message.ttl)));
+        }
+    }
+}
+
+// Measures TCP handshake time; a refused connection still proves the host is up
+fn tcp_ping(address: IpAddr, count: u32, timeout: Duration) -> Vec<PingProbe> {
+    let mut probes = Vec::new();
+
+    for index in 0..count {
+        if index > 0 {
+            std::thread::sleep(PROBE_INTERVAL);
+        }
+
+        let mut probe = PingProbe { seq: (index + 1) as u16, rtt_ms: None, ttl: None, port: None };
+
+        for port in FALLBACK_PORTS {
+            let start = Instant::now();
+            let answered = match TcpStream::connect_timeout(&SocketAddr::new(address, port), timeout) {
+                Ok(_) => true,
+                Err(e) => e.kind() == io::ErrorKind::ConnectionRefused
+            };
+
+            if answered {
+                probe.rtt_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+                probe.port = Some(port);
+                break;
+            }
+        }
+
+        probes.push(probe);
+    }
+
+    probes
+}
+
+fn summarize(host: &str, address: IpAddr, method: PingMethod, probes: Vec<PingProbe>) -> PingReport {
+    let rtts: Vec<f64> = probes.iter().filter_map(|p| p.rtt_ms).collect();
+    let transmitted = probes.len() as u32;
+    let received = rtts.len() as u32;
+
+    let (min, avg, max, stddev) = if rtts.is_empty() {
+        (None, None, None, None)
+    } else {
+        let mean = rtts.iter().sum::<f64>() / rtts.len() as f64;
+        let variance = rtts.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / rtts.len() as f64;
+
+        (
+            rtts.iter().copied().reduce(f64::min),
+            Some(mean),
+            rtts.iter().copied().reduce(f64::max),
+            Some(variance.sqrt())
+        )
+    };
+
+    PingReport {
+        target: host.trim().to_string(),
+        address,
+        method,
+        transmitted,
+        received,
+        loss_percent: (transmitted - received) as f64 * 100.0 / transmitted as f64,
+        min_ms: min,
+        avg_ms: avg,
+        max_ms: max,
+        stddev_ms: stddev,
+        probes
+    }
+}
+
+// Parses "host count=4 timeout=2" style input for the string dispatcher
+pub(crate) fn parse_ping_args(input: &str) -> Result<(String, u32, Duration), String> {
+    let mut parts = input.split_whitespace();
+    let host = parts.next().ok_or("No host specified for ping")?.to_string();
+    let mut count = 4;
+    let mut timeout = Duration::from_secs(2);
+
+    for part in parts {
+        match part.split_once('=') {
+            Some(("count", value)) => count = value.parse().map_err(|_| format!("Invalid count '{}'", value))?,
+            Some(("timeout", value)) => {
+                let secs: f64 = value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?;
+                if !secs.is_finite() || secs <= 0.0 {
+                    return Err(format!("Invalid timeout '{}'", value));
+                }
+                timeout = Duration::from_secs_f64(secs);
+            }
+            _ => return Err(format!("Unknown ping option '{}'", part))
+        }
+    }
+
+    Ok((host, count, timeout))
+}
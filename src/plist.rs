@@ -0,0 +1,936 @@
+/*
+    Property list parsing (XML and binary bplist00) and configuration profile summaries
+
+    Dates are stored as seconds since 2001-01-01 and rendered as RFC 3339. Configuration
+    profiles (.mobileconfig) may be plain plists or wrapped in PKCS#7 SignedData; the signature
+    certificates are reported but the signature itself is not verified.
+*/
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    finding::{Finding, Severity},
+    paths::path_to_string,
+    timefmt::{parse_utc, unix_to_rfc3339},
+    x509::{self, CertificateInfo}
+};
+
+/// Seconds between 1970-01-01 and the plist epoch of 2001-01-01
+const PLIST_EPOCH: i64 = 978_307_200;
+
+// Nesting deeper than this is treated as corrupt (or hostile) input
+const MAX_DEPTH: usize = 512;
+
+// Plists are read whole; anything larger is not a plist worth parsing
+const MAX_PLIST_SIZE: u64 = 256 * 1024 * 1024;
+
+/// A property list value
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlistValue {
+    Bool(bool),
+    Integer(i128),
+    Real(f64),
+    /// Seconds since 2001-01-01 00:00:00 UTC
+    Date(f64),
+    Data(Vec<u8>),
+    String(String),
+    /// NSKeyedArchiver object reference (binary plists only)
+    Uid(u64),
+    Array(Vec<PlistValue>),
+    Dict(BTreeMap<String, PlistValue>)
+}
+
+impl PlistValue {
+    /// Value of a dictionary key
+    pub fn get(&self, key: &str) -> Option<&PlistValue> {
+        match self {
+            PlistValue::Dict(entries) => entries.get(key),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PlistValue::String(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PlistValue::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[PlistValue]> {
+        match self {
+            PlistValue::Array(items) => Some(items),
+            _ => None
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            PlistValue::Data(bytes) => Some(bytes),
+            _ => None
+        }
+    }
+
+    /// Dates as RFC 3339 UTC
+    pub fn date_string(&self) -> Option<String> {
+        match self {
+            PlistValue::Date(seconds) => Some(plist_date(*seconds)),
+            _ => None
+        }
+    }
+}
+
+/// Serialized as natural JSON: dates become RFC 3339 strings, data becomes base64 and UIDs
+/// become {"CF$UID": n} as in plutil's JSON form
+impl Serialize for PlistValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            PlistValue::Bool(value) => serializer.serialize_bool(*value),
+            PlistValue::Integer(value) => serializer.serialize_i128(*value),
+            PlistValue::Real(value) => serializer.serialize_f64(*value),
+            PlistValue::Date(seconds) => serializer.serialize_str(&plist_date(*seconds)),
+            PlistValue::Data(bytes) => serializer.serialize_str(&base64::encode(bytes)),
+            PlistValue::String(text) => serializer.serialize_str(text),
+            PlistValue::Uid(value) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("CF$UID", value)?;
+                map.end()
+            }
+            PlistValue::Array(items) => items.serialize(serializer),
+            PlistValue::Dict(entries) => entries.serialize(serializer)
+        }
+    }
+}
+
+impl fmt::Display for PlistValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+// Seconds since 2001 as RFC 3339, keeping sub-second precision
+fn plist_date(seconds: f64) -> String {
+    let whole = seconds.floor();
+    let nanos = ((seconds - whole) * 1e9).round().min(999_999_999.0) as u32;
+    unix_to_rfc3339(whole as i64 + PLIST_EPOCH, nanos)
+}
+
+/// Parses an XML or binary property list
+pub fn parse_plist(data: &[u8]) -> Result<PlistValue, String> {
+    if data.starts_with(b"bplist00") {
+        return BinaryPlist::new(data)?.parse();
+    }
+
+    if data.starts_with(b"bplist") {
+        let version = String::from_utf8_lossy(&data[6..data.len().min(8)]).to_string();
+        return Err(format!("Unsupported binary plist version '{}'", version));
+    }
+
+    XmlPlist::new(data)?.parse()
+}
+
+/// Reads and parses the property list at `path`
+pub fn mercy_plist<P: AsRef<Path>>(path: P) -> Result<PlistValue, String> {
+    let path = path.as_ref();
+    parse_plist(&read_file(Path::new(path))?)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let size = fs::metadata(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?.len();
+    if size > MAX_PLIST_SIZE {
+        return Err(format!("{} is larger than {} bytes", path.display(), MAX_PLIST_SIZE));
+    }
+
+    fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))
+}
+
+/* Binary property lists */
+
+struct BinaryPlist<'a> {
+    data: &'a [u8],
+    offsets: Vec<usize>,
+    ref_size: usize,
+    top: usize,
+    // Objects live between the header and the offset table
+    objects_end: usize
+}
+
+impl<'a> BinaryPlist<'a> {
+    fn new(data: &'a [u8]) -> Result<BinaryPlist<'a>, String> {
+        if data.len() < 8 + 32 {
+            return Err(format!("Binary plist is too short for a trailer ({} bytes)", data.len()));
+        }
+
+        let trailer = data.len() - 32;
+        let corrupt = |field: usize, problem: &str| format!("Corrupt binary plist trailer at offset {}: {}", trailer + field, problem);
+
+        let offset_size = data[trailer + 6] as usize;
+        let ref_size = data[trailer + 7] as usize;
+        let count = be_uint(&data[trailer + 8..trailer + 16]);
+        let top = be_uint(&data[trailer + 16..trailer + 24]);
+        let table = be_uint(&data[trailer + 24..trailer + 32]);
+
+        if !(1..=8).contains(&offset_size) {
+            return Err(corrupt(6, &format!("offset size {} is not 1 to 8 bytes", offset_size)));
+        }
+        if !(1..=8).contains(&ref_size) {
+            return Err(corrupt(7, &format!("object reference size {} is not 1 to 8 bytes", ref_size)));
+        }
+        if count == 0 || top >= count {
+            return Err(corrupt(16, &format!("top object {} is not among {} objects", top, count)));
+        }
+
+        let table_end = count.checked_mul(offset_size as u64).and_then(|length| length.checked_add(table));
+        if table < 8 || table_end.map(|end| end > trailer as u64).unwrap_or(true) {
+            return Err(corrupt(24, &format!("offset table at {} with {} entries does not fit before the trailer", table, count)));
+        }
+
+        let table = table as usize;
+        let offsets = data[table..table + count as usize * offset_size]
+            .chunks_exact(offset_size)
+            .enumerate()
+            .map(|(index, entry)| {
+                let offset = be_uint(entry);
+                if offset < 8 || offset >= table as u64 {
+                    Err(format!("Object {} offset {} (offset table entry at {}) is outside the object data", index, offset, table + index * offset_size))
+                } else {
+                    Ok(offset as usize)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BinaryPlist { data, offsets, ref_size, top: top as usize, objects_end: table })
+    }
+
+    fn parse(&self) -> Result<PlistValue, String> {
+        self.object(self.top, &mut Vec::new())
+    }
+
+    // Bytes of an object's payload, bounded by the offset table
+    fn bytes(&self, start: usize, length: usize, offset: usize) -> Result<&'a [u8], String> {
+        start.checked_add(length)
+            .filter(|end| *end <= self.objects_end)
+            .map(|end| &self.data[start..end])
+            .ok_or_else(|| format!("Object at offset {} runs past the object data ({} bytes from {})", offset, length, start))
+    }
+
+    // Element count from the marker's low nibble, or the integer that follows when it is 0xF
+    fn count(&self, marker: u8, offset: usize) -> Result<(usize, usize), String> {
+        let nibble = (marker & 0x0F) as usize;
+        if nibble != 0x0F {
+            return Ok((nibble, offset + 1));
+        }
+
+        let size_marker = *self.bytes(offset + 1, 1, offset)?.first().unwrap_or(&0);
+        if size_marker & 0xF0 != 0x10 || size_marker & 0x0F > 3 {
+            return Err(format!("Object at offset {} has an invalid length marker 0x{:02x}", offset, size_marker));
+        }
+
+        let width = 1usize << (size_marker & 0x0F);
+        let count = be_uint(self.bytes(offset + 2, width, offset)?);
+        Ok((usize::try_from(count).map_err(|_| format!("Object at offset {} has an impossible length", offset))?, offset + 2 + width))
+    }
+
+    // Object references following a collection marker
+    fn references(&self, start: usize, count: usize, offset: usize) -> Result<Vec<usize>, String> {
+        let length = count.checked_mul(self.ref_size).ok_or_else(|| format!("Collection at offset {} is impossibly large", offset))?;
+
+        self.bytes(start, length, offset)?
+            .chunks_exact(self.ref_size)
+            .map(|entry| {
+                let reference = be_uint(entry);
+                if reference < self.offsets.len() as u64 {
+                    Ok(reference as usize)
+                } else {
+                    Err(format!("Collection at offset {} references object {} of {}", offset, reference, self.offsets.len()))
+                }
+            })
+            .collect()
+    }
+
+    fn object(&self, index: usize, path: &mut Vec<usize>) -> Result<PlistValue, String> {
+        let offset = self.offsets[index];
+
+        if path.contains(&index) {
+            return Err(format!("Object {} at offset {} contains itself", index, offset));
+        }
+        if path.len() >= MAX_DEPTH {
+            return Err(format!("Objects nested more than {} deep at offset {}", MAX_DEPTH, offset));
+        }
+
+        let marker = self.data[offset];
+
+        let value = match marker >> 4 {
+            0x0 => match marker {
+                0x08 => PlistValue::Bool(false),
+                0x09 => PlistValue::Bool(true),
+                _ => return Err(format!("Unsupported object marker 0x{:02x} at offset {}", marker, offset))
+            },
+            0x1 => {
+                let width = 1usize << (marker & 0x0F);
+                if width > 16 {
+                    return Err(format!("Integer at offset {} is {} bytes wide", offset, width));
+                }
+                let bytes = self.bytes(offset + 1, width, offset)?;
+                // 8 and 16 byte integers are signed; narrower ones are unsigned
+                PlistValue::Integer(match width {
+                    8 => be_uint(bytes) as i64 as i128,
+                    16 => bytes.iter().fold(0i128, |acc, byte| (acc << 8) | *byte as i128),
+                    _ => be_uint(bytes) as i128
+                })
+            }
+            0x2 => match marker & 0x0F {
+                2 => PlistValue::Real(f32::from_be_bytes(self.bytes(offset + 1, 4, offset)?.try_into().unwrap_or_default()) as f64),
+                3 => PlistValue::Real(f64::from_be_bytes(self.bytes(offset + 1, 8, offset)?.try_into().unwrap_or_default())),
+                _ => return Err(format!("Real at offset {} has an unsupported width marker 0x{:02x}", offset, marker))
+            },
+            0x3 if marker == 0x33 => PlistValue::Date(f64::from_be_bytes(self.bytes(offset + 1, 8, offset)?.try_into().unwrap_or_default())),
+            0x4 => {
+                let (length, start) = self.count(marker, offset)?;
+                PlistValue::Data(self.bytes(start, length, offset)?.to_vec())
+            }
+            0x5 => {
+                let (length, start) = self.count(marker, offset)?;
+                // Nominally ASCII, but Latin-1 is what writers actually emit for single byte strings
+                PlistValue::String(self.bytes(start, length, offset)?.iter().map(|byte| *byte as char).collect())
+            }
+            0x6 => {
+                let (units, start) = self.count(marker, offset)?;
+                let length = units.checked_mul(2).ok_or_else(|| format!("String at offset {} is impossibly long", offset))?;
+                let units: Vec<u16> = self.bytes(start, length, offset)?.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+                PlistValue::String(String::from_utf16_lossy(&units))
+            }
+            0x7 => {
+                let (length, start) = self.count(marker, offset)?;
+                PlistValue::String(String::from_utf8_lossy(self.bytes(start, length, offset)?).to_string())
+            }
+            0x8 => PlistValue::Uid(be_uint(self.bytes(offset + 1, (marker & 0x0F) as usize + 1, offset)?)),
+            // Arrays and sets (sets have no JSON form, so they become arrays)
+            0xA | 0xC => {
+                let (count, start) = self.count(marker, offset)?;
+                path.push(index);
+                let items = self.references(start, count, offset)?
+                    .into_iter()
+                    .map(|reference| self.object(reference, path))
+                    .collect::<Result<Vec<_>, _>>()?;
+                path.pop();
+                PlistValue::Array(items)
+            }
+            0xD => {
+                let (count, start) = self.count(marker, offset)?;
+                let references = self.references(start, count.checked_mul(2).ok_or_else(|| format!("Dictionary at offset {} is impossibly large", offset))?, offset)?;
+                let (keys, values) = references.split_at(count);
+
+                path.push(index);
+                let mut entries = BTreeMap::new();
+                for (key, value) in keys.iter().zip(values) {
+                    let key = match self.object(*key, path)? {
+                        PlistValue::String(key) => key,
+                        _ => return Err(format!("Dictionary at offset {} has a key that is not a string (object {})", offset, key))
+                    };
+                    entries.insert(key, self.object(*value, path)?);
+                }
+                path.pop();
+                PlistValue::Dict(entries)
+            }
+            _ => return Err(format!("Unsupported object marker 0x{:02x} at offset {}", marker, offset))
+        };
+
+        Ok(value)
+    }
+}
+
+// Big-endian unsigned integer of up to 8 bytes
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64)
+}
+
+/* XML property lists */
+
+// Just enough XML for the plist DTD: elements, text, entities, CDATA and comments
+struct XmlPlist<'a> {
+    text: &'a str,
+    position: usize
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    empty: bool,
+    offset: usize
+}
+
+impl<'a> XmlPlist<'a> {
+    fn new(data: &'a [u8]) -> Result<XmlPlist<'a>, String> {
+        let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+        let text = std::str::from_utf8(data).map_err(|e| format!("Not a property list: invalid UTF-8 at offset {}", e.valid_up_to()))?;
+
+        if !text.trim_start().starts_with('<') {
+            return Err("Not a property list: neither bplist00 nor XML".to_string());
+        }
+
+        Ok(XmlPlist { text, position: 0 })
+    }
+
+    fn parse(&mut self) -> Result<PlistValue, String> {
+        let tag = self.tag()?.ok_or("Not a property list: no elements found")?;
+
+        if tag.name != "plist" || tag.closing {
+            return self.value(tag, 0);
+        }
+        if tag.empty {
+            return Err(format!("Empty <plist> element at offset {}", tag.offset));
+        }
+
+        let first = self.tag()?.ok_or("Property list truncated after <plist>")?;
+        let value = self.value(first, 0)?;
+        self.expect_close("plist")?;
+        Ok(value)
+    }
+
+    fn error(&self, offset: usize, problem: &str) -> String {
+        format!("Malformed XML plist at offset {}: {}", offset, problem)
+    }
+
+    // Next start or end tag, skipping whitespace, declarations, comments and the DOCTYPE
+    fn tag(&mut self) -> Result<Option<Tag<'a>>, String> {
+        loop {
+            let rest = &self.text[self.position..];
+            let skipped = rest.len() - rest.trim_start().len();
+            self.position += skipped;
+            let rest = &self.text[self.position..];
+
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            if !rest.starts_with('<') {
+                return Err(self.error(self.position, "unexpected text between elements"));
+            }
+
+            let terminator = if rest.starts_with("<?") {
+                Some("?>")
+            } else if rest.starts_with("<!--") {
+                Some("-->")
+            } else if rest.starts_with("<!") {
+                Some(">")
+            } else {
+                None
+            };
+
+            if let Some(terminator) = terminator {
+                let end = rest.find(terminator).ok_or_else(|| self.error(self.position, "unterminated declaration or comment"))?;
+                self.position += end + terminator.len();
+                continue;
+            }
+
+            let offset = self.position;
+            let end = rest.find('>').ok_or_else(|| self.error(offset, "unterminated tag"))?;
+            let inner = &rest[1..end];
+            self.position += end + 1;
+
+            let closing = inner.starts_with('/');
+            let empty = inner.ends_with('/');
+            let inner = inner.trim_start_matches('/').trim_end_matches('/');
+            let name = inner.split(|c: char| c.is_whitespace()).next().unwrap_or("");
+
+            if name.is_empty() {
+                return Err(self.error(offset, "tag without a name"));
+            }
+
+            return Ok(Some(Tag { name, closing, empty, offset }));
+        }
+    }
+
+    fn expect_close(&mut self, name: &str) -> Result<(), String> {
+        let offset = self.position;
+        match self.tag()? {
+            Some(tag) if tag.closing && tag.name == name => Ok(()),
+            Some(tag) => Err(self.error(tag.offset, &format!("expected </{}>, found <{}{}>", name, if tag.closing { "/" } else { "" }, tag.name))),
+            None => Err(self.error(offset, &format!("missing </{}>", name)))
+        }
+    }
+
+    // Character data up to the matching end tag, with entities and CDATA resolved
+    fn content(&mut self, tag: &Tag) -> Result<String, String> {
+        if tag.empty {
+            return Ok(String::new());
+        }
+
+        let close = format!("</{}", tag.name);
+        let mut output = String::new();
+
+        loop {
+            let rest = &self.text[self.position..];
+
+            if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata.find("]]>").ok_or_else(|| self.error(self.position, "unterminated CDATA section"))?;
+                output.push_str(&cdata[..end]);
+                self.position += 9 + end + 3;
+                continue;
+            }
+
+            if rest.starts_with("<!--") {
+                let end = rest.find("-->").ok_or_else(|| self.error(self.position, "unterminated comment"))?;
+                self.position += end + 3;
+                continue;
+            }
+
+            if rest.starts_with(&close) {
+                let end = rest.find('>').ok_or_else(|| self.error(self.position, "unterminated tag"))?;
+                self.position += end + 1;
+                return Ok(output);
+            }
+
+            if rest.starts_with('<') {
+                return Err(self.error(self.position, &format!("unexpected element inside <{}>", tag.name)));
+            }
+
+            if let Some(reference) = rest.strip_prefix('&') {
+                let end = reference.find(';').filter(|end| *end <= 10).ok_or_else(|| self.error(self.position, "unterminated entity"))?;
+                let entity = &reference[..end];
+                output.push(entity_char(entity).ok_or_else(|| self.error(self.position, &format!("unknown entity &{};", entity)))?);
+                self.position += end + 2;
+                continue;
+            }
+
+            let next = rest.find(['<', '&']).ok_or_else(|| self.error(tag.offset, &format!("missing </{}>", tag.name)))?;
+            output.push_str(&rest[..next]);
+            self.position += next;
+        }
+    }
+
+    fn value(&mut self, tag: Tag<'a>, depth: usize) -> Result<PlistValue, String> {
+        if tag.closing {
+            return Err(self.error(tag.offset, &format!("unexpected </{}>", tag.name)));
+        }
+        if depth >= MAX_DEPTH {
+            return Err(self.error(tag.offset, &format!("values nested more than {} deep", MAX_DEPTH)));
+        }
+
+        let offset = tag.offset;
+        let value = match tag.name {
+            "true" | "false" => {
+                let value = tag.name == "true";
+                if !tag.empty {
+                    self.expect_close(tag.name)?;
+                }
+                PlistValue::Bool(value)
+            }
+            "string" => PlistValue::String(self.content(&tag)?),
+            "integer" => {
+                let text = self.content(&tag)?;
+                let text = text.trim();
+                let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                    Some(hex) => i128::from_str_radix(hex, 16),
+                    None => text.parse::<i128>()
+                };
+                PlistValue::Integer(parsed.map_err(|_| self.error(offset, &format!("invalid integer '{}'", text)))?)
+            }
+            "real" => {
+                let text = self.content(&tag)?;
+                PlistValue::Real(text.trim().parse::<f64>().map_err(|_| self.error(offset, &format!("invalid real '{}'", text.trim())))?)
+            }
+            "date" => {
+                let text = self.content(&tag)?;
+                let unix = parse_utc(text.trim()).map_err(|_| self.error(offset, &format!("invalid date '{}'", text.trim())))?;
+                PlistValue::Date((unix - PLIST_EPOCH) as f64)
+            }
+            "data" => {
+                let text = self.content(&tag)?;
+                let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+                PlistValue::Data(base64::decode(&compact).map_err(|e| self.error(offset, &format!("invalid base64 data: {}", e)))?)
+            }
+            "array" => {
+                let mut items = Vec::new();
+                if !tag.empty {
+                    loop {
+                        let next = self.tag()?.ok_or_else(|| self.error(offset, "missing </array>"))?;
+                        if next.closing && next.name == "array" {
+                            break;
+                        }
+                        items.push(self.value(next, depth + 1)?);
+                    }
+                }
+                PlistValue::Array(items)
+            }
+            "dict" => {
+                let mut entries = BTreeMap::new();
+                if !tag.empty {
+                    loop {
+                        let next = self.tag()?.ok_or_else(|| self.error(offset, "missing </dict>"))?;
+                        if next.closing && next.name == "dict" {
+                            break;
+                        }
+                        if next.name != "key" || next.closing {
+                            return Err(self.error(next.offset, &format!("expected <key> in <dict>, found <{}>", next.name)));
+                        }
+
+                        let key = self.content(&next)?;
+                        let value = self.tag()?.ok_or_else(|| self.error(next.offset, &format!("key '{}' has no value", key)))?;
+                        entries.insert(key, self.value(value, depth + 1)?);
+                    }
+                }
+                PlistValue::Dict(entries)
+            }
+            other => return Err(self.error(offset, &format!("unknown element <{}>", other)))
+        };
+
+        Ok(value)
+    }
+}
+
+// The predefined XML entities and character references
+fn entity_char(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/* Configuration profiles */
+
+/// A payload inside a configuration profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilePayload {
+    pub payload_type: String,
+    pub display_name: Option<String>,
+    pub identifier: Option<String>,
+    /// Certificates the payload installs (root, PKCS#1 and PEM certificate payloads)
+    pub certificates: Vec<CertificateInfo>,
+    /// Remaining settings flattened to dotted keys; passwords and shared secrets are redacted
+    pub settings: BTreeMap<String, String>
+}
+
+/// Summary of an iOS / macOS configuration profile
+#[derive(Debug, Clone, Serialize)]
+pub struct MobileconfigReport {
+    pub path: String,
+    pub display_name: Option<String>,
+    pub identifier: Option<String>,
+    pub organization: Option<String>,
+    pub uuid: Option<String>,
+    pub removal_disallowed: bool,
+    /// Wrapped in PKCS#7 SignedData (the signature is not verified)
+    pub signed: bool,
+    pub signer_certificates: Vec<CertificateInfo>,
+    pub payloads: Vec<ProfilePayload>,
+    /// Parts of the profile that could not be read
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for MobileconfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+
+        writeln!(f, "Profile: {}", self.path)?;
+        writeln!(f, "Name: {}", unknown(&self.display_name))?;
+        writeln!(f, "Identifier: {}", unknown(&self.identifier))?;
+        writeln!(f, "Organization: {}", unknown(&self.organization))?;
+        writeln!(f, "Removal disallowed: {}", self.removal_disallowed)?;
+        writeln!(f, "Signed: {}", self.signed)?;
+        for certificate in &self.signer_certificates {
+            writeln!(f, "Signer: {} (SHA-256 {})", certificate.subject, certificate.sha256)?;
+        }
+
+        writeln!(f, "Payloads ({}):", self.payloads.len())?;
+        for payload in &self.payloads {
+            writeln!(f, "  {}{}", payload.payload_type, payload.display_name.as_ref().map(|name| format!(" ({})", name)).unwrap_or_default())?;
+            for certificate in &payload.certificates {
+                writeln!(f, "    Certificate: {} (SHA-256 {}){}", certificate.subject, certificate.sha256, if certificate.self_signed { "  [self-signed]" } else { "" })?;
+            }
+            for (key, value) in &payload.settings {
+                writeln!(f, "    {} = {}", key, value)?;
+            }
+        }
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MobileconfigReport {
+    /// Profile contents commonly abused by malicious profiles (traffic interception, device control)
+    pub fn findings(&self) -> Vec<Finding> {
+        let target = self.identifier.clone().unwrap_or_else(|| self.path.clone());
+        let mut findings = Vec::new();
+
+        let roots: Vec<String> = self.payloads.iter()
+            .filter(|payload| payload.payload_type == "com.apple.security.root")
+            .flat_map(|payload| &payload.certificates)
+            .map(|certificate| format!("{} (SHA-256 {})", certificate.subject, certificate.sha256))
+            .collect();
+        if !roots.is_empty() {
+            findings.push(Finding::new(
+                "mobileconfig.root-certificate",
+                "Profile installs a root certificate",
+                Severity::High,
+                &target,
+                &roots.join("; "),
+                "Only install root certificates from a known issuer; a trusted root allows TLS interception"
+            ));
+        }
+
+        let mdm: Vec<String> = self.payloads_of("com.apple.mdm")
+            .map(|payload| payload.settings.get("ServerURL").cloned().unwrap_or_else(|| "com.apple.mdm payload".to_string()))
+            .collect();
+        if !mdm.is_empty() {
+            findings.push(Finding::new(
+                "mobileconfig.mdm-enrollment",
+                "Profile enrolls the device in MDM",
+                Severity::High,
+                &target,
+                &mdm.join("; "),
+                "Confirm the MDM server belongs to your organization before installing"
+            ));
+        }
+
+        let proxies: Vec<String> = self.payloads.iter()
+            .flat_map(|payload| payload.settings.iter().map(move |(key, value)| (payload, key, value)))
+            .filter(|(_, key, _)| {
+                let name = key.rsplit('.').next().unwrap_or(key);
+                ["ProxyServer", "ProxyPACURL", "HTTPProxy", "HTTPSProxy", "ProxyAutoConfigURLString"].contains(&name)
+            })
+            .map(|(payload, key, value)| format!("{} {}={}", payload.payload_type, key, value))
+            .collect();
+        if !proxies.is_empty() {
+            findings.push(Finding::new(
+                "mobileconfig.proxy",
+                "Profile configures a proxy",
+                Severity::Medium,
+                &target,
+                &proxies.join("; "),
+                "Verify the proxy host; a profile-defined proxy sees all web traffic it is applied to"
+            ));
+        }
+
+        let vpns: Vec<String> = self.payloads.iter()
+            .filter(|payload| payload.payload_type.starts_with("com.apple.vpn.managed"))
+            .map(|payload| {
+                let server = payload.settings.iter()
+                    .find(|(key, _)| key.ends_with("RemoteAddress") || key.ends_with("CommRemoteAddress"))
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_else(|| "unknown server".to_string());
+                format!("{} via {}", payload.display_name.clone().unwrap_or_else(|| payload.payload_type.clone()), server)
+            })
+            .collect();
+        if !vpns.is_empty() {
+            findings.push(Finding::new(
+                "mobileconfig.vpn",
+                "Profile configures a VPN",
+                Severity::Medium,
+                &target,
+                &vpns.join("; "),
+                "Verify the VPN server; traffic routed through it can be observed and modified"
+            ));
+        }
+
+        let dns: Vec<String> = self.payloads_of("com.apple.dnsSettings.managed")
+            .map(|payload| payload.settings.iter()
+                .filter(|(key, _)| key.starts_with("DNSSettings.Server"))
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(", "))
+            .collect();
+        if !dns.is_empty() {
+            findings.push(Finding::new(
+                "mobileconfig.dns",
+                "Profile overrides DNS resolution",
+                Severity::Medium,
+                &target,
+                &dns.join("; "),
+                "Verify the resolver; encrypted DNS settings send every lookup to the configured server"
+            ));
+        }
+
+        if self.removal_disallowed {
+            findings.push(Finding::new(
+                "mobileconfig.removal-disallowed",
+                "Profile cannot be removed by the user",
+                Severity::Medium,
+                &target,
+                "PayloadRemovalDisallowed is true",
+                "Avoid installing non-removable profiles from outside your organization"
+            ));
+        }
+
+        if !self.signed {
+            findings.push(Finding::new(
+                "mobileconfig.unsigned",
+                "Profile is not signed",
+                Severity::Low,
+                &target,
+                "No PKCS#7 signature around the profile",
+                "Distribute profiles signed by an identifiable certificate"
+            ));
+        }
+
+        findings
+    }
+
+    fn payloads_of<'a>(&'a self, payload_type: &'a str) -> impl Iterator<Item = &'a ProfilePayload> + 'a {
+        self.payloads.iter().filter(move |payload| payload.payload_type == payload_type)
+    }
+}
+
+/// Summarizes the configuration profile at `path`, signed or unsigned
+pub fn mercy_mobileconfig<P: AsRef<Path>>(path: P) -> Result<MobileconfigReport, String> {
+    let path = path.as_ref();
+    let data = read_file(Path::new(path))?;
+    let mut notes = Vec::new();
+
+    // Signed profiles are DER SignedData with the plist as the encapsulated content
+    let (plist, signed, signer_certificates) = if data.first() == Some(&0x30) {
+        let content = x509::pkcs7_content(&data)?.ok_or("Signed profile has detached content")?;
+        let certificates = x509::pkcs7_certificates(&data)?
+            .into_iter()
+            .filter_map(|der| match x509::parse_certificate(der) {
+                Ok(certificate) => Some(certificate),
+                Err(e) => {
+                    notes.push(format!("Signer certificate: {}", e));
+                    None
+                }
+            })
+            .collect();
+        (parse_plist(&content)?, true, certificates)
+    } else {
+        (parse_plist(&data)?, false, Vec::new())
+    };
+
+    if plist.get("PayloadType").and_then(PlistValue::as_str) != Some("Configuration") {
+        notes.push("Top-level PayloadType is not Configuration".to_string());
+    }
+
+    let text = |key: &str| plist.get(key).and_then(PlistValue::as_str).map(str::to_string);
+    let payloads = plist.get("PayloadContent")
+        .and_then(PlistValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, payload)| profile_payload(index, payload, &mut notes))
+        .collect();
+
+    Ok(MobileconfigReport {
+        path: path_to_string(path),
+        display_name: text("PayloadDisplayName"),
+        identifier: text("PayloadIdentifier"),
+        organization: text("PayloadOrganization"),
+        uuid: text("PayloadUUID"),
+        removal_disallowed: plist.get("PayloadRemovalDisallowed").and_then(PlistValue::as_bool).unwrap_or(false),
+        signed,
+        signer_certificates,
+        payloads,
+        notes
+    })
+}
+
+fn profile_payload(index: usize, payload: &PlistValue, notes: &mut Vec<String>) -> ProfilePayload {
+    let text = |key: &str| payload.get(key).and_then(PlistValue::as_str).map(str::to_string);
+    let payload_type = text("PayloadType").unwrap_or_else(|| "unknown".to_string());
+    let mut certificates = Vec::new();
+
+    let is_certificate = ["com.apple.security.root", "com.apple.security.pkcs1", "com.apple.security.pem"].contains(&payload_type.as_str());
+    if is_certificate {
+        match payload.get("PayloadContent").and_then(PlistValue::as_data) {
+            Some(content) => match x509::parse_certificate(&certificate_der(content)) {
+                Ok(certificate) => certificates.push(certificate),
+                Err(e) => notes.push(format!("Payload {} ({}): {}", index, payload_type, e))
+            },
+            None => notes.push(format!("Payload {} ({}): no certificate data", index, payload_type))
+        }
+    }
+
+    let mut settings = BTreeMap::new();
+    if let PlistValue::Dict(entries) = payload {
+        for (key, value) in entries {
+            // Identity keys are reported separately; certificate contents are parsed above
+            if key.starts_with("Payload") && (key != "PayloadContent" || is_certificate) {
+                continue;
+            }
+            flatten_setting(key, value, &mut settings, 0);
+        }
+    }
+
+    ProfilePayload {
+        payload_type,
+        display_name: text("PayloadDisplayName"),
+        identifier: text("PayloadIdentifier"),
+        certificates,
+        settings
+    }
+}
+
+// Certificate payloads hold DER or PEM
+fn certificate_der(content: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(content);
+    match (text.find("-----BEGIN CERTIFICATE-----"), text.find("-----END CERTIFICATE-----")) {
+        (Some(start), Some(end)) if start < end => {
+            let body: String = text[start + 27..end].chars().filter(|c| !c.is_whitespace()).collect();
+            base64::decode(body).unwrap_or_default()
+        }
+        _ => content.to_vec()
+    }
+}
+
+// Scalars keyed by their dotted path; arrays of scalars are joined
+fn flatten_setting(key: &str, value: &PlistValue, settings: &mut BTreeMap<String, String>, depth: usize) {
+    let lowered = key.to_ascii_lowercase();
+    if lowered.contains("password") || lowered.contains("sharedsecret") || lowered.ends_with("pin") {
+        settings.insert(key.to_string(), "(redacted)".to_string());
+        return;
+    }
+
+    match value {
+        PlistValue::Dict(entries) if depth < 8 => {
+            for (child, value) in entries {
+                flatten_setting(&format!("{}.{}", key, child), value, settings, depth + 1);
+            }
+        }
+        PlistValue::Array(items) if items.iter().all(|item| !matches!(item, PlistValue::Dict(_) | PlistValue::Array(_))) => {
+            settings.insert(key.to_string(), items.iter().map(setting_text).collect::<Vec<_>>().join(", "));
+        }
+        PlistValue::Array(items) if depth < 8 => {
+            for (index, item) in items.iter().enumerate() {
+                flatten_setting(&format!("{}.{}", key, index), item, settings, depth + 1);
+            }
+        }
+        _ => {
+            settings.insert(key.to_string(), setting_text(value));
+        }
+    }
+}
+
+fn setting_text(value: &PlistValue) -> String {
+    match value {
+        PlistValue::Bool(value) => value.to_string(),
+        PlistValue::Integer(value) => value.to_string(),
+        PlistValue::Real(value) => value.to_string(),
+        PlistValue::Date(seconds) => plist_date(*seconds),
+        PlistValue::Data(bytes) => format!("<{} bytes>", bytes.len()),
+        PlistValue::String(text) => text.clone(),
+        PlistValue::Uid(value) => format!("UID {}", value),
+        PlistValue::Array(items) => format!("<{} items>", items.len()),
+        PlistValue::Dict(entries) => format!("<{} keys>", entries.len())
+    }
+}
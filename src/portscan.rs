@@ -0,0 +1,202 @@
+/*
+    TCP port checks: one port, or a range scanned with a bounded pool of connect attempts
+
+    A port is open when the handshake completes, closed when the host answers with a reset, and
+    filtered when nothing answers before the timeout (or the network reports it unreachable).
+    Every attempt has a connect timeout, so a dropped SYN costs at most that long and a range
+    costs roughly ports / concurrency timeouts at worst.
+*/
+
+use std::{
+    fmt,
+    io,
+    net::{IpAddr, SocketAddr, TcpStream},
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+
+use crate::{checks::resolve_target, trailing_options};
+
+/// What a connect attempt found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortState {
+    Open,
+    /// Refused (RST)
+    Closed,
+    /// No answer within the timeout, or unreachable
+    Filtered
+}
+
+impl fmt::Display for PortState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortState::Open => write!(f, "open"),
+            PortState::Closed => write!(f, "closed"),
+            PortState::Filtered => write!(f, "filtered")
+        }
+    }
+}
+
+/// Options for `mercy_port_check` and `mercy_port_scan`
+#[derive(Debug, Clone)]
+pub struct PortOptions {
+    /// Per connect attempt
+    pub timeout: Duration,
+    /// Connect attempts in flight at once while scanning
+    pub concurrency: usize
+}
+
+impl Default for PortOptions {
+    fn default() -> Self {
+        PortOptions { timeout: Duration::from_secs(2), concurrency: 128 }
+    }
+}
+
+/// Result of connecting to one port
+#[derive(Debug, Clone, Serialize)]
+pub struct PortCheck {
+    pub address: SocketAddr,
+    pub state: PortState,
+    pub open: bool,
+    /// Time until the handshake completed or was refused
+    pub rtt_ms: Option<f64>,
+    /// Why the port is not open
+    pub detail: Option<String>
+}
+
+impl fmt::Display for PortCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.address, self.state)?;
+        if let Some(rtt) = self.rtt_ms {
+            write!(f, " ({:.1} ms)", rtt)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Open ports of a range, with counts of the rest
+#[derive(Debug, Clone, Serialize)]
+pub struct PortScanReport {
+    pub host: String,
+    pub address: IpAddr,
+    pub first_port: u16,
+    pub last_port: u16,
+    pub scanned: usize,
+    pub open: Vec<u16>,
+    pub closed: usize,
+    pub filtered: usize,
+    pub elapsed_ms: f64
+}
+
+impl fmt::Display for PortScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for port in &self.open {
+            writeln!(f, "{}", port)?;
+        }
+        write!(
+            f,
+            "Scanned {} ports ({}-{}) on {}: {} open, {} closed, {} filtered ({:.1} s)",
+            self.scanned, self.first_port, self.last_port, self.address, self.open.len(), self.closed, self.filtered, self.elapsed_ms / 1000.0
+        )
+    }
+}
+
+/// Connects once to "host:port"
+pub fn mercy_port_check(target: &str, options: &PortOptions) -> Result<PortCheck, String> {
+    let address = resolve_target(target, 0)?;
+    if address.port() == 0 {
+        return Err(format!("Expected \"host:port\", got '{}'", target.trim()));
+    }
+    Ok(probe(address, options.timeout))
+}
+
+/// Connects to every port from `first` to `last` on `host`, `options.concurrency` at a time
+pub fn mercy_port_scan(host: &str, first: u16, last: u16, options: &PortOptions) -> Result<PortScanReport, String> {
+    if first == 0 || first > last {
+        return Err(format!("Invalid port range {}-{} (expected 1-65535, start no higher than end)", first, last));
+    }
+    let address = resolve_target(host, 0)?.ip();
+
+    let start = Instant::now();
+    let work = Mutex::new(first..=last);
+    let results = Mutex::new(Vec::new());
+    let workers = options.concurrency.max(1).min((last - first) as usize + 1);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| while let Some(port) = work.lock().ok().and_then(|mut ports| ports.next()) {
+                let state = probe(SocketAddr::new(address, port), options.timeout).state;
+                if let Ok(mut results) = results.lock() {
+                    results.push((port, state));
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let count = |wanted: PortState| results.iter().filter(|(_, state)| *state == wanted).count();
+    let mut open: Vec<u16> = results.iter().filter(|(_, state)| *state == PortState::Open).map(|(port, _)| *port).collect();
+    open.sort_unstable();
+
+    Ok(PortScanReport {
+        host: host.trim().to_string(),
+        address,
+        first_port: first,
+        last_port: last,
+        scanned: results.len(),
+        closed: count(PortState::Closed),
+        filtered: count(PortState::Filtered),
+        open,
+        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0
+    })
+}
+
+fn probe(address: SocketAddr, timeout: Duration) -> PortCheck {
+    let start = Instant::now();
+    let rtt = || Some(start.elapsed().as_secs_f64() * 1000.0);
+
+    let (state, rtt_ms, detail) = match TcpStream::connect_timeout(&address, timeout) {
+        Ok(_) => (PortState::Open, rtt(), None),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => (PortState::Closed, rtt(), Some("connection refused".to_string())),
+        Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock) => {
+            (PortState::Filtered, None, Some(format!("no answer within {} s", timeout.as_secs_f64())))
+        },
+        Err(e) => (PortState::Filtered, None, Some(e.to_string()))
+    };
+
+    PortCheck { address, state, open: state == PortState::Open, rtt_ms, detail }
+}
+
+fn options_from_pairs(pairs: Vec<(&str, &str)>) -> Result<PortOptions, String> {
+    let mut options = PortOptions::default();
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.timeout = Duration::from_secs_f64(value.parse().ok().filter(|secs: &f64| *secs > 0.0).ok_or_else(|| format!("Invalid timeout '{}'", value))?),
+            _ => options.concurrency = value.parse().ok().filter(|workers: &usize| *workers > 0).ok_or_else(|| format!("Invalid concurrency '{}'", value))?
+        }
+    }
+    Ok(options)
+}
+
+// Parses "host:port [timeout=2]"
+pub(crate) fn port_check_from_args(input: &str) -> Result<PortCheck, String> {
+    let (target, pairs) = trailing_options(input, &["timeout"]);
+    mercy_port_check(&target, &options_from_pairs(pairs)?)
+}
+
+// Parses "host,start-end [timeout=2] [concurrency=128]" (or "host,port" for one)
+pub(crate) fn port_scan_from_args(input: &str) -> Result<PortScanReport, String> {
+    let (target, pairs) = trailing_options(input, &["timeout", "concurrency"]);
+    let (host, range) = target.rsplit_once(',').ok_or_else(|| format!("Expected \"host,start-end\", got '{}'", target))?;
+    let range = range.trim();
+    let (first, last) = range.split_once('-').unwrap_or((range, range));
+    let port = |text: &str| text.trim().parse::<u16>().map_err(|_| format!("Invalid port '{}' in range '{}'", text.trim(), range));
+
+    mercy_port_scan(host, port(first)?, port(last)?, &options_from_pairs(pairs)?)
+}
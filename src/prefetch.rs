@@ -0,0 +1,232 @@
+/*
+    Windows prefetch (.pf) parsing
+
+    Supports format versions 17 (XP/2003), 23 (Vista/7), 26 (8.1), 30 (10) and 31 (11), including
+    the MAM (LZXPRESS Huffman) compressed container used since Windows 10. Each version has its
+    own layout function feeding the shared `PrefetchInfo` output.
+*/
+
+use std::{fmt, fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    paths::path_to_string,
+    registry::{le32, le64, utf16},
+    timefmt::filetime_to_rfc3339,
+    xpress::decompress_huffman
+};
+
+// Refuse to inflate prefetch files beyond this size
+const MAX_UNCOMPRESSED: usize = 64 * 1024 * 1024;
+
+/// A volume referenced by a prefetch file
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchVolume {
+    pub device_path: String,
+    pub serial: String,
+    pub created: Option<String>
+}
+
+/// Parsed prefetch file
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchInfo {
+    pub path: String,
+    pub version: u32,
+    /// Windows release family the format version belongs to
+    pub format: String,
+    pub compressed: bool,
+    pub executable: String,
+    /// Prefetch hash of the executable path, as in the file name
+    pub hash: String,
+    pub run_count: u32,
+    /// Most recent first; one entry before Windows 8, up to eight after
+    pub last_run_times: Vec<String>,
+    pub referenced_files: Vec<String>,
+    pub volumes: Vec<PrefetchVolume>
+}
+
+impl fmt::Display for PrefetchInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Prefetch: {}", self.path)?;
+        writeln!(f, "Format: version {} ({}){}", self.version, self.format, if self.compressed { ", MAM compressed" } else { "" })?;
+        writeln!(f, "Executable: {}", self.executable)?;
+        writeln!(f, "Hash: {}", self.hash)?;
+        writeln!(f, "Run count: {}", self.run_count)?;
+
+        for (index, time) in self.last_run_times.iter().enumerate() {
+            writeln!(f, "Last run {}: {}", index + 1, time)?;
+        }
+
+        for volume in &self.volumes {
+            writeln!(f, "Volume: {} (serial {}, created {})", volume.device_path, volume.serial, volume.created.as_deref().unwrap_or("unknown"))?;
+        }
+
+        writeln!(f, "Referenced files ({}):", self.referenced_files.len())?;
+        for file in &self.referenced_files {
+            writeln!(f, "  {}", file)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Section offsets common to every version (the file information block at 0x54)
+struct Sections {
+    metrics_offset: usize,
+    filenames_offset: usize,
+    filenames_size: usize,
+    volumes_offset: usize,
+    volumes_count: usize
+}
+
+/// Parses a prefetch file, decompressing Windows 10+ MAM containers first
+pub fn mercy_prefetch<P: AsRef<Path>>(path: P) -> Result<PrefetchInfo, String> {
+    let path = path.as_ref();
+    let raw = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let (data, compressed) = decompress_mam(&raw)?;
+    parse_prefetch(&path_to_string(path), &data, compressed)
+}
+
+// Returns the uncompressed SCCA data and whether it was compressed
+fn decompress_mam(raw: &[u8]) -> Result<(Vec<u8>, bool), String> {
+    if raw.get(..3) != Some(b"MAM") {
+        return Ok((raw.to_vec(), false));
+    }
+
+    let flags = *raw.get(3).ok_or("Truncated MAM header")?;
+    let size = le32(raw, 4).ok_or("Truncated MAM header")? as usize;
+
+    if flags & 0x0F != 4 {
+        return Err(format!("Unsupported MAM compression method {}", flags & 0x0F));
+    }
+
+    if size > MAX_UNCOMPRESSED {
+        return Err(format!("MAM header claims an implausible size ({} bytes)", size));
+    }
+
+    // A checksum precedes the data when the high bit is set
+    let start = if flags & 0x80 != 0 { 12 } else { 8 };
+    let data = decompress_huffman(raw.get(start..).ok_or("Truncated MAM header")?, size)?;
+    Ok((data, true))
+}
+
+fn parse_prefetch(path: &str, data: &[u8], compressed: bool) -> Result<PrefetchInfo, String> {
+    if data.len() < 84 || data.get(4..8) != Some(b"SCCA") {
+        return Err("Not a prefetch file: missing SCCA signature".to_string());
+    }
+
+    let version = le32(data, 0).unwrap_or(0);
+    let sections = Sections {
+        metrics_offset: le32(data, 0x54).ok_or("Truncated file information")? as usize,
+        filenames_offset: le32(data, 0x64).ok_or("Truncated file information")? as usize,
+        filenames_size: le32(data, 0x68).ok_or("Truncated file information")? as usize,
+        volumes_offset: le32(data, 0x6C).ok_or("Truncated file information")? as usize,
+        volumes_count: le32(data, 0x70).ok_or("Truncated file information")? as usize
+    };
+
+    let (format, run_count, last_run_times, volume_entry_size) = match version {
+        17 => version_17(data)?,
+        23 => version_23(data)?,
+        26 => version_26(data, &sections)?,
+        30 | 31 => version_30(data, &sections, version)?,
+        other => return Err(format!("Unsupported prefetch version {}", other))
+    };
+
+    Ok(PrefetchInfo {
+        path: path.to_string(),
+        version,
+        format: format.to_string(),
+        compressed,
+        executable: utf16(data.get(16..76).unwrap_or(&[])),
+        hash: format!("{:08X}", le32(data, 76).unwrap_or(0)),
+        run_count,
+        last_run_times,
+        referenced_files: filenames(data, &sections),
+        volumes: volumes(data, &sections, volume_entry_size)
+    })
+}
+
+type VersionFields = (&'static str, u32, Vec<String>, usize);
+
+// Windows XP / Server 2003: one last run time at 0x78, run count at 0x90, 40-byte volume entries
+fn version_17(data: &[u8]) -> Result<VersionFields, String> {
+    let last_run = le64(data, 0x78).ok_or("Truncated version 17 header")?;
+    let run_count = le32(data, 0x90).ok_or("Truncated version 17 header")?;
+    Ok(("Windows XP/2003", run_count, run_times(&[last_run]), 40))
+}
+
+// Windows Vista / 7: one last run time at 0x80, run count at 0x98, 104-byte volume entries
+fn version_23(data: &[u8]) -> Result<VersionFields, String> {
+    let last_run = le64(data, 0x80).ok_or("Truncated version 23 header")?;
+    let run_count = le32(data, 0x98).ok_or("Truncated version 23 header")?;
+    Ok(("Windows Vista/7", run_count, run_times(&[last_run]), 104))
+}
+
+// Windows 8.1: eight run times from 0x80, run count at 0xD0, 104-byte volume entries
+fn version_26(data: &[u8], sections: &Sections) -> Result<VersionFields, String> {
+    let times = eight_run_times(data)?;
+    let run_count = le32(data, run_count_offset(sections)).ok_or("Truncated version 26 header")?;
+    Ok(("Windows 8.1", run_count, times, 104))
+}
+
+// Windows 10 / 11: as 8.1 but with two file information sizes and 96-byte volume entries
+fn version_30(data: &[u8], sections: &Sections, version: u32) -> Result<VersionFields, String> {
+    let times = eight_run_times(data)?;
+    let run_count = le32(data, run_count_offset(sections)).ok_or("Truncated version 30 header")?;
+    let format = if version == 31 { "Windows 11" } else { "Windows 10" };
+    Ok((format, run_count, times, 96))
+}
+
+// The shorter file information variant starts its metrics array at 0x128 and moves the run count up
+fn run_count_offset(sections: &Sections) -> usize {
+    if sections.metrics_offset == 0x128 { 0xC8 } else { 0xD0 }
+}
+
+fn eight_run_times(data: &[u8]) -> Result<Vec<String>, String> {
+    let raw: Vec<u64> = (0..8).map(|index| le64(data, 0x80 + index * 8).ok_or("Truncated run time array")).collect::<Result<_, _>>()?;
+    Ok(run_times(&raw))
+}
+
+fn run_times(raw: &[u64]) -> Vec<String> {
+    raw.iter().filter_map(|time| filetime_to_rfc3339(*time)).collect()
+}
+
+// The filename strings section is a run of NUL-terminated UTF-16 paths
+fn filenames(data: &[u8], sections: &Sections) -> Vec<String> {
+    let end = sections.filenames_offset.saturating_add(sections.filenames_size).min(data.len());
+    let section = data.get(sections.filenames_offset..end).unwrap_or(&[]);
+
+    let units: Vec<u16> = section.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    units.split(|unit| *unit == 0)
+        .filter(|name| !name.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect()
+}
+
+fn volumes(data: &[u8], sections: &Sections, entry_size: usize) -> Vec<PrefetchVolume> {
+    let mut volumes = Vec::new();
+
+    // Corrupt counts are common in carved files; stop at the first entry that does not fit
+    for index in 0..sections.volumes_count.min(64) {
+        let entry = sections.volumes_offset + index * entry_size;
+        let (path_offset, path_length) = match (le32(data, entry), le32(data, entry + 4)) {
+            (Some(offset), Some(length)) => (offset as usize, length as usize),
+            _ => break
+        };
+
+        let path_start = sections.volumes_offset + path_offset;
+        let device_path = match data.get(path_start..path_start + path_length * 2) {
+            Some(raw) => utf16(raw),
+            None => break
+        };
+
+        volumes.push(PrefetchVolume {
+            device_path,
+            serial: format!("{:08X}", le32(data, entry + 16).unwrap_or(0)),
+            created: le64(data, entry + 8).and_then(filetime_to_rfc3339)
+        });
+    }
+
+    volumes
+}
@@ -0,0 +1,222 @@
+/*
+    Fast membership tests against a set of IP blocks
+
+    Scope checks ("is this address in the client's ranges?") are asked thousands of times an
+    engagement against the same list, so the blocks are loaded once into a binary trie per
+    address family and each test is a single walk of at most 32 or 128 steps, whatever the
+    size of the list. IPv4 and IPv6 blocks can be mixed freely.
+
+    The string dispatcher keeps the last prefix file it loaded, and reloads it only when the
+    file changes, so repeated calls against one scope file do not re-parse it.
+*/
+
+use std::{
+    fmt,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime
+};
+
+use serde::Serialize;
+
+use crate::{
+    cidr::{Cidr, PrefixTrie},
+    stats::{self, Counter},
+    text::read_text_file,
+    trailing_options
+};
+
+// A prefix file with the modification time it was loaded at
+type LoadedSet = (PathBuf, Option<SystemTime>, Arc<PrefixSet>);
+
+// Last prefix file loaded by `prefix_contains`
+static LOADED: Mutex<Option<LoadedSet>> = Mutex::new(None);
+
+/// A set of IPv4 and IPv6 blocks answering containment queries in time independent of its size
+#[derive(Debug, Clone, Default)]
+pub struct PrefixSet {
+    // Values index the blocks
+    trie: PrefixTrie,
+    prefixes: Vec<Cidr>
+}
+
+impl PrefixSet {
+    pub fn new() -> PrefixSet {
+        PrefixSet::default()
+    }
+
+    /// Adds a block, returning false when it was already present
+    pub fn insert(&mut self, prefix: Cidr) -> bool {
+        let value = self.prefixes.len() as u32;
+        if let Some(previous) = self.trie.insert(&prefix, value) {
+            self.trie.insert(&prefix, previous);
+            return false;
+        }
+
+        self.prefixes.push(prefix);
+        true
+    }
+
+    /// Parses one block (or bare address) per line; "#" starts a comment and anything after the block is ignored
+    pub fn parse(text: &str) -> Result<PrefixSet, String> {
+        let mut set = PrefixSet::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let Some(block) = line.split('#').next().unwrap_or("").split_whitespace().next() else { continue };
+            let prefix: Cidr = block.trim_end_matches(',').parse().map_err(|e| format!("Prefix list line {}: {}", number + 1, e))?;
+            set.insert(prefix);
+        }
+
+        Ok(set)
+    }
+
+    /// Reads a prefix list file (see `parse`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PrefixSet, String> {
+        let path = path.as_ref();
+        let text = read_text_file(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        PrefixSet::parse(&text)
+    }
+
+    /// Whether any block contains the address
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.trie.longest(addr).is_some()
+    }
+
+    /// Most specific block containing the address
+    pub fn longest_match(&self, addr: IpAddr) -> Option<Cidr> {
+        self.trie.longest(addr).map(|(_, value)| self.prefixes[value as usize])
+    }
+
+    /// Whether one block contains all of `prefix`
+    pub fn covers(&self, prefix: &Cidr) -> bool {
+        !self.trie.covering(prefix).is_empty()
+    }
+
+    /// Whether any block shares addresses with `prefix`
+    pub fn overlaps(&self, prefix: &Cidr) -> bool {
+        self.covers(prefix) || !self.trie.within(prefix).is_empty()
+    }
+
+    /// Blocks containing all of `prefix`, least specific first
+    pub fn covering(&self, prefix: &Cidr) -> Vec<Cidr> {
+        self.trie.covering(prefix).into_iter().map(|(_, value)| self.prefixes[value as usize]).collect()
+    }
+
+    /// Blocks inside `prefix` (including `prefix` itself when present)
+    pub fn within(&self, prefix: &Cidr) -> Vec<Cidr> {
+        self.trie.within(prefix).into_iter().map(|value| self.prefixes[value as usize]).collect()
+    }
+
+    /// Number of distinct blocks
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Blocks in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Cidr> {
+        self.prefixes.iter()
+    }
+}
+
+impl FromIterator<Cidr> for PrefixSet {
+    fn from_iter<I: IntoIterator<Item = Cidr>>(iter: I) -> PrefixSet {
+        let mut set = PrefixSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Cidr> for PrefixSet {
+    fn extend<I: IntoIterator<Item = Cidr>>(&mut self, iter: I) {
+        for prefix in iter {
+            self.insert(prefix);
+        }
+    }
+}
+
+/// Whether one address or block is inside the set
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefixMembership {
+    pub query: String,
+    pub contained: bool,
+    /// Most specific block of the set containing the query
+    pub prefix: Option<String>
+}
+
+/// Membership of several queries in one prefix set
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefixContainsReport {
+    /// Blocks in the set
+    pub prefixes: usize,
+    pub results: Vec<PrefixMembership>
+}
+
+impl fmt::Display for PrefixContainsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            match &result.prefix {
+                Some(prefix) => writeln!(f, "{} in {}", result.query, prefix)?,
+                None => writeln!(f, "{} not in any of {} prefixes", result.query, self.prefixes)?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks each address or block against the set
+pub fn mercy_prefix_contains(queries: &[Cidr], set: &PrefixSet) -> PrefixContainsReport {
+    let results = queries.iter().map(|query| {
+        let prefix = set.covering(query).pop();
+        PrefixMembership { query: display_query(query), contained: prefix.is_some(), prefix: prefix.map(|prefix| prefix.to_string()) }
+    }).collect();
+
+    PrefixContainsReport { prefixes: set.len(), results }
+}
+
+// A single-address block reads as the bare address
+fn display_query(query: &Cidr) -> String {
+    if query.size() == 1 { query.network().to_string() } else { query.to_string() }
+}
+
+// The set in a file, reusing the last one loaded while the file is unchanged
+fn load_cached(path: &Path) -> Result<Arc<PrefixSet>, String> {
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let mut loaded = LOADED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if let Some((cached, time, set)) = loaded.as_ref() {
+        if cached == path && *time == modified && modified.is_some() {
+            stats::count(Counter::CacheHits, 1);
+            return Ok(Arc::clone(set));
+        }
+    }
+
+    stats::count(Counter::CacheMisses, 1);
+    let set = Arc::new(PrefixSet::load(path)?);
+    *loaded = Some((path.to_path_buf(), modified, Arc::clone(&set)));
+    Ok(set)
+}
+
+// Parses "ip_or_prefix[,ip_or_prefix...] prefixes=ranges.txt" (or "prefixes=10.0.0.0/8,2001:db8::/32")
+pub(crate) fn prefix_contains_from_args(input: &str) -> Result<PrefixContainsReport, String> {
+    let (targets, pairs) = trailing_options(input, &["prefixes"]);
+    let source = pairs.iter().find(|(key, _)| *key == "prefixes").map(|(_, value)| *value).ok_or("No prefix list given (prefixes=file or prefixes=cidr,cidr)")?;
+
+    let set = if Path::new(source).is_file() {
+        load_cached(Path::new(source))?
+    } else {
+        Arc::new(source.split(',').filter(|block| !block.is_empty()).map(str::parse).collect::<Result<PrefixSet, String>>()?)
+    };
+
+    let queries = targets.split(',').map(str::trim).filter(|target| !target.is_empty()).map(str::parse).collect::<Result<Vec<Cidr>, String>>()?;
+    if queries.is_empty() {
+        return Err("No IP address or prefix specified".to_string());
+    }
+
+    Ok(mercy_prefix_contains(&queries, &set))
+}
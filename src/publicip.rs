@@ -0,0 +1,95 @@
+/*
+    The public (egress) address of this host, as IP echo services see it
+
+    Two services are asked in turn over HTTPS, so one being down or blocked does not lose the
+    answer: ipify first, then icanhazip. Each has a hostname per address family, and the
+    connection is bound to an unspecified local address of that family, so asking for IPv4
+    on a dual-stack host cannot come back with the IPv6 address or the other way round. A
+    request that takes longer than the timeout counts as failed and is retried like other
+    lookups (see net.rs), so an offline host gets an error within seconds instead of hanging.
+
+    `set_service_endpoints` can name an echo service asked instead (a mirror, or the mock
+    HTTP server of the `testing` feature).
+*/
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::Duration
+};
+
+use log::{error, warn};
+
+use crate::{
+    config,
+    endpoints::service_endpoints,
+    http::{self, HttpIdentity, http_client_builder},
+    net,
+    runtime,
+    trailing_options
+};
+
+// Per request; the answer is a few bytes
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const IPV4_SERVICES: [&str; 2] = ["https://api.ipify.org", "https://ipv4.icanhazip.com"];
+const IPV6_SERVICES: [&str; 2] = ["https://api6.ipify.org", "https://ipv6.icanhazip.com"];
+
+/// The public IPv4 (or with `ipv6`, IPv6) address this host's traffic leaves from
+pub fn mercy_external_ip(ipv6: bool) -> Result<IpAddr, String> {
+    external_ip(ipv6, &HttpIdentity::default())
+}
+
+fn external_ip(ipv6: bool, identity: &HttpIdentity) -> Result<IpAddr, String> {
+    let services: Vec<String> = match service_endpoints().ip_echo {
+        Some(url) => vec![url],
+        None if ipv6 => IPV6_SERVICES.iter().map(|url| url.to_string()).collect(),
+        None => IPV4_SERVICES.iter().map(|url| url.to_string()).collect()
+    };
+    let local = if ipv6 { IpAddr::V6(Ipv6Addr::UNSPECIFIED) } else { IpAddr::V4(Ipv4Addr::UNSPECIFIED) };
+    let client = http_client_builder(identity)?
+        .local_address(local)
+        .timeout(config::read_timeout(TIMEOUT))
+        .build()
+        .map_err(|e| format!("Unable to build HTTP client: {}", e))?;
+
+    let mut failures = Vec::new();
+    for url in &services {
+        match runtime::block_on(echoed_address(&client, url)) {
+            Ok(address) if address.is_ipv6() == ipv6 => return Ok(address),
+            Ok(address) => failures.push(format!("{} answered {}", url, address)),
+            Err(e) => failures.push(format!("{}: {}", url, e))
+        }
+        if let Some(failure) = failures.last().filter(|_| services.len() > failures.len()) {
+            warn!("{}, asking the next echo service", failure);
+        }
+    }
+
+    let message = format!("Unable to determine external IP (offline?): {}", failures.join("; "));
+    error!("{}", message);
+    Err(message)
+}
+
+// The address an echo service answers with, as bare text
+async fn echoed_address(client: &reqwest::Client, url: &str) -> Result<IpAddr, String> {
+    let response = net::send(client.get(url)).await?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status().as_u16()));
+    }
+
+    let body = response.text().await.map_err(|e| e.to_string())?;
+    body.trim().parse().map_err(|_| format!("answered {:?}, not an IP address", body.chars().take(64).collect::<String>()))
+}
+
+// Parses "[user_agent=curl] [accept_language=en-US] [header=Name:Value]" for external_ip and external_ipv6
+pub(crate) fn external_ip_from_args(input: &str, ipv6: bool) -> Result<IpAddr, String> {
+    let (rest, pairs) = trailing_options(input, &http::IDENTITY_KEYS);
+    if !rest.is_empty() {
+        return Err(format!("Unknown option '{}' (external_ip takes only the HTTP identity options)", rest));
+    }
+
+    let mut identity = HttpIdentity::default();
+    for (key, value) in pairs {
+        http::identity_option(&mut identity, key, value)?;
+    }
+    external_ip(ipv6, &identity)
+}
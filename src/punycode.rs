@@ -0,0 +1,199 @@
+/*
+    Punycode (RFC 3492), the ASCII form of internationalized domain labels
+
+    A label with non-ASCII characters travels in DNS as "xn--" and its punycode, so
+    "аррӏе.com" in Cyrillic is "xn--80ak6aa92e.com" on the wire. Decoding shows what a label
+    will look like to a reader; encoding gives the form to search logs and blocklists for.
+    Non-ASCII labels are lowercased before encoding but not otherwise mapped as IDNA would,
+    so the result shows exactly the characters a name holds. Decoding is checked throughout:
+    bad digits, overflow, code points that cannot be characters and labels that are not the
+    standard encoding of what they decode to are errors, never a panic.
+*/
+
+// Punycode parameters (RFC 3492 section 5)
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// A domain with each "xn--" label decoded ("xn--80ak6aa92e.com" gives "аррӏе.com"); other labels pass through unchanged
+/// 
+/// A label that is not valid punycode, decodes to plain ASCII or is not the form encoding would give is an error
+pub fn punycode_decode(domain: &str) -> Result<String, String> {
+    domain.split('.')
+        .map(|label| match label.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("xn--") => decode_ace(label).map_err(|e| format!("Invalid punycode label '{}': {}", label, e)),
+            _ => Ok(label.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|labels| labels.join("."))
+}
+
+/// A domain with each non-ASCII label in its "xn--" form ("аррӏе.com" gives "xn--80ak6aa92e.com"); ASCII labels pass through unchanged
+/// 
+/// Non-ASCII labels are lowercased first; the ideographic full stops (。．｡) separate labels as '.' does
+pub fn punycode_encode(domain: &str) -> Result<String, String> {
+    domain.split(['.', '\u{3002}', '\u{FF0E}', '\u{FF61}'])
+        .map(|label| {
+            if label.is_ascii() {
+                return Ok(label.to_string());
+            }
+
+            let lowered: Vec<char> = label.chars().flat_map(char::to_lowercase).collect();
+            let encoded = encode_label(&lowered).map(|encoded| format!("xn--{}", encoded)).ok_or_else(|| format!("Label '{}' is too long to encode", label))?;
+            match encoded.len() {
+                0..=63 => Ok(encoded),
+                length => Err(format!("Label '{}' encodes to {} characters, more than the 63 DNS allows", label, length))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|labels| labels.join("."))
+}
+
+// One "xn--" label, checked to be the encoding of what it decodes to
+fn decode_ace(label: &str) -> Result<String, String> {
+    let decoded = decode_label(&label[4..])?;
+    if decoded.is_ascii() {
+        return Err("decodes to plain ASCII, which is never written as punycode".to_string());
+    }
+
+    let chars: Vec<char> = decoded.chars().collect();
+    if encode_label(&chars).is_none_or(|encoded| !encoded.eq_ignore_ascii_case(&label[4..])) {
+        return Err(format!("not the standard encoding of \"{}\"", decoded));
+    }
+    Ok(decoded)
+}
+
+/// RFC 3492 decoding of one label (without the "xn--" prefix)
+pub(crate) fn decode_label(input: &str) -> Result<String, String> {
+    if !input.is_ascii() {
+        return Err("punycode is ASCII only".to_string());
+    }
+    if input.is_empty() {
+        return Err("empty label".to_string());
+    }
+
+    // Basic code points come before the last delimiter, the encoded deltas after it
+    let (basic, extended) = match input.rfind('-') {
+        Some(delimiter) => (&input[..delimiter], &input[delimiter + 1..]),
+        None => ("", input)
+    };
+    let mut output: Vec<char> = basic.chars().collect();
+
+    let overflow = || "number too large".to_string();
+    let (mut n, mut i, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    let mut digits = extended.chars();
+
+    while !digits.as_str().is_empty() {
+        let old_i = i;
+        let mut weight = 1u32;
+        let mut k = BASE;
+        loop {
+            let c = digits.next().ok_or("truncated (a number ends mid-way)")?;
+            let digit = decode_digit(c).ok_or_else(|| format!("'{}' is not a punycode digit", c))?;
+            i = i.checked_add(digit.checked_mul(weight).ok_or_else(overflow)?).ok_or_else(overflow)?;
+
+            let threshold = threshold(k, bias);
+            if digit < threshold {
+                break;
+            }
+            weight = weight.checked_mul(BASE - threshold).ok_or_else(overflow)?;
+            k += BASE;
+        }
+
+        let points = output.len() as u32 + 1;
+        bias = adapt(i - old_i, points, old_i == 0);
+        n = n.checked_add(i / points).ok_or_else(overflow)?;
+        i %= points;
+
+        let c = char::from_u32(n).filter(|c| !c.is_ascii()).ok_or_else(|| format!("U+{:04X} cannot be encoded in a label", n))?;
+        output.insert(i as usize, c);
+        i += 1;
+    }
+
+    Ok(output.into_iter().collect())
+}
+
+/// RFC 3492 encoding of one label (without the "xn--" prefix); None if it overflows
+pub(crate) fn encode_label(input: &[char]) -> Option<String> {
+    let mut output: String = input.iter().filter(|c| c.is_ascii()).collect();
+    let basic = output.len() as u32;
+    let mut handled = basic;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let (mut n, mut delta, mut bias) = (INITIAL_N, 0u32, INITIAL_BIAS);
+    let total = input.len() as u32;
+
+    while handled < total {
+        let next = input.iter().map(|c| *c as u32).filter(|value| *value >= n).min()?;
+        delta = delta.checked_add((next - n).checked_mul(handled + 1)?)?;
+        n = next;
+
+        for c in input {
+            let value = *c as u32;
+            if value < n {
+                delta = delta.checked_add(1)?;
+            }
+            if value == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let threshold = threshold(k, bias);
+                    if q < threshold {
+                        break;
+                    }
+                    output.push(encode_digit(threshold + (q - threshold) % (BASE - threshold)));
+                    q = (q - threshold) / (BASE - threshold);
+                    k += BASE;
+                }
+                output.push(encode_digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+
+        delta = delta.checked_add(1)?;
+        n += 1;
+    }
+
+    Some(output)
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+fn encode_digit(digit: u32) -> char {
+    match digit {
+        0..=25 => (b'a' + digit as u8) as char,
+        _ => (b'0' + (digit - 26) as u8) as char
+    }
+}
+
+fn decode_digit(c: char) -> Option<u32> {
+    match c {
+        'a'..='z' => Some(c as u32 - 'a' as u32),
+        'A'..='Z' => Some(c as u32 - 'A' as u32),
+        '0'..='9' => Some(c as u32 - '0' as u32 + 26),
+        _ => None
+    }
+}
+
+fn threshold(k: u32, bias: u32) -> u32 {
+    if k <= bias { T_MIN } else if k >= bias + T_MAX { T_MAX } else { k - bias }
+}
@@ -0,0 +1,1502 @@
+/*
+    QR code decoding from PNG and JPEG images, and QR code generation
+
+    Decoding binarizes the image twice (one global Otsu threshold, then a local mean for uneven
+    lighting), finds finder patterns by their 1:1:3:1:1 run ratios, and groups them into
+    right-angled triples, shortest first, so several codes in one picture are each read once.
+    Each code is sampled through a perspective transform anchored on its finders and alignment
+    pattern, unmasked, de-interleaved and Reed-Solomon corrected before its segments are read.
+    Mirrored codes (printed from the back of a transparency, or read from a selfie camera) are
+    retried transposed.
+
+    Encoding picks the densest single mode that fits the text (numeric, alphanumeric or UTF-8
+    bytes), the smallest version for the requested error correction level, and the mask with the
+    lowest penalty, then writes a grayscale PNG.
+*/
+
+use std::{fmt, path::Path, sync::OnceLock};
+
+use serde::Serialize;
+
+use crate::{
+    paths::path_to_string,
+    raster::{read_gray_image, write_gray_png, GrayImage},
+    trailing_options
+};
+
+// Error correction codewords per block, by level (L, M, Q, H) and version
+const ECC_PER_BLOCK: [[u8; 40]; 4] = [
+    [7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30]
+];
+
+// Error correction blocks, by level (L, M, Q, H) and version
+const BLOCKS: [[u8; 40]; 4] = [
+    [1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81]
+];
+
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+// Finder candidates considered when forming codes, and decode attempts per binarization
+const MAX_FINDERS: usize = 40;
+const MAX_ATTEMPTS: usize = 100;
+
+/// QR error correction level, from about 7% (L) to 30% (H) of codewords recoverable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum QrErrorCorrection {
+    L,
+    M,
+    Q,
+    H
+}
+
+impl QrErrorCorrection {
+    // Row in the block tables
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // The two bits stored in the format information
+    fn format_bits(self) -> u32 {
+        match self {
+            QrErrorCorrection::L => 1,
+            QrErrorCorrection::M => 0,
+            QrErrorCorrection::Q => 3,
+            QrErrorCorrection::H => 2
+        }
+    }
+
+    fn from_format_bits(bits: u32) -> QrErrorCorrection {
+        match bits & 3 {
+            1 => QrErrorCorrection::L,
+            0 => QrErrorCorrection::M,
+            3 => QrErrorCorrection::Q,
+            _ => QrErrorCorrection::H
+        }
+    }
+}
+
+impl fmt::Display for QrErrorCorrection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// One data segment of a decoded code
+#[derive(Debug, Clone, Serialize)]
+pub struct QrSegment {
+    /// "numeric", "alphanumeric", "byte" or "kanji"
+    pub mode: String,
+    /// Characters (bytes for byte mode) in the segment
+    pub length: usize,
+    /// How byte segments were read ("utf-8" or "iso-8859-1"); kanji is "shift_jis" and is not converted
+    pub encoding: Option<String>,
+    /// Extended Channel Interpretation in effect, when the code declares one
+    pub eci: Option<u32>
+}
+
+/// A decoded QR code
+#[derive(Debug, Clone, Serialize)]
+pub struct QrCode {
+    pub payload: String,
+    pub version: u8,
+    pub error_correction: QrErrorCorrection,
+    pub mask: u8,
+    pub segments: Vec<QrSegment>,
+    /// Codewords repaired by Reed-Solomon correction
+    pub errors_corrected: usize,
+    /// The code was printed or photographed mirror-imaged
+    pub mirrored: bool,
+    /// Pixel centres of the top-left, top-right and bottom-left finder patterns
+    pub finder_patterns: Vec<[f64; 2]>
+}
+
+/// Every QR code found in an image
+#[derive(Debug, Clone, Serialize)]
+pub struct QrDecodeReport {
+    pub path: String,
+    pub width: usize,
+    pub height: usize,
+    /// Codes ordered by their centres, top to bottom then left to right
+    pub codes: Vec<QrCode>
+}
+
+impl fmt::Display for QrDecodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.codes.is_empty() {
+            return write!(f, "No QR codes found in {}", self.path);
+        }
+
+        for (index, code) in self.codes.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "QR code {} of {} (version {}-{}, {} error(s) corrected{})", index + 1, self.codes.len(), code.version, code.error_correction, code.errors_corrected, if code.mirrored { ", mirrored" } else { "" })?;
+            writeln!(f, "{}", code.payload)?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for generating a QR code image
+#[derive(Debug, Clone)]
+pub struct QrEncodeOptions {
+    pub error_correction: QrErrorCorrection,
+    /// Pixels per module
+    pub scale: usize,
+    /// Quiet zone in modules (the standard asks for 4)
+    pub border: usize
+}
+
+impl Default for QrEncodeOptions {
+    fn default() -> QrEncodeOptions {
+        QrEncodeOptions { error_correction: QrErrorCorrection::M, scale: 8, border: 4 }
+    }
+}
+
+/// A generated QR code image
+#[derive(Debug, Clone, Serialize)]
+pub struct QrEncodeReport {
+    pub output: String,
+    pub version: u8,
+    pub error_correction: QrErrorCorrection,
+    pub mask: u8,
+    /// "numeric", "alphanumeric" or "byte" (UTF-8)
+    pub mode: String,
+    /// Modules per side, without the quiet zone
+    pub modules: usize,
+    /// Pixels per side of the PNG
+    pub image_size: usize
+}
+
+impl fmt::Display for QrEncodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Wrote a version {}-{} QR code ({} mode, {}x{} modules, mask {}) to {} ({}x{} pixels)", self.version, self.error_correction, self.mode, self.modules, self.modules, self.mask, self.output, self.image_size, self.image_size)
+    }
+}
+
+/* Decoding */
+
+/// Decodes every QR code in a PNG or JPEG image; an image with none gives an empty list
+pub fn mercy_qr_decode<P: AsRef<Path>>(image_path: P) -> Result<QrDecodeReport, String> {
+    let image_path = image_path.as_ref();
+    let image = read_gray_image(image_path)?;
+
+    let mut found: Vec<(QrCode, f64)> = Vec::new();
+    for bits in [binarize_global(&image), binarize_local(&image)] {
+        for (code, module) in decode_all(&bits) {
+            let duplicate = found.iter().any(|(other, _)| {
+                other.payload == code.payload && distance(other.finder_patterns[0], code.finder_patterns[0]) < 10.0 * module
+            });
+            if !duplicate {
+                found.push((code, module));
+            }
+        }
+    }
+
+    let mut codes: Vec<QrCode> = found.into_iter().map(|(code, _)| code).collect();
+    codes.sort_by(|a, b| {
+        let (a, b) = (center(a), center(b));
+        a[1].total_cmp(&b[1]).then(a[0].total_cmp(&b[0]))
+    });
+
+    Ok(QrDecodeReport { path: path_to_string(image_path), width: image.width, height: image.height, codes })
+}
+
+// A bilevel image or sampled code, true for dark
+#[derive(Clone)]
+struct Bits {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>
+}
+
+impl Bits {
+    fn new(width: usize, height: usize) -> Bits {
+        Bits { width, height, dark: vec![false; width * height] }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.dark[y * self.width + x] = dark;
+    }
+
+    fn transposed(&self) -> Bits {
+        let mut bits = Bits::new(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                bits.set(y, x, self.get(x, y));
+            }
+        }
+        bits
+    }
+}
+
+// Dark below one Otsu threshold for the whole image
+fn binarize_global(image: &GrayImage) -> Bits {
+    let mut histogram = [0u64; 256];
+    for pixel in &image.pixels {
+        histogram[*pixel as usize] += 1;
+    }
+
+    let total = image.pixels.len() as f64;
+    let sum_all: f64 = histogram.iter().enumerate().map(|(value, count)| value as f64 * *count as f64).sum();
+    let (mut weight, mut sum, mut best, mut threshold) = (0.0, 0.0, -1.0, 127u8);
+    for (value, count) in histogram.iter().enumerate() {
+        weight += *count as f64;
+        sum += value as f64 * *count as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        let between = weight * (total - weight) * (sum / weight - (sum_all - sum) / (total - weight)).powi(2);
+        if between > best {
+            best = between;
+            threshold = value as u8;
+        }
+    }
+
+    Bits { width: image.width, height: image.height, dark: image.pixels.iter().map(|pixel| *pixel <= threshold).collect() }
+}
+
+// Dark when clearly below the mean of the surrounding block, for shadows and gradients
+fn binarize_local(image: &GrayImage) -> Bits {
+    let (width, height) = (image.width, image.height);
+    let stride = width + 1;
+    let mut integral = vec![0u64; stride * (height + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            row += image.get(x, y) as u64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row;
+        }
+    }
+
+    let radius = (width.min(height) / 12).max(8);
+    let mut bits = Bits::new(width, height);
+    for y in 0..height {
+        let (top, bottom) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (left, right) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let sum = integral[bottom * stride + right] + integral[top * stride + left] - integral[top * stride + right] - integral[bottom * stride + left];
+            let count = ((bottom - top) * (right - left)) as u64;
+            bits.set(x, y, image.get(x, y) as u64 * count * 10 < sum * 9);
+        }
+    }
+    bits
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FinderPattern {
+    x: f64,
+    y: f64,
+    module: f64,
+    count: usize
+}
+
+// Every code that can be read from one binarization, with its module size in pixels
+fn decode_all(bits: &Bits) -> Vec<(QrCode, f64)> {
+    let mut finders = find_finder_patterns(bits);
+    finders.sort_by_key(|finder| std::cmp::Reverse(finder.count));
+    if finders.iter().filter(|finder| finder.count >= 2).count() >= 3 {
+        finders.retain(|finder| finder.count >= 2);
+    }
+    finders.truncate(MAX_FINDERS);
+
+    let mut used = vec![false; finders.len()];
+    let mut codes = Vec::new();
+    for (attempt, (_, [a, b, c])) in finder_triples(&finders).into_iter().enumerate() {
+        if attempt == MAX_ATTEMPTS {
+            break;
+        }
+        if used[a] || used[b] || used[c] {
+            continue;
+        }
+        if let Some(code) = decode_at(bits, finders[a], finders[b], finders[c]) {
+            used[a] = true;
+            used[b] = true;
+            used[c] = true;
+            codes.push(code);
+        }
+    }
+    codes
+}
+
+// Centres of 1:1:3:1:1 dark/light runs confirmed across both axes, merged by position
+fn find_finder_patterns(bits: &Bits) -> Vec<FinderPattern> {
+    let mut found: Vec<FinderPattern> = Vec::new();
+
+    for y in 0..bits.height {
+        let mut counts = [0usize; 5];
+        let mut state = 0;
+        for x in 0..bits.width {
+            if bits.get(x, y) {
+                if state % 2 == 1 {
+                    state += 1;
+                }
+                counts[state] += 1;
+            } else if state % 2 == 1 {
+                counts[state] += 1;
+            } else if state < 4 {
+                state += 1;
+                counts[state] += 1;
+            } else {
+                if is_finder_ratio(&counts) {
+                    confirm_finder(bits, &counts, x, y, &mut found);
+                }
+                counts = [counts[2], counts[3], counts[4], 1, 0];
+                state = 3;
+            }
+        }
+        if state == 4 && is_finder_ratio(&counts) {
+            confirm_finder(bits, &counts, bits.width, y, &mut found);
+        }
+    }
+
+    found
+}
+
+fn is_finder_ratio(counts: &[usize; 5]) -> bool {
+    let total: usize = counts.iter().sum();
+    if total < 7 || counts.contains(&0) {
+        return false;
+    }
+
+    let module = total as f64 / 7.0;
+    counts.iter().enumerate().all(|(index, count)| {
+        let expected = if index == 2 { 3.0 } else { 1.0 };
+        (expected * module - *count as f64).abs() < expected * module / 2.0
+    })
+}
+
+// Checks a row match vertically then horizontally through its centre, and records it
+fn confirm_finder(bits: &Bits, counts: &[usize; 5], end: usize, y: usize, found: &mut Vec<FinderPattern>) {
+    let total: usize = counts.iter().sum();
+    let center_x = end as f64 - counts[4] as f64 - counts[3] as f64 - counts[2] as f64 / 2.0;
+
+    let Some((center_y, vertical)) = cross_check(bits, center_x as usize, y, true, counts[2], total) else { return };
+    let Some((center_x, horizontal)) = cross_check(bits, center_x as usize, center_y as usize, false, counts[2], total) else { return };
+    let module = (vertical + horizontal) as f64 / 14.0;
+
+    match found.iter_mut().find(|finder| (finder.x - center_x).abs() <= finder.module && (finder.y - center_y).abs() <= finder.module) {
+        Some(finder) => {
+            let count = finder.count as f64;
+            finder.x = (finder.x * count + center_x) / (count + 1.0);
+            finder.y = (finder.y * count + center_y) / (count + 1.0);
+            finder.module = (finder.module * count + module) / (count + 1.0);
+            finder.count += 1;
+        },
+        None => found.push(FinderPattern { x: center_x, y: center_y, module, count: 1 })
+    }
+}
+
+// Runs through (x, y) along one axis; the centre along that axis and the pattern's length when the ratios hold
+fn cross_check(bits: &Bits, x: usize, y: usize, vertical: bool, max_count: usize, original_total: usize) -> Option<(f64, usize)> {
+    let (start, length) = if vertical { (y as isize, bits.height as isize) } else { (x as isize, bits.width as isize) };
+    let dark = |i: isize| if vertical { bits.get(x, i as usize) } else { bits.get(i as usize, y) };
+    let mut counts = [0usize; 5];
+
+    let mut i = start;
+    while i >= 0 && dark(i) {
+        counts[2] += 1;
+        i -= 1;
+    }
+    while i >= 0 && !dark(i) && counts[1] <= max_count {
+        counts[1] += 1;
+        i -= 1;
+    }
+    if i < 0 || counts[1] > max_count {
+        return None;
+    }
+    while i >= 0 && dark(i) && counts[0] <= max_count {
+        counts[0] += 1;
+        i -= 1;
+    }
+    if counts[0] > max_count {
+        return None;
+    }
+
+    let mut i = start + 1;
+    while i < length && dark(i) {
+        counts[2] += 1;
+        i += 1;
+    }
+    while i < length && !dark(i) && counts[3] <= max_count {
+        counts[3] += 1;
+        i += 1;
+    }
+    if i == length || counts[3] > max_count {
+        return None;
+    }
+    while i < length && dark(i) && counts[4] <= max_count {
+        counts[4] += 1;
+        i += 1;
+    }
+    if counts[4] > max_count {
+        return None;
+    }
+
+    let total: usize = counts.iter().sum();
+    if 5 * total.abs_diff(original_total) >= 2 * original_total || !is_finder_ratio(&counts) {
+        return None;
+    }
+    Some((i as f64 - counts[4] as f64 - counts[3] as f64 - counts[2] as f64 / 2.0, total))
+}
+
+// Finder triples shaped like a code (similar modules, a right angle between equal legs), as
+// (leg length, [top-left, top-right, bottom-left]), smallest codes first
+fn finder_triples(finders: &[FinderPattern]) -> Vec<(f64, [usize; 3])> {
+    let mut triples = Vec::new();
+
+    for a in 0..finders.len() {
+        for b in a + 1..finders.len() {
+            for c in b + 1..finders.len() {
+                let modules = [finders[a].module, finders[b].module, finders[c].module];
+                let (smallest, largest) = (modules.iter().cloned().fold(f64::MAX, f64::min), modules.iter().cloned().fold(0.0, f64::max));
+                if largest > smallest * 1.5 {
+                    continue;
+                }
+
+                // The corner is opposite the longest side
+                let (corner, p, q) = {
+                    let ab = distance(point(&finders[a]), point(&finders[b]));
+                    let ac = distance(point(&finders[a]), point(&finders[c]));
+                    let bc = distance(point(&finders[b]), point(&finders[c]));
+                    if bc >= ab && bc >= ac { (a, b, c) } else if ac >= ab { (b, a, c) } else { (c, a, b) }
+                };
+                let leg_p = distance(point(&finders[corner]), point(&finders[p]));
+                let leg_q = distance(point(&finders[corner]), point(&finders[q]));
+                let hypotenuse = distance(point(&finders[p]), point(&finders[q]));
+
+                if !(0.75..=1.33).contains(&(leg_p / leg_q)) || ((leg_p.hypot(leg_q) - hypotenuse) / hypotenuse).abs() > 0.1 {
+                    continue;
+                }
+
+                let module = modules.iter().sum::<f64>() / 3.0;
+                let dimension = (leg_p + leg_q) / 2.0 / module + 7.0;
+                if !(17.0..=181.0).contains(&dimension) {
+                    continue;
+                }
+
+                // Clockwise from the corner (y grows downwards) is top-right
+                let cross = (finders[p].x - finders[corner].x) * (finders[q].y - finders[corner].y) - (finders[p].y - finders[corner].y) * (finders[q].x - finders[corner].x);
+                let order = if cross > 0.0 { [corner, p, q] } else { [corner, q, p] };
+                triples.push(((leg_p + leg_q) / 2.0, order));
+            }
+        }
+    }
+
+    // A real finder is confirmed on most rows through its 3-module centre; look-alikes in data
+    // areas on only a few, so triples of well-confirmed finders go first
+    let weak = |triple: &[usize; 3]| triple.iter().any(|index| (finders[*index].count as f64) < finders[*index].module * 1.5);
+    triples.sort_by(|a, b| weak(&a.1).cmp(&weak(&b.1)).then(a.0.total_cmp(&b.0)));
+    triples
+}
+
+fn point(finder: &FinderPattern) -> [f64; 2] {
+    [finder.x, finder.y]
+}
+
+// Midway between the top-right and bottom-left finders
+fn center(code: &QrCode) -> [f64; 2] {
+    let (top_right, bottom_left) = (code.finder_patterns[1], code.finder_patterns[2]);
+    [(top_right[0] + bottom_left[0]) / 2.0, (top_right[1] + bottom_left[1]) / 2.0]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+// Samples and decodes the code anchored on three finders, trying neighbouring versions when the
+// estimated size is off and a mirrored reading when the straight one fails
+fn decode_at(bits: &Bits, top_left: FinderPattern, top_right: FinderPattern, bottom_left: FinderPattern) -> Option<(QrCode, f64)> {
+    let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+    let across = distance(point(&top_left), point(&top_right)) / module;
+    let down = distance(point(&top_left), point(&bottom_left)) / module;
+    let estimate = (((across + down) / 2.0 + 7.0 - 17.0) / 4.0).round() as i64;
+
+    for version in [estimate, estimate - 1, estimate + 1] {
+        if !(1..=40).contains(&version) {
+            continue;
+        }
+
+        let mut version = version as usize;
+        let mut grid = sample_grid(bits, &top_left, &top_right, &bottom_left, module, version);
+        if version >= 7 {
+            match read_version(&grid) {
+                Some(stored) if stored != version => {
+                    version = stored;
+                    grid = sample_grid(bits, &top_left, &top_right, &bottom_left, module, version);
+                },
+                _ => {}
+            }
+        }
+
+        for mirrored in [false, true] {
+            let candidate = if mirrored { grid.transposed() } else { grid.clone() };
+            if let Ok(mut code) = decode_grid(&candidate) {
+                code.mirrored = mirrored;
+                code.finder_patterns = [top_left, top_right, bottom_left].iter()
+                    .map(|finder| [(finder.x * 10.0).round() / 10.0, (finder.y * 10.0).round() / 10.0])
+                    .collect();
+                return Some((code, module));
+            }
+        }
+    }
+
+    None
+}
+
+// Reads the modules of a version's grid through a perspective transform fitted to the finder
+// centres and the bottom-right alignment pattern (or the parallelogram corner without one)
+fn sample_grid(bits: &Bits, top_left: &FinderPattern, top_right: &FinderPattern, bottom_left: &FinderPattern, module: f64, version: usize) -> Bits {
+    let size = 17 + 4 * version;
+    let far = size as f64 - 3.5;
+    let span = size as f64 - 7.0;
+
+    let affine = |u: f64, v: f64| -> [f64; 2] {
+        let (s, t) = ((u - 3.5) / span, (v - 3.5) / span);
+        [
+            top_left.x + s * (top_right.x - top_left.x) + t * (bottom_left.x - top_left.x),
+            top_left.y + s * (top_right.y - top_left.y) + t * (bottom_left.y - top_left.y)
+        ]
+    };
+
+    let mut fourth = ([far, far], affine(far, far));
+    if version >= 2 {
+        let inner = size as f64 - 6.5;
+        if let Some(found) = find_alignment(bits, affine(inner, inner), module) {
+            fourth = ([inner, inner], found);
+        }
+    }
+
+    let from = [[3.5, 3.5], [far, 3.5], [3.5, far], fourth.0];
+    let to = [point(top_left), point(top_right), point(bottom_left), fourth.1];
+    let transform = homography(&from, &to).unwrap_or_else(|| homography(&[from[0], from[1], from[2], [far, far]], &[to[0], to[1], to[2], affine(far, far)]).unwrap_or([0.0; 8]));
+
+    let mut grid = Bits::new(size, size);
+    for y in 0..size {
+        for x in 0..size {
+            let [px, py] = project(&transform, x as f64 + 0.5, y as f64 + 0.5);
+            if px >= 0.0 && py >= 0.0 && (px as usize) < bits.width && (py as usize) < bits.height {
+                grid.set(x, y, bits.get(px as usize, py as usize));
+            }
+        }
+    }
+    grid
+}
+
+// The alignment pattern centre closest to where it is expected: a dark module with light then
+// dark one module away on all four sides
+fn find_alignment(bits: &Bits, expected: [f64; 2], module: f64) -> Option<[f64; 2]> {
+    let radius = (module * 8.0).ceil() as isize;
+    let (ex, ey) = (expected[0] as isize, expected[1] as isize);
+    let fits = |run: usize| (module * 0.5..=module * 1.7).contains(&(run as f64));
+    let mut best: Option<([f64; 2], f64)> = None;
+
+    for y in (ey - radius).max(0)..(ey + radius).min(bits.height as isize) {
+        for x in (ex - radius).max(0)..(ex + radius).min(bits.width as isize) {
+            if !bits.get(x as usize, y as usize) {
+                continue;
+            }
+
+            let mut center = [0.0; 2];
+            let mut matched = true;
+            for vertical in [false, true] {
+                let (start, length) = if vertical { (y, bits.height as isize) } else { (x, bits.width as isize) };
+                let dark = |i: isize| if vertical { bits.get(x as usize, i as usize) } else { bits.get(i as usize, y as usize) };
+                let run = |mut i: isize, step: isize, want: bool| -> (usize, isize) {
+                    let mut count = 0;
+                    while i >= 0 && i < length && dark(i) == want && count <= (module * 2.0) as usize {
+                        count += 1;
+                        i += step;
+                    }
+                    (count, i)
+                };
+
+                let (back, before) = run(start, -1, true);
+                let (ahead, after) = run(start + 1, 1, true);
+                let (light_before, edge_before) = run(before, -1, false);
+                let (light_after, edge_after) = run(after, 1, false);
+                let inside = |i: isize| i >= 0 && i < length && dark(i);
+                if !fits(back + ahead) || !fits(light_before) || !fits(light_after) || !inside(edge_before) || !inside(edge_after) {
+                    matched = false;
+                    break;
+                }
+                center[vertical as usize] = (before + after) as f64 / 2.0 + 0.5;
+            }
+
+            if matched {
+                let offset = distance(center, expected);
+                if best.is_none_or(|(_, closest)| offset < closest) {
+                    best = Some((center, offset));
+                }
+            }
+        }
+    }
+
+    best.map(|(center, _)| center)
+}
+
+// Solves the eight coefficients of the perspective transform taking each `from` point to its `to` point
+fn homography(from: &[[f64; 2]; 4], to: &[[f64; 2]; 4]) -> Option<[f64; 8]> {
+    let mut rows = [[0f64; 9]; 8];
+    for k in 0..4 {
+        let ([u, v], [x, y]) = (from[k], to[k]);
+        rows[2 * k] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+        rows[2 * k + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+    }
+
+    for column in 0..8 {
+        let pivot = (column..8).max_by(|a, b| rows[*a][column].abs().total_cmp(&rows[*b][column].abs()))?;
+        if rows[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        rows.swap(column, pivot);
+        for row in 0..8 {
+            if row != column {
+                let factor = rows[row][column] / rows[column][column];
+                let pivot_row = rows[column];
+                for (value, pivot_value) in rows[row].iter_mut().zip(pivot_row).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let mut coefficients = [0f64; 8];
+    for (index, coefficient) in coefficients.iter_mut().enumerate() {
+        *coefficient = rows[index][8] / rows[index][index];
+    }
+    Some(coefficients)
+}
+
+fn project(h: &[f64; 8], u: f64, v: f64) -> [f64; 2] {
+    let w = h[6] * u + h[7] * v + 1.0;
+    [(h[0] * u + h[1] * v + h[2]) / w, (h[3] * u + h[4] * v + h[5]) / w]
+}
+
+// Error correction level and mask from whichever format copy is closest to a valid codeword
+fn read_format(grid: &Bits) -> Result<(QrErrorCorrection, u8), String> {
+    let size = grid.width;
+    let mut first = 0u32;
+    let mut second = 0u32;
+    for i in 0..15 {
+        let (x, y) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8)
+        };
+        first |= (grid.get(x, y) as u32) << i;
+
+        let (x, y) = if i < 8 { (size - 1 - i, 8) } else { (8, size - 15 + i) };
+        second |= (grid.get(x, y) as u32) << i;
+    }
+
+    let (distance, data) = (0..32u32)
+        .map(|data| {
+            let codeword = format_codeword(data);
+            ((codeword ^ first).count_ones().min((codeword ^ second).count_ones()), data)
+        })
+        .min()
+        .unwrap_or((u32::MAX, 0));
+
+    if distance > 3 {
+        return Err("Format information is unreadable".to_string());
+    }
+    Ok((QrErrorCorrection::from_format_bits(data >> 3), (data & 7) as u8))
+}
+
+// BCH(15,5) format codeword for five data bits, masked as stored
+fn format_codeword(data: u32) -> u32 {
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    ((data << 10) | remainder) ^ 0x5412
+}
+
+// BCH(18,6) version codeword
+fn version_codeword(version: usize) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1F25);
+    }
+    ((version as u32) << 12) | remainder
+}
+
+// The version stored in a version 7+ grid, from whichever copy is closest to a valid codeword
+fn read_version(grid: &Bits) -> Option<usize> {
+    let size = grid.width;
+    let mut first = 0u32;
+    let mut second = 0u32;
+    for i in 0..18 {
+        let (a, b) = (size - 11 + i % 3, i / 3);
+        first |= (grid.get(a, b) as u32) << i;
+        second |= (grid.get(b, a) as u32) << i;
+    }
+
+    (7..=40)
+        .map(|version| {
+            let codeword = version_codeword(version);
+            ((codeword ^ first).count_ones().min((codeword ^ second).count_ones()), version)
+        })
+        .min()
+        .filter(|(distance, _)| *distance <= 3)
+        .map(|(_, version)| version)
+}
+
+// Reads, corrects and parses a sampled grid
+fn decode_grid(grid: &Bits) -> Result<QrCode, String> {
+    let version = (grid.width - 17) / 4;
+    let (error_correction, mask) = read_format(grid)?;
+    let symbol = Symbol::new(version);
+
+    let mut codewords = vec![0u8; raw_data_modules(version) / 8];
+    for (index, (x, y)) in symbol.data_positions().into_iter().enumerate().take(codewords.len() * 8) {
+        if grid.get(x, y) ^ mask_applies(mask, x, y) {
+            codewords[index / 8] |= 0x80 >> (index % 8);
+        }
+    }
+
+    let (data, errors_corrected) = correct_codewords(&codewords, version, error_correction)?;
+    let (payload, segments) = parse_segments(&data, version)?;
+
+    Ok(QrCode {
+        payload,
+        version: version as u8,
+        error_correction,
+        mask,
+        segments,
+        errors_corrected,
+        mirrored: false,
+        finder_patterns: Vec::new()
+    })
+}
+
+// Splits interleaved codewords into their blocks, corrects each, and joins the data parts
+fn correct_codewords(codewords: &[u8], version: usize, level: QrErrorCorrection) -> Result<(Vec<u8>, usize), String> {
+    let (blocks, ecc, short_length, short_blocks) = block_layout(version, level);
+
+    let mut rows = vec![Vec::with_capacity(short_length + 1); blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_length {
+        for (j, row) in rows.iter_mut().enumerate() {
+            if i == short_length - ecc && j < short_blocks {
+                continue;
+            }
+            if let Some(codeword) = next.next() {
+                row.push(*codeword);
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    let mut corrected = 0;
+    for (index, mut row) in rows.into_iter().enumerate() {
+        corrected += reed_solomon_correct(&mut row, ecc).ok_or_else(|| format!("Block {} has too many errors to correct", index + 1))?;
+        data.extend_from_slice(&row[..row.len() - ecc]);
+    }
+    Ok((data, corrected))
+}
+
+// (blocks, ecc codewords per block, length of a short block, number of short blocks)
+fn block_layout(version: usize, level: QrErrorCorrection) -> (usize, usize, usize, usize) {
+    let blocks = BLOCKS[level.index()][version - 1] as usize;
+    let ecc = ECC_PER_BLOCK[level.index()][version - 1] as usize;
+    let raw = raw_data_modules(version) / 8;
+    (blocks, ecc, raw / blocks, blocks - raw % blocks)
+}
+
+// Modules available for codewords (and remainder bits) in a version
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn data_codewords(version: usize, level: QrErrorCorrection) -> usize {
+    let (blocks, ecc, _, _) = block_layout(version, level);
+    raw_data_modules(version) / 8 - blocks * ecc
+}
+
+// Character count field width for a mode indicator
+fn count_bits(mode: u32, version: usize) -> usize {
+    let group = if version <= 9 { 0 } else if version <= 26 { 1 } else { 2 };
+    match mode {
+        1 => [10, 12, 14][group],
+        2 => [9, 11, 13][group],
+        4 => [8, 16, 16][group],
+        _ => [8, 10, 12][group]
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.position
+    }
+
+    fn read(&mut self, count: usize) -> Result<u32, String> {
+        if count > self.remaining() {
+            return Err("Segment runs past the end of the data".to_string());
+        }
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = (self.data[self.position / 8] >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | bit as u32;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+// The text of the data segments, and a description of each
+fn parse_segments(data: &[u8], version: usize) -> Result<(String, Vec<QrSegment>), String> {
+    let mut reader = BitReader { data, position: 0 };
+    let mut payload = String::new();
+    let mut segments = Vec::new();
+    let mut eci: Option<u32> = None;
+
+    while reader.remaining() >= 4 {
+        let mode = reader.read(4)?;
+        match mode {
+            0 => break,
+            // Numeric: three digits in 10 bits
+            1 => {
+                let length = reader.read(count_bits(mode, version))? as usize;
+                let mut left = length;
+                while left > 0 {
+                    let digits = left.min(3);
+                    let value = reader.read([4, 7, 10][digits - 1])?;
+                    if value >= 10u32.pow(digits as u32) {
+                        return Err("Invalid numeric segment".to_string());
+                    }
+                    payload.push_str(&format!("{:0width$}", value, width = digits));
+                    left -= digits;
+                }
+                segments.push(QrSegment { mode: "numeric".to_string(), length, encoding: None, eci });
+            },
+            // Alphanumeric: two characters in 11 bits
+            2 => {
+                let length = reader.read(count_bits(mode, version))? as usize;
+                let mut left = length;
+                while left > 0 {
+                    let (characters, value) = if left >= 2 { (2, reader.read(11)? as usize) } else { (1, reader.read(6)? as usize) };
+                    let indices = if characters == 2 { vec![value / 45, value % 45] } else { vec![value] };
+                    for index in indices {
+                        payload.push(*ALPHANUMERIC.get(index).ok_or("Invalid alphanumeric segment")? as char);
+                    }
+                    left -= characters;
+                }
+                segments.push(QrSegment { mode: "alphanumeric".to_string(), length, encoding: None, eci });
+            },
+            4 => {
+                let length = reader.read(count_bits(mode, version))? as usize;
+                let bytes = (0..length).map(|_| reader.read(8).map(|byte| byte as u8)).collect::<Result<Vec<u8>, String>>()?;
+                let latin1 = matches!(eci, Some(1) | Some(3)) || (eci != Some(26) && std::str::from_utf8(&bytes).is_err());
+                if latin1 {
+                    payload.extend(bytes.iter().map(|byte| *byte as char));
+                } else {
+                    payload.push_str(&String::from_utf8_lossy(&bytes));
+                }
+                segments.push(QrSegment { mode: "byte".to_string(), length, encoding: Some(if latin1 { "iso-8859-1" } else { "utf-8" }.to_string()), eci });
+            },
+            // Kanji: Shift JIS double bytes in 13 bits each, kept as replacement characters
+            8 => {
+                let length = reader.read(count_bits(mode, version))? as usize;
+                for _ in 0..length {
+                    reader.read(13)?;
+                    payload.push(char::REPLACEMENT_CHARACTER);
+                }
+                segments.push(QrSegment { mode: "kanji".to_string(), length, encoding: Some("shift_jis".to_string()), eci });
+            },
+            // ECI designator: one, two or three bytes
+            7 => {
+                let first = reader.read(8)?;
+                eci = Some(if first & 0x80 == 0 {
+                    first
+                } else if first & 0xC0 == 0x80 {
+                    ((first & 0x3F) << 8) | reader.read(8)?
+                } else {
+                    ((first & 0x1F) << 16) | reader.read(16)?
+                });
+            },
+            // Structured append header (position, total, parity) and FNC1 markers carry no text
+            3 => {
+                reader.read(16)?;
+            },
+            5 => {},
+            9 => {
+                reader.read(8)?;
+            },
+            other => return Err(format!("Unknown segment mode {}", other))
+        }
+    }
+
+    Ok((payload, segments))
+}
+
+/* Reed-Solomon over GF(256) */
+
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256]
+}
+
+fn galois() -> &'static Galois {
+    static TABLES: OnceLock<Galois> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut tables = Galois { exp: [0; 512], log: [0; 256] };
+        let mut value: u16 = 1;
+        for power in 0..255 {
+            tables.exp[power] = value as u8;
+            tables.log[value as usize] = power as u8;
+            value <<= 1;
+            if value & 0x100 != 0 {
+                value ^= 0x11D;
+            }
+        }
+        for power in 255..512 {
+            tables.exp[power] = tables.exp[power - 255];
+        }
+        tables
+    })
+}
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    let tables = galois();
+    tables.exp[tables.log[a as usize] as usize + tables.log[b as usize] as usize]
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        return 0;
+    }
+    let tables = galois();
+    tables.exp[tables.log[a as usize] as usize + 255 - tables.log[b as usize] as usize]
+}
+
+// Evaluates a polynomial with coefficients lowest degree first
+fn gf_eval(polynomial: &[u8], x: u8) -> u8 {
+    polynomial.iter().rev().fold(0, |acc, coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+// Generator polynomial with roots α^0 .. α^(degree-1), highest degree first without the leading 1
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0u8; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_mul(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_mul(root, 2);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0u8; divisor.len()];
+    for byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (value, coefficient) in remainder.iter_mut().zip(divisor) {
+            *value ^= gf_mul(*coefficient, factor);
+        }
+    }
+    remainder
+}
+
+// Corrects a block in place (data then `ecc` check codewords), returning the codewords changed
+fn reed_solomon_correct(block: &mut [u8], ecc: usize) -> Option<usize> {
+    let tables = galois();
+    let length = block.len();
+    let syndromes: Vec<u8> = (0..ecc).map(|j| block.iter().fold(0, |acc, codeword| gf_mul(acc, tables.exp[j]) ^ codeword)).collect();
+    if syndromes.iter().all(|syndrome| *syndrome == 0) {
+        return Some(0);
+    }
+
+    // Berlekamp-Massey for the error locator, lowest degree first
+    let mut locator = vec![1u8];
+    let mut previous = vec![1u8];
+    let (mut errors, mut shift, mut last) = (0usize, 1usize, 1u8);
+    for k in 0..ecc {
+        let mut delta = syndromes[k];
+        for i in 1..locator.len().min(errors + 1) {
+            delta ^= gf_mul(locator[i], syndromes[k - i]);
+        }
+        if delta == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let factor = gf_div(delta, last);
+        let mut next = locator.clone();
+        next.resize(next.len().max(previous.len() + shift), 0);
+        for (i, coefficient) in previous.iter().enumerate() {
+            next[i + shift] ^= gf_mul(factor, *coefficient);
+        }
+
+        if 2 * errors <= k {
+            previous = std::mem::replace(&mut locator, next);
+            errors = k + 1 - errors;
+            last = delta;
+            shift = 1;
+        } else {
+            locator = next;
+            shift += 1;
+        }
+    }
+    while locator.last() == Some(&0) {
+        locator.pop();
+    }
+    let degree = locator.len() - 1;
+    if degree == 0 || 2 * degree > ecc {
+        return None;
+    }
+
+    // Chien search: position i is wrong when the locator vanishes at α^-(length-1-i)
+    let positions: Vec<usize> = (0..length)
+        .filter(|i| gf_eval(&locator, tables.exp[(255 - (length - 1 - i) % 255) % 255]) == 0)
+        .collect();
+    if positions.len() != degree {
+        return None;
+    }
+
+    // Forney: magnitude = X Ω(X⁻¹) / Λ'(X⁻¹) with Ω = S Λ mod x^ecc
+    let mut evaluator = vec![0u8; ecc];
+    for (i, syndrome) in syndromes.iter().enumerate() {
+        for (j, coefficient) in locator.iter().enumerate() {
+            if i + j < ecc {
+                evaluator[i + j] ^= gf_mul(*syndrome, *coefficient);
+            }
+        }
+    }
+    let derivative: Vec<u8> = locator.iter().enumerate().skip(1).map(|(i, coefficient)| if i % 2 == 1 { *coefficient } else { 0 }).collect();
+
+    for position in &positions {
+        let power = (length - 1 - position) % 255;
+        let (x, x_inverse) = (tables.exp[power], tables.exp[(255 - power) % 255]);
+        let denominator = gf_eval(&derivative, x_inverse);
+        if denominator == 0 {
+            return None;
+        }
+        block[*position] ^= gf_mul(x, gf_div(gf_eval(&evaluator, x_inverse), denominator));
+    }
+
+    let clean = (0..ecc).all(|j| block.iter().fold(0, |acc, codeword| gf_mul(acc, tables.exp[j]) ^ codeword) == 0);
+    clean.then_some(degree)
+}
+
+/* Symbol layout, shared by decoding and encoding */
+
+struct Symbol {
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>
+}
+
+impl Symbol {
+    // Function patterns of a version drawn in, with format (and version) areas reserved
+    fn new(version: usize) -> Symbol {
+        let size = 17 + 4 * version;
+        let mut symbol = Symbol { size, modules: vec![false; size * size], function: vec![false; size * size] };
+
+        for i in 0..size {
+            symbol.set_function(6, i, i % 2 == 0);
+            symbol.set_function(i, 6, i % 2 == 0);
+        }
+
+        // Finders with their separators
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if x >= 0 && y >= 0 && (x as usize) < size && (y as usize) < size {
+                        let ring = dx.abs().max(dy.abs());
+                        symbol.set_function(x as usize, y as usize, ring != 2 && ring != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, x) in positions.iter().enumerate() {
+            for (j, y) in positions.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        symbol.set_function((*x as isize + dx) as usize, (*y as isize + dy) as usize, dx.abs().max(dy.abs()) != 1);
+                    }
+                }
+            }
+        }
+
+        symbol.draw_format(QrErrorCorrection::M, 0);
+        if version >= 7 {
+            let bits = version_codeword(version);
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 == 1;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                symbol.set_function(a, b, dark);
+                symbol.set_function(b, a, dark);
+            }
+        }
+
+        symbol
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    // Both copies of the format information, and the module that is always dark
+    fn draw_format(&mut self, level: QrErrorCorrection, mask: u8) {
+        let bits = format_codeword((level.format_bits() << 3) | mask as u32);
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    // Data modules in placement order: upwards and downwards in two-column strips from the right
+    fn data_positions(&self) -> Vec<(usize, usize)> {
+        let mut positions = Vec::new();
+        let mut right = self.size as isize - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..self.size {
+                for j in 0..2 {
+                    let x = (right - j) as usize;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { self.size - 1 - vertical } else { vertical };
+                    if !self.function[y * self.size + x] {
+                        positions.push((x, y));
+                    }
+                }
+            }
+            right -= 2;
+        }
+        positions
+    }
+}
+
+// Centre coordinates of the alignment patterns along each axis
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+
+    let count = version / 7 + 2;
+    let step = if version == 32 { 26 } else { (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2 };
+    let size = 17 + 4 * version;
+    let mut positions = vec![6];
+    positions.extend((0..count - 1).rev().map(|i| size - 7 - i * step));
+    positions
+}
+
+fn mask_applies(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2)
+    }
+}
+
+/* Encoding */
+
+/// Writes `text` as a QR code PNG, using the smallest version that holds it at the requested level
+pub fn mercy_qr_encode<P: AsRef<Path>>(text: &str, output_path: P, options: &QrEncodeOptions) -> Result<QrEncodeReport, String> {
+    let output_path = output_path.as_ref();
+    if !(1..=64).contains(&options.scale) {
+        return Err(format!("Scale ({}) must be between 1 and 64 pixels per module", options.scale));
+    }
+    if options.border > 64 {
+        return Err(format!("Border ({}) must be at most 64 modules", options.border));
+    }
+
+    let level = options.error_correction;
+    let (mode, indicator, characters) = if !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()) {
+        ("numeric", 1, text.len())
+    } else if !text.is_empty() && text.bytes().all(|byte| ALPHANUMERIC.contains(&byte)) {
+        ("alphanumeric", 2, text.len())
+    } else {
+        ("byte", 4, text.len())
+    };
+
+    let version = (1..=40)
+        .find(|version| 4 + count_bits(indicator, *version) + payload_bits(indicator, text) <= data_codewords(*version, level) * 8 && characters < 1 << count_bits(indicator, *version))
+        .ok_or_else(|| format!("Text is too long for a QR code at level {} ({} bytes)", level, text.len()))?;
+
+    // Segment, terminator, byte padding, then alternating pad codewords
+    let capacity = data_codewords(version, level) * 8;
+    let mut bits = BitWriter::default();
+    bits.push(indicator, 4);
+    bits.push(characters as u32, count_bits(indicator, version));
+    match indicator {
+        1 => {
+            for chunk in text.as_bytes().chunks(3) {
+                let value = chunk.iter().fold(0, |acc, digit| acc * 10 + (digit - b'0') as u32);
+                bits.push(value, chunk.len() * 3 + 1);
+            }
+        },
+        2 => {
+            for chunk in text.as_bytes().chunks(2) {
+                let value = chunk.iter().fold(0, |acc, character| acc * 45 + ALPHANUMERIC.iter().position(|c| c == character).unwrap_or(0) as u32);
+                bits.push(value, if chunk.len() == 2 { 11 } else { 6 });
+            }
+        },
+        _ => {
+            for byte in text.bytes() {
+                bits.push(byte as u32, 8);
+            }
+        }
+    }
+    bits.push(0, (capacity - bits.length).min(4));
+    bits.push(0, (8 - bits.length % 8) % 8);
+    for pad in [0xEC, 0x11].iter().cycle().take((capacity - bits.length) / 8) {
+        bits.push(*pad, 8);
+    }
+
+    let codewords = interleave_with_ecc(&bits.bytes, version, level);
+
+    let mut symbol = Symbol::new(version);
+    for (index, (x, y)) in symbol.data_positions().into_iter().enumerate() {
+        let dark = index < codewords.len() * 8 && codewords[index / 8] & (0x80 >> (index % 8)) != 0;
+        symbol.modules[y * symbol.size + x] = dark;
+    }
+
+    let (mask, modules) = (0..8u8)
+        .map(|mask| {
+            let mut candidate = Symbol { size: symbol.size, modules: symbol.modules.clone(), function: symbol.function.clone() };
+            for y in 0..candidate.size {
+                for x in 0..candidate.size {
+                    if !candidate.function[y * candidate.size + x] && mask_applies(mask, x, y) {
+                        candidate.modules[y * candidate.size + x] ^= true;
+                    }
+                }
+            }
+            candidate.draw_format(level, mask);
+            (mask, candidate.modules)
+        })
+        .min_by_key(|(_, modules)| penalty(modules, symbol.size))
+        .ok_or("No mask could be applied")?;
+
+    let image_size = (symbol.size + 2 * options.border) * options.scale;
+    let mut image = GrayImage { width: image_size, height: image_size, pixels: vec![255; image_size * image_size] };
+    for y in 0..symbol.size {
+        for x in 0..symbol.size {
+            if modules[y * symbol.size + x] {
+                for py in 0..options.scale {
+                    let row = ((y + options.border) * options.scale + py) * image_size;
+                    let start = row + (x + options.border) * options.scale;
+                    image.pixels[start..start + options.scale].fill(0);
+                }
+            }
+        }
+    }
+    write_gray_png(output_path, &image)?;
+
+    Ok(QrEncodeReport {
+        output: path_to_string(output_path),
+        version: version as u8,
+        error_correction: level,
+        mask,
+        mode: mode.to_string(),
+        modules: symbol.size,
+        image_size
+    })
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    length: usize
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            if self.length.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap_or(&mut 0) |= 0x80 >> (self.length % 8);
+            }
+            self.length += 1;
+        }
+    }
+}
+
+// Bits taken by the characters of a segment in one mode
+fn payload_bits(indicator: u32, text: &str) -> usize {
+    let length = text.len();
+    match indicator {
+        1 => length / 3 * 10 + [0, 4, 7][length % 3],
+        2 => length / 2 * 11 + (length % 2) * 6,
+        _ => length * 8
+    }
+}
+
+// Splits data into blocks, appends each block's check codewords, and interleaves them
+fn interleave_with_ecc(data: &[u8], version: usize, level: QrErrorCorrection) -> Vec<u8> {
+    let (blocks, ecc, short_length, short_blocks) = block_layout(version, level);
+    let divisor = reed_solomon_divisor(ecc);
+
+    let mut rows = Vec::with_capacity(blocks);
+    let mut offset = 0;
+    for i in 0..blocks {
+        let length = short_length - ecc + usize::from(i >= short_blocks);
+        let mut row = data[offset..offset + length].to_vec();
+        offset += length;
+        let check = reed_solomon_remainder(&row, &divisor);
+        if i < short_blocks {
+            row.push(0);
+        }
+        row.extend(check);
+        rows.push(row);
+    }
+
+    let mut result = Vec::new();
+    for i in 0..=short_length {
+        for (j, row) in rows.iter().enumerate() {
+            if i != short_length - ecc || j >= short_blocks {
+                result.push(row[i]);
+            }
+        }
+    }
+    result
+}
+
+// Mask penalty: long runs, 2x2 blocks, finder-like sequences and dark/light imbalance
+fn penalty(modules: &[bool], size: usize) -> usize {
+    let at = |x: usize, y: usize| modules[y * size + x];
+    let mut score = 0;
+
+    for vertical in [false, true] {
+        for line in 0..size {
+            let get = |i: usize| if vertical { at(line, i) } else { at(i, line) };
+            let mut run = 1;
+            for i in 1..size {
+                if get(i) == get(i - 1) {
+                    run += 1;
+                    if run == 5 {
+                        score += 3;
+                    } else if run > 5 {
+                        score += 1;
+                    }
+                } else {
+                    run = 1;
+                }
+            }
+
+            for i in 0..size.saturating_sub(10) {
+                let window: Vec<bool> = (i..i + 11).map(get).collect();
+                let core = [true, false, true, true, true, false, true];
+                if (window[..7] == core && window[7..].iter().all(|dark| !dark)) || (window[4..] == core && window[..4].iter().all(|dark| !dark)) {
+                    score += 40;
+                }
+            }
+        }
+    }
+
+    for y in 0..size - 1 {
+        for x in 0..size - 1 {
+            let dark = at(x, y);
+            if at(x + 1, y) == dark && at(x, y + 1) == dark && at(x + 1, y + 1) == dark {
+                score += 3;
+            }
+        }
+    }
+
+    let total = size * size;
+    let dark = modules.iter().filter(|dark| **dark).count();
+    let deviation = (dark * 20).abs_diff(total * 10);
+    score + deviation.div_ceil(total).saturating_sub(1) * 10
+}
+
+// Parses "text out=path [ec=M] [scale=8] [border=4]"; options trail the text
+pub(crate) fn qr_encode_from_args(input: &str) -> Result<QrEncodeReport, String> {
+    let (text, pairs) = trailing_options(input, &["out", "ec", "scale", "border"]);
+    let mut options = QrEncodeOptions::default();
+    let mut output = None;
+
+    for (key, value) in pairs {
+        match key {
+            "out" => output = Some(value),
+            "ec" => {
+                options.error_correction = match value.to_ascii_uppercase().as_str() {
+                    "L" => QrErrorCorrection::L,
+                    "M" => QrErrorCorrection::M,
+                    "Q" => QrErrorCorrection::Q,
+                    "H" => QrErrorCorrection::H,
+                    _ => return Err(format!("Invalid error correction level '{}' (L, M, Q or H)", value))
+                };
+            },
+            _ => {
+                let number = value.parse::<usize>().map_err(|_| format!("Invalid {} '{}'", key, value))?;
+                if key == "scale" {
+                    options.scale = number;
+                } else {
+                    options.border = number;
+                }
+            }
+        }
+    }
+
+    let output = output.ok_or("No output path specified for qr (out=path.png)")?;
+    mercy_qr_encode(&text, output, &options)
+}
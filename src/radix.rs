@@ -0,0 +1,367 @@
+/*
+    Base64 and base32 (RFC 4648), hex, base58 and base85
+
+    Base64 arrives wrapped at 76 characters in MIME parts and 64 in PEM, with its padding
+    stripped in URLs and tokens, and in the URL-safe alphabet ("-" and "_" for "+" and "/").
+    Decoding ignores whitespace and takes padding present or not; the alphabet is the one
+    asked for, and a character of the other one is named in the error. `base64_detect` says
+    which form a blob of unknown origin is in.
+
+    Hex payloads turn up as "0x"-prefixed runs, spaced byte dumps and mixed case; all of them
+    decode. Base32 is what DNS exfiltration tends to use, since it survives case folding, so
+    decoding ignores case and whitespace and accepts the padding either present or stripped.
+    Malformed input is an error naming the problem, never a panic.
+
+    Base58 uses the Bitcoin alphabet of cryptocurrency addresses, where each leading zero byte
+    is a leading '1'. Base85 comes in the Adobe ASCII85 form of PostScript and PDF streams
+    ("<~" and "~>" around it optional when decoding, "z" for a group of zeros) and as ZeroMQ's
+    Z85, which only encodes whole 4-byte groups.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+const Z85_ALPHABET: &[u8; 85] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+/// Lowercase hex of `bytes`
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Bytes of hex text in either case, with an optional "0x" prefix (on the whole or on each whitespace-separated group)
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text.split_whitespace()
+        .map(|group| group.strip_prefix("0x").or_else(|| group.strip_prefix("0X")).unwrap_or(group))
+        .collect();
+
+    if let Some((position, c)) = digits.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex character '{}' at position {}", c, position));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("Hex input has an odd number of digits ({})", digits.len()));
+    }
+
+    Ok(digits.as_bytes().chunks(2).map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1])).collect())
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10
+    }
+}
+
+/// Which base64 form a string is in, as `base64_detect` reads it
+#[derive(Debug, Clone, Serialize)]
+pub struct Base64Variant {
+    /// "standard" ("+" and "/"), "URL-safe" ("-" and "_") or "either" when the text has none of those four
+    pub alphabet: String,
+    /// "padded", "unpadded" or "none needed" when the length is a whole number of 4-symbol groups
+    pub padding: String,
+    /// Characters per line when the text is wrapped (76 for MIME, 64 for PEM)
+    pub line_length: Option<usize>,
+    /// Bytes it decodes to
+    pub decoded_length: usize
+}
+
+impl fmt::Display for Base64Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Alphabet: {}", self.alphabet)?;
+        writeln!(f, "Padding: {}", self.padding)?;
+        match self.line_length {
+            Some(76) => writeln!(f, "Lines: wrapped at 76 characters (MIME)")?,
+            Some(64) => writeln!(f, "Lines: wrapped at 64 characters (PEM)")?,
+            Some(length) => writeln!(f, "Lines: wrapped at {} characters", length)?,
+            None => writeln!(f, "Lines: not wrapped")?
+        }
+        write!(f, "Decodes to: {} bytes", self.decoded_length)
+    }
+}
+
+/// URL-safe base64 ("-" and "_" for "+" and "/") of `bytes`, without padding as in tokens and JWTs
+pub fn base64url_encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Bytes of URL-safe base64 text, padded or not; whitespace is ignored
+pub fn base64url_decode(text: &str) -> Result<Vec<u8>, String> {
+    base64_symbols(text, true).and_then(|symbols| base64_bytes(&symbols, true))
+}
+
+/// Bytes of standard base64 text, padded or not; whitespace, such as the line breaks MIME puts every 76 characters, is ignored
+pub(crate) fn base64_standard_decode(text: &str) -> Result<Vec<u8>, String> {
+    base64_symbols(text, false).and_then(|symbols| base64_bytes(&symbols, false))
+}
+
+/// The alphabet, padding and line wrapping of base64 text, which must decode in the form found
+pub fn base64_detect(text: &str) -> Result<Base64Variant, String> {
+    let standard = text.contains(['+', '/']);
+    let url_safe = text.contains(['-', '_']);
+    if standard && url_safe {
+        return Err("Base64 text mixes the standard (\"+\", \"/\") and URL-safe (\"-\", \"_\") alphabets".to_string());
+    }
+
+    let symbols = base64_symbols(text, url_safe)?;
+    let decoded = base64_bytes(&symbols, url_safe)?;
+    let data_length = symbols.trim_end_matches('=').len();
+
+    let lines: Vec<usize> = text.trim().lines().map(|line| line.trim().len()).filter(|length| *length > 0).collect();
+    Ok(Base64Variant {
+        alphabet: match (standard, url_safe) {
+            (true, _) => "standard",
+            (_, true) => "URL-safe",
+            _ => "either"
+        }.to_string(),
+        padding: match (symbols.len() > data_length, data_length % 4) {
+            (true, _) => "padded",
+            (false, 0) => "none needed",
+            _ => "unpadded"
+        }.to_string(),
+        line_length: if lines.len() > 1 { lines.first().copied() } else { None },
+        decoded_length: decoded.len()
+    })
+}
+
+// The symbols of base64 text without whitespace, checked against the alphabet and the padding rules
+fn base64_symbols(text: &str, url_safe: bool) -> Result<String, String> {
+    let symbols: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let data_length = symbols.trim_end_matches('=').len();
+    let (name, extra, other) = match url_safe {
+        true => ("URL-safe base64", ['-', '_'], "standard base64 (\"base64\")"),
+        false => ("base64", ['+', '/'], "URL-safe base64 (\"base64_url\")")
+    };
+
+    if let Some((position, c)) = symbols[..data_length].char_indices().find(|(_, c)| !c.is_ascii_alphanumeric() && !extra.contains(c)) {
+        let hint = if matches!(c, '+' | '/' | '-' | '_') { format!(", as in {}", other) } else { String::new() };
+        return Err(format!("Invalid {} character '{}' at position {}{}", name, c, position, hint));
+    }
+    if symbols.len() > data_length && (symbols.len() - data_length > 2 || !symbols.len().is_multiple_of(4)) {
+        return Err(format!("Base64 padding leaves {} symbols, not a multiple of 4", symbols.len()));
+    }
+    if data_length % 4 == 1 {
+        return Err(format!("Base64 input has an impossible length ({} symbols before padding)", data_length));
+    }
+    Ok(symbols)
+}
+
+fn base64_bytes(symbols: &str, url_safe: bool) -> Result<Vec<u8>, String> {
+    let config = if url_safe { base64::URL_SAFE_NO_PAD } else { base64::STANDARD_NO_PAD };
+    base64::decode_config(symbols.trim_end_matches('='), config).map_err(|e| format!("Invalid base64: {}", e))
+}
+
+/// Standard padded base32 of `bytes`
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(5) * 8);
+
+    for chunk in bytes.chunks(5) {
+        let mut block = [0u8; 5];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let bits = block.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+
+        // 1 to 5 input bytes fill 2, 4, 5, 7 or 8 symbols
+        let symbols = (chunk.len() * 8).div_ceil(5);
+        for index in 0..8 {
+            if index < symbols {
+                output.push(BASE32_ALPHABET[((bits >> (35 - index * 5)) & 0x1F) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+/// Bytes of base32 text, in either case, padded or not; whitespace is ignored
+pub fn base32_decode(text: &str) -> Result<Vec<u8>, String> {
+    let symbols: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let data_length = symbols.iter().rposition(|c| *c != '=').map(|last| last + 1).unwrap_or(0);
+
+    if symbols.len() > data_length && !symbols.len().is_multiple_of(8) {
+        return Err(format!("Base32 padding leaves {} symbols, not a multiple of 8", symbols.len()));
+    }
+    if matches!(data_length % 8, 1 | 3 | 6) {
+        return Err(format!("Base32 input has an impossible length ({} symbols before padding)", data_length));
+    }
+
+    let mut output = Vec::with_capacity(data_length * 5 / 8);
+    let mut bits = 0u64;
+    let mut count = 0;
+
+    for (position, c) in symbols[..data_length].iter().enumerate() {
+        let value = BASE32_ALPHABET.iter().position(|symbol| *symbol as char == c.to_ascii_uppercase())
+            .ok_or_else(|| format!("Invalid base32 character '{}' at position {}", c, position))?;
+
+        // Only the bits of an unfinished byte are kept
+        bits = ((bits << 5) | value as u64) & 0xFFF;
+        count += 5;
+        if count >= 8 {
+            count -= 8;
+            output.push((bits >> count) as u8);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Base58 (Bitcoin alphabet) of `bytes`, each leading zero byte as a '1'
+pub fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+
+    // Base 58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    "1".repeat(zeros) + &digits.iter().rev().map(|digit| BASE58_ALPHABET[*digit as usize] as char).collect::<String>()
+}
+
+/// Bytes of base58 text (Bitcoin alphabet); surrounding whitespace is ignored
+pub fn base58_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let zeros = text.bytes().take_while(|c| *c == b'1').count();
+
+    // Bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() * 733 / 1000 + 1);
+    for (position, c) in text.chars().enumerate().skip(zeros) {
+        let value = BASE58_ALPHABET.iter().position(|symbol| *symbol as char == c)
+            .ok_or_else(|| format!("Invalid base58 character '{}' at position {}", c, position))?;
+
+        let mut carry = value as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    Ok(std::iter::repeat_n(0, zeros).chain(bytes.into_iter().rev()).collect())
+}
+
+/// Adobe ASCII85 of `bytes` between "<~" and "~>", with "z" for each whole group of zeros
+pub fn ascii85_encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(4) * 5 + 4);
+    output.push_str("<~");
+
+    for chunk in bytes.chunks(4) {
+        if chunk == [0, 0, 0, 0] {
+            output.push('z');
+            continue;
+        }
+        // A final partial group is padded with zeros and cut to one character more than its bytes
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let symbols = base85_group(u32::from_be_bytes(group));
+        output.extend(symbols[..chunk.len() + 1].iter().map(|digit| (b'!' + digit) as char));
+    }
+
+    output.push_str("~>");
+    output
+}
+
+/// Bytes of Adobe ASCII85 text, with or without the "<~" and "~>" delimiters; whitespace is ignored
+pub fn ascii85_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let text = text.strip_prefix("<~").unwrap_or(text);
+    let text = text.strip_suffix("~>").unwrap_or(text);
+
+    let mut output = Vec::with_capacity(text.len() * 4 / 5);
+    let mut group = Vec::with_capacity(5);
+
+    for (position, c) in text.chars().enumerate().filter(|(_, c)| !c.is_whitespace()) {
+        match c {
+            'z' if group.is_empty() => output.extend_from_slice(&[0, 0, 0, 0]),
+            'z' => return Err(format!("ASCII85 'z' inside a group at position {}", position)),
+            '!'..='u' => group.push(c as u8 - b'!'),
+            _ => return Err(format!("Invalid ASCII85 character '{}' at position {}", c, position))
+        }
+        if group.len() == 5 {
+            output.extend_from_slice(&base85_value(&group, "ASCII85")?);
+            group.clear();
+        }
+    }
+
+    match group.len() {
+        0 => {},
+        1 => return Err("ASCII85 input ends with a single character, which encodes no bytes".to_string()),
+        length => {
+            // Padded with the highest digit, then cut back to the bytes it stands for
+            group.resize(5, 84);
+            output.extend_from_slice(&base85_value(&group, "ASCII85")?[..length - 1]);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Z85 of `bytes`, whose length must be a multiple of 4
+pub fn z85_encode(bytes: &[u8]) -> Result<String, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(format!("Z85 encodes whole 4-byte groups; {} bytes is not a multiple of 4", bytes.len()));
+    }
+
+    Ok(bytes.chunks(4)
+        .flat_map(|chunk| base85_group(u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])))
+        .map(|digit| Z85_ALPHABET[digit as usize] as char)
+        .collect())
+}
+
+/// Bytes of Z85 text, whose length must be a multiple of 5; surrounding whitespace is ignored
+pub fn z85_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(5) {
+        return Err(format!("Z85 input is {} characters, not a multiple of 5", text.len()));
+    }
+
+    let digits = text.chars().enumerate()
+        .map(|(position, c)| Z85_ALPHABET.iter().position(|symbol| *symbol as char == c).map(|digit| digit as u8)
+            .ok_or_else(|| format!("Invalid Z85 character '{}' at position {}", c, position)))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let mut output = Vec::with_capacity(digits.len() / 5 * 4);
+    for group in digits.chunks(5) {
+        output.extend_from_slice(&base85_value(group, "Z85")?);
+    }
+    Ok(output)
+}
+
+/// The text of decoded bytes, or "hex:" and their lowercase hex when they are not UTF-8
+pub(crate) fn utf8_or_hex(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|e| format!("hex:{}", hex_encode(e.as_bytes())))
+}
+
+// Five base 85 digits of a 4-byte group, most significant first
+fn base85_group(mut value: u32) -> [u8; 5] {
+    let mut digits = [0u8; 5];
+    for digit in digits.iter_mut().rev() {
+        *digit = (value % 85) as u8;
+        value /= 85;
+    }
+    digits
+}
+
+// The 4 bytes five base 85 digits stand for
+fn base85_value(digits: &[u8], name: &str) -> Result<[u8; 4], String> {
+    let value = digits.iter().fold(0u64, |value, digit| value * 85 + *digit as u64);
+    u32::try_from(value).map(u32::to_be_bytes).map_err(|_| format!("{} group decodes past 2^32", name))
+}
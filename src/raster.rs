@@ -0,0 +1,729 @@
+/*
+    PNG and baseline JPEG decoding to 8-bit luminance, and grayscale PNG writing
+
+    Only brightness is needed by the image scanners, so colour is reduced to luma as pixels are
+    decoded (transparent pixels are composited over white) and JPEG chroma blocks are entropy
+    decoded to keep the bitstream in step but never transformed. Progressive and arithmetic
+    coded JPEGs are reported as unsupported rather than decoded badly.
+*/
+
+use std::{fs, path::Path, sync::OnceLock};
+
+use crate::{checksum::crc32_update, inflate::inflate};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Largest image decoded, in pixels
+const MAX_PIXELS: u64 = 100_000_000;
+
+/// An 8-bit grayscale image, row by row
+#[derive(Debug, Clone)]
+pub(crate) struct GrayImage {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<u8>
+}
+
+impl GrayImage {
+    pub(crate) fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+/// Decodes a PNG or JPEG file, chosen by its signature
+pub(crate) fn read_gray_image(path: &Path) -> Result<GrayImage, String> {
+    let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    if data.starts_with(&PNG_SIGNATURE) {
+        decode_png(&data)
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        decode_jpeg(&data)
+    } else {
+        Err(format!("{} is not a PNG or JPEG image", path.display()))
+    }
+}
+
+/* PNG */
+
+struct PngHeader {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color: u8,
+    interlaced: bool
+}
+
+impl PngHeader {
+    fn channels(&self) -> usize {
+        match self.color {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1
+        }
+    }
+
+    // Bytes in one filtered row of `width` pixels, excluding the filter byte
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+}
+
+// A PNG's header, palette and transparency with its decompressed (still filtered) image data
+struct PngData {
+    header: PngHeader,
+    palette: Vec<[u8; 3]>,
+    alpha: Vec<u8>,
+    raw: Vec<u8>
+}
+
+fn parse_png(data: &[u8]) -> Result<PngData, String> {
+    let mut header = None;
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut alpha: Vec<u8> = Vec::new();
+    let mut compressed = Vec::new();
+    let mut offset = PNG_SIGNATURE.len();
+
+    while offset + 8 <= data.len() {
+        let length = u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as usize;
+        let kind = &data[offset + 4..offset + 8];
+        let body = data.get(offset + 8..offset + 8 + length).ok_or_else(|| format!("PNG chunk at offset {} is truncated", offset))?;
+
+        match kind {
+            b"IHDR" if body.len() >= 13 => {
+                let width = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+                let height = u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize;
+                header = Some(PngHeader { width, height, depth: body[8], color: body[9], interlaced: body[12] == 1 });
+            }
+            b"PLTE" => palette = body.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect(),
+            b"tRNS" => alpha = body.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += 12 + length;
+    }
+
+    let header = header.ok_or("PNG has no IHDR chunk")?;
+    let valid_depth = match header.color {
+        0 => [1, 2, 4, 8, 16].contains(&header.depth),
+        3 => [1, 2, 4, 8].contains(&header.depth),
+        2 | 4 | 6 => [8, 16].contains(&header.depth),
+        _ => false
+    };
+    if !valid_depth {
+        return Err(format!("Unsupported PNG colour type {} at bit depth {}", header.color, header.depth));
+    }
+    if header.width == 0 || header.height == 0 || (header.width as u64) * (header.height as u64) > MAX_PIXELS {
+        return Err(format!("PNG dimensions {}x{} are out of range", header.width, header.height));
+    }
+    if header.color == 3 && palette.is_empty() {
+        return Err("Palette PNG has no PLTE chunk".to_string());
+    }
+
+    let expected = png_passes(&header).iter()
+        .filter(|pass| pass.4 > 0 && pass.5 > 0)
+        .map(|pass| (header.row_bytes(pass.4) + 1) * pass.5)
+        .sum();
+
+    let raw = zlib_decompress(&compressed, expected)?;
+    if raw.len() < expected {
+        return Err(format!("PNG image data is truncated ({} of {} bytes)", raw.len(), expected));
+    }
+
+    Ok(PngData { header, palette, alpha, raw })
+}
+
+// Every pass of the image (one unless interlaced); rows are stored pass by pass, each prefixed by its filter byte
+fn png_passes(header: &PngHeader) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+    if header.interlaced { adam7_passes(header.width, header.height) } else { vec![(0, 0, 1, 1, header.width, header.height)] }
+}
+
+// Unfilters every row and hands each pixel's row and column to `visit` with its index in the full image
+fn for_each_pixel<F: FnMut(usize, &[u8], usize)>(png: &PngData, mut visit: F) -> Result<(), String> {
+    let header = &png.header;
+    let bytes_per_pixel = (header.channels() * header.depth as usize).div_ceil(8);
+    let mut position = 0;
+
+    for (x0, y0, dx, dy, width, height) in png_passes(header) {
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let stride = header.row_bytes(width);
+        let mut previous = vec![0u8; stride];
+
+        for row in 0..height {
+            let filter = png.raw[position];
+            let mut current = png.raw[position + 1..position + 1 + stride].to_vec();
+            position += stride + 1;
+            unfilter(filter, &mut current, &previous, bytes_per_pixel)?;
+
+            for column in 0..width {
+                visit((y0 + row * dy) * header.width + x0 + column * dx, &current, column);
+            }
+            previous = current;
+        }
+    }
+
+    Ok(())
+}
+
+fn decode_png(data: &[u8]) -> Result<GrayImage, String> {
+    let png = parse_png(data)?;
+    let mut image = GrayImage { width: png.header.width, height: png.header.height, pixels: vec![255; png.header.width * png.header.height] };
+    for_each_pixel(&png, |index, row, column| image.pixels[index] = png_pixel(&png.header, row, column, &png.palette, &png.alpha))?;
+    Ok(image)
+}
+
+/// The stored samples of a PNG, channel by channel, before any colour conversion
+#[derive(Debug, Clone)]
+pub(crate) struct PngSamples {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Channel names in sample order ("gray", "red", "green", "blue", "alpha", or "index" for palette images)
+    pub(crate) channels: Vec<&'static str>,
+    /// Samples pixel by pixel; 16-bit samples keep their low byte and lower depths their raw value
+    pub(crate) samples: Vec<u8>
+}
+
+/// Decodes a PNG file to its raw channel samples
+pub(crate) fn read_png_samples(data: &[u8]) -> Result<PngSamples, String> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err("Not a PNG image".to_string());
+    }
+
+    let png = parse_png(data)?;
+    let header = &png.header;
+    let channels: Vec<&'static str> = match header.color {
+        0 => vec!["gray"],
+        2 => vec!["red", "green", "blue"],
+        3 => vec!["index"],
+        4 => vec!["gray", "alpha"],
+        _ => vec!["red", "green", "blue", "alpha"]
+    };
+
+    let count = channels.len();
+    let mut samples = vec![0u8; header.width * header.height * count];
+    for_each_pixel(&png, |index, row, column| {
+        for channel in 0..count {
+            let sample = column * count + channel;
+            samples[index * count + channel] = match header.depth {
+                16 => row[sample * 2 + 1],
+                8 => row[sample],
+                depth => {
+                    let bit = sample * depth as usize;
+                    (row[bit / 8] >> (8 - depth as usize - bit % 8)) & ((1 << depth) - 1)
+                }
+            };
+        }
+    })?;
+
+    Ok(PngSamples { width: header.width, height: header.height, channels, samples })
+}
+
+// (x offset, y offset, x step, y step, width, height) of each Adam7 pass
+fn adam7_passes(width: usize, height: usize) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+    [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)]
+        .iter()
+        .map(|&(x0, y0, dx, dy)| (x0, y0, dx, dy, (width + dx - 1 - x0) / dx, (height + dy - 1 - y0) / dy))
+        .collect()
+}
+
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), String> {
+    for index in 0..row.len() {
+        let left = if index >= bpp { row[index - bpp] } else { 0 };
+        let up = previous[index];
+        let up_left = if index >= bpp { previous[index - bpp] } else { 0 };
+
+        row[index] = row[index].wrapping_add(match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            other => return Err(format!("Invalid PNG filter type {}", other))
+        });
+    }
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+// Luma of one pixel of an unfiltered row, composited over white
+fn png_pixel(header: &PngHeader, row: &[u8], column: usize, palette: &[[u8; 3]], alpha: &[u8]) -> u8 {
+    let sample = |index: usize| -> u8 {
+        match header.depth {
+            16 => row[index * 2],
+            8 => row[index],
+            depth => {
+                let bit = index * depth as usize;
+                let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) & ((1 << depth) - 1);
+                // Palette indices stay as they are, grays scale up to 0..255
+                if header.color == 3 { value } else { (value as u16 * 255 / ((1u16 << depth) - 1)) as u8 }
+            }
+        }
+    };
+
+    let channels = header.channels();
+    let base = column * channels;
+    let (luma, opacity) = match header.color {
+        0 => (sample(base), 255),
+        2 => (luma(sample(base), sample(base + 1), sample(base + 2)), 255),
+        3 => {
+            let index = sample(base) as usize;
+            let [r, g, b] = palette.get(index).copied().unwrap_or([0, 0, 0]);
+            (luma(r, g, b), alpha.get(index).copied().unwrap_or(255))
+        }
+        4 => (sample(base), sample(base + 1)),
+        _ => (luma(sample(base), sample(base + 1), sample(base + 2)), sample(base + 3))
+    };
+
+    ((luma as u32 * opacity as u32 + 255 * (255 - opacity as u32)) / 255) as u8
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+// zlib framing around a DEFLATE stream; the Adler-32 trailer is not checked
+fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 2 || data[0] & 0x0F != 8 || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31) {
+        return Err("PNG image data is not a zlib stream".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("PNG image data uses a preset dictionary".to_string());
+    }
+    inflate(&data[2..], limit)
+}
+
+/// Writes a grayscale image as an 8-bit PNG, using stored (uncompressed) DEFLATE blocks
+pub(crate) fn write_gray_png(path: &Path, image: &GrayImage) -> Result<(), String> {
+    let mut raw = Vec::with_capacity((image.width + 1) * image.height);
+    for row in image.pixels.chunks(image.width) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    // zlib header, stored blocks of up to 65535 bytes, Adler-32
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(65_535).collect();
+    for (index, block) in blocks.iter().enumerate() {
+        zlib.push((index + 1 == blocks.len()) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(image.width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(image.height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    for (kind, body) in [(b"IHDR", ihdr.as_slice()), (b"IDAT", zlib.as_slice()), (b"IEND", &[][..])] {
+        png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(body);
+        let crc = crc32_update(crc32_update(0xFFFF_FFFF, kind), body) ^ 0xFFFF_FFFF;
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    fs::write(path, png).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65_521;
+        b %= 65_521;
+    }
+    (b << 16) | a
+}
+
+/* JPEG */
+
+#[derive(Clone, Default)]
+struct HuffmanTable {
+    // (code length, code) to symbol, searched by length
+    lookup: Vec<Vec<(u16, u8)>>
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8], symbols: &[u8]) -> HuffmanTable {
+        let mut lookup = vec![Vec::new(); 17];
+        let mut code = 0u16;
+        let mut next = 0;
+
+        for (length, count) in counts.iter().enumerate() {
+            for _ in 0..*count {
+                if let Some(symbol) = symbols.get(next) {
+                    lookup[length + 1].push((code, *symbol));
+                }
+                next += 1;
+                code = code.wrapping_add(1);
+            }
+            code <<= 1;
+        }
+        HuffmanTable { lookup }
+    }
+}
+
+#[derive(Clone)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc_table: usize,
+    ac_table: usize,
+    predictor: i32
+}
+
+// Reads entropy-coded bits, undoing 0xFF00 stuffing and stopping at markers
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    bits: u32
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        if self.bits == 0 {
+            let byte = match self.data.get(self.position) {
+                Some(0xFF) if self.data.get(self.position + 1) == Some(&0x00) => {
+                    self.position += 2;
+                    0xFF
+                }
+                // A marker (or the end of data) pads with ones, as decoders conventionally do
+                Some(0xFF) | None => 0xFF,
+                Some(byte) => {
+                    self.position += 1;
+                    *byte
+                }
+            };
+            self.buffer = byte as u32;
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        Ok((self.buffer >> self.bits) & 1)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<i32, String> {
+        let mut value = 0i32;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()? as i32;
+        }
+        Ok(value)
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> Result<u8, String> {
+        let mut code = 0u16;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit()? as u16;
+            if let Some((_, symbol)) = table.lookup[length].iter().find(|(candidate, _)| *candidate == code) {
+                return Ok(*symbol);
+            }
+        }
+        Err(format!("Invalid JPEG Huffman code near offset {}", self.position))
+    }
+
+    // Skips to the restart marker that should follow and resets bit alignment
+    fn restart(&mut self) {
+        self.bits = 0;
+        while self.position + 1 < self.data.len() {
+            if self.data[self.position] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.position + 1]) {
+                self.position += 2;
+                return;
+            }
+            self.position += 1;
+        }
+    }
+}
+
+// Extends a `size`-bit magnitude category to its signed value
+fn extend(value: i32, size: u32) -> i32 {
+    if size == 0 {
+        0
+    } else if value < 1 << (size - 1) {
+        value - (1 << size) + 1
+    } else {
+        value
+    }
+}
+
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63
+];
+
+fn decode_jpeg(data: &[u8]) -> Result<GrayImage, String> {
+    let mut quant = [[0u16; 64]; 4];
+    let mut dc_tables = vec![HuffmanTable::default(); 4];
+    let mut ac_tables = vec![HuffmanTable::default(); 4];
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let mut restart_interval = 0usize;
+    // Luma plane at the component's own resolution, padded to whole blocks
+    let mut plane: Vec<u8> = Vec::new();
+    let mut plane_width = 0usize;
+    let mut offset = 2;
+
+    loop {
+        // Skip fill bytes before a marker
+        while data.get(offset) == Some(&0xFF) && data.get(offset + 1) == Some(&0xFF) {
+            offset += 1;
+        }
+        if data.get(offset) != Some(&0xFF) {
+            return Err(format!("Expected a JPEG marker at offset {}", offset));
+        }
+        let marker = *data.get(offset + 1).ok_or("JPEG ends before its image data")?;
+        offset += 2;
+
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+
+        let length = data.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize).ok_or("JPEG segment length truncated")?;
+        let segment = data.get(offset + 2..offset + length).ok_or_else(|| format!("JPEG segment at offset {} is truncated", offset))?;
+        offset += length;
+
+        match marker {
+            // Quantization tables, 8 or 16-bit precision
+            0xDB => {
+                let mut rest = segment;
+                while let Some((&info, tail)) = rest.split_first() {
+                    let wide = info >> 4 != 0;
+                    let size = if wide { 128 } else { 64 };
+                    let values = tail.get(..size).ok_or("JPEG quantization table truncated")?;
+                    let table = &mut quant[(info & 3) as usize];
+                    for (index, value) in table.iter_mut().enumerate() {
+                        *value = if wide { u16::from_be_bytes([values[index * 2], values[index * 2 + 1]]) } else { values[index] as u16 };
+                    }
+                    rest = &tail[size..];
+                }
+            }
+            0xC4 => {
+                let mut rest = segment;
+                while rest.len() >= 17 {
+                    let class = rest[0] >> 4;
+                    let index = (rest[0] & 3) as usize;
+                    let counts = &rest[1..17];
+                    let total: usize = counts.iter().map(|count| *count as usize).sum();
+                    let symbols = rest.get(17..17 + total).ok_or("JPEG Huffman table truncated")?;
+                    let table = HuffmanTable::new(counts, symbols);
+                    if class == 0 { dc_tables[index] = table } else { ac_tables[index] = table }
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xDD => restart_interval = segment.get(..2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize).unwrap_or(0),
+            // Baseline and extended sequential Huffman frames
+            0xC0 | 0xC1 => {
+                if segment.len() < 6 || segment[0] != 8 {
+                    return Err("Only 8-bit JPEG samples are supported".to_string());
+                }
+                height = u16::from_be_bytes([segment[1], segment[2]]) as usize;
+                width = u16::from_be_bytes([segment[3], segment[4]]) as usize;
+                if width == 0 || height == 0 || (width as u64) * (height as u64) > MAX_PIXELS {
+                    return Err(format!("JPEG dimensions {}x{} are out of range", width, height));
+                }
+
+                components = segment[6..].chunks_exact(3).take(segment[5] as usize)
+                    .map(|c| Component { id: c[0], h: (c[1] >> 4).max(1) as usize, v: (c[1] & 15).max(1) as usize, quant: (c[2] & 3) as usize, dc_table: 0, ac_table: 0, predictor: 0 })
+                    .collect();
+                if components.is_empty() {
+                    return Err("JPEG frame has no components".to_string());
+                }
+            }
+            0xC2 | 0xC6 | 0xCA | 0xCE => return Err("Progressive JPEGs are not supported".to_string()),
+            0xC3 | 0xC5 | 0xC7 | 0xC9 | 0xCB | 0xCD | 0xCF => return Err("Lossless, hierarchical and arithmetic coded JPEGs are not supported".to_string()),
+            0xDA => {
+                if components.is_empty() {
+                    return Err("JPEG scan appears before its frame header".to_string());
+                }
+
+                let count = *segment.first().ok_or("JPEG scan header truncated")? as usize;
+                let mut scan = Vec::new();
+                for selector in segment[1..].chunks_exact(2).take(count) {
+                    let index = components.iter().position(|c| c.id == selector[0]).ok_or("JPEG scan names an unknown component")?;
+                    components[index].dc_table = (selector[1] >> 4) as usize & 3;
+                    components[index].ac_table = (selector[1] & 15) as usize & 3;
+                    components[index].predictor = 0;
+                    scan.push(index);
+                }
+
+                let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+                let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+                let mcus_x = width.div_ceil(8 * h_max);
+                let mcus_y = height.div_ceil(8 * v_max);
+
+                // The first component is luma (or the only channel of a grayscale image)
+                if plane.is_empty() {
+                    plane_width = mcus_x * components[0].h * 8;
+                    plane = vec![0u8; plane_width * mcus_y * components[0].v * 8];
+                }
+
+                let mut reader = BitReader { data, position: offset, buffer: 0, bits: 0 };
+                let mut block = [0i32; 64];
+
+                // A single-component scan covers that component's blocks one at a time
+                let (units_x, units_y) = if scan.len() == 1 {
+                    let c = &components[scan[0]];
+                    ((width * c.h).div_ceil(h_max).div_ceil(8), (height * c.v).div_ceil(v_max).div_ceil(8))
+                } else {
+                    (mcus_x, mcus_y)
+                };
+
+                let mut units = 0;
+                for unit_y in 0..units_y {
+                    for unit_x in 0..units_x {
+                        if restart_interval > 0 && units > 0 && units % restart_interval == 0 {
+                            reader.restart();
+                            for index in &scan {
+                                components[*index].predictor = 0;
+                            }
+                        }
+                        units += 1;
+
+                        for index in &scan {
+                            let (h, v) = if scan.len() == 1 { (1, 1) } else { (components[*index].h, components[*index].v) };
+                            for block_y in 0..v {
+                                for block_x in 0..h {
+                                    decode_block(&mut reader, &mut components[*index], &dc_tables, &ac_tables, &mut block)?;
+                                    if *index != 0 {
+                                        continue;
+                                    }
+
+                                    let (bx, by) = if scan.len() == 1 { (unit_x, unit_y) } else { (unit_x * h + block_x, unit_y * v + block_y) };
+                                    let pixels = idct(&block, &quant[components[0].quant]);
+                                    for row in 0..8 {
+                                        let start = (by * 8 + row) * plane_width + bx * 8;
+                                        if let Some(target) = plane.get_mut(start..start + 8) {
+                                            target.copy_from_slice(&pixels[row * 8..row * 8 + 8]);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Continue after the entropy-coded data, at the next marker that is not a restart
+                offset = reader.position;
+                while offset + 1 < data.len() && !(data[offset] == 0xFF && data[offset + 1] != 0 && !(0xD0..=0xD7).contains(&data[offset + 1])) {
+                    offset += 1;
+                }
+            }
+            _ => {}
+        }
+
+        if offset >= data.len() {
+            break;
+        }
+    }
+
+    if plane.is_empty() {
+        return Err("JPEG has no image data".to_string());
+    }
+
+    // Scale the luma plane up when it is subsampled relative to the other components
+    let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+    let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+    let (sx, sy) = (h_max / components[0].h, v_max / components[0].v);
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.push(plane[(y / sy) * plane_width + x / sx]);
+        }
+    }
+    Ok(GrayImage { width, height, pixels })
+}
+
+fn decode_block(reader: &mut BitReader, component: &mut Component, dc_tables: &[HuffmanTable], ac_tables: &[HuffmanTable], block: &mut [i32; 64]) -> Result<(), String> {
+    *block = [0; 64];
+
+    let size = reader.decode(&dc_tables[component.dc_table])? as u32;
+    let difference = extend(reader.bits(size)?, size);
+    component.predictor += difference;
+    block[0] = component.predictor;
+
+    let mut index = 1;
+    while index < 64 {
+        let symbol = reader.decode(&ac_tables[component.ac_table])?;
+        let (run, size) = ((symbol >> 4) as usize, (symbol & 15) as u32);
+
+        if size == 0 {
+            // End of block, or a run of sixteen zeros
+            if run != 15 {
+                break;
+            }
+            index += 16;
+            continue;
+        }
+
+        index += run;
+        if index >= 64 {
+            return Err("JPEG block has too many coefficients".to_string());
+        }
+        block[ZIGZAG[index]] = extend(reader.bits(size)?, size);
+        index += 1;
+    }
+
+    Ok(())
+}
+
+// Dequantizes a block of zigzag-placed coefficients and applies the inverse DCT
+fn idct(block: &[i32; 64], quant: &[u16; 64]) -> [u8; 64] {
+    let mut coefficients = [0f32; 64];
+    for (zigzag, natural) in ZIGZAG.iter().enumerate() {
+        coefficients[*natural] = block[*natural] as f32 * quant[zigzag] as f32;
+    }
+
+    let cosines = |x: usize, u: usize| -> f32 { idct_cosines()[x][u] };
+
+    // Rows then columns
+    let mut temp = [0f32; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            temp[y * 8 + x] = (0..8).map(|u| cosines(x, u) * coefficients[y * 8 + u]).sum::<f32>() / 2.0;
+        }
+    }
+
+    let mut output = [0u8; 64];
+    for x in 0..8 {
+        for y in 0..8 {
+            let value = (0..8).map(|v| cosines(y, v) * temp[v * 8 + x]).sum::<f32>() / 2.0;
+            output[y * 8 + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    output
+}
+
+// Scaled cosine basis, cosines[x][u], computed once
+fn idct_cosines() -> &'static [[f32; 8]; 8] {
+    static COSINES: OnceLock<[[f32; 8]; 8]> = OnceLock::new();
+    COSINES.get_or_init(|| {
+        let mut table = [[0f32; 8]; 8];
+        for (x, row) in table.iter_mut().enumerate() {
+            for (u, value) in row.iter_mut().enumerate() {
+                let scale = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+                *value = scale * (((2 * x + 1) * u) as f32 * std::f32::consts::PI / 16.0).cos();
+            }
+        }
+        table
+    })
+}
@@ -0,0 +1,317 @@
+/*
+    Redaction of results before they are shared
+
+    Masks internal addresses (RFC 1918 IPv4, IPv6 unique-local and link-local), the local
+    hostname, usernames taken from profile paths (C:\Users\<name>, /home/<name>,
+    /Users/<name>, also with JSON-escaped backslashes) and caller-registered sensitive
+    strings, replacing each distinct value with a placeholder such as "internal-ip-1",
+    "host-1", "user-1" or "sensitive-1".
+
+    Placeholders are kept for the whole process, so one value maps to the same placeholder in
+    every result and correlations between results survive; `redaction_key` lists them for
+    the analyst and `reset_redaction_key` starts over. A username seen in a path is masked
+    wherever it appears afterwards, in this and later results. With `set_redaction` every
+    string dispatcher passes its result through the policy before returning it.
+*/
+
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::{Mutex, OnceLock}
+};
+
+use serde::Serialize;
+
+use crate::trailing_options;
+
+// Profile directory names that are not people
+const SHARED_PROFILES: &[&str] = &["all", "all users", "default", "default user", "public", "shared", "guest"];
+
+// Shorter usernames are only masked inside paths, not wherever they appear
+const MIN_BARE_USERNAME: usize = 3;
+
+static SESSION: Mutex<Session> = Mutex::new(Session { policy: None, placeholders: BTreeMap::new(), counts: BTreeMap::new(), key: Vec::new() });
+
+/// What to mask; everything but the sensitive strings is on by default
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactionPolicy {
+    /// RFC 1918 IPv4 and IPv6 unique-local/link-local addresses
+    pub internal_ips: bool,
+    /// This machine's hostname, full and short
+    pub hostname: bool,
+    /// Usernames in profile paths, then anywhere they recur
+    pub usernames: bool,
+    /// Masked wherever they appear, ignoring ASCII case (client names, internal domains...)
+    pub sensitive: Vec<String>
+}
+
+impl Default for RedactionPolicy {
+    fn default() -> Self {
+        RedactionPolicy {
+            internal_ips: true,
+            hostname: true,
+            usernames: true,
+            sensitive: Vec::new()
+        }
+    }
+}
+
+/// A placeholder and the value it stands for
+#[derive(Debug, Clone, Serialize)]
+pub struct RedactedValue {
+    pub placeholder: String,
+    /// "internal-ip", "host", "user" or "sensitive"
+    pub kind: String,
+    pub value: String
+}
+
+// Placeholders issued so far and the policy applied to dispatcher results, if any
+struct Session {
+    policy: Option<RedactionPolicy>,
+    placeholders: BTreeMap<(&'static str, String), String>,
+    counts: BTreeMap<&'static str, usize>,
+    key: Vec<RedactedValue>
+}
+
+impl Session {
+    fn placeholder(&mut self, kind: &'static str, value: &str) -> String {
+        let normalized = value.to_ascii_lowercase();
+        if let Some(placeholder) = self.placeholders.get(&(kind, normalized.clone())) {
+            return placeholder.clone();
+        }
+
+        let count = self.counts.entry(kind).or_insert(0);
+        *count += 1;
+        let placeholder = format!("{}-{}", kind, count);
+
+        self.placeholders.insert((kind, normalized), placeholder.clone());
+        self.key.push(RedactedValue { placeholder: placeholder.clone(), kind: kind.to_string(), value: value.to_string() });
+        placeholder
+    }
+
+    fn usernames(&self) -> Vec<String> {
+        self.placeholders.keys().filter(|(kind, _)| *kind == "user").map(|(_, value)| value.clone()).collect()
+    }
+}
+
+// A span of the text to replace
+struct Found {
+    start: usize,
+    end: usize,
+    kind: &'static str
+}
+
+/// Turns redaction of every string dispatcher result on with a policy, or off with `None`
+pub fn set_redaction(policy: Option<RedactionPolicy>) {
+    session().policy = policy;
+}
+
+/// The policy applied to dispatcher results, if redaction is on
+pub fn redaction_policy() -> Option<RedactionPolicy> {
+    session().policy.clone()
+}
+
+/// Every placeholder issued since the process started (or the last reset), in order
+pub fn redaction_key() -> Vec<RedactedValue> {
+    session().key.clone()
+}
+
+/// Forgets the placeholders issued so far, so numbering starts again at 1
+pub fn reset_redaction_key() {
+    let mut session = session();
+    session.placeholders.clear();
+    session.counts.clear();
+    session.key.clear();
+}
+
+/// The text with internal addresses, the hostname, usernames and sensitive strings replaced by stable placeholders
+pub fn mercy_redact_output(text: &str, policy: &RedactionPolicy) -> String {
+    let mut session = session();
+    let lowered = text.to_ascii_lowercase();
+    let mut found = Vec::new();
+
+    for sensitive in policy.sensitive.iter().filter(|sensitive| !sensitive.is_empty()) {
+        found.extend(occurrences(&lowered, &sensitive.to_ascii_lowercase(), false).map(|(start, end)| Found { start, end, kind: "sensitive" }));
+    }
+
+    if policy.hostname {
+        for name in hostnames() {
+            found.extend(occurrences(&lowered, name, true).map(|(start, end)| Found { start, end, kind: "host" }));
+        }
+    }
+
+    if policy.usernames {
+        let in_paths = profile_users(text, &lowered);
+        // Register names from paths first so later mentions share their placeholder
+        for (start, end) in &in_paths {
+            session.placeholder("user", &text[*start..*end]);
+        }
+        found.extend(in_paths.into_iter().map(|(start, end)| Found { start, end, kind: "user" }));
+
+        for name in session.usernames().iter().filter(|name| name.len() >= MIN_BARE_USERNAME) {
+            found.extend(occurrences(&lowered, name, true).map(|(start, end)| Found { start, end, kind: "user" }));
+        }
+    }
+
+    if policy.internal_ips {
+        found.extend(internal_addresses(text).into_iter().map(|(start, end)| Found { start, end, kind: "internal-ip" }));
+    }
+
+    // Earliest first, the longest of those starting together, skipping anything overlapping a kept span
+    found.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut position = 0;
+    for span in found {
+        if span.start < position {
+            continue;
+        }
+
+        let value = &text[span.start..span.end];
+        let value = if span.kind == "internal-ip" { canonical_ip(value) } else { value.to_string() };
+        redacted.push_str(&text[position..span.start]);
+        redacted.push_str(&session.placeholder(span.kind, &value));
+        position = span.end;
+    }
+
+    redacted.push_str(&text[position..]);
+    redacted
+}
+
+/// A dispatcher result, redacted when `set_redaction` turned redaction on
+pub(crate) fn redact_enabled(output: String) -> String {
+    match redaction_policy() {
+        Some(policy) => mercy_redact_output(&output, &policy),
+        None => output
+    }
+}
+
+fn session() -> std::sync::MutexGuard<'static, Session> {
+    SESSION.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// The local hostname in lowercase, and its first label when it is fully qualified
+fn hostnames() -> &'static [String] {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+
+    NAMES.get_or_init(|| {
+        let Ok(full) = sys_info::hostname() else { return Vec::new() };
+        let full = full.trim().to_ascii_lowercase();
+        let mut names = Vec::new();
+
+        if let Some((short, _)) = full.split_once('.') {
+            if short.len() >= MIN_BARE_USERNAME {
+                names.push(short.to_string());
+            }
+        }
+        if !full.is_empty() {
+            names.insert(0, full);
+        }
+        names
+    })
+}
+
+// Byte ranges of `needle` in `haystack` (both lowercased); `whole` requires it not to continue a name on either side
+fn occurrences<'a>(haystack: &'a str, needle: &'a str, whole: bool) -> impl Iterator<Item = (usize, usize)> + 'a {
+    haystack.match_indices(needle).map(|(start, _)| (start, start + needle.len())).filter(move |(start, end)| {
+        !whole || (!haystack[..*start].ends_with(name_char) && !haystack[*end..].starts_with(name_char))
+    })
+}
+
+fn name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+// Usernames in C:\Users\<name>, /home/<name> and /Users/<name>, as byte ranges of the name
+fn profile_users(text: &str, lowered: &str) -> Vec<(usize, usize)> {
+    let mut users = Vec::new();
+
+    for directory in ["users", "home"] {
+        for (start, _) in lowered.match_indices(directory) {
+            let before = &lowered[..start];
+            let after = &lowered[start + directory.len()..];
+            let windows = directory == "users" && before.ends_with('\\');
+            if !(before.ends_with('/') || windows) {
+                continue;
+            }
+
+            let separators = after.len() - after.trim_start_matches(['\\', '/']).len();
+            if separators == 0 {
+                continue;
+            }
+
+            let name_start = start + directory.len() + separators;
+            let name_len = text[name_start..].find(|c: char| c == '\\' || c == '/' || c == '"' || c == '\'' || c.is_whitespace() || c.is_control() || ":;,<>|*?".contains(c)).unwrap_or(text.len() - name_start);
+            let name = &lowered[name_start..name_start + name_len];
+
+            if name_len == 0 || SHARED_PROFILES.contains(&name) || name.starts_with(['%', '$', '{', '.']) {
+                continue;
+            }
+            users.push((name_start, name_start + name_len));
+        }
+    }
+
+    users
+}
+
+// Byte ranges of internal IPv4 and IPv6 addresses not embedded in longer tokens
+fn internal_addresses(text: &str) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let starts_token = index == 0 || !(bytes[index - 1].is_ascii_alphanumeric() || bytes[index - 1] == b'.' || bytes[index - 1] == b':');
+        if !starts_token || !bytes[index].is_ascii_hexdigit() {
+            index += 1;
+            continue;
+        }
+
+        let run = bytes[index..].iter().take_while(|b| b.is_ascii_hexdigit() || **b == b'.' || **b == b':').count();
+        let candidate = text[index..index + run].trim_end_matches(['.', ':']);
+        let end = index + candidate.len();
+        let ends_token = !text[end..].starts_with(|c: char| c.is_ascii_alphanumeric());
+
+        if ends_token {
+            let internal = match candidate.parse::<IpAddr>() {
+                Ok(IpAddr::V4(v4)) => v4.is_private(),
+                Ok(IpAddr::V6(v6)) => is_internal_v6(&v6),
+                Err(_) => false
+            };
+            if internal {
+                found.push((index, end));
+            }
+        }
+
+        index += run.max(1);
+    }
+
+    found
+}
+
+// Unique-local fc00::/7 and link-local fe80::/10
+fn is_internal_v6(addr: &Ipv6Addr) -> bool {
+    let first = addr.segments()[0];
+    first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+}
+
+fn canonical_ip(value: &str) -> String {
+    value.parse::<IpAddr>().map(|ip| ip.to_string()).unwrap_or_else(|_| value.to_string())
+}
+
+// Parses "text [sensitive=acme,contoso] [ips=false] [host=false] [users=false]"
+pub(crate) fn redact_from_args(input: &str) -> String {
+    let (text, pairs) = trailing_options(input, &["sensitive", "ips", "host", "users"]);
+    let mut policy = RedactionPolicy::default();
+
+    for (key, value) in pairs {
+        match key {
+            "sensitive" => policy.sensitive.extend(value.split(',').filter(|value| !value.is_empty()).map(str::to_string)),
+            "ips" => policy.internal_ips = value != "false",
+            "host" => policy.hostname = value != "false",
+            _ => policy.usernames = value != "false"
+        }
+    }
+
+    mercy_redact_output(&text, &policy)
+}
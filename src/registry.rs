@@ -0,0 +1,272 @@
+/*
+    Read-only Windows registry hive (regf) parsing
+
+    Enough of the format to walk keys by path and read values from an exported hive file: nk/vk
+    cells, lf/lh/li/ri subkey lists, inline and big-data (db) values. Transaction logs are not
+    replayed, so a dirty hive reflects its last flushed state. Every offset is bounds checked.
+*/
+
+use std::{fs, path::Path};
+
+// Hive bins start after the 4 KiB base block; cell offsets are relative to this
+const HBIN_START: usize = 4096;
+
+// Largest data a single value cell holds before the big-data (db) layout is used
+const BIG_DATA_THRESHOLD: usize = 16_344;
+
+// Guards against cyclic or absurdly deep subkey lists in corrupt hives
+const MAX_LIST_DEPTH: usize = 8;
+
+/// A loaded hive
+pub(crate) struct RegistryHive {
+    data: Vec<u8>,
+    root: usize,
+    minor_version: u32
+}
+
+/// A key (nk cell) within a hive
+#[derive(Clone, Copy)]
+pub(crate) struct RegistryKey<'a> {
+    hive: &'a RegistryHive,
+    cell: &'a [u8]
+}
+
+/// Registry value types that callers interpret
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RegistryData {
+    String(String),
+    Dword(u32),
+    Qword(u64),
+    Binary(Vec<u8>),
+    Other(u32, Vec<u8>)
+}
+
+impl RegistryHive {
+    pub(crate) fn open(path: &Path) -> Result<RegistryHive, String> {
+        let data = fs::read(path).map_err(|e| format!("Unable to read hive {}: {}", path.display(), e))?;
+        RegistryHive::from_bytes(data)
+    }
+
+    pub(crate) fn from_bytes(data: Vec<u8>) -> Result<RegistryHive, String> {
+        if data.len() < HBIN_START || &data[..4] != b"regf" {
+            return Err("Not a registry hive: missing regf signature".to_string());
+        }
+
+        let minor_version = le32(&data, 0x18).unwrap_or(0);
+        let root = le32(&data, 0x24).ok_or("Truncated hive header")? as usize;
+
+        let hive = RegistryHive { data, root, minor_version };
+        hive.root_key()?;
+        Ok(hive)
+    }
+
+    pub(crate) fn root_key(&self) -> Result<RegistryKey<'_>, String> {
+        self.key_at(self.root)
+    }
+
+    /// Looks up a backslash separated path below the root key, ignoring case
+    pub(crate) fn key(&self, path: &str) -> Result<Option<RegistryKey<'_>>, String> {
+        let mut key = self.root_key()?;
+
+        for part in path.split('\\').filter(|part| !part.is_empty()) {
+            match key.subkey(part)? {
+                Some(next) => key = next,
+                None => return Ok(None)
+            }
+        }
+
+        Ok(Some(key))
+    }
+
+    // Returns the data of the allocated cell at `offset` (without its size field)
+    fn cell(&self, offset: usize) -> Result<&[u8], String> {
+        let start = HBIN_START.checked_add(offset).ok_or("Cell offset overflow")?;
+        let size = le32(&self.data, start).ok_or_else(|| format!("Cell offset 0x{:x} is outside the hive", offset))? as i32;
+
+        // Allocated cells carry a negative size
+        if size >= 0 {
+            return Err(format!("Cell at 0x{:x} is not allocated", offset));
+        }
+
+        let size = size.unsigned_abs() as usize;
+        if size < 4 {
+            return Err(format!("Cell at 0x{:x} has an invalid size", offset));
+        }
+
+        self.data.get(start + 4..start + size).ok_or_else(|| format!("Cell at 0x{:x} runs past the end of the hive", offset))
+    }
+
+    fn key_at(&self, offset: usize) -> Result<RegistryKey<'_>, String> {
+        let cell = self.cell(offset)?;
+        if cell.len() < 76 || &cell[..2] != b"nk" {
+            return Err(format!("Cell at 0x{:x} is not a key", offset));
+        }
+
+        Ok(RegistryKey { hive: self, cell })
+    }
+
+    // Collects key offsets from an lf/lh/li/ri list
+    fn subkey_offsets(&self, offset: usize, depth: usize, out: &mut Vec<usize>) -> Result<(), String> {
+        if depth > MAX_LIST_DEPTH {
+            return Err("Subkey list nesting is too deep".to_string());
+        }
+
+        let cell = self.cell(offset)?;
+        let count = le16(cell, 2).ok_or("Truncated subkey list")? as usize;
+
+        match cell.get(..2) {
+            Some(b"lf") | Some(b"lh") => {
+                for index in 0..count {
+                    out.push(le32(cell, 4 + index * 8).ok_or("Truncated subkey list")? as usize);
+                }
+            }
+            Some(b"li") => {
+                for index in 0..count {
+                    out.push(le32(cell, 4 + index * 4).ok_or("Truncated subkey list")? as usize);
+                }
+            }
+            Some(b"ri") => {
+                for index in 0..count {
+                    let list = le32(cell, 4 + index * 4).ok_or("Truncated subkey list")? as usize;
+                    self.subkey_offsets(list, depth + 1, out)?;
+                }
+            }
+            _ => return Err(format!("Unknown subkey list at 0x{:x}", offset))
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> RegistryKey<'a> {
+    pub(crate) fn name(&self) -> String {
+        let flags = le16(self.cell, 2).unwrap_or(0);
+        let length = le16(self.cell, 72).unwrap_or(0) as usize;
+        let raw = self.cell.get(76..76 + length).unwrap_or(&[]);
+
+        // KEY_COMP_NAME: stored as Latin-1 rather than UTF-16
+        if flags & 0x0020 != 0 { latin1(raw) } else { utf16(raw) }
+    }
+
+    /// Last written time as a FILETIME
+    pub(crate) fn last_written(&self) -> u64 {
+        le64(self.cell, 4).unwrap_or(0)
+    }
+
+    pub(crate) fn subkeys(&self) -> Result<Vec<RegistryKey<'a>>, String> {
+        let count = le32(self.cell, 20).unwrap_or(0);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut offsets = Vec::new();
+        self.hive.subkey_offsets(le32(self.cell, 28).ok_or("Truncated key")? as usize, 0, &mut offsets)?;
+        offsets.into_iter().map(|offset| self.hive.key_at(offset)).collect()
+    }
+
+    pub(crate) fn subkey(&self, name: &str) -> Result<Option<RegistryKey<'a>>, String> {
+        Ok(self.subkeys()?.into_iter().find(|key| key.name().eq_ignore_ascii_case(name)))
+    }
+
+    /// Reads a value by name ("" for the default value), ignoring case
+    pub(crate) fn value(&self, name: &str) -> Result<Option<RegistryData>, String> {
+        let count = le32(self.cell, 36).unwrap_or(0) as usize;
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let list = self.hive.cell(le32(self.cell, 40).ok_or("Truncated key")? as usize)?;
+
+        for index in 0..count {
+            let offset = le32(list, index * 4).ok_or("Truncated value list")? as usize;
+            let cell = self.hive.cell(offset)?;
+            if cell.len() < 20 || &cell[..2] != b"vk" {
+                continue;
+            }
+
+            let name_length = le16(cell, 2).unwrap_or(0) as usize;
+            let raw_name = cell.get(20..20 + name_length).unwrap_or(&[]);
+            let value_name = if le16(cell, 16).unwrap_or(0) & 0x0001 != 0 { latin1(raw_name) } else { utf16(raw_name) };
+
+            if value_name.eq_ignore_ascii_case(name) {
+                return self.value_data(cell).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn value_data(&self, vk: &[u8]) -> Result<RegistryData, String> {
+        let raw_size = le32(vk, 4).ok_or("Truncated value")?;
+        let value_type = le32(vk, 12).ok_or("Truncated value")?;
+
+        // High bit set: up to four bytes stored in the offset field itself
+        let data = if raw_size & 0x8000_0000 != 0 {
+            let size = (raw_size & 0x7FFF_FFFF) as usize;
+            vk.get(8..8 + size.min(4)).ok_or("Truncated value")?.to_vec()
+        } else {
+            let size = raw_size as usize;
+            let offset = le32(vk, 8).ok_or("Truncated value")? as usize;
+
+            if size > BIG_DATA_THRESHOLD && self.hive.minor_version >= 4 {
+                self.big_data(offset, size)?
+            } else {
+                self.hive.cell(offset)?.get(..size).ok_or("Value data runs past its cell")?.to_vec()
+            }
+        };
+
+        Ok(match value_type {
+            1 | 2 => RegistryData::String(utf16(&data).trim_end_matches('\0').to_string()),
+            3 => RegistryData::Binary(data),
+            4 if data.len() >= 4 => RegistryData::Dword(le32(&data, 0).unwrap_or(0)),
+            11 if data.len() >= 8 => RegistryData::Qword(le64(&data, 0).unwrap_or(0)),
+            other => RegistryData::Other(other, data)
+        })
+    }
+
+    // Reassembles a db cell's segments
+    fn big_data(&self, offset: usize, size: usize) -> Result<Vec<u8>, String> {
+        let db = self.hive.cell(offset)?;
+        if db.get(..2) != Some(b"db") {
+            return Err("Expected a big data (db) cell".to_string());
+        }
+
+        let segments = le16(db, 2).ok_or("Truncated big data cell")? as usize;
+        let list = self.hive.cell(le32(db, 4).ok_or("Truncated big data cell")? as usize)?;
+        let mut data = Vec::with_capacity(size);
+
+        for index in 0..segments {
+            let segment = self.hive.cell(le32(list, index * 4).ok_or("Truncated segment list")? as usize)?;
+            let wanted = (size - data.len()).min(BIG_DATA_THRESHOLD).min(segment.len());
+            data.extend_from_slice(&segment[..wanted]);
+
+            if data.len() >= size {
+                break;
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+pub(crate) fn le16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+pub(crate) fn le32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+pub(crate) fn le64(bytes: &[u8], offset: usize) -> Option<u64> {
+    bytes.get(offset..offset + 8).map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+/// Decodes UTF-16LE, stopping at the first NUL
+pub(crate) fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).take_while(|unit| *unit != 0).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| *byte as char).collect()
+}
@@ -0,0 +1,174 @@
+/*
+    Random numbers for generators, seedable for reproducible runs
+
+    `MercyRng::seeded` gives the same sequence for the same seed (splitmix64), so a fuzz run,
+    a sampled payload set or a solver run can be repeated exactly for a test or to reproduce
+    an incident. `MercyRng::os` draws from the operating system's CSPRNG (getrandom or
+    /dev/urandom on Unix, BCryptGenRandom on Windows) and is what unseeded callers get.
+    Generators that report their seed draw that seed from the OS, so an unseeded run can
+    still be repeated afterwards.
+
+    Anything generating real secrets (passwords, keys, tokens) must use `MercyRng::secret`,
+    which refuses a seed unless the caller explicitly passes `insecure_seeded_secrets`: a
+    seeded secret is only as secret as the seed.
+*/
+
+use std::io;
+
+// Bytes fetched from the OS per refill
+const OS_BUFFER: usize = 256;
+
+/// Random source: a seeded deterministic generator or the OS CSPRNG
+#[derive(Debug, Clone)]
+pub struct MercyRng {
+    source: Source
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    // splitmix64 state and the seed it started from
+    Seeded { state: u64, seed: u64 },
+    // Buffered OS randomness and how much of it is used
+    Os { buffer: Vec<u8>, used: usize }
+}
+
+impl MercyRng {
+    /// Deterministic generator: identical seeds give identical sequences
+    pub fn seeded(seed: u64) -> MercyRng {
+        MercyRng { source: Source::Seeded { state: seed, seed } }
+    }
+
+    /// The operating system's CSPRNG
+    pub fn os() -> MercyRng {
+        MercyRng { source: Source::Os { buffer: Vec::new(), used: 0 } }
+    }
+
+    /// Seeded when a seed is given, the OS CSPRNG otherwise
+    pub fn from_seed(seed: Option<u64>) -> MercyRng {
+        seed.map(MercyRng::seeded).unwrap_or_else(MercyRng::os)
+    }
+
+    /// Generator for secrets (credentials, keys, tokens); a seed is refused unless `insecure_seeded_secrets` is set
+    pub fn secret(seed: Option<u64>, insecure_seeded_secrets: bool) -> Result<MercyRng, String> {
+        match seed {
+            Some(_) if !insecure_seeded_secrets => Err("A seed makes generated secrets predictable; pass the insecure_seeded_secrets flag to allow it (tests only)".to_string()),
+            seed => Ok(MercyRng::from_seed(seed))
+        }
+    }
+
+    /// Seed of a seeded generator
+    pub fn seed(&self) -> Option<u64> {
+        match self.source {
+            Source::Seeded { seed, .. } => Some(seed),
+            Source::Os { .. } => None
+        }
+    }
+
+    pub fn is_seeded(&self) -> bool {
+        self.seed().is_some()
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        match &mut self.source {
+            Source::Seeded { state, .. } => {
+                *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^ (z >> 31)
+            },
+            Source::Os { buffer, used } => {
+                if *used + 8 > buffer.len() {
+                    buffer.resize(OS_BUFFER, 0);
+                    // A machine without a working CSPRNG cannot generate anything safely
+                    os_fill(buffer).expect("Unable to read the operating system random number generator");
+                    *used = 0;
+                }
+
+                let mut word = [0u8; 8];
+                word.copy_from_slice(&buffer[*used..*used + 8]);
+                *used += 8;
+                u64::from_le_bytes(word)
+            }
+        }
+    }
+
+    /// A number below `bound` (which must not be 0)
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// Fills `bytes` with random data
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let word = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+}
+
+impl Default for MercyRng {
+    fn default() -> Self {
+        MercyRng::os()
+    }
+}
+
+/// A seed from the OS CSPRNG, for generators that report the seed they used
+pub(crate) fn random_seed() -> u64 {
+    MercyRng::os().next_u64()
+}
+
+#[cfg(target_os = "linux")]
+fn os_fill(bytes: &mut [u8]) -> io::Result<()> {
+    let mut filled = 0;
+    while filled < bytes.len() {
+        // getrandom writes at most the remaining length into the buffer
+        let read = unsafe { libc::getrandom(bytes[filled..].as_mut_ptr() as *mut libc::c_void, bytes.len() - filled, 0) };
+        if read < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            // Kernels before 3.17 lack the syscall
+            return if error.raw_os_error() == Some(libc::ENOSYS) { urandom_fill(&mut bytes[filled..]) } else { Err(error) };
+        }
+        filled += read as usize;
+    }
+
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn os_fill(bytes: &mut [u8]) -> io::Result<()> {
+    urandom_fill(bytes)
+}
+
+#[cfg(unix)]
+fn urandom_fill(bytes: &mut [u8]) -> io::Result<()> {
+    use std::io::Read;
+    std::fs::File::open("/dev/urandom")?.read_exact(bytes)
+}
+
+#[cfg(windows)]
+fn os_fill(bytes: &mut [u8]) -> io::Result<()> {
+    #[link(name = "bcrypt")]
+    extern "system" {
+        fn BCryptGenRandom(algorithm: *mut std::ffi::c_void, buffer: *mut u8, length: u32, flags: u32) -> i32;
+    }
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: u32 = 2;
+
+    for chunk in bytes.chunks_mut(u32::MAX as usize) {
+        // The system-preferred RNG needs no algorithm handle and writes exactly `length` bytes
+        let status = unsafe { BCryptGenRandom(std::ptr::null_mut(), chunk.as_mut_ptr(), chunk.len() as u32, BCRYPT_USE_SYSTEM_PREFERRED_RNG) };
+        if status != 0 {
+            return Err(io::Error::other(format!("BCryptGenRandom failed with status {:#x}", status)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn os_fill(_bytes: &mut [u8]) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no operating system random number generator on this platform"))
+}
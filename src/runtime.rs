@@ -0,0 +1,55 @@
+/*
+    The Tokio runtime behind the synchronous API
+
+    The HTTP calls are async; the string dispatchers are not. Starting a runtime per call
+    (what `#[tokio::main]` does) is slow and panics when the caller is itself running inside
+    a runtime, so the synchronous functions share one runtime, started on first use, and
+    wait on it from the calling thread. A caller already inside a runtime cannot block it
+    from its own thread, so its future is driven from a short-lived helper thread instead;
+    that still blocks the calling worker, which is why async callers should prefer the
+    `_async` functions. Either way the future sees the `MercyConfig` of the calling thread.
+*/
+
+use std::{
+    future::Future,
+    panic,
+    sync::OnceLock,
+    thread
+};
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::config;
+
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+fn runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| {
+        Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("mercy-runtime")
+            .enable_all()
+            .build()
+            // Without a runtime no network call can work at all
+            .expect("Unable to start the Tokio runtime")
+    })
+}
+
+// Runs a future to completion from synchronous code, inside or outside a runtime
+pub(crate) fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send
+{
+    let future = config::propagate(future);
+    if Handle::try_current().is_err() {
+        return runtime().block_on(future);
+    }
+
+    thread::scope(|scope| {
+        match scope.spawn(|| runtime().block_on(future)).join() {
+            Ok(output) => output,
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    })
+}
@@ -0,0 +1,262 @@
+/*
+    Rendering of untrusted domains, URLs and strings for terminals and reports
+
+    Attacker-controlled values can reorder the text around them (bidi controls), hide
+    characters (zero-width and tag characters), drive the terminal (control characters) or
+    pass for another name (homoglyphs). The safe form escapes every character that
+    `mercy_unicode_inspect` flags, and tabs and line breaks as well, as "\u{202E}" or "\t"
+    style escapes, so the value stays on one line and shows everything it contains. A value
+    with non-ASCII characters is shown twice, rendered and in an ASCII form side by side:
+
+        pаypal.com (xn--pypal-4ve.com)
+
+    Domains and URL hosts get their IDNA labels in punycode (RFC 3492; labels are lowercased
+    but not otherwise mapped, so the ASCII form shows the name as given), except that a label
+    holding a flagged character is escaped, as punycode would hide it. The rest of a URL is
+    percent-encoded, and other text uses "\u{...}" escapes. Values longer than the limit
+    are cut in the middle with an ellipsis, and the SHA-256 of the whole input is added so
+    the original can still be matched. The HTML form is the same text with &, <, >, " and '
+    escaped, safe inside an element or a quoted attribute.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    punycode,
+    trailing_options,
+    unicode
+};
+
+// Default longest rendering of each form, in characters
+const DEFAULT_MAX: usize = 120;
+
+/// What kind of value was rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DisplayKind {
+    Domain,
+    Url,
+    Text
+}
+
+/// A value made safe to print or embed in HTML
+#[derive(Debug, Clone, Serialize)]
+pub struct SafeDisplay {
+    pub kind: DisplayKind,
+    /// One-line plain text: the rendered form, then the ASCII form in parentheses when they differ
+    pub text: String,
+    /// `text` with HTML special characters escaped
+    pub html: String,
+    /// The value with dangerous characters escaped and non-ASCII letters kept
+    pub rendered: String,
+    /// Punycode, percent-encoded or "\u{...}" form, when the value has non-ASCII characters
+    pub ascii: Option<String>,
+    /// Characters replaced by escapes in the rendered form
+    pub escaped: usize,
+    pub truncated: bool,
+    /// SHA-256 of the whole input, given when it was truncated
+    pub sha256: Option<String>
+}
+
+impl fmt::Display for SafeDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Safe plain-text and HTML renderings of `input`, each form cut to `max_chars` characters
+pub fn mercy_safe_display(input: &str, max_chars: usize) -> SafeDisplay {
+    let kind = classify(input);
+    let (rendered, escaped) = escape_rendered(input);
+    let ascii = if input.is_ascii() {
+        None
+    } else {
+        Some(match kind {
+            DisplayKind::Domain => domain_ascii(input),
+            DisplayKind::Url => url_ascii(input),
+            DisplayKind::Text => escape_text(input)
+        })
+    };
+
+    let (rendered, cut_rendered) = truncate_middle(&rendered, max_chars);
+    let (ascii, cut_ascii) = match ascii {
+        Some(ascii) => {
+            let (ascii, cut) = truncate_middle(&ascii, max_chars);
+            (Some(ascii), cut)
+        },
+        None => (None, false)
+    };
+
+    let truncated = cut_rendered || cut_ascii;
+    let sha256 = truncated.then(|| format!("{:x}", Sha256::digest(input.as_bytes())));
+
+    let mut text = match &ascii {
+        Some(ascii) if *ascii != rendered => format!("{} ({})", rendered, ascii),
+        _ => rendered.clone()
+    };
+    if let Some(digest) = &sha256 {
+        text.push_str(&format!(" [sha256:{}]", digest));
+    }
+
+    SafeDisplay { kind, html: html_escape(&text), text, rendered, ascii, escaped, truncated, sha256 }
+}
+
+// A URL has a scheme; a domain is one dotted word
+fn classify(input: &str) -> DisplayKind {
+    if input.split_once("://").is_some_and(|(scheme, _)| !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))) {
+        DisplayKind::Url
+    } else if !input.is_empty() && !input.chars().any(char::is_whitespace) && input.chars().any(is_label_separator) {
+        DisplayKind::Domain
+    } else {
+        DisplayKind::Text
+    }
+}
+
+// "." and the ideographic and fullwidth full stops IDNA treats as dots
+fn is_label_separator(c: char) -> bool {
+    matches!(c, '.' | '\u{3002}' | '\u{FF0E}' | '\u{FF61}')
+}
+
+// The input with flagged characters, tabs and line breaks escaped, and how many there were
+fn escape_rendered(input: &str) -> (String, usize) {
+    let chars: Vec<char> = input.chars().collect();
+    let mut rendered = String::with_capacity(input.len());
+    let mut escaped = 0;
+
+    for (offset, c) in chars.iter().enumerate() {
+        let escape = match c {
+            '\t' => Some("\\t".to_string()),
+            '\n' => Some("\\n".to_string()),
+            '\r' => Some("\\r".to_string()),
+            _ if unicode::flag(&chars, offset).is_some() => Some(format!("\\u{{{:04X}}}", *c as u32)),
+            _ => None
+        };
+
+        match escape {
+            Some(escape) => {
+                rendered.push_str(&escape);
+                escaped += 1;
+            },
+            None => rendered.push(*c)
+        }
+    }
+
+    (rendered, escaped)
+}
+
+// Every character outside printable ASCII as an escape
+fn escape_text(input: &str) -> String {
+    input.chars().map(|c| match c {
+        '\t' => "\\t".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        ' '..='~' => c.to_string(),
+        _ => format!("\\u{{{:04X}}}", c as u32)
+    }).collect()
+}
+
+// Each label of a domain in punycode when it is not ASCII
+fn domain_ascii(domain: &str) -> String {
+    domain.split(is_label_separator).map(label_ascii).collect::<Vec<_>>().join(".")
+}
+
+fn label_ascii(label: &str) -> String {
+    if label.is_ascii() {
+        return escape_text(&label.to_ascii_lowercase());
+    }
+
+    // Punycode would fold a bidi, zero-width or control character into an innocent-looking
+    // "xn--" label, so a label holding one is escaped character by character instead
+    let lowered: Vec<char> = label.chars().flat_map(char::to_lowercase).collect();
+    if (0..lowered.len()).any(|offset| lowered[offset].is_control() || unicode::flag(&lowered, offset).is_some()) {
+        return escape_text(&lowered.iter().collect::<String>());
+    }
+
+    match punycode::encode_label(&lowered) {
+        Some(encoded) => format!("xn--{}", encoded),
+        None => escape_text(label)
+    }
+}
+
+// The host in punycode and everything else percent-encoded
+fn url_ascii(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(authority_end);
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((userinfo, host_port)) => (Some(userinfo), host_port),
+        None => (None, authority)
+    };
+
+    // A port is the digits after the last colon, unless the host is a bracketed IPv6 address
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !host_port.ends_with(']') && port.chars().all(|c| c.is_ascii_digit()) => (host, Some(port)),
+        _ => (host_port, None)
+    };
+
+    let mut ascii = format!("{}://", scheme);
+    if let Some(userinfo) = userinfo {
+        ascii.push_str(&percent_encode(userinfo));
+        ascii.push('@');
+    }
+    ascii.push_str(&domain_ascii(host));
+    if let Some(port) = port {
+        ascii.push(':');
+        ascii.push_str(port);
+    }
+    ascii.push_str(&percent_encode(tail));
+    ascii
+}
+
+// Non-ASCII and control bytes as %XX
+fn percent_encode(text: &str) -> String {
+    text.bytes().map(|byte| match byte {
+        0x21..=0x7E => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte)
+    }).collect()
+}
+
+// Keeps the start and end of a long value around an ellipsis
+fn truncate_middle(text: &str, max_chars: usize) -> (String, bool) {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return (text.to_string(), false);
+    }
+
+    let keep = max_chars.saturating_sub(1);
+    let head = keep.div_ceil(2);
+    let tail = keep - head;
+    let start: String = text.chars().take(head).collect();
+    let end: String = text.chars().skip(count - tail).collect();
+    (format!("{}\u{2026}{}", start, end), true)
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+// Parses "value [max=120]"
+pub(crate) fn safe_display_from_args(input: &str) -> Result<SafeDisplay, String> {
+    let (value, pairs) = trailing_options(input, &["max"]);
+    let mut max_chars = DEFAULT_MAX;
+
+    for (_, value) in pairs {
+        max_chars = value.parse().ok().filter(|max| *max >= 8).ok_or_else(|| format!("Invalid max '{}' (expected a length of at least 8)", value))?;
+    }
+
+    Ok(mercy_safe_display(&value, max_chars))
+}
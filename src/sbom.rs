@@ -0,0 +1,646 @@
+/*
+    SBOM parsing (CycloneDX JSON and SPDX JSON) and OSV vulnerability cross-reference
+
+    Components from either format are normalized to name, version, purl and licenses. Checks
+    run offline against a directory of OSV JSON records (such as an extracted osv.dev
+    ecosystem dump) or online against the OSV API, in batches paced and retried by the
+    shared net layer (see net.rs). Version ranges are evaluated with the shared version
+    comparison.
+*/
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    path::Path,
+    time::Duration
+};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    config,
+    finding::{Finding, Severity},
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net,
+    paths::path_to_string,
+    runtime,
+    trailing_options,
+    version::compare_versions,
+    walk::{CappedRead, EntryKind, WalkEntry, WalkOptions, read_capped, walk_dir}
+};
+
+// The OSV API accepts at most this many queries per batch
+const OSV_BATCH: usize = 1000;
+
+
+// SBOMs and advisory files are read whole
+const MAX_DOCUMENT: u64 = 256 * 1024 * 1024;
+
+// Directory levels searched for advisory files
+const MAX_WALK_DEPTH: usize = 8;
+
+/// A component listed in an SBOM
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SbomComponent {
+    pub name: String,
+    pub version: Option<String>,
+    pub purl: Option<String>,
+    pub licenses: Vec<String>
+}
+
+/// Components of a CycloneDX or SPDX document
+#[derive(Debug, Clone, Serialize)]
+pub struct SbomReport {
+    pub path: String,
+    /// "CycloneDX <specVersion>" or the SPDX version string
+    pub format: String,
+    /// Name of the described software or document
+    pub name: Option<String>,
+    pub components: Vec<SbomComponent>
+}
+
+impl fmt::Display for SbomReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+/// A component affected by an advisory
+#[derive(Debug, Clone, Serialize)]
+pub struct SbomVulnerability {
+    pub component: String,
+    pub version: String,
+    pub purl: Option<String>,
+    pub advisory: String,
+    pub aliases: Vec<String>,
+    pub summary: Option<String>,
+    /// Severity label from the advisory database (GHSA style: LOW, MODERATE, HIGH, CRITICAL)
+    pub severity: Option<String>,
+    /// Versions that fix the issue, lowest first
+    pub fixed_versions: Vec<String>
+}
+
+/// Results of checking components against OSV advisories
+#[derive(Debug, Clone, Serialize)]
+pub struct SbomCheckReport {
+    /// Advisory directory or API URL
+    pub source: String,
+    pub components_checked: usize,
+    /// Components without a version or a purl ecosystem OSV understands
+    pub components_skipped: Vec<String>,
+    pub vulnerabilities: Vec<SbomVulnerability>,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for SbomCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string_pretty(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl SbomCheckReport {
+    /// One finding per affected component and advisory
+    pub fn findings(&self) -> Vec<Finding> {
+        self.vulnerabilities.iter()
+            .map(|vulnerability| {
+                let severity = match vulnerability.severity.as_deref().map(str::to_ascii_uppercase).as_deref() {
+                    Some("CRITICAL") => Severity::Critical,
+                    Some("HIGH") => Severity::High,
+                    Some("LOW") => Severity::Low,
+                    _ => Severity::Medium
+                };
+                let fix = if vulnerability.fixed_versions.is_empty() {
+                    "No fixed version is listed; remove or replace the component".to_string()
+                } else {
+                    format!("Upgrade {} to {} or later", vulnerability.component, vulnerability.fixed_versions[0])
+                };
+
+                Finding::new(
+                    "sbom.vulnerable-component",
+                    &format!("{} {} is affected by {}", vulnerability.component, vulnerability.version, vulnerability.advisory),
+                    severity,
+                    vulnerability.purl.as_deref().unwrap_or(&vulnerability.component),
+                    &format!("{}{}", vulnerability.summary.clone().unwrap_or_default(), if vulnerability.aliases.is_empty() { String::new() } else { format!(" ({})", vulnerability.aliases.join(", ")) }),
+                    &fix
+                )
+            })
+            .collect()
+    }
+}
+
+/// Reads the components of a CycloneDX JSON or SPDX JSON SBOM
+pub fn mercy_sbom<P: AsRef<Path>>(path: P) -> Result<SbomReport, String> {
+    let path = path.as_ref();
+    let document = read_json(path)?;
+    let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+
+    if document.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX") {
+        let mut components = Vec::new();
+        if let Some(list) = document.get("components").and_then(Value::as_array) {
+            cyclonedx_components(list, &mut components, 0);
+        }
+
+        return Ok(SbomReport {
+            path: path_to_string(path),
+            format: format!("CycloneDX {}", text(document.get("specVersion")).unwrap_or_default()).trim().to_string(),
+            name: text(document.pointer("/metadata/component/name")),
+            components
+        });
+    }
+
+    if let Some(version) = document.get("spdxVersion").and_then(Value::as_str) {
+        let components = document.get("packages").and_then(Value::as_array).unwrap_or(&Vec::new())
+            .iter()
+            .filter_map(|package| {
+                let purl = package.get("externalRefs").and_then(Value::as_array)
+                    .and_then(|references| references.iter().find(|reference| reference.get("referenceType").and_then(Value::as_str) == Some("purl")))
+                    .and_then(|reference| text(reference.get("referenceLocator")));
+
+                // Concluded licenses win over declared ones; NOASSERTION and NONE say nothing
+                let licenses = ["licenseConcluded", "licenseDeclared"].iter()
+                    .filter_map(|key| text(package.get(*key)))
+                    .find(|license| license != "NOASSERTION" && license != "NONE")
+                    .into_iter()
+                    .collect();
+
+                Some(SbomComponent {
+                    name: text(package.get("name"))?,
+                    version: text(package.get("versionInfo")),
+                    purl,
+                    licenses
+                })
+            })
+            .collect();
+
+        return Ok(SbomReport {
+            path: path_to_string(path),
+            format: version.to_string(),
+            name: text(document.get("name")),
+            components
+        });
+    }
+
+    Err("Not a CycloneDX or SPDX JSON document".to_string())
+}
+
+// CycloneDX components nest (an application's components list its own dependencies)
+fn cyclonedx_components(list: &[Value], output: &mut Vec<SbomComponent>, depth: usize) {
+    for component in list {
+        let text = |key: &str| component.get(key).and_then(Value::as_str).map(str::to_string);
+
+        if let Some(name) = text("name") {
+            let name = match text("group").filter(|group| !group.is_empty()) {
+                Some(group) => format!("{}/{}", group, name),
+                None => name
+            };
+
+            let licenses = component.get("licenses").and_then(Value::as_array).unwrap_or(&Vec::new())
+                .iter()
+                .filter_map(|entry| {
+                    entry.get("expression")
+                        .or_else(|| entry.pointer("/license/id"))
+                        .or_else(|| entry.pointer("/license/name"))
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                })
+                .collect();
+
+            output.push(SbomComponent { name, version: text("version"), purl: text("purl"), licenses });
+        }
+
+        if depth < 16 {
+            if let Some(children) = component.get("components").and_then(Value::as_array) {
+                cyclonedx_components(children, output, depth + 1);
+            }
+        }
+    }
+}
+
+/// Checks components against OSV advisories from a local directory of OSV JSON records or the
+/// OSV API (an http(s) base URL such as "https://api.osv.dev")
+pub fn mercy_sbom_check(components: &[SbomComponent], advisories: &str) -> Result<SbomCheckReport, String> {
+    sbom_check(components, advisories, &HttpIdentity::default())
+}
+
+// The check, with this call's HTTP identity layered over the default for OSV API requests
+fn sbom_check(components: &[SbomComponent], advisories: &str, identity: &HttpIdentity) -> Result<SbomCheckReport, String> {
+    let mut queries = Vec::new();
+    let mut skipped = Vec::new();
+
+    for component in components {
+        match Query::from_component(component) {
+            Some(query) => queries.push(query),
+            None => skipped.push(format!("{}{}", component.name, component.version.as_ref().map(|version| format!("@{}", version)).unwrap_or_default()))
+        }
+    }
+
+    // The same package can appear more than once (nested components, several lock files)
+    let mut seen = HashSet::new();
+    queries.retain(|query| seen.insert((query.ecosystem.clone(), query.name.clone(), query.version.clone())));
+
+    let mut notes = Vec::new();
+    let vulnerabilities = if advisories.starts_with("http://") || advisories.starts_with("https://") {
+        runtime::block_on(osv_api(&queries, advisories.trim_end_matches('/'), identity, &mut notes))?
+    } else {
+        osv_directory(&queries, Path::new(advisories), &mut notes)?
+    };
+
+    Ok(SbomCheckReport {
+        source: advisories.to_string(),
+        components_checked: queries.len(),
+        components_skipped: skipped,
+        vulnerabilities,
+        notes
+    })
+}
+
+/// Check driven by "sbom.json osv=/path/to/osv" (the OSV API when osv is not given), with HTTP identity options
+pub(crate) fn sbom_check_from_args(input: &str) -> Result<SbomCheckReport, String> {
+    let keys: Vec<&str> = ["osv"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (path, pairs) = trailing_options(input, &keys);
+    let mut advisories = "https://api.osv.dev";
+    let mut identity = HttpIdentity::default();
+
+    for (key, value) in pairs {
+        match key {
+            "osv" => advisories = value,
+            _ => identity_option(&mut identity, key, value)?
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No SBOM path specified".to_string());
+    }
+
+    sbom_check(&mercy_sbom(&path)?.components, advisories, &identity)
+}
+
+// A component in the terms OSV uses
+#[derive(Debug, Clone)]
+struct Query {
+    ecosystem: String,
+    name: String,
+    version: String,
+    purl: Option<String>,
+    component: String
+}
+
+impl Query {
+    fn from_component(component: &SbomComponent) -> Option<Query> {
+        let purl = parse_purl(component.purl.as_deref()?)?;
+        let version = component.version.clone().or(purl.version)?;
+
+        Some(Query {
+            ecosystem: purl.ecosystem,
+            name: purl.name,
+            version,
+            purl: component.purl.clone(),
+            component: component.name.clone()
+        })
+    }
+
+    // OSV matches names as written, except PyPI, which normalizes them
+    fn matches_package(&self, ecosystem: &str, name: &str) -> bool {
+        let ecosystem = ecosystem.split(':').next().unwrap_or(ecosystem);
+        if ecosystem != self.ecosystem {
+            return false;
+        }
+
+        if self.ecosystem == "PyPI" { pypi_name(name) == self.name } else { name == self.name }
+    }
+}
+
+struct Purl {
+    ecosystem: String,
+    name: String,
+    version: Option<String>
+}
+
+// pkg:type/namespace/name@version?qualifiers#subpath, mapped to an OSV ecosystem and name
+fn parse_purl(purl: &str) -> Option<Purl> {
+    let rest = purl.strip_prefix("pkg:")?;
+    let rest = rest.split('#').next()?;
+    let rest = rest.split('?').next()?;
+
+    let (path, version) = match rest.rsplit_once('@') {
+        Some((path, version)) if !path.ends_with('/') => (path, Some(percent_decode(version))),
+        _ => (rest, None)
+    };
+
+    let mut parts: Vec<String> = path.split('/').filter(|part| !part.is_empty()).map(percent_decode).collect();
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let kind = parts.remove(0).to_ascii_lowercase();
+    let name = parts.pop()?;
+    let namespace = parts.join("/");
+
+    let (ecosystem, name) = match kind.as_str() {
+        "npm" => ("npm", if namespace.is_empty() { name } else { format!("{}/{}", namespace, name) }),
+        "pypi" => ("PyPI", pypi_name(&name)),
+        "maven" => ("Maven", format!("{}:{}", namespace, name)),
+        "golang" => ("Go", if namespace.is_empty() { name } else { format!("{}/{}", namespace, name) }),
+        "cargo" => ("crates.io", name),
+        "gem" => ("RubyGems", name),
+        "nuget" => ("NuGet", name),
+        "composer" => ("Packagist", format!("{}/{}", namespace, name)),
+        "hex" => ("Hex", name),
+        "pub" => ("Pub", name),
+        "github" => ("GitHub Actions", format!("{}/{}", namespace, name)),
+        "deb" => ("Debian", name),
+        "apk" => ("Alpine", name),
+        _ => return None
+    };
+
+    Some(Purl { ecosystem: ecosystem.to_string(), name, version })
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let decoded = (bytes[index] == b'%')
+            .then(|| text.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match decoded {
+            Some(byte) => {
+                output.push(byte);
+                index += 3;
+            }
+            None => {
+                output.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&output).to_string()
+}
+
+// PEP 503 normalization: lowercase with runs of -, _ and . as a single -
+fn pypi_name(name: &str) -> String {
+    let mut output = String::new();
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !output.ends_with('-') {
+                output.push('-');
+            }
+        } else {
+            output.push(c.to_ascii_lowercase());
+        }
+    }
+    output
+}
+
+// Fixed versions above the queried one when an OSV record affects it, None when it does not
+fn affected(record: &Value, query: &Query) -> Option<Vec<String>> {
+    let mut hit = false;
+    let mut fixed = BTreeSet::new();
+
+    for entry in record.get("affected").and_then(Value::as_array)? {
+        let ecosystem = entry.pointer("/package/ecosystem").and_then(Value::as_str).unwrap_or("");
+        let name = entry.pointer("/package/name").and_then(Value::as_str).unwrap_or("");
+        if !query.matches_package(ecosystem, name) {
+            continue;
+        }
+
+        let listed = entry.get("versions").and_then(Value::as_array)
+            .map(|versions| versions.iter().any(|version| version.as_str() == Some(query.version.as_str())))
+            .unwrap_or(false);
+
+        let mut in_range = false;
+        for range in entry.get("ranges").and_then(Value::as_array).unwrap_or(&Vec::new()) {
+            // Git ranges name commits, which a package version cannot be placed against
+            if range.get("type").and_then(Value::as_str) == Some("GIT") {
+                continue;
+            }
+
+            let events = range.get("events").and_then(Value::as_array).cloned().unwrap_or_default();
+            if range_affects(&events, &query.version) {
+                in_range = true;
+            }
+
+            for event in &events {
+                if let Some(version) = event.get("fixed").and_then(Value::as_str) {
+                    if compare_versions(version, &query.version) == Ordering::Greater {
+                        fixed.insert(version.to_string());
+                    }
+                }
+            }
+        }
+
+        hit |= listed || in_range;
+    }
+
+    if !hit {
+        return None;
+    }
+
+    let mut fixed: Vec<String> = fixed.into_iter().collect();
+    fixed.sort_by(|a, b| compare_versions(a, b));
+    Some(fixed)
+}
+
+// Walks the events in version order: introduced opens a range, fixed and last_affected close it
+fn range_affects(events: &[Value], version: &str) -> bool {
+    let mut ordered: Vec<(&str, &str)> = events.iter()
+        .filter_map(|event| {
+            let object = event.as_object()?;
+            let (kind, value) = object.iter().next()?;
+            Some((kind.as_str(), value.as_str()?))
+        })
+        .collect();
+
+    // "0" is the start of time; at equal versions closing events sort first so a range reopened there applies
+    ordered.sort_by(|a, b| match (a.1 == "0", b.1 == "0") {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => compare_versions(a.1, b.1).then_with(|| (a.0 == "introduced").cmp(&(b.0 == "introduced")))
+    });
+
+    let mut affected = false;
+    for (kind, value) in ordered {
+        let at_or_below = value == "0" || compare_versions(value, version) != Ordering::Greater;
+        match kind {
+            "introduced" if at_or_below => affected = true,
+            "fixed" if at_or_below => affected = false,
+            "last_affected" if value != "0" && compare_versions(value, version) == Ordering::Less => affected = false,
+            _ => {}
+        }
+    }
+
+    affected
+}
+
+fn vulnerability(record: &Value, query: &Query, fixed_versions: Vec<String>) -> SbomVulnerability {
+    let text = |pointer: &str| record.pointer(pointer).and_then(Value::as_str).map(str::to_string);
+
+    SbomVulnerability {
+        component: query.component.clone(),
+        version: query.version.clone(),
+        purl: query.purl.clone(),
+        advisory: text("/id").unwrap_or_default(),
+        aliases: record.get("aliases").and_then(Value::as_array)
+            .map(|aliases| aliases.iter().filter_map(|alias| alias.as_str().map(str::to_string)).collect())
+            .unwrap_or_default(),
+        summary: text("/summary").or_else(|| text("/details").map(|details| details.lines().next().unwrap_or("").to_string())),
+        severity: text("/database_specific/severity"),
+        fixed_versions
+    }
+}
+
+// Advisory records from a directory tree (or a single file) of OSV JSON
+fn osv_directory(queries: &[Query], root: &Path, notes: &mut Vec<String>) -> Result<Vec<SbomVulnerability>, String> {
+    if !root.exists() {
+        return Err(format!("Advisory path {} does not exist", root.display()));
+    }
+
+    let mut files = Vec::new();
+    collect_json(root, &mut files, notes)?;
+
+    let mut results = Vec::new();
+    for file in files {
+        let document = match file.read_capped(MAX_DOCUMENT).and_then(|read| json_document(&file.path, read)) {
+            Ok(document) => document,
+            Err(e) => {
+                notes.push(e);
+                continue;
+            }
+        };
+
+        let records = match document {
+            Value::Array(records) => records,
+            record => vec![record]
+        };
+
+        for record in &records {
+            for query in queries {
+                if let Some(fixed) = affected(record, query) {
+                    results.push(vulnerability(record, query, fixed));
+                }
+            }
+        }
+    }
+
+    results.sort_by(|a, b| (&a.component, &a.version, &a.advisory).cmp(&(&b.component, &b.version, &b.advisory)));
+    results.dedup_by(|a, b| a.component == b.component && a.version == b.version && a.advisory == b.advisory);
+    Ok(results)
+}
+
+// JSON files under `root` (or `root` itself), without following links or opening special files
+fn collect_json(root: &Path, files: &mut Vec<WalkEntry>, notes: &mut Vec<String>) -> Result<(), String> {
+    let options = WalkOptions { follow_symlinks: false, max_depth: Some(MAX_WALK_DEPTH) };
+
+    for walked in walk_dir(root, &options)? {
+        match walked {
+            Ok(walked) => {
+                let json = walked.path.extension().map(|extension| extension.eq_ignore_ascii_case("json")).unwrap_or(false);
+                // A linked root names the file the caller meant
+                let file = walked.kind == EntryKind::File || (walked.depth == 0 && root.is_file());
+                if json && file {
+                    files.push(walked);
+                }
+            },
+            Err(e) => notes.push(format!("Unable to list {}", e))
+        }
+    }
+
+    Ok(())
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    json_document(path, read_capped(path, MAX_DOCUMENT)?)
+}
+
+fn json_document(path: &Path, read: CappedRead) -> Result<Value, String> {
+    if read.truncated {
+        return Err(format!("{} is larger than {} bytes", path.display(), MAX_DOCUMENT));
+    }
+
+    serde_json::from_slice(&read.data).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))
+}
+
+// Batched OSV API queries, then the full record of each advisory for its fixed versions
+async fn osv_api(queries: &[Query], base: &str, identity: &HttpIdentity, notes: &mut Vec<String>) -> Result<Vec<SbomVulnerability>, String> {
+    let client = http_client(identity)?;
+    let mut hits: Vec<(usize, String)> = Vec::new();
+
+    // Each pending query is (index, page token); paged results come back as new work
+    let mut pending: Vec<(usize, Option<String>)> = (0..queries.len()).map(|index| (index, None)).collect();
+    while !pending.is_empty() {
+        let batch: Vec<(usize, Option<String>)> = pending.drain(..pending.len().min(OSV_BATCH)).collect();
+        let body = json!({
+            "queries": batch.iter().map(|(index, token)| {
+                let query = &queries[*index];
+                let mut request = json!({ "package": { "name": query.name, "ecosystem": query.ecosystem }, "version": query.version });
+                if let Some(token) = token {
+                    request["page_token"] = json!(token);
+                }
+                request
+            }).collect::<Vec<_>>()
+        });
+
+        let response = osv_request(&client, reqwest::Method::POST, &format!("{}/v1/querybatch", base), Some(&body)).await?;
+        let results = response.get("results").and_then(Value::as_array).ok_or("OSV querybatch response has no results")?;
+
+        for ((index, _), result) in batch.iter().zip(results) {
+            for advisory in result.get("vulns").and_then(Value::as_array).unwrap_or(&Vec::new()) {
+                if let Some(id) = advisory.get("id").and_then(Value::as_str) {
+                    hits.push((*index, id.to_string()));
+                }
+            }
+            if let Some(token) = result.get("next_page_token").and_then(Value::as_str) {
+                pending.push((*index, Some(token.to_string())));
+            }
+        }
+    }
+
+    let mut records: HashMap<String, Value> = HashMap::new();
+    let mut results = Vec::new();
+
+    for (index, id) in hits {
+        if !records.contains_key(&id) {
+            match osv_request(&client, reqwest::Method::GET, &format!("{}/v1/vulns/{}", base, id), None).await {
+                Ok(record) => {
+                    records.insert(id.clone(), record);
+                }
+                Err(e) => {
+                    notes.push(format!("{}: {}", id, e));
+                    continue;
+                }
+            }
+        }
+
+        let record = &records[&id];
+        let query = &queries[index];
+        // The API already decided the version is affected; the record supplies fixed versions
+        let fixed = affected(record, query).unwrap_or_default();
+        results.push(vulnerability(record, query, fixed));
+    }
+
+    results.sort_by(|a, b| (&a.component, &a.version, &a.advisory).cmp(&(&b.component, &b.version, &b.advisory)));
+    results.dedup_by(|a, b| a.component == b.component && a.version == b.version && a.advisory == b.advisory);
+    Ok(results)
+}
+
+// One OSV API request, paced and retried through the shared net layer
+async fn osv_request(client: &reqwest::Client, method: reqwest::Method, url: &str, body: Option<&Value>) -> Result<Value, String> {
+    let mut request = client.request(method, url).timeout(config::read_timeout(Duration::from_secs(30)));
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+
+    let response = net::send(request).await.map_err(|e| format!("OSV request to {} failed: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("OSV request to {} failed: HTTP {}", url, response.status()));
+    }
+    response.json::<Value>().await.map_err(|e| format!("Invalid OSV response from {}: {}", url, e))
+}
@@ -0,0 +1,107 @@
+/*
+    Secret scan of a configuration file or a pasted block of text
+
+    Every line goes through the shared secret patterns (cloud keys, tokens, private key
+    headers, JWTs and credential assignments; see secrets.rs), and each match is reported with
+    its rule, line and column and a masked excerpt that keeps only the first and last four
+    characters, so the report can be pasted into a ticket without leaking what it found.
+
+    Files are read a line at a time through the text layer, so a UTF-16 config (as Windows tools
+    save them) is scanned as text, invalid UTF-8 is replaced, and a binary or huge file costs no
+    more memory than its longest line.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    secrets::{SecretMatch, find_secrets, mask},
+    text::TextLines
+};
+
+/// A secret on one line
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretFinding {
+    /// "aws-access-key-id", "github-token", "private-key", "jwt", "secret-assignment" and so on
+    pub rule: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    /// The secret with all but its first and last four characters masked (a PEM header as it is)
+    pub excerpt: String
+}
+
+/// Secrets found in a file or text
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretScan {
+    /// The path scanned, or "text"
+    pub source: String,
+    pub lines: usize,
+    pub findings: Vec<SecretFinding>
+}
+
+impl fmt::Display for SecretScan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}:{}:{} {} {}", self.source, finding.line, finding.column, finding.rule, finding.excerpt)?;
+        }
+        match self.findings.len() {
+            0 => write!(f, "No secrets found in {} line(s) of {}", self.lines, self.source),
+            count => write!(f, "{} secret(s) in {} line(s) of {}", count, self.lines, self.source)
+        }
+    }
+}
+
+/// Secrets in a block of text
+pub fn mercy_secret_scan_text(text: &str) -> SecretScan {
+    let mut scan = SecretScan { source: "text".to_string(), lines: 0, findings: Vec::new() };
+    for line in text.lines() {
+        scan_line(&mut scan, line);
+    }
+    scan
+}
+
+/// Secrets in a file, read a line at a time
+pub fn mercy_secret_scan_file<P: AsRef<Path>>(path: P) -> Result<SecretScan, String> {
+    let path = path.as_ref();
+    let lines = TextLines::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut scan = SecretScan { source: path.display().to_string(), lines: 0, findings: Vec::new() };
+
+    for line in lines {
+        let line = line.map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        scan_line(&mut scan, &line);
+    }
+    Ok(scan)
+}
+
+fn scan_line(scan: &mut SecretScan, line: &str) {
+    scan.lines += 1;
+    for found in find_secrets(line) {
+        scan.findings.push(SecretFinding {
+            rule: found.rule.to_string(),
+            line: scan.lines,
+            column: line[..found.start].chars().count() + 1,
+            excerpt: masked(line, &found)
+        });
+    }
+}
+
+// PEM headers name the key type and hold nothing secret; anything else is masked
+fn masked(line: &str, found: &SecretMatch) -> String {
+    let text = &line[found.start..found.end];
+    if found.rule == "private-key" { text.trim().to_string() } else { mask(text) }
+}
+
+// A single-line input naming an existing file is scanned as that file, anything else as text
+pub(crate) fn secret_scan_from_args(input: &str) -> Result<SecretScan, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("No file or text to scan".to_string());
+    }
+    if !trimmed.contains('\n') && Path::new(trimmed).is_file() {
+        return mercy_secret_scan_file(trimmed);
+    }
+    Ok(mercy_secret_scan_text(input))
+}
@@ -0,0 +1,356 @@
+/*
+    AppCompatCache (Shimcache) extraction from an exported SYSTEM hive
+
+    The cache value changed layout with almost every Windows release, so each known format has its
+    own parser producing the shared `ShimcacheEntry`. Entry order is preserved: position 0 is the
+    most recently inserted entry.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    paths::path_to_string,
+    registry::{RegistryData, RegistryHive, le16, le32, le64, utf16},
+    timefmt::filetime_to_rfc3339
+};
+
+// Upper bound on entries parsed from one value, whatever the header claims
+const MAX_ENTRIES: usize = 100_000;
+
+/// One cached executable
+#[derive(Debug, Clone, Serialize)]
+pub struct ShimcacheEntry {
+    /// 0 is the most recent insertion
+    pub position: usize,
+    pub path: String,
+    /// File last-modified time recorded by the cache (not an execution time)
+    pub last_modified: Option<String>,
+    /// Insertion flag indicating execution (Windows Vista/7 only)
+    pub executed: Option<bool>
+}
+
+/// Shimcache contents of a SYSTEM hive
+#[derive(Debug, Clone, Serialize)]
+pub struct ShimcacheReport {
+    pub hive: String,
+    pub control_set: String,
+    /// Cache format detected from the value header
+    pub format: String,
+    /// Last write of the AppCompatCache key (roughly when the cache was last flushed)
+    pub key_last_written: Option<String>,
+    pub entries: Vec<ShimcacheEntry>
+}
+
+impl fmt::Display for ShimcacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Shimcache from {} ({}, {})", self.hive, self.control_set, self.format)?;
+        writeln!(f, "Key last written: {}", self.key_last_written.as_deref().unwrap_or("unknown"))?;
+
+        for entry in &self.entries {
+            write!(f, "{:>5}  {}  {}", entry.position, entry.last_modified.as_deref().unwrap_or("-"), entry.path)?;
+            if entry.executed == Some(true) {
+                write!(f, "  [executed]")?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "{} entries", self.entries.len())
+    }
+}
+
+/// Reads AppCompatCache from the current control set of an exported SYSTEM hive
+pub fn mercy_shimcache<P: AsRef<Path>>(hive_path: P) -> Result<ShimcacheReport, String> {
+    let hive_path = hive_path.as_ref();
+    let hive = RegistryHive::open(Path::new(hive_path))?;
+
+    // Select\Current names the control set in use; fall back to ControlSet001
+    let current = match hive.key("Select")? {
+        Some(select) => match select.value("Current")? {
+            Some(RegistryData::Dword(number)) => number,
+            _ => 1
+        },
+        None => 1
+    };
+
+    let control_set = format!("ControlSet{:03}", current);
+    let key = hive.key(&format!("{}\\Control\\Session Manager\\AppCompatCache", control_set))?
+        .or(hive.key(&format!("{}\\Control\\Session Manager\\AppCompatibility", control_set))?)
+        .ok_or_else(|| format!("No AppCompatCache key under {} (is this a SYSTEM hive?)", control_set))?;
+
+    let data = match key.value("AppCompatCache")? {
+        Some(RegistryData::Binary(data)) | Some(RegistryData::Other(_, data)) => data,
+        Some(_) => return Err("AppCompatCache value has an unexpected type".to_string()),
+        None => return Err("AppCompatCache value not found".to_string())
+    };
+
+    let (format, entries) = parse_cache(&data)?;
+
+    Ok(ShimcacheReport {
+        hive: path_to_string(hive_path),
+        control_set,
+        format: format.to_string(),
+        key_last_written: filetime_to_rfc3339(key.last_written()),
+        entries
+    })
+}
+
+/// Detects the cache format from its header and parses every entry
+fn parse_cache(data: &[u8]) -> Result<(&'static str, Vec<ShimcacheEntry>), String> {
+    let magic = le32(data, 0).ok_or("AppCompatCache value is empty")?;
+
+    match magic {
+        0xDEAD_BEEF => Ok(("Windows XP", parse_xp(data)?)),
+        0xBADC_0FFE => Ok(("Windows Server 2003/Vista/2008", parse_nt6(data, 8, false)?)),
+        0xBADC_0FEE => Ok(("Windows 7/2008 R2", parse_nt6(data, 128, true)?)),
+        0x30 | 0x34 => Ok(("Windows 10/11", parse_tagged(data, magic as usize, b"10ts")?)),
+        0x80 => match data.get(128..132) {
+            Some(b"00ts") => Ok(("Windows 8", parse_tagged(data, 128, b"00ts")?)),
+            Some(b"10ts") => Ok(("Windows 8.1", parse_tagged(data, 128, b"10ts")?)),
+            _ => Err("Unrecognised Windows 8 cache entry signature".to_string())
+        },
+        other => Err(format!("Unknown AppCompatCache format (header 0x{:08x})", other))
+    }
+}
+
+// Windows XP: 400-byte header, fixed 552-byte entries holding a MAX_PATH UTF-16 path
+fn parse_xp(data: &[u8]) -> Result<Vec<ShimcacheEntry>, String> {
+    let count = le32(data, 4).ok_or("Truncated XP cache header")? as usize;
+    let mut entries = Vec::new();
+
+    for position in 0..count.min(MAX_ENTRIES) {
+        let entry = 400 + position * 552;
+        let raw = match data.get(entry..entry + 552) {
+            Some(raw) => raw,
+            None => break
+        };
+
+        entries.push(ShimcacheEntry {
+            position,
+            path: utf16(&raw[..520]),
+            last_modified: le64(raw, 528).and_then(filetime_to_rfc3339),
+            executed: None
+        });
+    }
+
+    Ok(entries)
+}
+
+// Server 2003 / Vista / 7: UNICODE_STRING style entries with paths stored elsewhere in the value.
+// 32-bit systems use compact entries; 64-bit ones pad the string header and widen the offsets.
+fn parse_nt6(data: &[u8], header: usize, seven: bool) -> Result<Vec<ShimcacheEntry>, String> {
+    let count = le32(data, 4).ok_or("Truncated cache header")? as usize;
+
+    // On 64-bit systems the four bytes after the string lengths are padding, never a valid offset
+    let wide = le32(data, header + 4).ok_or("Truncated cache entries")? == 0;
+    let entry_size = match (seven, wide) {
+        (false, false) => 24,
+        (false, true) => 32,
+        (true, false) => 32,
+        (true, true) => 48
+    };
+
+    let mut entries = Vec::new();
+
+    for position in 0..count.min(MAX_ENTRIES) {
+        let entry = header + position * entry_size;
+        if entry + entry_size > data.len() {
+            break;
+        }
+
+        let length = le16(data, entry).unwrap_or(0) as usize;
+        let (path_offset, times) = if wide {
+            (le64(data, entry + 8).unwrap_or(0) as usize, entry + 16)
+        } else {
+            (le32(data, entry + 4).unwrap_or(0) as usize, entry + 8)
+        };
+
+        let path = data.get(path_offset..path_offset.saturating_add(length)).map(utf16).unwrap_or_default();
+
+        // Windows 7 keeps insertion flags after the timestamp; 0x2 marks an executed entry
+        let executed = if seven { le32(data, times + 8).map(|flags| flags & 0x2 != 0) } else { None };
+
+        entries.push(ShimcacheEntry {
+            position,
+            path,
+            last_modified: le64(data, times).and_then(filetime_to_rfc3339),
+            executed
+        });
+    }
+
+    Ok(entries)
+}
+
+// Windows 8 / 8.1 / 10 / 11: self-sized entries tagged "00ts" or "10ts"
+fn parse_tagged(data: &[u8], header: usize, tag: &[u8; 4]) -> Result<Vec<ShimcacheEntry>, String> {
+    let windows10 = header != 128;
+    let mut entries = Vec::new();
+    let mut offset = header;
+
+    while entries.len() < MAX_ENTRIES && offset + 12 <= data.len() {
+        if data.get(offset..offset + 4) != Some(tag.as_slice()) {
+            return Err(format!("Unexpected cache entry signature at offset {}", offset));
+        }
+
+        let entry_size = le32(data, offset + 8).unwrap_or(0) as usize;
+        let body = match data.get(offset + 12..offset + 12 + entry_size) {
+            Some(body) => body,
+            None => break
+        };
+
+        let path_length = le16(body, 0).unwrap_or(0) as usize;
+        let path = body.get(2..2 + path_length).map(utf16).unwrap_or_default();
+        let mut cursor = 2 + path_length;
+
+        // Windows 8.x adds a package name and two flag words before the timestamp
+        if !windows10 {
+            let package_length = le16(body, cursor).unwrap_or(0) as usize;
+            cursor += 2 + package_length + 8;
+        }
+
+        entries.push(ShimcacheEntry {
+            position: entries.len(),
+            path,
+            last_modified: le64(body, cursor).and_then(filetime_to_rfc3339),
+            executed: None
+        });
+
+        offset += 12 + entry_size;
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-03-01T10:00:00Z, and a day earlier for the second entry
+    const MODIFIED: u64 = 133_537_608_000_000_000;
+    const DAY: u64 = 864_000_000_000;
+    const PATHS: [&str; 2] = ["C:\\Windows\\System32\\cmd.exe", "C:\\Users\\alice\\Downloads\\payload.exe"];
+
+    fn utf16_bytes(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn put(data: &mut Vec<u8>, offset: usize, bytes: &[u8]) {
+        if data.len() < offset + bytes.len() {
+            data.resize(offset + bytes.len(), 0);
+        }
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn xp() -> Vec<u8> {
+        let mut data = vec![0u8; 400];
+        put(&mut data, 0, &0xDEAD_BEEFu32.to_le_bytes());
+        put(&mut data, 4, &2u32.to_le_bytes());
+        for (position, path) in PATHS.iter().enumerate() {
+            let entry = 400 + position * 552;
+            put(&mut data, entry, &utf16_bytes(path));
+            put(&mut data, entry + 528, &(MODIFIED - position as u64 * DAY).to_le_bytes());
+            put(&mut data, entry + 551, &[0]);
+        }
+        data
+    }
+
+    // Vista (8-byte header) or 7 (128-byte header with flags), 32- or 64-bit entries, paths after them
+    fn nt6(seven: bool, wide: bool) -> Vec<u8> {
+        let (magic, header) = if seven { (0xBADC_0FEEu32, 128) } else { (0xBADC_0FFE, 8) };
+        let entry_size = match (seven, wide) {
+            (false, false) => 24,
+            (false, true) | (true, false) => 32,
+            (true, true) => 48
+        };
+
+        let mut data = Vec::new();
+        put(&mut data, 0, &magic.to_le_bytes());
+        put(&mut data, 4, &2u32.to_le_bytes());
+        let mut path_offset = header + 2 * entry_size;
+        for (position, path) in PATHS.iter().enumerate() {
+            let entry = header + position * entry_size;
+            let path_bytes = utf16_bytes(path);
+            put(&mut data, entry, &(path_bytes.len() as u16).to_le_bytes());
+            put(&mut data, entry + 2, &(path_bytes.len() as u16 + 2).to_le_bytes());
+            let times = if wide {
+                put(&mut data, entry + 8, &(path_offset as u64).to_le_bytes());
+                entry + 16
+            } else {
+                put(&mut data, entry + 4, &(path_offset as u32).to_le_bytes());
+                entry + 8
+            };
+            put(&mut data, times, &(MODIFIED - position as u64 * DAY).to_le_bytes());
+            if seven {
+                // Only the first entry was executed
+                put(&mut data, times + 8, &(if position == 0 { 0x2u32 } else { 0 }).to_le_bytes());
+            }
+            put(&mut data, entry + entry_size - 1, &[0]);
+            put(&mut data, path_offset, &path_bytes);
+            path_offset += path_bytes.len() + 2;
+        }
+        data.resize(path_offset, 0);
+        data
+    }
+
+    // Windows 8 ("00ts"), 8.1 ("10ts" after a 128-byte header) or 10/11 ("10ts" after a 0x30 or 0x34 byte header)
+    fn tagged(header: usize, tag: &[u8; 4]) -> Vec<u8> {
+        let mut data = Vec::new();
+        put(&mut data, 0, &(header as u32).to_le_bytes());
+        data.resize(header, 0);
+
+        for (position, path) in PATHS.iter().enumerate() {
+            let path_bytes = utf16_bytes(path);
+            let mut body = (path_bytes.len() as u16).to_le_bytes().to_vec();
+            body.extend(&path_bytes);
+            if header == 128 {
+                let package = utf16_bytes("Microsoft.App");
+                body.extend((package.len() as u16).to_le_bytes());
+                body.extend(package);
+                body.extend([0u8; 8]);
+            }
+            body.extend((MODIFIED - position as u64 * DAY).to_le_bytes());
+            body.extend(0u32.to_le_bytes());
+
+            data.extend(tag);
+            data.extend([0u8; 4]);
+            data.extend((body.len() as u32).to_le_bytes());
+            data.extend(body);
+        }
+        data
+    }
+
+    fn check(format: &str, data: &[u8], executed: [Option<bool>; 2]) {
+        let (detected, entries) = parse_cache(data).expect(format);
+        assert_eq!(detected, format);
+        assert_eq!(entries.len(), 2, "{}", format);
+        for (position, entry) in entries.iter().enumerate() {
+            assert_eq!((entry.position, entry.path.as_str()), (position, PATHS[position]), "{}", format);
+            assert_eq!(entry.last_modified.as_deref(), Some(["2024-03-01T10:00:00Z", "2024-02-29T10:00:00Z"][position]), "{}", format);
+            assert_eq!(entry.executed, executed[position], "{}", format);
+        }
+    }
+
+    #[test]
+    fn every_cache_format_parses() {
+        check("Windows XP", &xp(), [None, None]);
+        check("Windows Server 2003/Vista/2008", &nt6(false, false), [None, None]);
+        check("Windows Server 2003/Vista/2008", &nt6(false, true), [None, None]);
+        check("Windows 7/2008 R2", &nt6(true, false), [Some(true), Some(false)]);
+        check("Windows 7/2008 R2", &nt6(true, true), [Some(true), Some(false)]);
+        check("Windows 8", &tagged(128, b"00ts"), [None, None]);
+        check("Windows 8.1", &tagged(128, b"10ts"), [None, None]);
+        check("Windows 10/11", &tagged(0x30, b"10ts"), [None, None]);
+        check("Windows 10/11", &tagged(0x34, b"10ts"), [None, None]);
+    }
+
+    #[test]
+    fn unknown_and_damaged_caches_are_refused() {
+        assert!(parse_cache(&[]).is_err());
+        assert!(parse_cache(&0x1234_5678u32.to_le_bytes()).expect_err("unknown").contains("0x12345678"));
+
+        let mut damaged = tagged(0x34, b"10ts");
+        damaged[0x34..0x38].copy_from_slice(b"00ts");
+        assert!(parse_cache(&damaged).expect_err("wrong tag").contains("signature"));
+    }
+}
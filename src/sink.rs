@@ -0,0 +1,456 @@
+/*
+    Output sinks for streamed results
+
+    The NDJSON and line-per-record writers (timeline, evtx, minidump, secrets_git,
+    normalize_timestamps, logs_merge) take any `Write`, so their output can go straight to a
+    collector instead of a string in memory. `OutputSink` is that writer for three targets:
+
+    - A file path. A FIFO (named pipe) is opened without blocking, so a pipe with no reader is
+      a failed attempt rather than a hang. On Windows a "\\.\pipe\name" path opens as a file.
+    - A Unix domain socket ("unix:/run/collector.sock", Unix only).
+    - A TCP address ("tcp:127.0.0.1:9000").
+
+    Records are whole lines and are sent in batches of complete lines, never split across a
+    reconnect. When the reader goes away (broken pipe, reset connection), the sink reconnects
+    with a growing delay up to a bounded number of attempts and resends what was in flight from
+    the start of a record, so the next reader never starts mid-line. Records the old reader's
+    socket or pipe had already accepted but it never read go with it: there are no
+    acknowledgements, so delivery around a reconnect is best effort. Once the attempts run
+    out, the write fails and the stream stops with that error.
+
+    Backpressure either blocks until the reader catches up, or offers each record without
+    waiting and drops (counting) those the reader has no room for. A record the reader has
+    started taking is always finished, so dropping never leaves half a line behind. Regular
+    files never push back, and on non-Unix platforms a pipe opened as a file always blocks.
+
+    Rust binaries ignore SIGPIPE, so a vanished reader is an error here rather than the end of
+    the process; a host that restores SIGPIPE must keep it ignored while streaming.
+*/
+
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    thread,
+    time::Duration
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use serde::Serialize;
+
+// Complete records collected before a send
+const BATCH_SIZE: usize = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where an `OutputSink` delivers records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkTarget {
+    /// A regular file (created or truncated) or a named pipe
+    File(PathBuf),
+    /// A Unix domain stream socket
+    #[cfg(unix)]
+    UnixSocket(PathBuf),
+    /// "host:port"
+    Tcp(String)
+}
+
+impl SinkTarget {
+    /// "unix:/path/to.sock", "tcp:host:port", or a file path
+    pub fn parse(spec: &str) -> Result<SinkTarget, String> {
+        if let Some(path) = spec.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(SinkTarget::UnixSocket(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(format!("Unix sockets are not supported on this platform ({})", path));
+        }
+
+        if let Some(address) = spec.strip_prefix("tcp:") {
+            if address.rsplit_once(':').is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err()) {
+                return Err(format!("Invalid TCP address '{}' (expected host:port)", address));
+            }
+            return Ok(SinkTarget::Tcp(address.to_string()));
+        }
+
+        if spec.is_empty() {
+            return Err("No output target specified".to_string());
+        }
+        Ok(SinkTarget::File(PathBuf::from(spec)))
+    }
+}
+
+impl fmt::Display for SinkTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkTarget::File(path) => write!(f, "{}", path.display()),
+            #[cfg(unix)]
+            SinkTarget::UnixSocket(path) => write!(f, "unix:{}", path.display()),
+            SinkTarget::Tcp(address) => write!(f, "tcp:{}", address)
+        }
+    }
+}
+
+/// What a sink does when the reader is not keeping up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backpressure {
+    /// Wait for the reader
+    #[default]
+    Block,
+    /// Drop what the reader has no room for, counting the records
+    Drop
+}
+
+/// Options for `OutputSink::open`
+#[derive(Debug, Clone)]
+pub struct SinkOptions {
+    pub backpressure: Backpressure,
+    /// Reconnection attempts after the reader goes away before the write fails
+    pub retries: u32,
+    /// Delay before the first reconnection attempt, growing by the same amount each attempt
+    pub retry_delay: Duration
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        SinkOptions { backpressure: Backpressure::Block, retries: 5, retry_delay: Duration::from_millis(200) }
+    }
+}
+
+/// Counts kept by an `OutputSink`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SinkStats {
+    pub target: String,
+    pub backpressure: Backpressure,
+    /// Records handed to the reader (resent records count again)
+    pub records_sent: u64,
+    /// Records dropped because the reader had no room (Drop only)
+    pub records_dropped: u64,
+    pub reconnects: u64
+}
+
+/// A `Write` delivering line records to a file, named pipe, Unix socket or TCP peer
+pub struct OutputSink {
+    target: SinkTarget,
+    options: SinkOptions,
+    connection: Option<Connection>,
+    pending: Vec<u8>,
+    stats: SinkStats,
+    // The last send gave up, so dropping the sink does not retry again
+    failed: bool
+}
+
+impl OutputSink {
+    /// Connects to `target`, retrying as for a reconnection
+    pub fn open(target: SinkTarget, options: &SinkOptions) -> Result<OutputSink, String> {
+        let stats = SinkStats { target: target.to_string(), backpressure: options.backpressure, ..SinkStats::default() };
+        let mut sink = OutputSink { target, options: options.clone(), connection: None, pending: Vec::new(), stats, failed: false };
+
+        let connection = sink.connect().map_err(|e| format!("Unable to open {}: {}", sink.target, e))?;
+        sink.connection = Some(connection);
+        Ok(sink)
+    }
+
+    /// Counts so far
+    pub fn stats(&self) -> &SinkStats {
+        &self.stats
+    }
+
+    // Connects within the retry budget, creating (truncating) a regular file
+    fn connect(&self) -> io::Result<Connection> {
+        let mut attempt = 0;
+        loop {
+            match Connection::open(&self.target, true, self.options.backpressure) {
+                Ok(connection) => return Ok(connection),
+                Err(e) if attempt >= self.options.retries => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(self.options.retry_delay * attempt);
+                }
+            }
+        }
+    }
+
+    // Sends the first `length` pending bytes, `records` records in all
+    fn send(&mut self, length: usize, records: u64) -> io::Result<()> {
+        let batch: Vec<u8> = self.pending.drain(..length).collect();
+        let result = self.deliver(&batch, records);
+        self.failed = result.is_err();
+        result
+    }
+
+    // One budget of attempts covers both reconnecting and resending
+    fn deliver(&mut self, batch: &[u8], records: u64) -> io::Result<()> {
+        let mut attempt = 0;
+        // With Drop each record is offered on its own, so a retry resumes at the record that failed
+        let mut offset = 0;
+
+        loop {
+            let connection = match &mut self.connection {
+                Some(connection) => connection,
+                None => match Connection::open(&self.target, false, self.options.backpressure) {
+                    Ok(connection) => {
+                        self.stats.reconnects += 1;
+                        self.connection.insert(connection)
+                    },
+                    Err(_) if attempt < self.options.retries => {
+                        attempt += 1;
+                        thread::sleep(self.options.retry_delay * attempt);
+                        continue;
+                    },
+                    Err(e) => return Err(e)
+                }
+            };
+
+            let result = match self.options.backpressure {
+                Backpressure::Block => connection.writer().write_all(batch).map(|_| self.stats.records_sent += records),
+                Backpressure::Drop => connection.offer(batch, &mut offset, &mut self.stats)
+            };
+
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) if is_disconnect(&e) && attempt < self.options.retries => {
+                    // With Block the batch goes again, whole, to the next reader
+                    self.connection = None;
+                    attempt += 1;
+                    thread::sleep(self.options.retry_delay * attempt);
+                },
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    // Sends every complete record pending
+    fn send_complete(&mut self) -> io::Result<()> {
+        let Some(end) = self.pending.iter().rposition(|byte| *byte == b'\n').map(|last| last + 1) else {
+            return Ok(());
+        };
+
+        let records = self.pending[..end].iter().filter(|byte| **byte == b'\n').count() as u64;
+        self.send(end, records)
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if self.pending.len() >= BATCH_SIZE {
+            self.send_complete()?;
+        }
+        Ok(buf.len())
+    }
+
+    // Sends everything pending, an unterminated last line as a record of its own
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_complete()?;
+        if !self.pending.is_empty() {
+            self.send(self.pending.len(), 1)?;
+        }
+
+        match self.connection.as_mut() {
+            Some(connection) => connection.flush(),
+            None => Ok(())
+        }
+    }
+}
+
+impl Drop for OutputSink {
+    fn drop(&mut self) {
+        if !self.failed {
+            let _ = self.flush();
+        }
+    }
+}
+
+fn is_disconnect(error: &io::Error) -> bool {
+    matches!(error.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::NotConnected | io::ErrorKind::UnexpectedEof)
+}
+
+enum Connection {
+    File(File),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Tcp(TcpStream)
+}
+
+impl Connection {
+    fn open(target: &SinkTarget, first: bool, backpressure: Backpressure) -> io::Result<Connection> {
+        let nonblocking = backpressure == Backpressure::Drop;
+        let connection = match target {
+            SinkTarget::File(path) => Connection::File(open_file(path, first)?),
+            #[cfg(unix)]
+            SinkTarget::UnixSocket(path) => Connection::Unix(UnixStream::connect(path)?),
+            SinkTarget::Tcp(address) => {
+                let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve", address));
+                let mut stream = None;
+                for addr in address.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                        Ok(connected) => {
+                            stream = Some(connected);
+                            break;
+                        },
+                        Err(e) => last_error = e
+                    }
+                }
+
+                let stream = stream.ok_or(last_error)?;
+                stream.set_nodelay(true)?;
+                Connection::Tcp(stream)
+            }
+        };
+
+        connection.set_nonblocking(nonblocking)?;
+        Ok(connection)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Connection::File(file) => set_file_nonblocking(file, nonblocking),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_nonblocking(nonblocking),
+            Connection::Tcp(stream) => stream.set_nonblocking(nonblocking)
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Connection::File(file) => file,
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream,
+            Connection::Tcp(stream) => stream
+        }
+    }
+
+    // Offers each record from `offset` without waiting, counting those the reader has no room for
+    fn offer(&mut self, batch: &[u8], offset: &mut usize, stats: &mut SinkStats) -> io::Result<()> {
+        while *offset < batch.len() {
+            let end = batch[*offset..].iter().position(|byte| *byte == b'\n').map(|index| *offset + index + 1).unwrap_or(batch.len());
+            if self.offer_record(&batch[*offset..end])? {
+                stats.records_sent += 1;
+            } else {
+                stats.records_dropped += 1;
+            }
+            *offset = end;
+        }
+
+        Ok(())
+    }
+
+    // Writes one record unless the reader has no room for any of it
+    fn offer_record(&mut self, record: &[u8]) -> io::Result<bool> {
+        let written = loop {
+            match self.writer().write(record) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => break written,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e)
+            }
+        };
+
+        // A started record is finished so no partial line is left in the stream
+        if written < record.len() {
+            self.set_nonblocking(false)?;
+            let result = self.writer().write_all(&record[written..]);
+            self.set_nonblocking(true)?;
+            result?;
+        }
+
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+#[cfg(unix)]
+fn open_file(path: &std::path::Path, first: bool) -> io::Result<File> {
+    use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+
+    let fifo = std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo());
+    if fifo {
+        // Without a reader this fails with ENXIO instead of waiting for one
+        return OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open(path);
+    }
+
+    open_regular(path, first)
+}
+
+#[cfg(not(unix))]
+fn open_file(path: &std::path::Path, first: bool) -> io::Result<File> {
+    open_regular(path, first)
+}
+
+// A reopened file is appended to rather than truncated again
+fn open_regular(path: &std::path::Path, first: bool) -> io::Result<File> {
+    if first {
+        File::create(path)
+    } else {
+        OpenOptions::new().append(true).create(true).open(path)
+    }
+}
+
+#[cfg(unix)]
+fn set_file_nonblocking(file: &File, nonblocking: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let flags = if nonblocking { flags | libc::O_NONBLOCK } else { flags & !libc::O_NONBLOCK };
+    if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_file_nonblocking(_file: &File, _nonblocking: bool) -> io::Result<()> {
+    Ok(())
+}
+
+// Output target of a streaming dispatcher call, from its "out=", "backpressure=" and "retries=" options
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SinkArgs {
+    target: Option<String>,
+    options: SinkOptions
+}
+
+impl SinkArgs {
+    // Takes a sink option, returning false for a key that is not one
+    pub fn accept(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        match key {
+            "out" => self.target = Some(value.to_string()),
+            "backpressure" => self.options.backpressure = match value {
+                "block" => Backpressure::Block,
+                "drop" => Backpressure::Drop,
+                _ => return Err(format!("Unknown backpressure '{}' (block or drop)", value))
+            },
+            "retries" => self.options.retries = value.parse().map_err(|_| format!("Invalid retries '{}'", value))?,
+            _ => return Ok(false)
+        }
+        Ok(true)
+    }
+
+    // The sink named by "out=", if any
+    pub fn open(&self) -> Result<Option<OutputSink>, String> {
+        match &self.target {
+            Some(target) => OutputSink::open(SinkTarget::parse(target)?, &self.options).map(Some),
+            None => Ok(None)
+        }
+    }
+}
+
+// Flushes a finished stream and reports its summary with the sink's counts
+pub(crate) fn finish_sink<T: Serialize>(mut sink: OutputSink, summary: &T) -> Result<String, String> {
+    sink.flush().map_err(|e| format!("Unable to write to {}: {}", sink.target, e))?;
+    let report = serde_json::json!({ "summary": summary, "sink": sink.stats() });
+    serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+}
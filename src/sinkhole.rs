@@ -0,0 +1,443 @@
+/*
+    Sinkhole detection for domain lookups
+
+    A domain taken over by a researcher, a vendor or law enforcement usually resolves into a
+    small set of well-known address ranges, is delegated to nameservers with tell-tale names,
+    or serves a page saying so. The A/AAAA records (following CNAMEs) and NS records of the
+    domain (or its closest parent that has some) are compared against a list of such ranges
+    and name patterns, and when a fetch is allowed the HTTP response is searched for sinkhole
+    and seizure banner phrases.
+
+    The built-in list is a starting point: sinkholes move, so it can be replaced or extended
+    at runtime, from code or from a file of lines such as
+
+        ip 198.51.100.0/24 Example research sinkhole
+        ns *.sinkhole.example Example research sinkhole
+        phrase this domain has been sinkholed
+
+    Null and loopback answers are included because resolvers with blocklists (RPZ) and
+    registries on hold return them for blocked domains.
+*/
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    sync::RwLock,
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    cidr::Cidr,
+    config,
+    dns::{DnsQuery, DnsRdata, DnsType, default_dns_server, dns_server_addr},
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net::{self, KEEP_LAST},
+    runtime,
+    text::read_text_file,
+    trailing_options
+};
+
+// Most of an HTTP response searched for banner phrases
+const MAX_BODY: usize = 64 * 1024;
+
+const BUILTIN_RANGES: &[(&str, &str)] = &[
+    ("0.0.0.0/8", "Null route (resolver blocklist or registry hold)"),
+    ("127.0.0.0/8", "Loopback (resolver blocklist)"),
+    ("::/128", "Null route (resolver blocklist)"),
+    ("::1/128", "Loopback (resolver blocklist)"),
+    ("72.5.65.111/32", "Palo Alto Networks DNS sinkhole"),
+    ("131.253.18.11/32", "Microsoft Digital Crimes Unit"),
+    ("131.253.18.12/32", "Microsoft Digital Crimes Unit"),
+    ("199.2.137.0/24", "Microsoft Digital Crimes Unit"),
+    ("146.112.61.104/29", "Cisco Umbrella (OpenDNS) block page"),
+    ("208.91.112.55/32", "Fortinet FortiGuard DNS sinkhole"),
+    ("195.22.26.192/26", "AnubisNetworks sinkhole"),
+    ("148.81.111.0/24", "CERT Polska sinkhole")
+];
+
+const BUILTIN_NAMESERVERS: &[(&str, &str)] = &[
+    ("*sinkhole*", "Host named as a sinkhole"),
+    ("*sinkdns*", "Host named as a sinkhole"),
+    ("*.microsoftinternetsafety.net", "Microsoft Digital Crimes Unit"),
+    ("*.shadowserver.org", "Shadowserver Foundation"),
+    ("*.seized.gov", "Law enforcement seizure"),
+    ("*seized*", "Host named for a seizure")
+];
+
+const BUILTIN_PHRASES: &[&str] = &[
+    "sinkhole",
+    "sinkholed",
+    "this domain has been seized",
+    "domain has been seized",
+    "this website has been seized",
+    "seized by the federal bureau of investigation",
+    "seized pursuant to",
+    "this domain is controlled by",
+    "this site is blocked due to a security threat",
+    "malicious domain blocked"
+];
+
+// Replaces the built-in list while set
+static ACTIVE_LIST: RwLock<Option<SinkholeList>> = RwLock::new(None);
+
+/// An address range used by a sinkhole operator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkholeRange {
+    pub cidr: Cidr,
+    pub label: String
+}
+
+/// A nameserver or CNAME target pattern ("*" matches anything, case-insensitive)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkholePattern {
+    pub pattern: String,
+    pub label: String
+}
+
+/// Ranges, name patterns and banner phrases that identify sinkholes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SinkholeList {
+    pub ranges: Vec<SinkholeRange>,
+    pub nameservers: Vec<SinkholePattern>,
+    /// Matched case-insensitively against HTTP response headers and body
+    pub phrases: Vec<String>
+}
+
+impl SinkholeList {
+    /// The list built into the crate
+    pub fn builtin() -> SinkholeList {
+        SinkholeList {
+            ranges: BUILTIN_RANGES.iter()
+                .filter_map(|(cidr, label)| Some(SinkholeRange { cidr: cidr.parse().ok()?, label: label.to_string() }))
+                .collect(),
+            nameservers: BUILTIN_NAMESERVERS.iter()
+                .map(|(pattern, label)| SinkholePattern { pattern: pattern.to_string(), label: label.to_string() })
+                .collect(),
+            phrases: BUILTIN_PHRASES.iter().map(|phrase| phrase.to_string()).collect()
+        }
+    }
+
+    /// Parses "ip <cidr> [label]", "ns <pattern> [label]" and "phrase <text>" lines; "#" starts a comment
+    pub fn parse(text: &str) -> Result<SinkholeList, String> {
+        let mut list = SinkholeList::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let (value, label) = rest.split_once(char::is_whitespace).map(|(value, label)| (value, label.trim())).unwrap_or((rest, ""));
+            let label = if label.is_empty() { "User list".to_string() } else { label.to_string() };
+            let invalid = |reason: String| format!("Sinkhole list line {}: {}", number + 1, reason);
+
+            match kind.to_ascii_lowercase().as_str() {
+                "ip" => list.ranges.push(SinkholeRange { cidr: value.parse().map_err(invalid)?, label }),
+                "ns" if !value.is_empty() => list.nameservers.push(SinkholePattern { pattern: value.to_string(), label }),
+                "phrase" if !rest.is_empty() => list.phrases.push(rest.to_lowercase()),
+                _ => return Err(invalid(format!("expected \"ip <cidr>\", \"ns <pattern>\" or \"phrase <text>\", got '{}'", line)))
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Reads a list file (see `parse`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<SinkholeList, String> {
+        let path = path.as_ref();
+        let text = read_text_file(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        SinkholeList::parse(&text)
+    }
+
+    /// Adds the entries of another list
+    pub fn extend(&mut self, other: SinkholeList) {
+        self.ranges.extend(other.ranges);
+        self.nameservers.extend(other.nameservers);
+        self.phrases.extend(other.phrases);
+    }
+}
+
+/// Replaces the list used by every check that is not given one (`None` restores the built-in list)
+pub fn set_sinkhole_list(list: Option<SinkholeList>) {
+    match ACTIVE_LIST.write() {
+        Ok(mut active) => *active = list,
+        Err(poisoned) => *poisoned.into_inner() = list
+    }
+}
+
+/// The list checks use by default: the one set with `set_sinkhole_list`, else the built-in list
+pub fn sinkhole_list() -> SinkholeList {
+    let active = match ACTIVE_LIST.read() {
+        Ok(active) => active.clone(),
+        Err(poisoned) => poisoned.into_inner().clone()
+    };
+
+    active.unwrap_or_else(SinkholeList::builtin)
+}
+
+/// Options for `mercy_sinkhole_check`
+#[derive(Debug, Clone)]
+pub struct SinkholeOptions {
+    /// List for this check; None uses `sinkhole_list()`
+    pub list: Option<SinkholeList>,
+    /// Fetch http://domain/ and search the response for banner phrases
+    pub fetch: bool,
+    /// DNS server (default: the system resolver's first nameserver)
+    pub server: Option<SocketAddr>,
+    pub timeout: Duration,
+    /// HTTP identity for the fetch, layered over the default
+    pub identity: HttpIdentity
+}
+
+impl Default for SinkholeOptions {
+    fn default() -> Self {
+        SinkholeOptions {
+            list: None,
+            fetch: false,
+            server: None,
+            timeout: config::read_timeout(Duration::from_secs(5)),
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// One match against the sinkhole list
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkholeEvidence {
+    /// "a", "aaaa", "cname", "ns" or "http"
+    pub kind: String,
+    /// The address, name or response excerpt that matched
+    pub value: String,
+    /// The range, pattern or phrase it matched
+    pub matched: String,
+    pub label: String
+}
+
+/// Whether a domain looks sinkholed, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkholeReport {
+    pub domain: String,
+    pub sinkholed: bool,
+    pub evidence: Vec<SinkholeEvidence>,
+    pub addresses: Vec<String>,
+    pub cnames: Vec<String>,
+    /// NS records of the domain, or of the closest parent that has them
+    pub nameservers: Vec<String>,
+    /// HTTP status of the fetch, when one was made and answered
+    pub http_status: Option<u16>,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for SinkholeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.domain, if self.sinkholed { "sinkholed" } else { "no sinkhole indicators" })?;
+        writeln!(f, "Addresses: {}", if self.addresses.is_empty() { "none".to_string() } else { self.addresses.join(", ") })?;
+        writeln!(f, "Nameservers: {}", if self.nameservers.is_empty() { "none".to_string() } else { self.nameservers.join(", ") })?;
+        if let Some(status) = self.http_status {
+            writeln!(f, "HTTP status: {}", status)?;
+        }
+
+        for evidence in &self.evidence {
+            writeln!(f, "  {} {} matches {} ({})", evidence.kind, evidence.value, evidence.matched, evidence.label)?;
+        }
+
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares a domain's addresses, CNAMEs and nameservers (and optionally its web page) against the sinkhole list
+pub fn mercy_sinkhole_check(domain: &str, options: &SinkholeOptions) -> Result<SinkholeReport, String> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    if domain.is_empty() || !domain.contains('.') {
+        return Err(format!("Invalid domain '{}'", domain));
+    }
+
+    let list = options.list.clone().unwrap_or_else(sinkhole_list);
+    let server = options.server.unwrap_or_else(default_dns_server);
+    let mut report = SinkholeReport {
+        domain: domain.clone(),
+        sinkholed: false,
+        evidence: Vec::new(),
+        addresses: Vec::new(),
+        cnames: Vec::new(),
+        nameservers: Vec::new(),
+        http_status: None,
+        notes: Vec::new()
+    };
+
+    for qtype in [DnsType::A, DnsType::Aaaa] {
+        match DnsQuery::new(&domain, qtype).send(server, options.timeout) {
+            Ok(response) => {
+                for record in &response.answers {
+                    match &record.rdata {
+                        DnsRdata::A(address) => report.addresses.push(address.to_string()),
+                        DnsRdata::Aaaa(address) => report.addresses.push(address.to_string()),
+                        DnsRdata::Name(name) if record.rtype == DnsType::Cname && !report.cnames.contains(name) => report.cnames.push(name.clone()),
+                        _ => {}
+                    }
+                }
+            },
+            Err(e) => report.notes.push(format!("{} lookup failed: {}", qtype, e))
+        }
+    }
+
+    report.nameservers = nameservers(&domain, server, options.timeout, &mut report.notes);
+
+    for address in &report.addresses {
+        let Ok(ip) = address.parse::<IpAddr>() else { continue };
+        if let Some(range) = list.ranges.iter().find(|range| range.cidr.contains(ip)) {
+            let kind = if ip.is_ipv4() { "a" } else { "aaaa" };
+            report.evidence.push(SinkholeEvidence { kind: kind.to_string(), value: address.clone(), matched: range.cidr.to_string(), label: range.label.clone() });
+        }
+    }
+
+    for (kind, names) in [("cname", &report.cnames), ("ns", &report.nameservers)] {
+        for name in names {
+            if let Some(pattern) = list.nameservers.iter().find(|pattern| glob_match(&pattern.pattern, name)) {
+                report.evidence.push(SinkholeEvidence { kind: kind.to_string(), value: name.clone(), matched: pattern.pattern.clone(), label: pattern.label.clone() });
+            }
+        }
+    }
+
+    if options.fetch {
+        match runtime::block_on(fetch_page(&domain, options)) {
+            Ok((status, page)) => {
+                report.http_status = Some(status);
+                let lowered = page.to_lowercase();
+                if let Some(phrase) = list.phrases.iter().find(|phrase| lowered.contains(phrase.to_lowercase().as_str())) {
+                    report.evidence.push(SinkholeEvidence { kind: "http".to_string(), value: excerpt(&page, &lowered, &phrase.to_lowercase()), matched: phrase.clone(), label: "Sinkhole or seizure banner".to_string() });
+                }
+            },
+            Err(e) => report.notes.push(format!("HTTP fetch failed: {}", e))
+        }
+    }
+
+    report.sinkholed = !report.evidence.is_empty();
+    Ok(report)
+}
+
+// NS records of the domain, walking up to its parents until some are found (never the TLD)
+fn nameservers(domain: &str, server: SocketAddr, timeout: Duration, notes: &mut Vec<String>) -> Vec<String> {
+    let mut name = domain;
+
+    while name.contains('.') {
+        match DnsQuery::new(name, DnsType::Ns).send(server, timeout) {
+            Ok(response) => {
+                let found: Vec<String> = response.answers.iter()
+                    .filter(|record| record.rtype == DnsType::Ns)
+                    .filter_map(|record| match &record.rdata {
+                        DnsRdata::Name(nameserver) => Some(nameserver.to_lowercase()),
+                        _ => None
+                    })
+                    .collect();
+                if !found.is_empty() {
+                    return found;
+                }
+            },
+            Err(e) => {
+                notes.push(format!("NS lookup for {} failed: {}", name, e));
+                return Vec::new();
+            }
+        }
+
+        name = name.split_once('.').map(|(_, parent)| parent).unwrap_or("");
+    }
+
+    Vec::new()
+}
+
+// Status and the headers plus the start of the body of http://domain/
+async fn fetch_page(domain: &str, options: &SinkholeOptions) -> Result<(u16, String), String> {
+    let client = http_client(&options.identity)?;
+    let mut response = net::send_with(client.get(format!("http://{}/", domain)).timeout(options.timeout), &KEEP_LAST).await?.response;
+
+    let status = response.status().as_u16();
+    let mut page: String = response.headers().iter()
+        .map(|(name, value)| format!("{}: {}\n", name, String::from_utf8_lossy(value.as_bytes())))
+        .collect();
+
+    let mut body = Vec::new();
+    while body.len() < MAX_BODY {
+        match response.chunk().await.map_err(|e| e.to_string())? {
+            Some(chunk) => body.extend_from_slice(&chunk),
+            None => break
+        }
+    }
+
+    body.truncate(MAX_BODY);
+    page.push_str(&String::from_utf8_lossy(&body));
+    Ok((status, page))
+}
+
+// About 80 characters of the page around a matched phrase, on one line
+fn excerpt(page: &str, lowered: &str, phrase: &str) -> String {
+    let at = lowered.find(phrase).unwrap_or(0);
+    let start = (0..=at.saturating_sub(30)).rev().find(|index| page.is_char_boundary(*index)).unwrap_or(0);
+    let end = (at + phrase.len() + 30).min(page.len());
+    let end = (end..=page.len()).find(|index| page.is_char_boundary(*index)).unwrap_or(page.len());
+
+    page.get(start..end).unwrap_or("").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Case-insensitive match where "*" stands for any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || !text[first.len()..].ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false
+        }
+    }
+
+    true
+}
+
+// Parses "domain [fetch=true] [list=extra.txt] [replace=true] [server=1.1.1.1] [timeout=5]" plus HTTP identity options
+pub(crate) fn sinkhole_check_from_args(input: &str) -> Result<SinkholeReport, String> {
+    let keys: Vec<&str> = ["fetch", "list", "replace", "server", "timeout"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (domain, pairs) = trailing_options(input, &keys);
+    let mut options = SinkholeOptions::default();
+    let mut extra = None;
+    let mut replace = false;
+
+    for (key, value) in pairs {
+        match key {
+            "fetch" => options.fetch = value == "true",
+            "list" => extra = Some(SinkholeList::load(value)?),
+            "replace" => replace = value == "true",
+            "server" => options.server = Some(dns_server_addr(value)?),
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => identity_option(&mut options.identity, key, value)?
+        }
+    }
+
+    if let Some(extra) = extra {
+        let mut list = if replace { SinkholeList::default() } else { sinkhole_list() };
+        list.extend(extra);
+        options.list = Some(list);
+    }
+
+    mercy_sinkhole_check(&domain, &options)
+}
@@ -0,0 +1,278 @@
+/*
+    Versioned snapshot files for state kept between runs
+
+    Features that compare against an earlier run (page baselines, watch checks, tree hashes,
+    certificate fingerprints) keep their state in one container instead of a format each:
+
+        {
+          "schema_version": 2,
+          "created_at": "2024-05-01T09:00:00Z",
+          "updated_at": "2024-05-02T09:00:00Z",
+          "tool_version": "1.2.17",
+          "sections": { "<check type>": <that check's state>, ... }
+        }
+
+    Files may be gzip compressed; loading detects it from the magic bytes. A file in an older
+    layout is migrated forward step by step when loaded, one from a newer mercy is refused, and
+    a file that cannot be read is never overwritten: saving over it fails until it is moved out
+    of the way. Saving can first copy the previous file to "<name>.<UTC timestamp>.bak", and
+    writes through a temporary file renamed into place so a crash leaves the old file intact.
+
+    Version 1 was a flat object: {"version": 1, "created": <Unix seconds>, "<check type>": ...}.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use crate::{
+    deflate::gzip,
+    inflate::gzip_reader,
+    paths::path_to_string,
+    timefmt::unix_to_rfc3339,
+    trailing_options
+};
+
+/// Layout version written by this build
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 2;
+
+// Largest decompressed snapshot accepted
+const MAX_SNAPSHOT_BYTES: u64 = 256 * 1024 * 1024;
+
+// Takes a document of one schema version to the next
+type Migration = fn(Value) -> Result<Value, String>;
+
+// The version each migration starts from
+const MIGRATIONS: &[(u32, Migration)] = &[
+    (1, migrate_v1)
+];
+
+/// State of one check type, stored as the section named `SECTION`
+pub trait SnapshotSection: Serialize + DeserializeOwned {
+    const SECTION: &'static str;
+}
+
+/// The container every stateful feature saves into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub schema_version: u32,
+    pub created_at: String,
+    pub updated_at: String,
+    /// mercy version that last saved the file
+    pub tool_version: String,
+    pub sections: BTreeMap<String, Value>,
+    /// Schema version the file had before it was migrated on load
+    #[serde(skip)]
+    pub migrated_from: Option<u32>
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        let now = now_rfc3339();
+        Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            created_at: now.clone(),
+            updated_at: now,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            sections: BTreeMap::new(),
+            migrated_from: None
+        }
+    }
+}
+
+impl Snapshot {
+    /// An empty snapshot created now
+    pub fn new() -> Snapshot {
+        Snapshot::default()
+    }
+
+    /// Parses a snapshot document, migrating an older layout to the current one
+    pub fn from_json(text: &str) -> Result<Snapshot, String> {
+        let mut document: Value = serde_json::from_str(text).map_err(|e| format!("not valid JSON ({})", e))?;
+        let original = schema_version(&document)?;
+
+        if original > SNAPSHOT_SCHEMA_VERSION {
+            return Err(format!("schema version {} is newer than this mercy supports ({}); upgrade mercy to read it", original, SNAPSHOT_SCHEMA_VERSION));
+        }
+
+        let mut version = original;
+        while version < SNAPSHOT_SCHEMA_VERSION {
+            let (_, migrate) = MIGRATIONS.iter().find(|(from, _)| *from == version).ok_or_else(|| format!("no migration from schema version {}", version))?;
+            document = migrate(document).map_err(|e| format!("migrating from schema version {} failed: {}", version, e))?;
+            version = schema_version(&document)?;
+        }
+
+        let mut snapshot: Snapshot = serde_json::from_value(document).map_err(|e| format!("invalid snapshot ({})", e))?;
+        snapshot.migrated_from = (original != SNAPSHOT_SCHEMA_VERSION).then_some(original);
+        Ok(snapshot)
+    }
+
+    /// The state of check type `T`, if the snapshot has it
+    pub fn get<T: SnapshotSection>(&self) -> Result<Option<T>, String> {
+        self.sections.get(T::SECTION)
+            .map(|value| serde_json::from_value(value.clone()).map_err(|e| format!("Invalid {} section in snapshot: {}", T::SECTION, e)))
+            .transpose()
+    }
+
+    /// Replaces the state of check type `T`
+    pub fn put<T: SnapshotSection>(&mut self, state: &T) -> Result<(), String> {
+        let value = serde_json::to_value(state).map_err(|e| format!("Unable to serialize the {} section: {}", T::SECTION, e))?;
+        self.sections.insert(T::SECTION.to_string(), value);
+        Ok(())
+    }
+
+    /// Drops the state of check type `T`, returning whether there was any
+    pub fn remove<T: SnapshotSection>(&mut self) -> bool {
+        self.sections.remove(T::SECTION).is_some()
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Snapshot schema version {}", self.schema_version)?;
+        if let Some(from) = self.migrated_from {
+            write!(f, " (migrated from {})", from)?;
+        }
+        writeln!(f, ", written by mercy {}", self.tool_version)?;
+        writeln!(f, "Created: {}", self.created_at)?;
+        writeln!(f, "Updated: {}", self.updated_at)?;
+
+        if self.sections.is_empty() {
+            write!(f, "Sections: (none)")?;
+        }
+        for (name, value) in &self.sections {
+            let size = serde_json::to_string(value).map(|json| json.len()).unwrap_or(0);
+            writeln!(f, "Section {}: {} bytes", name, size)?;
+        }
+        Ok(())
+    }
+}
+
+/// How `save_snapshot` writes the file
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotOptions {
+    /// gzip the JSON
+    pub compress: bool,
+    /// Copy the file being replaced to "<name>.<UTC timestamp>.bak" first
+    pub backup: bool
+}
+
+/// Reads a snapshot file, plain or gzip compressed, migrating an older layout forward
+pub fn load_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Unable to read snapshot {}: {}", path.display(), e))?;
+    let text = if bytes.starts_with(&[0x1F, 0x8B]) {
+        let mut text = String::new();
+        gzip_reader(bytes.as_slice())
+            .and_then(|reader| reader.take(MAX_SNAPSHOT_BYTES).read_to_string(&mut text).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Snapshot {} is corrupted: {}", path.display(), e))?;
+        text
+    } else {
+        String::from_utf8(bytes).map_err(|_| format!("Snapshot {} is corrupted: not UTF-8 text", path.display()))?
+    };
+
+    Snapshot::from_json(&text).map_err(|e| format!("Unable to load snapshot {}: {}", path.display(), e))
+}
+
+/// Writes a snapshot, refusing to replace a file that does not load; returns the backup path if one was made
+pub fn save_snapshot(path: &Path, snapshot: &mut Snapshot, options: &SnapshotOptions) -> Result<Option<PathBuf>, String> {
+    let mut backup = None;
+
+    if path.exists() {
+        load_snapshot(path).map_err(|e| format!("{}; not overwriting it (move it aside to start a new snapshot)", e))?;
+
+        if options.backup {
+            let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0);
+            let stamp: String = unix_to_rfc3339(secs, 0).chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+            let copy = PathBuf::from(format!("{}.{}.bak", path_to_string(path), stamp));
+            fs::copy(path, &copy).map_err(|e| format!("Unable to back up {} to {}: {}", path.display(), copy.display(), e))?;
+            backup = Some(copy);
+        }
+    }
+
+    snapshot.schema_version = SNAPSHOT_SCHEMA_VERSION;
+    snapshot.updated_at = now_rfc3339();
+    snapshot.tool_version = env!("CARGO_PKG_VERSION").to_string();
+    snapshot.migrated_from = None;
+
+    let json = serde_json::to_vec_pretty(snapshot).map_err(|e| format!("Unable to serialize snapshot: {}", e))?;
+    let bytes = if options.compress { gzip(&json) } else { json };
+
+    let temporary = PathBuf::from(format!("{}.tmp-{}", path_to_string(path), process::id()));
+    fs::write(&temporary, bytes).map_err(|e| format!("Unable to write {}: {}", temporary.display(), e))?;
+    fs::rename(&temporary, path).map_err(|e| {
+        let _ = fs::remove_file(&temporary);
+        format!("Unable to replace {}: {}", path.display(), e)
+    })?;
+
+    Ok(backup)
+}
+
+fn schema_version(document: &Value) -> Result<u32, String> {
+    let object = document.as_object().ok_or("not a JSON object")?;
+    let version = object.get("schema_version").or_else(|| object.get("version")).ok_or("no schema_version")?;
+
+    version.as_u64().and_then(|version| u32::try_from(version).ok()).ok_or_else(|| format!("invalid schema_version {}", version))
+}
+
+// Version 1: sections at the top level beside "version" and "created" (Unix seconds)
+fn migrate_v1(document: Value) -> Result<Value, String> {
+    let Value::Object(mut object) = document else {
+        return Err("not a JSON object".to_string());
+    };
+    object.remove("version");
+
+    let created = match object.remove("created") {
+        Some(Value::Number(secs)) => unix_to_rfc3339(secs.as_i64().ok_or("invalid created time")?, 0),
+        Some(other) => return Err(format!("invalid created time {}", other)),
+        None => return Err("no created time".to_string())
+    };
+
+    let mut migrated = Map::new();
+    migrated.insert("schema_version".to_string(), Value::from(2));
+    migrated.insert("created_at".to_string(), Value::from(created.clone()));
+    migrated.insert("updated_at".to_string(), Value::from(created));
+    // Version 1 files did not record who wrote them
+    migrated.insert("tool_version".to_string(), Value::from("unknown"));
+    migrated.insert("sections".to_string(), Value::Object(object));
+    Ok(Value::Object(migrated))
+}
+
+fn now_rfc3339() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    unix_to_rfc3339(now.as_secs() as i64, 0)
+}
+
+// Parses "path [migrate=true] [compress=true] [backup=true]"
+pub(crate) fn snapshot_info_from_args(input: &str) -> Result<Snapshot, String> {
+    let (path, pairs) = trailing_options(input, &["migrate", "compress", "backup"]);
+    let path = Path::new(path.trim());
+    let mut snapshot = load_snapshot(path)?;
+    let mut migrate = false;
+    let mut options = SnapshotOptions::default();
+
+    for (key, value) in pairs {
+        let flag = value.parse::<bool>().map_err(|_| format!("Invalid {} '{}' (expected true or false)", key, value))?;
+        match key {
+            "migrate" => migrate = flag,
+            "compress" => options.compress = flag,
+            _ => options.backup = flag
+        }
+    }
+
+    // Rewriting in the current layout; the migration note stays for the report
+    if migrate && snapshot.migrated_from.is_some() {
+        let from = snapshot.migrated_from;
+        save_snapshot(path, &mut snapshot, &options)?;
+        snapshot.migrated_from = from;
+    }
+    Ok(snapshot)
+}
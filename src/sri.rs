@@ -0,0 +1,549 @@
+/*
+    Subresource Integrity values and a page audit of third-party scripts and stylesheets
+
+    An integrity value is the base64 digest of the exact bytes served, prefixed with the hash
+    ("sha384-..."). The audit reads the script and stylesheet tags of a page (resolving
+    relative URLs against the page or its <base href>), and flags those loaded from another
+    origin without an integrity attribute, or with one but no crossorigin attribute (the
+    browser then fetches without CORS and must block the resource). With `fetch` each
+    third-party resource is downloaded, up to a size cap, to give the value it should carry
+    and to check the one it has.
+*/
+
+use std::{
+    fmt,
+    fs,
+    time::Duration
+};
+
+use reqwest::Url;
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::{
+    config,
+    finding::{Finding, Severity},
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net,
+    runtime,
+    trailing_options
+};
+
+/// Hash used for an integrity value; SRI allows only these three
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SriAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512
+}
+
+impl SriAlgorithm {
+    pub fn parse(name: &str) -> Result<SriAlgorithm, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(SriAlgorithm::Sha256),
+            "sha384" => Ok(SriAlgorithm::Sha384),
+            "sha512" => Ok(SriAlgorithm::Sha512),
+            _ => Err(format!("Unknown SRI algorithm '{}' (sha256, sha384 or sha512)", name))
+        }
+    }
+
+    /// The integrity value of `bytes`, such as "sha384-oqVuAfXRKap7fdgcCY5uykM6+R9GqQ8K/uxy9rx7HNQlGYl1kPzQho1wx4JwY8wC"
+    pub fn integrity(&self, bytes: &[u8]) -> String {
+        let digest = match self {
+            SriAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+            SriAlgorithm::Sha384 => Sha384::digest(bytes).to_vec(),
+            SriAlgorithm::Sha512 => Sha512::digest(bytes).to_vec()
+        };
+        format!("{}-{}", self, base64::encode(digest))
+    }
+}
+
+impl fmt::Display for SriAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SriAlgorithm::Sha256 => write!(f, "sha256"),
+            SriAlgorithm::Sha384 => write!(f, "sha384"),
+            SriAlgorithm::Sha512 => write!(f, "sha512")
+        }
+    }
+}
+
+/// Options for `mercy_sri` and `mercy_sri_audit`
+#[derive(Debug, Clone)]
+pub struct SriOptions {
+    /// Download third-party resources during an audit to compute and check their integrity
+    pub fetch: bool,
+    /// Base URL for the relative references of a page read from a file
+    pub base: Option<String>,
+    /// Largest resource downloaded
+    pub max_bytes: usize,
+    pub timeout: Duration,
+    pub identity: HttpIdentity
+}
+
+impl Default for SriOptions {
+    fn default() -> Self {
+        SriOptions {
+            fetch: false,
+            base: None,
+            max_bytes: 5 * 1024 * 1024,
+            timeout: config::read_timeout(Duration::from_secs(10)),
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// Integrity values of one resource
+#[derive(Debug, Clone, Serialize)]
+pub struct SriHashes {
+    pub source: String,
+    pub size: usize,
+    pub sha256: String,
+    pub sha384: String,
+    pub sha512: String
+}
+
+impl SriHashes {
+    pub fn of(source: &str, bytes: &[u8]) -> SriHashes {
+        SriHashes {
+            source: source.to_string(),
+            size: bytes.len(),
+            sha256: SriAlgorithm::Sha256.integrity(bytes),
+            sha384: SriAlgorithm::Sha384.integrity(bytes),
+            sha512: SriAlgorithm::Sha512.integrity(bytes)
+        }
+    }
+
+    pub fn get(&self, algorithm: SriAlgorithm) -> &str {
+        match algorithm {
+            SriAlgorithm::Sha256 => &self.sha256,
+            SriAlgorithm::Sha384 => &self.sha384,
+            SriAlgorithm::Sha512 => &self.sha512
+        }
+    }
+}
+
+impl fmt::Display for SriHashes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({} bytes)", self.source, self.size)?;
+        writeln!(f, "{}", self.sha256)?;
+        writeln!(f, "{}", self.sha384)?;
+        writeln!(f, "{}", self.sha512)
+    }
+}
+
+/// A script or stylesheet reference found on a page
+#[derive(Debug, Clone, Serialize)]
+pub struct SriTag {
+    /// "script" or "link"
+    pub tag: String,
+    /// The reference resolved against the page
+    pub url: String,
+    /// Served from another origin than the page
+    pub third_party: bool,
+    pub integrity: Option<String>,
+    pub crossorigin: Option<String>,
+    /// The value the tag should carry, when the resource was fetched
+    pub expected: Option<String>,
+    /// Whether the fetched resource matches the tag's integrity value
+    pub verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+/// Script and stylesheet tags of a page with their findings
+#[derive(Debug, Clone, Serialize)]
+pub struct SriAudit {
+    pub page: String,
+    pub tags: Vec<SriTag>,
+    pub findings: Vec<Finding>
+}
+
+impl fmt::Display for SriAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let third_party = self.tags.iter().filter(|tag| tag.third_party).count();
+        writeln!(f, "Page: {} ({} script/stylesheet tags, {} third-party)", self.page, self.tags.len(), third_party)?;
+
+        for tag in &self.tags {
+            let origin = if tag.third_party { "third-party" } else { "same origin" };
+            writeln!(f, "  <{}> {} ({})", tag.tag, tag.url, origin)?;
+            if let Some(integrity) = &tag.integrity {
+                writeln!(f, "    integrity: {}", integrity)?;
+            }
+            if let Some(crossorigin) = &tag.crossorigin {
+                writeln!(f, "    crossorigin: {}", crossorigin)?;
+            }
+            if let Some(expected) = &tag.expected {
+                writeln!(f, "    expected: {}", expected)?;
+            }
+            if let Some(error) = &tag.error {
+                writeln!(f, "    error: {}", error)?;
+            }
+        }
+
+        for finding in &self.findings {
+            writeln!(f)?;
+            write!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// Integrity values of a resource at a URL (fetched up to `options.max_bytes`) or in a local file
+pub fn mercy_sri(source: &str, options: &SriOptions) -> Result<SriHashes, String> {
+    let source = source.trim();
+    let bytes = if is_url(source) {
+        runtime::block_on(fetch_capped(&http_client(&options.identity)?, source, options))?
+    } else {
+        fs::read(source).map_err(|e| format!("Unable to read {}: {}", source, e))?
+    };
+
+    Ok(SriHashes::of(source, &bytes))
+}
+
+/// Audits the script and stylesheet tags of a page at a URL or in a local HTML file
+pub fn mercy_sri_audit(page: &str, options: &SriOptions) -> Result<SriAudit, String> {
+    let page = page.trim();
+    let client = http_client(&options.identity)?;
+
+    let (html, mut base) = if is_url(page) {
+        let html = runtime::block_on(fetch_capped(&client, page, options))?;
+        (String::from_utf8_lossy(&html).to_string(), Some(Url::parse(page).map_err(|e| format!("Invalid URL '{}': {}", page, e))?))
+    } else {
+        let html = fs::read(page).map_err(|e| format!("Unable to read {}: {}", page, e))?;
+        let base = options.base.as_deref().map(|base| Url::parse(base).map_err(|e| format!("Invalid base URL '{}': {}", base, e))).transpose()?;
+        (String::from_utf8_lossy(&html).to_string(), base)
+    };
+    let page_origin = base.as_ref().map(Url::origin);
+
+    let mut tags = Vec::new();
+    for tag in html_tags(&html) {
+        // <base href> changes how every later relative reference resolves
+        if tag.name == "base" {
+            if let Some(href) = tag.attribute("href") {
+                base = match &base {
+                    Some(current) => current.join(href).ok().or(base),
+                    None => Url::parse(href).ok()
+                };
+            }
+            continue;
+        }
+
+        let reference = match tag.name.as_str() {
+            "script" => tag.attribute("src"),
+            "link" if loads_code(&tag) => tag.attribute("href"),
+            _ => None
+        };
+        let Some(reference) = reference.filter(|reference| !reference.trim().is_empty()) else {
+            continue;
+        };
+
+        let resolved = match &base {
+            Some(base) => base.join(reference.trim()).ok(),
+            None => Url::parse(reference.trim()).ok()
+        };
+        let third_party = match (&resolved, &page_origin) {
+            (Some(url), Some(origin)) => url.origin() != *origin,
+            // A page without a base URL: only absolute references can be elsewhere
+            (Some(url), None) => url.has_host(),
+            (None, _) => reference.trim().starts_with("//")
+        };
+
+        tags.push(SriTag {
+            tag: tag.name.clone(),
+            url: resolved.map(|url| url.to_string()).unwrap_or_else(|| reference.to_string()),
+            third_party,
+            integrity: tag.attribute("integrity").map(|value| value.trim().to_string()).filter(|value| !value.is_empty()),
+            crossorigin: tag.attribute("crossorigin").map(str::to_string),
+            expected: None,
+            verified: None,
+            error: None
+        });
+    }
+
+    if options.fetch {
+        runtime::block_on(verify_tags(&client, &mut tags, options));
+    }
+
+    let findings = tags.iter().filter(|tag| tag.third_party).flat_map(|tag| tag_findings(page, tag)).collect();
+    Ok(SriAudit { page: page.to_string(), tags, findings })
+}
+
+fn is_url(source: &str) -> bool {
+    let lowered = source.to_ascii_lowercase();
+    lowered.starts_with("http://") || lowered.starts_with("https://")
+}
+
+// Stylesheets, module preloads and script/style preloads
+fn loads_code(tag: &HtmlTag) -> bool {
+    let rel = tag.attribute("rel").unwrap_or("").to_ascii_lowercase();
+    let preloads = tag.attribute("as").is_some_and(|kind| matches!(kind.to_ascii_lowercase().as_str(), "script" | "style"));
+
+    rel.split_ascii_whitespace().any(|token| token == "stylesheet" || token == "modulepreload" || (token == "preload" && preloads))
+}
+
+// Downloads a resource, refusing ones over the size cap
+async fn fetch_capped(client: &reqwest::Client, url: &str, options: &SriOptions) -> Result<Vec<u8>, String> {
+    let mut response = net::send(client.get(url).timeout(options.timeout)).await.map_err(|e| format!("Unable to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Unable to fetch {}: HTTP {}", url, response.status().as_u16()));
+    }
+    if response.content_length().is_some_and(|length| length > options.max_bytes as u64) {
+        return Err(format!("{} is larger than the {} byte limit", url, options.max_bytes));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("Unable to fetch {}: {}", url, e))? {
+        if body.len() + chunk.len() > options.max_bytes {
+            return Err(format!("{} is larger than the {} byte limit", url, options.max_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+// Fetches every third-party resource once and checks the integrity values its tags carry
+async fn verify_tags(client: &reqwest::Client, tags: &mut [SriTag], options: &SriOptions) {
+    let mut fetched: Vec<(String, Result<SriHashes, String>)> = Vec::new();
+
+    for tag in tags.iter_mut().filter(|tag| tag.third_party) {
+        if !fetched.iter().any(|(url, _)| *url == tag.url) {
+            let hashes = fetch_capped(client, &tag.url, options).await.map(|bytes| SriHashes::of(&tag.url, &bytes));
+            fetched.push((tag.url.clone(), hashes));
+        }
+
+        match fetched.iter().find(|(url, _)| *url == tag.url).map(|(_, hashes)| hashes) {
+            Some(Ok(hashes)) => {
+                let listed = integrity_values(tag.integrity.as_deref().unwrap_or(""));
+                // Browsers only check the values of the strongest algorithm listed
+                let strongest = listed.iter().map(|(algorithm, _)| *algorithm).max();
+                tag.expected = Some(hashes.get(strongest.unwrap_or(SriAlgorithm::Sha384)).to_string());
+                tag.verified = strongest.map(|strongest| listed.iter().any(|(algorithm, value)| *algorithm == strongest && value == hashes.get(strongest)));
+            },
+            Some(Err(e)) => tag.error = Some(e.clone()),
+            None => {}
+        }
+    }
+}
+
+// The "algorithm-base64" values of an integrity attribute, without their "?options"
+fn integrity_values(attribute: &str) -> Vec<(SriAlgorithm, String)> {
+    attribute.split_ascii_whitespace()
+        .filter_map(|value| {
+            let value = value.split('?').next().unwrap_or(value);
+            let (algorithm, _) = value.split_once('-')?;
+            Some((SriAlgorithm::parse(algorithm).ok()?, value.to_string()))
+        })
+        .collect()
+}
+
+fn tag_findings(page: &str, tag: &SriTag) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let element = format!("<{} {}=\"{}\">", tag.tag, if tag.tag == "script" { "src" } else { "href" }, tag.url);
+
+    match &tag.integrity {
+        None => {
+            let fix = match &tag.expected {
+                Some(expected) => format!("Add integrity=\"{}\" crossorigin=\"anonymous\", or serve the file from the site's own origin", expected),
+                None => "Add an integrity attribute (sha384) and crossorigin=\"anonymous\", or serve the file from the site's own origin".to_string()
+            };
+            findings.push(Finding::new(
+                "sri.missing-integrity",
+                "Third-party resource loaded without Subresource Integrity",
+                Severity::Medium,
+                page,
+                &format!("{} has no integrity attribute", element),
+                &fix
+            ));
+        },
+        Some(integrity) => {
+            if tag.crossorigin.is_none() {
+                findings.push(Finding::new(
+                    "sri.missing-crossorigin",
+                    "Integrity attribute without crossorigin",
+                    Severity::Low,
+                    page,
+                    &format!("{} has integrity=\"{}\" but no crossorigin attribute", element, integrity),
+                    "Add crossorigin=\"anonymous\"; without a CORS request the browser cannot check the integrity and blocks the resource"
+                ));
+            }
+            if integrity_values(integrity).is_empty() {
+                findings.push(Finding::new(
+                    "sri.invalid-integrity",
+                    "Integrity attribute has no usable value",
+                    Severity::Medium,
+                    page,
+                    &format!("{} has integrity=\"{}\"", element, integrity),
+                    "Use sha256-, sha384- or sha512- followed by the base64 digest; browsers ignore anything else and load the resource unchecked"
+                ));
+            }
+            if tag.verified == Some(false) {
+                findings.push(Finding::new(
+                    "sri.integrity-mismatch",
+                    "Resource does not match its integrity value",
+                    Severity::High,
+                    page,
+                    &format!("{} has integrity=\"{}\" but the resource served now hashes to {}", element, integrity, tag.expected.as_deref().unwrap_or("?")),
+                    "Browsers block this resource; find out why it changed (a new release or tampering) before updating the value"
+                ));
+            }
+        }
+    }
+
+    findings
+}
+
+// An HTML start tag with its attributes, names lowercased
+struct HtmlTag {
+    name: String,
+    attributes: Vec<(String, String)>
+}
+
+impl HtmlTag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+// The script, link and base start tags of a page, skipping comments and script bodies
+fn html_tags(html: &str) -> Vec<HtmlTag> {
+    let mut tags = Vec::new();
+    let mut position = 0;
+
+    while let Some(found) = html[position..].find('<') {
+        let start = position + found;
+        if html[start..].starts_with("<!--") {
+            position = html[start..].find("-->").map(|end| start + end + 3).unwrap_or(html.len());
+            continue;
+        }
+
+        let name_end = html[start + 1..].find(|c: char| !c.is_ascii_alphanumeric()).map(|end| start + 1 + end).unwrap_or(html.len());
+        let name = html[start + 1..name_end].to_ascii_lowercase();
+        let (attributes, end) = attributes(html, name_end);
+        position = end;
+
+        if !matches!(name.as_str(), "script" | "link" | "base") {
+            continue;
+        }
+        if name == "script" {
+            // The body of a script is not markup
+            let lowered = html[position..].to_ascii_lowercase();
+            position = lowered.find("</script").map(|close| position + close).unwrap_or(html.len());
+        }
+
+        tags.push(HtmlTag { name, attributes });
+    }
+
+    tags
+}
+
+// Attributes from `position` to the end of the tag, and the index after its '>'
+fn attributes(html: &str, mut position: usize) -> (Vec<(String, String)>, usize) {
+    let bytes = html.as_bytes();
+    let mut attributes = Vec::new();
+
+    loop {
+        while position < bytes.len() && (bytes[position].is_ascii_whitespace() || bytes[position] == b'/') {
+            position += 1;
+        }
+        if position >= bytes.len() {
+            return (attributes, position);
+        }
+        if bytes[position] == b'>' {
+            return (attributes, position + 1);
+        }
+
+        let name_start = position;
+        while position < bytes.len() && !bytes[position].is_ascii_whitespace() && !matches!(bytes[position], b'=' | b'>' | b'/') {
+            position += 1;
+        }
+        let name = html[name_start..position].to_ascii_lowercase();
+
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        let mut value = String::new();
+        if position < bytes.len() && bytes[position] == b'=' {
+            position += 1;
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if position < bytes.len() && matches!(bytes[position], b'"' | b'\'') {
+                let quote = bytes[position];
+                let value_start = position + 1;
+                let value_end = bytes[value_start..].iter().position(|byte| *byte == quote).map(|end| value_start + end).unwrap_or(bytes.len());
+                value = decode_entities(&html[value_start..value_end]);
+                position = (value_end + 1).min(bytes.len());
+            } else {
+                let value_start = position;
+                while position < bytes.len() && !bytes[position].is_ascii_whitespace() && bytes[position] != b'>' {
+                    position += 1;
+                }
+                value = decode_entities(&html[value_start..position]);
+            }
+        }
+
+        if !name.is_empty() {
+            attributes.push((name, value));
+        } else {
+            position += 1;
+        }
+    }
+}
+
+// The character references that show up in URLs and integrity values
+fn decode_entities(value: &str) -> String {
+    value.replace("&quot;", "\"").replace("&#39;", "'").replace("&#x27;", "'").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+// Parses "url_or_path [algorithm=sha384|all] [max_bytes=5242880] [timeout=10]" plus HTTP identity options
+pub(crate) fn sri_from_args(input: &str) -> Result<(SriHashes, Option<SriAlgorithm>), String> {
+    let keys: Vec<&str> = ["algorithm", "max_bytes", "timeout"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (source, pairs) = trailing_options(input, &keys);
+    let mut options = SriOptions::default();
+    let mut algorithm = Some(SriAlgorithm::Sha384);
+
+    for (key, value) in pairs {
+        match key {
+            "algorithm" if value == "all" => algorithm = None,
+            "algorithm" => algorithm = Some(SriAlgorithm::parse(value)?),
+            _ => common_option(&mut options, key, value)?
+        }
+    }
+
+    if source.is_empty() {
+        return Err("No URL or file specified for sri".to_string());
+    }
+    Ok((mercy_sri(&source, &options)?, algorithm))
+}
+
+// Parses "url_or_path [fetch=true] [base=https://example.com/] [max_bytes=5242880] [timeout=10]" plus HTTP identity options
+pub(crate) fn sri_audit_from_args(input: &str) -> Result<SriAudit, String> {
+    let keys: Vec<&str> = ["fetch", "base", "max_bytes", "timeout"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (page, pairs) = trailing_options(input, &keys);
+    let mut options = SriOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "fetch" => options.fetch = value == "true",
+            "base" => options.base = Some(value.to_string()),
+            _ => common_option(&mut options, key, value)?
+        }
+    }
+
+    if page.is_empty() {
+        return Err("No page URL or file specified for sri_audit".to_string());
+    }
+    mercy_sri_audit(&page, &options)
+}
+
+fn common_option(options: &mut SriOptions, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "max_bytes" => options.max_bytes = value.parse().map_err(|_| format!("Invalid max_bytes '{}'", value))?,
+        "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+        _ => identity_option(&mut options.identity, key, value)?
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+/*
+    ssdeep context-triggered piecewise hashing (spamsum)
+
+    A rolling hash over a 7-byte window marks chunk boundaries wherever it hits the block
+    size; each chunk contributes one base64 character of an FNV hash, so a local change only
+    alters the characters of the chunks it touches. Digests read "blocksize:chunks:chunks"
+    with the second part at twice the block size, and are produced and compared the way
+    ssdeep 2.13 does, so scores agree with the ssdeep tool.
+
+    Two digests can only score above zero when their block sizes are equal or a factor of
+    two apart and they share a 7-character run at a common block size, which is what makes
+    indexing large sets by (block size, 7-gram) exact rather than an approximation.
+*/
+
+use crate::stats::{self, Counter};
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u32 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A parsed ssdeep digest with runs of more than three identical characters cut to three, as compared
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SsdeepDigest {
+    pub(crate) block_size: u32,
+    pub(crate) first: Vec<u8>,
+    pub(crate) second: Vec<u8>
+}
+
+impl SsdeepDigest {
+    pub(crate) fn parse(digest: &str) -> Result<SsdeepDigest, String> {
+        let invalid = || format!("Invalid ssdeep digest '{}'", digest);
+        let mut parts = digest.splitn(3, ':');
+        let block_size: u32 = parts.next().and_then(|size| size.parse().ok()).ok_or_else(invalid)?;
+        let first = parts.next().ok_or_else(invalid)?;
+        // A file name may follow the digest, as in ssdeep's CSV output
+        let second = parts.next().ok_or_else(invalid)?.split(',').next().unwrap_or("");
+
+        Ok(SsdeepDigest { block_size, first: strip_sequences(first.as_bytes()), second: strip_sequences(second.as_bytes()) })
+    }
+
+    /// Every 7-character run of each part, keyed by the block size that part was hashed at
+    pub(crate) fn grams(&self) -> Vec<(u32, u64)> {
+        let mut grams = Vec::new();
+
+        for (size, part) in [(self.block_size, &self.first), (self.block_size.saturating_mul(2), &self.second)] {
+            for window in part.windows(ROLLING_WINDOW) {
+                grams.push((size, window.iter().fold(0u64, |value, c| (value << 8) | *c as u64)));
+            }
+        }
+
+        grams.sort_unstable();
+        grams.dedup();
+        grams
+    }
+}
+
+// Rolling hash over the last ROLLING_WINDOW bytes
+#[derive(Default)]
+struct Roll {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize
+}
+
+impl Roll {
+    fn update(&mut self, byte: u8) -> u32 {
+        let slot = self.n % ROLLING_WINDOW;
+        self.h2 = self.h2.wrapping_sub(self.h1).wrapping_add(ROLLING_WINDOW as u32 * byte as u32);
+        self.h1 = self.h1.wrapping_add(byte as u32).wrapping_sub(self.window[slot] as u32);
+        self.window[slot] = byte;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ byte as u32;
+        self.sum()
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// ssdeep digest of data as "blocksize:chunk hashes:chunk hashes at twice the block size"
+pub fn ssdeep_hash(data: &[u8]) -> String {
+    stats::count(Counter::BytesHashed, data.len() as u64);
+    let mut block_size = MIN_BLOCKSIZE;
+    while (block_size as usize) * SPAMSUM_LENGTH < data.len() {
+        block_size *= 2;
+    }
+
+    loop {
+        let mut roll = Roll::default();
+        let (mut h1, mut h2) = (HASH_INIT, HASH_INIT);
+        let (mut first, mut second) = (Vec::with_capacity(SPAMSUM_LENGTH), Vec::with_capacity(SPAMSUM_LENGTH / 2));
+
+        for byte in data {
+            h1 = h1.wrapping_mul(HASH_PRIME) ^ *byte as u32;
+            h2 = h2.wrapping_mul(HASH_PRIME) ^ *byte as u32;
+            let rolling = roll.update(*byte);
+
+            if rolling % block_size == block_size - 1 {
+                if first.len() < SPAMSUM_LENGTH - 1 {
+                    first.push(B64[(h1 % 64) as usize]);
+                    h1 = HASH_INIT;
+                }
+                if rolling % (block_size * 2) == block_size * 2 - 1 && second.len() < SPAMSUM_LENGTH / 2 - 1 {
+                    second.push(B64[(h2 % 64) as usize]);
+                    h2 = HASH_INIT;
+                }
+            }
+        }
+
+        // Too few chunks for a useful digest: try again at half the block size
+        if block_size > MIN_BLOCKSIZE && first.len() < SPAMSUM_LENGTH / 2 {
+            block_size /= 2;
+            continue;
+        }
+
+        if roll.sum() != 0 {
+            first.push(B64[(h1 % 64) as usize]);
+            second.push(B64[(h2 % 64) as usize]);
+        }
+
+        return format!("{}:{}:{}", block_size, String::from_utf8_lossy(&first), String::from_utf8_lossy(&second));
+    }
+}
+
+/// Similarity of two ssdeep digests from 0 (unrelated) to 100, as `ssdeep -d` scores it
+pub fn ssdeep_compare(a: &str, b: &str) -> Result<u32, String> {
+    Ok(compare_digests(&SsdeepDigest::parse(a)?, &SsdeepDigest::parse(b)?))
+}
+
+pub(crate) fn compare_digests(a: &SsdeepDigest, b: &SsdeepDigest) -> u32 {
+    if a.block_size == b.block_size && a.first == b.first {
+        return 100;
+    }
+
+    if a.block_size == b.block_size {
+        score_strings(&a.first, &b.first, a.block_size).max(score_strings(&a.second, &b.second, a.block_size.saturating_mul(2)))
+    } else if a.block_size == b.block_size.saturating_mul(2) {
+        score_strings(&a.first, &b.second, a.block_size)
+    } else if b.block_size == a.block_size.saturating_mul(2) {
+        score_strings(&a.second, &b.first, b.block_size)
+    } else {
+        0
+    }
+}
+
+fn score_strings(a: &[u8], b: &[u8], block_size: u32) -> u32 {
+    if a.len() > SPAMSUM_LENGTH || b.len() > SPAMSUM_LENGTH || !a.windows(ROLLING_WINDOW).any(|window| b.windows(ROLLING_WINDOW).any(|other| other == window)) {
+        return 0;
+    }
+
+    // Edit distance scaled to the digest length, then turned into a match percentage
+    let distance = edit_distance(a, b) as u32;
+    let scaled = distance * SPAMSUM_LENGTH as u32 / (a.len() + b.len()) as u32;
+    let score = 100u32.saturating_sub(100 * scaled / SPAMSUM_LENGTH as u32);
+
+    // Small block sizes: cap the score so short digests cannot claim a close match
+    if block_size >= (99 + ROLLING_WINDOW as u32) / ROLLING_WINDOW as u32 * MIN_BLOCKSIZE {
+        return score;
+    }
+
+    score.min(block_size / MIN_BLOCKSIZE * a.len().min(b.len()) as u32)
+}
+
+// Insertions and deletions cost 1, substitutions 2
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + if x == y { 0 } else { 2 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+// Runs of more than three identical characters carry no extra information
+fn strip_sequences(part: &[u8]) -> Vec<u8> {
+    let mut stripped: Vec<u8> = Vec::with_capacity(part.len());
+
+    for c in part {
+        if stripped.len() >= 3 && stripped[stripped.len() - 3..].iter().all(|previous| previous == c) {
+            continue;
+        }
+        stripped.push(*c);
+    }
+
+    stripped
+}
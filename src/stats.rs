@@ -0,0 +1,160 @@
+/*
+    Operation counters for long-running callers
+
+    Process-wide counters of DNS queries, HTTP clients, WHOIS lookups, cache hits and
+    misses, failed calls by kind, and bytes hashed or hex-dumped. Each is a relaxed atomic
+    add at the point the work happens, so counting costs next to nothing and is safe from
+    any thread. `mercy_stats` takes a snapshot; `reset_mercy_stats` zeroes the counters and
+    returns what they held, for per-interval reporting.
+*/
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering}
+};
+
+use serde::Serialize;
+
+// One slot per counter in COUNTERS
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Counter {
+    DnsQueries,
+    DnsTimeouts,
+    HttpRequests,
+    WhoisLookups,
+    CacheHits,
+    CacheMisses,
+    ErrorsTimeout,
+    ErrorsNetwork,
+    ErrorsIo,
+    ErrorsInput,
+    ErrorsOther,
+    BytesHashed,
+    BytesHexDumped
+}
+
+const COUNTER_COUNT: usize = Counter::BytesHexDumped as usize + 1;
+
+static COUNTERS: [AtomicU64; COUNTER_COUNT] = [const { AtomicU64::new(0) }; COUNTER_COUNT];
+
+/// Snapshot of the operation counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct MercyStats {
+    /// DNS queries sent (every resolver-based feature: lookups, sinkhole and ASN checks...)
+    pub dns_queries: u64,
+    pub dns_timeouts: u64,
+    /// HTTP-based operations started (one client per InQuest, OSV or page fetch call)
+    pub http_requests: u64,
+    pub whois_lookups: u64,
+    /// Answers reused from a cache (ASN prefixes, loaded prefix lists)
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Failed string dispatcher calls that timed out
+    pub errors_timeout: u64,
+    /// Failed calls that could not connect or send
+    pub errors_network: u64,
+    /// Failed calls that could not read or write a file
+    pub errors_io: u64,
+    /// Failed calls rejecting their input
+    pub errors_input: u64,
+    pub errors_other: u64,
+    /// Bytes through the hash functions (SHA-256, MD5, ssdeep, file hashing)
+    pub bytes_hashed: u64,
+    pub bytes_hex_dumped: u64
+}
+
+impl MercyStats {
+    /// Failed calls of every kind
+    pub fn errors(&self) -> u64 {
+        self.errors_timeout + self.errors_network + self.errors_io + self.errors_input + self.errors_other
+    }
+}
+
+impl fmt::Display for MercyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "DNS queries: {} ({} timed out)", self.dns_queries, self.dns_timeouts)?;
+        writeln!(f, "HTTP requests: {}", self.http_requests)?;
+        writeln!(f, "WHOIS lookups: {}", self.whois_lookups)?;
+        writeln!(f, "Cache: {} hits, {} misses", self.cache_hits, self.cache_misses)?;
+        writeln!(f, "Errors: {} (timeout {}, network {}, io {}, input {}, other {})", self.errors(), self.errors_timeout, self.errors_network, self.errors_io, self.errors_input, self.errors_other)?;
+        writeln!(f, "Bytes hashed: {}", self.bytes_hashed)?;
+        writeln!(f, "Bytes hex dumped: {}", self.bytes_hex_dumped)
+    }
+}
+
+/// Adds to a counter
+pub(crate) fn count(counter: Counter, amount: u64) {
+    COUNTERS[counter as usize].fetch_add(amount, Ordering::Relaxed);
+}
+
+// What a failure message describes, by the words in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorKind {
+    Timeout,
+    Quota,
+    Network,
+    Io,
+    Input,
+    Other
+}
+
+/// Sorts a failure message by what it describes (shared with `MercyError`)
+pub(crate) fn classify_error(message: &str) -> ErrorKind {
+    let lowered = message.to_ascii_lowercase();
+    if lowered.contains("timed out") || lowered.contains("timeout") {
+        ErrorKind::Timeout
+    } else if lowered.contains("http 429") || lowered.contains("too many requests") || lowered.contains("rate limit") || lowered.contains("quota") {
+        ErrorKind::Quota
+    } else if lowered.contains("connect") || lowered.contains("error sending request") || lowered.contains("unable to send") || lowered.contains("unable to receive") {
+        ErrorKind::Network
+    } else if lowered.contains("unable to read") || lowered.contains("unable to open") || lowered.contains("unable to write") || lowered.contains("unable to create") || lowered.contains("unable to locate") {
+        ErrorKind::Io
+    } else if lowered.contains("invalid") || lowered.starts_with("no ") || lowered.contains("expected") {
+        ErrorKind::Input
+    } else {
+        ErrorKind::Other
+    }
+}
+
+/// Counts a failed call under the kind its message describes (quota refusals count as network errors)
+pub(crate) fn count_error(message: &str) {
+    let counter = match classify_error(message) {
+        ErrorKind::Timeout => Counter::ErrorsTimeout,
+        ErrorKind::Quota | ErrorKind::Network => Counter::ErrorsNetwork,
+        ErrorKind::Io => Counter::ErrorsIo,
+        ErrorKind::Input => Counter::ErrorsInput,
+        ErrorKind::Other => Counter::ErrorsOther
+    };
+
+    count(counter, 1);
+}
+
+/// Current values of the operation counters
+pub fn mercy_stats() -> MercyStats {
+    snapshot(|counter| counter.load(Ordering::Relaxed))
+}
+
+/// Zeroes the operation counters, returning the values they held
+pub fn reset_mercy_stats() -> MercyStats {
+    snapshot(|counter| counter.swap(0, Ordering::Relaxed))
+}
+
+fn snapshot(read: impl Fn(&AtomicU64) -> u64) -> MercyStats {
+    let value = |counter: Counter| read(&COUNTERS[counter as usize]);
+
+    MercyStats {
+        dns_queries: value(Counter::DnsQueries),
+        dns_timeouts: value(Counter::DnsTimeouts),
+        http_requests: value(Counter::HttpRequests),
+        whois_lookups: value(Counter::WhoisLookups),
+        cache_hits: value(Counter::CacheHits),
+        cache_misses: value(Counter::CacheMisses),
+        errors_timeout: value(Counter::ErrorsTimeout),
+        errors_network: value(Counter::ErrorsNetwork),
+        errors_io: value(Counter::ErrorsIo),
+        errors_input: value(Counter::ErrorsInput),
+        errors_other: value(Counter::ErrorsOther),
+        bytes_hashed: value(Counter::BytesHashed),
+        bytes_hex_dumped: value(Counter::BytesHexDumped)
+    }
+}
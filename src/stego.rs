@@ -0,0 +1,496 @@
+/*
+    Heuristic steganography checks for PNG and JPEG images
+
+    Three independent signals are scored from 0 to 1: data appended after the image's end
+    marker, unusual PNG chunks, and the randomness of each channel's least significant bit
+    plane compared with the planes above it. None of them proves that anything is hidden (an
+    editor's private chunk or a noisy sensor can raise a score), and nothing is decoded from
+    the pixels. The only data ever extracted is an appended blob, which is plain bytes that
+    follow the image.
+
+    LSB embedding replaces the lowest bit of each sample with message bits, which are close to
+    random. In a natural image that plane keeps some of the structure seen in the planes above
+    it, so a lowest plane that is uniformly random while the next one is not is suspicious.
+    The comparison is made over the whole image and over horizontal bands, since sequential
+    embedding fills the top of the image first. JPEG stores DCT coefficients rather than
+    pixels, so the plane comparison is made for PNG only.
+*/
+
+use std::{fmt, fs, path::Path};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{checksum::crc32_update, entropy::shannon, exif::image_end, paths::path_to_string, raster::read_png_samples, trailing_options};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+// Largest file read
+const MAX_FILE: u64 = 1024 * 1024 * 1024;
+
+// Ancillary chunks above this size are reported; real metadata (ICC profiles, EXIF) is far smaller
+const MAX_ANCILLARY: usize = 256 * 1024;
+
+// Horizontal bands compared on their own, and the fewest 2x2 blocks a band needs to be scored
+const BANDS: usize = 8;
+const MIN_BLOCKS: u64 = 1024;
+
+// The lowest plane counts as random above this normalized entropy
+const RANDOM_PLANE: f64 = 0.99;
+
+// Registered PNG chunk types, including APNG and the PNG extensions
+const PNG_CHUNKS: &[&[u8; 4]] = &[
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP",
+    b"mDCV", b"cLLI", b"tEXt", b"zTXt", b"iTXt", b"bKGD", b"hIST", b"pHYs", b"sPLT", b"eXIf", b"tIME",
+    b"acTL", b"fcTL", b"fdAT", b"oFFs", b"pCAL", b"sCAL", b"sTER", b"gIFg", b"gIFx", b"gIFt", b"fRAc", b"dSIG"
+];
+
+// Private chunks written by common software (Apple, Adobe Fireworks, Photoshop, ImageMagick)
+const KNOWN_PRIVATE: &[&[u8; 4]] = &[
+    b"iDOT", b"CgBI", b"vpAg", b"caNv", b"orNT", b"mkBF", b"mkBS", b"mkBT", b"mkTS", b"prVW", b"npTc", b"skMf"
+];
+
+// Signatures looked for at the start of an appended blob
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"PK\x03\x04", "zip"),
+    (b"PK\x05\x06", "zip (empty)"),
+    (b"Rar!\x1A\x07", "rar"),
+    (b"7z\xBC\xAF\x27\x1C", "7z"),
+    (b"\x1F\x8B", "gzip"),
+    (b"BZh", "bzip2"),
+    (b"\xFD7zXZ\x00", "xz"),
+    (b"%PDF", "pdf"),
+    (b"MZ", "pe"),
+    (b"\x7FELF", "elf"),
+    (b"\xCF\xFA\xED\xFE", "mach-o"),
+    (b"\xCA\xFE\xBA\xBE", "mach-o (universal) or java class"),
+    (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "ole (office document)"),
+    (b"\x89PNG\r\n\x1A\n", "png"),
+    (b"\xFF\xD8\xFF", "jpeg"),
+    (b"GIF8", "gif"),
+    (b"-----BEGIN PGP", "pgp armor"),
+    (b"-----BEGIN ", "pem"),
+    (b"#!", "script"),
+    (b"<?php", "php"),
+    (b"<script", "html script"),
+    (b"<html", "html"),
+    (b"Salted__", "openssl encrypted")
+];
+
+/// Bytes stored after an image's end marker
+#[derive(Debug, Clone, Serialize)]
+pub struct AppendedData {
+    pub offset: u64,
+    pub size: u64,
+    /// Shannon entropy in bits per byte
+    pub entropy: f64,
+    pub sha256: String,
+    /// File type recognised from the blob's leading bytes (after any zero padding)
+    pub kind: Option<String>,
+    /// Where the blob was written, when extraction was asked for
+    pub extracted_to: Option<String>
+}
+
+/// A PNG chunk that a normal encoder would not write
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkAnomaly {
+    pub offset: u64,
+    pub chunk: String,
+    pub size: u64,
+    pub issue: String,
+    /// How strongly the anomaly suggests hidden data, from 0 to 1
+    pub score: f64
+}
+
+/// Statistics of one bit plane of one channel over the whole image
+#[derive(Debug, Clone, Serialize)]
+pub struct BitPlane {
+    pub channel: String,
+    /// 0 is the least significant bit
+    pub plane: u8,
+    /// Share of set bits
+    pub ones: f64,
+    /// Entropy of the plane's 2x2 bit patterns, from 0 (constant) to 1 (random)
+    pub entropy: f64
+}
+
+/// One heuristic, scored on its own
+#[derive(Debug, Clone, Serialize)]
+pub struct StegoSignal {
+    /// "appended_data", "png_chunks" or "lsb_planes"
+    pub name: String,
+    /// False when the check does not apply to the format
+    pub applicable: bool,
+    /// From 0 (nothing unusual) to 1 (strongly suspicious)
+    pub score: f64,
+    /// Score of at least 0.5
+    pub suspicious: bool,
+    pub summary: String
+}
+
+/// Heuristic steganography signals for an image
+#[derive(Debug, Clone, Serialize)]
+pub struct StegoReport {
+    pub path: String,
+    /// "png" or "jpeg"
+    pub format: String,
+    pub size: u64,
+    /// States that the signals are heuristics, not proof
+    pub note: String,
+    pub signals: Vec<StegoSignal>,
+    pub appended_data: Option<AppendedData>,
+    pub chunk_anomalies: Vec<ChunkAnomaly>,
+    /// Planes of each channel, lowest first (PNG only)
+    pub bit_planes: Vec<BitPlane>
+}
+
+impl fmt::Display for StegoReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({}, {} bytes)", self.path, self.format.to_uppercase(), self.size)?;
+        writeln!(f, "{}\n", self.note)?;
+
+        for signal in &self.signals {
+            let verdict = match (signal.applicable, signal.suspicious) {
+                (false, _) => "n/a",
+                (true, true) => "SUSPICIOUS",
+                (true, false) => "ok"
+            };
+            writeln!(f, "{:<14} {:.2} {:<10} {}", signal.name, signal.score, verdict, signal.summary)?;
+        }
+
+        if let Some(appended) = &self.appended_data {
+            writeln!(f, "\nAppended data: {} bytes at offset {}, entropy {:.2} bits/byte, type {} (sha256 {})",
+                appended.size, appended.offset, appended.entropy, appended.kind.as_deref().unwrap_or("unknown"), appended.sha256)?;
+            if let Some(output) = &appended.extracted_to {
+                writeln!(f, "Extracted to {}", output)?;
+            }
+        }
+
+        if !self.chunk_anomalies.is_empty() {
+            writeln!(f, "\nChunk anomalies:")?;
+            for anomaly in &self.chunk_anomalies {
+                writeln!(f, "  {} at offset {} ({} bytes): {}", anomaly.chunk, anomaly.offset, anomaly.size, anomaly.issue)?;
+            }
+        }
+
+        if !self.bit_planes.is_empty() {
+            writeln!(f, "\nBit plane entropy (plane 0 first):")?;
+            for planes in self.bit_planes.chunk_by(|a, b| a.channel == b.channel) {
+                let values: Vec<String> = planes.iter().map(|plane| format!("{:.3}", plane.entropy)).collect();
+                writeln!(f, "  {:<6} {}", planes[0].channel, values.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Scores a PNG or JPEG for appended data, unusual PNG chunks and a random LSB plane; nothing is extracted
+pub fn mercy_stego_check<P: AsRef<Path>>(path: P) -> Result<StegoReport, String> {
+    let path = path.as_ref();
+    let data = read_image(path)?;
+    let format = if data.starts_with(&PNG_SIGNATURE) { "png" } else { "jpeg" };
+    let end = image_end(&data);
+
+    let appended_data = end.filter(|end| *end < data.len()).map(|end| appended(&data, end));
+    let mut signals = vec![appended_signal(end, appended_data.as_ref())];
+
+    let mut chunk_anomalies = Vec::new();
+    let mut bit_planes = Vec::new();
+
+    if format == "png" {
+        chunk_anomalies = png_anomalies(&data);
+        signals.push(chunk_signal(&chunk_anomalies));
+
+        let (planes, signal) = plane_signal(&data);
+        bit_planes = planes;
+        signals.push(signal);
+    } else {
+        signals.push(not_applicable("png_chunks", "Not a PNG"));
+        signals.push(not_applicable("lsb_planes", "JPEG stores DCT coefficients, so pixel bit planes carry no embedding signal"));
+    }
+
+    Ok(StegoReport {
+        path: path_to_string(path),
+        format: format.to_string(),
+        size: data.len() as u64,
+        note: "Heuristic triage only: each signal is scored on its own, a high score is a reason to look closer rather than proof of hidden data, and a low score does not rule it out".to_string(),
+        signals,
+        appended_data,
+        chunk_anomalies,
+        bit_planes
+    })
+}
+
+/// The bytes stored after a PNG or JPEG's end marker, the only content this module extracts
+pub fn mercy_stego_extract<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, String> {
+    let path = path.as_ref();
+    let data = read_image(path)?;
+    match image_end(&data) {
+        Some(end) if end < data.len() => Ok(data[end..].to_vec()),
+        Some(_) => Err(format!("{} has no data after the end of the image", path.display())),
+        None => Err(format!("Unable to find the end of the image in {}", path.display()))
+    }
+}
+
+fn read_image(path: &Path) -> Result<Vec<u8>, String> {
+    let size = fs::metadata(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?.len();
+    if size > MAX_FILE {
+        return Err(format!("{} is larger than the {} byte limit", path.display(), MAX_FILE));
+    }
+
+    let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    if !data.starts_with(&PNG_SIGNATURE) && !data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Err(format!("{} is not a PNG or JPEG image", path.display()));
+    }
+
+    Ok(data)
+}
+
+fn not_applicable(name: &str, summary: &str) -> StegoSignal {
+    StegoSignal { name: name.to_string(), applicable: false, score: 0.0, suspicious: false, summary: summary.to_string() }
+}
+
+fn signal(name: &str, score: f64, summary: String) -> StegoSignal {
+    let score = round(score.clamp(0.0, 1.0));
+    StegoSignal { name: name.to_string(), applicable: true, score, suspicious: score >= 0.5, summary }
+}
+
+/* Appended data */
+
+fn appended(data: &[u8], end: usize) -> AppendedData {
+    let blob = &data[end..];
+    let mut histogram = [0u64; 256];
+    for byte in blob {
+        histogram[*byte as usize] += 1;
+    }
+
+    AppendedData {
+        offset: end as u64,
+        size: blob.len() as u64,
+        entropy: round(shannon(&histogram, blob.len() as u64)),
+        sha256: format!("{:x}", Sha256::digest(blob)),
+        kind: identify(blob),
+        extracted_to: None
+    }
+}
+
+// Type of a blob from its leading bytes, skipping zero padding; falls back to "text" when it is all printable
+fn identify(blob: &[u8]) -> Option<String> {
+    let start = blob.iter().position(|byte| *byte != 0)?;
+    let blob = &blob[start..];
+
+    if let Some((_, kind)) = MAGIC.iter().find(|(magic, _)| blob.starts_with(magic)) {
+        return Some(kind.to_string());
+    }
+
+    blob.iter()
+        .all(|byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace())
+        .then(|| "text".to_string())
+}
+
+fn appended_signal(end: Option<usize>, appended: Option<&AppendedData>) -> StegoSignal {
+    let Some(appended) = appended else {
+        return match end {
+            Some(_) => signal("appended_data", 0.0, "Nothing after the end of the image".to_string()),
+            None => signal("appended_data", 0.0, "No end of image marker found (truncated or corrupt), so appended data cannot be measured".to_string())
+        };
+    };
+
+    let (score, reason) = match &appended.kind {
+        Some(kind) if kind != "text" => (1.0, format!("a {} file", kind)),
+        _ if appended.entropy >= 7.5 => (0.9, "high-entropy (compressed or encrypted) data".to_string()),
+        // A few stray zero bytes or a trailing newline is left by some encoders
+        None if appended.size <= 16 => (0.2, "padding".to_string()),
+        Some(_) => (0.7, "text".to_string()),
+        None => (0.7, "unidentified data".to_string())
+    };
+
+    signal("appended_data", score, format!("{} bytes after the end of the image: {}", appended.size, reason))
+}
+
+/* PNG chunks */
+
+// Unregistered types, oversized ancillary chunks, bad CRCs, split IDAT runs and truncation
+fn png_anomalies(data: &[u8]) -> Vec<ChunkAnomaly> {
+    let mut anomalies = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+    let mut idat_runs = 0;
+    let mut previous: Option<[u8; 4]> = None;
+
+    while position + 8 <= data.len() {
+        let length = u32::from_be_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]]) as usize;
+        let kind = [data[position + 4], data[position + 5], data[position + 6], data[position + 7]];
+        let name = String::from_utf8_lossy(&kind).to_string();
+        let mut report = |issue: String, score: f64| anomalies.push(ChunkAnomaly { offset: position as u64, chunk: name.clone(), size: length as u64, issue, score });
+
+        let Some(body) = data.get(position + 8..position + 8 + length) else {
+            report("Chunk runs past the end of the file".to_string(), 0.5);
+            break;
+        };
+        let Some(stored) = data.get(position + 8 + length..position + 12 + length) else {
+            report("Chunk has no CRC".to_string(), 0.5);
+            break;
+        };
+
+        let ancillary = kind[0].is_ascii_lowercase();
+        if !kind.iter().all(u8::is_ascii_alphabetic) {
+            report("Chunk type is not four letters".to_string(), 0.8);
+        } else if !PNG_CHUNKS.contains(&&kind) && !KNOWN_PRIVATE.contains(&&kind) {
+            match ancillary {
+                true => report("Unregistered ancillary chunk".to_string(), 0.5),
+                false => report("Unregistered critical chunk (decoders must reject the image)".to_string(), 0.7)
+            }
+        }
+
+        if ancillary && length > MAX_ANCILLARY {
+            report(format!("Ancillary chunk is larger than {} KiB", MAX_ANCILLARY / 1024), 0.6);
+        }
+
+        if crc32_update(crc32_update(0xFFFF_FFFF, &kind), body) ^ 0xFFFF_FFFF != u32::from_be_bytes([stored[0], stored[1], stored[2], stored[3]]) {
+            report("Bad CRC (chunk edited after encoding)".to_string(), 0.4);
+        }
+
+        if &kind == b"IDAT" && previous != Some(kind) {
+            idat_runs += 1;
+            if idat_runs == 2 {
+                report("IDAT chunks are not consecutive".to_string(), 0.3);
+            }
+        }
+
+        previous = Some(kind);
+        position += 12 + length;
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+
+    anomalies
+}
+
+fn chunk_signal(anomalies: &[ChunkAnomaly]) -> StegoSignal {
+    let score = anomalies.iter().map(|anomaly| anomaly.score).fold(0.0, f64::max);
+    let summary = match anomalies.len() {
+        0 => "Only registered chunks, all with valid CRCs".to_string(),
+        count => {
+            let chunks: Vec<&str> = anomalies.iter().map(|anomaly| anomaly.chunk.as_str()).collect();
+            format!("{} anomal{} ({})", count, if count == 1 { "y" } else { "ies" }, chunks.join(", "))
+        }
+    };
+    signal("png_chunks", score, summary)
+}
+
+/* Bit planes */
+
+// 2x2 pattern counts for each band and plane of one channel
+type PlaneCounts = Vec<[[u64; 16]; 8]>;
+
+fn plane_signal(data: &[u8]) -> (Vec<BitPlane>, StegoSignal) {
+    let image = match read_png_samples(data) {
+        Ok(image) => image,
+        Err(e) => return (Vec::new(), signal("lsb_planes", 0.0, format!("Pixels not analysed: {}", e)))
+    };
+
+    let count = image.channels.len();
+    let bands = if image.height >= BANDS * 16 { BANDS } else { 1 };
+    let mut planes = Vec::new();
+    let mut best = (0.0, String::new());
+
+    for (channel, name) in image.channels.iter().enumerate() {
+        let mut counts: PlaneCounts = vec![[[0; 16]; 8]; bands];
+
+        for y in (0..image.height - image.height % 2).step_by(2) {
+            let band = (y * bands / image.height).min(bands - 1);
+            for x in (0..image.width - image.width % 2).step_by(2) {
+                let sample = |dx: usize, dy: usize| image.samples[((y + dy) * image.width + x + dx) * count + channel];
+                let quad = [sample(0, 0), sample(1, 0), sample(0, 1), sample(1, 1)];
+                for (plane, histogram) in counts[band].iter_mut().enumerate() {
+                    let pattern = quad.iter().enumerate().fold(0, |pattern, (bit, value)| pattern | (((value >> plane) & 1) as usize) << bit);
+                    histogram[pattern] += 1;
+                }
+            }
+        }
+
+        let mut whole = [[0u64; 16]; 8];
+        for band in &counts {
+            for (plane, histogram) in band.iter().enumerate() {
+                for (pattern, value) in histogram.iter().enumerate() {
+                    whole[plane][pattern] += value;
+                }
+            }
+        }
+
+        for (plane, histogram) in whole.iter().enumerate() {
+            let total: u64 = histogram.iter().sum();
+            let ones: u64 = histogram.iter().enumerate().map(|(pattern, count)| pattern.count_ones() as u64 * count).sum();
+            planes.push(BitPlane {
+                channel: name.to_string(),
+                plane: plane as u8,
+                ones: round(if total == 0 { 0.0 } else { ones as f64 / (total * 4) as f64 }),
+                entropy: round(pattern_entropy(histogram))
+            });
+        }
+
+        let regions = std::iter::once((whole, "whole image".to_string()))
+            .chain(counts.iter().enumerate().filter(|_| bands > 1).map(|(band, histograms)| (*histograms, format!("band {} of {}", band + 1, bands))));
+
+        for (histograms, region) in regions {
+            if histograms[0].iter().sum::<u64>() < MIN_BLOCKS {
+                continue;
+            }
+
+            let (lowest, next) = (pattern_entropy(&histograms[0]), pattern_entropy(&histograms[1]));
+            let score = if lowest >= RANDOM_PLANE { (lowest - next) / 0.1 } else { 0.0 };
+            if score > best.0 {
+                best = (score, format!("{} LSB plane is random (entropy {:.3}) while plane 1 is structured ({:.3}) in the {}", name, lowest, next, region));
+            }
+        }
+    }
+
+    let summary = if best.0 > 0.0 {
+        best.1
+    } else {
+        "LSB planes are no more random than the planes above them".to_string()
+    };
+
+    (planes, signal("lsb_planes", best.0, summary))
+}
+
+// Entropy of 2x2 bit patterns, normalized to 0..1
+fn pattern_entropy(histogram: &[u64; 16]) -> f64 {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    histogram.iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total as f64;
+            p * (1.0 / p).log2()
+        })
+        .sum::<f64>() / 4.0
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+// Parses "path [out=file]"; "out" writes any appended blob to the file
+pub(crate) fn stego_check_from_args(input: &str) -> Result<StegoReport, String> {
+    let (path, pairs) = trailing_options(input, &["out"]);
+    if path.is_empty() {
+        return Err("No path specified for stego_check".to_string());
+    }
+
+    let mut report = mercy_stego_check(&path)?;
+
+    if let Some((_, output)) = pairs.last() {
+        let appended = report.appended_data.as_mut().ok_or_else(|| format!("{} has no appended data to extract", path))?;
+        let blob = mercy_stego_extract(&path)?;
+        fs::write(output, blob).map_err(|e| format!("Unable to write {}: {}", output, e))?;
+        appended.extracted_to = Some(output.to_string());
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,195 @@
+/*
+    Printable strings in binary files, like `strings`
+
+    Two encodings are searched in one pass: runs of printable ASCII (with tabs), and UTF-16LE
+    runs of the same characters each followed by a zero byte, the wide strings Windows
+    binaries are full of and plain `strings` skips unless asked. A UTF-16LE run may start at
+    an even or an odd offset, so both alignments are followed at once.
+
+    The file is read in chunks and every scanner carries its partial run from one chunk to the
+    next, so a string crossing a chunk boundary is found whole at its true offset and memory
+    stays flat apart from the strings found.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::{filemap::ScanFile, paths::path_to_string, trailing_options};
+
+const READ_CHUNK: usize = 1024 * 1024;
+
+/// Encoding a string was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StringEncoding {
+    Ascii,
+    Utf16le
+}
+
+/// A printable run and where it starts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExtractedString {
+    pub offset: u64,
+    pub encoding: StringEncoding,
+    pub text: String
+}
+
+/// Options for `mercy_strings`
+#[derive(Debug, Clone)]
+pub struct StringsOptions {
+    /// Shortest run kept, in characters
+    pub min_length: usize,
+    pub ascii: bool,
+    pub utf16le: bool,
+    /// Prefix each line of the text output with the offset in hex
+    pub offsets: bool
+}
+
+impl Default for StringsOptions {
+    fn default() -> Self {
+        StringsOptions { min_length: 4, ascii: true, utf16le: true, offsets: false }
+    }
+}
+
+/// The strings of a file in offset order
+#[derive(Debug, Clone, Serialize)]
+pub struct StringsReport {
+    pub path: String,
+    pub size: u64,
+    pub min_length: usize,
+    pub strings: Vec<ExtractedString>,
+    #[serde(skip)]
+    offsets: bool
+}
+
+impl fmt::Display for StringsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, string) in self.strings.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            if self.offsets {
+                write!(f, "{:7x} ", string.offset)?;
+            }
+            write!(f, "{}", string.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Extracts the ASCII and UTF-16LE strings of at least `options.min_length` characters from `path`
+pub fn mercy_strings<P: AsRef<Path>>(path: P, options: &StringsOptions) -> Result<StringsReport, String> {
+    strings_in_chunks(path.as_ref(), options, READ_CHUNK)
+}
+
+// Scans with reads of `chunk` bytes; small chunks put strings across boundaries (self-test)
+pub(crate) fn strings_in_chunks(path: &Path, options: &StringsOptions, chunk: usize) -> Result<StringsReport, String> {
+    if options.min_length == 0 {
+        return Err("The minimum string length must be at least 1".to_string());
+    }
+    if !options.ascii && !options.utf16le {
+        return Err("No encoding selected (ascii, utf16le or all)".to_string());
+    }
+
+    let file = ScanFile::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+    let mut ascii = Run::new(StringEncoding::Ascii);
+    // UTF-16LE characters starting at even and at odd offsets
+    let mut wide = [Run::new(StringEncoding::Utf16le), Run::new(StringEncoding::Utf16le)];
+    let mut low = [None::<u8>; 2];
+    let mut found = Vec::new();
+
+    let mut position: u64 = 0;
+    loop {
+        let bytes = file.read_at(position, chunk.max(2)).map_err(|e| format!("Unable to read {} at offset {}: {}", path.display(), position, e))?;
+        if bytes.is_empty() {
+            break;
+        }
+
+        for byte in bytes.iter() {
+            if options.ascii {
+                if printable(*byte) {
+                    ascii.push(position, *byte);
+                } else {
+                    ascii.end(options.min_length, &mut found);
+                }
+            }
+
+            if options.utf16le {
+                // The byte completes a character of one alignment and may start one of the other
+                let completing = ((position + 1) % 2) as usize;
+                if let Some(first) = low[completing].take() {
+                    if printable(first) && *byte == 0 {
+                        wide[completing].push(position - 1, first);
+                    } else {
+                        wide[completing].end(options.min_length, &mut found);
+                    }
+                }
+                low[(position % 2) as usize] = Some(*byte);
+            }
+
+            position += 1;
+        }
+    }
+
+    ascii.end(options.min_length, &mut found);
+    for run in &mut wide {
+        run.end(options.min_length, &mut found);
+    }
+    found.sort_by_key(|string| string.offset);
+
+    Ok(StringsReport { path: path_to_string(path), size: position, min_length: options.min_length, strings: found, offsets: options.offsets })
+}
+
+// A run of printable characters in progress
+struct Run {
+    encoding: StringEncoding,
+    start: u64,
+    text: String
+}
+
+impl Run {
+    fn new(encoding: StringEncoding) -> Run {
+        Run { encoding, start: 0, text: String::new() }
+    }
+
+    fn push(&mut self, offset: u64, byte: u8) {
+        if self.text.is_empty() {
+            self.start = offset;
+        }
+        self.text.push(byte as char);
+    }
+
+    // Keeps the run if it is long enough and starts a new one
+    fn end(&mut self, min_length: usize, found: &mut Vec<ExtractedString>) {
+        if self.text.len() >= min_length {
+            found.push(ExtractedString { offset: self.start, encoding: self.encoding, text: std::mem::take(&mut self.text) });
+        }
+        self.text.clear();
+    }
+}
+
+fn printable(byte: u8) -> bool {
+    (0x20..=0x7E).contains(&byte) || byte == b'\t'
+}
+
+// Parses "path [min=4] [encoding=ascii|utf16le|all] [offsets=true]"
+pub(crate) fn strings_from_args(input: &str) -> Result<StringsReport, String> {
+    let (path, pairs) = trailing_options(input, &["min", "encoding", "offsets"]);
+    let mut options = StringsOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "min" => options.min_length = value.parse().map_err(|_| format!("Invalid minimum length '{}'", value))?,
+            "encoding" => (options.ascii, options.utf16le) = match value {
+                "ascii" => (true, false),
+                "utf16le" | "utf16" | "wide" => (false, true),
+                "all" => (true, true),
+                _ => return Err(format!("Unknown encoding '{}' (expected ascii, utf16le or all)", value))
+            },
+            _ => options.offsets = value == "true"
+        }
+    }
+
+    mercy_strings(path, &options)
+}
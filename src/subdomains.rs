@@ -0,0 +1,142 @@
+/*
+    Subdomain enumeration from certificate-transparency logs
+
+    Every publicly trusted certificate is logged, so the names on the certificates issued for
+    a domain's subdomains give a footprint of its hosts without sending the target a packet.
+    crt.sh searches the logs; its JSON answer for "%.domain" lists each certificate with its
+    names in "name_value", several per entry separated by newlines. Those are split, wildcard
+    prefixes dropped ("*.dev.example.com" names dev.example.com), anything that is not a
+    hostname under the domain (email addresses of S/MIME certificates, other domains sharing a
+    certificate) set aside, and the rest sorted without duplicates.
+
+    crt.sh is slow and often answers 502 or 503 under load, so a failed request is tried once
+    more after a short pause (see net.rs). The answer is parsed in memory.
+*/
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    time::Duration
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    endpoints::service_endpoints,
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net::{self, SendOptions},
+    runtime,
+    trailing_options
+};
+
+// crt.sh takes a while over domains with many certificates
+const CRTSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Hostnames under a domain named by certificates in the certificate-transparency logs
+#[derive(Debug, Clone, Serialize)]
+pub struct Subdomains {
+    pub domain: String,
+    /// Certificate entries crt.sh returned
+    pub certificates: usize,
+    /// Sorted, each once, without the domain itself
+    pub subdomains: Vec<String>
+}
+
+impl fmt::Display for Subdomains {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.certificates, self.subdomains.is_empty()) {
+            (0, _) => write!(f, "No certificates found for {}", self.domain),
+            (certificates, true) => write!(f, "No subdomains of {} in {} certificate(s)", self.domain, certificates),
+            _ => write!(f, "{}", self.subdomains.join("\n"))
+        }
+    }
+}
+
+// The fields of a crt.sh entry used here
+#[derive(Deserialize)]
+struct CrtshEntry {
+    #[serde(default)]
+    common_name: Option<String>,
+    #[serde(default)]
+    name_value: Option<String>
+}
+
+/// Subdomains of `domain` from the certificates crt.sh knows, tried twice before giving up
+pub fn mercy_subdomains(domain: &str, timeout: Duration, identity: &HttpIdentity) -> Result<Subdomains, String> {
+    let domain = normalize_domain(domain)?;
+    let url = format!("{}/?q=%25.{}&output=json", service_endpoints().crtsh.trim_end_matches('/'), domain);
+    let client = http_client(identity)?;
+
+    // crt.sh is asked twice at most, whatever the config's retries
+    let options = SendOptions { retries: Some(1), ..SendOptions::default() };
+    let body = runtime::block_on(async {
+        let sent = net::send_with(client.get(&url).timeout(timeout), &options).await.map_err(|e| format!("crt.sh lookup failed: {}", e))?;
+        let status = sent.response.status();
+        if !status.is_success() {
+            return Err(format!("crt.sh answered HTTP {}", status.as_u16()));
+        }
+        sent.response.text().await.map_err(|e| format!("crt.sh lookup failed: {}", e))
+    })?;
+
+    subdomains_from_crtsh(&domain, &body)
+}
+
+/// Parses a crt.sh JSON answer into the sorted, unique hostnames under `domain`
+pub(crate) fn subdomains_from_crtsh(domain: &str, body: &str) -> Result<Subdomains, String> {
+    let domain = normalize_domain(domain)?;
+
+    // An empty body is how crt.sh sometimes says it found nothing
+    let entries: Vec<CrtshEntry> = match body.trim() {
+        "" => Vec::new(),
+        json => serde_json::from_str(json).map_err(|e| format!("crt.sh sent an answer that is not its JSON list ({})", e))?
+    };
+
+    let suffix = format!(".{}", domain);
+    let names: BTreeSet<String> = entries.iter()
+        .flat_map(|entry| entry.name_value.iter().chain(&entry.common_name))
+        .flat_map(|names| names.lines())
+        .filter_map(|name| {
+            let name = name.trim().trim_end_matches('.').to_ascii_lowercase();
+            let name = name.trim_start_matches("*.");
+            (name.ends_with(&suffix) && is_hostname(name)).then(|| name.to_string())
+        })
+        .collect();
+
+    Ok(Subdomains { domain, certificates: entries.len(), subdomains: names.into_iter().collect() })
+}
+
+// Lowercase, without a trailing dot or a wildcard, and shaped like a domain name
+fn normalize_domain(input: &str) -> Result<String, String> {
+    let domain = input.trim().trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_start_matches("*.").to_string();
+    if !domain.contains('.') || !is_hostname(&domain) {
+        return Err(format!("Invalid domain '{}' (expected a name such as example.com)", input.trim()));
+    }
+    Ok(domain)
+}
+
+// Letters, digits, hyphens and underscores in non-empty labels of at most 63 characters, punycode included
+fn is_hostname(name: &str) -> bool {
+    name.len() <= 253 && name.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63 && !label.starts_with('-') && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+// Parses "domain [timeout=30]" plus HTTP identity options
+pub(crate) fn subdomains_from_args(input: &str) -> Result<Subdomains, String> {
+    let keys: Vec<&str> = ["timeout"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (domain, pairs) = trailing_options(input, &keys);
+    let mut timeout = config::read_timeout(CRTSH_TIMEOUT);
+    let mut identity = HttpIdentity::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => identity_option(&mut identity, key, value)?
+        }
+    }
+
+    mercy_subdomains(&domain, timeout, &identity)
+}
@@ -0,0 +1,262 @@
+/*
+    Streaming tar reader (ustar, GNU long names and PAX headers)
+
+    Entries are visited in order without unpacking anything to disk. Data that is not read is
+    skipped, by seeking when the source is a file and by reading through it otherwise, so nested
+    archives (an image layer inside an image tarball) can be walked from a decompression stream.
+*/
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom}
+};
+
+const BLOCK: u64 = 512;
+
+/// A source a tar archive can be read from
+pub(crate) trait TarSource: Read {
+    /// Moves past `count` bytes without returning them
+    fn skip(&mut self, count: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut self.take(count), &mut io::sink())?;
+        if skipped < count {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "archive truncated"));
+        }
+        Ok(())
+    }
+}
+
+impl TarSource for File {
+    fn skip(&mut self, count: u64) -> io::Result<()> {
+        // Seeking past the end succeeds, so check against the length instead
+        let target = self.stream_position()? + count;
+        if target > self.metadata()?.len() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "archive truncated"));
+        }
+        self.seek(SeekFrom::Start(target)).map(|_| ())
+    }
+}
+
+/// An archive member
+#[derive(Debug, Clone)]
+pub(crate) struct TarEntry {
+    pub(crate) path: String,
+    /// Type flag: b'0' file, b'1' hard link, b'2' symlink, b'5' directory and so on
+    pub(crate) kind: u8,
+    pub(crate) size: u64,
+    pub(crate) link: String,
+    /// Offset of the entry's data from the start of the archive
+    pub(crate) data_offset: u64
+}
+
+impl TarEntry {
+    pub(crate) fn is_file(&self) -> bool {
+        self.kind == b'0' || self.kind == 0 || self.kind == b'7'
+    }
+}
+
+/// Reads entries one at a time from a tar stream
+pub(crate) struct TarReader<R> {
+    input: R,
+    position: u64,
+    // Unread data and padding of the current entry
+    data_left: u64,
+    padding: u64
+}
+
+impl<R: TarSource> TarReader<R> {
+    pub(crate) fn new(input: R) -> TarReader<R> {
+        TarReader { input, position: 0, data_left: 0, padding: 0 }
+    }
+
+    /// The next member, skipping whatever was not read of the previous one
+    pub(crate) fn next_entry(&mut self) -> Result<Option<TarEntry>, String> {
+        self.skip(self.data_left + self.padding)?;
+        self.data_left = 0;
+        self.padding = 0;
+
+        let mut long_path = None;
+        let mut long_link = None;
+
+        loop {
+            let offset = self.position;
+            let mut header = [0u8; BLOCK as usize];
+
+            match read_full(&mut self.input, &mut header).map_err(|e| format!("Tar read failed at offset {}: {}", offset, e))? {
+                // A clean end without the two zero blocks still ends the archive
+                0 => return Ok(None),
+                count if count < header.len() => return Err(format!("Tar header truncated at offset {}", offset)),
+                _ => {}
+            }
+            self.position += BLOCK;
+
+            if header.iter().all(|byte| *byte == 0) {
+                return Ok(None);
+            }
+
+            verify_checksum(&header).map_err(|e| format!("Corrupt tar header at offset {}: {}", offset, e))?;
+
+            let size = octal(&header[124..136]).ok_or_else(|| format!("Corrupt tar header at offset {}: invalid size field", offset))?;
+            let kind = header[156];
+            let padded = size.div_ceil(BLOCK) * BLOCK;
+
+            match kind {
+                // GNU long name / long link name: the data is the name of the next entry
+                b'L' | b'K' => {
+                    let name = self.metadata(size, padded, offset)?;
+                    let name = String::from_utf8_lossy(&name).trim_end_matches('\0').to_string();
+                    if kind == b'L' { long_path = Some(name) } else { long_link = Some(name) }
+                }
+                // PAX extended header for the next entry
+                b'x' => {
+                    let records = self.metadata(size, padded, offset)?;
+                    for (key, value) in pax_records(&records) {
+                        match key.as_str() {
+                            "path" => long_path = Some(value),
+                            "linkpath" => long_link = Some(value),
+                            _ => {}
+                        }
+                    }
+                }
+                // Global PAX headers carry nothing the walkers need
+                b'g' => self.skip(padded)?,
+                _ => {
+                    let path = long_path.take().unwrap_or_else(|| header_path(&header));
+                    let link = long_link.take().unwrap_or_else(|| field_string(&header[157..257]));
+
+                    // Links and directories have no data whatever the size field says
+                    let size = if matches!(kind, b'1' | b'2' | b'5') { 0 } else { size };
+                    self.data_left = size;
+                    self.padding = size.div_ceil(BLOCK) * BLOCK - size;
+
+                    return Ok(Some(TarEntry { path, kind, size, link, data_offset: self.position }));
+                }
+            }
+        }
+    }
+
+    /// Reads the current entry's data (as much as the caller consumes)
+    pub(crate) fn data(&mut self) -> EntryData<'_, R> {
+        EntryData { reader: self }
+    }
+
+    fn skip(&mut self, count: u64) -> Result<(), String> {
+        self.input.skip(count).map_err(|e| format!("Tar archive truncated at offset {}: {}", self.position, e))?;
+        self.position += count;
+        Ok(())
+    }
+
+    // Small metadata payloads (long names, PAX records)
+    fn metadata(&mut self, size: u64, padded: u64, offset: u64) -> Result<Vec<u8>, String> {
+        if size > 1024 * 1024 {
+            return Err(format!("Tar metadata entry at offset {} is {} bytes", offset, size));
+        }
+
+        let mut data = vec![0u8; padded as usize];
+        if read_full(&mut self.input, &mut data).map_err(|e| e.to_string())? < data.len() {
+            return Err(format!("Tar metadata entry at offset {} is truncated", offset));
+        }
+        self.position += padded;
+
+        data.truncate(size as usize);
+        Ok(data)
+    }
+}
+
+/// Reader over the current entry's data
+pub(crate) struct EntryData<'a, R> {
+    reader: &'a mut TarReader<R>
+}
+
+impl<R: TarSource> Read for EntryData<'_, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let wanted = (buffer.len() as u64).min(self.reader.data_left) as usize;
+        if wanted == 0 {
+            return Ok(0);
+        }
+
+        let count = self.reader.input.read(&mut buffer[..wanted])?;
+        if count == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "tar entry data truncated"));
+        }
+
+        self.reader.data_left -= count as u64;
+        self.reader.position += count as u64;
+        Ok(count)
+    }
+}
+
+// Fills the buffer unless the input ends first; returns the bytes read
+fn read_full<R: Read>(input: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(count) => filled += count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(filled)
+}
+
+fn verify_checksum(header: &[u8]) -> Result<(), String> {
+    let stored = octal(&header[148..156]).ok_or("invalid checksum field")?;
+    let computed: u64 = header.iter().enumerate()
+        .map(|(index, byte)| if (148..156).contains(&index) { b' ' as u64 } else { *byte as u64 })
+        .sum();
+
+    if stored != computed {
+        return Err(format!("checksum {} does not match {}", stored, computed));
+    }
+    Ok(())
+}
+
+// Octal numeric field, or GNU base-256 when the high bit of the first byte is set
+fn octal(field: &[u8]) -> Option<u64> {
+    if field.first().map(|byte| byte & 0x80 != 0).unwrap_or(false) {
+        return Some(field[1..].iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64));
+    }
+
+    let text = std::str::from_utf8(field).ok()?.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn field_string(field: &[u8]) -> String {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+// ustar splits long paths into a prefix and a name
+fn header_path(header: &[u8]) -> String {
+    let name = field_string(&header[..100]);
+    if &header[257..262] != b"ustar" {
+        return name;
+    }
+
+    let prefix = field_string(&header[345..500]);
+    if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) }
+}
+
+// "<length> <key>=<value>\n" records
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+
+    while let Some(space) = rest.iter().position(|byte| *byte == b' ') {
+        let length = match std::str::from_utf8(&rest[..space]).ok().and_then(|text| text.parse::<usize>().ok()) {
+            Some(length) if length > space && length <= rest.len() => length,
+            _ => break
+        };
+
+        let record = String::from_utf8_lossy(&rest[space + 1..length]).trim_end_matches('\n').to_string();
+        if let Some((key, value)) = record.split_once('=') {
+            records.push((key.to_string(), value.to_string()));
+        }
+        rest = &rest[length..];
+    }
+
+    records
+}
@@ -0,0 +1,519 @@
+/*
+    Local mock services for running the network code offline (the `testing` feature)
+
+    Each mock listens on an ephemeral loopback port on a thread of its own and stops when it
+    is dropped:
+
+    - `MockHttp` answers requests from a table of path prefixes with canned responses and
+      records the request lines and headers it received; a route limited with `times` stands
+      in for a service that fails a few times before it recovers
+    - `MockTcp` sends a banner on connect (SSH, FTP, SMTP style services) or answers one
+      request line, or a bulk query between "begin" and "end", the way a WHOIS server does,
+      recording the queries it answered
+    - `MockDns` answers UDP queries from a table of records, NXDOMAIN for unknown names
+
+    `MockServices` starts one of each with canned InQuest, WHOIS, Team Cymru, banner, IP
+    echo, Pwned Passwords, crt.sh and DNS data and points the InQuest, WHOIS, ASN WHOIS,
+    external IP, breached password and subdomain lookups at them with `set_service_endpoints` until it is dropped.
+    Downstream crates can use the same mocks for their own tests of code built on mercy.
+*/
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering}
+    },
+    thread,
+    time::Duration
+};
+
+use crate::{
+    dns::{DnsRdata, DnsResponse, DnsType, encode_name},
+    endpoints::{ServiceEndpoints, set_service_endpoints}
+};
+
+// How often the accept loops look at the stop flag
+const POLL: Duration = Duration::from_millis(10);
+
+// How long a connection may sit idle before the mock gives up on it
+const IDLE: Duration = Duration::from_secs(5);
+
+/// Domain the canned InQuest response classifies as malicious
+pub const MOCK_MALICIOUS_DOMAIN: &str = "evil.example";
+
+/// Canned InQuest DFI search response with one malicious and one suspicious sample
+pub const MOCK_INQUEST_MALICIOUS: &str = r#"{"success": true, "data": [
+    {"sha256": "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08", "classification": "MALICIOUS", "file_type": "DOC", "subcategory": "macro_hunter", "first_seen": "2024-03-01T10:00:00", "last_updated": "2024-03-05T12:00:00"},
+    {"sha256": "60303AE22B998861BCE3B28F33EEC1BE758A213C86C93C076DBE9F558C11C752", "classification": "SUSPICIOUS", "file_type": "PDF", "subcategory": "", "first_seen": "2024-03-02T08:30:00", "last_updated": "2024-03-02T08:30:00"}
+]}"#;
+
+/// SHA-256 the canned InQuest hash response classifies as malicious
+pub const MOCK_MALICIOUS_HASH: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+/// Canned InQuest DFI hash search response for `MOCK_MALICIOUS_HASH`
+pub const MOCK_INQUEST_HASH: &str = r#"{"success": true, "data": [
+    {"sha256": "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08", "md5": "098F6BCD4621D373CADE4E832627B4F6", "classification": "MALICIOUS", "file_type": "DOC", "subcategory": "macro_hunter", "first_seen": "2024-03-01T10:00:00", "last_updated": "2024-03-05T12:00:00"}
+]}"#;
+
+/// Canned InQuest response for a domain with no samples
+pub const MOCK_INQUEST_EMPTY: &str = r#"{"success": true, "data": []}"#;
+
+/// Canned WHOIS answer in the Verisign layout
+pub const MOCK_WHOIS: &str = "   Domain Name: EVIL.EXAMPLE\r\n   Registry Domain ID: 2336799_DOMAIN_COM-VRSN\r\n   Registrar: Example Registrar, Inc.\r\n   Updated Date: 2024-03-01T00:00:00Z\r\n   Creation Date: 2024-02-28T00:00:00Z\r\n   Registry Expiry Date: 2025-02-28T00:00:00Z\r\n   Name Server: NS1.EVIL.EXAMPLE\r\n>>> Last update of whois database: 2024-03-06T00:00:00Z <<<\r\n";
+
+/// Address the mock IP echo service answers with
+pub const MOCK_EXTERNAL_IP: &str = "203.0.113.7";
+
+/// Password the mock Pwned Passwords API reports as breached
+pub const MOCK_PWNED_PASSWORD: &str = "password123";
+
+/// Canned range API answer for the prefix of `MOCK_PWNED_PASSWORD`'s SHA-1 (CBFDA), padding included
+pub const MOCK_PWNED_RANGE: &str = "C5E4C1E5D0A9B3C0A6F4A1E9B0D1C2E3F40:0\r\nC6008F9CAB4083784CBD1874F76618D2A97:2254650\r\nD0B1C2A3E4F5061728394A5B6C7D8E9F0A1:3\r\n";
+
+/// Domain the canned crt.sh answer lists certificates for
+pub const MOCK_CRTSH_DOMAIN: &str = "corp.example";
+
+/// Canned crt.sh JSON answer: a multi-name entry, a wildcard, a repeat in another case, an email address and an unrelated name
+pub const MOCK_CRTSH: &str = r#"[
+    {"issuer_name": "C=US, O=Let's Encrypt, CN=R3", "common_name": "corp.example", "name_value": "corp.example\nwww.corp.example\nmail.corp.example", "id": 1001},
+    {"issuer_name": "C=US, O=Let's Encrypt, CN=R3", "common_name": "*.dev.corp.example", "name_value": "*.dev.corp.example", "id": 1002},
+    {"issuer_name": "C=US, O=Let's Encrypt, CN=R3", "common_name": "WWW.Corp.Example", "name_value": "WWW.Corp.Example\nvpn.corp.example.", "id": 1003},
+    {"issuer_name": "C=US, O=Example CA, CN=S/MIME", "common_name": "it@corp.example", "name_value": "it@corp.example", "id": 1004},
+    {"issuer_name": "C=US, O=Example CA, CN=Shared", "common_name": "cdn.other.example", "name_value": "cdn.other.example\napi.corp.example", "id": 1005}
+]"#;
+
+/// Canned Team Cymru verbose bulk answer: two announced addresses padded as the service pads them, one not announced
+pub const MOCK_CYMRU: &str = "Bulk mode; whois.cymru.com [2024-03-06 00:00:00 +0000]\n\
+AS      | IP               | BGP Prefix          | CC | Registry | Allocated  | AS Name\n\
+13335   | 1.1.1.1          | 1.1.1.0/24          | AU | apnic    | 2011-08-11 | CLOUDFLARENET, US\n\
+15169   | 8.8.8.8          | 8.8.8.0/24          | US | arin     | 2023-12-28 | GOOGLE, US\n\
+NA      | 100.128.0.1      | NA                  |    |          |            | NA\n";
+
+/// Canned SSH banner
+pub const MOCK_BANNER: &str = "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6\r\n";
+
+// Stops a mock's thread when the mock is dropped
+struct Stopper(Arc<AtomicBool>);
+
+impl Drop for Stopper {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A canned HTTP response for requests whose path starts with `path`
+#[derive(Debug, Clone)]
+pub struct MockRoute {
+    /// Prefix of the request target, query string included
+    pub path: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Wait before answering, to stand in for a slow service
+    pub delay: Duration,
+    /// Requests answered before the route steps aside for the next matching one; None for all of them
+    pub times: Option<usize>,
+    // Requests answered so far, shared by the clones serving each connection
+    served: Arc<AtomicUsize>
+}
+
+impl MockRoute {
+    pub fn new(path: &str, status: u16, body: &str) -> MockRoute {
+        MockRoute { path: path.to_string(), status, headers: Vec::new(), body: body.to_string(), delay: Duration::ZERO, times: None, served: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Adds a response header
+    pub fn header(mut self, name: &str, value: &str) -> MockRoute {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Answers only after `delay`
+    pub fn delay(mut self, delay: Duration) -> MockRoute {
+        self.delay = delay;
+        self
+    }
+
+    /// Answers the first `count` matching requests only; later ones go to the next longest matching route
+    pub fn times(mut self, count: usize) -> MockRoute {
+        self.times = Some(count);
+        self
+    }
+
+    // Counts a request against the route, false when it has answered all it will
+    fn claim(&self) -> bool {
+        match self.times {
+            Some(limit) => self.served.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |served| (served < limit).then_some(served + 1)).is_ok(),
+            None => true
+        }
+    }
+}
+
+/// A request line and its headers, names lowercased
+pub type MockRequest = (String, Vec<(String, String)>);
+
+/// HTTP/1.1 server answering from a route table; the longest matching prefix with answers left wins, anything else is 404
+pub struct MockHttp {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    _stop: Stopper
+}
+
+impl MockHttp {
+    pub fn start(routes: Vec<MockRoute>) -> Result<MockHttp, String> {
+        let (listener, addr, stop) = listen()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+
+        accept_loop(listener, Arc::clone(&stop), move |stream| {
+            let routes = routes.clone();
+            let seen = Arc::clone(&seen);
+            thread::spawn(move || serve_http(stream, &routes, &seen));
+        });
+
+        Ok(MockHttp { addr, requests, _stop: Stopper(stop) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// "http://127.0.0.1:port" followed by `path`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Request lines received so far, e.g. "GET /api/... HTTP/1.1"
+    pub fn requests(&self) -> Vec<String> {
+        self.received().into_iter().map(|(line, _)| line).collect()
+    }
+
+    /// Requests received so far with their headers
+    pub fn received(&self) -> Vec<MockRequest> {
+        self.requests.lock().map(|requests| requests.clone()).unwrap_or_default()
+    }
+}
+
+fn serve_http(stream: TcpStream, routes: &[MockRoute], seen: &Mutex<Vec<MockRequest>>) {
+    let _ = stream.set_read_timeout(Some(IDLE));
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return
+    });
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+    }
+    let request_line = request_line.trim_end().to_string();
+
+    // Headers, then a body of Content-Length bytes if there is one
+    let mut length = 0;
+    let mut received = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+            received.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let _ = reader.take(length).read_to_end(&mut Vec::new());
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+    if let Ok(mut seen) = seen.lock() {
+        seen.push((request_line, received));
+    }
+
+    let mut matching: Vec<&MockRoute> = routes.iter().filter(|route| target.starts_with(&route.path)).collect();
+    matching.sort_by_key(|route| std::cmp::Reverse(route.path.len()));
+    let route = matching.into_iter().find(|route| route.claim());
+    let (status, headers, body) = match route {
+        Some(route) => {
+            thread::sleep(route.delay);
+            (route.status, route.headers.clone(), route.body.clone())
+        },
+        None => (404, Vec::new(), "not found".to_string())
+    };
+
+    let mut response = format!("HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, reason(status), body.len());
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type")) {
+        response.push_str("Content-Type: application/json\r\n");
+    }
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(&body);
+
+    let mut stream = stream;
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        301 => "Moved Permanently",
+        302 => "Found",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Mock"
+    }
+}
+
+/// TCP service that sends a banner on connect, or answers one request line like WHOIS
+pub struct MockTcp {
+    addr: SocketAddr,
+    queries: Arc<Mutex<Vec<String>>>,
+    _stop: Stopper
+}
+
+impl MockTcp {
+    /// Sends `banner` to every client, then reads until the client hangs up or goes idle
+    pub fn banner(banner: &str) -> Result<MockTcp, String> {
+        let banner = banner.to_string();
+        MockTcp::start(Arc::default(), move |mut stream| {
+            let _ = stream.write_all(banner.as_bytes());
+            let _ = stream.read_to_end(&mut Vec::new());
+        })
+    }
+
+    /// Reads one line (the query), or every line up to "end" after a "begin", and answers `response`, then closes, as a WHOIS server does
+    pub fn whois(response: &str) -> Result<MockTcp, String> {
+        let response = response.to_string();
+        let queries: Arc<Mutex<Vec<String>>> = Arc::default();
+        let seen = Arc::clone(&queries);
+
+        MockTcp::start(queries, move |mut stream| {
+            let mut lines = Vec::new();
+            if let Ok(reader) = stream.try_clone() {
+                let mut reader = BufReader::new(reader);
+                let mut line = String::new();
+                let _ = reader.read_line(&mut line);
+                lines.push(line.trim_end().to_string());
+                if line.trim() == "begin" {
+                    while line.trim() != "end" {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            break;
+                        }
+                        lines.push(line.trim_end().to_string());
+                    }
+                }
+            }
+            if let Ok(mut seen) = seen.lock() {
+                seen.push(lines.join("\n"));
+            }
+            let _ = stream.write_all(response.as_bytes());
+        })
+    }
+
+    fn start(queries: Arc<Mutex<Vec<String>>>, handler: impl Fn(TcpStream) + Send + Sync + 'static) -> Result<MockTcp, String> {
+        let (listener, addr, stop) = listen()?;
+        let handler = Arc::new(handler);
+
+        accept_loop(listener, Arc::clone(&stop), move |stream| {
+            let handler = Arc::clone(&handler);
+            let _ = stream.set_read_timeout(Some(IDLE));
+            thread::spawn(move || handler(stream));
+        });
+
+        Ok(MockTcp { addr, queries, _stop: Stopper(stop) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The query of each connection a WHOIS mock answered, in order, its lines joined with "\n"
+    pub fn queries(&self) -> Vec<String> {
+        self.queries.lock().map(|queries| queries.clone()).unwrap_or_default()
+    }
+}
+
+// A non-blocking loopback listener on an ephemeral port and its stop flag
+fn listen() -> Result<(TcpListener, SocketAddr, Arc<AtomicBool>), String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| format!("Unable to start mock service: {}", e))?;
+    listener.set_nonblocking(true).map_err(|e| format!("Unable to start mock service: {}", e))?;
+    let addr = listener.local_addr().map_err(|e| format!("Unable to start mock service: {}", e))?;
+
+    Ok((listener, addr, Arc::new(AtomicBool::new(false))))
+}
+
+fn accept_loop(listener: TcpListener, stop: Arc<AtomicBool>, handle: impl Fn(TcpStream) + Send + 'static) {
+    thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    handle(stream);
+                },
+                Err(_) => thread::sleep(POLL)
+            }
+        }
+    });
+}
+
+/// UDP DNS server answering from a table of (name, type, record data) entries
+pub struct MockDns {
+    addr: SocketAddr,
+    _stop: Stopper
+}
+
+impl MockDns {
+    /// Answers queries for names in `records` with the records of the asked type (none is NOERROR
+    /// with no answers) and unknown names with NXDOMAIN
+    pub fn start(records: Vec<(String, DnsType, DnsRdata)>) -> Result<MockDns, String> {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).map_err(|e| format!("Unable to start mock DNS server: {}", e))?;
+        socket.set_read_timeout(Some(POLL * 5)).map_err(|e| format!("Unable to start mock DNS server: {}", e))?;
+        let addr = socket.local_addr().map_err(|e| format!("Unable to start mock DNS server: {}", e))?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1500];
+            while !stopped.load(Ordering::Relaxed) {
+                let Ok((length, client)) = socket.recv_from(&mut buffer) else {
+                    continue;
+                };
+                if let Some(answer) = dns_answer(&buffer[..length], &records) {
+                    let _ = socket.send_to(&answer, client);
+                }
+            }
+        });
+
+        Ok(MockDns { addr, _stop: Stopper(stop) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+fn dns_answer(query: &[u8], records: &[(String, DnsType, DnsRdata)]) -> Option<Vec<u8>> {
+    let parsed = DnsResponse::parse(query).ok()?;
+    let question = parsed.questions.first()?;
+    let name = question.name.trim_end_matches('.').to_ascii_lowercase();
+
+    let known = records.iter().any(|(owner, _, _)| owner.eq_ignore_ascii_case(&name));
+    let answers: Vec<(DnsType, &DnsRdata)> = records.iter()
+        .filter(|(owner, rtype, _)| owner.eq_ignore_ascii_case(&name) && (*rtype == question.qtype || question.qtype == DnsType::Any))
+        .map(|(_, rtype, rdata)| (*rtype, rdata))
+        .collect();
+
+    // Response, recursion desired and available; NXDOMAIN for names not in the table
+    let flags: u16 = 0x8180 | if known { 0 } else { 3 };
+    let mut packet = Vec::new();
+    packet.extend(parsed.id.to_be_bytes());
+    packet.extend(flags.to_be_bytes());
+    packet.extend(1u16.to_be_bytes());
+    packet.extend((answers.len() as u16).to_be_bytes());
+    packet.extend([0, 0, 0, 0]);
+    encode_name(&question.name, &mut packet).ok()?;
+    packet.extend(question.qtype.code().to_be_bytes());
+    packet.extend(1u16.to_be_bytes());
+
+    for (rtype, rdata) in answers {
+        let mut data = Vec::new();
+        match rdata {
+            DnsRdata::A(addr) => data.extend(addr.octets()),
+            DnsRdata::Aaaa(addr) => data.extend(addr.octets()),
+            DnsRdata::Name(target) => encode_name(target, &mut data).ok()?,
+            DnsRdata::Mx { preference, exchange } => {
+                data.extend(preference.to_be_bytes());
+                encode_name(exchange, &mut data).ok()?;
+            },
+            DnsRdata::Txt(parts) => {
+                for part in parts {
+                    data.push(part.len().min(255) as u8);
+                    data.extend(&part.as_bytes()[..part.len().min(255)]);
+                }
+            },
+            _ => return None
+        }
+
+        // Owner name as a pointer to the question, class IN, TTL 300
+        packet.extend([0xC0, 0x0C]);
+        packet.extend(rtype.code().to_be_bytes());
+        packet.extend(1u16.to_be_bytes());
+        packet.extend(300u32.to_be_bytes());
+        packet.extend((data.len() as u16).to_be_bytes());
+        packet.extend(data);
+    }
+
+    Some(packet)
+}
+
+/// One of each mock with canned data, wired into the InQuest, WHOIS, ASN WHOIS, external IP, breached password and subdomain lookups while it lives
+pub struct MockServices {
+    pub http: MockHttp,
+    pub whois: MockTcp,
+    /// Team Cymru's WHOIS service, answering `MOCK_CYMRU`
+    pub cymru: MockTcp,
+    pub banner: MockTcp,
+    pub dns: MockDns
+}
+
+impl MockServices {
+    /// InQuest reports `MOCK_MALICIOUS_DOMAIN` and `MOCK_MALICIOUS_HASH` as malicious and other
+    /// domains and hashes as unseen, the IP echo service answers `MOCK_EXTERNAL_IP`, the Pwned
+    /// Passwords range API knows only `MOCK_PWNED_PASSWORD`, crt.sh knows only the certificates of
+    /// `MOCK_CRTSH_DOMAIN` (`MOCK_CRTSH`), WHOIS
+    /// answers `MOCK_WHOIS`, the banner service sends `MOCK_BANNER`, and DNS knows the A, MX,
+    /// NS and TXT records of `MOCK_MALICIOUS_DOMAIN` (192.0.2.10, whose PTR points back to it)
+    pub fn start() -> Result<MockServices, String> {
+        let search = "/api/dfi/search/ioc/domain?keyword=";
+        let http = MockHttp::start(vec![
+            MockRoute::new(search, 200, MOCK_INQUEST_EMPTY),
+            MockRoute::new(&format!("{}{}", search, MOCK_MALICIOUS_DOMAIN), 200, MOCK_INQUEST_MALICIOUS),
+            MockRoute::new("/api/dfi/search/hash/", 200, MOCK_INQUEST_EMPTY),
+            MockRoute::new(&format!("/api/dfi/search/hash/sha256?hash={}", MOCK_MALICIOUS_HASH), 200, MOCK_INQUEST_HASH),
+            MockRoute::new("/ip", 200, &format!("{}\n", MOCK_EXTERNAL_IP)),
+            MockRoute::new("/range/", 200, "0000000000000000000000000000000000A:0\r\n"),
+            MockRoute::new("/range/CBFDA", 200, MOCK_PWNED_RANGE),
+            MockRoute::new("/?q=", 200, "[]"),
+            MockRoute::new(&format!("/?q=%25.{}&", MOCK_CRTSH_DOMAIN), 200, MOCK_CRTSH),
+            MockRoute::new("/", 200, "<html><body>mock</body></html>").header("Content-Type", "text/html")
+        ])?;
+
+        let dns = MockDns::start(vec![
+            (MOCK_MALICIOUS_DOMAIN.to_string(), DnsType::A, DnsRdata::A(Ipv4Addr::new(192, 0, 2, 10))),
+            (MOCK_MALICIOUS_DOMAIN.to_string(), DnsType::Mx, DnsRdata::Mx { preference: 10, exchange: format!("mail.{}", MOCK_MALICIOUS_DOMAIN) }),
+            (MOCK_MALICIOUS_DOMAIN.to_string(), DnsType::Ns, DnsRdata::Name(format!("ns1.{}", MOCK_MALICIOUS_DOMAIN))),
+            (MOCK_MALICIOUS_DOMAIN.to_string(), DnsType::Txt, DnsRdata::Txt(vec!["v=spf1 -all".to_string()])),
+            ("10.2.0.192.in-addr.arpa".to_string(), DnsType::Ptr, DnsRdata::Name(MOCK_MALICIOUS_DOMAIN.to_string()))
+        ])?;
+
+        let services = MockServices {
+            whois: MockTcp::whois(MOCK_WHOIS)?,
+            cymru: MockTcp::whois(MOCK_CYMRU)?,
+            banner: MockTcp::banner(MOCK_BANNER)?,
+            http,
+            dns
+        };
+
+        set_service_endpoints(Some(ServiceEndpoints {
+            inquest: services.http.url(""),
+            whois: Some(services.whois.addr().to_string()),
+            ip_echo: Some(services.http.url("/ip")),
+            pwned_passwords: services.http.url(""),
+            crtsh: services.http.url(""),
+            asn_whois: services.cymru.addr().to_string()
+        }));
+        Ok(services)
+    }
+}
+
+impl Drop for MockServices {
+    fn drop(&mut self) {
+        set_service_endpoints(None);
+    }
+}
@@ -0,0 +1,307 @@
+/*
+    Shared text-reading layer
+
+    Every line-oriented input (blocklists, batch files, feeds, wordlists) is read through here so
+    Windows artifacts behave the same as Unix ones: BOMs are stripped, CRLF/CR line endings are
+    normalized and UTF-16 files (with or without a BOM) are transcoded to UTF-8.
+*/
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Chain, Cursor, Read},
+    path::Path
+};
+
+use crate::charset::{Charset, transcode};
+
+/// Text encodings recognised by the shared readers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be
+}
+
+// Number of leading bytes inspected when there is no BOM
+const SNIFF_LEN: usize = 512;
+
+// Detects the encoding of a buffer and returns it with the length of any BOM found
+pub(crate) fn sniff_encoding(bytes: &[u8]) -> (TextEncoding, usize) {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return (TextEncoding::Utf8, 3);
+    }
+
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return (TextEncoding::Utf16Le, 2);
+    }
+
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return (TextEncoding::Utf16Be, 2);
+    }
+
+    // Without a BOM, UTF-16 text that is mostly ASCII has a zero byte in every other position
+    let sample = &bytes[..bytes.len().min(SNIFF_LEN) & !1];
+    if sample.len() >= 4 {
+        let pairs = sample.len() / 2;
+        let even_zero = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let odd_zero = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+        if odd_zero * 10 >= pairs * 4 && even_zero * 10 < pairs {
+            return (TextEncoding::Utf16Le, 0);
+        }
+
+        if even_zero * 10 >= pairs * 4 && odd_zero * 10 < pairs {
+            return (TextEncoding::Utf16Be, 0);
+        }
+    }
+
+    (TextEncoding::Utf8, 0)
+}
+
+/// Detects the encoding of a byte buffer (BOM first, then a UTF-16 zero-byte heuristic)
+pub fn detect_text_encoding(bytes: &[u8]) -> TextEncoding {
+    sniff_encoding(bytes).0
+}
+
+/// Decodes raw bytes into normalized UTF-8 text (no BOM, `\n` line endings only)
+pub fn decode_text(bytes: &[u8]) -> String {
+    let (encoding, bom_len) = sniff_encoding(bytes);
+    let body = &bytes[bom_len..];
+
+    let text = match encoding {
+        TextEncoding::Utf8 => String::from_utf8_lossy(body).into_owned(),
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => decode_utf16(body, encoding)
+    };
+
+    normalize_newlines(&text)
+}
+
+// Transcodes UTF-16 code units, replacing lone surrogates and a trailing odd byte
+fn decode_utf16(bytes: &[u8], encoding: TextEncoding) -> String {
+    let charset = match encoding {
+        TextEncoding::Utf16Be => Charset::Utf16Be,
+        _ => Charset::Utf16Le
+    };
+    transcode(bytes, charset).text
+}
+
+/// Converts CRLF and lone CR line endings to `\n`
+pub fn normalize_newlines(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Reads an entire file as normalized UTF-8 text
+pub fn read_text_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    Ok(decode_text(&bytes))
+}
+
+/// Reads every line of a file, with BOM, line-ending and UTF-16 handling applied
+pub fn read_text_lines<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>> {
+    TextLines::open(path)?.collect()
+}
+
+/// Streaming line iterator over any reader, applying the same normalization as `decode_text`
+///
+/// Lines are yielded without their terminator, so files far larger than memory can be consumed.
+pub struct TextLines<R: Read> {
+    // The sniffed head of the stream, then the rest of it
+    reader: BufReader<Chain<Cursor<Vec<u8>>, R>>,
+    encoding: Option<TextEncoding>,
+    // The last UTF-16 line ended in CR, so an LF opening the next one completes that CRLF
+    after_cr: bool,
+    done: bool
+}
+
+impl TextLines<File> {
+    /// Opens a file for streaming line reads
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(TextLines::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> TextLines<R> {
+    /// Wraps a reader; the encoding is detected from the first bytes of the stream
+    pub fn new(reader: R) -> Self {
+        TextLines {
+            reader: BufReader::with_capacity(64 * 1024, Cursor::new(Vec::new()).chain(reader)),
+            encoding: None,
+            after_cr: false,
+            done: false
+        }
+    }
+
+    /// Encoding detected for the stream (available after the first line has been read)
+    pub fn encoding(&self) -> Option<TextEncoding> {
+        self.encoding
+    }
+
+    fn detect(&mut self) -> io::Result<TextEncoding> {
+        if let Some(encoding) = self.encoding {
+            return Ok(encoding);
+        }
+
+        // Read enough to see a BOM or the UTF-16 zero-byte pattern, however little each read returns;
+        // nothing has been buffered yet, so the head is read straight from the stream
+        let (head, stream) = self.reader.get_mut().get_mut();
+        let mut sniffed = Vec::with_capacity(SNIFF_LEN);
+        while sniffed.len() < SNIFF_LEN {
+            let mut chunk = [0u8; SNIFF_LEN];
+            match stream.read(&mut chunk[..SNIFF_LEN - sniffed.len()]) {
+                Ok(0) => break,
+                Ok(read) => sniffed.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e)
+            }
+        }
+
+        let (encoding, bom_len) = sniff_encoding(&sniffed);
+        head.set_position(bom_len as u64);
+        *head.get_mut() = sniffed;
+        self.encoding = Some(encoding);
+
+        Ok(encoding)
+    }
+
+    // Reads one UTF-8 line, treating `\n`, `\r\n` and `\r` as terminators
+    fn next_utf8_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+
+        loop {
+            let buffered = self.reader.fill_buf()?;
+            if buffered.is_empty() {
+                self.done = true;
+                return Ok(if line.is_empty() { None } else { Some(String::from_utf8_lossy(&line).into_owned()) });
+            }
+
+            match buffered.iter().position(|b| *b == b'\n' || *b == b'\r') {
+                Some(pos) => {
+                    let terminator = buffered[pos];
+                    line.extend_from_slice(&buffered[..pos]);
+                    self.reader.consume(pos + 1);
+
+                    if terminator == b'\r' && self.reader.fill_buf()?.first() == Some(&b'\n') {
+                        self.reader.consume(1);
+                    }
+
+                    return Ok(Some(String::from_utf8_lossy(&line).into_owned()));
+                }
+                None => {
+                    let len = buffered.len();
+                    line.extend_from_slice(buffered);
+                    self.reader.consume(len);
+                }
+            }
+        }
+    }
+
+    // Reads a single UTF-16 code unit, returning a dangling byte as U+FFFD
+    fn next_unit(&mut self, encoding: TextEncoding) -> io::Result<Option<Result<u16, ()>>> {
+        let mut pair = [0u8; 2];
+        let mut filled = 0;
+
+        while filled < 2 {
+            let read = self.reader.read(&mut pair[filled..])?;
+            if read == 0 {
+                return Ok(if filled == 0 { None } else { Some(Err(())) });
+            }
+            filled += read;
+        }
+
+        Ok(Some(Ok(match encoding {
+            TextEncoding::Utf16Be => u16::from_be_bytes(pair),
+            _ => u16::from_le_bytes(pair)
+        })))
+    }
+
+    // Reads one UTF-16 line and transcodes it to UTF-8
+    fn next_utf16_line(&mut self, encoding: TextEncoding) -> io::Result<Option<String>> {
+        let mut units = Vec::new();
+        let mut trailing_garbage = false;
+        let mut saw_any = false;
+
+        loop {
+            match self.next_unit(encoding)? {
+                None => {
+                    self.done = true;
+                    break;
+                }
+                Some(Err(())) => {
+                    self.done = true;
+                    trailing_garbage = true;
+                    saw_any = true;
+                    break;
+                }
+                Some(Ok(unit)) => {
+                    // The LF of a CRLF split from its CR by the end of the last line
+                    if std::mem::take(&mut self.after_cr) && unit == 0x0A {
+                        continue;
+                    }
+                    saw_any = true;
+
+                    if unit == 0x0A {
+                        break;
+                    }
+
+                    if unit == 0x0D {
+                        self.after_cr = true;
+                        break;
+                    }
+
+                    units.push(unit);
+                }
+            }
+        }
+
+        if !saw_any {
+            return Ok(None);
+        }
+
+        let mut line: String = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        if trailing_garbage {
+            line.push(char::REPLACEMENT_CHARACTER);
+        }
+
+        Ok(Some(line))
+    }
+}
+
+impl<R: Read> Iterator for TextLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let encoding = match self.detect() {
+            Ok(encoding) => encoding,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+
+        let line = match encoding {
+            TextEncoding::Utf8 => self.next_utf8_line(),
+            _ => self.next_utf16_line(encoding)
+        };
+
+        match line {
+            Ok(Some(line)) => Some(Ok(line)),
+            Ok(None) => None,
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
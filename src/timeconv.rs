@@ -0,0 +1,111 @@
+/*
+    Conversions between the timestamp forms forensic artifacts use
+
+    Unix epoch values come in seconds from logs and most file formats, milliseconds from
+    JavaScript and Java, and micro- or nanoseconds from browsers and tracing tools; the unit is
+    told apart by magnitude, since any present-day date is about 1.7e9 seconds, 1.7e12
+    milliseconds, 1.7e15 microseconds or 1.7e18 nanoseconds. Windows FILETIME values (registry
+    key times, MFT and event records) count 100 ns intervals since 1601. Results stay within
+    the years RFC 3339 can write (0000 to 9999).
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    logtime::parse_any_timestamp,
+    timefmt::{filetime_to_unix, unix_to_rfc3339}
+};
+
+// Unix seconds of 0000-01-01T00:00:00Z and of the first second after 9999-12-31T23:59:59Z
+const MIN_SECS: i64 = -62_167_219_200;
+const MAX_SECS: i64 = 253_402_300_800;
+
+/// One instant in the forms a timestamp conversion deals with
+#[derive(Debug, Clone, Serialize)]
+pub struct TimestampConversion {
+    pub input: String,
+    /// How the input was read: seconds, milliseconds, microseconds, nanoseconds, filetime or iso8601
+    pub unit: &'static str,
+    /// RFC 3339 UTC
+    pub utc: String,
+    /// Unix seconds (floor) and the nanoseconds past them
+    pub epoch: i64,
+    pub nanos: u32
+}
+
+impl TimestampConversion {
+    /// Unix seconds, with the fraction when there is one ("1709208000", "1709208000.25")
+    pub fn epoch_seconds(&self) -> String {
+        if self.nanos == 0 {
+            return self.epoch.to_string();
+        }
+        format!("{}.{:09}", self.epoch, self.nanos).trim_end_matches('0').to_string()
+    }
+}
+
+impl fmt::Display for TimestampConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.utc)
+    }
+}
+
+/// Unix epoch seconds, milliseconds, microseconds or nanoseconds (told apart by magnitude) as UTC
+pub fn epoch_to_utc(value: &str) -> Result<TimestampConversion, String> {
+    let input = value.trim();
+    let number: f64 = input.parse().ok().filter(|number: &f64| number.is_finite())
+        .ok_or_else(|| format!("Invalid epoch value '{}' (expected Unix seconds, milliseconds, microseconds or nanoseconds)", input))?;
+
+    let (unit, per_second) = match number.abs() {
+        magnitude if magnitude < 1e11 => ("seconds", 1e0),
+        magnitude if magnitude < 1e14 => ("milliseconds", 1e3),
+        magnitude if magnitude < 1e17 => ("microseconds", 1e6),
+        _ => ("nanoseconds", 1e9)
+    };
+
+    // Whole digits are split exactly; only a fractional input goes through floating point
+    let (secs, nanos) = match input.parse::<i128>() {
+        Ok(whole) => {
+            let per_second = per_second as i128;
+            (whole.div_euclid(per_second), (whole.rem_euclid(per_second) * (1_000_000_000 / per_second)) as u32)
+        },
+        Err(_) => {
+            let secs = number / per_second;
+            (secs.floor() as i128, ((secs - secs.floor()) * 1e9).round().min(999_999_999.0) as u32)
+        }
+    };
+
+    conversion(input, unit, secs, nanos)
+}
+
+/// ISO 8601 / RFC 3339 text (a zoneless time is UTC) as Unix seconds
+pub fn utc_to_epoch(text: &str) -> Result<TimestampConversion, String> {
+    let input = text.trim();
+    let timestamp = parse_any_timestamp(input)
+        .map_err(|_| format!("Unparseable timestamp '{}' (expected ISO 8601 / RFC 3339 such as 2024-02-29T12:00:00Z, with a real calendar date)", input))?;
+
+    conversion(input, "iso8601", timestamp.secs as i128, timestamp.nanos)
+}
+
+/// A Windows FILETIME (100 ns intervals since 1601-01-01, decimal or "0x" hex) as UTC
+pub fn filetime_to_utc(value: &str) -> Result<TimestampConversion, String> {
+    let input = value.trim();
+    let filetime = match input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => input.parse::<u64>()
+    }
+    .map_err(|_| format!("Invalid FILETIME '{}' (expected an unsigned 64-bit count of 100 ns intervals, decimal or 0x hex)", input))?;
+
+    let (secs, nanos) = filetime_to_unix(filetime);
+    conversion(input, "filetime", secs as i128, nanos)
+}
+
+fn conversion(input: &str, unit: &'static str, secs: i128, nanos: u32) -> Result<TimestampConversion, String> {
+    if !(MIN_SECS as i128..MAX_SECS as i128).contains(&secs) {
+        return Err(format!("'{}' ({}) is outside the representable range 0000-01-01T00:00:00Z to 9999-12-31T23:59:59Z", input, unit));
+    }
+
+    let secs = secs as i64;
+    Ok(TimestampConversion { input: input.to_string(), unit, utc: unix_to_rfc3339(secs, nanos), epoch: secs, nanos })
+}
@@ -0,0 +1,123 @@
+/*
+    UTC timestamp conversion shared by the forensic parsers (no timezone database needed)
+*/
+
+/// Seconds between 1601-01-01 (Windows FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_DIFF: i64 = 11_644_473_600;
+
+/// Formats Unix seconds (plus nanoseconds) as RFC 3339 UTC, e.g. "2024-01-31T13:45:00.123456789Z"
+pub(crate) fn unix_to_rfc3339(secs: i64, nanos: u32) -> String {
+    let days = secs.div_euclid(86_400);
+    let second_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    let mut output = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, second_of_day / 3600, (second_of_day % 3600) / 60, second_of_day % 60
+    );
+
+    if nanos > 0 {
+        output.push_str(format!(".{:09}", nanos).trim_end_matches('0'));
+    }
+
+    output.push('Z');
+    output
+}
+
+/// Converts a Windows FILETIME (100 ns intervals since 1601) to Unix seconds and nanoseconds
+pub(crate) fn filetime_to_unix(filetime: u64) -> (i64, u32) {
+    let secs = (filetime / 10_000_000) as i64 - FILETIME_UNIX_DIFF;
+    let nanos = ((filetime % 10_000_000) * 100) as u32;
+    (secs, nanos)
+}
+
+/// Formats a Windows FILETIME as RFC 3339 UTC; zero means "not set"
+pub(crate) fn filetime_to_rfc3339(filetime: u64) -> Option<String> {
+    if filetime == 0 {
+        return None;
+    }
+
+    let (secs, nanos) = filetime_to_unix(filetime);
+    Some(unix_to_rfc3339(secs, nanos))
+}
+
+/// Parses Unix seconds, "YYYY-MM-DD", "YYYY-MM-DDTHH:MM:SS" or the same with a trailing "Z" (always UTC)
+pub(crate) fn parse_utc(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+
+    if let Ok(secs) = input.parse::<i64>() {
+        return Ok(secs);
+    }
+
+    let invalid = || format!("Invalid timestamp '{}' (expected Unix seconds or YYYY-MM-DD[THH:MM:SS][Z])", input);
+    let trimmed = input.trim_end_matches('Z');
+    let (date, time) = match trimmed.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (trimmed, None)
+    };
+
+    let date: Vec<i64> = date.split('-').map(|part| part.parse::<i64>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+    if date.len() != 3 || !(1..=12).contains(&date[1]) || !(1..=31).contains(&date[2]) {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0;
+    if let Some(time) = time {
+        let time = time.split('.').next().unwrap_or(time);
+        let parts: Vec<i64> = time.split(':').map(|part| part.parse::<i64>()).collect::<Result<_, _>>().map_err(|_| invalid())?;
+        if parts.is_empty() || parts.len() > 3 || parts[0] > 23 || parts.iter().skip(1).any(|part| *part > 59) {
+            return Err(invalid());
+        }
+        seconds = parts[0] * 3600 + parts.get(1).unwrap_or(&0) * 60 + parts.get(2).unwrap_or(&0);
+    }
+
+    Ok(days_from_civil(date[0], date[1], date[2]) * 86_400 + seconds)
+}
+
+/// Unix seconds of a calendar date plus seconds into that day; None for an impossible date
+pub(crate) fn civil_to_unix(year: i64, month: u32, day: u32, second_of_day: i64) -> Option<i64> {
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None
+    };
+
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+
+    Some(days_from_civil(year, month as i64, day as i64) * 86_400 + second_of_day)
+}
+
+/// Calendar year of Unix seconds (UTC)
+pub(crate) fn unix_year(secs: i64) -> i64 {
+    civil_from_days(secs.div_euclid(86_400)).0
+}
+
+// Howard Hinnant's days-from-civil algorithms (proleptic Gregorian calendar)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
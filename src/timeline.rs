@@ -0,0 +1,355 @@
+/*
+    Filesystem timeline (MACB) generation
+
+    Walks a directory tree without following symlinks (unless asked) or opening special files,
+    records the modified, accessed, changed and born timestamps the platform exposes, and
+    writes them as a Sleuth Kit body file (for mactime) or NDJSON. Per-entry errors are counted
+    and sampled, never fatal.
+*/
+
+use std::{
+    fmt,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    paths::{path_to_string, path_with_flag},
+    sink::{SinkArgs, finish_sink},
+    timefmt::{parse_utc, unix_to_rfc3339},
+    trailing_options,
+    walk::{WalkEntry, WalkError, WalkOptions, walk_dir}
+};
+
+// Number of error messages kept in the summary; the rest are only counted
+const ERROR_SAMPLES: usize = 100;
+
+/// A point in time as Unix seconds and nanoseconds, serialized as RFC 3339 UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32
+}
+
+impl Timestamp {
+    fn from_system(time: SystemTime) -> Timestamp {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Timestamp { secs: after.as_secs() as i64, nanos: after.subsec_nanos() },
+            Err(e) => {
+                let before = e.duration();
+                let nanos = before.subsec_nanos();
+                if nanos == 0 {
+                    Timestamp { secs: -(before.as_secs() as i64), nanos: 0 }
+                } else {
+                    Timestamp { secs: -(before.as_secs() as i64) - 1, nanos: 1_000_000_000 - nanos }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&unix_to_rfc3339(self.secs, self.nanos))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// One file system entry with its MACB timestamps
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineEntry {
+    pub path: String,
+    /// The name was not valid UTF-8 and `path` is percent-encoded (see `path_from_string`)
+    pub path_encoded: bool,
+    /// "file", "dir", "symlink", "fifo", "socket", "block-device", "char-device" or "other"
+    pub kind: String,
+    /// Where a symlink points, as stored in the link
+    pub link_target: Option<String>,
+    /// Length from the metadata, which for a sparse or pseudo file need not match what is stored
+    pub size: u64,
+    /// Fewer bytes are allocated than the length claims
+    pub sparse: bool,
+    pub modified: Option<Timestamp>,
+    pub accessed: Option<Timestamp>,
+    /// Inode change time (Unix only)
+    pub changed: Option<Timestamp>,
+    /// Creation (birth) time where the platform and file system record it
+    pub born: Option<Timestamp>,
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub inode: Option<u64>
+}
+
+impl TimelineEntry {
+    fn timestamps(&self) -> impl Iterator<Item = Timestamp> + '_ {
+        [self.modified, self.accessed, self.changed, self.born].into_iter().flatten()
+    }
+
+    /// Most recent of the MACB timestamps, used as the sort key
+    pub fn latest(&self) -> Option<Timestamp> {
+        self.timestamps().max()
+    }
+
+    /// Sleuth Kit body file line: MD5|name|inode|mode|UID|GID|size|atime|mtime|ctime|crtime
+    pub fn to_bodyfile(&self) -> String {
+        let secs = |time: Option<Timestamp>| time.map(|t| t.secs).unwrap_or(0);
+
+        // mactime shows symlinks as "name -> target"
+        let name = match &self.link_target {
+            Some(target) => format!("{} -> {}", self.path, target),
+            None => self.path.clone()
+        };
+
+        format!(
+            "0|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            name,
+            self.inode.unwrap_or(0),
+            mode_string(&self.kind, self.mode),
+            self.uid.unwrap_or(0),
+            self.gid.unwrap_or(0),
+            self.size,
+            secs(self.accessed),
+            secs(self.modified),
+            secs(self.changed),
+            secs(self.born)
+        )
+    }
+}
+
+/// Output format for `write_timeline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineFormat {
+    /// Sleuth Kit body file, consumable by mactime
+    Bodyfile,
+    /// One JSON object per line with RFC 3339 timestamps
+    Ndjson
+}
+
+/// Options for `write_timeline`
+#[derive(Debug, Clone)]
+pub struct TimelineOptions {
+    pub format: TimelineFormat,
+    /// Descend into symlinked directories (loops are detected)
+    pub follow_symlinks: bool,
+    /// Keep only entries with at least one timestamp at or after this Unix time
+    pub start: Option<i64>,
+    /// Keep only entries with at least one timestamp at or before this Unix time
+    pub end: Option<i64>,
+    /// Sort by most recent timestamp before writing; false streams entries as they are walked
+    pub sorted: bool
+}
+
+impl Default for TimelineOptions {
+    fn default() -> Self {
+        TimelineOptions {
+            format: TimelineFormat::Bodyfile,
+            follow_symlinks: false,
+            start: None,
+            end: None,
+            sorted: true
+        }
+    }
+}
+
+/// Counts from a timeline run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TimelineSummary {
+    pub root: String,
+    pub written: u64,
+    /// Entries outside the time window
+    pub filtered: u64,
+    pub errors: u64,
+    /// The first few per-entry errors (permission denied, vanished files)
+    pub error_samples: Vec<String>
+}
+
+impl fmt::Display for TimelineSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Timeline of {}: {} written, {} outside window, {} errors", self.root, self.written, self.filtered, self.errors)?;
+
+        for error in &self.error_samples {
+            writeln!(f, "Error: {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Walks `root` and writes one timeline record per entry to `writer`
+pub fn write_timeline<P: AsRef<Path>, W: Write>(root: P, options: &TimelineOptions, writer: &mut W) -> Result<TimelineSummary, String> {
+    let root = root.as_ref();
+    let walk_options = WalkOptions { follow_symlinks: options.follow_symlinks, max_depth: None };
+    let walk = walk_dir(root, &walk_options)?;
+
+    let mut summary = TimelineSummary { root: path_to_string(root), ..TimelineSummary::default() };
+    let mut pending: Vec<TimelineEntry> = Vec::new();
+
+    for walked in walk {
+        let walked = match walked {
+            Ok(walked) => walked,
+            Err(e) => {
+                record_error(&mut summary, &e);
+                continue;
+            }
+        };
+
+        let entry = timeline_entry(&walked);
+
+        if !in_window(&entry, options) {
+            summary.filtered += 1;
+            continue;
+        }
+
+        if options.sorted {
+            pending.push(entry);
+        } else {
+            write_entry(writer, &entry, options.format)?;
+            summary.written += 1;
+        }
+    }
+
+    if options.sorted {
+        pending.sort_by(|a, b| a.latest().cmp(&b.latest()).then_with(|| a.path.cmp(&b.path)));
+
+        for entry in &pending {
+            write_entry(writer, entry, options.format)?;
+            summary.written += 1;
+        }
+    }
+
+    writer.flush().map_err(|e| format!("Unable to write timeline: {}", e))?;
+    Ok(summary)
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &TimelineEntry, format: TimelineFormat) -> Result<(), String> {
+    let line = match format {
+        TimelineFormat::Bodyfile => entry.to_bodyfile(),
+        TimelineFormat::Ndjson => serde_json::to_string(entry).map_err(|e| e.to_string())?
+    };
+
+    writeln!(writer, "{}", line).map_err(|e| format!("Unable to write timeline: {}", e))
+}
+
+fn record_error(summary: &mut TimelineSummary, error: &WalkError) {
+    summary.errors += 1;
+    if summary.error_samples.len() < ERROR_SAMPLES {
+        summary.error_samples.push(error.to_string());
+    }
+}
+
+fn in_window(entry: &TimelineEntry, options: &TimelineOptions) -> bool {
+    if options.start.is_none() && options.end.is_none() {
+        return true;
+    }
+
+    entry.timestamps().any(|time| {
+        options.start.map(|start| time.secs >= start).unwrap_or(true) && options.end.map(|end| time.secs <= end).unwrap_or(true)
+    })
+}
+
+fn timeline_entry(walked: &WalkEntry) -> TimelineEntry {
+    let metadata = &walked.metadata;
+    let (name, path_encoded) = path_with_flag(&walked.path);
+    let mut entry = TimelineEntry {
+        path: name,
+        path_encoded,
+        kind: walked.kind.as_str().to_string(),
+        link_target: walked.link_target.as_deref().map(path_to_string),
+        size: metadata.len(),
+        sparse: walked.sparse,
+        modified: metadata.modified().ok().map(Timestamp::from_system),
+        accessed: metadata.accessed().ok().map(Timestamp::from_system),
+        changed: None,
+        born: metadata.created().ok().map(Timestamp::from_system),
+        mode: None,
+        uid: None,
+        gid: None,
+        inode: None
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        entry.changed = Some(Timestamp { secs: metadata.ctime(), nanos: metadata.ctime_nsec() as u32 });
+        entry.mode = Some(metadata.mode());
+        entry.uid = Some(metadata.uid());
+        entry.gid = Some(metadata.gid());
+        entry.inode = Some(metadata.ino());
+    }
+
+    entry
+}
+
+// ls-style mode string ("drwxr-xr-x"); permissions are dashes when unknown
+fn mode_string(kind: &str, mode: Option<u32>) -> String {
+    let type_char = match kind {
+        "dir" => 'd',
+        "symlink" => 'l',
+        "file" => '-',
+        "fifo" => 'p',
+        "socket" => 's',
+        "block-device" => 'b',
+        "char-device" => 'c',
+        _ => '?'
+    };
+
+    let mut output = type_char.to_string();
+    let mode = mode.unwrap_or(0);
+
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        output.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        output.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        output.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+
+    output
+}
+
+// Parses "path [format=ndjson] [start=...] [end=...] [follow=true] [sorted=false] [out=file] [backpressure=block] [retries=5]"; options trail the path
+pub(crate) fn timeline_from_args(input: &str) -> Result<String, String> {
+    let (path, pairs) = trailing_options(input, &["format", "start", "end", "follow", "sorted", "out", "backpressure", "retries"]);
+    let mut options = TimelineOptions::default();
+    let mut sink = SinkArgs::default();
+
+    for (key, value) in pairs {
+        if sink.accept(key, value)? {
+            continue;
+        }
+
+        match key {
+            "format" => options.format = match value {
+                "bodyfile" | "body" => TimelineFormat::Bodyfile,
+                "ndjson" | "json" => TimelineFormat::Ndjson,
+                _ => return Err(format!("Unknown timeline format '{}'", value))
+            },
+            "start" => options.start = Some(parse_utc(value)?),
+            "end" => options.end = Some(parse_utc(value)?),
+            "follow" => options.follow_symlinks = value == "true",
+            _ => options.sorted = value != "false"
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No path specified for timeline".to_string());
+    }
+
+    if let Some(mut output) = sink.open()? {
+        let summary = write_timeline(Path::new(&path), &options, &mut output)?;
+        return finish_sink(output, &summary);
+    }
+
+    let mut output = Vec::new();
+    write_timeline(Path::new(&path), &options, &mut output)?;
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
@@ -0,0 +1,203 @@
+/*
+    TLS certificate of a remote service
+
+    Connects, completes a TLS handshake and summarizes the leaf certificate the server sent:
+    subject common name and alternative names, issuer, validity with the days left, serial
+    and SHA-256 fingerprint. Nothing is verified during the handshake, since expired,
+    self-signed and wrongly named certificates are the ones an assessment wants to see; they
+    are reported with a warning instead. Only a host that cannot be reached, or a service
+    that does not complete a TLS handshake, is an error.
+
+    The host name is sent as SNI so virtual hosts return their own certificate; an IP
+    address target sends none unless a name is given with "sni=".
+*/
+
+use std::{
+    fmt,
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use native_tls::{HandshakeError, TlsConnector};
+use serde::Serialize;
+
+use crate::{
+    checks::{connect, resolve_target},
+    config,
+    timefmt::parse_utc,
+    trailing_options,
+    x509::{CertificateInfo, parse_certificate, subject_alt_names, subject_common_name}
+};
+
+/// Options for `mercy_tls_cert`
+#[derive(Debug, Clone)]
+pub struct TlsCertOptions {
+    /// For connecting and for the handshake
+    pub timeout: Duration,
+    /// Name sent as SNI and checked against the certificate, instead of the target's host
+    pub server_name: Option<String>
+}
+
+impl Default for TlsCertOptions {
+    fn default() -> Self {
+        TlsCertOptions {
+            timeout: config::read_timeout(Duration::from_secs(5)),
+            server_name: None
+        }
+    }
+}
+
+/// The leaf certificate a TLS service presented
+#[derive(Debug, Clone, Serialize)]
+pub struct TlsCertificate {
+    /// "host:port" as connected to
+    pub target: String,
+    pub address: String,
+    pub common_name: Option<String>,
+    /// "DNS:host", "IP:address", "email:..." or "URI:..."
+    pub subject_alt_names: Vec<String>,
+    pub certificate: CertificateInfo,
+    /// Whole days until notAfter, negative once expired
+    pub days_until_expiry: Option<i64>,
+    pub expired: bool,
+    /// Whether the certificate names the host (or the "sni=" name)
+    pub name_matches: bool,
+    /// "expired", "not yet valid", "self-signed" and name mismatch notes
+    pub warnings: Vec<String>
+}
+
+impl fmt::Display for TlsCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Certificate of {} ({})", self.target, self.address)?;
+        writeln!(f, "Common name: {}", self.common_name.as_deref().unwrap_or("(none)"))?;
+        writeln!(f, "Alternative names: {}", if self.subject_alt_names.is_empty() { "(none)".to_string() } else { self.subject_alt_names.join(", ") })?;
+        writeln!(f, "Subject: {}", self.certificate.subject)?;
+        writeln!(f, "Issuer: {}", self.certificate.issuer)?;
+        writeln!(f, "Serial: {}", self.certificate.serial)?;
+        write!(f, "Valid: {} to {}", self.certificate.not_before.as_deref().unwrap_or("?"), self.certificate.not_after.as_deref().unwrap_or("?"))?;
+        match self.days_until_expiry {
+            Some(days) if days < 0 => writeln!(f, " (expired {} days ago)", -days)?,
+            Some(days) => writeln!(f, " ({} days left)", days)?,
+            None => writeln!(f)?
+        }
+        write!(f, "SHA-256: {}", self.certificate.sha256)?;
+        for warning in &self.warnings {
+            write!(f, "\nWARNING: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Connects to `target` ("host" or "host:port", port 443 by default), completes a TLS handshake without verifying anything and summarizes the leaf certificate
+pub fn mercy_tls_cert(target: &str, options: &TlsCertOptions) -> Result<TlsCertificate, String> {
+    let target = target.trim();
+    let addr = resolve_target(target, 443)?;
+    let host = target_host(target);
+    let name = options.server_name.clone().unwrap_or_else(|| host.clone());
+
+    let connector = TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| format!("Unable to set up TLS: {}", e))?;
+    let stream = connect(addr, options.timeout)?;
+
+    let tls = connector.connect(&name, stream).map_err(|e| match e {
+        HandshakeError::WouldBlock(_) => format!("Timed out during the TLS handshake with {}", addr),
+        HandshakeError::Failure(e) => format!("TLS handshake with {} failed (is it a TLS service?): {}", addr, e)
+    })?;
+    let der = tls.peer_certificate()
+        .map_err(|e| format!("Unable to read the certificate of {}: {}", addr, e))?
+        .ok_or_else(|| format!("{} sent no certificate", addr))?
+        .to_der()
+        .map_err(|e| format!("Unable to encode the certificate of {}: {}", addr, e))?;
+
+    let certificate = parse_certificate(&der).map_err(|e| format!("Unable to parse the certificate of {}: {}", addr, e))?;
+    let common_name = subject_common_name(&der);
+    let subject_alt_names = subject_alt_names(&der);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0);
+    let not_after = certificate.not_after.as_deref().and_then(|time| parse_utc(time).ok());
+    let not_before = certificate.not_before.as_deref().and_then(|time| parse_utc(time).ok());
+    let expired = not_after.map(|not_after| not_after < now).unwrap_or(false);
+    let name_matches = names_host(&name, common_name.as_deref(), &subject_alt_names);
+
+    let mut warnings = Vec::new();
+    if expired {
+        warnings.push(format!("expired on {}", certificate.not_after.as_deref().unwrap_or("?")));
+    }
+    if not_before.map(|not_before| not_before > now).unwrap_or(false) {
+        warnings.push(format!("not yet valid, valid from {}", certificate.not_before.as_deref().unwrap_or("?")));
+    }
+    if certificate.self_signed {
+        warnings.push("self-signed".to_string());
+    }
+    if !name_matches {
+        warnings.push(format!("not issued for {}", name));
+    }
+
+    Ok(TlsCertificate {
+        target: match host.contains(':') {
+            true => format!("[{}]:{}", host, addr.port()),
+            false => format!("{}:{}", host, addr.port())
+        },
+        address: addr.to_string(),
+        common_name,
+        subject_alt_names,
+        days_until_expiry: not_after.map(|not_after| (not_after - now).div_euclid(86_400)),
+        expired,
+        name_matches,
+        warnings,
+        certificate
+    })
+}
+
+// The host part of "host", "host:port", "v4:port", "[v6]:port" or a bare IPv6 address
+fn target_host(target: &str) -> String {
+    if target.parse::<IpAddr>().is_ok() {
+        return target.to_string();
+    }
+    if let Some(rest) = target.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest).to_string();
+    }
+    target.rsplit_once(':').map(|(host, _)| host).unwrap_or(target).to_string()
+}
+
+// Whether the certificate names the host: DNS names (one leading "*." label matches one label) and
+// IP addresses, or the common name when there are no alternative names
+fn names_host(host: &str, common_name: Option<&str>, alt_names: &[String]) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let dns_matches = |pattern: &str| {
+        let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(suffix) => host.split_once('.').map(|(first, rest)| !first.is_empty() && rest == suffix).unwrap_or(false),
+            None => pattern == host
+        }
+    };
+
+    if alt_names.is_empty() {
+        return common_name.map(dns_matches).unwrap_or(false);
+    }
+
+    let ip = host.parse::<IpAddr>().ok();
+    alt_names.iter().any(|name| match (name.split_once(':'), ip) {
+        (Some(("IP", address)), Some(ip)) => address.parse::<IpAddr>().map(|address| address == ip).unwrap_or(false),
+        (Some(("DNS", pattern)), None) => dns_matches(pattern),
+        _ => false
+    })
+}
+
+// Parses "host[:port] [timeout=5] [sni=name]"
+pub(crate) fn tls_cert_from_args(input: &str) -> Result<TlsCertificate, String> {
+    let (target, pairs) = trailing_options(input, &["timeout", "sni"]);
+    let mut options = TlsCertOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => options.server_name = Some(value.to_string())
+        }
+    }
+
+    mercy_tls_cert(&target, &options)
+}
@@ -0,0 +1,414 @@
+/*
+    UDP/TCP traceroute
+
+    UDP probes work unprivileged on Linux by reading ICMP errors from the socket error queue. With
+    raw ICMP access (root / CAP_NET_RAW) both UDP and TCP SYN probes are supported everywhere on
+    Unix. When neither is possible a capability error is returned instead of hanging.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::{
+    dns::{default_dns_server, resolve_host, reverse_lookup},
+    icmp::{self, IcmpAccess}
+};
+
+/// Transport used for traceroute probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceMethod {
+    Udp,
+    Tcp
+}
+
+/// Tunables for `mercy_traceroute`
+#[derive(Debug, Clone)]
+pub struct TracerouteOptions {
+    pub method: TraceMethod,
+    /// Destination port (UDP probes increment it per probe, like classic traceroute)
+    pub port: u16,
+    pub max_ttl: u8,
+    pub probes_per_hop: u8,
+    /// Time to wait for each probe's answer
+    pub timeout: Duration,
+    pub resolve_names: bool
+}
+
+impl Default for TracerouteOptions {
+    fn default() -> Self {
+        TracerouteOptions {
+            method: TraceMethod::Udp,
+            port: 33434,
+            max_ttl: 30,
+            probes_per_hop: 3,
+            timeout: Duration::from_secs(2),
+            resolve_names: true
+        }
+    }
+}
+
+/// One TTL step of a trace
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceHop {
+    pub ttl: u8,
+    pub address: Option<IpAddr>,
+    pub hostname: Option<String>,
+    /// Round-trip time per probe in milliseconds (`None` for a probe that got no answer)
+    pub rtts_ms: Vec<Option<f64>>,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    /// Set when the hop answered with something other than time-exceeded/port-unreachable
+    pub note: Option<String>
+}
+
+/// Result of a traceroute run
+#[derive(Debug, Clone, Serialize)]
+pub struct TracerouteReport {
+    pub target: String,
+    pub address: IpAddr,
+    pub method: TraceMethod,
+    pub max_ttl: u8,
+    /// How ICMP answers were collected ("raw-icmp" or "error-queue")
+    pub receiver: String,
+    pub reached: bool,
+    pub hops: Vec<TraceHop>
+}
+
+impl fmt::Display for TracerouteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method { TraceMethod::Udp => "udp", TraceMethod::Tcp => "tcp" };
+        writeln!(f, "traceroute to {} ({}), {} hops max, {} probes ({})", self.target, self.address, self.max_ttl, method, self.receiver)?;
+
+        for hop in &self.hops {
+            write!(f, "{:>2}  ", hop.ttl)?;
+
+            match hop.address {
+                Some(address) => write!(f, "{} ({})", hop.hostname.as_deref().unwrap_or(&address.to_string()), address)?,
+                None => write!(f, "*")?
+            }
+
+            for rtt in &hop.rtts_ms {
+                match rtt {
+                    Some(ms) => write!(f, "  {:.3} ms", ms)?,
+                    None => write!(f, "  *")?
+                }
+            }
+
+            if let (Some(min), Some(avg), Some(max)) = (hop.min_ms, hop.avg_ms, hop.max_ms) {
+                write!(f, "  [min/avg/max {:.3}/{:.3}/{:.3}]", min, avg, max)?;
+            }
+
+            if let Some(note) = &hop.note {
+                write!(f, "  {}", note)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        if !self.reached {
+            writeln!(f, "Destination not reached within {} hops", self.max_ttl)?;
+        }
+
+        Ok(())
+    }
+}
+
+// What a single probe learned
+enum ProbeOutcome {
+    Silent,
+    Hop(IpAddr, f64),
+    Reached(IpAddr, f64),
+    Unreachable(IpAddr, f64, String)
+}
+
+// Strategy for collecting ICMP answers
+enum Receiver {
+    Raw(Socket),
+    #[cfg(target_os = "linux")]
+    ErrorQueue
+}
+
+/// Traces the path to `host`, returning a capability error when probing isn't possible here
+pub fn mercy_traceroute(host: &str, options: &TracerouteOptions) -> Result<TracerouteReport, String> {
+    if options.max_ttl == 0 || options.probes_per_hop == 0 {
+        return Err("max_ttl and probes_per_hop must be at least 1".to_string());
+    }
+
+    let address = resolve_host(host)?;
+    let v6 = address.is_ipv6();
+    let receiver = choose_receiver(options.method, v6)?;
+
+    let receiver_name = match receiver {
+        Receiver::Raw(_) => "raw-icmp",
+        #[cfg(target_os = "linux")]
+        Receiver::ErrorQueue => "error-queue"
+    };
+
+    let dns_server = default_dns_server();
+    let mut names: HashMap<IpAddr, Option<String>> = HashMap::new();
+    let mut hops = Vec::new();
+    let mut reached = false;
+    let mut sequence: u16 = 0;
+
+    for ttl in 1..=options.max_ttl {
+        let mut hop = TraceHop { ttl, address: None, hostname: None, rtts_ms: Vec::new(), min_ms: None, avg_ms: None, max_ms: None, note: None };
+        let mut stop = false;
+
+        for _ in 0..options.probes_per_hop {
+            let port = match options.method {
+                TraceMethod::Udp => options.port.wrapping_add(sequence),
+                TraceMethod::Tcp => options.port
+            };
+            sequence = sequence.wrapping_add(1);
+
+            let outcome = match options.method {
+                TraceMethod::Udp => udp_probe(&receiver, SocketAddr::new(address, port), ttl, options.timeout)?,
+                TraceMethod::Tcp => tcp_probe(&receiver, SocketAddr::new(address, port), ttl, options.timeout)?
+            };
+
+            match outcome {
+                ProbeOutcome::Silent => hop.rtts_ms.push(None),
+                ProbeOutcome::Hop(from, rtt) => {
+                    hop.address.get_or_insert(from);
+                    hop.rtts_ms.push(Some(rtt));
+                }
+                ProbeOutcome::Reached(from, rtt) => {
+                    hop.address.get_or_insert(from);
+                    hop.rtts_ms.push(Some(rtt));
+                    stop = true;
+                    reached = true;
+                }
+                ProbeOutcome::Unreachable(from, rtt, reason) => {
+                    hop.address.get_or_insert(from);
+                    hop.rtts_ms.push(Some(rtt));
+                    hop.note = Some(reason);
+                    stop = true;
+                }
+            }
+        }
+
+        let answered: Vec<f64> = hop.rtts_ms.iter().flatten().copied().collect();
+        if !answered.is_empty() {
+            hop.min_ms = answered.iter().copied().reduce(f64::min);
+            hop.max_ms = answered.iter().copied().reduce(f64::max);
+            hop.avg_ms = Some(answered.iter().sum::<f64>() / answered.len() as f64);
+        }
+
+        if let (Some(addr), true) = (hop.address, options.resolve_names) {
+            hop.hostname = names.entry(addr)
+                .or_insert_with(|| reverse_lookup(addr, dns_server, Duration::from_secs(1)).ok().flatten())
+                .clone();
+        }
+
+        hops.push(hop);
+        if stop {
+            break;
+        }
+    }
+
+    Ok(TracerouteReport {
+        target: host.to_string(),
+        address,
+        method: options.method,
+        max_ttl: options.max_ttl,
+        receiver: receiver_name.to_string(),
+        reached,
+        hops
+    })
+}
+
+fn choose_receiver(method: TraceMethod, v6: bool) -> Result<Receiver, String> {
+    if !cfg!(unix) {
+        return Err("Traceroute is not supported on this platform".to_string());
+    }
+
+    let access = icmp::icmp_access(v6);
+    if access == IcmpAccess::Raw {
+        let socket = icmp::open_icmp(v6, true).map_err(|e| format!("Unable to open raw ICMP socket: {}", e))?;
+        return Ok(Receiver::Raw(socket));
+    }
+
+    let reason = match access {
+        IcmpAccess::Unavailable(reason) => reason,
+        _ => "raw sockets require root or CAP_NET_RAW".to_string()
+    };
+
+    match method {
+        #[cfg(target_os = "linux")]
+        TraceMethod::Udp => Ok(Receiver::ErrorQueue),
+        #[cfg(not(target_os = "linux"))]
+        TraceMethod::Udp => Err(format!("Traceroute unavailable: UDP probes need raw ICMP sockets here ({})", reason)),
+        TraceMethod::Tcp => Err(format!("Traceroute unavailable: TCP probes need raw ICMP sockets ({}); run privileged or use UDP", reason))
+    }
+}
+
+fn ip_socket(target: SocketAddr, kind: Type, protocol: Protocol, ttl: u8) -> Result<Socket, String> {
+    let domain = if target.is_ipv6() { Domain::IPV6 } else { Domain::IPV4 };
+    let socket = Socket::new(domain, kind, Some(protocol)).map_err(|e| format!("Unable to create probe socket: {}", e))?;
+
+    let result = if target.is_ipv6() { socket.set_unicast_hops_v6(ttl as u32) } else { socket.set_ttl(ttl as u32) };
+    result.map_err(|e| format!("Unable to set probe TTL: {}", e))?;
+
+    Ok(socket)
+}
+
+fn udp_probe(receiver: &Receiver, target: SocketAddr, ttl: u8, timeout: Duration) -> Result<ProbeOutcome, String> {
+    let v6 = target.is_ipv6();
+    let socket = ip_socket(target, Type::DGRAM, Protocol::UDP, ttl)?;
+
+    #[cfg(target_os = "linux")]
+    if let Receiver::ErrorQueue = receiver {
+        icmp::enable_recverr(&socket, v6).map_err(|e| format!("Unable to enable IP_RECVERR: {}", e))?;
+    }
+
+    socket.connect(&target.into()).map_err(|e| format!("Unable to address probe to {}: {}", target, e))?;
+
+    let start = Instant::now();
+    if let Err(e) = socket.send(b"mercy-traceroute") {
+        // An immediately reported error (e.g. no route) ends the trace at this hop
+        return Err(format!("Unable to send probe: {}", e));
+    }
+
+    match receiver {
+        Receiver::Raw(raw) => {
+            let deadline = start + timeout;
+
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(ProbeOutcome::Silent);
+                }
+
+                let message = match icmp::recv_icmp(raw, v6, true, remaining).map_err(|e| format!("Unable to read ICMP: {}", e))? {
+                    Some(message) => message,
+                    None => return Ok(ProbeOutcome::Silent)
+                };
+
+                let matches = message.quoted(v6).map(|q| q.protocol == 17 && q.destination == target.ip() && q.dst_port == target.port()).unwrap_or(false);
+                if matches {
+                    return Ok(classify(v6, message.source, message.icmp_type, message.code, target.ip(), elapsed_ms(start)));
+                }
+            }
+        }
+        #[cfg(target_os = "linux")]
+        Receiver::ErrorQueue => {
+            match icmp::recv_queued_error(&socket, timeout).map_err(|e| format!("Unable to read socket error queue: {}", e))? {
+                Some(error) => match error.offender {
+                    Some(from) => Ok(classify(v6, from, error.icmp_type, error.code, target.ip(), elapsed_ms(start))),
+                    None => Ok(ProbeOutcome::Silent)
+                },
+                None => Ok(ProbeOutcome::Silent)
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn tcp_probe(receiver: &Receiver, target: SocketAddr, ttl: u8, timeout: Duration) -> Result<ProbeOutcome, String> {
+    use std::os::unix::io::AsRawFd;
+
+    let raw = match receiver {
+        Receiver::Raw(raw) => raw,
+        #[cfg(target_os = "linux")]
+        Receiver::ErrorQueue => return Err("TCP traceroute requires raw ICMP sockets".to_string())
+    };
+
+    let v6 = target.is_ipv6();
+    let socket = ip_socket(target, Type::STREAM, Protocol::TCP, ttl)?;
+    socket.set_nonblocking(true).map_err(|e| format!("Unable to configure probe socket: {}", e))?;
+
+    let start = Instant::now();
+    match socket.connect(&target.into()) {
+        Ok(()) => return Ok(ProbeOutcome::Reached(target.ip(), elapsed_ms(start))),
+        Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) || e.kind() == std::io::ErrorKind::WouldBlock => {}
+        Err(e) => return Err(format!("Unable to send TCP probe: {}", e))
+    }
+
+    let local_port = socket.local_addr().ok().and_then(|a| a.as_socket()).map(|a| a.port()).unwrap_or(0);
+    let deadline = start + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(ProbeOutcome::Silent);
+        }
+
+        let mut fds = [
+            libc::pollfd { fd: socket.as_raw_fd(), events: libc::POLLOUT, revents: 0 },
+            libc::pollfd { fd: raw.as_raw_fd(), events: libc::POLLIN, revents: 0 }
+        ];
+
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), 2, remaining.as_millis().min(i32::MAX as u128) as libc::c_int) };
+        if ready < 0 {
+            return Err(format!("Unable to wait for probe: {}", std::io::Error::last_os_error()));
+        }
+
+        if ready == 0 {
+            return Ok(ProbeOutcome::Silent);
+        }
+
+        // The connection completing or being refused both mean the destination answered
+        if fds[0].revents != 0 {
+            match socket.take_error() {
+                Ok(None) => return Ok(ProbeOutcome::Reached(target.ip(), elapsed_ms(start))),
+                Ok(Some(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    return Ok(ProbeOutcome::Reached(target.ip(), elapsed_ms(start)));
+                }
+                // Host/net unreachable errors also surface here; the raw socket carries the detail
+                _ => {}
+            }
+        }
+
+        if fds[1].revents != 0 {
+            if let Some(message) = icmp::recv_icmp(raw, v6, true, Duration::from_millis(0)).map_err(|e| format!("Unable to read ICMP: {}", e))? {
+                let matches = message.quoted(v6).map(|q| q.protocol == 6 && q.destination == target.ip() && q.src_port == local_port).unwrap_or(false);
+                if matches {
+                    return Ok(classify(v6, message.source, message.icmp_type, message.code, target.ip(), elapsed_ms(start)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn tcp_probe(_receiver: &Receiver, _target: SocketAddr, _ttl: u8, _timeout: Duration) -> Result<ProbeOutcome, String> {
+    Err("Traceroute is not supported on this platform".to_string())
+}
+
+// Maps an ICMP type/code pair to what it means for the trace
+fn classify(v6: bool, from: IpAddr, icmp_type: u8, code: u8, target: IpAddr, rtt: f64) -> ProbeOutcome {
+    let (time_exceeded, unreachable, port_unreachable) = if v6 {
+        (icmp::ICMP6_TIME_EXCEEDED, icmp::ICMP6_DEST_UNREACH, 4)
+    } else {
+        (icmp::ICMP4_TIME_EXCEEDED, icmp::ICMP4_DEST_UNREACH, 3)
+    };
+
+    if icmp_type == time_exceeded {
+        ProbeOutcome::Hop(from, rtt)
+    } else if icmp_type == unreachable && (code == port_unreachable || from == target) {
+        ProbeOutcome::Reached(from, rtt)
+    } else if icmp_type == unreachable {
+        let reason = match (v6, code) {
+            (false, 0) | (true, 0) => "!N (network unreachable)",
+            (false, 1) | (true, 3) => "!H (host unreachable)",
+            (false, 13) | (true, 1) => "!X (administratively prohibited)",
+            _ => "! (unreachable)"
+        };
+        ProbeOutcome::Unreachable(from, rtt, reason.to_string())
+    } else {
+        ProbeOutcome::Unreachable(from, rtt, format!("unexpected ICMP type {} code {}", icmp_type, code))
+    }
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
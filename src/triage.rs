@@ -0,0 +1,513 @@
+/*
+    One-shot triage of a single indicator
+
+    The input is refanged and classified (IP address, domain, URL, email address or hash),
+    then the checks that make sense for that kind run side by side, each on its own thread
+    with its own deadline:
+
+    - domain (and the domain of an email address): A/AAAA/MX/NS records, WHOIS registration
+      age, InQuest reputation and the sinkhole check
+    - IP address: special-purpose range, origin ASN with its country, and reverse DNS
+    - URL: a breakdown of the URL, the security headers of the page it serves and the
+      reputation of its host
+    - hash: the algorithm its length and alphabet point to
+
+    A step that fails, panics or runs out of time is reported as such and the others still
+    complete, so one dead provider costs one line of the report rather than the report.
+*/
+
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}
+};
+
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    asn::{AsnOptions, mercy_asn_lookup},
+    bogon::bogon_range,
+    config,
+    dns::{DnsQuery, DnsType, default_dns_server, dns_server_addr, reverse_lookup},
+    finding::{Finding, Severity},
+    headers::audit_security_headers,
+    http::{HttpIdentity, IDENTITY_KEYS, http_client, identity_option},
+    net::{self, KEEP_LAST},
+    malicious_domain_report,
+    refang,
+    runtime,
+    sinkhole::{SinkholeOptions, mercy_sinkhole_check},
+    timefmt::parse_utc,
+    trailing_options,
+    whois::mercy_whois
+};
+
+// Registered more recently than this many days counts as a young domain
+const YOUNG_DOMAIN_DAYS: i64 = 30;
+
+/// What kind of indicator the input is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndicatorKind {
+    Ip,
+    Domain,
+    Url,
+    Email,
+    Hash
+}
+
+impl fmt::Display for IndicatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndicatorKind::Ip => write!(f, "IP address"),
+            IndicatorKind::Domain => write!(f, "domain"),
+            IndicatorKind::Url => write!(f, "URL"),
+            IndicatorKind::Email => write!(f, "email address"),
+            IndicatorKind::Hash => write!(f, "hash")
+        }
+    }
+}
+
+/// A classified indicator
+#[derive(Debug, Clone, Serialize)]
+pub struct Indicator {
+    pub kind: IndicatorKind,
+    /// The input refanged, trimmed and (for domains and email addresses) lowercased
+    pub value: String
+}
+
+impl Indicator {
+    /// Classifies one indicator, refanging it first ("hxxps://evil[.]com" is a URL)
+    pub fn parse(input: &str) -> Result<Indicator, String> {
+        let value = refang(input.trim()).trim().to_string();
+        let indicator = |kind, value: String| Ok(Indicator { kind, value });
+
+        if value.is_empty() {
+            return Err("No indicator given".to_string());
+        }
+        if let Ok(ip) = value.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            return indicator(IndicatorKind::Ip, ip.to_string());
+        }
+        if value.contains("://") {
+            let url = Url::parse(&value).map_err(|e| format!("Invalid URL '{}': {}", value, e))?;
+            return indicator(IndicatorKind::Url, url.to_string());
+        }
+        if hash_algorithm(&value).is_some() {
+            return indicator(IndicatorKind::Hash, value.to_ascii_lowercase());
+        }
+        if let Some((local, domain)) = value.rsplit_once('@') {
+            if !local.is_empty() && valid_domain(domain) {
+                return indicator(IndicatorKind::Email, value.to_lowercase());
+            }
+        }
+        let domain = value.trim_end_matches('.').to_lowercase();
+        if valid_domain(&domain) {
+            return indicator(IndicatorKind::Domain, domain);
+        }
+
+        Err(format!("'{}' is not an IP address, domain, URL, email address or hash", value))
+    }
+}
+
+fn valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| !label.is_empty() && label.len() <= 63 && label.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_'))
+        && !labels[labels.len() - 1].chars().all(|c| c.is_ascii_digit())
+}
+
+// The hash algorithm a hex digest's length implies, or ssdeep for "blocksize:hash:hash"
+fn hash_algorithm(value: &str) -> Option<&'static str> {
+    let mut parts = value.split(':');
+    if let (Some(block), Some(_), Some(_), None) = (parts.next(), parts.next(), parts.next(), parts.next()) {
+        if !block.is_empty() && block.chars().all(|c| c.is_ascii_digit()) {
+            return Some("ssdeep");
+        }
+    }
+    if !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    match value.len() {
+        32 => Some("md5"),
+        40 => Some("sha1"),
+        56 => Some("sha224"),
+        64 => Some("sha256"),
+        96 => Some("sha384"),
+        128 => Some("sha512"),
+        _ => None
+    }
+}
+
+/// Options for `mercy_triage`
+#[derive(Debug, Clone)]
+pub struct TriageOptions {
+    /// Longest any one step may take; steps run side by side
+    pub step_timeout: Duration,
+    /// DNS server (default: the system resolver's first nameserver)
+    pub server: Option<SocketAddr>,
+    /// HTTP identity for the page fetch of a URL
+    pub identity: HttpIdentity
+}
+
+impl Default for TriageOptions {
+    fn default() -> Self {
+        TriageOptions {
+            step_timeout: Duration::from_secs(15),
+            server: None,
+            identity: HttpIdentity::default()
+        }
+    }
+}
+
+/// How a step ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriageStatus {
+    Ok,
+    Failed,
+    TimedOut
+}
+
+impl fmt::Display for TriageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriageStatus::Ok => write!(f, "ok"),
+            TriageStatus::Failed => write!(f, "failed"),
+            TriageStatus::TimedOut => write!(f, "timed out")
+        }
+    }
+}
+
+/// One check of a triage run
+#[derive(Debug, Clone, Serialize)]
+pub struct TriageStep {
+    pub name: String,
+    pub status: TriageStatus,
+    pub duration_ms: f64,
+    /// One line for the human summary
+    pub summary: String,
+    /// What the step found, in the shape of the underlying check's JSON
+    pub data: Option<Value>,
+    pub error: Option<String>
+}
+
+/// Every step run for an indicator, with the findings they raised
+#[derive(Debug, Clone, Serialize)]
+pub struct TriageReport {
+    pub input: String,
+    pub indicator: Indicator,
+    pub steps: Vec<TriageStep>,
+    pub findings: Vec<Finding>,
+    pub duration_ms: f64
+}
+
+impl fmt::Display for TriageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.steps.iter().filter(|step| step.status != TriageStatus::Ok).count();
+        writeln!(f, "Triage of {} {}: {} step(s), {} failed, {} finding(s) in {:.0} ms", self.indicator.kind, self.indicator.value,
+            self.steps.len(), failed, self.findings.len(), self.duration_ms)?;
+
+        for step in &self.steps {
+            match &step.error {
+                Some(error) => writeln!(f, "  {:<12} {}: {}", step.name, step.status, error)?,
+                None => writeln!(f, "  {:<12} {}", step.name, step.summary)?
+            }
+        }
+
+        for finding in &self.findings {
+            writeln!(f)?;
+            write!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+// What a step returns when it works
+struct StepOutput {
+    summary: String,
+    data: Value,
+    findings: Vec<Finding>
+}
+
+impl StepOutput {
+    fn new(summary: String, data: Value) -> StepOutput {
+        StepOutput { summary, data, findings: Vec::new() }
+    }
+}
+
+type Step = (&'static str, Box<dyn FnOnce() -> Result<StepOutput, String> + Send>);
+
+// A step's result and how long it took in milliseconds
+type StepResult = (Result<StepOutput, String>, f64);
+
+/// Classifies the indicator and runs the checks for its kind, keeping whatever completes
+pub fn mercy_triage(input: &str, options: &TriageOptions) -> Result<TriageReport, String> {
+    let started = Instant::now();
+    let indicator = Indicator::parse(input)?;
+    let server = options.server.unwrap_or_else(default_dns_server);
+    let timeout = options.step_timeout;
+
+    let steps: Vec<Step> = match indicator.kind {
+        IndicatorKind::Domain => domain_steps(&indicator.value, server, timeout),
+        IndicatorKind::Email => domain_steps(indicator.value.rsplit('@').next().unwrap_or(""), server, timeout),
+        IndicatorKind::Ip => {
+            let ip: IpAddr = indicator.value.parse().map_err(|_| format!("Invalid IP address '{}'", indicator.value))?;
+            ip_steps(ip, server, timeout)
+        },
+        IndicatorKind::Url => url_steps(&indicator.value, &options.identity, timeout),
+        IndicatorKind::Hash => {
+            let hash = indicator.value.clone();
+            vec![("algorithm", Box::new(move || hash_step(&hash)))]
+        }
+    };
+
+    let (steps, findings) = run_steps(steps, timeout);
+    Ok(TriageReport {
+        input: input.trim().to_string(),
+        indicator,
+        steps,
+        findings,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0
+    })
+}
+
+// Starts every step on its own thread and waits for each until the shared deadline
+fn run_steps(steps: Vec<Step>, timeout: Duration) -> (Vec<TriageStep>, Vec<Finding>) {
+    let started = Instant::now();
+    let deadline = started + timeout;
+
+    let receivers: Vec<(&str, mpsc::Receiver<StepResult>)> = steps.into_iter()
+        .map(|(name, step)| {
+            let (sender, receiver) = mpsc::channel();
+            let step = config::propagate_fn(step);
+            // A step still running at the deadline is left to finish on its own; its result is dropped
+            thread::spawn(move || {
+                let step_started = Instant::now();
+                let result = step();
+                let _ = sender.send((result, step_started.elapsed().as_secs_f64() * 1000.0));
+            });
+            (name, receiver)
+        })
+        .collect();
+
+    let mut report = Vec::new();
+    let mut findings = Vec::new();
+    for (name, receiver) in receivers {
+        let (status, duration_ms, result) = match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((result, duration_ms)) => (if result.is_ok() { TriageStatus::Ok } else { TriageStatus::Failed }, duration_ms, result),
+            Err(mpsc::RecvTimeoutError::Timeout) => (TriageStatus::TimedOut, timeout.as_secs_f64() * 1000.0, Err(format!("no answer within {} s", timeout.as_secs()))),
+            // The thread ended without sending: the step panicked
+            Err(mpsc::RecvTimeoutError::Disconnected) => (TriageStatus::Failed, started.elapsed().as_secs_f64() * 1000.0, Err("the step crashed".to_string()))
+        };
+
+        report.push(match result {
+            Ok(output) => {
+                findings.extend(output.findings);
+                TriageStep { name: name.to_string(), status, duration_ms, summary: output.summary, data: Some(output.data), error: None }
+            },
+            Err(e) => TriageStep { name: name.to_string(), status, duration_ms, summary: String::new(), data: None, error: Some(e) }
+        });
+    }
+
+    (report, findings)
+}
+
+fn domain_steps(domain: &str, server: SocketAddr, timeout: Duration) -> Vec<Step> {
+    let (dns, whois, reputation, sinkhole) = (domain.to_string(), domain.to_string(), domain.to_string(), domain.to_string());
+
+    vec![
+        ("dns", Box::new(move || dns_step(&dns, server, timeout))),
+        ("whois", Box::new(move || whois_step(&whois))),
+        ("reputation", Box::new(move || reputation_step(&reputation))),
+        ("sinkhole", Box::new(move || {
+            let options = SinkholeOptions { server: Some(server), timeout, ..Default::default() };
+            let report = mercy_sinkhole_check(&sinkhole, &options)?;
+            let mut output = StepOutput::new(if report.sinkholed { "sinkholed".to_string() } else { "no sinkhole indicators".to_string() }, json!(report));
+            if report.sinkholed {
+                let evidence: Vec<String> = report.evidence.iter().map(|evidence| format!("{} {} ({})", evidence.kind, evidence.value, evidence.label)).collect();
+                output.findings.push(Finding::new("triage.sinkholed", "Domain is sinkholed", Severity::Info, &sinkhole, &evidence.join("; "),
+                    "The domain is under a researcher's, vendor's or registry's control; traffic to it shows infected hosts rather than live attacker infrastructure"));
+            }
+            Ok(output)
+        }))
+    ]
+}
+
+fn dns_step(domain: &str, server: SocketAddr, timeout: Duration) -> Result<StepOutput, String> {
+    let mut records = serde_json::Map::new();
+    let mut summary = Vec::new();
+
+    for qtype in [DnsType::A, DnsType::Aaaa, DnsType::Mx, DnsType::Ns] {
+        let response = DnsQuery::new(domain, qtype).send(server, timeout)?;
+        if response.rcode == 3 {
+            return Ok(StepOutput::new("NXDOMAIN (the name does not exist)".to_string(), json!({ "rcode": "NXDOMAIN" })));
+        }
+
+        let values: Vec<String> = response.answers.iter().filter(|record| record.rtype == qtype).map(|record| record.rdata.to_string()).collect();
+        if !values.is_empty() {
+            summary.push(format!("{} {}", qtype, values.join(", ")));
+        }
+        records.insert(qtype.to_string(), json!(values));
+    }
+
+    Ok(StepOutput::new(if summary.is_empty() { "no records".to_string() } else { summary.join("; ") }, Value::Object(records)))
+}
+
+fn whois_step(domain: &str) -> Result<StepOutput, String> {
+    let response = mercy_whois(domain)?;
+    let created = response.record.lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(key, _)| matches!(key.trim().to_ascii_lowercase().as_str(), "creation date" | "created" | "registered on" | "registration time"))
+        .map(|(_, value)| value.trim().to_string());
+    let Some(created) = created else {
+        return Ok(StepOutput::new("no creation date in the WHOIS response".to_string(), json!({ "created": null })));
+    };
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as i64).unwrap_or(0);
+    let age_days = parse_utc(&created).ok().map(|secs| (now - secs) / 86_400);
+    let summary = match age_days {
+        Some(days) => format!("registered {} ({} days ago)", created, days),
+        None => format!("registered {}", created)
+    };
+
+    let mut output = StepOutput::new(summary, json!({ "created": created, "age_days": age_days }));
+    if let Some(days) = age_days.filter(|days| *days < YOUNG_DOMAIN_DAYS) {
+        output.findings.push(Finding::new("triage.young-domain", "Recently registered domain", Severity::Medium, domain,
+            &format!("Creation Date: {} ({} days ago)", created, days), "Treat with suspicion: most phishing and malware domains are used within days of registration"));
+    }
+    Ok(output)
+}
+
+fn reputation_step(domain: &str) -> Result<StepOutput, String> {
+    let report = runtime::block_on(malicious_domain_report(domain))?;
+    let mut output = StepOutput::new(format!("InQuest: {} ({} sample(s))", report.status(), report.samples.len()), json!(report));
+
+    let severity = match report.classification.as_deref() {
+        Some("MALICIOUS") => Some(Severity::High),
+        Some("SUSPICIOUS") => Some(Severity::Medium),
+        _ => None
+    };
+    if let Some(severity) = severity {
+        output.findings.push(Finding::new("triage.reputation", "Indicator has a bad reputation", severity, domain,
+            &format!("InQuest classifies it {} ({} malicious, {} suspicious sample(s))", report.status(), report.malicious, report.suspicious),
+            "Block the indicator and look for connections to it"));
+    }
+    Ok(output)
+}
+
+fn ip_steps(ip: IpAddr, server: SocketAddr, timeout: Duration) -> Vec<Step> {
+    vec![
+        ("bogon", Box::new(move || Ok(match bogon_range(ip) {
+            Some((category, description)) => StepOutput::new(format!("{} ({})", description, category), json!({ "bogon": true, "category": category, "description": description })),
+            None => StepOutput::new("globally routable".to_string(), json!({ "bogon": false }))
+        }))),
+        ("asn", Box::new(move || {
+            let options = AsnOptions { server: Some(server), timeout, concurrency: 1, ..Default::default() };
+            let report = mercy_asn_lookup(&[ip], &options);
+            let info = report.results.into_iter().next().ok_or_else(|| "no ASN answer".to_string())?;
+            let summary = match (info.asn, &info.note) {
+                (Some(_), _) => info.to_string().split(" | ").skip(1).collect::<Vec<&str>>().join(" | "),
+                (None, Some(note)) => note.clone(),
+                (None, None) => "no origin found".to_string()
+            };
+            Ok(StepOutput::new(summary, json!(info)))
+        })),
+        ("reverse_dns", Box::new(move || {
+            let name = reverse_lookup(ip, server, timeout)?;
+            Ok(StepOutput::new(name.clone().unwrap_or_else(|| "no PTR record".to_string()), json!({ "ptr": name })))
+        }))
+    ]
+}
+
+fn url_steps(url: &str, identity: &HttpIdentity, timeout: Duration) -> Vec<Step> {
+    let (breakdown, page) = (url.to_string(), url.to_string());
+    let identity = identity.clone();
+    let mut steps: Vec<Step> = vec![
+        ("url", Box::new(move || url_step(&breakdown))),
+        ("headers", Box::new(move || headers_step(&page, &identity, timeout)))
+    ];
+
+    let host = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+    if let Some(host) = host.filter(|host| host.parse::<IpAddr>().is_err() && !host.starts_with('[')) {
+        steps.push(("reputation", Box::new(move || reputation_step(&host))));
+    }
+    steps
+}
+
+fn url_step(url: &str) -> Result<StepOutput, String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+    let host = parsed.host_str().unwrap_or("").to_string();
+    let port = parsed.port();
+    let query: Vec<(String, String)> = parsed.query_pairs().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+    let mut output = StepOutput::new(
+        format!("{} host {}{}, path {}, {} query parameter(s)", parsed.scheme(), host, port.map(|port| format!(":{}", port)).unwrap_or_default(), parsed.path(), query.len()),
+        json!({ "scheme": parsed.scheme(), "host": host, "port": port, "path": parsed.path(), "query": query, "username": parsed.username(), "defanged": crate::defang(url) })
+    );
+
+    if !parsed.username().is_empty() {
+        output.findings.push(Finding::new("triage.url-userinfo", "URL carries a user name before the host", Severity::Medium, url,
+            &format!("user name '{}' before host {}", parsed.username(), host), "A user name that looks like a domain hides the real host from readers; the browser goes to the host after '@'"));
+    }
+    if host.split('.').any(|label| label.starts_with("xn--")) {
+        output.findings.push(Finding::new("triage.url-punycode", "URL host is internationalized", Severity::Low, url,
+            &format!("host {}", host), "Check the host for look-alike characters (see safe_display)"));
+    }
+    if host.parse::<IpAddr>().is_ok() || host.starts_with('[') {
+        output.findings.push(Finding::new("triage.url-ip-host", "URL points at a bare IP address", Severity::Low, url,
+            &format!("host {}", host), "Legitimate sites rarely link to bare addresses; check who hosts it"));
+    }
+    Ok(output)
+}
+
+fn headers_step(url: &str, identity: &HttpIdentity, timeout: Duration) -> Result<StepOutput, String> {
+    let client = http_client(identity)?;
+    let (status, final_url, headers) = runtime::block_on(async {
+        let response = net::send_with(client.get(url).timeout(timeout), &KEEP_LAST).await.map_err(|e| format!("Unable to fetch {}: {}", url, e))?.response;
+        let headers: Vec<(String, String)> = response.headers().iter()
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).to_string()))
+            .collect();
+        Ok::<_, String>((response.status().as_u16(), response.url().to_string(), headers))
+    })?;
+
+    let mut output = StepOutput::new(
+        format!("HTTP {} from {}", status, final_url),
+        json!({ "status": status, "final_url": final_url, "headers": headers.iter().map(|(name, value)| json!({ "name": name, "value": value })).collect::<Vec<Value>>() })
+    );
+    output.findings = audit_security_headers(&final_url, &headers);
+    Ok(output)
+}
+
+fn hash_step(hash: &str) -> Result<StepOutput, String> {
+    let algorithm = hash_algorithm(hash).ok_or_else(|| format!("'{}' is not a recognised hash", hash))?;
+    let note = match algorithm {
+        "md5" => "MD5, or another 128-bit digest (NTLM, MD4)",
+        "sha1" => "SHA-1, or another 160-bit digest (RIPEMD-160)",
+        "sha256" => "SHA-256, or another 256-bit digest (SHA3-256, BLAKE2s)",
+        "sha512" => "SHA-512, or another 512-bit digest (SHA3-512, BLAKE2b)",
+        "ssdeep" => "ssdeep fuzzy hash",
+        _ => algorithm
+    };
+    Ok(StepOutput::new(note.to_string(), json!({ "algorithm": algorithm, "bits": (algorithm != "ssdeep").then_some(hash.len() * 4) })))
+}
+
+// Parses "indicator [timeout=15] [server=1.1.1.1]" plus HTTP identity options
+pub(crate) fn triage_from_args(input: &str) -> Result<TriageReport, String> {
+    let keys: Vec<&str> = ["timeout", "server"].into_iter().chain(IDENTITY_KEYS).collect();
+    let (indicator, pairs) = trailing_options(input, &keys);
+    let mut options = TriageOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "timeout" => options.step_timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            "server" => options.server = Some(dns_server_addr(value)?),
+            _ => identity_option(&mut options.identity, key, value)?
+        }
+    }
+
+    mercy_triage(&indicator, &options)
+}
@@ -0,0 +1,464 @@
+/*
+    Invisible, bidirectional and mixed-script characters in text
+
+    Trojan-source attacks hide code behind bidi controls (an RLO reorders everything after it
+    until the end of the line) and invisible characters split identifiers or smuggle data in
+    tag and variation-selector sequences. Every non-ASCII codepoint is listed with its name,
+    general category and script, and the characters that change how text displays without
+    showing themselves are reported with char offsets (not byte offsets), lines and columns.
+    Words that mix scripts ("pаypal" with a Cyrillic а) are reported unless the combination is
+    one that real text uses (Latin with Han and kana, or with Han and Hangul).
+
+    Names and categories come from a table covering the Latin, Greek, Cyrillic, Hebrew and
+    Arabic blocks, kana, punctuation, symbols, dingbats, fullwidth forms and every invisible or control
+    character; CJK ideographs, Hangul syllables and the variation selector supplement are
+    named algorithmically. Other characters have no name and a category approximated from
+    their properties.
+*/
+
+use std::{collections::BTreeMap, fmt, fs, path::Path, sync::OnceLock};
+
+use serde::Serialize;
+
+use crate::{paths::path_to_string, text::read_text_file};
+
+// Codepoint, general category and name, one per line ("202E Cf RIGHT-TO-LEFT OVERRIDE")
+const UNICODE_NAMES: &str = include_str!("unicode_names.txt");
+
+// Largest file scanned
+const MAX_FILE: u64 = 64 * 1024 * 1024;
+
+// Script combinations allowed within one word (Japanese, Chinese and Korean text)
+const ALLOWED_MIXES: &[&[&str]] = &[
+    &["Latin", "Han", "Hiragana", "Katakana"],
+    &["Latin", "Han", "Bopomofo"],
+    &["Latin", "Han", "Hangul"]
+];
+
+// First and last codepoint of each script range; anything else is Common unless it is alphabetic
+const SCRIPTS: &[(u32, u32, &str)] = &[
+    (0x0041, 0x005A, "Latin"), (0x0061, 0x007A, "Latin"), (0x00AA, 0x00AA, "Latin"), (0x00BA, 0x00BA, "Latin"),
+    (0x00C0, 0x00D6, "Latin"), (0x00D8, 0x00F6, "Latin"), (0x00F8, 0x02AF, "Latin"),
+    (0x0300, 0x036F, "Inherited"), (0x0370, 0x03FF, "Greek"), (0x0400, 0x052F, "Cyrillic"),
+    (0x0530, 0x058F, "Armenian"), (0x0590, 0x05FF, "Hebrew"), (0x0600, 0x06FF, "Arabic"),
+    (0x0700, 0x074F, "Syriac"), (0x0750, 0x077F, "Arabic"), (0x0780, 0x07BF, "Thaana"),
+    (0x08A0, 0x08FF, "Arabic"), (0x0900, 0x097F, "Devanagari"), (0x0980, 0x09FF, "Bengali"),
+    (0x0A00, 0x0A7F, "Gurmukhi"), (0x0A80, 0x0AFF, "Gujarati"), (0x0B00, 0x0B7F, "Oriya"),
+    (0x0B80, 0x0BFF, "Tamil"), (0x0C00, 0x0C7F, "Telugu"), (0x0C80, 0x0CFF, "Kannada"),
+    (0x0D00, 0x0D7F, "Malayalam"), (0x0D80, 0x0DFF, "Sinhala"), (0x0E00, 0x0E7F, "Thai"),
+    (0x0E80, 0x0EFF, "Lao"), (0x0F00, 0x0FFF, "Tibetan"), (0x1000, 0x109F, "Myanmar"),
+    (0x10A0, 0x10FF, "Georgian"), (0x1100, 0x11FF, "Hangul"), (0x1200, 0x139F, "Ethiopic"),
+    (0x13A0, 0x13FF, "Cherokee"), (0x1780, 0x17FF, "Khmer"), (0x1800, 0x18AF, "Mongolian"),
+    (0x1AB0, 0x1AFF, "Inherited"), (0x1C80, 0x1C8F, "Cyrillic"), (0x1C90, 0x1CBF, "Georgian"),
+    (0x1D00, 0x1D25, "Latin"), (0x1D26, 0x1D2A, "Greek"), (0x1D2B, 0x1D2B, "Cyrillic"),
+    (0x1D2C, 0x1D7F, "Latin"), (0x1DC0, 0x1DFF, "Inherited"), (0x1E00, 0x1EFF, "Latin"),
+    (0x1F00, 0x1FFF, "Greek"), (0x200C, 0x200D, "Inherited"), (0x20D0, 0x20FF, "Inherited"),
+    (0x2C60, 0x2C7F, "Latin"), (0x2D00, 0x2D2F, "Georgian"), (0x2DE0, 0x2DFF, "Cyrillic"),
+    (0x2E80, 0x2FDF, "Han"), (0x3005, 0x3005, "Han"), (0x3007, 0x3007, "Han"), (0x3021, 0x3029, "Han"),
+    (0x3038, 0x303B, "Han"), (0x3041, 0x309F, "Hiragana"), (0x30A1, 0x30FA, "Katakana"),
+    (0x30FD, 0x30FF, "Katakana"), (0x3100, 0x312F, "Bopomofo"), (0x3131, 0x318F, "Hangul"),
+    (0x31F0, 0x31FF, "Katakana"), (0x3400, 0x4DBF, "Han"), (0x4E00, 0x9FFF, "Han"),
+    (0xA640, 0xA69F, "Cyrillic"), (0xA720, 0xA7FF, "Latin"), (0xA960, 0xA97F, "Hangul"),
+    (0xAB30, 0xAB6F, "Latin"), (0xAB70, 0xABBF, "Cherokee"), (0xAC00, 0xD7FF, "Hangul"),
+    (0xF900, 0xFAFF, "Han"), (0xFB00, 0xFB06, "Latin"), (0xFB1D, 0xFB4F, "Hebrew"),
+    (0xFB50, 0xFDFF, "Arabic"), (0xFE00, 0xFE0F, "Inherited"), (0xFE20, 0xFE2F, "Inherited"),
+    (0xFE70, 0xFEFE, "Arabic"), (0xFF21, 0xFF3A, "Latin"), (0xFF41, 0xFF5A, "Latin"),
+    (0xFF66, 0xFF9F, "Katakana"), (0xFFA0, 0xFFDC, "Hangul"), (0x20000, 0x3FFFF, "Han"), (0xE0100, 0xE01EF, "Inherited")
+];
+
+// Hangul syllable name parts: leading consonants, vowels and trailing consonants
+const HANGUL_LEADS: [&str; 19] = ["G", "GG", "N", "D", "DD", "R", "M", "B", "BB", "S", "SS", "", "J", "JJ", "C", "K", "T", "P", "H"];
+const HANGUL_VOWELS: [&str; 21] = ["A", "AE", "YA", "YAE", "EO", "E", "YEO", "YE", "O", "WA", "WAE", "OE", "YO", "U", "WEO", "WE", "WI", "YU", "EU", "YI", "I"];
+const HANGUL_TAILS: [&str; 28] = ["", "G", "GG", "GS", "N", "NJ", "NH", "D", "L", "LG", "LM", "LB", "LS", "LT", "LP", "LH", "M", "B", "BS", "S", "SS", "NG", "J", "C", "K", "T", "P", "H"];
+
+/// A distinct non-ASCII character of the text
+#[derive(Debug, Clone, Serialize)]
+pub struct UnicodeCharacter {
+    /// "U+202E"
+    pub codepoint: String,
+    pub character: String,
+    /// None for characters outside the name table
+    pub name: Option<String>,
+    /// General category ("Lu", "Cf", ...)
+    pub category: String,
+    /// "Latin", "Cyrillic", "Common", "Inherited", ...
+    pub script: String,
+    pub count: usize,
+    /// Char offset of the first occurrence
+    pub first_offset: usize
+}
+
+/// A character or word that changes how text displays or reads
+#[derive(Debug, Clone, Serialize)]
+pub struct UnicodeFinding {
+    /// "zero_width", "bidi_control", "bidi_unterminated", "control" or "mixed_script"
+    pub kind: String,
+    /// Char offset from the start of the text
+    pub offset: usize,
+    /// Length in chars
+    pub length: usize,
+    /// 1-based line and column (in chars)
+    pub line: usize,
+    pub column: usize,
+    /// The characters responsible ("U+202E")
+    pub codepoints: Vec<String>,
+    pub detail: String
+}
+
+/// Non-ASCII characters and suspicious characters of a text, with cleaned copies
+#[derive(Debug, Clone, Serialize)]
+pub struct UnicodeReport {
+    /// Set when the text was read from a file
+    pub path: Option<String>,
+    pub characters: usize,
+    pub lines: usize,
+    pub non_ascii: Vec<UnicodeCharacter>,
+    pub findings: Vec<UnicodeFinding>,
+    /// The text with each flagged character replaced by an escape ("\u{202E}")
+    pub cleaned: String,
+    /// The text with each flagged character removed
+    pub stripped: String
+}
+
+impl fmt::Display for UnicodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path)?;
+        }
+        writeln!(f, "{} characters on {} line(s), {} distinct non-ASCII, {} finding(s)", self.characters, self.lines, self.non_ascii.len(), self.findings.len())?;
+
+        if !self.findings.is_empty() {
+            writeln!(f, "\nFindings:")?;
+            for finding in &self.findings {
+                writeln!(f, "  {}:{} (offset {}) {}: {}", finding.line, finding.column, finding.offset, finding.kind, finding.detail)?;
+            }
+        }
+
+        if !self.non_ascii.is_empty() {
+            writeln!(f, "\nNon-ASCII characters:")?;
+            for character in &self.non_ascii {
+                let shown = if character.category.starts_with(['C', 'Z', 'M']) { String::new() } else { format!(" {}", character.character) };
+                writeln!(f, "  {:<8} {} {:<10}{} {} (x{})", character.codepoint, character.category, character.script,
+                    shown, character.name.as_deref().unwrap_or("(no name data)"), character.count)?;
+            }
+        }
+
+        // A whole file is too long to repeat here
+        if self.path.is_none() && !self.findings.is_empty() {
+            writeln!(f, "\nCleaned: {}", self.cleaned)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the non-ASCII characters of `text` and flags invisible, bidi, control and mixed-script characters
+pub fn mercy_unicode_inspect(text: &str) -> UnicodeReport {
+    let chars: Vec<char> = text.chars().collect();
+    let positions = line_positions(&chars);
+    let mut report = UnicodeReport {
+        path: None,
+        characters: chars.len(),
+        lines: positions.last().map_or(0, |position| position.0),
+        non_ascii: Vec::new(),
+        findings: Vec::new(),
+        cleaned: String::with_capacity(text.len()),
+        stripped: String::with_capacity(text.len())
+    };
+
+    let mut distinct: BTreeMap<char, UnicodeCharacter> = BTreeMap::new();
+    for (offset, c) in chars.iter().enumerate() {
+        if !c.is_ascii() {
+            distinct.entry(*c).or_insert_with(|| describe(*c, offset)).count += 1;
+        }
+
+        match flag(&chars, offset) {
+            Some((kind, detail)) => {
+                let (line, column) = positions[offset];
+                report.findings.push(UnicodeFinding {
+                    kind: kind.to_string(),
+                    offset,
+                    length: 1,
+                    line,
+                    column,
+                    codepoints: vec![codepoint(*c)],
+                    detail
+                });
+                report.cleaned.push_str(&format!("\\u{{{:04X}}}", *c as u32));
+            },
+            None => {
+                report.cleaned.push(*c);
+                report.stripped.push(*c);
+            }
+        }
+    }
+
+    report.findings.extend(unterminated_bidi(&chars, &positions));
+    report.findings.extend(mixed_scripts(&chars, &positions));
+    report.findings.sort_by_key(|finding| finding.offset);
+    report.non_ascii = distinct.into_values().collect();
+
+    report
+}
+
+/// Inspects a text file (UTF-8 or UTF-16), reporting each finding's line and column
+pub fn mercy_unicode_inspect_file<P: AsRef<Path>>(path: P) -> Result<UnicodeReport, String> {
+    let path = path.as_ref();
+    let size = fs::metadata(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?.len();
+    if size > MAX_FILE {
+        return Err(format!("{} is larger than the {} byte limit", path.display(), MAX_FILE));
+    }
+
+    let text = read_text_file(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    let mut report = mercy_unicode_inspect(&text);
+    report.path = Some(path_to_string(path));
+    Ok(report)
+}
+
+/* Classification */
+
+// Why a character is flagged, or None when it is harmless where it stands
+pub(crate) fn flag(chars: &[char], offset: usize) -> Option<(&'static str, String)> {
+    let c = chars[offset];
+    let value = c as u32;
+    let name = || name(c).unwrap_or_else(|| codepoint(c));
+
+    match value {
+        0x061C | 0x200E | 0x200F | 0x202A..=0x202E | 0x2066..=0x2069 => Some(("bidi_control", name())),
+        0x00AD | 0x034F | 0x115F | 0x1160 | 0x17B4 | 0x17B5 | 0x180B..=0x180F | 0x200B..=0x200D | 0x2060..=0x2064 | 0x3164 | 0xFEFF | 0xFFA0
+        | 0xFE00..=0xFE0F | 0x1BCA0..=0x1BCA3 | 0x1D173..=0x1D17A | 0xE0000..=0xE007F | 0xE0100..=0xE01EF => {
+            if legitimate_joiner(chars, offset) {
+                None
+            } else {
+                Some(("zero_width", name()))
+            }
+        },
+        0x09 | 0x0A | 0x0D => None,
+        0x00..=0x1F | 0x7F..=0x9F | 0x2028 | 0x2029 | 0xFFF9..=0xFFFB => Some(("control", name())),
+        _ => None
+    }
+}
+
+// Joiners inside emoji sequences and scripts that need them, and variation selectors after a symbol
+fn legitimate_joiner(chars: &[char], offset: usize) -> bool {
+    let previous = offset.checked_sub(1).map(|index| chars[index]);
+    let next = chars.get(offset + 1).copied();
+
+    match chars[offset] as u32 {
+        0x200C | 0x200D => match (previous, next) {
+            (Some(previous), Some(next)) if pictographic(previous) && pictographic(next) => true,
+            (Some(previous), Some(next)) => {
+                let script = script(previous);
+                script == script_of_letter(next) && !["Latin", "Greek", "Cyrillic", "Common", "Inherited"].contains(&script)
+            },
+            _ => false
+        },
+        0xFE0E | 0xFE0F => previous.is_some_and(|previous| pictographic(previous) || matches!(previous, '0'..='9' | '#' | '*')),
+        _ => false
+    }
+}
+
+fn pictographic(c: char) -> bool {
+    matches!(c as u32, 0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..=0x21AA | 0x231A..=0x23FF | 0x24C2 | 0x25AA..=0x27BF | 0x2934 | 0x2935 | 0x2B05..=0x2B55 | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x1F000..=0x1FAFF)
+}
+
+// Embeddings and overrides (closed by PDF) and isolates (closed by PDI) left open at the end of a line
+fn unterminated_bidi(chars: &[char], positions: &[(usize, usize)]) -> Vec<UnicodeFinding> {
+    let mut findings = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+
+    for (offset, c) in chars.iter().enumerate().chain(std::iter::once((chars.len(), &'\n'))) {
+        match *c as u32 {
+            0x202A..=0x202E if *c != '\u{202C}' => open.push(offset),
+            0x2066..=0x2068 => open.push(offset),
+            0x202C => {
+                if let Some(index) = open.iter().rposition(|opened| (0x202A..=0x202E).contains(&(chars[*opened] as u32))) {
+                    open.truncate(index);
+                }
+            },
+            0x2069 => {
+                if let Some(index) = open.iter().rposition(|opened| (0x2066..=0x2068).contains(&(chars[*opened] as u32))) {
+                    open.truncate(index);
+                }
+            },
+            0x0A | 0x2029 => {
+                for opened in open.drain(..) {
+                    let (line, column) = positions[opened];
+                    findings.push(UnicodeFinding {
+                        kind: "bidi_unterminated".to_string(),
+                        offset: opened,
+                        length: offset - opened,
+                        line,
+                        column,
+                        codepoints: vec![codepoint(chars[opened])],
+                        detail: format!("{} is not closed before the end of the line, so the rest of the line displays reordered", name(chars[opened]).unwrap_or_default())
+                    });
+                }
+            },
+            _ => {}
+        }
+    }
+
+    findings
+}
+
+/// How many letters of each script a word has, Common and Inherited characters left out
+pub(crate) fn letter_scripts(word: &[char]) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    for c in word.iter().filter(|c| c.is_alphabetic()) {
+        let script = script_of_letter(*c);
+        if script != "Common" && script != "Inherited" {
+            *counts.entry(script).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// Whether the scripts of a word are more than one, outside the combinations real text uses
+pub(crate) fn mixes_scripts(counts: &BTreeMap<&'static str, usize>) -> bool {
+    counts.len() > 1 && !ALLOWED_MIXES.iter().any(|mix| counts.keys().all(|script| mix.contains(script)))
+}
+
+// Words whose letters come from more than one script, outside the allowed combinations
+fn mixed_scripts(chars: &[char], positions: &[(usize, usize)]) -> Vec<UnicodeFinding> {
+    let mut findings = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let in_word = |c: char| c.is_alphanumeric() || script(c) == "Inherited" || matches!(c as u32, 0x00AD | 0x200B..=0x200D | 0x2060 | 0xFEFF);
+        if !in_word(chars[start]) {
+            start += 1;
+            continue;
+        }
+
+        let end = (start..chars.len()).find(|index| !in_word(chars[*index])).unwrap_or(chars.len());
+        let word = &chars[start..end];
+
+        let counts = letter_scripts(word);
+        if mixes_scripts(&counts) {
+            let main = counts.iter().max_by_key(|(_, count)| **count).map(|(script, _)| *script).unwrap_or_default();
+            let mut codepoints: Vec<String> = word.iter().filter(|c| c.is_alphabetic() && !["Common", "Inherited", main].contains(&script_of_letter(**c))).map(|c| codepoint(*c)).collect();
+            codepoints.dedup();
+
+            let (line, column) = positions[start];
+            findings.push(UnicodeFinding {
+                kind: "mixed_script".to_string(),
+                offset: start,
+                length: end - start,
+                line,
+                column,
+                codepoints,
+                detail: format!("\"{}\" mixes {}", word.iter().collect::<String>(), counts.keys().copied().collect::<Vec<_>>().join(" and "))
+            });
+        }
+
+        start = end;
+    }
+
+    findings
+}
+
+// (line, column) of every char, 1-based
+fn line_positions(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut positions = Vec::with_capacity(chars.len());
+    let (mut line, mut column) = (1, 1);
+
+    for c in chars {
+        positions.push((line, column));
+        if *c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+
+    positions
+}
+
+/* Character data */
+
+fn describe(c: char, offset: usize) -> UnicodeCharacter {
+    let entry = lookup(c);
+    UnicodeCharacter {
+        codepoint: codepoint(c),
+        character: c.to_string(),
+        name: name(c),
+        category: entry.map_or_else(|| approximate_category(c), |(category, _)| category).to_string(),
+        script: script(c).to_string(),
+        count: 0,
+        first_offset: offset
+    }
+}
+
+pub(crate) fn codepoint(c: char) -> String {
+    format!("U+{:04X}", c as u32)
+}
+
+pub(crate) fn name(c: char) -> Option<String> {
+    if let Some((_, name)) = lookup(c) {
+        return Some(name.to_string());
+    }
+
+    let value = c as u32;
+    match value {
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0x20000..=0x3FFFF if c.is_alphabetic() => Some(format!("CJK UNIFIED IDEOGRAPH-{:04X}", value)),
+        0xAC00..=0xD7A3 => {
+            let index = (value - 0xAC00) as usize;
+            Some(format!("HANGUL SYLLABLE {}{}{}", HANGUL_LEADS[index / (21 * 28)], HANGUL_VOWELS[index % (21 * 28) / 28], HANGUL_TAILS[index % 28]))
+        },
+        0xE0100..=0xE01EF => Some(format!("VARIATION SELECTOR-{}", value - 0xE0100 + 17)),
+        _ => None
+    }
+}
+
+// Category and name from the table
+fn lookup(c: char) -> Option<(&'static str, &'static str)> {
+    let table = name_table();
+    let index = table.binary_search_by_key(&(c as u32), |(value, _, _)| *value).ok()?;
+    Some((table[index].1, table[index].2))
+}
+
+fn name_table() -> &'static [(u32, &'static str, &'static str)] {
+    static TABLE: OnceLock<Vec<(u32, &'static str, &'static str)>> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        UNICODE_NAMES.lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ' ');
+                let value = u32::from_str_radix(fields.next()?, 16).ok()?;
+                Some((value, fields.next()?, fields.next()?))
+            })
+            .collect()
+    })
+}
+
+// Best guess at the general category of a character outside the table
+fn approximate_category(c: char) -> &'static str {
+    match c as u32 {
+        0xE000..=0xF8FF | 0xF0000..=0x10FFFF => "Co",
+        _ if c.is_control() => "Cc",
+        _ if c.is_whitespace() => "Zs",
+        _ if c.is_uppercase() => "Lu",
+        _ if c.is_lowercase() => "Ll",
+        _ if c.is_alphabetic() => "Lo",
+        _ if c.is_numeric() => "No",
+        _ if script(c) == "Inherited" => "Mn",
+        _ => "So"
+    }
+}
+
+fn script(c: char) -> &'static str {
+    let value = c as u32;
+    SCRIPTS.iter()
+        .find(|(first, last, _)| (*first..=*last).contains(&value))
+        .map_or("Common", |(_, _, script)| script)
+}
+
+// Script of a letter, with letters outside the known ranges kept apart from Common
+pub(crate) fn script_of_letter(c: char) -> &'static str {
+    match script(c) {
+        "Common" if c.is_alphabetic() => "Unknown",
+        script => script
+    }
+}
@@ -0,0 +1,193 @@
+/*
+    URLs broken into parts and normalized for comparison
+
+    Parsing follows the WHATWG URL standard the browsers use (through the `url` crate behind
+    reqwest), so the host reported is the one a browser would connect to, whatever the text
+    looks like to a reader. That standard already lowercases the scheme and host, turns a
+    Unicode host into punycode, drops a port that is the scheme's default and resolves "."
+    and ".." path segments; normalizing adds sorted query parameters with the common
+    tracking parameters (utm_*, fbclid, gclid and the like) removed, so two links to the same
+    page compare equal.
+
+    The constructs phishing relies on are flagged: a user name before '@' that pushes the
+    real host out of sight ("https://google.com@evil.com"), a bare IP address host (with the
+    form it was written in when that was hex, octal or a single number), and a punycode host,
+    shown decoded so look-alike characters can be seen.
+*/
+
+use std::{fmt, net::IpAddr};
+
+use reqwest::Url;
+use serde::Serialize;
+
+use crate::{
+    escape::url_decode,
+    punycode::punycode_decode
+};
+
+// Query parameters that only track the click, removed when normalizing (with any "utm_" parameter)
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "mc_cid", "mc_eid", "igshid", "yclid", "_hsenc", "_hsmi"];
+
+/// One query parameter, percent-decoding and '+' for space undone
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryParam {
+    pub name: String,
+    pub value: String
+}
+
+/// A construct phishing links use to mislead
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UrlWarning {
+    /// "userinfo", "ip-host" or "punycode"
+    pub kind: &'static str,
+    pub detail: String
+}
+
+/// A URL as a browser reads it
+#[derive(Debug, Clone, Serialize)]
+pub struct UrlParts {
+    pub url: String,
+    pub scheme: String,
+    /// The user name before '@', if any (a password is only noted, in `has_password`)
+    pub username: Option<String>,
+    pub has_password: bool,
+    /// The host connected to, IDNA labels in punycode
+    pub host: Option<String>,
+    /// The host with its punycode labels decoded, when it has any
+    pub host_unicode: Option<String>,
+    /// The port given, or the scheme's default
+    pub port: Option<u16>,
+    pub path: String,
+    pub query: Vec<QueryParam>,
+    pub fragment: Option<String>,
+    /// The URL as `url_normalize` gives it
+    pub normalized: String,
+    pub warnings: Vec<UrlWarning>
+}
+
+impl fmt::Display for UrlParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "scheme: {}", self.scheme)?;
+        if let Some(username) = &self.username {
+            writeln!(f, "username: {}{}", username, if self.has_password { " (with a password)" } else { "" })?;
+        }
+        match (&self.host, &self.host_unicode) {
+            (Some(host), Some(unicode)) => writeln!(f, "host: {} ({})", host, unicode)?,
+            (Some(host), None) => writeln!(f, "host: {}", host)?,
+            _ => writeln!(f, "host: (none)")?
+        }
+        if let Some(port) = self.port {
+            writeln!(f, "port: {}", port)?;
+        }
+        write!(f, "path: {}", self.path)?;
+        for param in &self.query {
+            write!(f, "\nquery: {}={}", param.name, param.value)?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "\nfragment: {}", fragment)?;
+        }
+        write!(f, "\nnormalized: {}", self.normalized)?;
+        for warning in &self.warnings {
+            write!(f, "\nWARNING: {}", warning.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Breaks a URL into its parts, flagging user names before the host, IP address hosts and punycode hosts
+pub fn mercy_url_parse(url: &str) -> Result<UrlParts, String> {
+    let input = url.trim();
+    let parsed = parse(input)?;
+
+    let host = parsed.host_str().map(str::to_string);
+    let host_unicode = match &host {
+        Some(host) if host.split('.').any(|label| label.starts_with("xn--")) => Some(punycode_decode(host).map_err(|e| format!("Invalid URL '{}': {}", input, e))?),
+        _ => None
+    };
+
+    let mut warnings = Vec::new();
+    let host_text = host.clone().unwrap_or_default();
+    if !parsed.username().is_empty() {
+        let username = decoded(parsed.username());
+        let detail = match username.contains('.') {
+            true => format!("user name '{}' before '@' looks like a host: the browser goes to {}, not {}", username, host_text, username),
+            false => format!("user name '{}' before '@' of host {}", username, host_text)
+        };
+        warnings.push(UrlWarning { kind: "userinfo", detail });
+    }
+    if host_text.parse::<IpAddr>().is_ok() || host_text.starts_with('[') {
+        let written = written_host(input);
+        let form = match written.eq_ignore_ascii_case(&host_text) {
+            true => String::new(),
+            false => format!(" (written as {})", written)
+        };
+        warnings.push(UrlWarning { kind: "ip-host", detail: format!("host is the bare IP address {}{}", host_text, form) });
+    }
+    if let Some(unicode) = &host_unicode {
+        warnings.push(UrlWarning { kind: "punycode", detail: format!("punycode host {} reads as {}; check it for look-alike characters", host_text, unicode) });
+    }
+
+    Ok(UrlParts {
+        url: input.to_string(),
+        scheme: parsed.scheme().to_string(),
+        username: Some(decoded(parsed.username())).filter(|username| !username.is_empty()),
+        has_password: parsed.password().is_some(),
+        host,
+        host_unicode,
+        port: parsed.port_or_known_default(),
+        path: parsed.path().to_string(),
+        query: parsed.query_pairs().map(|(name, value)| QueryParam { name: name.into_owned(), value: value.into_owned() }).collect(),
+        fragment: parsed.fragment().map(decoded),
+        normalized: normalize(&parsed),
+        warnings
+    })
+}
+
+/// The URL normalized for comparison: scheme and host lowercased, default port dropped, "." and ".." resolved, tracking parameters removed and the rest sorted
+pub fn mercy_url_normalize(url: &str) -> Result<String, String> {
+    parse(url.trim()).map(|parsed| normalize(&parsed))
+}
+
+fn parse(input: &str) -> Result<Url, String> {
+    Url::parse(input).map_err(|e| match input.contains("://") || input.contains(':') {
+        true => format!("Invalid URL '{}': {}", input, e),
+        false => format!("Invalid URL '{}': {} (include the scheme, as https://)", input, e)
+    })
+}
+
+fn normalize(parsed: &Url) -> String {
+    let mut normalized = parsed.clone();
+    let mut params: Vec<(String, String)> = parsed.query_pairs()
+        .filter(|(name, _)| !tracking(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+
+    if params.is_empty() {
+        normalized.set_query(None);
+    } else {
+        normalized.query_pairs_mut().clear().extend_pairs(params);
+    }
+    normalized.to_string()
+}
+
+fn tracking(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+// The host as the input wrote it, before the standard rewrote it (hex, octal and single-number IPv4)
+fn written_host(input: &str) -> String {
+    let rest = input.split_once("://").map(|(_, rest)| rest).unwrap_or(input);
+    let authority = &rest[..rest.find(['/', '?', '#', '\\']).unwrap_or(rest.len())];
+    let host_port = authority.rsplit_once('@').map(|(_, host_port)| host_port).unwrap_or(authority);
+    match host_port.rsplit_once(':') {
+        Some((host, port)) if !host_port.ends_with(']') && port.chars().all(|c| c.is_ascii_digit()) => host.to_string(),
+        _ => host_port.to_string()
+    }
+}
+
+// Percent-decoded; the standard leaves only valid escapes, so this keeps the text if one is not
+fn decoded(text: &str) -> String {
+    url_decode(text, false).unwrap_or_else(|_| text.to_string())
+}
@@ -0,0 +1,258 @@
+/*
+    UUID (GUID) generation and inspection
+
+    v4 UUIDs take 122 bits from the operating system's CSPRNG, or from a seeded `MercyRng`
+    when a test or a reproduced run needs the same UUIDs again ("seed=42"). v5 UUIDs are the SHA-1 of a
+    namespace UUID followed by a name (RFC 4122 section 4.3), so the same namespace and name
+    always give the same ID; the namespaces of the RFC can be named ("dns", "url", "oid",
+    "x500").
+
+    Inspection accepts a UUID with or without hyphens, in braces as Windows writes GUIDs, as a
+    "urn:uuid:" URN and in either case, and reports its version and variant. Time-based UUIDs
+    carry when they were made: v1 and v6 count 100 ns intervals since 1582-10-15 (the
+    Gregorian reform) and v7 milliseconds since the Unix epoch. v1 and v6 also carry a clock
+    sequence and a node that is usually the generating host's MAC address, which is how old
+    Office documents and registry artifacts tie back to a machine; a node with the multicast
+    bit set was randomized instead.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    digests::Sha1,
+    mac::mercy_mac_lookup,
+    rng::MercyRng,
+    timefmt::unix_to_rfc3339,
+    trailing_options
+};
+
+// 100 ns intervals from 1582-10-15 to 1970-01-01
+const GREGORIAN_UNIX_DIFF: u64 = 0x01B2_1DD2_1381_4000;
+
+// The name-based namespaces of RFC 4122 appendix C
+const NAMESPACES: &[(&str, &str)] = &[
+    ("dns", "6ba7b810-9dad-11d1-80b4-00c04fd430c8"),
+    ("url", "6ba7b811-9dad-11d1-80b4-00c04fd430c8"),
+    ("oid", "6ba7b812-9dad-11d1-80b4-00c04fd430c8"),
+    ("x500", "6ba7b814-9dad-11d1-80b4-00c04fd430c8")
+];
+
+/// What a UUID says about itself
+#[derive(Debug, Clone, Serialize)]
+pub struct UuidInfo {
+    /// Normalized as lowercase "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx"
+    pub uuid: String,
+    /// The version field (1 to 8 for RFC 4122 UUIDs; meaningless for other variants)
+    pub version: u8,
+    /// "time-based", "random", "name-based, SHA-1" and so on, "nil" or "max"
+    pub version_name: String,
+    /// "RFC 4122", "NCS (reserved)", "Microsoft (reserved)" or "future (reserved)"; "none" for the nil and max UUIDs
+    pub variant: String,
+    /// Creation time of a v1, v6 or v7 UUID as RFC 3339 UTC
+    pub timestamp: Option<String>,
+    /// Clock sequence of a v1 or v6 UUID
+    pub clock_sequence: Option<u16>,
+    /// Node of a v1 or v6 UUID as "AA:BB:CC:DD:EE:FF"
+    pub node: Option<String>,
+    /// Vendor of the node's OUI when it is a real MAC address of a known vendor
+    pub node_vendor: Option<String>,
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for UuidInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "UUID: {}", self.uuid)?;
+        writeln!(f, "Version: {} ({})", self.version, self.version_name)?;
+        write!(f, "Variant: {}", self.variant)?;
+
+        if let Some(timestamp) = &self.timestamp {
+            write!(f, "\nTimestamp: {}", timestamp)?;
+        }
+        if let Some(clock_sequence) = self.clock_sequence {
+            write!(f, "\nClock sequence: {}", clock_sequence)?;
+        }
+        if let Some(node) = &self.node {
+            write!(f, "\nNode: {}", node)?;
+            if let Some(vendor) = &self.node_vendor {
+                write!(f, " ({})", vendor)?;
+            }
+        }
+        for note in &self.notes {
+            write!(f, "\nNote: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// A random (version 4) UUID from the OS CSPRNG
+pub fn mercy_uuid_v4() -> String {
+    mercy_uuid_v4_with(&mut MercyRng::os())
+}
+
+/// A version 4 UUID from `rng`; a seeded generator gives the same sequence of UUIDs every run
+pub fn mercy_uuid_v4_with(rng: &mut MercyRng) -> String {
+    let mut bytes = [0u8; 16];
+    rng.fill(&mut bytes);
+    format_uuid(&with_version(bytes, 4))
+}
+
+/// The name-based (version 5, SHA-1) UUID of `name` in `namespace`, a UUID or one of "dns", "url", "oid" and "x500"
+pub fn mercy_uuid_v5(namespace: &str, name: &str) -> Result<String, String> {
+    let namespace = namespace.trim();
+    let namespace = NAMESPACES.iter()
+        .find(|(label, _)| label.eq_ignore_ascii_case(namespace))
+        .map_or(namespace, |(_, uuid)| uuid);
+
+    let mut sha1 = Sha1::new();
+    sha1.update(&parse_uuid(namespace).map_err(|e| format!("{} (the namespace must be a UUID or dns, url, oid or x500)", e))?);
+    sha1.update(name.as_bytes());
+
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&sha1.finalize()[..16]);
+    Ok(format_uuid(&with_version(bytes, 5)))
+}
+
+/// Version, variant and, for time-based UUIDs, the embedded time and node of a UUID
+pub fn mercy_uuid_inspect(uuid: &str) -> Result<UuidInfo, String> {
+    let bytes = parse_uuid(uuid)?;
+    let version = bytes[6] >> 4;
+    let variant = match bytes[8] {
+        byte if byte & 0x80 == 0 => "NCS (reserved)",
+        byte if byte & 0x40 == 0 => "RFC 4122",
+        byte if byte & 0x20 == 0 => "Microsoft (reserved)",
+        _ => "future (reserved)"
+    };
+
+    let mut info = UuidInfo {
+        uuid: format_uuid(&bytes),
+        version,
+        version_name: String::new(),
+        variant: variant.to_string(),
+        timestamp: None,
+        clock_sequence: None,
+        node: None,
+        node_vendor: None,
+        notes: Vec::new()
+    };
+
+    if bytes == [0; 16] || bytes == [0xFF; 16] {
+        info.version_name = if bytes[0] == 0 { "nil" } else { "max" }.to_string();
+        info.variant = "none".to_string();
+        return Ok(info);
+    }
+
+    info.version_name = match version {
+        1 => "time-based",
+        2 => "DCE security",
+        3 => "name-based, MD5",
+        4 => "random",
+        5 => "name-based, SHA-1",
+        6 => "reordered time-based",
+        7 => "Unix time-based",
+        8 => "custom",
+        _ => "unknown"
+    }.to_string();
+
+    if variant != "RFC 4122" {
+        info.notes.push(format!("Not an RFC 4122 UUID; the version field has no defined meaning in the {} variant", variant));
+        return Ok(info);
+    }
+
+    match version {
+        1 | 6 => {
+            let (low, mid, high) = (be(&bytes[0..4]), be(&bytes[4..6]), be(&bytes[6..8]) & 0x0FFF);
+            let ticks = match version {
+                1 => (high << 48) | (mid << 32) | low,
+                _ => (low << 28) | (mid << 12) | high
+            };
+            let unix_ticks = ticks as i64 - GREGORIAN_UNIX_DIFF as i64;
+            info.timestamp = Some(unix_to_rfc3339(unix_ticks.div_euclid(10_000_000), (unix_ticks.rem_euclid(10_000_000) * 100) as u32));
+            info.clock_sequence = Some(be(&bytes[8..10]) as u16 & 0x3FFF);
+
+            let node = bytes[10..16].iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(":");
+            if bytes[10] & 0x01 != 0 {
+                info.notes.push("The node has the multicast bit set: a random node ID, not a MAC address".to_string());
+            } else {
+                info.node_vendor = mercy_mac_lookup(&node).ok().filter(|lookup| lookup.known).map(|lookup| lookup.vendor);
+            }
+            info.node = Some(node);
+        },
+        7 => {
+            let millis = be(&bytes[0..6]) as i64;
+            info.timestamp = Some(unix_to_rfc3339(millis.div_euclid(1000), (millis.rem_euclid(1000) * 1_000_000) as u32));
+        },
+        2 => info.notes.push("DCE security UUIDs replace the low timestamp bits with a local user or group ID".to_string()),
+        _ => {}
+    }
+
+    Ok(info)
+}
+
+// Accepts hyphenated or bare hex, in braces or as a "urn:uuid:" URN, in either case
+fn parse_uuid(input: &str) -> Result<[u8; 16], String> {
+    let trimmed = input.trim();
+    let invalid = |reason: &str| format!("Invalid UUID '{}': {}", trimmed, reason);
+
+    let mut uuid = trimmed;
+    if uuid.get(..9).is_some_and(|prefix| prefix.eq_ignore_ascii_case("urn:uuid:")) {
+        uuid = &uuid[9..];
+    }
+    if let Some(inner) = uuid.strip_prefix('{') {
+        uuid = inner.strip_suffix('}').ok_or_else(|| invalid("unbalanced braces"))?;
+    }
+
+    let groups: Vec<&str> = uuid.split('-').collect();
+    if groups.len() > 1 && groups.iter().map(|group| group.len()).ne([8, 4, 4, 4, 12]) {
+        return Err(invalid("expected hyphenated groups of 8, 4, 4, 4 and 12 hex digits"));
+    }
+
+    let digits = groups.concat();
+    if let Some(bad) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(invalid(&format!("'{}' is not a hex digit", bad)));
+    }
+    if digits.len() != 32 {
+        return Err(invalid(&format!("expected 32 hex digits, got {}", digits.len())));
+    }
+
+    let mut bytes = [0u8; 16];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).map_err(|e| invalid(&e.to_string()))?;
+    }
+    Ok(bytes)
+}
+
+// Sets the version nibble and the RFC 4122 variant bits
+fn with_version(mut bytes: [u8; 16], version: u8) -> [u8; 16] {
+    bytes[6] = (bytes[6] & 0x0F) | (version << 4);
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+    bytes
+}
+
+fn format_uuid(bytes: &[u8; 16]) -> String {
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+// Big-endian value of up to eight bytes
+fn be(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+// Parses "[seed=42]"
+pub(crate) fn uuid_v4_from_args(input: &str) -> Result<String, String> {
+    let (rest, pairs) = trailing_options(input, &["seed"]);
+    if !rest.is_empty() {
+        return Err(format!("Unexpected uuid_v4 argument '{}' (only seed= is accepted)", rest));
+    }
+
+    let seed = pairs.last().map(|(_, value)| value.parse::<u64>().map_err(|_| format!("Invalid seed '{}'", value))).transpose()?;
+    Ok(mercy_uuid_v4_with(&mut MercyRng::from_seed(seed)))
+}
+
+// Parses "<namespace>,<name>"; the name is everything after the first comma, exactly as given
+pub(crate) fn uuid_v5_from_args(input: &str) -> Result<String, String> {
+    let (namespace, name) = input.split_once(',').ok_or_else(|| format!("Expected \"namespace,name\" (namespace a UUID or dns, url, oid or x500), got '{}'", input))?;
+    mercy_uuid_v5(namespace, name)
+}
@@ -0,0 +1,125 @@
+/*
+    Package version comparison shared by the vulnerability and banner checks
+
+    One ordering covers the common schemes well enough for range checks: semantic versions
+    (with pre-releases), PEP 440 style qualifiers (1.0a1 < 1.0rc1 < 1.0 < 1.0.post1), Debian
+    style epochs ("1:2.3") and Maven qualifiers. Build metadata after '+' is ignored.
+*/
+
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u64),
+    // A word, and whether it followed '-' (a semver pre-release)
+    Word(String, bool)
+}
+
+/// Compares two version strings
+pub(crate) fn compare_versions(left: &str, right: &str) -> Ordering {
+    let (left_epoch, left) = split_epoch(left);
+    let (right_epoch, right) = split_epoch(right);
+
+    left_epoch.cmp(&right_epoch).then_with(|| {
+        let left = tokens(left);
+        let right = tokens(right);
+
+        for index in 0..left.len().max(right.len()) {
+            let ordering = match (left.get(index), right.get(index)) {
+                (Some(a), Some(b)) => compare_tokens(a, b),
+                (Some(a), None) => against_end(a),
+                (None, Some(b)) => against_end(b).reverse(),
+                (None, None) => Ordering::Equal
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        Ordering::Equal
+    })
+}
+
+// "2:1.0" carries an epoch that outranks everything after it
+fn split_epoch(version: &str) -> (u64, &str) {
+    match version.split_once(':') {
+        Some((epoch, rest)) if !epoch.is_empty() && epoch.bytes().all(|byte| byte.is_ascii_digit()) => (epoch.parse().unwrap_or(0), rest),
+        _ => (0, version)
+    }
+}
+
+fn tokens(version: &str) -> Vec<Token> {
+    let version = version.trim();
+    let version = version.split('+').next().unwrap_or(version);
+    let version = version.strip_prefix(['v', 'V']).filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit())).unwrap_or(version);
+
+    let mut tokens = Vec::new();
+    let mut chars = version.chars().peekable();
+    let mut after_dash = false;
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut digits = c.to_string();
+            while let Some(next) = chars.next_if(char::is_ascii_digit) {
+                digits.push(next);
+            }
+            tokens.push(Token::Number(digits.parse().unwrap_or(u64::MAX)));
+            after_dash = false;
+        } else if c.is_ascii_alphabetic() {
+            let mut word = c.to_ascii_lowercase().to_string();
+            while let Some(next) = chars.next_if(char::is_ascii_alphabetic) {
+                word.push(next.to_ascii_lowercase());
+            }
+            tokens.push(Token::Word(word, after_dash));
+            after_dash = false;
+        } else {
+            after_dash = c == '-' || (after_dash && c != '.');
+        }
+    }
+
+    tokens
+}
+
+// Rank of a qualifier relative to the release itself (0)
+fn qualifier_rank(word: &str) -> Option<i32> {
+    match word {
+        "dev" | "snapshot" => Some(-5),
+        "alpha" | "a" => Some(-4),
+        "beta" | "b" => Some(-3),
+        "milestone" | "m" => Some(-2),
+        "pre" | "preview" | "c" | "rc" | "cr" => Some(-1),
+        "final" | "ga" | "release" => Some(0),
+        "post" | "p" | "patch" | "sp" | "r" | "rev" => Some(1),
+        _ => None
+    }
+}
+
+fn compare_tokens(left: &Token, right: &Token) -> Ordering {
+    match (left, right) {
+        (Token::Number(a), Token::Number(b)) => a.cmp(b),
+        // Qualifiers sort before numbers: 1.0rc1 < 1.0.1 and 1.0.post1 < 1.0.1
+        (Token::Word(..), Token::Number(_)) => Ordering::Less,
+        (Token::Number(_), Token::Word(..)) => Ordering::Greater,
+        (Token::Word(a, _), Token::Word(b, _)) => match (qualifier_rank(a), qualifier_rank(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (Some(x), None) => if x < 0 { Ordering::Less } else { Ordering::Greater },
+            (None, Some(y)) => if y < 0 { Ordering::Greater } else { Ordering::Less },
+            (None, None) => a.cmp(b)
+        }
+    }
+}
+
+// How a trailing token compares with the shorter version having ended: 1.0.0 == 1.0,
+// 1.0.1 > 1.0, 1.0rc1 < 1.0, 1.0-foo < 1.0 (semver pre-release), 1.0.post1 > 1.0
+fn against_end(token: &Token) -> Ordering {
+    match token {
+        Token::Number(0) => Ordering::Equal,
+        Token::Number(_) => Ordering::Greater,
+        Token::Word(word, after_dash) => match qualifier_rank(word) {
+            Some(rank) => rank.cmp(&0),
+            None if *after_dash => Ordering::Less,
+            None => Ordering::Greater
+        }
+    }
+}
@@ -0,0 +1,404 @@
+/*
+    Directory walking over untrusted file systems
+
+    Every scanning feature that descends a tree (timeline, cluster, advisory directories)
+    walks it through here, so a hostile tree gets the same treatment everywhere:
+
+    - Symlinks are reported as links with their target and never followed unless asked. The
+      root itself is followed, as it was named by the caller.
+    - FIFOs, sockets and device nodes are reported by kind and never opened: opening a FIFO
+      with no writer blocks forever, and reading a device may never end.
+    - Directories are keyed by device and inode (canonical path where there are no inodes),
+      so bind mounts and followed links cannot loop the walk.
+    - Reads are capped and never trust the size in the metadata: sparse files claim terabytes
+      they do not store, and /proc-style pseudo-files report 0 bytes yet have content. Files
+      found by the walk are opened without following a swapped-in symlink and checked to be
+      regular after opening, so a file replaced between listing and reading is refused.
+*/
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, File, Metadata},
+    io::{self, Read},
+    path::{Path, PathBuf}
+};
+
+/// Options for `walk_dir`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WalkOptions {
+    /// Descend into symlinked directories (loops are still detected)
+    pub follow_symlinks: bool,
+    /// Deepest directory level entered, the root being 0; None enters every level
+    pub max_depth: Option<usize>
+}
+
+/// What a walked entry is, from its own metadata (links are not resolved)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    Other
+}
+
+impl EntryKind {
+    fn from_metadata(metadata: &Metadata) -> EntryKind {
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            return EntryKind::Symlink;
+        } else if file_type.is_dir() {
+            return EntryKind::Dir;
+        } else if file_type.is_file() {
+            return EntryKind::File;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+
+            if file_type.is_fifo() {
+                return EntryKind::Fifo;
+            } else if file_type.is_socket() {
+                return EntryKind::Socket;
+            } else if file_type.is_block_device() {
+                return EntryKind::BlockDevice;
+            } else if file_type.is_char_device() {
+                return EntryKind::CharDevice;
+            }
+        }
+
+        EntryKind::Other
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Dir => "dir",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Fifo => "fifo",
+            EntryKind::Socket => "socket",
+            EntryKind::BlockDevice => "block-device",
+            EntryKind::CharDevice => "char-device",
+            EntryKind::Other => "other"
+        }
+    }
+}
+
+/// One entry of a walk
+#[derive(Debug, Clone)]
+pub(crate) struct WalkEntry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// The entry's own metadata (lstat)
+    pub metadata: Metadata,
+    /// Levels below the root
+    pub depth: usize,
+    /// Where a symlink points, as stored in the link
+    pub link_target: Option<PathBuf>,
+    /// A regular file storing fewer bytes than its length claims
+    pub sparse: bool
+}
+
+/// An entry that could not be read; the walk carries on past it
+#[derive(Debug, Clone)]
+pub(crate) struct WalkError {
+    pub path: PathBuf,
+    pub message: String
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Depth-first walk in name order, yielding each entry before its children
+pub(crate) struct Walk {
+    options: WalkOptions,
+    // Paths still to visit with their depth, the next one last
+    stack: Vec<(PathBuf, usize)>,
+    visited: HashSet<DirectoryKey>,
+    // Errors found while listing a directory, returned before its children
+    errors: Vec<WalkError>
+}
+
+/// Walks `root`, which must exist; per-entry problems come back as `WalkError` items
+pub(crate) fn walk_dir<P: AsRef<Path>>(root: P, options: &WalkOptions) -> Result<Walk, String> {
+    let root = root.as_ref();
+    fs::metadata(root).map_err(|e| format!("Unable to read {}: {}", root.display(), e))?;
+
+    Ok(Walk { options: options.clone(), stack: vec![(root.to_path_buf(), 0)], visited: HashSet::new(), errors: Vec::new() })
+}
+
+impl Iterator for Walk {
+    type Item = Result<WalkEntry, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.errors.pop() {
+            return Some(Err(error));
+        }
+
+        let (path, depth) = self.stack.pop()?;
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => return Some(Err(WalkError { path, message: e.to_string() }))
+        };
+
+        let kind = EntryKind::from_metadata(&metadata);
+        let link_target = (kind == EntryKind::Symlink).then(|| fs::read_link(&path).ok()).flatten();
+
+        // The root is followed when it is a link; below it, only when asked
+        let directory = match kind {
+            EntryKind::Dir => Some(metadata.clone()),
+            EntryKind::Symlink if depth == 0 || self.options.follow_symlinks => fs::metadata(&path).ok().filter(Metadata::is_dir),
+            _ => None
+        };
+
+        if let Some(directory) = directory {
+            let within_depth = self.options.max_depth.map(|max| depth <= max).unwrap_or(true);
+            if within_depth && self.visited.insert(directory_key(&path, &directory)) {
+                self.list(&path, depth);
+            }
+        }
+
+        let sparse = kind == EntryKind::File && is_sparse(&metadata);
+        Some(Ok(WalkEntry { path, kind, metadata, depth, link_target, sparse }))
+    }
+}
+
+impl Walk {
+    fn list(&mut self, path: &Path, depth: usize) {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.errors.push(WalkError { path: path.to_path_buf(), message: e.to_string() });
+                return;
+            }
+        };
+
+        let mut children: Vec<PathBuf> = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => children.push(entry.path()),
+                Err(e) => self.errors.push(WalkError { path: path.to_path_buf(), message: e.to_string() })
+            }
+        }
+
+        // Reverse so the stack pops children in name order
+        children.sort();
+        self.stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+    }
+}
+
+#[cfg(unix)]
+type DirectoryKey = (u64, u64);
+
+#[cfg(unix)]
+fn directory_key(_path: &Path, metadata: &Metadata) -> DirectoryKey {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+// Without inodes, the canonical path identifies the directory
+#[cfg(not(unix))]
+type DirectoryKey = PathBuf;
+
+#[cfg(not(unix))]
+fn directory_key(path: &Path, _metadata: &Metadata) -> DirectoryKey {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(unix)]
+fn is_sparse(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks counts 512-byte units whatever the file system's block size
+    metadata.blocks().saturating_mul(512) < metadata.len()
+}
+
+#[cfg(windows)]
+fn is_sparse(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+    metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_sparse(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Contents of a regular file, read up to a cap
+#[derive(Debug, Clone)]
+pub(crate) struct CappedRead {
+    pub data: Vec<u8>,
+    /// The file turned out to hold more than the cap while being read (its length said otherwise); `data` is its first `cap` bytes
+    pub truncated: bool
+}
+
+/// Reads at most `cap` bytes of a regular file, refusing FIFOs, sockets, devices and files whose length exceeds the cap
+pub(crate) fn read_capped<P: AsRef<Path>>(path: P, cap: u64) -> Result<CappedRead, String> {
+    read_regular(path.as_ref(), cap, true)
+}
+
+impl WalkEntry {
+    /// Reads at most `cap` bytes of the entry, refusing anything that is no longer a regular file (below the root, a link swapped in since the walk included)
+    pub fn read_capped(&self, cap: u64) -> Result<CappedRead, String> {
+        read_regular(&self.path, cap, self.depth == 0)
+    }
+}
+
+fn read_regular(path: &Path, cap: u64, follow: bool) -> Result<CappedRead, String> {
+    let file = open_regular(path, follow).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    // A length over the cap is real (pseudo-files only ever under-report), so a sparse giant is refused unread
+    let length = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    if length > cap {
+        return Err(format!("{} is larger than the {} byte limit", path.display(), cap));
+    }
+
+    // One byte past the cap tells a file of exactly `cap` bytes from a longer one
+    let mut data = Vec::new();
+    file.take(cap.saturating_add(1)).read_to_end(&mut data).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    let truncated = data.len() as u64 > cap;
+    data.truncate(cap.min(data.len() as u64) as usize);
+    Ok(CappedRead { data, truncated })
+}
+
+// Opened without blocking on a FIFO (or following a final symlink unless asked), then checked on the handle
+fn open_regular(path: &Path, follow: bool) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(if follow { libc::O_NONBLOCK } else { libc::O_NONBLOCK | libc::O_NOFOLLOW });
+    }
+
+    #[cfg(not(unix))]
+    if !follow && fs::symlink_metadata(path)?.file_type().is_symlink() {
+        return Err(io::Error::other("is a symlink"));
+    }
+
+    let file = options.open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::other("not a regular file"));
+    }
+
+    Ok(file)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        ffi::CString,
+        fs,
+        io::{Seek, SeekFrom, Write},
+        os::unix::{ffi::OsStrExt, fs::symlink},
+        path::PathBuf
+    };
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mercy-walk-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entries(root: &Path, options: &WalkOptions) -> Vec<WalkEntry> {
+        walk_dir(root, options).unwrap().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn symlink_loops_are_reported_and_never_loop() {
+        let root = fixture("loop");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file.txt"), b"text").unwrap();
+        symlink("..", root.join("sub/up")).unwrap();
+        symlink("sub/file.txt", root.join("link.txt")).unwrap();
+
+        let walked = entries(&root, &WalkOptions::default());
+        let names: Vec<(String, EntryKind)> = walked.iter().map(|entry| (entry.path.strip_prefix(&root).unwrap().display().to_string(), entry.kind)).collect();
+        assert_eq!(names, [
+            (String::new(), EntryKind::Dir),
+            ("link.txt".to_string(), EntryKind::Symlink),
+            ("sub".to_string(), EntryKind::Dir),
+            ("sub/file.txt".to_string(), EntryKind::File),
+            ("sub/up".to_string(), EntryKind::Symlink)
+        ]);
+        assert_eq!(walked[4].link_target, Some(PathBuf::from("..")));
+
+        // Followed, the link leads back to the root, which has been listed already
+        let followed = entries(&root, &WalkOptions { follow_symlinks: true, max_depth: None });
+        assert_eq!(followed.len(), walked.len());
+        assert_eq!(followed.iter().filter(|entry| entry.path.ends_with("file.txt")).count(), 1);
+
+        // A link in the walk is not read through, even to a regular file
+        let error = walked[1].read_capped(1024).unwrap_err();
+        assert!(error.starts_with("Unable to read"), "{}", error);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn fifos_are_listed_and_never_block() {
+        let root = fixture("fifo");
+        let fifo = root.join("pipe");
+        let name = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(name.as_ptr(), 0o600) }, 0);
+
+        let walked = entries(&root, &WalkOptions::default());
+        assert_eq!(walked[1].kind, EntryKind::Fifo);
+        assert_eq!(walked[1].kind.as_str(), "fifo");
+
+        // No writer ever comes: a blocking open would hang here
+        assert!(walked[1].read_capped(1024).unwrap_err().contains("not a regular file"));
+        assert!(read_capped(&fifo, 1024).unwrap_err().contains("not a regular file"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn sparse_files_are_refused_unread() {
+        let root = fixture("sparse");
+        let path = root.join("huge.img");
+        let mut file = File::create(&path).unwrap();
+        file.seek(SeekFrom::Start(1 << 36)).unwrap();
+        file.write_all(b"end").unwrap();
+        drop(file);
+
+        let walked = entries(&root, &WalkOptions::default());
+        assert_eq!((walked[1].kind, walked[1].metadata.len()), (EntryKind::File, (1 << 36) + 3));
+        assert!(walked[1].sparse);
+        assert!(walked[1].read_capped(1 << 20).unwrap_err().contains("larger than the 1048576 byte limit"));
+
+        let small = root.join("small.txt");
+        fs::write(&small, b"all of it").unwrap();
+        let read = read_capped(&small, 9).unwrap();
+        assert_eq!((read.data.as_slice(), read.truncated), (&b"all of it"[..], false));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    // /proc files claim no length at all, yet have content
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pseudo_files_are_read_to_the_cap() {
+        let status = Path::new("/proc/self/status");
+        assert_eq!(fs::metadata(status).unwrap().len(), 0);
+
+        let read = read_capped(status, 1 << 20).unwrap();
+        assert!(read.data.starts_with(b"Name:") && !read.truncated);
+
+        let read = read_capped(status, 5).unwrap();
+        assert_eq!((read.data.as_slice(), read.truncated), (&b"Name:"[..], true));
+    }
+}
@@ -0,0 +1,270 @@
+/*
+    WHOIS lookups routed to the server that is authoritative for the query
+
+    Domains go to their TLD's registry from a table of common gTLDs and ccTLDs, or to IANA for
+    any other TLD, following IANA's "refer:" line to the registry. Thin registries (.com, .net)
+    only know the registrar, so their "Registrar WHOIS Server:" line is followed one hop for the
+    full record. IP addresses go to ARIN, whose "ReferralServer:" line hands addresses managed by
+    RIPE, APNIC, LACNIC or AFRINIC to that registry.
+
+    `set_service_endpoints` can name a server that is asked first instead (a mirror, or the
+    mock WHOIS server of the `testing` feature); referrals from it are still followed. WHOIS is
+    plain TCP on port 43, so it takes the timeouts of a `MercyConfig` but not its proxy. A
+    server that refuses the connection, times out or hangs up without answering is asked
+    again with the retries and rate limits of net.rs; one that cannot be resolved is not.
+*/
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{IpAddr, TcpStream, ToSocketAddrs},
+    time::Duration
+};
+
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::{
+    config,
+    endpoints::service_endpoints,
+    net::{self, Failure},
+    stats::{self, Counter}
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Largest response kept; registries answer a few KiB
+const MAX_RESPONSE: u64 = 1024 * 1024;
+
+// Keys registries use for the registrar and the creation date (gTLDs, ccTLDs and RIRs)
+const REGISTRAR_KEYS: &[&str] = &["Registrar", "Sponsoring Registrar", "Registrar Name"];
+const CREATED_KEYS: &[&str] = &["Creation Date", "Created", "Created On", "Registered on", "Registration Time", "Domain Registration Date", "RegDate"];
+
+const IANA: &str = "whois.iana.org";
+const ARIN: &str = "whois.arin.net";
+
+// Registry WHOIS servers of common TLDs; any other TLD is asked of IANA
+const TLD_SERVERS: &[(&str, &str)] = &[
+    ("ai", "whois.nic.ai"),
+    ("app", "whois.nic.google"),
+    ("au", "whois.auda.org.au"),
+    ("be", "whois.dns.be"),
+    ("biz", "whois.nic.biz"),
+    ("br", "whois.registro.br"),
+    ("ca", "whois.cira.ca"),
+    ("cc", "ccwhois.verisign-grs.com"),
+    ("ch", "whois.nic.ch"),
+    ("cn", "whois.cnnic.cn"),
+    ("co", "whois.nic.co"),
+    ("com", "whois.verisign-grs.com"),
+    ("de", "whois.denic.de"),
+    ("dev", "whois.nic.google"),
+    ("edu", "whois.educause.edu"),
+    ("es", "whois.nic.es"),
+    ("eu", "whois.eu"),
+    ("fr", "whois.nic.fr"),
+    ("gov", "whois.dotgov.gov"),
+    ("in", "whois.registry.in"),
+    ("info", "whois.nic.info"),
+    ("io", "whois.nic.io"),
+    ("it", "whois.nic.it"),
+    ("jp", "whois.jprs.jp"),
+    ("me", "whois.nic.me"),
+    ("mobi", "whois.nic.mobi"),
+    ("name", "whois.nic.name"),
+    ("net", "whois.verisign-grs.com"),
+    ("nl", "whois.domain-registry.nl"),
+    ("nz", "whois.irs.net.nz"),
+    ("online", "whois.nic.online"),
+    ("org", "whois.pir.org"),
+    ("pl", "whois.dns.pl"),
+    ("ru", "whois.tcinet.ru"),
+    ("se", "whois.iis.se"),
+    ("site", "whois.nic.site"),
+    ("top", "whois.nic.top"),
+    ("tv", "whois.nic.tv"),
+    ("uk", "whois.nic.uk"),
+    ("us", "whois.nic.us"),
+    ("xyz", "whois.nic.xyz")
+];
+
+/// A WHOIS record and the servers asked for it
+#[derive(Debug, Clone, Serialize)]
+pub struct WhoisResponse {
+    pub query: String,
+    /// Every server asked, in order; the record is the last one's answer
+    pub servers: Vec<String>,
+    pub record: String,
+    /// Why a referral could not be followed (the record is then the referring server's)
+    pub referral_error: Option<String>,
+    /// The registrar and creation date as the record gives them, when it names them
+    pub registrar: Option<String>,
+    pub created: Option<String>
+}
+
+impl fmt::Display for WhoisResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.record)?;
+        if let Some(error) = &self.referral_error {
+            write!(f, "\n% {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// WHOIS record of a domain or IP address from the registry (and, for thin registries, the registrar)
+pub fn mercy_whois(query: &str) -> Result<WhoisResponse, String> {
+    let query = query.trim().trim_end_matches('.').to_ascii_lowercase();
+    if query.is_empty() {
+        return Err("No domain or IP address specified".to_string());
+    }
+
+    let address = query.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok();
+    let first = match (service_endpoints().whois, address) {
+        (Some(server), _) => server,
+        (None, Some(_)) => ARIN.to_string(),
+        (None, None) => tld_server(&query).unwrap_or(IANA).to_string()
+    };
+
+    let mut response = WhoisResponse { query: query.clone(), servers: Vec::new(), record: String::new(), referral_error: None, registrar: None, created: None };
+    // ARIN's "n +" asks for the network with its full details rather than a list of matches
+    let text = match address {
+        Some(ip) if first == ARIN => format!("n + {}", ip),
+        _ => query.clone()
+    };
+    response.record = whois_query(&first, &text)?;
+    response.servers.push(first);
+
+    // IANA only names the registry of the TLD
+    if response.servers[0] == IANA {
+        if let Some(registry) = referral(&response.record, &["refer"]) {
+            follow(&mut response, registry);
+        }
+    }
+
+    let keys: &[&str] = if address.is_some() { &["ReferralServer"] } else { &["Registrar WHOIS Server"] };
+    if let Some(server) = referral(&response.record, keys) {
+        follow(&mut response, server);
+    }
+
+    response.registrar = record_value(&response.record, REGISTRAR_KEYS);
+    response.created = record_value(&response.record, CREATED_KEYS);
+    Ok(response)
+}
+
+// Replaces the record with the answer of the server referred to, unless that fails
+fn follow(response: &mut WhoisResponse, server: String) {
+    if response.servers.iter().any(|asked| same_server(asked, &server)) {
+        return;
+    }
+
+    match whois_query(&server, &response.query) {
+        Ok(record) => {
+            response.record = record;
+            response.referral_error = None;
+        },
+        Err(e) => {
+            warn!("Referral of {} to {} failed, keeping the record of {}: {}", response.query, server, response.servers.last().map(String::as_str).unwrap_or("-"), e);
+            response.referral_error = Some(format!("Referral to {} failed: {}", server, e));
+        }
+    }
+    response.servers.push(server);
+}
+
+fn tld_server(domain: &str) -> Option<&'static str> {
+    let tld = domain.rsplit('.').next()?;
+    TLD_SERVERS.iter().find(|(name, _)| *name == tld).map(|(_, server)| *server)
+}
+
+// The WHOIS server named by the first of `keys` in a response ("whois://host:port", "host" or "host:port");
+// rwhois and web referrals are not WHOIS and are ignored
+fn referral(record: &str, keys: &[&str]) -> Option<String> {
+    record.lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .find(|(key, _)| keys.iter().any(|wanted| key.trim().eq_ignore_ascii_case(wanted)))
+        .map(|(_, value)| value.trim())
+        .and_then(|value| match value.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("whois") => Some(rest),
+            Some(_) => None,
+            None => Some(value)
+        })
+        .map(|server| server.trim_end_matches('/').to_ascii_lowercase())
+        .filter(|server| !server.is_empty() && !server.contains(char::is_whitespace))
+}
+
+// The value of the first line keyed by one of `keys`; registries that put it on the next,
+// indented line (Nominet) are followed there
+fn record_value(record: &str, keys: &[&str]) -> Option<String> {
+    let mut lines = record.lines();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        if !keys.iter().any(|wanted| key.trim().eq_ignore_ascii_case(wanted)) {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = if value.is_empty() {
+            lines.by_ref().map(str::trim).find(|next| !next.is_empty())?
+        } else {
+            value
+        };
+        return Some(value.to_string());
+    }
+    None
+}
+
+fn same_server(a: &str, b: &str) -> bool {
+    a.trim_end_matches(":43") == b.trim_end_matches(":43")
+}
+
+// Sends one query to "host" or "host:port" (port 43 by default) and reads the answer until the server closes,
+// asking again while the server cannot be reached or does not answer
+pub(crate) fn whois_query(server: &str, query: &str) -> Result<String, String> {
+    let target = if server.rsplit_once(':').is_some_and(|(host, port)| !host.contains(':') && port.parse::<u16>().is_ok()) {
+        server.to_string()
+    } else {
+        format!("{}:43", server)
+    };
+    let host = target.rsplit_once(':').map_or(server, |(host, _)| host);
+
+    net::retry_blocking(host, || whois_attempt(server, &target, query))
+}
+
+fn whois_attempt(server: &str, target: &str, query: &str) -> Result<String, Failure> {
+    stats::count(Counter::WhoisLookups, 1);
+    debug!("WHOIS {:?} to {}", query, target);
+
+    let addresses: Vec<_> = target.to_socket_addrs().map_err(|e| Failure::Permanent(format!("Unable to resolve WHOIS server {}: {}", server, e)))?.collect();
+    let mut last_error = format!("Unable to resolve WHOIS server {}", server);
+    let mut stream = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, config::connect_timeout(CONNECT_TIMEOUT)) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            },
+            Err(e) => last_error = format!("Unable to connect to WHOIS server {}: {}", server, e)
+        }
+    }
+    let mut stream = stream.ok_or(Failure::Transient(last_error))?;
+
+    let timeout = config::read_timeout(READ_TIMEOUT);
+    stream.set_read_timeout(Some(timeout)).map_err(|e| Failure::Permanent(format!("Unable to set read timeout: {}", e)))?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| Failure::Permanent(format!("Unable to set write timeout: {}", e)))?;
+    stream.write_all(format!("{}\r\n", query).as_bytes()).map_err(|e| Failure::Transient(format!("Unable to send the query to WHOIS server {}: {}", server, e)))?;
+
+    let mut response = Vec::new();
+    stream.take(MAX_RESPONSE).read_to_end(&mut response).map_err(|e| Failure::Transient(match e.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => format!("WHOIS server {} timed out", server),
+        _ => format!("Unable to read the answer of WHOIS server {}: {}", server, e)
+    }))?;
+
+    if response.is_empty() {
+        return Err(Failure::Transient(format!("WHOIS server {} closed the connection without answering", server)));
+    }
+    // Some registries answer in Latin-1
+    Ok(String::from_utf8_lossy(&response).into_owned())
+}
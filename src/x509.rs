@@ -0,0 +1,318 @@
+/*
+    Minimal DER and X.509 certificate parsing
+
+    Enough ASN.1 to summarize a certificate (subject, issuer, serial, validity, SHA-256 of the
+    DER encoding, subject alternative names) and to pull certificates out of PKCS#7
+    SignedData, as found in APK META-INF signatures and configuration profiles. Nothing here
+    verifies a signature.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Summary of an X.509 certificate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: Option<String>,
+    pub not_after: Option<String>,
+    /// SHA-256 of the DER encoding, as shown by most tooling
+    pub sha256: String,
+    pub self_signed: bool
+}
+
+impl fmt::Display for CertificateInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Subject: {}", self.subject)?;
+        writeln!(f, "Issuer: {}", self.issuer)?;
+        writeln!(f, "Serial: {}", self.serial)?;
+        writeln!(f, "Valid: {} to {}", self.not_before.as_deref().unwrap_or("?"), self.not_after.as_deref().unwrap_or("?"))?;
+        writeln!(f, "SHA-256: {}", self.sha256)
+    }
+}
+
+// A DER element: its tag, its content and the whole encoding
+#[derive(Clone, Copy)]
+pub(crate) struct Tlv<'a> {
+    pub(crate) tag: u8,
+    pub(crate) content: &'a [u8],
+    pub(crate) raw: &'a [u8]
+}
+
+/// Reads one element, returning it and the bytes after it
+pub(crate) fn read_tlv(input: &[u8]) -> Option<(Tlv<'_>, &[u8])> {
+    let tag = *input.first()?;
+
+    // High tag numbers never appear in the structures parsed here
+    if tag & 0x1F == 0x1F {
+        return None;
+    }
+
+    let first = *input.get(1)? as usize;
+    let (length, header) = match first {
+        0..=0x7F => (first, 2),
+        0x81..=0x84 => {
+            let count = first & 0x7F;
+            let bytes = input.get(2..2 + count)?;
+            (bytes.iter().fold(0usize, |acc, byte| (acc << 8) | *byte as usize), 2 + count)
+        }
+        _ => return None
+    };
+
+    let end = header.checked_add(length)?;
+    let content = input.get(header..end)?;
+    Some((Tlv { tag, content, raw: &input[..end] }, &input[end..]))
+}
+
+/// The elements of a constructed value's content
+pub(crate) fn children(content: &[u8]) -> Vec<Tlv<'_>> {
+    let mut items = Vec::new();
+    let mut rest = content;
+
+    while !rest.is_empty() {
+        match read_tlv(rest) {
+            Some((item, next)) => {
+                items.push(item);
+                rest = next;
+            }
+            None => break
+        }
+    }
+
+    items
+}
+
+/// Dotted form of an OBJECT IDENTIFIER's content
+pub(crate) fn oid_string(content: &[u8]) -> String {
+    let mut parts = Vec::new();
+    let mut value = 0u64;
+
+    for byte in content {
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            // The first subidentifier packs the first two arcs
+            if parts.is_empty() {
+                let first = (value / 40).min(2);
+                parts.push(first);
+                parts.push(value - first * 40);
+            } else {
+                parts.push(value);
+            }
+            value = 0;
+        }
+    }
+
+    parts.iter().map(|part| part.to_string()).collect::<Vec<_>>().join(".")
+}
+
+/// Text of the ASN.1 string types used in names
+pub(crate) fn string_value(tlv: &Tlv) -> String {
+    match tlv.tag {
+        // BMPString is UTF-16BE
+        0x1E => {
+            let units: Vec<u16> = tlv.content.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        // UniversalString is UTF-32BE
+        0x1C => tlv.content.chunks_exact(4)
+            .filter_map(|quad| char::from_u32(u32::from_be_bytes([quad[0], quad[1], quad[2], quad[3]])))
+            .collect(),
+        _ => String::from_utf8_lossy(tlv.content).to_string()
+    }
+}
+
+// Short names for the attribute types seen in certificate names
+fn attribute_name(oid: &str) -> String {
+    let name = match oid {
+        "2.5.4.3" => "CN",
+        "2.5.4.4" => "SN",
+        "2.5.4.5" => "serialNumber",
+        "2.5.4.6" => "C",
+        "2.5.4.7" => "L",
+        "2.5.4.8" => "ST",
+        "2.5.4.9" => "street",
+        "2.5.4.10" => "O",
+        "2.5.4.11" => "OU",
+        "2.5.4.12" => "title",
+        "2.5.4.42" => "GN",
+        "0.9.2342.19200300.100.1.1" => "UID",
+        "0.9.2342.19200300.100.1.25" => "DC",
+        "1.2.840.113549.1.9.1" => "emailAddress",
+        other => return other.to_string()
+    };
+    name.to_string()
+}
+
+/// Renders a Name as "C=US, O=Example, CN=host" in encoded order
+pub(crate) fn name_string(name: &Tlv) -> String {
+    let mut parts = Vec::new();
+
+    for set in children(name.content) {
+        for attribute in children(set.content) {
+            let fields = children(attribute.content);
+            if let [oid, value, ..] = fields.as_slice() {
+                parts.push(format!("{}={}", attribute_name(&oid_string(oid.content)), string_value(value)));
+            }
+        }
+    }
+
+    parts.join(", ")
+}
+
+// UTCTime (YYMMDDHHMMSSZ) or GeneralizedTime (YYYYMMDDHHMMSSZ) as RFC 3339
+fn time_string(tlv: &Tlv) -> Option<String> {
+    let text = std::str::from_utf8(tlv.content).ok()?;
+    let digits = text.trim_end_matches('Z');
+
+    let (year, rest) = match tlv.tag {
+        0x17 => {
+            let short: u32 = digits.get(..2)?.parse().ok()?;
+            (if short < 50 { 2000 + short } else { 1900 + short }, digits.get(2..)?)
+        }
+        0x18 => (digits.get(..4)?.parse().ok()?, digits.get(4..)?),
+        _ => return None
+    };
+
+    let field = |range: std::ops::Range<usize>| -> Option<u32> { rest.get(range)?.parse().ok() };
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, field(0..2)?, field(2..4)?, field(4..6)?, field(6..8)?, field(8..10).unwrap_or(0)
+    ))
+}
+
+// The outer Certificate and the fields of its TBSCertificate, the optional version removed
+fn tbs_fields(der: &[u8]) -> Result<(Tlv<'_>, Vec<Tlv<'_>>), String> {
+    let (certificate, _) = read_tlv(der).filter(|(tlv, _)| tlv.tag == 0x30).ok_or("Not a DER certificate")?;
+    let (tbs, _) = read_tlv(certificate.content).filter(|(tlv, _)| tlv.tag == 0x30).ok_or("Certificate has no TBSCertificate")?;
+    let mut fields = children(tbs.content);
+
+    // Optional explicit version
+    if fields.first().map(|field| field.tag == 0xA0).unwrap_or(false) {
+        fields.remove(0);
+    }
+
+    Ok((certificate, fields))
+}
+
+/// Summarizes a DER encoded certificate
+pub(crate) fn parse_certificate(der: &[u8]) -> Result<CertificateInfo, String> {
+    let (certificate, fields) = tbs_fields(der)?;
+
+    let (serial, issuer, validity, subject) = match fields.as_slice() {
+        [serial, _algorithm, issuer, validity, subject, ..] => (serial, issuer, validity, subject),
+        _ => return Err("TBSCertificate is truncated".to_string())
+    };
+
+    let times = children(validity.content);
+    let serial_hex: String = serial.content.iter().skip_while(|byte| **byte == 0).map(|byte| format!("{:02x}", byte)).collect();
+
+    Ok(CertificateInfo {
+        subject: name_string(subject),
+        issuer: name_string(issuer),
+        serial: if serial_hex.is_empty() { "00".to_string() } else { serial_hex },
+        not_before: times.first().and_then(time_string),
+        not_after: times.get(1).and_then(time_string),
+        sha256: format!("{:x}", Sha256::digest(certificate.raw)),
+        self_signed: subject.raw == issuer.raw
+    })
+}
+
+/// The last common name (CN) of the subject, the one TLS clients once matched against the host
+pub(crate) fn subject_common_name(der: &[u8]) -> Option<String> {
+    let (_, fields) = tbs_fields(der).ok()?;
+    let subject = fields.get(4)?;
+
+    children(subject.content).iter()
+        .flat_map(|set| children(set.content))
+        .filter_map(|attribute| match children(attribute.content).as_slice() {
+            [oid, value, ..] if oid_string(oid.content) == "2.5.4.3" => Some(string_value(value)),
+            _ => None
+        })
+        .next_back()
+}
+
+/// The subjectAltName entries as "DNS:host", "IP:address", "email:..." or "URI:..."
+pub(crate) fn subject_alt_names(der: &[u8]) -> Vec<String> {
+    let fields = match tbs_fields(der) {
+        Ok((_, fields)) => fields,
+        Err(_) => return Vec::new()
+    };
+
+    // [3] EXPLICIT Extensions, each SEQUENCE { extnID, critical BOOLEAN OPTIONAL, extnValue OCTET STRING }
+    let extensions = fields.iter().find(|field| field.tag == 0xA3).and_then(|wrapper| read_tlv(wrapper.content)).map(|(list, _)| children(list.content)).unwrap_or_default();
+    let value = extensions.iter().find_map(|extension| {
+        let parts = children(extension.content);
+        match (parts.first(), parts.last()) {
+            (Some(oid), Some(value)) if oid.tag == 0x06 && oid_string(oid.content) == "2.5.29.17" && value.tag == 0x04 => Some(value.content),
+            _ => None
+        }
+    });
+    let names = match value.and_then(read_tlv) {
+        Some((names, _)) if names.tag == 0x30 => names,
+        _ => return Vec::new()
+    };
+
+    children(names.content).iter()
+        .filter_map(|name| match name.tag {
+            0x81 => Some(format!("email:{}", String::from_utf8_lossy(name.content))),
+            0x82 => Some(format!("DNS:{}", String::from_utf8_lossy(name.content))),
+            0x86 => Some(format!("URI:{}", String::from_utf8_lossy(name.content))),
+            0x87 => match name.content.len() {
+                4 => <[u8; 4]>::try_from(name.content).ok().map(|octets| format!("IP:{}", std::net::Ipv4Addr::from(octets))),
+                16 => <[u8; 16]>::try_from(name.content).ok().map(|octets| format!("IP:{}", std::net::Ipv6Addr::from(octets))),
+                _ => None
+            },
+            _ => None
+        })
+        .collect()
+}
+
+// The SignedData inside a PKCS#7 / CMS ContentInfo
+fn signed_data(der: &[u8]) -> Result<Tlv<'_>, String> {
+    let (content_info, _) = read_tlv(der).filter(|(tlv, _)| tlv.tag == 0x30).ok_or("Not a PKCS#7 structure")?;
+    let fields = children(content_info.content);
+
+    match fields.as_slice() {
+        [oid, wrapper, ..] if oid.tag == 0x06 && oid_string(oid.content) == "1.2.840.113549.1.7.2" && wrapper.tag == 0xA0 => {
+            Ok(read_tlv(wrapper.content).ok_or("Truncated SignedData")?.0)
+        }
+        _ => Err("PKCS#7 content is not SignedData".to_string())
+    }
+}
+
+/// DER encodings of the certificates carried in a PKCS#7 / CMS SignedData ContentInfo
+pub(crate) fn pkcs7_certificates(der: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let signed = signed_data(der)?;
+
+    // version, digestAlgorithms, encapContentInfo, then [0] IMPLICIT certificates
+    Ok(children(signed.content)
+        .into_iter()
+        .filter(|field| field.tag == 0xA0)
+        .flat_map(|set| children(set.content))
+        .filter(|certificate| certificate.tag == 0x30)
+        .map(|certificate| certificate.raw)
+        .collect())
+}
+
+/// The signed content of an attached PKCS#7 / CMS SignedData (None when detached)
+pub(crate) fn pkcs7_content(der: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let signed = signed_data(der)?;
+    let fields = children(signed.content);
+    let encapsulated = fields.get(2).filter(|field| field.tag == 0x30).ok_or("SignedData has no encapsulated content")?;
+
+    let explicit = match children(encapsulated.content).get(1) {
+        Some(explicit) if explicit.tag == 0xA0 => *explicit,
+        _ => return Ok(None)
+    };
+
+    match read_tlv(explicit.content) {
+        Some((octets, _)) if octets.tag == 0x04 => Ok(Some(octets.content.to_vec())),
+        // Constructed OCTET STRING: the content is split across primitive segments
+        Some((octets, _)) if octets.tag == 0x24 => Ok(Some(children(octets.content).iter().flat_map(|segment| segment.content.iter().copied()).collect())),
+        _ => Err("Encapsulated content is not an OCTET STRING".to_string())
+    }
+}
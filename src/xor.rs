@@ -0,0 +1,107 @@
+/*
+    XOR decoding of obfuscated payloads
+
+    Droppers commonly hide configuration blobs and strings behind a single-byte or short
+    repeating XOR key. A known key is applied directly; an unknown single-byte key is found by
+    trying all 256 and ranking the results by how much of each is printable and how English
+    its letters and spaces are, which puts readable text at the top even when a few keys give
+    all-printable output.
+*/
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    cipher::english_similarity,
+    radix::hex_decode
+};
+
+// Candidates returned by the brute force
+const TOP_CANDIDATES: usize = 5;
+
+/// A single-byte key and what it decodes the payload to
+#[derive(Debug, Clone, Serialize)]
+pub struct XorCandidate {
+    /// The key byte as "0x2a"
+    pub key: String,
+    /// Printable share times English similarity, from 0 to 1
+    pub score: f64,
+    /// Share of printable ASCII (with tab, CR and LF) in the output
+    pub printable: f64,
+    /// The decoded bytes, invalid UTF-8 replaced with U+FFFD
+    pub plaintext: String
+}
+
+/// The candidates of a brute force, best first
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct XorRanking(pub Vec<XorCandidate>);
+
+/// `data` XORed with `key` repeated over it
+pub fn xor_bytes(data: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
+    if key.is_empty() {
+        return Err("The XOR key is empty".to_string());
+    }
+    Ok(data.iter().zip(key.iter().cycle()).map(|(byte, key)| byte ^ key).collect())
+}
+
+/// The five single-byte keys whose output of `data` looks most like English text, best first
+pub fn xor_brute(data: &[u8]) -> Result<Vec<XorCandidate>, String> {
+    if data.is_empty() {
+        return Err("The XOR payload is empty".to_string());
+    }
+
+    let mut candidates: Vec<(u8, f64, f64, Vec<u8>)> = (0..=255u8)
+        .map(|key| {
+            let plain: Vec<u8> = data.iter().map(|byte| byte ^ key).collect();
+            let printable = plain.iter().filter(|byte| matches!(byte, 0x20..=0x7E | b'\t' | b'\r' | b'\n')).count() as f64 / plain.len() as f64;
+            (key, printable * english_similarity(&plain), printable, plain)
+        })
+        .collect();
+    // Highest score first; equal scores keep the lower key first
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    Ok(candidates.into_iter()
+        .take(TOP_CANDIDATES)
+        .map(|(key, score, printable, plain)| XorCandidate {
+            key: format!("{:#04x}", key),
+            score: round(score),
+            printable: round(printable),
+            plaintext: String::from_utf8_lossy(&plain).into_owned()
+        })
+        .collect())
+}
+
+// The brute force of a hex payload
+pub(crate) fn xor_brute_from_args(input: &str) -> Result<XorRanking, String> {
+    xor_brute(&hex_decode(input)?).map(XorRanking)
+}
+
+// Parses "<hex payload>,<hex key>"
+pub(crate) fn xor_from_args(input: &str) -> Result<Vec<u8>, String> {
+    let (payload, key) = input.rsplit_once(',').ok_or("Expected \"<hex payload>,<hex key>\"")?;
+    let payload = hex_decode(payload).map_err(|e| format!("Payload: {}", e))?;
+    let key = hex_decode(key).map_err(|e| format!("Key: {}", e))?;
+    xor_bytes(&payload, &key)
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+// One "key 0x58 score 0.93: <text>" line per candidate, control characters escaped so each stays on its line
+impl fmt::Display for XorRanking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, candidate) in self.0.iter().enumerate() {
+            let text: String = candidate.plaintext.chars()
+                .map(|c| if c.is_control() { c.escape_default().to_string() } else { c.to_string() })
+                .collect();
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "key {} score {:.2}: {}", candidate.key, candidate.score, text)?;
+        }
+        Ok(())
+    }
+}
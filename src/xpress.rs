@@ -0,0 +1,144 @@
+/*
+    LZXPRESS Huffman decompression ([MS-XCA] 2.1/2.2), used by Windows 10+ compressed prefetch files
+*/
+
+// Output bytes covered by each Huffman table
+const BLOCK_SIZE: usize = 65_536;
+
+// Symbols per table and the width of the decoding table index
+const SYMBOLS: usize = 512;
+const TABLE_BITS: u32 = 15;
+
+/// Decompresses `input` into exactly `output_size` bytes
+pub(crate) fn decompress_huffman(input: &[u8], output_size: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(output_size);
+    let mut position = 0;
+
+    while output.len() < output_size {
+        let table_bytes = input.get(position..position + SYMBOLS / 2).ok_or("Compressed data truncated before a Huffman table")?;
+        let (lengths, table) = build_table(table_bytes)?;
+        position += SYMBOLS / 2;
+
+        let mut bits = BitReader::new(input, position);
+        let block_end = (output.len() + BLOCK_SIZE).min(output_size);
+
+        while output.len() < block_end {
+            let symbol = table[(bits.next >> (32 - TABLE_BITS)) as usize] as usize;
+            bits.consume(lengths[symbol] as u32);
+
+            if symbol < 256 {
+                output.push(symbol as u8);
+                continue;
+            }
+
+            let symbol = symbol - 256;
+            let mut length = symbol & 15;
+            let offset_bits = (symbol >> 4) as u32;
+
+            if length == 15 {
+                length = bits.byte()? as usize;
+                if length == 255 {
+                    length = bits.word()? as usize;
+                    if length < 15 {
+                        return Err("Invalid match length in compressed data".to_string());
+                    }
+                    length -= 15;
+                }
+                length += 15;
+            }
+            length += 3;
+
+            let offset = (bits.next.checked_shr(32 - offset_bits).unwrap_or(0) as usize) + (1usize << offset_bits);
+            bits.consume(offset_bits);
+
+            if offset > output.len() {
+                return Err("Match offset points before the start of the output".to_string());
+            }
+
+            for _ in 0..length.min(output_size - output.len()) {
+                let byte = output[output.len() - offset];
+                output.push(byte);
+            }
+        }
+
+        position = bits.position;
+    }
+
+    Ok(output)
+}
+
+// Builds the canonical Huffman decoding table from 256 bytes of packed 4-bit code lengths
+fn build_table(packed: &[u8]) -> Result<([u8; SYMBOLS], Vec<u16>), String> {
+    let mut lengths = [0u8; SYMBOLS];
+    for (index, byte) in packed.iter().enumerate() {
+        lengths[index * 2] = byte & 0x0F;
+        lengths[index * 2 + 1] = byte >> 4;
+    }
+
+    let size = 1usize << TABLE_BITS;
+    let mut table = Vec::with_capacity(size);
+
+    for bit_length in 1..=TABLE_BITS as u8 {
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length == bit_length {
+                let count = 1usize << (TABLE_BITS as u8 - bit_length);
+                if table.len() + count > size {
+                    return Err("Invalid Huffman table (over-subscribed)".to_string());
+                }
+                table.extend(std::iter::repeat_n(symbol as u16, count));
+            }
+        }
+    }
+
+    if table.len() != size {
+        return Err("Invalid Huffman table (incomplete)".to_string());
+    }
+
+    Ok((lengths, table))
+}
+
+// The [MS-XCA] bit stream: 16-bit little-endian words consumed MSB first, with raw bytes interleaved
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    next: u32,
+    extra: i32
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8], position: usize) -> BitReader<'a> {
+        let mut reader = BitReader { input, position, next: 0, extra: 16 };
+        reader.next = (reader.read16() as u32) << 16;
+        reader.next |= reader.read16() as u32;
+        reader
+    }
+
+    // Reads past the end yield zeros; the output size bounds decoding
+    fn read16(&mut self) -> u16 {
+        let value = self.input.get(self.position..self.position + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).unwrap_or(0);
+        self.position += 2;
+        value
+    }
+
+    fn consume(&mut self, count: u32) {
+        self.next = self.next.checked_shl(count).unwrap_or(0);
+        self.extra -= count as i32;
+
+        if self.extra < 0 {
+            self.next |= (self.read16() as u32) << (-self.extra) as u32;
+            self.extra += 16;
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = *self.input.get(self.position).ok_or("Compressed data truncated")?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn word(&mut self) -> Result<u16, String> {
+        let bytes = self.input.get(self.position..self.position + 2).ok_or("Compressed data truncated")?;
+        self.position += 2;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
@@ -0,0 +1,190 @@
+/*
+    Read-only ZIP archive access for ZIP based containers (APK, JAR, OOXML)
+
+    Entries are listed from the central directory, which is authoritative for sizes even when
+    local headers defer them to data descriptors. Stored and deflated entries can be read.
+    `list_entries` reads only the end of a file and its central directory, so listing a large
+    archive does not load it.
+*/
+
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::Path
+};
+
+use crate::{
+    inflate::inflate,
+    registry::{le16, le32}
+};
+
+// End of central directory record plus the longest possible comment
+const EOCD_SEARCH: usize = 22 + 65_535;
+
+/// An entry listed in the central directory
+#[derive(Debug, Clone)]
+pub(crate) struct ZipEntry {
+    pub(crate) name: String,
+    pub(crate) method: u16,
+    pub(crate) compressed_size: u64,
+    pub(crate) size: u64,
+    /// CRC-32 of the uncompressed data, as the archive records it
+    pub(crate) crc32: u32,
+    /// General purpose flag bit 0: the data is encrypted
+    pub(crate) encrypted: bool,
+    local_offset: usize
+}
+
+/// A loaded archive
+pub(crate) struct ZipArchive {
+    data: Vec<u8>,
+    entries: Vec<ZipEntry>,
+    central_directory: usize
+}
+
+impl ZipArchive {
+    pub(crate) fn open(path: &Path) -> Result<ZipArchive, String> {
+        let data = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        ZipArchive::from_bytes(data)
+    }
+
+    pub(crate) fn from_bytes(data: Vec<u8>) -> Result<ZipArchive, String> {
+        let search_start = data.len().saturating_sub(EOCD_SEARCH);
+        let end = end_of_central_directory(&data[search_start..])?;
+        let central_directory = end.offset as usize;
+
+        let directory = data.get(central_directory..).ok_or("Central directory truncated")?;
+        let entries = central_directory_entries(directory, central_directory, end.count)?;
+
+        Ok(ZipArchive { data, entries, central_directory })
+    }
+
+    pub(crate) fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// The whole archive, for formats that hide data outside the entries (APK signing blocks)
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Offset of the first central directory entry
+    pub(crate) fn central_directory(&self) -> usize {
+        self.central_directory
+    }
+
+    /// Reads an entry by exact name
+    pub(crate) fn read(&self, name: &str, limit: usize) -> Result<Option<Vec<u8>>, String> {
+        match self.entries.iter().find(|entry| entry.name == name) {
+            Some(entry) => self.read_entry(entry, limit).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// Reads and decompresses an entry, refusing entries larger than `limit`
+    pub(crate) fn read_entry(&self, entry: &ZipEntry, limit: usize) -> Result<Vec<u8>, String> {
+        if entry.size > limit as u64 {
+            return Err(format!("{} is larger than {} bytes", entry.name, limit));
+        }
+
+        let local = self.data.get(entry.local_offset..entry.local_offset + 30).ok_or_else(|| format!("{}: local header outside the archive", entry.name))?;
+        if &local[..4] != b"PK\x03\x04" {
+            return Err(format!("{}: corrupt local header", entry.name));
+        }
+
+        let start = entry.local_offset + 30 + le16(local, 26).unwrap_or(0) as usize + le16(local, 28).unwrap_or(0) as usize;
+        let raw = self.data.get(start..start + entry.compressed_size as usize).ok_or_else(|| format!("{}: data runs past the archive", entry.name))?;
+
+        if entry.encrypted {
+            return Err(format!("{}: entry is encrypted (password protected)", entry.name));
+        }
+
+        match entry.method {
+            0 => Ok(raw.to_vec()),
+            8 => inflate(raw, limit).map_err(|e| format!("{}: {}", entry.name, e)),
+            other => Err(format!("{}: unsupported compression method {}", entry.name, other))
+        }
+    }
+}
+
+/// The entries of the archive at `path`, reading only its central directory
+pub(crate) fn list_entries(path: &Path) -> Result<Vec<ZipEntry>, String> {
+    let read_error = |e: std::io::Error| format!("Unable to read {}: {}", path.display(), e);
+    let mut file = File::open(path).map_err(read_error)?;
+    let size = file.metadata().map_err(read_error)?.len();
+
+    let search_start = size.saturating_sub(EOCD_SEARCH as u64);
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(search_start)).map_err(read_error)?;
+    (&mut file).take(EOCD_SEARCH as u64).read_to_end(&mut tail).map_err(read_error)?;
+    let end = end_of_central_directory(&tail)?;
+
+    if end.offset as u64 + end.size as u64 > search_start + end.position as u64 {
+        return Err(format!("Central directory at offset {} runs past the end of central directory record", end.offset));
+    }
+
+    let mut directory = vec![0; end.size as usize];
+    file.seek(SeekFrom::Start(end.offset as u64)).map_err(read_error)?;
+    file.read_exact(&mut directory).map_err(read_error)?;
+    central_directory_entries(&directory, end.offset as usize, end.count)
+}
+
+// What the end of central directory record gives
+struct EndRecord {
+    // Where the record starts within the searched bytes
+    position: usize,
+    count: usize,
+    size: u32,
+    offset: u32
+}
+
+// Finds the end of central directory record in the last bytes of an archive
+fn end_of_central_directory(tail: &[u8]) -> Result<EndRecord, String> {
+    let position = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|offset| tail[*offset..].starts_with(b"PK\x05\x06"))
+        .ok_or("Not a ZIP archive: no end of central directory record")?;
+
+    let count = le16(tail, position + 10).unwrap_or(0);
+    let size = le32(tail, position + 12).unwrap_or(0);
+    let offset = le32(tail, position + 16).unwrap_or(0);
+
+    if count == 0xFFFF || offset == 0xFFFF_FFFF {
+        return Err("ZIP64 archives are not supported".to_string());
+    }
+
+    Ok(EndRecord { position, count: count as usize, size, offset })
+}
+
+// Parses `count` entries from central directory bytes that start at `base` in the archive
+fn central_directory_entries(data: &[u8], base: usize, count: usize) -> Result<Vec<ZipEntry>, String> {
+    let mut entries = Vec::with_capacity(count);
+    let mut position = 0;
+
+    for _ in 0..count {
+        let header = data.get(position..position + 46).ok_or("Central directory truncated")?;
+        if &header[..4] != b"PK\x01\x02" {
+            return Err(format!("Corrupt central directory entry at offset {}", base + position));
+        }
+
+        let flags = le16(header, 8).unwrap_or(0);
+        let name_length = le16(header, 28).unwrap_or(0) as usize;
+        let extra_length = le16(header, 30).unwrap_or(0) as usize;
+        let comment_length = le16(header, 32).unwrap_or(0) as usize;
+        let name = data.get(position + 46..position + 46 + name_length).ok_or("Central directory truncated")?;
+
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).to_string(),
+            method: le16(header, 10).unwrap_or(0),
+            compressed_size: le32(header, 20).unwrap_or(0) as u64,
+            size: le32(header, 24).unwrap_or(0) as u64,
+            crc32: le32(header, 16).unwrap_or(0),
+            encrypted: flags & 0x0001 != 0,
+            local_offset: le32(header, 42).unwrap_or(0) as usize
+        });
+
+        position += 46 + name_length + extra_length + comment_length;
+    }
+
+    Ok(entries)
+}
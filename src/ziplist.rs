@@ -0,0 +1,101 @@
+/*
+    Listing the entries of a ZIP archive without extracting it
+
+    Only the end of central directory record and the central directory are read, so a
+    multi-gigabyte archive is listed in a few reads and nothing is written to disk. Sizes and
+    CRC-32s are the ones the archive records for each entry. Entries encrypted with a password
+    are still listed, since ZIP leaves names and sizes in the clear, and are marked as such;
+    an archive whose central directory cannot be read is an error saying what is wrong.
+*/
+
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::zip::list_entries;
+
+/// One entry of a ZIP archive as its central directory records it
+#[derive(Debug, Clone, Serialize)]
+pub struct ZipListEntry {
+    pub name: String,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// CRC-32 of the uncompressed data as "1a2b3c4d"
+    pub crc32: String,
+    /// "stored", "deflate", "bzip2", "lzma", "zstd", "xz", "aes" or "method N"
+    pub method: String,
+    /// Password protected: the data cannot be read without the password
+    pub encrypted: bool
+}
+
+/// The entries of a ZIP archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ZipListing {
+    pub path: String,
+    pub entries: Vec<ZipListEntry>,
+    /// Entries that are password protected
+    pub encrypted: usize,
+    pub compressed_size: u64,
+    pub size: u64
+}
+
+impl fmt::Display for ZipListing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = if self.entries.len() == 1 { "entry" } else { "entries" };
+        write!(f, "{} {}, {} bytes compressed, {} bytes uncompressed", self.entries.len(), noun, self.compressed_size, self.size)?;
+        if self.encrypted > 0 {
+            write!(f, "; {} password protected", self.encrypted)?;
+        }
+
+        for entry in &self.entries {
+            write!(f, "\n{}  {:>12}  {:>12}  {:<8}  {}", entry.crc32, entry.compressed_size, entry.size, entry.method, entry.name)?;
+            if entry.encrypted {
+                write!(f, " (encrypted)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists the entries of the ZIP archive at `path` (name, sizes, CRC-32, method) from its central directory
+pub fn mercy_zip_list<P: AsRef<Path>>(path: P) -> Result<ZipListing, String> {
+    let path = path.as_ref();
+    let entries = list_entries(path).map_err(|e| match e.starts_with("Unable to read") {
+        true => e,
+        false => format!("{}: {}", path.display(), e)
+    })?;
+
+    let entries: Vec<ZipListEntry> = entries.into_iter()
+        .map(|entry| ZipListEntry {
+            name: entry.name,
+            compressed_size: entry.compressed_size,
+            size: entry.size,
+            crc32: format!("{:08x}", entry.crc32),
+            method: method_name(entry.method),
+            encrypted: entry.encrypted
+        })
+        .collect();
+
+    Ok(ZipListing {
+        path: path.display().to_string(),
+        encrypted: entries.iter().filter(|entry| entry.encrypted).count(),
+        compressed_size: entries.iter().map(|entry| entry.compressed_size).sum(),
+        size: entries.iter().map(|entry| entry.size).sum(),
+        entries
+    })
+}
+
+// APPNOTE compression method numbers
+fn method_name(method: u16) -> String {
+    match method {
+        0 => "stored",
+        8 => "deflate",
+        9 => "deflate64",
+        12 => "bzip2",
+        14 => "lzma",
+        93 => "zstd",
+        95 => "xz",
+        99 => "aes",
+        other => return format!("method {}", other)
+    }.to_string()
+}
@@ -0,0 +1,810 @@
+/*
+    BIND master zone files (RFC 1035 section 5) and common zone misconfigurations
+
+    The parser follows the master file syntax: `;` comments, parentheses continuing an entry
+    over several lines (as SOA records usually are), quoted strings, `\X` and `\DDD` escapes,
+    `$ORIGIN` and `$TTL` directives, `@` for the origin, blank owners repeating the previous
+    one, relative names completed with the origin, and the TTL and class in either order.
+    Records come out normalized: lowercase absolute names, every TTL in seconds (BIND unit
+    suffixes such as "1h30m" included) and the class filled in. `$INCLUDE` and `$GENERATE`
+    are reported rather than followed, and an entry that cannot be parsed is reported with
+    its line number and skipped, so one bad line does not hide the rest of an export.
+
+    Without `$ORIGIN` or an explicit origin, the origin is guessed from file names such as
+    "db.example.com" or "example.com.zone".
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::Path,
+    time::Duration
+};
+
+use serde::Serialize;
+
+use crate::{
+    bogon::bogon_range,
+    config,
+    dns::{DnsQuery, DnsType, default_dns_server, dns_server_addr},
+    finding::{Finding, Severity},
+    text::read_text_file,
+    trailing_options
+};
+
+// Suffixes of zones that are never published on the Internet
+const INTERNAL_SUFFIXES: &[&str] = &["local.", "internal.", "intranet.", "lan.", "corp.", "home.", "home.arpa.", "localdomain.", "private."];
+
+/// One record of a zone, with absolute names and its TTL in seconds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ZoneRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub class: String,
+    #[serde(rename = "type")]
+    pub rtype: String,
+    /// Data fields, names made absolute and SOA timers in seconds; TXT strings unquoted
+    pub rdata: Vec<String>,
+    /// Line of the zone file the record starts on
+    pub line: usize
+}
+
+impl ZoneRecord {
+    /// The name a CNAME, DNAME, NS, PTR, MX or SRV record points to
+    pub fn target(&self) -> Option<&str> {
+        let index = match self.rtype.as_str() {
+            "CNAME" | "DNAME" | "NS" | "PTR" => 0,
+            "MX" => 1,
+            "SRV" => 3,
+            _ => return None
+        };
+        self.rdata.get(index).map(String::as_str)
+    }
+
+    /// The address of an A or AAAA record
+    pub fn address(&self) -> Option<IpAddr> {
+        match self.rtype.as_str() {
+            "A" | "AAAA" => self.rdata.first()?.parse().ok(),
+            _ => None
+        }
+    }
+
+    /// The strings of a TXT or SPF record joined, as resolvers hand them to SPF and DMARC
+    pub fn text(&self) -> Option<String> {
+        match self.rtype.as_str() {
+            "TXT" | "SPF" => Some(self.rdata.concat()),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for ZoneRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.name, self.ttl, self.class, self.rtype)?;
+        for field in &self.rdata {
+            if matches!(self.rtype.as_str(), "TXT" | "SPF") || (self.rtype == "CAA" && field.contains([' ', ';'])) {
+                write!(f, " {:?}", field)?;
+            } else {
+                write!(f, " {}", field)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A parsed zone file
+#[derive(Debug, Clone, Serialize)]
+pub struct ZoneFile {
+    /// Origin the file starts with (the last `$ORIGIN` may differ)
+    pub origin: Option<String>,
+    pub records: Vec<ZoneRecord>,
+    /// Skipped entries and unfollowed directives, with their line numbers
+    pub warnings: Vec<String>
+}
+
+impl fmt::Display for ZoneFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(origin) = &self.origin {
+            writeln!(f, "$ORIGIN {}", origin)?;
+        }
+        for record in &self.records {
+            writeln!(f, "{}", record)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "; {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for the zone audit
+#[derive(Debug, Clone)]
+pub struct ZoneAuditOptions {
+    /// Origin for files without `$ORIGIN`, instead of guessing it from the file name
+    pub origin: Option<String>,
+    /// The zone is only served internally: internal addresses are expected and mail records are not checked
+    pub internal: bool,
+    /// Resolve CNAME targets outside the zone and flag those that do not exist
+    pub resolve: bool,
+    /// Resolver for `resolve` (the system's by default)
+    pub server: Option<SocketAddr>,
+    pub timeout: Duration,
+    /// Largest ratio between the longest and shortest TTL before the spread is flagged
+    pub ttl_ratio: u32
+}
+
+impl Default for ZoneAuditOptions {
+    fn default() -> Self {
+        ZoneAuditOptions {
+            origin: None,
+            internal: false,
+            resolve: false,
+            server: None,
+            timeout: config::read_timeout(Duration::from_secs(3)),
+            ttl_ratio: 1000
+        }
+    }
+}
+
+/// Findings of a zone audit
+#[derive(Debug, Clone, Serialize)]
+pub struct ZoneAudit {
+    pub zone: String,
+    pub records: usize,
+    pub findings: Vec<Finding>,
+    /// Parser warnings and CNAME targets that could not be resolved
+    pub notes: Vec<String>
+}
+
+impl fmt::Display for ZoneAudit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Zone: {} ({} records, {} findings)", self.zone, self.records, self.findings.len())?;
+        for finding in &self.findings {
+            writeln!(f)?;
+            write!(f, "{}", finding)?;
+        }
+        if !self.notes.is_empty() {
+            writeln!(f)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "Note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+// A token of an entry; quoted strings are never directives, `@` or empty owners
+struct Token {
+    text: String,
+    quoted: bool
+}
+
+// The tokens of one entry (a line, or several joined by parentheses)
+struct Entry {
+    line: usize,
+    // The entry starts with a blank, so it has no owner of its own
+    inherits_owner: bool,
+    tokens: Vec<Token>
+}
+
+// Splits master file text into entries, handling comments, quotes, escapes and parentheses
+fn entries(text: &str, warnings: &mut Vec<String>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    let mut depth = 0;
+    let mut current: Option<Entry> = None;
+    let mut at_line_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                line += 1;
+                at_line_start = depth == 0;
+                if depth == 0 {
+                    if let Some(done) = current.take().filter(|done| !done.tokens.is_empty()) {
+                        entries.push(done);
+                    }
+                }
+            },
+            ';' => {
+                while chars.peek().is_some_and(|next| *next != '\n') {
+                    chars.next();
+                }
+            },
+            ' ' | '\t' | '\r' => {
+                if at_line_start && current.is_none() {
+                    current = Some(Entry { line, inherits_owner: true, tokens: Vec::new() });
+                }
+                at_line_start = false;
+            },
+            '(' => {
+                current.get_or_insert_with(|| Entry { line, inherits_owner: false, tokens: Vec::new() });
+                depth += 1;
+                at_line_start = false;
+            },
+            ')' => {
+                if depth == 0 {
+                    warnings.push(format!("line {}: unbalanced ')'", line));
+                } else {
+                    depth -= 1;
+                }
+            },
+            '"' => {
+                current.get_or_insert_with(|| Entry { line, inherits_owner: false, tokens: Vec::new() });
+                let start = line;
+                let mut value = String::new();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => {
+                            closed = true;
+                            break;
+                        },
+                        '\\' => value.push(unescape(&mut chars)),
+                        '\n' => {
+                            line += 1;
+                            value.push('\n');
+                        },
+                        _ => value.push(c)
+                    }
+                }
+                if !closed {
+                    warnings.push(format!("line {}: unterminated quoted string", start));
+                }
+                if let Some(current) = current.as_mut() {
+                    current.tokens.push(Token { text: value, quoted: true });
+                }
+                at_line_start = false;
+            },
+            _ => {
+                current.get_or_insert_with(|| Entry { line, inherits_owner: false, tokens: Vec::new() });
+                let mut value = String::new();
+                let mut c = c;
+                loop {
+                    // Names keep their escapes ("esc\\.aped" is one label); text fields are unescaped later
+                    value.push(c);
+                    if c == '\\' {
+                        value.extend(chars.next());
+                    }
+                    match chars.peek() {
+                        Some(next) if !matches!(next, ' ' | '\t' | '\r' | '\n' | ';' | '(' | ')' | '"') => c = chars.next().unwrap_or_default(),
+                        _ => break
+                    }
+                }
+                if let Some(current) = current.as_mut() {
+                    current.tokens.push(Token { text: value, quoted: false });
+                }
+                at_line_start = false;
+            }
+        }
+    }
+
+    if depth > 0 {
+        warnings.push(format!("line {}: unbalanced '(' at the end of the file", line));
+    }
+    if let Some(done) = current.filter(|done| !done.tokens.is_empty()) {
+        entries.push(done);
+    }
+
+    entries
+}
+
+// The character after a backslash: "\065" is 'A', "\." is '.'
+fn unescape(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> char {
+    let code: String = chars.clone().take(3).collect();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_digit()) {
+        chars.nth(2);
+        return code.parse::<u8>().map(char::from).unwrap_or('?');
+    }
+
+    chars.next().unwrap_or('\\')
+}
+
+fn unescape_all(text: &str) -> String {
+    let mut chars = text.chars().peekable();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            value.push(unescape(&mut chars));
+        } else {
+            value.push(c);
+        }
+    }
+    value
+}
+
+// Parses a TTL in seconds, with BIND's unit suffixes ("3600", "1h", "1w2d", "1H30M")
+pub(crate) fn parse_ttl(text: &str) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(seconds) = text.parse::<u32>() {
+        return Some(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c.to_ascii_lowercase() {
+            'w' => 604_800,
+            'd' => 86_400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None
+        };
+        total += number.parse::<u64>().ok()? * unit;
+        number.clear();
+    }
+
+    // A trailing number without a unit is seconds
+    if !number.is_empty() {
+        total += number.parse::<u64>().ok()?;
+    }
+    u32::try_from(total).ok()
+}
+
+fn is_class(text: &str) -> bool {
+    matches!(text.to_ascii_uppercase().as_str(), "IN" | "CH" | "HS" | "CS")
+}
+
+// Makes a name absolute: "@" is the origin, names not ending in a dot are relative to it
+fn absolute(name: &str, origin: Option<&str>) -> Result<String, String> {
+    let absolute = if name == "@" {
+        origin.ok_or("'@' used without an origin (add $ORIGIN or pass one)")?.to_string()
+    } else if name.ends_with('.') && !name.ends_with("\\.") {
+        name.to_string()
+    } else {
+        match origin.ok_or_else(|| format!("relative name '{}' without an origin (add $ORIGIN or pass one)", name))? {
+            "." => format!("{}.", name),
+            origin => format!("{}.{}", name, origin)
+        }
+    };
+
+    Ok(absolute.to_ascii_lowercase())
+}
+
+// Guesses the zone from a file name: db.example.com, example.com.zone, example.com.db, example.com.hosts
+fn origin_from_path(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    let name = name.strip_prefix("db.").unwrap_or(&name);
+    let name = [".zone", ".db", ".hosts", ".txt"].iter().find_map(|suffix| name.strip_suffix(suffix)).unwrap_or(name);
+
+    if name.contains('.') && name.split('.').all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+        Some(format!("{}.", name))
+    } else {
+        None
+    }
+}
+
+impl ZoneFile {
+    /// Parses master file text; `origin` is the zone the file belongs to, used until a `$ORIGIN`
+    pub fn parse(text: &str, origin: Option<&str>) -> ZoneFile {
+        let mut warnings = Vec::new();
+        let initial = origin.map(|origin| {
+            let origin = origin.trim().to_ascii_lowercase();
+            if origin.ends_with('.') { origin } else { format!("{}.", origin) }
+        });
+        let mut origin = initial.clone();
+        let mut default_ttl: Option<u32> = None;
+        let mut last_ttl: Option<u32> = None;
+        let mut soa_minimum: Option<u32> = None;
+        let mut owner: Option<String> = None;
+        let mut records = Vec::new();
+
+        for entry in entries(text, &mut warnings) {
+            let first = &entry.tokens[0];
+
+            if !entry.inherits_owner && !first.quoted && first.text.starts_with('$') {
+                let argument = entry.tokens.get(1).map(|token| token.text.as_str());
+                match (first.text.to_ascii_uppercase().as_str(), argument) {
+                    ("$ORIGIN", Some(name)) => match absolute(name, origin.as_deref()) {
+                        Ok(name) => origin = Some(name),
+                        Err(e) => warnings.push(format!("line {}: $ORIGIN {}", entry.line, e))
+                    },
+                    ("$TTL", Some(ttl)) => match parse_ttl(ttl) {
+                        Some(ttl) => default_ttl = Some(ttl),
+                        None => warnings.push(format!("line {}: invalid $TTL '{}'", entry.line, ttl))
+                    },
+                    ("$INCLUDE", _) => warnings.push(format!("line {}: $INCLUDE not followed; parse the included file separately", entry.line)),
+                    ("$GENERATE", _) => warnings.push(format!("line {}: $GENERATE records not expanded", entry.line)),
+                    (directive, _) => warnings.push(format!("line {}: unknown or incomplete directive {}", entry.line, directive))
+                }
+                continue;
+            }
+
+            match parse_record(&entry, origin.as_deref(), &mut owner) {
+                Ok((mut record, ttl)) => {
+                    if record.rtype == "SOA" {
+                        soa_minimum = record.rdata.get(6).and_then(|minimum| minimum.parse().ok());
+                    }
+
+                    // RFC 2308: $TTL first; before it, the last explicit TTL (RFC 1035), then the SOA minimum
+                    record.ttl = match ttl.or(default_ttl).or(last_ttl).or(soa_minimum) {
+                        Some(ttl) => ttl,
+                        None => {
+                            warnings.push(format!("line {}: no TTL for {} {} and no $TTL; using 0", entry.line, record.name, record.rtype));
+                            0
+                        }
+                    };
+                    if ttl.is_some() {
+                        last_ttl = ttl;
+                    }
+                    records.push(record);
+                },
+                Err(e) => warnings.push(format!("line {}: {}", entry.line, e))
+            }
+        }
+
+        ZoneFile { origin: initial.or_else(|| records.first().filter(|record| record.rtype == "SOA").map(|record| record.name.clone())), records, warnings }
+    }
+
+    /// The zone apex: the owner of the SOA record, or the origin
+    pub fn apex(&self) -> Option<&str> {
+        self.records.iter().find(|record| record.rtype == "SOA").map(|record| record.name.as_str()).or(self.origin.as_deref())
+    }
+}
+
+// Parses "[owner] [ttl] [class] type rdata..." (TTL and class in either order), returning the record and its explicit TTL
+fn parse_record(entry: &Entry, origin: Option<&str>, owner: &mut Option<String>) -> Result<(ZoneRecord, Option<u32>), String> {
+    let mut tokens = entry.tokens.iter().peekable();
+
+    if !entry.inherits_owner {
+        let name = tokens.next().ok_or("empty entry")?;
+        *owner = Some(absolute(&name.text, origin)?);
+    }
+    let name = owner.clone().ok_or("record without an owner name")?;
+
+    let mut ttl = None;
+    let mut class = None;
+    let rtype = loop {
+        let token = tokens.next().ok_or_else(|| format!("no record type for {}", name))?;
+        if token.quoted {
+            return Err(format!("unexpected quoted string \"{}\" before the record type", token.text));
+        }
+
+        match (parse_ttl(&token.text), is_class(&token.text)) {
+            (Some(value), _) if ttl.is_none() => ttl = Some(value),
+            (_, true) if class.is_none() => class = Some(token.text.to_ascii_uppercase()),
+            _ => break token.text.to_ascii_uppercase()
+        }
+    };
+
+    if !rtype.starts_with(|c: char| c.is_ascii_alphabetic()) || !rtype.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("invalid record type '{}'", rtype));
+    }
+
+    let fields: Vec<&Token> = tokens.collect();
+    let rdata = parse_rdata(&rtype, &fields, origin)?;
+
+    Ok((ZoneRecord { name, ttl: 0, class: class.unwrap_or_else(|| "IN".to_string()), rtype, rdata, line: entry.line }, ttl))
+}
+
+// Normalizes the data fields of the types whose layout is known
+fn parse_rdata(rtype: &str, fields: &[&Token], origin: Option<&str>) -> Result<Vec<String>, String> {
+    let texts: Vec<&str> = fields.iter().map(|token| token.text.as_str()).collect();
+    let expect = |count: usize| -> Result<(), String> {
+        if texts.len() == count {
+            Ok(())
+        } else {
+            Err(format!("{} record with {} data fields (expected {}): {}", rtype, texts.len(), count, texts.join(" ")))
+        }
+    };
+    let number = |text: &str| -> Result<String, String> {
+        text.parse::<u16>().map(|value| value.to_string()).map_err(|_| format!("invalid number '{}' in {} record", text, rtype))
+    };
+    let name = |text: &str| absolute(text, origin);
+
+    let rdata = match rtype {
+        "A" => {
+            expect(1)?;
+            vec![texts[0].parse::<Ipv4Addr>().map_err(|_| format!("invalid IPv4 address '{}'", texts[0]))?.to_string()]
+        },
+        "AAAA" => {
+            expect(1)?;
+            vec![texts[0].parse::<Ipv6Addr>().map_err(|_| format!("invalid IPv6 address '{}'", texts[0]))?.to_string()]
+        },
+        "NS" | "CNAME" | "PTR" | "DNAME" => {
+            expect(1)?;
+            vec![name(texts[0])?]
+        },
+        "MX" => {
+            expect(2)?;
+            vec![number(texts[0])?, name(texts[1])?]
+        },
+        "SRV" => {
+            expect(4)?;
+            vec![number(texts[0])?, number(texts[1])?, number(texts[2])?, name(texts[3])?]
+        },
+        "SOA" => {
+            expect(7)?;
+            let mut rdata = vec![name(texts[0])?, name(texts[1])?];
+            for timer in &texts[2..] {
+                rdata.push(parse_ttl(timer).ok_or_else(|| format!("invalid SOA value '{}'", timer))?.to_string());
+            }
+            rdata
+        },
+        "TXT" | "SPF" => {
+            if texts.is_empty() {
+                return Err(format!("{} record without strings", rtype));
+            }
+            fields.iter().map(|token| if token.quoted { token.text.clone() } else { unescape_all(&token.text) }).collect()
+        },
+        "CAA" => {
+            expect(3)?;
+            vec![texts[0].parse::<u8>().map_err(|_| format!("invalid CAA flags '{}'", texts[0]))?.to_string(), texts[1].to_ascii_lowercase(), texts[2].to_string()]
+        },
+        _ => texts.iter().map(|text| text.to_string()).collect()
+    };
+
+    Ok(rdata)
+}
+
+/// Parses the zone file at `path`; `origin` is the zone it belongs to when the file has no `$ORIGIN`
+pub fn mercy_zonefile<P: AsRef<Path>>(path: P, origin: Option<&str>) -> Result<ZoneFile, String> {
+    let path = path.as_ref();
+    // A BOM left in front of the first line would hide a leading $ORIGIN
+    let text = read_text_file(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    let guessed = match origin {
+        Some(_) => None,
+        None => origin_from_path(path)
+    };
+    let mut zone = ZoneFile::parse(&text, origin.or(guessed.as_deref()));
+    if let Some(guessed) = guessed {
+        zone.warnings.insert(0, format!("origin {} guessed from the file name", guessed));
+    }
+
+    if zone.records.is_empty() {
+        return Err(match zone.warnings.first() {
+            Some(warning) => format!("No records in {} ({})", path.display(), warning),
+            None => format!("No records in {}", path.display())
+        });
+    }
+
+    Ok(zone)
+}
+
+/// Audits the zone file at `path` for internal addresses, wildcards, dangling CNAMEs, missing SPF/DMARC and TTL spread
+pub fn mercy_zone_audit<P: AsRef<Path>>(path: P, options: &ZoneAuditOptions) -> Result<ZoneAudit, String> {
+    let zone = mercy_zonefile(path, options.origin.as_deref())?;
+    Ok(zone.audit(options))
+}
+
+impl ZoneFile {
+    /// Common misconfigurations of a zone published on the Internet
+    pub fn audit(&self, options: &ZoneAuditOptions) -> ZoneAudit {
+        let apex = self.apex().unwrap_or(".").to_string();
+        let mut findings = Vec::new();
+        let mut notes = self.warnings.clone();
+
+        let internal_zone = options.internal || INTERNAL_SUFFIXES.iter().any(|suffix| apex == *suffix || apex.ends_with(&format!(".{}", suffix)));
+        if !internal_zone {
+            self.address_findings(&mut findings);
+        }
+
+        for record in self.records.iter().filter(|record| record.name == "*." || record.name.starts_with("*.")) {
+            findings.push(Finding::new(
+                "zone.wildcard",
+                "Wildcard record",
+                Severity::Low,
+                &record.name,
+                &format!("{} (line {})", record, record.line),
+                "Wildcards answer for every name below them, including typos and names an attacker chooses; list the names that are needed instead"
+            ));
+        }
+
+        self.cname_findings(&apex, options, &mut findings, &mut notes);
+        if !internal_zone {
+            self.mail_findings(&apex, &mut findings);
+        }
+        self.ttl_findings(&apex, options.ttl_ratio, &mut findings);
+
+        ZoneAudit { zone: apex, records: self.records.len(), findings, notes }
+    }
+
+    fn address_findings(&self, findings: &mut Vec<Finding>) {
+        for record in &self.records {
+            let Some(address) = record.address() else {
+                continue;
+            };
+            let Some((category, description)) = bogon_range(address) else {
+                continue;
+            };
+
+            if matches!(category, "private" | "loopback" | "link-local") {
+                findings.push(Finding::new(
+                    "zone.internal-address",
+                    "Internal address published in a public zone",
+                    Severity::Medium,
+                    &record.name,
+                    &format!("{} ({}, line {})", record, description, record.line),
+                    "Serve internal names from an internal zone or split-horizon view; published internal addresses map the internal network for anyone"
+                ));
+            } else {
+                findings.push(Finding::new(
+                    "zone.unroutable-address",
+                    "Unroutable address published in a public zone",
+                    Severity::Low,
+                    &record.name,
+                    &format!("{} ({}, line {})", record, description, record.line),
+                    "Remove the record or point it at a routable address"
+                ));
+            }
+        }
+    }
+
+    fn cname_findings(&self, apex: &str, options: &ZoneAuditOptions, findings: &mut Vec<Finding>, notes: &mut Vec<String>) {
+        let names: HashSet<&str> = self.records.iter().map(|record| record.name.as_str()).collect();
+        let wildcards: Vec<&str> = names.iter().filter_map(|name| name.strip_prefix('*')).collect();
+        let delegations: Vec<&str> = self.records.iter()
+            .filter(|record| record.rtype == "NS" && record.name != apex)
+            .map(|record| record.name.as_str())
+            .collect();
+        let server = options.server.unwrap_or_else(default_dns_server);
+        let mut resolved: HashMap<String, Result<bool, String>> = HashMap::new();
+
+        for record in self.records.iter().filter(|record| record.rtype == "CNAME") {
+            let Some(target) = record.target() else {
+                continue;
+            };
+            let in_zone = apex == "." || target == apex || target.ends_with(&format!(".{}", apex));
+            let delegated = delegations.iter().any(|child| target == *child || target.ends_with(&format!(".{}", child)));
+
+            if in_zone && !delegated {
+                let covered = wildcards.iter().any(|suffix| target.ends_with(suffix) && target.len() > suffix.len());
+                if !names.contains(target) && !covered {
+                    findings.push(Finding::new(
+                        "zone.dangling-cname",
+                        "CNAME points to a name not defined in the zone",
+                        Severity::Medium,
+                        &record.name,
+                        &format!("{} (line {}); no records for {}", record, record.line, target),
+                        "Remove the CNAME or define its target; a dangling alias resolves to nothing and can be taken over if the name is later delegated"
+                    ));
+                }
+                continue;
+            }
+
+            if !options.resolve || in_zone {
+                continue;
+            }
+
+            let exists = resolved.entry(target.to_string()).or_insert_with(|| {
+                let response = DnsQuery::new(target, DnsType::A).send(server, options.timeout)?;
+                match response.rcode {
+                    0 => Ok(true),
+                    3 => Ok(false),
+                    _ => Err(response.rcode_name())
+                }
+            });
+
+            match exists {
+                Ok(true) => {},
+                Ok(false) => findings.push(Finding::new(
+                    "zone.dangling-cname-external",
+                    "CNAME points to an external name that does not exist",
+                    Severity::High,
+                    &record.name,
+                    &format!("{} (line {}); {} returned NXDOMAIN from {}", record, record.line, target, server),
+                    "Remove the CNAME; whoever registers the target name or claims it on the hosting service serves content for this name (subdomain takeover)"
+                )),
+                Err(e) => notes.push(format!("Unable to resolve CNAME target {}: {}", target, e))
+            }
+        }
+    }
+
+    fn mail_findings(&self, apex: &str, findings: &mut Vec<Finding>) {
+        let texts = |name: &str| -> Vec<String> {
+            self.records.iter().filter(|record| record.name == name).filter_map(ZoneRecord::text).collect()
+        };
+        let sends_mail = self.records.iter().any(|record| record.name == apex && record.rtype == "MX");
+        let severity = if sends_mail { Severity::Medium } else { Severity::Low };
+
+        if !texts(apex).iter().any(|text| text.to_ascii_lowercase().starts_with("v=spf1")) {
+            findings.push(Finding::new(
+                "zone.spf-missing",
+                "No SPF record at the zone apex",
+                severity,
+                apex,
+                if sends_mail { "The zone has MX records but no TXT record starting with v=spf1" } else { "No TXT record starting with v=spf1" },
+                if sends_mail { "Publish an SPF record listing the servers allowed to send mail for the domain" } else { "Publish \"v=spf1 -all\" so nobody can send mail as a domain that sends none" }
+            ));
+        }
+
+        let dmarc_name = format!("_dmarc.{}", apex.trim_start_matches('.'));
+        let dmarc: Vec<String> = texts(&dmarc_name).into_iter().filter(|text| text.to_ascii_lowercase().starts_with("v=dmarc1")).collect();
+        match dmarc.first() {
+            None => findings.push(Finding::new(
+                "zone.dmarc-missing",
+                "No DMARC record",
+                severity,
+                &dmarc_name,
+                &format!("No TXT record starting with v=DMARC1 at {}", dmarc_name),
+                "Publish a DMARC policy (\"v=DMARC1; p=reject\", or p=quarantine while rolling out)"
+            )),
+            Some(policy) => {
+                let none = policy.split(';').map(str::trim).any(|tag| tag.eq_ignore_ascii_case("p=none"));
+                if none {
+                    findings.push(Finding::new(
+                        "zone.dmarc-monitor-only",
+                        "DMARC policy does not act on failing mail",
+                        Severity::Low,
+                        &dmarc_name,
+                        policy,
+                        "Move from p=none to p=quarantine or p=reject once reports show legitimate mail passing"
+                    ));
+                }
+            }
+        }
+    }
+
+    fn ttl_findings(&self, apex: &str, ratio: u32, findings: &mut Vec<Finding>) {
+        let shortest = self.records.iter().filter(|record| record.ttl > 0).min_by_key(|record| record.ttl);
+        let longest = self.records.iter().max_by_key(|record| record.ttl);
+
+        if let (Some(shortest), Some(longest)) = (shortest, longest) {
+            if longest.ttl as u64 > shortest.ttl as u64 * ratio.max(1) as u64 {
+                findings.push(Finding::new(
+                    "zone.ttl-spread",
+                    "TTLs vary widely across the zone",
+                    Severity::Low,
+                    apex,
+                    &format!("shortest {} s ({} {}, line {}), longest {} s ({} {}, line {})", shortest.ttl, shortest.name, shortest.rtype, shortest.line, longest.ttl, longest.name, longest.rtype, longest.line),
+                    "Keep TTLs within a narrow range; very long TTLs delay changes and incident response, very short ones add load and hint at fast-flux"
+                ));
+            }
+        }
+
+        for record in self.records.iter().filter(|record| record.ttl == 0 && record.rtype != "SOA") {
+            findings.push(Finding::new(
+                "zone.zero-ttl",
+                "Record with a TTL of 0",
+                Severity::Info,
+                &record.name,
+                &format!("{} (line {})", record, record.line),
+                "Give the record a TTL; 0 disables caching of the answer"
+            ));
+        }
+    }
+}
+
+// Parses "path [origin=example.com]"
+pub(crate) fn zonefile_from_args(input: &str) -> Result<ZoneFile, String> {
+    let (path, pairs) = trailing_options(input, &["origin"]);
+    let origin = pairs.into_iter().find(|(key, _)| *key == "origin").map(|(_, value)| value);
+
+    if path.is_empty() {
+        return Err("No zone file specified".to_string());
+    }
+    mercy_zonefile(Path::new(&path), origin)
+}
+
+// Parses "path [origin=example.com] [internal=true] [resolve=true] [server=1.1.1.1] [timeout=3] [ttl_ratio=1000]"
+pub(crate) fn zone_audit_from_args(input: &str) -> Result<ZoneAudit, String> {
+    let (path, pairs) = trailing_options(input, &["origin", "internal", "resolve", "server", "timeout", "ttl_ratio"]);
+    let mut options = ZoneAuditOptions::default();
+
+    for (key, value) in pairs {
+        match key {
+            "origin" => options.origin = Some(value.to_string()),
+            "internal" => options.internal = value == "true",
+            "resolve" => options.resolve = value == "true",
+            "server" => options.server = Some(dns_server_addr(value)?),
+            "timeout" => options.timeout = Duration::from_secs(value.parse().map_err(|_| format!("Invalid timeout '{}'", value))?),
+            _ => options.ttl_ratio = value.parse().map_err(|_| format!("Invalid ttl_ratio '{}'", value))?
+        }
+    }
+
+    if path.is_empty() {
+        return Err("No zone file specified".to_string());
+    }
+    mercy_zone_audit(Path::new(&path), &options)
+}
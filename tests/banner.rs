@@ -0,0 +1,118 @@
+// Banner grabbing against a TcpListener spawned by each test: the probe sent, the banner read and escaped, and each way a port can fail
+
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::Duration
+};
+
+use mercy::{BannerOptions, BannerProbe, escape_banner, mercy_banner_grab, mercy_extra};
+
+// A binary greeting: ESC sequences, NUL, a backslash and a high byte among the text
+const BINARY: &[u8] = b"220 ftp\x1b[31m ready\r\n\x00\x01\\\xfe\tend\n";
+
+// Accepts one connection and hands it to `serve`; the handle gives back what the client sent
+fn listener(serve: impl FnOnce(TcpStream) -> Vec<u8> + Send + 'static) -> (SocketAddr, JoinHandle<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let address = listener.local_addr().expect("address");
+    let handle = thread::spawn(move || serve(listener.accept().expect("accept").0));
+    (address, handle)
+}
+
+// Sends `banner`, then reads whatever the client sends until it hangs up
+fn greeter(banner: &'static [u8]) -> (SocketAddr, JoinHandle<Vec<u8>>) {
+    listener(move |mut stream| {
+        stream.write_all(banner).expect("banner");
+        stream.shutdown(Shutdown::Write).expect("shutdown");
+        let mut received = Vec::new();
+        let _ = stream.read_to_end(&mut received);
+        received
+    })
+}
+
+fn options(probe: BannerProbe) -> BannerOptions {
+    BannerOptions { timeout: Duration::from_secs(2), probe }
+}
+
+#[test]
+fn banners_are_read_and_escaped() {
+    let (address, server) = greeter(BINARY);
+    let grab = mercy_banner_grab(&address.to_string(), &options(BannerProbe::Crlf)).expect("banner");
+    assert_eq!(grab.banner, "220 ftp\\x1b[31m ready\n\\x00\\x01\\x5c\\xfe\tend\n");
+    assert_eq!((grab.address, grab.bytes, grab.truncated, grab.probe), (address, BINARY.len(), false, BannerProbe::Crlf));
+    assert_eq!(server.join().expect("server"), b"\r\n");
+    assert_eq!(escape_banner(BINARY), grab.banner);
+
+    // An ephemeral port gets the CRLF probe when left to choose; the HEAD request only on request
+    let (address, server) = greeter(b"HTTP/1.0 200 OK\r\nServer: mock\r\n\r\n");
+    let grab = mercy_banner_grab(&address.to_string(), &options(BannerProbe::Auto)).expect("auto");
+    assert_eq!((grab.probe, server.join().expect("server")), (BannerProbe::Crlf, b"\r\n".to_vec()));
+    let (address, server) = greeter(b"HTTP/1.0 200 OK\r\nServer: mock\r\n\r\n");
+    let grab = mercy_banner_grab(&address.to_string(), &options(BannerProbe::Http)).expect("http");
+    assert_eq!((grab.banner.as_str(), server.join().expect("server")), ("HTTP/1.0 200 OK\nServer: mock\n\n", b"HEAD / HTTP/1.0\r\n\r\n".to_vec()));
+
+    // A service that talks first is only listened to
+    let (address, server) = greeter(b"SSH-2.0-mock\r\n");
+    assert_eq!(mercy_banner_grab(&address.to_string(), &options(BannerProbe::None)).expect("none").banner, "SSH-2.0-mock\n");
+    assert!(server.join().expect("server").is_empty());
+
+    // More than the limit is cut at 4096 bytes
+    let (address, server) = greeter(&[b'A'; 6000]);
+    let grab = mercy_banner_grab(&address.to_string(), &options(BannerProbe::None)).expect("large");
+    assert_eq!((grab.bytes, grab.truncated, grab.banner.len()), (4096, true, 4096));
+    server.join().expect("server");
+
+    // The string call with its trailing options
+    let (address, server) = greeter(b"+OK POP3 ready\r\n");
+    let output = mercy_extra("banner_grab", &format!("{} timeout=2 probe=none", address));
+    assert_eq!(output, format!("Banner from {} (16 bytes, probe none):\n+OK POP3 ready", address));
+    server.join().expect("server");
+    let (address, server) = greeter(b"\x00\xff");
+    let json: serde_json::Value = serde_json::from_str(&mercy_extra("banner_grab_json", &format!("{} probe=none", address))).expect("JSON");
+    assert_eq!((json["banner"].as_str(), json["bytes"].as_u64(), json["probe"].as_str()), (Some("\\x00\\xff"), Some(2), Some("none")));
+    server.join().expect("server");
+}
+
+#[test]
+fn refused_silent_reset_and_closed_ports_fail_differently() {
+    // Nothing listening: the port of a listener that is gone
+    let address = TcpListener::bind("127.0.0.1:0").expect("bind").local_addr().expect("address");
+    let error = mercy_banner_grab(&address.to_string(), &options(BannerProbe::Crlf)).expect_err("refused");
+    assert_eq!(error, format!("Connection refused by {} (nothing listening)", address));
+
+    // Accepted, then nothing said until the client gives up
+    let (address, server) = listener(|mut stream| {
+        let mut received = Vec::new();
+        let _ = stream.read_to_end(&mut received);
+        received
+    });
+    let quick = BannerOptions { timeout: Duration::from_millis(300), probe: BannerProbe::None };
+    let error = mercy_banner_grab(&address.to_string(), &quick).expect_err("silent");
+    assert_eq!(error, format!("Connected to {} but nothing was sent within 0.3 s (probe none)", address));
+    server.join().expect("server");
+
+    // Closed with the probe unread, which makes the kernel reset the connection
+    let (address, server) = listener(|stream| {
+        thread::sleep(Duration::from_millis(200));
+        drop(stream);
+        Vec::new()
+    });
+    let error = mercy_banner_grab(&address.to_string(), &options(BannerProbe::Crlf)).expect_err("reset");
+    assert_eq!(error, format!("{} reset the connection without sending anything", address));
+    server.join().expect("server");
+
+    // Closed cleanly without a word
+    let (address, server) = listener(|stream| {
+        drop(stream);
+        Vec::new()
+    });
+    let error = mercy_banner_grab(&address.to_string(), &options(BannerProbe::None)).expect_err("closed");
+    assert_eq!(error, format!("{} closed the connection without sending anything (probe none)", address));
+    server.join().expect("server");
+
+    // Input the call cannot use
+    assert_eq!(mercy_banner_grab("127.0.0.1", &options(BannerProbe::None)).expect_err("no port"), "Expected \"host:port\", got '127.0.0.1'");
+    assert!(mercy_extra("banner_grab", &format!("{} probe=ftp", address)).contains("Invalid probe 'ftp' (expected auto, none, crlf or http)"));
+    assert!(mercy_extra("banner_grab", &format!("{} timeout=0", address)).contains("Invalid timeout '0'"));
+}
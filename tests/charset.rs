@@ -0,0 +1,138 @@
+// Encoding detection and transcoding to UTF-8 of fixtures in each encoding
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use mercy::{Charset, EncodingConfidence, mercy_decode, mercy_detect_encoding, mercy_extra, transcode};
+
+// What Windows-1252 puts at 0x80 to 0x9F (from the code page's published table), None where it has nothing
+const WINDOWS_1252_C1: [(u8, Option<char>); 32] = [
+    (0x80, Some('€')), (0x81, None), (0x82, Some('‚')), (0x83, Some('ƒ')), (0x84, Some('„')), (0x85, Some('…')), (0x86, Some('†')), (0x87, Some('‡')),
+    (0x88, Some('ˆ')), (0x89, Some('‰')), (0x8A, Some('Š')), (0x8B, Some('‹')), (0x8C, Some('Œ')), (0x8D, None), (0x8E, Some('Ž')), (0x8F, None),
+    (0x90, None), (0x91, Some('‘')), (0x92, Some('’')), (0x93, Some('“')), (0x94, Some('”')), (0x95, Some('•')), (0x96, Some('–')), (0x97, Some('—')),
+    (0x98, Some('˜')), (0x99, Some('™')), (0x9A, Some('š')), (0x9B, Some('›')), (0x9C, Some('œ')), (0x9D, None), (0x9E, Some('ž')), (0x9F, Some('Ÿ'))
+];
+
+// Non-ASCII in every encoding below
+const TEXT: &str = "Grüße aus Köln, naïve café, ½ × ¾ = 0,375\n";
+
+fn windows_1252(text: &str) -> Vec<u8> {
+    text.chars().map(|c| match WINDOWS_1252_C1.iter().find(|(_, mapped)| *mapped == Some(c)) {
+        Some((byte, _)) => *byte,
+        None => u8::try_from(u32::from(c)).expect("a Windows-1252 character")
+    }).collect()
+}
+
+fn latin_1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| u8::try_from(u32::from(c)).expect("a Latin-1 character")).collect()
+}
+
+fn utf16(text: &str, little_endian: bool, bom: bool) -> Vec<u8> {
+    let bom = bom.then_some(0xfeff);
+    bom.into_iter().chain(text.encode_utf16()).flat_map(|unit| if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() }).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn fixtures_are_detected_and_read_back() {
+    let dir = fixture_dir("charset-fixtures");
+    let quoted = "“Smart quotes” – and a € sign…\n";
+    let fixtures = [
+        ("utf8.txt", TEXT.as_bytes().to_vec(), Charset::Utf8, EncodingConfidence::High, "utf8"),
+        ("utf16le-bom.txt", utf16(TEXT, true, true), Charset::Utf16Le, EncodingConfidence::High, "utf16le"),
+        ("utf16be-bom.txt", utf16(TEXT, false, true), Charset::Utf16Be, EncodingConfidence::High, "utf16be"),
+        ("utf16le.txt", utf16(TEXT, true, false), Charset::Utf16Le, EncodingConfidence::Medium, "utf16le"),
+        ("utf16be.txt", utf16(TEXT, false, false), Charset::Utf16Be, EncodingConfidence::Medium, "utf16be"),
+        ("latin1.txt", latin_1(TEXT), Charset::Latin1, EncodingConfidence::Medium, "latin1"),
+        ("cp1252.txt", windows_1252(quoted), Charset::Windows1252, EncodingConfidence::Medium, "windows1252")
+    ];
+
+    for (name, bytes, charset, confidence, call) in fixtures {
+        let path = dir.join(name);
+        fs::write(&path, &bytes).expect("fixture");
+
+        let report = mercy_detect_encoding(&path).expect("report");
+        assert_eq!((report.encoding, report.confidence), (charset, confidence), "{}: {}", name, report.reason);
+        assert_eq!(report.size, bytes.len() as u64);
+        assert_eq!(report.bom, name.contains("bom"), "{}", name);
+
+        // The single-byte encodings are told apart from UTF-8 at the first high byte
+        let expected_offset = matches!(charset, Charset::Latin1 | Charset::Windows1252).then(|| bytes.iter().position(|byte| *byte >= 0x80).expect("high byte"));
+        assert_eq!(report.invalid_utf8_offset, expected_offset, "{}", name);
+
+        let text = if charset == Charset::Windows1252 { quoted } else { TEXT };
+        if call != "utf8" {
+            assert_eq!(mercy_decode(call, path.to_str().expect("path")), text, "{}", name);
+            assert_eq!(mercy_decode(call, &hex(&bytes)), text, "{}", name);
+        }
+        assert!(mercy_extra("detect_encoding", path.to_str().expect("path")).contains(&charset.to_string()), "{}", name);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn every_byte_round_trips_through_latin_1() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    let transcoded = transcode(&bytes, Charset::Latin1);
+    assert_eq!(transcoded.text.chars().count(), 256);
+    assert_eq!(latin_1(&transcoded.text), bytes);
+    assert_eq!(transcoded.with_warning().chars().count(), 256);
+}
+
+#[test]
+fn windows_1252_maps_0x80_to_0x9f() {
+    for (byte, expected) in WINDOWS_1252_C1 {
+        let transcoded = transcode(&[b'<', byte, b'>'], Charset::Windows1252);
+        match expected {
+            Some(c) => {
+                assert_eq!(transcoded.text, format!("<{}>", c), "0x{:02x}", byte);
+                assert_eq!(transcoded.undefined, 0);
+                assert_eq!(windows_1252(&transcoded.text), [b'<', byte, b'>']);
+            },
+            None => {
+                assert_eq!(transcoded.text, "<\u{FFFD}>", "0x{:02x}", byte);
+                assert_eq!(transcoded.undefined, 1);
+                assert!(transcoded.with_warning().ends_with("\n[warning: 1 undefined byte(s) replaced with U+FFFD]"));
+            }
+        }
+    }
+
+    // Outside 0x80 to 0x9F it agrees with Latin-1
+    let rest: Vec<u8> = (0..0x80).chain(0xA0..=0xFF).collect();
+    assert_eq!(transcode(&rest, Charset::Windows1252).text, transcode(&rest, Charset::Latin1).text);
+
+    // An undefined byte is no Windows-1252, so the file is taken for Latin-1 without much confidence
+    let dir = fixture_dir("charset-undefined");
+    fs::write(dir.join("c1.txt"), b"caf\xe9 \x81 \x93quoted\x94").expect("fixture");
+    let report = mercy_detect_encoding(dir.join("c1.txt")).expect("report");
+    assert_eq!((report.encoding, report.confidence, report.invalid_utf8_offset), (Charset::Latin1, EncodingConfidence::Low, Some(3)));
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn utf16_round_trips_and_damage_is_reported() {
+    let text = "Ωmega 東京 😀 \u{10FFFF}";
+    for little_endian in [true, false] {
+        let charset = if little_endian { Charset::Utf16Le } else { Charset::Utf16Be };
+        let bytes = utf16(text, little_endian, false);
+        let transcoded = transcode(&bytes, charset);
+        assert_eq!(transcoded.text, text);
+        assert_eq!(utf16(&transcoded.text, little_endian, false), bytes);
+
+        // A lone high surrogate and a byte left over at the end
+        let mut damaged = utf16("a", little_endian, false);
+        damaged.extend(if little_endian { [0x3D, 0xD8] } else { [0xD8, 0x3D] });
+        damaged.extend(utf16("b", little_endian, false));
+        damaged.push(0x41);
+        let transcoded = transcode(&damaged, charset);
+        assert_eq!(transcoded.text, "a\u{FFFD}b\u{FFFD}");
+        assert_eq!((transcoded.lone_surrogates, transcoded.truncated), (1, true));
+        assert!(transcoded.with_warning().ends_with("[warning: 1 lone surrogate(s), a truncated trailing byte replaced with U+FFFD]"));
+    }
+}
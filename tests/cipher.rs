@@ -0,0 +1,74 @@
+// Classical cipher solvers recovering known plaintexts
+
+use std::time::Duration;
+
+use mercy::{MercyRng, SubstitutionOptions, mercy_caesar_solve, mercy_encode, mercy_solve, mercy_substitution_solve};
+
+const PLAINTEXT: &str = "It was the best of times, it was the worst of times, it was the age of wisdom, \
+    it was the age of foolishness, it was the epoch of belief, it was the epoch of incredulity, \
+    it was the season of Light, it was the season of Darkness, it was the spring of hope, \
+    it was the winter of despair, we had everything before us, we had nothing before us.";
+
+// A freshly shuffled alphabet: the ciphertext letter for each of A to Z
+fn scrambled_key(seed: u64) -> Vec<u8> {
+    let mut rng = MercyRng::seeded(seed);
+    let mut key: Vec<u8> = (b'A'..=b'Z').collect();
+    for index in (1..key.len()).rev() {
+        key.swap(index, rng.below(index + 1));
+    }
+    key
+}
+
+// Substitutes letters keeping their case; everything else passes through
+fn encipher(text: &str, key: &[u8]) -> String {
+    text.chars().map(|c| match c {
+        'A'..='Z' => key[(c as u8 - b'A') as usize] as char,
+        'a'..='z' => key[(c as u8 - b'a') as usize].to_ascii_lowercase() as char,
+        _ => c
+    }).collect()
+}
+
+#[test]
+fn substitution_solver_recovers_a_scrambled_plaintext() {
+    let key = scrambled_key(224);
+    let ciphertext = encipher(PLAINTEXT, &key);
+    assert_ne!(ciphertext, PLAINTEXT);
+
+    let options = SubstitutionOptions { seed: Some(42), ..SubstitutionOptions::default() };
+    let solution = mercy_substitution_solve(&ciphertext, &options).expect("solves");
+    assert_eq!(solution.plaintext, PLAINTEXT, "key {} after {} iterations", solution.key, solution.iterations);
+    assert_eq!(solution.seed, 42);
+
+    // Every letter of the text maps back; letters the text never uses (J, Q, X, Z here) can land anywhere
+    for (plain, cipher) in (b'A'..=b'Z').zip(&key) {
+        if PLAINTEXT.to_ascii_uppercase().contains(plain as char) {
+            assert_eq!(solution.mapping.get(&(*cipher as char)), Some(&(plain as char)));
+            assert_eq!(solution.key.as_bytes()[(plain - b'A') as usize], *cipher);
+        }
+    }
+
+    // On an iteration budget, the same seed walks the same path
+    let short = SubstitutionOptions { iterations: 100_000, time_limit: Duration::from_secs(600), seed: Some(7) };
+    let first = mercy_substitution_solve(&ciphertext, &short).expect("solves");
+    let again = mercy_substitution_solve(&ciphertext, &short).expect("solves");
+    assert_eq!((&first.key, first.iterations, first.restarts, first.score), (&again.key, again.iterations, again.restarts, again.score));
+
+    let output = mercy_solve("substitution", &format!("{} iterations=100000 seed=7", ciphertext));
+    assert!(output.contains(&first.key) && output.contains(&first.plaintext), "{}", output);
+}
+
+#[test]
+fn caesar_solver_finds_the_shift_of_long_and_short_texts() {
+    for shift in [1, 7, 13, 25] {
+        let solution = mercy_caesar_solve(&mercy_encode("rot", &format!("{},{}", shift, PLAINTEXT))).expect("solves");
+        assert_eq!((solution.shift, solution.plaintext.as_str()), (shift, PLAINTEXT));
+        assert_eq!(solution.runners_up.len(), 3);
+    }
+
+    // Rot16 has the closer letter counts here; the common bigrams of "Hello World" outweigh them
+    let solution = mercy_caesar_solve("Uryyb Jbeyq").expect("solves");
+    assert_eq!((solution.shift, solution.plaintext.as_str()), (13, "Hello World"));
+    assert!(solution.runners_up.iter().any(|candidate| candidate.shift == 16 && candidate.chi_squared < solution.chi_squared), "{:?}", solution);
+
+    assert_eq!(mercy_caesar_solve("1234 !?").expect_err("no letters"), "No letters to analyse");
+}
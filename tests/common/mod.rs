@@ -0,0 +1,95 @@
+/*
+    Shared harness for the integration tests
+
+    Re-exports the local mock services of the `testing` feature, which the dev-dependency of
+    mercy on itself turns on for every test, and serializes the tests that point mercy's
+    service endpoints somewhere: the endpoints are process-wide and the tests of one file run
+    on parallel threads.
+*/
+
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    ops::Deref,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+    time::Duration
+};
+
+// Not every test file uses every mock
+#[allow(unused_imports)]
+pub use mercy::{
+    MOCK_BANNER,
+    MOCK_CRTSH,
+    MOCK_CRTSH_DOMAIN,
+    MOCK_CYMRU,
+    MOCK_EXTERNAL_IP,
+    MOCK_INQUEST_EMPTY,
+    MOCK_INQUEST_HASH,
+    MOCK_INQUEST_MALICIOUS,
+    MOCK_MALICIOUS_DOMAIN,
+    MOCK_MALICIOUS_HASH,
+    MOCK_PWNED_PASSWORD,
+    MOCK_PWNED_RANGE,
+    MOCK_WHOIS,
+    MockDns,
+    MockHttp,
+    MockRequest,
+    MockRoute,
+    MockServices,
+    MockTcp
+};
+
+use mercy::{MercyConfig, ServiceEndpoints, service_endpoints, set_service_endpoints};
+
+static ENDPOINTS: Mutex<()> = Mutex::new(());
+
+/// The canned mock services, wired into mercy's lookups until dropped
+pub struct Mocks {
+    // Dropped first, which restores the default endpoints before the lock is released
+    services: MockServices,
+    _lock: MutexGuard<'static, ()>
+}
+
+impl Deref for Mocks {
+    type Target = MockServices;
+
+    fn deref(&self) -> &MockServices {
+        &self.services
+    }
+}
+
+/// Starts `MockServices` once no other test of the file is using the endpoints
+pub fn mocks() -> Mocks {
+    let lock = lock_endpoints();
+    let services = MockServices::start().expect("mock services start");
+    Mocks { services, _lock: lock }
+}
+
+/// Holds the endpoints for a test that sets its own
+pub fn lock_endpoints() -> MutexGuard<'static, ()> {
+    ENDPOINTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs `call` with the endpoints `change` makes of the current ones, then puts them back
+pub fn with_endpoints<T>(change: impl FnOnce(ServiceEndpoints) -> ServiceEndpoints, call: impl FnOnce() -> T) -> T {
+    let previous = service_endpoints();
+    set_service_endpoints(Some(change(previous.clone())));
+    let result = call();
+    set_service_endpoints(Some(previous));
+    result
+}
+
+/// A config that retries `retries` times with a short backoff, so failing lookups end quickly
+pub fn quick_config(retries: u32) -> MercyConfig {
+    MercyConfig::builder().retries(retries).retry_backoff(Duration::from_millis(20)).build().expect("valid config")
+}
+
+/// An empty directory of its own for the fixtures of test `name`
+pub fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mercy-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("fixture directory");
+    dir
+}
@@ -0,0 +1,50 @@
+// Defanging and refanging URLs, domains, IPv4 addresses and email addresses, each way and back
+
+use mercy::mercy_extra;
+
+// (live, defanged)
+const INDICATORS: &[(&str, &str)] = &[
+    ("https://evil.com/a.b?x=1.2", "hxxps://evil[.]com/a[.]b?x=1[.]2"),
+    ("http://login.evil.example:8080/", "hxxp://login[.]evil[.]example:8080/"),
+    ("ftp://files.evil.example/drop.exe", "fxp://files[.]evil[.]example/drop[.]exe"),
+    ("evil.co.uk", "evil[.]co[.]uk"),
+    ("xn--80ak6aa92e.com", "xn--80ak6aa92e[.]com"),
+    ("192.168.1.10", "192[.]168[.]1[.]10"),
+    ("10.0.0.1:4444", "10[.]0[.]0[.]1:4444"),
+    ("user.name@evil.com", "user[.]name[@]evil[.]com"),
+    ("Phish from billing@pay.evil.com, see https://pay.evil.com/inv or 203.0.113.9", "Phish from billing[@]pay[.]evil[.]com, see hxxps://pay[.]evil[.]com/inv or 203[.]0[.]113[.]9")
+];
+
+#[test]
+fn indicators_defang_and_refang() {
+    for (live, defanged) in INDICATORS {
+        assert_eq!(mercy_extra("defang", live), *defanged);
+        assert_eq!(mercy_extra("refang", defanged), *live);
+
+        // Defanging twice changes nothing, and live text is left as it is by refang
+        assert_eq!(mercy_extra("defang", defanged), *defanged);
+        assert_eq!(mercy_extra("refang", live), *live);
+    }
+
+    // Schemes in any case are defanged
+    assert_eq!(mercy_extra("defang", "HTTPS://Evil.COM"), "hxxps://Evil[.]COM");
+}
+
+#[test]
+fn refang_reads_the_defanging_styles_seen_in_reports() {
+    let cases = [
+        ("HXXPS://EVIL[.]COM/x", "https://EVIL.COM/x"),
+        ("hxxps[://]evil(.)com", "https://evil.com"),
+        ("hxtp://evil{.}example", "http://evil.example"),
+        ("hxxp[:]//evil[dot]example", "http://evil.example"),
+        ("evil(dot)co{dot}uk", "evil.co.uk"),
+        ("192(.)168[.]1{.}10", "192.168.1.10"),
+        ("user[at]evil[.]com", "user@evil.com"),
+        ("user(@)evil(DOT)com", "user@evil.com"),
+        ("admin{at}evil{.}com", "admin@evil.com"),
+        ("fxp://files[.]evil[.]example[/]drop", "ftp://files.evil.example/drop")
+    ];
+    for (defanged, live) in cases {
+        assert_eq!(mercy_extra("refang", defanged), live, "{}", defanged);
+    }
+}
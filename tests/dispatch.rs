@@ -0,0 +1,78 @@
+// Every call the registry lists reaches code in its dispatcher
+
+mod common;
+
+use common::*;
+use mercy::{
+    MercyError, mercy_canary, mercy_capabilities, mercy_decode_result, mercy_discover, mercy_encode_result, mercy_extra, mercy_forensics, mercy_generate, mercy_hash_compare_result, mercy_hash_result, mercy_hex_result,
+    mercy_hmac_result, mercy_malicious, mercy_methods, mercy_parse, mercy_solve
+};
+
+const MODULES: &[&str] = &["decode", "encode", "hash", "hash_compare", "hmac", "hex", "malicious", "extra", "discover", "forensics", "parse", "solve", "generate", "canary"];
+
+// Not a host, a path, a number or a key, so the calls that do real work turn it down at once
+const INPUT: &str = "!";
+
+// Whether a fallible dispatcher knew the call, whatever it made of the input
+fn known<T>(result: Result<T, MercyError>) -> bool {
+    !matches!(result, Err(MercyError::UnsupportedMethod { .. }))
+}
+
+// Whether the dispatcher ran code for the call rather than answering that it has none
+fn dispatches(module: &str, call: &str) -> bool {
+    match module {
+        "decode" => known(mercy_decode_result(call, INPUT)),
+        "encode" => known(mercy_encode_result(call, INPUT)),
+        "hash" => known(mercy_hash_result(call, INPUT)),
+        "hash_compare" => known(mercy_hash_compare_result(call, INPUT, INPUT)),
+        "hmac" => known(mercy_hmac_result(call, "key", INPUT)),
+        "hex" => known(mercy_hex_result(call, INPUT)),
+        "malicious" => mercy_malicious(call, INPUT) != "Unable to classify domain",
+        "extra" => mercy_extra(call, INPUT) != "Unable to provide the information you requested",
+        "discover" => mercy_discover(call, INPUT) != "Unable to run the discovery method requested",
+        "forensics" => mercy_forensics(call, INPUT) != "Unable to run the forensic method requested",
+        "parse" => mercy_parse(call, INPUT) != "Unable to parse the format requested",
+        "solve" => mercy_solve(call, INPUT) != "Unable to solve the cipher requested",
+        "generate" => mercy_generate(call, INPUT) != "Unable to generate the payloads requested",
+        "canary" => mercy_canary(call, INPUT) != "Unable to handle the canary request",
+        _ => panic!("no dispatcher for {}", module)
+    }
+}
+
+#[test]
+fn every_listed_method_dispatches() {
+    // The network calls go to the mocks, so none of them waits on the internet
+    let _mocks = mocks();
+
+    for module in MODULES {
+        let methods = mercy_methods(module);
+        assert!(!methods.is_empty(), "no calls listed for {}", module);
+
+        for method in methods {
+            assert!(dispatches(module, &method.name), "mercy_{} lists '{}' but answers that it is an unknown method", module, method.name);
+        }
+    }
+}
+
+#[test]
+fn unlisted_methods_do_not_dispatch() {
+    for module in MODULES {
+        assert!(!dispatches(module, "no_such_call"), "mercy_{} ran a call it does not list", module);
+    }
+
+    // A `_json` variant exists only where the registry says so
+    assert!(!dispatches("hex", "hex_dump_json"));
+    assert!(!dispatches("extra", "defang_json"));
+}
+
+#[test]
+fn calls_that_use_the_network_say_so() {
+    // A listener talks to remote clients as much as a banner grab talks to a server
+    let extra = mercy_methods("extra");
+    let network = |name: &str| extra.iter().find(|method| method.name == name).map(|method| method.network);
+    assert_eq!((network("listen"), network("banner_grab"), network("defang")), (Some(true), Some(true), Some(false)));
+
+    let capabilities = mercy_capabilities().capabilities;
+    let listen = capabilities.iter().find(|capability| (capability.dispatcher.as_str(), capability.call.as_str()) == ("mercy_extra", "listen")).expect("listen");
+    assert!(listen.network);
+}
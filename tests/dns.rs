@@ -0,0 +1,270 @@
+// DNS packets built by hand: the query encoder's bytes, a response with every record type, hostile compression and fuzzed input
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, UdpSocket},
+    thread,
+    time::{Duration, Instant}
+};
+
+use mercy::{DnsQuery, DnsRdata, DnsResponse, DnsType, EdnsInfo, MercyRng};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+// "example.com" as labels, and a pointer to it where the question puts it (offset 12)
+const EXAMPLE: &[u8] = b"\x07example\x03com\x00";
+const AT_EXAMPLE: [u8; 2] = [0xC0, 0x0C];
+
+fn record(name: &[u8], rtype: u16, ttl: u32, rdata: &[u8]) -> Vec<u8> {
+    let mut record = name.to_vec();
+    record.extend(rtype.to_be_bytes());
+    record.extend(1u16.to_be_bytes());
+    record.extend(ttl.to_be_bytes());
+    record.extend((rdata.len() as u16).to_be_bytes());
+    record.extend(rdata);
+    record
+}
+
+// A response to "example.com A" with the given flags and sections
+fn response(id: u16, flags: u16, answers: &[Vec<u8>], additionals: &[Vec<u8>]) -> Vec<u8> {
+    let mut packet = id.to_be_bytes().to_vec();
+    for value in [flags, 1, answers.len() as u16, 0, additionals.len() as u16] {
+        packet.extend(value.to_be_bytes());
+    }
+    packet.extend(EXAMPLE);
+    packet.extend([0, 1, 0, 1]);
+    packet.extend(answers.concat());
+    packet.extend(additionals.concat());
+    packet
+}
+
+// OPT pseudo-record: root name, payload size as class, extended rcode, version and DO bit as TTL
+fn opt(udp_size: u16, extended_rcode: u8, dnssec_ok: bool) -> Vec<u8> {
+    let mut opt = vec![0, 0, 41];
+    opt.extend(udp_size.to_be_bytes());
+    opt.extend([extended_rcode, 0, if dnssec_ok { 0x80 } else { 0 }, 0, 0, 0]);
+    opt
+}
+
+// Every record type the parser decodes, names compressed against the question and each other
+fn full_response() -> Vec<u8> {
+    let mx = [&[0, 10][..], b"\x04mail", &AT_EXAMPLE].concat();
+    let soa = [&b"\x03ns1"[..], &AT_EXAMPLE, b"\x0ahostmaster", &AT_EXAMPLE, &2024030101u32.to_be_bytes(), &7200u32.to_be_bytes(),
+        &3600u32.to_be_bytes(), &1_209_600u32.to_be_bytes(), &300u32.to_be_bytes()].concat();
+    let srv = [&[0, 5, 0, 20, 0x14, 0x95][..], b"\x03sip", &AT_EXAMPLE].concat();
+    let answers = [
+        record(&AT_EXAMPLE, 1, 300, &[93, 184, 216, 34]),
+        record(&AT_EXAMPLE, 28, 300, &[0x26, 0x06, 0x28, 0x00, 0x02, 0x20, 0, 1, 0x02, 0x48, 0x18, 0x93, 0x25, 0xC8, 0x19, 0x46]),
+        record(b"\x03www\xC0\x0C", 5, 60, &AT_EXAMPLE),
+        record(&AT_EXAMPLE, 15, 3600, &mx),
+        record(&AT_EXAMPLE, 16, 3600, b"\x0bv=spf1 -all\x08say \"hi\"\x00"),
+        record(&AT_EXAMPLE, 6, 3600, &soa),
+        record(b"\x04_sip\x04_tcp\xC0\x0C", 33, 3600, &srv),
+        record(&AT_EXAMPLE, 257, 3600, b"\x80\x05issueletsencrypt.org"),
+        record(&AT_EXAMPLE, 99, 3600, &[0xDE, 0xAD, 0xBE, 0xEF]),
+        record(b"\x03odd\x07a.b\\\x02\x00\xff\xC0\x0C", 12, 3600, &AT_EXAMPLE)
+    ];
+    // QR, AA, RD and RA
+    response(0xBEEF, 0x8580, &answers, &[opt(1232, 0, true)])
+}
+
+#[test]
+fn queries_encode_to_the_wire_format() {
+    let mut query = DnsQuery::new(" Example.COM. ", DnsType::Caa);
+    assert_eq!((query.name.as_str(), query.recursion_desired, query.edns), ("Example.COM", true, true));
+    query.id = 0x1234;
+
+    let mut expected = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1];
+    expected.extend(b"\x07Example\x03COM\x00");
+    expected.extend([0x01, 0x01, 0, 1]);
+    expected.extend([0, 0, 41, 0x04, 0xD0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(query.to_bytes().expect("query"), expected);
+
+    // No recursion and no OPT record: the header says so and the packet ends after the question
+    query.recursion_desired = false;
+    query.edns = false;
+    let bytes = query.to_bytes().expect("query");
+    assert_eq!((&bytes[2..4], &bytes[10..12], bytes.len()), (&[0, 0][..], &[0, 0][..], 12 + 13 + 4));
+
+    // Ids differ from one query to the next
+    let ids: Vec<u16> = (0..8).map(|_| DnsQuery::new("example.com", DnsType::A).id).collect();
+    assert!(ids.iter().any(|id| *id != ids[0]), "{:?}", ids);
+
+    let long_label = "a".repeat(64);
+    let long_name = ["b".repeat(63).as_str(); 4].join(".");
+    for (name, error) in [("double..dot", "Empty label"), (long_label.as_str(), "exceeds 63 bytes"), (long_name.as_str(), "exceeds 255 bytes")] {
+        let message = DnsQuery::new(name, DnsType::A).to_bytes().expect_err(name);
+        assert!(message.contains(error), "{}: {}", name, message);
+    }
+    assert_eq!(DnsQuery::new(".", DnsType::Ns).to_bytes().expect("root")[12..], [0, 0, 2, 0, 1, 0, 0, 41, 0x04, 0xD0, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn every_record_type_parses_and_renders() {
+    let response = DnsResponse::parse(&full_response()).expect("response");
+    assert_eq!((response.id, response.opcode, response.rcode_name().as_str()), (0xBEEF, 0, "NOERROR"));
+    assert_eq!((response.authoritative, response.truncated, response.recursion_desired, response.recursion_available), (true, false, true, true));
+    assert_eq!((response.questions[0].name.as_str(), response.questions[0].qtype, response.questions[0].class), ("example.com", DnsType::A, 1));
+
+    // The OPT record becomes `edns` and leaves the additional section
+    assert_eq!(response.edns, Some(EdnsInfo { udp_payload_size: 1232, extended_rcode: 0, version: 0, dnssec_ok: true }));
+    assert!(response.additionals.is_empty() && response.authorities.is_empty());
+
+    let rendered: Vec<String> = response.answers.iter().map(|record| record.to_string()).collect();
+    assert_eq!(rendered, [
+        "example.com 300 A 93.184.216.34",
+        "example.com 300 AAAA 2606:2800:220:1:248:1893:25c8:1946",
+        "www.example.com 60 CNAME example.com",
+        "example.com 3600 MX 10 mail.example.com",
+        "example.com 3600 TXT \"v=spf1 -all\" \"say \\\"hi\\\"\" \"\"",
+        "example.com 3600 SOA ns1.example.com hostmaster.example.com 2024030101 7200 3600 1209600 300",
+        "_sip._tcp.example.com 3600 SRV 5 20 5269 sip.example.com",
+        "example.com 3600 CAA 128 issue \"letsencrypt.org\"",
+        "example.com 3600 TYPE99 \\# 4 deadbeef",
+        "odd.a\\.b\\\\\\002\\000\\255.example.com 3600 PTR example.com"
+    ]);
+    assert_eq!(response.answers[4].rdata, DnsRdata::Txt(vec!["v=spf1 -all".to_string(), "say \"hi\"".to_string(), String::new()]));
+
+    let text = response.to_string();
+    assert!(text.starts_with("Status: NOERROR (id 48879)\nFlags: aa=true tc=false rd=true ra=true\nEDNS: version 0, udp size 1232, do=true\nAnswer: example.com 300 A"), "{}", text);
+}
+
+#[test]
+fn header_flags_and_extended_rcodes() {
+    // NXDOMAIN from a recursive resolver that is not authoritative, no EDNS
+    let nxdomain = DnsResponse::parse(&response(1, 0x8183, &[], &[])).expect("nxdomain");
+    assert_eq!((nxdomain.rcode, nxdomain.rcode_name().as_str(), nxdomain.authoritative, nxdomain.recursion_available), (3, "NXDOMAIN", false, true));
+    assert_eq!(nxdomain.edns, None);
+    assert!(nxdomain.to_string().contains("EDNS: not present"));
+
+    // An extended rcode of 1 lifts the header's 0 to BADVERS (16); the OPT class is the payload size
+    let badvers = DnsResponse::parse(&response(2, 0x8000, &[], &[opt(512, 1, false)])).expect("badvers");
+    assert_eq!((badvers.rcode, badvers.rcode_name().as_str()), (16, "BADVERS"));
+    assert_eq!(badvers.edns, Some(EdnsInfo { udp_payload_size: 512, extended_rcode: 1, version: 0, dnssec_ok: false }));
+    assert_eq!(DnsResponse::parse(&response(3, 0x8000 | 0x7800 | 0x0200 | 9, &[], &[])).map(|r| (r.opcode, r.truncated, r.rcode_name())), Ok((15, true, "RCODE9".to_string())));
+}
+
+#[test]
+fn hostile_compression_is_refused() {
+    let answer = |name: &[u8]| response(4, 0x8180, &[record(name, 1, 60, &[192, 0, 2, 1])], &[]);
+    let question_end = 12 + EXAMPLE.len() + 4;
+
+    // A pointer to itself, one jumping forward, and two pointing at each other
+    let own = [0xC0 | (question_end >> 8) as u8, question_end as u8];
+    let ahead = [0xC0, (question_end + 4) as u8];
+    let mut pair = response(5, 0x8180, &[], &[]);
+    pair.truncate(12);
+    pair.extend([0xC0, 14, 0xC0, 12, 0, 1, 0, 1]);
+    for (label, packet) in [("itself", answer(&own)), ("forward", answer(&ahead)), ("pair", pair)] {
+        let error = DnsResponse::parse(&packet).expect_err(label);
+        assert!(error.contains("Forward or looping compression pointer"), "{}: {}", label, error);
+    }
+
+    // Pointers that only ever go backwards still end after 32 jumps
+    let error = DnsResponse::parse(&pointer_chain(33)).expect_err("long chain");
+    assert!(error.contains("Too many compression pointers"), "{}", error);
+    assert_eq!(DnsResponse::parse(&pointer_chain(32)).expect("32 jumps").answers[1].name, ".");
+
+    // A question name whose labels add up past 255 bytes
+    let mut long = response(7, 0x8180, &[], &[]);
+    long.truncate(12);
+    for _ in 0..5 {
+        long.push(63);
+        long.extend([b'x'; 63]);
+    }
+    long.extend([0, 0, 1, 0, 1]);
+    let error = DnsResponse::parse(&long).expect_err("long name");
+    assert!(error.contains("Name exceeds 255 bytes"), "{}", error);
+
+    let error = DnsResponse::parse(&answer(&[0x80, 0])).expect_err("label type");
+    assert!(error.contains("Unsupported label type 0x80"), "{}", error);
+}
+
+// An answer whose name takes `jumps` pointers to resolve: the first record's opaque data holds the
+// root name and a run of pointers, each to the one before it
+fn pointer_chain(jumps: usize) -> Vec<u8> {
+    let hops_start = 12 + EXAMPLE.len() + 4 + AT_EXAMPLE.len() + 10;
+    let mut hops = vec![0u8];
+    for hop in 0..jumps - 1 {
+        let target = if hop == 0 { hops_start } else { hops_start + 1 + (hop - 1) * 2 };
+        hops.extend([0xC0 | (target >> 8) as u8, target as u8]);
+    }
+    let last = hops_start + hops.len() - 2;
+    response(8, 0x8180, &[record(&AT_EXAMPLE, 99, 60, &hops), record(&[0xC0 | (last >> 8) as u8, last as u8], 1, 60, &[192, 0, 2, 1])], &[])
+}
+
+#[test]
+fn fuzzed_packets_never_panic() {
+    let valid = full_response();
+
+    // Every cut short of the whole message is an error, never a panic or a partial answer
+    for length in 0..valid.len() {
+        assert!(DnsResponse::parse(&valid[..length]).is_err(), "prefix of {} bytes", length);
+    }
+
+    // Random bytes, and the valid response with a few bytes changed; whatever parses renders and parses the same again
+    let mut rng = MercyRng::seeded(204);
+    let started = Instant::now();
+    let mut parsed = 0;
+    for round in 0..20_000 {
+        let packet = if round % 4 == 0 {
+            let mut noise = vec![0u8; rng.below(512)];
+            rng.fill(&mut noise);
+            noise
+        } else {
+            let mut mutated = valid.clone();
+            for _ in 0..1 + rng.below(4) {
+                let position = rng.below(mutated.len());
+                mutated[position] = rng.next_u64() as u8;
+            }
+            mutated
+        };
+
+        if let Ok(response) = DnsResponse::parse(&packet) {
+            parsed += 1;
+            assert!(!response.to_string().is_empty());
+            assert_eq!(DnsResponse::parse(&packet), Ok(response));
+        }
+    }
+    assert!(parsed > 1000, "only {} of the mutated packets parsed", parsed);
+    assert!(started.elapsed() < Duration::from_secs(30), "{:?}", started.elapsed());
+}
+
+// A resolver whose UDP answer is truncated, so the query is asked again over TCP on the same port
+#[test]
+fn truncated_udp_answers_are_retried_over_tcp() {
+    let (udp, tcp) = (0..10).find_map(|_| {
+        let udp = UdpSocket::bind("127.0.0.1:0").ok()?;
+        let tcp = TcpListener::bind(udp.local_addr().ok()?).ok()?;
+        Some((udp, tcp))
+    }).expect("a UDP and TCP port pair");
+    let server = udp.local_addr().expect("address");
+
+    thread::spawn(move || {
+        let mut query = [0u8; 512];
+        let (_, client) = udp.recv_from(&mut query).expect("query");
+        let id = u16::from_be_bytes([query[0], query[1]]);
+        // A stray datagram for some other query first, which is ignored
+        let _ = udp.send_to(&response(id.wrapping_add(1), 0x8180, &[], &[]), client);
+        let _ = udp.send_to(&response(id, 0x8380, &[], &[]), client);
+
+        let (mut stream, _) = tcp.accept().expect("connection");
+        let mut length = [0u8; 2];
+        stream.read_exact(&mut length).expect("length");
+        let mut query = vec![0u8; usize::from(u16::from_be_bytes(length))];
+        stream.read_exact(&mut query).expect("query");
+        let answer = response(u16::from_be_bytes([query[0], query[1]]), 0x8180, &[record(&AT_EXAMPLE, 1, 60, &[192, 0, 2, 7])], &[]);
+        let _ = stream.write_all(&[&(answer.len() as u16).to_be_bytes()[..], &answer].concat());
+    });
+
+    let query = DnsQuery::new("example.com", DnsType::A);
+    let response = query.send(server, TIMEOUT).expect("answer");
+    assert_eq!((response.id, response.truncated), (query.id, false));
+    assert_eq!(response.answers[0].rdata, DnsRdata::A([192, 0, 2, 7].into()));
+
+    // Nothing answers at all: the wait ends at the timeout
+    let silent = UdpSocket::bind("127.0.0.1:0").expect("socket");
+    let started = Instant::now();
+    let error = DnsQuery::new("example.com", DnsType::A).send(silent.local_addr().expect("address"), Duration::from_millis(200)).expect_err("silence");
+    assert!(error.ends_with("timed out") && started.elapsed() < TIMEOUT, "{} after {:?}", error, started.elapsed());
+}
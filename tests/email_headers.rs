@@ -0,0 +1,131 @@
+// A relayed phishing message's headers: the Received chain in order, the origin, the authentication results and what gets flagged
+
+use mercy::{mercy_email_headers, mercy_extra};
+
+// Four hops from a compromised webmail account through a bulk relay to the recipient's
+// gateway; folded lines, CRLF endings, encoded words and a body that looks like a header
+const PHISHING: &str = "\
+Received: from gw1.corp.example (10.20.0.5) by exch02.corp.example (10.20.0.12) with\r
+ Microsoft SMTP Server (version=TLS1_2, cipher=TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384) id\r
+ 15.2.1258.12; Thu, 29 Feb 2024 14:02:11 +0000\r
+Received: from relay7.mailblast.example (relay7.mailblast.example [198.51.100.23])\r
+\tby gw1.corp.example (Postfix) with ESMTPS id 4Tq9Xk1Zz3z9rxL\r
+\tfor <j.doe@corp.example>; Thu, 29 Feb 2024 14:02:09 +0000 (UTC)\r
+Authentication-Results: gw1.corp.example;\r
+\tspf=softfail (domain of transitioning bounce-8812@mailblast.example does not designate 198.51.100.23 as permitted sender) smtp.mailfrom=bounce-8812@mailblast.example;\r
+\tdkim=fail (signature did not verify) header.d=microsoft.com header.s=selector1;\r
+\tdmarc=fail (p=REJECT sp=REJECT dis=NONE) header.from=microsoft.com\r
+Received-SPF: SoftFail (gw1.corp.example: domain of bounce-8812@mailblast.example does not designate 198.51.100.23 as permitted sender) client-ip=198.51.100.23;\r
+Return-Path: <bounce-8812@mailblast.example>\r
+Received: from webmail.hacked-bakery.example (unknown [203.0.113.77])\r
+\tby relay7.mailblast.example (Postfix) with ESMTPSA id 7C1D0E;\r
+\tThu, 29 Feb 2024 15:01:58 +0100\r
+Received: from [192.168.1.20] (localhost [127.0.0.1])\r
+\tby webmail.hacked-bakery.example with HTTP; Thu, 29 Feb 2024 15:01:55 +0100\r
+X-Originating-IP: [192.0.2.200]\r
+From: \"no-reply@microsoft.com\" <account-security@hacked-bakery.example>\r
+Reply-To: =?UTF-8?B?TWljcm9zb2Z0IFN1cHBvcnQ=?= <ms.recovery.team@freemail.example>\r
+To: j.doe@corp.example\r
+Subject: =?UTF-8?Q?Unusual_sign-in_activity_=E2=80=93?=\r
+ =?UTF-8?Q?_verify_your_account?=\r
+Date: Thu, 29 Feb 2024 15:01:54 +0100\r
+Message-ID: <20240229140154.4F1A@hacked-bakery.example>\r
+\r
+Received: from a.line.in.the.body (not a header [192.0.2.1])\r
+";
+
+const EXPECTED: &str = "\
+Message
+  Subject: Unusual sign-in activity \u{2013} verify your account
+  Date: Thu, 29 Feb 2024 15:01:54 +0100
+  Message-ID: <20240229140154.4F1A@hacked-bakery.example>
+
+Addresses
+  From: \"no-reply@microsoft.com\" <account-security@hacked-bakery.example>
+  Reply-To: \"Microsoft Support\" <ms.recovery.team@freemail.example>
+  Return-Path: <bounce-8812@mailblast.example>
+
+Received chain (origin first)
+  1. from [192.168.1.20] (localhost [127.0.0.1]) by webmail.hacked-bakery.example with HTTP at 2024-02-29T14:01:55Z
+  2. from webmail.hacked-bakery.example (unknown [203.0.113.77]) by relay7.mailblast.example with ESMTPSA at 2024-02-29T14:01:58Z (+3 s)
+  3. from relay7.mailblast.example (relay7.mailblast.example [198.51.100.23]) by gw1.corp.example with ESMTPS at 2024-02-29T14:02:09Z (+11 s)
+  4. from gw1.corp.example (10.20.0.5) by exch02.corp.example with Microsoft at 2024-02-29T14:02:11Z (+2 s)
+  Originating IP: 203.0.113.77
+
+Authentication
+  spf=softfail (domain of transitioning bounce-8812@mailblast.example does not designate 198.51.100.23 as permitted sender) smtp.mailfrom=bounce-8812@mailblast.example
+  dkim=fail (signature did not verify) header.d=microsoft.com header.s=selector1
+  dmarc=fail (p=REJECT sp=REJECT dis=NONE) header.from=microsoft.com
+  spf=softfail (gw1.corp.example: domain of bounce-8812@mailblast.example does not designate 198.51.100.23 as permitted sender) client-ip=198.51.100.23
+
+Flags
+  - Reply-To domain freemail.example differs from From domain hacked-bakery.example
+  - Return-Path domain mailblast.example differs from From domain hacked-bakery.example
+  - From display name shows no-reply@microsoft.com but the address is account-security@hacked-bakery.example
+  - SPF softfail at gw1.corp.example
+  - DKIM fail at gw1.corp.example
+  - DMARC fail at gw1.corp.example";
+
+#[test]
+fn the_phishing_message_is_read_in_order() {
+    assert_eq!(mercy_extra("email_headers", PHISHING), EXPECTED);
+
+    // Origin first; the loopback first hop is passed over for the origin, and X-Originating-IP is only a fallback
+    let report = mercy_email_headers(PHISHING).expect("headers");
+    let hops: Vec<_> = report.received.iter().map(|hop| (hop.hop, hop.ip.as_deref(), hop.by.as_deref(), hop.delay)).collect();
+    assert_eq!(hops, vec![
+        (1, Some("127.0.0.1"), Some("webmail.hacked-bakery.example"), None),
+        (2, Some("203.0.113.77"), Some("relay7.mailblast.example"), Some(3)),
+        (3, Some("198.51.100.23"), Some("gw1.corp.example"), Some(11)),
+        (4, Some("10.20.0.5"), Some("exch02.corp.example"), Some(2))
+    ]);
+    assert_eq!(report.originating_ip.as_deref(), Some("203.0.113.77"));
+
+    // Every header up to the blank line, duplicates and folded ones whole, the body left out
+    let names: Vec<&str> = report.headers.iter().map(|header| header.name.as_str()).collect();
+    assert_eq!(names, vec![
+        "Received", "Received", "Authentication-Results", "Received-SPF", "Return-Path", "Received", "Received",
+        "X-Originating-IP", "From", "Reply-To", "To", "Subject", "Date", "Message-ID"
+    ]);
+    assert_eq!(report.headers[0].value, "from gw1.corp.example (10.20.0.5) by exch02.corp.example (10.20.0.12) with Microsoft SMTP Server (version=TLS1_2, cipher=TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384) id 15.2.1258.12; Thu, 29 Feb 2024 14:02:11 +0000");
+    assert_eq!(report.skipped_lines, 0);
+    let authentication: Vec<(&str, &str, Option<&str>)> = report.authentication.iter().map(|result| (result.method.as_str(), result.result.as_str(), result.server.as_deref())).collect();
+    assert_eq!(authentication, vec![("spf", "softfail", Some("gw1.corp.example")), ("dkim", "fail", Some("gw1.corp.example")), ("dmarc", "fail", Some("gw1.corp.example")), ("spf", "softfail", None)]);
+
+    // The same in JSON
+    let json: serde_json::Value = serde_json::from_str(&mercy_extra("email_headers_json", PHISHING)).expect("JSON");
+    assert_eq!(json["originating_ip"], "203.0.113.77");
+    assert_eq!(json["received"][1]["timestamp"], "2024-02-29T14:01:58Z");
+    assert_eq!(json["addresses"][1]["display_name"], "Microsoft Support");
+    assert_eq!(json["flags"].as_array().map(Vec::len), Some(6));
+}
+
+#[test]
+fn forged_and_missing_parts_are_flagged() {
+    // A hop claiming a time well before the one it came from, and only private addresses in the chain
+    let forged = "\
+Received: from relay.example (relay.example [10.0.0.7]) by mx.example with ESMTP; Thu, 29 Feb 2024 10:00:00 +0000
+Received: from sender.example (sender.example [192.168.5.5]) by relay.example with SMTP; Thu, 29 Feb 2024 12:00:00 +0000
+X-Originating-IP: [192.0.2.200]
+From: Alice <alice@example.com>
+Reply-To: alice@mail.example.com
+Authentication-Results: mx.example; spf=pass smtp.mailfrom=example.com
+";
+    let report = mercy_email_headers(forged).expect("headers");
+    assert_eq!(report.received[1].delay, Some(-7200));
+    assert_eq!(report.originating_ip.as_deref(), Some("192.0.2.200"));
+    assert_eq!(report.flags, vec!["Received hop 2 is 7200 s earlier than the hop before it; a forged or misdated header", "No DMARC result"]);
+
+    // Nothing relayed, nothing authenticated, and two From headers
+    let bare = "From: a@one.example\nFrom: b@two.example\nSubject: hi\n";
+    let report = mercy_email_headers(bare).expect("headers");
+    assert_eq!(report.flags, vec![
+        "2 From headers; clients differ in which they show",
+        "No Received headers; the message was not relayed or they were removed",
+        "No Authentication-Results or Received-SPF; SPF, DKIM and DMARC were not recorded"
+    ]);
+    assert_eq!(report.originating_ip, None);
+
+    assert!(mercy_email_headers("just some text\nwith no headers").is_err());
+    assert!(mercy_extra("email_headers", "").contains("No email headers found"));
+}
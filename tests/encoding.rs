@@ -0,0 +1,235 @@
+// Round trips through the encoders and decoders, with known answers and the input forms each decoder accepts
+
+use mercy::{ascii85_decode, ascii85_encode, base58_decode, base58_encode, base64_detect, mercy_decode, mercy_decode_bytes, mercy_encode, mercy_encode_bytes, morse_decode, morse_encode, nato_decode, nato_encode, z85_decode, z85_encode};
+
+const PANGRAMS: &[&str] = &["THE QUICK BROWN FOX JUMPS OVER THE LAZY DOG", "PACK MY BOX WITH FIVE DOZEN LIQUOR JUGS", "SPHINX OF BLACK QUARTZ, JUDGE MY VOW."];
+
+const BYTE_CALLS: &[&str] = &["base64", "base64_url", "hex", "base32", "base58", "base85", "z85", "url", "url_form"];
+
+#[test]
+fn every_byte_value_round_trips() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    for call in BYTE_CALLS {
+        let encoded = mercy_encode_bytes(call, &bytes);
+        assert!(encoded.is_ascii(), "{}: {}", call, encoded);
+        assert_eq!(mercy_decode_bytes(call, &encoded).expect("decodes"), bytes, "{}", call);
+
+        // Shellcode-like data with NULs at both ends
+        let shellcode = [0x00, 0x31, 0xc0, 0x50, 0x68, 0x2f, 0x2f, 0x73, 0x68, 0xff, 0xfe, 0x00];
+        assert_eq!(mercy_decode_bytes(call, &mercy_encode_bytes(call, &shellcode)).expect("decodes"), shellcode, "{}", call);
+    }
+
+    // The text calls give the same bytes, with what is not UTF-8 replaced
+    let encoded = mercy_encode_bytes("base64", &bytes);
+    assert_eq!(mercy_decode("base64", &encoded), String::from_utf8_lossy(&bytes));
+    assert_eq!(mercy_decode("hex", &mercy_encode("hex", "Grüße")), "Grüße");
+    assert_eq!(mercy_encode_bytes("base64", "Grüße".as_bytes()), mercy_encode("base64", "Grüße"));
+}
+
+#[test]
+fn morse_round_trips() {
+    // Every letter, digit and punctuation mark with a code
+    let punctuation = "0123456789 .,?'!/()&:;=+-_\"$@";
+    for text in PANGRAMS.iter().copied().chain([punctuation]) {
+        let code = morse_encode(text);
+        assert!(code.chars().all(|c| ".- /".contains(c)), "{}: {}", text, code);
+        assert_eq!(morse_decode(&code), text);
+        assert_eq!(mercy_decode("morse", &mercy_encode("morse", text)), text);
+    }
+    assert_eq!(morse_encode("sos Help"), "... --- ... / .... . .-.. .--.");
+
+    // Any whitespace between symbols, typographic dots and dashes, runs of separators
+    assert_eq!(morse_decode("  ...\t---\n...   //  ·−·−·−  "), "SOS .");
+    assert_eq!(morse_decode("•••  ———  ∙∙∙ / .._ ..."), "SOS US");
+
+    // What has no code stays visible as "?" both ways
+    assert_eq!(morse_encode("caf\u{e9} #1"), "-.-. .- ..-. ? / ? .----");
+    assert_eq!(morse_decode("-.-. ........ .-"), "C?A");
+}
+
+#[test]
+fn nato_round_trips() {
+    for text in PANGRAMS {
+        assert_eq!(nato_decode(&nato_encode(text)), *text);
+        assert_eq!(mercy_decode("nato", &mercy_encode("nato", text)), *text);
+    }
+    let mixed = "ROOM 101/B, FLOOR 9!";
+    assert_eq!(nato_encode(mixed), "Romeo Oscar Oscar Mike / One Zero One Slash Bravo , / Foxtrot Lima Oscar Oscar Romeo / Nine !");
+    assert_eq!(nato_decode(&nato_encode(mixed)), mixed);
+
+    // Case and spacing do not matter, common other spellings are understood, unknown words are "?"
+    assert_eq!(nato_decode("  hOTEL\tECHO lima LIMA oscar  /  alpha JULIET xray niner tree fife "), "HELLO AJX935");
+    assert_eq!(nato_decode("Echo Banana X-Ray"), "E?X");
+    assert_eq!(nato_encode("mercy"), nato_encode("MERCY"));
+}
+
+#[test]
+fn base58_known_answers() {
+    // Bitcoin's genesis address (P2PKH) and a P2SH address: version byte, HASH160, checksum
+    let addresses = [
+        ("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", "0062e907b15cbf27d5425399ebf6f0fb50ebb88f18c29b7d93"),
+        ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", "05b472a266d0bd89c13706a4132ccfb16f7c3b9fcb81fcfc02")
+    ];
+    for (address, hex) in addresses {
+        let bytes = mercy_decode_bytes("hex", hex).expect("hex");
+        assert_eq!(base58_decode(address).expect("address"), bytes);
+        assert_eq!(base58_encode(&bytes), address);
+        // Not UTF-8, so the text call gives the bytes as hex
+        assert_eq!(mercy_decode("base58", address), format!("hex:{}", hex));
+    }
+
+    // Each leading zero byte is a '1', and a text payload comes back as text
+    assert_eq!(base58_encode(&[0, 0, 0, 0x28, 0x7f, 0xb4, 0xcd]), "111233QC4");
+    assert_eq!(base58_decode("111233QC4").expect("zeros"), [0, 0, 0, 0x28, 0x7f, 0xb4, 0xcd]);
+    assert_eq!((base58_encode(&[0]), base58_encode(&[])), ("1".to_string(), String::new()));
+    assert_eq!(mercy_encode("base58", "Hello World!"), "2NEpo7TZRRrLZSi2U");
+    assert_eq!(mercy_decode("base58", " 2NEpo7TZRRrLZSi2U\n"), "Hello World!");
+
+    // 0, O, I and l are left out of the alphabet so they cannot be misread
+    for (text, c, position) in [("1A1zP1eP5QGefi2DMPTfTL5SLmv7Div0Na", '0', 31), ("O1", 'O', 0), ("2NEpI", 'I', 4), ("l", 'l', 0)] {
+        assert_eq!(base58_decode(text).expect_err(text), format!("Invalid base58 character '{}' at position {}", c, position));
+    }
+}
+
+#[test]
+fn base85_and_z85_known_answers() {
+    // Adobe ASCII85, with or without its delimiters and across line breaks
+    let man = "<~9jqo^BlbD-BleB1DJ+*+F(f,q~>";
+    assert_eq!(ascii85_encode(b"Man is distinguished"), man);
+    for text in [man, "9jqo^BlbD-BleB1DJ+*+F(f,q", "<~9jqo^BlbD-\nBleB1DJ+*+\r\nF(f,q~>"] {
+        assert_eq!(ascii85_decode(text).expect("ascii85"), b"Man is distinguished");
+    }
+    assert_eq!(mercy_decode("base85", man), "Man is distinguished");
+
+    // "z" for a whole group of zeros, and a short last group
+    let zeros = b"\0\0\0\0ab\0\0\0\0";
+    assert_eq!(ascii85_encode(zeros), "<~z@:B3:!!!~>");
+    assert_eq!(ascii85_decode("<~z@:B3:!!!~>").expect("zeros"), zeros);
+    assert_eq!(mercy_decode("base85", &ascii85_encode(&[0xc3, 0x28])), "hex:c328");
+    assert_eq!(ascii85_decode("F*2M7/c").expect("partial"), b"sure.");
+    assert_eq!(ascii85_decode("9jqvo").expect_err("v"), "Invalid ASCII85 character 'v' at position 3");
+    assert!(ascii85_decode("<~9jqo^B~>").expect_err("single").contains("single character"));
+    assert!(ascii85_decode("9jzqo").expect_err("z").contains("'z' inside a group"));
+
+    // The ZeroMQ specification's vector
+    let hello = [0x86, 0x4f, 0xd2, 0x6f, 0xb5, 0x59, 0xf7, 0x5b];
+    assert_eq!(z85_encode(&hello).expect("z85"), "HelloWorld");
+    assert_eq!(z85_decode("HelloWorld").expect("z85"), hello);
+    assert_eq!(mercy_decode("z85", "HelloWorld"), "hex:864fd26fb559f75b");
+    assert_eq!(z85_encode(b"abc").expect_err("length"), "Z85 encodes whole 4-byte groups; 3 bytes is not a multiple of 4");
+    assert_eq!(z85_decode("Hello~").expect_err("length"), "Z85 input is 6 characters, not a multiple of 5");
+    assert_eq!(z85_decode("Hell\"").expect_err("quote"), "Invalid Z85 character '\"' at position 4");
+}
+
+#[test]
+fn base58_base85_and_z85_round_trip() {
+    let samples: [&[u8]; 6] = [b"", b"\0", b"\0\0\x01", b"exfil.example:4444", &[0xff; 33], b"\0\0\0\0\0\0\0\0trailing\0\0"];
+    for sample in samples {
+        assert_eq!(base58_decode(&base58_encode(sample)).expect("base58"), sample, "{:?}", sample);
+        assert_eq!(ascii85_decode(&ascii85_encode(sample)).expect("ascii85"), sample, "{:?}", sample);
+        if sample.len() % 4 == 0 {
+            assert_eq!(z85_decode(&z85_encode(sample).expect("z85")).expect("z85"), sample, "{:?}", sample);
+        }
+    }
+    for text in PANGRAMS {
+        for call in ["base58", "base85"] {
+            assert_eq!(mercy_decode(call, &mercy_encode(call, text)), *text, "{}", call);
+        }
+    }
+}
+
+#[test]
+fn rot_round_trips_for_every_shift() {
+    let texts = ["Attack at dawn, 06:00! Bring 3 maps & a torch.", "caf\u{e9} na\u{ef}ve \u{1F600} \u{5f8c} [x] {y}", "Line one\nline, two\ttab"];
+    for shift in 1..=25 {
+        for text in PANGRAMS.iter().chain(texts.iter()) {
+            let encoded = mercy_encode("rot", &format!("{},{}", shift, text));
+            assert_eq!(mercy_decode("rot", &format!("{},{}", shift, encoded)), *text, "shift {}", shift);
+
+            // Letters move and keep their case; nothing else changes
+            for (before, after) in text.chars().zip(encoded.chars()) {
+                match before.is_ascii_alphabetic() {
+                    true => assert_eq!((after.is_ascii_uppercase(), (after.to_ascii_lowercase() as u8 + 26 - before.to_ascii_lowercase() as u8) % 26), (before.is_ascii_uppercase(), shift), "{:?}", text),
+                    false => assert_eq!(after, before, "{:?}", text)
+                }
+            }
+        }
+    }
+
+    // Known answers; a shift of 13 is ROT13, and only the first comma splits
+    assert_eq!(mercy_encode("rot", "3,Hello, World!"), "Khoor, Zruog!");
+    assert_eq!(mercy_decode("rot", "3,Khoor, Zruog!"), "Hello, World!");
+    assert_eq!(mercy_encode("rot", "25,abc XYZ"), "zab WXY");
+    assert_eq!(mercy_decode("rot", "13,Uryyb, Jbeyq!"), mercy_decode("rot13", "Uryyb, Jbeyq!"));
+    for input in ["0,abc", "26,abc", "-1,abc", "x,abc", "abc"] {
+        let output = mercy_decode("rot", input);
+        assert!(output.contains("Shift must be 1 to 25") || output.contains("Expected \"shift,text\""), "{}: {}", input, output);
+    }
+
+    // ROT47 covers '!' to '~' and is its own inverse
+    assert_eq!(mercy_encode("rot47", "Hello, World!"), "w6==@[ (@C=5P");
+    assert_eq!(mercy_decode("rot47", "w6==@[ (@C=5P"), "Hello, World!");
+    let printable: String = (b' '..=b'~').map(char::from).chain("\t\u{e9}\n".chars()).collect();
+    assert_eq!(mercy_decode("rot47", &mercy_encode("rot47", &printable)), printable);
+    assert_ne!(mercy_encode("rot47", &printable), printable);
+
+    // The brute force lists every shift and marks the one that reads as English
+    let brute = mercy_decode("rot_brute", &mercy_encode("rot", "7,Meet me at the old mill after midnight,\nbring the documents."));
+    let lines: Vec<&str> = brute.lines().collect();
+    assert_eq!(lines.len(), 25);
+    assert!(lines.iter().enumerate().all(|(index, line)| line.starts_with(&format!("rot{}: ", index + 1))), "{}", brute);
+    let marked: Vec<&&str> = lines.iter().filter(|line| line.ends_with("  <- most English-like")).collect();
+    assert_eq!(marked, vec![&"rot7: Meet me at the old mill after midnight,\\nbring the documents.  <- most English-like"]);
+
+    // Two short words have too few letters for their frequencies alone; the common bigrams settle it
+    let brute = mercy_decode("rot_brute", "Uryyb Jbeyq");
+    let marked: Vec<&str> = brute.lines().filter(|line| line.ends_with("  <- most English-like")).collect();
+    assert_eq!(marked, vec!["rot13: Hello World  <- most English-like"], "{}", brute);
+    assert!(mercy_decode("rot_brute", "1234 !?").contains("No letters to rotate"));
+}
+
+#[test]
+fn base64_wrapped_unpadded_and_url_safe() {
+    // MIME wraps at 76 characters, with CRLF or LF
+    let bytes: Vec<u8> = (0..200).collect();
+    let encoded = mercy_encode_bytes("base64", &bytes);
+    let lines: Vec<&str> = encoded.as_bytes().chunks(76).map(|line| std::str::from_utf8(line).expect("ASCII")).collect();
+    assert_eq!((lines.len(), lines[3].len()), (4, 40));
+    for wrapped in [lines.join("\r\n"), lines.join("\n") + "\n", format!("  {}\n", lines.join("\n \t"))] {
+        assert_eq!(mercy_decode_bytes("base64", &wrapped).expect("MIME"), bytes);
+    }
+    let mime = base64_detect(&lines.join("\r\n")).expect("detect");
+    assert_eq!((mime.alphabet.as_str(), mime.padding.as_str(), mime.line_length, mime.decoded_length), ("standard", "padded", Some(76), 200));
+    assert_eq!(mercy_decode("base64_detect", &lines.join("\n")), "Alphabet: standard\nPadding: padded\nLines: wrapped at 76 characters (MIME)\nDecodes to: 200 bytes");
+
+    // Padding is optional
+    for (text, decoded, padding) in [("TWFu", "Man", "none needed"), ("TWE=", "Ma", "padded"), ("TWE", "Ma", "unpadded"), ("TQ==", "M", "padded"), ("TQ", "M", "unpadded")] {
+        assert_eq!(mercy_decode("base64", text), decoded, "{}", text);
+        assert_eq!(mercy_decode("base64_url", text), decoded, "{}", text);
+        assert_eq!(base64_detect(text).expect(text).padding, padding, "{}", text);
+    }
+
+    // "-" and "_" belong to the URL-safe alphabet only, and a JWT's parts come unpadded
+    let url_safe = [0xfb, 0xff, 0xbf, 0xfe];
+    assert_eq!((mercy_encode_bytes("base64", &url_safe), mercy_encode_bytes("base64_url", &url_safe)), ("+/+//g==".to_string(), "-_-__g".to_string()));
+    assert_eq!(mercy_decode_bytes("base64_url", "-_-__g").expect("URL-safe"), url_safe);
+    assert_eq!(mercy_decode_bytes("base64_url", "-_-__g==").expect("padded URL-safe"), url_safe);
+    assert_eq!(mercy_decode("base64_url", "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9"), r#"{"alg":"HS256","typ":"JWT"}"#);
+    let detected = base64_detect("-_-__g").expect("detect");
+    assert_eq!((detected.alphabet.as_str(), detected.padding.as_str(), detected.line_length), ("URL-safe", "unpadded", None));
+    assert_eq!(base64_detect("TWFu").expect("detect").alphabet, "either");
+
+    // The wrong alphabet is named, and broken lengths and padding are errors
+    let errors = [
+        ("base64", "-_-__g", "Invalid base64 character '-' at position 0, as in URL-safe base64 (\"base64_url\")"),
+        ("base64_url", "+/+//g==", "Invalid URL-safe base64 character '+' at position 0, as in standard base64 (\"base64\")"),
+        ("base64", "TWFu!", "Invalid base64 character '!' at position 4"),
+        ("base64", "TWFuT", "Base64 input has an impossible length (5 symbols before padding)"),
+        ("base64", "TQ=", "Base64 padding leaves 3 symbols, not a multiple of 4"),
+        ("base64_detect", "ab+-", "mixes the standard (\"+\", \"/\") and URL-safe (\"-\", \"_\") alphabets")
+    ];
+    for (call, input, error) in errors {
+        let output = mercy_decode(call, input);
+        assert!(output.contains(error), "{} of {:?}: {}", call, input, output);
+    }
+}
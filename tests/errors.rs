@@ -0,0 +1,88 @@
+// Every MercyError variant: its message, its stable code and what failure text it is sorted into
+
+use std::io;
+
+use mercy::{ErrorContext, MercyError, mercy_hash_result};
+
+fn context() -> ErrorContext {
+    ErrorContext { operation: Some("mercy_extra/whois".to_string()), input: Some("example.com".to_string()) }
+}
+
+// One of each variant with the Display it gives and the code it carries, which never change within 1.x
+fn variants() -> Vec<(MercyError, &'static str, &'static str, u8)> {
+    let message = |text: &str| text.to_string();
+    vec![
+        (MercyError::Failed { message: message("Unable to classify"), context: context() }, "Unable to classify", "E_FAILED", 1),
+        (MercyError::InvalidInput { message: message("Invalid port 'x'"), context: context() }, "Invalid port 'x'", "E_INVALID_INPUT", 2),
+        (MercyError::InvalidEncoding { encoding: "hex".to_string(), message: message("Odd number of digits"), context: context() }, "Odd number of digits", "E_INVALID_ENCODING", 3),
+        (MercyError::UnsupportedMethod { dispatcher: "mercy_hash".to_string(), method: "sha384".to_string(), context: context() }, "mercy_hash does not support 'sha384'", "E_UNSUPPORTED_METHOD", 4),
+        (MercyError::Io { path: Some("/tmp/x".to_string()), source: io::Error::new(io::ErrorKind::NotFound, "not found"), context: context() }, "Unable to read /tmp/x: not found", "E_IO", 5),
+        (MercyError::Io { path: None, source: io::Error::other("disk full"), context: context() }, "disk full", "E_IO", 5),
+        (MercyError::Timeout { message: message("whois.example timed out"), context: context() }, "whois.example timed out", "E_TIMEOUT", 6),
+        (MercyError::Network { message: message("Unable to connect"), context: context() }, "Unable to connect", "E_NETWORK", 7),
+        (MercyError::ProviderQuota { message: message("HTTP 429"), context: context() }, "HTTP 429", "E_PROVIDER_QUOTA", 8)
+    ]
+}
+
+#[test]
+fn every_variant_has_its_message_and_code() {
+    let variants = variants();
+    for (error, display, code, number) in &variants {
+        assert_eq!(error.to_string(), *display);
+        assert_eq!((error.code(), error.numeric_code()), (*code, *number), "{}", display);
+        assert!(MercyError::CODES.contains(&(*code, *number)));
+        assert_eq!(error.context(), &context());
+
+        let json = error.to_json();
+        assert_eq!(json["code"], *code);
+        assert_eq!(json["numeric_code"], *number);
+        assert_eq!(json["message"], *display);
+        assert_eq!(json["context"]["operation"], "mercy_extra/whois");
+        assert_eq!(json["context"]["input"], "example.com");
+    }
+
+    // Every code is used, numbered from 1 in order
+    for (index, (code, number)) in MercyError::CODES.iter().enumerate() {
+        assert_eq!(usize::from(*number), index + 1);
+        assert!(variants.iter().any(|(error, ..)| error.code() == *code), "{} has no variant", code);
+    }
+
+    // Only I/O errors carry a source
+    for (error, ..) in &variants {
+        assert_eq!(std::error::Error::source(error).is_some(), error.code() == "E_IO");
+    }
+}
+
+#[test]
+fn failure_text_is_sorted_by_what_it_describes() {
+    let kinds = [
+        ("whois.example timed out after 5s", "E_TIMEOUT"),
+        ("HTTP 429 Too Many Requests", "E_PROVIDER_QUOTA"),
+        ("Daily quota exceeded", "E_PROVIDER_QUOTA"),
+        ("Unable to connect to 192.0.2.1:43", "E_NETWORK"),
+        ("Unable to open capture.pcap", "E_IO"),
+        ("Invalid CIDR '10.0.0.0/33'", "E_INVALID_INPUT"),
+        ("No WHOIS server for .invalid", "E_INVALID_INPUT"),
+        ("The server said no", "E_FAILED")
+    ];
+    for (message, code) in kinds {
+        let error = MercyError::from(message.to_string());
+        assert_eq!(error.code(), code, "{}", message);
+        assert_eq!(error.to_string(), message);
+        assert_eq!(error.context(), &ErrorContext::default());
+    }
+}
+
+#[test]
+fn dispatcher_errors_name_the_call_and_input() {
+    let error = mercy_hash_result("sha2_256_file", "/nonexistent/mercy-error-test").expect_err("no such file");
+    assert_eq!(error.code(), "E_IO");
+    assert_eq!(error.context().operation.as_deref(), Some("mercy_hash/sha2_256_file"));
+    assert_eq!(error.context().input.as_deref(), Some("/nonexistent/mercy-error-test"));
+
+    // Text being hashed is the caller's own and is withheld
+    let error = mercy_hash_result("sha384", "secret text").expect_err("no such call");
+    assert_eq!(error.code(), "E_UNSUPPORTED_METHOD");
+    assert_eq!(error.context().input.as_deref(), Some("<11 bytes withheld>"));
+    assert!(!error.to_json().to_string().contains("secret text"), "{}", error.to_json());
+}
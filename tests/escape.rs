@@ -0,0 +1,130 @@
+// Escaping for one context at a time: vectors per context, the other forms each decoder accepts, round trips, and URL and HTML entity layers
+
+use mercy::{mercy_decode, mercy_encode};
+
+// (call, text, escaped), each escaped form checked both ways
+const VECTORS: &[(&str, &str, &str)] = &[
+    // Breaking out of a value in any quote style, or none, takes one of the escaped characters
+    ("html_attr", r#"" onmouseover="alert(1)"#, "&#x22;&#x20;onmouseover&#x3D;&#x22;alert&#x28;1&#x29;"),
+    ("html_attr", "x' autofocus onfocus=alert`1` y='", "x&#x27;&#x20;autofocus&#x20;onfocus&#x3D;alert&#x60;1&#x60;&#x20;y&#x3D;&#x27;"),
+    ("html_attr", "a b\tc\nd&e<f>", "a&#x20;b&#x09;c&#x0A;d&#x26;e&#x3C;f&#x3E;"),
+    ("html_attr", "na\u{ef}ve,1.0-beta_2", "na\u{ef}ve,1.0-beta_2"),
+
+    // Neither quote style, a backslash, a line break nor "</script>" ends the literal
+    ("js_string", r#"it's "quoted" \ "#, r#"it\'s \"quoted\" \\ "#),
+    ("js_string", "</script><script>alert(1)</script>", r"\x3C\x2Fscript\x3E\x3Cscript\x3Ealert(1)\x3C\x2Fscript\x3E"),
+    ("js_string", "line\nbreak\r\ttab\u{8}\u{c}\0\u{1b}\u{7f}", r"line\nbreak\r\ttab\b\f\x00\x1B\x7F"),
+    ("js_string", "`${document.cookie}` & \u{2028}\u{2029}", r"\x60${document.cookie}\x60 \x26 \u2028\u2029"),
+    ("js_string", "caf\u{e9} \u{1F600}", "caf\u{e9} \u{1F600}"),
+
+    // Quotes doubled and nothing else: backslashes are ordinary characters in standard SQL
+    ("sql_string", "O'Reilly", "O''Reilly"),
+    ("sql_string", "' OR '1'='1' --", "'' OR ''1''=''1'' --"),
+    ("sql_string", r#"C:\temp\ "x" ; DROP TABLE t"#, r#"C:\temp\ "x" ; DROP TABLE t"#),
+
+    // One word: bare when nothing in it is special, else single-quoted with "'\''" for a quote
+    ("cmd_unix", "report-2024_01.tar.gz", "report-2024_01.tar.gz"),
+    ("cmd_unix", "user@host:/var/log/x=1,y+%", "user@host:/var/log/x=1,y+%"),
+    ("cmd_unix", "", "''"),
+    ("cmd_unix", "two words", "'two words'"),
+    ("cmd_unix", "it's", r"'it'\''s'"),
+    ("cmd_unix", "$(rm -rf ~); `id` > /tmp/x *", "'$(rm -rf ~); `id` > /tmp/x *'"),
+    ("cmd_unix", "line\nbreak", "'line\nbreak'"),
+
+    // Quoted for CommandLineToArgvW, then every cmd.exe metacharacter (the quotes too) behind a caret
+    ("cmd_windows", "hello", r#"^"hello^""#),
+    ("cmd_windows", r"C:\Program Files\", r#"^"C:\Program Files\\^""#),
+    ("cmd_windows", r#"say "hi" & del *"#, r#"^"say \^"hi\^" ^& del *^""#),
+    ("cmd_windows", r#"a\\"b"#, r#"^"a\\\\\^"b^""#),
+    ("cmd_windows", "100% !x! a|b <c> (d) ^", r#"^"100^% ^!x^! a^|b ^<c^> ^(d^) ^^^""#),
+    ("cmd_windows", "", r#"^"^""#)
+];
+
+#[test]
+fn each_context_escapes_what_matters_there() {
+    for (call, text, escaped) in VECTORS {
+        assert_eq!(mercy_encode(call, text), *escaped, "{} of {:?}", call, text);
+        assert_eq!(mercy_decode(call, escaped), *text, "{} of {:?}", call, escaped);
+    }
+}
+
+#[test]
+fn decoders_accept_the_other_forms_of_their_context() {
+    let cases = [
+        // Decimal, hex in either case and named references; unknown or unterminated ones left alone
+        ("html_attr", "&#34;&#x22;&#X22;&quot;&amp;&lt;&gt;&apos;&#39;&nbsp;", "\"\"\"\"&<>''\u{a0}"),
+        ("html_attr", "&copy; &#xZZ; & &amp", "&copy; &#xZZ; & &amp"),
+
+        // \uHHHH (with surrogate pairs), \u{...}, \v, a line continuation; a broken escape keeps its backslash
+        ("js_string", r"\u0041\x42\u{1F600}\uD83D\uDE00\v\0 \q", "AB\u{1F600}\u{1F600}\u{b}\0 q"),
+        ("js_string", "one \\\ntwo", "one two"),
+        ("js_string", r"\xZZ \u12 \uD83D!", r"\xZZ \u12 \uD83D!"),
+
+        ("sql_string", "''''", "''"),
+
+        // Double quotes with their four escapes, a bare backslash, and quote styles run together
+        ("cmd_unix", r#""a \$HOME \"q\" \\ \n b""#, r#"a $HOME "q" \ \n b"#),
+        ("cmd_unix", r"a\ b\'c", "a b'c"),
+        ("cmd_unix", r#"'x'"y"z"#, "xyz"),
+
+        // Unquoted, "" inside quotes, and backslashes that do not come before a quote
+        ("cmd_windows", "plain^&word", "plain&word"),
+        ("cmd_windows", r#""a""b""#, r#"a"b"#),
+        ("cmd_windows", r#""C:\dir\\" tail"#, r"C:\dir\ tail")
+    ];
+    for (call, escaped, text) in cases {
+        assert_eq!(mercy_decode(call, escaped), text, "{} of {:?}", call, escaped);
+    }
+}
+
+#[test]
+fn every_context_round_trips() {
+    let samples = [
+        "",
+        "plain",
+        r#"<img src=x onerror="alert('1')">"#,
+        r"\\server\share\ trailing\\",
+        "quotes ' \" ` and $VAR %PATH% !x! ^caret",
+        "controls \0\t\r\n\u{1b}\u{7f} and \u{2028}",
+        "\u{1F600} na\u{ef}ve \u{5f8c}"
+    ];
+    for call in ["html_attr", "js_string", "sql_string", "cmd_unix", "cmd_windows"] {
+        for text in samples {
+            assert_eq!(mercy_decode(call, &mercy_encode(call, text)), text, "{} of {:?}", call, text);
+        }
+    }
+}
+
+#[test]
+fn url_and_html_entity_layers() {
+    // Each decode takes one layer off, so double-encoded input needs two
+    let twice = "https%253A%252F%252Fevil.example%252Fa%2520b%253Fq%253D%25E2%2582%25AC";
+    let once = mercy_decode("url", twice);
+    assert_eq!(once, "https%3A%2F%2Fevil.example%2Fa%20b%3Fq%3D%E2%82%AC");
+    assert_eq!(mercy_decode("url", &once), "https://evil.example/a b?q=\u{20ac}");
+    assert_eq!(mercy_encode("url", &mercy_encode("url", "a b/\u{20ac}")), "a%2520b%252F%25E2%2582%25AC");
+    assert_eq!(mercy_decode("html_entity", "&amp;lt;script&amp;gt;"), "&lt;script&gt;");
+    assert_eq!(mercy_decode("html_entity", &mercy_decode("html_entity", "&amp;lt;script&amp;gt;")), "<script>");
+
+    // Mixed content: escapes among plain text, either hex case, "+" a space only in form mode
+    assert_eq!(mercy_decode("url", "plain+text%2b%2Bmore%7e~"), "plain+text++more~~");
+    assert_eq!(mercy_decode("url_form", "plain+text%2b%2Bmore"), "plain text++more");
+    assert_eq!(mercy_decode("html_entity", "Tom &amp; Jerry &lt;3 &#x41;&#65;&#39;s &quot;&#x1F600;&quot; & more"), "Tom & Jerry <3 AA's \"\u{1F600}\" & more");
+    assert_eq!(mercy_encode("html_entity", r#"<a href="x?a=1&b='2'">"#), "&lt;a href=&quot;x?a=1&amp;b=&#39;2&#39;&quot;&gt;");
+
+    // A cut-off or non-hex escape is an error, not a guess
+    for input in ["%2", "abc%", "%zz", "ok%4", "%\u{20ac}"] {
+        let decoded = mercy_decode("url", input);
+        assert!(decoded.contains("Invalid percent escape"), "{:?}: {}", input, decoded);
+    }
+
+    // Multi-byte UTF-8 round trips, each byte escaped on the way out
+    for text in ["\u{e9}", "\u{20ac}", "\u{1F600}", "\u{65e5}\u{672c}\u{8a9e} text, \u{645}\u{631}\u{62d}\u{628}\u{627}"] {
+        let encoded = mercy_encode("url", text);
+        assert!(encoded.is_ascii(), "{}", encoded);
+        assert_eq!(mercy_decode("url", &encoded), text);
+        assert_eq!(mercy_decode("url_form", &mercy_encode("url_form", text)), text);
+        assert_eq!(mercy_decode("html_entity", &mercy_encode("html_entity", text)), text);
+    }
+    assert_eq!(mercy_encode("url", "\u{1F600}"), "%F0%9F%98%80");
+}
@@ -0,0 +1,82 @@
+// Memory-mapped and buffered scans of the same file give the same results
+
+mod common;
+
+use std::{fs, path::Path, sync::Mutex};
+
+use common::*;
+use mercy::{CarveOptions, FILE_HASH_ALGORITHMS, FileAccess, StringsOptions, carve_file, mercy_entropy_profile, mercy_hash_file, mercy_strings, set_file_access};
+
+// The access mode is process-wide
+static ACCESS: Mutex<()> = Mutex::new(());
+
+// Noise with a PNG straddling a page boundary, a PDF and some text in it
+fn fixture(dir: &Path) -> std::path::PathBuf {
+    let mut state = 0x2545_f491_u32;
+    let mut bytes: Vec<u8> = (0..1_500_000).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as u8
+    }).collect();
+
+    let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x10\x00\x00\x00\x10\x08\x02\x00\x00\x00\x90\x91\x68\x36".to_vec();
+    png.extend_from_slice(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+    bytes[4090..4090 + png.len()].copy_from_slice(&png);
+
+    let pdf = b"%PDF-1.7\n1 0 obj << /Type /Catalog >> endobj\ntrailer << /Root 1 0 R >>\n%%EOF\n";
+    bytes[1_000_003..1_000_003 + pdf.len()].copy_from_slice(pdf);
+
+    let text = b"C:\\Windows\\System32\\drivers\\etc\\hosts";
+    bytes[700_000..700_000 + text.len()].copy_from_slice(text);
+
+    let path = dir.join("image.bin");
+    fs::write(&path, bytes).expect("fixture");
+    path
+}
+
+// Everything the scans report, as JSON so any difference shows
+fn scan(path: &Path, access: FileAccess) -> Vec<String> {
+    set_file_access(access);
+
+    let mut results: Vec<String> = FILE_HASH_ALGORITHMS.iter().map(|algorithm| mercy_hash_file(path, algorithm).expect("hash")).collect();
+    results.push(serde_json::to_string(&mercy_entropy_profile(path, 4096, 1024).expect("profile")).expect("json"));
+    results.push(serde_json::to_string(&mercy_strings(path, &StringsOptions::default()).expect("strings")).expect("json"));
+    results.push(serde_json::to_string(&carve_file(path, &CarveOptions::default()).expect("carve")).expect("json"));
+
+    set_file_access(FileAccess::Buffered);
+    results
+}
+
+#[test]
+fn mapped_scans_match_buffered_scans() {
+    let _access = ACCESS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let dir = fixture_dir("file-access");
+    let path = fixture(&dir);
+
+    let buffered = scan(&path, FileAccess::Buffered);
+    let mapped = scan(&path, FileAccess::Mapped);
+    assert_eq!(buffered, mapped);
+
+    // The fixture gives the scans something to find
+    let carved = buffered.last().expect("carve report");
+    assert!(carved.contains("\"offset\":4090") && carved.contains("\"offset\":1000003"), "{}", carved);
+    assert!(buffered.iter().any(|result| result.contains("drivers\\\\etc\\\\hosts")));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn empty_files_scan_the_same_either_way() {
+    let _access = ACCESS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let dir = fixture_dir("file-access-empty");
+    let path = dir.join("empty.bin");
+    fs::write(&path, b"").expect("fixture");
+
+    for algorithm in FILE_HASH_ALGORITHMS {
+        set_file_access(FileAccess::Mapped);
+        let mapped = mercy_hash_file(&path, algorithm);
+        set_file_access(FileAccess::Buffered);
+        assert_eq!(mapped, mercy_hash_file(&path, algorithm), "{}", algorithm);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,79 @@
+// ssdeep fuzzy hashing: an edited copy of a file stays similar, unrelated data does not, and digests at neighbouring block sizes still compare
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use mercy::{MercyRng, mercy_hash, mercy_hash_compare, mercy_hash_compare_result, mercy_hash_file, ssdeep_compare, ssdeep_hash};
+
+// Seeded random bytes, so every run hashes the same files
+fn random(seed: u64, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    MercyRng::seeded(seed).fill(&mut bytes);
+    bytes
+}
+
+fn write(dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = dir.join(name);
+    fs::write(&path, data).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+#[test]
+fn an_edited_copy_scores_high_and_unrelated_data_near_zero() {
+    let dir = fixture_dir("fuzzy-files");
+    let original = random(301, 64 * 1024);
+    let mut edited = original.clone();
+    for offset in [1000, 20_000, 40_000] {
+        edited[offset] ^= 0xff;
+    }
+    let (original, edited, unrelated) = (write(&dir, "original.bin", &original), write(&dir, "edited.bin", &edited), write(&dir, "unrelated.bin", &random(302, 64 * 1024)));
+
+    let same = mercy_hash_compare_result("fuzzy_file", &original, &original).expect("same");
+    let similar = mercy_hash_compare_result("fuzzy_file", &original, &edited).expect("edited");
+    let different = mercy_hash_compare_result("ssdeep_file", &original, &unrelated).expect("unrelated");
+    assert_eq!(same, 100);
+    assert!(similar > 80 && similar < 100, "three changed bytes scored {}", similar);
+    assert!(different <= 10, "unrelated data scored {}", different);
+
+    // The file calls agree with hashing the bytes and comparing the digests
+    let digest = |path: &str| mercy_hash_file(path, "ssdeep").expect(path);
+    assert_eq!(digest(&original), ssdeep_hash(&fs::read(&original).expect("read")));
+    assert_eq!(ssdeep_compare(&digest(&original), &digest(&edited)), Ok(similar));
+    assert_eq!(mercy_hash_compare("fuzzy", &digest(&original), &digest(&unrelated)), different.to_string());
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn text_edits_block_sizes_and_bad_digests() {
+    // A few words changed in a long text, and a text that grew by a third
+    let words = ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet", "kilo", "lima"];
+    let mut rng = MercyRng::seeded(301);
+    let text: Vec<&str> = (0..4000).map(|_| words[rng.below(words.len())]).collect();
+    let original = text.join(" ");
+    let edited = original.replacen("alpha", "omega", 2);
+    let longer = format!("{} {}", original, text[..1800].join(" "));
+
+    let digest = mercy_hash("fuzzy", &original);
+    let (edited, longer) = (mercy_hash("fuzzy", &edited), mercy_hash("ssdeep", &longer));
+    let score = |a: &str, b: &str| mercy_hash_compare_result("fuzzy", a, b).expect("digests");
+    assert!(score(&digest, &edited) > 80, "{}", score(&digest, &edited));
+    assert!(score(&digest, &longer) > 50, "{}", score(&digest, &longer));
+
+    // Block sizes more than a factor of two apart never compare
+    let block = |digest: &str| digest.split(':').next().and_then(|size| size.parse::<u32>().ok()).expect("block size");
+    let small = mercy_hash("fuzzy", &original[..original.len() / 8]);
+    assert!(block(&digest) >= 4 * block(&small), "{} {}", digest, small);
+    assert_eq!(score(&digest, &small), 0);
+
+    // The empty input, and digests that cannot be read
+    assert_eq!(ssdeep_hash(b""), "3::");
+    assert_eq!(mercy_hash_compare_result("fuzzy", "3::", "3::").expect("empty"), 100);
+    for bad in ["", "3", "x:abc:def", "3:abc"] {
+        assert!(mercy_hash_compare_result("fuzzy", &digest, bad).is_err(), "{:?}", bad);
+    }
+    assert_eq!(ssdeep_compare(&digest, "nope"), Err("Invalid ssdeep digest 'nope'".to_string()));
+    assert!(mercy_hash_compare_result("md5", &digest, &digest).is_err());
+}
@@ -0,0 +1,110 @@
+// Hash identification: hex digests by length, crypt and scheme prefixes, lines from dumps, and input that is no hash at all
+
+use mercy::{mercy_extra, mercy_hash_identify};
+
+// The algorithms offered for `input`, most likely first
+fn algorithms(input: &str) -> Vec<String> {
+    mercy_hash_identify(input).expect(input).candidates.into_iter().map(|candidate| candidate.algorithm).collect()
+}
+
+#[test]
+fn hex_digests_by_length() {
+    // (digest of "password", the most likely algorithms in order)
+    let cases: [(&str, &[&str]); 4] = [
+        ("5f4dcc3b5aa765d61d8327deb882cf99", &["MD5", "NTLM", "MD4", "LM", "RIPEMD-128"]),
+        ("5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8", &["SHA-1", "MySQL 4.1+ without the leading '*'", "RIPEMD-160"]),
+        ("5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8", &["SHA-256", "SHA3-256", "Keccak-256", "BLAKE2s-256", "GOST R 34.11-94"]),
+        ("b109f3bbbc244eb82441917ed06d618b9008dd09b3befd1b5e07394c706a8bb980b1d7785e5976ec049b46df5f1326af5a2ea6d103fd07c95385ffab0cacbc86", &["SHA-512", "SHA3-512", "BLAKE2b-512", "Whirlpool"])
+    ];
+    for (digest, expected) in cases {
+        assert_eq!(algorithms(digest), expected, "{}", digest);
+        assert_eq!(algorithms(&digest.to_ascii_uppercase()), expected, "{} in upper case", digest);
+    }
+
+    // Every candidate with its hashcat mode and reason
+    let output = mercy_extra("hash_identify", "5f4dcc3b5aa765d61d8327deb882cf99");
+    assert_eq!(output.lines().collect::<Vec<_>>()[..2], [
+        "1. MD5 (hashcat -m 0): 128 bits, the most common digest of this length",
+        "2. NTLM (hashcat -m 1000): 128-bit MD4 of the UTF-16LE password, as Windows stores it"
+    ]);
+    assert!(output.ends_with("5. RIPEMD-128: 128 bits, rarely seen"), "{}", output);
+
+    // Known constants move their algorithm to the front
+    let empty_ntlm = mercy_hash_identify("31d6cfe0d16ae931b73c59d7e0c089c0").expect("empty NTLM");
+    assert_eq!((empty_ntlm.candidates[0].algorithm.as_str(), empty_ntlm.candidates[0].reason.as_str()), ("NTLM", "the NTLM hash of an empty password"));
+    assert_eq!(algorithms("aad3b435b51404eeaad3b435b51404ee")[0], "LM");
+    assert_eq!(algorithms("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[0], "SHA-256");
+    assert_eq!(algorithms("cbf43926")[0], "CRC-32");
+}
+
+#[test]
+fn crypt_and_scheme_prefixes() {
+    // (hash, the one algorithm, its hashcat mode)
+    let cases = [
+        ("$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW", "bcrypt", Some(3200)),
+        ("$2y$10$abcdefghijklmnopqrstuuO8v3JjQq7aQ4bT1vuQ7J1bN0o6VY6mW", "bcrypt", Some(3200)),
+        ("$6$rounds=5000$saltsalt$IxDD3jeSOb5eB1CX5LBsqZFVkJdido3OUILO5Ifz5iwMuTS4XMS130MTSuDDl3aCI6WouIL9AjRbLCelDCy.g.", "sha512crypt", Some(1800)),
+        ("$5$saltsalt$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZF4Dt8Ztu", "sha256crypt", Some(7400)),
+        ("$1$saltsalt$qjXMvbEw8oaL.CzflDugX/", "md5crypt", Some(500)),
+        ("$apr1$Kd2ofIxM$aAMlpFu.JOiBEv8Z9I4c1.", "Apache MD5 (apr1)", Some(1600)),
+        ("$argon2id$v=19$m=65536,t=3,p=4$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG", "Argon2id", None),
+        ("$y$j9T$F5Jx5fExrKuPp53xLKQ..1$X3DX6M94c7o.9agCG9G317fhZg9SqC.5i5rd.RhAtQ7", "yescrypt", None),
+        ("$P$984478476IagS59wHZvyQMArzfx58u.", "phpass", Some(400)),
+        ("pbkdf2_sha256$260000$salt$Y3Jhc2hlZA==", "Django PBKDF2-SHA256", Some(10000)),
+        ("*2470C0C06DEE42FD1618BB99005ADCA2EC9D1E19", "MySQL 4.1+", Some(300))
+    ];
+    for (hash, algorithm, mode) in cases {
+        let found = mercy_hash_identify(hash).expect(hash);
+        assert!(found.recognized, "{}", hash);
+        assert_eq!(found.candidates.iter().map(|candidate| (candidate.algorithm.as_str(), candidate.hashcat_mode)).collect::<Vec<_>>(), vec![(algorithm, mode)], "{}", hash);
+    }
+
+    // A bcrypt prefix on something too short is still bcrypt, with the shape questioned
+    assert_eq!(mercy_hash_identify("$2b$12$short").expect("short").candidates[0].reason, "\"$2b$\" prefix, but not the usual $2b$NN$ and 53 characters");
+
+    // LDAP schemes: the base64 value is checked against the digest length
+    let ssha = mercy_hash_identify("{SSHA}gVK8WC9YyFT1gMsQHTGCgT3sSv5zYWx0").expect("SSHA");
+    assert_eq!((ssha.candidates[0].algorithm.as_str(), ssha.candidates[0].reason.as_str()), ("Salted SHA-1 (LDAP SSHA)", "{SSHA} scheme: 20-byte digest and 4-byte salt"));
+    assert_eq!(mercy_hash_identify("{ssha}not base64!").expect("bad SSHA").candidates[0].reason, "{SSHA} scheme, but the value is not the expected base64");
+    let crypt = mercy_hash_identify("{CRYPT}$1$saltsalt$qjXMvbEw8oaL.CzflDugX/").expect("CRYPT");
+    assert_eq!((crypt.candidates[0].algorithm.as_str(), crypt.candidates[0].reason.as_str()), ("md5crypt", "{CRYPT} scheme around a \"$1$\" prefix of MD5-based Unix crypt"));
+
+    // A base64 digest with no scheme, and an ssdeep digest despite its colons
+    assert_eq!(algorithms("XohImNooBHFR0OVvjcYpJ3NgPQ1qq73WKhHvch0VQtg=")[0], "SHA-256 (base64)");
+    assert_eq!(algorithms("3:AXGBicFlgVNhBGcL6wCrFQEv:AXGHsNhxLsr2C"), vec!["ssdeep"]);
+}
+
+#[test]
+fn lines_from_dumps() {
+    // pwdump: the NT hash, with LM shown as disabled
+    let pwdump = mercy_hash_identify("Administrator:500:aad3b435b51404eeaad3b435b51404ee:31d6cfe0d16ae931b73c59d7e0c089c0:::").expect("pwdump");
+    assert_eq!((pwdump.hash.as_str(), pwdump.username.as_deref()), ("31d6cfe0d16ae931b73c59d7e0c089c0", Some("Administrator")));
+    assert_eq!(pwdump.candidates.iter().map(|candidate| candidate.algorithm.as_str()).collect::<Vec<_>>(), vec!["NTLM", "LM (disabled)"]);
+    assert!(pwdump.candidates[0].reason.ends_with("this is the NTLM hash of an empty password"));
+
+    // LM:NT with a real LM half, and user:hash with the hash identified on its own
+    assert_eq!(algorithms("e52cac67419a9a224a3b108f3fa6cb6d:8846f7eaee8fb117ad06bdd830b7586c"), vec!["NTLM", "LM"]);
+    let user = mercy_hash_identify("alice:$6$saltsalt$IxDD3jeSOb5eB1CX5LBsqZFVkJdido3OUILO5Ifz5iwMuTS4XMS130MTSuDDl3aCI6WouIL9AjRbLCelDCy.g.").expect("user:hash");
+    assert_eq!((user.username.as_deref(), user.candidates[0].algorithm.as_str()), (Some("alice"), "sha512crypt"));
+    assert!(mercy_extra("hash_identify", "bob:5f4dcc3b5aa765d61d8327deb882cf99").starts_with("User: bob\n1. MD5 (hashcat -m 0): "));
+
+    // hash:salt leaves out what is never salted
+    assert_eq!(algorithms("5f4dcc3b5aa765d61d8327deb882cf99:pepper"), vec!["salted MD5", "salted MD4", "salted RIPEMD-128"]);
+
+    // A NetNTLMv2 response as Responder writes it
+    let netntlm = format!("bob::CORP:1122334455667788:{}:{}", "0".repeat(32), "0101".repeat(20));
+    assert_eq!(mercy_hash_identify(&netntlm).expect("NetNTLMv2").candidates[0].hashcat_mode, Some(5600));
+}
+
+#[test]
+fn garbage_is_not_a_hash() {
+    for input in ["hello world", "5f4dcc3b5aa765d61d8327deb882cf9", "zzzz4dcc3b5aa765d61d8327deb882cf99", "user:notahash"] {
+        let found = mercy_hash_identify(input).expect(input);
+        assert!(!found.recognized && found.candidates.is_empty(), "{}: {:?}", input, found.candidates);
+        assert_eq!(mercy_extra("hash_identify", input), format!("'{}' is not a recognized hash format", input));
+    }
+    assert_eq!(mercy_hash_identify("  ").expect_err("empty"), "No hash given");
+
+    let json: serde_json::Value = serde_json::from_str(&mercy_extra("hash_identify_json", "$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW")).expect("JSON");
+    assert_eq!((json["recognized"].as_bool(), json["candidates"][0]["algorithm"].as_str(), json["candidates"][0]["hashcat_mode"].as_u64()), (Some(true), Some("bcrypt"), Some(3200)));
+}
@@ -0,0 +1,212 @@
+// Known answers for the public hashing calls
+
+mod common;
+
+use std::{
+    fs,
+    io::{self, Write}
+};
+
+use common::*;
+use mercy::{INCREMENTAL_HASH_ALGORITHMS, MercyHasher, MercyRng, RESUMABLE_HASH_ALGORITHMS, mercy_hash, mercy_hash_file, mercy_hmac, mercy_hmac_result, mercy_hmac_verify};
+
+const FOX: &str = "The quick brown fox jumps over the lazy dog";
+
+// Random text with one- to four-byte characters, so splits fall inside characters too
+fn random_text(rng: &mut MercyRng, chars: usize) -> String {
+    const ALPHABET: [char; 8] = ['a', 'Z', '7', ' ', '\n', 'ß', '東', '😀'];
+    (0..chars).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect()
+}
+
+// 200,001 bytes of a pattern, so a file spans several read chunks and ends part way into one
+fn patterned() -> Vec<u8> {
+    (0..200_001u32).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+// RFC 4231 test cases whose data is text, with binary keys given in hex
+#[test]
+fn hmac_calls_give_the_rfc_4231_answers() {
+    let jefe = ("Jefe", "what do ya want for nothing?");
+    assert_eq!(mercy_hmac("hmac_sha256", jefe.0, jefe.1), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    assert_eq!(mercy_hmac("hmac_sha512", jefe.0, jefe.1), "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737");
+    assert_eq!(mercy_hmac("hmac_sha256_hexkey", "4a656665", jefe.1), mercy_hmac("hmac_sha256", jefe.0, jefe.1));
+
+    let key = "0b".repeat(20);
+    assert_eq!(mercy_hmac("hmac_sha256_hexkey", &key, "Hi There"), "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7");
+    assert_eq!(mercy_hmac("hmac_sha512_hexkey", &key, "Hi There"), "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854");
+
+    let key = "0c".repeat(20);
+    assert!(mercy_hmac("hmac_sha256_hexkey", &key, "Test With Truncation").starts_with("a3b6167473100ee06e0c796c2955552b"));
+    assert!(mercy_hmac("hmac_sha512_hexkey", &key, "Test With Truncation").starts_with("415fad6271580a531d4179bc891d87a6"));
+
+    // A key longer than the block is hashed first
+    let key = "aa".repeat(131);
+    let data = "Test Using Larger Than Block-Size Key - Hash Key First";
+    assert_eq!(mercy_hmac("hmac_sha256_hexkey", &key, data), "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    assert_eq!(mercy_hmac("hmac_sha512_hexkey", &key, data), "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598");
+    let data = "This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.";
+    assert_eq!(mercy_hmac("hmac_sha256_hexkey", &key, data), "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2");
+    assert_eq!(mercy_hmac("hmac_sha512_hexkey", &key, data), "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58");
+
+    assert!(mercy_hmac_result("hmac_sha256_hexkey", "0b0", "Hi There").is_err());
+    assert!(mercy_hmac_result("hmac_sha384", "Jefe", jefe.1).is_err());
+}
+
+#[test]
+fn hmac_verify_takes_either_case_and_rejects_near_misses() {
+    let expected = "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+    let data = "what do ya want for nothing?";
+    assert!(mercy_hmac_verify("hmac_sha256", "Jefe", data, expected));
+    assert!(mercy_hmac_verify("hmac_sha256", "Jefe", data, &expected.to_uppercase()));
+    assert!(!mercy_hmac_verify("hmac_sha256", "Jefe", data, &expected.replace("43", "44")));
+    assert!(!mercy_hmac_verify("hmac_sha256", "Jefe", data, &expected[..32]));
+    assert!(!mercy_hmac_verify("hmac_sha256", "jefe", data, expected));
+}
+
+// CRC-32 as zlib computes it, xxHash64 as the reference implementation does
+#[test]
+fn checksums_give_the_known_answers() {
+    assert_eq!(mercy_hash("crc32", "123456789"), "cbf43926");
+    assert_eq!(mercy_hash("crc32", FOX), "414fa339");
+    assert_eq!(mercy_hash("crc32", ""), "00000000");
+
+    assert_eq!(mercy_hash("xxhash64", ""), "ef46db3751d8e999");
+    assert_eq!(mercy_hash("xxhash64", "a"), "d24ec4f1a98c6e5b");
+    assert_eq!(mercy_hash("xxhash64", "abc"), "44bc2cf5ad770999");
+    assert_eq!(mercy_hash("xxhash64", FOX), "0b242d361fda71bc");
+    assert_eq!(mercy_hash("xxhash64_seeded", "seed=0 abc"), "44bc2cf5ad770999");
+    assert_eq!(mercy_hash("xxhash64_seeded", "seed=42 abc"), "13c1d910702770e6");
+    assert_eq!(mercy_hash("xxhash64_seeded", &format!("seed=0x2a {}", FOX)), "aa9f288a8baa3d3f");
+}
+
+#[test]
+fn file_checksums_stream_to_the_same_answers() {
+    let dir = fixture_dir("checksum-files");
+    let (small, large) = (dir.join("digits.txt"), dir.join("pattern.bin"));
+    fs::write(&small, "123456789").expect("fixture");
+    fs::write(&large, patterned()).expect("fixture");
+
+    assert_eq!(mercy_hash_file(&small, "crc32").as_deref(), Ok("cbf43926"));
+    assert_eq!(mercy_hash_file(&large, "crc32").as_deref(), Ok("69f7ed61"));
+    assert_eq!(mercy_hash_file(&large, "xxhash64").as_deref(), Ok("4320d37d07a10e02"));
+
+    let large = large.to_str().expect("path");
+    assert_eq!(mercy_hash("crc32_file", large), "69f7ed61");
+    assert_eq!(mercy_hash("xxhash64_file", large), "4320d37d07a10e02");
+    assert_eq!(mercy_hash("xxhash64_file", &format!("{} seed=42", large)), "5c7b1bd9294916b1");
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+// Digests of the empty string and of text that is multi-byte in UTF-8, which is what gets hashed
+#[test]
+fn digests_give_the_known_answers() {
+    let text = "Grüße, 東京 😀";
+    let vectors = [
+        ("md5", "d41d8cd98f00b204e9800998ecf8427e", "78f14b9c541650584b896e50159c5df2"),
+        ("sha1", "da39a3ee5e6b4b0d3255bfef95601890afd80709", "089ef88879d83400bfdf089f86f75d7842c0c492"),
+        ("sha2_256", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", "8a8893a8f60df87b53f1e6f24b2319e3c1fd43613a11df77a7313d3d7df34bb0"),
+        ("sha2_512",
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "bc4438f82facbba276e6d1d765b07244b2cba91ebef722a245b8e2354b3b9856a43dddd4cbd4e24ed343191d01c770c16355018342ed5c054545481d399296ae"),
+        ("sha3_256", "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a", "d30c5f0c4e6bab8191a5db03b90bd01332a70f27f113f2482e13b34d064fc34e"),
+        ("sha3_512",
+            "a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26",
+            "ecffc73f8d75f0bf6a8c1922d747c474f3fe250579c0940b34bb8d1cd872f9a8e76dd6031e7ec04697fc169781968aec3ea09ff7059fd7a8294dcbb8b907921b")
+    ];
+
+    let all = mercy_hash("all", text);
+    for (algorithm, empty, multibyte) in vectors {
+        assert_eq!(mercy_hash(algorithm, ""), empty, "{}", algorithm);
+        assert_eq!(mercy_hash(algorithm, text), multibyte, "{}", algorithm);
+        assert!(all.lines().any(|line| line == format!("{}: {}", algorithm, multibyte)), "{} missing from:\n{}", algorithm, all);
+    }
+    assert_eq!(all.lines().count(), 9);
+    assert!(all.lines().any(|line| line.starts_with("ssdeep: ")));
+}
+
+// Saved and resumed after every piece of a random split, in the 64-byte blocks or across them
+#[test]
+fn resumed_hashing_equals_one_shot_hashing() {
+    let mut rng = MercyRng::seeded(249);
+    for round in 0..200 {
+        let chars = rng.below(300);
+        let text = random_text(&mut rng, chars);
+        let bytes = text.as_bytes();
+
+        for algorithm in RESUMABLE_HASH_ALGORITHMS {
+            let mut hasher = MercyHasher::new(algorithm).expect("hasher");
+            let mut offset = 0;
+            while offset < bytes.len() {
+                let end = (offset + 1 + rng.below(bytes.len() - offset)).min(bytes.len());
+                hasher.update(&bytes[offset..end]);
+                offset = end;
+
+                let state = hasher.serialize_state().expect("resumable");
+                hasher = MercyHasher::resume(algorithm, &state).expect("resumes");
+                assert_eq!(hasher.len(), offset as u64);
+            }
+            assert_eq!(hasher.finalize(), mercy_hash(algorithm, &text), "round {}: {} of {} bytes", round, algorithm, bytes.len());
+        }
+    }
+}
+
+#[test]
+fn saved_states_resume_only_as_what_they_were() {
+    let mut hasher = MercyHasher::new("sha2_256").expect("hasher");
+    hasher.update(b"arriving over hours");
+    let state = hasher.serialize_state().expect("resumable");
+    assert!(state.starts_with("mercy-hasher/1/sha2_256/19/"), "{}", state);
+
+    assert!(MercyHasher::resume("md5", &state).is_err());
+    assert!(MercyHasher::resume("sha1", &state).is_err());
+    assert!(MercyHasher::resume("sha2_256", &state.replacen("/1/", "/2/", 1)).is_err());
+    assert!(MercyHasher::resume("sha2_256", &state.replacen("/19/", "/20/", 1)).is_err());
+    assert!(MercyHasher::resume("sha2_256", "not a state").is_err());
+
+    assert!(MercyHasher::new("sha1").expect("hasher").serialize_state().is_err());
+    assert!(MercyHasher::new("ssdeep").is_err());
+}
+
+#[test]
+fn one_blob_and_single_bytes_hash_alike() {
+    let text = format!("{} {}", FOX, "Grüße, 東京 😀 ".repeat(20));
+    let bytes = text.as_bytes();
+
+    for algorithm in INCREMENTAL_HASH_ALGORITHMS {
+        let mut blob = MercyHasher::new(algorithm).expect("hasher");
+        blob.update(bytes);
+        let mut single = MercyHasher::new(algorithm).expect("hasher");
+        for byte in bytes {
+            single.update(&[*byte]);
+        }
+        let mut written = MercyHasher::new(algorithm).expect("hasher");
+        for chunk in bytes.chunks(7) {
+            written.write_all(chunk).expect("write");
+        }
+        let mut copied = MercyHasher::new(algorithm).expect("hasher");
+        assert_eq!(io::copy(&mut &bytes[..], &mut copied).expect("copy"), bytes.len() as u64);
+
+        let one_shot = mercy_hash(algorithm, &text);
+        for (how, hasher) in [("blob", &blob), ("1-byte chunks", &single), ("write", &written), ("io::copy", &copied)] {
+            assert_eq!(hasher.finalize(), one_shot, "{} fed as {}", algorithm, how);
+            assert_eq!(hasher.len(), bytes.len() as u64);
+        }
+    }
+}
+
+// A file copied in through io::Write hashes as mercy_hash_file reads it
+#[test]
+fn files_copied_into_a_hasher_match_file_hashing() {
+    let dir = fixture_dir("hasher-copy");
+    let path = dir.join("pattern.bin");
+    fs::write(&path, patterned()).expect("fixture");
+
+    for algorithm in INCREMENTAL_HASH_ALGORITHMS {
+        let mut hasher = MercyHasher::new(algorithm).expect("hasher");
+        io::copy(&mut fs::File::open(&path).expect("open"), &mut hasher).expect("copy");
+        assert_eq!(Ok(hasher.finalize()), mercy_hash_file(&path, algorithm), "{}", algorithm);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,56 @@
+// Hex dumps of a small fixture, character for character as xxd prints them
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use mercy::{HexRow, hexdump_bytes, hexdump_rows, mercy_hex, mercy_hex_range};
+
+// An MZ header, text, control bytes, bytes past ASCII and a short last line
+const FIXTURE: &[u8] = b"MZ\x90\x00Hello, mercy!\n\x00\x01\x7f\xff~ tab\there\r\n \"quoted\" {x}";
+
+// `xxd` of the fixture
+const DUMP: &str = "\
+00000000: 4d5a 9000 4865 6c6c 6f2c 206d 6572 6379  MZ..Hello, mercy
+00000010: 210a 0001 7fff 7e20 7461 6209 6865 7265  !.....~ tab.here
+00000020: 0d0a 2022 7175 6f74 6564 2220 7b78 7d    .. \"quoted\" {x}
+";
+
+#[test]
+fn a_file_dumps_exactly_as_xxd_does() {
+    let dir = fixture_dir("hexdump");
+    let path = dir.join("sample.bin");
+    fs::write(&path, FIXTURE).expect("fixture");
+    let path = path.to_str().expect("path");
+
+    assert_eq!(mercy_hex("hex_dump", path), DUMP);
+    assert_eq!(hexdump_bytes(FIXTURE, 0), DUMP);
+
+    // A range keeps the file's offsets, and a line of one byte pads its hex column
+    let range = "00000011: 0a00 017f                                ....\n";
+    assert_eq!(mercy_hex_range(path, 0x11, 4).expect("range"), range);
+    assert_eq!(mercy_hex("hex_dump", &format!("{} offset=0x11 length=4", path)), range);
+    assert_eq!(hexdump_bytes(b"~", 0x1000), format!("00001000: 7e{}~\n", " ".repeat(39)));
+    assert_eq!(hexdump_bytes(b"", 0), "");
+
+    // The rows of the JSON form hold the same columns
+    let rows = hexdump_rows(FIXTURE, 0);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1], HexRow { offset: 0x10, bytes: "210a00017fff7e207461620968657265".to_string(), ascii: "!.....~ tab.here".to_string() });
+    assert_eq!(rows[2].ascii, ".. \"quoted\" {x}");
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+// Every byte outside 0x20 to 0x7e is a '.'
+#[test]
+fn only_printable_ascii_shows_as_itself() {
+    let bytes: Vec<u8> = (0..=255).collect();
+    let ascii: String = hexdump_rows(&bytes, 0).into_iter().map(|row| row.ascii).collect();
+
+    for (byte, shown) in bytes.iter().zip(ascii.chars()) {
+        let expected = if (0x20..=0x7e).contains(byte) { *byte as char } else { '.' };
+        assert_eq!(shown, expected, "0x{:02x}", byte);
+    }
+}
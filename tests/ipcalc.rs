@@ -0,0 +1,90 @@
+// CIDR arithmetic at the edges: /31 and /32 blocks, their IPv6 counterparts, and malformed input
+
+use std::net::IpAddr;
+
+use mercy::{mercy_cidr_range, mercy_extra, mercy_ip_in_cidr, mercy_is_private};
+
+fn ip(text: &str) -> IpAddr {
+    text.parse().expect("address")
+}
+
+#[test]
+fn usable_hosts_change_at_slash_31_and_32() {
+    // (block, network, broadcast, first host, last host, addresses, usable hosts)
+    let cases = [
+        ("192.168.1.77/24", "192.168.1.0", Some("192.168.1.255"), "192.168.1.1", "192.168.1.254", 256, 254),
+        ("10.0.0.4/30", "10.0.0.4", Some("10.0.0.7"), "10.0.0.5", "10.0.0.6", 4, 2),
+        // RFC 3021: both addresses of a point-to-point /31 are hosts, and there is no broadcast
+        ("10.0.0.5/31", "10.0.0.4", None, "10.0.0.4", "10.0.0.5", 2, 2),
+        ("10.0.0.5/32", "10.0.0.5", None, "10.0.0.5", "10.0.0.5", 1, 1),
+        ("10.0.0.5", "10.0.0.5", None, "10.0.0.5", "10.0.0.5", 1, 1),
+        ("0.0.0.0/0", "0.0.0.0", Some("255.255.255.255"), "0.0.0.1", "255.255.255.254", 1 << 32, (1 << 32) - 2),
+        // IPv6 never reserves a broadcast address, whatever the prefix
+        ("2001:db8::1/127", "2001:db8::", None, "2001:db8::", "2001:db8::1", 2, 2),
+        ("2001:db8::1/128", "2001:db8::1", None, "2001:db8::1", "2001:db8::1", 1, 1),
+        ("2001:db8:0:1::/64", "2001:db8:0:1::", None, "2001:db8:0:1::", "2001:db8:0:1:ffff:ffff:ffff:ffff", 1 << 64, 1 << 64)
+    ];
+
+    for (block, network, broadcast, first, last, addresses, usable) in cases {
+        let range = mercy_cidr_range(block).expect(block);
+        assert_eq!(range.network, ip(network), "{}", block);
+        assert_eq!(range.broadcast, broadcast.map(ip), "{}", block);
+        assert_eq!((range.first_host, range.last_host), (ip(first), ip(last)), "{}", block);
+        assert_eq!((range.addresses, range.usable_hosts), (addresses, usable), "{}", block);
+    }
+
+    let slash_31 = mercy_cidr_range("10.0.0.5/31").expect("/31");
+    assert_eq!((slash_31.cidr.as_str(), slash_31.netmask), ("10.0.0.4/31", ip("255.255.255.254")));
+    assert_eq!(mercy_cidr_range("10.0.0.5/32").expect("/32").netmask, ip("255.255.255.255"));
+    assert_eq!(mercy_cidr_range("2001:db8::1/127").expect("/127").netmask, ip("ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe"));
+
+    let text = mercy_extra("cidr_range", "10.0.0.5/31");
+    assert!(text.contains("Broadcast:    (none)") && text.ends_with("Usable hosts: 2"), "{}", text);
+}
+
+#[test]
+fn containment_at_the_edges_of_small_blocks() {
+    let cases = [
+        ("10.0.0.4", "10.0.0.4/31", true),
+        ("10.0.0.5", "10.0.0.4/31", true),
+        ("10.0.0.6", "10.0.0.4/31", false),
+        ("10.0.0.3", "10.0.0.4/31", false),
+        ("10.0.0.5", "10.0.0.5/32", true),
+        ("10.0.0.4", "10.0.0.5/32", false),
+        ("255.255.255.255", "0.0.0.0/0", true),
+        ("2001:db8::1", "2001:db8::/127", true),
+        ("2001:db8::2", "2001:db8::/127", false),
+        ("2001:db8::1", "2001:db8::1/128", true),
+        ("[2001:db8::ffff]", "2001:db8::/64", true),
+        // An address of the other family is never inside
+        ("10.0.0.5", "::/0", false),
+        ("::ffff:10.0.0.5", "10.0.0.0/8", false)
+    ];
+
+    for (address, block, inside) in cases {
+        assert_eq!(mercy_ip_in_cidr(address, block).expect(block).inside, inside, "{} in {}", address, block);
+    }
+
+    assert_eq!(mercy_extra("ip_in_cidr", "10.0.0.6,10.0.0.4/31"), "10.0.0.6 is not inside 10.0.0.4/31");
+    assert!(mercy_is_private("::ffff:10.0.0.5").expect("mapped").private);
+}
+
+#[test]
+fn malformed_input_is_described() {
+    let errors = [
+        (mercy_cidr_range("10.0.0.0/33"), "Prefix length /33 is out of range for 10.0.0.0"),
+        (mercy_cidr_range("2001:db8::/129"), "Prefix length /129 is out of range for 2001:db8::"),
+        (mercy_cidr_range("10.0.0.256/24"), "Invalid IP address in '10.0.0.256/24'"),
+        (mercy_cidr_range("10.0.0.0/x"), "Invalid prefix length in '10.0.0.0/x'"),
+        (mercy_cidr_range("10.0.0.0/-1"), "Invalid prefix length")
+    ];
+    for (result, expected) in errors {
+        let error = result.expect_err(expected);
+        assert!(error.contains(expected), "{}", error);
+    }
+
+    assert!(mercy_ip_in_cidr("10.0.0.0/24", "10.0.0.0/8").expect_err("a block").contains("got the CIDR block"));
+    assert!(mercy_ip_in_cidr("", "10.0.0.0/8").expect_err("empty").contains("No IP address given"));
+    assert!(mercy_is_private("10.0.0.300").expect_err("bad octet").contains("Invalid IP address '10.0.0.300'"));
+    assert!(mercy_extra("ip_in_cidr", "10.0.0.1").contains("Expected \"address,cidr\""));
+}
@@ -0,0 +1,116 @@
+// Every kind of mercy_json answer read back with serde, into the envelope and typed outputs
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use mercy::{HexRow, MercyJson, mercy_json};
+use serde::{Deserialize, de::DeserializeOwned};
+
+#[derive(Debug, Deserialize)]
+struct ErrorJson {
+    code: String,
+    numeric_code: u32,
+    message: String,
+    context: ContextJson
+}
+
+#[derive(Debug, Deserialize)]
+struct ContextJson {
+    operation: Option<String>,
+    input: Option<String>
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoisJson {
+    query: String,
+    record: String,
+    registrar: Option<String>,
+    created: Option<String>
+}
+
+// The envelope parsed, checked to serialize back to exactly the same text
+fn envelope(module: &str, call: &str, input: &str) -> MercyJson {
+    let text = mercy_json(module, call, input);
+    let parsed: MercyJson = serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {}", e, text));
+    assert_eq!(serde_json::to_string_pretty(&parsed).expect("serializes"), text);
+    assert!(parsed.output.is_null() != parsed.error.is_none(), "exactly one of output and error: {}", text);
+    parsed
+}
+
+fn output<T: DeserializeOwned>(answer: &MercyJson) -> T {
+    serde_json::from_value(answer.output.clone()).unwrap_or_else(|e| panic!("{}: {}", e, answer.output))
+}
+
+fn error(answer: &MercyJson) -> ErrorJson {
+    serde_json::from_value(answer.error.clone().expect("an error")).expect("error JSON")
+}
+
+#[test]
+fn decode_and_encode_answers_read_back() {
+    let decoded = envelope("decode", "base64", "aGVsbG8sIG1lcmN5");
+    assert_eq!((decoded.module.as_str(), decoded.method.as_str(), decoded.input.as_str()), ("mercy_decode", "base64", "aGVsbG8sIG1lcmN5"));
+    assert_eq!(output::<String>(&decoded), "hello, mercy");
+
+    // Decoded text that happens to be JSON stays a string
+    let decoded = envelope("mercy_decode", "hex", "7b2261223a317d");
+    assert_eq!(output::<String>(&decoded), "{\"a\":1}");
+
+    let encoded = envelope("encode", "base32", "hello");
+    assert_eq!(output::<String>(&encoded), "NBSWY3DP");
+
+    let failed = envelope("decode", "hex", "abc");
+    let failure = error(&failed);
+    assert_eq!((failure.code.as_str(), failure.context.operation.as_deref(), failure.context.input.as_deref()), ("E_INVALID_ENCODING", Some("mercy_decode/hex"), Some("abc")));
+    assert!(failure.numeric_code == 3 && !failure.message.is_empty());
+
+    let unknown = error(&envelope("decode", "no_such_call", "x"));
+    assert_eq!(unknown.code, "E_UNSUPPORTED_METHOD");
+    assert_eq!(error(&envelope("no_such_module", "base64", "x")).code, "E_UNSUPPORTED_METHOD");
+}
+
+#[test]
+fn hex_dumps_read_back_as_rows() {
+    let dir = fixture_dir("json-hex");
+    let path = dir.join("sample.bin");
+    fs::write(&path, b"MZ\x90\x00 twenty bytes\xff\x00").expect("fixture");
+    let path = path.to_str().expect("path");
+
+    let rows: Vec<HexRow> = output(&envelope("hex", "hex_dump", path));
+    assert_eq!(rows.len(), 2);
+    assert_eq!((rows[0].offset, rows[0].bytes.as_str(), rows[0].ascii.as_str()), (0, "4d5a9000207477656e74792062797465", "MZ.. twenty byte"));
+    assert_eq!((rows[1].offset, rows[1].ascii.as_str()), (16, "s.."));
+
+    let rows: Vec<HexRow> = output(&envelope("hex", "hex_dump", &format!("{} offset=4 length=3", path)));
+    assert_eq!(rows, [HexRow { offset: 4, bytes: "207477".to_string(), ascii: " tw".to_string() }]);
+
+    let missing = error(&envelope("hex", "hex_dump", &dir.join("missing.bin").to_string_lossy()));
+    assert!(missing.message.contains("Unable to locate"), "{}", missing.message);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn reports_and_system_info_read_back_as_objects() {
+    let _mocks = mocks();
+
+    let whois: WhoisJson = output(&envelope("extra", "whois", MOCK_MALICIOUS_DOMAIN));
+    assert_eq!(whois.query, MOCK_MALICIOUS_DOMAIN);
+    assert_eq!(whois.record, MOCK_WHOIS);
+    assert_eq!((whois.registrar.as_deref(), whois.created.as_deref()), (Some("Example Registrar, Inc."), Some("2024-02-28T00:00:00Z")));
+
+    let info = envelope("extra", "system_info", "all");
+    let fields = info.output.as_object().expect("keyed fields");
+    for key in ["hostname", "cpu_cores", "memory_total_bytes", "uptime_seconds", "disk_free_bytes", "user"] {
+        assert!(fields.contains_key(key), "{} missing from {:?}", key, fields);
+    }
+    let cores = envelope("extra", "system_info", "cpu_cores");
+    assert!(output::<serde_json::Map<String, serde_json::Value>>(&cores)["cpu_cores"].as_u64().is_some_and(|cores| cores > 0));
+    assert!(error(&envelope("extra", "system_info", "gpu")).message.contains("Unknown system_info field 'gpu'"));
+
+    // Secrets are withheld from the envelope as from errors
+    let strength = envelope("extra", "password_strength", "password123");
+    assert_eq!(strength.input, "<11 bytes withheld>");
+    assert!(strength.output.is_object());
+}
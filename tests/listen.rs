@@ -0,0 +1,188 @@
+// Fake services on loopback: what each persona says, how many clients it serves at once, and how it stops
+
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+
+use mercy::{ConnectionRecord, ListenHandle, ListenOptions, Persona, mercy_listen, ndjson_logger};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Records = Arc<Mutex<Vec<ConnectionRecord>>>;
+
+fn listen(persona: Persona, options: &ListenOptions) -> (ListenHandle, Records) {
+    let records: Records = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&records);
+    let handle = mercy_listen(0, persona, options, move |record| log.lock().expect("records").push(record.clone())).expect("listen");
+    (handle, records)
+}
+
+fn connect(handle: &ListenHandle) -> TcpStream {
+    let stream = TcpStream::connect(handle.local_addr()).expect("connect");
+    stream.set_read_timeout(Some(TIMEOUT)).expect("timeout");
+    stream
+}
+
+// Everything the service sends until it closes the connection
+fn read_all(stream: &mut TcpStream) -> Vec<u8> {
+    let mut received = Vec::new();
+    let _ = stream.read_to_end(&mut received);
+    received
+}
+
+// Waits for a condition the service threads bring about
+fn wait_for(what: &str, condition: impl Fn() -> bool) {
+    let started = Instant::now();
+    while !condition() {
+        assert!(started.elapsed() < TIMEOUT, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn each_persona_speaks_its_protocol() {
+    // SSH: the banner first, then whatever the client says is only listened to
+    let (ssh, records) = listen(Persona::ssh(), &ListenOptions::default());
+    let mut client = connect(&ssh);
+    let mut banner = [0u8; 41];
+    client.read_exact(&mut banner).expect("banner");
+    assert_eq!(&banner, b"SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6\r\n");
+    client.write_all(b"SSH-2.0-Go\r\n").expect("send");
+    client.shutdown(Shutdown::Write).expect("close");
+    assert!(read_all(&mut client).is_empty());
+    let summary = ssh.stop();
+    assert_eq!((summary.persona.as_str(), summary.connections, summary.refused, summary.bytes_received), ("ssh", 1, 0, 12));
+
+    let record = records.lock().expect("records")[0].clone();
+    assert_eq!((record.bytes_received, record.bytes_sent, record.truncated, record.refused, record.error), (12, 41, false, false, None));
+    assert_eq!(record.received_hex, "00000000: 5353 482d 322e 302d 476f 0d0a            SSH-2.0-Go..");
+    assert_eq!(record.source, client.local_addr().expect("address").to_string());
+
+    // HTTP: the whole request, body included, is read before the canned answer goes out
+    let persona = Persona::Http { status: 404, headers: vec![("X-Trap".to_string(), "1".to_string())], body: "nothing here".to_string() };
+    let (http, records) = listen(persona, &ListenOptions::default());
+    let mut client = connect(&http);
+    let head = b"POST /login HTTP/1.1\r\nHost: x\r\nContent-Length: 9\r\n\r\nuser=";
+    client.write_all(head).expect("send");
+    thread::sleep(Duration::from_millis(100));
+    client.write_all(b"root").expect("send");
+    assert_eq!(String::from_utf8(read_all(&mut client)).expect("text"), "HTTP/1.1 404 Not Found\r\nX-Trap: 1\r\nContent-Length: 12\r\nConnection: close\r\n\r\nnothing here");
+    http.stop();
+    assert_eq!(records.lock().expect("records")[0].bytes_received, head.len() + 4);
+
+    // Echo: each chunk straight back
+    let (echo, records) = listen(Persona::Echo, &ListenOptions::default());
+    let mut client = connect(&echo);
+    for chunk in [&b"ping\n"[..], b"\x00\xff binary \r\n"] {
+        client.write_all(chunk).expect("send");
+        let mut back = vec![0u8; chunk.len()];
+        client.read_exact(&mut back).expect("echo");
+        assert_eq!(back, chunk);
+    }
+    client.shutdown(Shutdown::Write).expect("close");
+    assert!(read_all(&mut client).is_empty());
+    echo.stop();
+    let record = records.lock().expect("records")[0].clone();
+    assert_eq!((record.persona.as_str(), record.bytes_received, record.bytes_sent), ("echo", 17, 17));
+
+    assert!(Persona::parse("telnet").is_err());
+    let error = mercy_listen(0, Persona::Echo, &ListenOptions { max_connections: 0, ..ListenOptions::default() }, |_| {}).expect_err("no connections");
+    assert!(error.contains("max_connections"), "{}", error);
+}
+
+#[test]
+fn clients_are_served_at_once_up_to_the_limit() {
+    // Sixteen clients at the same time each get their own bytes back
+    let (echo, records) = listen(Persona::Echo, &ListenOptions::default());
+    let address = echo.local_addr();
+    let clients: Vec<_> = (0..16).map(|index| thread::spawn(move || {
+        let mut client = TcpStream::connect(address).expect("connect");
+        client.set_read_timeout(Some(TIMEOUT)).expect("timeout");
+        let message = format!("client {:02} says hello", index).repeat(50);
+        client.write_all(message.as_bytes()).expect("send");
+        let mut back = vec![0u8; message.len()];
+        client.read_exact(&mut back).expect("echo");
+        assert_eq!(back, message.as_bytes());
+    })).collect();
+    for client in clients {
+        client.join().expect("client");
+    }
+    let summary = echo.stop();
+    assert_eq!((summary.connections, summary.refused, summary.bytes_received), (16, 0, 16 * 1000));
+    assert_eq!(records.lock().expect("records").len(), 16);
+
+    // Two held open fill a limit of two; the third is closed unserved and logged as refused
+    let (echo, records) = listen(Persona::Echo, &ListenOptions { max_connections: 2, ..ListenOptions::default() });
+    let mut held: Vec<TcpStream> = (0..2).map(|_| connect(&echo)).collect();
+    for client in &mut held {
+        client.write_all(b"hi").expect("send");
+        client.read_exact(&mut [0u8; 2]).expect("echo");
+    }
+    assert_eq!(echo.active(), 2);
+
+    let mut third = connect(&echo);
+    let _ = third.write_all(b"let me in");
+    assert!(read_all(&mut third).is_empty());
+    wait_for("the refusal", || records.lock().expect("records").iter().any(|record| record.refused));
+
+    // Once one closes, there is room again
+    held.pop();
+    wait_for("a free slot", || echo.active() == 1);
+    let mut fourth = connect(&echo);
+    fourth.write_all(b"ok").expect("send");
+    fourth.read_exact(&mut [0u8; 2]).expect("echo");
+
+    let summary = echo.stop();
+    assert_eq!((summary.connections, summary.refused), (4, 1));
+    let records = records.lock().expect("records");
+    let refused: Vec<&ConnectionRecord> = records.iter().filter(|record| record.refused).collect();
+    assert_eq!((refused.len(), refused[0].bytes_received, refused[0].bytes_sent), (1, 0, 0));
+    assert!(refused[0].to_string().contains("(refused)"));
+}
+
+#[test]
+fn limits_end_connections_and_stop_ends_them_all() {
+    let options = ListenOptions { max_read: 10, read_timeout: Duration::from_millis(300), ..ListenOptions::default() };
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let echo = mercy_listen(0, Persona::Echo, &options, ndjson_logger(Arc::clone(&log))).expect("listen");
+
+    // Past the read limit the connection is closed, with only the first ten bytes echoed
+    let mut greedy = connect(&echo);
+    greedy.write_all(b"0123456789abcdefghij").expect("send");
+    assert_eq!(read_all(&mut greedy), b"0123456789");
+
+    // A client that never sends is dropped at the read timeout
+    let started = Instant::now();
+    let mut silent = connect(&echo);
+    assert!(read_all(&mut silent).is_empty());
+    assert!(started.elapsed() >= Duration::from_millis(250) && started.elapsed() < TIMEOUT, "{:?}", started.elapsed());
+
+    // Stopping closes the listener and waits for a client still connected
+    let slow = ListenOptions { read_timeout: Duration::from_secs(60), ..ListenOptions::default() };
+    let (ssh, records) = listen(Persona::ssh(), &slow);
+    let mut waiting = connect(&ssh);
+    waiting.read_exact(&mut [0u8; 41]).expect("banner");
+    let address = ssh.local_addr();
+    let started = Instant::now();
+    let summary = ssh.stop();
+    assert!(started.elapsed() < Duration::from_secs(2), "stop took {:?}", started.elapsed());
+    assert_eq!(summary.connections, 1);
+    assert_eq!(records.lock().expect("records")[0].error.as_deref(), Some("listener stopped"));
+    assert!(read_all(&mut waiting).is_empty());
+    assert!(TcpStream::connect_timeout(&address, Duration::from_secs(1)).is_err(), "still listening on {}", address);
+
+    // Dropping the handle stops it too; every record has been written by then
+    drop(echo);
+    let log = String::from_utf8(log.lock().expect("log").clone()).expect("text");
+    let lines: Vec<serde_json::Value> = log.lines().map(|line| serde_json::from_str(line).expect("JSON line")).collect();
+    assert_eq!(lines.len(), 2, "{}", log);
+    let greedy = lines.iter().find(|line| line["truncated"] == true).expect("truncated record");
+    assert_eq!((greedy["bytes_received"].as_u64(), greedy["bytes_sent"].as_u64()), (Some(10), Some(10)));
+    let silent = lines.iter().find(|line| line["error"] == "read timeout").expect("timed out record");
+    assert_eq!(silent["bytes_received"].as_u64(), Some(0));
+    assert!(silent.get("received_hex").is_some_and(|hex| hex == ""));
+}
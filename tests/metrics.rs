@@ -0,0 +1,181 @@
+// Prometheus text exposition of one result of each kind, checked line by line and against the format's grammar
+
+use std::net::IpAddr;
+
+use mercy::{
+    CertificateInfo,
+    CheckResult,
+    Finding,
+    FtpCheck,
+    PingMethod,
+    PingReport,
+    RdpCheck,
+    Severity,
+    SinkholeReport,
+    SmbCheck,
+    SweepHost,
+    SweepReport,
+    TelnetCheck,
+    mercy_metrics_render_at
+};
+
+// 2024-02-29T00:00:00Z
+const NOW: i64 = 1_709_164_800;
+
+// Label values with a quote, a backslash and a line feed in them
+const SHARE: &str = r#"fs01 "corp" \\share"#;
+const SUBJECT: &str = "CN=\"intranet\", O=Back\\slash\nOU=Ops";
+
+fn certificate(subject: &str, not_after: Option<&str>, sha256: &str) -> CertificateInfo {
+    CertificateInfo {
+        subject: subject.to_string(),
+        issuer: "CN=Test CA".to_string(),
+        serial: "01".to_string(),
+        not_before: Some("2023-01-01T00:00:00Z".to_string()),
+        not_after: not_after.map(str::to_string),
+        sha256: sha256.to_string(),
+        self_signed: false
+    }
+}
+
+fn results() -> Vec<CheckResult> {
+    let address: IpAddr = "10.0.0.9".parse().expect("address");
+    vec![
+        CheckResult::Smb(SmbCheck { target: SHARE.to_string(), reachable: true, dialect: Some("3.1.1".to_string()), smb1_accepted: Some(true), signing_enabled: Some(true), signing_required: Some(false), server_guid: None, notes: Vec::new() }),
+        CheckResult::Certificate { target: "intranet.example:443".to_string(), certificate: certificate(SUBJECT, Some("2024-03-01T00:00:00Z"), "ab12") },
+        CheckResult::Rdp(RdpCheck { target: "10.0.0.5:3389".to_string(), answered: false, standard_rdp: None, tls: None, nla: None, notes: Vec::new() }),
+        CheckResult::Ftp(FtpCheck { target: "ftp.example:21".to_string(), banner: "220 ready".to_string(), anonymous_login: true, features: Vec::new(), tls_supported: false, severity: Severity::High, notes: Vec::new() }),
+        CheckResult::Telnet(TelnetCheck { target: "switch:23".to_string(), banner: String::new(), options_refused: Vec::new(), login_prompt: false, severity: Severity::Info }),
+        CheckResult::Ping(PingReport { target: "gw".to_string(), address, method: PingMethod::TcpConnect, transmitted: 4, received: 3, loss_percent: 25.0, min_ms: Some(10.0), avg_ms: Some(12.5), max_ms: Some(15.0), stddev_ms: Some(2.0), probes: Vec::new() }),
+        CheckResult::Sweep(SweepReport { range: "10.0.0.8/30".to_string(), method: "tcp".to_string(), scanned: 4, alive: vec![SweepHost { address, evidence: "tcp/22 open".to_string(), port: Some(22), rtt_ms: 1.0, hostname: None }], elapsed_ms: 40.0 }),
+        // Expired an hour ago, and one without a notAfter, which has no sample
+        CheckResult::Certificate { target: "old.example:443".to_string(), certificate: certificate("CN=old.example", Some("2024-02-28T23:00:00Z"), "cd34") },
+        CheckResult::Certificate { target: "odd.example:443".to_string(), certificate: certificate("CN=odd.example", None, "ef56") },
+        CheckResult::Sinkhole(SinkholeReport { domain: "evil.example".to_string(), sinkholed: true, evidence: Vec::new(), addresses: Vec::new(), cnames: Vec::new(), nameservers: Vec::new(), http_status: None, notes: Vec::new() }),
+        CheckResult::Audit { target: "https://example.com/".to_string(), check: "security_headers".to_string(), findings: vec![
+            Finding::new("headers.csp-missing", "No CSP", Severity::Medium, "https://example.com/", "", ""),
+            Finding::new("headers.hsts-missing", "No HSTS", Severity::Medium, "https://example.com/", "", ""),
+            Finding::new("headers.cors-wildcard", "CORS allows any origin", Severity::High, "https://example.com/", "", "")
+        ]}
+    ]
+}
+
+const EXPECTED: &str = r#"# HELP mercy_check_up Whether the checked service answered (1) or not (0).
+# TYPE mercy_check_up gauge
+mercy_check_up{target="fs01 \"corp\" \\\\share",check="smb"} 1
+mercy_check_up{target="10.0.0.5:3389",check="rdp"} 0
+mercy_check_up{target="ftp.example:21",check="ftp"} 1
+mercy_check_up{target="switch:23",check="telnet"} 1
+mercy_check_up{target="gw",check="ping"} 1
+# HELP mercy_check_weakness Insecure setting seen by a check (1 present, 0 absent).
+# TYPE mercy_check_weakness gauge
+mercy_check_weakness{target="fs01 \"corp\" \\\\share",check="smb",weakness="smb1_accepted"} 1
+mercy_check_weakness{target="fs01 \"corp\" \\\\share",check="smb",weakness="signing_not_required"} 1
+mercy_check_weakness{target="ftp.example:21",check="ftp",weakness="anonymous_login"} 1
+mercy_check_weakness{target="ftp.example:21",check="ftp",weakness="no_tls"} 1
+mercy_check_weakness{target="switch:23",check="telnet",weakness="cleartext_login"} 0
+# HELP mercy_ping_loss_ratio Share of ICMP echo requests without a reply.
+# TYPE mercy_ping_loss_ratio gauge
+mercy_ping_loss_ratio{target="gw",check="ping"} 0.25
+# HELP mercy_ping_rtt_seconds Average round-trip time of answered echo requests.
+# TYPE mercy_ping_rtt_seconds gauge
+mercy_ping_rtt_seconds{target="gw",check="ping"} 0.0125
+# HELP mercy_sweep_hosts_scanned Addresses probed by a host sweep.
+# TYPE mercy_sweep_hosts_scanned gauge
+mercy_sweep_hosts_scanned{target="10.0.0.8/30",check="sweep",method="tcp"} 4
+# HELP mercy_sweep_hosts_alive Addresses that answered a host sweep.
+# TYPE mercy_sweep_hosts_alive gauge
+mercy_sweep_hosts_alive{target="10.0.0.8/30",check="sweep",method="tcp"} 1
+# HELP mercy_cert_expiry_seconds Seconds until the certificate expires, negative once it has.
+# TYPE mercy_cert_expiry_seconds gauge
+mercy_cert_expiry_seconds{target="intranet.example:443",check="certificate",subject="CN=\"intranet\", O=Back\\slash\nOU=Ops",sha256="ab12"} 86400
+mercy_cert_expiry_seconds{target="old.example:443",check="certificate",subject="CN=old.example",sha256="cd34"} -3600
+# HELP mercy_domain_sinkholed Whether the domain looks sinkholed (1) or not (0).
+# TYPE mercy_domain_sinkholed gauge
+mercy_domain_sinkholed{target="evil.example",check="sinkhole"} 1
+# HELP mercy_findings Audit findings by severity.
+# TYPE mercy_findings gauge
+mercy_findings{target="https://example.com/",check="security_headers",severity="critical"} 0
+mercy_findings{target="https://example.com/",check="security_headers",severity="high"} 1
+mercy_findings{target="https://example.com/",check="security_headers",severity="medium"} 2
+mercy_findings{target="https://example.com/",check="security_headers",severity="low"} 0
+mercy_findings{target="https://example.com/",check="security_headers",severity="info"} 0
+"#;
+
+// A sample line split by the text format's grammar: metric name, label pairs with their escapes undone, value
+fn parse_sample(line: &str) -> (String, Vec<(String, String)>, f64) {
+    let open = line.find('{').expect("labels");
+    let name = &line[..open];
+    assert!(name.chars().enumerate().all(|(index, c)| c.is_ascii_alphabetic() || c == '_' || c == ':' || (index > 0 && c.is_ascii_digit())), "metric name {:?}", name);
+
+    let mut labels = Vec::new();
+    let mut chars = line[open + 1..].chars();
+    loop {
+        let label: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        assert!(!label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'), "label name {:?} in {}", label, line);
+        assert_eq!(chars.next(), Some('"'), "{}", line);
+
+        let mut value = String::new();
+        loop {
+            match chars.next().expect("closing quote") {
+                '"' => break,
+                '\\' => value.push(match chars.next().expect("escape") {
+                    '\\' => '\\',
+                    '"' => '"',
+                    'n' => '\n',
+                    other => panic!("invalid escape \\{} in {}", other, line)
+                }),
+                '\n' => panic!("raw line feed in {}", line),
+                c => value.push(c)
+            }
+        }
+        labels.push((label, value));
+
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => break,
+            other => panic!("{:?} after a label value in {}", other, line)
+        }
+    }
+
+    let rest: String = chars.collect();
+    let value = rest.strip_prefix(' ').expect("space before the value").parse().expect("value");
+    (name.to_string(), labels, value)
+}
+
+#[test]
+fn every_result_kind_renders() {
+    assert_eq!(mercy_metrics_render_at(&results(), NOW), EXPECTED);
+    assert_eq!(mercy_metrics_render_at(&[], NOW), "");
+}
+
+#[test]
+fn exposition_follows_the_text_format() {
+    let text = mercy_metrics_render_at(&results(), NOW);
+    assert!(text.ends_with('\n'));
+
+    // One HELP and one TYPE per family, before every sample of it, and each family's samples together
+    let mut families: Vec<String> = Vec::new();
+    let mut samples = Vec::new();
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# HELP ") {
+            let name = rest.split(' ').next().expect("name").to_string();
+            assert!(!families.contains(&name), "second HELP for {}", name);
+            families.push(name);
+        } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+            assert_eq!(Some(format!("{} gauge", families.last().expect("HELP first"))), Some(rest.to_string()));
+        } else {
+            let (name, labels, value) = parse_sample(line);
+            assert_eq!(Some(&name), families.last(), "{} outside its family", name);
+            samples.push((name, labels, value));
+        }
+    }
+    assert_eq!((families.len(), samples.len()), (9, 22));
+
+    // Label values come back exactly as they went in
+    let (_, labels, _) = &samples[0];
+    assert_eq!(labels[0], ("target".to_string(), SHARE.to_string()));
+    let certificate = samples.iter().find(|(name, _, _)| name == "mercy_cert_expiry_seconds").expect("expiry");
+    assert_eq!(certificate.1[2], ("subject".to_string(), SUBJECT.to_string()));
+    assert_eq!(certificate.2, 86_400.0);
+}
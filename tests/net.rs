@@ -0,0 +1,115 @@
+// Retries, Retry-After and exhaustion of the shared net layer, through the lookups that use it
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::*;
+use mercy::{
+    HttpHeadersOptions, MercyConfig, NotifyOptions, NotifyPayload, ServiceEndpoints, mercy_extra_with, mercy_http_headers, mercy_malicious_with, mercy_notify, with_mercy_config
+};
+use serde_json::json;
+
+#[test]
+fn two_rate_limits_then_an_answer() {
+    let _lock = lock_endpoints();
+    let limited = MockHttp::start(vec![
+        MockRoute::new("/api/dfi/search/ioc/domain", 429, "").header("Retry-After", "1").times(1),
+        MockRoute::new("/api/dfi/search/ioc/domain", 429, "").times(1),
+        MockRoute::new("/api/dfi/search/ioc/domain", 200, MOCK_INQUEST_MALICIOUS)
+    ]).expect("mock");
+
+    let started = Instant::now();
+    let output = with_endpoints(|endpoints| ServiceEndpoints { inquest: limited.url(""), ..endpoints }, || mercy_malicious_with(&quick_config(3), "status", MOCK_MALICIOUS_DOMAIN));
+
+    assert!(output.starts_with("Malicious"), "{}", output);
+    assert_eq!(limited.requests().len(), 3);
+    // The first wait is the Retry-After, not the 20 ms backoff
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn five_server_errors_outlast_the_retries() {
+    let _lock = lock_endpoints();
+    let failing = MockHttp::start(vec![
+        MockRoute::new("/api/dfi/search/", 500, "").times(5),
+        MockRoute::new("/api/dfi/search/", 200, MOCK_INQUEST_EMPTY)
+    ]).expect("mock");
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { inquest: failing.url(""), ..endpoints }, || mercy_malicious_with(&quick_config(3), "hash_status", MOCK_MALICIOUS_HASH));
+
+    assert!(output.contains("gave up after 4 attempt(s), last status 500 Internal Server Error"), "{}", output);
+    assert_eq!(failing.requests().len(), 4);
+}
+
+#[test]
+fn long_retry_after_ends_the_lookup() {
+    let _lock = lock_endpoints();
+    let limited = MockHttp::start(vec![MockRoute::new("/api/dfi/search/", 429, "").header("Retry-After", "3600")]).expect("mock");
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { inquest: limited.url(""), ..endpoints }, || mercy_malicious_with(&quick_config(3), "status", "clean.example"));
+
+    assert!(output.contains("asked to retry after 3600 s (429 Too Many Requests, attempt 1)"), "{}", output);
+    assert_eq!(limited.requests().len(), 1);
+}
+
+#[test]
+fn blocking_lookups_retry_transient_failures() {
+    let _lock = lock_endpoints();
+
+    // Nothing listens on the discard port, so every WHOIS connection fails
+    let started = Instant::now();
+    let output = with_endpoints(|endpoints| ServiceEndpoints { asn_whois: "127.0.0.1:9".to_string(), ..endpoints }, || {
+        mercy_extra_with(&quick_config(2), "asn_lookup", "8.8.8.8")
+    });
+
+    assert!(output.contains("Unable to connect to WHOIS server 127.0.0.1:9"), "{}", output);
+    assert!(output.contains("(gave up after 3 attempts)"), "{}", output);
+    assert!(started.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn webhook_retries_without_naming_its_secret() {
+    let hook = MockHttp::start(vec![
+        MockRoute::new("/services/", 503, "").times(1),
+        MockRoute::new("/services/", 200, "ok")
+    ]).expect("mock");
+    let payload = NotifyPayload { title: "Scan finished".to_string(), summary: json!({ "hosts": 3 }) };
+
+    let report = with_mercy_config(&quick_config(3), || mercy_notify(&hook.url("/services/T000/SECRET"), &payload, &NotifyOptions::default())).expect("delivered");
+    assert_eq!((report.status, report.attempts), (200, 2));
+    assert_eq!(report.target, hook.url(""));
+
+    let refused = MockHttp::start(vec![MockRoute::new("/", 503, "")]).expect("mock");
+    let options = NotifyOptions { retries: 1, ..NotifyOptions::default() };
+    let error = with_mercy_config(&MercyConfig::builder().retry_backoff(Duration::from_millis(20)).build().expect("config"), || {
+        mercy_notify(&refused.url("/services/T000/SECRET"), &payload, &options)
+    }).expect_err("refused");
+    assert!(error.contains("gave up after 2 attempt(s), last status 503 Service Unavailable"), "{}", error);
+    assert!(!error.contains("SECRET"), "{}", error);
+    assert_eq!(refused.requests().len(), 2);
+}
+
+#[test]
+fn subdomains_ask_crtsh_twice() {
+    let _lock = lock_endpoints();
+    let overloaded = MockHttp::start(vec![MockRoute::new("/", 503, "")]).expect("mock");
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { crtsh: overloaded.url(""), ..endpoints }, || mercy_extra_with(&quick_config(5), "subdomains", MOCK_CRTSH_DOMAIN));
+    assert!(output.contains("gave up after 2 attempt(s), last status 503 Service Unavailable"), "{}", output);
+    assert_eq!(overloaded.requests().len(), 2);
+}
+
+#[test]
+fn header_audits_report_the_last_status() {
+    let flaky = MockHttp::start(vec![
+        MockRoute::new("/", 502, "").times(1),
+        MockRoute::new("/", 200, "<html></html>").header("Content-Type", "text/html"),
+        MockRoute::new("/down", 503, "")
+    ]).expect("mock");
+
+    let headers = |url: String| with_mercy_config(&quick_config(1), || mercy_http_headers(&url, &HttpHeadersOptions::default()));
+    assert_eq!(headers(flaky.url("/")).expect("answer").status, 200);
+    assert_eq!(headers(flaky.url("/down")).expect("answer").status, 503);
+    assert_eq!(flaky.requests().len(), 4);
+}
@@ -0,0 +1,397 @@
+// The network lookups against the local mock services, no network needed
+
+mod common;
+
+use std::{
+    fs,
+    net::{IpAddr, UdpSocket},
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant}
+};
+
+use common::*;
+use mercy::{
+    BATCH_CONCURRENCY, BannerOptions, DnsLookupOptions, MercyConfig, DnsLookupStatus, DnsQuery, DnsRdata, DnsType, HttpHeadersOptions, HttpIdentity, PortOptions, PortState, ServiceEndpoints, UserAgent, http_identity, mercy_banner_grab, mercy_dns_lookup, mercy_external_ip,
+    mercy_extra, mercy_extra_with, mercy_hibp_check, mercy_http_headers, mercy_malicious, mercy_malicious_batch, mercy_port_check, mercy_reverse_dns, mercy_subdomains, mercy_telnet_check, mercy_whois, reverse_lookup, set_http_identity, with_mercy_config
+};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+#[test]
+fn malicious_status_classifies_from_inquest() {
+    let mocks = mocks();
+
+    assert!(mercy_malicious("status", MOCK_MALICIOUS_DOMAIN).starts_with("Malicious"));
+    assert!(mercy_malicious("status", "clean.example").contains("No classification available"));
+    assert_eq!(mocks.http.requests().len(), 2);
+}
+
+// InQuest answering both domains slowly, so lookups made at the same time overlap
+fn slow_inquest() -> MockHttp {
+    let search = "/api/dfi/search/ioc/domain?keyword=";
+    MockHttp::start(vec![
+        MockRoute::new(search, 200, MOCK_INQUEST_EMPTY).delay(Duration::from_millis(50)),
+        MockRoute::new(&format!("{}{}", search, MOCK_MALICIOUS_DOMAIN), 200, MOCK_INQUEST_MALICIOUS).delay(Duration::from_millis(50))
+    ]).expect("mock InQuest")
+}
+
+#[test]
+fn concurrent_lookups_keep_to_their_own_responses() {
+    let _lock = lock_endpoints();
+    let inquest = slow_inquest();
+
+    let lookup = |domain: &'static str, expected: &'static str, body: &'static str| move || {
+        let mut saved = Vec::new();
+        for _ in 0..5 {
+            assert!(mercy_malicious("status", domain).starts_with(expected), "{}", domain);
+
+            let output = mercy_malicious("status_save", domain);
+            assert!(output.starts_with(expected), "{}: {}", domain, output);
+            let path = PathBuf::from(output.rsplit_once("(response saved to ").and_then(|(_, path)| path.strip_suffix(')')).expect("saved path"));
+            assert_eq!(fs::read_to_string(&path).expect("saved response"), body);
+            saved.push(path);
+        }
+        saved
+    };
+
+    let saved = with_endpoints(|endpoints| ServiceEndpoints { inquest: inquest.url(""), ..endpoints }, || thread::scope(|scope| {
+        let malicious = scope.spawn(lookup(MOCK_MALICIOUS_DOMAIN, "Malicious", MOCK_INQUEST_MALICIOUS));
+        let clean = scope.spawn(lookup("clean.example", "No classification available", MOCK_INQUEST_EMPTY));
+        [malicious.join().expect("malicious lookups"), clean.join().expect("clean lookups")].concat()
+    }));
+
+    // Every save got a file of its own, still there after the other thread's lookups
+    assert_eq!(inquest.requests().len(), 20);
+    for (n, path) in saved.iter().enumerate() {
+        assert!(!saved[..n].contains(path), "{} saved twice", path.display());
+        assert!(path.starts_with(std::env::temp_dir()), "{}", path.display());
+        fs::remove_file(path).expect("saved response");
+    }
+}
+
+// Each lookup waits 50 ms, so 100 of them only finish quickly if they overlap, and no quicker than the cap allows
+#[test]
+fn malicious_batches_run_concurrently_and_keep_their_order() {
+    let _lock = lock_endpoints();
+    let inquest = slow_inquest();
+    let domains: Vec<String> = (0..100).map(|n| match n % 10 {
+        7 => MOCK_MALICIOUS_DOMAIN.to_string(),
+        9 => format!("host{}.example header=broken", n),
+        _ => format!("host{}.example", n)
+    }).collect();
+    let inputs: Vec<&str> = domains.iter().map(String::as_str).collect();
+
+    let (single, batch, outputs, capped) = with_endpoints(|endpoints| ServiceEndpoints { inquest: inquest.url(""), ..endpoints }, || {
+        let started = Instant::now();
+        mercy_malicious("status", "single.example");
+        let single = started.elapsed();
+
+        let started = Instant::now();
+        let outputs = mercy_malicious_batch("status", &inputs);
+        let batch = started.elapsed();
+
+        let config = MercyConfig::builder().concurrency(50).build().expect("valid config");
+        let started = Instant::now();
+        with_mercy_config(&config, || mercy_malicious_batch("status", &inputs));
+        (single, batch, outputs, started.elapsed())
+    });
+
+    // A malformed option fails its own lookup without taking the rest of the batch with it
+    assert_eq!(outputs.len(), 100);
+    for (n, output) in outputs.iter().enumerate() {
+        match n % 10 {
+            7 => assert!(output.starts_with("Malicious"), "{}: {}", n, output),
+            9 => assert!(output.contains("Invalid header 'broken'"), "{}: {}", n, output),
+            _ => assert!(output.contains("No classification available"), "{}: {}", n, output)
+        }
+    }
+
+    let rounds = |concurrency: usize| Duration::from_millis(50) * 90usize.div_ceil(concurrency) as u32;
+    assert!(batch < single * 25, "100 lookups took {:?}, one {:?}", batch, single);
+    assert!(batch >= rounds(BATCH_CONCURRENCY), "100 lookups took {:?}, more than {} ran at once", batch, BATCH_CONCURRENCY);
+    assert!(capped >= rounds(50) && capped < batch, "{:?} with 50 at once, {:?} with {}", capped, batch, BATCH_CONCURRENCY);
+}
+
+#[test]
+fn malicious_hash_status() {
+    let mocks = mocks();
+
+    assert!(mercy_malicious("hash_status", MOCK_MALICIOUS_HASH).contains("Malicious, file type DOC, first seen 2024-03-01T10:00:00"));
+    assert!(mercy_malicious("hash_status", "098f6bcd4621d373cade4e832627b4f6").contains("No records found"));
+
+    // An invalid hash is refused before any request is made
+    let before = mocks.http.requests().len();
+    assert!(mercy_malicious("hash_status", "abc123").contains("Invalid hash"));
+    assert_eq!(mocks.http.requests().len(), before);
+}
+
+#[test]
+fn whois_record_and_fields() {
+    let _mocks = mocks();
+
+    let response = mercy_whois(MOCK_MALICIOUS_DOMAIN).expect("whois answer");
+    assert_eq!(response.registrar.as_deref(), Some("Example Registrar, Inc."));
+    assert_eq!(response.created.as_deref(), Some("2024-02-28T00:00:00Z"));
+    assert!(mercy_extra("whois", MOCK_MALICIOUS_DOMAIN).contains("Creation Date: 2024-02-28T00:00:00Z"));
+}
+
+#[test]
+fn whois_follows_referrals() {
+    let _mocks = mocks();
+    let registrar = MockTcp::whois("Domain Name: evil.example\r\nRegistrar: Example Registrar, Inc.\r\nRegistrant Country: XX\r\n").expect("registrar");
+    let registry = MockTcp::whois(&format!("Domain Name: EVIL.EXAMPLE\r\nRegistrar WHOIS Server: {}\r\n", registrar.addr())).expect("registry");
+    let rir = MockTcp::whois(&format!("NetRange: 192.0.2.0 - 192.0.2.255\r\nReferralServer: whois://{}\r\n", registrar.addr())).expect("rir");
+
+    let domain = with_endpoints(|endpoints| ServiceEndpoints { whois: Some(registry.addr().to_string()), ..endpoints }, || mercy_whois(MOCK_MALICIOUS_DOMAIN)).expect("domain answer");
+    assert_eq!(domain.servers, vec![registry.addr().to_string(), registrar.addr().to_string()]);
+    assert!(domain.record.contains("Registrant Country"));
+
+    let address = with_endpoints(|endpoints| ServiceEndpoints { whois: Some(rir.addr().to_string()), ..endpoints }, || mercy_whois("192.0.2.10")).expect("address answer");
+    assert_eq!(address.servers.len(), 2);
+}
+
+#[test]
+fn asn_lookup_and_bulk() {
+    let _mocks = mocks();
+
+    assert!(mercy_extra("asn_lookup", "8.8.8.8").contains("8.8.8.8 | AS15169 | 8.8.8.0/24 | US | arin | GOOGLE, US"));
+
+    let output = mercy_extra("asn_bulk", "1.1.1.1, 10.0.0.1, 8.8.8.8, 100.128.0.1, 198.18.255.1, not-an-ip");
+    assert!(output.contains("1.1.1.1 | AS13335 | 1.1.1.0/24 | AU | apnic | CLOUDFLARENET, US"), "{}", output);
+    assert!(output.contains("10.0.0.1 | - | Private address \u{2014} no ASN"), "{}", output);
+    assert!(output.contains("100.128.0.1 | - | not announced"), "{}", output);
+    assert!(output.contains("198.18.255.1 | - | Reserved address"), "{}", output);
+    assert!(output.contains("Skipped (not an IP address): not-an-ip"), "{}", output);
+}
+
+#[test]
+fn asn_bulk_asks_once_for_the_public_addresses() {
+    let _mocks = mocks();
+    let cymru = MockTcp::whois(MOCK_CYMRU).expect("cymru");
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { asn_whois: cymru.addr().to_string(), ..endpoints }, || mercy_extra("asn_bulk", "8.8.8.8,192.168.0.1, 1.1.1.1 ,100.128.0.1,1.1.1.1"));
+    assert!(output.contains("8.8.8.8 | AS15169 | 8.8.8.0/24 | US | arin | GOOGLE, US"), "{}", output);
+    assert!(output.contains("192.168.0.1 | - | Private address \u{2014} no ASN"), "{}", output);
+
+    // One bulk query holding each public address once; the private one never leaves the process
+    assert_eq!(cymru.queries(), vec!["begin\nverbose\n1.1.1.1\n8.8.8.8\n100.128.0.1\nend"]);
+
+    // A single address goes as a "-v" query
+    with_endpoints(|endpoints| ServiceEndpoints { asn_whois: cymru.addr().to_string(), ..endpoints }, || mercy_extra("asn_lookup", "1.1.1.1"));
+    assert_eq!(cymru.queries().last().map(String::as_str), Some("-v 1.1.1.1"));
+
+    // A bulk query of private addresses only makes no connection at all
+    with_endpoints(|endpoints| ServiceEndpoints { asn_whois: cymru.addr().to_string(), ..endpoints }, || mercy_extra("asn_bulk", "10.0.0.1, 172.16.0.1"));
+    assert_eq!(cymru.queries().len(), 2);
+}
+
+#[test]
+fn asn_lookup_offline_and_private() {
+    let _mocks = mocks();
+
+    // Private addresses are answered without a connection, so an unreachable service does not matter
+    let output = with_endpoints(|endpoints| ServiceEndpoints { asn_whois: "127.0.0.1:9".to_string(), ..endpoints }, || {
+        assert!(mercy_extra("asn_lookup", "192.168.1.20").contains("Private address \u{2014} no ASN"));
+        mercy_extra_with(&quick_config(0), "asn_lookup", "8.8.8.8")
+    });
+    assert!(output.contains("ASN lookup of 8.8.8.8 failed: Unable to connect to WHOIS server 127.0.0.1:9"), "{}", output);
+}
+
+#[test]
+fn dns_queries_against_the_stub() {
+    let mocks = mocks();
+
+    let response = DnsQuery::new(MOCK_MALICIOUS_DOMAIN, DnsType::A).send(mocks.dns.addr(), TIMEOUT).expect("answer");
+    assert!(matches!(response.answers.first().map(|record| &record.rdata), Some(DnsRdata::A(addr)) if addr.to_string() == "192.0.2.10"));
+
+    let missing = DnsQuery::new("missing.example", DnsType::A).send(mocks.dns.addr(), TIMEOUT).expect("answer");
+    assert_eq!(missing.rcode, 3);
+
+    let name = reverse_lookup(IpAddr::from([192, 0, 2, 10]), mocks.dns.addr(), TIMEOUT).expect("answer");
+    assert_eq!(name.as_deref(), Some(MOCK_MALICIOUS_DOMAIN));
+}
+
+#[test]
+fn dns_lookup_statuses() {
+    let mocks = mocks();
+    let options = DnsLookupOptions { server: Some(mocks.dns.addr()), timeout: TIMEOUT };
+
+    assert_eq!(mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &options).expect("lookup").status, DnsLookupStatus::Ok);
+    assert_eq!(mercy_dns_lookup("missing.example", &options).expect("lookup").status, DnsLookupStatus::Nxdomain);
+    assert_eq!(mercy_reverse_dns(IpAddr::from([192, 0, 2, 10]), &options).status, DnsLookupStatus::Ok);
+    assert_eq!(mercy_reverse_dns(IpAddr::from([192, 0, 2, 11]), &options).status, DnsLookupStatus::NoRecords);
+
+    // A socket nobody reads from stands in for an unresponsive server
+    let silent = UdpSocket::bind("127.0.0.1:0").expect("socket");
+    let options = DnsLookupOptions { server: Some(silent.local_addr().expect("address")), timeout: Duration::from_millis(300) };
+    assert_eq!(mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &options).expect("lookup").status, DnsLookupStatus::Timeout);
+}
+
+#[test]
+fn banner_grab_and_port_check() {
+    let mocks = mocks();
+    let address = mocks.banner.addr().to_string();
+
+    let grab = mercy_banner_grab(&address, &BannerOptions::default()).expect("banner");
+    assert_eq!(grab.banner, "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.6\n");
+    assert!(mercy_telnet_check(&address).expect("check").banner.starts_with("SSH-2.0-"));
+    assert_eq!(mercy_port_check(&address, &PortOptions::default()).expect("check").state, PortState::Open);
+}
+
+#[test]
+fn subdomains_from_certificate_transparency() {
+    let _mocks = mocks();
+
+    // Names split, unwrapped, lowercased and deduplicated; the email address, the apex and the other domain left out
+    let found = mercy_subdomains(MOCK_CRTSH_DOMAIN, TIMEOUT, &http_identity()).expect("subdomains");
+    assert_eq!(found.subdomains, vec!["api.corp.example", "dev.corp.example", "mail.corp.example", "vpn.corp.example", "www.corp.example"]);
+    assert_eq!(found.certificates, 5);
+    assert_eq!(mercy_extra("subdomains", "quiet.example"), "No certificates found for quiet.example");
+}
+
+// A crt.sh answer with every kind of name the parser has to sort out
+const CRTSH_FIXTURE: &str = r#"[
+    {"id": 1, "common_name": "acme.example", "name_value": "acme.example\nwww.acme.example\nshop.acme.example"},
+    {"id": 2, "common_name": "*.api.acme.example", "name_value": "*.api.acme.example\napi.acme.example"},
+    {"id": 3, "common_name": "WWW.ACME.EXAMPLE", "name_value": "WWW.Acme.Example.\nvpn.eu.acme.example"},
+    {"id": 4, "common_name": "admin@acme.example", "name_value": "admin@acme.example"},
+    {"id": 5, "common_name": "notacme.example", "name_value": "notacme.example\nwww.notacme.example\nacme.example.evil.test"},
+    {"id": 6, "common_name": "legacy.acme.example"},
+    {"id": 7, "common_name": "bad", "name_value": "bad host.acme.example\n-dash.acme.example\n\n"}
+]"#;
+
+#[test]
+fn subdomains_from_a_canned_crtsh_fixture() {
+    let _lock = lock_endpoints();
+    let query = "/?q=%25.acme.example&output=json";
+    // crt.sh under load: one 503 first, which is retried
+    let crtsh = MockHttp::start(vec![
+        MockRoute::new(query, 503, "busy").times(1),
+        MockRoute::new(query, 200, CRTSH_FIXTURE),
+        MockRoute::new("/?q=%25.quiet.example", 200, ""),
+        MockRoute::new("/?q=%25.broken.example", 502, "bad gateway"),
+        MockRoute::new("/?q=%25.gone.example", 404, "not found")
+    ]).expect("mock crt.sh");
+
+    let (found, quiet, broken, gone) = with_endpoints(|endpoints| ServiceEndpoints { crtsh: crtsh.url(""), ..endpoints }, || with_mercy_config(&quick_config(5), || (
+        mercy_subdomains("ACME.example.", TIMEOUT, &http_identity()),
+        mercy_extra("subdomains", "quiet.example"),
+        mercy_extra("subdomains", "broken.example"),
+        mercy_extra("subdomains", "gone.example")
+    )));
+
+    // Split, unwrapped, lowercased and deduplicated; the apex, the email address, the look-alike domains and the malformed names left out
+    let found = found.expect("subdomains");
+    assert_eq!((found.domain.as_str(), found.certificates), ("acme.example", 7));
+    assert_eq!(found.subdomains, ["api.acme.example", "legacy.acme.example", "shop.acme.example", "vpn.eu.acme.example", "www.acme.example"]);
+    assert_eq!(found.to_string(), found.subdomains.join("\n"));
+
+    assert_eq!(quiet, "No certificates found for quiet.example");
+    // Asked twice at most, whatever the config's retries; an answer not worth retrying is reported as it came
+    assert!(broken.contains("gave up after 2 attempt(s), last status 502"), "{}", broken);
+    assert!(gone.contains("crt.sh answered HTTP 404"), "{}", gone);
+
+    let requests = crtsh.requests();
+    let count = |prefix: &str| requests.iter().filter(|request| request.starts_with(&format!("GET {}", prefix))).count();
+    assert_eq!((count(query), count("/?q=%25.quiet.example&output=json"), count("/?q=%25.broken.example&output=json"), count("/?q=%25.gone.example&output=json")), (2, 1, 2, 1), "{:?}", requests);
+}
+
+#[test]
+fn subdomains_offline() {
+    let _mocks = mocks();
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { crtsh: "http://127.0.0.1:9".to_string(), ..endpoints }, || mercy_extra_with(&quick_config(0), "subdomains", MOCK_CRTSH_DOMAIN));
+    assert!(output.contains("(offline?)"), "{}", output);
+}
+
+#[test]
+fn hibp_sends_only_the_prefix() {
+    let mocks = mocks();
+
+    let pwned = mercy_hibp_check(MOCK_PWNED_PASSWORD, TIMEOUT, &http_identity()).expect("range answer");
+    assert_eq!((pwned.prefix.as_str(), pwned.count, pwned.breached), ("CBFDA", 2254650, true));
+    assert!(mercy_extra("hibp_check", "q7#Vx!2mR9$kLp@4Zt&w").contains("Not found"));
+
+    let requests = mocks.http.requests();
+    assert!(requests.iter().any(|request| request.starts_with("GET /range/CBFDA ")), "{:?}", requests);
+    assert!(!requests.iter().any(|request| request.contains("C6008F9")), "{:?}", requests);
+}
+
+#[test]
+fn hibp_offline() {
+    let _mocks = mocks();
+
+    let output = with_endpoints(|endpoints| ServiceEndpoints { pwned_passwords: "http://127.0.0.1:9".to_string(), ..endpoints }, || mercy_extra_with(&quick_config(0), "hibp_check", MOCK_PWNED_PASSWORD));
+    assert!(output.contains("(offline?)"), "{}", output);
+}
+
+#[test]
+fn external_ip_from_the_echo_service() {
+    let _mocks = mocks();
+
+    assert_eq!(mercy_external_ip(false).expect("address").to_string(), MOCK_EXTERNAL_IP);
+    assert_eq!(mercy_extra("external_ip", ""), MOCK_EXTERNAL_IP);
+}
+
+#[test]
+fn http_headers_from_the_mock_server() {
+    let mocks = mocks();
+
+    let response = mercy_http_headers(&mocks.http.url("/login"), &HttpHeadersOptions::default()).expect("response");
+    assert_eq!(response.status, 200);
+    assert!(response.headers.iter().any(|header| header.name == "content-type"));
+
+    // The banner service does not speak HTTP
+    assert!(mercy_http_headers(&format!("http://{}/", mocks.banner.addr()), &HttpHeadersOptions::default()).is_err());
+}
+
+// The value of header `name` in the request `index` of what `http` received
+fn sent(http: &MockHttp, index: usize, name: &str) -> Option<String> {
+    let received = http.received();
+    received[index].1.iter().find(|(header, _)| header == name).map(|(_, value)| value.clone())
+}
+
+#[test]
+fn http_identity_arrives_as_configured() {
+    let mocks = mocks();
+    let url = mocks.http.url("/login");
+    let version = format!("mercy/{}", env!("CARGO_PKG_VERSION"));
+
+    // Nothing set: mercy names itself
+    mercy_http_headers(&url, &HttpHeadersOptions::default()).expect("response");
+    assert_eq!(sent(&mocks.http, 0, "user-agent"), Some(version.clone()));
+    assert_eq!(sent(&mocks.http, 0, "accept-language"), None);
+
+    set_http_identity(HttpIdentity {
+        user_agent: Some(UserAgent::Curl),
+        accept_language: Some("en-GB".to_string()),
+        headers: vec![("X-Team".to_string(), "red".to_string()), ("X-Trace".to_string(), "default".to_string())]
+    });
+
+    mercy_extra("http_headers", &url);
+    assert!(mercy_malicious("status", MOCK_MALICIOUS_DOMAIN).starts_with("Malicious"));
+    for index in [1, 2] {
+        assert!(sent(&mocks.http, index, "user-agent").is_some_and(|agent| agent.starts_with("curl/")), "{:?}", mocks.http.received()[index]);
+        assert_eq!(sent(&mocks.http, index, "accept-language").as_deref(), Some("en-GB"));
+        assert_eq!(sent(&mocks.http, index, "x-team").as_deref(), Some("red"));
+    }
+
+    // A call's options beat the defaults, header by header
+    mercy_extra("http_headers", &format!("{} user_agent=mercy accept_language=fr header=X-Trace:call", url));
+    assert_eq!(sent(&mocks.http, 3, "user-agent"), Some(version));
+    assert_eq!(sent(&mocks.http, 3, "accept-language").as_deref(), Some("fr"));
+    assert_eq!((sent(&mocks.http, 3, "x-team").as_deref(), sent(&mocks.http, 3, "x-trace").as_deref()), (Some("red"), Some("call")));
+
+    // The config's User-Agent sits between the call's and the default one
+    let config = MercyConfig::builder().user_agent(UserAgent::Custom("audit-bot/2".to_string())).build().expect("valid config");
+    with_mercy_config(&config, || {
+        mercy_extra("http_headers", &url);
+        mercy_extra("http_headers", &format!("{} user_agent=browser", url));
+    });
+    assert_eq!(sent(&mocks.http, 4, "user-agent").as_deref(), Some("audit-bot/2"));
+    assert!(sent(&mocks.http, 5, "user-agent").is_some_and(|agent| agent.starts_with("Mozilla/5.0")));
+    assert_eq!(sent(&mocks.http, 5, "x-team").as_deref(), Some("red"));
+
+    set_http_identity(HttpIdentity::default());
+}
@@ -0,0 +1,62 @@
+// Offline password strength: common passwords and their patterns are weak, long random strings are strong
+
+use mercy::{MercyRng, mercy_extra, mercy_password_strength};
+
+// Printable ASCII, space excluded
+const PRINTABLE: &[u8] = b"!\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+
+#[test]
+fn password123_is_weak() {
+    let strength = mercy_password_strength("password123");
+    assert_eq!((strength.verdict, strength.length, strength.classes.lowercase, strength.classes.digits), ("weak", 11, 8, 3));
+    let patterns: Vec<(&str, usize, &str)> = strength.patterns.iter().map(|pattern| (pattern.kind, pattern.offset, pattern.text.as_str())).collect();
+    assert_eq!(patterns, vec![("common word", 0, "password"), ("sequence", 8, "123")]);
+    assert!(strength.entropy_bits < 40.0, "{}", strength.entropy_bits);
+    assert!(strength.reasons.contains(&"contains the common password word \"password\"".to_string()), "{:?}", strength.reasons);
+    assert!(strength.reasons.contains(&"contains the sequence \"123\"".to_string()), "{:?}", strength.reasons);
+
+    let output = mercy_extra("password_strength", "password123");
+    assert!(output.starts_with("Verdict: weak\nEntropy: about "), "{}", output);
+    assert!(output.contains("\nLength: 11 (8 lowercase, 3 digits)\nPattern: common word \"password\" at 0\nPattern: sequence \"123\" at 8\n"), "{}", output);
+}
+
+#[test]
+fn guessable_passwords_are_weak() {
+    // Substitutions, capitals, walks, years and repeats do not make a common password strong
+    for (password, kind, text) in [
+        ("P@ssw0rd!", "common word", "P@ssw0rd"),
+        ("Summer2024", "year", "2024"),
+        ("qwerty123456", "keyboard walk", "123456"),
+        ("1qaz2wsx3edc", "keyboard walk", "1qaz"),
+        ("aaaaaaaaaaaa", "repeat", "aaaaaaaaaaaa"),
+        ("abcdefgh", "sequence", "abcdefgh"),
+        ("M0nk3y!!", "common word", "M0nk3y")
+    ] {
+        let strength = mercy_password_strength(password);
+        assert_ne!(strength.verdict, "strong", "{}: {:?}", password, strength);
+        assert!(strength.patterns.iter().any(|pattern| (pattern.kind, pattern.text.as_str()) == (kind, text)), "{}: {:?}", password, strength.patterns);
+    }
+    for password in ["", "Xk9#", "Zq7!vM2"] {
+        let strength = mercy_password_strength(password);
+        assert_eq!(strength.verdict, "weak", "{:?}", password);
+    }
+    assert_eq!(mercy_password_strength("").reasons[0], "empty");
+    assert_eq!(mercy_password_strength("Zq7!vM2").reasons[0], "only 7 characters, fewer than 8");
+}
+
+#[test]
+fn random_20_character_strings_are_strong() {
+    let strength = mercy_password_strength("q7#Vx!2mR9$kLp@4Zt&w");
+    let classes = &strength.classes;
+    assert_eq!((strength.verdict, classes.lowercase, classes.uppercase, classes.digits, classes.symbols), ("strong", 7, 4, 4, 5));
+    assert!(strength.entropy_bits >= 65.0, "{}", strength.entropy_bits);
+    assert!(mercy_extra("password_strength", "q7#Vx!2mR9$kLp@4Zt&w").starts_with("Verdict: strong\n"));
+
+    // Whatever short patterns chance puts in them, twenty random printable characters stay strong
+    let mut rng = MercyRng::seeded(291);
+    for _ in 0..200 {
+        let password: String = (0..20).map(|_| PRINTABLE[rng.below(PRINTABLE.len())] as char).collect();
+        let strength = mercy_password_strength(&password);
+        assert_eq!(strength.verdict, "strong", "{}: {:?}", password, strength);
+    }
+}
@@ -0,0 +1,64 @@
+// File names that are not UTF-8 and paths longer than Windows' MAX_PATH, hashed and reported losslessly
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use mercy::{mercy_hash, mercy_hash_file, path_from_string, path_is_utf8, path_to_string};
+
+const FOX: &str = "The quick brown fox jumps over the lazy dog";
+const FOX_SHA256: &str = "d7a8fbb307d7809469ca9abcb0082e4f8d5651e46d3cdb762d02d0bf37c9e592";
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_names_hash_and_come_back_exactly() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    use mercy::{TimelineFormat, TimelineOptions, write_timeline};
+
+    let dir = fixture_dir("paths-non-utf8");
+    // Bytes a dropper might use: invalid UTF-8, and a "%" that has to be escaped too
+    let path = dir.join(OsStr::from_bytes(b"dropper-\xff\xfe%.bin"));
+    fs::write(&path, FOX).expect("fixture");
+    assert!(path.to_str().is_none());
+
+    assert_eq!(mercy_hash_file(&path, "sha2_256").expect("hashes"), FOX_SHA256);
+    assert_eq!(mercy_hash("sha2_256", FOX), FOX_SHA256);
+
+    let text = path_to_string(&path);
+    assert!(!path_is_utf8(&path));
+    assert!(text.ends_with("/dropper-%FF%FE%25.bin"), "{}", text);
+    assert_eq!(path_from_string(&text, true), path);
+    assert_eq!(mercy_hash_file(path_from_string(&text, true), "md5").expect("hashes"), "9e107d9d372bb6826bd81d3542a419d6");
+
+    // A walk reports the name encoded and flagged
+    let options = TimelineOptions { format: TimelineFormat::Ndjson, ..TimelineOptions::default() };
+    let mut records = Vec::new();
+    write_timeline(&dir, &options, &mut records).expect("timeline");
+    let records = String::from_utf8(records).expect("UTF-8 records");
+    let record = records.lines().find(|line| line.contains("dropper-")).expect("the file's record");
+    assert!(record.contains("\"path_encoded\":true") && record.contains("dropper-%FF%FE%25.bin"), "{}", record);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+// Windows needs the \\?\ prefix past MAX_PATH, which the standard library adds; elsewhere this is simply a long path
+#[test]
+fn paths_longer_than_max_path_hash() {
+    let dir = fixture_dir("paths-long");
+    let mut path = dir.clone();
+    for part in ["a", "b", "c"] {
+        path.push(part.repeat(100));
+    }
+    fs::create_dir_all(&path).expect("fixture");
+    path.push("sample.txt");
+    fs::write(&path, FOX).expect("fixture");
+    assert!(path.as_os_str().len() > 300);
+
+    assert_eq!(mercy_hash_file(&path, "sha2_256").expect("hashes"), FOX_SHA256);
+    assert!(path_is_utf8(&path));
+    assert_eq!(path_from_string(&path_to_string(&path), false), path);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
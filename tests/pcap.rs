@@ -0,0 +1,184 @@
+// The counters of a small capture, written as pcap in both byte orders and as pcapng
+
+mod common;
+
+use std::{fs, net::IpAddr, path::Path};
+
+use common::*;
+use mercy::{PcapSummary, mercy_extra, mercy_pcap_summary};
+
+// 2023-11-14T22:13:20Z
+const START: u64 = 1_700_000_000;
+
+fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    [&[0x02, 0, 0, 0, 0, 0x01, 0x02, 0, 0, 0, 0, 0x02][..], &ethertype.to_be_bytes(), payload].concat()
+}
+
+fn ipv4(source: [u8; 4], destination: [u8; 4], protocol: u8, segment: &[u8]) -> Vec<u8> {
+    let mut header = vec![0x45, 0];
+    header.extend((20 + segment.len() as u16).to_be_bytes());
+    header.extend([0, 0, 0, 0, 64, protocol, 0, 0]);
+    header.extend(source);
+    header.extend(destination);
+    [header, segment.to_vec()].concat()
+}
+
+fn ipv6(source: &str, destination: &str, next: u8, segment: &[u8]) -> Vec<u8> {
+    let address = |text: &str| match text.parse::<IpAddr>() {
+        Ok(IpAddr::V6(address)) => address.octets(),
+        _ => panic!("IPv6 address")
+    };
+    let mut header = vec![0x60, 0, 0, 0];
+    header.extend((segment.len() as u16).to_be_bytes());
+    header.extend([next, 64]);
+    header.extend(address(source));
+    header.extend(address(destination));
+    [header, segment.to_vec()].concat()
+}
+
+fn tcp(port: u16) -> Vec<u8> {
+    [&49_152u16.to_be_bytes()[..], &port.to_be_bytes(), &[0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x02, 0xFF, 0xFF, 0, 0, 0, 0]].concat()
+}
+
+fn udp(port: u16) -> Vec<u8> {
+    [&53_000u16.to_be_bytes()[..], &port.to_be_bytes(), &[0, 8, 0, 0]].concat()
+}
+
+// (microseconds after START, frame, original length when the capture cut the frame short)
+fn packets() -> Vec<(u64, Vec<u8>, Option<u32>)> {
+    let client = [10, 0, 0, 1];
+    let vlan_tcp = [&[0x00, 0x0A, 0x08, 0x00][..], &ipv4([10, 0, 0, 2], [192, 0, 2, 80], 6, &tcp(80))].concat();
+    vec![
+        (0, ethernet(0x0800, &ipv4(client, [93, 184, 216, 34], 6, &tcp(443))), None),
+        (500_000, ethernet(0x0800, &ipv4(client, [93, 184, 216, 34], 6, &tcp(443))), Some(1514)),
+        (1_000_000, ethernet(0x0800, &ipv4([10, 0, 0, 2], [8, 8, 8, 8], 17, &udp(53))), None),
+        (1_250_000, ethernet(0x0800, &ipv4(client, [8, 8, 8, 8], 17, &udp(53))), None),
+        (1_500_000, ethernet(0x0800, &ipv4([10, 0, 0, 3], client, 1, &[8, 0, 0, 0, 0, 1, 0, 1])), None),
+        (2_000_000, ethernet(0x86DD, &ipv6("2001:db8::1", "2001:db8::2", 6, &tcp(22))), None),
+        (2_250_000, ethernet(0x0806, &[0; 28]), None),
+        // An IPv4 header cut off after ten bytes
+        (2_400_000, ethernet(0x0800, &ipv4(client, [8, 8, 8, 8], 6, &tcp(443))[..10]), None),
+        (2_450_000, ethernet(0x0800, &ipv4([10, 0, 0, 2], [192, 0, 2, 1], 47, &[0, 0, 0x08, 0x00])), None),
+        (2_500_000, ethernet(0x8100, &vlan_tcp), None)
+    ]
+}
+
+fn pcap(big: bool, nanosecond: bool) -> Vec<u8> {
+    let word = |value: u32| if big { value.to_be_bytes() } else { value.to_le_bytes() };
+    let half = |value: u16| if big { value.to_be_bytes() } else { value.to_le_bytes() };
+
+    let mut file = word(if nanosecond { 0xA1B2_3C4D } else { 0xA1B2_C3D4 }).to_vec();
+    file.extend(half(2));
+    file.extend(half(4));
+    file.extend([0; 8]);
+    file.extend(word(65_535));
+    file.extend(word(1));
+    for (micros, frame, original) in packets() {
+        let fraction = (micros % 1_000_000) as u32;
+        file.extend(word((START + micros / 1_000_000) as u32));
+        file.extend(word(if nanosecond { fraction * 1000 } else { fraction }));
+        file.extend(word(frame.len() as u32));
+        file.extend(word(original.unwrap_or(frame.len() as u32)));
+        file.extend(frame);
+    }
+    file
+}
+
+fn pcapng() -> Vec<u8> {
+    let block = |kind: u32, body: &[u8]| {
+        let padded = body.len().div_ceil(4) * 4;
+        let length = (12 + padded) as u32;
+        let mut block = [kind.to_le_bytes(), length.to_le_bytes()].concat();
+        block.extend(body);
+        block.resize(8 + padded, 0);
+        block.extend(length.to_le_bytes());
+        block
+    };
+
+    let mut file = block(0x0A0D_0D0A, &[&0x1A2B_3C4Du32.to_le_bytes()[..], &[1, 0, 0, 0], &[0xFF; 8]].concat());
+    file.extend(block(1, &[&1u16.to_le_bytes()[..], &[0, 0], &65_535u32.to_le_bytes()].concat()));
+    for (micros, frame, original) in packets() {
+        let ticks = START * 1_000_000 + micros;
+        let mut body = 0u32.to_le_bytes().to_vec();
+        body.extend(((ticks >> 32) as u32).to_le_bytes());
+        body.extend((ticks as u32).to_le_bytes());
+        body.extend((frame.len() as u32).to_le_bytes());
+        body.extend(original.unwrap_or(frame.len() as u32).to_le_bytes());
+        body.extend(frame);
+        file.extend(block(6, &body));
+    }
+    file
+}
+
+fn check(summary: &PcapSummary, format: &str) {
+    assert_eq!((summary.format.as_str(), summary.link_type.as_str()), (format, "Ethernet"));
+    assert_eq!((summary.packets, summary.malformed), (9, 1), "{}", format);
+
+    let bytes: u64 = packets().iter().enumerate().filter(|(index, _)| *index != 7).map(|(_, (_, frame, original))| u64::from(original.unwrap_or(frame.len() as u32))).sum();
+    assert_eq!(summary.bytes, bytes, "{}", format);
+
+    assert_eq!(summary.first_packet.as_deref(), Some("2023-11-14T22:13:20Z"), "{}", format);
+    assert_eq!(summary.last_packet.as_deref(), Some("2023-11-14T22:13:22.5Z"), "{}", format);
+    assert!((summary.duration_secs - 2.5).abs() < 1e-9, "{}: {}", format, summary.duration_secs);
+
+    let protocols = &summary.protocols;
+    assert_eq!((protocols.tcp, protocols.udp, protocols.icmp, protocols.other), (4, 2, 1, 2), "{}", format);
+
+    // 10.0.0.1 and 10.0.0.2 sent three packets each, the first more bytes (a 1514-byte frame); then by bytes
+    let talkers: Vec<(String, u64)> = summary.top_talkers.iter().map(|talker| (talker.address.to_string(), talker.packets)).collect();
+    assert_eq!(talkers, [("10.0.0.1".to_string(), 3), ("10.0.0.2".to_string(), 3), ("2001:db8::1".to_string(), 1), ("10.0.0.3".to_string(), 1)], "{}", format);
+    assert_eq!(summary.top_talkers[0].bytes, 54 + 1514 + 42, "{}", format);
+
+    let ports: Vec<(u16, &str, u64)> = summary.top_ports.iter().map(|port| (port.port, port.protocol.as_str(), port.packets)).collect();
+    assert_eq!(ports, [(53, "udp", 2), (443, "tcp", 2), (22, "tcp", 1), (80, "tcp", 1)], "{}", format);
+}
+
+fn write(dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = dir.join(name);
+    fs::write(&path, data).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+#[test]
+fn every_capture_format_gives_the_same_counters() {
+    let dir = fixture_dir("pcap-formats");
+
+    for (name, data, format) in [
+        ("little-micro.pcap", pcap(false, false), "pcap"),
+        ("big-nano.pcap", pcap(true, true), "pcap"),
+        ("capture.pcapng", pcapng(), "pcapng")
+    ] {
+        let path = write(&dir, name, &data);
+        check(&mercy_pcap_summary(&path).expect(name), format);
+
+        let text = mercy_extra("pcap_summary", &path);
+        assert!(text.contains("Packets: 9 (") && text.contains("Malformed records skipped: 1") && text.contains("53/udp (2 packets)"), "{}", text);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn damaged_captures_keep_what_came_before() {
+    let dir = fixture_dir("pcap-damaged");
+
+    // The last record cut off part way through its frame
+    let mut truncated = pcap(false, false);
+    truncated.truncate(truncated.len() - 10);
+    let summary = mercy_pcap_summary(write(&dir, "truncated.pcap", &truncated)).expect("truncated");
+    assert_eq!((summary.packets, summary.malformed, summary.protocols.tcp), (8, 2, 3));
+    assert_eq!(summary.last_packet.as_deref(), Some("2023-11-14T22:13:22.45Z"));
+
+    // A record header claiming a frame far too large ends the read there
+    let mut huge = pcap(false, false);
+    let first = 24 + 16 + packets()[0].1.len();
+    huge[first + 8..first + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+    let summary = mercy_pcap_summary(write(&dir, "huge.pcap", &huge)).expect("huge");
+    assert_eq!((summary.packets, summary.malformed), (1, 1));
+
+    let error = mercy_pcap_summary(write(&dir, "text.pcap", b"not a capture at all")).expect_err("not a capture");
+    assert!(error.contains("not a pcap or pcapng capture"), "{}", error);
+    assert!(mercy_pcap_summary(write(&dir, "empty.pcap", b"")).expect_err("empty").contains("is empty"));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,195 @@
+// Hand-built PE32 and PE32+ images: every header field read back, the packer tells noted, and damaged headers refused by name
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use mercy::{PeInfo, mercy_extra, mercy_pe_info};
+
+// 2024-02-29T12:00:00Z
+const LINKED: u32 = 0x65e0_71c0;
+
+// Where the fixture keeps things
+const PE_OFFSET: usize = 0x80;
+const OVERLAY: usize = 0x800;
+const CERTIFICATE: usize = 0x900;
+
+fn put16(image: &mut [u8], offset: usize, value: u16) {
+    image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put32(image: &mut [u8], offset: usize, value: u32) {
+    image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// A DLL with .text (raw data of every byte value), .rdata holding three imports, an empty
+// rwx UPX0, then an overlay and an Authenticode certificate table at the end
+fn image(machine: u16, plus: bool) -> Vec<u8> {
+    let mut image = vec![0u8; CERTIFICATE + 0x40];
+    image[..2].copy_from_slice(b"MZ");
+    put32(&mut image, 0x3c, PE_OFFSET as u32);
+
+    // COFF file header
+    let optional_size: u16 = if plus { 240 } else { 224 };
+    image[PE_OFFSET..PE_OFFSET + 4].copy_from_slice(b"PE\0\0");
+    put16(&mut image, PE_OFFSET + 4, machine);
+    put16(&mut image, PE_OFFSET + 6, 3);
+    put32(&mut image, PE_OFFSET + 8, LINKED);
+    put16(&mut image, PE_OFFSET + 20, optional_size);
+    put16(&mut image, PE_OFFSET + 22, 0x2102);
+
+    // Optional header: the image base and the data directories move in PE32+
+    let optional = PE_OFFSET + 24;
+    let directories = optional + if plus { 112 } else { 96 };
+    put16(&mut image, optional, if plus { 0x20b } else { 0x10b });
+    put32(&mut image, optional + 16, 0x1010);
+    match plus {
+        true => image[optional + 24..optional + 32].copy_from_slice(&0x1_8000_0000u64.to_le_bytes()),
+        false => put32(&mut image, optional + 28, 0x1000_0000)
+    }
+    put32(&mut image, optional + 60, 0x400);
+    put16(&mut image, optional + 68, 2);
+    put32(&mut image, directories - 4, 16);
+    put32(&mut image, directories + 8, 0x2000);
+    put32(&mut image, directories + 12, 80);
+    put32(&mut image, directories + 32, CERTIFICATE as u32);
+    put32(&mut image, directories + 36, 0x40);
+
+    // Section table: name, virtual size and address, raw size and offset, characteristics
+    let sections = [
+        (".text", 0x1a0, 0x1000, 0x200, 0x400, 0x6000_0020),
+        (".rdata", 0x120, 0x2000, 0x200, 0x600, 0x4000_0040),
+        ("UPX0", 0x4000, 0x3000, 0, 0, 0xe000_0080)
+    ];
+    for (index, (name, virtual_size, address, raw_size, raw_offset, characteristics)) in sections.iter().enumerate() {
+        let entry = optional + optional_size as usize + index * 40;
+        image[entry..entry + name.len()].copy_from_slice(name.as_bytes());
+        put32(&mut image, entry + 8, *virtual_size);
+        put32(&mut image, entry + 12, *address);
+        put32(&mut image, entry + 16, *raw_size);
+        put32(&mut image, entry + 20, *raw_offset);
+        put32(&mut image, entry + 36, *characteristics);
+    }
+
+    // .text and the overlay: every byte value equally often
+    for offset in 0..0x200 {
+        image[0x400 + offset] = offset as u8;
+    }
+    for offset in 0..0x100 {
+        image[OVERLAY + offset] = offset as u8;
+    }
+
+    // .rdata: three import descriptors and the terminating zero one, then the names
+    for (index, name) in ["KERNEL32.dll", "ADVAPI32.dll", "WININET.dll"].iter().enumerate() {
+        let name_rva = 0x2060 + index * 0x10;
+        put32(&mut image, 0x600 + index * 20 + 12, name_rva as u32);
+        let at = 0x600 + name_rva - 0x2000;
+        image[at..at + name.len()].copy_from_slice(name.as_bytes());
+    }
+
+    image[CERTIFICATE..CERTIFICATE + 8].copy_from_slice(&[0x40, 0, 0, 0, 0, 2, 2, 0]);
+    image
+}
+
+fn write(dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = dir.join(name);
+    fs::write(&path, data).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+fn parse(dir: &Path, data: &[u8]) -> Result<PeInfo, String> {
+    mercy_pe_info(write(dir, "sample.dll", data))
+}
+
+#[test]
+fn headers_sections_imports_and_overlay() {
+    let dir = fixture_dir("pe-fields");
+    let info = parse(&dir, &image(0x14c, false)).expect("PE32");
+
+    assert_eq!((info.machine.as_str(), info.format.as_str(), info.dll, info.subsystem.as_str()), ("x86", "PE32", true, "Windows GUI"));
+    assert_eq!((info.compiled.as_deref(), info.timestamp), (Some("2024-02-29T12:00:00Z"), LINKED));
+    assert_eq!((info.entry_point, info.image_base, info.file_size), (0x1010, 0x1000_0000, 0x940));
+
+    let sections: Vec<_> = info.sections.iter().map(|section| (section.name.as_str(), section.virtual_address, section.virtual_size, section.raw_offset, section.raw_size, section.permissions.as_str())).collect();
+    assert_eq!(sections, vec![
+        (".text", 0x1000, 0x1a0, 0x400, 0x200, "r-x"),
+        (".rdata", 0x2000, 0x120, 0x600, 0x200, "r--"),
+        ("UPX0", 0x3000, 0x4000, 0, 0, "rwx")
+    ]);
+    assert_eq!((info.section_count, info.sections[0].entropy, info.sections[2].entropy), (3, 8.0, 0.0));
+    assert!(info.sections[1].entropy > 0.0 && info.sections[1].entropy < 4.0, "{}", info.sections[1].entropy);
+
+    assert_eq!(info.imports, vec!["KERNEL32.dll", "ADVAPI32.dll", "WININET.dll"]);
+
+    // The certificate table at the very end is not overlay; the bytes between it and .rdata are
+    assert!(info.signed);
+    let overlay = info.overlay.as_ref().expect("overlay");
+    assert_eq!((overlay.offset, overlay.size, overlay.entropy), (OVERLAY as u64, 0x100, 8.0));
+    assert_eq!(info.notes, vec![
+        "Section .text has entropy 8.00; packed or encrypted",
+        "Section UPX0 is writable and executable",
+        "Executable section UPX0 has no data on disk; unpacked at run time",
+        "256 bytes of overlay after the last section"
+    ]);
+
+    // PE32+ keeps a 64-bit image base and its directories further on
+    let info = parse(&dir, &image(0xaa64, true)).expect("PE32+");
+    assert_eq!((info.machine.as_str(), info.format.as_str(), info.image_base), ("ARM64", "PE32+", 0x1_8000_0000));
+    assert_eq!((info.imports.len(), info.overlay.map(|overlay| overlay.size)), (3, Some(0x100)));
+    assert_eq!(parse(&dir, &image(0x8664, true)).expect("x64").machine, "x64");
+    assert_eq!(parse(&dir, &image(0x1234, false)).expect("unknown").machine, "unknown (0x1234)");
+
+    // The string calls
+    let path = write(&dir, "sample.dll", &image(0x14c, false));
+    let output = mercy_extra("pe_info", &path);
+    for line in ["Machine: x86 (PE32, DLL)\n", "Compiled: 2024-02-29T12:00:00Z (0x65e071c0)\n", "Sections (3):\n", "Imports (3):\n  KERNEL32.dll\n", "Overlay: 256 bytes at 0x800 (entropy 8.00)\n"] {
+        assert!(output.contains(line), "{:?} missing from\n{}", line, output);
+    }
+    let json: serde_json::Value = serde_json::from_str(&mercy_extra("pe_info_json", &path)).expect("JSON");
+    assert_eq!((json["sections"][1]["name"].as_str(), json["imports"][2].as_str()), (Some(".rdata"), Some("WININET.dll")));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn damaged_headers_are_refused_by_name() {
+    let dir = fixture_dir("pe-damaged");
+    let good = image(0x8664, true);
+    let changed = |offset: usize, bytes: &[u8]| {
+        let mut image = good.clone();
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+        image
+    };
+
+    let cases: [(Vec<u8>, &str); 10] = [
+        (Vec::new(), "Not a PE file: missing MZ"),
+        (changed(0, b"ZM"), "Not a PE file: missing MZ"),
+        (b"MZ".to_vec(), "Truncated DOS header"),
+        (changed(0x3c, &0x10000u32.to_le_bytes()), "Not a PE file: PE header offset 0x10000 is past the end of the file"),
+        (changed(PE_OFFSET, b"PX"), "Not a PE file: missing PE signature at 0x80"),
+        (good[..PE_OFFSET + 6].to_vec(), "Truncated COFF file header"),
+        (changed(PE_OFFSET + 6, &200u16.to_le_bytes()), "Corrupted COFF file header: 200 sections (the loader allows 96)"),
+        (good[..0x100].to_vec(), "Truncated optional header"),
+        (changed(PE_OFFSET + 24, &0x107u16.to_le_bytes()), "Not a PE image: optional header magic 0x107 (0x10b or 0x20b expected)"),
+        (good[..0x1b0].to_vec(), "Truncated section table (3 sections declared, 1 present)")
+    ];
+    for (data, error) in cases {
+        assert_eq!(parse(&dir, &data).expect_err(error), error);
+    }
+
+    // Damage past the headers is a note, and the rest is still read
+    let cut = parse(&dir, &good[..0x700]).expect("cut short");
+    assert!(cut.notes.contains(&"Section .rdata raw data runs 256 bytes past the end of the file".to_string()), "{:?}", cut.notes);
+    assert_eq!((cut.imports.len(), cut.overlay.is_none()), (3, true));
+    let lost = parse(&dir, &changed(PE_OFFSET + 24 + 112 + 8, &0x9000u32.to_le_bytes())).expect("imports elsewhere");
+    assert!(lost.imports.is_empty() && lost.notes.contains(&"Import descriptor 0 at RVA 0x9000 is outside every section".to_string()), "{:?}", lost.notes);
+
+    // Cut anywhere, the file gives an answer or an error, never a panic
+    for length in 0..good.len() {
+        let _ = parse(&dir, &good[..length]);
+    }
+    assert!(mercy_extra("pe_info", &write(&dir, "text.dll", b"just text")).contains("Not a PE file: missing MZ"));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
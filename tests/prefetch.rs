@@ -0,0 +1,208 @@
+// Prefetch files of every supported format version, built field by field, plain and MAM compressed
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use mercy::{PrefetchInfo, mercy_forensics, mercy_prefetch};
+
+// 2024-03-01T10:00:00Z and the seven run times before it, a day apart, as FILETIMEs
+const LAST_RUN: u64 = 133_537_608_000_000_000;
+const DAY: u64 = 864_000_000_000;
+const VOLUME_CREATED: u64 = 132_223_104_000_000_000;
+
+const FILES: [&str; 3] = [
+    "\\VOLUME{01d5a2f0c1e2b3a4-1a2b3c4d}\\WINDOWS\\SYSTEM32\\NTDLL.DLL",
+    "\\VOLUME{01d5a2f0c1e2b3a4-1a2b3c4d}\\WINDOWS\\SYSTEM32\\KERNEL32.DLL",
+    "\\VOLUME{01d5a2f0c1e2b3a4-1a2b3c4d}\\USERS\\ALICE\\DOWNLOADS\\PAYLOAD.EXE"
+];
+const DEVICE: &str = "\\VOLUME{01d5a2f0c1e2b3a4-1a2b3c4d}";
+
+// Where each version keeps its fields: metrics array offset, run time offset and count, run count offset, volume entry size
+struct Layout {
+    version: u32,
+    metrics: u32,
+    run_times: (usize, usize),
+    run_count: usize,
+    volume_entry: usize
+}
+
+const LAYOUTS: [Layout; 6] = [
+    Layout { version: 17, metrics: 0x98, run_times: (0x78, 1), run_count: 0x90, volume_entry: 40 },
+    Layout { version: 23, metrics: 0xF0, run_times: (0x80, 1), run_count: 0x98, volume_entry: 104 },
+    Layout { version: 26, metrics: 0x130, run_times: (0x80, 8), run_count: 0xD0, volume_entry: 104 },
+    Layout { version: 30, metrics: 0x130, run_times: (0x80, 8), run_count: 0xD0, volume_entry: 96 },
+    // The shorter Windows 10 file information block moves the run count up
+    Layout { version: 30, metrics: 0x128, run_times: (0x80, 8), run_count: 0xC8, volume_entry: 96 },
+    Layout { version: 31, metrics: 0x130, run_times: (0x80, 8), run_count: 0xD0, volume_entry: 96 }
+];
+
+fn put32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+// An uncompressed SCCA file for "PAYLOAD.EXE", run 42 times
+fn scca(layout: &Layout) -> Vec<u8> {
+    let filenames: Vec<u8> = FILES.iter().flat_map(|file| [utf16(file), vec![0, 0]].concat()).collect();
+    let (filenames_offset, volumes_offset) = (0x200, 0x200 + filenames.len());
+    let device = utf16(DEVICE);
+
+    let mut data = vec![0u8; volumes_offset + layout.volume_entry + device.len() + 2];
+    put32(&mut data, 0, layout.version);
+    data[4..8].copy_from_slice(b"SCCA");
+    let size = data.len() as u32;
+    put32(&mut data, 12, size);
+    let executable = utf16("PAYLOAD.EXE");
+    data[16..16 + executable.len()].copy_from_slice(&executable);
+    put32(&mut data, 76, 0x1A2B_3C4D);
+
+    put32(&mut data, 0x54, layout.metrics);
+    put32(&mut data, 0x64, filenames_offset as u32);
+    put32(&mut data, 0x68, filenames.len() as u32);
+    put32(&mut data, 0x6C, volumes_offset as u32);
+    put32(&mut data, 0x70, 1);
+
+    let (times, count) = layout.run_times;
+    for index in 0..count {
+        put64(&mut data, times + index * 8, LAST_RUN - index as u64 * DAY);
+    }
+    put32(&mut data, layout.run_count, 42);
+
+    data[filenames_offset..volumes_offset].copy_from_slice(&filenames);
+    put32(&mut data, volumes_offset, layout.volume_entry as u32);
+    put32(&mut data, volumes_offset + 4, DEVICE.len() as u32);
+    put64(&mut data, volumes_offset + 8, VOLUME_CREATED);
+    put32(&mut data, volumes_offset + 16, 0x1A2B_3C4D);
+    let path = volumes_offset + layout.volume_entry;
+    data[path..path + device.len()].copy_from_slice(&device);
+    data
+}
+
+// LZXPRESS Huffman with every symbol 9 bits long, so a symbol's code is its own number:
+// bytes are literals, runs of a repeated byte are matches at offset 1
+fn mam(data: &[u8]) -> Vec<u8> {
+    assert!(data.len() < 65_536, "one Huffman block only");
+    let mut symbols = Vec::new();
+    let mut position = 0;
+    while position < data.len() {
+        let run = data[position..].iter().take(18).take_while(|byte| **byte == data[position]).count();
+        if position > 0 && data[position - 1] == data[position] && run >= 3 {
+            // Offset bits 0 (offset 1), length 3 to 17 in the low four bits
+            let length = run.min(17);
+            symbols.push(256 + length - 3);
+            position += length;
+        } else {
+            symbols.push(usize::from(data[position]));
+            position += 1;
+        }
+    }
+
+    // 16-bit little-endian words filled from the most significant bit
+    let mut words: Vec<u16> = Vec::new();
+    let (mut current, mut used) = (0u32, 0);
+    for symbol in symbols {
+        current = (current << 9) | symbol as u32;
+        used += 9;
+        while used >= 16 {
+            words.push((current >> (used - 16)) as u16);
+            used -= 16;
+            current &= (1 << used) - 1;
+        }
+    }
+    if used > 0 {
+        words.push((current << (16 - used)) as u16);
+    }
+    words.extend([0, 0]);
+
+    let mut compressed = b"MAM\x04".to_vec();
+    compressed.extend((data.len() as u32).to_le_bytes());
+    compressed.extend([0x99; 256]);
+    compressed.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+    compressed
+}
+
+fn check(info: &PrefetchInfo, layout: &Layout, compressed: bool) {
+    let label = format!("version {} at metrics 0x{:x}{}", layout.version, layout.metrics, if compressed { ", compressed" } else { "" });
+    assert_eq!((info.version, info.compressed), (layout.version, compressed), "{}", label);
+    assert_eq!((info.executable.as_str(), info.hash.as_str(), info.run_count), ("PAYLOAD.EXE", "1A2B3C4D", 42), "{}", label);
+    assert_eq!(info.referenced_files, FILES, "{}", label);
+
+    let expected_times: Vec<String> = (0..layout.run_times.1).map(|days| format!("2024-0{}T10:00:00Z", ["3-01", "2-29", "2-28", "2-27", "2-26", "2-25", "2-24", "2-23"][days])).collect();
+    assert_eq!(info.last_run_times, expected_times, "{}", label);
+
+    assert_eq!(info.volumes.len(), 1, "{}", label);
+    assert_eq!((info.volumes[0].device_path.as_str(), info.volumes[0].serial.as_str()), (DEVICE, "1A2B3C4D"), "{}", label);
+    assert_eq!(info.volumes[0].created.as_deref(), Some("2020-01-01T00:00:00Z"), "{}", label);
+}
+
+fn write(dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = dir.join(name);
+    fs::write(&path, data).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+#[test]
+fn every_format_version_parses() {
+    let dir = fixture_dir("prefetch-versions");
+    let formats = ["Windows XP/2003", "Windows Vista/7", "Windows 8.1", "Windows 10", "Windows 10", "Windows 11"];
+
+    for (layout, format) in LAYOUTS.iter().zip(formats) {
+        let path = write(&dir, &format!("PAYLOAD.EXE-1A2B3C4D.v{}-{:x}.pf", layout.version, layout.metrics), &scca(layout));
+        let info = mercy_prefetch(&path).expect("parses");
+        check(&info, layout, false);
+        assert_eq!(info.format, format);
+
+        let report = mercy_forensics("prefetch", &path);
+        assert!(report.contains("Run count: 42") && report.contains("Referenced files (3):"), "{}", report);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+// Windows 10 and 11 compress their prefetch files; XP to 8.1 never did, but the container is the same
+#[test]
+fn mam_compressed_files_parse_as_the_plain_ones() {
+    let dir = fixture_dir("prefetch-mam");
+
+    for layout in &LAYOUTS[3..] {
+        let plain = scca(layout);
+        let compressed = mam(&plain);
+        assert!(compressed.len() < plain.len() + 256, "the runs of zeros should compress");
+
+        let path = write(&dir, &format!("v{}-{:x}.pf", layout.version, layout.metrics), &compressed);
+        check(&mercy_prefetch(&path).expect("parses"), layout, true);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn damaged_prefetch_files_are_refused() {
+    let dir = fixture_dir("prefetch-damaged");
+    let plain = scca(&LAYOUTS[3]);
+
+    let mut unknown = plain.clone();
+    put32(&mut unknown, 0, 29);
+    let cases = [
+        ("not-scca.pf", b"MZ\x90\x00 not a prefetch file".to_vec(), "missing SCCA signature"),
+        ("unknown.pf", unknown, "Unsupported prefetch version 29"),
+        ("lzx.pf", [b"MAM\x03".as_slice(), &mam(&plain)[4..]].concat(), "Unsupported MAM compression method 3"),
+        ("truncated.pf", mam(&plain)[..200].to_vec(), "truncated")
+    ];
+    for (name, data, error) in cases {
+        let path = write(&dir, name, &data);
+        let message = mercy_prefetch(&path).expect_err(name);
+        assert!(message.contains(error), "{}: {}", name, message);
+    }
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,80 @@
+/*
+    The library prints nothing: what a call has to say comes back in its return value
+
+    Runs without the test harness, whose own output (and output capture) would hide what the
+    library writes. The test starts itself again as a child that makes the calls, and the
+    child's stdout and stderr have to stay empty.
+*/
+
+mod common;
+
+use std::{
+    env,
+    fs,
+    process::{Command, ExitCode}
+};
+
+use common::*;
+use mercy::{mercy_extra, mercy_hex, mercy_methods};
+
+const CHILD: &str = "MERCY_QUIET_CHILD";
+
+// Every hex and extra call with junk input, then a few with input they can work with
+fn calls() {
+    let _mocks = mocks();
+    let dir = fixture_dir("quiet");
+    let file = dir.join("sample.bin");
+    fs::write(&file, b"MZ\x90\x00 some text \xff\xfe\x00\x01 http://evil.example/payload").expect("fixture");
+    let file = file.to_str().expect("path");
+
+    for method in mercy_methods("hex") {
+        for input in ["!", "68656c6c6f", file] {
+            let _ = mercy_hex(&method.name, input);
+        }
+    }
+    for method in mercy_methods("extra") {
+        let _ = mercy_extra(&method.name, "!");
+    }
+
+    let extras = [
+        ("defang", "http://evil.example/payload"),
+        ("refang", "hxxp://evil[.]example"),
+        ("entropy_file", file),
+        ("strings", file),
+        ("file_type", file),
+        ("detect_encoding", file),
+        ("hash_identify", "5f4dcc3b5aa765d61d8327deb882cf99"),
+        ("password_strength", "password123"),
+        ("unicode_inspect", "p\u{0430}ypal\u{202E}"),
+        ("safe_display", "xn--80ak6aa92e.com\u{202E}"),
+        ("epoch_to_utc", "0"),
+        ("whois", MOCK_MALICIOUS_DOMAIN),
+        ("asn_bulk", "1.1.1.1, 10.0.0.1"),
+        ("hibp_check", MOCK_PWNED_PASSWORD),
+        ("no_such_call", "")
+    ];
+    for (call, input) in extras {
+        let _ = mercy_extra(call, input);
+    }
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+fn main() -> ExitCode {
+    if env::var_os(CHILD).is_some() {
+        calls();
+        return ExitCode::SUCCESS;
+    }
+
+    let child = Command::new(env::current_exe().expect("test binary")).env(CHILD, "1").output().expect("child runs");
+    let (stdout, stderr) = (String::from_utf8_lossy(&child.stdout), String::from_utf8_lossy(&child.stderr));
+
+    if !child.status.success() || !stdout.is_empty() || !stderr.is_empty() {
+        eprintln!("mercy_hex and mercy_extra must print nothing ({})", child.status);
+        eprintln!("--- stdout ---\n{}\n--- stderr ---\n{}", stdout, stderr);
+        return ExitCode::FAILURE;
+    }
+
+    println!("quiet: mercy_hex and mercy_extra printed nothing");
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,92 @@
+// Seeded generators repeat themselves exactly; unseeded ones do not
+
+use mercy::{CanaryKind, CanaryOptions, FuzzOptions, MercyRng, mercy_canary, mercy_canary_generate_with, mercy_fuzz_bytes, mercy_fuzz_strings, mercy_generate, mercy_uuid_v4};
+
+fn draws(rng: &mut MercyRng) -> Vec<u64> {
+    (0..64).map(|_| rng.next_u64()).collect()
+}
+
+#[test]
+fn seeded_generators_repeat_themselves() {
+    assert_eq!(draws(&mut MercyRng::seeded(245)), draws(&mut MercyRng::seeded(245)));
+    assert_ne!(draws(&mut MercyRng::seeded(245)), draws(&mut MercyRng::seeded(246)));
+    assert_eq!(MercyRng::seeded(245).seed(), Some(245));
+
+    let options = FuzzOptions { count: Some(12), seed: Some(245), categories: Vec::new() };
+    let payloads = |options: &FuzzOptions| {
+        let (strings, seed) = mercy_fuzz_strings(options).expect("strings");
+        (strings.into_iter().map(|string| string.payload).collect::<Vec<String>>(), seed)
+    };
+    assert_eq!(payloads(&options), payloads(&options));
+    assert_eq!(payloads(&options).1, 245);
+
+    let mutations = |options: &FuzzOptions| {
+        let (mutations, seed) = mercy_fuzz_bytes(b"GET /index.html HTTP/1.1", options).expect("mutations");
+        (mutations.into_iter().map(|mutation| (mutation.detail, mutation.hex)).collect::<Vec<(String, String)>>(), seed)
+    };
+    assert_eq!(mutations(&options), mutations(&options));
+
+    // The dispatcher takes the seed as an option and gives the same NDJSON
+    let output = mercy_generate("fuzz_bytes", "deadbeef count=20 seed=245 hex=true");
+    assert_eq!(output, mercy_generate("fuzz_bytes", "deadbeef count=20 seed=245 hex=true"));
+    assert!(output.lines().count() > 20, "{}", output);
+}
+
+#[test]
+fn unseeded_generators_differ() {
+    let mut os = MercyRng::os();
+    assert_eq!(os.seed(), None);
+    assert_ne!(draws(&mut os), draws(&mut MercyRng::os()));
+    assert!(!MercyRng::default().is_seeded());
+
+    // Each unseeded run draws a seed of its own from the OS, and reports it
+    let options = FuzzOptions { count: Some(12), ..FuzzOptions::default() };
+    let (_, first) = mercy_fuzz_strings(&options).expect("strings");
+    let (_, second) = mercy_fuzz_strings(&options).expect("strings");
+    assert_ne!(first, second);
+
+    // and that seed repeats the run afterwards
+    let hexes = |options: &FuzzOptions| {
+        let (mutations, seed) = mercy_fuzz_bytes(b"GET / HTTP/1.1", options).expect("mutations");
+        (mutations.into_iter().map(|mutation| mutation.hex).collect::<Vec<String>>(), seed)
+    };
+    let (unseeded, seed) = hexes(&options);
+    assert_eq!(hexes(&FuzzOptions { seed: Some(seed), ..options }), (unseeded, seed));
+
+    assert_ne!(mercy_uuid_v4(), mercy_uuid_v4());
+}
+
+#[test]
+fn secrets_refuse_a_seed_unless_told_it_is_insecure() {
+    let error = MercyRng::secret(Some(7), false).expect_err("a seeded secret");
+    assert!(error.contains("insecure_seeded_secrets"), "{}", error);
+
+    assert!(!MercyRng::secret(None, false).expect("OS randomness").is_seeded());
+    let mut seeded = MercyRng::secret(Some(7), true).expect("allowed for tests");
+    assert_eq!(draws(&mut seeded), draws(&mut MercyRng::seeded(7)));
+}
+
+#[test]
+fn canaries_take_a_seed_only_when_told_it_is_insecure() {
+    let seeded = CanaryOptions { domain: Some("canary.example.com".to_string()), seed: Some(245), insecure_seeded_secrets: false };
+    let error = mercy_canary_generate_with(CanaryKind::AwsKey, &seeded).expect_err("a seeded credential");
+    assert!(error.contains("insecure_seeded_secrets"), "{}", error);
+    assert!(mercy_canary("generate", "dns domain=canary.example.com seed=245").contains("insecure_seeded_secrets"));
+
+    // Allowed, the same seed gives the same key ID and secret, and another seed different ones
+    let allowed = CanaryOptions { insecure_seeded_secrets: true, ..seeded };
+    let key = |options: &CanaryOptions| {
+        let token = mercy_canary_generate_with(CanaryKind::AwsKey, options).expect("canary");
+        (token.token, token.secret)
+    };
+    assert_eq!(key(&allowed), key(&allowed));
+    assert_ne!(key(&allowed), key(&CanaryOptions { seed: Some(246), ..allowed.clone() }));
+    assert_ne!(key(&CanaryOptions { seed: None, ..allowed.clone() }), key(&CanaryOptions { seed: None, ..allowed.clone() }));
+
+    // The string call takes both options
+    let generate = || mercy_canary("generate_json", "url domain=canary.example.com seed=245 insecure_seeded_secrets=true");
+    let (first, second): (serde_json::Value, serde_json::Value) = (serde_json::from_str(&generate()).expect("JSON"), serde_json::from_str(&generate()).expect("JSON"));
+    assert_eq!(first["token"], second["token"]);
+    assert_eq!(first["token"].as_str(), Some(format!("https://canary.example.com/t/{}", first["id"].as_str().expect("id")).as_str()));
+    assert!(mercy_canary("generate", "dns domain=canary.example.com seed=x insecure_seeded_secrets=true").contains("Invalid seed 'x'"));
+}
@@ -0,0 +1,31 @@
+// Lookups from inside a caller's own Tokio runtime: the async entry point awaited, and the synchronous one called from a runtime thread
+
+mod common;
+
+use common::*;
+use mercy::{mercy_malicious, mercy_malicious_async, mercy_malicious_batch};
+
+// Both entry points give the same answers from wherever they are called
+async fn both_entry_points() {
+    let mocks = mocks();
+
+    let malicious = mercy_malicious_async("status", MOCK_MALICIOUS_DOMAIN).await.expect("async lookup");
+    assert!(malicious.starts_with("Malicious"), "{}", malicious);
+    let clean = mercy_malicious_async("status", "clean.example").await.expect("async lookup");
+    assert!(clean.contains("No classification available"), "{}", clean);
+
+    // The synchronous call cannot block this runtime's thread, so it runs on a helper thread
+    assert_eq!(mercy_malicious("status", MOCK_MALICIOUS_DOMAIN), malicious);
+    assert_eq!(mercy_malicious_batch("status", &[MOCK_MALICIOUS_DOMAIN, "clean.example"]), vec![malicious, clean]);
+    assert_eq!(mocks.http.requests().len(), 5);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn inside_a_current_thread_runtime() {
+    both_entry_points().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn inside_a_multi_thread_runtime() {
+    both_entry_points().await;
+}
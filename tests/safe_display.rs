@@ -0,0 +1,68 @@
+// Untrusted names rendered so that nothing they contain stays hidden
+
+use mercy::{DisplayKind, mercy_extra, mercy_safe_display, mercy_url_normalize, mercy_url_parse};
+
+#[test]
+fn punycode_never_hides_controls_or_bidi() {
+    // The trailing RLO must not vanish into a valid-looking "xn--com-7q0a" label
+    let display = mercy_safe_display("xn--80ak6aa92e.com\u{202E}", 120);
+    assert_eq!(display.kind, DisplayKind::Domain);
+    assert_eq!(display.rendered, "xn--80ak6aa92e.com\\u{202E}");
+    assert_eq!(display.ascii.as_deref(), Some("xn--80ak6aa92e.com\\u{202E}"));
+    assert_eq!(display.text, "xn--80ak6aa92e.com\\u{202E}");
+    assert_eq!(display.escaped, 1);
+
+    // A homoglyph label with a zero-width space in it is escaped whole, the clean label still converted
+    let display = mercy_safe_display("p\u{0430}ypal\u{200B}.\u{0435}xample.com", 120);
+    assert_eq!(display.rendered, "p\u{0430}ypal\\u{200B}.\u{0435}xample.com");
+    assert_eq!(display.ascii.as_deref(), Some("p\\u{0430}ypal\\u{200B}.xn--xample-2of.com"));
+
+    // The same for a URL host, with the control character in the path percent-encoded
+    let display = mercy_safe_display("https://\u{0430}pple.com\u{2066}/login\u{0007}", 120);
+    assert_eq!(display.kind, DisplayKind::Url);
+    assert_eq!(display.ascii.as_deref(), Some("https://xn--pple-43d.com\\u{2066}/login%07"));
+    assert_eq!(display.escaped, 2);
+
+    for input in ["xn--80ak6aa92e.com\u{202E}", "b\u{00FC}cher\u{0000}.de", "\u{202D}\u{0441}ber.example"] {
+        let ascii = mercy_safe_display(input, 120).ascii.expect("non-ASCII input");
+        assert!(ascii.is_ascii() && !ascii.chars().any(|c| c.is_control()), "{}", ascii);
+        assert!(ascii.contains("\\u{"), "{} shows no escape: {}", input.escape_unicode(), ascii);
+    }
+}
+
+#[test]
+fn clean_idn_labels_still_become_punycode() {
+    let display = mercy_safe_display("p\u{0430}ypal.com", 120);
+    assert_eq!(display.text, "p\u{0430}ypal.com (xn--pypal-4ve.com)");
+    assert_eq!(display.escaped, 0);
+
+    assert_eq!(mercy_safe_display("B\u{00FC}cher.DE", 120).ascii.as_deref(), Some("xn--bcher-kva.de"));
+}
+
+// "аpple.com" with a Cyrillic "а" sends the browser somewhere other than Apple, and says so
+#[test]
+fn idn_homograph_urls_are_flagged() {
+    let parts = mercy_url_parse("HTTPS://\u{0430}pple.com:443/./account/../login?utm_source=mail&b=2&a=1#top").expect("parses");
+    assert_eq!((parts.scheme.as_str(), parts.host.as_deref(), parts.port), ("https", Some("xn--pple-43d.com"), Some(443)));
+    assert_eq!(parts.host_unicode.as_deref(), Some("\u{0430}pple.com"));
+    assert_eq!((parts.path.as_str(), parts.fragment.as_deref()), ("/login", Some("top")));
+    assert_eq!(parts.normalized, "https://xn--pple-43d.com/login?a=1&b=2#top");
+
+    let kinds: Vec<&str> = parts.warnings.iter().map(|warning| warning.kind).collect();
+    assert_eq!(kinds, ["punycode"]);
+    assert!(parts.warnings[0].detail.contains("reads as \u{0430}pple.com"), "{}", parts.warnings[0].detail);
+
+    // Spelled out already, with the real brand as a user name in front
+    let parts = mercy_url_parse("https://apple.com@xn--80ak6aa92e.com/").expect("parses");
+    assert_eq!(parts.host_unicode.as_deref(), Some("\u{0430}\u{0440}\u{0440}\u{04CF}\u{0435}.com"));
+    let kinds: Vec<&str> = parts.warnings.iter().map(|warning| warning.kind).collect();
+    assert_eq!(kinds, ["userinfo", "punycode"]);
+    assert!(parts.warnings[0].detail.contains("the browser goes to xn--80ak6aa92e.com, not apple.com"), "{}", parts.warnings[0].detail);
+
+    let text = mercy_extra("url_parse", "https://apple.com@xn--80ak6aa92e.com/");
+    assert!(text.contains("host: xn--80ak6aa92e.com (\u{0430}\u{0440}\u{0440}\u{04CF}\u{0435}.com)") && text.contains("WARNING: punycode host"), "{}", text);
+
+    // The two spellings of the look-alike normalize alike, and never to the real host
+    assert_eq!(mercy_url_normalize("https://\u{0430}\u{0440}\u{0440}\u{04CF}\u{0435}.com/?fbclid=x").expect("normalizes"), "https://xn--80ak6aa92e.com/");
+    assert!(mercy_url_parse("https://exa mple.com/").expect_err("a space in the host").starts_with("Invalid URL"));
+}
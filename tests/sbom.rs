@@ -0,0 +1,144 @@
+// A fixture SBOM with known-vulnerable dependencies checked against OSV records on disk and a mock OSV API
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use mercy::{SbomCheckReport, mercy_parse, mercy_sbom, mercy_sbom_check};
+
+const LODASH_ADVISORY: &str = r#"{
+    "id": "GHSA-35jh-r3h4-6jhm",
+    "aliases": ["CVE-2021-23337"],
+    "summary": "Command Injection in lodash",
+    "database_specific": {"severity": "HIGH"},
+    "affected": [{
+        "package": {"ecosystem": "npm", "name": "lodash"},
+        "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}, {"fixed": "4.17.21"}]}]
+    }]
+}"#;
+
+const REQUESTS_ADVISORY: &str = r#"{
+    "id": "PYSEC-2018-28",
+    "aliases": ["CVE-2018-18074"],
+    "details": "The Requests package sends an HTTP Authorization header to an http URI upon receiving a same-hostname https-to-http redirect.\nMore detail.",
+    "affected": [{
+        "package": {"ecosystem": "PyPI", "name": "requests"},
+        "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "2.20.0"}]}],
+        "versions": ["2.19.0", "2.19.1"]
+    }]
+}"#;
+
+// lodash and (nested under worker) requests are affected; left-pad is not, and worker has no purl
+const CYCLONEDX: &str = r#"{
+    "bomFormat": "CycloneDX",
+    "specVersion": "1.5",
+    "metadata": {"component": {"name": "billing-service"}},
+    "components": [
+        {"name": "lodash", "version": "4.17.20", "purl": "pkg:npm/lodash@4.17.20", "licenses": [{"license": {"id": "MIT"}}]},
+        {"name": "left-pad", "version": "1.3.0", "purl": "pkg:npm/left-pad@1.3.0", "licenses": [{"expression": "WTFPL"}]},
+        {"name": "worker", "version": "2.0.0", "components": [
+            {"name": "requests", "version": "2.19.0", "purl": "pkg:pypi/requests@2.19.0", "licenses": [{"license": {"name": "Apache 2.0"}}]}
+        ]}
+    ]
+}"#;
+
+const SPDX: &str = r#"{
+    "spdxVersion": "SPDX-2.3",
+    "name": "billing-service",
+    "packages": [
+        {"name": "lodash", "versionInfo": "4.17.20", "licenseConcluded": "MIT",
+            "externalRefs": [{"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": "pkg:npm/lodash@4.17.20"}]},
+        {"name": "requests", "versionInfo": "2.19.0", "licenseConcluded": "NOASSERTION", "licenseDeclared": "Apache-2.0",
+            "externalRefs": [{"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl", "referenceLocator": "pkg:pypi/requests@2.19.0"}]}
+    ]
+}"#;
+
+fn write(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, contents).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+// Both advisories found against the right components, with their fixes
+fn check_findings(report: &SbomCheckReport) {
+    assert_eq!(report.components_checked, 3);
+    assert_eq!(report.components_skipped, ["worker@2.0.0"]);
+
+    let found: Vec<(&str, &str, &str)> = report.vulnerabilities.iter().map(|v| (v.component.as_str(), v.version.as_str(), v.advisory.as_str())).collect();
+    assert_eq!(found, [("lodash", "4.17.20", "GHSA-35jh-r3h4-6jhm"), ("requests", "2.19.0", "PYSEC-2018-28")]);
+    assert_eq!(report.vulnerabilities[0].fixed_versions, ["4.17.21"]);
+    assert_eq!(report.vulnerabilities[0].severity.as_deref(), Some("HIGH"));
+    assert_eq!(report.vulnerabilities[1].fixed_versions, ["2.20.0"]);
+    assert!(report.vulnerabilities[1].summary.as_deref().is_some_and(|summary| summary.ends_with("redirect.")));
+
+    let findings = report.findings();
+    assert_eq!(findings.len(), 2);
+    assert_eq!(findings[0].title, "lodash 4.17.20 is affected by GHSA-35jh-r3h4-6jhm");
+    assert_eq!(findings[0].recommendation, "Upgrade lodash to 4.17.21 or later");
+}
+
+#[test]
+fn cyclonedx_and_spdx_give_the_same_components() {
+    let dir = fixture_dir("sbom-formats");
+
+    let cyclonedx = mercy_sbom(write(&dir, "bom.json", CYCLONEDX)).expect("CycloneDX");
+    assert_eq!((cyclonedx.format.as_str(), cyclonedx.name.as_deref()), ("CycloneDX 1.5", Some("billing-service")));
+    let names: Vec<&str> = cyclonedx.components.iter().map(|component| component.name.as_str()).collect();
+    assert_eq!(names, ["lodash", "left-pad", "worker", "requests"]);
+    assert_eq!(cyclonedx.components[3].licenses, ["Apache 2.0"]);
+
+    let spdx = mercy_sbom(write(&dir, "spdx.json", SPDX)).expect("SPDX");
+    assert_eq!(spdx.format, "SPDX-2.3");
+    assert_eq!(spdx.components[0], cyclonedx.components[0]);
+    assert_eq!((spdx.components[1].purl.as_deref(), spdx.components[1].licenses.as_slice()), (Some("pkg:pypi/requests@2.19.0"), ["Apache-2.0".to_string()].as_slice()));
+
+    assert!(mercy_sbom(write(&dir, "other.json", "{\"name\": \"not an sbom\"}")).is_err());
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn vulnerable_dependencies_are_found_offline() {
+    let dir = fixture_dir("sbom-offline");
+    let osv = dir.join("osv");
+    fs::create_dir_all(osv.join("npm")).expect("fixture");
+    fs::create_dir_all(osv.join("PyPI")).expect("fixture");
+    write(&osv, "npm/GHSA-35jh-r3h4-6jhm.json", LODASH_ADVISORY);
+    write(&osv, "PyPI/PYSEC-2018-28.json", REQUESTS_ADVISORY);
+    let sbom = write(&dir, "bom.json", CYCLONEDX);
+
+    let report = mercy_sbom_check(&mercy_sbom(&sbom).expect("sbom").components, osv.to_str().expect("path")).expect("check");
+    check_findings(&report);
+    assert!(report.notes.is_empty(), "{:?}", report.notes);
+
+    let output = mercy_parse("sbom_check", &format!("{} osv={}", sbom, osv.display()));
+    assert!(output.contains("GHSA-35jh-r3h4-6jhm") && output.contains("PYSEC-2018-28"), "{}", output);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn vulnerable_dependencies_are_found_through_the_osv_api() {
+    let osv = MockHttp::start(vec![
+        MockRoute::new("/v1/querybatch", 200, r#"{"results": [
+            {"vulns": [{"id": "GHSA-35jh-r3h4-6jhm", "modified": "2024-01-01T00:00:00Z"}]},
+            {},
+            {"vulns": [{"id": "PYSEC-2018-28", "modified": "2024-01-01T00:00:00Z"}]}
+        ]}"#),
+        MockRoute::new("/v1/vulns/GHSA-35jh-r3h4-6jhm", 200, LODASH_ADVISORY),
+        MockRoute::new("/v1/vulns/PYSEC-2018-28", 200, REQUESTS_ADVISORY)
+    ]).expect("mock OSV");
+
+    let dir = fixture_dir("sbom-api");
+    let components = mercy_sbom(write(&dir, "bom.json", CYCLONEDX)).expect("sbom").components;
+    let report = mercy_sbom_check(&components, &osv.url("/")).expect("check");
+    check_findings(&report);
+
+    // One batch for every component, then each advisory once
+    let requests = osv.requests();
+    assert_eq!(requests.len(), 3, "{:?}", requests);
+    assert!(requests[0].starts_with("POST /v1/querybatch "), "{:?}", requests);
+    assert!(requests.iter().any(|request| request.starts_with("GET /v1/vulns/PYSEC-2018-28 ")), "{:?}", requests);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,34 @@
+// The self-test battery run offline against a scratch artifact directory: nothing fails and the network checks are skipped
+
+mod common;
+
+use std::fs;
+
+use common::*;
+use mercy::{SelftestOptions, SelftestStatus, mercy_extra, mercy_selftest};
+
+#[test]
+fn offline_selftest_passes() {
+    let dir = fixture_dir("selftest-offline");
+    let options = SelftestOptions { network: false, artifact_dir: dir.clone(), ..SelftestOptions::default() };
+    let report = mercy_selftest(&options);
+
+    let failed: Vec<String> = report.checks.iter().filter(|check| check.status == SelftestStatus::Fail).map(|check| format!("{}: {}", check.name, check.detail)).collect();
+    assert!(failed.is_empty(), "{:#?}", failed);
+    assert!(report.ok() && report.failed == 0);
+    assert_eq!(report.passed + report.warnings + report.failed + report.skipped, report.checks.len());
+
+    let status = |name: &str| report.checks.iter().find(|check| check.name == name).map(|check| check.status);
+    assert_eq!((status("network/dns"), status("network/http")), (Some(SelftestStatus::Skip), Some(SelftestStatus::Skip)));
+    assert!(report.checks.len() > 60, "{} checks", report.checks.len());
+
+    // Everything it wrote to the artifact directory is cleaned up again
+    assert_eq!(fs::read_dir(&dir).expect("artifact dir").count(), 0);
+
+    // The string call with its options
+    let output = mercy_extra("selftest", &format!("network=false dir={}", dir.display()));
+    assert!(output.contains(" 0 failed, "), "{}", output);
+    assert!(output.lines().any(|line| line.starts_with("[SKIP] network/dns (") && line.ends_with("): network checks turned off")), "{}", output);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
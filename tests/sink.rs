@@ -0,0 +1,223 @@
+// Records streamed into a local Unix socket, including readers that vanish mid-stream, and batches streamed there with a webhook told when they finish
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::Duration
+};
+
+use common::*;
+use mercy::{
+    Backpressure, BatchOutput, OutputSink, SinkOptions, SinkTarget, last_notification, mercy_decode_batch_to, mercy_forensics, mercy_hash_batch, mercy_hash_batch_to, mercy_malicious_batch,
+    mercy_malicious_batch_to
+};
+
+fn record(n: usize) -> String {
+    format!("{{\"n\":{},\"padding\":\"{}\"}}\n", n, "x".repeat(80))
+}
+
+fn target(socket: &Path) -> SinkTarget {
+    SinkTarget::parse(&format!("unix:{}", socket.display())).expect("target")
+}
+
+// Every line whole JSON, returning the record numbers in order
+fn numbers(lines: &[String]) -> Vec<u64> {
+    lines.iter().map(|line| {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", e, line));
+        value["n"].as_u64().expect("n")
+    }).collect()
+}
+
+fn read_lines(stream: UnixStream, limit: usize) -> Vec<String> {
+    BufReader::new(stream).lines().take(limit).map(|line| line.expect("line")).collect()
+}
+
+#[test]
+fn records_arrive_whole_and_in_order() {
+    let dir = fixture_dir("sink-stream");
+    let socket = dir.join("collector.sock");
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let reader = thread::spawn(move || read_lines(listener.accept().expect("connection").0, usize::MAX));
+
+    // Several times the batch size, so records go out in more than one send
+    let mut sink = OutputSink::open(target(&socket), &SinkOptions::default()).expect("sink");
+    for n in 0..5_000 {
+        sink.write_all(record(n).as_bytes()).expect("write");
+    }
+    sink.flush().expect("flush");
+    let stats = sink.stats().clone();
+    drop(sink);
+
+    assert_eq!(numbers(&reader.join().expect("reader")), (0..5_000).collect::<Vec<u64>>());
+    assert_eq!((stats.records_sent, stats.records_dropped, stats.reconnects), (5_000, 0, 0));
+    assert_eq!(stats.target, format!("unix:{}", socket.display()));
+
+    // A streaming call sends its records there and returns the summary with the sink's counts
+    let tree = dir.join("tree");
+    fs::create_dir_all(&tree).expect("fixture");
+    fs::write(tree.join("a.txt"), "a").expect("fixture");
+    fs::write(tree.join("b.txt"), "b").expect("fixture");
+    fs::remove_file(&socket).expect("old socket");
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let reader = thread::spawn(move || read_lines(listener.accept().expect("connection").0, usize::MAX));
+    let summary = mercy_forensics("timeline", &format!("{} format=ndjson out=unix:{}", tree.display(), socket.display()));
+    let lines = reader.join().expect("reader");
+    assert_eq!(lines.len(), 3, "{:?}", lines);
+    assert!(lines.iter().all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()), "{:?}", lines);
+    assert!(summary.contains("\"records_sent\": 3"), "{}", summary);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn a_vanished_reader_is_replaced_at_a_record_boundary() {
+    let dir = fixture_dir("sink-reconnect");
+    let socket = dir.join("collector.sock");
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let (closed, wait_closed) = mpsc::channel();
+
+    // The first reader takes the first batch and goes away; the second takes the rest
+    let reader = thread::spawn(move || {
+        let first = read_lines(listener.accept().expect("connection").0, 100);
+        closed.send(()).expect("signal");
+        let second = read_lines(listener.accept().expect("connection").0, usize::MAX);
+        (first, second)
+    });
+
+    let options = SinkOptions { retries: 5, retry_delay: Duration::from_millis(20), ..SinkOptions::default() };
+    let mut sink = OutputSink::open(target(&socket), &options).expect("sink");
+    for n in 0..100 {
+        sink.write_all(record(n).as_bytes()).expect("write");
+    }
+    sink.flush().expect("flush");
+    wait_closed.recv().expect("first reader gone");
+
+    for n in 100..300 {
+        sink.write_all(record(n).as_bytes()).expect("write");
+    }
+    sink.flush().expect("flush after reconnecting");
+    assert_eq!(sink.stats().reconnects, 1);
+    drop(sink);
+
+    let (first, second) = reader.join().expect("reader");
+    assert_eq!(numbers(&first), (0..100).collect::<Vec<u64>>());
+    assert_eq!(numbers(&second), (100..300).collect::<Vec<u64>>());
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn a_reader_that_never_returns_fails_the_write() {
+    let dir = fixture_dir("sink-gone");
+    let socket = dir.join("collector.sock");
+    let listener = UnixListener::bind(&socket).expect("listener");
+
+    let options = SinkOptions { retries: 2, retry_delay: Duration::from_millis(10), ..SinkOptions::default() };
+    let mut sink = OutputSink::open(target(&socket), &options).expect("sink");
+    sink.write_all(record(0).as_bytes()).expect("write");
+    sink.flush().expect("flush");
+
+    // The collector shuts down for good: its connection, listener and socket file all go
+    drop(listener.accept().expect("connection"));
+    drop(listener);
+    fs::remove_file(&socket).expect("socket removed");
+
+    sink.write_all(record(1).as_bytes()).expect("buffered");
+    assert!(sink.flush().is_err());
+    assert_eq!((sink.stats().records_sent, sink.stats().reconnects), (1, 0));
+    drop(sink);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn a_slow_reader_gets_whole_records_when_others_are_dropped() {
+    let dir = fixture_dir("sink-drop");
+    let socket = dir.join("collector.sock");
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let (done, wait_done) = mpsc::channel::<()>();
+
+    // Reads nothing until the writer has finished, so the socket buffer fills
+    let reader = thread::spawn(move || {
+        let stream = listener.accept().expect("connection").0;
+        let _ = wait_done.recv();
+        read_lines(stream, usize::MAX)
+    });
+
+    let options = SinkOptions { backpressure: Backpressure::Drop, ..SinkOptions::default() };
+    let mut sink = OutputSink::open(target(&socket), &options).expect("sink");
+    let total = 50_000;
+    for n in 0..total {
+        sink.write_all(record(n).as_bytes()).expect("write");
+    }
+    sink.flush().expect("flush");
+    let stats = sink.stats().clone();
+    drop(sink);
+    done.send(()).expect("signal");
+
+    assert!(stats.records_dropped > 0, "{:?}", stats);
+    assert_eq!(stats.records_sent + stats.records_dropped, total as u64);
+
+    let received = numbers(&reader.join().expect("reader"));
+    assert_eq!(received.len() as u64, stats.records_sent);
+    assert!(received.windows(2).all(|pair| pair[0] < pair[1]));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn batches_stream_their_outputs_and_notify_when_done() {
+    let dir = fixture_dir("sink-batch");
+    let socket = dir.join("collector.sock");
+    let hook = MockHttp::start(vec![MockRoute::new("/hooks/", 200, "ok")]).expect("mock webhook");
+    let output = BatchOutput { sink: Some(target(&socket)), notify: Some(hook.url("/hooks/batch")), ..BatchOutput::default() };
+
+    // Every digest a record, in order, and the same outputs as the plain batch
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let reader = thread::spawn(move || read_lines(listener.accept().expect("connection").0, usize::MAX));
+    let inputs = ["a", "b", "c"];
+    let report = mercy_hash_batch_to("md5", &inputs, &output).expect("hash batch");
+    let lines = reader.join().expect("reader");
+    assert_eq!((report.call.as_str(), &report.outputs, report.failed), ("md5", &mercy_hash_batch("md5", &inputs), 0));
+    assert_eq!(report.sink.as_ref().map(|sink| sink.records_sent), Some(3));
+    let record: serde_json::Value = serde_json::from_str(&lines[1]).expect("record");
+    assert_eq!((lines.len(), record["index"].as_u64(), record["output"].as_str()), (3, Some(1), Some("92eb5ffee6ae2fec3ad71c777531578f")));
+    assert_eq!(last_notification().expect("notified").expect("delivered").status, 200);
+
+    // A failed input is an error record and counted, without stopping the rest
+    fs::remove_file(&socket).expect("old socket");
+    let listener = UnixListener::bind(&socket).expect("listener");
+    let reader = thread::spawn(move || read_lines(listener.accept().expect("connection").0, usize::MAX));
+    let report = mercy_decode_batch_to("base64", &["aGk=", "!!", "aGk="], &output).expect("decode batch");
+    let lines = reader.join().expect("reader");
+    assert_eq!((report.outputs[0].as_str(), report.outputs[2].as_str(), report.failed), ("hi", "hi", 1));
+    let record: serde_json::Value = serde_json::from_str(&lines[1]).expect("record");
+    assert_eq!((record["index"].as_u64(), record["error"].as_str()), (Some(1), Some(report.outputs[1].as_str())));
+
+    // No collector: the batch fails before it starts, and the webhook hears that
+    fs::remove_file(&socket).expect("old socket");
+    let error = mercy_hash_batch_to("md5", &inputs, &output).expect_err("no collector");
+    assert!(error.starts_with(&format!("Unable to open unix:{}", socket.display())), "{}", error);
+    let posts = hook.requests();
+    assert_eq!(posts.len(), 3);
+    assert!(posts.iter().all(|post| post.starts_with("POST /hooks/batch ")), "{:?}", posts);
+
+    // A network batch notifies the same way
+    let mocks = mocks();
+    let domains = [MOCK_MALICIOUS_DOMAIN, "clean.example"];
+    let notify_only = BatchOutput { notify: Some(hook.url("/hooks/batch")), ..BatchOutput::default() };
+    let report = mercy_malicious_batch_to("status", &domains, &notify_only).expect("malicious batch");
+    assert_eq!((report.outputs, report.sink.is_none()), (mercy_malicious_batch("status", &domains), true));
+    assert_eq!((hook.requests().len(), last_notification().expect("notified").map(|sent| sent.status)), (4, Ok(200)));
+    drop(mocks);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,113 @@
+// Snapshot files migrated forward from the version 1 layout, and damaged ones left alone
+
+mod common;
+
+use std::{collections::BTreeMap, fs};
+
+use common::*;
+use mercy::{SNAPSHOT_SCHEMA_VERSION, Snapshot, SnapshotOptions, SnapshotSection, load_snapshot, mercy_extra, save_snapshot};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TreeHashes {
+    files: BTreeMap<String, String>
+}
+
+impl SnapshotSection for TreeHashes {
+    const SECTION: &'static str = "tree_hash";
+}
+
+// Sections beside "version" and "created" (2024-05-01T09:00:00Z) at the top level
+const VERSION_1: &str = r#"{
+    "version": 1,
+    "created": 1714554000,
+    "tree_hash": {"files": {"bin/agent": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"}},
+    "cert_fingerprints": {"example.com:443": "3b:1f:aa"}
+}"#;
+
+fn tree_hashes() -> TreeHashes {
+    TreeHashes { files: BTreeMap::from([("bin/agent".to_string(), "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".to_string())]) }
+}
+
+#[test]
+fn version_1_files_migrate_forward() {
+    let dir = fixture_dir("snapshot-migrate");
+    let path = dir.join("state.json");
+    fs::write(&path, VERSION_1).expect("fixture");
+
+    let snapshot = load_snapshot(&path).expect("migrates");
+    assert_eq!((snapshot.schema_version, snapshot.migrated_from), (SNAPSHOT_SCHEMA_VERSION, Some(1)));
+    assert_eq!((snapshot.created_at.as_str(), snapshot.updated_at.as_str()), ("2024-05-01T09:00:00Z", "2024-05-01T09:00:00Z"));
+    assert_eq!(snapshot.tool_version, "unknown");
+    assert_eq!(snapshot.sections.keys().collect::<Vec<&String>>(), ["cert_fingerprints", "tree_hash"]);
+    assert_eq!(snapshot.get::<TreeHashes>().expect("section"), Some(tree_hashes()));
+    // Loading alone leaves the file as it was
+    assert_eq!(fs::read_to_string(&path).expect("file"), VERSION_1);
+
+    // snapshot_info reports the migration, and with migrate=true rewrites the file after backing it up
+    let info = mercy_extra("snapshot_info", &format!("{} migrate=true backup=true", path.display()));
+    assert!(info.starts_with("Snapshot schema version 2 (migrated from 1)"), "{}", info);
+    let rewritten: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).expect("file")).expect("JSON");
+    assert_eq!((rewritten["schema_version"].as_u64(), rewritten["created_at"].as_str()), (Some(2), Some("2024-05-01T09:00:00Z")));
+    assert_eq!(rewritten["tool_version"].as_str(), Some(env!("CARGO_PKG_VERSION")));
+
+    let backups: Vec<String> = fs::read_dir(&dir).expect("dir").map(|entry| entry.expect("entry").file_name().to_string_lossy().to_string()).filter(|name| name.ends_with(".bak")).collect();
+    assert_eq!(backups.len(), 1, "{:?}", backups);
+    assert_eq!(fs::read_to_string(dir.join(&backups[0])).expect("backup"), VERSION_1);
+
+    let reloaded = load_snapshot(&path).expect("loads");
+    assert_eq!(reloaded.migrated_from, None);
+    assert_eq!(reloaded.get::<TreeHashes>().expect("section"), Some(tree_hashes()));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn compressed_snapshots_round_trip() {
+    let dir = fixture_dir("snapshot-compressed");
+    let path = dir.join("state.json.gz");
+
+    let mut snapshot = Snapshot::new();
+    snapshot.put(&tree_hashes()).expect("put");
+    assert_eq!(save_snapshot(&path, &mut snapshot, &SnapshotOptions { compress: true, backup: true }).expect("saves"), None);
+    assert!(fs::read(&path).expect("file").starts_with(&[0x1F, 0x8B]));
+
+    let mut loaded = load_snapshot(&path).expect("loads");
+    assert_eq!((loaded.created_at.as_str(), loaded.migrated_from), (snapshot.created_at.as_str(), None));
+    assert_eq!(loaded.get::<TreeHashes>().expect("section"), Some(tree_hashes()));
+    assert!(loaded.remove::<TreeHashes>());
+    assert_eq!(loaded.get::<TreeHashes>().expect("section"), None);
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
+
+#[test]
+fn damaged_and_newer_snapshots_are_refused_and_kept() {
+    let dir = fixture_dir("snapshot-damaged");
+    let cases = [
+        ("truncated.json", &VERSION_1[..60], "not valid JSON"),
+        ("newer.json", r#"{"schema_version": 3, "sections": {}}"#, "schema version 3 is newer than this mercy supports (2)"),
+        ("no-created.json", r#"{"version": 1, "tree_hash": {}}"#, "migrating from schema version 1 failed: no created time"),
+        ("no-version.json", r#"{"sections": {}}"#, "no schema_version")
+    ];
+
+    for (name, contents, error) in cases {
+        let path = dir.join(name);
+        fs::write(&path, contents).expect("fixture");
+
+        let message = load_snapshot(&path).expect_err(name);
+        assert!(message.contains(error), "{}: {}", name, message);
+
+        // Never overwritten, backup or not
+        let message = save_snapshot(&path, &mut Snapshot::new(), &SnapshotOptions { compress: false, backup: true }).expect_err(name);
+        assert!(message.ends_with("not overwriting it (move it aside to start a new snapshot)"), "{}: {}", name, message);
+        assert_eq!(fs::read_to_string(&path).expect("file"), contents);
+    }
+
+    let gzip = dir.join("bad.gz");
+    fs::write(&gzip, [0x1F, 0x8B, 8, 0, 0, 0]).expect("fixture");
+    assert!(load_snapshot(&gzip).expect_err("bad gzip").contains("is corrupted"));
+    assert_eq!(fs::read_dir(&dir).expect("dir").count(), 5, "no backups or temporary files left behind");
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,68 @@
+// The operation counters after a known sequence of calls, in a test binary of their own so no other test adds to them
+
+mod common;
+
+use std::{fs, net::UdpSocket, time::Duration};
+
+use common::*;
+use mercy::{DnsLookupOptions, MercyStats, mercy_decode_bytes, mercy_dns_lookup, mercy_extra, mercy_hash, mercy_hex, mercy_malicious, mercy_stats, reset_mercy_stats};
+
+#[test]
+fn a_known_sequence_of_calls_gives_a_known_snapshot() {
+    let mocks = mocks();
+    let dir = fixture_dir("stats");
+    let dump = dir.join("dump.bin");
+    fs::write(&dump, [0x41; 100]).expect("fixture");
+    let prefixes = dir.join("prefixes.txt");
+    fs::write(&prefixes, "10.0.0.0/8\n192.0.2.0/24\n").expect("fixture");
+    let (dump, prefixes) = (dump.to_str().expect("path"), prefixes.to_str().expect("path"));
+    reset_mercy_stats();
+
+    mercy_hash("sha2_256", "abc");
+    mercy_hash("md5", "hello world");
+    mercy_hex("hex_dump", dump);
+
+    // An A and an AAAA query each, both unanswered by the silent server
+    let options = DnsLookupOptions { server: Some(mocks.dns.addr()), timeout: Duration::from_secs(2) };
+    mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &options).expect("lookup");
+    let silent = UdpSocket::bind("127.0.0.1:0").expect("socket");
+    let options = DnsLookupOptions { server: Some(silent.local_addr().expect("address")), timeout: Duration::from_millis(200) };
+    mercy_dns_lookup(MOCK_MALICIOUS_DOMAIN, &options).expect("lookup");
+
+    assert!(mercy_malicious("status", MOCK_MALICIOUS_DOMAIN).starts_with("Malicious"));
+    mercy_extra("whois", MOCK_MALICIOUS_DOMAIN);
+
+    // The prefix file is read once, then reused while it is unchanged
+    for address in ["10.1.2.3", "198.51.100.7"] {
+        mercy_extra("prefix_contains", &format!("{} prefixes={}", address, prefixes));
+    }
+
+    // Failures sorted by kind; an unknown call is no failure of the toolbox
+    mercy_hex("hex_dump", &dir.join("missing.bin").to_string_lossy());
+    assert!(mercy_decode_bytes("hex", "zz").is_err());
+    mercy_hex("no_such_call", dump);
+
+    let expected = MercyStats {
+        dns_queries: 4,
+        dns_timeouts: 2,
+        http_requests: 1,
+        whois_lookups: 1,
+        cache_hits: 1,
+        cache_misses: 1,
+        errors_io: 1,
+        errors_input: 1,
+        bytes_hashed: 3 + 11,
+        bytes_hex_dumped: 100,
+        ..MercyStats::default()
+    };
+    assert_eq!(mercy_stats(), expected);
+    assert_eq!(expected.errors(), 2);
+
+    // Resetting hands back the counts and starts again from zero
+    assert_eq!(reset_mercy_stats(), expected);
+    assert_eq!(mercy_stats(), MercyStats::default());
+    mercy_hash("sha2_256", "abcd");
+    assert!(mercy_extra("stats", "").contains("\"bytes_hashed\": 4"));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
@@ -0,0 +1,90 @@
+// Strings among binary noise: ASCII and UTF-16LE runs at either alignment, found whole at their offsets, chunk boundary included
+
+mod common;
+
+use std::{fs, path::Path};
+
+use common::*;
+use mercy::{ExtractedString, StringEncoding, StringsOptions, mercy_extra, mercy_strings};
+
+// Bytes none of which is printable, so nothing in the noise reads as a string
+const NOISE: &[u8] = &[0x00, 0x01, 0xff, 0x90, 0x8b, 0xfe, 0x02, 0x7f, 0xc3, 0x10, 0x1f, 0x80];
+
+// The offset the first chunk read ends at
+const CHUNK: usize = 1024 * 1024;
+
+fn noise(data: &mut Vec<u8>, until: usize) {
+    while data.len() < until {
+        data.push(NOISE[data.len() % NOISE.len()]);
+    }
+}
+
+fn wide(text: &str) -> Vec<u8> {
+    text.bytes().flat_map(|byte| [byte, 0]).collect()
+}
+
+fn write(dir: &Path, name: &str, data: &[u8]) -> String {
+    let path = dir.join(name);
+    fs::write(&path, data).expect("fixture");
+    path.to_str().expect("path").to_string()
+}
+
+fn found(offset: u64, encoding: StringEncoding, text: &str) -> ExtractedString {
+    ExtractedString { offset, encoding, text: text.to_string() }
+}
+
+// (offset, bytes) placed among the noise
+fn fixture() -> Vec<u8> {
+    let placed: [(usize, Vec<u8>); 8] = [
+        (0x40, b"http://evil.example/payload.bin".to_vec()),
+        (0xc9, wide("C:\\Users\\Public\\svchost.exe")),
+        (0x130, wide("Global\\MercyMutex")),
+        // Too short to keep: three ASCII characters, two wide ones
+        (0x180, b"abc".to_vec()),
+        (0x190, wide("xy")),
+        (0x1a0, b"ABCD\tEF".to_vec()),
+        // Across the end of the first read, and a wide one at an odd offset just past it
+        (CHUNK - 9, b"split across two reads".to_vec()),
+        (CHUNK + 0x21, wide("wide after the boundary"))
+    ];
+    let mut data = Vec::new();
+    for (offset, bytes) in placed {
+        noise(&mut data, offset);
+        data.extend_from_slice(&bytes);
+    }
+    noise(&mut data, CHUNK + 0x100);
+    data
+}
+
+#[test]
+fn strings_are_found_at_their_offsets() {
+    let dir = fixture_dir("strings-offsets");
+    let path = write(&dir, "sample.bin", &fixture());
+
+    let report = mercy_strings(&path, &StringsOptions::default()).expect("strings");
+    assert_eq!(report.size, (CHUNK + 0x100) as u64);
+    assert_eq!(report.strings, vec![
+        found(0x40, StringEncoding::Ascii, "http://evil.example/payload.bin"),
+        found(0xc9, StringEncoding::Utf16le, "C:\\Users\\Public\\svchost.exe"),
+        found(0x130, StringEncoding::Utf16le, "Global\\MercyMutex"),
+        found(0x1a0, StringEncoding::Ascii, "ABCD\tEF"),
+        found(CHUNK as u64 - 9, StringEncoding::Ascii, "split across two reads"),
+        found(CHUNK as u64 + 0x21, StringEncoding::Utf16le, "wide after the boundary")
+    ]);
+
+    // One encoding at a time, and a longer minimum
+    let ascii = mercy_strings(&path, &StringsOptions { utf16le: false, ..StringsOptions::default() }).expect("ascii");
+    assert!(ascii.strings.iter().all(|string| string.encoding == StringEncoding::Ascii) && ascii.strings.len() == 3);
+    let short = mercy_strings(&path, &StringsOptions { min_length: 2, ascii: false, ..StringsOptions::default() }).expect("short");
+    assert!(short.strings.contains(&found(0x190, StringEncoding::Utf16le, "xy")));
+    let long = mercy_strings(&path, &StringsOptions { min_length: 20, ..StringsOptions::default() }).expect("long");
+    assert_eq!(long.strings.iter().map(|string| string.offset).collect::<Vec<u64>>(), vec![0x40, 0xc9, CHUNK as u64 - 9, CHUNK as u64 + 0x21]);
+
+    // Through the string call, with offsets in hex
+    let output = mercy_extra("strings", &format!("{} min=16 encoding=utf16le offsets=true", path));
+    assert_eq!(output, "     c9 C:\\Users\\Public\\svchost.exe\n    130 Global\\MercyMutex\n 100021 wide after the boundary");
+    assert!(mercy_extra("strings", &format!("{} encoding=utf8", path)).contains("Unknown encoding 'utf8'"));
+    assert!(mercy_extra("strings", &format!("{} min=0", path)).contains("at least 1"));
+
+    fs::remove_dir_all(dir).expect("cleanup");
+}
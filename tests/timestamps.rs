@@ -0,0 +1,96 @@
+// Epoch, ISO 8601 and FILETIME conversions: the epoch itself, units told apart by magnitude, a leap day, and the edges of the range
+
+use mercy::{epoch_to_utc, filetime_to_utc, mercy_extra, utc_to_epoch};
+
+#[test]
+fn epoch_values_in_each_unit() {
+    // (input, unit read, UTC, Unix seconds as text)
+    let cases = [
+        ("0", "seconds", "1970-01-01T00:00:00Z", "0"),
+        ("-1", "seconds", "1969-12-31T23:59:59Z", "-1"),
+        ("1709208000", "seconds", "2024-02-29T12:00:00Z", "1709208000"),
+        ("1709208000.25", "seconds", "2024-02-29T12:00:00.25Z", "1709208000.25"),
+        ("1700000000123", "milliseconds", "2023-11-14T22:13:20.123Z", "1700000000.123"),
+        (" 1709208000000 ", "milliseconds", "2024-02-29T12:00:00Z", "1709208000"),
+        ("1709208000000001", "microseconds", "2024-02-29T12:00:00.000001Z", "1709208000.000001"),
+        ("1709208000123456789", "nanoseconds", "2024-02-29T12:00:00.123456789Z", "1709208000.123456789"),
+        // Just under and at the first magnitude boundary
+        ("99999999999", "seconds", "5138-11-16T09:46:39Z", "99999999999"),
+        ("100000000000", "milliseconds", "1973-03-03T09:46:40Z", "100000000")
+    ];
+    for (input, unit, utc, seconds) in cases {
+        let conversion = epoch_to_utc(input).expect(input);
+        assert_eq!((conversion.unit, conversion.utc.as_str(), conversion.epoch_seconds()), (unit, utc, seconds.to_string()), "{}", input);
+    }
+    assert_eq!(mercy_extra("epoch_to_utc", "0"), "1970-01-01T00:00:00Z");
+}
+
+#[test]
+fn iso_text_and_the_leap_day() {
+    // (input, Unix seconds)
+    let cases = [
+        ("1970-01-01T00:00:00Z", "0"),
+        ("2024-02-29T12:00:00Z", "1709208000"),
+        ("2024-02-29T12:00:00", "1709208000"),
+        ("2024-02-29 12:00:00Z", "1709208000"),
+        ("2024-02-29T14:00:00+02:00", "1709208000"),
+        ("2024-02-29T23:30:00-05:00", "1709267400"),
+        ("2024-02-29T12:00:00.5Z", "1709208000.5"),
+        ("2000-02-29T00:00:00Z", "951782400")
+    ];
+    for (input, seconds) in cases {
+        assert_eq!(utc_to_epoch(input).expect(input).epoch_seconds(), seconds, "{}", input);
+    }
+
+    // Back again, and the leap day through the string call
+    assert_eq!(epoch_to_utc(&utc_to_epoch("2024-02-29T23:59:59Z").expect("leap day").epoch_seconds()).expect("epoch").utc, "2024-02-29T23:59:59Z");
+    assert_eq!(mercy_extra("utc_to_epoch", "2024-02-29T12:00:00Z"), "1709208000");
+
+    // There is no 29 February in a year that is not a leap year, 1900 and 2100 included
+    for input in ["2023-02-29T00:00:00Z", "1900-02-29T00:00:00Z", "2100-02-29T00:00:00Z", "2024-02-30T00:00:00Z", "2024-13-01T00:00:00Z", "yesterday", ""] {
+        let error = utc_to_epoch(input).expect_err(input);
+        assert!(error.starts_with(&format!("Unparseable timestamp '{}'", input)), "{}: {}", input, error);
+    }
+}
+
+#[test]
+fn filetime_values() {
+    // (input, UTC)
+    let cases = [
+        ("0", "1601-01-01T00:00:00Z"),
+        ("116444736000000000", "1970-01-01T00:00:00Z"),
+        ("132539328000000000", "2021-01-01T00:00:00Z"),
+        ("0x01DA6B06D21DE000", "2024-02-29T12:00:00Z"),
+        ("0x1da6b06d21de000", "2024-02-29T12:00:00Z"),
+        ("132539328000000001", "2021-01-01T00:00:00.0000001Z")
+    ];
+    for (input, utc) in cases {
+        let conversion = filetime_to_utc(input).expect(input);
+        assert_eq!((conversion.unit, conversion.utc.as_str()), ("filetime", utc), "{}", input);
+    }
+    assert_eq!(filetime_to_utc("116444736000000000").expect("epoch").epoch, 0);
+    assert_eq!(mercy_extra("filetime_to_utc", "132539328000000000"), "2021-01-01T00:00:00Z");
+}
+
+#[test]
+fn unreadable_and_out_of_range_values_are_errors() {
+    for input in ["", "abc", "NaN", "inf", "12:00"] {
+        let error = epoch_to_utc(input).expect_err(input);
+        assert_eq!(error, format!("Invalid epoch value '{}' (expected Unix seconds, milliseconds, microseconds or nanoseconds)", input));
+    }
+    for input in ["-1", "0x", "0xZZ", "1.5", "18446744073709551616"] {
+        let error = filetime_to_utc(input).expect_err(input);
+        assert!(error.starts_with(&format!("Invalid FILETIME '{}'", input)), "{}: {}", input, error);
+    }
+
+    // Past 9999-12-31 in nanoseconds, and the largest FILETIME, which is in the year 60056
+    let range = "is outside the representable range 0000-01-01T00:00:00Z to 9999-12-31T23:59:59Z";
+    assert_eq!(epoch_to_utc("1e30").expect_err("far future"), format!("'1e30' (nanoseconds) {}", range));
+    assert_eq!(epoch_to_utc("-1e30").expect_err("far past"), format!("'-1e30' (nanoseconds) {}", range));
+    assert_eq!(filetime_to_utc("0xFFFFFFFFFFFFFFFF").expect_err("max"), format!("'0xFFFFFFFFFFFFFFFF' (filetime) {}", range));
+    assert!(mercy_extra("filetime_to_utc", "18446744073709551615").contains(range));
+
+    // Only nanoseconds reach that far: the last representable second is fine, the one after it is not
+    assert_eq!(epoch_to_utc("253402300799000000000").expect("last second").utc, "9999-12-31T23:59:59Z");
+    assert!(epoch_to_utc("253402300800000000000").expect_err("after").contains(range));
+}
@@ -0,0 +1,126 @@
+// UUIDs: the RFC's v5 known answers, random v4s, the time and node inside time-based ones, and the forms input comes in
+
+use mercy::{MercyRng, mercy_extra, mercy_uuid_inspect, mercy_uuid_v4, mercy_uuid_v4_with, mercy_uuid_v5};
+
+const DNS: &str = "6ba7b810-9dad-11d1-80b4-00c04fd430c8";
+
+#[test]
+fn v5_known_answers() {
+    // RFC 9562 appendix A.4, then other names in each RFC 4122 namespace
+    let vectors = [
+        (DNS, "www.example.com", "2ed6657d-e927-568b-95e1-2665a8aea6a2"),
+        ("dns", "python.org", "886313e1-3b8a-5372-9b90-0c9aee199e5d"),
+        ("url", "http://www.example.com/", "fcde3c85-2270-590f-9e7c-ee003d65e0e2"),
+        ("oid", "1.3.6.1", "1447fa61-5277-5fef-a9b3-fbc6e44f4af3"),
+        ("x500", "cn=John Doe", "6b28d549-d26e-5bfc-ae5e-9a39af63dc3f"),
+        ("DNS", "\u{fc}n\u{ef}c\u{f6}d\u{e9}.example", "9ce0d29f-cac7-5959-a9d2-5ed1cbb2e529")
+    ];
+    for (namespace, name, uuid) in vectors {
+        assert_eq!(mercy_uuid_v5(namespace, name).expect(name), uuid, "{} in {}", name, namespace);
+    }
+
+    // The namespace in any form; the name exactly as given, commas and spaces included
+    for namespace in ["{6BA7B810-9DAD-11D1-80B4-00C04FD430C8}", "6ba7b8109dad11d180b400c04fd430c8", "urn:uuid:6ba7b810-9dad-11d1-80b4-00c04fd430c8"] {
+        assert_eq!(mercy_uuid_v5(namespace, "www.example.com").expect(namespace), "2ed6657d-e927-568b-95e1-2665a8aea6a2");
+    }
+    assert_eq!(mercy_extra("uuid_v5", "dns,www.example.com"), "2ed6657d-e927-568b-95e1-2665a8aea6a2");
+    assert_ne!(mercy_extra("uuid_v5", "dns, www.example.com"), "2ed6657d-e927-568b-95e1-2665a8aea6a2");
+    assert_eq!(mercy_extra("uuid_v5", "dns,a,b"), mercy_uuid_v5("dns", "a,b").expect("comma"));
+
+    assert!(mercy_uuid_v5("isbn", "x").expect_err("namespace").contains("the namespace must be a UUID or dns, url, oid or x500"));
+    assert!(mercy_extra("uuid_v5", "www.example.com").contains("Expected \"namespace,name\""));
+}
+
+#[test]
+fn v4_is_random_with_the_version_and_variant_set() {
+    let uuids: Vec<String> = (0..200).map(|_| mercy_uuid_v4()).collect();
+    for uuid in &uuids {
+        let info = mercy_uuid_inspect(uuid).expect(uuid);
+        assert_eq!((info.uuid.as_str(), info.version, info.variant.as_str()), (uuid.as_str(), 4, "RFC 4122"));
+        assert_eq!((uuid.len(), &uuid[14..15]), (36, "4"));
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"), "{}", uuid);
+    }
+    assert!(uuids.iter().enumerate().all(|(index, uuid)| !uuids[index + 1..].contains(uuid)));
+
+    // The version nibble and the variant bits are fixed, and every other bit varies
+    let (fixed, set) = ((0xf << 76) | (0x3 << 62), (0x4 << 76) | (0x2 << 62));
+    let bits = |uuid: &String| u128::from_str_radix(&uuid.replace('-', ""), 16).expect("hex");
+    let (ones, zeros) = uuids.iter().fold((0u128, u128::MAX), |(ones, zeros), uuid| (ones | bits(uuid), zeros & bits(uuid)));
+    assert_eq!((ones, zeros), (!fixed | set, set));
+    assert_eq!(mercy_uuid_inspect(&mercy_extra("uuid_v4", "")).expect("v4").version, 4);
+}
+
+#[test]
+fn seeded_v4_repeats_with_the_version_and_variant_set() {
+    // The same seed gives the same sequence, another seed a different one
+    let run = |seed: u64| {
+        let mut rng = MercyRng::seeded(seed);
+        (0..20).map(|_| mercy_uuid_v4_with(&mut rng)).collect::<Vec<String>>()
+    };
+    let (first, second) = (run(298), run(298));
+    assert_eq!(first, second);
+    assert_ne!(first, run(299));
+    assert!(first.iter().enumerate().all(|(index, uuid)| !first[index + 1..].contains(uuid)));
+    for uuid in &first {
+        let info = mercy_uuid_inspect(uuid).expect(uuid);
+        assert_eq!((info.version, info.variant.as_str()), (4, "RFC 4122"), "{}", uuid);
+    }
+
+    // The string call takes the seed as its input and starts the same sequence
+    assert_eq!(mercy_extra("uuid_v4", "seed=298"), first[0]);
+    assert_eq!(mercy_extra("uuid_v4", "seed=298"), mercy_extra("uuid_v4", "seed=298"));
+    assert_ne!(mercy_extra("uuid_v4", ""), mercy_extra("uuid_v4", ""));
+    assert_eq!(mercy_extra("uuid_v4", "seed=x"), "Invalid seed 'x'");
+    assert!(mercy_extra("uuid_v4", "please").contains("Unexpected uuid_v4 argument 'please'"));
+}
+
+#[test]
+fn time_based_uuids_give_their_time_and_node() {
+    // RFC 9562's v1, v6 and v7 examples are all 2022-02-22T19:22:22Z
+    let v1 = mercy_uuid_inspect("C232AB00-9414-11EC-B3C8-9F6BDECED846").expect("v1");
+    assert_eq!((v1.uuid.as_str(), v1.version, v1.version_name.as_str(), v1.variant.as_str()), ("c232ab00-9414-11ec-b3c8-9f6bdeced846", 1, "time-based", "RFC 4122"));
+    assert_eq!((v1.timestamp.as_deref(), v1.clock_sequence, v1.node.as_deref()), (Some("2022-02-22T19:22:22Z"), Some(0x33c8), Some("9F:6B:DE:CE:D8:46")));
+    assert_eq!(v1.notes, vec!["The node has the multicast bit set: a random node ID, not a MAC address"]);
+
+    let v6 = mercy_uuid_inspect("1ec9414c-232a-6b00-b3c8-9f6bdeced846").expect("v6");
+    assert_eq!((v6.version, v6.timestamp, v6.clock_sequence, v6.node), (6, v1.timestamp.clone(), v1.clock_sequence, v1.node.clone()));
+    let v7 = mercy_uuid_inspect("017f22e2-79b0-7cc3-98c4-dc0c0c07398f").expect("v7");
+    assert_eq!((v7.version, v7.timestamp.as_deref(), v7.node), (7, Some("2022-02-22T19:22:22Z"), None));
+
+    // A real MAC address in the node names its vendor, here through the string call with a GUID's braces
+    let output = mercy_extra("uuid_inspect", "{c232ab00-9414-11ec-b3c8-005056c00008}");
+    assert_eq!(output, "UUID: c232ab00-9414-11ec-b3c8-005056c00008\nVersion: 1 (time-based)\nVariant: RFC 4122\nTimestamp: 2022-02-22T19:22:22Z\nClock sequence: 13256\nNode: 00:50:56:C0:00:08 (VMware)");
+
+    // The first moments of the Gregorian count, and a fraction of a second
+    assert_eq!(mercy_uuid_inspect("00000000-0000-1000-8000-000000000000").expect("start").timestamp.as_deref(), Some("1582-10-15T00:00:00Z"));
+    assert_eq!(mercy_uuid_inspect("c232ab05-9414-11ec-8000-010203040506").expect("fraction").timestamp.as_deref(), Some("2022-02-22T19:22:22.0000005Z"));
+}
+
+#[test]
+fn other_variants_forms_and_errors() {
+    // COM's IUnknown, and the nil and max UUIDs
+    let unknown = mercy_uuid_inspect("00000000-0000-0000-C000-000000000046").expect("IUnknown");
+    assert_eq!((unknown.variant.as_str(), unknown.timestamp), ("Microsoft (reserved)", None));
+    let nil = mercy_uuid_inspect("00000000000000000000000000000000").expect("nil");
+    let max = mercy_uuid_inspect("FFFFFFFF-FFFF-FFFF-FFFF-FFFFFFFFFFFF").expect("max");
+    assert_eq!((nil.version_name.as_str(), nil.variant.as_str(), max.version_name.as_str()), ("nil", "none", "max"));
+
+    // One UUID in every accepted form
+    for form in ["2ed6657d-e927-568b-95e1-2665a8aea6a2", " 2ED6657D-E927-568B-95E1-2665A8AEA6A2 ", "{2ed6657d-e927-568b-95e1-2665a8aea6a2}", "2ed6657de927568b95e12665a8aea6a2", "URN:UUID:2ed6657d-e927-568b-95e1-2665a8aea6a2"] {
+        let info = mercy_uuid_inspect(form).expect(form);
+        assert_eq!((info.uuid.as_str(), info.version_name.as_str()), ("2ed6657d-e927-568b-95e1-2665a8aea6a2", "name-based, SHA-1"), "{}", form);
+    }
+
+    let errors = [
+        ("2ed6657d-e927-568b-95e1-2665a8aea6a", "expected hyphenated groups of 8, 4, 4, 4 and 12 hex digits"),
+        ("2ed6657d-e927-568b-95e12665a8aea6a2", "expected hyphenated groups of 8, 4, 4, 4 and 12 hex digits"),
+        ("2ed6657de927568b95e12665a8aea6a", "expected 32 hex digits, got 31"),
+        ("2ed6657d-e927-568b-95e1-2665a8aea6g2", "'g' is not a hex digit"),
+        ("{2ed6657d-e927-568b-95e1-2665a8aea6a2", "unbalanced braces"),
+        ("", "expected 32 hex digits, got 0")
+    ];
+    for (input, reason) in errors {
+        assert_eq!(mercy_uuid_inspect(input).expect_err(input), format!("Invalid UUID '{}': {}", input, reason));
+    }
+    assert!(mercy_extra("uuid_inspect", "not-a-uuid").contains("Invalid UUID 'not-a-uuid'"));
+}
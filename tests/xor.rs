@@ -0,0 +1,85 @@
+// XOR with a known key, single-byte or repeating, and the brute force that must put English text first
+
+use mercy::{XorCandidate, mercy_decode, mercy_decode_bytes, mercy_encode_bytes, mercy_json, xor_brute, xor_bytes};
+
+const CRYPTOPALS: &str = "1b37373331363f78151b7f2b783431333d78397828372d363c78373e783a393b3736";
+
+const SENTENCE: &str = "The configuration is stored in the registry under the Run key, so it starts with Windows.";
+
+fn hex(data: &[u8]) -> String {
+    mercy_encode_bytes("hex", data)
+}
+
+#[test]
+fn the_english_sentence_is_ranked_first() {
+    // Keys from across the byte range, each well clear of the runner-up
+    for key in [0x01, 0x2a, 0x5a, 0x7f, 0x80, 0xaa, 0xff] {
+        let payload = xor_bytes(SENTENCE.as_bytes(), &[key]).expect("xor");
+        let candidates = xor_brute(&payload).expect("brute");
+        assert_eq!(candidates.len(), 5);
+        assert_eq!((candidates[0].key.clone(), candidates[0].plaintext.as_str(), candidates[0].printable), (format!("{:#04x}", key), SENTENCE, 1.0));
+        assert!(candidates.windows(2).all(|pair| pair[0].score >= pair[1].score), "{:?}", candidates);
+        assert!(candidates[0].score > candidates[1].score + 0.05, "{:?}", candidates);
+    }
+
+    // The cryptopals vector, through the string calls: a line per key as text, the candidates as JSON
+    let output = mercy_decode("xor_brute", CRYPTOPALS);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", output);
+    assert!(lines[0].starts_with("key 0x58 score 0."), "{}", output);
+    assert!(lines[0].ends_with(": Cooking MC's like a pound of bacon"), "{}", output);
+    assert!(lines.iter().all(|line| line.starts_with("key 0x")), "{}", output);
+    let candidates: Vec<serde_json::Value> = serde_json::from_str(&mercy_decode("xor_brute_json", CRYPTOPALS)).expect("JSON");
+    assert_eq!((candidates[0]["key"].as_str(), candidates[0]["plaintext"].as_str()), (Some("0x58"), Some("Cooking MC's like a pound of bacon")));
+    assert_eq!(candidates.len(), 5);
+    let envelope: serde_json::Value = serde_json::from_str(&mercy_json("decode", "xor_brute", CRYPTOPALS)).expect("envelope");
+    assert_eq!(envelope["output"][0]["key"].as_str(), Some("0x58"));
+
+    // A control character in the plaintext is escaped so each candidate keeps to one line
+    let output = mercy_decode("xor_brute", &hex(&xor_bytes(b"line one\nline two of the text", &[0x41]).expect("xor")));
+    assert_eq!(output.lines().count(), 5, "{}", output);
+    assert!(output.lines().next().expect("best").ends_with(": line one\\nline two of the text"), "{}", output);
+
+    // Bytes that are noise under every key still give five, none of them scoring like text
+    let noise: Vec<u8> = (0..64u32).map(|index| (index * 37 % 256) as u8).collect();
+    let candidates: Vec<XorCandidate> = xor_brute(&noise).expect("brute");
+    assert!(candidates.iter().all(|candidate| candidate.score < 0.5), "{:?}", candidates);
+}
+
+#[test]
+fn a_repeating_key_decodes() {
+    // A four-byte key repeated over a length that is not a multiple of it
+    let key = [0xde, 0xad, 0xbe, 0xef];
+    let payload = xor_bytes(SENTENCE.as_bytes(), &key).expect("xor");
+    assert_eq!(payload.len() % key.len(), 1);
+    assert_eq!(payload[..4], [b'T' ^ 0xde, b'h' ^ 0xad, b'e' ^ 0xbe, b' ' ^ 0xef]);
+    assert_eq!(mercy_decode("xor", &format!("{},deadbeef", hex(&payload))), SENTENCE);
+    assert_eq!(mercy_decode("xor", &format!("{},DEADBEEF", hex(&payload).to_uppercase())), SENTENCE);
+
+    // Applying a key twice is the identity, and a key longer than the payload uses only its start
+    let binary: Vec<u8> = (0..=255).collect();
+    assert_eq!(xor_bytes(&xor_bytes(&binary, &key).expect("xor"), &key).expect("xor"), binary);
+    assert_eq!(mercy_decode_bytes("xor", "0000,0102030405").expect("short"), [0x01, 0x02]);
+    assert_eq!(mercy_decode_bytes("xor", ",58").expect("empty payload"), Vec::<u8>::new());
+}
+
+#[test]
+fn invalid_input_is_an_error() {
+    let cases = [
+        ("xor", "zz,58", "Payload: Invalid hex character 'z' at position 0"),
+        ("xor", "1b3,58", "Payload: Hex input has an odd number of digits (3)"),
+        ("xor", "1b37,5g", "Key: Invalid hex character 'g' at position 1"),
+        ("xor", "1b37,", "The XOR key is empty"),
+        ("xor", "1b37", "Expected \"<hex payload>,<hex key>\""),
+        ("xor_brute", "not hex", "Invalid hex character 'n' at position 0"),
+        ("xor_brute", "", "The XOR payload is empty"),
+        ("xor_brute_json", "not hex", "Invalid hex character 'n' at position 0")
+    ];
+    for (call, input, error) in cases {
+        let output = mercy_decode(call, input);
+        assert!(output.contains(error), "{} of {:?}: {}", call, input, output);
+    }
+    assert!(mercy_decode_bytes("xor", "zz,58").is_err());
+    assert_eq!(xor_bytes(b"data", &[]).expect_err("no key"), "The XOR key is empty");
+    assert_eq!(xor_brute(&[]).expect_err("no payload"), "The XOR payload is empty");
+}